@@ -47,7 +47,7 @@ fn functional_simulator_benchmark(c: &mut Criterion) {
             }
 
             // Run simulation
-            simulator.run();
+            simulator.run().unwrap();
 
             black_box(simulator.registers.read(2));
         });
@@ -106,7 +106,7 @@ fn memory_access_benchmark(c: &mut Criterion) {
         b.iter(|| {
             for i in 0..1000 {
                 memory.write_word_init(i * 4, i as u32);
-                black_box(memory.read_word(i * 4));
+                let _ = black_box(memory.read_word(i * 4));
             }
         });
     });
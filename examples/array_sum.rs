@@ -48,7 +48,9 @@ fn main() {
     }
 
     println!("Running array sum calculation...");
-    simulator.run();
+    if let Err(error) = simulator.run() {
+        println!("Simulation error: {}", error);
+    }
 
     // Get the result
     let result = simulator.memory.read_word(0x1100).unwrap_or(0);
@@ -75,7 +75,9 @@ fn main() {
     }
 
     println!("Running bubble sort (one pass)...");
-    simulator.run();
+    if let Err(error) = simulator.run() {
+        println!("Simulation error: {}", error);
+    }
 
     // Print sorted array
     println!("\nArray after one bubble sort pass:");
@@ -62,7 +62,9 @@ fn main() {
     }
 
     println!("Running dot product calculation...");
-    simulator.run();
+    if let Err(error) = simulator.run() {
+        println!("Simulation error: {}", error);
+    }
 
     // Get the result
     let result = simulator.memory.read_word(0x1200).unwrap_or(0);
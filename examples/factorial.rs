@@ -47,7 +47,9 @@ fn main() {
     }
 
     println!("Running factorial calculation...");
-    simulator.run();
+    if let Err(error) = simulator.run() {
+        println!("Simulation error: {}", error);
+    }
 
     // Get the result
     let result = simulator.memory.read_word(0x1000).unwrap_or(0);
@@ -42,7 +42,9 @@ fn main() {
     }
 
     println!("Running Fibonacci calculation...");
-    simulator.run();
+    if let Err(error) = simulator.run() {
+        println!("Simulation error: {}", error);
+    }
 
     // Get the result
     let result = simulator.memory.read_word(0x1000).unwrap_or(0);
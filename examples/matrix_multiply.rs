@@ -93,7 +93,9 @@ fn main() {
     }
 
     println!("Running matrix multiplication...");
-    simulator.run();
+    if let Err(error) = simulator.run() {
+        println!("Simulation error: {}", error);
+    }
 
     // Read and display the result matrix
     let c00 = simulator.memory.read_word(0x1200).unwrap_or(0);
@@ -0,0 +1,46 @@
+# bubble_sort.s
+#
+# Sorts eight words in place with bubble sort, then prints them in order.
+# Shows nested loops, loads and stores through a pointer and slt.
+#
+#! expect output = "-7\n0\n3\n9\n12\n25\n41\n88\n"
+#! expect mem[array] = -7
+#! expect mem[array+28] = 88
+
+.data
+array:  .word 41, 9, -7, 88, 0, 25, 3, 12
+count:  .word 8
+
+.text
+main:
+    la $t9, count
+    lw $s1, 0($t9)          # passes left
+    addi $s1, $s1, -1
+outer:
+    la $t0, array
+    add $t1, $s1, $zero     # pairs to compare in this pass
+inner:
+    lw $t2, 0($t0)
+    lw $t3, 4($t0)
+    slt $t4, $t3, $t2       # swap when the pair is out of order
+    beq $t4, $zero, next
+    sw $t3, 0($t0)
+    sw $t2, 4($t0)
+next:
+    addi $t0, $t0, 4
+    addi $t1, $t1, -1
+    bne $t1, $zero, inner
+    addi $s1, $s1, -1
+    bne $s1, $zero, outer
+
+    la $s0, array
+    lw $s1, 0($t9)          # words to print
+print:
+    lw $a0, 0($s0)
+    li $v0, 1               # print_int
+    syscall
+    addi $s0, $s0, 4
+    addi $s1, $s1, -1
+    bne $s1, $zero, print
+    li $v0, 10
+    syscall
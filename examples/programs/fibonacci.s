@@ -0,0 +1,30 @@
+# fibonacci.s
+#
+# Prints the first ten Fibonacci numbers, one per line, and stores F(10).
+# Shows a counted loop, register moves with add and the print_int syscall.
+#
+#! expect output = "0\n1\n1\n2\n3\n5\n8\n13\n21\n34\n"
+#! expect mem[result] = 55
+
+.data
+result: .word 0
+
+.text
+main:
+    li $s0, 0               # F(i)
+    li $s1, 1               # F(i+1)
+    li $s2, 10              # numbers left to print
+loop:
+    add $a0, $s0, $zero
+    li $v0, 1               # print_int
+    syscall
+    add $t0, $s0, $s1       # F(i+2)
+    add $s0, $s1, $zero
+    add $s1, $t0, $zero
+    addi $s2, $s2, -1
+    bne $s2, $zero, loop
+
+    la $t1, result
+    sw $s0, 0($t1)
+    li $v0, 10              # exit
+    syscall
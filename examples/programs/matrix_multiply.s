@@ -0,0 +1,63 @@
+# matrix_multiply.s
+#
+# Multiplies two 3x3 matrices of words and prints the product row by row.
+# Shows a triple loop, row-major addressing with mul and sll, and la.
+#
+#! expect output = "30\n24\n18\n84\n69\n54\n138\n114\n90\n"
+#! expect mem[product] = 30
+#! expect mem[product+32] = 90
+
+.data
+lhs:     .word 1, 2, 3, 4, 5, 6, 7, 8, 9
+rhs:     .word 9, 8, 7, 6, 5, 4, 3, 2, 1
+product: .space 36
+
+.text
+main:
+    li $s7, 3               # N
+    li $s0, 0               # i
+row:
+    li $s1, 0               # j
+column:
+    li $s2, 0               # k
+    li $t7, 0               # sum of A[i][k] * B[k][j]
+term:
+    mul $t0, $s0, $s7       # A[i][k] is at lhs + (i*N + k)*4
+    add $t0, $t0, $s2
+    sll $t0, $t0, 2
+    la $t1, lhs
+    add $t1, $t1, $t0
+    lw $t2, 0($t1)
+    mul $t0, $s2, $s7       # B[k][j] is at rhs + (k*N + j)*4
+    add $t0, $t0, $s1
+    sll $t0, $t0, 2
+    la $t1, rhs
+    add $t1, $t1, $t0
+    lw $t3, 0($t1)
+    mul $t4, $t2, $t3
+    add $t7, $t7, $t4
+    addi $s2, $s2, 1
+    bne $s2, $s7, term
+
+    mul $t0, $s0, $s7       # C[i][j] is at product + (i*N + j)*4
+    add $t0, $t0, $s1
+    sll $t0, $t0, 2
+    la $t1, product
+    add $t1, $t1, $t0
+    sw $t7, 0($t1)
+    addi $s1, $s1, 1
+    bne $s1, $s7, column
+    addi $s0, $s0, 1
+    bne $s0, $s7, row
+
+    la $s0, product
+    li $s1, 9               # elements to print
+print:
+    lw $a0, 0($s0)
+    li $v0, 1               # print_int
+    syscall
+    addi $s0, $s0, 4
+    addi $s1, $s1, -1
+    bne $s1, $zero, print
+    li $v0, 10
+    syscall
@@ -0,0 +1,56 @@
+# syscalls.s
+#
+# Tours the console syscalls: strings, characters, numbers in several bases,
+# and sbrk to get heap memory for a string built at run time.
+#
+#! expect output = "Hello from the simulator!\nMIPS\n-42\n0xff\n0b101\nOK\n"
+#! expect $v0 = 10
+
+.data
+greeting:   .asciiz "Hello from the simulator!"
+letters:    .asciiz "MIPS"
+
+.text
+main:
+    la $a0, greeting
+    li $v0, 4               # print_string, followed by a newline
+    syscall
+
+    la $s0, letters         # print_char, one character at a time
+chars:
+    lb $a0, 0($s0)
+    beq $a0, $zero, numbers
+    li $v0, 11
+    syscall
+    addi $s0, $s0, 1
+    j chars
+numbers:
+    li $a0, 10              # print_char of a newline
+    li $v0, 11
+    syscall
+
+    li $a0, -42
+    li $v0, 1               # print_int
+    syscall
+    li $a0, 255
+    li $v0, 34              # print_hex
+    syscall
+    li $a0, 5
+    li $v0, 35              # print_bin
+    syscall
+
+    li $a0, 3               # sbrk returns 3 bytes of heap in $v0
+    li $v0, 9
+    syscall
+    add $s1, $v0, $zero
+    li $t0, 79              # 'O'
+    sb $t0, 0($s1)
+    li $t0, 75              # 'K'
+    sb $t0, 1($s1)
+    sb $zero, 2($s1)
+    add $a0, $s1, $zero
+    li $v0, 4
+    syscall
+
+    li $v0, 10              # exit
+    syscall
@@ -55,7 +55,9 @@ fn main() {
     }
 
     println!("Running calculator program...");
-    simulator.run();
+    if let Err(error) = simulator.run() {
+        println!("Simulation error: {}", error);
+    }
 
     // Get results
     let final_result = simulator.memory.read_word(0x1100).unwrap_or(0);
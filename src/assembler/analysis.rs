@@ -0,0 +1,476 @@
+// analysis.rs
+//
+// This file contains the static hazard analysis of an assembled program. The
+// text section is split into basic blocks at labels, branch targets and the
+// instructions after branches and jumps. Each block lists the register
+// dependencies between its instructions, the loads whose result is used by
+// the next instruction, and an instruction that could be moved into the
+// delay slot of the branch or jump ending it. Nothing is executed, so the
+// report shows what the pipeline will meet before the program is simulated.
+
+use crate::functional_simulator::instructions::Instruction;
+use crate::functional_simulator::registers::Register;
+use crate::functional_simulator::simulator::{decode_instruction, instruction_to_string};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+/// How a later instruction depends on an earlier one through a register
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum DependencyKind {
+    /// Reads the register the earlier instruction writes
+    Raw,
+    /// Writes a register the earlier instruction reads
+    War,
+    /// Writes the same register as the earlier instruction
+    Waw,
+}
+
+impl DependencyKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DependencyKind::Raw => "RAW",
+            DependencyKind::War => "WAR",
+            DependencyKind::Waw => "WAW",
+        }
+    }
+}
+
+/// A dependency between two instructions of a block, on the nearest earlier
+/// instruction that causes it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Dependency {
+    pub producer: u32,
+    pub consumer: u32,
+    pub register: u32,
+    pub kind: DependencyKind,
+    /// Instructions from the producer to the consumer
+    pub distance: usize,
+}
+
+/// A load whose result is read by the very next instruction, which stalls
+/// one cycle even with forwarding
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LoadUseHazard {
+    pub load: u32,
+    pub consumer: u32,
+    pub register: u32,
+}
+
+/// The branch or jump ending a block and an earlier instruction of the block
+/// that could fill its delay slot. Without a candidate the slot needs a
+/// `nop`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DelaySlot {
+    pub branch: u32,
+    pub candidate: Option<u32>,
+}
+
+/// A straight-line run of instructions entered only at its start
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BasicBlock {
+    pub start: u32,
+    /// Labels defined at the start of the block
+    pub labels: Vec<String>,
+    /// Address and word of each instruction
+    pub instructions: Vec<(u32, u32)>,
+    /// Blocks that can run next, by start address
+    pub successors: Vec<u32>,
+    /// Registers read before the block writes them
+    pub live_in: Vec<u32>,
+    /// Registers the block writes
+    pub written: Vec<u32>,
+    pub dependencies: Vec<Dependency>,
+    pub load_use_hazards: Vec<LoadUseHazard>,
+    pub delay_slot: Option<DelaySlot>,
+}
+
+/// Basic blocks of a text section with their hazards
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct HazardAnalysis {
+    pub blocks: Vec<BasicBlock>,
+}
+
+impl HazardAnalysis {
+    /// Analyze instruction words placed from `address`. `symbols` names the
+    /// blocks and starts a new block at every label inside the text.
+    pub fn new(text: &[u32], address: u32, symbols: &BTreeMap<String, u32>) -> Self {
+        let end = address.wrapping_add(text.len() as u32 * 4);
+        let in_text = |target: u32| target >= address && target < end && target % 4 == 0;
+        let mut labels: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+        for (name, &value) in symbols {
+            labels.entry(value).or_default().push(name.clone());
+        }
+
+        let blocks = basic_blocks(text, address, symbols)
+            .iter()
+            .map(|block| analyze_block(block, &labels, &in_text))
+            .collect();
+        Self { blocks }
+    }
+
+    pub fn load_use_hazards(&self) -> impl Iterator<Item = &LoadUseHazard> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.load_use_hazards.iter())
+    }
+
+    pub fn delay_slots(&self) -> impl Iterator<Item = &DelaySlot> {
+        self.blocks
+            .iter()
+            .filter_map(|block| block.delay_slot.as_ref())
+    }
+
+    /// The report printed by the `analyze` subcommand
+    pub fn render(&self) -> String {
+        let mut result = String::new();
+        for (index, block) in self.blocks.iter().enumerate() {
+            let name = if block.labels.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", block.labels.join(", "))
+            };
+            let successors: Vec<String> = block
+                .successors
+                .iter()
+                .map(|successor| format!("0x{:08X}", successor))
+                .collect();
+            let _ = writeln!(
+                result,
+                "Block {} at 0x{:08X}{}: {} instructions, successors: {}",
+                index,
+                block.start,
+                name,
+                block.instructions.len(),
+                if successors.is_empty() {
+                    "none".to_string()
+                } else {
+                    successors.join(", ")
+                }
+            );
+            for &(pc, word) in &block.instructions {
+                let text = instruction_to_string(&decode_instruction(word), word);
+                let _ = writeln!(result, "  0x{:08X}  {}", pc, text);
+            }
+            let _ = writeln!(
+                result,
+                "  Reads before writing: {}",
+                registers(&block.live_in)
+            );
+            let _ = writeln!(result, "  Writes: {}", registers(&block.written));
+            for dependency in &block.dependencies {
+                let _ = writeln!(
+                    result,
+                    "  {} {} 0x{:08X} -> 0x{:08X} (distance {})",
+                    dependency.kind.name(),
+                    Register::Gpr(dependency.register),
+                    dependency.producer,
+                    dependency.consumer,
+                    dependency.distance
+                );
+            }
+            for hazard in &block.load_use_hazards {
+                let _ = writeln!(
+                    result,
+                    "  Load-use hazard: 0x{:08X} uses {} loaded at 0x{:08X}",
+                    hazard.consumer,
+                    Register::Gpr(hazard.register),
+                    hazard.load
+                );
+            }
+            match &block.delay_slot {
+                Some(DelaySlot {
+                    branch,
+                    candidate: Some(candidate),
+                }) => {
+                    let _ = writeln!(
+                        result,
+                        "  Delay slot of 0x{:08X}: can be filled by 0x{:08X}",
+                        branch, candidate
+                    );
+                },
+                Some(DelaySlot { branch, .. }) => {
+                    let _ = writeln!(result, "  Delay slot of 0x{:08X}: needs a nop", branch);
+                },
+                None => {},
+            }
+            result.push('\n');
+        }
+
+        let slots: Vec<&DelaySlot> = self.delay_slots().collect();
+        let _ = writeln!(
+            result,
+            "{} blocks, {} load-use hazards, {} of {} delay slots can be filled",
+            self.blocks.len(),
+            self.load_use_hazards().count(),
+            slots.iter().filter(|slot| slot.candidate.is_some()).count(),
+            slots.len()
+        );
+        result
+    }
+}
+
+/// An instruction with its address and word
+pub(crate) type Located = (u32, u32, Instruction);
+
+/// Split instruction words placed from `address` into basic blocks. Blocks
+/// start at the first instruction, labels, branch targets and after every
+/// branch or jump.
+pub(crate) fn basic_blocks(
+    text: &[u32],
+    address: u32,
+    symbols: &BTreeMap<String, u32>,
+) -> Vec<Vec<Located>> {
+    let end = address.wrapping_add(text.len() as u32 * 4);
+    let in_text = |target: u32| target >= address && target < end && target % 4 == 0;
+    let decoded: Vec<Located> = text
+        .iter()
+        .enumerate()
+        .map(|(index, &word)| {
+            let pc = address.wrapping_add(index as u32 * 4);
+            (pc, word, decode_instruction(word))
+        })
+        .collect();
+
+    let mut leaders = BTreeSet::new();
+    leaders.insert(address);
+    leaders.extend(symbols.values().copied().filter(|&value| in_text(value)));
+    for (pc, _, instruction) in &decoded {
+        if ends_block(instruction) {
+            leaders.insert(pc.wrapping_add(4));
+            if let Some(target) = instruction.calculate_branch_target(*pc) {
+                leaders.insert(target);
+            }
+        }
+    }
+
+    let mut blocks = Vec::new();
+    let mut current: Vec<Located> = Vec::new();
+    for entry in decoded {
+        if leaders.contains(&entry.0) && !current.is_empty() {
+            blocks.push(std::mem::take(&mut current));
+        }
+        current.push(entry);
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+    blocks
+}
+
+/// Branches, jumps and exception returns transfer control
+pub(crate) fn ends_block(instruction: &Instruction) -> bool {
+    instruction.is_branch_or_jump()
+}
+
+/// Registers an instruction reads, without $zero
+pub(crate) fn reads(instruction: &Instruction) -> Vec<u32> {
+    let mut sources = instruction.get_source_registers();
+    sources.retain(|&register| register != 0);
+    sources.sort_unstable();
+    sources.dedup();
+    sources
+}
+
+fn registers(list: &[u32]) -> String {
+    if list.is_empty() {
+        return "none".to_string();
+    }
+    list.iter()
+        .map(|&register| Register::Gpr(register).to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn analyze_block(
+    instructions: &[Located],
+    labels: &BTreeMap<u32, Vec<String>>,
+    in_text: &dyn Fn(u32) -> bool,
+) -> BasicBlock {
+    let start = instructions[0].0;
+    let mut live_in = BTreeSet::new();
+    let mut written = BTreeSet::new();
+    let mut dependencies = Vec::new();
+    let mut load_use_hazards = Vec::new();
+
+    for (index, (pc, _, instruction)) in instructions.iter().enumerate() {
+        let sources = reads(instruction);
+        let destination = instruction.get_destination_register();
+
+        for &register in &sources {
+            let producer = instructions[..index]
+                .iter()
+                .rposition(|(_, _, earlier)| earlier.get_destination_register() == Some(register));
+            match producer {
+                Some(producer) => {
+                    let (producer_pc, _, earlier) = &instructions[producer];
+                    dependencies.push(Dependency {
+                        producer: *producer_pc,
+                        consumer: *pc,
+                        register,
+                        kind: DependencyKind::Raw,
+                        distance: index - producer,
+                    });
+                    if earlier.is_load() && producer + 1 == index {
+                        load_use_hazards.push(LoadUseHazard {
+                            load: *producer_pc,
+                            consumer: *pc,
+                            register,
+                        });
+                    }
+                },
+                None => {
+                    live_in.insert(register);
+                },
+            }
+        }
+
+        if let Some(register) = destination {
+            let earlier = &instructions[..index];
+            if let Some(reader) = earlier
+                .iter()
+                .rposition(|(_, _, earlier)| reads(earlier).contains(&register))
+            {
+                dependencies.push(Dependency {
+                    producer: earlier[reader].0,
+                    consumer: *pc,
+                    register,
+                    kind: DependencyKind::War,
+                    distance: index - reader,
+                });
+            }
+            // Reading the register already orders the two writes
+            let writer = earlier
+                .iter()
+                .rposition(|(_, _, earlier)| earlier.get_destination_register() == Some(register))
+                .filter(|_| !sources.contains(&register));
+            if let Some(writer) = writer {
+                dependencies.push(Dependency {
+                    producer: earlier[writer].0,
+                    consumer: *pc,
+                    register,
+                    kind: DependencyKind::Waw,
+                    distance: index - writer,
+                });
+            }
+            written.insert(register);
+        }
+    }
+
+    let (last_pc, _, last) = &instructions[instructions.len() - 1];
+    let mut successors = Vec::new();
+    let delay_slot = if ends_block(last) {
+        if let Some(target) = last.calculate_branch_target(*last_pc) {
+            successors.push(target);
+        }
+        // Conditional branches and calls also continue after the branch
+        let unconditional = matches!(
+            last,
+            Instruction::J { .. } | Instruction::Jr { .. } | Instruction::Eret
+        );
+        if !unconditional {
+            successors.push(last_pc.wrapping_add(4));
+        }
+        Some(DelaySlot {
+            branch: *last_pc,
+            candidate: delay_slot_candidate(instructions).map(|index| instructions[index].0),
+        })
+    } else {
+        successors.push(last_pc.wrapping_add(4));
+        None
+    };
+    successors.retain(|&successor| in_text(successor));
+    successors.dedup();
+
+    BasicBlock {
+        start,
+        labels: labels.get(&start).cloned().unwrap_or_default(),
+        instructions: instructions
+            .iter()
+            .map(|&(pc, word, _)| (pc, word))
+            .collect(),
+        successors,
+        live_in: live_in.into_iter().collect(),
+        written: written.into_iter().collect(),
+        dependencies,
+        load_use_hazards,
+        delay_slot,
+    }
+}
+
+/// Position of the latest instruction before the branch ending a block that
+/// no later instruction of the block conflicts with, so it can move past the
+/// branch into the delay slot
+pub(crate) fn delay_slot_candidate(instructions: &[Located]) -> Option<usize> {
+    let body = &instructions[..instructions.len() - 1];
+    (0..body.len()).rev().find(|&index| {
+        movable(&body[index])
+            && instructions[index + 1..]
+                .iter()
+                .all(|later| !conflicts(&body[index], later))
+    })
+}
+
+/// Whether an instruction may be reordered at all. System calls, traps,
+/// coprocessor instructions and words that do not decode stay in place, and
+/// moving a `nop` gains nothing.
+pub(crate) fn movable((_, word, instruction): &Located) -> bool {
+    let coprocessor = matches!(word >> 26, 0x10 | 0x11 | 0x31 | 0x39);
+    !coprocessor
+        && !matches!(
+            instruction,
+            Instruction::Syscall
+                | Instruction::Break { .. }
+                | Instruction::Nop
+                | Instruction::InvalidInstruction
+                | Instruction::Eret
+                | Instruction::Ll { .. }
+                | Instruction::Sc { .. }
+                | Instruction::Teq { .. }
+                | Instruction::Tne { .. }
+                | Instruction::Tge { .. }
+                | Instruction::Tgeu { .. }
+                | Instruction::Tlt { .. }
+                | Instruction::Tltu { .. }
+                | Instruction::Teqi { .. }
+                | Instruction::Tnei { .. }
+                | Instruction::Tgei { .. }
+                | Instruction::Tgeiu { .. }
+                | Instruction::Tlti { .. }
+                | Instruction::Tltiu { .. }
+        )
+}
+
+/// Whether two instructions must stay in order: one of them cannot move,
+/// they share a register one of them writes, both use HI and LO, or both
+/// access memory
+pub(crate) fn conflicts(earlier: &Located, later: &Located) -> bool {
+    let written = |instruction: &Instruction, other: &Instruction| {
+        instruction
+            .get_destination_register()
+            .map_or(false, |register| {
+                reads(other).contains(&register)
+                    || other.get_destination_register() == Some(register)
+            })
+    };
+    let hi_lo = |instruction: &Instruction| {
+        matches!(
+            instruction,
+            Instruction::Mult { .. }
+                | Instruction::Div { .. }
+                | Instruction::Divu { .. }
+                | Instruction::Mfhi { .. }
+                | Instruction::Mflo { .. }
+                | Instruction::Mthi { .. }
+                | Instruction::Mtlo { .. }
+        )
+    };
+    let fixed = |located: &Located| !movable(located) && located.2 != Instruction::Nop;
+    let (first, second) = (&earlier.2, &later.2);
+    fixed(earlier)
+        || fixed(later)
+        || written(first, second)
+        || written(second, first)
+        || (hi_lo(first) && hi_lo(second))
+        || (first.is_memory_access() && second.is_memory_access())
+}
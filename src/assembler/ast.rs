@@ -0,0 +1,157 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// ast.rs
+//
+// This file contains the syntax tree the assembler builds before encoding.
+// Each source line becomes a `Statement` holding its label, if any, and a
+// directive or instruction with typed operands: registers, numbers,
+// strings, labels with an optional offset and `offset(base)` addresses.
+// Comments, `.eqv` names and repeated values are resolved while parsing, so
+// both assembler passes, and tools such as formatters, optimizers and
+// linters, work from the same statements instead of re-reading the text.
+
+use crate::functional_simulator::registers::REGISTER_NAMES;
+use std::fmt;
+
+/// One operand of a directive or instruction, as written between commas
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Register(u32),
+    FpRegister(u32),
+    /// An integer literal, in any base
+    Integer(i64),
+    Float(f64),
+    /// A string literal with its escapes resolved
+    String(String),
+    /// A label, with the constant in `label+4` or `label-4`
+    Symbol {
+        name: String,
+        offset: Option<i64>,
+    },
+    /// `offset(base)`, `label(base)` or `(base)`
+    Memory {
+        offset: Option<Box<Operand>>,
+        base: u32,
+    },
+    /// A directive name used as an operand, as in `.section .data`
+    Directive(String),
+}
+
+/// What a statement does
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatementKind {
+    /// A directive such as `.word`, with the leading dot
+    Directive {
+        name: String,
+        operands: Vec<Operand>,
+    },
+    /// An instruction or pseudo-instruction, by its canonical mnemonic
+    Instruction {
+        mnemonic: String,
+        operands: Vec<Operand>,
+    },
+}
+
+/// One source line that holds a label, a directive or instruction, or both
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statement {
+    /// Line number in the source, from 1
+    pub line: usize,
+    pub label: Option<String>,
+    pub kind: Option<StatementKind>,
+}
+
+impl Statement {
+    pub fn operands(&self) -> &[Operand] {
+        match &self.kind {
+            Some(StatementKind::Directive { operands, .. })
+            | Some(StatementKind::Instruction { operands, .. }) => operands,
+            None => &[],
+        }
+    }
+
+    pub fn is_instruction(&self) -> bool {
+        matches!(self.kind, Some(StatementKind::Instruction { .. }))
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(number) => write!(f, "{}", REGISTER_NAMES[*number as usize % 32]),
+            Operand::FpRegister(number) => write!(f, "$f{}", number),
+            Operand::Integer(value) => write!(f, "{}", value),
+            Operand::Float(value) => write!(f, "{:?}", value),
+            Operand::String(text) => {
+                f.write_str("\"")?;
+                for c in text.chars() {
+                    match c {
+                        '\n' => f.write_str("\\n")?,
+                        '\t' => f.write_str("\\t")?,
+                        '\r' => f.write_str("\\r")?,
+                        '\0' => f.write_str("\\0")?,
+                        '\\' | '"' => write!(f, "\\{}", c)?,
+                        // Other bytes outside printable ASCII came from \x escapes
+                        c if (c as u32) < 0x20 || ('\u{7f}'..='\u{ff}').contains(&c) => {
+                            write!(f, "\\x{:02x}", c as u32)?
+                        },
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                f.write_str("\"")
+            },
+            Operand::Symbol { name, offset } => match offset {
+                Some(offset) => write!(f, "{}{:+}", name, offset),
+                None => write!(f, "{}", name),
+            },
+            Operand::Memory { offset, base } => {
+                if let Some(offset) = offset {
+                    write!(f, "{}", offset)?;
+                }
+                write!(f, "({})", REGISTER_NAMES[*base as usize % 32])
+            },
+            Operand::Directive(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// The statement as assembly source, with the label on its own line
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(label) = &self.label {
+            write!(f, "{}:", label)?;
+            if self.kind.is_some() {
+                writeln!(f)?;
+            }
+        }
+        let (name, operands) = match &self.kind {
+            Some(StatementKind::Directive { name, operands }) => (name, operands),
+            Some(StatementKind::Instruction { mnemonic, operands }) => (mnemonic, operands),
+            None => return Ok(()),
+        };
+        write!(f, "    {}", name)?;
+        for (index, operand) in operands.iter().enumerate() {
+            write!(f, "{}{}", if index == 0 { " " } else { ", " }, operand)?;
+        }
+        Ok(())
+    }
+}
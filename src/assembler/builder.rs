@@ -0,0 +1,386 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// builder.rs
+//
+// This file contains ProgramBuilder, a Rust DSL for writing programs without
+// assembly text. Each method appends one instruction, built with the
+// encoder, and branches, jumps and `la` name labels that are resolved when
+// the program is built. The result is the same `Program` the assembler
+// produces, with symbols and relocations, so it loads and runs like
+// assembled source.
+
+use super::program::{Program, Relocation, RelocationKind};
+use super::sections::SectionKind;
+use crate::functional_simulator::instructions::{encode, Instruction};
+use crate::functional_simulator::registers::{RA, ZERO};
+use crate::loader::{ProgramImage, DATA_BASE, TEXT_BASE};
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuildError {
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    /// A branch whose label is more than 32K instructions away
+    BranchOutOfRange(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::UndefinedLabel(label) => write!(f, "Undefined label: {}", label),
+            BuildError::DuplicateLabel(label) => write!(f, "Label defined twice: {}", label),
+            BuildError::BranchOutOfRange(label) => {
+                write!(f, "Branch to {} is out of range", label)
+            },
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+// A text word waiting for the address of a label
+struct Fixup {
+    index: usize,
+    label: String,
+    kind: FixupKind,
+}
+
+enum FixupKind {
+    // 16-bit word offset from the following instruction
+    Branch,
+    Absolute(RelocationKind),
+}
+
+// Builder methods for instructions whose operands map one to one onto the
+// fields of an Instruction variant
+macro_rules! instructions {
+    ($($name:ident => $variant:ident { $($field:ident: $ty:ty),* }),* $(,)?) => {
+        $(
+            #[doc = concat!("Append `", stringify!($name), "`")]
+            pub fn $name(self, $($field: $ty),*) -> Self {
+                self.instruction(Instruction::$variant { $($field),* })
+            }
+        )*
+    };
+}
+
+/// Builds a program instruction by instruction:
+///
+/// ```
+/// use vmips_rust::assembler::ProgramBuilder;
+/// use vmips_rust::functional_simulator::registers::{T0, T1, V0, ZERO};
+///
+/// let program = ProgramBuilder::new()
+///     .li(T1, 10)
+///     .label("loop")
+///     .addi(T0, T0, 1)
+///     .bne(T0, T1, "loop")
+///     .li(V0, 10)
+///     .syscall()
+///     .build()
+///     .unwrap();
+/// assert_eq!(program.text.len(), 5);
+/// ```
+#[derive(Default)]
+pub struct ProgramBuilder {
+    text: Vec<u32>,
+    data: Vec<u8>,
+    labels: BTreeMap<String, (SectionKind, u32)>,
+    fixups: Vec<Fixup>,
+    errors: Vec<BuildError>,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name the next instruction. A `__start` label is the entry point.
+    pub fn label(self, name: &str) -> Self {
+        let offset = self.text.len() as u32 * 4;
+        self.define(name, SectionKind::Text, offset)
+    }
+
+    /// Name the next byte of the data section
+    pub fn data_label(self, name: &str) -> Self {
+        let offset = self.data.len() as u32;
+        self.define(name, SectionKind::Data, offset)
+    }
+
+    /// Append any instruction
+    pub fn instruction(mut self, instruction: Instruction) -> Self {
+        self.text.push(encode(&instruction));
+        self
+    }
+
+    instructions! {
+        add => Add { rd: u32, rs: u32, rt: u32 },
+        addu => Addu { rd: u32, rs: u32, rt: u32 },
+        sub => Sub { rd: u32, rs: u32, rt: u32 },
+        and => And { rd: u32, rs: u32, rt: u32 },
+        or => Or { rd: u32, rs: u32, rt: u32 },
+        xor => Xor { rd: u32, rs: u32, rt: u32 },
+        nor => Nor { rd: u32, rs: u32, rt: u32 },
+        slt => Slt { rd: u32, rs: u32, rt: u32 },
+        sltu => Sltu { rd: u32, rs: u32, rt: u32 },
+        sll => Sll { rd: u32, rt: u32, shamt: u32 },
+        srl => Srl { rd: u32, rt: u32, shamt: u32 },
+        sra => Sra { rd: u32, rt: u32, shamt: u32 },
+        mult => Mult { rs: u32, rt: u32 },
+        div => Div { rs: u32, rt: u32 },
+        mfhi => Mfhi { rd: u32 },
+        mflo => Mflo { rd: u32 },
+        jr => Jr { rs: u32 },
+        addi => Addi { rt: u32, rs: u32, imm: i16 },
+        addiu => Addiu { rt: u32, rs: u32, imm: i16 },
+        slti => Slti { rt: u32, rs: u32, imm: i16 },
+        andi => Andi { rt: u32, rs: u32, imm: u16 },
+        ori => Ori { rt: u32, rs: u32, imm: u16 },
+        xori => Xori { rt: u32, rs: u32, imm: u16 },
+        lui => Lui { rt: u32, imm: u16 },
+        lw => Lw { rt: u32, offset: i16, base: u32 },
+        sw => Sw { rt: u32, offset: i16, base: u32 },
+        lb => Lb { rt: u32, offset: i16, base: u32 },
+        lbu => Lbu { rt: u32, offset: i16, base: u32 },
+        sb => Sb { rt: u32, offset: i16, base: u32 },
+        lh => Lh { rt: u32, offset: i16, base: u32 },
+        lhu => Lhu { rt: u32, offset: i16, base: u32 },
+        sh => Sh { rt: u32, offset: i16, base: u32 },
+    }
+
+    pub fn syscall(self) -> Self {
+        self.instruction(Instruction::Syscall)
+    }
+
+    pub fn nop(self) -> Self {
+        self.instruction(Instruction::Nop)
+    }
+
+    /// `jalr $ra, rs`
+    pub fn jalr(self, rs: u32) -> Self {
+        self.instruction(Instruction::Jalr { rd: RA, rs })
+    }
+
+    pub fn beq(self, rs: u32, rt: u32, label: &str) -> Self {
+        self.branch(Instruction::Beq { rs, rt, offset: 0 }, label)
+    }
+
+    pub fn bne(self, rs: u32, rt: u32, label: &str) -> Self {
+        self.branch(Instruction::Bne { rs, rt, offset: 0 }, label)
+    }
+
+    pub fn blez(self, rs: u32, label: &str) -> Self {
+        self.branch(Instruction::Blez { rs, offset: 0 }, label)
+    }
+
+    pub fn bgtz(self, rs: u32, label: &str) -> Self {
+        self.branch(Instruction::Bgtz { rs, offset: 0 }, label)
+    }
+
+    pub fn bltz(self, rs: u32, label: &str) -> Self {
+        self.branch(Instruction::Bltz { rs, offset: 0 }, label)
+    }
+
+    pub fn bgez(self, rs: u32, label: &str) -> Self {
+        self.branch(Instruction::Bgez { rs, offset: 0 }, label)
+    }
+
+    /// Unconditional branch, as `beq $zero, $zero`
+    pub fn b(self, label: &str) -> Self {
+        self.beq(ZERO, ZERO, label)
+    }
+
+    pub fn j(self, label: &str) -> Self {
+        self.absolute(Instruction::J { target: 0 }, label, RelocationKind::Jump26)
+    }
+
+    pub fn jal(self, label: &str) -> Self {
+        self.absolute(
+            Instruction::Jal { target: 0 },
+            label,
+            RelocationKind::Jump26,
+        )
+    }
+
+    /// Load a 32-bit constant in one or two instructions, as the `li`
+    /// pseudo-instruction does
+    pub fn li(self, rt: u32, value: i32) -> Self {
+        if (-32768..=32767).contains(&value) {
+            self.addi(rt, ZERO, value as i16)
+        } else if (0..=0xFFFF).contains(&value) {
+            self.ori(rt, ZERO, value as u16)
+        } else {
+            self.lui(rt, (value as u32 >> 16) as u16)
+                .ori(rt, rt, value as u16)
+        }
+    }
+
+    /// Load the address of a label with `lui` and `ori`
+    pub fn la(self, rt: u32, label: &str) -> Self {
+        self.absolute(
+            Instruction::Lui { rt, imm: 0 },
+            label,
+            RelocationKind::High16,
+        )
+        .absolute(
+            Instruction::Ori { rt, rs: rt, imm: 0 },
+            label,
+            RelocationKind::Low16,
+        )
+    }
+
+    /// `add rd, rs, $zero`
+    pub fn mov(self, rd: u32, rs: u32) -> Self {
+        self.add(rd, rs, ZERO)
+    }
+
+    /// Append words to the data section. Use `align_data(4)` first if
+    /// bytes or strings come before them.
+    pub fn words(mut self, values: &[u32]) -> Self {
+        for value in values {
+            self.data.extend_from_slice(&value.to_le_bytes());
+        }
+        self
+    }
+
+    pub fn bytes(mut self, values: &[u8]) -> Self {
+        self.data.extend_from_slice(values);
+        self
+    }
+
+    /// Append a string and its null terminator to the data section
+    pub fn asciiz(self, text: &str) -> Self {
+        self.bytes(text.as_bytes()).bytes(&[0])
+    }
+
+    /// Pad the data section with zeros to a multiple of `alignment` bytes
+    pub fn align_data(mut self, alignment: usize) -> Self {
+        while self.data.len() % alignment != 0 {
+            self.data.push(0);
+        }
+        self
+    }
+
+    /// Reserve zeroed bytes in the data section
+    pub fn space(mut self, size: usize) -> Self {
+        self.data.resize(self.data.len() + size, 0);
+        self
+    }
+
+    /// Resolve every label and return the program, with the text section at
+    /// `TEXT_BASE` and data at `DATA_BASE` as the assembler places them
+    pub fn build(mut self) -> Result<Program, BuildError> {
+        if !self.errors.is_empty() {
+            return Err(self.errors.remove(0));
+        }
+
+        let symbols: BTreeMap<String, u32> = self
+            .labels
+            .iter()
+            .map(|(name, &(section, offset))| (name.clone(), section_base(section) + offset))
+            .collect();
+
+        let mut relocations = Vec::new();
+        for fixup in &self.fixups {
+            let target = *symbols
+                .get(&fixup.label)
+                .ok_or_else(|| BuildError::UndefinedLabel(fixup.label.clone()))?;
+            let address = TEXT_BASE + fixup.index as u32 * 4;
+            let word = &mut self.text[fixup.index];
+            match fixup.kind {
+                FixupKind::Branch => {
+                    let offset = (target.wrapping_sub(address + 4) as i32) >> 2;
+                    if i16::try_from(offset).is_err() {
+                        return Err(BuildError::BranchOutOfRange(fixup.label.clone()));
+                    }
+                    *word |= offset as u16 as u32;
+                },
+                FixupKind::Absolute(kind) => {
+                    *word |= match kind {
+                        RelocationKind::Jump26 => (target >> 2) & 0x3FF_FFFF,
+                        RelocationKind::High16 => target >> 16,
+                        _ => target & 0xFFFF,
+                    };
+                    relocations.push(Relocation {
+                        address,
+                        section: SectionKind::Text,
+                        kind,
+                        symbol: fixup.label.clone(),
+                        addend: 0,
+                    });
+                },
+            }
+        }
+
+        Ok(Program {
+            entry: symbols.get("__start").copied().unwrap_or(TEXT_BASE),
+            text: self.text,
+            text_address: TEXT_BASE,
+            data: self.data,
+            data_address: DATA_BASE,
+            symbols,
+            relocations,
+        })
+    }
+
+    /// Build the program and place it in memory as a loadable image
+    pub fn image(self) -> Result<ProgramImage, BuildError> {
+        self.build()
+            .map(|program| ProgramImage::from_program(&program))
+    }
+
+    fn define(mut self, name: &str, section: SectionKind, offset: u32) -> Self {
+        if self.labels.contains_key(name) {
+            self.errors
+                .push(BuildError::DuplicateLabel(name.to_string()));
+        } else {
+            self.labels.insert(name.to_string(), (section, offset));
+        }
+        self
+    }
+
+    fn branch(self, instruction: Instruction, label: &str) -> Self {
+        self.fixup(instruction, label, FixupKind::Branch)
+    }
+
+    fn absolute(self, instruction: Instruction, label: &str, kind: RelocationKind) -> Self {
+        self.fixup(instruction, label, FixupKind::Absolute(kind))
+    }
+
+    fn fixup(mut self, instruction: Instruction, label: &str, kind: FixupKind) -> Self {
+        self.fixups.push(Fixup {
+            index: self.text.len(),
+            label: label.to_string(),
+            kind,
+        });
+        self.instruction(instruction)
+    }
+}
+
+fn section_base(section: SectionKind) -> u32 {
+    if section == SectionKind::Data {
+        DATA_BASE
+    } else {
+        TEXT_BASE
+    }
+}
@@ -25,12 +25,18 @@
 // It defines the Assembler struct, which is responsible for parsing MIPS
 // assembly code, resolving labels, and generating machine code.
 
-use std::collections::HashMap;
+use super::ast::{Operand, Statement, StatementKind};
+use super::dialect::{Dialect, DirectiveUse};
+use super::program::{Program, Relocation, RelocationKind};
+use super::sections::{AssembledSection, SectionKind, SectionState};
+use crate::loader::{DATA_BASE, KDATA_BASE, KTEXT_BASE, TEXT_BASE};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
-use std::fs::File;
-use std::io::BufReader;
-use std::io::{self, BufRead};
+use std::fs;
+use std::io;
+use std::iter::Peekable;
 use std::path::Path;
+use std::str::Chars;
 
 // Register mapping
 const REGISTER_MAP: &[(&str, u32)] = &[
@@ -193,6 +199,9 @@ enum Token {
     Register(u32),
     FpRegister(u32),
     Immediate(i32),
+    // Integer literal too wide for 32 bits, only accepted by .dword
+    LongImmediate(i64),
+    Float(f64),
     Address(String, i32), // Base register name and offset
     Symbol(String),
     Comma,
@@ -202,18 +211,41 @@ enum Token {
     StringLiteral(String),
 }
 
+// A label's position within its section. The address is the section's
+// base plus the offset, and bases are only known after the first pass.
+#[derive(Clone, Copy, Debug)]
+struct Label {
+    section: SectionKind,
+    offset: u32,
+}
+
+// Sections without an address follow the section before them, starting on
+// a page boundary so .align in the first pass matches the final layout
+const SECTION_ALIGNMENT: u32 = 0x1000;
+
 // Assembler struct
 pub struct Assembler {
-    labels: HashMap<String, u32>,
-    data_section: Vec<u8>,
-    text_section: Vec<u32>,
+    labels: HashMap<String, Label>,
+    // Set once the first pass has recorded every label
+    labels_complete: bool,
+    sections: Vec<SectionState>,
+    section_bases: [u32; 6],
+    current_section: SectionKind,
     current_address: u32,
-    in_data_section: bool,
     current_line: usize,
     errors: Vec<AssemblerError>,
     register_map: HashMap<String, u32>,
     fp_register_map: HashMap<String, u32>,
     current_filename: String,
+    // Set by `.set noat`, which hands $at to the program
+    noat: bool,
+    warnings: Vec<String>,
+    relocations: Vec<Relocation>,
+    dialect: Dialect,
+    // Names defined with `.eqv` and the text they stand for
+    equivalences: HashMap<String, String>,
+    // Syscall number last loaded into $v0 in the current block, if known
+    syscall_number: Option<u32>,
 }
 
 impl Default for Assembler {
@@ -236,624 +268,743 @@ impl Assembler {
 
         Self {
             labels: HashMap::new(),
-            data_section: Vec::new(),
-            text_section: Vec::new(),
+            labels_complete: false,
+            sections: Vec::new(),
+            section_bases: [0; 6],
+            current_section: SectionKind::Text,
             current_address: 0,
-            in_data_section: false,
             current_line: 0,
             errors: Vec::new(),
             register_map,
             fp_register_map,
             current_filename: String::new(),
+            noat: false,
+            warnings: Vec::new(),
+            relocations: Vec::new(),
+            dialect: Dialect::Native,
+            equivalences: HashMap::new(),
+            syscall_number: None,
         }
     }
 
-    // Assemble a file
-    pub fn assemble_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<u8>, AssemblerError> {
-        let file = File::open(&path)?;
-        self.current_filename = path.as_ref().to_string_lossy().to_string();
-        let reader = io::BufReader::new(file);
-
-        // First pass: collect labels and directives
-        self.first_pass(reader)?;
-
-        // Reset for second pass
-        self.current_address = 0;
-        self.in_data_section = false;
+    /// Accept the directive spellings, label rules and syscall numbers of
+    /// another assembler's dialect, reporting what differs as warnings
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
 
-        // Second pass: generate code
-        let file = File::open(path)?;
-        let reader = io::BufReader::new(file);
-        self.second_pass(reader)?;
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
 
-        // Combine data and text sections
-        let mut result = Vec::new();
+    // Assemble a file into flat output
+    pub fn assemble_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<u8>, AssemblerError> {
+        let program = self.assemble_program_file(path)?;
+        self.warn_flat_addresses();
+        Ok(program.to_bytes())
+    }
 
-        // Write data section size (4 bytes)
-        let data_size = self.data_section.len() as u32;
-        result.extend_from_slice(&data_size.to_le_bytes());
+    // Assemble from a string into flat output
+    pub fn assemble_string(&mut self, code: &str) -> Result<Vec<u8>, AssemblerError> {
+        let program = self.assemble_program(code)?;
+        self.warn_flat_addresses();
+        Ok(program.to_bytes())
+    }
 
-        // Write text section size (4 bytes)
-        let text_size = self.text_section.len() as u32 * 4;
-        result.extend_from_slice(&text_size.to_le_bytes());
+    /// Assemble a file into a `Program` with its symbols and relocations
+    pub fn assemble_program_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<Program, AssemblerError> {
+        let code = fs::read_to_string(&path)?;
+        self.current_filename = path.as_ref().to_string_lossy().to_string();
+        let statements = self.parse(&code)?;
+        self.assemble_statements(&statements)
+    }
 
-        // Write data section
-        result.extend_from_slice(&self.data_section);
+    /// Assemble source into a `Program` with its symbols and relocations
+    pub fn assemble_program(&mut self, code: &str) -> Result<Program, AssemblerError> {
+        self.current_filename = "<string>".to_string();
+        let statements = self.parse(code)?;
+        self.assemble_statements(&statements)
+    }
 
-        // Write text section
-        for instr in &self.text_section {
-            result.extend_from_slice(&instr.to_le_bytes());
+    // Collect the text and data sections, which are all a Program holds,
+    // warning about any other section that would be lost
+    fn program(&mut self) -> Result<Program, AssemblerError> {
+        for kind in SectionKind::ALL {
+            if kind != SectionKind::Text
+                && kind != SectionKind::Data
+                && !self.sections[kind.index()].bytes.is_empty()
+            {
+                self.warnings.push(format!(
+                    "{} is left out of the flat output; load the source directly to keep it",
+                    kind
+                ));
+            }
         }
 
-        if !self.errors.is_empty() {
-            // Return the first error
-            return Err(self.errors.remove(0));
+        let text = &self.sections[SectionKind::Text.index()];
+        let data = &self.sections[SectionKind::Data.index()];
+        let symbols: BTreeMap<String, u32> = self
+            .labels
+            .keys()
+            .filter_map(|name| Some((name.clone(), self.symbol_address(name)?)))
+            .collect();
+
+        // Text only grows by whole instructions from aligned addresses, but a
+        // partial word is reported rather than read past the end
+        let words = text.bytes.chunks_exact(4);
+        if !words.remainder().is_empty() {
+            return Err(AssemblerError::Range(
+                format!(
+                    ".text is {} bytes long, not a whole number of instructions",
+                    text.bytes.len()
+                ),
+                self.current_line,
+            ));
         }
 
-        Ok(result)
+        Ok(Program {
+            text: words
+                .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+                .collect(),
+            text_address: text.base,
+            data: data.bytes.clone(),
+            data_address: data.base,
+            entry: symbols.get("__start").copied().unwrap_or(text.base),
+            symbols,
+            relocations: self.relocations.clone(),
+        })
     }
 
-    // Assemble from a string
-    pub fn assemble_string(&mut self, code: &str) -> Result<Vec<u8>, AssemblerError> {
-        self.current_filename = "<string>".to_string();
+    // The flat output loader places data at DATA_BASE and text at
+    // TEXT_BASE, so sections assembled for other addresses would break
+    fn warn_flat_addresses(&mut self) {
+        for (kind, loaded_at) in [
+            (SectionKind::Text, TEXT_BASE),
+            (SectionKind::Data, DATA_BASE),
+        ] {
+            let section = &self.sections[kind.index()];
+            if !section.bytes.is_empty() && section.base != loaded_at {
+                self.warnings.push(format!(
+                    "{} is assembled for 0x{:08X} but flat output loads it at 0x{:08X}",
+                    kind, section.base, loaded_at
+                ));
+            }
+        }
+    }
 
-        // Create a cursor that implements BufRead
-        let cursor = io::Cursor::new(code);
+    /// Contents and addresses of every non-empty section from the last assembly
+    pub fn sections(&self) -> Vec<AssembledSection> {
+        SectionKind::ALL
+            .iter()
+            .map(|&kind| (kind, &self.sections[kind.index()]))
+            .filter(|(_, section)| !section.bytes.is_empty())
+            .map(|(kind, section)| AssembledSection {
+                kind,
+                address: section.base,
+                bytes: section.bytes.clone(),
+            })
+            .collect()
+    }
 
-        // First pass: collect labels and directives
-        self.first_pass(BufReader::new(cursor.clone()))?;
+    /// Warnings from the last assembly, such as uses of $at without `.set noat`
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
 
-        // Reset for second pass
-        self.current_address = 0;
-        self.in_data_section = false;
+    /// Parse source into statements without assembling it. Comments, `.eqv`
+    /// names and repeated values are resolved, and operands are typed.
+    pub fn parse(&mut self, code: &str) -> Result<Vec<Statement>, AssemblerError> {
+        self.current_line = 0;
+        self.equivalences.clear();
+        let mut statements = Vec::new();
 
-        // Second pass: generate code
-        self.second_pass(BufReader::new(cursor))?;
+        for line in code.lines() {
+            self.current_line += 1;
+            let line = self.preprocess_line(line);
 
-        // Combine data and text sections
-        let mut result = Vec::new();
+            if line.is_empty() {
+                continue;
+            }
+
+            // Split off the label, if the line has one
+            let (label, rest) = match label_end(&line) {
+                Some(end) => (line[..end].trim(), line[end + 1..].trim()),
+                None => ("", line.as_str()),
+            };
+            let kind = self.statement_kind(&self.tokenize(rest)?)?;
+            statements.push(Statement {
+                line: self.current_line,
+                label: Some(label.to_string()).filter(|label| !label.is_empty()),
+                kind,
+            });
+        }
 
-        // Write data section size (4 bytes)
-        let data_size = self.data_section.len() as u32;
-        result.extend_from_slice(&data_size.to_le_bytes());
+        Ok(statements)
+    }
 
-        // Write text section size (4 bytes)
-        let text_size = self.text_section.len() as u32 * 4;
-        result.extend_from_slice(&text_size.to_le_bytes());
+    // The directive or instruction a line's tokens hold
+    fn statement_kind(&self, tokens: &[Token]) -> Result<Option<StatementKind>, AssemblerError> {
+        let (first, rest) = match tokens.split_first() {
+            Some(split) => split,
+            None => return Ok(None),
+        };
 
-        // Write data section
-        result.extend_from_slice(&self.data_section);
+        let kind = match first {
+            Token::Directive(name) => StatementKind::Directive {
+                name: name.clone(),
+                operands: self.operands(rest)?,
+            },
+            Token::Instruction(mnemonic) => StatementKind::Instruction {
+                mnemonic: mnemonic.clone(),
+                operands: self.operands(rest)?,
+            },
+            _ => {
+                return Err(AssemblerError::Syntax(
+                    format!("Unexpected token at start of line: {:?}", first),
+                    self.current_line,
+                ))
+            },
+        };
+        Ok(Some(kind))
+    }
 
-        // Write text section
-        for instr in &self.text_section {
-            result.extend_from_slice(&instr.to_le_bytes());
+    // Group the tokens between commas into typed operands
+    fn operands(&self, tokens: &[Token]) -> Result<Vec<Operand>, AssemblerError> {
+        if tokens.is_empty() {
+            return Ok(Vec::new());
         }
+        tokens
+            .split(|token| *token == Token::Comma)
+            .map(|group| self.operand(group))
+            .collect()
+    }
+
+    fn operand(&self, tokens: &[Token]) -> Result<Operand, AssemblerError> {
+        let operand = match tokens {
+            [] => {
+                return Err(AssemblerError::Operand(
+                    "Missing operand between commas".to_string(),
+                    self.current_line,
+                ))
+            },
+            [Token::Register(number)] => Some(Operand::Register(*number)),
+            [Token::FpRegister(number)] => Some(Operand::FpRegister(*number)),
+            [Token::Immediate(value)] => Some(Operand::Integer(*value as i64)),
+            [Token::LongImmediate(value)] => Some(Operand::Integer(*value)),
+            [Token::Float(value)] => Some(Operand::Float(*value)),
+            [Token::StringLiteral(text)] => Some(Operand::String(text.clone())),
+            [Token::Directive(name)] => Some(Operand::Directive(name.clone())),
+            [offset @ .., Token::LeftParen, Token::Register(base), Token::RightParen] => {
+                match offset {
+                    [] => Some(Operand::Memory {
+                        offset: None,
+                        base: *base,
+                    }),
+                    [Token::Immediate(value)] => Some(Operand::Memory {
+                        offset: Some(Box::new(Operand::Integer(*value as i64))),
+                        base: *base,
+                    }),
+                    _ => symbol_operand(offset).map(|symbol| Operand::Memory {
+                        offset: Some(Box::new(symbol)),
+                        base: *base,
+                    }),
+                }
+            },
+            _ => symbol_operand(tokens),
+        };
+
+        operand.ok_or_else(|| {
+            AssemblerError::Operand(format!("Invalid operand: {:?}", tokens), self.current_line)
+        })
+    }
+
+    // Assemble statements from `parse`, laying out every section first so
+    // that labels may be used before they are defined
+    fn assemble_statements(&mut self, statements: &[Statement]) -> Result<Program, AssemblerError> {
+        // First pass: collect labels and section sizes
+        self.first_pass(statements)?;
+
+        // Second pass: generate code
+        self.second_pass(statements)?;
 
         if !self.errors.is_empty() {
             // Return the first error
             return Err(self.errors.remove(0));
         }
 
-        Ok(result)
+        self.program()
     }
 
-    // First pass: collect labels and directives
-    fn first_pass<R: BufRead>(&mut self, reader: R) -> Result<(), AssemblerError> {
-        self.current_line = 0;
-        self.current_address = 0;
-        self.in_data_section = false;
+    // First pass: collect labels and section sizes
+    fn first_pass(&mut self, statements: &[Statement]) -> Result<(), AssemblerError> {
+        self.section_bases = [0; 6];
+        self.reset_sections();
+        self.noat = false;
         self.labels.clear();
+        self.labels_complete = false;
 
-        for line_result in reader.lines() {
-            self.current_line += 1;
-            let line = line_result?;
-            let line = self.preprocess_line(&line);
-
-            if line.is_empty() {
-                continue;
-            }
+        for statement in statements {
+            self.current_line = statement.line;
 
-            // Check if this line has a label
-            if let Some(label_end) = line.find(':') {
-                let label = line[..label_end].trim();
-                if !label.is_empty() {
-                    // Add label to symbol table
-                    self.labels.insert(label.to_string(), self.current_address);
-                }
+            if let Some(label) = &statement.label {
+                self.check_label(label)?;
 
-                // Process the rest of the line (if any)
-                let rest = line[label_end + 1..].trim();
-                if rest.is_empty() {
-                    continue;
-                }
+                // Add label to symbol table
+                let label_position = Label {
+                    section: self.current_section,
+                    offset: self.current_address - self.sections[self.current_section.index()].base,
+                };
+                self.labels.insert(label.clone(), label_position);
+            }
 
-                self.process_first_pass_line(rest)?;
-            } else {
-                // No label, process the whole line
-                self.process_first_pass_line(&line)?;
+            match &statement.kind {
+                Some(StatementKind::Directive { name, operands }) => {
+                    self.directive(name, operands)?
+                },
+                Some(StatementKind::Instruction { mnemonic, operands }) => {
+                    if self.in_data_section() {
+                        return Err(AssemblerError::Syntax(
+                            "Instructions must be in .text section".to_string(),
+                            self.current_line,
+                        ));
+                    }
+                    // Pseudo-instructions take one word per real instruction
+                    self.advance(self.expanded_word_count(mnemonic, &lower(operands)) * 4)?;
+                },
+                None => {},
             }
         }
 
+        self.layout_sections();
+        self.labels_complete = true;
         Ok(())
     }
 
-    // Process a line during the first pass
-    fn process_first_pass_line(&mut self, line: &str) -> Result<(), AssemblerError> {
-        let tokens = self.tokenize(line)?;
+    // Second pass: generate code
+    fn second_pass(&mut self, statements: &[Statement]) -> Result<(), AssemblerError> {
+        self.reset_sections();
+        self.noat = false;
+        self.warnings.clear();
+        self.relocations.clear();
+        self.syscall_number = None;
+
+        for statement in statements {
+            self.current_line = statement.line;
+
+            if statement.label.is_some() {
+                // A branch may arrive here with another syscall number
+                self.syscall_number = None;
+            }
 
-        if tokens.is_empty() {
-            return Ok(());
+            match &statement.kind {
+                Some(StatementKind::Directive { name, operands }) => {
+                    self.directive(name, operands)?
+                },
+                Some(StatementKind::Instruction { mnemonic, operands }) => {
+                    self.instruction(mnemonic, &lower(operands))?
+                },
+                None => {},
+            }
         }
 
-        match &tokens[0] {
-            Token::Directive(directive) => {
-                match directive.as_str() {
-                    ".data" => {
-                        self.in_data_section = true;
-                    },
-                    ".text" => {
-                        self.in_data_section = false;
-                    },
-                    ".word" => {
-                        // Each word takes 4 bytes in data section
-                        if self.in_data_section {
-                            // Count how many words are defined
-                            let word_count = tokens
-                                .iter()
-                                .filter(|t| matches!(t, Token::Immediate(_) | Token::Symbol(_)))
-                                .count();
-                            self.current_address += word_count as u32 * 4;
-                        } else {
-                            return Err(AssemblerError::Syntax(
-                                ".word directive must be in .data section".to_string(),
-                                self.current_line,
-                            ));
-                        }
-                    },
-                    ".byte" => {
-                        // Each byte takes 1 byte in data section
-                        if self.in_data_section {
-                            // Count how many bytes are defined
-                            let byte_count = tokens
-                                .iter()
-                                .filter(|t| matches!(t, Token::Immediate(_)))
-                                .count();
-                            self.current_address += byte_count as u32;
-                        } else {
-                            return Err(AssemblerError::Syntax(
-                                ".byte directive must be in .data section".to_string(),
-                                self.current_line,
-                            ));
-                        }
-                    },
-                    ".half" => {
-                        // Each halfword takes 2 bytes in data section
-                        if self.in_data_section {
-                            // Count how many halfwords are defined
-                            let half_count = tokens
-                                .iter()
-                                .filter(|t| matches!(t, Token::Immediate(_)))
-                                .count();
-                            self.current_address += half_count as u32 * 2;
-                        } else {
-                            return Err(AssemblerError::Syntax(
-                                ".half directive must be in .data section".to_string(),
-                                self.current_line,
-                            ));
-                        }
-                    },
-                    ".ascii" | ".asciiz" => {
-                        // String length + 1 for null terminator if .asciiz
-                        if self.in_data_section {
-                            if tokens.len() < 2 {
-                                return Err(AssemblerError::Syntax(
-                                    format!("{} directive requires a string argument", directive),
-                                    self.current_line,
-                                ));
-                            }
-
-                            if let Token::StringLiteral(string) = &tokens[1] {
-                                let null_terminator = if directive == ".asciiz" { 1 } else { 0 };
-                                self.current_address += string.len() as u32 + null_terminator;
-                            } else {
-                                return Err(AssemblerError::Syntax(
-                                    format!("{} directive requires a string argument", directive),
-                                    self.current_line,
-                                ));
-                            }
-                        } else {
-                            return Err(AssemblerError::Syntax(
-                                format!("{} directive must be in .data section", directive),
-                                self.current_line,
-                            ));
-                        }
-                    },
-                    ".space" => {
-                        // Allocate n bytes of space
-                        if self.in_data_section {
-                            if tokens.len() < 2 {
-                                return Err(AssemblerError::Syntax(
-                                    ".space directive requires a size argument".to_string(),
-                                    self.current_line,
-                                ));
-                            }
-
-                            if let Token::Immediate(size) = tokens[1] {
-                                if size < 0 {
-                                    return Err(AssemblerError::Range(
-                                        "Space size must be non-negative".to_string(),
-                                        self.current_line,
-                                    ));
-                                }
-                                self.current_address += size as u32;
-                            } else {
-                                return Err(AssemblerError::Syntax(
-                                    ".space directive requires a numeric size argument".to_string(),
-                                    self.current_line,
-                                ));
-                            }
-                        } else {
-                            return Err(AssemblerError::Syntax(
-                                ".space directive must be in .data section".to_string(),
-                                self.current_line,
-                            ));
-                        }
-                    },
-                    ".align" => {
-                        // Align to 2^n boundary
-                        if tokens.len() < 2 {
-                            return Err(AssemblerError::Syntax(
-                                ".align directive requires an alignment argument".to_string(),
-                                self.current_line,
-                            ));
-                        }
+        Ok(())
+    }
 
-                        if let Token::Immediate(align) = tokens[1] {
-                            if !(0..=31).contains(&align) {
-                                return Err(AssemblerError::Range(
-                                    "Alignment must be between 0 and 31".to_string(),
-                                    self.current_line,
-                                ));
-                            }
+    // Apply a directive. Both passes run it, so data takes the same space in
+    // each; symbols read as 0 until the first pass has seen every label.
+    fn directive(&mut self, directive: &str, operands: &[Operand]) -> Result<(), AssemblerError> {
+        self.check_bss_directive(directive)?;
+        if !self.dialect_directive(directive)? {
+            return Ok(());
+        }
 
-                            let alignment = 1 << align;
-                            let misalignment = self.current_address % alignment;
-                            if misalignment != 0 {
-                                self.current_address += alignment - misalignment;
-                            }
-                        } else {
-                            return Err(AssemblerError::Syntax(
-                                ".align directive requires a numeric alignment argument"
-                                    .to_string(),
-                                self.current_line,
-                            ));
-                        }
-                    },
-                    _ => {
-                        // Ignore unknown directives in first pass
-                    },
-                }
+        match directive {
+            ".text" | ".data" | ".rodata" | ".bss" | ".ktext" | ".kdata" => {
+                self.switch_section(directive, operands.first())
             },
-            Token::Instruction(_) => {
-                if !self.in_data_section {
-                    // Each instruction takes 4 bytes in text section
-                    self.current_address += 4;
-                } else {
+            ".set" => {
+                self.set_option(operands.first());
+                Ok(())
+            },
+            ".align" => self.align(operands.first()),
+            ".word" | ".half" | ".byte" | ".float" | ".double" | ".dword" | ".ascii"
+            | ".asciiz" | ".space" => {
+                if !self.in_data_section() {
                     return Err(AssemblerError::Syntax(
-                        "Instructions must be in .text section".to_string(),
+                        format!("{} directive must be in .data section", directive),
                         self.current_line,
                     ));
                 }
+                match directive {
+                    ".ascii" | ".asciiz" => self.emit_string(directive, operands.first()),
+                    ".space" => self.reserve_space(operands.first()),
+                    _ => operands
+                        .iter()
+                        .try_for_each(|operand| self.emit_value(directive, operand)),
+                }
             },
             _ => {
-                return Err(AssemblerError::Syntax(
-                    format!("Unexpected token at start of line: {:?}", tokens[0]),
-                    self.current_line,
-                ));
+                // Other directives, such as .globl, do not change the output
+                Ok(())
             },
         }
-
-        Ok(())
     }
 
-    // Second pass: generate code
-    fn second_pass<R: BufRead>(&mut self, reader: R) -> Result<(), AssemblerError> {
-        self.current_line = 0;
-        self.current_address = 0;
-        self.in_data_section = false;
-        self.data_section.clear();
-        self.text_section.clear();
+    // Generate machine code for an instruction, or for each instruction a
+    // pseudo-instruction expands to
+    fn instruction(&mut self, instr: &str, operands: &[Token]) -> Result<(), AssemblerError> {
+        if self.in_data_section() {
+            return Err(AssemblerError::Syntax(
+                "Instructions must be in .text section".to_string(),
+                self.current_line,
+            ));
+        }
 
-        for line_result in reader.lines() {
-            self.current_line += 1;
-            let line = line_result?;
-            let line = self.preprocess_line(&line);
+        self.track_syscall_number(instr, operands);
+        if !self.noat && operands.contains(&Token::Register(AT)) {
+            self.warnings.push(format!(
+                "line {}: used $at without \".set noat\"",
+                self.current_line
+            ));
+        }
 
-            if line.is_empty() {
-                continue;
+        let expansion = self.expand_pseudo(instr, operands)?;
+        let mut relocations = symbol_relocations(instr, operands, expansion.len());
+        for (index, (name, operands)) in expansion.into_iter().enumerate() {
+            if let Some(position) = relocations.iter().position(|&(at, ..)| at == index) {
+                let (_, kind, symbol, addend) = relocations.remove(position);
+                self.add_relocation(kind, symbol, addend);
             }
+            let machine_code = self.assemble_instruction(name, &operands)?;
+            self.section_bytes()
+                .extend_from_slice(&machine_code.to_le_bytes());
+            self.advance(4)?;
+        }
+        Ok(())
+    }
 
-            // Check if this line has a label
-            if let Some(label_end) = line.find(':') {
-                // Process the rest of the line (if any)
-                let rest = line[label_end + 1..].trim();
-                if rest.is_empty() {
-                    continue;
+    fn reset_sections(&mut self) {
+        self.sections = SectionKind::ALL
+            .iter()
+            .map(|kind| {
+                let base = self.section_bases[kind.index()];
+                SectionState {
+                    base,
+                    location: base,
+                    ..SectionState::default()
                 }
+            })
+            .collect();
+        self.current_section = SectionKind::Text;
+        self.current_address = self.section_bases[SectionKind::Text.index()];
+    }
 
-                self.process_second_pass_line(rest)?;
+    // Choose the address of every section once the first pass knows their
+    // sizes. Text and data start where the loader places flat output, kernel
+    // sections at the usual kseg0 addresses, and .rodata and .bss follow the
+    // data. Sections given an address keep it.
+    fn layout_sections(&mut self) {
+        self.sections[self.current_section.index()].location = self.current_address;
+        let mut data_end = DATA_BASE;
+        for kind in SectionKind::ALL {
+            let section = &self.sections[kind.index()];
+            let size = section.location - section.base;
+            let base = if section.explicit_address {
+                section.base
             } else {
-                // No label, process the whole line
-                self.process_second_pass_line(&line)?;
+                match kind {
+                    SectionKind::Text => TEXT_BASE,
+                    SectionKind::Data => DATA_BASE,
+                    SectionKind::Ktext => KTEXT_BASE,
+                    SectionKind::Kdata => KDATA_BASE,
+                    SectionKind::Rodata | SectionKind::Bss => {
+                        (data_end + SECTION_ALIGNMENT - 1) & !(SECTION_ALIGNMENT - 1)
+                    },
+                }
+            };
+            if !kind.is_text() && kind != SectionKind::Kdata {
+                data_end = data_end.max(base.wrapping_add(size));
             }
+            self.section_bases[kind.index()] = base;
         }
+    }
 
-        Ok(())
+    // Record that the next word emitted holds the address of a symbol
+    fn add_relocation(&mut self, kind: RelocationKind, symbol: &str, addend: i32) {
+        self.relocations.push(Relocation {
+            address: self.current_address,
+            section: self.current_section,
+            kind,
+            symbol: symbol.to_string(),
+            addend,
+        });
     }
 
-    // Process a line during the second pass
-    fn process_second_pass_line(&mut self, line: &str) -> Result<(), AssemblerError> {
-        let tokens = self.tokenize(line)?;
+    // Address of a label, once section bases are known
+    fn symbol_address(&self, symbol: &str) -> Option<u32> {
+        self.labels
+            .get(symbol)
+            .map(|label| self.section_bases[label.section.index()].wrapping_add(label.offset))
+    }
 
-        if tokens.is_empty() {
-            return Ok(());
+    // Address of a label, or 0 during the first pass when it may be defined
+    // further on and only the size of the code that uses it matters
+    fn resolve_symbol(&self, symbol: &str) -> Result<u32, AssemblerError> {
+        match self.symbol_address(symbol) {
+            Some(addr) => Ok(addr),
+            None if !self.labels_complete => Ok(0),
+            None => Err(AssemblerError::Symbol(
+                format!("Undefined symbol: {}", symbol),
+                self.current_line,
+            )),
         }
+    }
 
-        match &tokens[0] {
-            Token::Directive(directive) => {
-                match directive.as_str() {
-                    ".data" => {
-                        self.in_data_section = true;
-                    },
-                    ".text" => {
-                        self.in_data_section = false;
-                    },
-                    ".word" => {
-                        if self.in_data_section {
-                            for token in tokens.iter().skip(1) {
-                                if let Token::Comma = token {
-                                    continue;
-                                }
+    // .bss only reserves zeroed space, so it cannot hold initialized values
+    fn check_bss_directive(&self, directive: &str) -> Result<(), AssemblerError> {
+        let initializes = matches!(
+            directive,
+            ".word" | ".half" | ".byte" | ".ascii" | ".asciiz" | ".float" | ".double" | ".dword"
+        );
+        if self.current_section == SectionKind::Bss && initializes {
+            return Err(AssemblerError::Syntax(
+                format!("{} directive is not allowed in .bss, use .space", directive),
+                self.current_line,
+            ));
+        }
+        Ok(())
+    }
 
-                                match token {
-                                    Token::Immediate(value) => {
-                                        // Add word to data section
-                                        self.data_section
-                                            .extend_from_slice(&(*value as u32).to_le_bytes());
-                                        self.current_address += 4;
-                                    },
-                                    Token::Symbol(symbol) => {
-                                        // Look up symbol value
-                                        if let Some(&addr) = self.labels.get(symbol) {
-                                            self.data_section
-                                                .extend_from_slice(&addr.to_le_bytes());
-                                            self.current_address += 4;
-                                        } else {
-                                            return Err(AssemblerError::Symbol(
-                                                format!("Undefined symbol: {}", symbol),
-                                                self.current_line,
-                                            ));
-                                        }
-                                    },
-                                    _ => {
-                                        return Err(AssemblerError::Syntax(
-                                            format!("Expected immediate value or symbol in .word directive, got {:?}", token),
-                                            self.current_line,
-                                        ));
-                                    },
-                                }
-                            }
-                        } else {
-                            return Err(AssemblerError::Syntax(
-                                ".word directive must be in .data section".to_string(),
-                                self.current_line,
-                            ));
-                        }
-                    },
-                    ".byte" => {
-                        if self.in_data_section {
-                            for token in tokens.iter().skip(1) {
-                                if let Token::Comma = token {
-                                    continue;
-                                }
+    fn in_data_section(&self) -> bool {
+        !self.current_section.is_text()
+    }
 
-                                if let Token::Immediate(value) = token {
-                                    if !(-128..=255).contains(value) {
-                                        return Err(AssemblerError::Range(
-                                            format!("Byte value out of range: {}", value),
-                                            self.current_line,
-                                        ));
-                                    }
+    fn section_bytes(&mut self) -> &mut Vec<u8> {
+        &mut self.sections[self.current_section.index()].bytes
+    }
 
-                                    // Add byte to data section
-                                    self.data_section.push(*value as u8);
-                                    self.current_address += 1;
-                                } else {
-                                    return Err(AssemblerError::Syntax(
-                                        format!(
-                                            "Expected immediate value in .byte directive, got {:?}",
-                                            token
-                                        ),
-                                        self.current_line,
-                                    ));
-                                }
-                            }
-                        } else {
-                            return Err(AssemblerError::Syntax(
-                                ".byte directive must be in .data section".to_string(),
-                                self.current_line,
-                            ));
-                        }
-                    },
-                    ".half" => {
-                        if self.in_data_section {
-                            for i in 1..tokens.len() {
-                                if let Token::Comma = tokens[i] {
-                                    continue;
-                                }
+    // Move the location counter past `bytes` just assembled, which must not
+    // run past the end of the 32-bit address space
+    fn advance(&mut self, bytes: u32) -> Result<(), AssemblerError> {
+        self.current_address = self.current_address.checked_add(bytes).ok_or_else(|| {
+            AssemblerError::Range(
+                format!(
+                    "{} runs past the end of the address space at 0x{:08X}",
+                    self.current_section.name(),
+                    self.current_address
+                ),
+                self.current_line,
+            )
+        })?;
+        Ok(())
+    }
 
-                                if let Token::Immediate(value) = tokens[i] {
-                                    if value < -32768 || value > 65535 {
-                                        return Err(AssemblerError::Range(
-                                            format!("Halfword value out of range: {}", value),
-                                            self.current_line,
-                                        ));
-                                    }
+    // Switch to the section named by `directive`, resuming at its location
+    // counter. An address argument places a section that has no contents
+    // yet, or moves a section forward, padding the gap with zeros.
+    fn switch_section(
+        &mut self,
+        directive: &str,
+        address: Option<&Operand>,
+    ) -> Result<(), AssemblerError> {
+        let kind = SectionKind::from_directive(directive).expect("section directive");
+        self.sections[self.current_section.index()].location = self.current_address;
+        self.current_section = kind;
+        self.current_address = self.sections[kind.index()].location;
+
+        let address = match address {
+            None => return Ok(()),
+            Some(Operand::Integer(address)) => u32::try_from(*address).map_err(|_| {
+                AssemblerError::Range(
+                    format!("Section address out of range: 0x{:X}", address),
+                    self.current_line,
+                )
+            })?,
+            Some(operand) => {
+                return Err(AssemblerError::Syntax(
+                    format!("Expected an address after {}, got {}", directive, operand),
+                    self.current_line,
+                ))
+            },
+        };
+        // Instructions can only be fetched from word addresses
+        if kind.is_text() && address % 4 != 0 {
+            return Err(AssemblerError::Range(
+                format!(
+                    "{} address 0x{:08X} is not word-aligned",
+                    directive, address
+                ),
+                self.current_line,
+            ));
+        }
 
-                                    // Add halfword to data section
-                                    self.data_section
-                                        .extend_from_slice(&(value as u16).to_le_bytes());
-                                    self.current_address += 2;
-                                } else {
-                                    return Err(AssemblerError::Syntax(
-                                        format!(
-                                            "Expected immediate value in .half directive, got {:?}",
-                                            tokens[i]
-                                        ),
-                                        self.current_line,
-                                    ));
-                                }
-                            }
-                        } else {
-                            return Err(AssemblerError::Syntax(
-                                ".half directive must be in .data section".to_string(),
-                                self.current_line,
-                            ));
-                        }
-                    },
-                    ".ascii" | ".asciiz" => {
-                        if self.in_data_section {
-                            if tokens.len() < 2 {
-                                return Err(AssemblerError::Syntax(
-                                    format!("{} directive requires a string argument", directive),
-                                    self.current_line,
-                                ));
-                            }
+        let section = &mut self.sections[kind.index()];
+        if section.location == section.base && !section.explicit_address {
+            section.base = address;
+            section.explicit_address = true;
+        } else if address >= section.location {
+            let padding = (address - section.location) as usize;
+            section.bytes.extend(vec![0; padding]);
+        } else {
+            return Err(AssemblerError::Range(
+                format!(
+                    "Cannot move {} back to 0x{:08X}, it is already at 0x{:08X}",
+                    directive, address, section.location
+                ),
+                self.current_line,
+            ));
+        }
+        self.current_address = address;
+        Ok(())
+    }
 
-                            if let Token::StringLiteral(string) = &tokens[1] {
-                                // Add string to data section
-                                self.data_section.extend_from_slice(string.as_bytes());
-                                self.current_address += string.len() as u32;
+    // Pad to a 2^n boundary with zeros, which are NOPs in text
+    fn align(&mut self, operand: Option<&Operand>) -> Result<(), AssemblerError> {
+        let align = match operand {
+            None => {
+                return Err(AssemblerError::Syntax(
+                    ".align directive requires an alignment argument".to_string(),
+                    self.current_line,
+                ))
+            },
+            Some(Operand::Integer(align)) => *align,
+            Some(_) => {
+                return Err(AssemblerError::Syntax(
+                    ".align directive requires a numeric alignment argument".to_string(),
+                    self.current_line,
+                ))
+            },
+        };
+        if !(0..=31).contains(&align) {
+            return Err(AssemblerError::Range(
+                "Alignment must be between 0 and 31".to_string(),
+                self.current_line,
+            ));
+        }
 
-                                // Add null terminator for .asciiz
-                                if directive == ".asciiz" {
-                                    self.data_section.push(0);
-                                    self.current_address += 1;
-                                }
-                            } else {
-                                return Err(AssemblerError::Syntax(
-                                    format!("{} directive requires a string argument", directive),
-                                    self.current_line,
-                                ));
-                            }
-                        } else {
-                            return Err(AssemblerError::Syntax(
-                                format!("{} directive must be in .data section", directive),
-                                self.current_line,
-                            ));
-                        }
-                    },
-                    ".space" => {
-                        if self.in_data_section {
-                            if tokens.len() < 2 {
-                                return Err(AssemblerError::Syntax(
-                                    ".space directive requires a size argument".to_string(),
-                                    self.current_line,
-                                ));
-                            }
+        let alignment = 1u32 << align;
+        let misalignment = self.current_address % alignment;
+        if misalignment != 0 {
+            let padding = alignment - misalignment;
+            self.section_bytes().extend(vec![0; padding as usize]);
+            self.advance(padding)?;
+        }
+        Ok(())
+    }
 
-                            if let Token::Immediate(size) = tokens[1] {
-                                if size < 0 {
-                                    return Err(AssemblerError::Range(
-                                        "Space size must be non-negative".to_string(),
-                                        self.current_line,
-                                    ));
-                                }
+    // Emit the string of an .ascii or .asciiz directive
+    fn emit_string(
+        &mut self,
+        directive: &str,
+        operand: Option<&Operand>,
+    ) -> Result<(), AssemblerError> {
+        let string = match operand {
+            Some(Operand::String(string)) => string.clone(),
+            _ => {
+                return Err(AssemblerError::Syntax(
+                    format!("{} directive requires a string argument", directive),
+                    self.current_line,
+                ))
+            },
+        };
 
-                                // Add zeros to data section
-                                self.data_section.extend(vec![0; size as usize]);
-                                self.current_address += size as u32;
-                            } else {
-                                return Err(AssemblerError::Syntax(
-                                    ".space directive requires a numeric size argument".to_string(),
-                                    self.current_line,
-                                ));
-                            }
-                        } else {
-                            return Err(AssemblerError::Syntax(
-                                ".space directive must be in .data section".to_string(),
-                                self.current_line,
-                            ));
-                        }
-                    },
-                    ".align" => {
-                        if tokens.len() < 2 {
-                            return Err(AssemblerError::Syntax(
-                                ".align directive requires an alignment argument".to_string(),
-                                self.current_line,
-                            ));
-                        }
+        self.section_bytes().extend_from_slice(string.as_bytes());
+        self.advance(string.len() as u32)?;
 
-                        if let Token::Immediate(align) = tokens[1] {
-                            if align < 0 || align > 31 {
-                                return Err(AssemblerError::Range(
-                                    "Alignment must be between 0 and 31".to_string(),
-                                    self.current_line,
-                                ));
-                            }
+        // Add null terminator for .asciiz
+        if directive == ".asciiz" {
+            self.emit_data(0, 1)?;
+        }
+        Ok(())
+    }
 
-                            let alignment = 1 << align;
-                            let misalignment = self.current_address % alignment;
-                            if misalignment != 0 {
-                                let padding = alignment - misalignment;
-
-                                // Add padding bytes
-                                if self.in_data_section {
-                                    self.data_section.extend(vec![0; padding as usize]);
-                                } else {
-                                    // In text section, add NOP instructions (0x00000000)
-                                    for _ in 0..(padding / 4) {
-                                        self.text_section.push(0);
-                                    }
-                                }
+    // Reserve the zeroed bytes of a .space directive
+    fn reserve_space(&mut self, operand: Option<&Operand>) -> Result<(), AssemblerError> {
+        match operand {
+            None => Err(AssemblerError::Syntax(
+                ".space directive requires a size argument".to_string(),
+                self.current_line,
+            )),
+            Some(Operand::Integer(size)) if *size < 0 => Err(AssemblerError::Range(
+                "Space size must be non-negative".to_string(),
+                self.current_line,
+            )),
+            Some(Operand::Integer(size)) if *size <= i32::MAX as i64 => {
+                self.section_bytes().extend(vec![0; *size as usize]);
+                self.advance(*size as u32)
+            },
+            Some(_) => Err(AssemblerError::Syntax(
+                ".space directive requires a numeric size argument".to_string(),
+                self.current_line,
+            )),
+        }
+    }
 
-                                self.current_address += padding;
-                            }
-                        } else {
-                            return Err(AssemblerError::Syntax(
-                                ".align directive requires a numeric alignment argument"
-                                    .to_string(),
-                                self.current_line,
-                            ));
-                        }
-                    },
-                    _ => {
-                        // Ignore unknown directives in second pass
-                    },
-                }
+    // Emit one value of a .word, .half, .byte, .float, .double or .dword
+    // directive. Symbols are allowed where a full address fits.
+    fn emit_value(&mut self, directive: &str, operand: &Operand) -> Result<(), AssemblerError> {
+        let (value, size) = match (directive, operand) {
+            (".word" | ".dword", Operand::Symbol { name, offset }) => {
+                let addend = offset.unwrap_or(0) as i32;
+                let addr = self.resolve_symbol(name)?.wrapping_add(addend as u32);
+                self.add_relocation(RelocationKind::Word, name, addend);
+                (addr as u64, if directive == ".word" { 4 } else { 8 })
             },
-            Token::Instruction(instr) => {
-                if !self.in_data_section {
-                    // Generate machine code for instruction
-                    let machine_code = self.assemble_instruction(instr, &tokens[1..])?;
-                    self.text_section.push(machine_code);
-                    self.current_address += 4;
-                } else {
-                    return Err(AssemblerError::Syntax(
-                        "Instructions must be in .text section".to_string(),
-                        self.current_line,
-                    ));
-                }
+            (".word", Operand::Integer(value)) => (
+                self.check_range(*value, i32::MIN as i64, u32::MAX as i64, "Word")?,
+                4,
+            ),
+            (".half", Operand::Integer(value)) => {
+                (self.check_range(*value, -32768, 65535, "Halfword")?, 2)
             },
+            (".byte", Operand::Integer(value)) => (self.check_range(*value, -128, 255, "Byte")?, 1),
+            (".float", Operand::Float(value)) => ((*value as f32).to_bits() as u64, 4),
+            (".float", Operand::Integer(value)) => ((*value as f32).to_bits() as u64, 4),
+            (".double", Operand::Float(value)) => (value.to_bits(), 8),
+            (".double", Operand::Integer(value)) => ((*value as f64).to_bits(), 8),
+            (".dword", Operand::Integer(value)) => (*value as u64, 8),
             _ => {
                 return Err(AssemblerError::Syntax(
-                    format!("Unexpected token at start of line: {:?}", tokens[0]),
+                    format!(
+                        "Expected a number in {} directive, got {}",
+                        directive, operand
+                    ),
                     self.current_line,
-                ));
+                ))
             },
+        };
+
+        self.emit_data(value, size)
+    }
+
+    // A data value as its two's complement bits, if it fits its directive
+    fn check_range(
+        &self,
+        value: i64,
+        min: i64,
+        max: i64,
+        what: &str,
+    ) -> Result<u64, AssemblerError> {
+        if value < min || value > max {
+            return Err(AssemblerError::Range(
+                format!("{} value out of range: {}", what, value),
+                self.current_line,
+            ));
         }
+        Ok(value as u64)
+    }
 
-        Ok(())
+    // Append the low `size` bytes of a value to the data section in the
+    // target byte order. Simulated memory is little-endian, so a doubleword
+    // keeps its low word first, as lwc1 pairs and two lw loads expect.
+    fn emit_data(&mut self, value: u64, size: usize) -> Result<(), AssemblerError> {
+        self.section_bytes()
+            .extend_from_slice(&value.to_le_bytes()[..size]);
+        self.advance(size as u32)
     }
 
     // Tokenize a line of assembly code
@@ -966,8 +1117,8 @@ impl Assembler {
                                     }
 
                                     // Parse hex number
-                                    if let Ok(value) = i32::from_str_radix(&num_str[2..], 16) {
-                                        tokens.push(Token::Immediate(value));
+                                    if let Ok(value) = i64::from_str_radix(&num_str[2..], 16) {
+                                        tokens.push(number_token(value));
                                     } else {
                                         return Err(AssemblerError::Parse(
                                             format!("Invalid hex number: {}", num_str),
@@ -990,8 +1141,8 @@ impl Assembler {
                                     }
 
                                     // Parse binary number
-                                    if let Ok(value) = i32::from_str_radix(&num_str[2..], 2) {
-                                        tokens.push(Token::Immediate(value));
+                                    if let Ok(value) = i64::from_str_radix(&num_str[2..], 2) {
+                                        tokens.push(number_token(value));
                                     } else {
                                         return Err(AssemblerError::Parse(
                                             format!("Invalid binary number: {}", num_str),
@@ -1011,8 +1162,8 @@ impl Assembler {
                                     }
 
                                     // Parse octal number
-                                    if let Ok(value) = i32::from_str_radix(&num_str[1..], 8) {
-                                        tokens.push(Token::Immediate(value));
+                                    if let Ok(value) = i64::from_str_radix(&num_str[1..], 8) {
+                                        tokens.push(number_token(value));
                                     } else {
                                         return Err(AssemblerError::Parse(
                                             format!("Invalid octal number: {}", num_str),
@@ -1021,8 +1172,12 @@ impl Assembler {
                                     }
                                 },
                                 _ => {
-                                    // Just a zero
-                                    tokens.push(Token::Immediate(0));
+                                    // Just a zero, or a float such as 0.5
+                                    if read_float_suffix(&mut chars, &mut num_str) {
+                                        tokens.push(self.float_token(&num_str)?);
+                                    } else {
+                                        tokens.push(Token::Immediate(0));
+                                    }
                                 },
                             }
                         } else {
@@ -1040,9 +1195,11 @@ impl Assembler {
                             }
                         }
 
-                        // Parse decimal number
-                        if let Ok(value) = num_str.parse::<i32>() {
-                            tokens.push(Token::Immediate(value));
+                        // Parse decimal number, which may be a float
+                        if read_float_suffix(&mut chars, &mut num_str) {
+                            tokens.push(self.float_token(&num_str)?);
+                        } else if let Ok(value) = num_str.parse::<i64>() {
+                            tokens.push(number_token(value));
                         } else {
                             return Err(AssemblerError::Parse(
                                 format!("Invalid decimal number: {}", num_str),
@@ -1173,22 +1330,34 @@ impl Assembler {
         Ok(tokens)
     }
 
+    fn float_token(&self, text: &str) -> Result<Token, AssemblerError> {
+        text.parse::<f64>().map(Token::Float).map_err(|_| {
+            AssemblerError::Parse(
+                format!("Invalid floating-point number: {}", text),
+                self.current_line,
+            )
+        })
+    }
+
     // Normalize instruction name (convert aliases to canonical form)
     fn normalize_instruction(&self, name: &str) -> Option<String> {
         // Common instruction aliases
         match name.to_lowercase().as_str() {
-            "add" | "addu" => Some("add".to_string()),
+            "add" => Some("add".to_string()),
+            "addu" => Some("addu".to_string()),
             "sub" | "subu" => Some("sub".to_string()),
             "and" => Some("and".to_string()),
             "or" => Some("or".to_string()),
             "xor" => Some("xor".to_string()),
             "nor" => Some("nor".to_string()),
-            "slt" | "sltu" => Some("slt".to_string()),
+            "slt" => Some("slt".to_string()),
+            "sltu" => Some("sltu".to_string()),
             "sll" | "sllv" => Some("sll".to_string()),
             "srl" | "srlv" => Some("srl".to_string()),
             "sra" | "srav" => Some("sra".to_string()),
             "addi" | "addiu" => Some("addi".to_string()),
-            "slti" | "sltiu" => Some("slti".to_string()),
+            "slti" => Some("slti".to_string()),
+            "sltiu" => Some("sltiu".to_string()),
             "andi" => Some("andi".to_string()),
             "ori" => Some("ori".to_string()),
             "xori" => Some("xori".to_string()),
@@ -1201,6 +1370,8 @@ impl Assembler {
             "lh" => Some("lh".to_string()),
             "lhu" => Some("lhu".to_string()),
             "sh" => Some("sh".to_string()),
+            "ll" => Some("ll".to_string()),
+            "sc" => Some("sc".to_string()),
             "beq" => Some("beq".to_string()),
             "bne" => Some("bne".to_string()),
             "bgtz" => Some("bgtz".to_string()),
@@ -1217,13 +1388,53 @@ impl Assembler {
             "mflo" => Some("mflo".to_string()),
             "mthi" => Some("mthi".to_string()),
             "mtlo" => Some("mtlo".to_string()),
+            "mfc0" => Some("mfc0".to_string()),
+            "mtc0" => Some("mtc0".to_string()),
+            "eret" => Some("eret".to_string()),
+            "rdpgpr" => Some("rdpgpr".to_string()),
+            "wrpgpr" => Some("wrpgpr".to_string()),
+            "rdhwr" => Some("rdhwr".to_string()),
             "syscall" => Some("syscall".to_string()),
             "break" => Some("break".to_string()),
+            "teq" => Some("teq".to_string()),
+            "tne" => Some("tne".to_string()),
+            "tge" => Some("tge".to_string()),
+            "tgeu" => Some("tgeu".to_string()),
+            "tlt" => Some("tlt".to_string()),
+            "tltu" => Some("tltu".to_string()),
+            "teqi" => Some("teqi".to_string()),
+            "tnei" => Some("tnei".to_string()),
+            "tgei" => Some("tgei".to_string()),
+            "tgeiu" => Some("tgeiu".to_string()),
+            "tlti" => Some("tlti".to_string()),
+            "tltiu" => Some("tltiu".to_string()),
             "nop" => Some("nop".to_string()),
             "move" => Some("move".to_string()),
             "li" => Some("li".to_string()),
             "la" => Some("la".to_string()),
             "b" => Some("b".to_string()),
+            "blt" => Some("blt".to_string()),
+            "bgt" => Some("bgt".to_string()),
+            "ble" => Some("ble".to_string()),
+            "bge" => Some("bge".to_string()),
+            "bltu" => Some("bltu".to_string()),
+            "bgtu" => Some("bgtu".to_string()),
+            "bleu" => Some("bleu".to_string()),
+            "bgeu" => Some("bgeu".to_string()),
+            "beqz" => Some("beqz".to_string()),
+            "bnez" => Some("bnez".to_string()),
+            "mul" => Some("mul".to_string()),
+            "neg" => Some("neg".to_string()),
+            "not" => Some("not".to_string()),
+            "abs" => Some("abs".to_string()),
+            "sgt" => Some("sgt".to_string()),
+            "sge" => Some("sge".to_string()),
+            "sle" => Some("sle".to_string()),
+            "seq" => Some("seq".to_string()),
+            "sne" => Some("sne".to_string()),
+            "push" => Some("push".to_string()),
+            "pop" => Some("pop".to_string()),
+            "negu" => Some("neg".to_string()),
             // FP instructions
             "add.s" => Some("add.s".to_string()),
             "sub.s" => Some("sub.s".to_string()),
@@ -1246,7 +1457,7 @@ impl Assembler {
     }
 
     // Preprocess a line of code
-    fn preprocess_line(&self, line: &str) -> String {
+    fn preprocess_line(&mut self, line: &str) -> String {
         // Remove comments
         let mut result = String::new();
         let mut in_string = false;
@@ -1271,20 +1482,152 @@ impl Assembler {
                 result.push(c);
             }
         }
+        let mut line = result.trim().to_string();
+
+        if self.dialect.has_equivalences() {
+            if let Some(definition) = line.strip_prefix(".eqv ") {
+                let mut parts = definition.trim().splitn(2, char::is_whitespace);
+                if let (Some(name), Some(text)) = (parts.next(), parts.next()) {
+                    self.equivalences
+                        .insert(name.to_string(), text.trim().to_string());
+                    return String::new();
+                }
+            }
+            if !self.equivalences.is_empty() {
+                line = self.substitute_equivalences(&line);
+            }
+        }
+        if self.dialect.repeats_values() {
+            line = expand_repeated_values(&line);
+        }
+        line
+    }
+
+    // Replace names defined with `.eqv` outside string literals
+    fn substitute_equivalences(&self, line: &str) -> String {
+        let mut result = String::new();
+        let mut word = String::new();
+        let mut in_string = false;
+        let mut escape = false;
+
+        // A trailing space flushes the last word
+        for c in line.chars().chain(std::iter::once(' ')) {
+            if !in_string && (c.is_alphanumeric() || c == '_' || c == '.' || c == '$') {
+                word.push(c);
+                continue;
+            }
+            if !word.is_empty() {
+                result.push_str(self.equivalences.get(&word).unwrap_or(&word));
+                word.clear();
+            }
+            if in_string {
+                if escape {
+                    escape = false;
+                } else if c == '\\' {
+                    escape = true;
+                } else if c == '"' {
+                    in_string = false;
+                }
+            } else if c == '"' {
+                in_string = true;
+            }
+            result.push(c);
+        }
+        result.trim_end().to_string()
+    }
+
+    // Labels the dialect rejects: names that are not identifiers, a second
+    // definition of the same name, or the name of an instruction
+    fn check_label(&self, label: &str) -> Result<(), AssemblerError> {
+        if !self.dialect.checks_labels() {
+            return Ok(());
+        }
+        let problem = if label.starts_with(|c: char| c.is_ascii_digit())
+            || !label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$')
+        {
+            "is not a valid label name"
+        } else if self.labels.contains_key(label) {
+            "is already defined"
+        } else if self.normalize_instruction(label).is_some() {
+            "is the name of an instruction"
+        } else {
+            return Ok(());
+        };
+        Err(AssemblerError::Symbol(
+            format!("{} {} in the {} dialect", label, problem, self.dialect),
+            self.current_line,
+        ))
+    }
+
+    // Apply the dialect's rules for a directive, returning false for
+    // directives to skip. Warnings are only recorded in the second pass.
+    fn dialect_directive(&mut self, directive: &str) -> Result<bool, AssemblerError> {
+        match self.dialect.directive(directive) {
+            DirectiveUse::Assembled => Ok(true),
+            DirectiveUse::Ignored => Ok(false),
+            DirectiveUse::Extension => {
+                if self.labels_complete {
+                    self.warnings.push(format!(
+                        "line {}: {} is not a {} directive",
+                        self.current_line, directive, self.dialect
+                    ));
+                }
+                Ok(true)
+            },
+            DirectiveUse::Rejected => Err(AssemblerError::Unsupported(
+                format!(
+                    "{} is not supported in the {} dialect",
+                    directive, self.dialect
+                ),
+                self.current_line,
+            )),
+        }
+    }
+
+    // Follow the constant loaded into $v0 and report syscalls that behave
+    // differently than in the dialect's simulator
+    fn track_syscall_number(&mut self, instr: &str, operands: &[Token]) {
+        if self.dialect == Dialect::Native {
+            return;
+        }
+        if instr == "syscall" {
+            let incompatibility = self
+                .syscall_number
+                .take()
+                .and_then(|number| self.dialect.syscall_incompatibility(number));
+            if let Some(incompatibility) = incompatibility {
+                self.warnings
+                    .push(format!("line {}: {}", self.current_line, incompatibility));
+            }
+            return;
+        }
 
-        result.trim().to_string()
+        let values: Vec<&Token> = operands.iter().filter(|t| **t != Token::Comma).collect();
+        if values.first() == Some(&&Token::Register(2)) {
+            self.syscall_number = match (instr, &values[1..]) {
+                ("li", [Token::Immediate(number)]) => Some(*number as u32),
+                ("addi" | "ori", [Token::Register(0), Token::Immediate(number)]) => {
+                    Some(*number as u32)
+                },
+                _ => None,
+            };
+        }
     }
 
     // Assemble an instruction
     fn assemble_instruction(&self, instr: &str, operands: &[Token]) -> Result<u32, AssemblerError> {
         match instr {
             "add" => self.assemble_r_type(0, 0x20, operands),
+            "addu" => self.assemble_r_type(0, 0x21, operands),
             "sub" => self.assemble_r_type(0, 0x22, operands),
             "and" => self.assemble_r_type(0, 0x24, operands),
             "or" => self.assemble_r_type(0, 0x25, operands),
             "xor" => self.assemble_r_type(0, 0x26, operands),
             "nor" => self.assemble_r_type(0, 0x27, operands),
             "slt" => self.assemble_r_type(0, 0x2A, operands),
+            "sltu" => self.assemble_r_type(0, 0x2B, operands),
             "sll" => self.assemble_shift(0, 0x00, operands),
             "srl" => self.assemble_shift(0, 0x02, operands),
             "sra" => self.assemble_shift(0, 0x03, operands),
@@ -1294,6 +1637,8 @@ impl Assembler {
             "andi" => self.assemble_i_type(0x0C, operands),
             "ori" => self.assemble_i_type(0x0D, operands),
             "xori" => self.assemble_i_type(0x0E, operands),
+            "slti" => self.assemble_i_type(0x0A, operands),
+            "sltiu" => self.assemble_i_type(0x0B, operands),
             "lui" => self.assemble_lui(operands),
             "lw" => self.assemble_load_store(0x23, operands),
             "sw" => self.assemble_load_store(0x2B, operands),
@@ -1303,6 +1648,8 @@ impl Assembler {
             "lh" => self.assemble_load_store(0x21, operands),
             "lhu" => self.assemble_load_store(0x25, operands),
             "sh" => self.assemble_load_store(0x29, operands),
+            "ll" => self.assemble_load_store(0x30, operands),
+            "sc" => self.assemble_load_store(0x38, operands),
             "beq" => self.assemble_branch(0x04, operands),
             "bne" => self.assemble_branch(0x05, operands),
             "bgtz" => self.assemble_branch_z(0x07, operands),
@@ -1317,12 +1664,28 @@ impl Assembler {
             "mflo" => self.assemble_mf(0, 0x12, operands),
             "mthi" => self.assemble_mt(0, 0x11, operands),
             "mtlo" => self.assemble_mt(0, 0x13, operands),
+            "mfc0" => self.assemble_cop0_move(0x00, operands),
+            "mtc0" => self.assemble_cop0_move(0x04, operands),
+            "eret" => Ok(0x42000018),
+            "rdpgpr" => self.assemble_shadow_move(0x0A, operands),
+            "wrpgpr" => self.assemble_shadow_move(0x0E, operands),
+            "rdhwr" => self.assemble_rdhwr(operands),
             "syscall" => self.assemble_syscall(),
             "break" => self.assemble_break(operands),
+            "teq" => self.assemble_trap(0x34, operands),
+            "tne" => self.assemble_trap(0x36, operands),
+            "tge" => self.assemble_trap(0x30, operands),
+            "tgeu" => self.assemble_trap(0x31, operands),
+            "tlt" => self.assemble_trap(0x32, operands),
+            "tltu" => self.assemble_trap(0x33, operands),
+            "teqi" => self.assemble_trap_imm(0x0C, operands),
+            "tnei" => self.assemble_trap_imm(0x0E, operands),
+            "tgei" => self.assemble_trap_imm(0x08, operands),
+            "tgeiu" => self.assemble_trap_imm(0x09, operands),
+            "tlti" => self.assemble_trap_imm(0x0A, operands),
+            "tltiu" => self.assemble_trap_imm(0x0B, operands),
             "nop" => Ok(0),
             "move" => self.assemble_move(operands),
-            "li" => self.assemble_li(operands),
-            "la" => self.assemble_la(operands),
             "b" => self.assemble_b(operands),
             // FP instructions
             "add.s" => self.assemble_fp_r_type(0x11, 0x10, 0x00, operands),
@@ -1334,9 +1697,9 @@ impl Assembler {
             "mov.s" => self.assemble_fp_r_type_fs(0x11, 0x10, 0x06, operands),
             "cvt.s.w" => self.assemble_fp_r_type_fs(0x11, 0x14, 0x20, operands),
             "cvt.w.s" => self.assemble_fp_r_type_fs(0x11, 0x10, 0x24, operands),
-            "c.eq.s" => self.assemble_fp_cmp(0x11, 0x10, 0x30, 0x00, operands),
-            "c.lt.s" => self.assemble_fp_cmp(0x11, 0x10, 0x30, 0x01, operands),
-            "c.le.s" => self.assemble_fp_cmp(0x11, 0x10, 0x30, 0x02, operands),
+            "c.eq.s" => self.assemble_fp_cmp(0x11, 0x10, 0x32, operands),
+            "c.lt.s" => self.assemble_fp_cmp(0x11, 0x10, 0x3C, operands),
+            "c.le.s" => self.assemble_fp_cmp(0x11, 0x10, 0x3E, operands),
             "lwc1" => self.assemble_fp_load_store(0x31, operands),
             "swc1" => self.assemble_fp_load_store(0x39, operands),
             "bc1t" => self.assemble_fp_branch(0x11, 0x08, 0x01, operands),
@@ -1498,7 +1861,7 @@ impl Assembler {
             },
             (Token::Register(rt), Token::Register(rs), Token::Symbol(symbol)) => {
                 // Look up symbol value
-                if let Some(&addr) = self.labels.get(symbol) {
+                if let Some(addr) = self.symbol_address(symbol) {
                     // Calculate offset for PC-relative addressing
                     let offset = (addr as i32 - (self.current_address as i32 + 4)) / 4;
 
@@ -1564,8 +1927,8 @@ impl Assembler {
 
         match &operands[0] {
             Token::Register(rt) => {
-                // Parse the address operand
-                let (base, offset) = self.parse_address(&operands[1..])?;
+                // Parse the address operand, which follows the comma
+                let (base, offset) = self.parse_address(&operands[2..])?;
 
                 // opcode (6 bits) | base (5 bits) | rt (5 bits) | offset (16 bits)
                 Ok((opcode << 26) | (base << 21) | (*rt << 16) | (offset as u32 & 0xFFFF))
@@ -1606,7 +1969,7 @@ impl Assembler {
                 },
                 (Token::Symbol(symbol), Token::Register(base)) => {
                     // Look up symbol value
-                    if let Some(&addr) = self.labels.get(symbol) {
+                    if let Some(addr) = self.symbol_address(symbol) {
                         Ok((*base, addr as i16))
                     } else {
                         Err(AssemblerError::Symbol(
@@ -1640,7 +2003,7 @@ impl Assembler {
         match (&operands[0], &operands[2], &operands[4]) {
             (Token::Register(rs), Token::Register(rt), Token::Symbol(symbol)) => {
                 // Look up symbol value
-                if let Some(&addr) = self.labels.get(symbol) {
+                if let Some(addr) = self.symbol_address(symbol) {
                     // Calculate offset for PC-relative addressing
                     let offset = (addr as i32 - (self.current_address as i32 + 4)) / 4;
 
@@ -1690,7 +2053,7 @@ impl Assembler {
         match (&operands[0], &operands[2]) {
             (Token::Register(rs), Token::Symbol(symbol)) => {
                 // Look up symbol value
-                if let Some(&addr) = self.labels.get(symbol) {
+                if let Some(addr) = self.symbol_address(symbol) {
                     // Calculate offset for PC-relative addressing
                     let offset = (addr as i32 - (self.current_address as i32 + 4)) / 4;
 
@@ -1745,7 +2108,7 @@ impl Assembler {
         match (&operands[0], &operands[2]) {
             (Token::Register(rs), Token::Symbol(symbol)) => {
                 // Look up symbol value
-                if let Some(&addr) = self.labels.get(symbol) {
+                if let Some(addr) = self.symbol_address(symbol) {
                     // Calculate offset for PC-relative addressing
                     let offset = (addr as i32 - (self.current_address as i32 + 4)) / 4;
 
@@ -1795,7 +2158,7 @@ impl Assembler {
         match &operands[0] {
             Token::Symbol(symbol) => {
                 // Look up symbol value
-                if let Some(&addr) = self.labels.get(symbol) {
+                if let Some(addr) = self.symbol_address(symbol) {
                     if addr % 4 != 0 {
                         return Err(AssemblerError::Range(
                             format!("Jump target not word-aligned: 0x{:08X}", addr),
@@ -1910,6 +2273,81 @@ impl Assembler {
         }
     }
 
+    // Assemble coprocessor 0 moves (mfc0, mtc0), with an optional select
+    fn assemble_cop0_move(&self, rs: u32, operands: &[Token]) -> Result<u32, AssemblerError> {
+        if operands.len() < 3 {
+            return Err(AssemblerError::Syntax(
+                "MFC0/MTC0 instruction requires a register and a CP0 register".to_string(),
+                self.current_line,
+            ));
+        }
+
+        let select = match operands.get(4) {
+            None => 0,
+            Some(Token::Immediate(select)) if (0..8).contains(select) => *select as u32,
+            Some(_) => {
+                return Err(AssemblerError::Syntax(
+                    "MFC0/MTC0 select must be between 0 and 7".to_string(),
+                    self.current_line,
+                ))
+            },
+        };
+
+        match (&operands[0], &operands[2]) {
+            (Token::Register(rt), Token::Register(rd)) => {
+                // opcode (6 bits) | rs (5 bits) | rt (5 bits) | rd (5 bits) | 0 (8 bits) | sel (3 bits)
+                Ok((0x10 << 26) | (rs << 21) | (*rt << 16) | (*rd << 11) | select)
+            },
+            _ => Err(AssemblerError::Syntax(
+                "Invalid operands for MFC0/MTC0 instruction".to_string(),
+                self.current_line,
+            )),
+        }
+    }
+
+    // Assemble rdpgpr and wrpgpr, which move a register between the current
+    // and the previous shadow register set
+    fn assemble_shadow_move(&self, rs: u32, operands: &[Token]) -> Result<u32, AssemblerError> {
+        if operands.len() < 3 {
+            return Err(AssemblerError::Syntax(
+                "RDPGPR/WRPGPR instruction requires two registers".to_string(),
+                self.current_line,
+            ));
+        }
+
+        match (&operands[0], &operands[2]) {
+            (Token::Register(rd), Token::Register(rt)) => {
+                // opcode (6 bits) | rs (5 bits) | rt (5 bits) | rd (5 bits) | 0 (11 bits)
+                Ok((0x10 << 26) | (rs << 21) | (*rt << 16) | (*rd << 11))
+            },
+            _ => Err(AssemblerError::Syntax(
+                "Invalid operands for RDPGPR/WRPGPR instruction".to_string(),
+                self.current_line,
+            )),
+        }
+    }
+
+    // Assemble rdhwr, which reads a hardware register into rt
+    fn assemble_rdhwr(&self, operands: &[Token]) -> Result<u32, AssemblerError> {
+        if operands.len() < 3 {
+            return Err(AssemblerError::Syntax(
+                "RDHWR instruction requires a register and a hardware register".to_string(),
+                self.current_line,
+            ));
+        }
+
+        match (&operands[0], &operands[2]) {
+            (Token::Register(rt), Token::Register(rd)) => {
+                // SPECIAL3 (6 bits) | 0 (5 bits) | rt (5 bits) | rd (5 bits) | 0 (5 bits) | funct (6 bits)
+                Ok((0x1F << 26) | (*rt << 16) | (*rd << 11) | 0x3B)
+            },
+            _ => Err(AssemblerError::Syntax(
+                "Invalid operands for RDHWR instruction".to_string(),
+                self.current_line,
+            )),
+        }
+    }
+
     // Assemble syscall instruction
     fn assemble_syscall(&self) -> Result<u32, AssemblerError> {
         // opcode (6 bits) | 0 (20 bits) | funct (6 bits)
@@ -1939,107 +2377,348 @@ impl Assembler {
         Ok((0 << 26) | (code << 6) | 0x0D)
     }
 
-    // Assemble move pseudo-instruction (move $rd, $rs)
-    fn assemble_move(&self, operands: &[Token]) -> Result<u32, AssemblerError> {
+    // Assemble register trap instructions (teq $rs, $rt[, code])
+    fn assemble_trap(&self, funct: u32, operands: &[Token]) -> Result<u32, AssemblerError> {
         if operands.len() < 3 {
             return Err(AssemblerError::Syntax(
-                "MOVE instruction requires two registers".to_string(),
+                "Trap instruction requires two registers".to_string(),
                 self.current_line,
             ));
         }
 
+        let code = match operands.get(4) {
+            Some(Token::Immediate(code)) => {
+                if *code < 0 || *code > 1023 {
+                    return Err(AssemblerError::Range(
+                        format!("Trap code out of range: {}", code),
+                        self.current_line,
+                    ));
+                }
+                *code as u32
+            },
+            _ => 0,
+        };
+
         match (&operands[0], &operands[2]) {
-            (Token::Register(rd), Token::Register(rs)) => {
-                // Translate to "addu $rd, $zero, $rs"
-                // opcode (6 bits) | zero (5 bits) | rs (5 bits) | rd (5 bits) | 0 (5 bits) | addu funct (6 bits)
-                Ok((*rs << 16) | (*rd << 11) | 0x21)
+            (Token::Register(rs), Token::Register(rt)) => {
+                // opcode (6 bits) | rs (5 bits) | rt (5 bits) | code (10 bits) | funct (6 bits)
+                Ok((*rs << 21) | (*rt << 16) | (code << 6) | funct)
             },
             _ => Err(AssemblerError::Syntax(
-                "Invalid operands for MOVE instruction".to_string(),
+                "Invalid operands for trap instruction".to_string(),
                 self.current_line,
             )),
         }
     }
 
-    // Assemble load immediate pseudo-instruction (li $rt, imm)
-    fn assemble_li(&self, operands: &[Token]) -> Result<u32, AssemblerError> {
+    // Assemble immediate trap instructions (teqi $rs, imm)
+    fn assemble_trap_imm(&self, rt: u32, operands: &[Token]) -> Result<u32, AssemblerError> {
         if operands.len() < 3 {
             return Err(AssemblerError::Syntax(
-                "LI instruction requires a register and an immediate value".to_string(),
+                "Trap instruction requires a register and an immediate".to_string(),
                 self.current_line,
             ));
         }
 
         match (&operands[0], &operands[2]) {
-            (Token::Register(rt), Token::Immediate(imm)) => {
-                if *imm >= -32768 && *imm <= 32767 {
-                    // Small immediate, use "addi $rt, $zero, imm"
-                    // opcode (6 bits) | zero (5 bits) | rt (5 bits) | immediate (16 bits)
-                    Ok((0x08 << 26) | (0 << 21) | (*rt << 16) | (*imm as u32 & 0xFFFF))
-                } else {
-                    // Large immediate, split into two instructions
-                    // This case is tricky since we can't return two instructions
-                    // For now, just return the first instruction and rely on the assembler to expand this
-                    let upper = (*imm >> 16) & 0xFFFF;
-
-                    // First instruction: "lui $rt, upper"
-                    // opcode (6 bits) | 0 (5 bits) | rt (5 bits) | upper (16 bits)
-                    let lui = (0x0F << 26) | (0 << 21) | (*rt << 16) | (upper as u32 & 0xFFFF);
-
-                    // Warn about truncated expansion
-                    println!(
-                        "Warning: LI expansion truncated to first instruction at line {}",
-                        self.current_line
-                    );
-
-                    Ok(lui)
+            (Token::Register(rs), Token::Immediate(imm)) => {
+                if *imm < -32768 || *imm > 32767 {
+                    return Err(AssemblerError::Range(
+                        format!("Immediate value out of range: {}", imm),
+                        self.current_line,
+                    ));
                 }
+
+                // REGIMM opcode (6 bits) | rs (5 bits) | rt (5 bits) | immediate (16 bits)
+                Ok((0x01 << 26) | (*rs << 21) | (rt << 16) | (*imm as u32 & 0xFFFF))
             },
             _ => Err(AssemblerError::Syntax(
-                "Invalid operands for LI instruction".to_string(),
+                "Invalid operands for trap instruction".to_string(),
                 self.current_line,
             )),
         }
     }
 
-    // Assemble load address pseudo-instruction (la $rt, symbol)
-    fn assemble_la(&self, operands: &[Token]) -> Result<u32, AssemblerError> {
+    // Assemble move pseudo-instruction (move $rd, $rs)
+    fn assemble_move(&self, operands: &[Token]) -> Result<u32, AssemblerError> {
         if operands.len() < 3 {
             return Err(AssemblerError::Syntax(
-                "LA instruction requires a register and a symbol".to_string(),
+                "MOVE instruction requires two registers".to_string(),
                 self.current_line,
             ));
         }
 
         match (&operands[0], &operands[2]) {
-            (Token::Register(rt), Token::Symbol(symbol)) => {
-                // Look up symbol value
-                if let Some(&addr) = self.labels.get(symbol) {
-                    let upper = (addr >> 16) & 0xFFFF;
-
-                    // First instruction: "lui $rt, upper"
-                    // opcode (6 bits) | 0 (5 bits) | rt (5 bits) | upper (16 bits)
-                    let lui = (0x0F << 26) | (0 << 21) | (*rt << 16) | (upper as u32);
+            (Token::Register(rd), Token::Register(rs)) => {
+                // Translate to "addu $rd, $zero, $rs"
+                // opcode (6 bits) | zero (5 bits) | rs (5 bits) | rd (5 bits) | 0 (5 bits) | addu funct (6 bits)
+                Ok((*rs << 16) | (*rd << 11) | 0x21)
+            },
+            _ => Err(AssemblerError::Syntax(
+                "Invalid operands for MOVE instruction".to_string(),
+                self.current_line,
+            )),
+        }
+    }
 
-                    // Warn about truncated expansion
-                    println!(
-                        "Warning: LA expansion truncated to first instruction at line {}",
-                        self.current_line
-                    );
+    // Number of words an instruction occupies once pseudo-instructions are
+    // expanded. Operand errors are reported by the second pass.
+    fn expanded_word_count(&self, instr: &str, operands: &[Token]) -> u32 {
+        self.expand_pseudo(instr, operands)
+            .map_or(1, |expansion| expansion.len() as u32)
+    }
 
-                    Ok(lui)
-                } else {
-                    Err(AssemblerError::Symbol(
-                        format!("Undefined symbol: {}", symbol),
-                        self.current_line,
-                    ))
+    // Expand a pseudo-instruction into the real instructions it stands for,
+    // using $at as the scratch register. Real instructions are returned as is.
+    fn expand_pseudo<'a>(
+        &self,
+        instr: &'a str,
+        operands: &[Token],
+    ) -> Result<Vec<(&'a str, Vec<Token>)>, AssemblerError> {
+        let expansion = match instr {
+            "li" => match self.pseudo_operands(instr, operands, 2)?[..] {
+                [Token::Register(rt), Token::Immediate(imm)] => load_immediate(*rt, *imm),
+                _ => return Err(self.invalid_pseudo_operands(instr)),
+            },
+            "la" => match self.pseudo_operands(instr, operands, 2)?[..] {
+                [Token::Register(rt), Token::Symbol(symbol)] => {
+                    let addr = self.resolve_symbol(symbol)?;
+                    vec![
+                        (
+                            "lui",
+                            args(&[reg(*rt), Token::Immediate((addr >> 16) as i32)]),
+                        ),
+                        (
+                            "ori",
+                            args(&[reg(*rt), reg(*rt), Token::Immediate((addr & 0xFFFF) as i32)]),
+                        ),
+                    ]
+                },
+                _ => return Err(self.invalid_pseudo_operands(instr)),
+            },
+            "blt" | "bgt" | "ble" | "bge" | "bltu" | "bgtu" | "bleu" | "bgeu" => {
+                let (rs, rt, target) = match self.pseudo_operands(instr, operands, 3)?[..] {
+                    [Token::Register(rs), rt @ (Token::Register(_) | Token::Immediate(_)), target @ (Token::Symbol(_) | Token::Immediate(_))] => {
+                        (*rs, rt, target)
+                    },
+                    _ => return Err(self.invalid_pseudo_operands(instr)),
+                };
+                self.require_at(instr)?;
+
+                // An immediate comparand is loaded into $at first
+                let (mut expansion, rt) = match rt {
+                    Token::Immediate(imm) => (load_immediate(AT, *imm), AT),
+                    Token::Register(rt) => (Vec::new(), *rt),
+                    _ => unreachable!(),
+                };
+
+                let compare = if instr.ends_with('u') { "sltu" } else { "slt" };
+                // blt/bge test rs < rt, bgt/ble test rt < rs
+                let (left, right) = match &instr[..3] {
+                    "blt" | "bge" => (rs, rt),
+                    _ => (rt, rs),
+                };
+                // blt/bgt branch when the comparison holds, bge/ble when it does not
+                let branch = match &instr[..3] {
+                    "blt" | "bgt" => "bne",
+                    _ => "beq",
+                };
+
+                expansion.push((compare, args(&[reg(AT), reg(left), reg(right)])));
+                expansion.push((branch, args(&[reg(AT), reg(0), target.clone()])));
+                expansion
+            },
+            "beqz" | "bnez" => match self.pseudo_operands(instr, operands, 2)?[..] {
+                [Token::Register(rs), target @ (Token::Symbol(_) | Token::Immediate(_))] => {
+                    let branch = if instr == "beqz" { "beq" } else { "bne" };
+                    vec![(branch, args(&[reg(*rs), reg(0), target.clone()]))]
+                },
+                _ => return Err(self.invalid_pseudo_operands(instr)),
+            },
+            "neg" | "not" | "abs" => match self.pseudo_operands(instr, operands, 2)?[..] {
+                [Token::Register(rd), Token::Register(rs)] => match instr {
+                    "neg" => vec![("sub", args(&[reg(*rd), reg(0), reg(*rs)]))],
+                    "not" => vec![("nor", args(&[reg(*rd), reg(*rs), reg(0)]))],
+                    // $at holds all ones for negative values, so xor and
+                    // subtract negate them and leave others unchanged
+                    _ => {
+                        self.require_at(instr)?;
+                        vec![
+                            ("sra", args(&[reg(AT), reg(*rs), Token::Immediate(31)])),
+                            ("xor", args(&[reg(*rd), reg(*rs), reg(AT)])),
+                            ("sub", args(&[reg(*rd), reg(*rd), reg(AT)])),
+                        ]
+                    },
+                },
+                _ => return Err(self.invalid_pseudo_operands(instr)),
+            },
+            "mul" | "sgt" | "sge" | "sle" | "seq" | "sne" => {
+                match self.pseudo_operands(instr, operands, 3)?[..] {
+                    [Token::Register(rd), Token::Register(rs), Token::Register(rt)] => {
+                        let (rd, rs, rt) = (*rd, *rs, *rt);
+                        match instr {
+                            "mul" => vec![
+                                ("mult", args(&[reg(rs), reg(rt)])),
+                                ("mflo", args(&[reg(rd)])),
+                            ],
+                            "sgt" => vec![("slt", args(&[reg(rd), reg(rt), reg(rs)]))],
+                            "sge" => vec![
+                                ("slt", args(&[reg(rd), reg(rs), reg(rt)])),
+                                ("xori", args(&[reg(rd), reg(rd), Token::Immediate(1)])),
+                            ],
+                            "sle" => vec![
+                                ("slt", args(&[reg(rd), reg(rt), reg(rs)])),
+                                ("xori", args(&[reg(rd), reg(rd), Token::Immediate(1)])),
+                            ],
+                            "seq" => vec![
+                                ("xor", args(&[reg(rd), reg(rs), reg(rt)])),
+                                ("sltiu", args(&[reg(rd), reg(rd), Token::Immediate(1)])),
+                            ],
+                            _ => vec![
+                                ("xor", args(&[reg(rd), reg(rs), reg(rt)])),
+                                ("sltu", args(&[reg(rd), reg(0), reg(rd)])),
+                            ],
+                        }
+                    },
+                    _ => return Err(self.invalid_pseudo_operands(instr)),
                 }
             },
-            _ => Err(AssemblerError::Syntax(
-                "Invalid operands for LA instruction".to_string(),
+            "push" | "pop" => match self.pseudo_operands(instr, operands, 1)?[..] {
+                [Token::Register(rt)] => {
+                    let stack_slot = vec![
+                        reg(*rt),
+                        Token::Comma,
+                        Token::Immediate(0),
+                        Token::LeftParen,
+                        reg(SP),
+                        Token::RightParen,
+                    ];
+                    if instr == "push" {
+                        vec![
+                            ("addi", args(&[reg(SP), reg(SP), Token::Immediate(-4)])),
+                            ("sw", stack_slot),
+                        ]
+                    } else {
+                        vec![
+                            ("lw", stack_slot),
+                            ("addi", args(&[reg(SP), reg(SP), Token::Immediate(4)])),
+                        ]
+                    }
+                },
+                _ => return Err(self.invalid_pseudo_operands(instr)),
+            },
+            "lw" | "sw" | "lb" | "lbu" | "sb" | "lh" | "lhu" | "sh" | "ll" | "sc" | "lwc1"
+            | "swc1"
+                if matches!(operands.get(2), Some(Token::Symbol(_))) =>
+            {
+                self.expand_symbol_access(instr, operands)?
+            },
+            _ => vec![(instr, operands.to_vec())],
+        };
+
+        Ok(expansion)
+    }
+
+    // Expand a load or store of `label`, `label+offset` or `label($base)`
+    // into lui of the high half into $at, an add of the base register, and
+    // the access itself with the sign-extended low half as its offset
+    fn expand_symbol_access<'a>(
+        &self,
+        instr: &'a str,
+        operands: &[Token],
+    ) -> Result<Vec<(&'a str, Vec<Token>)>, AssemblerError> {
+        let symbol = match &operands[2] {
+            Token::Symbol(symbol) => symbol,
+            _ => return Err(self.invalid_pseudo_operands(instr)),
+        };
+        let mut rest = &operands[3..];
+        let mut offset = 0;
+        if let Some(Token::Immediate(value)) = rest.first() {
+            offset = *value;
+            rest = &rest[1..];
+        }
+        let base = match rest {
+            [] => None,
+            [Token::LeftParen, Token::Register(base), Token::RightParen] => Some(*base),
+            _ => return Err(self.invalid_pseudo_operands(instr)),
+        };
+        self.require_at(instr)?;
+
+        let address = self.resolve_symbol(symbol)?.wrapping_add(offset as u32);
+        let low = address as u16 as i16 as i32;
+        // lui takes the high half rounded up when the low half is negative
+        let high = (address.wrapping_add(0x8000) >> 16) as i32;
+
+        let mut expansion = vec![("lui", args(&[reg(AT), Token::Immediate(high)]))];
+        if let Some(base) = base {
+            expansion.push(("add", args(&[reg(AT), reg(AT), reg(base)])));
+        }
+        expansion.push((
+            instr,
+            vec![
+                operands[0].clone(),
+                Token::Comma,
+                Token::Immediate(low),
+                Token::LeftParen,
+                reg(AT),
+                Token::RightParen,
+            ],
+        ));
+        Ok(expansion)
+    }
+
+    // Fail a pseudo-instruction that needs $at after `.set noat`
+    fn require_at(&self, instr: &str) -> Result<(), AssemblerError> {
+        if self.noat {
+            return Err(AssemblerError::Syntax(
+                format!(
+                    "{} instruction needs $at after .set noat",
+                    instr.to_uppercase()
+                ),
                 self.current_line,
-            )),
+            ));
+        }
+        Ok(())
+    }
+
+    // Handle `.set at` and `.set noat`. Other options, such as reorder, do
+    // not change the output and are ignored
+    fn set_option(&mut self, option: Option<&Operand>) {
+        if let Some(Operand::Symbol { name, offset: None }) = option {
+            match name.as_str() {
+                "noat" => self.noat = true,
+                "at" => self.noat = false,
+                _ => {},
+            }
+        }
+    }
+
+    // Operands of a pseudo-instruction without the separating commas
+    fn pseudo_operands<'t>(
+        &self,
+        instr: &str,
+        operands: &'t [Token],
+        count: usize,
+    ) -> Result<Vec<&'t Token>, AssemblerError> {
+        let values: Vec<&Token> = operands.iter().filter(|t| **t != Token::Comma).collect();
+        if values.len() != count {
+            return Err(AssemblerError::Syntax(
+                format!(
+                    "{} instruction requires {} operands",
+                    instr.to_uppercase(),
+                    count
+                ),
+                self.current_line,
+            ));
         }
+        Ok(values)
+    }
+
+    fn invalid_pseudo_operands(&self, instr: &str) -> AssemblerError {
+        AssemblerError::Syntax(
+            format!("Invalid operands for {} instruction", instr.to_uppercase()),
+            self.current_line,
+        )
     }
 
     // Assemble branch unconditional pseudo-instruction (b label)
@@ -2054,7 +2733,7 @@ impl Assembler {
         match &operands[0] {
             Token::Symbol(symbol) => {
                 // Look up symbol value
-                if let Some(&addr) = self.labels.get(symbol) {
+                if let Some(addr) = self.symbol_address(symbol) {
                     // Calculate offset for PC-relative addressing
                     let offset = (addr as i32 - (self.current_address as i32 + 4)) / 4;
 
@@ -2142,7 +2821,6 @@ impl Assembler {
         opcode: u32,
         fmt: u32,
         funct: u32,
-        cond: u32,
         operands: &[Token],
     ) -> Result<u32, AssemblerError> {
         if operands.len() < 3 {
@@ -2155,14 +2833,9 @@ impl Assembler {
         match (&operands[0], &operands[2]) {
             (Token::FpRegister(fs), Token::FpRegister(ft)) => {
                 // opcode (6 bits) | fmt (5 bits) | ft (5 bits) | fs (5 bits) | cc (3 bits) | 0 (2 bits) | funct (6 bits)
+                // The funct field holds the condition: 0x32 eq, 0x3C lt, 0x3E le
                 let cc = 0; // Use condition code 0
-                Ok((opcode << 26)
-                    | (fmt << 21)
-                    | (*ft << 16)
-                    | (*fs << 11)
-                    | (cc << 8)
-                    | (cond << 4)
-                    | funct)
+                Ok((opcode << 26) | (fmt << 21) | (*ft << 16) | (*fs << 11) | (cc << 8) | funct)
             },
             _ => Err(AssemblerError::Syntax(
                 "Invalid operands for FP compare instruction".to_string(),
@@ -2186,8 +2859,8 @@ impl Assembler {
 
         match &operands[0] {
             Token::FpRegister(ft) => {
-                // Parse the address operand
-                let (base, offset) = self.parse_address(&operands[1..])?;
+                // Parse the address operand, which follows the comma
+                let (base, offset) = self.parse_address(&operands[2..])?;
 
                 // opcode (6 bits) | base (5 bits) | ft (5 bits) | offset (16 bits)
                 Ok((opcode << 26) | (base << 21) | (*ft << 16) | (offset as u32 & 0xFFFF))
@@ -2217,7 +2890,7 @@ impl Assembler {
         match &operands[0] {
             Token::Symbol(symbol) => {
                 // Look up symbol value
-                if let Some(&addr) = self.labels.get(symbol) {
+                if let Some(addr) = self.symbol_address(symbol) {
                     // Calculate offset for PC-relative addressing
                     let offset = (addr as i32 - (self.current_address as i32 + 4)) / 4;
 
@@ -2244,3 +2917,227 @@ impl Assembler {
         }
     }
 }
+
+// Assembler temporary and stack pointer used by pseudo-instruction expansions
+const AT: u32 = 1;
+const SP: u32 = 29;
+
+fn reg(number: u32) -> Token {
+    Token::Register(number)
+}
+
+// Words of an instruction's expansion that hold a symbol's address, by
+// their index in the expansion. Branches are PC-relative and need none.
+fn symbol_relocations<'a>(
+    instr: &str,
+    operands: &'a [Token],
+    expansion_len: usize,
+) -> Vec<(usize, RelocationKind, &'a str, i32)> {
+    let symbol = operands.iter().find_map(|token| match token {
+        Token::Symbol(symbol) => Some(symbol.as_str()),
+        _ => None,
+    });
+    let symbol = match symbol {
+        Some(symbol) => symbol,
+        None => return Vec::new(),
+    };
+
+    match instr {
+        "j" | "jal" => vec![(0, RelocationKind::Jump26, symbol, 0)],
+        "la" => vec![
+            (0, RelocationKind::High16, symbol, 0),
+            (1, RelocationKind::Low16, symbol, 0),
+        ],
+        "lw" | "sw" | "lb" | "lbu" | "sb" | "lh" | "lhu" | "sh" | "ll" | "sc" | "lwc1" | "swc1"
+            if matches!(operands.get(2), Some(Token::Symbol(_))) =>
+        {
+            let addend = match operands.get(3) {
+                Some(Token::Immediate(value)) => *value,
+                _ => 0,
+            };
+            vec![
+                (0, RelocationKind::High16Adjusted, symbol, addend),
+                (expansion_len - 1, RelocationKind::Low16, symbol, addend),
+            ]
+        },
+        _ => Vec::new(),
+    }
+}
+
+// Comma-separated operand list, as the tokenizer produces it
+fn args(operands: &[Token]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (i, operand) in operands.iter().enumerate() {
+        if i > 0 {
+            tokens.push(Token::Comma);
+        }
+        tokens.push(operand.clone());
+    }
+    tokens
+}
+
+// Instructions that load a 32-bit constant: one addi or ori when the value
+// fits in 16 bits, otherwise lui followed by ori
+fn load_immediate(rt: u32, imm: i32) -> Vec<(&'static str, Vec<Token>)> {
+    if (-32768..=32767).contains(&imm) {
+        vec![("addi", args(&[reg(rt), reg(0), Token::Immediate(imm)]))]
+    } else if (0..=0xFFFF).contains(&imm) {
+        vec![("ori", args(&[reg(rt), reg(0), Token::Immediate(imm)]))]
+    } else {
+        let upper = (imm as u32 >> 16) as i32;
+        let lower = (imm as u32 & 0xFFFF) as i32;
+        vec![
+            ("lui", args(&[reg(rt), Token::Immediate(upper)])),
+            ("ori", args(&[reg(rt), reg(rt), Token::Immediate(lower)])),
+        ]
+    }
+}
+
+// Position of the colon ending a label at the start of a line. Text with
+// spaces or quotes before the colon is not a label, as in `.word 0:4`.
+fn label_end(line: &str) -> Option<usize> {
+    let end = line.find(':')?;
+    let label = line[..end].trim();
+    if label.contains(char::is_whitespace) || label.contains('"') || label.contains('\'') {
+        None
+    } else {
+        Some(end)
+    }
+}
+
+// Expand `value:count` in a data directive into count copies of the value,
+// the way MARS and SPIM repeat values
+fn expand_repeated_values(line: &str) -> String {
+    let (label, rest) = match label_end(line) {
+        Some(end) => line.split_at(end + 1),
+        None => ("", line),
+    };
+    let rest = rest.trim_start();
+    let directive = rest.split_whitespace().next().unwrap_or("");
+    if ![".word", ".half", ".byte", ".float", ".double"].contains(&directive) {
+        return line.to_string();
+    }
+    let operands = rest[directive.len()..].trim();
+    match operands.split_once(':') {
+        Some((value, count)) if !value.contains(',') => match count.trim().parse::<usize>() {
+            Ok(count) if count > 0 => format!(
+                "{} {} {}",
+                label,
+                directive,
+                vec![value.trim(); count].join(", ")
+            )
+            .trim()
+            .to_string(),
+            _ => line.to_string(),
+        },
+        _ => line.to_string(),
+    }
+}
+
+// An operand naming a label, as `label` or `label+4`. Labels that share a
+// name with an instruction tokenize as that instruction.
+fn symbol_operand(tokens: &[Token]) -> Option<Operand> {
+    let (name, offset) = match tokens {
+        [Token::Symbol(name) | Token::Instruction(name)] => (name, None),
+        [Token::Symbol(name) | Token::Instruction(name), Token::Immediate(offset)] => {
+            (name, Some(*offset as i64))
+        },
+        [Token::Symbol(name) | Token::Instruction(name), Token::LongImmediate(offset)] => {
+            (name, Some(*offset))
+        },
+        _ => return None,
+    };
+    Some(Operand::Symbol {
+        name: name.clone(),
+        offset,
+    })
+}
+
+// The token stream of an operand list, as the tokenizer produces it
+fn lower(operands: &[Operand]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (i, operand) in operands.iter().enumerate() {
+        if i > 0 {
+            tokens.push(Token::Comma);
+        }
+        lower_operand(operand, &mut tokens);
+    }
+    tokens
+}
+
+fn lower_operand(operand: &Operand, tokens: &mut Vec<Token>) {
+    match operand {
+        Operand::Register(number) => tokens.push(Token::Register(*number)),
+        Operand::FpRegister(number) => tokens.push(Token::FpRegister(*number)),
+        Operand::Integer(value) => tokens.push(number_token(*value)),
+        Operand::Float(value) => tokens.push(Token::Float(*value)),
+        Operand::String(text) => tokens.push(Token::StringLiteral(text.clone())),
+        Operand::Symbol { name, offset } => {
+            tokens.push(Token::Symbol(name.clone()));
+            if let Some(offset) = offset {
+                tokens.push(number_token(*offset));
+            }
+        },
+        Operand::Memory { offset, base } => {
+            if let Some(offset) = offset {
+                lower_operand(offset, tokens);
+            }
+            tokens.extend([Token::LeftParen, Token::Register(*base), Token::RightParen]);
+        },
+        Operand::Directive(name) => tokens.push(Token::Directive(name.clone())),
+    }
+}
+
+// Integer literals that fit in 32 bits become ordinary immediates
+fn number_token(value: i64) -> Token {
+    if value >= i32::MIN as i64 && value <= i32::MAX as i64 {
+        Token::Immediate(value as i32)
+    } else {
+        Token::LongImmediate(value)
+    }
+}
+
+// Append the fraction and exponent of a decimal float to `number`, if they
+// follow. Returns whether the literal is a float.
+fn read_float_suffix(chars: &mut Peekable<Chars>, number: &mut String) -> bool {
+    let mut is_float = false;
+
+    let mut ahead = chars.clone();
+    if ahead.next() == Some('.') && ahead.peek().map_or(false, |c| c.is_ascii_digit()) {
+        is_float = true;
+        number.push('.');
+        chars.next();
+        while let Some(&c) = chars.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            number.push(c);
+            chars.next();
+        }
+    }
+
+    let mut ahead = chars.clone();
+    if matches!(ahead.next(), Some('e') | Some('E')) {
+        if matches!(ahead.peek(), Some('+') | Some('-')) {
+            ahead.next();
+        }
+        if ahead.peek().map_or(false, |c| c.is_ascii_digit()) {
+            is_float = true;
+            number.push('e');
+            chars.next();
+            if let Some(&sign @ ('+' | '-')) = chars.peek() {
+                number.push(sign);
+                chars.next();
+            }
+            while let Some(&c) = chars.peek() {
+                if !c.is_ascii_digit() {
+                    break;
+                }
+                number.push(c);
+                chars.next();
+            }
+        }
+    }
+
+    is_float
+}
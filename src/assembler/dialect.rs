@@ -0,0 +1,149 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// dialect.rs
+//
+// This file contains the assembly dialects the assembler can be switched
+// to. Course material is often written for the MARS or QtSPIM simulators,
+// whose assemblers spell some directives differently, repeat values with
+// `value:count` and accept their own syscall numbers. A dialect accepts
+// those spellings and reports what would behave differently here; the
+// strict dialect keeps a program to what both MARS and SPIM accept.
+
+use std::fmt;
+
+/// Source dialect the assembler accepts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dialect {
+    /// This assembler's own syntax, which ignores unknown directives
+    Native,
+    /// The MARS simulator's assembler
+    Mars,
+    /// The SPIM and QtSPIM assembler
+    Spim,
+    /// Only what both MARS and SPIM accept
+    Strict,
+}
+
+/// What a dialect does with a directive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DirectiveUse {
+    /// Assembled as usual
+    Assembled,
+    /// Assembled, with a warning that the dialect's own assembler would
+    /// reject it
+    Extension,
+    /// Accepted and skipped, since it only matters to a linker
+    Ignored,
+    /// Rejected with an error
+    Rejected,
+}
+
+// Directives MARS and SPIM both assemble the way this assembler does
+const PORTABLE_DIRECTIVES: &[&str] = &[
+    ".text", ".data", ".ktext", ".kdata", ".word", ".half", ".byte", ".float", ".double", ".ascii",
+    ".asciiz", ".space", ".align", ".set",
+];
+
+// Directives only this assembler knows
+const NATIVE_DIRECTIVES: &[&str] = &[".rodata", ".bss", ".dword"];
+
+impl Dialect {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "native" => Some(Self::Native),
+            "mars" => Some(Self::Mars),
+            "spim" => Some(Self::Spim),
+            "strict" => Some(Self::Strict),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Native => "native",
+            Self::Mars => "mars",
+            Self::Spim => "spim",
+            Self::Strict => "strict",
+        }
+    }
+
+    /// Whether data directives repeat a value with `value:count`
+    pub fn repeats_values(self) -> bool {
+        self != Self::Native
+    }
+
+    /// Whether `.eqv NAME TEXT` defines a name replaced by TEXT
+    pub fn has_equivalences(self) -> bool {
+        self == Self::Mars
+    }
+
+    /// Whether defining a label twice, or naming a label after an
+    /// instruction, is an error rather than the last definition winning
+    pub fn checks_labels(self) -> bool {
+        self != Self::Native
+    }
+
+    pub(crate) fn directive(self, directive: &str) -> DirectiveUse {
+        let portable = PORTABLE_DIRECTIVES.contains(&directive);
+        let native = NATIVE_DIRECTIVES.contains(&directive);
+        match self {
+            Self::Native if portable || native => DirectiveUse::Assembled,
+            Self::Native => DirectiveUse::Ignored,
+            _ if portable => DirectiveUse::Assembled,
+            _ if directive == ".globl" || directive == ".extern" => DirectiveUse::Ignored,
+            Self::Mars if directive == ".global" => DirectiveUse::Ignored,
+            Self::Mars | Self::Spim if native => DirectiveUse::Extension,
+            _ => DirectiveUse::Rejected,
+        }
+    }
+
+    /// How a syscall would behave differently here than in the dialect's
+    /// simulator, or `None` if it behaves the same
+    pub fn syscall_incompatibility(self, number: u32) -> Option<String> {
+        let problem = match (self, number) {
+            (Self::Native, _) | (_, 1..=17) => return None,
+            (Self::Mars, 32) | (Self::Mars, 34..=36) => return None,
+            (Self::Mars, 30) => "returns the time in $v0; MARS returns it in $a0 and $a1",
+            (Self::Mars, 31) => "sleeps for $a0 milliseconds; MARS plays a MIDI note",
+            (Self::Mars, 33) => "is unimplemented; MARS plays a MIDI note",
+            (Self::Mars, 40..=44) => "is unimplemented; MARS generates random numbers",
+            (Self::Mars, 50..=59) => "is unimplemented; MARS shows a dialog",
+            (Self::Mars, _) => "is not a MARS syscall",
+            (Self::Spim, _) => "is not a SPIM syscall",
+            (Self::Strict, 30..=36) => "is a MARS extension that SPIM lacks",
+            (Self::Strict, _) => "is neither a MARS nor a SPIM syscall",
+        };
+        Some(format!("syscall {} {}", number, problem))
+    }
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self::Native
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
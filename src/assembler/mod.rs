@@ -22,7 +22,26 @@
 // mod.rs
 //
 // This file is the module declaration for the assembler module.
-// It re-exports the Assembler struct from the assembler.rs file.
+// It re-exports the Assembler struct from the assembler.rs file, the
+// Program and section types it produces, the source dialects it accepts,
+// the syntax tree it parses source into, the builder for writing programs
+// in Rust, the static hazard analysis and the optimization pass.
 
+mod analysis;
+mod ast;
+mod builder;
 mod core;
+mod dialect;
+mod optimizer;
+mod program;
+mod sections;
+pub use analysis::{
+    BasicBlock, DelaySlot, Dependency, DependencyKind, HazardAnalysis, LoadUseHazard,
+};
+pub use ast::{Operand, Statement, StatementKind};
+pub use builder::{BuildError, ProgramBuilder};
 pub use core::{Assembler, AssemblerError};
+pub use dialect::Dialect;
+pub use optimizer::{Change, ChangeKind, OptimizationReport, Optimizer, ScheduleSummary};
+pub use program::{Program, Relocation, RelocationKind};
+pub use sections::{AssembledSection, SectionKind};
@@ -0,0 +1,453 @@
+// optimizer.rs
+//
+// This file contains the optional optimization pass over an assembled
+// program. Working one basic block at a time, it removes moves that have no
+// effect, moves an independent instruction between a load and the use of
+// its result, and, for pipelines with branch delay slots, moves an
+// instruction of the block after its final branch or pads the slot with a
+// `nop`. Instructions never leave their block, so after the blocks are laid
+// out again only branch offsets, jump targets, symbols and relocated
+// addresses need rewriting.
+
+use super::analysis::{
+    basic_blocks, conflicts, delay_slot_candidate, ends_block, movable, reads, Located,
+};
+use super::program::{Program, RelocationKind};
+use crate::functional_simulator::instructions::Instruction;
+use crate::functional_simulator::simulator::instruction_to_string;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+/// Which optimizations to apply
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Optimizer {
+    pub remove_moves: bool,
+    pub schedule_loads: bool,
+    /// Lay the program out for a pipeline that executes the instruction
+    /// after every branch and jump
+    pub fill_delay_slots: bool,
+}
+
+/// What the optimizer changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// A move that copies a register to itself, copies it back, or whose
+    /// result is overwritten before it is read
+    RemovedMove,
+    /// An independent instruction placed between the load at this address
+    /// and the use of its result
+    ScheduledLoad { load: u32 },
+    /// An instruction moved after the branch or jump at this address
+    FilledDelaySlot { branch: u32 },
+    /// A `nop` added after the branch or jump at this address
+    InsertedNop { branch: u32 },
+}
+
+/// One change, at the original address of the instruction it affects
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub address: u32,
+    pub instruction: String,
+    pub kind: ChangeKind,
+}
+
+/// Static counts of a program before or after optimization
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScheduleSummary {
+    pub instructions: usize,
+    pub load_use_hazards: usize,
+    /// Branches and jumps whose delay slot holds a `nop`
+    pub nop_slots: usize,
+}
+
+/// The changes made by the optimizer and the program before and after
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OptimizationReport {
+    pub changes: Vec<Change>,
+    pub before: ScheduleSummary,
+    pub after: ScheduleSummary,
+}
+
+impl OptimizationReport {
+    pub fn render(&self) -> String {
+        let mut result = String::from("Optimization report:\n");
+        for change in &self.changes {
+            let description = match change.kind {
+                ChangeKind::RemovedMove => "removed redundant move".to_string(),
+                ChangeKind::ScheduledLoad { load } => {
+                    format!("moved after the load at 0x{:08X}", load)
+                },
+                ChangeKind::FilledDelaySlot { branch } => {
+                    format!("moved into the delay slot of 0x{:08X}", branch)
+                },
+                ChangeKind::InsertedNop { branch } => {
+                    format!("no instruction fits the delay slot of 0x{:08X}", branch)
+                },
+            };
+            let _ = writeln!(
+                result,
+                "  0x{:08X}  {:<28} {}",
+                change.address, change.instruction, description
+            );
+        }
+        if self.changes.is_empty() {
+            result.push_str("  No changes\n");
+        }
+        let summary = |name: &str, summary: &ScheduleSummary| {
+            format!(
+                "{}: {} instructions, {} load-use hazards, {} nop delay slots\n",
+                name, summary.instructions, summary.load_use_hazards, summary.nop_slots
+            )
+        };
+        result.push_str(&summary("Before", &self.before));
+        result.push_str(&summary("After", &self.after));
+        result
+    }
+}
+
+impl Optimizer {
+    /// Move removal and load scheduling, for pipelines without delay slots
+    pub fn new() -> Self {
+        Self {
+            remove_moves: true,
+            schedule_loads: true,
+            fill_delay_slots: false,
+        }
+    }
+
+    pub fn with_delay_slots(mut self, enabled: bool) -> Self {
+        self.fill_delay_slots = enabled;
+        self
+    }
+
+    /// Optimize `program`, returning the new program and what changed
+    pub fn optimize(&self, program: &Program) -> (Program, OptimizationReport) {
+        let mut changes = Vec::new();
+        let mut blocks = basic_blocks(&program.text, program.text_address, &program.symbols);
+        let starts: Vec<u32> = blocks.iter().map(|block| block[0].0).collect();
+        // The immediate of a relocated `ori` or `addi` is an address, not zero
+        let relocated: BTreeSet<u32> = program
+            .relocations
+            .iter()
+            .map(|relocation| relocation.address)
+            .collect();
+        for block in blocks.iter_mut() {
+            if self.remove_moves {
+                remove_moves(block, &relocated, &mut changes);
+            }
+            if self.schedule_loads {
+                schedule_loads(block, &mut changes);
+            }
+            if self.fill_delay_slots {
+                fill_delay_slot(block, &mut changes);
+            }
+        }
+
+        let optimized = relayout(program, &starts, &blocks);
+        let report = OptimizationReport {
+            changes,
+            before: summarize(program, false),
+            after: summarize(&optimized, self.fill_delay_slots),
+        };
+        (optimized, report)
+    }
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn summarize(program: &Program, delay_slots: bool) -> ScheduleSummary {
+    let analysis = program.analyze();
+    let nop_slots = if delay_slots {
+        analysis
+            .delay_slots()
+            .filter(|slot| {
+                let index = (slot.branch.wrapping_sub(program.text_address) / 4 + 1) as usize;
+                program.text.get(index) == Some(&0)
+            })
+            .count()
+    } else {
+        0
+    };
+    ScheduleSummary {
+        instructions: program.text.len(),
+        load_use_hazards: analysis.load_use_hazards().count(),
+        nop_slots,
+    }
+}
+
+fn describe((_, word, instruction): &Located) -> String {
+    instruction_to_string(instruction, *word)
+}
+
+// Destination and source of a register copy: `add`, `addu` or `or` with
+// $zero, or an add or or of zero
+fn as_move((_, _, instruction): &Located) -> Option<(u32, u32)> {
+    match *instruction {
+        Instruction::Add { rd, rs, rt }
+        | Instruction::Addu { rd, rs, rt }
+        | Instruction::Or { rd, rs, rt }
+            if rt == 0 =>
+        {
+            Some((rd, rs))
+        },
+        Instruction::Add { rd, rs, rt }
+        | Instruction::Addu { rd, rs, rt }
+        | Instruction::Or { rd, rs, rt }
+            if rs == 0 =>
+        {
+            Some((rd, rt))
+        },
+        Instruction::Addi { rt, rs, imm: 0 } | Instruction::Addiu { rt, rs, imm: 0 } => {
+            Some((rt, rs))
+        },
+        Instruction::Ori { rt, rs, imm: 0 } => Some((rt, rs)),
+        _ => None,
+    }
+}
+
+// Whether an instruction reads and whether it writes `register`
+fn touches((_, _, instruction): &Located, register: u32) -> (bool, bool) {
+    (
+        reads(instruction).contains(&register),
+        instruction.get_destination_register() == Some(register),
+    )
+}
+
+fn remove_moves(block: &mut Vec<Located>, relocated: &BTreeSet<u32>, changes: &mut Vec<Change>) {
+    let mut index = 0;
+    while index < block.len() {
+        let redundant = match as_move(&block[index]) {
+            Some((destination, source)) if !relocated.contains(&block[index].0) => {
+                destination == 0
+                    || destination == source
+                    || overwritten_before_read(&block[index + 1..], destination)
+                    || copies_back(&block[..index], destination, source)
+            },
+            _ => false,
+        };
+        if redundant {
+            let removed = block.remove(index);
+            changes.push(Change {
+                address: removed.0,
+                instruction: describe(&removed),
+                kind: ChangeKind::RemovedMove,
+            });
+        } else {
+            index += 1;
+        }
+    }
+}
+
+// Whether the block writes `register` again before anything reads it
+fn overwritten_before_read(rest: &[Located], register: u32) -> bool {
+    for located in rest {
+        if !movable(located) && located.2 != Instruction::Nop {
+            return false;
+        }
+        let (read, written) = touches(located, register);
+        if read {
+            return false;
+        }
+        if written {
+            return true;
+        }
+    }
+    false
+}
+
+// Whether an earlier move copied `destination` into `source`, with neither
+// register changed since
+fn copies_back(earlier: &[Located], destination: u32, source: u32) -> bool {
+    for located in earlier.iter().rev() {
+        if as_move(located) == Some((source, destination)) {
+            return true;
+        }
+        if !movable(located) && located.2 != Instruction::Nop {
+            return false;
+        }
+        if touches(located, destination).1 || touches(located, source).1 {
+            return false;
+        }
+    }
+    false
+}
+
+fn schedule_loads(block: &mut Vec<Located>, changes: &mut Vec<Change>) {
+    // The branch ending the block stays last
+    let end = if block.last().map_or(false, |last| ends_block(&last.2)) {
+        block.len() - 1
+    } else {
+        block.len()
+    };
+    for index in 0..end.saturating_sub(2) {
+        let load = &block[index];
+        let loaded = match load.2.get_destination_register() {
+            Some(register) if load.2.is_load() => register,
+            _ => continue,
+        };
+        if !reads(&block[index + 1].2).contains(&loaded) {
+            continue;
+        }
+
+        let next = &block[index + 1];
+        let chosen = (index + 2..end).find(|&candidate| {
+            let moved = &block[candidate];
+            // The moved instruction must not wait on the load itself, or
+            // become a load the next instruction waits on
+            let waits = reads(&moved.2).contains(&loaded);
+            let stalls_next = moved.2.is_load()
+                && moved
+                    .2
+                    .get_destination_register()
+                    .map_or(false, |register| reads(&next.2).contains(&register));
+            // Nor may its removal leave a load next to a use of its result
+            let leaves_hazard = candidate + 1 < block.len()
+                && block[candidate - 1].2.is_load()
+                && block[candidate - 1]
+                    .2
+                    .get_destination_register()
+                    .map_or(false, |register| {
+                        reads(&block[candidate + 1].2).contains(&register)
+                    });
+            movable(moved)
+                && !waits
+                && !stalls_next
+                && !leaves_hazard
+                && block[index + 1..candidate]
+                    .iter()
+                    .all(|between| !conflicts(between, moved))
+        });
+        if let Some(candidate) = chosen {
+            let moved = block.remove(candidate);
+            changes.push(Change {
+                address: moved.0,
+                instruction: describe(&moved),
+                kind: ChangeKind::ScheduledLoad {
+                    load: block[index].0,
+                },
+            });
+            block.insert(index + 1, moved);
+        }
+    }
+}
+
+// Move an instruction after the branch ending the block, or add a nop there.
+// The slot keeps the address of the branch's old successor in the list of
+// locations, so it is laid out inside this block.
+fn fill_delay_slot(block: &mut Vec<Located>, changes: &mut Vec<Change>) {
+    let branch = match block.last() {
+        Some(last) if ends_block(&last.2) => last.0,
+        _ => return,
+    };
+    match delay_slot_candidate(block) {
+        Some(index) => {
+            let moved = block.remove(index);
+            changes.push(Change {
+                address: moved.0,
+                instruction: describe(&moved),
+                kind: ChangeKind::FilledDelaySlot { branch },
+            });
+            block.push(moved);
+        },
+        None => {
+            changes.push(Change {
+                address: branch,
+                instruction: "nop".to_string(),
+                kind: ChangeKind::InsertedNop { branch },
+            });
+            block.push((branch, 0, Instruction::Nop));
+        },
+    }
+}
+
+// Lay the blocks, which started at `starts` before optimization, out from
+// the text address and rewrite everything that holds a text address
+fn relayout(program: &Program, starts: &[u32], blocks: &[Vec<Located>]) -> Program {
+    let base = program.text_address;
+    let old_end = base.wrapping_add(program.text.len() as u32 * 4);
+
+    // New address of each block start and of each surviving instruction
+    let mut block_starts = BTreeMap::new();
+    let mut moved = BTreeMap::new();
+    let mut placed: Vec<(u32, &Located)> = Vec::new();
+    for (&old_start, block) in starts.iter().zip(blocks) {
+        block_starts.insert(old_start, base.wrapping_add(placed.len() as u32 * 4));
+        for located in block {
+            let new_address = base.wrapping_add(placed.len() as u32 * 4);
+            // An added nop shares the address of the branch before it
+            moved.entry(located.0).or_insert(new_address);
+            placed.push((new_address, located));
+        }
+    }
+    let new_end = base.wrapping_add(placed.len() as u32 * 4);
+    block_starts.insert(old_end, new_end);
+    let remap = |address: u32| block_starts.get(&address).copied().unwrap_or(address);
+
+    // Branch offsets and jump targets follow their target blocks
+    let mut text: Vec<u32> = placed
+        .iter()
+        .map(|&(new_address, (old_address, word, instruction))| {
+            let target = match instruction.calculate_branch_target(*old_address) {
+                Some(target) => remap(target),
+                None => return *word,
+            };
+            match instruction {
+                Instruction::J { .. } | Instruction::Jal { .. } => {
+                    (word & 0xFC00_0000) | ((target >> 2) & 0x03FF_FFFF)
+                },
+                _ => {
+                    let offset = (target.wrapping_sub(new_address.wrapping_add(4)) as i32) >> 2;
+                    (word & 0xFFFF_0000) | (offset as u32 & 0xFFFF)
+                },
+            }
+        })
+        .collect();
+
+    let mut optimized = program.clone();
+    for value in optimized.symbols.values_mut() {
+        *value = remap(*value);
+    }
+    optimized.entry = remap(program.entry);
+
+    // Addresses written into instructions and data
+    let data_end = program.data_address as usize + program.data.len();
+    for relocation in optimized.relocations.iter_mut() {
+        if relocation.address >= base && relocation.address < old_end {
+            relocation.address = moved
+                .get(&relocation.address)
+                .copied()
+                .unwrap_or(relocation.address);
+        }
+        let value = match optimized.symbols.get(&relocation.symbol) {
+            Some(&value) => value.wrapping_add(relocation.addend as u32),
+            None => continue,
+        };
+        let address = relocation.address;
+        if address >= base && address < new_end {
+            let word = &mut text[(address.wrapping_sub(base) / 4) as usize];
+            *word = patch(*word, relocation.kind, value);
+        } else if address >= program.data_address && (address as usize) + 4 <= data_end {
+            let offset = (address - program.data_address) as usize;
+            let bytes = &mut optimized.data[offset..offset + 4];
+            let word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            bytes.copy_from_slice(&patch(word, relocation.kind, value).to_le_bytes());
+        }
+    }
+    optimized.text = text;
+    optimized
+}
+
+// Write `value` into the field of `word` a relocation of `kind` covers
+fn patch(word: u32, kind: RelocationKind, value: u32) -> u32 {
+    match kind {
+        RelocationKind::Word => value,
+        RelocationKind::Jump26 => (word & 0xFC00_0000) | ((value >> 2) & 0x03FF_FFFF),
+        RelocationKind::High16 => (word & 0xFFFF_0000) | (value >> 16),
+        RelocationKind::High16Adjusted => (word & 0xFFFF_0000) | (value.wrapping_add(0x8000) >> 16),
+        RelocationKind::Low16 => (word & 0xFFFF_0000) | (value & 0xFFFF),
+    }
+}
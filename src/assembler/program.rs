@@ -0,0 +1,152 @@
+// program.rs
+//
+// This file contains the Program type, the structured result of assembling
+// the text and data sections of a source file. It keeps the instruction
+// words, the data bytes, the symbol table and the places where addresses
+// were written into the program, and converts to and from the flat binary
+// format with its 8-byte size header.
+
+use super::analysis::HazardAnalysis;
+use super::sections::SectionKind;
+use crate::functional_simulator::simulator::{decode_instruction, instruction_to_string};
+use crate::loader::{DATA_BASE, TEXT_BASE};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Flat output starts with the data and text section sizes
+const HEADER_SIZE: usize = 8;
+
+/// How an address is written into the word at a relocation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RelocationKind {
+    /// The whole word is the address, as in `.word label`
+    Word,
+    /// Bits 2-27 of the address in a `j` or `jal` target field
+    Jump26,
+    /// Upper half of the address in a `lui`, paired with a zero-extended
+    /// `ori` as in `la`
+    High16,
+    /// Upper half of the address in a `lui`, rounded up when the lower half
+    /// is negative because the paired load or store sign-extends it
+    High16Adjusted,
+    /// Lower half of the address in the immediate field
+    Low16,
+}
+
+/// A word that holds the address of a symbol, so the program can be moved
+/// by rewriting it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Relocation {
+    /// Address of the word
+    pub address: u32,
+    pub section: SectionKind,
+    pub kind: RelocationKind,
+    pub symbol: String,
+    /// Offset added to the symbol, as in `lw $t0, table+8`
+    pub addend: i32,
+}
+
+/// An assembled program
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Program {
+    /// Instruction words of the text section
+    pub text: Vec<u32>,
+    pub text_address: u32,
+    /// Contents of the data section
+    pub data: Vec<u8>,
+    pub data_address: u32,
+    /// Address of every label
+    pub symbols: BTreeMap<String, u32>,
+    /// Address execution starts at: the `__start` label if there is one,
+    /// otherwise the start of the text section
+    pub entry: u32,
+    pub relocations: Vec<Relocation>,
+}
+
+impl Program {
+    /// The flat binary format: data and text section sizes, then the data
+    /// and text sections. Symbols, relocations and section addresses are not
+    /// kept.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.data.len() + self.text.len() * 4);
+        bytes.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.text.len() as u32 * 4).to_le_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes.extend_from_slice(&self.text_bytes());
+        bytes
+    }
+
+    /// Read the flat binary format, placing the sections at `DATA_BASE` and
+    /// `TEXT_BASE`. Returns `None` if the header sizes do not match the
+    /// length of `bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_SIZE {
+            return None;
+        }
+        let data_size = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        let text_size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        if text_size % 4 != 0 || data_size.checked_add(text_size)? != bytes.len() - HEADER_SIZE {
+            return None;
+        }
+
+        let text_start = HEADER_SIZE + data_size;
+        Some(Self {
+            text: bytes[text_start..]
+                .chunks(4)
+                .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+                .collect(),
+            text_address: TEXT_BASE,
+            data: bytes[HEADER_SIZE..text_start].to_vec(),
+            data_address: DATA_BASE,
+            symbols: BTreeMap::new(),
+            entry: TEXT_BASE,
+            relocations: Vec::new(),
+        })
+    }
+
+    /// The program, with its symbols and relocations, as JSON
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// The text section as little-endian bytes, as it is laid out in memory
+    pub fn text_bytes(&self) -> Vec<u8> {
+        self.text
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    /// Address and disassembly of every instruction, with the labels
+    /// defined at each address
+    pub fn disassemble(&self) -> Vec<(u32, String)> {
+        let mut labels: BTreeMap<u32, Vec<&str>> = BTreeMap::new();
+        for (name, &address) in &self.symbols {
+            labels.entry(address).or_default().push(name);
+        }
+
+        self.text
+            .iter()
+            .enumerate()
+            .map(|(index, &word)| {
+                let address = self.text_address.wrapping_add(index as u32 * 4);
+                let text = instruction_to_string(&decode_instruction(word), word);
+                let line = match labels.get(&address) {
+                    Some(names) => format!("{}: {}", names.join(": "), text),
+                    None => text,
+                };
+                (address, line)
+            })
+            .collect()
+    }
+
+    /// Basic blocks of the text section with their register dependencies,
+    /// load-use hazards and delay slot candidates
+    pub fn analyze(&self) -> HazardAnalysis {
+        HazardAnalysis::new(&self.text, self.text_address, &self.symbols)
+    }
+}
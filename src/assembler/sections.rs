@@ -0,0 +1,93 @@
+// sections.rs
+//
+// This file contains the sections of an assembly program. Each section has
+// its own location counter and contents, so code and data can be
+// interleaved in the source.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A section that assembly source can switch to with a directive
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SectionKind {
+    Text,
+    Data,
+    Rodata,
+    Bss,
+    Ktext,
+    Kdata,
+}
+
+impl SectionKind {
+    /// Every section, in output order
+    pub const ALL: [SectionKind; 6] = [
+        SectionKind::Text,
+        SectionKind::Data,
+        SectionKind::Rodata,
+        SectionKind::Bss,
+        SectionKind::Ktext,
+        SectionKind::Kdata,
+    ];
+
+    /// The section a directive such as `.data` switches to
+    pub fn from_directive(directive: &str) -> Option<Self> {
+        match directive {
+            ".text" => Some(SectionKind::Text),
+            ".data" => Some(SectionKind::Data),
+            ".rodata" => Some(SectionKind::Rodata),
+            ".bss" => Some(SectionKind::Bss),
+            ".ktext" => Some(SectionKind::Ktext),
+            ".kdata" => Some(SectionKind::Kdata),
+            _ => None,
+        }
+    }
+
+    /// Section name, as written in the directive
+    pub fn name(self) -> &'static str {
+        match self {
+            SectionKind::Text => ".text",
+            SectionKind::Data => ".data",
+            SectionKind::Rodata => ".rodata",
+            SectionKind::Bss => ".bss",
+            SectionKind::Ktext => ".ktext",
+            SectionKind::Kdata => ".kdata",
+        }
+    }
+
+    /// Whether the section holds instructions rather than data
+    pub fn is_text(self) -> bool {
+        matches!(self, SectionKind::Text | SectionKind::Ktext)
+    }
+
+    pub(super) fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for SectionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The assembled contents of one section
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssembledSection {
+    pub kind: SectionKind,
+    /// Address given with the section directive, such as `.data 0x10010000`,
+    /// or the default address for the section
+    pub address: u32,
+    pub bytes: Vec<u8>,
+}
+
+// Location counter and contents of a section during assembly
+#[derive(Clone, Debug, Default)]
+pub(super) struct SectionState {
+    // Address of the start of the section. During the first pass this is 0
+    // unless a directive set it, since other sections' sizes are not known.
+    pub base: u32,
+    pub explicit_address: bool,
+    // Address of the next byte, saved while another section is current
+    pub location: u32,
+    pub bytes: Vec<u8>,
+}
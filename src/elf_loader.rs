@@ -23,6 +23,8 @@
 //! This module provides functionality to load ELF (Executable and Linkable Format)
 //! binaries for MIPS architecture into the simulator's memory.
 
+use crate::functional_simulator::memory::MemError;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{self, Read};
 use std::path::Path;
@@ -61,10 +63,67 @@ pub struct ProgramHeader {
     pub p_align: u32,  // Segment alignment
 }
 
+/// Section header structure
+#[repr(C)]
+#[derive(Debug)]
+pub struct SectionHeader {
+    pub sh_name: u32,      // Offset of the name in the section name table
+    pub sh_type: u32,      // Section type
+    pub sh_flags: u32,     // Section flags
+    pub sh_addr: u32,      // Section virtual address
+    pub sh_offset: u32,    // Section file offset
+    pub sh_size: u32,      // Section size in bytes
+    pub sh_link: u32,      // Link to another section
+    pub sh_info: u32,      // Additional information
+    pub sh_addralign: u32, // Section alignment
+    pub sh_entsize: u32,   // Entry size if the section holds a table
+}
+
 /// ELF constants
 pub const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
 pub const EM_MIPS: u16 = 8; // MIPS architecture
 pub const PT_LOAD: u32 = 1; // Loadable segment
+pub const SHT_SYMTAB: u32 = 2; // Symbol table
+pub const SHT_NOBITS: u32 = 8; // Section occupies no file space (.bss)
+pub const SHF_ALLOC: u32 = 0x2; // Section occupies memory at run time
+pub const STT_FUNC: u8 = 2; // Symbol names a function
+pub const SHN_UNDEF: u16 = 0; // Symbol is not defined in this file
+const SYMBOL_SIZE: usize = 16; // Size of a symbol table entry
+
+/// A named section of an ELF file that occupies memory at run time
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    pub address: u32,
+    pub size: u32,
+    /// Whether the section has no file contents and starts zeroed, like .bss
+    pub zeroed: bool,
+}
+
+impl Section {
+    /// First address past the end of the section
+    pub fn end(&self) -> u32 {
+        self.address.wrapping_add(self.size)
+    }
+
+    pub fn contains(&self, address: u32) -> bool {
+        address >= self.address && address < self.end()
+    }
+}
+
+impl std::fmt::Display for Section {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:<12} 0x{:08X}-0x{:08X} {:>8} bytes{}",
+            self.name,
+            self.address,
+            self.end(),
+            self.size,
+            if self.zeroed { ", zeroed" } else { "" }
+        )
+    }
+}
 
 /// Errors that can occur during ELF loading
 #[derive(Debug)]
@@ -73,7 +132,7 @@ pub enum ElfError {
     InvalidMagic,
     UnsupportedArchitecture,
     InvalidFormat,
-    MemoryError,
+    MemoryError(MemError),
 }
 
 impl std::fmt::Display for ElfError {
@@ -83,7 +142,7 @@ impl std::fmt::Display for ElfError {
             ElfError::InvalidMagic => write!(f, "Invalid ELF magic number"),
             ElfError::UnsupportedArchitecture => write!(f, "Unsupported architecture (not MIPS)"),
             ElfError::InvalidFormat => write!(f, "Invalid ELF format"),
-            ElfError::MemoryError => write!(f, "Memory error during loading"),
+            ElfError::MemoryError(e) => write!(f, "Memory error during loading: {}", e),
         }
     }
 }
@@ -108,6 +167,8 @@ pub struct ElfLoader {
     data: Vec<u8>,
     header: ElfHeader,
     program_headers: Vec<ProgramHeader>,
+    sections: Vec<Section>,
+    symbols: BTreeMap<String, u32>,
 }
 
 impl ElfLoader {
@@ -157,10 +218,15 @@ impl ElfLoader {
             program_headers.push(ph);
         }
 
+        let section_headers = parse_section_headers(&data, &header);
+        let sections = parse_sections(&data, &header, &section_headers);
+        let symbols = parse_symbols(&data, &section_headers);
         Ok(ElfLoader {
             data,
             header,
             program_headers,
+            sections,
+            symbols,
         })
     }
 
@@ -169,45 +235,79 @@ impl ElfLoader {
         self.header.e_entry
     }
 
-    /// Load the ELF binary into memory
-    pub fn load_into_memory(
-        &self,
-        memory: &mut crate::functional_simulator::memory::Memory,
-    ) -> Result<(), ElfError> {
+    /// File contents of each loadable segment with its virtual address
+    pub fn loadable_segments(&self) -> Result<Vec<(u32, &[u8])>, ElfError> {
+        let mut segments = Vec::new();
         for ph in &self.program_headers {
             if ph.p_type == PT_LOAD && ph.p_filesz > 0 {
                 let file_offset = ph.p_offset as usize;
-                let vaddr = ph.p_vaddr as usize;
                 let size = ph.p_filesz as usize;
 
                 // Validate bounds
                 if file_offset + size > self.data.len() {
                     return Err(ElfError::InvalidFormat);
                 }
+                segments.push((ph.p_vaddr, &self.data[file_offset..file_offset + size]));
+            }
+        }
+        Ok(segments)
+    }
 
-                // Copy segment data to memory
-                let segment_data = &self.data[file_offset..file_offset + size];
-
-                // Load data word by word
-                for (i, chunk) in segment_data.chunks(4).enumerate() {
-                    let addr = vaddr + i * 4;
-                    let mut word = 0u32;
-
-                    // Handle partial words at the end
-                    for (j, &byte) in chunk.iter().enumerate() {
-                        word |= (byte as u32) << (j * 8);
-                    }
-
-                    if !memory.write_word(addr, word) {
-                        return Err(ElfError::MemoryError);
-                    }
-                }
+    /// Contents of each loadable segment as it is laid out in memory. The
+    /// bytes past the segment's file size, such as .bss, are zero.
+    pub fn segment_images(&self) -> Result<Vec<(u32, Vec<u8>)>, ElfError> {
+        let mut segments = Vec::new();
+        for ph in &self.program_headers {
+            if ph.p_type != PT_LOAD || ph.p_memsz == 0 {
+                continue;
+            }
+            if ph.p_memsz < ph.p_filesz {
+                return Err(ElfError::InvalidFormat);
             }
+            let file_offset = ph.p_offset as usize;
+            let contents = self
+                .data
+                .get(file_offset..file_offset + ph.p_filesz as usize)
+                .ok_or(ElfError::InvalidFormat)?;
+            let mut bytes = contents.to_vec();
+            bytes.resize(ph.p_memsz as usize, 0);
+            segments.push((ph.p_vaddr, bytes));
+        }
+        Ok(segments)
+    }
+
+    /// Load the ELF binary into memory, zeroing the part of each segment
+    /// that is not in the file
+    pub fn load_into_memory(
+        &self,
+        memory: &mut crate::functional_simulator::memory::Memory,
+    ) -> Result<(), ElfError> {
+        for (vaddr, bytes) in self.segment_images()? {
+            memory
+                .write_block(vaddr as usize, &bytes)
+                .map_err(ElfError::MemoryError)?;
         }
 
         Ok(())
     }
 
+    /// Sections that occupy memory at run time, in file order. Empty if the
+    /// file has no section header table.
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// The section with the given name, such as `.bss`
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// Addresses of the functions and data objects in the symbol table, by
+    /// name. Empty if the file has been stripped.
+    pub fn symbols(&self) -> &BTreeMap<String, u32> {
+        &self.symbols
+    }
+
     /// Get information about loaded segments
     pub fn get_segments(&self) -> Vec<(u32, u32, u32)> {
         self.program_headers
@@ -218,6 +318,88 @@ impl ElfLoader {
     }
 }
 
+// The section header table. A missing or malformed table is not needed for
+// loading, so it yields no headers.
+fn parse_section_headers(data: &[u8], header: &ElfHeader) -> Vec<SectionHeader> {
+    let table = header.e_shoff as usize;
+    let entry_size = header.e_shentsize as usize;
+    if table == 0 || entry_size < std::mem::size_of::<SectionHeader>() {
+        return Vec::new();
+    }
+
+    let mut headers = Vec::new();
+    for i in 0..header.e_shnum as usize {
+        let offset = table + i * entry_size;
+        if offset + std::mem::size_of::<SectionHeader>() > data.len() {
+            return Vec::new();
+        }
+        let sh =
+            unsafe { std::ptr::read_unaligned(data[offset..].as_ptr() as *const SectionHeader) };
+        headers.push(sh);
+    }
+    headers
+}
+
+// File contents of a section, empty if they lie outside the file
+fn section_data<'a>(data: &'a [u8], sh: &SectionHeader) -> &'a [u8] {
+    let start = sh.sh_offset as usize;
+    data.get(start..start + sh.sh_size as usize).unwrap_or(&[])
+}
+
+// The NUL-terminated name at `offset` in a string table
+fn string_at(table: &[u8], offset: u32) -> String {
+    table
+        .get(offset as usize..)
+        .and_then(|rest| rest.split(|&byte| byte == 0).next())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .unwrap_or_default()
+}
+
+// The sections that occupy memory at run time
+fn parse_sections(data: &[u8], header: &ElfHeader, headers: &[SectionHeader]) -> Vec<Section> {
+    let names = headers
+        .get(header.e_shstrndx as usize)
+        .map(|sh| section_data(data, sh))
+        .unwrap_or(&[]);
+    headers
+        .iter()
+        .filter(|sh| sh.sh_flags & SHF_ALLOC != 0 && sh.sh_size > 0)
+        .map(|sh| Section {
+            name: string_at(names, sh.sh_name),
+            address: sh.sh_addr,
+            size: sh.sh_size,
+            zeroed: sh.sh_type == SHT_NOBITS,
+        })
+        .collect()
+}
+
+// Defined functions, objects and untyped labels of every symbol table, by
+// name. Section and file symbols are left out.
+fn parse_symbols(data: &[u8], headers: &[SectionHeader]) -> BTreeMap<String, u32> {
+    let mut symbols = BTreeMap::new();
+    for table in headers.iter().filter(|sh| sh.sh_type == SHT_SYMTAB) {
+        let names = headers
+            .get(table.sh_link as usize)
+            .map(|sh| section_data(data, sh))
+            .unwrap_or(&[]);
+        for entry in section_data(data, table).chunks_exact(SYMBOL_SIZE) {
+            let field = |at: usize| {
+                u32::from_le_bytes([entry[at], entry[at + 1], entry[at + 2], entry[at + 3]])
+            };
+            let kind = entry[12] & 0xF;
+            let section = u16::from_le_bytes([entry[14], entry[15]]);
+            if kind > STT_FUNC || section == SHN_UNDEF {
+                continue;
+            }
+            let name = string_at(names, field(0));
+            if !name.is_empty() {
+                symbols.entry(name).or_insert(field(4));
+            }
+        }
+    }
+    symbols
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
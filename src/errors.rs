@@ -1,3 +1,5 @@
+use crate::functional_simulator::memory::{MemAccess, MemError};
+use crate::utils::limits::TerminationReason;
 use std::error::Error;
 use std::fmt;
 
@@ -56,3 +58,137 @@ impl From<std::io::Error> for SimulatorError {
         SimulatorError::IoError(error)
     }
 }
+
+/// What went wrong when the functional simulator stopped on a fault
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimulationErrorCause {
+    /// The fetched word does not decode to a supported instruction
+    InvalidInstruction,
+    /// A load or store addressed memory outside the simulated range
+    MemoryViolation(u32),
+    /// A load or store address was not aligned to the access size
+    MisalignedAccess(u32),
+    /// The memory region holding the address does not allow the access
+    PermissionDenied(u32, MemAccess),
+    /// Adding a load or store offset to its base register wrapped around
+    /// the address space; holds the base register value and the offset
+    AddressError(u32, i16),
+    /// The PC, or a branch or jump target, lies outside memory
+    PcOutOfBounds(u32),
+    /// A trap instruction's condition held; holds the trap code
+    Trap(u32),
+    /// A `jr $ra` went somewhere other than the shadow stack's return
+    /// address; holds the target and the expected address
+    ReturnMismatch(u32, u32),
+    /// The saved `$ra` slot at this address was overwritten before the
+    /// function returned
+    StackSmashed(u32),
+}
+
+/// Runtime fault returned by `Simulator::run` and `Simulator::step`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SimulationError {
+    /// Address of the faulting instruction
+    pub pc: u32,
+    /// The faulting instruction word, if it could be fetched
+    pub instruction: Option<u32>,
+    pub cause: SimulationErrorCause,
+}
+
+impl fmt::Display for SimulationErrorCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationErrorCause::InvalidInstruction => write!(f, "invalid instruction"),
+            SimulationErrorCause::MemoryViolation(addr) => {
+                write!(f, "memory access out of bounds at 0x{:08X}", addr)
+            },
+            SimulationErrorCause::MisalignedAccess(addr) => {
+                write!(f, "misaligned memory access at 0x{:08X}", addr)
+            },
+            SimulationErrorCause::PermissionDenied(addr, access) => {
+                write!(f, "{} not permitted at 0x{:08X}", access, addr)
+            },
+            SimulationErrorCause::AddressError(base, offset) => write!(
+                f,
+                "address error: {}(0x{:08X}) wraps around the address space",
+                offset, base
+            ),
+            SimulationErrorCause::PcOutOfBounds(addr) => {
+                write!(f, "PC out of bounds: 0x{:08X}", addr)
+            },
+            SimulationErrorCause::Trap(code) => write!(f, "trap (code {})", code),
+            SimulationErrorCause::ReturnMismatch(target, expected) => write!(
+                f,
+                "return to 0x{:08X} does not match the shadow stack, which expected 0x{:08X}",
+                target, expected
+            ),
+            SimulationErrorCause::StackSmashed(slot) => write!(
+                f,
+                "stack smashing detected: the return address saved at 0x{:08X} was overwritten",
+                slot
+            ),
+        }
+    }
+}
+
+impl From<MemError> for SimulationErrorCause {
+    fn from(error: MemError) -> Self {
+        match error {
+            MemError::OutOfBounds(addr) => SimulationErrorCause::MemoryViolation(addr),
+            MemError::Misaligned(addr) => SimulationErrorCause::MisalignedAccess(addr),
+            MemError::PermissionDenied(addr, access) => {
+                SimulationErrorCause::PermissionDenied(addr, access)
+            },
+        }
+    }
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.instruction {
+            Some(word) => write!(
+                f,
+                "{} at PC 0x{:08X} (instruction 0x{:08X})",
+                self.cause, self.pc, word
+            ),
+            None => write!(f, "{} at PC 0x{:08X}", self.cause, self.pc),
+        }
+    }
+}
+
+impl Error for SimulationError {}
+
+/// Why `Simulator::call_function` did not return a result
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CallError {
+    /// No label or symbol of the loaded programs has this name
+    UnknownFunction(String),
+    /// The stack arguments or the return trampoline could not be written
+    Stack(MemError),
+    /// The function faulted
+    Fault(SimulationError),
+    /// The program stopped before the function returned, for example by
+    /// calling exit or using up its instruction budget
+    Stopped(TerminationReason),
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::UnknownFunction(name) => write!(f, "no function named '{}'", name),
+            CallError::Stack(e) => write!(f, "cannot set up the call's stack frame: {}", e),
+            CallError::Fault(e) => write!(f, "{}", e),
+            CallError::Stopped(reason) => {
+                write!(f, "function did not return: {}", reason)
+            },
+        }
+    }
+}
+
+impl Error for CallError {}
+
+impl From<SimulationError> for CallError {
+    fn from(error: SimulationError) -> Self {
+        CallError::Fault(error)
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// checkpoint.rs
+//
+// This file contains delta checkpoints of a functional run. The first
+// checkpoint keeps a full copy of memory; every later one keeps only the
+// pages written since the checkpoint before it, found with the memory's
+// dirty-page tracking, so taking one every few thousand instructions costs
+// little even with a large memory. Any checkpoint can be restored by
+// applying the deltas up to it to the first copy.
+
+use super::memory::{Memory, DIRTY_PAGE_SIZE};
+use super::registers::Registers;
+
+/// A page of memory as it was when a checkpoint was taken
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageDelta {
+    /// Physical address of the page
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+/// Machine state at one checkpoint
+#[derive(Clone)]
+pub struct Checkpoint {
+    /// Instructions executed before the checkpoint was taken
+    pub instructions: usize,
+    pub pc: u32,
+    pub registers: Registers,
+    pub heap_end: usize,
+    /// Pages written since the previous checkpoint; empty for the first,
+    /// which is kept as a full copy
+    pub pages: Vec<PageDelta>,
+}
+
+/// Checkpoints taken every `interval` instructions
+#[derive(Clone)]
+pub struct CheckpointLog {
+    pub interval: usize,
+    /// Memory at the first checkpoint
+    base: Vec<u8>,
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl CheckpointLog {
+    pub fn new(interval: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            base: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Whether a checkpoint is due before the instruction after
+    /// `instructions` runs
+    pub fn is_due(&self, instructions: usize) -> bool {
+        instructions % self.interval == 0
+            && self
+                .checkpoints
+                .last()
+                .map_or(true, |last| last.instructions != instructions)
+    }
+
+    /// Save the state and start a new set of dirty pages in `memory`. A
+    /// checkpoint earlier than the last one starts the log over
+    pub fn take(
+        &mut self,
+        registers: &Registers,
+        memory: &mut Memory,
+        pc: u32,
+        instructions: usize,
+    ) {
+        let restart = self
+            .checkpoints
+            .last()
+            .map_or(true, |last| last.instructions > instructions);
+        let pages = if restart || !memory.is_tracking_dirty_pages() {
+            self.base = memory.data.clone();
+            self.checkpoints.clear();
+            memory.track_dirty_pages();
+            Vec::new()
+        } else {
+            memory
+                .dirty_pages()
+                .into_iter()
+                .map(|address| {
+                    let start = address as usize;
+                    let end = (start + DIRTY_PAGE_SIZE).min(memory.data.len());
+                    PageDelta {
+                        address,
+                        data: memory.data[start..end].to_vec(),
+                    }
+                })
+                .collect()
+        };
+        memory.clear_dirty_pages();
+        self.checkpoints.push(Checkpoint {
+            instructions,
+            pc,
+            registers: registers.clone(),
+            heap_end: memory.heap_end(),
+            pages,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checkpoints.is_empty()
+    }
+
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// The latest checkpoint taken at or before `instructions`
+    pub fn latest_before(&self, instructions: usize) -> Option<usize> {
+        self.checkpoints
+            .iter()
+            .rposition(|checkpoint| checkpoint.instructions <= instructions)
+    }
+
+    /// Bytes of memory the log holds: the first copy and every delta page
+    pub fn stored_bytes(&self) -> usize {
+        self.base.len()
+            + self
+                .checkpoints
+                .iter()
+                .flat_map(|checkpoint| &checkpoint.pages)
+                .map(|page| page.data.len())
+                .sum::<usize>()
+    }
+
+    /// Report of how many checkpoints were taken and the memory they hold,
+    /// against keeping a full copy at each
+    pub fn render(&self) -> String {
+        let pages: usize = self
+            .checkpoints
+            .iter()
+            .map(|checkpoint| checkpoint.pages.len())
+            .sum();
+        format!(
+            "Checkpoints: {} taken every {} instructions, {} pages in deltas\n  \
+             {} bytes stored ({} bytes as full copies)\n",
+            self.checkpoints.len(),
+            self.interval,
+            pages,
+            self.stored_bytes(),
+            self.base.len() * self.checkpoints.len()
+        )
+    }
+
+    /// Memory contents at checkpoint `index`
+    pub fn memory_at(&self, index: usize) -> Option<Vec<u8>> {
+        if index >= self.checkpoints.len() {
+            return None;
+        }
+        let mut data = self.base.clone();
+        for checkpoint in &self.checkpoints[1..=index] {
+            for page in &checkpoint.pages {
+                let start = page.address as usize;
+                data[start..start + page.data.len()].copy_from_slice(&page.data);
+            }
+        }
+        Some(data)
+    }
+
+    /// Put the registers and memory back as they were at checkpoint `index`
+    /// and return its program counter. Later checkpoints are dropped, since
+    /// running on from here takes them again
+    pub fn restore(
+        &mut self,
+        index: usize,
+        registers: &mut Registers,
+        memory: &mut Memory,
+    ) -> Result<u32, String> {
+        let data = self
+            .memory_at(index)
+            .ok_or_else(|| format!("no checkpoint {} (have {})", index, self.checkpoints.len()))?;
+        if data.len() != memory.data.len() {
+            return Err(format!(
+                "checkpoints are of {} bytes of memory, not {}",
+                data.len(),
+                memory.data.len()
+            ));
+        }
+        memory.data = data;
+        memory.track_dirty_pages();
+        self.checkpoints.truncate(index + 1);
+
+        let checkpoint = &self.checkpoints[index];
+        *registers = checkpoint.registers.clone();
+        memory.set_heap_end(checkpoint.heap_end);
+        Ok(checkpoint.pc)
+    }
+}
@@ -0,0 +1,341 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// commit_log.rs
+//
+// This file contains the commit log: one record per retired instruction with
+// its PC, its instruction word, the registers it wrote and the memory it
+// accessed. The text format follows the commit log Spike prints with
+// `-l --log-commits`, and the CSV format the trace CSV riscv-dv compares, so
+// co-simulation scripts written for those can check an RTL MIPS core
+// against this simulator instruction by instruction.
+
+use super::instructions::Instruction;
+use super::registers::{Register, Registers, REGISTER_NAMES};
+use super::simulator::instruction_to_string;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Privilege mode written with each commit. The simulator runs everything
+/// at the most privileged level, which Spike numbers 3.
+pub const COMMIT_MODE: u32 = 3;
+
+/// Header row of the riscv-dv trace CSV
+pub const CSV_HEADER: &str = "pc,instr,gpr,csr,binary,mode,instr_str,operand,pad";
+
+/// Name Spike gives a register in commit lines: `x0` to `x31`, `f0` to
+/// `f31`, `hi`, `lo` and `c31_fcsr`
+pub fn register_name(register: Register) -> String {
+    match register {
+        Register::Gpr(reg_num) => format!("x{}", reg_num),
+        Register::Fpr(reg_num) => format!("f{}", reg_num),
+        Register::Hi => "hi".to_string(),
+        Register::Lo => "lo".to_string(),
+        Register::Fcsr => "c31_fcsr".to_string(),
+    }
+}
+
+/// Layout of a commit log
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommitLogFormat {
+    /// Spike's text log: a disassembly line and a commit line per instruction
+    Spike,
+    /// riscv-dv's trace CSV, one row per instruction
+    Csv,
+}
+
+impl CommitLogFormat {
+    /// CSV for a path ending in `.csv`, Spike text otherwise
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => CommitLogFormat::Csv,
+            _ => CommitLogFormat::Spike,
+        }
+    }
+}
+
+/// A load or store made by a retired instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub address: u32,
+    /// Bytes accessed
+    pub size: u32,
+    /// Value stored, or `None` for a load
+    pub value: Option<u32>,
+}
+
+/// One retired instruction and its effects
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Commit {
+    pub pc: u32,
+    pub word: u32,
+    pub instruction: Instruction,
+    /// Registers written and their new values, the destination register first
+    pub writes: Vec<(Register, u32)>,
+    pub memory: Option<MemoryAccess>,
+}
+
+impl Commit {
+    /// The commit as Spike prints it: the disassembly, then the mode, the
+    /// register writes and the memory access
+    pub fn to_spike(&self) -> String {
+        let mut line = format!(
+            "core   0: 0x{:08x} (0x{:08x}) {}\ncore   0: {} 0x{:08x} (0x{:08x})",
+            self.pc,
+            self.word,
+            instruction_to_string(&self.instruction, self.word),
+            COMMIT_MODE,
+            self.pc,
+            self.word
+        );
+        for (register, value) in &self.writes {
+            line.push_str(&format!(" {:<3} 0x{:08x}", register_name(*register), value));
+        }
+        if let Some(access) = &self.memory {
+            line.push_str(&format!(" mem 0x{:08x}", access.address));
+            if let Some(value) = access.value {
+                line.push_str(&format!(
+                    " 0x{:0width$x}",
+                    value,
+                    width = access.size as usize * 2
+                ));
+            }
+        }
+        line.push('\n');
+        line
+    }
+
+    /// The commit as a riscv-dv trace CSV row. General-purpose, HI, LO and
+    /// floating-point writes go in the `gpr` column and FCSR writes in the
+    /// `csr` column, each as `name:value`.
+    pub fn to_csv(&self) -> String {
+        let mut gpr = Vec::new();
+        let mut csr = Vec::new();
+        for (register, value) in &self.writes {
+            match register {
+                Register::Gpr(reg_num) => gpr.push(format!(
+                    "{}:{:08x}",
+                    REGISTER_NAMES[*reg_num as usize % 32].trim_start_matches('$'),
+                    value
+                )),
+                Register::Fpr(reg_num) => gpr.push(format!("f{}:{:08x}", reg_num, value)),
+                Register::Hi => gpr.push(format!("hi:{:08x}", value)),
+                Register::Lo => gpr.push(format!("lo:{:08x}", value)),
+                Register::Fcsr => csr.push(format!("fcsr:{:08x}", value)),
+            }
+        }
+
+        let text = instruction_to_string(&self.instruction, self.word);
+        let (mnemonic, operands) = match text.find(' ') {
+            Some(split) => (&text[..split], text[split + 1..].trim()),
+            None => (text.as_str(), ""),
+        };
+        let fields = [
+            format!("{:08x}", self.pc),
+            mnemonic.to_string(),
+            gpr.join(";"),
+            csr.join(";"),
+            format!("{:08x}", self.word),
+            COMMIT_MODE.to_string(),
+            text.clone(),
+            operands.to_string(),
+            String::new(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        format!("{}\n", row.join(","))
+    }
+}
+
+// Quote a CSV field holding a comma or a quote
+fn csv_field(field: &str) -> String {
+    if field.contains(',') || field.contains('"') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// The register file and store data from before an instruction executes,
+/// completed into a `Commit` once it has
+#[derive(Clone, Debug)]
+pub struct PendingCommit {
+    pc: u32,
+    word: u32,
+    instruction: Instruction,
+    before: Registers,
+    memory: Option<MemoryAccess>,
+}
+
+impl PendingCommit {
+    pub fn new(pc: u32, word: u32, instruction: &Instruction, registers: &Registers) -> Self {
+        let memory = instruction.memory_operand().map(|(base, offset)| {
+            let size = instruction.access_size();
+            let value = match instruction {
+                Instruction::SwC1 { ft, .. } => Some(registers.read_float(*ft).to_bits()),
+                Instruction::Sw { rt, .. }
+                | Instruction::Sh { rt, .. }
+                | Instruction::Sb { rt, .. }
+                | Instruction::Sc { rt, .. } => {
+                    let mask = if size == 4 {
+                        u32::MAX
+                    } else {
+                        (1 << (size * 8)) - 1
+                    };
+                    Some(registers.read(*rt) & mask)
+                },
+                _ => None,
+            };
+            MemoryAccess {
+                address: registers.read(base).wrapping_add(offset as i32 as u32),
+                size,
+                value,
+            }
+        });
+        Self {
+            pc,
+            word,
+            instruction: instruction.clone(),
+            before: registers.clone(),
+            memory,
+        }
+    }
+
+    /// The commit, given the register file after the instruction executed.
+    /// The destination register, and HI and LO after `mult`, `div` and the
+    /// moves to them, are logged even when their value is unchanged.
+    pub fn finish(self, registers: &Registers) -> Commit {
+        let mut writes: Vec<(Register, u32)> = self
+            .before
+            .diff(registers)
+            .map(|change| (change.register, change.new))
+            .collect();
+        if !self.instruction.is_fp_instruction() {
+            if let Some(rd) = self.instruction.get_destination_register() {
+                let destination = Register::Gpr(rd);
+                writes.retain(|(register, _)| *register != destination);
+                writes.insert(0, (destination, registers.read(rd)));
+            }
+        }
+        let (writes_hi, writes_lo) = match self.instruction {
+            Instruction::Mult { .. } | Instruction::Div { .. } | Instruction::Divu { .. } => {
+                (true, true)
+            },
+            Instruction::Mthi { .. } => (true, false),
+            Instruction::Mtlo { .. } => (false, true),
+            _ => (false, false),
+        };
+        if writes_hi || writes_lo {
+            writes.retain(|(register, _)| *register != Register::Hi && *register != Register::Lo);
+            if writes_hi {
+                writes.push((Register::Hi, registers.hi));
+            }
+            if writes_lo {
+                writes.push((Register::Lo, registers.lo));
+            }
+        }
+
+        // A failed sc stores nothing
+        let mut memory = self.memory;
+        if let Instruction::Sc { rt, .. } = self.instruction {
+            if registers.read(rt) == 0 {
+                memory = None;
+            }
+        }
+
+        Commit {
+            pc: self.pc,
+            word: self.word,
+            instruction: self.instruction,
+            writes,
+            memory,
+        }
+    }
+}
+
+/// Destination of the commit log. Write errors are kept and reported by
+/// `finish`, so a full disk does not stop the simulation.
+pub struct CommitLog {
+    format: CommitLogFormat,
+    writer: Box<dyn Write>,
+    error: Option<io::Error>,
+    last: Option<Commit>,
+    /// Commits written so far
+    pub commits: u64,
+}
+
+impl CommitLog {
+    pub fn new(writer: Box<dyn Write>, format: CommitLogFormat) -> io::Result<Self> {
+        let mut log = Self {
+            format,
+            writer,
+            error: None,
+            last: None,
+            commits: 0,
+        };
+        if format == CommitLogFormat::Csv {
+            writeln!(log.writer, "{}", CSV_HEADER)?;
+        }
+        Ok(log)
+    }
+
+    /// Create the log file at `path`, in the format its extension selects
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path)?;
+        Self::new(
+            Box::new(BufWriter::new(file)),
+            CommitLogFormat::from_path(path),
+        )
+    }
+
+    pub fn format(&self) -> CommitLogFormat {
+        self.format
+    }
+
+    pub fn record(&mut self, commit: &Commit) {
+        self.last = Some(commit.clone());
+        if self.error.is_some() {
+            return;
+        }
+        let text = match self.format {
+            CommitLogFormat::Spike => commit.to_spike(),
+            CommitLogFormat::Csv => commit.to_csv(),
+        };
+        match self.writer.write_all(text.as_bytes()) {
+            Ok(()) => self.commits += 1,
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// The commit recorded most recently, if it has not been taken yet
+    pub fn take_last(&mut self) -> Option<Commit> {
+        self.last.take()
+    }
+
+    /// Flush the log, returning the first error met while writing it
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,457 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// console.rs
+//
+// This file contains the memory-mapped console: a text screen with a buffer
+// of character and attribute cells, and a keyboard that buffers key presses
+// and can raise an interrupt. The devices are mapped into guest memory, and
+// a `Console` handle shared with them lets the host press keys and render
+// the screen.
+
+use super::memory::{Memory, MemoryMappedDevice};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Default address of the screen's cell buffer
+pub const SCREEN_BASE: u32 = 0xFFFF_1000;
+/// Default address of the keyboard's registers
+pub const KEYBOARD_BASE: u32 = 0xFFFF_0000;
+
+// Screen registers, as offsets from the screen base
+pub const SCREEN_CURSOR: u32 = 0xFF0; // Cursor row in bits 31:16, column in bits 15:0
+pub const SCREEN_PUTC: u32 = 0xFF4; // Write a character at the cursor and advance it
+pub const SCREEN_ATTRIBUTE: u32 = 0xFF8; // Attribute of characters written through PUTC
+pub const SCREEN_CONTROL: u32 = 0xFFC; // Reads rows and columns, writing 1 clears the screen
+
+// Keyboard registers, as offsets from the keyboard base
+pub const KEYBOARD_CONTROL: u32 = 0x0; // Bit 0: a key is ready, bit 1: interrupt enable
+pub const KEYBOARD_DATA: u32 = 0x4; // Reading takes the next key
+
+pub const KEYBOARD_READY: u32 = 1 << 0;
+pub const KEYBOARD_INTERRUPT_ENABLE: u32 = 1 << 1;
+
+/// Light grey on black
+pub const DEFAULT_ATTRIBUTE: u8 = 0x07;
+
+/// Size and addresses of the console devices
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConsoleConfig {
+    pub columns: usize,
+    pub rows: usize,
+    pub screen_base: u32,
+    pub keyboard_base: u32,
+}
+
+impl ConsoleConfig {
+    pub fn new(columns: usize, rows: usize) -> Self {
+        assert!(
+            columns > 0 && rows > 0,
+            "Console must have at least one cell"
+        );
+        assert!(
+            columns * rows * 2 <= SCREEN_CURSOR as usize,
+            "Console cells must fit below the screen registers"
+        );
+        Self {
+            columns,
+            rows,
+            screen_base: SCREEN_BASE,
+            keyboard_base: KEYBOARD_BASE,
+        }
+    }
+
+    pub fn with_screen_base(mut self, base: u32) -> Self {
+        self.screen_base = base;
+        self
+    }
+
+    pub fn with_keyboard_base(mut self, base: u32) -> Self {
+        self.keyboard_base = base;
+        self
+    }
+}
+
+impl Default for ConsoleConfig {
+    /// An 80x25 screen
+    fn default() -> Self {
+        Self::new(80, 25)
+    }
+}
+
+#[derive(Debug)]
+struct ScreenState {
+    columns: usize,
+    rows: usize,
+    /// Character and attribute of every cell, row by row
+    cells: Vec<(u8, u8)>,
+    row: usize,
+    column: usize,
+    attribute: u8,
+    /// Set by every change, cleared when the host takes it
+    dirty: bool,
+}
+
+impl ScreenState {
+    fn new(columns: usize, rows: usize) -> Self {
+        Self {
+            columns,
+            rows,
+            cells: vec![(b' ', DEFAULT_ATTRIBUTE); columns * rows],
+            row: 0,
+            column: 0,
+            attribute: DEFAULT_ATTRIBUTE,
+            dirty: true,
+        }
+    }
+
+    fn clear(&mut self) {
+        let blank = (b' ', self.attribute);
+        self.cells.iter_mut().for_each(|cell| *cell = blank);
+        self.row = 0;
+        self.column = 0;
+    }
+
+    // Write a character at the cursor as a terminal would: newline, carriage
+    // return and backspace move the cursor, and the screen scrolls up when
+    // the cursor leaves the last row
+    fn put(&mut self, character: u8) {
+        match character {
+            b'\n' => {
+                self.column = 0;
+                self.row += 1;
+            },
+            b'\r' => self.column = 0,
+            0x08 => {
+                if self.column > 0 {
+                    self.column -= 1;
+                    let index = self.row * self.columns + self.column;
+                    self.cells[index] = (b' ', self.attribute);
+                }
+            },
+            _ => {
+                let index = self.row * self.columns + self.column;
+                self.cells[index] = (character, self.attribute);
+                self.column += 1;
+                if self.column == self.columns {
+                    self.column = 0;
+                    self.row += 1;
+                }
+            },
+        }
+        if self.row == self.rows {
+            self.cells.drain(..self.columns);
+            let blank = (b' ', self.attribute);
+            self.cells
+                .extend(std::iter::repeat(blank).take(self.columns));
+            self.row = self.rows - 1;
+        }
+    }
+
+    fn read_byte(&self, offset: usize) -> u8 {
+        let cell = offset / 2;
+        match self.cells.get(cell) {
+            Some(&(character, _)) if offset % 2 == 0 => character,
+            Some(&(_, attribute)) => attribute,
+            None => 0,
+        }
+    }
+
+    fn write_byte(&mut self, offset: usize, value: u8) {
+        if let Some(cell) = self.cells.get_mut(offset / 2) {
+            if offset % 2 == 0 {
+                cell.0 = value;
+            } else {
+                cell.1 = value;
+            }
+        }
+    }
+
+    fn read_register(&self, offset: u32) -> u32 {
+        match offset {
+            SCREEN_CURSOR => ((self.row as u32) << 16) | self.column as u32,
+            SCREEN_ATTRIBUTE => self.attribute as u32,
+            SCREEN_CONTROL => ((self.rows as u32) << 16) | self.columns as u32,
+            _ => 0,
+        }
+    }
+
+    fn write_register(&mut self, offset: u32, value: u32) {
+        match offset {
+            SCREEN_CURSOR => {
+                self.row = ((value >> 16) as usize).min(self.rows - 1);
+                self.column = ((value & 0xFFFF) as usize).min(self.columns - 1);
+            },
+            SCREEN_PUTC => self.put(value as u8),
+            SCREEN_ATTRIBUTE => self.attribute = value as u8,
+            SCREEN_CONTROL if value & 1 != 0 => self.clear(),
+            _ => {},
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct KeyboardState {
+    keys: VecDeque<u8>,
+    interrupt_enable: bool,
+}
+
+impl KeyboardState {
+    fn control(&self) -> u32 {
+        let ready = if self.keys.is_empty() {
+            0
+        } else {
+            KEYBOARD_READY
+        };
+        let enable = if self.interrupt_enable {
+            KEYBOARD_INTERRUPT_ENABLE
+        } else {
+            0
+        };
+        ready | enable
+    }
+}
+
+fn lock<T>(state: &Mutex<T>) -> MutexGuard<'_, T> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+struct ScreenDevice(Arc<Mutex<ScreenState>>);
+
+impl MemoryMappedDevice for ScreenDevice {
+    fn read_byte(&self, offset: usize) -> u8 {
+        let state = lock(&self.0);
+        if offset >= SCREEN_CURSOR as usize {
+            let word = state.read_register(offset as u32 & !3);
+            (word >> ((offset % 4) * 8)) as u8
+        } else {
+            state.read_byte(offset)
+        }
+    }
+
+    fn write_byte(&mut self, offset: usize, value: u8) {
+        let mut state = lock(&self.0);
+        if offset >= SCREEN_CURSOR as usize {
+            state.write_register(offset as u32 & !3, value as u32);
+        } else {
+            state.write_byte(offset, value);
+        }
+        state.dirty = true;
+    }
+
+    fn read_word(&self, offset: usize) -> u32 {
+        let state = lock(&self.0);
+        if offset >= SCREEN_CURSOR as usize {
+            return state.read_register(offset as u32 & !3);
+        }
+        (0..4).fold(0, |word, byte| {
+            word | (state.read_byte(offset + byte) as u32) << (byte * 8)
+        })
+    }
+
+    fn write_word(&mut self, offset: usize, value: u32) {
+        let mut state = lock(&self.0);
+        if offset >= SCREEN_CURSOR as usize {
+            state.write_register(offset as u32 & !3, value);
+        } else {
+            for byte in 0..4 {
+                state.write_byte(offset + byte, (value >> (byte * 8)) as u8);
+            }
+        }
+        state.dirty = true;
+    }
+}
+
+struct KeyboardDevice(Arc<Mutex<KeyboardState>>);
+
+impl MemoryMappedDevice for KeyboardDevice {
+    fn read_byte(&self, offset: usize) -> u8 {
+        (self.read_word(offset & !3) >> ((offset % 4) * 8)) as u8
+    }
+
+    fn write_byte(&mut self, offset: usize, value: u8) {
+        if offset == KEYBOARD_CONTROL as usize {
+            self.write_word(offset, value as u32);
+        }
+    }
+
+    fn read_word(&self, offset: usize) -> u32 {
+        let mut state = lock(&self.0);
+        match offset as u32 {
+            KEYBOARD_CONTROL => state.control(),
+            KEYBOARD_DATA => state.keys.pop_front().map_or(0, u32::from),
+            _ => 0,
+        }
+    }
+
+    fn write_word(&mut self, offset: usize, value: u32) {
+        if offset as u32 == KEYBOARD_CONTROL {
+            lock(&self.0).interrupt_enable = value & KEYBOARD_INTERRUPT_ENABLE != 0;
+        }
+    }
+}
+
+/// Host side of a console mapped into guest memory. Clones share the same
+/// devices, so a handle can be passed to another thread to press keys or
+/// redraw the screen while the guest runs.
+#[derive(Clone, Debug)]
+pub struct Console {
+    pub config: ConsoleConfig,
+    screen: Arc<Mutex<ScreenState>>,
+    keyboard: Arc<Mutex<KeyboardState>>,
+}
+
+impl Console {
+    /// Create the devices and map them into `memory`
+    pub fn attach(memory: &mut Memory, config: ConsoleConfig) -> Self {
+        let console = Self {
+            config,
+            screen: Arc::new(Mutex::new(ScreenState::new(config.columns, config.rows))),
+            keyboard: Arc::new(Mutex::new(KeyboardState::default())),
+        };
+        memory.map_device(
+            config.screen_base as usize,
+            Box::new(ScreenDevice(Arc::clone(&console.screen))),
+        );
+        memory.map_device(
+            config.keyboard_base as usize,
+            Box::new(KeyboardDevice(Arc::clone(&console.keyboard))),
+        );
+        console
+    }
+
+    /// Buffer a key press for the guest
+    pub fn press_key(&self, key: u8) {
+        lock(&self.keyboard).keys.push_back(key);
+    }
+
+    pub fn press_keys(&self, keys: &[u8]) {
+        lock(&self.keyboard).keys.extend(keys.iter().copied());
+    }
+
+    /// Whether the keyboard's interrupt line is raised: a key is waiting and
+    /// the guest enabled the interrupt
+    pub fn keyboard_interrupt(&self) -> bool {
+        let state = lock(&self.keyboard);
+        state.interrupt_enable && !state.keys.is_empty()
+    }
+
+    /// Keys the guest has not read yet
+    pub fn pending_keys(&self) -> usize {
+        lock(&self.keyboard).keys.len()
+    }
+
+    /// Character and attribute of a cell
+    pub fn cell(&self, row: usize, column: usize) -> (u8, u8) {
+        let state = lock(&self.screen);
+        state.cells[row * state.columns + column]
+    }
+
+    /// Cursor row and column
+    pub fn cursor(&self) -> (usize, usize) {
+        let state = lock(&self.screen);
+        (state.row, state.column)
+    }
+
+    /// The characters on screen, one line per row without trailing spaces
+    pub fn text(&self) -> String {
+        let state = lock(&self.screen);
+        state
+            .cells
+            .chunks(state.columns)
+            .map(|row| {
+                let line: String = row
+                    .iter()
+                    .map(|&(character, _)| printable(character))
+                    .collect();
+                line.trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The screen in a border, as plain text
+    pub fn render(&self) -> String {
+        self.frame(false)
+    }
+
+    /// The screen in a border, with attributes as ANSI colours
+    pub fn render_ansi(&self) -> String {
+        self.frame(true)
+    }
+
+    /// Whether the screen changed since the last call
+    pub fn take_dirty(&self) -> bool {
+        std::mem::replace(&mut lock(&self.screen).dirty, false)
+    }
+
+    fn frame(&self, colour: bool) -> String {
+        let state = lock(&self.screen);
+        let border = format!("+{}+\n", "-".repeat(state.columns));
+        let mut result = border.clone();
+        for row in state.cells.chunks(state.columns) {
+            result.push('|');
+            let mut current = DEFAULT_ATTRIBUTE;
+            for &(character, attribute) in row {
+                if colour && attribute != current {
+                    result.push_str(&sgr(attribute));
+                    current = attribute;
+                }
+                result.push(printable(character));
+            }
+            if colour && current != DEFAULT_ATTRIBUTE {
+                result.push_str("\x1b[0m");
+            }
+            result.push_str("|\n");
+        }
+        result.push_str(&border);
+        result
+    }
+}
+
+fn printable(character: u8) -> char {
+    if (0x20..0x7F).contains(&character) {
+        character as char
+    } else {
+        ' '
+    }
+}
+
+// ANSI escape selecting the colours of a VGA-style attribute: foreground in
+// bits 0-2 with bit 3 for bright, background in bits 4-6. The default
+// attribute uses the terminal's own colours.
+fn sgr(attribute: u8) -> String {
+    if attribute == DEFAULT_ATTRIBUTE {
+        return "\x1b[0m".to_string();
+    }
+    let foreground = ansi_colour(attribute);
+    let background = ansi_colour(attribute >> 4);
+    let base = if attribute & 0x8 != 0 { 90 } else { 30 };
+    format!("\x1b[0;{};{}m", base + foreground, 40 + background)
+}
+
+// VGA colours are ordered blue, green, red from the lowest bit and ANSI
+// colours red, green, blue
+fn ansi_colour(colour: u8) -> u8 {
+    ((colour & 0x1) << 2) | (colour & 0x2) | ((colour & 0x4) >> 2)
+}
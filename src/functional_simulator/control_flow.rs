@@ -0,0 +1,150 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// control_flow.rs
+//
+// This file contains the control-flow integrity checks the functional
+// simulator can run on a program. A shadow stack keeps the return address
+// of every call out of the program's reach and checks each `jr $ra`
+// against it, and the canary check notes where a function saves `$ra` and
+// reports when that slot was overwritten by the time the function returns,
+// as a compiler's stack protector would. Both stop the program with a trap,
+// which is how buffer overflow and return-oriented programming examples
+// are caught.
+
+use super::instructions::Instruction;
+use super::memory::Memory;
+use super::registers::Registers;
+use crate::errors::SimulationErrorCause;
+use std::fmt::Write;
+
+/// Which checks are made
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ControlFlowChecks {
+    /// Check every `jr $ra` against the shadow stack of return addresses
+    pub shadow_stack: bool,
+    /// Check the saved `$ra` slot of a function when it returns
+    pub canary: bool,
+}
+
+/// The call a return is checked against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// Address the call links to
+    pub return_address: u32,
+    /// Where the callee first stored `$ra` and the value it stored
+    pub saved_slot: Option<(u32, u32)>,
+}
+
+/// Shadow stack and saved return address slots of a running program
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ControlFlowGuard {
+    pub checks: ControlFlowChecks,
+    /// Calls not yet returned from, innermost last
+    pub frames: Vec<Frame>,
+    pub calls: usize,
+    /// Returns that matched a call and were checked
+    pub returns: usize,
+    /// Deepest the call stack got
+    pub max_depth: usize,
+}
+
+impl ControlFlowGuard {
+    pub fn new(checks: ControlFlowChecks) -> Self {
+        Self {
+            checks,
+            ..Self::default()
+        }
+    }
+
+    /// Follow `instruction`, about to execute at `pc`. A call pushes a
+    /// frame, a store of `$ra` marks the innermost frame's saved slot and a
+    /// `jr $ra` pops the frame and is checked against it. A return with no
+    /// call to match, such as `main` returning, is not checked.
+    pub fn check(
+        &mut self,
+        instruction: &Instruction,
+        registers: &Registers,
+        memory: &Memory,
+        pc: u32,
+    ) -> Result<(), SimulationErrorCause> {
+        match *instruction {
+            Instruction::Jal { .. } | Instruction::Jalr { .. }
+                if instruction.get_destination_register().is_some() =>
+            {
+                self.frames.push(Frame {
+                    return_address: pc.wrapping_add(4),
+                    saved_slot: None,
+                });
+                self.calls += 1;
+                self.max_depth = self.max_depth.max(self.frames.len());
+            },
+            Instruction::Sw { rt: 31, .. } => {
+                if let Some(frame) = self.frames.last_mut() {
+                    if frame.saved_slot.is_none() {
+                        let address = instruction.get_address(registers, pc);
+                        frame.saved_slot = Some((address, registers.read(31)));
+                    }
+                }
+            },
+            Instruction::Jr { rs: 31 } => {
+                let frame = match self.frames.pop() {
+                    Some(frame) => frame,
+                    None => return Ok(()),
+                };
+                self.returns += 1;
+                if self.checks.canary {
+                    if let Some((slot, saved)) = frame.saved_slot {
+                        if memory.read_word(slot as usize) != Ok(saved) {
+                            return Err(SimulationErrorCause::StackSmashed(slot));
+                        }
+                    }
+                }
+                let target = registers.read(31);
+                if self.checks.shadow_stack && target != frame.return_address {
+                    return Err(SimulationErrorCause::ReturnMismatch(
+                        target,
+                        frame.return_address,
+                    ));
+                }
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    pub fn render(&self) -> String {
+        let mut result = String::from("Control-flow integrity\n");
+        let _ = writeln!(
+            result,
+            "  {} calls, {} returns checked, deepest call stack {}",
+            self.calls, self.returns, self.max_depth
+        );
+        for frame in self.frames.iter().rev() {
+            let _ = writeln!(
+                result,
+                "  Still open: call returning to 0x{:08X}",
+                frame.return_address
+            );
+        }
+        result
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// cp0.rs
+//
+// This file contains the System Control Coprocessor (CP0) definitions used by
+// the functional simulator. It defines the CP0 register numbers and bit fields
+// needed for interrupt handling, the asynchronous events that can be queued
+// from the host, and the interrupt controller that delivers them to a
+// guest-registered handler.
+
+use super::registers::Registers;
+use std::collections::VecDeque;
+
+// CP0 register numbers. A register number carries the select field of
+// mfc0/mtc0 in bits 7:5; registers whose selects are not modelled alias
+// select 0, so UserLocal (select 2 of register 4) is register 4
+pub const CP0_SELECT_SHIFT: u32 = 5;
+pub const CP0_USERLOCAL: u32 = 4;
+pub const CP0_BADVADDR: u32 = 8;
+pub const CP0_COUNT: u32 = 9;
+/// EntryHi, whose low eight bits hold the current ASID
+pub const CP0_ENTRYHI: u32 = 10;
+pub const CP0_COMPARE: u32 = 11;
+pub const CP0_STATUS: u32 = 12;
+pub const CP0_CAUSE: u32 = 13;
+pub const CP0_EPC: u32 = 14;
+pub const CP0_EBASE: u32 = 15;
+/// Shadow register set control, select 2 of register 12
+pub const CP0_SRSCTL: u32 = CP0_STATUS | (2 << CP0_SELECT_SHIFT);
+
+// Status register fields
+pub const STATUS_IE: u32 = 1 << 0; // Global interrupt enable
+pub const STATUS_EXL: u32 = 1 << 1; // Exception level
+pub const STATUS_ERL: u32 = 1 << 2; // Error level
+pub const STATUS_IM_SHIFT: u32 = 8; // Interrupt mask bits 15:8
+pub const STATUS_BEV: u32 = 1 << 22; // Exceptions use the bootstrap vectors
+
+// SRSCtl register fields, each a 4-bit shadow set number
+pub const SRSCTL_HSS_SHIFT: u32 = 26; // Highest shadow set
+pub const SRSCTL_ESS_SHIFT: u32 = 12; // Set used by exceptions
+pub const SRSCTL_PSS_SHIFT: u32 = 6; // Set in use before the exception
+pub const SRSCTL_CSS_SHIFT: u32 = 0; // Set in use now
+pub const SRSCTL_SET_MASK: u32 = 0xF;
+
+// Cause register fields
+pub const CAUSE_IP_SHIFT: u32 = 8; // Pending interrupt bits 15:8
+pub const CAUSE_EXCCODE_SHIFT: u32 = 2;
+pub const CAUSE_EXCCODE_MASK: u32 = 0x1F << CAUSE_EXCCODE_SHIFT;
+pub const EXCCODE_INT: u32 = 0;
+pub const EXCCODE_RI: u32 = 10; // Reserved Instruction
+
+/// Offset of the general exception vector from EBase
+pub const EXCEPTION_VECTOR_OFFSET: u32 = 0x180;
+
+/// Reset value of EBase (kseg0)
+pub const DEFAULT_EBASE: u32 = 0x8000_0000;
+
+/// Where the processor starts after reset: the boot ROM, through kseg1
+pub const RESET_VECTOR: u32 = 0xBFC0_0000;
+
+/// Base of the exception vectors while `Status.BEV` is set
+pub const BOOTSTRAP_EBASE: u32 = 0xBFC0_0200;
+
+/// Status after reset: bootstrap vectors, at error level
+pub const RESET_STATUS: u32 = STATUS_BEV | STATUS_ERL;
+
+// Interrupt lines used by the built-in event sources
+pub const KEYBOARD_IRQ: u32 = 2;
+pub const DISK_IRQ: u32 = 3;
+pub const NETWORK_IRQ: u32 = 4;
+pub const TIMER_IRQ: u32 = 7;
+
+/// Asynchronous events that the host can raise in the guest
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AsyncEvent {
+    /// A key press from the console; the byte is returned by read_char
+    KeyPress(u8),
+    /// A timer tick on the timer interrupt line
+    Timer,
+    /// A raw hardware interrupt line (0-7)
+    External(u32),
+}
+
+impl AsyncEvent {
+    pub fn irq_line(&self) -> u32 {
+        match self {
+            AsyncEvent::KeyPress(_) => KEYBOARD_IRQ,
+            AsyncEvent::Timer => TIMER_IRQ,
+            AsyncEvent::External(line) => *line & 0x7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InterruptController {
+    scheduled: Vec<(usize, AsyncEvent)>,
+    key_buffer: VecDeque<u8>,
+    pub interrupts_taken: usize,
+}
+
+impl InterruptController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Raise an event immediately by setting its pending bit in Cause
+    pub fn raise(&mut self, registers: &mut Registers, event: AsyncEvent) {
+        if let AsyncEvent::KeyPress(key) = event {
+            self.key_buffer.push_back(key);
+        }
+        set_pending(registers, event.irq_line());
+    }
+
+    // Raise an event once the given number of instructions has executed
+    pub fn schedule(&mut self, at_step: usize, event: AsyncEvent) {
+        self.scheduled.push((at_step, event));
+        self.scheduled.sort_by_key(|&(step, _)| step);
+    }
+
+    // Raise all scheduled events that are due and advance the Count/Compare timer
+    pub fn tick(&mut self, registers: &mut Registers, step: usize) {
+        while let Some(&(at_step, event)) = self.scheduled.first() {
+            if at_step > step {
+                break;
+            }
+            self.scheduled.remove(0);
+            self.raise(registers, event);
+        }
+
+        let count = registers.cp0[CP0_COUNT as usize].wrapping_add(1);
+        registers.cp0[CP0_COUNT as usize] = count;
+        let compare = registers.cp0[CP0_COMPARE as usize];
+        if compare != 0 && count == compare {
+            set_pending(registers, TIMER_IRQ);
+        }
+    }
+
+    // Take the next buffered key and acknowledge the keyboard interrupt when empty
+    pub fn take_key(&mut self, registers: &mut Registers) -> Option<u8> {
+        let key = self.key_buffer.pop_front();
+        if self.key_buffer.is_empty() {
+            clear_pending(registers, KEYBOARD_IRQ);
+        }
+        key
+    }
+
+    pub fn has_pending_key(&self) -> bool {
+        !self.key_buffer.is_empty()
+    }
+
+    // Deliver a pending, unmasked interrupt. Returns the handler address when
+    // an interrupt is taken; `pc` is saved into EPC.
+    pub fn deliver(&mut self, registers: &mut Registers, pc: u32) -> Option<u32> {
+        if !interrupt_pending(registers) {
+            return None;
+        }
+        self.interrupts_taken += 1;
+        Some(enter_exception(registers, pc, EXCCODE_INT))
+    }
+}
+
+pub fn set_pending(registers: &mut Registers, line: u32) {
+    registers.cp0[CP0_CAUSE as usize] |= 1 << (CAUSE_IP_SHIFT + line);
+}
+
+pub fn clear_pending(registers: &mut Registers, line: u32) {
+    registers.cp0[CP0_CAUSE as usize] &= !(1 << (CAUSE_IP_SHIFT + line));
+}
+
+// True if an interrupt is pending, unmasked, and interrupts are enabled
+pub fn interrupt_pending(registers: &Registers) -> bool {
+    let status = registers.cp0[CP0_STATUS as usize];
+    let cause = registers.cp0[CP0_CAUSE as usize];
+    let enabled = status & STATUS_IE != 0 && status & (STATUS_EXL | STATUS_ERL) == 0;
+    let pending = (cause >> CAUSE_IP_SHIFT) & (status >> STATUS_IM_SHIFT) & 0xFF;
+    enabled && pending != 0
+}
+
+// Record an exception in CP0 and return the address of the exception vector.
+// An exception taken outside exception level switches to the exception
+// shadow set, remembering the interrupted set in SRSCtl.PSS.
+pub fn enter_exception(registers: &mut Registers, pc: u32, exc_code: u32) -> u32 {
+    registers.cp0[CP0_EPC as usize] = pc;
+    let cause = registers.cp0[CP0_CAUSE as usize] & !CAUSE_EXCCODE_MASK;
+    registers.cp0[CP0_CAUSE as usize] = cause | (exc_code << CAUSE_EXCCODE_SHIFT);
+    if registers.cp0[CP0_STATUS as usize] & STATUS_EXL == 0 && registers.shadow.is_enabled() {
+        let current = registers.shadow.current_set();
+        registers.shadow.set_previous_set(current);
+        let exception_set = registers.shadow.exception_set();
+        if exception_set != current {
+            registers.switch_register_set(exception_set);
+            registers.shadow.switched_exceptions += 1;
+        }
+    }
+    registers.cp0[CP0_STATUS as usize] |= STATUS_EXL;
+    let base = if registers.cp0[CP0_STATUS as usize] & STATUS_BEV != 0 {
+        BOOTSTRAP_EBASE
+    } else {
+        registers.cp0[CP0_EBASE as usize]
+    };
+    base.wrapping_add(EXCEPTION_VECTOR_OFFSET)
+}
+
+// Leave exception level, return to the interrupted shadow set and return the
+// address to resume at
+pub fn return_from_exception(registers: &mut Registers) -> u32 {
+    if registers.cp0[CP0_STATUS as usize] & STATUS_EXL != 0 && registers.shadow.is_enabled() {
+        registers.switch_register_set(registers.shadow.previous_set());
+    }
+    registers.cp0[CP0_STATUS as usize] &= !STATUS_EXL;
+    registers.ll_bit = false;
+    registers.cp0[CP0_EPC as usize]
+}
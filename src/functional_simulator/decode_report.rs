@@ -0,0 +1,203 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// decode_report.rs
+//
+// This file contains the lenient decoding mode, which lets a run continue
+// past instruction words the decoder does not recognise. Each unknown word
+// is either skipped as a NOP or raised to the guest as a Reserved
+// Instruction exception, and its address and encoding are recorded so the
+// gaps in the decoder can be catalogued after the run.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Name and value of the field that selects the operation within an
+/// opcode, such as `("funct", 0x21)`
+pub type SelectingField = (&'static str, u32);
+
+/// What to do with an instruction word the decoder does not recognise
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnknownInstructionPolicy {
+    /// Skip the word as if it were a NOP
+    Nop,
+    /// Raise a Reserved Instruction exception for the guest's handler
+    Trap,
+}
+
+impl UnknownInstructionPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nop" => Some(Self::Nop),
+            "trap" => Some(Self::Trap),
+            _ => None,
+        }
+    }
+}
+
+/// An address that held an unknown instruction word
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownInstruction {
+    pub pc: u32,
+    pub word: u32,
+    /// Times the word was reached
+    pub count: usize,
+}
+
+/// Unknown instruction words sharing an opcode and, where the opcode has
+/// one, the field that selects the operation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecoderGap {
+    pub opcode: u32,
+    pub field: Option<SelectingField>,
+    /// Distinct addresses with a word of this kind
+    pub sites: usize,
+    pub executions: usize,
+    /// The word at the lowest such address
+    pub example: u32,
+}
+
+impl DecoderGap {
+    pub fn label(&self) -> String {
+        match self.field {
+            Some((name, value)) => format!("opcode 0x{:02X} {} 0x{:02X}", self.opcode, name, value),
+            None => format!("opcode 0x{:02X}", self.opcode),
+        }
+    }
+}
+
+// The field that selects the operation within an opcode, if any
+fn selecting_field(word: u32) -> Option<SelectingField> {
+    match word >> 26 {
+        0x00 | 0x1C | 0x1F => Some(("funct", word & 0x3F)),
+        0x01 => Some(("rt", (word >> 16) & 0x1F)),
+        0x10..=0x13 => Some(("rs", (word >> 21) & 0x1F)),
+        _ => None,
+    }
+}
+
+/// Unknown instructions met during a run under lenient decoding
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodeReport {
+    pub policy: UnknownInstructionPolicy,
+    sites: BTreeMap<u32, UnknownInstruction>,
+}
+
+impl DecodeReport {
+    pub fn new(policy: UnknownInstructionPolicy) -> Self {
+        Self {
+            policy,
+            sites: BTreeMap::new(),
+        }
+    }
+
+    /// Note that the word at `pc` could not be decoded
+    pub fn record(&mut self, pc: u32, word: u32) {
+        let site = self
+            .sites
+            .entry(pc)
+            .or_insert(UnknownInstruction { pc, word, count: 0 });
+        // Self-modifying code may leave a different word at the address
+        site.word = word;
+        site.count += 1;
+    }
+
+    /// Every address that held an unknown word, in address order
+    pub fn unknown_instructions(&self) -> impl Iterator<Item = &UnknownInstruction> {
+        self.sites.values()
+    }
+
+    /// Times an unknown word was reached
+    pub fn executions(&self) -> usize {
+        self.sites.values().map(|site| site.count).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sites.is_empty()
+    }
+
+    /// Unknown words grouped by opcode and selecting field, most executed
+    /// first
+    pub fn gaps(&self) -> Vec<DecoderGap> {
+        let mut gaps: BTreeMap<(u32, Option<SelectingField>), DecoderGap> = BTreeMap::new();
+        for site in self.sites.values() {
+            let opcode = site.word >> 26;
+            let field = selecting_field(site.word);
+            let gap = gaps.entry((opcode, field)).or_insert(DecoderGap {
+                opcode,
+                field,
+                sites: 0,
+                executions: 0,
+                example: site.word,
+            });
+            gap.sites += 1;
+            gap.executions += site.count;
+        }
+        let mut gaps: Vec<DecoderGap> = gaps.into_values().collect();
+        gaps.sort_by_key(|gap| Reverse(gap.executions));
+        gaps
+    }
+
+    /// A report of the decoder gaps and the addresses they were met at
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let action = match self.policy {
+            UnknownInstructionPolicy::Nop => "skipped as NOPs",
+            UnknownInstructionPolicy::Trap => "raised as Reserved Instruction exceptions",
+        };
+        let _ = writeln!(
+            out,
+            "Unknown instructions: {} at {} addresses, {}",
+            self.executions(),
+            self.sites.len(),
+            action
+        );
+        if self.sites.is_empty() {
+            return out;
+        }
+
+        let _ = writeln!(
+            out,
+            "{:<24} {:>6} {:>10}  example",
+            "encoding", "sites", "executed"
+        );
+        for gap in self.gaps() {
+            let _ = writeln!(
+                out,
+                "{:<24} {:>6} {:>10}  0x{:08X}",
+                gap.label(),
+                gap.sites,
+                gap.executions,
+                gap.example
+            );
+        }
+        let _ = writeln!(out, "{:<12} {:<12} {:>10}", "address", "word", "executed");
+        for site in self.sites.values() {
+            let _ = writeln!(
+                out,
+                "0x{:08X}   0x{:08X}   {:>10}",
+                site.pc, site.word, site.count
+            );
+        }
+        out
+    }
+}
@@ -0,0 +1,422 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// disk.rs
+//
+// This file contains a memory-mapped block storage device backed by a host
+// file. The guest selects a sector and issues read or write commands, which
+// either go through a one-sector buffer in the device's registers or, with
+// DMA enabled, transfer whole runs of sectors straight into guest memory.
+// A command completes after a configurable latency and can raise an
+// interrupt. A `Disk` handle shared with the device lets the host inspect
+// the storage and its statistics.
+
+use super::memory::{Memory, MemoryMappedDevice};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Default address of the disk's registers
+pub const DISK_BASE: u32 = 0xFFFF_2000;
+
+/// Bytes per sector
+pub const SECTOR_SIZE: usize = 512;
+
+// Disk registers, as offsets from the disk base
+pub const DISK_COMMAND: u32 = 0x00; // Write a command to start it
+pub const DISK_STATUS: u32 = 0x04; // Busy, done and error bits; writing acknowledges
+pub const DISK_SECTOR: u32 = 0x08; // First sector of the transfer
+pub const DISK_COUNT: u32 = 0x0C; // Sectors to transfer with DMA
+pub const DISK_ADDRESS: u32 = 0x10; // Guest address of a DMA transfer
+pub const DISK_CONTROL: u32 = 0x14; // Bit 0: DMA, bit 1: interrupt enable
+pub const DISK_CAPACITY: u32 = 0x18; // Number of sectors, read-only
+pub const DISK_BUFFER: u32 = 0x200; // One sector for transfers without DMA
+
+// Commands
+pub const DISK_READ: u32 = 1;
+pub const DISK_WRITE: u32 = 2;
+
+// Status bits
+pub const DISK_BUSY: u32 = 1 << 0;
+pub const DISK_DONE: u32 = 1 << 1;
+pub const DISK_ERROR: u32 = 1 << 2;
+
+// Control bits
+pub const DISK_DMA_ENABLE: u32 = 1 << 0;
+pub const DISK_INTERRUPT_ENABLE: u32 = 1 << 1;
+
+/// Instructions a command takes per sector unless configured otherwise
+pub const DEFAULT_SECTOR_LATENCY: u64 = 100;
+
+/// Backing storage and timing of a disk
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiskConfig {
+    /// Host file holding the disk image. Without one, the disk starts zeroed
+    /// and writes are kept in memory
+    pub path: Option<PathBuf>,
+    /// Size of an in-memory disk, in sectors
+    pub sectors: usize,
+    pub base: u32,
+    /// Whether the device has a DMA engine
+    pub dma: bool,
+    /// Instructions each sector of a command takes to complete
+    pub sector_latency: u64,
+}
+
+impl DiskConfig {
+    /// A disk backed by `path`. Writes from the guest are written through to
+    /// the file
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: Some(path.as_ref().to_path_buf()),
+            ..Self::in_memory(0)
+        }
+    }
+
+    /// A zeroed disk of `sectors` sectors that is not backed by a file
+    pub fn in_memory(sectors: usize) -> Self {
+        Self {
+            path: None,
+            sectors,
+            base: DISK_BASE,
+            dma: true,
+            sector_latency: DEFAULT_SECTOR_LATENCY,
+        }
+    }
+
+    pub fn with_base(mut self, base: u32) -> Self {
+        self.base = base;
+        self
+    }
+
+    pub fn with_dma(mut self, dma: bool) -> Self {
+        self.dma = dma;
+        self
+    }
+
+    pub fn with_sector_latency(mut self, instructions: u64) -> Self {
+        self.sector_latency = instructions;
+        self
+    }
+}
+
+/// Work done by a disk since it was attached
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiskStatistics {
+    pub commands: u64,
+    pub sectors_read: u64,
+    pub sectors_written: u64,
+    /// Commands that moved data with the DMA engine
+    pub dma_transfers: u64,
+    pub errors: u64,
+}
+
+// A command waiting for its latency to pass
+#[derive(Clone, Copy, Debug)]
+struct PendingCommand {
+    command: u32,
+    sector: usize,
+    count: usize,
+    address: u32,
+    dma: bool,
+    remaining: u64,
+}
+
+#[derive(Debug)]
+struct DiskState {
+    storage: Vec<u8>,
+    file: Option<File>,
+    sector_latency: u64,
+    dma_available: bool,
+    sector: u32,
+    count: u32,
+    address: u32,
+    control: u32,
+    status: u32,
+    buffer: Vec<u8>,
+    pending: Option<PendingCommand>,
+    statistics: DiskStatistics,
+}
+
+impl DiskState {
+    fn sectors(&self) -> usize {
+        self.storage.len() / SECTOR_SIZE
+    }
+
+    fn read_register(&self, offset: u32) -> u32 {
+        match offset {
+            DISK_STATUS => self.status,
+            DISK_SECTOR => self.sector,
+            DISK_COUNT => self.count,
+            DISK_ADDRESS => self.address,
+            DISK_CONTROL => self.control,
+            DISK_CAPACITY => self.sectors() as u32,
+            _ => 0,
+        }
+    }
+
+    fn write_register(&mut self, offset: u32, value: u32) {
+        match offset {
+            DISK_COMMAND => self.start(value),
+            // Any write acknowledges a finished command
+            DISK_STATUS => self.status &= !(DISK_DONE | DISK_ERROR),
+            DISK_SECTOR => self.sector = value,
+            DISK_COUNT => self.count = value,
+            DISK_ADDRESS => self.address = value,
+            DISK_CONTROL => {
+                let mut mask = DISK_INTERRUPT_ENABLE;
+                if self.dma_available {
+                    mask |= DISK_DMA_ENABLE;
+                }
+                self.control = value & mask;
+            },
+            _ => {},
+        }
+    }
+
+    // Accept a command, or fail it at once if it is unknown, the disk is
+    // busy or the sectors are not on the disk
+    fn start(&mut self, command: u32) {
+        self.statistics.commands += 1;
+        let dma = self.control & DISK_DMA_ENABLE != 0;
+        let count = if dma { self.count as usize } else { 1 };
+        let sector = self.sector as usize;
+        let valid = (command == DISK_READ || command == DISK_WRITE)
+            && self.pending.is_none()
+            && count > 0
+            && sector
+                .checked_add(count)
+                .map_or(false, |end| end <= self.sectors());
+        if !valid {
+            self.fail();
+            return;
+        }
+        self.status = DISK_BUSY;
+        self.pending = Some(PendingCommand {
+            command,
+            sector,
+            count,
+            address: self.address,
+            dma,
+            remaining: self.sector_latency.saturating_mul(count as u64),
+        });
+    }
+
+    fn fail(&mut self) {
+        self.status = (self.status & DISK_BUSY) | DISK_DONE | DISK_ERROR;
+        self.statistics.errors += 1;
+    }
+
+    // Count down the pending command and carry it out once its latency has
+    // passed
+    fn advance(&mut self, memory: &mut Memory) {
+        let mut pending = match self.pending {
+            Some(pending) => pending,
+            None => return,
+        };
+        if pending.remaining > 0 {
+            pending.remaining -= 1;
+            self.pending = Some(pending);
+            return;
+        }
+        self.pending = None;
+        self.status &= !DISK_BUSY;
+        match self.transfer(&pending, memory) {
+            Ok(()) => self.status |= DISK_DONE,
+            Err(()) => self.fail(),
+        }
+    }
+
+    fn transfer(&mut self, pending: &PendingCommand, memory: &mut Memory) -> Result<(), ()> {
+        let start = pending.sector * SECTOR_SIZE;
+        let end = start + pending.count * SECTOR_SIZE;
+        if pending.command == DISK_READ {
+            if pending.dma {
+                memory
+                    .write_block(pending.address as usize, &self.storage[start..end])
+                    .map_err(|_| ())?;
+            } else {
+                self.buffer.copy_from_slice(&self.storage[start..end]);
+            }
+            self.statistics.sectors_read += pending.count as u64;
+        } else {
+            let data = if pending.dma {
+                memory
+                    .read_block(pending.address as usize, end - start)
+                    .map_err(|_| ())?
+            } else {
+                self.buffer.clone()
+            };
+            self.storage[start..end].copy_from_slice(&data);
+            if let Some(file) = self.file.as_mut() {
+                file.seek(SeekFrom::Start(start as u64))
+                    .and_then(|_| file.write_all(&data))
+                    .map_err(|_| ())?;
+            }
+            self.statistics.sectors_written += pending.count as u64;
+        }
+        if pending.dma {
+            self.statistics.dma_transfers += 1;
+        }
+        Ok(())
+    }
+}
+
+fn lock(state: &Mutex<DiskState>) -> MutexGuard<'_, DiskState> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+struct DiskDevice(Arc<Mutex<DiskState>>);
+
+impl MemoryMappedDevice for DiskDevice {
+    fn read_byte(&self, offset: usize) -> u8 {
+        let state = lock(&self.0);
+        if offset >= DISK_BUFFER as usize {
+            return state
+                .buffer
+                .get(offset - DISK_BUFFER as usize)
+                .copied()
+                .unwrap_or(0);
+        }
+        (state.read_register(offset as u32 & !3) >> ((offset % 4) * 8)) as u8
+    }
+
+    fn write_byte(&mut self, offset: usize, value: u8) {
+        let mut state = lock(&self.0);
+        if offset >= DISK_BUFFER as usize {
+            if let Some(byte) = state.buffer.get_mut(offset - DISK_BUFFER as usize) {
+                *byte = value;
+            }
+        } else {
+            state.write_register(offset as u32 & !3, value as u32);
+        }
+    }
+
+    fn read_word(&self, offset: usize) -> u32 {
+        if offset >= DISK_BUFFER as usize {
+            return (0..4).fold(0, |word, byte| {
+                word | (self.read_byte(offset + byte) as u32) << (byte * 8)
+            });
+        }
+        lock(&self.0).read_register(offset as u32 & !3)
+    }
+
+    fn write_word(&mut self, offset: usize, value: u32) {
+        if offset >= DISK_BUFFER as usize {
+            for byte in 0..4 {
+                self.write_byte(offset + byte, (value >> (byte * 8)) as u8);
+            }
+        } else {
+            lock(&self.0).write_register(offset as u32 & !3, value);
+        }
+    }
+}
+
+/// Host side of a disk mapped into guest memory. Clones share the device.
+#[derive(Clone, Debug)]
+pub struct Disk {
+    pub config: DiskConfig,
+    state: Arc<Mutex<DiskState>>,
+}
+
+impl Disk {
+    /// Open the backing storage, create the device and map it into `memory`.
+    /// A file image is padded with zeroes to a whole number of sectors.
+    pub fn attach(memory: &mut Memory, config: DiskConfig) -> io::Result<Self> {
+        let (storage, file) = match config.path.as_ref() {
+            Some(path) => {
+                let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+                let mut storage = Vec::new();
+                file.read_to_end(&mut storage)?;
+                let sectors = (storage.len() + SECTOR_SIZE - 1) / SECTOR_SIZE;
+                storage.resize(sectors * SECTOR_SIZE, 0);
+                (storage, Some(file))
+            },
+            None => (vec![0; config.sectors * SECTOR_SIZE], None),
+        };
+        let state = DiskState {
+            storage,
+            file,
+            sector_latency: config.sector_latency,
+            dma_available: config.dma,
+            sector: 0,
+            count: 0,
+            address: 0,
+            control: 0,
+            status: 0,
+            buffer: vec![0; SECTOR_SIZE],
+            pending: None,
+            statistics: DiskStatistics::default(),
+        };
+        let disk = Self {
+            config,
+            state: Arc::new(Mutex::new(state)),
+        };
+        memory.map_device(
+            disk.config.base as usize,
+            Box::new(DiskDevice(Arc::clone(&disk.state))),
+        );
+        Ok(disk)
+    }
+
+    /// Advance a pending command by one instruction, moving its data when it
+    /// completes
+    pub fn advance(&self, memory: &mut Memory) {
+        lock(&self.state).advance(memory);
+    }
+
+    /// Whether the disk's interrupt line is raised: a command finished, the
+    /// guest has not acknowledged it and the interrupt is enabled
+    pub fn interrupt(&self) -> bool {
+        let state = lock(&self.state);
+        state.control & DISK_INTERRUPT_ENABLE != 0 && state.status & DISK_DONE != 0
+    }
+
+    pub fn busy(&self) -> bool {
+        lock(&self.state).pending.is_some()
+    }
+
+    /// Number of sectors on the disk
+    pub fn sectors(&self) -> usize {
+        lock(&self.state).sectors()
+    }
+
+    /// Contents of a sector
+    pub fn read_sector(&self, sector: usize) -> Vec<u8> {
+        let state = lock(&self.state);
+        state.storage[sector * SECTOR_SIZE..(sector + 1) * SECTOR_SIZE].to_vec()
+    }
+
+    /// Replace the start of a sector, without touching the backing file
+    pub fn write_sector(&self, sector: usize, bytes: &[u8]) {
+        let mut state = lock(&self.state);
+        let start = sector * SECTOR_SIZE;
+        state.storage[start..start + bytes.len().min(SECTOR_SIZE)]
+            .copy_from_slice(&bytes[..bytes.len().min(SECTOR_SIZE)]);
+    }
+
+    pub fn statistics(&self) -> DiskStatistics {
+        lock(&self.state).statistics
+    }
+}
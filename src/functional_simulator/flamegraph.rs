@@ -0,0 +1,120 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// flamegraph.rs
+//
+// This file contains the call stack profile the functional simulator can
+// record for flamegraphs. Calls and returns are followed as the program
+// runs, and every instruction, or every Nth one when sampling, is counted
+// against the stack of functions it ran in. The counts are written in the
+// collapsed-stack format read by flamegraph.pl, inferno and speedscope,
+// with each function named by the label at its entry point.
+
+use super::instructions::Instruction;
+use super::registers::Registers;
+use std::collections::{BTreeMap, HashMap};
+
+/// Counts of instructions by the call stack they ran in
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackProfile {
+    /// Count one instruction in every `every`
+    pub every: u64,
+    /// Instructions seen, counted or not
+    pub instructions: u64,
+    /// Entry points of the functions not yet returned from, innermost last
+    pub frames: Vec<u32>,
+    counts: HashMap<Vec<u32>, u64>,
+}
+
+impl StackProfile {
+    /// A profile of a program starting at `entry`, counting every
+    /// `every`th instruction
+    pub fn new(entry: u32, every: u64) -> Self {
+        Self {
+            every: every.max(1),
+            instructions: 0,
+            frames: vec![entry],
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Count `instruction`, about to execute at `pc`, and follow it if it
+    /// is a call or a `jr $ra`. A return from the outermost function is
+    /// ignored, so the entry point stays at the bottom of every stack.
+    pub fn record(&mut self, instruction: &Instruction, registers: &Registers, pc: u32) {
+        if self.instructions % self.every == 0 {
+            *self.counts.entry(self.frames.clone()).or_insert(0) += 1;
+        }
+        self.instructions += 1;
+        match *instruction {
+            Instruction::Jal { .. } | Instruction::Jalr { .. }
+                if instruction.get_destination_register().is_some() =>
+            {
+                self.frames.push(instruction.get_address(registers, pc));
+            },
+            Instruction::Jr { rs: 31 } if self.frames.len() > 1 => {
+                self.frames.pop();
+            },
+            _ => {},
+        }
+    }
+
+    /// Instructions counted
+    pub fn samples(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Different call stacks seen
+    pub fn stacks(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// One line per call stack, outermost function first, such as
+    /// `main;sort;swap 120`. Functions are named by the label at their
+    /// entry point, or by the nearest label before it as `label+0x10`, or
+    /// by their address. Lines are sorted so runs can be compared.
+    pub fn collapsed(&self, symbols: &BTreeMap<String, u32>) -> String {
+        let mut labels: BTreeMap<u32, &str> = BTreeMap::new();
+        for (name, &address) in symbols {
+            let preferred = labels.get(&address).map_or(true, |current| {
+                current.starts_with('_') && !name.starts_with('_')
+            });
+            if preferred {
+                labels.insert(address, name);
+            }
+        }
+        let name = |address: u32| match labels.range(..=address).next_back() {
+            Some((&start, label)) if start == address => label.to_string(),
+            Some((&start, label)) => format!("{}+0x{:X}", label, address - start),
+            None => format!("0x{:08X}", address),
+        };
+
+        let mut lines: BTreeMap<String, u64> = BTreeMap::new();
+        for (frames, count) in &self.counts {
+            let stack: Vec<String> = frames.iter().map(|&address| name(address)).collect();
+            *lines.entry(stack.join(";")).or_insert(0) += count;
+        }
+        lines
+            .into_iter()
+            .map(|(stack, count)| format!("{} {}\n", stack, count))
+            .collect()
+    }
+}
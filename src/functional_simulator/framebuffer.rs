@@ -0,0 +1,399 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// framebuffer.rs
+//
+// This file contains a memory-mapped bitmap framebuffer. Every pixel is a
+// word of the form 0x00RRGGBB, stored row by row from the framebuffer base.
+// A small control device reports the resolution and counts presented
+// frames, and can write every Nth presented frame to a PNG or BMP file. A
+// `Framebuffer` handle shared with the devices lets the host read pixels
+// and save images.
+
+use super::memory::{Memory, MemoryMappedDevice};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Default address of the first pixel
+pub const FRAMEBUFFER_BASE: u32 = 0xFF00_0000;
+/// Default address of the control registers
+pub const FRAMEBUFFER_CONTROL_BASE: u32 = 0xFFFF_3000;
+/// Largest framebuffer that fits between its base and the other devices
+pub const FRAMEBUFFER_MAX_BYTES: usize = 0x00FF_0000;
+
+// Control registers, as offsets from the control base
+pub const FRAMEBUFFER_WIDTH: u32 = 0x0; // Pixels per row, read-only
+pub const FRAMEBUFFER_HEIGHT: u32 = 0x4; // Rows, read-only
+pub const FRAMEBUFFER_FRAME: u32 = 0x8; // Writing presents a frame, reading counts them
+
+/// File formats frames can be saved in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Bmp,
+}
+
+impl ImageFormat {
+    /// The format named by a path's extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "bmp" => Some(ImageFormat::Bmp),
+            _ => None,
+        }
+    }
+
+    pub fn encode(self, width: usize, height: usize, pixels: &[u32]) -> Vec<u8> {
+        match self {
+            ImageFormat::Png => encode_png(width, height, pixels),
+            ImageFormat::Bmp => encode_bmp(width, height, pixels),
+        }
+    }
+}
+
+/// Resolution and addresses of the framebuffer, and where presented frames
+/// are written
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FramebufferConfig {
+    pub width: usize,
+    pub height: usize,
+    pub base: u32,
+    pub control_base: u32,
+    /// Frames are written next to this path, numbered by frame
+    pub dump: Option<PathBuf>,
+    /// Write every Nth presented frame
+    pub dump_every: u64,
+}
+
+impl FramebufferConfig {
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(
+            width > 0 && height > 0,
+            "Framebuffer must have at least one pixel"
+        );
+        assert!(
+            width * height * 4 <= FRAMEBUFFER_MAX_BYTES,
+            "Framebuffer must fit below the other devices"
+        );
+        Self {
+            width,
+            height,
+            base: FRAMEBUFFER_BASE,
+            control_base: FRAMEBUFFER_CONTROL_BASE,
+            dump: None,
+            dump_every: 1,
+        }
+    }
+
+    pub fn with_base(mut self, base: u32) -> Self {
+        self.base = base;
+        self
+    }
+
+    pub fn with_control_base(mut self, base: u32) -> Self {
+        self.control_base = base;
+        self
+    }
+
+    /// Write every `every`th presented frame to `path` with the frame number
+    /// added to the file name, as in `frame-0004.png` for `frame.png`
+    pub fn with_dump<P: AsRef<Path>>(mut self, path: P, every: u64) -> Self {
+        self.dump = Some(path.as_ref().to_path_buf());
+        self.dump_every = every.max(1);
+        self
+    }
+}
+
+#[derive(Debug)]
+struct FramebufferState {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+    frames: u64,
+    dump: Option<PathBuf>,
+    dump_every: u64,
+    written: Vec<PathBuf>,
+    dump_error: Option<String>,
+}
+
+impl FramebufferState {
+    fn present(&mut self) {
+        self.frames += 1;
+        let path = match self.dump.as_ref() {
+            Some(path) if self.frames % self.dump_every == 0 => numbered_path(path, self.frames),
+            _ => return,
+        };
+        match save(&path, self.width, self.height, &self.pixels) {
+            Ok(()) => self.written.push(path),
+            Err(error) => self.dump_error = Some(format!("{}: {}", path.display(), error)),
+        }
+    }
+}
+
+// `frame.png` becomes `frame-0004.png` for frame 4
+fn numbered_path(path: &Path, frame: u64) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map_or_else(|| "frame".into(), |stem| stem.to_string_lossy());
+    let extension = path.extension().map_or_else(String::new, |extension| {
+        format!(".{}", extension.to_string_lossy())
+    });
+    path.with_file_name(format!("{}-{:04}{}", stem, frame, extension))
+}
+
+fn save(path: &Path, width: usize, height: usize, pixels: &[u32]) -> io::Result<()> {
+    let format = ImageFormat::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "expected a .png or .bmp file name",
+        )
+    })?;
+    fs::write(path, format.encode(width, height, pixels))
+}
+
+fn lock(state: &Mutex<FramebufferState>) -> MutexGuard<'_, FramebufferState> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+struct PixelDevice(Arc<Mutex<FramebufferState>>);
+
+impl MemoryMappedDevice for PixelDevice {
+    fn read_byte(&self, offset: usize) -> u8 {
+        (self.read_word(offset & !3) >> ((offset % 4) * 8)) as u8
+    }
+
+    fn write_byte(&mut self, offset: usize, value: u8) {
+        let mut state = lock(&self.0);
+        if let Some(pixel) = state.pixels.get_mut(offset / 4) {
+            let shift = (offset % 4) * 8;
+            *pixel = (*pixel & !(0xFF << shift)) | (value as u32) << shift;
+        }
+    }
+
+    fn read_word(&self, offset: usize) -> u32 {
+        lock(&self.0).pixels.get(offset / 4).copied().unwrap_or(0)
+    }
+
+    fn write_word(&mut self, offset: usize, value: u32) {
+        if let Some(pixel) = lock(&self.0).pixels.get_mut(offset / 4) {
+            *pixel = value;
+        }
+    }
+
+    fn size(&self) -> usize {
+        lock(&self.0).pixels.len() * 4
+    }
+}
+
+struct ControlDevice(Arc<Mutex<FramebufferState>>);
+
+impl MemoryMappedDevice for ControlDevice {
+    fn read_byte(&self, offset: usize) -> u8 {
+        (self.read_word(offset & !3) >> ((offset % 4) * 8)) as u8
+    }
+
+    fn write_byte(&mut self, offset: usize, value: u8) {
+        self.write_word(offset & !3, value as u32);
+    }
+
+    fn read_word(&self, offset: usize) -> u32 {
+        let state = lock(&self.0);
+        match offset as u32 {
+            FRAMEBUFFER_WIDTH => state.width as u32,
+            FRAMEBUFFER_HEIGHT => state.height as u32,
+            FRAMEBUFFER_FRAME => state.frames as u32,
+            _ => 0,
+        }
+    }
+
+    fn write_word(&mut self, offset: usize, _value: u32) {
+        if offset as u32 == FRAMEBUFFER_FRAME {
+            lock(&self.0).present();
+        }
+    }
+}
+
+/// Host side of a framebuffer mapped into guest memory. Clones share the
+/// same pixels.
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    pub config: FramebufferConfig,
+    state: Arc<Mutex<FramebufferState>>,
+}
+
+impl Framebuffer {
+    /// Create the devices and map them into `memory`
+    pub fn attach(memory: &mut Memory, config: FramebufferConfig) -> Self {
+        let state = FramebufferState {
+            width: config.width,
+            height: config.height,
+            pixels: vec![0; config.width * config.height],
+            frames: 0,
+            dump: config.dump.clone(),
+            dump_every: config.dump_every,
+            written: Vec::new(),
+            dump_error: None,
+        };
+        let framebuffer = Self {
+            config,
+            state: Arc::new(Mutex::new(state)),
+        };
+        memory.map_device(
+            framebuffer.config.base as usize,
+            Box::new(PixelDevice(Arc::clone(&framebuffer.state))),
+        );
+        memory.map_device(
+            framebuffer.config.control_base as usize,
+            Box::new(ControlDevice(Arc::clone(&framebuffer.state))),
+        );
+        framebuffer
+    }
+
+    /// Colour of a pixel as 0x00RRGGBB
+    pub fn pixel(&self, x: usize, y: usize) -> u32 {
+        let state = lock(&self.state);
+        state.pixels[y * state.width + x]
+    }
+
+    /// Every pixel, row by row
+    pub fn pixels(&self) -> Vec<u32> {
+        lock(&self.state).pixels.clone()
+    }
+
+    /// Frames the guest has presented
+    pub fn frames(&self) -> u64 {
+        lock(&self.state).frames
+    }
+
+    /// Files written for presented frames, in order
+    pub fn written_frames(&self) -> Vec<PathBuf> {
+        lock(&self.state).written.clone()
+    }
+
+    /// The last error from writing a presented frame
+    pub fn dump_error(&self) -> Option<String> {
+        lock(&self.state).dump_error.clone()
+    }
+
+    /// Write the current pixels to a PNG or BMP file, chosen by extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let state = lock(&self.state);
+        save(path.as_ref(), state.width, state.height, &state.pixels)
+    }
+}
+
+/// Encode 0x00RRGGBB pixels as an uncompressed 8-bit RGB PNG
+pub fn encode_png(width: usize, height: usize, pixels: &[u32]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(height * (width * 3 + 1));
+    for row in pixels.chunks(width).take(height) {
+        raw.push(0); // No filter
+        for &pixel in row {
+            raw.extend_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]);
+        }
+    }
+
+    // A zlib stream of stored deflate blocks
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(0xFFFF).collect();
+    for (index, block) in blocks.iter().enumerate() {
+        zlib.push((index + 1 == blocks.len()) as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]); // 8-bit RGB, no interlace
+
+    let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(&mut png, b"IDAT", &zlib);
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// Encode 0x00RRGGBB pixels as a 24-bit BMP
+pub fn encode_bmp(width: usize, height: usize, pixels: &[u32]) -> Vec<u8> {
+    let row_size = (width * 3 + 3) & !3;
+    let image_size = row_size * height;
+    let mut bmp = Vec::with_capacity(54 + image_size);
+    bmp.extend_from_slice(b"BM");
+    bmp.extend_from_slice(&((54 + image_size) as u32).to_le_bytes());
+    bmp.extend_from_slice(&[0; 4]);
+    bmp.extend_from_slice(&54u32.to_le_bytes());
+    bmp.extend_from_slice(&40u32.to_le_bytes());
+    bmp.extend_from_slice(&(width as i32).to_le_bytes());
+    bmp.extend_from_slice(&(height as i32).to_le_bytes());
+    bmp.extend_from_slice(&1u16.to_le_bytes());
+    bmp.extend_from_slice(&24u16.to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // Uncompressed
+    bmp.extend_from_slice(&(image_size as u32).to_le_bytes());
+    bmp.extend_from_slice(&[0; 16]); // Resolution and palette
+                                     // Rows are stored bottom-up, each padded to four bytes
+    for row in pixels.chunks(width).take(height).rev() {
+        for &pixel in row {
+            bmp.extend_from_slice(&[pixel as u8, (pixel >> 8) as u8, (pixel >> 16) as u8]);
+        }
+        bmp.resize(bmp.len() + row_size - width * 3, 0);
+    }
+    bmp
+}
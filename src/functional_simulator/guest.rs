@@ -0,0 +1,226 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// guest.rs
+//
+// This file contains helpers that move data between host Rust code and
+// guest memory: NUL-terminated strings, fixed-size values and structs, and
+// arrays of them. Syscalls, host calls and the debugger use these instead
+// of walking memory a byte at a time. Multi-byte values are converted from
+// and to the byte order the guest uses.
+
+use super::memory::{MemError, Memory};
+use std::error::Error;
+use std::fmt;
+
+/// Why guest data could not be read or written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GuestError {
+    /// A byte of the data could not be accessed
+    Memory(MemError),
+    /// No NUL terminator within `limit` bytes of `address`
+    Unterminated { address: u32, limit: usize },
+    /// The string at `address` is not valid UTF-8
+    InvalidUtf8(u32),
+}
+
+impl fmt::Display for GuestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GuestError::Memory(e) => write!(f, "{}", e),
+            GuestError::Unterminated { address, limit } => write!(
+                f,
+                "string at 0x{:08X} is not terminated within {} bytes",
+                address, limit
+            ),
+            GuestError::InvalidUtf8(address) => {
+                write!(f, "string at 0x{:08X} is not valid UTF-8", address)
+            },
+        }
+    }
+}
+
+impl Error for GuestError {}
+
+impl From<MemError> for GuestError {
+    fn from(e: MemError) -> Self {
+        GuestError::Memory(e)
+    }
+}
+
+/// Byte order of multi-byte values in guest memory
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endian {
+    /// The order of the simulated MIPS machine
+    #[default]
+    Little,
+    Big,
+}
+
+/// The bytes of the NUL-terminated string at `address`, without the
+/// terminator. At most `limit` bytes are read looking for it.
+pub fn read_c_string(memory: &Memory, address: u32, limit: usize) -> Result<Vec<u8>, GuestError> {
+    let mut bytes = Vec::new();
+    for offset in 0..limit {
+        match memory.read_byte(address as usize + offset)? {
+            0 => return Ok(bytes),
+            byte => bytes.push(byte),
+        }
+    }
+    Err(GuestError::Unterminated { address, limit })
+}
+
+/// The NUL-terminated UTF-8 string at `address`
+pub fn read_string(memory: &Memory, address: u32, limit: usize) -> Result<String, GuestError> {
+    let bytes = read_c_string(memory, address, limit)?;
+    String::from_utf8(bytes).map_err(|_| GuestError::InvalidUtf8(address))
+}
+
+/// Write `bytes` and a NUL terminator to a buffer of `capacity` bytes at
+/// `address`, truncating the string to fit. Returns the length written,
+/// without the terminator; nothing is written to an empty buffer.
+pub fn write_c_string(
+    memory: &mut Memory,
+    address: u32,
+    bytes: &[u8],
+    capacity: usize,
+) -> Result<usize, GuestError> {
+    if capacity == 0 {
+        return Ok(0);
+    }
+    let length = bytes.len().min(capacity - 1);
+    let mut terminated = bytes[..length].to_vec();
+    terminated.push(0);
+    memory.write_block(address as usize, &terminated)?;
+    Ok(length)
+}
+
+/// A value with a fixed size and layout in guest memory. Structs implement
+/// it by decoding and encoding their fields in order, usually with the
+/// implementations for integers and arrays.
+pub trait GuestValue: Sized {
+    /// Size in guest memory, in bytes
+    const SIZE: usize;
+
+    /// Decode a value from exactly `SIZE` bytes
+    fn decode(bytes: &[u8], endian: Endian) -> Self;
+
+    /// Encode the value into exactly `SIZE` bytes
+    fn encode(&self, bytes: &mut [u8], endian: Endian);
+}
+
+macro_rules! guest_integer {
+    ($($ty:ty),*) => {$(
+        impl GuestValue for $ty {
+            const SIZE: usize = std::mem::size_of::<$ty>();
+
+            fn decode(bytes: &[u8], endian: Endian) -> Self {
+                let mut raw = [0; std::mem::size_of::<$ty>()];
+                raw.copy_from_slice(&bytes[..Self::SIZE]);
+                match endian {
+                    Endian::Little => <$ty>::from_le_bytes(raw),
+                    Endian::Big => <$ty>::from_be_bytes(raw),
+                }
+            }
+
+            fn encode(&self, bytes: &mut [u8], endian: Endian) {
+                let raw = match endian {
+                    Endian::Little => self.to_le_bytes(),
+                    Endian::Big => self.to_be_bytes(),
+                };
+                bytes[..Self::SIZE].copy_from_slice(&raw);
+            }
+        }
+    )*};
+}
+
+guest_integer!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+impl<T: GuestValue + Copy + Default, const N: usize> GuestValue for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+
+    fn decode(bytes: &[u8], endian: Endian) -> Self {
+        let mut values = [T::default(); N];
+        for (value, chunk) in values.iter_mut().zip(bytes.chunks(T::SIZE)) {
+            *value = T::decode(chunk, endian);
+        }
+        values
+    }
+
+    fn encode(&self, bytes: &mut [u8], endian: Endian) {
+        for (value, chunk) in self.iter().zip(bytes.chunks_mut(T::SIZE)) {
+            value.encode(chunk, endian);
+        }
+    }
+}
+
+/// Read a value from guest memory at `address`
+pub fn read_value<T: GuestValue>(
+    memory: &Memory,
+    address: u32,
+    endian: Endian,
+) -> Result<T, GuestError> {
+    let bytes = memory.read_block(address as usize, T::SIZE)?;
+    Ok(T::decode(&bytes, endian))
+}
+
+/// Write a value to guest memory at `address`
+pub fn write_value<T: GuestValue>(
+    memory: &mut Memory,
+    address: u32,
+    value: &T,
+    endian: Endian,
+) -> Result<(), GuestError> {
+    let mut bytes = vec![0; T::SIZE];
+    value.encode(&mut bytes, endian);
+    memory.write_block(address as usize, &bytes)?;
+    Ok(())
+}
+
+/// Read `count` consecutive values starting at `address`
+pub fn read_array<T: GuestValue>(
+    memory: &Memory,
+    address: u32,
+    count: usize,
+    endian: Endian,
+) -> Result<Vec<T>, GuestError> {
+    let bytes = memory.read_block(address as usize, T::SIZE * count)?;
+    Ok(bytes
+        .chunks(T::SIZE.max(1))
+        .take(count)
+        .map(|chunk| T::decode(chunk, endian))
+        .collect())
+}
+
+/// Write `values` one after another starting at `address`
+pub fn write_array<T: GuestValue>(
+    memory: &mut Memory,
+    address: u32,
+    values: &[T],
+    endian: Endian,
+) -> Result<(), GuestError> {
+    let mut bytes = vec![0; T::SIZE * values.len()];
+    for (value, chunk) in values.iter().zip(bytes.chunks_mut(T::SIZE.max(1))) {
+        value.encode(chunk, endian);
+    }
+    memory.write_block(address as usize, &bytes)?;
+    Ok(())
+}
@@ -23,17 +23,106 @@
 //
 // This file contains the instruction definitions and execution logic for the
 // MIPS functional simulator. It defines the Instruction enum, which represents
-// all supported MIPS instructions, and the execute method, which implements
-// the behavior of each instruction.
+// all supported MIPS instructions, the execute method, which implements
+// the behavior of each instruction, and `encode`, which turns an instruction
+// back into its machine word.
 
-use super::memory::Memory;
+use super::cp0::{return_from_exception, CP0_SELECT_SHIFT};
+use super::memory::{MemError, Memory};
 use super::registers::Registers;
+use super::simulator::Exception;
 use crate::utils::syscall::handle_syscall;
 
-#[derive(Debug, Clone)]
+/// Where execution continues after an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NextPc {
+    /// Fall through to the following instruction
+    Next,
+    /// Branch by a byte offset from the following instruction (PC + 4)
+    Relative(i32),
+    /// Jump to an absolute address
+    Absolute(u32),
+    /// The instruction raised an exception and did not complete
+    Trap(Exception),
+    /// The program asked to stop, for example through the exit syscall
+    Halt,
+}
+
+impl NextPc {
+    /// The address to continue at after the instruction at `pc`, or `None`
+    /// if execution does not continue
+    pub fn target(self, pc: u32) -> Option<u32> {
+        match self {
+            NextPc::Next => Some(pc.wrapping_add(4)),
+            NextPc::Relative(offset) => Some(pc.wrapping_add(4).wrapping_add(offset as u32)),
+            NextPc::Absolute(address) => Some(address),
+            NextPc::Trap(_) | NextPc::Halt => None,
+        }
+    }
+}
+
+/// Byte displacement of a PC-relative branch with a 16-bit word offset,
+/// counted from the following instruction
+pub fn branch_offset(offset: i16) -> i32 {
+    (offset as i32) << 2
+}
+
+/// Address a taken PC-relative branch at `pc` continues at
+pub fn branch_target(pc: u32, offset: i16) -> u32 {
+    pc.wrapping_add(4)
+        .wrapping_add(branch_offset(offset) as u32)
+}
+
+// Result of a trap instruction whose condition evaluated to `condition`
+fn trap_if(condition: bool) -> NextPc {
+    if condition {
+        NextPc::Trap(Exception::Trap)
+    } else {
+        NextPc::Next
+    }
+}
+
+// Report a load or store that memory refused and raise the exception
+fn memory_fault(error: MemError) -> NextPc {
+    println!("Memory access exception: {}", error);
+    NextPc::Trap(Exception::MemoryAccessViolation)
+}
+
+/// Address a load or store of `offset(base)` accesses, or `None` if adding
+/// the sign-extended offset carries past either end of the 32-bit address
+/// space, as `lw $t0, -4($zero)` does
+pub fn effective_address(base_value: u32, offset: i16) -> Option<u32> {
+    let address = base_value as i64 + offset as i64;
+    if (0..=u32::MAX as i64).contains(&address) {
+        Some(address as u32)
+    } else {
+        None
+    }
+}
+
+// Effective address of a load or store, or the address error it raises
+fn checked_address(registers: &Registers, base: u32, offset: i16) -> Result<u32, NextPc> {
+    let base_value = registers.read(base);
+    effective_address(base_value, offset).ok_or_else(|| {
+        println!(
+            "Address error exception: {}(0x{:08X}) wraps around the address space",
+            offset, base_value
+        );
+        NextPc::Trap(Exception::AddressError)
+    })
+}
+
+/// Address a J-type jump at `pc` continues at: the 26-bit word index
+/// replaces the low bits of the address of the following instruction
+pub fn jump_target(pc: u32, target: u32) -> u32 {
+    (pc.wrapping_add(4) & 0xF000_0000) | ((target & 0x03FF_FFFF) << 2)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Instruction {
     // Original R-type instructions
     Add { rd: u32, rs: u32, rt: u32 },
+    Addu { rd: u32, rs: u32, rt: u32 },
     Sub { rd: u32, rs: u32, rt: u32 },
     And { rd: u32, rs: u32, rt: u32 },
     Or { rd: u32, rs: u32, rt: u32 },
@@ -68,6 +157,7 @@ pub enum Instruction {
     Divu { rs: u32, rt: u32 },
     Xor { rd: u32, rs: u32, rt: u32 },
     Nor { rd: u32, rs: u32, rt: u32 },
+    Sltu { rd: u32, rs: u32, rt: u32 },
     Mfhi { rd: u32 },
     Mthi { rs: u32 },
     Mtlo { rs: u32 },
@@ -84,6 +174,24 @@ pub enum Instruction {
     Sb { rt: u32, base: u32, offset: i16 },
     Sh { rt: u32, base: u32, offset: i16 },
 
+    // Atomic read-modify-write instructions
+    Ll { rt: u32, base: u32, offset: i16 },
+    Sc { rt: u32, base: u32, offset: i16 },
+
+    // Trap instructions
+    Teq { rs: u32, rt: u32, code: u32 },
+    Tne { rs: u32, rt: u32, code: u32 },
+    Tge { rs: u32, rt: u32, code: u32 },
+    Tgeu { rs: u32, rt: u32, code: u32 },
+    Tlt { rs: u32, rt: u32, code: u32 },
+    Tltu { rs: u32, rt: u32, code: u32 },
+    Teqi { rs: u32, imm: i16 },
+    Tnei { rs: u32, imm: i16 },
+    Tgei { rs: u32, imm: i16 },
+    Tgeiu { rs: u32, imm: i16 },
+    Tlti { rs: u32, imm: i16 },
+    Tltiu { rs: u32, imm: i16 },
+
     // Branch instructions
     Bgtz { rs: u32, offset: i16 },
     Blez { rs: u32, offset: i16 },
@@ -110,7 +218,15 @@ pub enum Instruction {
     BC1T { offset: i16 },
     BC1F { offset: i16 },
 
+    // System control coprocessor (CP0) instructions
+    Mfc0 { rt: u32, rd: u32 },
+    Mtc0 { rt: u32, rd: u32 },
+    Eret,
+    Rdpgpr { rd: u32, rt: u32 },
+    Wrpgpr { rd: u32, rt: u32 },
+
     // Special instructions
+    Rdhwr { rt: u32, rd: u32 },
     Syscall,
     Break { code: u32 },
     Nop,
@@ -119,55 +235,61 @@ pub enum Instruction {
 }
 
 impl Instruction {
-    pub fn execute(&self, registers: &mut Registers, memory: &mut Memory) -> Option<u32> {
+    pub fn execute(&self, registers: &mut Registers, memory: &mut Memory) -> NextPc {
         match self {
             // Original R-type instructions
-            Instruction::Add { rd, rs, rt } => {
+            // Neither addition traps on overflow
+            Instruction::Add { rd, rs, rt } | Instruction::Addu { rd, rs, rt } => {
                 let rs_value = registers.read(*rs);
                 let rt_value = registers.read(*rt);
                 let result = rs_value.wrapping_add(rt_value);
                 registers.write(*rd, result);
-                None
+                NextPc::Next
             },
             Instruction::Sub { rd, rs, rt } => {
                 let rs_value = registers.read(*rs);
                 let rt_value = registers.read(*rt);
                 let result = rs_value.wrapping_sub(rt_value);
                 registers.write(*rd, result);
-                None
+                NextPc::Next
             },
             Instruction::And { rd, rs, rt } => {
                 let rs_value = registers.read(*rs);
                 let rt_value = registers.read(*rt);
                 let result = rs_value & rt_value;
                 registers.write(*rd, result);
-                None
+                NextPc::Next
             },
             Instruction::Or { rd, rs, rt } => {
                 let rs_value = registers.read(*rs);
                 let rt_value = registers.read(*rt);
                 let result = rs_value | rt_value;
                 registers.write(*rd, result);
-                None
+                NextPc::Next
             },
             Instruction::Slt { rd, rs, rt } => {
                 let rs_value = registers.read(*rs) as i32;
                 let rt_value = registers.read(*rt) as i32;
                 let result = (rs_value < rt_value) as u32;
                 registers.write(*rd, result);
-                None
+                NextPc::Next
+            },
+            Instruction::Sltu { rd, rs, rt } => {
+                let result = (registers.read(*rs) < registers.read(*rt)) as u32;
+                registers.write(*rd, result);
+                NextPc::Next
             },
             Instruction::Sll { rd, rt, shamt } => {
                 let rt_value = registers.read(*rt);
                 let result = rt_value << shamt;
                 registers.write(*rd, result);
-                None
+                NextPc::Next
             },
             Instruction::Srl { rd, rt, shamt } => {
                 let rt_value = registers.read(*rt);
                 let result = rt_value >> shamt;
                 registers.write(*rd, result);
-                None
+                NextPc::Next
             },
 
             // Original I-type instructions
@@ -175,11 +297,13 @@ impl Instruction {
                 let rs_value = registers.read(*rs);
                 let result = rs_value.wrapping_add(*imm as u32);
                 registers.write(*rt, result);
-                None
+                NextPc::Next
             },
             Instruction::Lw { rt, base, offset } => {
-                let base_value = registers.read(*base);
-                let address = base_value.wrapping_add(*offset as u32);
+                let address = match checked_address(registers, *base, *offset) {
+                    Ok(address) => address,
+                    Err(trap) => return trap,
+                };
 
                 // Check alignment - MIPS requires word accesses to be aligned
                 if address % 4 != 0 {
@@ -187,26 +311,22 @@ impl Instruction {
                         "Memory alignment exception: address 0x{:08x} not aligned for word access",
                         address
                     );
-                    return Some(address); // Return address causing the exception
+                    return NextPc::Trap(Exception::MemoryAccessViolation);
                 }
 
                 match memory.read_word(address as usize) {
-                    Some(value) => {
+                    Ok(value) => {
                         registers.write(*rt, value);
-                        None
-                    },
-                    None => {
-                        println!(
-                            "Memory access exception: address 0x{:08x} out of bounds",
-                            address
-                        );
-                        Some(address)
+                        NextPc::Next
                     },
+                    Err(error) => memory_fault(error),
                 }
             },
             Instruction::Sw { rt, base, offset } => {
-                let base_value = registers.read(*base);
-                let address = base_value.wrapping_add(*offset as u32);
+                let address = match checked_address(registers, *base, *offset) {
+                    Ok(address) => address,
+                    Err(trap) => return trap,
+                };
 
                 // Check alignment - MIPS requires word accesses to be aligned
                 if address % 4 != 0 {
@@ -214,18 +334,13 @@ impl Instruction {
                         "Memory alignment exception: address 0x{:08x} not aligned for word access",
                         address
                     );
-                    return Some(address); // Return address causing the exception
+                    return NextPc::Trap(Exception::MemoryAccessViolation);
                 }
 
                 let value = registers.read(*rt);
-                if memory.write_word(address as usize, value) {
-                    None
-                } else {
-                    println!(
-                        "Memory access exception: address 0x{:08x} out of bounds",
-                        address
-                    );
-                    Some(address)
+                match memory.write_word(address as usize, value) {
+                    Ok(()) => NextPc::Next,
+                    Err(error) => memory_fault(error),
                 }
             },
             Instruction::Beq { rs, rt, offset } => {
@@ -237,30 +352,30 @@ impl Instruction {
                         "BEQ: (${} == ${}): {} == {} - Branch taken",
                         rs, rt, rs_value, rt_value
                     );
-                    Some((*offset as u32) << 2)
+                    NextPc::Relative(branch_offset(*offset))
                 } else {
                     println!(
                         "BEQ: (${} == ${}): {} != {} - Branch NOT taken",
                         rs, rt, rs_value, rt_value
                     );
-                    None
+                    NextPc::Next
                 }
             },
 
             // Original J-type instruction
-            Instruction::J { target } => Some(*target),
+            Instruction::J { target } => NextPc::Absolute(jump_target(registers.pc, *target)),
 
             // Previously implemented instructions
             Instruction::Lui { rt, imm } => {
                 let value = (*imm as u32) << 16;
                 registers.write(*rt, value);
-                None
+                NextPc::Next
             },
             Instruction::Ori { rt, rs, imm } => {
                 let rs_value = registers.read(*rs);
                 let result = rs_value | (*imm as u32);
                 registers.write(*rt, result);
-                None
+                NextPc::Next
             },
             Instruction::Mult { rs, rt } => {
                 let rs_value = registers.read(*rs) as i32;
@@ -269,31 +384,31 @@ impl Instruction {
 
                 registers.set_lo((result & 0xFFFFFFFF) as u32);
                 registers.set_hi(((result >> 32) & 0xFFFFFFFF) as u32);
-                None
+                NextPc::Next
             },
             Instruction::Mflo { rd } => {
                 let lo_value = registers.get_lo();
                 registers.write(*rd, lo_value);
-                None
+                NextPc::Next
             },
             Instruction::Addiu { rt, rs, imm } => {
                 let rs_value = registers.read(*rs);
                 let result = rs_value.wrapping_add(*imm as u32);
                 registers.write(*rt, result);
-                None
+                NextPc::Next
             },
             Instruction::Bne { rs, rt, offset } => {
                 let rs_value = registers.read(*rs);
                 let rt_value = registers.read(*rt);
                 if rs_value != rt_value {
-                    Some((*offset as u32) << 2)
+                    NextPc::Relative(branch_offset(*offset))
                 } else {
-                    None
+                    NextPc::Next
                 }
             },
             Instruction::Jr { rs } => {
                 let target_address = registers.read(*rs);
-                Some(target_address)
+                NextPc::Absolute(target_address)
             },
 
             // New R-type instructions implementations
@@ -301,28 +416,28 @@ impl Instruction {
                 let rt_value = registers.read(*rt) as i32;
                 let result = rt_value >> shamt;
                 registers.write(*rd, result as u32);
-                None
+                NextPc::Next
             },
             Instruction::Sllv { rd, rt, rs } => {
                 let rt_value = registers.read(*rt);
                 let rs_value = registers.read(*rs) & 0x1F; // Only use lower 5 bits of rs
                 let result = rt_value << rs_value;
                 registers.write(*rd, result);
-                None
+                NextPc::Next
             },
             Instruction::Srlv { rd, rt, rs } => {
                 let rt_value = registers.read(*rt);
                 let rs_value = registers.read(*rs) & 0x1F; // Only use lower 5 bits of rs
                 let result = rt_value >> rs_value;
                 registers.write(*rd, result);
-                None
+                NextPc::Next
             },
             Instruction::Srav { rd, rt, rs } => {
                 let rt_value = registers.read(*rt) as i32;
                 let rs_value = registers.read(*rs) & 0x1F; // Only use lower 5 bits of rs
                 let result = rt_value >> rs_value;
                 registers.write(*rd, result as u32);
-                None
+                NextPc::Next
             },
             Instruction::Div { rs, rt } => {
                 let rs_value = registers.read(*rs) as i32;
@@ -340,7 +455,7 @@ impl Instruction {
                     registers.set_lo(quotient as u32);
                     registers.set_hi(remainder as u32);
                 }
-                None
+                NextPc::Next
             },
             Instruction::Divu { rs, rt } => {
                 let rs_value = registers.read(*rs);
@@ -356,36 +471,36 @@ impl Instruction {
                     registers.set_lo(quotient);
                     registers.set_hi(remainder);
                 }
-                None
+                NextPc::Next
             },
             Instruction::Xor { rd, rs, rt } => {
                 let rs_value = registers.read(*rs);
                 let rt_value = registers.read(*rt);
                 let result = rs_value ^ rt_value;
                 registers.write(*rd, result);
-                None
+                NextPc::Next
             },
             Instruction::Nor { rd, rs, rt } => {
                 let rs_value = registers.read(*rs);
                 let rt_value = registers.read(*rt);
                 let result = !(rs_value | rt_value);
                 registers.write(*rd, result);
-                None
+                NextPc::Next
             },
             Instruction::Mfhi { rd } => {
                 let hi_value = registers.get_hi();
                 registers.write(*rd, hi_value);
-                None
+                NextPc::Next
             },
             Instruction::Mthi { rs } => {
                 let rs_value = registers.read(*rs);
                 registers.set_hi(rs_value);
-                None
+                NextPc::Next
             },
             Instruction::Mtlo { rs } => {
                 let rs_value = registers.read(*rs);
                 registers.set_lo(rs_value);
-                None
+                NextPc::Next
             },
 
             // New I-type instruction implementations
@@ -393,171 +508,231 @@ impl Instruction {
                 let rs_value = registers.read(*rs);
                 let result = rs_value & (*imm as u32);
                 registers.write(*rt, result);
-                None
+                NextPc::Next
             },
             Instruction::Xori { rt, rs, imm } => {
                 let rs_value = registers.read(*rs);
                 let result = rs_value ^ (*imm as u32);
                 registers.write(*rt, result);
-                None
+                NextPc::Next
             },
             Instruction::Slti { rt, rs, imm } => {
                 let rs_value = registers.read(*rs) as i32;
                 let imm_value = *imm as i32;
                 let result = (rs_value < imm_value) as u32;
                 registers.write(*rt, result);
-                None
+                NextPc::Next
             },
             Instruction::Sltiu { rt, rs, imm } => {
                 let rs_value = registers.read(*rs);
                 let imm_value = *imm as u32;
                 let result = (rs_value < imm_value) as u32;
                 registers.write(*rt, result);
-                None
+                NextPc::Next
             },
             Instruction::Lb { rt, base, offset } => {
-                let base_value = registers.read(*base);
-                let address = base_value.wrapping_add(*offset as u32);
+                let address = match checked_address(registers, *base, *offset) {
+                    Ok(address) => address,
+                    Err(trap) => return trap,
+                };
                 match memory.read_byte(address as usize) {
-                    Some(value) => {
+                    Ok(value) => {
                         // Sign extend
                         let sign_extended = ((value as i8) as i32) as u32;
                         registers.write(*rt, sign_extended);
-                        None
+                        NextPc::Next
                     },
-                    None => Some(address),
+                    Err(error) => memory_fault(error),
                 }
             },
             Instruction::Lbu { rt, base, offset } => {
-                let base_value = registers.read(*base);
-                let address = base_value.wrapping_add(*offset as u32);
+                let address = match checked_address(registers, *base, *offset) {
+                    Ok(address) => address,
+                    Err(trap) => return trap,
+                };
                 match memory.read_byte(address as usize) {
-                    Some(value) => {
+                    Ok(value) => {
                         // Zero extend
                         registers.write(*rt, value as u32);
-                        None
+                        NextPc::Next
                     },
-                    None => Some(address),
+                    Err(error) => memory_fault(error),
                 }
             },
             Instruction::Lh { rt, base, offset } => {
-                let base_value = registers.read(*base);
-                let address = base_value.wrapping_add(*offset as u32);
+                let address = match checked_address(registers, *base, *offset) {
+                    Ok(address) => address,
+                    Err(trap) => return trap,
+                };
 
                 // Check alignment - MIPS requires halfword accesses to be aligned
                 if address % 2 != 0 {
                     println!("Memory alignment exception: address 0x{:08x} not aligned for halfword access", address);
-                    return Some(address); // Return address causing the exception
+                    return NextPc::Trap(Exception::MemoryAccessViolation);
                 }
 
-                if address as usize + 1 < memory.size {
-                    let low_byte = memory.read_byte(address as usize).unwrap_or(0);
-                    let high_byte = memory.read_byte((address as usize) + 1).unwrap_or(0);
-                    let halfword = ((high_byte as u16) << 8) | (low_byte as u16);
-                    // Sign extend
-                    let sign_extended = ((halfword as i16) as i32) as u32;
-                    registers.write(*rt, sign_extended);
-                    None
-                } else {
-                    println!(
-                        "Memory access exception: address 0x{:08x} out of bounds",
-                        address
-                    );
-                    Some(address)
+                match memory.read_halfword(address as usize) {
+                    Ok(halfword) => {
+                        // Sign extend
+                        let sign_extended = ((halfword as i16) as i32) as u32;
+                        registers.write(*rt, sign_extended);
+                        NextPc::Next
+                    },
+                    Err(error) => memory_fault(error),
                 }
             },
             Instruction::Lhu { rt, base, offset } => {
-                let base_value = registers.read(*base);
-                let address = base_value.wrapping_add(*offset as u32);
+                let address = match checked_address(registers, *base, *offset) {
+                    Ok(address) => address,
+                    Err(trap) => return trap,
+                };
 
                 // Check alignment - MIPS requires halfword accesses to be aligned
                 if address % 2 != 0 {
                     println!("Memory alignment exception: address 0x{:08x} not aligned for halfword access", address);
-                    return Some(address); // Return address causing the exception
+                    return NextPc::Trap(Exception::MemoryAccessViolation);
                 }
 
-                if address as usize + 1 < memory.size {
-                    let low_byte = memory.read_byte(address as usize).unwrap_or(0);
-                    let high_byte = memory.read_byte((address as usize) + 1).unwrap_or(0);
-                    let halfword = ((high_byte as u16) << 8) | (low_byte as u16);
-                    // Zero extend
-                    registers.write(*rt, halfword as u32);
-                    None
-                } else {
-                    println!(
-                        "Memory access exception: address 0x{:08x} out of bounds",
-                        address
-                    );
-                    Some(address)
+                match memory.read_halfword(address as usize) {
+                    Ok(halfword) => {
+                        // Zero extend
+                        registers.write(*rt, halfword as u32);
+                        NextPc::Next
+                    },
+                    Err(error) => memory_fault(error),
                 }
             },
             Instruction::Sb { rt, base, offset } => {
-                let base_value = registers.read(*base);
-                let address = base_value.wrapping_add(*offset as u32);
+                let address = match checked_address(registers, *base, *offset) {
+                    Ok(address) => address,
+                    Err(trap) => return trap,
+                };
                 let value = registers.read(*rt) as u8;
-                if memory.write_byte(address as usize, value) {
-                    None
-                } else {
-                    println!(
-                        "Memory access exception: address 0x{:08x} out of bounds",
-                        address
-                    );
-                    Some(address)
+                match memory.write_byte(address as usize, value) {
+                    Ok(()) => NextPc::Next,
+                    Err(error) => memory_fault(error),
                 }
             },
             Instruction::Sh { rt, base, offset } => {
-                let base_value = registers.read(*base);
-                let address = base_value.wrapping_add(*offset as u32);
+                let address = match checked_address(registers, *base, *offset) {
+                    Ok(address) => address,
+                    Err(trap) => return trap,
+                };
 
                 // Check alignment - MIPS requires halfword accesses to be aligned
                 if address % 2 != 0 {
                     println!("Memory alignment exception: address 0x{:08x} not aligned for halfword access", address);
-                    return Some(address); // Return address causing the exception
+                    return NextPc::Trap(Exception::MemoryAccessViolation);
                 }
 
                 let value = registers.read(*rt) as u16;
-                if address as usize + 1 < memory.size {
-                    let low_byte = value as u8;
-                    let high_byte = (value >> 8) as u8;
-                    memory.write_byte(address as usize, low_byte);
-                    memory.write_byte((address as usize) + 1, high_byte);
-                    None
+                match memory.write_halfword(address as usize, value) {
+                    Ok(()) => NextPc::Next,
+                    Err(error) => memory_fault(error),
+                }
+            },
+
+            Instruction::Ll { rt, base, offset } => {
+                let address = match checked_address(registers, *base, *offset) {
+                    Ok(address) => address,
+                    Err(trap) => return trap,
+                };
+
+                if address % 4 != 0 {
+                    println!(
+                        "Memory alignment exception: address 0x{:08x} not aligned for word access",
+                        address
+                    );
+                    return NextPc::Trap(Exception::MemoryAccessViolation);
+                }
+
+                match memory.read_word(address as usize) {
+                    Ok(value) => {
+                        registers.write(*rt, value);
+                        registers.ll_bit = true;
+                        NextPc::Next
+                    },
+                    Err(error) => memory_fault(error),
+                }
+            },
+            Instruction::Sc { rt, base, offset } => {
+                let address = match checked_address(registers, *base, *offset) {
+                    Ok(address) => address,
+                    Err(trap) => return trap,
+                };
+
+                if address % 4 != 0 {
+                    println!(
+                        "Memory alignment exception: address 0x{:08x} not aligned for word access",
+                        address
+                    );
+                    return NextPc::Trap(Exception::MemoryAccessViolation);
+                }
+
+                // The store only happens if nothing broke the link since the ll
+                if registers.ll_bit {
+                    let value = registers.read(*rt);
+                    if let Err(error) = memory.write_word(address as usize, value) {
+                        return memory_fault(error);
+                    }
+                    registers.write(*rt, 1);
                 } else {
-                    Some(address)
+                    registers.write(*rt, 0);
                 }
+                registers.ll_bit = false;
+                NextPc::Next
+            },
+
+            // Trap instructions raise an exception when their condition holds
+            Instruction::Teq { rs, rt, .. } => trap_if(registers.read(*rs) == registers.read(*rt)),
+            Instruction::Tne { rs, rt, .. } => trap_if(registers.read(*rs) != registers.read(*rt)),
+            Instruction::Tge { rs, rt, .. } => {
+                trap_if(registers.read(*rs) as i32 >= registers.read(*rt) as i32)
             },
+            Instruction::Tgeu { rs, rt, .. } => trap_if(registers.read(*rs) >= registers.read(*rt)),
+            Instruction::Tlt { rs, rt, .. } => {
+                trap_if((registers.read(*rs) as i32) < registers.read(*rt) as i32)
+            },
+            Instruction::Tltu { rs, rt, .. } => trap_if(registers.read(*rs) < registers.read(*rt)),
+            Instruction::Teqi { rs, imm } => trap_if(registers.read(*rs) as i32 == *imm as i32),
+            Instruction::Tnei { rs, imm } => trap_if(registers.read(*rs) as i32 != *imm as i32),
+            Instruction::Tgei { rs, imm } => trap_if(registers.read(*rs) as i32 >= *imm as i32),
+            // The unsigned forms compare against the sign-extended immediate
+            Instruction::Tgeiu { rs, imm } => trap_if(registers.read(*rs) >= *imm as i32 as u32),
+            Instruction::Tlti { rs, imm } => trap_if((registers.read(*rs) as i32) < *imm as i32),
+            Instruction::Tltiu { rs, imm } => trap_if(registers.read(*rs) < *imm as i32 as u32),
 
             // Branch instructions
             Instruction::Bgtz { rs, offset } => {
                 let rs_value = registers.read(*rs) as i32;
                 if rs_value > 0 {
-                    Some(*offset as u32)
+                    NextPc::Relative(branch_offset(*offset))
                 } else {
-                    None
+                    NextPc::Next
                 }
             },
             Instruction::Blez { rs, offset } => {
                 let rs_value = registers.read(*rs) as i32;
                 if rs_value <= 0 {
-                    Some(*offset as u32)
+                    NextPc::Relative(branch_offset(*offset))
                 } else {
-                    None
+                    NextPc::Next
                 }
             },
             Instruction::Bltz { rs, offset } => {
                 let rs_value = registers.read(*rs) as i32;
                 if rs_value < 0 {
-                    Some(*offset as u32)
+                    NextPc::Relative(branch_offset(*offset))
                 } else {
-                    None
+                    NextPc::Next
                 }
             },
             Instruction::Bgez { rs, offset } => {
                 let rs_value = registers.read(*rs) as i32;
                 if rs_value >= 0 {
-                    Some(*offset as u32)
+                    NextPc::Relative(branch_offset(*offset))
                 } else {
-                    None
+                    NextPc::Next
                 }
             },
 
@@ -565,13 +740,13 @@ impl Instruction {
             Instruction::Jal { target } => {
                 // Store return address in $ra (register 31)
                 registers.write(31, registers.pc + 4);
-                Some(*target)
+                NextPc::Absolute(jump_target(registers.pc, *target))
             },
             Instruction::Jalr { rd, rs } => {
                 // Store return address in rd
                 registers.write(*rd, registers.pc + 4);
                 // Jump to address in rs
-                Some(registers.read(*rs))
+                NextPc::Absolute(registers.read(*rs))
             },
 
             // Floating-point instructions
@@ -580,21 +755,21 @@ impl Instruction {
                 let ft_value = registers.read_float(*ft);
                 let result = fs_value + ft_value;
                 registers.write_float(*fd, result);
-                None
+                NextPc::Next
             },
             Instruction::SubS { fd, fs, ft } => {
                 let fs_value = registers.read_float(*fs);
                 let ft_value = registers.read_float(*ft);
                 let result = fs_value - ft_value;
                 registers.write_float(*fd, result);
-                None
+                NextPc::Next
             },
             Instruction::MulS { fd, fs, ft } => {
                 let fs_value = registers.read_float(*fs);
                 let ft_value = registers.read_float(*ft);
                 let result = fs_value * ft_value;
                 registers.write_float(*fd, result);
-                None
+                NextPc::Next
             },
             Instruction::DivS { fd, fs, ft } => {
                 let fs_value = registers.read_float(*fs);
@@ -614,35 +789,35 @@ impl Instruction {
                     // Set appropriate flags in FCSR
                     registers.fcsr |= 0x8; // Division by zero flag
                 }
-                None
+                NextPc::Next
             },
             Instruction::AbsS { fd, fs } => {
                 let fs_value = registers.read_float(*fs);
                 let result = fs_value.abs();
                 registers.write_float(*fd, result);
-                None
+                NextPc::Next
             },
             Instruction::NegS { fd, fs } => {
                 let fs_value = registers.read_float(*fs);
                 let result = -fs_value;
                 registers.write_float(*fd, result);
-                None
+                NextPc::Next
             },
             Instruction::MovS { fd, fs } => {
                 let fs_value = registers.read_float(*fs);
                 registers.write_float(*fd, fs_value);
-                None
+                NextPc::Next
             },
             Instruction::CvtSW { fd, fs } => {
                 let fs_value = registers.read(*fs) as i32 as f32;
                 registers.write_float(*fd, fs_value);
-                None
+                NextPc::Next
             },
             Instruction::CvtWS { fd, fs } => {
                 let fs_value = registers.read_float(*fs);
                 let result = fs_value as i32 as u32;
                 registers.write(*fd, result);
-                None
+                NextPc::Next
             },
             Instruction::CmpS { fs, ft, cond } => {
                 let fs_value = registers.read_float(*fs);
@@ -663,62 +838,99 @@ impl Instruction {
                 } else {
                     registers.fcsr &= !0x800000; // Clear condition bit
                 }
-                None
+                NextPc::Next
             },
             Instruction::LwC1 { ft, base, offset } => {
-                let base_value = registers.read(*base);
-                let address = base_value.wrapping_add(*offset as u32);
+                let address = match checked_address(registers, *base, *offset) {
+                    Ok(address) => address,
+                    Err(trap) => return trap,
+                };
                 match memory.read_word(address as usize) {
-                    Some(value) => {
+                    Ok(value) => {
                         // Convert raw bits to float
                         let float_value = f32::from_bits(value);
                         registers.write_float(*ft, float_value);
-                        None
+                        NextPc::Next
                     },
-                    None => Some(address),
+                    Err(error) => memory_fault(error),
                 }
             },
             Instruction::SwC1 { ft, base, offset } => {
-                let base_value = registers.read(*base);
-                let address = base_value.wrapping_add(*offset as u32);
+                let address = match checked_address(registers, *base, *offset) {
+                    Ok(address) => address,
+                    Err(trap) => return trap,
+                };
                 let value = registers.read_float(*ft).to_bits();
-                if memory.write_word(address as usize, value) {
-                    None
-                } else {
-                    Some(address)
+                match memory.write_word(address as usize, value) {
+                    Ok(()) => NextPc::Next,
+                    Err(error) => memory_fault(error),
                 }
             },
             Instruction::BC1T { offset } => {
                 // Branch if FP condition flag is true (bit 23 of fcsr)
                 if (registers.fcsr & 0x800000) != 0 {
-                    Some(*offset as u32)
+                    NextPc::Relative(branch_offset(*offset))
                 } else {
-                    None
+                    NextPc::Next
                 }
             },
             Instruction::BC1F { offset } => {
                 // Branch if FP condition flag is false (bit 23 of fcsr)
                 if (registers.fcsr & 0x800000) == 0 {
-                    Some(*offset as u32)
+                    NextPc::Relative(branch_offset(*offset))
                 } else {
-                    None
+                    NextPc::Next
                 }
             },
 
+            // CP0 instructions
+            Instruction::Mfc0 { rt, rd } => {
+                let value = registers.read_cp0(*rd);
+                registers.write(*rt, value);
+                NextPc::Next
+            },
+            Instruction::Mtc0 { rt, rd } => {
+                let value = registers.read(*rt);
+                registers.write_cp0(*rd, value);
+                NextPc::Next
+            },
+            Instruction::Eret => NextPc::Absolute(return_from_exception(registers)),
+            // Move between the current and the previous shadow register set
+            Instruction::Rdpgpr { rd, rt } => {
+                let value = registers.read_previous_set(*rt);
+                registers.write(*rd, value);
+                NextPc::Next
+            },
+            Instruction::Wrpgpr { rd, rt } => {
+                let value = registers.read(*rt);
+                registers.write_previous_set(*rd, value);
+                NextPc::Next
+            },
+
             // Special instructions
+            Instruction::Rdhwr { rt, rd } => match registers.read_hardware(*rd) {
+                Some(value) => {
+                    registers.write(*rt, value);
+                    NextPc::Next
+                },
+                // Reading an unimplemented hardware register is a reserved
+                // instruction
+                None => NextPc::Trap(Exception::InvalidInstruction),
+            },
             Instruction::Syscall => handle_syscall(registers, memory),
             Instruction::Break { code: _ } => {
                 // Normally would trigger debugger, but for our simulator we'll just print a message
                 println!("Breakpoint encountered at PC: 0x{:08X}", registers.pc);
-                None
+                NextPc::Next
             },
-            Instruction::Nop => None,
+            Instruction::Nop => NextPc::Next,
 
-            Instruction::InvalidInstruction => None,
+            Instruction::InvalidInstruction => NextPc::Trap(Exception::InvalidInstruction),
         }
     }
 
-    pub fn get_address(&self, registers: &Registers, pc: u32) -> u32 {
+    /// Base register and offset of a load or store
+    pub fn memory_operand(&self) -> Option<(u32, i16)> {
         match self {
             Instruction::Lw { base, offset, .. }
             | Instruction::Sw { base, offset, .. }
@@ -728,11 +940,30 @@ impl Instruction {
             | Instruction::Lhu { base, offset, .. }
             | Instruction::Sb { base, offset, .. }
             | Instruction::Sh { base, offset, .. }
+            | Instruction::Ll { base, offset, .. }
+            | Instruction::Sc { base, offset, .. }
             | Instruction::LwC1 { base, offset, .. }
-            | Instruction::SwC1 { base, offset, .. } => {
-                let base_value = registers.read(*base);
-                base_value.wrapping_add(*offset as u32)
-            },
+            | Instruction::SwC1 { base, offset, .. } => Some((*base, *offset)),
+            _ => None,
+        }
+    }
+
+    /// Bytes a load or store accesses
+    pub fn access_size(&self) -> u32 {
+        match self {
+            Instruction::Lb { .. } | Instruction::Lbu { .. } | Instruction::Sb { .. } => 1,
+            Instruction::Lh { .. } | Instruction::Lhu { .. } | Instruction::Sh { .. } => 2,
+            _ => 4,
+        }
+    }
+
+    /// Address a memory access or control transfer uses. Load and store
+    /// addresses wrap; `effective_address` tells whether they would fault.
+    pub fn get_address(&self, registers: &Registers, pc: u32) -> u32 {
+        if let Some((base, offset)) = self.memory_operand() {
+            return registers.read(base).wrapping_add(offset as u32);
+        }
+        match self {
             Instruction::Beq { offset, .. }
             | Instruction::Bne { offset, .. }
             | Instruction::Bgtz { offset, .. }
@@ -742,12 +973,9 @@ impl Instruction {
             | Instruction::BC1T { offset }
             | Instruction::BC1F { offset } => {
                 // PC-relative addressing: PC + 4 + (offset << 2)
-                pc.wrapping_add(4).wrapping_add((*offset as u32) << 2)
-            },
-            Instruction::J { target } | Instruction::Jal { target } => {
-                // Jump format: (PC & 0xF0000000) | (target << 2)
-                (pc & 0xF0000000) | (*target << 2)
+                branch_target(pc, *offset)
             },
+            Instruction::J { target } | Instruction::Jal { target } => jump_target(pc, *target),
             Instruction::Jr { rs } | Instruction::Jalr { rs, .. } => {
                 // Jump register: address in rs
                 registers.read(*rs)
@@ -760,12 +988,14 @@ impl Instruction {
         matches!(
             self,
             Instruction::Add { .. }
+                | Instruction::Addu { .. }
                 | Instruction::Sub { .. }
                 | Instruction::And { .. }
                 | Instruction::Or { .. }
                 | Instruction::Xor { .. }
                 | Instruction::Nor { .. }
                 | Instruction::Slt { .. }
+                | Instruction::Sltu { .. }
                 | Instruction::Slti { .. }
                 | Instruction::Sltiu { .. }
                 | Instruction::Sll { .. }
@@ -785,8 +1015,13 @@ impl Instruction {
                 | Instruction::Lbu { .. }
                 | Instruction::Lh { .. }
                 | Instruction::Lhu { .. }
+                | Instruction::Ll { .. }
+                | Instruction::Sc { .. }
                 | Instruction::Mflo { .. }
                 | Instruction::Mfhi { .. }
+                | Instruction::Mfc0 { .. }
+                | Instruction::Rdpgpr { .. }
+                | Instruction::Rdhwr { .. }
                 | Instruction::LwC1 { .. }
                 | Instruction::AddS { .. }
                 | Instruction::SubS { .. }
@@ -800,15 +1035,19 @@ impl Instruction {
         )
     }
 
+    /// Register the instruction writes. Writes to `$zero` are discarded, so
+    /// an instruction targeting it has no destination.
     pub fn get_destination_register(&self) -> Option<u32> {
         match self {
             Instruction::Add { rd, .. }
+            | Instruction::Addu { rd, .. }
             | Instruction::Sub { rd, .. }
             | Instruction::And { rd, .. }
             | Instruction::Or { rd, .. }
             | Instruction::Xor { rd, .. }
             | Instruction::Nor { rd, .. }
             | Instruction::Slt { rd, .. }
+            | Instruction::Sltu { rd, .. }
             | Instruction::Sll { rd, .. }
             | Instruction::Srl { rd, .. }
             | Instruction::Sra { rd, .. }
@@ -817,7 +1056,12 @@ impl Instruction {
             | Instruction::Srav { rd, .. }
             | Instruction::Mflo { rd }
             | Instruction::Mfhi { rd }
-            | Instruction::Jalr { rd, .. } => Some(*rd),
+            | Instruction::Rdpgpr { rd, .. }
+            | Instruction::Jalr { rd, .. } => Some(*rd).filter(|&reg| reg != 0),
+
+            Instruction::Mfc0 { rt, .. } | Instruction::Rdhwr { rt, .. } => {
+                Some(*rt).filter(|&reg| reg != 0)
+            },
 
             Instruction::Addi { rt, .. }
             | Instruction::Addiu { rt, .. }
@@ -831,7 +1075,9 @@ impl Instruction {
             | Instruction::Lb { rt, .. }
             | Instruction::Lbu { rt, .. }
             | Instruction::Lh { rt, .. }
-            | Instruction::Lhu { rt, .. } => Some(*rt),
+            | Instruction::Lhu { rt, .. }
+            | Instruction::Ll { rt, .. }
+            | Instruction::Sc { rt, .. } => Some(*rt).filter(|&reg| reg != 0),
 
             Instruction::Jal { .. } => Some(31), // $ra
 
@@ -855,12 +1101,14 @@ impl Instruction {
     pub fn get_source_registers(&self) -> Vec<u32> {
         match self {
             Instruction::Add { rs, rt, .. }
+            | Instruction::Addu { rs, rt, .. }
             | Instruction::Sub { rs, rt, .. }
             | Instruction::And { rs, rt, .. }
             | Instruction::Or { rs, rt, .. }
             | Instruction::Xor { rs, rt, .. }
             | Instruction::Nor { rs, rt, .. }
-            | Instruction::Slt { rs, rt, .. } => vec![*rs, *rt],
+            | Instruction::Slt { rs, rt, .. }
+            | Instruction::Sltu { rs, rt, .. } => vec![*rs, *rt],
 
             Instruction::Sll { rt, .. }
             | Instruction::Srl { rt, .. }
@@ -883,8 +1131,11 @@ impl Instruction {
             | Instruction::Lbu { base, .. }
             | Instruction::Lh { base, .. }
             | Instruction::Lhu { base, .. }
+            | Instruction::Ll { base, .. }
             | Instruction::LwC1 { base, .. } => vec![*base],
 
+            Instruction::Sc { rt, base, .. } => vec![*rt, *base],
+
             Instruction::Sw { rt, base, .. } => {
                 if *base == 0 {
                     vec![*rt] // Special case for storing to absolute address
@@ -916,6 +1167,20 @@ impl Instruction {
 
             Instruction::Beq { rs, rt, .. } | Instruction::Bne { rs, rt, .. } => vec![*rs, *rt],
 
+            Instruction::Teq { rs, rt, .. }
+            | Instruction::Tne { rs, rt, .. }
+            | Instruction::Tge { rs, rt, .. }
+            | Instruction::Tgeu { rs, rt, .. }
+            | Instruction::Tlt { rs, rt, .. }
+            | Instruction::Tltu { rs, rt, .. } => vec![*rs, *rt],
+
+            Instruction::Teqi { rs, .. }
+            | Instruction::Tnei { rs, .. }
+            | Instruction::Tgei { rs, .. }
+            | Instruction::Tgeiu { rs, .. }
+            | Instruction::Tlti { rs, .. }
+            | Instruction::Tltiu { rs, .. } => vec![*rs],
+
             Instruction::Bgtz { rs, .. }
             | Instruction::Blez { rs, .. }
             | Instruction::Bltz { rs, .. }
@@ -926,6 +1191,8 @@ impl Instruction {
             | Instruction::Mthi { rs }
             | Instruction::Mtlo { rs } => vec![*rs],
 
+            Instruction::Mtc0 { rt, .. } | Instruction::Wrpgpr { rt, .. } => vec![*rt],
+
             Instruction::Mult { rs, rt }
             | Instruction::Div { rs, rt }
             | Instruction::Divu { rs, rt } => vec![*rs, *rt],
@@ -968,6 +1235,7 @@ impl Instruction {
                 | Instruction::Jalr { .. }
                 | Instruction::BC1T { .. }
                 | Instruction::BC1F { .. }
+                | Instruction::Eret
         )
     }
 
@@ -982,6 +1250,8 @@ impl Instruction {
                 | Instruction::Lhu { .. }
                 | Instruction::Sb { .. }
                 | Instruction::Sh { .. }
+                | Instruction::Ll { .. }
+                | Instruction::Sc { .. }
                 | Instruction::LwC1 { .. }
                 | Instruction::SwC1 { .. }
         )
@@ -995,6 +1265,7 @@ impl Instruction {
                 | Instruction::Lbu { .. }
                 | Instruction::Lh { .. }
                 | Instruction::Lhu { .. }
+                | Instruction::Ll { .. }
                 | Instruction::LwC1 { .. }
         )
     }
@@ -1005,6 +1276,7 @@ impl Instruction {
             Instruction::Sw { .. }
                 | Instruction::Sb { .. }
                 | Instruction::Sh { .. }
+                | Instruction::Sc { .. }
                 | Instruction::SwC1 { .. }
         )
     }
@@ -1050,7 +1322,7 @@ impl Instruction {
             | Instruction::BC1F { offset } => {
                 // PC-relative branches: returns offset to be added to PC+4
                 // The caller is responsible for adding this to the appropriate PC value
-                Some((*offset as u32) << 2)
+                Some(branch_offset(*offset) as u32)
             },
             _ => None,
         }
@@ -1061,9 +1333,7 @@ impl Instruction {
     pub fn calculate_branch_target(&self, current_pc: u32) -> Option<u32> {
         match self {
             Instruction::J { target } | Instruction::Jal { target } => {
-                // J-type instructions: (PC+4 & 0xF0000000) | (target << 2)
-                let pc_plus_4 = current_pc.wrapping_add(4);
-                Some((pc_plus_4 & 0xF000_0000) | ((*target & 0x03FF_FFFF) << 2))
+                Some(jump_target(current_pc, *target))
             },
             Instruction::Beq { offset, .. }
             | Instruction::Bne { offset, .. }
@@ -1072,12 +1342,140 @@ impl Instruction {
             | Instruction::Bltz { offset, .. }
             | Instruction::Bgez { offset, .. }
             | Instruction::BC1T { offset }
-            | Instruction::BC1F { offset } => {
-                // PC-relative branches: PC + 4 + (offset << 2)
-                let pc_plus_4 = current_pc.wrapping_add(4);
-                Some(pc_plus_4.wrapping_add(((*offset as i32) << 2) as u32))
-            },
+            | Instruction::BC1F { offset } => Some(branch_target(current_pc, *offset)),
             _ => None,
         }
     }
 }
+
+fn r_type(rs: u32, rt: u32, rd: u32, shamt: u32, funct: u32) -> u32 {
+    ((rs & 0x1F) << 21) | ((rt & 0x1F) << 16) | ((rd & 0x1F) << 11) | ((shamt & 0x1F) << 6) | funct
+}
+
+fn i_type(opcode: u32, rs: u32, rt: u32, imm: u16) -> u32 {
+    (opcode << 26) | ((rs & 0x1F) << 21) | ((rt & 0x1F) << 16) | imm as u32
+}
+
+fn fp_type(fmt: u32, ft: u32, fs: u32, fd: u32, funct: u32) -> u32 {
+    (0x11 << 26) | r_type(fmt, ft, fs, fd, funct)
+}
+
+// Register traps keep a 10-bit code between rd and funct
+fn trap(rs: u32, rt: u32, code: u32, funct: u32) -> u32 {
+    r_type(rs, rt, 0, 0, funct) | ((code & 0x3FF) << 6)
+}
+
+/// Machine word of an instruction, the inverse of `decode_instruction`.
+/// Fields wider than their encoding are truncated, and
+/// `InvalidInstruction` becomes a word with an unused opcode.
+pub fn encode(instruction: &Instruction) -> u32 {
+    match *instruction {
+        Instruction::Add { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x20),
+        Instruction::Addu { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x21),
+        Instruction::Sub { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x22),
+        Instruction::And { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x24),
+        Instruction::Or { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x25),
+        Instruction::Xor { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x26),
+        Instruction::Nor { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x27),
+        Instruction::Slt { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x2A),
+        Instruction::Sltu { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x2B),
+        Instruction::Sll { rd, rt, shamt } => r_type(0, rt, rd, shamt, 0x00),
+        Instruction::Srl { rd, rt, shamt } => r_type(0, rt, rd, shamt, 0x02),
+        Instruction::Sra { rd, rt, shamt } => r_type(0, rt, rd, shamt, 0x03),
+        Instruction::Sllv { rd, rt, rs } => r_type(rs, rt, rd, 0, 0x04),
+        Instruction::Srlv { rd, rt, rs } => r_type(rs, rt, rd, 0, 0x06),
+        Instruction::Srav { rd, rt, rs } => r_type(rs, rt, rd, 0, 0x07),
+        Instruction::Jr { rs } => r_type(rs, 0, 0, 0, 0x08),
+        Instruction::Jalr { rd, rs } => r_type(rs, 0, rd, 0, 0x09),
+        Instruction::Syscall => 0x0C,
+        // The 20-bit break code sits between rs and funct
+        Instruction::Break { code } => ((code & 0xFFFFF) << 6) | 0x0D,
+        Instruction::Mfhi { rd } => r_type(0, 0, rd, 0, 0x10),
+        Instruction::Mthi { rs } => r_type(rs, 0, 0, 0, 0x11),
+        Instruction::Mflo { rd } => r_type(0, 0, rd, 0, 0x12),
+        Instruction::Mtlo { rs } => r_type(rs, 0, 0, 0, 0x13),
+        Instruction::Mult { rs, rt } => r_type(rs, rt, 0, 0, 0x18),
+        Instruction::Div { rs, rt } => r_type(rs, rt, 0, 0, 0x1A),
+        Instruction::Divu { rs, rt } => r_type(rs, rt, 0, 0, 0x1B),
+        Instruction::Tge { rs, rt, code } => trap(rs, rt, code, 0x30),
+        Instruction::Tgeu { rs, rt, code } => trap(rs, rt, code, 0x31),
+        Instruction::Tlt { rs, rt, code } => trap(rs, rt, code, 0x32),
+        Instruction::Tltu { rs, rt, code } => trap(rs, rt, code, 0x33),
+        Instruction::Teq { rs, rt, code } => trap(rs, rt, code, 0x34),
+        Instruction::Tne { rs, rt, code } => trap(rs, rt, code, 0x36),
+        Instruction::Nop => 0,
+
+        Instruction::Addi { rt, rs, imm } => i_type(0x08, rs, rt, imm as u16),
+        Instruction::Addiu { rt, rs, imm } => i_type(0x09, rs, rt, imm as u16),
+        Instruction::Slti { rt, rs, imm } => i_type(0x0A, rs, rt, imm as u16),
+        Instruction::Sltiu { rt, rs, imm } => i_type(0x0B, rs, rt, imm as u16),
+        Instruction::Andi { rt, rs, imm } => i_type(0x0C, rs, rt, imm),
+        Instruction::Ori { rt, rs, imm } => i_type(0x0D, rs, rt, imm),
+        Instruction::Xori { rt, rs, imm } => i_type(0x0E, rs, rt, imm),
+        Instruction::Lui { rt, imm } => i_type(0x0F, 0, rt, imm),
+        Instruction::Lb { rt, base, offset } => i_type(0x20, base, rt, offset as u16),
+        Instruction::Lh { rt, base, offset } => i_type(0x21, base, rt, offset as u16),
+        Instruction::Lw { rt, base, offset } => i_type(0x23, base, rt, offset as u16),
+        Instruction::Lbu { rt, base, offset } => i_type(0x24, base, rt, offset as u16),
+        Instruction::Lhu { rt, base, offset } => i_type(0x25, base, rt, offset as u16),
+        Instruction::Sb { rt, base, offset } => i_type(0x28, base, rt, offset as u16),
+        Instruction::Sh { rt, base, offset } => i_type(0x29, base, rt, offset as u16),
+        Instruction::Sw { rt, base, offset } => i_type(0x2B, base, rt, offset as u16),
+        Instruction::Ll { rt, base, offset } => i_type(0x30, base, rt, offset as u16),
+        Instruction::Sc { rt, base, offset } => i_type(0x38, base, rt, offset as u16),
+        Instruction::LwC1 { ft, base, offset } => i_type(0x31, base, ft, offset as u16),
+        Instruction::SwC1 { ft, base, offset } => i_type(0x39, base, ft, offset as u16),
+
+        Instruction::Beq { rs, rt, offset } => i_type(0x04, rs, rt, offset as u16),
+        Instruction::Bne { rs, rt, offset } => i_type(0x05, rs, rt, offset as u16),
+        Instruction::Blez { rs, offset } => i_type(0x06, rs, 0, offset as u16),
+        Instruction::Bgtz { rs, offset } => i_type(0x07, rs, 0, offset as u16),
+        // REGIMM instructions select the operation with rt
+        Instruction::Bltz { rs, offset } => i_type(0x01, rs, 0x00, offset as u16),
+        Instruction::Bgez { rs, offset } => i_type(0x01, rs, 0x01, offset as u16),
+        Instruction::Tgei { rs, imm } => i_type(0x01, rs, 0x08, imm as u16),
+        Instruction::Tgeiu { rs, imm } => i_type(0x01, rs, 0x09, imm as u16),
+        Instruction::Tlti { rs, imm } => i_type(0x01, rs, 0x0A, imm as u16),
+        Instruction::Tltiu { rs, imm } => i_type(0x01, rs, 0x0B, imm as u16),
+        Instruction::Teqi { rs, imm } => i_type(0x01, rs, 0x0C, imm as u16),
+        Instruction::Tnei { rs, imm } => i_type(0x01, rs, 0x0E, imm as u16),
+
+        Instruction::J { target } => (0x02 << 26) | (target & 0x3FF_FFFF),
+        Instruction::Jal { target } => (0x03 << 26) | (target & 0x3FF_FFFF),
+
+        // The CP0 select field is kept in bits 7:5 of the register number
+        Instruction::Mfc0 { rt, rd } => {
+            (0x10 << 26) | r_type(0x00, rt, rd, 0, (rd >> CP0_SELECT_SHIFT) & 0x7)
+        },
+        Instruction::Mtc0 { rt, rd } => {
+            (0x10 << 26) | r_type(0x04, rt, rd, 0, (rd >> CP0_SELECT_SHIFT) & 0x7)
+        },
+        Instruction::Rdpgpr { rd, rt } => (0x10 << 26) | r_type(0x0A, rt, rd, 0, 0),
+        Instruction::Wrpgpr { rd, rt } => (0x10 << 26) | r_type(0x0E, rt, rd, 0, 0),
+        Instruction::Eret => (0x10 << 26) | r_type(0x10, 0, 0, 0, 0x18),
+        Instruction::Rdhwr { rt, rd } => (0x1F << 26) | r_type(0, rt, rd, 0, 0x3B),
+
+        Instruction::AddS { fd, fs, ft } => fp_type(0x10, ft, fs, fd, 0x00),
+        Instruction::SubS { fd, fs, ft } => fp_type(0x10, ft, fs, fd, 0x01),
+        Instruction::MulS { fd, fs, ft } => fp_type(0x10, ft, fs, fd, 0x02),
+        Instruction::DivS { fd, fs, ft } => fp_type(0x10, ft, fs, fd, 0x03),
+        Instruction::AbsS { fd, fs } => fp_type(0x10, 0, fs, fd, 0x05),
+        Instruction::MovS { fd, fs } => fp_type(0x10, 0, fs, fd, 0x06),
+        Instruction::NegS { fd, fs } => fp_type(0x10, 0, fs, fd, 0x07),
+        Instruction::CvtWS { fd, fs } => fp_type(0x10, 0, fs, fd, 0x24),
+        Instruction::CvtSW { fd, fs } => fp_type(0x14, 0, fs, fd, 0x20),
+        // Conditions 0, 1 and 2 are c.eq.s, c.lt.s and c.le.s
+        Instruction::CmpS { fs, ft, cond } => {
+            let funct = match cond {
+                0 => 0x32,
+                1 => 0x3C,
+                _ => 0x3E,
+            };
+            fp_type(0x10, ft, fs, 0, funct)
+        },
+        Instruction::BC1F { offset } => i_type(0x11, 0x08, 0, offset as u16),
+        Instruction::BC1T { offset } => i_type(0x11, 0x08, 1, offset as u16),
+
+        Instruction::InvalidInstruction => 0xFC00_0000,
+    }
+}
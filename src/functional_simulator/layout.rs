@@ -0,0 +1,96 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// layout.rs
+//
+// This file contains the guest memory layout: where the stack starts and
+// where `sbrk` hands out heap memory. The layout is normally fixed, so a
+// program finds its stack and heap at the same addresses on every run.
+// Security exercises can instead randomize both from a seed, as address
+// space layout randomization does, to show how it defeats exploits that
+// hard-code the addresses of stack buffers or heap objects.
+
+use super::memory::Memory;
+use super::simulator::STACK_TOP;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// The stack top is moved down by up to this many bytes
+pub const STACK_RANDOM_RANGE: u32 = 0x0010_0000;
+/// The stack top keeps its alignment modulo this many bytes
+pub const STACK_ALIGNMENT: u32 = 16;
+/// The heap start is moved up by whole pages of this size
+pub const HEAP_PAGE_SIZE: usize = 4096;
+/// The heap start is moved up by at most this many pages
+pub const HEAP_RANDOM_PAGES: usize = 256;
+
+/// Initial stack pointer and heap start of a guest program
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryLayout {
+    pub stack_top: u32,
+    pub heap_start: usize,
+    /// Seed the layout was randomized with, or `None` for the fixed layout
+    pub seed: Option<u64>,
+}
+
+impl MemoryLayout {
+    /// The usual layout: the stack at `STACK_TOP` and the heap where the
+    /// memory starts it
+    pub fn fixed(memory: &Memory) -> Self {
+        Self {
+            stack_top: STACK_TOP,
+            heap_start: memory.heap_end(),
+            seed: None,
+        }
+    }
+
+    /// A layout randomized from `seed`, which gives the same layout every
+    /// time. The heap never gives up more than half of the space above its
+    /// usual start.
+    pub fn randomized(memory: &Memory, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let fixed = Self::fixed(memory);
+        let stack_slots = STACK_RANDOM_RANGE / STACK_ALIGNMENT;
+        let stack_offset = rng.gen_range(0..stack_slots) * STACK_ALIGNMENT;
+        let free_pages = memory.size.saturating_sub(fixed.heap_start) / 2 / HEAP_PAGE_SIZE;
+        let heap_pages = rng.gen_range(0..=free_pages.min(HEAP_RANDOM_PAGES));
+        Self {
+            stack_top: fixed.stack_top - stack_offset,
+            heap_start: fixed.heap_start + heap_pages * HEAP_PAGE_SIZE,
+            seed: Some(seed),
+        }
+    }
+
+    pub fn is_randomized(&self) -> bool {
+        self.seed.is_some()
+    }
+
+    pub fn render(&self) -> String {
+        let mode = match self.seed {
+            Some(seed) => format!("randomized with seed {}", seed),
+            None => "fixed".to_string(),
+        };
+        format!(
+            "Memory layout: stack top 0x{:08X}, heap start 0x{:08X} ({})",
+            self.stack_top, self.heap_start, mode
+        )
+    }
+}
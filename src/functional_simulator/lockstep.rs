@@ -0,0 +1,225 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// lockstep.rs
+//
+// This file contains the lockstep determinism checker. Two functional
+// simulators built from the same inputs are stepped together and their
+// architectural state is compared at regular checkpoints. Any difference
+// means the run depends on something besides its inputs, such as host time
+// or the iteration order of a hash map, so replaying or grading it would not
+// be reliable.
+
+use super::simulator::Simulator;
+use std::fmt::Write;
+
+/// Instructions between checkpoints unless another interval is chosen
+pub const DEFAULT_CHECKPOINT_INTERVAL: usize = 1000;
+
+/// The first checkpoint at which the two runs disagreed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Instructions the first run had executed at the checkpoint
+    pub instructions: usize,
+    /// Each difference as `what: first vs second`
+    pub differences: Vec<String>,
+}
+
+/// Outcome of a lockstep check
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LockstepReport {
+    pub checkpoints: usize,
+    pub instructions: usize,
+    pub divergence: Option<Divergence>,
+}
+
+impl LockstepReport {
+    pub fn is_deterministic(&self) -> bool {
+        self.divergence.is_none()
+    }
+
+    pub fn render(&self) -> String {
+        match &self.divergence {
+            None => format!(
+                "Lockstep: both runs matched at {} checkpoints over {} instructions\n",
+                self.checkpoints, self.instructions
+            ),
+            Some(divergence) => {
+                let mut text = format!(
+                    "Lockstep: runs diverged at checkpoint {} after {} instructions\n",
+                    self.checkpoints, divergence.instructions
+                );
+                for difference in &divergence.differences {
+                    let _ = writeln!(text, "  {}", difference);
+                }
+                text
+            },
+        }
+    }
+}
+
+/// Steps two simulators together and compares them every `interval`
+/// instructions and where they stop
+#[derive(Clone, Copy, Debug)]
+pub struct LockstepChecker {
+    pub interval: usize,
+}
+
+impl Default for LockstepChecker {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHECKPOINT_INTERVAL)
+    }
+}
+
+impl LockstepChecker {
+    pub fn new(interval: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+        }
+    }
+
+    /// Run both simulators to the end, or to the first checkpoint where
+    /// they differ. Both should have been built the same way.
+    pub fn run(&self, first: &mut Simulator, second: &mut Simulator) -> LockstepReport {
+        let mut report = LockstepReport::default();
+        loop {
+            let first_result = first.step();
+            let second_result = second.step();
+            let first_running = matches!(first_result, Ok(true));
+            let second_running = matches!(second_result, Ok(true));
+            let stopped = !first_running || !second_running;
+            if !stopped && first.step_count % self.interval != 0 {
+                continue;
+            }
+
+            report.checkpoints += 1;
+            let mut differences = compare_state(first, second);
+            let first_error = first_result.err().map(|error| error.to_string());
+            let second_error = second_result.err().map(|error| error.to_string());
+            if first_running != second_running || first_error != second_error {
+                differences.push(format!(
+                    "status: {} vs {}",
+                    describe_status(first_running, &first_error),
+                    describe_status(second_running, &second_error)
+                ));
+            }
+            if !differences.is_empty() {
+                report.divergence = Some(Divergence {
+                    instructions: first.step_count,
+                    differences,
+                });
+                break;
+            }
+            if stopped {
+                break;
+            }
+        }
+        report.instructions = first.step_count;
+        report
+    }
+}
+
+/// Differences in the architectural state of two simulators: the PC,
+/// instruction count, registers, CP0, memory, captured output and
+/// termination reason
+pub fn compare_state(first: &Simulator, second: &Simulator) -> Vec<String> {
+    let mut differences = Vec::new();
+    if first.pc() != second.pc() {
+        differences.push(format!("pc: 0x{:08X} vs 0x{:08X}", first.pc(), second.pc()));
+    }
+    if first.step_count != second.step_count {
+        differences.push(format!(
+            "instructions: {} vs {}",
+            first.step_count, second.step_count
+        ));
+    }
+    for change in first.registers.diff(&second.registers) {
+        differences.push(format!(
+            "{}: 0x{:08X} vs 0x{:08X}",
+            change.register, change.old, change.new
+        ));
+    }
+    let cp0 = first.registers.cp0.iter().zip(&second.registers.cp0);
+    for (reg_num, (old, new)) in cp0.enumerate().filter(|(_, (old, new))| old != new) {
+        differences.push(format!("cp0 ${}: 0x{:08X} vs 0x{:08X}", reg_num, old, new));
+    }
+    if let Some(difference) = compare_memory(&first.memory.data, &second.memory.data) {
+        differences.push(difference);
+    }
+    if first.captured_output != second.captured_output {
+        let length = |output: &Option<Vec<u8>>| output.as_ref().map_or(0, Vec::len);
+        differences.push(format!(
+            "output: {} bytes vs {} bytes",
+            length(&first.captured_output),
+            length(&second.captured_output)
+        ));
+    }
+    if first.termination_reason != second.termination_reason {
+        let describe =
+            |reason: Option<_>| reason.map_or("running".to_string(), |r| format!("{}", r));
+        differences.push(format!(
+            "termination: {} vs {}",
+            describe(first.termination_reason),
+            describe(second.termination_reason)
+        ));
+    }
+    differences
+}
+
+// Describe where two memory images first differ and how many bytes differ
+fn compare_memory(first: &[u8], second: &[u8]) -> Option<String> {
+    if first == second {
+        return None;
+    }
+    if first.len() != second.len() {
+        return Some(format!(
+            "memory size: {} vs {} bytes",
+            first.len(),
+            second.len()
+        ));
+    }
+    let differing = first.iter().zip(second).filter(|(a, b)| a != b).count();
+    let offset = first.iter().zip(second).position(|(a, b)| a != b)?;
+    let word = offset & !3;
+    let read = |bytes: &[u8]| {
+        u32::from_le_bytes([
+            bytes[word],
+            bytes[word + 1],
+            bytes[word + 2],
+            bytes[word + 3],
+        ])
+    };
+    Some(format!(
+        "memory: {} bytes differ, first word at 0x{:08X}: 0x{:08X} vs 0x{:08X}",
+        differing,
+        word,
+        read(first),
+        read(second)
+    ))
+}
+
+fn describe_status(running: bool, error: &Option<String>) -> String {
+    match (running, error) {
+        (_, Some(error)) => format!("error ({})", error),
+        (true, None) => "running".to_string(),
+        (false, None) => "stopped".to_string(),
+    }
+}
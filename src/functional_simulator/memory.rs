@@ -27,6 +27,102 @@
 // access permissions.
 
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// Kind of memory access, for permission checks and error reports
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemAccess {
+    Read,
+    Write,
+    Execute,
+}
+
+impl fmt::Display for MemAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemAccess::Read => write!(f, "read"),
+            MemAccess::Write => write!(f, "write"),
+            MemAccess::Execute => write!(f, "execute"),
+        }
+    }
+}
+
+// Bases of the kernel segments of the MIPS32 address space
+pub const KSEG0_BASE: u32 = 0x8000_0000;
+pub const KSEG1_BASE: u32 = 0xA000_0000;
+pub const KSEG2_BASE: u32 = 0xC000_0000;
+
+/// Physical address bits of a kseg0 or kseg1 address
+pub const KSEG_PHYSICAL_MASK: u32 = 0x1FFF_FFFF;
+
+/// Segment of the MIPS32 address space an address falls in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressSegment {
+    /// User segment below 0x80000000
+    Kuseg,
+    /// Unmapped, cached window onto the first 512 MiB of physical memory
+    Kseg0,
+    /// Unmapped, uncached window onto the same physical memory
+    Kseg1,
+    /// Mapped kernel segments from 0xC0000000
+    Kseg2,
+}
+
+impl AddressSegment {
+    pub fn of(address: u32) -> Self {
+        if address < KSEG0_BASE {
+            AddressSegment::Kuseg
+        } else if address < KSEG1_BASE {
+            AddressSegment::Kseg0
+        } else if address < KSEG2_BASE {
+            AddressSegment::Kseg1
+        } else {
+            AddressSegment::Kseg2
+        }
+    }
+
+    /// Whether accesses through this segment go through the caches
+    pub fn is_cached(self) -> bool {
+        self != AddressSegment::Kseg1
+    }
+
+    /// Physical address of `address` if this segment maps it without a TLB
+    pub fn physical_address(self, address: u32) -> Option<u32> {
+        match self {
+            AddressSegment::Kseg0 | AddressSegment::Kseg1 => Some(address & KSEG_PHYSICAL_MASK),
+            AddressSegment::Kuseg | AddressSegment::Kseg2 => None,
+        }
+    }
+}
+
+/// Why a memory access failed. Each variant holds the address accessed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemError {
+    /// The address lies outside simulated memory after translation
+    OutOfBounds(u32),
+    /// A halfword or word access at an address that is not a multiple of
+    /// its size
+    Misaligned(u32),
+    /// The memory region holding the address does not allow the access
+    PermissionDenied(u32, MemAccess),
+}
+
+impl fmt::Display for MemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemError::OutOfBounds(address) => {
+                write!(f, "address 0x{:08X} out of bounds", address)
+            },
+            MemError::Misaligned(address) => write!(f, "misaligned access at 0x{:08X}", address),
+            MemError::PermissionDenied(address, access) => {
+                write!(f, "{} not permitted at 0x{:08X}", access, address)
+            },
+        }
+    }
+}
+
+impl Error for MemError {}
 
 /// Configuration options for memory behavior
 #[derive(Clone, Copy, Debug)]
@@ -56,6 +152,8 @@ pub struct Memory {
     memory_regions: Vec<MemoryRegion>,
     mapped_devices: HashMap<usize, Box<dyn MemoryMappedDevice>>,
     config: MemoryConfig,
+    // One bit per page written since the set was last cleared, when tracked
+    dirty_pages: Option<Vec<u64>>,
 }
 
 impl Clone for Memory {
@@ -67,16 +165,29 @@ impl Clone for Memory {
             memory_regions: self.memory_regions.clone(),
             mapped_devices: HashMap::new(), // Empty on clone
             config: self.config,
+            dirty_pages: self.dirty_pages.clone(),
         }
     }
 }
 
+/// Bytes of address space a memory-mapped device covers unless it says
+/// otherwise
+pub const DEVICE_WINDOW_SIZE: usize = 4096;
+
+/// Size of the pages dirty-page tracking works in
+pub const DIRTY_PAGE_SIZE: usize = 4096;
+
 // Define a trait for memory-mapped devices
 pub trait MemoryMappedDevice: Send + Sync {
     fn read_byte(&self, offset: usize) -> u8;
     fn write_byte(&mut self, offset: usize, value: u8);
     fn read_word(&self, offset: usize) -> u32;
     fn write_word(&mut self, offset: usize, value: u32);
+
+    /// Bytes of address space the device covers from its base address
+    fn size(&self) -> usize {
+        DEVICE_WINDOW_SIZE
+    }
 }
 
 // Define memory regions with different access permissions
@@ -110,6 +221,7 @@ impl Memory {
             mapped_devices: HashMap::new(),
             memory_regions,
             config,
+            dirty_pages: None,
         }
     }
 
@@ -136,6 +248,7 @@ impl Memory {
                 enable_permissions: false, // Disable permissions for simple mode
                 enable_translation: false, // Disable translation for simple mode
             },
+            dirty_pages: None,
         }
     }
 
@@ -193,13 +306,18 @@ impl Memory {
         regions
     }
 
-    /// Address translation function to map virtual addresses to physical addresses
+    /// Address translation function to map virtual addresses to physical addresses.
+    /// kseg0 and kseg1 are aliases of the same physical memory.
     pub fn translate_address(&self, address: usize) -> usize {
         if !self.config.enable_translation {
             return address;
         }
+        let address = AddressSegment::of(address as u32)
+            .physical_address(address as u32)
+            .map_or(address, |physical| physical as usize);
 
-        // Handle high virtual addresses (typically from LUI instructions)
+        // Handle high virtual addresses (typically from LUI instructions),
+        // including physical addresses beyond memory such as the boot ROM
         if address >= 0x10000000 {
             // Map high addresses to lower physical space
             let physical_addr = address & (self.size - 1).max(0xFFFFF);
@@ -216,249 +334,286 @@ impl Memory {
         }
     }
 
-    /// Check if an address is valid for the given operation
-    fn is_valid_access(&self, address: usize, size: usize) -> bool {
+    /// Check that an access of `size` bytes at `address` is inside memory
+    /// and aligned, returning the physical address
+    fn check_access(&self, address: usize, size: usize) -> Result<usize, MemError> {
         let physical_addr = self.translate_address(address);
 
         // Check bounds
         if physical_addr >= self.size || physical_addr + size > self.size {
-            return false;
+            return Err(MemError::OutOfBounds(address as u32));
         }
 
         // Always check alignment for simple memory (when permissions are disabled)
         // This ensures predictable behavior for property tests
         if !self.config.enable_permissions || self.config.strict_alignment {
-            match size {
-                1 => true,             // Byte access - no alignment required
-                2 => address % 2 == 0, // Halfword - 2-byte aligned
-                4 => address % 4 == 0, // Word - 4-byte aligned
-                _ => false,            // Invalid size
+            if !matches!(size, 1 | 2 | 4) || address % size != 0 {
+                return Err(MemError::Misaligned(address as u32));
             }
-        } else {
+        } else if size > 1 && address % size != 0 && self.config.verbose_errors {
             // In non-strict mode with permissions enabled, allow unaligned access but warn
-            if size > 1 && address % size != 0 && self.config.verbose_errors {
-                println!(
-                    "Warning: Unaligned {}-byte access at address 0x{:08X}",
-                    size, address
-                );
+            println!(
+                "Warning: Unaligned {}-byte access at address 0x{:08X}",
+                size, address
+            );
+        }
+        Ok(physical_addr)
+    }
+
+    /// Check that `size` bytes at `address` can be accessed, without
+    /// accessing them, and return the reason if not
+    pub fn check(&self, address: usize, size: usize, access: MemAccess) -> Result<(), MemError> {
+        if self.get_mapped_device(address).is_some() {
+            return Ok(());
+        }
+        self.check_access(address, size)?;
+        if self.config.enable_permissions {
+            let (read, write, execute) = match access {
+                MemAccess::Read => (true, false, false),
+                MemAccess::Write => (false, true, false),
+                MemAccess::Execute => (false, false, true),
+            };
+            // Accesses are at most a word, so their first and last bytes
+            // cover every region they touch
+            for byte in [address, address + size - 1] {
+                if !self.check_permission(byte, read, write, execute) {
+                    return Err(MemError::PermissionDenied(address as u32, access));
+                }
             }
-            true
+        }
+        Ok(())
+    }
+
+    // Check an access and print why it failed when verbose errors are on
+    fn checked(&self, address: usize, size: usize, access: MemAccess) -> Result<usize, MemError> {
+        match self.check(address, size, access) {
+            Ok(()) => Ok(self.translate_address(address)),
+            Err(error) => {
+                if self.config.verbose_errors {
+                    println!(
+                        "Memory {} failed: {} (physical: 0x{:08X}, size: {})",
+                        access,
+                        error,
+                        self.translate_address(address),
+                        self.size
+                    );
+                }
+                Err(error)
+            },
         }
     }
 
     /// Direct write method that bypasses permission checks (for initialization)
     pub fn write_word_init(&mut self, address: usize, value: u32) -> bool {
-        if !self.is_valid_access(address, 4) {
-            if self.config.verbose_errors {
-                let physical_addr = self.translate_address(address);
-                println!("Memory write failed: address 0x{:08X} (physical: 0x{:08X}) out of bounds (size: {})", 
-                         address, physical_addr, self.size);
-            }
-            return false;
-        }
+        let physical_addr = match self.check_access(address, 4) {
+            Ok(physical_addr) => physical_addr,
+            Err(error) => {
+                if self.config.verbose_errors {
+                    println!("Memory write failed: {}", error);
+                }
+                return false;
+            },
+        };
 
-        let physical_addr = self.translate_address(address);
         let bytes = value.to_le_bytes();
         self.data[physical_addr..physical_addr + 4].copy_from_slice(&bytes);
+        self.mark_dirty(physical_addr, 4);
         true
     }
 
     /// Direct byte write method (for initialization)
     pub fn write_byte_init(&mut self, address: usize, value: u8) -> bool {
-        if !self.is_valid_access(address, 1) {
-            if self.config.verbose_errors {
-                let physical_addr = self.translate_address(address);
-                println!(
-                    "Memory byte write failed: address 0x{:08X} (physical: 0x{:08X}) out of bounds",
-                    address, physical_addr
-                );
-            }
-            return false;
-        }
+        let physical_addr = match self.check_access(address, 1) {
+            Ok(physical_addr) => physical_addr,
+            Err(error) => {
+                if self.config.verbose_errors {
+                    println!("Memory byte write failed: {}", error);
+                }
+                return false;
+            },
+        };
 
-        let physical_addr = self.translate_address(address);
         self.data[physical_addr] = value;
+        self.mark_dirty(physical_addr, 1);
         true
     }
 
     /// Read a single byte from memory
-    pub fn read_byte(&self, address: usize) -> Option<u8> {
+    pub fn read_byte(&self, address: usize) -> Result<u8, MemError> {
         // Check if address is mapped to a device
         if let Some((base_addr, device)) = self.get_mapped_device(address) {
             let offset = address - base_addr;
-            return Some(device.read_byte(offset));
-        }
-
-        if !self.is_valid_access(address, 1) {
-            if self.config.verbose_errors {
-                let physical_addr = self.translate_address(address);
-                println!("Memory read failed: address 0x{:08X} (physical: 0x{:08X}) out of bounds (size: {})",
-                         address, physical_addr, self.size);
-            }
-            return None;
-        }
-
-        if self.config.enable_permissions && !self.check_permission(address, true, false, false) {
-            return None;
+            return Ok(device.read_byte(offset));
         }
 
-        let physical_addr = self.translate_address(address);
-        Some(self.data[physical_addr])
+        let physical_addr = self.checked(address, 1, MemAccess::Read)?;
+        Ok(self.data[physical_addr])
     }
 
     /// Write a single byte to memory
-    pub fn write_byte(&mut self, address: usize, value: u8) -> bool {
+    pub fn write_byte(&mut self, address: usize, value: u8) -> Result<(), MemError> {
         // Check if address is mapped to a device
         if let Some((base_addr, device)) = self.get_mapped_device_mut(address) {
             let offset = address - base_addr;
             device.write_byte(offset, value);
-            return true;
-        }
-
-        if !self.is_valid_access(address, 1) {
-            if self.config.verbose_errors {
-                let physical_addr = self.translate_address(address);
-                println!("Memory write failed: address 0x{:08X} (physical: 0x{:08X}) out of bounds (size: {})", 
-                        address, physical_addr, self.size);
-            }
-            return false;
-        }
-
-        if self.config.enable_permissions && !self.check_permission(address, false, true, false) {
-            return false;
+            return Ok(());
         }
 
-        let physical_addr = self.translate_address(address);
+        let physical_addr = self.checked(address, 1, MemAccess::Write)?;
         self.data[physical_addr] = value;
-        true
+        self.mark_dirty(physical_addr, 1);
+        Ok(())
     }
 
     /// Read a 32-bit word from memory
-    pub fn read_word(&self, address: usize) -> Option<u32> {
+    pub fn read_word(&self, address: usize) -> Result<u32, MemError> {
         // Check if address is mapped to a device
         if let Some((base_addr, device)) = self.get_mapped_device(address) {
             let offset = address - base_addr;
-            return Some(device.read_word(offset));
-        }
-
-        if !self.is_valid_access(address, 4) {
-            if self.config.verbose_errors {
-                let physical_addr = self.translate_address(address);
-                println!("Memory read failed: address 0x{:08X} (physical: 0x{:08X}) out of bounds (size: {})", 
-                         address, physical_addr, self.size);
-            }
-            return None;
+            return Ok(device.read_word(offset));
         }
 
-        if self.config.enable_permissions
-            && (!self.check_permission(address, true, false, false)
-                || !self.check_permission(address + 3, true, false, false))
-        {
-            return None;
-        }
-
-        let physical_addr = self.translate_address(address);
+        let physical_addr = self.checked(address, 4, MemAccess::Read)?;
         let bytes = &self.data[physical_addr..physical_addr + 4];
-        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
     }
 
     /// Write a 32-bit word to memory
-    pub fn write_word(&mut self, address: usize, value: u32) -> bool {
+    pub fn write_word(&mut self, address: usize, value: u32) -> Result<(), MemError> {
         // Check if address is mapped to a device
         if let Some((base_addr, device)) = self.get_mapped_device_mut(address) {
             let offset = address - base_addr;
             device.write_word(offset, value);
-            return true;
+            return Ok(());
         }
 
-        if !self.is_valid_access(address, 4) {
-            if self.config.verbose_errors {
-                let physical_addr = self.translate_address(address);
-                println!("Memory write failed: address 0x{:08X} (physical: 0x{:08X}) out of bounds (size: {})", 
-                         address, physical_addr, self.size);
-            }
-            return false;
-        }
-
-        if self.config.enable_permissions
-            && (!self.check_permission(address, false, true, false)
-                || !self.check_permission(address + 3, false, true, false))
-        {
-            if self.config.verbose_errors {
-                println!(
-                    "Memory write failed: address 0x{:08X} permission denied",
-                    address
-                );
-            }
-            return false;
-        }
-
-        let physical_addr = self.translate_address(address);
+        let physical_addr = self.checked(address, 4, MemAccess::Write)?;
         let bytes = value.to_le_bytes();
         self.data[physical_addr..physical_addr + 4].copy_from_slice(&bytes);
-        true
+        self.mark_dirty(physical_addr, 4);
+        Ok(())
     }
 
     /// Read a 16-bit halfword from memory
-    pub fn read_halfword(&self, address: usize) -> Option<u16> {
+    pub fn read_halfword(&self, address: usize) -> Result<u16, MemError> {
         // Check if address is mapped to a device
         if let Some((base_addr, device)) = self.get_mapped_device(address) {
             let offset = address - base_addr;
-            return Some((device.read_word(offset) & 0xFFFF) as u16);
+            return Ok((device.read_word(offset) & 0xFFFF) as u16);
         }
 
-        if !self.is_valid_access(address, 2) {
-            if self.config.verbose_errors {
-                let physical_addr = self.translate_address(address);
-                println!("Memory read failed: address 0x{:08X} (physical: 0x{:08X}) out of bounds (size: {})", 
-                         address, physical_addr, self.size);
-            }
-            return None;
-        }
-
-        if self.config.enable_permissions
-            && (!self.check_permission(address, true, false, false)
-                || !self.check_permission(address + 1, true, false, false))
-        {
-            return None;
-        }
-
-        let physical_addr = self.translate_address(address);
+        let physical_addr = self.checked(address, 2, MemAccess::Read)?;
         let bytes = &self.data[physical_addr..physical_addr + 2];
-        Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
     }
 
     /// Write a 16-bit halfword to memory
-    pub fn write_halfword(&mut self, address: usize, value: u16) -> bool {
+    pub fn write_halfword(&mut self, address: usize, value: u16) -> Result<(), MemError> {
         // Check if address is mapped to a device
         if let Some((base_addr, device)) = self.get_mapped_device_mut(address) {
             let offset = address - base_addr;
             device.write_word(offset, value as u32);
-            return true;
+            return Ok(());
         }
 
-        if !self.is_valid_access(address, 2) {
-            if self.config.verbose_errors {
-                let physical_addr = self.translate_address(address);
-                println!(
-                    "Memory halfword write failed: address 0x{:08X} (physical: 0x{:08X})",
-                    address, physical_addr
-                );
-            }
-            return false;
+        let physical_addr = self.checked(address, 2, MemAccess::Write)?;
+        let bytes = value.to_le_bytes();
+        self.data[physical_addr..physical_addr + 2].copy_from_slice(&bytes);
+        self.mark_dirty(physical_addr, 2);
+        Ok(())
+    }
+
+    /// Read `length` bytes starting at `address`, in memory order. Block
+    /// accesses go straight to memory and do not reach mapped devices.
+    pub fn read_block(&self, address: usize, length: usize) -> Result<Vec<u8>, MemError> {
+        let physical_addr = self.check_block(address, length, MemAccess::Read)?;
+        Ok(self.data[physical_addr..physical_addr + length].to_vec())
+    }
+
+    /// Write `bytes` starting at `address`. Nothing is written unless the
+    /// whole block is inside memory and writable.
+    pub fn write_block(&mut self, address: usize, bytes: &[u8]) -> Result<(), MemError> {
+        let physical_addr = self.check_block(address, bytes.len(), MemAccess::Write)?;
+        self.data[physical_addr..physical_addr + bytes.len()].copy_from_slice(bytes);
+        self.mark_dirty(physical_addr, bytes.len());
+        Ok(())
+    }
+
+    /// Write a block bypassing write permissions, for loaders
+    pub fn write_block_init(&mut self, address: usize, bytes: &[u8]) -> Result<(), MemError> {
+        let physical_addr = self.block_bounds(address, bytes.len())?;
+        self.data[physical_addr..physical_addr + bytes.len()].copy_from_slice(bytes);
+        self.mark_dirty(physical_addr, bytes.len());
+        Ok(())
+    }
+
+    /// Set `length` bytes starting at `address` to `value`, all or nothing
+    pub fn fill(&mut self, address: usize, length: usize, value: u8) -> Result<(), MemError> {
+        let physical_addr = self.check_block(address, length, MemAccess::Write)?;
+        self.data[physical_addr..physical_addr + length].fill(value);
+        self.mark_dirty(physical_addr, length);
+        Ok(())
+    }
+
+    /// Read `count` little-endian words starting at `address`
+    pub fn read_words(&self, address: usize, count: usize) -> Result<Vec<u32>, MemError> {
+        if address % 4 != 0 {
+            return Err(MemError::Misaligned(address as u32));
         }
+        let bytes = self.read_block(address, count * 4)?;
+        Ok(bytes
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect())
+    }
 
-        if self.config.enable_permissions
-            && (!self.check_permission(address, false, true, false)
-                || !self.check_permission(address + 1, false, true, false))
-        {
-            return false;
+    /// Write `words` starting at `address` as little-endian bytes
+    pub fn write_words(&mut self, address: usize, words: &[u32]) -> Result<(), MemError> {
+        if address % 4 != 0 {
+            return Err(MemError::Misaligned(address as u32));
         }
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        self.write_block(address, &bytes)
+    }
 
+    // Physical start of a block, if all of it is inside memory
+    fn block_bounds(&self, address: usize, length: usize) -> Result<usize, MemError> {
         let physical_addr = self.translate_address(address);
-        let bytes = value.to_le_bytes();
-        self.data[physical_addr..physical_addr + 2].copy_from_slice(&bytes);
-        true
+        match physical_addr.checked_add(length) {
+            Some(end) if end <= self.size => Ok(physical_addr),
+            _ => Err(MemError::OutOfBounds(address as u32)),
+        }
+    }
+
+    // Check a block against memory bounds and the permissions of every
+    // region it overlaps. A region can only change the permission where
+    // one starts or ends, so only those addresses are checked.
+    fn check_block(
+        &self,
+        address: usize,
+        length: usize,
+        access: MemAccess,
+    ) -> Result<usize, MemError> {
+        let physical_addr = self.block_bounds(address, length)?;
+        if self.config.enable_permissions && length > 0 {
+            let (read, write, execute) = match access {
+                MemAccess::Read => (true, false, false),
+                MemAccess::Write => (false, true, false),
+                MemAccess::Execute => (false, false, true),
+            };
+            let end = address + length;
+            let boundaries = self
+                .memory_regions
+                .iter()
+                .flat_map(|region| [region.start, region.end])
+                .filter(|&boundary| boundary > address && boundary < end);
+            for byte in std::iter::once(address).chain(boundaries) {
+                if !self.check_permission(byte, read, write, execute) {
+                    return Err(MemError::PermissionDenied(byte as u32, access));
+                }
+            }
+        }
+        Ok(physical_addr)
     }
 
     // Memory-mapped device management
@@ -472,8 +627,7 @@ impl Memory {
 
     fn get_mapped_device(&self, address: usize) -> Option<(usize, &dyn MemoryMappedDevice)> {
         for (&base_addr, device) in &self.mapped_devices {
-            // Assuming each device has a fixed size of 4KB
-            if address >= base_addr && address < base_addr + 4096 {
+            if address >= base_addr && address < base_addr + device.size() {
                 return Some((base_addr, device.as_ref()));
             }
         }
@@ -485,9 +639,8 @@ impl Memory {
         address: usize,
     ) -> Option<(usize, &mut dyn MemoryMappedDevice)> {
         let mut found_base = None;
-        for &base_addr in self.mapped_devices.keys() {
-            // Assuming each device has a fixed size of 4KB
-            if address >= base_addr && address < base_addr + 4096 {
+        for (&base_addr, device) in &self.mapped_devices {
+            if address >= base_addr && address < base_addr + device.size() {
                 found_base = Some(base_addr);
                 break;
             }
@@ -633,7 +786,7 @@ impl Memory {
     /// Check if an address range is writable
     pub fn is_range_writable(&self, start: usize, length: usize) -> bool {
         if !self.config.enable_permissions {
-            return self.is_valid_access(start, length);
+            return self.check_access(start, length).is_ok();
         }
 
         let end = start + length;
@@ -685,14 +838,67 @@ impl Memory {
         }
     }
 
+    /// Start recording which pages are written, with every page clean
+    pub fn track_dirty_pages(&mut self) {
+        let pages = (self.data.len() + DIRTY_PAGE_SIZE - 1) / DIRTY_PAGE_SIZE;
+        self.dirty_pages = Some(vec![0; (pages + 63) / 64]);
+    }
+
+    pub fn is_tracking_dirty_pages(&self) -> bool {
+        self.dirty_pages.is_some()
+    }
+
+    /// Physical addresses of the pages written since tracking started or
+    /// the set was last cleared, in address order
+    pub fn dirty_pages(&self) -> Vec<u32> {
+        let bits = match &self.dirty_pages {
+            Some(bits) => bits,
+            None => return Vec::new(),
+        };
+        let mut pages = Vec::new();
+        for (index, &word) in bits.iter().enumerate() {
+            let mut word = word;
+            while word != 0 {
+                let page = index * 64 + word.trailing_zeros() as usize;
+                pages.push((page * DIRTY_PAGE_SIZE) as u32);
+                word &= word - 1;
+            }
+        }
+        pages
+    }
+
+    /// Mark every page clean
+    pub fn clear_dirty_pages(&mut self) {
+        if let Some(bits) = self.dirty_pages.as_mut() {
+            bits.iter_mut().for_each(|word| *word = 0);
+        }
+    }
+
+    /// Record a write of `length` bytes at a physical address. Code that
+    /// changes `data` directly calls this to keep the dirty pages right
+    pub fn mark_dirty(&mut self, physical_addr: usize, length: usize) {
+        let bits = match self.dirty_pages.as_mut() {
+            Some(bits) if length > 0 => bits,
+            _ => return,
+        };
+        let first = physical_addr / DIRTY_PAGE_SIZE;
+        let last = (physical_addr + length - 1) / DIRTY_PAGE_SIZE;
+        for page in first..=last {
+            if let Some(word) = bits.get_mut(page / 64) {
+                *word |= 1 << (page % 64);
+            }
+        }
+    }
+
     /// Clear all memory (set to zero)
     pub fn clear(&mut self) {
         self.data.fill(0);
+        self.mark_dirty(0, self.data.len());
     }
 
     /// Fill memory range with a specific value
     pub fn fill_range(&mut self, start: usize, length: usize, value: u8) -> bool {
-        if !self.is_valid_access(start, length) {
+        if self.check_access(start, length).is_err() {
             return false;
         }
 
@@ -701,6 +907,7 @@ impl Memory {
 
         if end <= self.size {
             self.data[physical_start..end].fill(value);
+            self.mark_dirty(physical_start, length);
             true
         } else {
             false
@@ -709,7 +916,7 @@ impl Memory {
 
     /// Copy data from one memory location to another
     pub fn copy_range(&mut self, src: usize, dst: usize, length: usize) -> bool {
-        if !self.is_valid_access(src, length) || !self.is_valid_access(dst, length) {
+        if self.check_access(src, length).is_err() || self.check_access(dst, length).is_err() {
             return false;
         }
 
@@ -720,6 +927,7 @@ impl Memory {
             // Use a temporary buffer to handle overlapping ranges
             let temp: Vec<u8> = self.data[physical_src..physical_src + length].to_vec();
             self.data[physical_dst..physical_dst + length].copy_from_slice(&temp);
+            self.mark_dirty(physical_dst, length);
             true
         } else {
             false
@@ -22,12 +22,32 @@
 // mod.rs
 //
 // This file is the module declaration for the functional_simulator module.
-// It re-exports the instructions, memory, registers, and simulator modules.
+// It re-exports the checkpoint, console, control_flow, decode_report, disk, flamegraph, framebuffer, guest,
+// instructions, layout, lockstep, memory, network, registers, semihosting, simulator, snapshot,
+// symbolic and taint modules.
 
+pub mod checkpoint;
+pub mod commit_log;
+pub mod console;
+pub mod control_flow;
+pub mod cp0;
+pub mod decode_report;
+pub mod disk;
+pub mod flamegraph;
+pub mod framebuffer;
+pub mod guest;
 pub mod instructions;
+pub mod layout;
+pub mod lockstep;
 pub mod loop_detector;
 pub mod memory;
+pub mod network;
 pub mod pc_manager;
 pub mod register_allocator;
 pub mod registers;
+pub mod semihosting;
 pub mod simulator;
+pub mod snapshot;
+pub mod symbolic;
+pub mod taint;
+pub mod threads;
@@ -0,0 +1,348 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// network.rs
+//
+// This file contains a memory-mapped network device that sends and receives
+// whole packets. The guest fills a transmit buffer and issues a send
+// command, and takes received packets one at a time into a receive buffer.
+// Packets either loop back to the device itself or travel as UDP datagrams
+// to a host peer. A `Network` handle shared with the device lets the host
+// inject packets and read statistics.
+
+use super::memory::{Memory, MemoryMappedDevice};
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Default address of the network registers
+pub const NETWORK_BASE: u32 = 0xFFFF_4000;
+
+/// Largest packet the buffers hold
+pub const MAX_PACKET_SIZE: usize = 1536;
+/// Received packets kept before new ones are dropped
+pub const RECEIVE_QUEUE_LIMIT: usize = 64;
+/// Instructions between checks of the UDP socket for new datagrams
+pub const POLL_INTERVAL: u32 = 64;
+
+// Network registers, as offsets from the network base
+pub const NETWORK_CONTROL: u32 = 0x00; // Bit 0: receive interrupt enable
+pub const NETWORK_STATUS: u32 = 0x04; // Bit 0: packet waiting, bit 1: last send failed
+pub const NETWORK_COMMAND: u32 = 0x08; // Write 1 to send, 2 to take the next packet
+pub const NETWORK_TX_LENGTH: u32 = 0x0C; // Bytes of the transmit buffer to send
+pub const NETWORK_RX_LENGTH: u32 = 0x10; // Bytes of the packet in the receive buffer
+pub const NETWORK_RX_PENDING: u32 = 0x14; // Packets waiting to be taken
+pub const NETWORK_TX_BUFFER: u32 = 0x400;
+pub const NETWORK_RX_BUFFER: u32 = 0xA00;
+
+// Commands
+pub const NETWORK_SEND: u32 = 1;
+pub const NETWORK_RECEIVE: u32 = 2;
+
+// Status bits
+pub const NETWORK_RX_READY: u32 = 1 << 0;
+pub const NETWORK_TX_ERROR: u32 = 1 << 1;
+
+// Control bits
+pub const NETWORK_INTERRUPT_ENABLE: u32 = 1 << 0;
+
+/// Where sent packets go
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetworkBackend {
+    /// Every packet sent is received by the device itself
+    Loopback,
+    /// Packets are UDP datagrams sent from `bind` to `peer`, and datagrams
+    /// arriving at `bind` are received
+    Udp { bind: SocketAddr, peer: SocketAddr },
+}
+
+/// Backend and address of a network device
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetworkConfig {
+    pub backend: NetworkBackend,
+    pub base: u32,
+}
+
+impl NetworkConfig {
+    pub fn loopback() -> Self {
+        Self {
+            backend: NetworkBackend::Loopback,
+            base: NETWORK_BASE,
+        }
+    }
+
+    pub fn udp(bind: SocketAddr, peer: SocketAddr) -> Self {
+        Self {
+            backend: NetworkBackend::Udp { bind, peer },
+            base: NETWORK_BASE,
+        }
+    }
+
+    pub fn with_base(mut self, base: u32) -> Self {
+        self.base = base;
+        self
+    }
+}
+
+/// Traffic through a network device since it was attached
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NetworkStatistics {
+    pub packets_sent: u64,
+    pub bytes_sent: u64,
+    pub packets_received: u64,
+    pub bytes_received: u64,
+    /// Packets lost to a full receive queue or a failed send
+    pub dropped: u64,
+}
+
+#[derive(Debug)]
+struct NetworkState {
+    socket: Option<(UdpSocket, SocketAddr)>,
+    until_poll: u32,
+    control: u32,
+    send_failed: bool,
+    tx_length: u32,
+    tx_buffer: Vec<u8>,
+    rx_buffer: Vec<u8>,
+    received: VecDeque<Vec<u8>>,
+    statistics: NetworkStatistics,
+}
+
+impl NetworkState {
+    fn status(&self) -> u32 {
+        let mut status = 0;
+        if !self.received.is_empty() {
+            status |= NETWORK_RX_READY;
+        }
+        if self.send_failed {
+            status |= NETWORK_TX_ERROR;
+        }
+        status
+    }
+
+    fn read_register(&self, offset: u32) -> u32 {
+        match offset {
+            NETWORK_CONTROL => self.control,
+            NETWORK_STATUS => self.status(),
+            NETWORK_TX_LENGTH => self.tx_length,
+            NETWORK_RX_LENGTH => self.rx_buffer.len() as u32,
+            NETWORK_RX_PENDING => self.received.len() as u32,
+            _ => 0,
+        }
+    }
+
+    fn write_register(&mut self, offset: u32, value: u32) {
+        match offset {
+            NETWORK_CONTROL => self.control = value & NETWORK_INTERRUPT_ENABLE,
+            NETWORK_COMMAND if value == NETWORK_SEND => self.send(),
+            NETWORK_COMMAND if value == NETWORK_RECEIVE => {
+                self.rx_buffer = self.received.pop_front().unwrap_or_default();
+            },
+            NETWORK_TX_LENGTH => self.tx_length = value,
+            _ => {},
+        }
+    }
+
+    fn send(&mut self) {
+        let length = (self.tx_length as usize).min(MAX_PACKET_SIZE);
+        let packet = self.tx_buffer[..length].to_vec();
+        let sent = match self.socket.as_ref() {
+            Some((socket, peer)) => socket.send_to(&packet, peer).is_ok(),
+            None => {
+                self.deliver(packet.clone());
+                true
+            },
+        };
+        self.send_failed = !sent;
+        if sent {
+            self.statistics.packets_sent += 1;
+            self.statistics.bytes_sent += length as u64;
+        } else {
+            self.statistics.dropped += 1;
+        }
+    }
+
+    // Queue a received packet for the guest
+    fn deliver(&mut self, mut packet: Vec<u8>) {
+        if self.received.len() >= RECEIVE_QUEUE_LIMIT {
+            self.statistics.dropped += 1;
+            return;
+        }
+        packet.truncate(MAX_PACKET_SIZE);
+        self.statistics.packets_received += 1;
+        self.statistics.bytes_received += packet.len() as u64;
+        self.received.push_back(packet);
+    }
+
+    // Take datagrams that arrived at the socket since the last poll, once
+    // every POLL_INTERVAL calls
+    fn poll(&mut self) {
+        if self.until_poll > 0 {
+            self.until_poll -= 1;
+            return;
+        }
+        self.until_poll = POLL_INTERVAL - 1;
+        let mut datagram = [0u8; MAX_PACKET_SIZE];
+        loop {
+            let received = match self.socket.as_ref() {
+                Some((socket, _)) => socket.recv_from(&mut datagram),
+                None => return,
+            };
+            match received {
+                Ok((length, _)) => self.deliver(datagram[..length].to_vec()),
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+fn lock(state: &Mutex<NetworkState>) -> MutexGuard<'_, NetworkState> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+struct NetworkDevice(Arc<Mutex<NetworkState>>);
+
+impl MemoryMappedDevice for NetworkDevice {
+    fn read_byte(&self, offset: usize) -> u8 {
+        let state = lock(&self.0);
+        if offset >= NETWORK_RX_BUFFER as usize {
+            let index = offset - NETWORK_RX_BUFFER as usize;
+            return state.rx_buffer.get(index).copied().unwrap_or(0);
+        }
+        if offset >= NETWORK_TX_BUFFER as usize {
+            return state.tx_buffer[offset - NETWORK_TX_BUFFER as usize];
+        }
+        (state.read_register(offset as u32 & !3) >> ((offset % 4) * 8)) as u8
+    }
+
+    fn write_byte(&mut self, offset: usize, value: u8) {
+        let mut state = lock(&self.0);
+        if offset >= NETWORK_RX_BUFFER as usize {
+            return;
+        }
+        if offset >= NETWORK_TX_BUFFER as usize {
+            state.tx_buffer[offset - NETWORK_TX_BUFFER as usize] = value;
+        } else {
+            state.write_register(offset as u32 & !3, value as u32);
+        }
+    }
+
+    fn read_word(&self, offset: usize) -> u32 {
+        if offset >= NETWORK_TX_BUFFER as usize {
+            return (0..4).fold(0, |word, byte| {
+                word | (self.read_byte(offset + byte) as u32) << (byte * 8)
+            });
+        }
+        lock(&self.0).read_register(offset as u32 & !3)
+    }
+
+    fn write_word(&mut self, offset: usize, value: u32) {
+        if offset >= NETWORK_TX_BUFFER as usize {
+            for byte in 0..4 {
+                self.write_byte(offset + byte, (value >> (byte * 8)) as u8);
+            }
+        } else {
+            lock(&self.0).write_register(offset as u32 & !3, value);
+        }
+    }
+}
+
+/// Host side of a network device mapped into guest memory. Clones share
+/// the device.
+#[derive(Clone, Debug)]
+pub struct Network {
+    pub config: NetworkConfig,
+    state: Arc<Mutex<NetworkState>>,
+}
+
+impl Network {
+    /// Create the device, binding its socket for the UDP backend, and map it
+    /// into `memory`
+    pub fn attach(memory: &mut Memory, config: NetworkConfig) -> io::Result<Self> {
+        let socket = match config.backend {
+            NetworkBackend::Loopback => None,
+            NetworkBackend::Udp { bind, peer } => {
+                let socket = UdpSocket::bind(bind)?;
+                socket.set_nonblocking(true)?;
+                Some((socket, peer))
+            },
+        };
+        let state = NetworkState {
+            socket,
+            until_poll: 0,
+            control: 0,
+            send_failed: false,
+            tx_length: 0,
+            tx_buffer: vec![0; MAX_PACKET_SIZE],
+            rx_buffer: Vec::new(),
+            received: VecDeque::new(),
+            statistics: NetworkStatistics::default(),
+        };
+        let network = Self {
+            config,
+            state: Arc::new(Mutex::new(state)),
+        };
+        memory.map_device(
+            network.config.base as usize,
+            Box::new(NetworkDevice(Arc::clone(&network.state))),
+        );
+        Ok(network)
+    }
+
+    /// Advance by one instruction, receiving datagrams waiting at the UDP
+    /// socket every `POLL_INTERVAL` instructions
+    pub fn poll(&self) {
+        lock(&self.state).poll();
+    }
+
+    /// Whether the receive interrupt line is raised: a packet is waiting and
+    /// the guest enabled the interrupt
+    pub fn interrupt(&self) -> bool {
+        let state = lock(&self.state);
+        state.control & NETWORK_INTERRUPT_ENABLE != 0 && !state.received.is_empty()
+    }
+
+    /// Deliver a packet to the guest as if it had arrived from the network
+    pub fn inject(&self, packet: &[u8]) {
+        lock(&self.state).deliver(packet.to_vec());
+    }
+
+    /// Packets waiting for the guest to take them
+    pub fn pending(&self) -> usize {
+        lock(&self.state).received.len()
+    }
+
+    /// Address the UDP backend's socket is bound to
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        let state = lock(&self.state);
+        state
+            .socket
+            .as_ref()
+            .and_then(|(socket, _)| socket.local_addr().ok())
+    }
+
+    pub fn statistics(&self) -> NetworkStatistics {
+        lock(&self.state).statistics
+    }
+}
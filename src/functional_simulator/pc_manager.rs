@@ -1,3 +1,4 @@
+use super::instructions::{branch_target, jump_target};
 use crate::errors::SimulatorError;
 
 pub struct PcManager {
@@ -56,9 +57,7 @@ impl PcManager {
 
     pub fn calculate_branch_target(&self, offset: i16) -> Result<u32, SimulatorError> {
         // PC-relative addressing: PC + 4 + (offset * 4)
-        let pc_plus_4 = self.pc.wrapping_add(4);
-        let offset_bytes = (offset as i32) * 4;
-        let target = pc_plus_4.wrapping_add(offset_bytes as u32);
+        let target = branch_target(self.pc, offset);
 
         // Validate target address
         if target as usize >= self.memory_size {
@@ -73,8 +72,8 @@ impl PcManager {
     }
 
     pub fn calculate_jump_target(&self, target: u32) -> Result<u32, SimulatorError> {
-        // Jump target: (PC & 0xF0000000) | (target << 2)
-        let jump_addr = (self.pc & 0xF000_0000) | ((target & 0x03FF_FFFF) << 2);
+        // Jump target: (PC + 4 & 0xF0000000) | (target << 2)
+        let jump_addr = jump_target(self.pc, target);
 
         // Validate target address
         if jump_addr as usize >= self.memory_size {
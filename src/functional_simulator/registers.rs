@@ -26,15 +26,257 @@
 // floating-point registers, as well as the special-purpose HI, LO, and PC
 // registers.
 
+use super::cp0::{
+    clear_pending, CAUSE_IP_SHIFT, CP0_CAUSE, CP0_COMPARE, CP0_EBASE, CP0_SELECT_SHIFT, CP0_SRSCTL,
+    CP0_USERLOCAL, DEFAULT_EBASE, SRSCTL_CSS_SHIFT, SRSCTL_ESS_SHIFT, SRSCTL_HSS_SHIFT,
+    SRSCTL_PSS_SHIFT, SRSCTL_SET_MASK, TIMER_IRQ,
+};
+use std::fmt;
+
+/// ABI names of the general-purpose registers, indexed by number
+pub const REGISTER_NAMES: [&str; 32] = [
+    "$zero", "$at", "$v0", "$v1", "$a0", "$a1", "$a2", "$a3", "$t0", "$t1", "$t2", "$t3", "$t4",
+    "$t5", "$t6", "$t7", "$s0", "$s1", "$s2", "$s3", "$s4", "$s5", "$s6", "$s7", "$t8", "$t9",
+    "$k0", "$k1", "$gp", "$sp", "$fp", "$ra",
+];
+
+// General-purpose register numbers by ABI name
+pub const ZERO: u32 = 0;
+pub const AT: u32 = 1;
+pub const V0: u32 = 2;
+pub const V1: u32 = 3;
+pub const A0: u32 = 4;
+pub const A1: u32 = 5;
+pub const A2: u32 = 6;
+pub const A3: u32 = 7;
+pub const T0: u32 = 8;
+pub const T1: u32 = 9;
+pub const T2: u32 = 10;
+pub const T3: u32 = 11;
+pub const T4: u32 = 12;
+pub const T5: u32 = 13;
+pub const T6: u32 = 14;
+pub const T7: u32 = 15;
+pub const S0: u32 = 16;
+pub const S1: u32 = 17;
+pub const S2: u32 = 18;
+pub const S3: u32 = 19;
+pub const S4: u32 = 20;
+pub const S5: u32 = 21;
+pub const S6: u32 = 22;
+pub const S7: u32 = 23;
+pub const T8: u32 = 24;
+pub const T9: u32 = 25;
+pub const K0: u32 = 26;
+pub const K1: u32 = 27;
+pub const GP: u32 = 28;
+pub const SP: u32 = 29;
+pub const FP: u32 = 30;
+pub const RA: u32 = 31;
+
+/// A register reported by `Registers::diff`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    /// General-purpose register by number
+    Gpr(u32),
+    /// Floating-point register by number
+    Fpr(u32),
+    Hi,
+    Lo,
+    Fcsr,
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::Gpr(reg_num) => write!(f, "{}", REGISTER_NAMES[*reg_num as usize % 32]),
+            Register::Fpr(reg_num) => write!(f, "$f{}", reg_num),
+            Register::Hi => write!(f, "hi"),
+            Register::Lo => write!(f, "lo"),
+            Register::Fcsr => write!(f, "fcsr"),
+        }
+    }
+}
+
+/// A register whose value differs between two register files. Floating-point
+/// values are compared and reported as their bit patterns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegisterChange {
+    pub register: Register,
+    pub old: u32,
+    pub new: u32,
+}
+
+// Hardware registers read by rdhwr
+pub const HWR_CPU_NUM: u32 = 0;
+pub const HWR_SYNCI_STEP: u32 = 1;
+pub const HWR_CC: u32 = 2;
+pub const HWR_CC_RES: u32 = 3;
+pub const HWR_USER_LOCAL: u32 = 29;
+/// Implementation-defined: instructions retired so far
+pub const HWR_INSTRET: u32 = 30;
+/// Implementation-defined: core clock frequency in kilohertz
+pub const HWR_CLOCK_KHZ: u32 = 31;
+
+/// Cache line size reported through `rdhwr` when no cache is modelled
+pub const DEFAULT_CACHE_LINE_SIZE: u32 = 64;
+
+/// Rate of the simulated clock the time syscalls read, in cycles per second
+pub const DEFAULT_CLOCK_HZ: u64 = 100_000_000;
+
+/// Counters and constants behind the hardware registers. The simulators
+/// update the counters before each instruction executes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HardwareRegisters {
+    pub cpu_num: u32,
+    /// Cache line size in bytes, the step `synci` works in
+    pub synci_step: u32,
+    pub cycles: u64,
+    pub instructions: u64,
+    /// Rate the cycle counter ticks at, in cycles per second
+    pub clock_hz: u64,
+    /// Cycles the guest has slept for. The core does no work in them, but
+    /// the clock keeps ticking.
+    pub idle_cycles: u64,
+}
+
+impl Default for HardwareRegisters {
+    fn default() -> Self {
+        Self {
+            cpu_num: 0,
+            synci_step: DEFAULT_CACHE_LINE_SIZE,
+            cycles: 0,
+            instructions: 0,
+            clock_hz: DEFAULT_CLOCK_HZ,
+            idle_cycles: 0,
+        }
+    }
+}
+
+impl HardwareRegisters {
+    /// Cycles on the simulated clock: those executed plus those slept
+    pub fn elapsed_cycles(&self) -> u64 {
+        self.cycles + self.idle_cycles
+    }
+
+    /// Simulated time since the run began, in microseconds. It depends only
+    /// on the cycle count, so a run reads the same times every time.
+    pub fn elapsed_us(&self) -> u64 {
+        (self.elapsed_cycles() as u128 * 1_000_000 / self.clock_hz.max(1) as u128) as u64
+    }
+
+    /// Let `ms` milliseconds of simulated time pass without executing
+    pub fn sleep_ms(&mut self, ms: u32) {
+        self.idle_cycles += ms as u64 * self.clock_hz / 1000;
+    }
+}
+
+/// Most shadow register sets besides the normal set; SRSCtl numbers sets
+/// with four bits
+pub const MAX_SHADOW_SETS: u32 = 15;
+
+/// Registers a handler without a shadow set saves on entry and restores
+/// before `eret`: every general-purpose register but `$zero`, `$k0` and `$k1`
+pub const FULL_SAVE_REGISTERS: u64 = 29;
+
+/// Banks of general-purpose registers selected through SRSCtl. Set 0 is the
+/// normal register file. `Registers::data` always holds the current set, so
+/// the bank kept here for it is stale.
+#[derive(Clone, Debug, Default)]
+pub struct ShadowRegisterSets {
+    banks: Vec<Vec<u32>>,
+    pub srsctl: u32,
+    /// Exceptions and interrupts whose handler ran in another set
+    pub switched_exceptions: u64,
+}
+
+impl ShadowRegisterSets {
+    pub fn is_enabled(&self) -> bool {
+        self.highest_set() > 0
+    }
+
+    pub fn highest_set(&self) -> u32 {
+        self.field(SRSCTL_HSS_SHIFT)
+    }
+
+    pub fn exception_set(&self) -> u32 {
+        self.field(SRSCTL_ESS_SHIFT)
+    }
+
+    pub fn previous_set(&self) -> u32 {
+        self.field(SRSCTL_PSS_SHIFT)
+    }
+
+    pub fn current_set(&self) -> u32 {
+        self.field(SRSCTL_CSS_SHIFT)
+    }
+
+    pub fn set_previous_set(&mut self, set: u32) {
+        self.set_field(SRSCTL_PSS_SHIFT, set);
+    }
+
+    /// Cycles the switched exceptions saved over storing and reloading
+    /// `FULL_SAVE_REGISTERS` registers, at one cycle per load or store
+    pub fn cycles_saved(&self) -> u64 {
+        self.switched_exceptions * 2 * FULL_SAVE_REGISTERS
+    }
+
+    /// Summary of the sets and the save/restore work they avoided
+    pub fn render(&self) -> String {
+        format!(
+            "Shadow register sets: {}, {} exceptions switched sets, saving about {} cycles of register save/restore",
+            self.highest_set(),
+            self.switched_exceptions,
+            self.cycles_saved()
+        )
+    }
+
+    // Software may choose the exception and previous sets, but only among
+    // the sets that exist
+    fn write_srsctl(&mut self, value: u32) {
+        for shift in [SRSCTL_ESS_SHIFT, SRSCTL_PSS_SHIFT] {
+            let set = (value >> shift) & SRSCTL_SET_MASK;
+            if set <= self.highest_set() {
+                self.set_field(shift, set);
+            }
+        }
+    }
+
+    fn field(&self, shift: u32) -> u32 {
+        (self.srsctl >> shift) & SRSCTL_SET_MASK
+    }
+
+    fn set_field(&mut self, shift: u32, set: u32) {
+        self.srsctl =
+            (self.srsctl & !(SRSCTL_SET_MASK << shift)) | ((set & SRSCTL_SET_MASK) << shift);
+    }
+}
+
+// Getters for the general-purpose registers by ABI name
+macro_rules! named_registers {
+    ($($name:ident = $reg_num:expr),* $(,)?) => {
+        $(
+            #[doc = concat!("Value of `$", stringify!($name), "`")]
+            pub fn $name(&self) -> u32 {
+                self.read($reg_num)
+            }
+        )*
+    };
+}
+
 #[derive(Debug, Clone)]
 pub struct Registers {
-    pub data: Vec<u32>,          // General-purpose registers
-    pub fp_registers: Vec<f32>,  // Floating-point registers
-    pub hi: u32,                 // HI register for mult/div results
-    pub lo: u32,                 // LO register for mult/div results
-    pub pc: u32,                 // Program counter
-    pub fcsr: u32,               // Floating-point Control Status Register
-    pub target_reg: Option<u32>, // Target register for certain instructions
+    pub data: Vec<u32>,              // General-purpose registers
+    pub fp_registers: Vec<f32>,      // Floating-point registers
+    pub hi: u32,                     // HI register for mult/div results
+    pub lo: u32,                     // LO register for mult/div results
+    pub pc: u32,                     // Program counter
+    pub fcsr: u32,                   // Floating-point Control Status Register
+    pub target_reg: Option<u32>,     // Target register for certain instructions
+    pub ll_bit: bool,                // Link bit set by ll, checked and cleared by sc
+    pub cp0: Vec<u32>,               // System control coprocessor (CP0) registers
+    pub hardware: HardwareRegisters, // Registers read by rdhwr
+    pub shadow: ShadowRegisterSets,  // Banks switched in by exceptions
 }
 
 impl Default for Registers {
@@ -53,6 +295,111 @@ impl Registers {
             pc: 0,
             fcsr: 0,
             target_reg: None,
+            ll_bit: false,
+            cp0: Self::reset_cp0(),
+            hardware: HardwareRegisters::default(),
+            shadow: ShadowRegisterSets::default(),
+        }
+    }
+
+    fn reset_cp0() -> Vec<u32> {
+        let mut cp0 = vec![0; 32];
+        cp0[CP0_EBASE as usize] = DEFAULT_EBASE;
+        cp0
+    }
+
+    pub fn read_cp0(&self, reg_num: u32) -> u32 {
+        if reg_num == CP0_SRSCTL {
+            return self.shadow.srsctl;
+        }
+        let reg_num = reg_num & ((1 << CP0_SELECT_SHIFT) - 1);
+        self.cp0.get(reg_num as usize).copied().unwrap_or(0)
+    }
+
+    pub fn write_cp0(&mut self, reg_num: u32, value: u32) {
+        if reg_num == CP0_SRSCTL {
+            self.shadow.write_srsctl(value);
+            return;
+        }
+        let reg_num = reg_num & ((1 << CP0_SELECT_SHIFT) - 1);
+        match reg_num {
+            CP0_COMPARE => {
+                // Writing Compare acknowledges the timer interrupt
+                self.cp0[CP0_COMPARE as usize] = value;
+                clear_pending(self, TIMER_IRQ);
+            },
+            CP0_CAUSE => {
+                // Only the two software interrupt bits are writable
+                let mask = 0x3 << CAUSE_IP_SHIFT;
+                let cause = self.cp0[CP0_CAUSE as usize];
+                self.cp0[CP0_CAUSE as usize] = (cause & !mask) | (value & mask);
+            },
+            _ if (reg_num as usize) < self.cp0.len() => self.cp0[reg_num as usize] = value,
+            _ => {},
+        }
+    }
+
+    /// Add `count` shadow register sets to the normal set. Exceptions use
+    /// set 1 until the guest chooses another through SRSCtl.ESS.
+    pub fn enable_shadow_sets(&mut self, count: u32) {
+        let count = count.min(MAX_SHADOW_SETS);
+        self.shadow = ShadowRegisterSets {
+            banks: vec![vec![0; 32]; count as usize + 1],
+            ..ShadowRegisterSets::default()
+        };
+        if count > 0 {
+            self.shadow.set_field(SRSCTL_HSS_SHIFT, count);
+            self.shadow.set_field(SRSCTL_ESS_SHIFT, 1);
+        }
+    }
+
+    /// Make `set` the current register set, keeping the outgoing registers
+    /// in their bank. Sets beyond SRSCtl.HSS are ignored.
+    pub fn switch_register_set(&mut self, set: u32) {
+        let current = self.shadow.current_set();
+        if set == current || set > self.shadow.highest_set() {
+            return;
+        }
+        std::mem::swap(&mut self.data, &mut self.shadow.banks[current as usize]);
+        std::mem::swap(&mut self.data, &mut self.shadow.banks[set as usize]);
+        self.shadow.set_field(SRSCTL_CSS_SHIFT, set);
+    }
+
+    /// Read a register of the previous set (SRSCtl.PSS), as rdpgpr does
+    pub fn read_previous_set(&self, reg_num: u32) -> u32 {
+        let previous = self.shadow.previous_set();
+        if previous == self.shadow.current_set() || reg_num == 0 {
+            return self.read(reg_num);
+        }
+        self.shadow.banks[previous as usize]
+            .get(reg_num as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Write a register of the previous set (SRSCtl.PSS), as wrpgpr does
+    pub fn write_previous_set(&mut self, reg_num: u32, value: u32) {
+        let previous = self.shadow.previous_set();
+        if previous == self.shadow.current_set() {
+            self.write(reg_num, value);
+        } else if reg_num != 0 && reg_num < 32 {
+            self.shadow.banks[previous as usize][reg_num as usize] = value;
+        }
+    }
+
+    /// Value of a hardware register, or `None` if it is not implemented.
+    /// The counters read as their low 32 bits, and the cycle counter counts
+    /// every cycle, including those slept.
+    pub fn read_hardware(&self, reg_num: u32) -> Option<u32> {
+        match reg_num {
+            HWR_CPU_NUM => Some(self.hardware.cpu_num),
+            HWR_SYNCI_STEP => Some(self.hardware.synci_step),
+            HWR_CC => Some(self.hardware.elapsed_cycles() as u32),
+            HWR_CC_RES => Some(1),
+            HWR_USER_LOCAL => Some(self.read_cp0(CP0_USERLOCAL)),
+            HWR_INSTRET => Some(self.hardware.instructions as u32),
+            HWR_CLOCK_KHZ => Some((self.hardware.clock_hz / 1000) as u32),
+            _ => None,
         }
     }
 
@@ -88,6 +435,40 @@ impl Registers {
         // Ignore out-of-bounds writes
     }
 
+    named_registers!(
+        at = 1,
+        v0 = 2,
+        v1 = 3,
+        a0 = 4,
+        a1 = 5,
+        a2 = 6,
+        a3 = 7,
+        t0 = 8,
+        t1 = 9,
+        t2 = 10,
+        t3 = 11,
+        t4 = 12,
+        t5 = 13,
+        t6 = 14,
+        t7 = 15,
+        s0 = 16,
+        s1 = 17,
+        s2 = 18,
+        s3 = 19,
+        s4 = 20,
+        s5 = 21,
+        s6 = 22,
+        s7 = 23,
+        t8 = 24,
+        t9 = 25,
+        k0 = 26,
+        k1 = 27,
+        gp = 28,
+        sp = 29,
+        fp = 30,
+        ra = 31,
+    );
+
     pub fn get_hi(&self) -> u32 {
         self.hi
     }
@@ -104,6 +485,44 @@ impl Registers {
         self.lo = value;
     }
 
+    pub fn get_fcsr(&self) -> u32 {
+        self.fcsr
+    }
+
+    pub fn set_fcsr(&mut self, value: u32) {
+        self.fcsr = value;
+    }
+
+    /// Registers whose value in `other` differs from this register file, in
+    /// the order general-purpose, HI, LO, FCSR, floating-point. `old` is the
+    /// value here and `new` the value in `other`. `$zero` always reads as
+    /// zero, so it never differs.
+    pub fn diff<'a>(&'a self, other: &'a Registers) -> impl Iterator<Item = RegisterChange> + 'a {
+        let gprs = (1..32).map(move |reg_num| {
+            (
+                Register::Gpr(reg_num),
+                self.read(reg_num),
+                other.read(reg_num),
+            )
+        });
+        let special = vec![
+            (Register::Hi, self.hi, other.hi),
+            (Register::Lo, self.lo, other.lo),
+            (Register::Fcsr, self.fcsr, other.fcsr),
+        ];
+        let fprs = (0..32).map(move |reg_num| {
+            (
+                Register::Fpr(reg_num),
+                self.read_float(reg_num).to_bits(),
+                other.read_float(reg_num).to_bits(),
+            )
+        });
+        gprs.chain(special)
+            .chain(fprs)
+            .filter(|(_, old, new)| old != new)
+            .map(|(register, old, new)| RegisterChange { register, old, new })
+    }
+
     pub fn dump_registers(&self) -> String {
         let mut result = String::new();
 
@@ -112,41 +531,7 @@ impl Registers {
         for i in 0..8 {
             for j in 0..4 {
                 let reg_num = i + j * 8;
-                let reg_name = match reg_num {
-                    0 => "$zero",
-                    1 => "$at",
-                    2 => "$v0",
-                    3 => "$v1",
-                    4 => "$a0",
-                    5 => "$a1",
-                    6 => "$a2",
-                    7 => "$a3",
-                    8 => "$t0",
-                    9 => "$t1",
-                    10 => "$t2",
-                    11 => "$t3",
-                    12 => "$t4",
-                    13 => "$t5",
-                    14 => "$t6",
-                    15 => "$t7",
-                    16 => "$s0",
-                    17 => "$s1",
-                    18 => "$s2",
-                    19 => "$s3",
-                    20 => "$s4",
-                    21 => "$s5",
-                    22 => "$s6",
-                    23 => "$s7",
-                    24 => "$t8",
-                    25 => "$t9",
-                    26 => "$k0",
-                    27 => "$k1",
-                    28 => "$gp",
-                    29 => "$sp",
-                    30 => "$fp",
-                    31 => "$ra",
-                    _ => unreachable!(),
-                };
+                let reg_name = REGISTER_NAMES[reg_num as usize];
                 result.push_str(&format!("{:<5} = 0x{:08x} ", reg_name, self.read(reg_num)));
             }
             result.push('\n');
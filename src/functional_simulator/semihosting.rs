@@ -0,0 +1,150 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// semihosting.rs
+//
+// This file contains the semihosting interface, which lets guest programs
+// ask the host for services that the MIPS syscall ABI does not cover:
+// reading and writing host files, reading wall-clock time and reporting
+// benchmark scores. A host call is a `break` instruction with a reserved
+// code, so it cannot clash with syscall numbers. Calls only work when the
+// host enabled semihosting, and file paths stay inside a host directory.
+
+use super::guest;
+use super::memory::Memory;
+use super::registers::Registers;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Code of the `break` instruction that makes a host call
+pub const SEMIHOST_BREAK_CODE: u32 = 0xAB;
+
+// Host call numbers, passed in $v0. Arguments are in $a0-$a2 and results
+// are returned in $v0, with the upper word of 64-bit results in $v1.
+pub const SEMIHOST_READ_FILE: u32 = 1; // $a0 path, $a1 buffer, $a2 size: bytes read
+pub const SEMIHOST_WRITE_FILE: u32 = 2; // $a0 path, $a1 buffer, $a2 length: bytes written
+pub const SEMIHOST_FILE_SIZE: u32 = 3; // $a0 path: size in bytes
+pub const SEMIHOST_TIME: u32 = 4; // Seconds since the Unix epoch, microseconds in $v1
+pub const SEMIHOST_ELAPSED: u32 = 5; // Microseconds since semihosting was enabled
+pub const SEMIHOST_INSTRUCTIONS: u32 = 6; // Instructions executed so far
+pub const SEMIHOST_REPORT_SCORE: u32 = 7; // $a0 name, $a1 signed score
+
+/// Returned in $v0 by failed or unknown host calls
+pub const SEMIHOST_ERROR: u32 = u32::MAX;
+
+/// Longest path or score name read from guest memory
+pub const MAX_NAME_LENGTH: usize = 256;
+
+/// A score reported by the guest
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BenchmarkScore {
+    pub name: String,
+    pub value: i32,
+    /// Instructions executed when the score was reported
+    pub instructions: usize,
+}
+
+/// Host side of semihosting: the directory guest paths are relative to,
+/// and what the guest reported
+#[derive(Clone, Debug)]
+pub struct Semihosting {
+    pub root: PathBuf,
+    pub scores: Vec<BenchmarkScore>,
+    /// Host calls made, including failed ones
+    pub calls: u64,
+    started: Instant,
+}
+
+impl Semihosting {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            scores: Vec::new(),
+            calls: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Carry out the host call selected by $v0
+    pub fn call(&mut self, registers: &mut Registers, memory: &mut Memory, instructions: usize) {
+        self.calls += 1;
+        let (a0, a1, a2) = (registers.read(4), registers.read(5), registers.read(6));
+        let result = match registers.read(2) {
+            SEMIHOST_READ_FILE => self.path(memory, a0).and_then(|path| {
+                let mut contents = fs::read(path).ok()?;
+                contents.truncate(a2 as usize);
+                memory.write_block(a1 as usize, &contents).ok()?;
+                Some(contents.len() as u64)
+            }),
+            SEMIHOST_WRITE_FILE => self.path(memory, a0).and_then(|path| {
+                let contents = memory.read_block(a1 as usize, a2 as usize).ok()?;
+                fs::write(path, &contents).ok()?;
+                Some(contents.len() as u64)
+            }),
+            SEMIHOST_FILE_SIZE => self
+                .path(memory, a0)
+                .and_then(|path| Some(fs::metadata(path).ok()?.len())),
+            SEMIHOST_TIME => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|time| (time.subsec_micros() as u64) << 32 | (time.as_secs() & 0xFFFF_FFFF)),
+            SEMIHOST_ELAPSED => Some(self.started.elapsed().as_micros() as u64),
+            SEMIHOST_INSTRUCTIONS => Some(instructions as u64),
+            SEMIHOST_REPORT_SCORE => read_string(memory, a0).map(|name| {
+                self.scores.push(BenchmarkScore {
+                    name,
+                    value: a1 as i32,
+                    instructions,
+                });
+                0
+            }),
+            _ => None,
+        };
+        match result {
+            // Only the 64-bit results use $v1
+            Some(value) => {
+                registers.write(2, value as u32);
+                registers.write(3, (value >> 32) as u32);
+            },
+            None => registers.write(2, SEMIHOST_ERROR),
+        }
+    }
+
+    // A guest path as a host path under the root. Absolute paths and paths
+    // that climb out of the root are refused.
+    fn path(&self, memory: &Memory, address: u32) -> Option<PathBuf> {
+        let name = read_string(memory, address)?;
+        let relative = Path::new(&name);
+        let inside = relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if name.is_empty() || !inside {
+            return None;
+        }
+        Some(self.root.join(relative))
+    }
+}
+
+// A NUL-terminated string from guest memory
+fn read_string(memory: &Memory, address: u32) -> Option<String> {
+    guest::read_string(memory, address, MAX_NAME_LENGTH).ok()
+}
@@ -26,20 +26,57 @@
 // memory, and program counter. The simulator is responsible for fetching,
 // decoding, and executing MIPS instructions.
 
-use super::instructions::Instruction;
-use super::memory::Memory;
+use super::checkpoint::CheckpointLog;
+use super::commit_log::{CommitLog, PendingCommit};
+use super::console::{Console, ConsoleConfig};
+use super::control_flow::{ControlFlowChecks, ControlFlowGuard};
+use super::cp0::{
+    clear_pending, enter_exception, set_pending, AsyncEvent, InterruptController, CP0_SELECT_SHIFT,
+    CP0_STATUS, DISK_IRQ, EXCCODE_RI, KEYBOARD_IRQ, NETWORK_IRQ, RESET_STATUS, RESET_VECTOR,
+};
+use super::decode_report::{DecodeReport, UnknownInstructionPolicy};
+use super::disk::{Disk, DiskConfig};
+use super::flamegraph::StackProfile;
+use super::framebuffer::{Framebuffer, FramebufferConfig};
+use super::instructions::{Instruction, NextPc};
+use super::layout::MemoryLayout;
+use super::memory::{MemAccess, MemError, Memory};
+use super::network::{Network, NetworkConfig};
 use super::registers::Registers;
-use crate::utils::syscall::handle_syscall;
-use std::collections::HashMap;
-
-#[derive(Debug)]
+use super::semihosting::{Semihosting, SEMIHOST_BREAK_CODE};
+use super::snapshot::StateSnapshot;
+use super::taint::TaintTracker;
+use super::threads::{
+    ThreadScheduler, SYSCALL_THREAD_CREATE, SYSCALL_THREAD_EXIT, SYSCALL_THREAD_JOIN,
+    SYSCALL_THREAD_SELF, SYSCALL_THREAD_YIELD, THREAD_RETURN_ADDRESS,
+};
+use crate::elf_loader::ElfLoader;
+use crate::errors::{CallError, SimulationError, SimulationErrorCause};
+use crate::loader::{preload_words, LoadError, ProgramImage};
+use crate::utils::crash_dump::{CrashDump, RecentInstructions};
+use crate::utils::limits::{
+    MemoryUsage, RunLimits, SyscallUsage, TerminationReason, MEMORY_CHECK_INTERVAL,
+};
+use crate::utils::progress::{ProgressCallback, ProgressReporter};
+use crate::utils::syscall::handle_syscall_with_io;
+use crate::utils::throttle::{cycle_counter, ThrottleClock};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, BufRead, Cursor, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Exception {
     InvalidInstruction,
     MemoryAccessViolation,
+    /// A load or store address computation wrapped around the address space
+    AddressError,
     SystemCall,
     BreakPoint,
     ArithmeticOverflow,
     FloatingPointException,
+    Trap,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -54,12 +91,105 @@ pub struct Simulator {
     pc: u32,
     pub exception: Option<Exception>,
     pub step_count: usize,
-    pub max_steps: usize,
+    pub limits: RunLimits,
+    pub termination_reason: Option<TerminationReason>,
+    run_started: Instant,
+    pub progress: Option<ProgressReporter>,
     pub break_points: HashMap<u32, bool>,
+    /// Labels and ELF symbols of the loaded programs, for `call_function`
+    pub symbols: BTreeMap<String, u32>,
     pub mode: ExecutionMode,
     pub fp_enabled: bool,
     pub trace_enabled: bool,
     pub debug_enabled: bool,
+    /// Leave out the progress and termination messages `run` and `step`
+    /// print, for callers whose stdout carries a report of its own
+    pub quiet: bool,
+    pub threads: ThreadScheduler,
+    pub interrupts: InterruptController,
+    /// Memory-mapped screen and keyboard, when attached
+    pub console: Option<Console>,
+    /// Memory-mapped block storage, when attached
+    pub disk: Option<Disk>,
+    /// Memory-mapped bitmap display, when attached
+    pub framebuffer: Option<Framebuffer>,
+    /// Memory-mapped network device, when attached
+    pub network: Option<Network>,
+    /// Host services for `break 0xAB` calls, when enabled
+    pub semihosting: Option<Semihosting>,
+    /// Unknown instructions met so far, when lenient decoding is enabled
+    pub lenient_decode: Option<DecodeReport>,
+    /// Taint state and the sinks tainted data reached, when enabled
+    pub taint: Option<TaintTracker>,
+    /// Shadow stack and stack canary checks, when enabled
+    pub control_flow: Option<ControlFlowGuard>,
+    /// Scenario the cycle counter is throttled by, when one is set
+    pub throttle: Option<ThrottleClock>,
+    /// Log of retired instructions for co-simulation, when enabled
+    pub commit_log: Option<CommitLog>,
+    /// Delta checkpoints taken every few instructions, when enabled
+    pub checkpoints: Option<CheckpointLog>,
+    /// Last instructions fetched, for crash dumps, when enabled
+    pub recent_instructions: Option<RecentInstructions>,
+    /// Instruction counts by call stack, for flamegraphs, when enabled
+    pub stack_profile: Option<StackProfile>,
+    /// When set, output from print syscalls is appended here instead of
+    /// being written to stdout
+    pub captured_output: Option<Vec<u8>>,
+    /// When set, read syscalls take their input from here instead of stdin
+    pub input: Option<Cursor<Vec<u8>>>,
+    /// Output, file writes and heap growth of the current run, counted
+    /// against `limits.quotas`
+    pub syscall_usage: SyscallUsage,
+}
+
+// Output that stops passing bytes on once a quota is used up, while still
+// counting them so the overrun can be reported
+struct QuotaWriter<'a> {
+    inner: &'a mut dyn Write,
+    written: &'a mut usize,
+    quota: Option<usize>,
+}
+
+impl Write for QuotaWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let allowed = match self.quota {
+            Some(quota) => quota.saturating_sub(*self.written).min(buf.len()),
+            None => buf.len(),
+        };
+        self.inner.write_all(&buf[..allowed])?;
+        *self.written += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Initial `$sp` of programs started by `load_program` and `run_asm`
+pub const STACK_TOP: u32 = 0x7FFF_FFFC;
+
+/// Code of the `break` in the return trampoline `call_function` pushes.
+/// The call ends when the function returns to it, so it only runs if
+/// something else jumps there.
+pub const CALL_RETURN_BREAK_CODE: u32 = 0xCA11;
+
+/// Bytes the o32 ABI has the caller reserve on the stack for the four
+/// register arguments
+const ARGUMENT_HOME_SIZE: u32 = 16;
+
+/// Final state of a program run by `Simulator::run_asm`
+pub struct RunResult {
+    pub registers: Registers,
+    pub memory: Memory,
+    /// Everything the program printed through syscalls
+    pub stdout: String,
+    pub pc: u32,
+    pub instructions: usize,
+    pub termination_reason: Option<TerminationReason>,
+    /// Runtime fault that stopped the program, if any
+    pub error: Option<SimulationError>,
 }
 
 impl Simulator {
@@ -70,96 +200,269 @@ impl Simulator {
             pc: 0,
             exception: None,
             step_count: 0,
-            max_steps: 1000000, // Prevent infinite loops
+            limits: RunLimits::default(), // Prevent infinite loops
+            termination_reason: None,
+            run_started: Instant::now(),
+            progress: None,
             break_points: HashMap::new(),
+            symbols: BTreeMap::new(),
             mode: ExecutionMode::User,
             fp_enabled: true,
             trace_enabled: false,
             debug_enabled: false,
+            quiet: false,
+            threads: ThreadScheduler::default(),
+            interrupts: InterruptController::new(),
+            console: None,
+            disk: None,
+            framebuffer: None,
+            network: None,
+            semihosting: None,
+            lenient_decode: None,
+            taint: None,
+            control_flow: None,
+            throttle: None,
+            commit_log: None,
+            checkpoints: None,
+            recent_instructions: None,
+            stack_profile: None,
+            captured_output: None,
+            input: None,
+            syscall_usage: SyscallUsage::default(),
         }
     }
 
-    pub fn load_program(&mut self, program: &[u8]) {
-        println!("Loading program of size {} bytes", program.len());
+    /// A simulator with just enough memory for `image`, with it loaded
+    pub fn from_image(image: &ProgramImage) -> Result<Self, LoadError> {
+        let mut simulator = Simulator::new(image.memory_required().next_power_of_two());
+        simulator.load_image(image)?;
+        Ok(simulator)
+    }
 
-        // Load test data first - this ensures it's in memory before the program tries to use it
-        self.memory.write_word(0x1000, 10);
-        self.memory.write_word(0x1004, 20);
-        self.memory.write_word(0x1008, 0); // Will hold add result
-        self.memory.write_word(0x100C, 0); // Will hold mult result
-        println!("Test data loaded into memory at addresses 0x1000-0x100C");
+    /// Assemble `source` and load it into a simulator sized for it
+    pub fn from_assembly(source: &str) -> Result<Self, LoadError> {
+        Self::from_image(&ProgramImage::from_assembly(source)?)
+    }
 
-        // First check if this is an assembler-generated file with header
-        let has_header = program.len() >= 8
-            && u32::from_le_bytes([program[0], program[1], program[2], program[3]]) < 1_000_000
-            && u32::from_le_bytes([program[4], program[5], program[6], program[7]]) < 1_000_000;
+    /// Assemble `source`, load every section at its address and run it to
+    /// completion with `$sp` at the top of the stack. Returns the final
+    /// registers and memory and what the program printed. Assembly errors
+    /// are returned as `Err`; runtime faults are reported in
+    /// `RunResult::error`.
+    pub fn run_asm(source: &str) -> Result<RunResult, LoadError> {
+        let mut simulator = Simulator::from_assembly(source)?;
+        simulator.registers.write(29, STACK_TOP);
+        simulator.captured_output = Some(Vec::new());
+
+        let error = simulator.run().err();
+        let stdout = simulator.captured_output.take().unwrap_or_default();
+        Ok(RunResult {
+            stdout: String::from_utf8_lossy(&stdout).into_owned(),
+            pc: simulator.pc,
+            instructions: simulator.step_count,
+            termination_reason: simulator.termination_reason,
+            error,
+            registers: simulator.registers,
+            memory: simulator.memory,
+        })
+    }
 
-        if has_header {
-            // Extract data and text section sizes from header
-            let data_size =
-                u32::from_le_bytes([program[0], program[1], program[2], program[3]]) as usize;
+    // Run a syscall, sending its output to the capture buffer if there is one.
+    // A syscall that would go past a quota stops the run instead.
+    fn syscall(&mut self) -> NextPc {
+        let quotas = self.limits.quotas;
+        let mut usage = self.syscall_usage;
+        let heap_end = self.memory.heap_end();
+        match self.registers.read(2) {
+            9 => usage.heap += self.registers.read(4) as usize,
+            15 if !matches!(self.registers.read(4), 1 | 2) => {
+                usage.file_write += self.registers.read(6) as usize
+            },
+            _ => {},
+        }
+        if let Err(e) = quotas.check(&usage) {
+            return self.exceed_quota(e);
+        }
 
-            let text_size =
-                u32::from_le_bytes([program[4], program[5], program[6], program[7]]) as usize;
+        let mut stdout = io::stdout();
+        let mut output = QuotaWriter {
+            inner: match self.captured_output.as_mut() {
+                Some(buffer) => buffer,
+                None => &mut stdout,
+            },
+            written: &mut usage.output,
+            quota: quotas.max_output,
+        };
+        let input = self.input.as_mut().map(|input| input as &mut dyn BufRead);
+        let next_pc =
+            handle_syscall_with_io(&mut self.registers, &mut self.memory, input, &mut output);
+
+        // sbrk only counts when it grew the heap
+        if self.memory.heap_end() == heap_end {
+            usage.heap = self.syscall_usage.heap;
+        }
+        self.syscall_usage = usage;
+        match quotas.check(&usage) {
+            Ok(()) => next_pc,
+            Err(e) => self.exceed_quota(e),
+        }
+    }
 
-            println!(
-                "Loading program with data section: {} bytes, text section: {} bytes",
-                data_size, text_size
-            );
+    // Progress and termination messages, left out when `quiet` is set
+    fn status(&self, message: fmt::Arguments) {
+        if !self.quiet {
+            println!("{}", message);
+        }
+    }
 
-            // Validate sizes before attempting to copy
-            if data_size + text_size + 8 > program.len() {
-                println!("Warning: Invalid section sizes in header. Falling back to raw loading.");
-                // Fall back to raw loading
-                self.memory.data[..program.len()].copy_from_slice(program);
-                self.pc = 0;
-            } else {
-                // Load data section at address 0x10000000 (data segment)
-                for (i, &byte) in program[8..8 + data_size].iter().enumerate() {
-                    self.memory.write_byte(0x10000000 + i, byte);
-                }
+    fn exceed_quota(&mut self, message: String) -> NextPc {
+        self.status(format_args!("Stopping execution: {}", message));
+        self.termination_reason = Some(TerminationReason::QuotaExceeded);
+        NextPc::Halt
+    }
 
-                // Load text section at address 0x00400000 (text segment)
-                for (i, chunk) in program[8 + data_size..8 + data_size + text_size]
-                    .chunks_exact(4)
-                    .enumerate()
-                {
-                    let instr = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
-                    self.memory.write_word(0x00400000 + i * 4, instr);
-                }
+    /// Address of the next instruction to execute
+    pub fn pc(&self) -> u32 {
+        self.pc
+    }
+
+    /// Move the PC, for example to resume at a different address after a pause
+    pub fn set_pc(&mut self, pc: u32) {
+        self.pc = pc;
+        self.registers.pc = pc;
+    }
 
-                // Set PC to start of text segment
-                self.pc = 0x00400000;
+    /// Start the next run at `entry`, clearing the state of any earlier run
+    pub fn set_entry_point(&mut self, entry: u32) {
+        self.set_pc(entry);
+        self.step_count = 0;
+        self.exception = None;
+        self.termination_reason = None;
+    }
+
+    /// Give the guest this stack pointer and heap start
+    pub fn apply_layout(&mut self, layout: &MemoryLayout) {
+        self.registers.write(29, layout.stack_top);
+        self.memory.set_heap_end(layout.heap_start);
+    }
+
+    /// Start the next run at the reset vector with Status in its reset state,
+    /// using the bootstrap exception vectors, as firmware in the boot ROM
+    /// expects
+    pub fn reset_to_boot_vector(&mut self) {
+        self.registers.cp0[CP0_STATUS as usize] = RESET_STATUS;
+        self.set_entry_point(RESET_VECTOR);
+    }
+
+    /// Copy the loadable segments of an ELF binary into memory and start at
+    /// its entry point
+    pub fn load_elf(&mut self, elf: &ElfLoader) -> Result<(), LoadError> {
+        self.load_image(&ProgramImage::elf(elf)?)
+    }
+
+    /// Place a program image in memory and start at its entry point. Its
+    /// symbols are added to those of programs loaded before.
+    pub fn load_image(&mut self, image: &ProgramImage) -> Result<(), LoadError> {
+        image.load_into(&mut self.memory)?;
+        self.set_entry_point(image.entry_point);
+        self.symbols.extend(
+            image
+                .symbols
+                .iter()
+                .map(|(name, &address)| (name.clone(), address)),
+        );
+        Ok(())
+    }
+
+    /// Call the function at a label or ELF symbol with `args` and return
+    /// its `$v0`. See `call_address`.
+    pub fn call_function(&mut self, name: &str, args: &[u32]) -> Result<u32, CallError> {
+        let address = *self
+            .symbols
+            .get(name)
+            .ok_or_else(|| CallError::UnknownFunction(name.to_string()))?;
+        self.call_address(address, args)
+    }
+
+    /// Call the function at `address` following the o32 ABI and return its
+    /// `$v0`. The first four arguments go in `$a0`-`$a3` and the rest on the
+    /// stack above the 16 bytes reserved for them. `$ra` points at a return
+    /// trampoline pushed on the stack, and the function runs until it
+    /// returns there. `$sp`, `$ra` and the PC are restored afterwards; other
+    /// registers and memory keep what the function left in them.
+    pub fn call_address(&mut self, address: u32, args: &[u32]) -> Result<u32, CallError> {
+        let (saved_pc, saved_sp, saved_ra) =
+            (self.pc, self.registers.read(29), self.registers.read(31));
+        let top = match saved_sp {
+            0 => STACK_TOP,
+            sp => sp,
+        };
+
+        // The trampoline goes above the frame, which stays 8-byte aligned
+        let trampoline = (top & !7) - 8;
+        let break_word = (CALL_RETURN_BREAK_CODE << 6) | 0x0D;
+        let stack_arguments = args.len().saturating_sub(4) as u32 * 4;
+        let sp = (trampoline - ARGUMENT_HOME_SIZE - stack_arguments) & !7;
+        self.memory
+            .write_words(trampoline as usize, &[break_word, 0])
+            .map_err(CallError::Stack)?;
+        for (i, &arg) in args.iter().enumerate() {
+            if i < 4 {
+                self.registers.write(4 + i as u32, arg);
+            } else {
+                self.memory
+                    .write_word((sp + i as u32 * 4) as usize, arg)
+                    .map_err(CallError::Stack)?;
             }
-        } else {
-            // Load raw binary as instructions with explicit endianness handling
-            println!(
-                "Loading raw program without header ({} bytes)",
-                program.len()
-            );
+        }
+        self.registers.write(29, sp);
+        self.registers.write(31, trampoline);
+        self.set_entry_point(address);
 
-            // Make sure we're handling 4-byte instruction alignment
-            if program.len() % 4 != 0 {
-                println!("Warning: Program size is not a multiple of 4 bytes");
+        let result = loop {
+            if self.pc == trampoline {
+                break Ok(self.registers.read(2));
+            }
+            match self.step() {
+                Ok(true) => {},
+                Ok(false) => {
+                    let reason = self.termination_reason.unwrap_or(TerminationReason::Halted);
+                    break Err(CallError::Stopped(reason));
+                },
+                Err(e) => break Err(CallError::Fault(e)),
             }
+        };
 
-            // Print program instructions for debugging
-            println!("Program instructions:");
-            for (i, chunk) in program.chunks_exact(4).enumerate().take(16) {
-                // Convert 4 bytes to a 32-bit instruction - ensure little-endian
-                let instr = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
-                println!("  0x{:04X}: 0x{:08X}", i * 4, instr);
+        self.registers.write(29, saved_sp);
+        self.registers.write(31, saved_ra);
+        self.set_pc(saved_pc);
+        result
+    }
 
-                // Write the instruction to memory
-                self.memory.write_word(i * 4, instr);
-            }
+    /// Write initial word values into memory, for programs that expect data
+    /// the loader does not place
+    pub fn preload_memory(&mut self, values: &[(u32, u32)]) -> Result<(), LoadError> {
+        preload_words(&mut self.memory, values)
+    }
+
+    /// Load a raw binary, assembler output or ELF image, detecting the format
+    pub fn load_program(&mut self, program: &[u8]) {
+        println!("Loading program of size {} bytes", program.len());
 
-            // Set PC to 0
-            self.pc = 0;
+        let loaded = ProgramImage::from_bytes(program).and_then(|image| {
+            println!(
+                "Loading {} with {} segment(s)",
+                image.format,
+                image.segments.len()
+            );
+            self.load_image(&image)
+        });
+        if let Err(e) = loaded {
+            println!("Warning: failed to load program: {}", e);
         }
 
         // Initialize stack pointer
-        self.registers.write(29, 0x7FFFFFFC); // $sp = 0x7FFFFFFC
+        self.registers.write(29, STACK_TOP);
 
         println!(
             "Program loaded. Initial PC: 0x{:08X}, SP: 0x{:08X}",
@@ -168,15 +471,17 @@ impl Simulator {
         );
     }
 
-    pub fn run(&mut self) {
-        println!("Starting execution at PC: 0x{:08X}", self.pc);
+    /// Run until the program exits, a budget is used up or a fault occurs.
+    /// Faults are returned as a `SimulationError`.
+    pub fn run(&mut self) -> Result<(), SimulationError> {
+        self.status(format_args!("Starting execution at PC: 0x{:08X}", self.pc));
 
         // Print first few instructions for debugging
         if self.debug_enabled {
             for offset in 0..5 {
                 let addr = self.pc as usize + offset * 4;
                 if addr < self.memory.size {
-                    if let Some(instruction_word) = self.memory.read_word(addr) {
+                    if let Ok(instruction_word) = self.memory.read_word(addr) {
                         println!("Instruction at 0x{:08X}: 0x{:08X}", addr, instruction_word);
                     }
                 }
@@ -184,23 +489,36 @@ impl Simulator {
         }
 
         self.step_count = 0;
+        self.termination_reason = None;
+        self.syscall_usage = SyscallUsage::default();
+        self.run_started = Instant::now();
+        if let Some(progress) = self.progress.as_mut() {
+            progress.start();
+        }
 
         // Save the PC in registers for use in branch instructions
         self.registers.pc = self.pc;
 
         // Track frequency of PC values to detect loops
         let mut pc_frequency: HashMap<u32, usize> = HashMap::new();
+        let mut error = None;
 
         loop {
-            // Check if we've reached the maximum number of steps
-            self.step_count += 1;
-            if self.step_count > self.max_steps {
-                println!(
-                    "Reached maximum instruction limit ({}). Stopping execution.",
-                    self.max_steps
-                );
+            // Stop once the instruction, cycle or time budget is used up
+            if let Some(reason) = self.check_limits() {
+                self.status(format_args!("Stopping execution: {}", reason));
+                self.termination_reason = Some(reason);
                 break;
             }
+            self.take_due_checkpoint();
+            self.step_count += 1;
+            self.update_progress();
+
+            // Preempt the running guest thread once its time slice is used up
+            self.threads.tick(&mut self.registers, &mut self.pc);
+
+            // Deliver pending interrupts to the guest handler
+            self.service_interrupts();
 
             // Record this PC in our frequency map for loop detection
             *pc_frequency.entry(self.pc).or_insert(0) += 1;
@@ -221,13 +539,33 @@ impl Simulator {
 
             // Check if this address is a breakpoint
             if self.break_points.contains_key(&self.pc) {
-                println!("Breakpoint hit at address 0x{:08X}", self.pc);
+                self.status(format_args!("Breakpoint hit at address 0x{:08X}", self.pc));
                 self.exception = Some(Exception::BreakPoint);
                 break;
             }
 
             // Fetch instruction
-            let instruction = self.fetch_instruction();
+            let (word, instruction) = match self.fetch_instruction() {
+                Ok(fetched) => fetched,
+                Err(fault) => {
+                    error = Some(fault);
+                    break;
+                },
+            };
+            if let Some(taint) = self.taint.as_mut() {
+                taint.propagate(&instruction, &self.registers, &self.memory, self.pc);
+            }
+            if let Some(profile) = self.stack_profile.as_mut() {
+                profile.record(&instruction, &self.registers, self.pc);
+            }
+            if let Some(guard) = self.control_flow.as_mut() {
+                if let Err(cause) =
+                    guard.check(&instruction, &self.registers, &self.memory, self.pc)
+                {
+                    error = Some(self.fault(Some(word), cause));
+                    break;
+                }
+            }
 
             // Update PC in registers for branch delay calculations
             self.registers.pc = self.pc;
@@ -235,30 +573,59 @@ impl Simulator {
             // Execute instruction
             match instruction {
                 Instruction::InvalidInstruction => {
-                    println!("Invalid instruction encountered at PC: 0x{:08X}", self.pc);
-                    self.exception = Some(Exception::InvalidInstruction);
+                    if self.skip_unknown_instruction(word) {
+                        continue;
+                    }
+                    error = Some(self.fault(Some(word), SimulationErrorCause::InvalidInstruction));
                     break;
                 },
                 Instruction::Syscall => {
-                    // Handle system call
-                    if let Some(new_pc) = handle_syscall(&mut self.registers, &mut self.memory) {
-                        if new_pc == 0xFFFFFFFF {
-                            // Program termination requested
-                            println!("Program terminated via syscall at PC: 0x{:08X}", self.pc);
+                    let syscall_num = self.registers.read(2);
+                    if ThreadScheduler::is_thread_syscall(syscall_num) {
+                        if !self.handle_thread_syscall(syscall_num) {
+                            self.status(format_args!(
+                                "No runnable threads left at PC: 0x{:08X}",
+                                self.pc
+                            ));
                             break;
                         }
-                        self.pc = new_pc;
-                    } else {
-                        // Normal syscall - continue execution
-                        self.pc += 4;
+                        continue;
+                    }
+
+                    // Keys queued as events are read before host stdin
+                    if syscall_num == 12 && self.interrupts.has_pending_key() {
+                        self.read_queued_key();
+                        continue;
                     }
+
+                    // Handle system call
+                    let pending = self.begin_commit(word, &instruction);
+                    let next_pc = self.syscall();
+                    self.end_commit(pending);
+                    if next_pc == NextPc::Halt {
+                        self.status(format_args!(
+                            "Program terminated via syscall at PC: 0x{:08X}",
+                            self.pc
+                        ));
+                        break;
+                    }
+                    self.pc += 4;
+                    continue;
+                },
+                Instruction::Break { code }
+                    if code == SEMIHOST_BREAK_CODE && self.semihosting.is_some() =>
+                {
+                    let pending = self.begin_commit(word, &instruction);
+                    self.host_call();
+                    self.end_commit(pending);
+                    self.pc += 4;
                     continue;
                 },
                 Instruction::Break { code: _ } => {
-                    println!(
+                    self.status(format_args!(
                         "Breakpoint instruction encountered at PC: 0x{:08X}",
                         self.pc
-                    );
+                    ));
                     self.exception = Some(Exception::BreakPoint);
                     break;
                 },
@@ -269,7 +636,7 @@ impl Simulator {
                         for i in 0..4 {
                             let addr = self.pc as usize + i * 4;
                             if addr < self.memory.size {
-                                if let Some(word) = self.memory.read_word(addr) {
+                                if let Ok(word) = self.memory.read_word(addr) {
                                     if word == 0 {
                                         nop_count += 1;
                                     }
@@ -278,66 +645,63 @@ impl Simulator {
                         }
 
                         if nop_count >= 3 {
-                            println!(
+                            self.status(format_args!(
                                 "Reached multiple NOPs at PC 0x{:08X} - terminating program",
                                 self.pc
-                            );
+                            ));
+                            self.termination_reason = Some(TerminationReason::Halted);
                             break;
                         }
                     }
 
                     // Just a regular NOP - continue execution
+                    let pending = self.begin_commit(word, &instruction);
+                    self.end_commit(pending);
                     self.pc += 4;
                     continue;
                 },
                 _ => {
                     // Execute regular instruction
-                    let pc_offset = self.execute_instruction(&instruction);
-
-                    match pc_offset {
-                        Some(offset) => {
-                            // Branch or jump instruction - calculate new PC
-                            let new_pc = if offset == 0xFFFFFFFF {
-                                // Special value used by some instructions to set PC directly
-                                self.registers.read(31) // Use $ra register instead of target_reg
-                            } else {
-                                // For branch instructions, PC+4+offset; for jumps, just PC+offset
-                                if instruction.is_branch_or_jump() {
-                                    if matches!(
-                                        instruction,
-                                        Instruction::J { .. } | Instruction::Jal { .. }
-                                    ) {
-                                        // Jump instructions use the lower 26 bits shifted left by 2
-                                        // with the upper 4 bits from the current PC
-                                        (self.pc & 0xF0000000) | (offset & 0x0FFFFFFF)
-                                    } else if matches!(
-                                        instruction,
-                                        Instruction::Jr { .. } | Instruction::Jalr { .. }
-                                    ) {
-                                        // JR and JALR use the register value directly
-                                        offset
-                                    } else {
-                                        // Branch instructions: add offset to PC+4
-                                        self.pc.wrapping_add(4).wrapping_add(offset)
-                                    }
-                                } else {
-                                    // Default case
-                                    self.pc.wrapping_add(offset)
+                    let next_pc = self.execute_instruction(word, &instruction);
+
+                    match next_pc {
+                        NextPc::Trap(exception) => {
+                            error = Some(self.trap(word, &instruction, exception));
+                            break;
+                        },
+                        NextPc::Halt => break,
+                        NextPc::Relative(_) | NextPc::Absolute(_) => {
+                            // Branch or jump instruction
+                            let new_pc = next_pc.target(self.pc).unwrap_or_default();
+
+                            // Returning from a thread's start function ends the thread
+                            if new_pc == THREAD_RETURN_ADDRESS && self.threads.is_active() {
+                                let exit_value = self.registers.read(2);
+                                if !self.threads.exit_current(
+                                    exit_value,
+                                    &mut self.registers,
+                                    &mut self.pc,
+                                ) {
+                                    self.status(format_args!("All threads finished"));
+                                    break;
                                 }
-                            };
+                                continue;
+                            }
 
                             // Track branching for debugging
-                            println!(
-                                "Branch/Jump: from PC=0x{:08X} to PC=0x{:08X}, offset=0x{:08X}",
-                                self.pc, new_pc, offset
-                            );
+                            self.status(format_args!(
+                                "Branch/Jump: from PC=0x{:08X} to PC=0x{:08X}",
+                                self.pc, new_pc
+                            ));
 
                             // Check for potential infinite loop (jumping to same address)
                             if new_pc == self.pc && pc_frequency.get(&self.pc).unwrap_or(&0) > &10 {
-                                println!("Warning: Jump to same address detected (0x{:08X}). Breaking potential infinite loop.", 
-                                         new_pc);
+                                self.status(format_args!("Warning: Jump to same address detected (0x{:08X}). Breaking potential infinite loop.", 
+                                         new_pc));
                                 self.pc = self.pc.wrapping_add(4); // Skip to next instruction
-                            } else if new_pc < self.memory.size as u32 {
+                            } else if self.memory.translate_address(new_pc as usize)
+                                < self.memory.size
+                            {
                                 if self.trace_enabled
                                     && pc_frequency.get(&self.pc).unwrap_or(&0) > &10
                                 {
@@ -348,21 +712,21 @@ impl Simulator {
                                 }
                                 self.pc = new_pc;
                             } else {
-                                println!("Invalid jump target: 0x{:08X}", new_pc);
-                                self.exception = Some(Exception::MemoryAccessViolation);
+                                let cause = SimulationErrorCause::PcOutOfBounds(new_pc);
+                                error = Some(self.fault(Some(word), cause));
                                 break;
                             }
                         },
-                        None => {
+                        NextPc::Next => {
                             // Regular instruction - increment PC
                             self.pc += 4;
 
                             // Check if an exception occurred during execution
                             if self.exception.is_some() {
-                                println!(
+                                self.status(format_args!(
                                     "Exception during instruction execution at PC: 0x{:08X}",
                                     self.pc - 4
-                                );
+                                ));
                                 break;
                             }
                         },
@@ -371,105 +735,184 @@ impl Simulator {
             }
         }
 
-        println!(
+        if self.termination_reason.is_none() {
+            self.termination_reason = Some(self.reason_from_exception());
+        }
+        if let Some(progress) = self.progress.as_mut() {
+            progress.finish(self.step_count, self.step_count);
+        }
+
+        self.status(format_args!(
             "Simulation ended after executing {} instructions",
             self.step_count
-        );
-        println!("Final PC: 0x{:08X}", self.pc);
+        ));
+        self.status(format_args!("Final PC: 0x{:08X}", self.pc));
+        if let Some(reason) = self.termination_reason {
+            self.status(format_args!("Termination reason: {}", reason));
+        }
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
     }
 
-    pub fn step(&mut self) -> bool {
-        // Execute a single instruction and return true if execution should continue
+    /// Execute a single instruction. Returns `Ok(true)` while execution should
+    /// continue, `Ok(false)` once the program stops normally and an error if
+    /// the instruction faulted.
+    pub fn step(&mut self) -> Result<bool, SimulationError> {
+        if self.step_count == 0 {
+            self.termination_reason = None;
+            self.syscall_usage = SyscallUsage::default();
+            self.run_started = Instant::now();
+            if let Some(progress) = self.progress.as_mut() {
+                progress.start();
+            }
+        }
 
-        // Check if we've reached the maximum number of steps
-        self.step_count += 1;
-        if self.step_count > self.max_steps {
-            println!(
-                "Reached maximum instruction limit ({}). Stopping execution.",
-                self.max_steps
-            );
-            return false;
+        let result = self.step_instruction();
+        if !matches!(result, Ok(true)) && self.termination_reason.is_none() {
+            self.termination_reason = Some(self.reason_from_exception());
+        }
+        result
+    }
+
+    fn step_instruction(&mut self) -> Result<bool, SimulationError> {
+        // Stop once the instruction, cycle or time budget is used up
+        if let Some(reason) = self.check_limits() {
+            self.status(format_args!("Stopping execution: {}", reason));
+            self.termination_reason = Some(reason);
+            return Ok(false);
         }
+        self.take_due_checkpoint();
+        self.step_count += 1;
+        self.update_progress();
+
+        // Preempt the running guest thread once its time slice is used up
+        self.threads.tick(&mut self.registers, &mut self.pc);
+
+        // Deliver pending interrupts to the guest handler
+        self.service_interrupts();
 
         // Check if this address is a breakpoint
         if self.break_points.contains_key(&self.pc) {
-            println!("Breakpoint hit at address 0x{:08X}", self.pc);
+            self.status(format_args!("Breakpoint hit at address 0x{:08X}", self.pc));
             self.exception = Some(Exception::BreakPoint);
-            return false;
+            return Ok(false);
         }
 
         // Update PC in registers for branch delay calculations
         self.registers.pc = self.pc;
 
         // Fetch instruction
-        let instruction = self.fetch_instruction();
+        let (word, instruction) = self.fetch_instruction()?;
+        if let Some(taint) = self.taint.as_mut() {
+            taint.propagate(&instruction, &self.registers, &self.memory, self.pc);
+        }
+        if let Some(profile) = self.stack_profile.as_mut() {
+            profile.record(&instruction, &self.registers, self.pc);
+        }
+        if let Some(guard) = self.control_flow.as_mut() {
+            guard
+                .check(&instruction, &self.registers, &self.memory, self.pc)
+                .map_err(|cause| self.fault(Some(word), cause))?;
+        }
 
         // Execute instruction
         match instruction {
             Instruction::InvalidInstruction => {
-                println!("Invalid instruction encountered at PC: 0x{:08X}", self.pc);
-                self.exception = Some(Exception::InvalidInstruction);
-                return false;
+                if !self.skip_unknown_instruction(word) {
+                    return Err(self.fault(Some(word), SimulationErrorCause::InvalidInstruction));
+                }
             },
             Instruction::Syscall => {
+                let syscall_num = self.registers.read(2);
+                if ThreadScheduler::is_thread_syscall(syscall_num) {
+                    return Ok(self.handle_thread_syscall(syscall_num));
+                }
+
+                // Keys queued as events are read before host stdin
+                if syscall_num == 12 && self.interrupts.has_pending_key() {
+                    self.read_queued_key();
+                    return Ok(true);
+                }
+
                 // Handle system call
-                if let Some(new_pc) = handle_syscall(&mut self.registers, &mut self.memory) {
-                    if new_pc == 0xFFFFFFFF {
-                        // Program termination requested
-                        println!("Program terminated via syscall at PC: 0x{:08X}", self.pc);
-                        return false;
-                    }
-                    self.pc = new_pc;
-                } else {
-                    // Normal syscall - continue execution
-                    self.pc += 4;
+                let pending = self.begin_commit(word, &instruction);
+                let next_pc = self.syscall();
+                self.end_commit(pending);
+                if next_pc == NextPc::Halt {
+                    self.status(format_args!(
+                        "Program terminated via syscall at PC: 0x{:08X}",
+                        self.pc
+                    ));
+                    return Ok(false);
                 }
+                self.pc += 4;
+            },
+            Instruction::Break { code }
+                if code == SEMIHOST_BREAK_CODE && self.semihosting.is_some() =>
+            {
+                let pending = self.begin_commit(word, &instruction);
+                self.host_call();
+                self.end_commit(pending);
+                self.pc += 4;
             },
             Instruction::Break { code: _ } => {
-                println!(
+                self.status(format_args!(
                     "Breakpoint instruction encountered at PC: 0x{:08X}",
                     self.pc
-                );
+                ));
                 self.exception = Some(Exception::BreakPoint);
-                return false;
+                return Ok(false);
             },
             Instruction::Nop => {
                 // Just a regular NOP - continue execution
+                let pending = self.begin_commit(word, &instruction);
+                self.end_commit(pending);
                 self.pc += 4;
             },
             _ => {
                 // Execute regular instruction
-                let pc_offset = self.execute_instruction(&instruction);
-
-                match pc_offset {
-                    Some(offset) => {
-                        // Branch or jump instruction - calculate new PC
-                        let new_pc = if offset == 0xFFFFFFFF {
-                            // Special value used by some instructions to set PC directly
-                            self.registers.read(self.registers.target_reg.unwrap_or(0))
-                        } else {
-                            self.pc.wrapping_add(offset)
-                        };
+                let next_pc = self.execute_instruction(word, &instruction);
 
-                        if new_pc < self.memory.size as u32 {
+                match next_pc {
+                    NextPc::Trap(exception) => {
+                        return Err(self.trap(word, &instruction, exception));
+                    },
+                    NextPc::Halt => return Ok(false),
+                    NextPc::Relative(_) | NextPc::Absolute(_) => {
+                        // Branch or jump instruction
+                        let new_pc = next_pc.target(self.pc).unwrap_or_default();
+
+                        // Returning from a thread's start function ends the thread
+                        if new_pc == THREAD_RETURN_ADDRESS && self.threads.is_active() {
+                            let exit_value = self.registers.read(2);
+                            return Ok(self.threads.exit_current(
+                                exit_value,
+                                &mut self.registers,
+                                &mut self.pc,
+                            ));
+                        }
+
+                        if self.memory.translate_address(new_pc as usize) < self.memory.size {
                             self.pc = new_pc;
                         } else {
-                            println!("Invalid jump target: 0x{:08X}", new_pc);
-                            self.exception = Some(Exception::MemoryAccessViolation);
-                            return false;
+                            let cause = SimulationErrorCause::PcOutOfBounds(new_pc);
+                            return Err(self.fault(Some(word), cause));
                         }
                     },
-                    None => {
+                    NextPc::Next => {
                         // Regular instruction - increment PC
                         self.pc += 4;
 
                         // Check if an exception occurred during execution
                         if self.exception.is_some() {
-                            println!(
+                            self.status(format_args!(
                                 "Exception during instruction execution at PC: 0x{:08X}",
                                 self.pc - 4
-                            );
-                            return false;
+                            ));
+                            return Ok(false);
                         }
                     },
                 }
@@ -477,26 +920,89 @@ impl Simulator {
         }
 
         // Execution should continue
-        true
+        Ok(true)
     }
 
-    fn fetch_instruction(&self) -> Instruction {
+    fn fetch_instruction(&mut self) -> Result<(u32, Instruction), SimulationError> {
         match self.memory.read_word(self.pc as usize) {
-            Some(instruction_word) => {
+            Ok(instruction_word) => {
                 if self.debug_enabled || self.trace_enabled {
                     println!("Fetched 0x{:08X} at PC=0x{:08X}", instruction_word, self.pc);
                 }
-                decode_instruction(instruction_word)
+                if let Some(recent) = self.recent_instructions.as_mut() {
+                    recent.record(self.pc, instruction_word);
+                }
+                Ok((instruction_word, decode_instruction(instruction_word)))
             },
-            None => {
-                println!("Memory access violation at PC: 0x{:08X}", self.pc);
-                Instruction::InvalidInstruction
+            Err(error) => {
+                let cause = match error {
+                    MemError::PermissionDenied(..) => SimulationErrorCause::from(error),
+                    _ => SimulationErrorCause::PcOutOfBounds(self.pc),
+                };
+                Err(self.fault(None, cause))
             },
         }
     }
 
-    fn execute_instruction(&mut self, instruction: &Instruction) -> Option<u32> {
-        // Execute the instruction and return the PC offset if it's a branch/jump
+    // Turn an exception raised by an instruction into a fault for the caller.
+    // Faulting loads and stores leave their base register unchanged, so the
+    // address is recomputed from it.
+    fn trap(
+        &mut self,
+        word: u32,
+        instruction: &Instruction,
+        exception: Exception,
+    ) -> SimulationError {
+        let cause = match exception {
+            Exception::MemoryAccessViolation => {
+                let address = instruction.get_address(&self.registers, self.pc);
+                memory_fault_cause(&self.memory, instruction, address)
+            },
+            Exception::AddressError => match instruction.memory_operand() {
+                Some((base, offset)) => {
+                    SimulationErrorCause::AddressError(self.registers.read(base), offset)
+                },
+                None => SimulationErrorCause::InvalidInstruction,
+            },
+            Exception::Trap => SimulationErrorCause::Trap(trap_code(instruction)),
+            _ => SimulationErrorCause::InvalidInstruction,
+        };
+        self.fault(Some(word), cause)
+    }
+
+    // Record a fault as the pending exception and build the error for the caller
+    fn fault(&mut self, instruction: Option<u32>, cause: SimulationErrorCause) -> SimulationError {
+        self.exception = Some(match cause {
+            SimulationErrorCause::InvalidInstruction => Exception::InvalidInstruction,
+            SimulationErrorCause::Trap(_)
+            | SimulationErrorCause::ReturnMismatch(..)
+            | SimulationErrorCause::StackSmashed(_) => Exception::Trap,
+            SimulationErrorCause::AddressError(..) => Exception::AddressError,
+            _ => Exception::MemoryAccessViolation,
+        });
+        SimulationError {
+            pc: self.pc,
+            instruction,
+            cause,
+        }
+    }
+
+    // Capture the state a commit log record needs before an instruction
+    // executes, when the log is enabled
+    fn begin_commit(&self, word: u32, instruction: &Instruction) -> Option<PendingCommit> {
+        self.commit_log
+            .as_ref()
+            .map(|_| PendingCommit::new(self.pc, word, instruction, &self.registers))
+    }
+
+    fn end_commit(&mut self, pending: Option<PendingCommit>) {
+        if let (Some(pending), Some(log)) = (pending, self.commit_log.as_mut()) {
+            log.record(&pending.finish(&self.registers));
+        }
+    }
+
+    fn execute_instruction(&mut self, word: u32, instruction: &Instruction) -> NextPc {
+        // Execute the instruction and return where execution continues
         // Print debug info for branching instructions to diagnose test failures
         if let Instruction::Beq { rs, rt, offset } = &instruction {
             let rs_val = self.registers.read(*rs);
@@ -506,7 +1012,272 @@ impl Simulator {
                 rs, rs_val, rt, rt_val, offset, self.pc
             );
         }
-        instruction.execute(&mut self.registers, &mut self.memory)
+        // rdhwr reads the counters, and every instruction takes one cycle
+        let retired = self.step_count.saturating_sub(1) as u64;
+        self.registers.hardware.cycles = cycle_counter(&mut self.throttle, retired);
+        self.registers.hardware.instructions = retired;
+        let pending = self.begin_commit(word, instruction);
+        let next_pc = instruction.execute(&mut self.registers, &mut self.memory);
+        if !matches!(next_pc, NextPc::Trap(_)) {
+            self.end_commit(pending);
+        }
+        next_pc
+    }
+
+    // Handle the thread create/join/yield/exit/self syscalls. Returns false
+    // when no thread is left to run.
+    fn handle_thread_syscall(&mut self, syscall_num: u32) -> bool {
+        // A thread resumes after its syscall when it is scheduled again
+        self.pc = self.pc.wrapping_add(4);
+
+        match syscall_num {
+            SYSCALL_THREAD_CREATE => {
+                let entry = self.registers.read(4);
+                let arg = self.registers.read(5);
+                let stack_top = self.registers.read(6);
+                let id = self
+                    .threads
+                    .create(&self.registers, self.pc, entry, arg, stack_top);
+                self.registers.write(2, id);
+                true
+            },
+            SYSCALL_THREAD_JOIN => {
+                let id = self.registers.read(4);
+                self.threads.join(id, &mut self.registers, &mut self.pc)
+            },
+            SYSCALL_THREAD_YIELD => self.threads.yield_now(&mut self.registers, &mut self.pc),
+            SYSCALL_THREAD_EXIT => {
+                let exit_value = self.registers.read(4);
+                self.threads
+                    .exit_current(exit_value, &mut self.registers, &mut self.pc)
+            },
+            SYSCALL_THREAD_SELF => {
+                let id = self.threads.current_id();
+                self.registers.write(2, id);
+                true
+            },
+            _ => true,
+        }
+    }
+
+    // Advance the CP0 timer, raise due events and, if an unmasked interrupt
+    // is pending, transfer control to the exception vector
+    fn service_interrupts(&mut self) {
+        self.interrupts.tick(&mut self.registers, self.step_count);
+        if let Some(console) = self.console.as_ref() {
+            // Key presses go to the keyboard device, which holds the
+            // keyboard line while a key waits and its interrupt is enabled
+            while let Some(key) = self.interrupts.take_key(&mut self.registers) {
+                console.press_key(key);
+            }
+            if console.keyboard_interrupt() {
+                set_pending(&mut self.registers, KEYBOARD_IRQ);
+            } else {
+                clear_pending(&mut self.registers, KEYBOARD_IRQ);
+            }
+        }
+        if let Some(disk) = self.disk.as_ref() {
+            disk.advance(&mut self.memory);
+            if disk.interrupt() {
+                set_pending(&mut self.registers, DISK_IRQ);
+            } else {
+                clear_pending(&mut self.registers, DISK_IRQ);
+            }
+        }
+        if let Some(network) = self.network.as_ref() {
+            network.poll();
+            if network.interrupt() {
+                set_pending(&mut self.registers, NETWORK_IRQ);
+            } else {
+                clear_pending(&mut self.registers, NETWORK_IRQ);
+            }
+        }
+        if let Some(vector) = self.interrupts.deliver(&mut self.registers, self.pc) {
+            if self.trace_enabled || self.debug_enabled {
+                println!(
+                    "Interrupt taken at PC 0x{:08X}, jumping to handler at 0x{:08X}",
+                    self.pc, vector
+                );
+            }
+            self.pc = vector;
+        }
+    }
+
+    fn host_call(&mut self) {
+        if let Some(semihosting) = self.semihosting.as_mut() {
+            semihosting.call(&mut self.registers, &mut self.memory, self.step_count);
+        }
+    }
+
+    fn read_queued_key(&mut self) {
+        let key = self.interrupts.take_key(&mut self.registers).unwrap_or(0);
+        self.registers.write(2, key as u32);
+        self.pc = self.pc.wrapping_add(4);
+    }
+
+    /// Map a screen and keyboard into memory. Returns a handle for pressing
+    /// keys and rendering the screen, which stays valid while the guest runs.
+    pub fn attach_console(&mut self, config: ConsoleConfig) -> Console {
+        let console = Console::attach(&mut self.memory, config);
+        self.console = Some(console.clone());
+        console
+    }
+
+    /// Map a block storage device into memory. Returns a handle for reading
+    /// its sectors and statistics, or the error from opening its image file.
+    pub fn attach_disk(&mut self, config: DiskConfig) -> io::Result<Disk> {
+        let disk = Disk::attach(&mut self.memory, config)?;
+        self.disk = Some(disk.clone());
+        Ok(disk)
+    }
+
+    /// Map a bitmap framebuffer into memory. Returns a handle for reading
+    /// pixels and saving them as images.
+    pub fn attach_framebuffer(&mut self, config: FramebufferConfig) -> Framebuffer {
+        let framebuffer = Framebuffer::attach(&mut self.memory, config);
+        self.framebuffer = Some(framebuffer.clone());
+        framebuffer
+    }
+
+    /// Serve host calls made with `break 0xAB`, resolving guest file paths
+    /// under `root`
+    pub fn enable_semihosting<P: AsRef<Path>>(&mut self, root: P) {
+        self.semihosting = Some(Semihosting::new(root));
+    }
+
+    /// Keep running past instruction words the decoder does not recognise,
+    /// recording each one and handling it as `policy` says
+    /// Track taint from now on, from the read syscalls when `taint_input`
+    /// is set and from ranges marked with `taint_memory`
+    pub fn enable_taint_tracking(&mut self, taint_input: bool) {
+        self.taint = Some(TaintTracker::new(taint_input));
+    }
+
+    /// Check returns against a shadow stack, saved return addresses for
+    /// overwrites, or both, from now on
+    pub fn enable_control_flow_checks(&mut self, checks: ControlFlowChecks) {
+        self.control_flow = Some(ControlFlowGuard::new(checks));
+    }
+
+    /// Taint `length` bytes from `address`, enabling taint tracking without
+    /// input sources if it is off
+    pub fn taint_memory(&mut self, address: u32, length: u32) {
+        self.taint
+            .get_or_insert_with(TaintTracker::default)
+            .taint_range(&self.memory, address, length);
+    }
+
+    pub fn enable_lenient_decode(&mut self, policy: UnknownInstructionPolicy) {
+        self.lenient_decode = Some(DecodeReport::new(policy));
+    }
+
+    // Record an unknown instruction word and move past it under lenient
+    // decoding. Returns false when lenient decoding is off.
+    fn skip_unknown_instruction(&mut self, word: u32) -> bool {
+        let report = match self.lenient_decode.as_mut() {
+            Some(report) => report,
+            None => return false,
+        };
+        report.record(self.pc, word);
+        self.pc = match report.policy {
+            UnknownInstructionPolicy::Nop => self.pc.wrapping_add(4),
+            UnknownInstructionPolicy::Trap => {
+                enter_exception(&mut self.registers, self.pc, EXCCODE_RI)
+            },
+        };
+        true
+    }
+
+    /// Capture the registers, program counter and memory
+    pub fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot::capture(&self.registers, &self.memory, self.pc, self.step_count)
+    }
+
+    /// Continue from a snapshot taken of a simulator with the same memory size
+    pub fn restore_snapshot(&mut self, snapshot: &StateSnapshot) -> Result<(), String> {
+        snapshot.restore(&mut self.registers, &mut self.memory)?;
+        self.pc = snapshot.pc;
+        self.step_count = snapshot.instructions;
+        self.exception = None;
+        self.termination_reason = None;
+        Ok(())
+    }
+
+    /// Take a delta checkpoint of the registers and memory every `interval`
+    /// instructions, starting before the first
+    pub fn enable_checkpoints(&mut self, interval: usize) {
+        self.checkpoints = Some(CheckpointLog::new(interval));
+    }
+
+    /// Continue from checkpoint `index`, dropping the checkpoints after it
+    pub fn restore_checkpoint(&mut self, index: usize) -> Result<(), String> {
+        let log = self
+            .checkpoints
+            .as_mut()
+            .ok_or_else(|| "checkpoints are not enabled".to_string())?;
+        let pc = log.restore(index, &mut self.registers, &mut self.memory)?;
+        self.pc = pc;
+        self.registers.pc = pc;
+        self.step_count = log.checkpoints()[index].instructions;
+        self.exception = None;
+        self.termination_reason = None;
+        Ok(())
+    }
+
+    /// Count instructions by call stack from the current PC on, one in
+    /// every `every`, for a flamegraph
+    pub fn enable_stack_profile(&mut self, every: u64) {
+        self.stack_profile = Some(StackProfile::new(self.pc, every));
+    }
+
+    /// Remember the last `depth` instructions fetched so `crash_dump` can
+    /// list them
+    pub fn enable_crash_dumps(&mut self, depth: usize) {
+        self.recent_instructions = Some(RecentInstructions::new(depth));
+    }
+
+    /// The machine state for a postmortem, if the run stopped on a fault
+    pub fn crash_dump(&self) -> Option<CrashDump> {
+        let reason = self.termination_reason.filter(|reason| reason.is_fault())?;
+        Some(CrashDump::capture(
+            "functional",
+            reason,
+            self.pc,
+            self.step_count,
+            &self.registers,
+            &self.memory,
+            self.recent_instructions.as_ref(),
+        ))
+    }
+
+    fn take_due_checkpoint(&mut self) {
+        if let Some(log) = self.checkpoints.as_mut() {
+            if log.is_due(self.step_count) {
+                log.take(&self.registers, &mut self.memory, self.pc, self.step_count);
+            }
+        }
+    }
+
+    /// Map a network device into memory. Returns a handle for injecting
+    /// packets and reading statistics, or the error from binding its socket.
+    pub fn attach_network(&mut self, config: NetworkConfig) -> io::Result<Network> {
+        let network = Network::attach(&mut self.memory, config)?;
+        self.network = Some(network.clone());
+        Ok(network)
+    }
+
+    /// Raise an asynchronous event (key press, timer, external line) now
+    pub fn queue_event(&mut self, event: AsyncEvent) {
+        self.interrupts.raise(&mut self.registers, event);
+    }
+
+    /// Raise an asynchronous event after `at_step` instructions have executed
+    pub fn schedule_event(&mut self, at_step: usize, event: AsyncEvent) {
+        self.interrupts.schedule(at_step, event);
+    }
+
+    pub fn set_time_slice(&mut self, instructions: usize) {
+        self.threads.time_slice = instructions.max(1);
     }
 
     pub fn add_breakpoint(&mut self, address: u32) {
@@ -551,8 +1322,92 @@ impl Simulator {
     }
 
     pub fn set_max_steps(&mut self, max_steps: usize) {
-        self.max_steps = max_steps;
-        println!("Maximum steps set to {}", max_steps);
+        self.set_max_instructions(max_steps);
+    }
+
+    pub fn set_max_instructions(&mut self, max_instructions: usize) {
+        self.limits.max_instructions = max_instructions;
+        println!("Maximum instructions set to {}", max_instructions);
+    }
+
+    /// The functional simulator retires one instruction per cycle
+    pub fn set_max_cycles(&mut self, max_cycles: usize) {
+        self.limits.max_cycles = max_cycles;
+        println!("Maximum cycles set to {}", max_cycles);
+    }
+
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.limits.timeout = timeout;
+    }
+
+    /// Stop runs once the simulator holds more host memory than this. The
+    /// run ends with `TerminationReason::MemoryLimit`, and
+    /// `limits.check_memory(&self.memory_usage())` describes the overrun.
+    pub fn set_max_memory(&mut self, max_memory: Option<usize>) {
+        self.limits.max_memory = max_memory;
+    }
+
+    /// Host memory held by the simulator: the memory image and any output
+    /// captured from the program
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            memory: self.memory.data.len(),
+            caches: 0,
+            traces: self.captured_output.as_ref().map_or(0, Vec::len),
+        }
+    }
+
+    /// Host memory a simulator with this much memory starts with, so a cap
+    /// can be checked before any of it is allocated
+    pub fn projected_memory_usage(memory_size: usize) -> MemoryUsage {
+        MemoryUsage {
+            memory: memory_size,
+            ..MemoryUsage::default()
+        }
+    }
+
+    /// Report progress on stderr at most once per interval
+    pub fn enable_progress(&mut self, interval: Duration) {
+        self.progress = Some(ProgressReporter::stderr(interval));
+    }
+
+    /// Report progress through a callback at most once per interval
+    pub fn set_progress_callback(&mut self, interval: Duration, callback: ProgressCallback) {
+        self.progress = Some(ProgressReporter::new(interval, callback));
+    }
+
+    fn update_progress(&mut self) {
+        if let Some(progress) = self.progress.as_mut() {
+            progress.update(self.step_count, self.step_count);
+        }
+    }
+
+    fn check_limits(&self) -> Option<TerminationReason> {
+        let reason = self
+            .limits
+            .exceeded(self.step_count, self.step_count, self.run_started);
+        if reason.is_none()
+            && self.limits.max_memory.is_some()
+            && self.step_count % MEMORY_CHECK_INTERVAL == 0
+            && self.limits.check_memory(&self.memory_usage()).is_err()
+        {
+            return Some(TerminationReason::MemoryLimit);
+        }
+        reason
+    }
+
+    // Map the exception that stopped a run to its termination reason
+    fn reason_from_exception(&self) -> TerminationReason {
+        match self.exception {
+            Some(Exception::InvalidInstruction) => TerminationReason::InvalidInstruction,
+            Some(Exception::BreakPoint) => TerminationReason::Breakpoint,
+            Some(Exception::MemoryAccessViolation) | Some(Exception::AddressError) => {
+                TerminationReason::MemoryFault
+            },
+            Some(Exception::Trap) => TerminationReason::Trap,
+            Some(_) => TerminationReason::Halted,
+            None => TerminationReason::Exited,
+        }
     }
 
     pub fn get_disassembly(&self, address: u32, count: usize) -> Vec<(u32, String)> {
@@ -564,7 +1419,7 @@ impl Simulator {
                 break;
             }
 
-            if let Some(word) = self.memory.read_word(current_addr as usize) {
+            if let Ok(word) = self.memory.read_word(current_addr as usize) {
                 let instruction = decode_instruction(word);
                 let disasm = format!(
                     "{:08X}: {}",
@@ -583,6 +1438,19 @@ impl Simulator {
     }
 }
 
+// Code field of a register trap, which immediate traps do not have
+fn trap_code(instruction: &Instruction) -> u32 {
+    match instruction {
+        Instruction::Teq { code, .. }
+        | Instruction::Tne { code, .. }
+        | Instruction::Tge { code, .. }
+        | Instruction::Tgeu { code, .. }
+        | Instruction::Tlt { code, .. }
+        | Instruction::Tltu { code, .. } => *code,
+        _ => 0,
+    }
+}
+
 // Decode an instruction word into an Instruction enum
 // Basic implementation for decode_instruction
 pub fn decode_instruction(instruction_word: u32) -> Instruction {
@@ -604,10 +1472,12 @@ pub fn decode_instruction(instruction_word: u32) -> Instruction {
                 _ if instruction_word == 0 => Instruction::Nop,
                 // Then check other function codes
                 0x20 => Instruction::Add { rd, rs, rt },
+                0x21 => Instruction::Addu { rd, rs, rt },
                 0x22 => Instruction::Sub { rd, rs, rt },
                 0x24 => Instruction::And { rd, rs, rt },
                 0x25 => Instruction::Or { rd, rs, rt },
                 0x2A => Instruction::Slt { rd, rs, rt },
+                0x2B => Instruction::Sltu { rd, rs, rt },
                 0x00 => Instruction::Sll { rd, rt, shamt },
                 0x02 => Instruction::Srl { rd, rt, shamt },
                 0x03 => Instruction::Sra { rd, rt, shamt },
@@ -617,8 +1487,9 @@ pub fn decode_instruction(instruction_word: u32) -> Instruction {
                 0x08 => Instruction::Jr { rs },
                 0x09 => Instruction::Jalr { rd, rs },
                 0x0C => Instruction::Syscall,
+                // The 20-bit break code sits between rs and funct
                 0x0D => Instruction::Break {
-                    code: instruction_word >> 16,
+                    code: (instruction_word >> 6) & 0xFFFFF,
                 },
                 0x10 => Instruction::Mfhi { rd },
                 0x11 => Instruction::Mthi { rs },
@@ -629,6 +1500,18 @@ pub fn decode_instruction(instruction_word: u32) -> Instruction {
                 0x1B => Instruction::Divu { rs, rt },
                 0x26 => Instruction::Xor { rd, rs, rt },
                 0x27 => Instruction::Nor { rd, rs, rt },
+                // Register traps carry a 10-bit code between rd and funct
+                0x30..=0x34 | 0x36 => {
+                    let code = (instruction_word >> 6) & 0x3FF;
+                    match funct {
+                        0x30 => Instruction::Tge { rs, rt, code },
+                        0x31 => Instruction::Tgeu { rs, rt, code },
+                        0x32 => Instruction::Tlt { rs, rt, code },
+                        0x33 => Instruction::Tltu { rs, rt, code },
+                        0x34 => Instruction::Teq { rs, rt, code },
+                        _ => Instruction::Tne { rs, rt, code },
+                    }
+                },
                 _ => {
                     println!(
                         "Unrecognized R-type instruction with funct: 0x{:02X}",
@@ -717,6 +1600,16 @@ pub fn decode_instruction(instruction_word: u32) -> Instruction {
             base: rs,
             offset: immediate as i16,
         },
+        0x30 => Instruction::Ll {
+            rt,
+            base: rs,
+            offset: immediate as i16,
+        },
+        0x38 => Instruction::Sc {
+            rt,
+            base: rs,
+            offset: immediate as i16,
+        },
         0x04 => Instruction::Beq {
             rs,
             rt,
@@ -746,6 +1639,30 @@ pub fn decode_instruction(instruction_word: u32) -> Instruction {
                     rs,
                     offset: immediate as i16,
                 },
+                0x08 => Instruction::Tgei {
+                    rs,
+                    imm: immediate as i16,
+                },
+                0x09 => Instruction::Tgeiu {
+                    rs,
+                    imm: immediate as i16,
+                },
+                0x0A => Instruction::Tlti {
+                    rs,
+                    imm: immediate as i16,
+                },
+                0x0B => Instruction::Tltiu {
+                    rs,
+                    imm: immediate as i16,
+                },
+                0x0C => Instruction::Teqi {
+                    rs,
+                    imm: immediate as i16,
+                },
+                0x0E => Instruction::Tnei {
+                    rs,
+                    imm: immediate as i16,
+                },
                 _ => {
                     println!("Unrecognized branch instruction with rt: 0x{:02X}", rt);
                     Instruction::InvalidInstruction
@@ -754,6 +1671,27 @@ pub fn decode_instruction(instruction_word: u32) -> Instruction {
         },
         0x02 => Instruction::J { target },
         0x03 => Instruction::Jal { target },
+        0x10 => {
+            // System control coprocessor (CP0)
+            match rs {
+                // The select field goes in bits 7:5 of the register number
+                0x00 => Instruction::Mfc0 {
+                    rt,
+                    rd: rd | ((funct & 0x7) << CP0_SELECT_SHIFT),
+                },
+                0x04 => Instruction::Mtc0 {
+                    rt,
+                    rd: rd | ((funct & 0x7) << CP0_SELECT_SHIFT),
+                },
+                0x0A => Instruction::Rdpgpr { rd, rt },
+                0x0E => Instruction::Wrpgpr { rd, rt },
+                0x10 if funct == 0x18 => Instruction::Eret,
+                _ => {
+                    println!("Unrecognized CP0 instruction with rs: 0x{:02X}", rs);
+                    Instruction::InvalidInstruction
+                },
+            }
+        },
         // Coprocessor instructions
         0x31 => Instruction::LwC1 {
             ft: rt,
@@ -783,9 +1721,8 @@ pub fn decode_instruction(instruction_word: u32) -> Instruction {
                         0x05 => Instruction::AbsS { fd, fs },
                         0x07 => Instruction::NegS { fd, fs },
                         0x06 => Instruction::MovS { fd, fs },
-                        0x32 => Instruction::CvtWS { fd, fs },
-                        0x20 => Instruction::CvtSW { fd, fs },
-                        0x30 => Instruction::CmpS { fs, ft, cond: 0 }, // eq
+                        0x24 => Instruction::CvtWS { fd, fs },
+                        0x32 => Instruction::CmpS { fs, ft, cond: 0 }, // eq
                         0x3C => Instruction::CmpS { fs, ft, cond: 1 }, // lt
                         0x3E => Instruction::CmpS { fs, ft, cond: 2 }, // le
                         _ => {
@@ -794,6 +1731,16 @@ pub fn decode_instruction(instruction_word: u32) -> Instruction {
                         },
                     }
                 },
+                0x14 => {
+                    // Word format, only used as the source of conversions
+                    match funct {
+                        0x20 => Instruction::CvtSW { fd, fs },
+                        _ => {
+                            println!("Unrecognized FPU instruction with funct: 0x{:02X}", funct);
+                            Instruction::InvalidInstruction
+                        },
+                    }
+                },
                 0x08 => {
                     // Branch on FP condition
                     if rt == 0 {
@@ -815,6 +1762,8 @@ pub fn decode_instruction(instruction_word: u32) -> Instruction {
                 },
             }
         },
+        // SPECIAL3; only rdhwr is implemented
+        0x1F if funct == 0x3B && rs == 0 && shamt == 0 => Instruction::Rdhwr { rt, rd },
         _ => {
             println!("Unrecognized instruction with opcode: 0x{:02X}", opcode);
             Instruction::InvalidInstruction
@@ -822,12 +1771,16 @@ pub fn decode_instruction(instruction_word: u32) -> Instruction {
     }
 }
 
-// Convert an instruction to a human-readable string
-fn instruction_to_string(instruction: &Instruction, raw_word: u32) -> String {
+/// Assembly text of a decoded instruction, with the raw word for ones that
+/// do not decode
+pub fn instruction_to_string(instruction: &Instruction, raw_word: u32) -> String {
     match instruction {
         Instruction::Add { rd, rs, rt } => {
             format!("add ${}, ${}, ${}", rd, rs, rt)
         },
+        Instruction::Addu { rd, rs, rt } => {
+            format!("addu ${}, ${}, ${}", rd, rs, rt)
+        },
         Instruction::Sub { rd, rs, rt } => {
             format!("sub ${}, ${}, ${}", rd, rs, rt)
         },
@@ -846,6 +1799,9 @@ fn instruction_to_string(instruction: &Instruction, raw_word: u32) -> String {
         Instruction::Slt { rd, rs, rt } => {
             format!("slt ${}, ${}, ${}", rd, rs, rt)
         },
+        Instruction::Sltu { rd, rs, rt } => {
+            format!("sltu ${}, ${}, ${}", rd, rs, rt)
+        },
         Instruction::Sll { rd, rt, shamt } => {
             format!("sll ${}, ${}, {}", rd, rt, shamt)
         },
@@ -943,6 +1899,48 @@ fn instruction_to_string(instruction: &Instruction, raw_word: u32) -> String {
         Instruction::Sw { rt, base, offset } => {
             format!("sw ${}, {}(${})", rt, offset, base)
         },
+        Instruction::Ll { rt, base, offset } => {
+            format!("ll ${}, {}(${})", rt, offset, base)
+        },
+        Instruction::Sc { rt, base, offset } => {
+            format!("sc ${}, {}(${})", rt, offset, base)
+        },
+        Instruction::Teq { rs, rt, code } => {
+            format!("teq ${}, ${}, {}", rs, rt, code)
+        },
+        Instruction::Tne { rs, rt, code } => {
+            format!("tne ${}, ${}, {}", rs, rt, code)
+        },
+        Instruction::Tge { rs, rt, code } => {
+            format!("tge ${}, ${}, {}", rs, rt, code)
+        },
+        Instruction::Tgeu { rs, rt, code } => {
+            format!("tgeu ${}, ${}, {}", rs, rt, code)
+        },
+        Instruction::Tlt { rs, rt, code } => {
+            format!("tlt ${}, ${}, {}", rs, rt, code)
+        },
+        Instruction::Tltu { rs, rt, code } => {
+            format!("tltu ${}, ${}, {}", rs, rt, code)
+        },
+        Instruction::Teqi { rs, imm } => {
+            format!("teqi ${}, {}", rs, imm)
+        },
+        Instruction::Tnei { rs, imm } => {
+            format!("tnei ${}, {}", rs, imm)
+        },
+        Instruction::Tgei { rs, imm } => {
+            format!("tgei ${}, {}", rs, imm)
+        },
+        Instruction::Tgeiu { rs, imm } => {
+            format!("tgeiu ${}, {}", rs, imm)
+        },
+        Instruction::Tlti { rs, imm } => {
+            format!("tlti ${}, {}", rs, imm)
+        },
+        Instruction::Tltiu { rs, imm } => {
+            format!("tltiu ${}, {}", rs, imm)
+        },
         Instruction::Beq { rs, rt, offset } => {
             format!("beq ${}, ${}, {}", rs, rt, offset)
         },
@@ -1016,9 +2014,62 @@ fn instruction_to_string(instruction: &Instruction, raw_word: u32) -> String {
         Instruction::BC1F { offset } => {
             format!("bc1f {}", offset)
         },
+        Instruction::Mfc0 { rt, rd } => {
+            format!("mfc0 ${}, {}", rt, cp0_operand(*rd))
+        },
+        Instruction::Mtc0 { rt, rd } => {
+            format!("mtc0 ${}, {}", rt, cp0_operand(*rd))
+        },
+        Instruction::Eret => "eret".to_string(),
+        Instruction::Rdpgpr { rd, rt } => {
+            format!("rdpgpr ${}, ${}", rd, rt)
+        },
+        Instruction::Wrpgpr { rd, rt } => {
+            format!("wrpgpr ${}, ${}", rd, rt)
+        },
+        Instruction::Rdhwr { rt, rd } => {
+            format!("rdhwr ${}, ${}", rt, rd)
+        },
         Instruction::Nop => "nop".to_string(),
         Instruction::InvalidInstruction => {
             format!("INVALID (0x{:08X})", raw_word)
         },
     }
 }
+
+// A CP0 register operand, with its select when it is not 0
+fn cp0_operand(reg_num: u32) -> String {
+    let select = reg_num >> CP0_SELECT_SHIFT;
+    let reg_num = reg_num & ((1 << CP0_SELECT_SHIFT) - 1);
+    if select == 0 {
+        format!("${}", reg_num)
+    } else {
+        format!("${}, {}", reg_num, select)
+    }
+}
+
+// Find out why memory refused the access of a faulting load or store.
+// Loads and stores check their own alignment before accessing memory.
+fn memory_fault_cause(
+    memory: &Memory,
+    instruction: &Instruction,
+    address: u32,
+) -> SimulationErrorCause {
+    let (size, access) = match instruction {
+        Instruction::Lb { .. } | Instruction::Lbu { .. } => (1, MemAccess::Read),
+        Instruction::Sb { .. } => (1, MemAccess::Write),
+        Instruction::Lh { .. } | Instruction::Lhu { .. } => (2, MemAccess::Read),
+        Instruction::Sh { .. } => (2, MemAccess::Write),
+        Instruction::Sw { .. } | Instruction::Sc { .. } | Instruction::SwC1 { .. } => {
+            (4, MemAccess::Write)
+        },
+        _ => (4, MemAccess::Read),
+    };
+    if address % size != 0 {
+        return SimulationErrorCause::MisalignedAccess(address);
+    }
+    match memory.check(address as usize, size as usize, access) {
+        Err(error) => error.into(),
+        Ok(()) => SimulationErrorCause::MemoryViolation(address),
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// snapshot.rs
+//
+// This file contains the snapshot format for the architectural state of a
+// functional run: the program counter, the general-purpose, HI/LO,
+// floating-point and CP0 registers, and every memory page that is not all
+// zeros. Snapshots are written as versioned JSON so other tools can read
+// them, and can be restored into a simulator with the same memory size.
+
+use super::memory::Memory;
+use super::registers::Registers;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+
+/// Name and version written at the top of a snapshot
+pub const SNAPSHOT_FORMAT: &str = "vmips-snapshot";
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Size of the memory pages a snapshot stores
+pub const SNAPSHOT_PAGE_SIZE: usize = 4096;
+
+/// A page of memory holding at least one non-zero byte
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryPage {
+    pub address: u32,
+    /// The page's bytes as lowercase hex
+    pub data: String,
+}
+
+/// Architectural state of a functional run
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub format: String,
+    pub version: u32,
+    pub pc: u32,
+    /// Instructions executed before the snapshot was taken
+    pub instructions: usize,
+    pub registers: Vec<u32>,
+    pub hi: u32,
+    pub lo: u32,
+    /// Bit patterns of the floating-point registers
+    pub fp_registers: Vec<u32>,
+    pub fcsr: u32,
+    pub cp0: Vec<u32>,
+    pub memory_size: usize,
+    pub pages: Vec<MemoryPage>,
+}
+
+impl StateSnapshot {
+    pub fn capture(registers: &Registers, memory: &Memory, pc: u32, instructions: usize) -> Self {
+        let pages = memory
+            .data
+            .chunks(SNAPSHOT_PAGE_SIZE)
+            .enumerate()
+            .filter(|(_, page)| page.iter().any(|&byte| byte != 0))
+            .map(|(index, page)| MemoryPage {
+                address: (index * SNAPSHOT_PAGE_SIZE) as u32,
+                data: page.iter().fold(String::new(), |mut hex, byte| {
+                    let _ = write!(hex, "{:02x}", byte);
+                    hex
+                }),
+            })
+            .collect();
+
+        Self {
+            format: SNAPSHOT_FORMAT.to_string(),
+            version: SNAPSHOT_VERSION,
+            pc,
+            instructions,
+            registers: registers.data.clone(),
+            hi: registers.hi,
+            lo: registers.lo,
+            fp_registers: registers.fp_registers.iter().map(|f| f.to_bits()).collect(),
+            fcsr: registers.fcsr,
+            cp0: registers.cp0.clone(),
+            memory_size: memory.data.len(),
+            pages,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Parse a snapshot, refusing other formats and newer versions
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let snapshot: Self =
+            serde_json::from_str(json).map_err(|e| format!("invalid snapshot: {}", e))?;
+        if snapshot.format != SNAPSHOT_FORMAT {
+            return Err(format!("not a snapshot: format is {:?}", snapshot.format));
+        }
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(format!(
+                "unsupported snapshot version {} (newest supported is {})",
+                snapshot.version, SNAPSHOT_VERSION
+            ));
+        }
+        Ok(snapshot)
+    }
+
+    /// Put the registers and memory back. Memory must be the size it was
+    /// when the snapshot was taken.
+    pub fn restore(&self, registers: &mut Registers, memory: &mut Memory) -> Result<(), String> {
+        if memory.data.len() != self.memory_size {
+            return Err(format!(
+                "snapshot is of {} bytes of memory, not {}",
+                self.memory_size,
+                memory.data.len()
+            ));
+        }
+        if self.registers.len() != 32 || self.fp_registers.len() != 32 || self.cp0.len() != 32 {
+            return Err("snapshot does not hold 32 of each register".to_string());
+        }
+
+        let mut data = vec![0u8; self.memory_size];
+        for page in &self.pages {
+            let bytes = decode_hex(&page.data)
+                .ok_or_else(|| format!("page at 0x{:08X} is not valid hex", page.address))?;
+            let start = page.address as usize;
+            let end = start + bytes.len();
+            if end > data.len() {
+                return Err(format!("page at 0x{:08X} is outside memory", page.address));
+            }
+            data[start..end].copy_from_slice(&bytes);
+        }
+        memory.data = data;
+        memory.mark_dirty(0, self.memory_size);
+
+        registers.data = self.registers.clone();
+        registers.hi = self.hi;
+        registers.lo = self.lo;
+        registers.fp_registers = self
+            .fp_registers
+            .iter()
+            .map(|&bits| f32::from_bits(bits))
+            .collect();
+        registers.fcsr = self.fcsr;
+        registers.cp0 = self.cp0.clone();
+        registers.pc = self.pc;
+        Ok(())
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,977 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// symbolic.rs
+//
+// This file contains an experimental symbolic executor for small functions.
+// Registers and memory words hold expressions over symbolic inputs instead of
+// numbers. At a branch whose condition depends on an input, execution forks
+// and each side records the condition as a path constraint. A small solver
+// searches for input values that satisfy the constraints, both to drop
+// branch directions no input can take and to report concrete inputs for
+// every path, such as the ones that reach a failing assertion. Instructions
+// whose operands are all known run through `Instruction::execute`, so both
+// simulators agree on their results.
+
+use super::instructions::{branch_target, effective_address, jump_target, Instruction, NextPc};
+use super::memory::Memory;
+use super::registers::{Registers, REGISTER_NAMES};
+use super::simulator::{decode_instruction, instruction_to_string};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fmt::Write as _;
+use std::rc::Rc;
+
+/// Paths reported before exploration stops, by default
+pub const DEFAULT_MAX_PATHS: usize = 64;
+/// Instructions a single path may execute, by default
+pub const DEFAULT_MAX_STEPS: usize = 10_000;
+/// Address in `$ra` when the function starts. Returning to it ends the path.
+pub const RETURN_ADDRESS: u32 = 0xFFFF_FFFC;
+/// Initial stack pointer of the function
+pub const STACK_POINTER: u32 = 0x7FFF_FFFC;
+
+// Input assignments the solver tries for one query before giving up
+const SOLVER_BUDGET: usize = 200_000;
+// Constants from the constraints that are also added and subtracted pairwise
+// to build candidate values
+const PAIRED_CONSTANTS: usize = 16;
+
+// Syscalls the executor models
+const SYSCALL_PRINT_INT: u32 = 1;
+const SYSCALL_PRINT_STRING: u32 = 4;
+const SYSCALL_READ_INT: u32 = 5;
+const SYSCALL_EXIT: u32 = 10;
+const SYSCALL_PRINT_CHAR: u32 = 11;
+const SYSCALL_READ_CHAR: u32 = 12;
+const SYSCALL_EXIT2: u32 = 17;
+
+/// An operation on two 32-bit values, with the meaning of the MIPS
+/// instruction of the same name. Comparisons give 1 or 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    /// Low 32 bits of the product
+    Mul,
+    And,
+    Or,
+    Xor,
+    Nor,
+    Sll,
+    Srl,
+    Sra,
+    Slt,
+    Sltu,
+    Eq,
+}
+
+impl BinaryOp {
+    pub fn apply(self, a: u32, b: u32) -> u32 {
+        match self {
+            BinaryOp::Add => a.wrapping_add(b),
+            BinaryOp::Sub => a.wrapping_sub(b),
+            BinaryOp::Mul => a.wrapping_mul(b),
+            BinaryOp::And => a & b,
+            BinaryOp::Or => a | b,
+            BinaryOp::Xor => a ^ b,
+            BinaryOp::Nor => !(a | b),
+            BinaryOp::Sll => a << (b & 0x1F),
+            BinaryOp::Srl => a >> (b & 0x1F),
+            BinaryOp::Sra => ((a as i32) >> (b & 0x1F)) as u32,
+            BinaryOp::Slt => ((a as i32) < (b as i32)) as u32,
+            BinaryOp::Sltu => (a < b) as u32,
+            BinaryOp::Eq => (a == b) as u32,
+        }
+    }
+}
+
+/// A value computed from the symbolic inputs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Const(u32),
+    /// The input with this index
+    Input(usize),
+    Binary(BinaryOp, Rc<Expr>, Rc<Expr>),
+}
+
+/// An expression shared between registers, memory words and constraints
+pub type Value = Rc<Expr>;
+
+impl Expr {
+    pub fn constant(value: u32) -> Value {
+        Rc::new(Expr::Const(value))
+    }
+
+    pub fn input(index: usize) -> Value {
+        Rc::new(Expr::Input(index))
+    }
+
+    /// `op` applied to `a` and `b`, folded to a constant when both are
+    pub fn binary(op: BinaryOp, a: &Value, b: &Value) -> Value {
+        match (a.as_const(), b.as_const()) {
+            (Some(a), Some(b)) => Expr::constant(op.apply(a, b)),
+            _ => Rc::new(Expr::Binary(op, a.clone(), b.clone())),
+        }
+    }
+
+    pub fn as_const(&self) -> Option<u32> {
+        match *self {
+            Expr::Const(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value with `inputs` substituted. Inputs past the end are 0.
+    pub fn evaluate(&self, inputs: &[u32]) -> u32 {
+        match self {
+            Expr::Const(value) => *value,
+            Expr::Input(index) => inputs.get(*index).copied().unwrap_or(0),
+            Expr::Binary(op, a, b) => op.apply(a.evaluate(inputs), b.evaluate(inputs)),
+        }
+    }
+
+    // Add the inputs and constants the expression mentions
+    fn collect(&self, inputs: &mut BTreeSet<usize>, constants: &mut BTreeSet<u32>) {
+        match self {
+            Expr::Const(value) => {
+                constants.insert(*value);
+            },
+            Expr::Input(index) => {
+                inputs.insert(*index);
+            },
+            Expr::Binary(_, a, b) => {
+                a.collect(inputs, constants);
+                b.collect(inputs, constants);
+            },
+        }
+    }
+}
+
+/// A branch decision on a path: `condition` is nonzero exactly when `holds`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Constraint {
+    pub condition: Value,
+    pub holds: bool,
+}
+
+impl Constraint {
+    pub fn is_satisfied(&self, inputs: &[u32]) -> bool {
+        (self.condition.evaluate(inputs) != 0) == self.holds
+    }
+}
+
+/// Values for `input_count` inputs that satisfy every constraint. Candidates
+/// are built from the constants the constraints mention, their neighbours
+/// and their pairwise sums and differences, which covers comparisons of
+/// inputs against constants and offsets. `None` means no candidate worked,
+/// not that the constraints are unsatisfiable.
+pub fn solve(constraints: &[Constraint], input_count: usize) -> Option<Vec<u32>> {
+    let mut inputs = BTreeSet::new();
+    let mut constants = BTreeSet::new();
+    for constraint in constraints {
+        constraint.condition.collect(&mut inputs, &mut constants);
+    }
+
+    let mut candidates: BTreeSet<u32> = [0, 1, 2, u32::MAX, i32::MAX as u32, i32::MIN as u32]
+        .iter()
+        .copied()
+        .collect();
+    for &constant in &constants {
+        candidates.insert(constant);
+        candidates.insert(constant.wrapping_add(1));
+        candidates.insert(constant.wrapping_sub(1));
+        candidates.insert(constant.wrapping_neg());
+    }
+    let paired: Vec<u32> = constants.iter().copied().take(PAIRED_CONSTANTS).collect();
+    for &a in &paired {
+        for &b in &paired {
+            candidates.insert(a.wrapping_add(b));
+            candidates.insert(a.wrapping_sub(b));
+        }
+    }
+    let candidates: Vec<u32> = candidates.into_iter().collect();
+    let inputs: Vec<usize> = inputs.into_iter().collect();
+
+    let mut model = vec![0; input_count];
+    let mut choice = vec![0; inputs.len()];
+    for _ in 0..SOLVER_BUDGET {
+        for (slot, &input) in inputs.iter().enumerate() {
+            model[input] = candidates[choice[slot]];
+        }
+        if constraints
+            .iter()
+            .all(|constraint| constraint.is_satisfied(&model))
+        {
+            return Some(model);
+        }
+
+        // Move to the next combination of candidates, odometer style
+        let mut slot = 0;
+        loop {
+            if slot == choice.len() {
+                return None;
+            }
+            choice[slot] += 1;
+            if choice[slot] < candidates.len() {
+                break;
+            }
+            choice[slot] = 0;
+            slot += 1;
+        }
+    }
+    None
+}
+
+/// How a path ended
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathEnd {
+    /// The function returned to `RETURN_ADDRESS` with this value in `$v0`
+    Returned(u32),
+    /// The program called the exit syscall
+    Exited,
+    /// A `break`, or a trap instruction whose condition held
+    Assertion(String),
+    /// The path executed the step limit without ending
+    StepLimit,
+    /// A fetch, load or store failed
+    Fault(String),
+    /// The path needs something the executor does not model
+    Unsupported(String),
+}
+
+impl fmt::Display for PathEnd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathEnd::Returned(value) => write!(f, "returned {}", *value as i32),
+            PathEnd::Exited => write!(f, "exited"),
+            PathEnd::Assertion(instruction) => write!(f, "reached assertion `{}`", instruction),
+            PathEnd::StepLimit => write!(f, "reached the step limit"),
+            PathEnd::Fault(message) => write!(f, "faulted: {}", message),
+            PathEnd::Unsupported(what) => write!(f, "stopped at unsupported {}", what),
+        }
+    }
+}
+
+/// One explored path
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathResult {
+    pub end: PathEnd,
+    /// Address of the instruction the path ended at
+    pub pc: u32,
+    /// Instructions executed
+    pub steps: usize,
+    /// Branch decisions that depended on an input
+    pub branches: usize,
+    /// Input values that take this path, by input name
+    pub inputs: Vec<(String, u32)>,
+}
+
+impl PathResult {
+    pub fn is_assertion(&self) -> bool {
+        matches!(self.end, PathEnd::Assertion(_))
+    }
+}
+
+/// Every path explored from one entry point
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolicReport {
+    pub entry: u32,
+    pub paths: Vec<PathResult>,
+    /// Branch directions dropped because no input was found to take them
+    pub pruned: usize,
+    /// Exploration stopped at the path limit with paths left to explore
+    pub truncated: bool,
+}
+
+impl SymbolicReport {
+    /// Paths that reach an assertion
+    pub fn assertions(&self) -> impl Iterator<Item = &PathResult> {
+        self.paths.iter().filter(|path| path.is_assertion())
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Symbolic execution from 0x{:08X}: {} paths, {} reaching an assertion",
+            self.entry,
+            self.paths.len(),
+            self.assertions().count()
+        );
+        for (number, path) in self.paths.iter().enumerate() {
+            let inputs = if path.inputs.is_empty() {
+                "no inputs".to_string()
+            } else {
+                path.inputs
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, *value as i32))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            let _ = writeln!(
+                out,
+                "  Path {}: {} at 0x{:08X} after {} instructions and {} branch decisions with {}",
+                number + 1,
+                path.end,
+                path.pc,
+                path.steps,
+                path.branches,
+                inputs
+            );
+        }
+        if self.pruned > 0 {
+            let _ = writeln!(
+                out,
+                "{} branch directions had no inputs that take them and were not explored",
+                self.pruned
+            );
+        }
+        if self.truncated {
+            let _ = writeln!(
+                out,
+                "Stopped after {} paths with more left to explore",
+                self.paths.len()
+            );
+        }
+        out
+    }
+}
+
+// The symbolic machine state of one path
+#[derive(Clone)]
+struct PathState {
+    pc: u32,
+    registers: Vec<Value>,
+    hi: Value,
+    lo: Value,
+    // Words the path stored, by aligned address. Other words come from the
+    // program's memory.
+    memory: BTreeMap<u32, Value>,
+    inputs: Vec<String>,
+    constraints: Vec<Constraint>,
+    // Input values that satisfy every constraint
+    model: Vec<u32>,
+    steps: usize,
+}
+
+impl PathState {
+    fn new(entry: u32, arguments: usize) -> Self {
+        let mut registers = vec![Expr::constant(0); 32];
+        registers[29] = Expr::constant(STACK_POINTER);
+        registers[31] = Expr::constant(RETURN_ADDRESS);
+        let mut inputs = Vec::new();
+        for (index, register) in (4..8).take(arguments).enumerate() {
+            registers[register] = Expr::input(index);
+            inputs.push(REGISTER_NAMES[register].to_string());
+        }
+        Self {
+            pc: entry,
+            registers,
+            hi: Expr::constant(0),
+            lo: Expr::constant(0),
+            memory: BTreeMap::new(),
+            model: vec![0; inputs.len()],
+            inputs,
+            constraints: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    fn read(&self, reg_num: u32) -> Value {
+        self.registers[reg_num as usize].clone()
+    }
+
+    fn write(&mut self, reg_num: u32, value: Value) {
+        if reg_num != 0 {
+            self.registers[reg_num as usize] = value;
+        }
+    }
+
+    // A new input, unconstrained so far
+    fn new_input(&mut self, name: &str) -> Value {
+        let count = self
+            .inputs
+            .iter()
+            .filter(|input| input.starts_with(name))
+            .count();
+        self.inputs.push(format!("{}#{}", name, count + 1));
+        self.model.push(0);
+        Expr::input(self.inputs.len() - 1)
+    }
+
+    // Add a constraint, returning whether inputs were found that satisfy it
+    // along with the earlier ones
+    fn assume(&mut self, condition: &Value, holds: bool) -> bool {
+        let constraint = Constraint {
+            condition: condition.clone(),
+            holds,
+        };
+        let satisfied = constraint.is_satisfied(&self.model);
+        self.constraints.push(constraint);
+        if satisfied {
+            return true;
+        }
+        match solve(&self.constraints, self.inputs.len()) {
+            Some(model) => {
+                self.model = model;
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn load_word(&self, memory: &Memory, address: u32) -> Result<Value, PathEnd> {
+        if let Some(value) = self.memory.get(&address) {
+            return Ok(value.clone());
+        }
+        memory
+            .read_word(address as usize)
+            .map(Expr::constant)
+            .map_err(|error| PathEnd::Fault(error.to_string()))
+    }
+
+    // Concrete address of `offset(base)`, which must be aligned to `size`
+    fn address(&self, base: u32, offset: i16, size: u32) -> Result<u32, PathEnd> {
+        let base_value = self.registers[base as usize]
+            .as_const()
+            .ok_or_else(|| PathEnd::Unsupported("symbolic address".to_string()))?;
+        let address = effective_address(base_value, offset).ok_or_else(|| {
+            PathEnd::Fault(format!(
+                "{}(0x{:08X}) wraps around the address space",
+                offset, base_value
+            ))
+        })?;
+        if address % size != 0 {
+            return Err(PathEnd::Fault(format!(
+                "address 0x{:08X} is not aligned for a {}-byte access",
+                address, size
+            )));
+        }
+        Ok(address)
+    }
+
+    // The zero-extended byte or halfword at `address` within its word
+    fn load_part(&self, memory: &Memory, address: u32, mask: u32) -> Result<Value, PathEnd> {
+        let word = self.load_word(memory, address & !3)?;
+        let shift = Expr::constant((address & 3) * 8);
+        Ok(Expr::binary(
+            BinaryOp::And,
+            &Expr::binary(BinaryOp::Srl, &word, &shift),
+            &Expr::constant(mask),
+        ))
+    }
+
+    // Replace the byte or halfword at `address` within its word
+    fn store_part(
+        &mut self,
+        memory: &Memory,
+        address: u32,
+        mask: u32,
+        value: &Value,
+    ) -> Result<(), PathEnd> {
+        let aligned = address & !3;
+        let word = self.load_word(memory, aligned)?;
+        let shift = (address & 3) * 8;
+        let kept = Expr::binary(BinaryOp::And, &word, &Expr::constant(!(mask << shift)));
+        let part = Expr::binary(
+            BinaryOp::Sll,
+            &Expr::binary(BinaryOp::And, value, &Expr::constant(mask)),
+            &Expr::constant(shift),
+        );
+        self.memory
+            .insert(aligned, Expr::binary(BinaryOp::Or, &kept, &part));
+        Ok(())
+    }
+}
+
+// Sign-extend the low `bits` bits of a value
+fn sign_extend(value: &Value, bits: u32) -> Value {
+    let shift = Expr::constant(32 - bits);
+    Expr::binary(
+        BinaryOp::Sra,
+        &Expr::binary(BinaryOp::Sll, value, &shift),
+        &shift,
+    )
+}
+
+// Condition of a trap instruction, and whether the trap fires when the
+// condition is nonzero
+fn trap_condition(instruction: &Instruction, state: &PathState) -> Option<(Value, bool)> {
+    let immediate = |imm: i16| Expr::constant(imm as i32 as u32);
+    let compare = |op, rs: u32, rt: &Value| Expr::binary(op, &state.read(rs), rt);
+    Some(match *instruction {
+        Instruction::Teq { rs, rt, .. } => (compare(BinaryOp::Eq, rs, &state.read(rt)), true),
+        Instruction::Tne { rs, rt, .. } => (compare(BinaryOp::Eq, rs, &state.read(rt)), false),
+        Instruction::Tge { rs, rt, .. } => (compare(BinaryOp::Slt, rs, &state.read(rt)), false),
+        Instruction::Tgeu { rs, rt, .. } => (compare(BinaryOp::Sltu, rs, &state.read(rt)), false),
+        Instruction::Tlt { rs, rt, .. } => (compare(BinaryOp::Slt, rs, &state.read(rt)), true),
+        Instruction::Tltu { rs, rt, .. } => (compare(BinaryOp::Sltu, rs, &state.read(rt)), true),
+        Instruction::Teqi { rs, imm } => (compare(BinaryOp::Eq, rs, &immediate(imm)), true),
+        Instruction::Tnei { rs, imm } => (compare(BinaryOp::Eq, rs, &immediate(imm)), false),
+        Instruction::Tgei { rs, imm } => (compare(BinaryOp::Slt, rs, &immediate(imm)), false),
+        Instruction::Tgeiu { rs, imm } => (compare(BinaryOp::Sltu, rs, &immediate(imm)), false),
+        Instruction::Tlti { rs, imm } => (compare(BinaryOp::Slt, rs, &immediate(imm)), true),
+        Instruction::Tltiu { rs, imm } => (compare(BinaryOp::Sltu, rs, &immediate(imm)), true),
+        _ => return None,
+    })
+}
+
+// Expression for an ALU instruction with symbolic operands, as the
+// destination register and its new value
+fn symbolic_result(instruction: &Instruction, state: &PathState) -> Option<(u32, Value)> {
+    let register = |op, rd: u32, rs: u32, rt: u32| {
+        Some((rd, Expr::binary(op, &state.read(rs), &state.read(rt))))
+    };
+    let immediate = |op, rt: u32, rs: u32, imm: u32| {
+        Some((rt, Expr::binary(op, &state.read(rs), &Expr::constant(imm))))
+    };
+    match *instruction {
+        Instruction::Add { rd, rs, rt } | Instruction::Addu { rd, rs, rt } => {
+            register(BinaryOp::Add, rd, rs, rt)
+        },
+        Instruction::Sub { rd, rs, rt } => register(BinaryOp::Sub, rd, rs, rt),
+        Instruction::And { rd, rs, rt } => register(BinaryOp::And, rd, rs, rt),
+        Instruction::Or { rd, rs, rt } => register(BinaryOp::Or, rd, rs, rt),
+        Instruction::Xor { rd, rs, rt } => register(BinaryOp::Xor, rd, rs, rt),
+        Instruction::Nor { rd, rs, rt } => register(BinaryOp::Nor, rd, rs, rt),
+        Instruction::Slt { rd, rs, rt } => register(BinaryOp::Slt, rd, rs, rt),
+        Instruction::Sltu { rd, rs, rt } => register(BinaryOp::Sltu, rd, rs, rt),
+        Instruction::Sllv { rd, rt, rs } => register(BinaryOp::Sll, rd, rt, rs),
+        Instruction::Srlv { rd, rt, rs } => register(BinaryOp::Srl, rd, rt, rs),
+        Instruction::Srav { rd, rt, rs } => register(BinaryOp::Sra, rd, rt, rs),
+        Instruction::Sll { rd, rt, shamt } => immediate(BinaryOp::Sll, rd, rt, shamt),
+        Instruction::Srl { rd, rt, shamt } => immediate(BinaryOp::Srl, rd, rt, shamt),
+        Instruction::Sra { rd, rt, shamt } => immediate(BinaryOp::Sra, rd, rt, shamt),
+        Instruction::Addi { rt, rs, imm } | Instruction::Addiu { rt, rs, imm } => {
+            immediate(BinaryOp::Add, rt, rs, imm as i32 as u32)
+        },
+        Instruction::Slti { rt, rs, imm } => immediate(BinaryOp::Slt, rt, rs, imm as i32 as u32),
+        Instruction::Sltiu { rt, rs, imm } => immediate(BinaryOp::Sltu, rt, rs, imm as i32 as u32),
+        Instruction::Andi { rt, rs, imm } => immediate(BinaryOp::And, rt, rs, imm as u32),
+        Instruction::Ori { rt, rs, imm } => immediate(BinaryOp::Or, rt, rs, imm as u32),
+        Instruction::Xori { rt, rs, imm } => immediate(BinaryOp::Xor, rt, rs, imm as u32),
+        _ => None,
+    }
+}
+
+// Whether an instruction only computes registers, so it can run through
+// `Instruction::execute` once its operands are known
+fn is_computation(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Add { .. }
+            | Instruction::Addu { .. }
+            | Instruction::Sub { .. }
+            | Instruction::And { .. }
+            | Instruction::Or { .. }
+            | Instruction::Xor { .. }
+            | Instruction::Nor { .. }
+            | Instruction::Slt { .. }
+            | Instruction::Sltu { .. }
+            | Instruction::Sll { .. }
+            | Instruction::Srl { .. }
+            | Instruction::Sra { .. }
+            | Instruction::Sllv { .. }
+            | Instruction::Srlv { .. }
+            | Instruction::Srav { .. }
+            | Instruction::Addi { .. }
+            | Instruction::Addiu { .. }
+            | Instruction::Slti { .. }
+            | Instruction::Sltiu { .. }
+            | Instruction::Andi { .. }
+            | Instruction::Ori { .. }
+            | Instruction::Xori { .. }
+            | Instruction::Lui { .. }
+            | Instruction::Mult { .. }
+            | Instruction::Div { .. }
+            | Instruction::Divu { .. }
+    )
+}
+
+// Exploration state shared by every path
+struct Explorer<'a> {
+    memory: &'a Memory,
+    max_steps: usize,
+    paths: Vec<PathResult>,
+    pruned: usize,
+    // Register file the concrete instructions run on
+    scratch: Registers,
+    scratch_memory: Memory,
+}
+
+impl<'a> Explorer<'a> {
+    fn finish(&mut self, state: PathState, end: PathEnd) {
+        let end = match end {
+            PathEnd::Returned(_) => PathEnd::Returned(state.registers[2].evaluate(&state.model)),
+            end => end,
+        };
+        self.paths.push(PathResult {
+            end,
+            pc: state.pc,
+            steps: state.steps,
+            branches: state.constraints.len(),
+            inputs: state
+                .inputs
+                .iter()
+                .cloned()
+                .zip(state.model.iter().copied())
+                .collect(),
+        });
+    }
+
+    // Execute one instruction. Returns the other side of a fork to explore
+    // later, or how the path ended.
+    fn step(&mut self, state: &mut PathState) -> Result<Option<PathState>, PathEnd> {
+        if state.steps >= self.max_steps {
+            return Err(PathEnd::StepLimit);
+        }
+        let word = state
+            .load_word(self.memory, state.pc)?
+            .as_const()
+            .ok_or_else(|| PathEnd::Unsupported("symbolic instruction word".to_string()))?;
+        let instruction = decode_instruction(word);
+        state.steps += 1;
+        let pc = state.pc;
+        let next = pc.wrapping_add(4);
+
+        match instruction {
+            Instruction::Beq { rs, rt, offset } | Instruction::Bne { rs, rt, offset } => {
+                let condition = Expr::binary(BinaryOp::Eq, &state.read(rs), &state.read(rt));
+                let taken_when = matches!(instruction, Instruction::Beq { .. });
+                return Ok(self.branch(state, condition, taken_when, branch_target(pc, offset)));
+            },
+            Instruction::Bgtz { rs, offset } | Instruction::Blez { rs, offset } => {
+                let condition = Expr::binary(BinaryOp::Slt, &Expr::constant(0), &state.read(rs));
+                let taken_when = matches!(instruction, Instruction::Bgtz { .. });
+                return Ok(self.branch(state, condition, taken_when, branch_target(pc, offset)));
+            },
+            Instruction::Bltz { rs, offset } | Instruction::Bgez { rs, offset } => {
+                let condition = Expr::binary(BinaryOp::Slt, &state.read(rs), &Expr::constant(0));
+                let taken_when = matches!(instruction, Instruction::Bltz { .. });
+                return Ok(self.branch(state, condition, taken_when, branch_target(pc, offset)));
+            },
+            Instruction::J { target } => {
+                state.pc = jump_target(pc, target);
+                return Ok(None);
+            },
+            Instruction::Jal { target } => {
+                state.write(31, Expr::constant(next));
+                state.pc = jump_target(pc, target);
+                return Ok(None);
+            },
+            Instruction::Jr { rs } | Instruction::Jalr { rs, .. } => {
+                let target = state.registers[rs as usize]
+                    .as_const()
+                    .ok_or_else(|| PathEnd::Unsupported("symbolic jump target".to_string()))?;
+                if let Instruction::Jalr { rd, .. } = instruction {
+                    state.write(rd, Expr::constant(next));
+                }
+                if target == RETURN_ADDRESS {
+                    return Err(PathEnd::Returned(0));
+                }
+                state.pc = target;
+                return Ok(None);
+            },
+            Instruction::Break { .. } => {
+                return Err(PathEnd::Assertion(instruction_to_string(
+                    &instruction,
+                    word,
+                )));
+            },
+            Instruction::Syscall => self.syscall(state)?,
+            Instruction::Nop => {},
+            Instruction::Lw { rt, base, offset } | Instruction::Ll { rt, base, offset } => {
+                let address = state.address(base, offset, 4)?;
+                let value = state.load_word(self.memory, address)?;
+                state.write(rt, value);
+            },
+            Instruction::Lh { rt, base, offset } | Instruction::Lhu { rt, base, offset } => {
+                let address = state.address(base, offset, 2)?;
+                let half = state.load_part(self.memory, address, 0xFFFF)?;
+                let value = match instruction {
+                    Instruction::Lh { .. } => sign_extend(&half, 16),
+                    _ => half,
+                };
+                state.write(rt, value);
+            },
+            Instruction::Lb { rt, base, offset } | Instruction::Lbu { rt, base, offset } => {
+                let address = state.address(base, offset, 1)?;
+                let byte = state.load_part(self.memory, address, 0xFF)?;
+                let value = match instruction {
+                    Instruction::Lb { .. } => sign_extend(&byte, 8),
+                    _ => byte,
+                };
+                state.write(rt, value);
+            },
+            Instruction::Sw { rt, base, offset } | Instruction::Sc { rt, base, offset } => {
+                let address = state.address(base, offset, 4)?;
+                // A path runs alone, so every store-conditional succeeds
+                state.memory.insert(address, state.read(rt));
+                if let Instruction::Sc { .. } = instruction {
+                    state.write(rt, Expr::constant(1));
+                }
+            },
+            Instruction::Sh { rt, base, offset } => {
+                let address = state.address(base, offset, 2)?;
+                let value = state.read(rt);
+                state.store_part(self.memory, address, 0xFFFF, &value)?;
+            },
+            Instruction::Sb { rt, base, offset } => {
+                let address = state.address(base, offset, 1)?;
+                let value = state.read(rt);
+                state.store_part(self.memory, address, 0xFF, &value)?;
+            },
+            Instruction::Mfhi { rd } => state.write(rd, state.hi.clone()),
+            Instruction::Mflo { rd } => state.write(rd, state.lo.clone()),
+            Instruction::Mthi { rs } => state.hi = state.read(rs),
+            Instruction::Mtlo { rs } => state.lo = state.read(rs),
+            _ => {
+                if let Some((condition, fires_when)) = trap_condition(&instruction, state) {
+                    let text = instruction_to_string(&instruction, word);
+                    self.trap(state, condition, fires_when, text)?;
+                } else if is_computation(&instruction) {
+                    self.compute(&instruction, state)?;
+                } else {
+                    let text = instruction_to_string(&instruction, word);
+                    return Err(PathEnd::Unsupported(format!("instruction `{}`", text)));
+                }
+            },
+        }
+        state.pc = next;
+        Ok(None)
+    }
+
+    // Follow a conditional branch, forking when the condition depends on an
+    // input and inputs were found for both directions
+    fn branch(
+        &mut self,
+        state: &mut PathState,
+        condition: Value,
+        taken_when: bool,
+        target: u32,
+    ) -> Option<PathState> {
+        let fall_through = state.pc.wrapping_add(4);
+        if let Some(value) = condition.as_const() {
+            state.pc = if (value != 0) == taken_when {
+                target
+            } else {
+                fall_through
+            };
+            return None;
+        }
+
+        let mut other = state.clone();
+        let taken = state.assume(&condition, taken_when);
+        let not_taken = other.assume(&condition, !taken_when);
+        match (taken, not_taken) {
+            (true, true) => {
+                state.pc = target;
+                other.pc = fall_through;
+                Some(other)
+            },
+            (true, false) => {
+                self.pruned += 1;
+                state.pc = target;
+                None
+            },
+            _ => {
+                // The current inputs take one of the two directions, so at
+                // least one is always feasible
+                self.pruned += 1;
+                *state = other;
+                state.pc = fall_through;
+                None
+            },
+        }
+    }
+
+    // A trap instruction ends the path as an assertion when it fires. When
+    // that depends on an input, the firing side is reported and the path
+    // continues on the other.
+    fn trap(
+        &mut self,
+        state: &mut PathState,
+        condition: Value,
+        fires_when: bool,
+        text: String,
+    ) -> Result<(), PathEnd> {
+        if let Some(value) = condition.as_const() {
+            if (value != 0) == fires_when {
+                return Err(PathEnd::Assertion(text));
+            }
+            return Ok(());
+        }
+
+        let mut fired = state.clone();
+        let fires = fired.assume(&condition, fires_when);
+        let passes = state.assume(&condition, !fires_when);
+        match (fires, passes) {
+            (true, true) => {
+                self.finish(fired, PathEnd::Assertion(text));
+                Ok(())
+            },
+            (true, false) => {
+                self.pruned += 1;
+                *state = fired;
+                Err(PathEnd::Assertion(text))
+            },
+            _ => {
+                self.pruned += 1;
+                Ok(())
+            },
+        }
+    }
+
+    // Run a register computation, through `Instruction::execute` when every
+    // operand is known and as an expression otherwise
+    fn compute(&mut self, instruction: &Instruction, state: &mut PathState) -> Result<(), PathEnd> {
+        let sources = instruction.get_source_registers();
+        let known: Option<Vec<u32>> = sources
+            .iter()
+            .map(|&reg_num| state.registers[reg_num as usize].as_const())
+            .collect();
+        if let Some(values) = known {
+            for (&reg_num, &value) in sources.iter().zip(&values) {
+                self.scratch.write(reg_num, value);
+            }
+            self.scratch.pc = state.pc;
+            if let NextPc::Trap(_) =
+                instruction.execute(&mut self.scratch, &mut self.scratch_memory)
+            {
+                return Err(PathEnd::Fault(
+                    "instruction raised an exception".to_string(),
+                ));
+            }
+            match *instruction {
+                Instruction::Mult { .. } | Instruction::Div { .. } | Instruction::Divu { .. } => {
+                    state.hi = Expr::constant(self.scratch.get_hi());
+                    state.lo = Expr::constant(self.scratch.get_lo());
+                },
+                _ => {
+                    if let Some(rd) = instruction.get_destination_register() {
+                        state.write(rd, Expr::constant(self.scratch.read(rd)));
+                    }
+                },
+            }
+            return Ok(());
+        }
+
+        match *instruction {
+            Instruction::Mult { rs, rt } => {
+                // HI holds what `Instruction::execute` leaves there: the sign
+                // of the 32-bit product
+                let product = Expr::binary(BinaryOp::Mul, &state.read(rs), &state.read(rt));
+                state.hi = Expr::binary(BinaryOp::Sra, &product, &Expr::constant(31));
+                state.lo = product;
+                Ok(())
+            },
+            _ => match symbolic_result(instruction, state) {
+                Some((rd, value)) => {
+                    state.write(rd, value);
+                    Ok(())
+                },
+                None => Err(PathEnd::Unsupported(
+                    "division of a symbolic value".to_string(),
+                )),
+            },
+        }
+    }
+
+    fn syscall(&mut self, state: &mut PathState) -> Result<(), PathEnd> {
+        let number = state.registers[2]
+            .as_const()
+            .ok_or_else(|| PathEnd::Unsupported("symbolic syscall number".to_string()))?;
+        match number {
+            SYSCALL_EXIT | SYSCALL_EXIT2 => Err(PathEnd::Exited),
+            SYSCALL_READ_INT => {
+                let value = state.new_input("read_int");
+                state.write(2, value);
+                Ok(())
+            },
+            SYSCALL_READ_CHAR => {
+                let value = state.new_input("read_char");
+                state.write(2, value);
+                Ok(())
+            },
+            // Output does not change the state
+            SYSCALL_PRINT_INT | SYSCALL_PRINT_STRING | SYSCALL_PRINT_CHAR => Ok(()),
+            _ => Err(PathEnd::Unsupported(format!("syscall {}", number))),
+        }
+    }
+}
+
+/// Explores the paths of a function whose first `arguments` argument
+/// registers hold symbolic inputs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolicExecutor {
+    /// Argument registers, from `$a0`, that start as inputs
+    pub arguments: usize,
+    pub max_paths: usize,
+    pub max_steps: usize,
+}
+
+impl SymbolicExecutor {
+    pub fn new(arguments: usize) -> Self {
+        Self {
+            arguments: arguments.min(4),
+            max_paths: DEFAULT_MAX_PATHS,
+            max_steps: DEFAULT_MAX_STEPS,
+        }
+    }
+
+    /// Explore every path from `entry` in a loaded program's memory, which
+    /// is read but never written
+    pub fn explore(&self, memory: &Memory, entry: u32) -> SymbolicReport {
+        let mut explorer = Explorer {
+            memory,
+            max_steps: self.max_steps,
+            paths: Vec::new(),
+            pruned: 0,
+            scratch: Registers::new(),
+            scratch_memory: Memory::new_simple(0),
+        };
+        let mut pending = vec![PathState::new(entry, self.arguments)];
+        while let Some(mut state) = pending.pop() {
+            if explorer.paths.len() >= self.max_paths {
+                pending.push(state);
+                break;
+            }
+            loop {
+                match explorer.step(&mut state) {
+                    Ok(None) => {},
+                    Ok(Some(other)) => pending.push(other),
+                    Err(end) => {
+                        explorer.finish(state, end);
+                        break;
+                    },
+                }
+            }
+        }
+
+        SymbolicReport {
+            entry,
+            truncated: !pending.is_empty(),
+            paths: explorer.paths,
+            pruned: explorer.pruned,
+        }
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// taint.rs
+//
+// This file contains dynamic taint tracking. Data from chosen sources,
+// the read syscalls and marked memory ranges, is tainted, and the taint
+// follows the data as instructions copy and combine it through registers
+// and memory. When tainted data is used as a jump target or passed to a
+// syscall, the event is recorded, which is how an attacker's input is seen
+// taking control of a program in security exercises.
+
+use super::instructions::Instruction;
+use super::memory::Memory;
+use super::registers::{Register, Registers};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+
+// Syscalls that read input: read_int, read_string, read_char and read
+const SYSCALL_READ_INT: u32 = 5;
+const SYSCALL_READ_STRING: u32 = 8;
+const SYSCALL_READ_CHAR: u32 = 12;
+const SYSCALL_READ_FILE: u32 = 14;
+
+// Argument registers each syscall reads. Syscalls not listed here are
+// assumed to read all four.
+fn syscall_arguments(syscall: u32) -> &'static [u32] {
+    match syscall {
+        5 | 6 | 7 | 10 | 12 | 30 => &[],
+        1 | 4 | 9 | 11 | 16 | 17 | 31 | 34 => &[4],
+        8 => &[4, 5],
+        13..=15 => &[4, 5, 6],
+        _ => &[4, 5, 6, 7],
+    }
+}
+
+/// A use of tainted data that the tracker reports
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TaintSink {
+    /// `jr` or `jalr` through a tainted register
+    JumpTarget { register: u32 },
+    /// A syscall whose number or an argument is tainted
+    SyscallArgument { syscall: u32, register: u32 },
+}
+
+impl fmt::Display for TaintSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaintSink::JumpTarget { register } => {
+                write!(f, "jump target {} is tainted", Register::Gpr(*register))
+            },
+            TaintSink::SyscallArgument { syscall, register } => write!(
+                f,
+                "syscall {} argument {} is tainted",
+                syscall,
+                Register::Gpr(*register)
+            ),
+        }
+    }
+}
+
+/// A sink reached at an address, with the times it was reached
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TaintEvent {
+    pub pc: u32,
+    pub sink: TaintSink,
+    pub count: usize,
+}
+
+/// Taint state of the registers and memory, and the sinks tainted data
+/// has reached. Memory taint is kept per byte at physical addresses.
+/// Floating-point registers and registers of other shadow sets are not
+/// tracked, so data moved through them loses its taint.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TaintTracker {
+    /// Taint what the read syscalls return
+    pub taint_input: bool,
+    registers: u32,
+    hi: bool,
+    lo: bool,
+    memory: BTreeSet<usize>,
+    events: BTreeMap<(u32, TaintSink), usize>,
+    /// Instructions that read tainted data
+    pub tainted_instructions: usize,
+}
+
+impl TaintTracker {
+    pub fn new(taint_input: bool) -> Self {
+        Self {
+            taint_input,
+            ..Self::default()
+        }
+    }
+
+    /// Taint `length` bytes of memory from `address`
+    pub fn taint_range(&mut self, memory: &Memory, address: u32, length: u32) {
+        self.set_memory(memory, address, length, true);
+    }
+
+    pub fn is_register_tainted(&self, reg_num: u32) -> bool {
+        reg_num != 0 && reg_num < 32 && self.registers & (1 << reg_num) != 0
+    }
+
+    pub fn is_memory_tainted(&self, memory: &Memory, address: u32) -> bool {
+        self.memory
+            .contains(&memory.translate_address(address as usize))
+    }
+
+    /// Number of tainted bytes of memory
+    pub fn tainted_bytes(&self) -> usize {
+        self.memory.len()
+    }
+
+    /// General-purpose registers that are tainted
+    pub fn tainted_registers(&self) -> Vec<u32> {
+        (1..32)
+            .filter(|&reg_num| self.is_register_tainted(reg_num))
+            .collect()
+    }
+
+    /// Every sink reached, in address order
+    pub fn events(&self) -> Vec<TaintEvent> {
+        self.events
+            .iter()
+            .map(|(&(pc, sink), &count)| TaintEvent { pc, sink, count })
+            .collect()
+    }
+
+    /// Update the taint for `instruction` at `pc`, before it executes, and
+    /// record any sink its tainted operands reach
+    pub fn propagate(
+        &mut self,
+        instruction: &Instruction,
+        registers: &Registers,
+        memory: &Memory,
+        pc: u32,
+    ) {
+        if let Instruction::Syscall = instruction {
+            return self.syscall(registers, memory, pc);
+        }
+        let sources = instruction.get_source_registers();
+        if !instruction.is_fp_instruction()
+            && sources
+                .iter()
+                .any(|&reg_num| self.is_register_tainted(reg_num))
+        {
+            self.tainted_instructions += 1;
+        }
+
+        match *instruction {
+            Instruction::Jr { rs } | Instruction::Jalr { rs, .. }
+                if self.is_register_tainted(rs) =>
+            {
+                self.record(pc, TaintSink::JumpTarget { register: rs });
+            },
+            Instruction::Mult { rs, rt }
+            | Instruction::Div { rs, rt }
+            | Instruction::Divu { rs, rt } => {
+                let tainted = self.is_register_tainted(rs) || self.is_register_tainted(rt);
+                self.hi = tainted;
+                self.lo = tainted;
+            },
+            Instruction::Mthi { rs } => self.hi = self.is_register_tainted(rs),
+            Instruction::Mtlo { rs } => self.lo = self.is_register_tainted(rs),
+            Instruction::Mfhi { rd } => return self.load_register(rd, self.hi),
+            Instruction::Mflo { rd } => return self.load_register(rd, self.lo),
+            _ => {},
+        }
+
+        // Loads and stores move the taint of the data, not of the address
+        if let Some((base, offset)) = instruction.memory_operand() {
+            let address = registers.read(base).wrapping_add(offset as i32 as u32);
+            let size = instruction.access_size();
+            match *instruction {
+                Instruction::Sw { rt, .. }
+                | Instruction::Sh { rt, .. }
+                | Instruction::Sb { rt, .. } => {
+                    let tainted = self.is_register_tainted(rt);
+                    self.set_memory(memory, address, size, tainted);
+                },
+                Instruction::Sc { rt, .. } => {
+                    let tainted = self.is_register_tainted(rt);
+                    self.set_memory(memory, address, size, tainted);
+                    self.set_register(rt, false);
+                },
+                Instruction::SwC1 { .. } => self.set_memory(memory, address, size, false),
+                Instruction::LwC1 { .. } => {},
+                _ => {
+                    if let Some(rt) = instruction.get_destination_register() {
+                        let tainted = (0..size)
+                            .any(|byte| self.is_memory_tainted(memory, address.wrapping_add(byte)));
+                        self.load_register(rt, tainted);
+                    }
+                },
+            }
+            return;
+        }
+
+        if instruction.is_fp_instruction() {
+            return;
+        }
+        // Results depend on every source register; links, CP0 reads and
+        // immediates are clean
+        if let Some(rd) = instruction.get_destination_register() {
+            let tainted = match instruction {
+                Instruction::Jal { .. } | Instruction::Jalr { .. } => false,
+                _ => sources
+                    .iter()
+                    .any(|&reg_num| self.is_register_tainted(reg_num)),
+            };
+            self.set_register(rd, tainted);
+        }
+    }
+
+    /// A summary of the tainted state and every sink reached
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let registers: Vec<String> = self
+            .tainted_registers()
+            .into_iter()
+            .map(|reg_num| Register::Gpr(reg_num).to_string())
+            .collect();
+        let _ = writeln!(
+            out,
+            "Taint: {} instructions read tainted data; {} bytes of memory and {} registers tainted at the end{}",
+            self.tainted_instructions,
+            self.tainted_bytes(),
+            registers.len(),
+            if registers.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", registers.join(", "))
+            }
+        );
+        if self.events.is_empty() {
+            let _ = writeln!(out, "Tainted data reached no jump target or syscall");
+            return out;
+        }
+        let _ = writeln!(out, "{:<12} {:>8}  event", "address", "count");
+        for event in self.events() {
+            let _ = writeln!(
+                out,
+                "0x{:08X}   {:>8}  {}",
+                event.pc, event.count, event.sink
+            );
+        }
+        out
+    }
+
+    // Check the syscall's arguments, then taint what it reads
+    fn syscall(&mut self, registers: &Registers, memory: &Memory, pc: u32) {
+        let syscall = registers.read(2);
+        let mut reads_taint = false;
+        for &register in [2].iter().chain(syscall_arguments(syscall)) {
+            if self.is_register_tainted(register) {
+                self.record(pc, TaintSink::SyscallArgument { syscall, register });
+                reads_taint = true;
+            }
+        }
+        if reads_taint {
+            self.tainted_instructions += 1;
+        }
+        match syscall {
+            SYSCALL_READ_INT | SYSCALL_READ_CHAR => self.set_register(2, self.taint_input),
+            SYSCALL_READ_STRING if self.taint_input => {
+                self.taint_range(memory, registers.read(4), registers.read(5));
+            },
+            SYSCALL_READ_FILE => {
+                if self.taint_input {
+                    self.taint_range(memory, registers.read(5), registers.read(6));
+                }
+                self.set_register(2, false);
+            },
+            _ => self.set_register(2, false),
+        }
+    }
+
+    fn record(&mut self, pc: u32, sink: TaintSink) {
+        *self.events.entry((pc, sink)).or_insert(0) += 1;
+    }
+
+    /// Set a register loaded from memory or HI/LO, counting a tainted read
+    fn load_register(&mut self, reg_num: u32, tainted: bool) {
+        if tainted {
+            self.tainted_instructions += 1;
+        }
+        self.set_register(reg_num, tainted);
+    }
+
+    fn set_register(&mut self, reg_num: u32, tainted: bool) {
+        if reg_num == 0 || reg_num >= 32 {
+            return;
+        }
+        if tainted {
+            self.registers |= 1 << reg_num;
+        } else {
+            self.registers &= !(1 << reg_num);
+        }
+    }
+
+    fn set_memory(&mut self, memory: &Memory, address: u32, length: u32, tainted: bool) {
+        for byte in 0..length {
+            let physical = memory.translate_address(address.wrapping_add(byte) as usize);
+            if tainted {
+                self.memory.insert(physical);
+            } else {
+                self.memory.remove(&physical);
+            }
+        }
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// threads.rs
+//
+// This file contains a simple round-robin thread scheduler for the functional
+// simulator. Guest threads share memory and each have their own register
+// context. Threads are created, joined and switched through syscalls, which
+// makes it possible to run small concurrency exercises built on ll/sc.
+
+use super::registers::Registers;
+
+// Thread syscall numbers (placed after the MARS-compatible range)
+pub const SYSCALL_THREAD_CREATE: u32 = 60;
+pub const SYSCALL_THREAD_JOIN: u32 = 61;
+pub const SYSCALL_THREAD_YIELD: u32 = 62;
+pub const SYSCALL_THREAD_EXIT: u32 = 63;
+pub const SYSCALL_THREAD_SELF: u32 = 64;
+
+/// Return address given to new threads; jumping here exits the thread with $v0
+pub const THREAD_RETURN_ADDRESS: u32 = 0xFFFF_FFF0;
+
+/// Stack space reserved below the creating thread's stack for each new thread
+pub const THREAD_STACK_SIZE: u32 = 0x1000;
+
+/// Default number of instructions a thread runs before being preempted
+pub const DEFAULT_TIME_SLICE: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThreadState {
+    Ready,
+    Running,
+    Blocked { join_on: u32 },
+    Finished { exit_value: u32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct ThreadContext {
+    pub id: u32,
+    pub registers: Registers,
+    pub pc: u32,
+    pub state: ThreadState,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThreadScheduler {
+    pub threads: Vec<ThreadContext>,
+    pub current: usize,
+    pub time_slice: usize,
+    pub context_switches: usize,
+    slice_used: usize,
+}
+
+impl Default for ThreadScheduler {
+    fn default() -> Self {
+        Self::new(DEFAULT_TIME_SLICE)
+    }
+}
+
+impl ThreadScheduler {
+    pub fn new(time_slice: usize) -> Self {
+        Self {
+            threads: Vec::new(),
+            current: 0,
+            time_slice: time_slice.max(1),
+            context_switches: 0,
+            slice_used: 0,
+        }
+    }
+
+    pub fn is_thread_syscall(number: u32) -> bool {
+        (SYSCALL_THREAD_CREATE..=SYSCALL_THREAD_SELF).contains(&number)
+    }
+
+    /// True once a second thread has been created
+    pub fn is_active(&self) -> bool {
+        !self.threads.is_empty()
+    }
+
+    pub fn current_id(&self) -> u32 {
+        self.threads.get(self.current).map_or(0, |t| t.id)
+    }
+
+    pub fn thread_count(&self) -> usize {
+        self.threads.len().max(1)
+    }
+
+    pub fn get_thread(&self, id: u32) -> Option<&ThreadContext> {
+        self.threads.iter().find(|t| t.id == id)
+    }
+
+    // Create a new thread starting at `entry` with `arg` in $a0. A zero
+    // `stack_top` allocates a stack below the creating thread's stack.
+    pub fn create(
+        &mut self,
+        registers: &Registers,
+        pc: u32,
+        entry: u32,
+        arg: u32,
+        stack_top: u32,
+    ) -> u32 {
+        if self.threads.is_empty() {
+            // The main program becomes thread 0
+            self.threads.push(ThreadContext {
+                id: 0,
+                registers: registers.clone(),
+                pc,
+                state: ThreadState::Running,
+            });
+            self.current = 0;
+        }
+
+        let id = self.threads.len() as u32;
+        let mut thread_registers = Registers::new();
+        let stack = if stack_top != 0 {
+            stack_top
+        } else {
+            registers
+                .read(29)
+                .wrapping_sub(id.wrapping_mul(THREAD_STACK_SIZE))
+        };
+        thread_registers.write(4, arg);
+        thread_registers.write(28, registers.read(28));
+        thread_registers.write(29, stack);
+        thread_registers.write(31, THREAD_RETURN_ADDRESS);
+        thread_registers.pc = entry;
+
+        self.threads.push(ThreadContext {
+            id,
+            registers: thread_registers,
+            pc: entry,
+            state: ThreadState::Ready,
+        });
+
+        id
+    }
+
+    // Count one executed instruction and preempt the running thread when its
+    // time slice is used up. Returns true if a different thread was scheduled.
+    pub fn tick(&mut self, registers: &mut Registers, pc: &mut u32) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+
+        self.slice_used += 1;
+        if self.slice_used < self.time_slice {
+            return false;
+        }
+
+        let previous = self.current;
+        self.threads[previous].state = ThreadState::Ready;
+        self.switch(registers, pc) && self.current != previous
+    }
+
+    // Give up the processor. Returns false only if no thread can run.
+    pub fn yield_now(&mut self, registers: &mut Registers, pc: &mut u32) -> bool {
+        if !self.is_active() {
+            return true;
+        }
+        self.threads[self.current].state = ThreadState::Ready;
+        self.switch(registers, pc)
+    }
+
+    // Finish the running thread, waking up any joiners. Returns false when no
+    // thread is left to run.
+    pub fn exit_current(
+        &mut self,
+        exit_value: u32,
+        registers: &mut Registers,
+        pc: &mut u32,
+    ) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+
+        let id = self.current_id();
+        self.threads[self.current].state = ThreadState::Finished { exit_value };
+
+        for thread in self.threads.iter_mut() {
+            if thread.state == (ThreadState::Blocked { join_on: id }) {
+                thread.registers.write(2, exit_value);
+                thread.state = ThreadState::Ready;
+            }
+        }
+
+        self.switch(registers, pc)
+    }
+
+    // Wait for thread `id` to finish. Its exit value is returned in $v0.
+    // Returns false if waiting would deadlock or the thread does not exist.
+    pub fn join(&mut self, id: u32, registers: &mut Registers, pc: &mut u32) -> bool {
+        let state = match self.get_thread(id) {
+            Some(thread) => thread.state,
+            None => {
+                println!("thread_join: no such thread {}", id);
+                registers.write(2, 0);
+                return true;
+            },
+        };
+
+        if let ThreadState::Finished { exit_value } = state {
+            registers.write(2, exit_value);
+            return true;
+        }
+
+        if id == self.current_id() {
+            println!("thread_join: thread {} cannot join itself", id);
+            return false;
+        }
+
+        self.threads[self.current].state = ThreadState::Blocked { join_on: id };
+        self.switch(registers, pc)
+    }
+
+    // Save the running context and load the next ready thread in round-robin
+    // order. The link bit is cleared so an interrupted ll/sc sequence fails.
+    fn switch(&mut self, registers: &mut Registers, pc: &mut u32) -> bool {
+        let count = self.threads.len();
+        let previous = self.current;
+
+        self.threads[previous].registers = registers.clone();
+        self.threads[previous].pc = *pc;
+        self.slice_used = 0;
+
+        let next = (1..=count)
+            .map(|i| (previous + i) % count)
+            .find(|&i| self.threads[i].state == ThreadState::Ready);
+
+        match next {
+            Some(index) => {
+                if index != previous {
+                    self.context_switches += 1;
+                }
+                self.current = index;
+                self.threads[index].state = ThreadState::Running;
+                // CP0 belongs to the processor, not to the thread
+                let cp0 = std::mem::take(&mut registers.cp0);
+                *registers = self.threads[index].registers.clone();
+                registers.cp0 = cp0;
+                *pc = self.threads[index].pc;
+                registers.ll_bit = false;
+                true
+            },
+            None => {
+                if self
+                    .threads
+                    .iter()
+                    .any(|t| matches!(t.state, ThreadState::Blocked { .. }))
+                {
+                    println!("Deadlock: all remaining threads are blocked");
+                }
+                false
+            },
+        }
+    }
+}
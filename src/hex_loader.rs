@@ -0,0 +1,267 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// hex_loader.rs
+//
+// This file contains readers for the two text image formats that embedded
+// toolchains use to ship firmware: Motorola S-records and Intel HEX. Each
+// line of either format is a record holding a few bytes for an address, or
+// the address to start executing at, protected by a checksum. The records
+// are gathered into contiguous blocks of memory for the program loader.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A text image format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HexFormat {
+    /// Motorola S-records (`S0` to `S9`)
+    SRecord,
+    /// Intel HEX records starting with `:`
+    IntelHex,
+}
+
+/// Why a record could not be read
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HexErrorKind {
+    /// The line is not made of the hex digits its record type needs
+    Malformed,
+    /// The record's checksum does not match its contents
+    Checksum { expected: u8, found: u8 },
+    /// The record type is not part of the format
+    UnsupportedRecord(String),
+}
+
+/// An unreadable record and the line it is on, counting from 1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HexError {
+    pub line: usize,
+    pub kind: HexErrorKind,
+}
+
+impl fmt::Display for HexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            HexErrorKind::Malformed => write!(f, "malformed record"),
+            HexErrorKind::Checksum { expected, found } => write!(
+                f,
+                "checksum 0x{:02X} does not match the record's 0x{:02X}",
+                found, expected
+            ),
+            HexErrorKind::UnsupportedRecord(record) => {
+                write!(f, "unsupported record type {}", record)
+            },
+        }
+    }
+}
+
+impl std::error::Error for HexError {}
+
+/// The contents of a hex file: contiguous blocks of bytes in address
+/// order, and the start address if the file gave one
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HexImage {
+    pub blocks: Vec<(u32, Vec<u8>)>,
+    pub entry_point: Option<u32>,
+}
+
+/// Recognise a hex file: every non-blank line must look like a record of
+/// the same format
+pub fn detect(text: &str) -> Option<HexFormat> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let first = lines.next()?;
+    let format = if first.starts_with(':') {
+        HexFormat::IntelHex
+    } else if first.starts_with('S') {
+        HexFormat::SRecord
+    } else {
+        return None;
+    };
+    let looks_like_record = |line: &str| match format {
+        HexFormat::IntelHex => {
+            line.starts_with(':') && line[1..].chars().all(|c| c.is_ascii_hexdigit())
+        },
+        HexFormat::SRecord => {
+            let mut chars = line.chars();
+            chars.next() == Some('S')
+                && chars.next().map_or(false, |c| c.is_ascii_digit())
+                && chars.all(|c| c.is_ascii_hexdigit())
+        },
+    };
+    if looks_like_record(first) && lines.all(looks_like_record) {
+        Some(format)
+    } else {
+        None
+    }
+}
+
+/// Read a hex file in either format
+pub fn parse(text: &str, format: HexFormat) -> Result<HexImage, HexError> {
+    match format {
+        HexFormat::SRecord => parse_srecords(text),
+        HexFormat::IntelHex => parse_intel_hex(text),
+    }
+}
+
+/// Read Motorola S-records. `S1`, `S2` and `S3` hold data at 16, 24 and
+/// 32-bit addresses and `S7`, `S8` and `S9` give the start address. Header
+/// (`S0`) and count (`S5`, `S6`) records are checked and skipped.
+pub fn parse_srecords(text: &str) -> Result<HexImage, HexError> {
+    let mut memory = BTreeMap::new();
+    let mut entry_point = None;
+    for (index, line) in numbered_lines(text) {
+        let error = |kind| HexError { line: index, kind };
+        let record_type = line
+            .get(..2)
+            .ok_or_else(|| error(HexErrorKind::Malformed))?;
+        let bytes = decode_hex(&line[2..]).ok_or_else(|| error(HexErrorKind::Malformed))?;
+        if bytes.is_empty() || bytes[0] as usize != bytes.len() - 1 {
+            return Err(error(HexErrorKind::Malformed));
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - 1);
+        let expected = !body.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        if checksum[0] != expected {
+            return Err(error(HexErrorKind::Checksum {
+                expected: checksum[0],
+                found: expected,
+            }));
+        }
+        let address_length = match record_type {
+            "S0" | "S1" | "S5" | "S9" => 2,
+            "S2" | "S6" | "S8" => 3,
+            "S3" | "S7" => 4,
+            _ => {
+                return Err(error(HexErrorKind::UnsupportedRecord(
+                    record_type.to_string(),
+                )))
+            },
+        };
+        if body.len() < 1 + address_length {
+            return Err(error(HexErrorKind::Malformed));
+        }
+        let address = body[1..=address_length]
+            .iter()
+            .fold(0u32, |address, &byte| address << 8 | byte as u32);
+        let data = &body[1 + address_length..];
+        match record_type {
+            "S1" | "S2" | "S3" => place(&mut memory, address, data),
+            "S7" | "S8" | "S9" => entry_point = Some(address),
+            _ => {},
+        }
+    }
+    Ok(HexImage {
+        blocks: gather_blocks(&memory),
+        entry_point,
+    })
+}
+
+/// Read Intel HEX records: data (`00`), end of file (`01`), extended
+/// segment and linear addresses (`02`, `04`) and start addresses (`03`,
+/// `05`). Records after the end of file record are ignored.
+pub fn parse_intel_hex(text: &str) -> Result<HexImage, HexError> {
+    let mut memory = BTreeMap::new();
+    let mut entry_point = None;
+    let mut base = 0u32;
+    for (index, line) in numbered_lines(text) {
+        let error = |kind| HexError { line: index, kind };
+        let bytes = line
+            .strip_prefix(':')
+            .and_then(decode_hex)
+            .ok_or_else(|| error(HexErrorKind::Malformed))?;
+        if bytes.len() < 5 || bytes[0] as usize != bytes.len() - 5 {
+            return Err(error(HexErrorKind::Malformed));
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - 1);
+        let sum = body.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        let expected = sum.wrapping_neg();
+        if checksum[0] != expected {
+            return Err(error(HexErrorKind::Checksum {
+                expected: checksum[0],
+                found: expected,
+            }));
+        }
+        let offset = u16::from_be_bytes([body[1], body[2]]) as u32;
+        let data = &body[4..];
+        let value = data
+            .iter()
+            .fold(0u32, |value, &byte| value << 8 | byte as u32);
+        match (body[3], data.len()) {
+            (0x00, _) => place(&mut memory, base.wrapping_add(offset), data),
+            (0x01, 0) => break,
+            (0x02, 2) => base = value << 4,
+            (0x03, 4) => entry_point = Some((value >> 16 << 4) + (value & 0xFFFF)),
+            (0x04, 2) => base = value << 16,
+            (0x05, 4) => entry_point = Some(value),
+            (0x01..=0x05, _) => return Err(error(HexErrorKind::Malformed)),
+            (record_type, _) => {
+                return Err(error(HexErrorKind::UnsupportedRecord(format!(
+                    "{:02X}",
+                    record_type
+                ))))
+            },
+        }
+    }
+    Ok(HexImage {
+        blocks: gather_blocks(&memory),
+        entry_point,
+    })
+}
+
+// Non-blank lines with surrounding whitespace removed, numbered from 1
+fn numbered_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(index, line)| (index + 1, line))
+}
+
+// Pairs of hex digits as bytes
+fn decode_hex(digits: &str) -> Option<Vec<u8>> {
+    if digits.len() % 2 != 0 || !digits.is_ascii() {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
+// Later records overwrite earlier ones at the same address
+fn place(memory: &mut BTreeMap<u32, u8>, address: u32, data: &[u8]) {
+    for (offset, &byte) in data.iter().enumerate() {
+        memory.insert(address.wrapping_add(offset as u32), byte);
+    }
+}
+
+fn gather_blocks(memory: &BTreeMap<u32, u8>) -> Vec<(u32, Vec<u8>)> {
+    let mut blocks: Vec<(u32, Vec<u8>)> = Vec::new();
+    for (&address, &byte) in memory {
+        match blocks.last_mut() {
+            Some((start, bytes)) if *start as u64 + bytes.len() as u64 == address as u64 => {
+                bytes.push(byte)
+            },
+            _ => blocks.push((address, vec![byte])),
+        }
+    }
+    blocks
+}
@@ -0,0 +1,59 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// mips.rs
+//
+// This file contains the MIPS frontend, the default instruction set. Its
+// instructions are timed as themselves.
+
+use super::Isa;
+use crate::functional_simulator::instructions::{Instruction, NextPc};
+use crate::functional_simulator::memory::Memory;
+use crate::functional_simulator::registers::Registers;
+use crate::functional_simulator::simulator::{decode_instruction, instruction_to_string};
+
+/// The MIPS32 instruction set the rest of the simulator is built around
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mips;
+
+impl Isa for Mips {
+    fn name(&self) -> &'static str {
+        "mips"
+    }
+
+    fn timing_instruction(&self, word: u32) -> Instruction {
+        decode_instruction(word)
+    }
+
+    fn execute(
+        &self,
+        word: u32,
+        registers: &mut Registers,
+        memory: &mut Memory,
+        _pc: u32,
+    ) -> NextPc {
+        decode_instruction(word).execute(registers, memory)
+    }
+
+    fn disassemble(&self, word: u32) -> String {
+        instruction_to_string(&decode_instruction(word), word)
+    }
+}
@@ -0,0 +1,91 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// mod.rs
+//
+// This file is the module declaration for the isa module, which puts
+// instruction decoding and semantics behind the `Isa` trait so frontends
+// other than MIPS can share the memory, caches, pipeline and visualization.
+// It re-exports the mips and riscv modules.
+
+pub mod mips;
+pub mod riscv;
+
+use crate::functional_simulator::instructions::{Instruction, NextPc};
+use crate::functional_simulator::memory::Memory;
+use crate::functional_simulator::registers::Registers;
+
+/// An instruction set the simulators can run. Registers and memory are
+/// shared by every frontend: general-purpose register `n` of the ISA is
+/// register `n` of the register file, and register 0 always reads 0.
+///
+/// The timing models charge MIPS instructions, so a frontend also describes
+/// each of its instructions as the MIPS instruction with the same register
+/// operands, memory access and kind of control flow. The pipeline, caches
+/// and visualization time that instruction while the frontend executes its
+/// own.
+pub trait Isa {
+    /// Name used on the command line
+    fn name(&self) -> &'static str;
+
+    /// The MIPS instruction the timing models charge for `word`, or
+    /// `Instruction::InvalidInstruction` if `word` is not an instruction
+    fn timing_instruction(&self, word: u32) -> Instruction;
+
+    /// Execute `word` at `pc` and say where execution continues
+    fn execute(&self, word: u32, registers: &mut Registers, memory: &mut Memory, pc: u32)
+        -> NextPc;
+
+    /// Assembly text of `word`
+    fn disassemble(&self, word: u32) -> String;
+}
+
+/// The instruction sets a simulator can be set to run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IsaKind {
+    #[default]
+    Mips,
+    /// Experimental RV32I base integer set
+    Rv32i,
+}
+
+impl IsaKind {
+    /// Name used on the command line: `mips` or `rv32i`
+    pub fn name(&self) -> &'static str {
+        self.frontend().name()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mips" => Some(IsaKind::Mips),
+            "rv32i" => Some(IsaKind::Rv32i),
+            _ => None,
+        }
+    }
+
+    /// The frontend that decodes and executes this instruction set
+    pub fn frontend(&self) -> &'static dyn Isa {
+        match self {
+            IsaKind::Mips => &mips::Mips,
+            IsaKind::Rv32i => &riscv::Rv32i,
+        }
+    }
+}
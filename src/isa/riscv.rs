@@ -0,0 +1,629 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// riscv.rs
+//
+// This file contains an experimental RV32I frontend: the RISC-V base
+// integer instruction set, without the M, A, F or C extensions and without
+// CSRs. Programs run as raw little-endian binaries. `ecall` with 93 (exit)
+// or 10 in a7 ends the run, and `ebreak` raises a breakpoint.
+
+use super::Isa;
+use crate::functional_simulator::instructions::{Instruction, NextPc};
+use crate::functional_simulator::memory::Memory;
+use crate::functional_simulator::registers::Registers;
+use crate::functional_simulator::simulator::Exception;
+
+/// Register holding the system call number, a7
+const SYSCALL_NUMBER: u32 = 17;
+
+/// Register and immediate arithmetic operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AluOp {
+    Add,
+    Sub,
+    Sll,
+    Slt,
+    Sltu,
+    Xor,
+    Srl,
+    Sra,
+    Or,
+    And,
+}
+
+/// Conditions of the conditional branches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchCondition {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Ltu,
+    Geu,
+}
+
+/// Widths of loads and stores; the unsigned ones are for loads only
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+    Byte,
+    Half,
+    Word,
+    ByteUnsigned,
+    HalfUnsigned,
+}
+
+/// A decoded RV32I instruction. Immediates are sign-extended, and those of
+/// `lui` and `auipc` are already shifted into the upper 20 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RvInstruction {
+    Lui {
+        rd: u32,
+        imm: u32,
+    },
+    Auipc {
+        rd: u32,
+        imm: u32,
+    },
+    Jal {
+        rd: u32,
+        offset: i32,
+    },
+    Jalr {
+        rd: u32,
+        rs1: u32,
+        offset: i32,
+    },
+    Branch {
+        condition: BranchCondition,
+        rs1: u32,
+        rs2: u32,
+        offset: i32,
+    },
+    Load {
+        width: Width,
+        rd: u32,
+        rs1: u32,
+        offset: i32,
+    },
+    Store {
+        width: Width,
+        rs1: u32,
+        rs2: u32,
+        offset: i32,
+    },
+    OpImm {
+        op: AluOp,
+        rd: u32,
+        rs1: u32,
+        imm: i32,
+    },
+    Op {
+        op: AluOp,
+        rd: u32,
+        rs1: u32,
+        rs2: u32,
+    },
+    Fence,
+    Ecall,
+    Ebreak,
+    Invalid,
+}
+
+// Sign-extend the low `bits` bits of `value`
+fn sign_extend(value: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32) >> shift
+}
+
+/// Decode an RV32I instruction word
+pub fn decode(word: u32) -> RvInstruction {
+    let opcode = word & 0x7F;
+    let rd = (word >> 7) & 0x1F;
+    let funct3 = (word >> 12) & 0x7;
+    let rs1 = (word >> 15) & 0x1F;
+    let rs2 = (word >> 20) & 0x1F;
+    let funct7 = word >> 25;
+    let i_imm = sign_extend(word >> 20, 12);
+    let s_imm = sign_extend(((word >> 25) << 5) | ((word >> 7) & 0x1F), 12);
+    let b_imm = sign_extend(
+        ((word >> 31) << 12)
+            | (((word >> 7) & 0x1) << 11)
+            | (((word >> 25) & 0x3F) << 5)
+            | (((word >> 8) & 0xF) << 1),
+        13,
+    );
+    let j_imm = sign_extend(
+        ((word >> 31) << 20)
+            | (((word >> 12) & 0xFF) << 12)
+            | (((word >> 20) & 0x1) << 11)
+            | (((word >> 21) & 0x3FF) << 1),
+        21,
+    );
+
+    match opcode {
+        0x37 => RvInstruction::Lui {
+            rd,
+            imm: word & 0xFFFF_F000,
+        },
+        0x17 => RvInstruction::Auipc {
+            rd,
+            imm: word & 0xFFFF_F000,
+        },
+        0x6F => RvInstruction::Jal { rd, offset: j_imm },
+        0x67 if funct3 == 0 => RvInstruction::Jalr {
+            rd,
+            rs1,
+            offset: i_imm,
+        },
+        0x63 => {
+            let condition = match funct3 {
+                0 => BranchCondition::Eq,
+                1 => BranchCondition::Ne,
+                4 => BranchCondition::Lt,
+                5 => BranchCondition::Ge,
+                6 => BranchCondition::Ltu,
+                7 => BranchCondition::Geu,
+                _ => return RvInstruction::Invalid,
+            };
+            RvInstruction::Branch {
+                condition,
+                rs1,
+                rs2,
+                offset: b_imm,
+            }
+        },
+        0x03 => {
+            let width = match funct3 {
+                0 => Width::Byte,
+                1 => Width::Half,
+                2 => Width::Word,
+                4 => Width::ByteUnsigned,
+                5 => Width::HalfUnsigned,
+                _ => return RvInstruction::Invalid,
+            };
+            RvInstruction::Load {
+                width,
+                rd,
+                rs1,
+                offset: i_imm,
+            }
+        },
+        0x23 => {
+            let width = match funct3 {
+                0 => Width::Byte,
+                1 => Width::Half,
+                2 => Width::Word,
+                _ => return RvInstruction::Invalid,
+            };
+            RvInstruction::Store {
+                width,
+                rs1,
+                rs2,
+                offset: s_imm,
+            }
+        },
+        0x13 => {
+            let op = match (funct3, funct7) {
+                (0, _) => AluOp::Add,
+                (2, _) => AluOp::Slt,
+                (3, _) => AluOp::Sltu,
+                (4, _) => AluOp::Xor,
+                (6, _) => AluOp::Or,
+                (7, _) => AluOp::And,
+                (1, 0x00) => AluOp::Sll,
+                (5, 0x00) => AluOp::Srl,
+                (5, 0x20) => AluOp::Sra,
+                _ => return RvInstruction::Invalid,
+            };
+            // Shifts take a 5-bit amount in place of the immediate
+            let imm = match op {
+                AluOp::Sll | AluOp::Srl | AluOp::Sra => rs2 as i32,
+                _ => i_imm,
+            };
+            RvInstruction::OpImm { op, rd, rs1, imm }
+        },
+        0x33 => {
+            let op = match (funct3, funct7) {
+                (0, 0x00) => AluOp::Add,
+                (0, 0x20) => AluOp::Sub,
+                (1, 0x00) => AluOp::Sll,
+                (2, 0x00) => AluOp::Slt,
+                (3, 0x00) => AluOp::Sltu,
+                (4, 0x00) => AluOp::Xor,
+                (5, 0x00) => AluOp::Srl,
+                (5, 0x20) => AluOp::Sra,
+                (6, 0x00) => AluOp::Or,
+                (7, 0x00) => AluOp::And,
+                _ => return RvInstruction::Invalid,
+            };
+            RvInstruction::Op { op, rd, rs1, rs2 }
+        },
+        0x0F => RvInstruction::Fence,
+        0x73 if word == 0x0000_0073 => RvInstruction::Ecall,
+        0x73 if word == 0x0010_0073 => RvInstruction::Ebreak,
+        _ => RvInstruction::Invalid,
+    }
+}
+
+// Result of an arithmetic operation
+fn alu(op: AluOp, a: u32, b: u32) -> u32 {
+    match op {
+        AluOp::Add => a.wrapping_add(b),
+        AluOp::Sub => a.wrapping_sub(b),
+        AluOp::Sll => a << (b & 0x1F),
+        AluOp::Slt => ((a as i32) < (b as i32)) as u32,
+        AluOp::Sltu => (a < b) as u32,
+        AluOp::Xor => a ^ b,
+        AluOp::Srl => a >> (b & 0x1F),
+        AluOp::Sra => ((a as i32) >> (b & 0x1F)) as u32,
+        AluOp::Or => a | b,
+        AluOp::And => a & b,
+    }
+}
+
+fn taken(condition: BranchCondition, a: u32, b: u32) -> bool {
+    match condition {
+        BranchCondition::Eq => a == b,
+        BranchCondition::Ne => a != b,
+        BranchCondition::Lt => (a as i32) < (b as i32),
+        BranchCondition::Ge => (a as i32) >= (b as i32),
+        BranchCondition::Ltu => a < b,
+        BranchCondition::Geu => a >= b,
+    }
+}
+
+fn alu_name(op: AluOp) -> &'static str {
+    match op {
+        AluOp::Add => "add",
+        AluOp::Sub => "sub",
+        AluOp::Sll => "sll",
+        AluOp::Slt => "slt",
+        AluOp::Sltu => "sltu",
+        AluOp::Xor => "xor",
+        AluOp::Srl => "srl",
+        AluOp::Sra => "sra",
+        AluOp::Or => "or",
+        AluOp::And => "and",
+    }
+}
+
+impl RvInstruction {
+    /// Execute the instruction at `pc`. Control transfers continue at an
+    /// absolute address, since RISC-V offsets count from the instruction
+    /// itself.
+    pub fn execute(&self, registers: &mut Registers, memory: &mut Memory, pc: u32) -> NextPc {
+        match *self {
+            RvInstruction::Lui { rd, imm } => registers.write(rd, imm),
+            RvInstruction::Auipc { rd, imm } => registers.write(rd, pc.wrapping_add(imm)),
+            RvInstruction::Jal { rd, offset } => {
+                registers.write(rd, pc.wrapping_add(4));
+                return NextPc::Absolute(pc.wrapping_add(offset as u32));
+            },
+            RvInstruction::Jalr { rd, rs1, offset } => {
+                // Read the base before writing the link, which may replace it
+                let target = registers.read(rs1).wrapping_add(offset as u32) & !1;
+                registers.write(rd, pc.wrapping_add(4));
+                return NextPc::Absolute(target);
+            },
+            RvInstruction::Branch {
+                condition,
+                rs1,
+                rs2,
+                offset,
+            } => {
+                if taken(condition, registers.read(rs1), registers.read(rs2)) {
+                    return NextPc::Absolute(pc.wrapping_add(offset as u32));
+                }
+            },
+            RvInstruction::Load {
+                width,
+                rd,
+                rs1,
+                offset,
+            } => {
+                let address = registers.read(rs1).wrapping_add(offset as u32) as usize;
+                let value = match width {
+                    Width::Byte => memory.read_byte(address).map(|b| b as i8 as i32 as u32),
+                    Width::ByteUnsigned => memory.read_byte(address).map(u32::from),
+                    Width::Half => memory
+                        .read_halfword(address)
+                        .map(|h| h as i16 as i32 as u32),
+                    Width::HalfUnsigned => memory.read_halfword(address).map(u32::from),
+                    Width::Word => memory.read_word(address),
+                };
+                match value {
+                    Ok(value) => registers.write(rd, value),
+                    Err(error) => {
+                        println!("Memory access exception: {}", error);
+                        return NextPc::Trap(Exception::MemoryAccessViolation);
+                    },
+                }
+            },
+            RvInstruction::Store {
+                width,
+                rs1,
+                rs2,
+                offset,
+            } => {
+                let address = registers.read(rs1).wrapping_add(offset as u32) as usize;
+                let value = registers.read(rs2);
+                let stored = match width {
+                    Width::Byte | Width::ByteUnsigned => memory.write_byte(address, value as u8),
+                    Width::Half | Width::HalfUnsigned => {
+                        memory.write_halfword(address, value as u16)
+                    },
+                    Width::Word => memory.write_word(address, value),
+                };
+                if let Err(error) = stored {
+                    println!("Memory access exception: {}", error);
+                    return NextPc::Trap(Exception::MemoryAccessViolation);
+                }
+            },
+            RvInstruction::OpImm { op, rd, rs1, imm } => {
+                registers.write(rd, alu(op, registers.read(rs1), imm as u32))
+            },
+            RvInstruction::Op { op, rd, rs1, rs2 } => {
+                registers.write(rd, alu(op, registers.read(rs1), registers.read(rs2)))
+            },
+            RvInstruction::Fence => {},
+            RvInstruction::Ecall => match registers.read(SYSCALL_NUMBER) {
+                10 | 93 => return NextPc::Halt,
+                number => println!("Unimplemented RISC-V system call: {}", number),
+            },
+            RvInstruction::Ebreak => return NextPc::Trap(Exception::BreakPoint),
+            RvInstruction::Invalid => return NextPc::Trap(Exception::InvalidInstruction),
+        }
+        NextPc::Next
+    }
+
+    /// The MIPS instruction with the same register operands, memory access
+    /// and kind of control flow, which the timing models charge in its place
+    pub fn timing_instruction(&self) -> Instruction {
+        match *self {
+            // Upper immediates read no registers, like MIPS lui
+            RvInstruction::Lui { rd, imm } | RvInstruction::Auipc { rd, imm } => Instruction::Lui {
+                rt: rd,
+                imm: (imm >> 16) as u16,
+            },
+            // Jumps are unconditional; the link is a result like jalr's
+            RvInstruction::Jal { rd, .. } => Instruction::Jalr { rd, rs: 0 },
+            RvInstruction::Jalr { rd, rs1, .. } => Instruction::Jalr { rd, rs: rs1 },
+            // Every conditional branch compares two registers, like beq,
+            // with the word offset that reaches the same target
+            RvInstruction::Branch {
+                rs1, rs2, offset, ..
+            } => Instruction::Beq {
+                rs: rs1,
+                rt: rs2,
+                offset: ((offset - 4) >> 2) as i16,
+            },
+            RvInstruction::Load {
+                width,
+                rd,
+                rs1,
+                offset,
+            } => {
+                let (rt, base, offset) = (rd, rs1, offset as i16);
+                match width {
+                    Width::Byte => Instruction::Lb { rt, base, offset },
+                    Width::ByteUnsigned => Instruction::Lbu { rt, base, offset },
+                    Width::Half => Instruction::Lh { rt, base, offset },
+                    Width::HalfUnsigned => Instruction::Lhu { rt, base, offset },
+                    Width::Word => Instruction::Lw { rt, base, offset },
+                }
+            },
+            RvInstruction::Store {
+                width,
+                rs1,
+                rs2,
+                offset,
+            } => {
+                let (rt, base, offset) = (rs2, rs1, offset as i16);
+                match width {
+                    Width::Byte | Width::ByteUnsigned => Instruction::Sb { rt, base, offset },
+                    Width::Half | Width::HalfUnsigned => Instruction::Sh { rt, base, offset },
+                    Width::Word => Instruction::Sw { rt, base, offset },
+                }
+            },
+            RvInstruction::OpImm { op, rd, rs1, imm } => {
+                let (rt, rs) = (rd, rs1);
+                match op {
+                    AluOp::Sll => Instruction::Sll {
+                        rd,
+                        rt: rs1,
+                        shamt: imm as u32,
+                    },
+                    AluOp::Srl => Instruction::Srl {
+                        rd,
+                        rt: rs1,
+                        shamt: imm as u32,
+                    },
+                    AluOp::Sra => Instruction::Sra {
+                        rd,
+                        rt: rs1,
+                        shamt: imm as u32,
+                    },
+                    AluOp::Slt => Instruction::Slti {
+                        rt,
+                        rs,
+                        imm: imm as i16,
+                    },
+                    AluOp::Sltu => Instruction::Sltiu {
+                        rt,
+                        rs,
+                        imm: imm as i16,
+                    },
+                    AluOp::Xor => Instruction::Xori {
+                        rt,
+                        rs,
+                        imm: imm as u16,
+                    },
+                    AluOp::Or => Instruction::Ori {
+                        rt,
+                        rs,
+                        imm: imm as u16,
+                    },
+                    AluOp::And => Instruction::Andi {
+                        rt,
+                        rs,
+                        imm: imm as u16,
+                    },
+                    AluOp::Add | AluOp::Sub => Instruction::Addiu {
+                        rt,
+                        rs,
+                        imm: imm as i16,
+                    },
+                }
+            },
+            RvInstruction::Op { op, rd, rs1, rs2 } => {
+                let (rs, rt) = (rs1, rs2);
+                match op {
+                    AluOp::Add => Instruction::Add { rd, rs, rt },
+                    AluOp::Sub => Instruction::Sub { rd, rs, rt },
+                    AluOp::Slt => Instruction::Slt { rd, rs, rt },
+                    AluOp::Sltu => Instruction::Sltu { rd, rs, rt },
+                    AluOp::Xor => Instruction::Xor { rd, rs, rt },
+                    AluOp::Or => Instruction::Or { rd, rs, rt },
+                    AluOp::And => Instruction::And { rd, rs, rt },
+                    // MIPS variable shifts take the amount in rs
+                    AluOp::Sll => Instruction::Sllv {
+                        rd,
+                        rt: rs1,
+                        rs: rs2,
+                    },
+                    AluOp::Srl => Instruction::Srlv {
+                        rd,
+                        rt: rs1,
+                        rs: rs2,
+                    },
+                    AluOp::Sra => Instruction::Srav {
+                        rd,
+                        rt: rs1,
+                        rs: rs2,
+                    },
+                }
+            },
+            RvInstruction::Fence | RvInstruction::Ecall | RvInstruction::Ebreak => Instruction::Nop,
+            RvInstruction::Invalid => Instruction::InvalidInstruction,
+        }
+    }
+}
+
+impl std::fmt::Display for RvInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            RvInstruction::Lui { rd, imm } => write!(f, "lui x{}, 0x{:x}", rd, imm >> 12),
+            RvInstruction::Auipc { rd, imm } => write!(f, "auipc x{}, 0x{:x}", rd, imm >> 12),
+            RvInstruction::Jal { rd, offset } => write!(f, "jal x{}, {}", rd, offset),
+            RvInstruction::Jalr { rd, rs1, offset } => {
+                write!(f, "jalr x{}, {}(x{})", rd, offset, rs1)
+            },
+            RvInstruction::Branch {
+                condition,
+                rs1,
+                rs2,
+                offset,
+            } => {
+                let name = match condition {
+                    BranchCondition::Eq => "beq",
+                    BranchCondition::Ne => "bne",
+                    BranchCondition::Lt => "blt",
+                    BranchCondition::Ge => "bge",
+                    BranchCondition::Ltu => "bltu",
+                    BranchCondition::Geu => "bgeu",
+                };
+                write!(f, "{} x{}, x{}, {}", name, rs1, rs2, offset)
+            },
+            RvInstruction::Load {
+                width,
+                rd,
+                rs1,
+                offset,
+            } => {
+                let name = match width {
+                    Width::Byte => "lb",
+                    Width::Half => "lh",
+                    Width::Word => "lw",
+                    Width::ByteUnsigned => "lbu",
+                    Width::HalfUnsigned => "lhu",
+                };
+                write!(f, "{} x{}, {}(x{})", name, rd, offset, rs1)
+            },
+            RvInstruction::Store {
+                width,
+                rs1,
+                rs2,
+                offset,
+            } => {
+                let name = match width {
+                    Width::Byte | Width::ByteUnsigned => "sb",
+                    Width::Half | Width::HalfUnsigned => "sh",
+                    Width::Word => "sw",
+                };
+                write!(f, "{} x{}, {}(x{})", name, rs2, offset, rs1)
+            },
+            RvInstruction::OpImm { op, rd, rs1, imm } => {
+                write!(f, "{}i x{}, x{}, {}", alu_name(op), rd, rs1, imm)
+            },
+            RvInstruction::Op { op, rd, rs1, rs2 } => {
+                write!(f, "{} x{}, x{}, x{}", alu_name(op), rd, rs1, rs2)
+            },
+            RvInstruction::Fence => write!(f, "fence"),
+            RvInstruction::Ecall => write!(f, "ecall"),
+            RvInstruction::Ebreak => write!(f, "ebreak"),
+            RvInstruction::Invalid => write!(f, "unknown"),
+        }
+    }
+}
+
+/// The RV32I base integer instruction set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rv32i;
+
+impl Isa for Rv32i {
+    fn name(&self) -> &'static str {
+        "rv32i"
+    }
+
+    fn timing_instruction(&self, word: u32) -> Instruction {
+        // A zero word is illegal in RISC-V, but the pipeline stops at one
+        // the way it stops at the end of a MIPS program
+        if word == 0 {
+            return Instruction::Nop;
+        }
+        decode(word).timing_instruction()
+    }
+
+    fn execute(
+        &self,
+        word: u32,
+        registers: &mut Registers,
+        memory: &mut Memory,
+        pc: u32,
+    ) -> NextPc {
+        decode(word).execute(registers, memory, pc)
+    }
+
+    fn disassemble(&self, word: u32) -> String {
+        decode(word).to_string()
+    }
+}
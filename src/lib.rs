@@ -22,13 +22,17 @@
 // lib.rs
 //
 // This file is the main library file for the vmips-rust project.
-// It re-exports the functional_simulator, timing_simulator, utils, and
+// It re-exports the functional_simulator, timing_simulator, isa, utils, and
 // assembler modules.
 
 pub mod assembler;
 pub mod elf_loader;
 pub mod errors;
 pub mod functional_simulator;
+pub mod hex_loader;
+pub mod isa;
+pub mod loader;
+pub mod server;
 pub mod timing_simulator;
 pub mod utils;
 
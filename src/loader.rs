@@ -0,0 +1,505 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// loader.rs
+//
+// This file contains the program loader shared by the functional and timing
+// simulators and the command line. It turns raw binaries, assembler output,
+// assembly source, ELF files and S-record or Intel HEX files into a
+// ProgramImage: a list of segments to place in memory and the address to
+// start executing at.
+
+use crate::assembler::{AssembledSection, Assembler, AssemblerError, Program, SectionKind};
+use crate::elf_loader::{ElfError, ElfLoader, Section, ELF_MAGIC};
+use crate::functional_simulator::cp0::RESET_VECTOR;
+use crate::functional_simulator::memory::{AddressSegment, Memory};
+use crate::hex_loader::{self, HexError, HexFormat};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// Load address of the text section of assembler output
+pub const TEXT_BASE: u32 = 0x0040_0000;
+/// Load address of the data section of assembler output
+pub const DATA_BASE: u32 = 0x1000_0000;
+/// Default address of the assembler's `.ktext` section
+pub const KTEXT_BASE: u32 = 0x8000_0000;
+/// Default address of the assembler's `.kdata` section
+pub const KDATA_BASE: u32 = 0x9000_0000;
+
+// File extensions treated as assembly source
+const ASSEMBLY_EXTENSIONS: [&str; 3] = ["s", "S", "asm"];
+
+/// Where a program image came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgramFormat {
+    /// Bare instruction words loaded at address 0
+    Raw,
+    /// Output of the built-in assembler: size header, data and text sections
+    Assembled,
+    /// Assembly source, assembled when it was loaded
+    Source,
+    /// ELF executable
+    Elf,
+    /// Motorola S-record file
+    SRecord,
+    /// Intel HEX file
+    IntelHex,
+    /// Several programs loaded into one address space
+    Overlay,
+}
+
+impl fmt::Display for ProgramFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            ProgramFormat::Raw => "raw binary",
+            ProgramFormat::Assembled => "assembler output",
+            ProgramFormat::Source => "assembly source",
+            ProgramFormat::Elf => "ELF executable",
+            ProgramFormat::SRecord => "Motorola S-record file",
+            ProgramFormat::IntelHex => "Intel HEX file",
+            ProgramFormat::Overlay => "multi-program image",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// A contiguous block of bytes placed at a fixed address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub address: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// Errors that can occur while building or loading a program image
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Elf(ElfError),
+    Assembly(AssemblerError),
+    Hex(HexError),
+    /// A segment does not fit in the simulated memory
+    OutOfBounds {
+        address: u32,
+        memory_size: usize,
+    },
+    /// A load address was given for a format that places its own segments
+    FixedAddresses(ProgramFormat),
+    /// Two programs loaded together place bytes at the same address
+    Overlap {
+        address: u32,
+    },
+    /// A start symbol that is not in the program's symbol table
+    UnknownSymbol(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "I/O error: {}", e),
+            LoadError::Elf(e) => write!(f, "{}", e),
+            LoadError::Assembly(e) => write!(f, "assembly failed: {}", e),
+            LoadError::Hex(e) => write!(f, "invalid hex file: {}", e),
+            LoadError::OutOfBounds {
+                address,
+                memory_size,
+            } => write!(
+                f,
+                "address 0x{:08X} is outside the {} byte memory",
+                address, memory_size
+            ),
+            LoadError::FixedAddresses(format) => write!(
+                f,
+                "a load address only applies to raw binaries, not to this {}",
+                format
+            ),
+            LoadError::Overlap { address } => write!(
+                f,
+                "programs overlap at 0x{:08X}; load them at different addresses",
+                address
+            ),
+            LoadError::UnknownSymbol(name) => write!(f, "no symbol named '{}'", name),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(error: io::Error) -> Self {
+        LoadError::Io(error)
+    }
+}
+
+impl From<ElfError> for LoadError {
+    fn from(error: ElfError) -> Self {
+        LoadError::Elf(error)
+    }
+}
+
+impl From<AssemblerError> for LoadError {
+    fn from(error: AssemblerError) -> Self {
+        LoadError::Assembly(error)
+    }
+}
+
+impl From<HexError> for LoadError {
+    fn from(error: HexError) -> Self {
+        LoadError::Hex(error)
+    }
+}
+
+/// A program ready to be placed in a simulator's memory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProgramImage {
+    pub format: ProgramFormat,
+    pub segments: Vec<Segment>,
+    /// Address of the first instruction to execute
+    pub entry_point: u32,
+    /// Labels of assembled programs and symbols of ELF executables by name;
+    /// empty for formats without them
+    pub symbols: BTreeMap<String, u32>,
+    /// Sections of ELF executables, such as .bss; empty for other formats
+    pub sections: Vec<Section>,
+}
+
+impl ProgramImage {
+    /// Bare instruction words loaded at address 0
+    pub fn raw(bytes: &[u8]) -> Self {
+        Self::raw_at(bytes, 0)
+    }
+
+    /// Bare instruction words loaded and started at `address`, for images
+    /// linked to run somewhere other than address 0
+    pub fn raw_at(bytes: &[u8], address: u32) -> Self {
+        Self {
+            format: ProgramFormat::Raw,
+            segments: vec![Segment {
+                address,
+                bytes: bytes.to_vec(),
+            }],
+            entry_point: address,
+            symbols: BTreeMap::new(),
+            sections: Vec::new(),
+        }
+    }
+
+    /// A firmware image of bare instruction words in the boot ROM, starting
+    /// at the reset vector
+    pub fn firmware(bytes: &[u8]) -> Self {
+        Self::raw_at(bytes, RESET_VECTOR)
+    }
+
+    /// Move a raw binary to `address` and start it there. Other formats
+    /// give their own addresses and are refused.
+    pub fn with_load_address(self, address: u32) -> Result<Self, LoadError> {
+        match self.format {
+            ProgramFormat::Raw => {
+                let bytes: Vec<u8> = self
+                    .segments
+                    .into_iter()
+                    .flat_map(|segment| segment.bytes)
+                    .collect();
+                Ok(Self::raw_at(&bytes, address))
+            },
+            format => Err(LoadError::FixedAddresses(format)),
+        }
+    }
+
+    /// Load `other` into the same address space, keeping this image's entry
+    /// point. Segments are compared at their physical addresses, so a kseg0
+    /// kernel and a user program that alias the same memory are refused.
+    pub fn overlay(mut self, other: ProgramImage) -> Result<Self, LoadError> {
+        for segment in &other.segments {
+            let (start, end) = physical_range(segment);
+            let overlap = self
+                .segments
+                .iter()
+                .map(physical_range)
+                .filter(|&(other_start, other_end)| start < other_end && other_start < end)
+                .map(|(other_start, _)| start.max(other_start))
+                .min();
+            if let Some(address) = overlap {
+                return Err(LoadError::Overlap {
+                    address: address as u32,
+                });
+            }
+        }
+        self.segments.extend(other.segments);
+        self.sections.extend(other.sections);
+        for (name, address) in other.symbols {
+            self.symbols.entry(name).or_insert(address);
+        }
+        self.format = ProgramFormat::Overlay;
+        Ok(self)
+    }
+
+    /// Start executing at `entry_point` instead of the image's own entry
+    pub fn with_entry_point(self, entry_point: u32) -> Self {
+        Self {
+            entry_point,
+            ..self
+        }
+    }
+
+    /// Start executing at the address of a label or ELF symbol, such as
+    /// `main`, instead of the image's own entry
+    pub fn with_start_symbol(self, name: &str) -> Result<Self, LoadError> {
+        match self.symbols.get(name) {
+            Some(&address) => Ok(self.with_entry_point(address)),
+            None => Err(LoadError::UnknownSymbol(name.to_string())),
+        }
+    }
+
+    /// The image to boot from the reset vector: raw binaries become
+    /// firmware, other formats keep the addresses they carry
+    pub fn for_boot(self) -> Self {
+        match self.format {
+            ProgramFormat::Raw => {
+                let bytes: Vec<u8> = self
+                    .segments
+                    .iter()
+                    .flat_map(|segment| segment.bytes.iter().copied())
+                    .collect();
+                Self::firmware(&bytes)
+            },
+            _ => self,
+        }
+    }
+
+    /// Assembler output, with the data section at `DATA_BASE` and the text
+    /// section at `TEXT_BASE`. Returns `None` if the header sizes do not
+    /// match the length of `bytes`.
+    pub fn assembled(bytes: &[u8]) -> Option<Self> {
+        let program = Program::from_bytes(bytes)?;
+        Some(Self {
+            format: ProgramFormat::Assembled,
+            ..Self::from_program(&program)
+        })
+    }
+
+    /// The text and data sections of an assembled `Program` at their
+    /// addresses, starting at its entry point
+    pub fn from_program(program: &Program) -> Self {
+        let mut segments = Vec::new();
+        if !program.data.is_empty() {
+            segments.push(Segment {
+                address: program.data_address,
+                bytes: program.data.clone(),
+            });
+        }
+        segments.push(Segment {
+            address: program.text_address,
+            bytes: program.text_bytes(),
+        });
+
+        Self {
+            format: ProgramFormat::Source,
+            segments,
+            entry_point: program.entry,
+            symbols: program.symbols.clone(),
+            sections: Vec::new(),
+        }
+    }
+
+    /// The loadable segments, sections and entry point of an ELF
+    /// executable. Segments extend to their size in memory, with the part
+    /// not in the file, such as .bss, zeroed.
+    pub fn elf(elf: &ElfLoader) -> Result<Self, LoadError> {
+        let segments = elf
+            .segment_images()?
+            .into_iter()
+            .map(|(address, bytes)| Segment { address, bytes })
+            .collect();
+
+        Ok(Self {
+            format: ProgramFormat::Elf,
+            segments,
+            entry_point: elf.entry_point(),
+            symbols: elf.symbols().clone(),
+            sections: elf.sections().to_vec(),
+        })
+    }
+
+    /// The records of an S-record or Intel HEX file, starting at the
+    /// file's start address or else at its lowest address
+    pub fn hex(text: &str, format: HexFormat) -> Result<Self, LoadError> {
+        let hex = hex_loader::parse(text, format)?;
+        let entry_point = hex
+            .entry_point
+            .or_else(|| hex.blocks.first().map(|&(address, _)| address))
+            .unwrap_or(0);
+        Ok(Self {
+            format: match format {
+                HexFormat::SRecord => ProgramFormat::SRecord,
+                HexFormat::IntelHex => ProgramFormat::IntelHex,
+            },
+            segments: hex
+                .blocks
+                .into_iter()
+                .map(|(address, bytes)| Segment { address, bytes })
+                .collect(),
+            entry_point,
+            symbols: BTreeMap::new(),
+            sections: Vec::new(),
+        })
+    }
+
+    /// Assemble MIPS source with the built-in assembler and lay out the
+    /// result like assembler output
+    pub fn from_assembly(source: &str) -> Result<Self, LoadError> {
+        let mut assembler = Assembler::new();
+        let program = assembler.assemble_program(source)?;
+        Ok(Self {
+            entry_point: program.entry,
+            symbols: program.symbols,
+            ..Self::from_sections(&assembler.sections())
+        })
+    }
+
+    /// Place assembled sections at their addresses, starting at the text
+    /// section
+    pub fn from_sections(sections: &[AssembledSection]) -> Self {
+        let segments = sections
+            .iter()
+            .map(|section| Segment {
+                address: section.address,
+                bytes: section.bytes.clone(),
+            })
+            .collect();
+        let entry_point = sections
+            .iter()
+            .find(|section| section.kind == SectionKind::Text)
+            .map_or(TEXT_BASE, |section| section.address);
+
+        Self {
+            format: ProgramFormat::Source,
+            segments,
+            entry_point,
+            symbols: BTreeMap::new(),
+            sections: Vec::new(),
+        }
+    }
+
+    /// Detect the format of `bytes`: ELF by its magic number, S-record and
+    /// Intel HEX files by every line being a record, assembly source by
+    /// being printable text, assembler output by a header matching the
+    /// length, and raw otherwise
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LoadError> {
+        if bytes.len() >= 4 && bytes[0..4] == ELF_MAGIC {
+            let elf = ElfLoader::load_from_bytes(bytes.to_vec())?;
+            return Self::elf(&elf);
+        }
+        if is_assembly_source(bytes) {
+            let text = String::from_utf8_lossy(bytes);
+            if let Some(format) = hex_loader::detect(&text) {
+                return Self::hex(&text, format);
+            }
+            return Self::from_assembly(&text);
+        }
+        Ok(Self::assembled(bytes).unwrap_or_else(|| Self::raw(bytes)))
+    }
+
+    /// Read a program file and detect its format. Files ending in `.s`,
+    /// `.S` or `.asm` are always assembled.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let bytes = std::fs::read(&path)?;
+        let is_source = path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map_or(false, |extension| ASSEMBLY_EXTENSIONS.contains(&extension));
+        if is_source {
+            return Self::from_assembly(&String::from_utf8_lossy(&bytes));
+        }
+        Self::from_bytes(&bytes)
+    }
+
+    /// Total number of bytes in all segments
+    pub fn size(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| segment.bytes.len())
+            .sum()
+    }
+
+    /// Smallest memory size that holds every segment below `DATA_BASE`,
+    /// placing kseg0 and kseg1 segments at their physical addresses.
+    /// Higher addresses are folded into low memory by address translation.
+    pub fn memory_required(&self) -> usize {
+        self.segments
+            .iter()
+            .map(physical_range)
+            .filter(|&(start, _)| start < DATA_BASE as u64)
+            .map(|(_, end)| end as usize)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Copy every segment into memory, bypassing write permissions
+    pub fn load_into(&self, memory: &mut Memory) -> Result<(), LoadError> {
+        for segment in &self.segments {
+            if memory
+                .write_block_init(segment.address as usize, &segment.bytes)
+                .is_err()
+            {
+                return Err(LoadError::OutOfBounds {
+                    address: segment.address,
+                    memory_size: memory.size,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write `(address, value)` words into memory, bypassing write permissions
+pub fn preload_words(memory: &mut Memory, values: &[(u32, u32)]) -> Result<(), LoadError> {
+    for &(address, value) in values {
+        if !memory.write_word_init(address as usize, value) {
+            return Err(LoadError::OutOfBounds {
+                address,
+                memory_size: memory.size,
+            });
+        }
+    }
+    Ok(())
+}
+
+// The physical addresses a segment covers, with kseg0 and kseg1 addresses
+// translated
+fn physical_range(segment: &Segment) -> (u64, u64) {
+    let start = AddressSegment::of(segment.address)
+        .physical_address(segment.address)
+        .unwrap_or(segment.address) as u64;
+    (start, start + segment.bytes.len() as u64)
+}
+
+// Assembly source is printable ASCII text, while machine code almost always
+// contains zero or other control bytes
+fn is_assembly_source(bytes: &[u8]) -> bool {
+    !bytes.is_empty()
+        && bytes.iter().all(|&byte| {
+            byte == b'\n' || byte == b'\r' || byte == b'\t' || (0x20..0x7F).contains(&byte)
+        })
+}
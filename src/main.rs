@@ -25,27 +25,90 @@
 // It provides a command-line interface for running the functional or timing
 // simulator with a test program.
 
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use vmips_rust::assembler::{Assembler, HazardAnalysis, ProgramBuilder};
 use vmips_rust::elf_loader::ElfLoader;
-use vmips_rust::functional_simulator::instructions::Instruction;
+use vmips_rust::functional_simulator::commit_log::CommitLog;
+use vmips_rust::functional_simulator::console::{Console, ConsoleConfig};
+use vmips_rust::functional_simulator::control_flow::ControlFlowChecks;
+use vmips_rust::functional_simulator::decode_report::UnknownInstructionPolicy;
+use vmips_rust::functional_simulator::disk::DiskConfig;
+use vmips_rust::functional_simulator::framebuffer::{
+    FramebufferConfig, ImageFormat, FRAMEBUFFER_MAX_BYTES,
+};
+use vmips_rust::functional_simulator::layout::MemoryLayout;
+use vmips_rust::functional_simulator::lockstep::LockstepChecker;
 use vmips_rust::functional_simulator::memory::Memory;
-use vmips_rust::functional_simulator::simulator::decode_instruction;
+use vmips_rust::functional_simulator::network::NetworkConfig;
+use vmips_rust::functional_simulator::registers::{DEFAULT_CLOCK_HZ, V0, V1, ZERO};
 use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
-use vmips_rust::timing_simulator::config::{BranchPredictorType, CacheConfig, PipelineConfig};
-use vmips_rust::timing_simulator::pipeline::PipelineStageStatus;
+use vmips_rust::functional_simulator::symbolic::{SymbolicExecutor, SymbolicReport};
+use vmips_rust::isa::IsaKind;
+use vmips_rust::loader::{LoadError, ProgramFormat, ProgramImage, Segment};
+use vmips_rust::server::cosim::{CosimBridge, CosimReport};
+use vmips_rust::server::Server;
+use vmips_rust::timing_simulator::branch_predictor::PredictorState;
+use vmips_rust::timing_simulator::cache_sweep::{CacheSweep, SweepSpec};
+use vmips_rust::timing_simulator::cache_validation::{parse_check, standard_checks, MissRateCheck};
+use vmips_rust::timing_simulator::compare::{PipelineComparison, TimingVariant};
+use vmips_rust::timing_simulator::config::{
+    BranchPredictorType, CacheConfig, PipelineConfig, TomasuloConfig,
+};
+use vmips_rust::timing_simulator::data_breakpoint::DataBreakpoint;
+use vmips_rust::timing_simulator::events::{render_events, EventLog, EventQuery, EventSummary};
+use vmips_rust::timing_simulator::history::PipelineHistory;
+use vmips_rust::timing_simulator::last_writer::Location;
+use vmips_rust::timing_simulator::latency_probe::LatencyProbe;
+use vmips_rust::timing_simulator::loop_memory::LoopMemoryReport;
+use vmips_rust::timing_simulator::regions::render_regions;
+use vmips_rust::timing_simulator::sampling::{SampledSimulator, SamplingConfig};
 use vmips_rust::timing_simulator::simulator::{ExecutionMode, Simulator as TimingSimulator};
+use vmips_rust::timing_simulator::stats::{CpiStack, InstructionTrace, StatsInterval};
+use vmips_rust::timing_simulator::stress::{AccessPattern, StressConfig, StressProgram};
+use vmips_rust::timing_simulator::trace_sampling::{TraceFilter, TraceSampling};
+use vmips_rust::timing_simulator::unrolling::UnrollReport;
+use vmips_rust::timing_simulator::watch::{render_watches, WatchExpression};
+use vmips_rust::timing_simulator::what_if::WhatIfSession;
+use vmips_rust::timing_simulator::workloads::{Workload, WorkloadKind};
+use vmips_rust::utils::clock::parse_frequency;
+use vmips_rust::utils::crash_dump::CrashDump;
+use vmips_rust::utils::examples::{find_example, EXAMPLES};
+use vmips_rust::utils::grading::grade_source;
+use vmips_rust::utils::limits::{
+    parse_byte_size, MemoryUsage, RunLimits, SyscallQuotas, TerminationReason,
+};
 use vmips_rust::utils::logger::{LogLevel, Logger};
+use vmips_rust::utils::progress::DEFAULT_PROGRESS_INTERVAL;
+use vmips_rust::utils::schema::{all_schemas, Artifact};
+use vmips_rust::utils::throttle::{ThrottleClock, ThrottleScenario};
 
 #[derive(Parser)]
 #[command(name = "vmips_rust")]
 #[command(about = "A MIPS processor simulator written in Rust")]
 #[command(version)]
+#[command(arg_required_else_help = true)]
 struct Cli {
+    /// Print the JSON Schema of an exported file format and exit
+    #[arg(
+        long,
+        value_name = "ARTIFACT",
+        value_parser = ["stats", "trace", "snapshot", "pipeline-history", "predictor", "all"]
+    )]
+    schema: Option<String>,
+
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
+// Parsed once per run, so the size of the largest variant does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Run the functional simulator
@@ -69,6 +132,200 @@ enum Commands {
         /// Load as ELF binary instead of raw assembly
         #[arg(long)]
         elf: bool,
+
+        /// Write a word into memory before running, as ADDRESS=VALUE (repeatable)
+        #[arg(long, value_parser = parse_preload)]
+        preload: Vec<(u32, u32)>,
+
+        /// Maximum instructions to execute
+        #[arg(long)]
+        max_instructions: Option<usize>,
+
+        /// Maximum cycles to simulate
+        #[arg(long)]
+        max_cycles: Option<usize>,
+
+        /// Stop the simulation after this many seconds of wall-clock time
+        #[arg(long)]
+        timeout_seconds: Option<f64>,
+
+        /// Stop the simulation once it holds more host memory than this,
+        /// such as 512M or 2G
+        #[arg(long, value_name = "SIZE", value_parser = parse_byte_size)]
+        max_memory: Option<usize>,
+
+        #[command(flatten)]
+        quotas: QuotaArgs,
+
+        /// Periodically report progress and simulation speed on stderr
+        #[arg(long)]
+        progress: bool,
+
+        /// Map a text screen and keyboard into memory, draw the screen while
+        /// running and send stdin to the keyboard
+        #[arg(long)]
+        console: bool,
+
+        /// Size of the console screen as COLUMNSxROWS
+        #[arg(long, default_value = "80x25", value_parser = parse_console_size)]
+        console_size: ConsoleConfig,
+
+        /// Map a block storage device backed by this disk image into memory
+        #[arg(long)]
+        disk: Option<PathBuf>,
+
+        /// Instructions each disk sector takes to transfer
+        #[arg(long, default_value = "100")]
+        disk_latency: u64,
+
+        /// Map a bitmap framebuffer of WIDTHxHEIGHT pixels into memory
+        #[arg(long, value_parser = parse_framebuffer_size)]
+        framebuffer: Option<FramebufferConfig>,
+
+        /// Write the final frame, and every presented frame, to this PNG or
+        /// BMP file
+        #[arg(long, requires = "framebuffer", value_parser = parse_image_path)]
+        framebuffer_dump: Option<PathBuf>,
+
+        /// Write only every Nth presented frame
+        #[arg(long, value_name = "N", default_value = "1")]
+        framebuffer_every: u64,
+
+        /// Map a network device into memory whose packets loop back to it or
+        /// travel as UDP datagrams
+        #[arg(long, value_name = "BACKEND", value_parser = ["loopback", "udp"])]
+        network: Option<String>,
+
+        /// Local address of the UDP backend's socket
+        #[arg(long, default_value = "127.0.0.1:0")]
+        udp_bind: SocketAddr,
+
+        /// Address the UDP backend sends packets to
+        #[arg(long)]
+        udp_peer: Option<SocketAddr>,
+
+        /// Serve host calls made with `break 0xAB`, with file paths relative
+        /// to the current directory
+        #[arg(long)]
+        semihosting: bool,
+
+        /// Keep running past unknown instruction words, skipping them as
+        /// NOPs or raising Reserved Instruction exceptions, and report them
+        #[arg(
+            long,
+            value_name = "ACTION",
+            num_args = 0..=1,
+            default_missing_value = "nop",
+            value_parser = ["nop", "trap"]
+        )]
+        lenient_decode: Option<String>,
+
+        /// Give exception and interrupt handlers this many shadow register
+        /// sets (1-15) and report the save/restore cycles they avoid
+        #[arg(long, value_name = "COUNT", value_parser = clap::value_parser!(u32).range(1..=15))]
+        shadow_sets: Option<u32>,
+
+        /// Write the final registers and memory as a JSON snapshot to this file
+        #[arg(long)]
+        snapshot: Option<PathBuf>,
+
+        /// Start at the reset vector 0xBFC00000 with Status in its reset
+        /// state; raw binaries are loaded there as boot ROM images
+        #[arg(long)]
+        boot: bool,
+
+        /// Load a raw binary at this address instead of 0 and start it there
+        #[arg(long, value_name = "ADDRESS", value_parser = parse_number, conflicts_with = "boot")]
+        load_address: Option<u32>,
+
+        /// Start executing at this address instead of the program's entry point
+        #[arg(long, value_name = "ADDRESS", value_parser = parse_number)]
+        entry: Option<u32>,
+
+        /// Start executing at this label or ELF symbol, such as main, instead
+        /// of the program's entry point
+        #[arg(long, value_name = "SYMBOL", conflicts_with = "entry")]
+        start: Option<String>,
+
+        /// Also load this program, as FILE or FILE@ADDRESS with raw binaries
+        /// placed at ADDRESS (repeatable)
+        #[arg(long, value_name = "FILE[@ADDRESS]", value_parser = parse_load_spec)]
+        load: Vec<(PathBuf, Option<u32>)>,
+
+        /// Run the program twice with identical inputs and check the runs
+        /// match every INTERVAL instructions, exiting with status 1 if not
+        #[arg(
+            long,
+            value_name = "INTERVAL",
+            num_args = 0..=1,
+            default_missing_value = "1000",
+            value_parser = clap::value_parser!(u64).range(1..),
+            conflicts_with_all = ["console", "disk", "framebuffer", "network"]
+        )]
+        lockstep: Option<u64>,
+
+        /// Randomize the initial stack pointer and heap start, from SEED or
+        /// else from the clock, to demonstrate address space randomization
+        #[arg(long, value_name = "SEED", num_args = 0..=1)]
+        randomize_layout: Option<Option<u64>>,
+
+        /// Track taint from the read syscalls and report when tainted data
+        /// reaches a jump target or syscall argument
+        #[arg(long, conflicts_with = "lockstep")]
+        taint: bool,
+
+        /// Also taint this memory range, as ADDRESS:LENGTH (repeatable)
+        #[arg(long, value_name = "ADDRESS:LENGTH", value_parser = parse_taint_range, conflicts_with = "lockstep")]
+        taint_range: Vec<(u32, u32)>,
+
+        /// Keep a shadow stack of return addresses and trap when a `jr $ra`
+        /// goes anywhere else
+        #[arg(long, conflicts_with = "lockstep")]
+        shadow_stack: bool,
+
+        /// Trap when a function returns after the slot it saved `$ra` in was
+        /// overwritten, like a stack canary
+        #[arg(long, conflicts_with = "lockstep")]
+        stack_canary: bool,
+
+        /// Throttle the core by a scenario of rates over simulated time, read
+        /// from a file or written inline as TIME:RATE,... in milliseconds and
+        /// cycles per millisecond
+        #[arg(long, value_name = "SCENARIO", value_parser = parse_throttle)]
+        throttle: Option<ThrottleScenario>,
+
+        /// Core clock frequency, such as 250MHz or 1.5GHz, that the time
+        /// syscalls and `rdhwr $31` read (default 100MHz)
+        #[arg(long, value_name = "FREQUENCY", value_parser = parse_frequency)]
+        cpu_freq: Option<u64>,
+
+        /// Write a log of every retired instruction for co-simulation, as
+        /// Spike-style text or, for a .csv file, a riscv-dv trace CSV
+        #[arg(long, value_name = "FILE", conflicts_with = "lockstep")]
+        commit_log: Option<PathBuf>,
+
+        /// Take a delta checkpoint of registers and memory every N
+        /// instructions and report how much they store
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        checkpoint_every: Option<u64>,
+
+        /// If the run stops on a fault, write the registers, last
+        /// instructions and stack to this file
+        #[arg(long, value_name = "FILE")]
+        crash_dump: Option<PathBuf>,
+
+        /// Instructions listed in a crash dump
+        #[arg(long, value_name = "N", default_value = "32", requires = "crash_dump")]
+        crash_depth: usize,
+
+        /// Count instructions by call stack and write them in the collapsed
+        /// format read by flamegraph tools, with functions named by label
+        #[arg(long, value_name = "FILE")]
+        flamegraph: Option<PathBuf>,
+
+        /// Count only every Nth instruction in the flamegraph
+        #[arg(long, value_name = "N", default_value = "1", requires = "flamegraph", value_parser = clap::value_parser!(u64).range(1..))]
+        flamegraph_every: u64,
     },
     /// Run the timing simulator
     Timing {
@@ -93,180 +350,1043 @@ enum Commands {
         visualize: bool,
 
         /// Maximum cycles to simulate
+        #[arg(long)]
+        max_cycles: Option<usize>,
+
+        /// Load as ELF binary instead of raw assembly
+        #[arg(long)]
+        elf: bool,
+
+        /// Write a word into memory before running, as ADDRESS=VALUE (repeatable)
+        #[arg(long, value_parser = parse_preload)]
+        preload: Vec<(u32, u32)>,
+
+        /// Maximum instructions to execute
+        #[arg(long)]
+        max_instructions: Option<usize>,
+
+        /// Stop the simulation after this many seconds of wall-clock time
+        #[arg(long)]
+        timeout_seconds: Option<f64>,
+
+        /// Stop the simulation once it holds more host memory than this,
+        /// such as 512M or 2G
+        #[arg(long, value_name = "SIZE", value_parser = parse_byte_size)]
+        max_memory: Option<usize>,
+
+        /// Periodically report progress and simulation speed on stderr
+        #[arg(long)]
+        progress: bool,
+
+        /// Write end-of-run and interval statistics as JSON to this file
+        #[arg(long)]
+        stats_json: Option<PathBuf>,
+
+        /// Sample statistics every N instructions (or cycles, see --stats-interval-unit)
+        #[arg(long)]
+        stats_interval: Option<usize>,
+
+        /// Unit for --stats-interval (instructions, cycles)
+        #[arg(long, default_value = "instructions", value_parser = ["instructions", "cycles"])]
+        stats_interval_unit: String,
+
+        /// Fast-forward functionally and simulate timing only for sampled windows
+        #[arg(long)]
+        sampling: bool,
+
+        /// Instructions to fast-forward between samples
+        #[arg(long, default_value = "100000")]
+        sample_fast_forward: usize,
+
+        /// Instructions simulated to warm up caches and predictors before each sample
         #[arg(long, default_value = "1000")]
-        max_cycles: usize,
+        sample_warmup: usize,
+
+        /// Instructions measured in each sample
+        #[arg(long, default_value = "10000")]
+        sample_measure: usize,
+
+        /// Run the pipeline model and record per-cycle stage occupancy to this
+        /// file, as JSON if its name ends in .json or as a table of the PC in
+        /// each stage per cycle if it ends in .csv
+        #[arg(long, conflicts_with = "sampling")]
+        pipeline_history: Option<PathBuf>,
+
+        /// Run the pipeline model and write the timing of every instruction as
+        /// JSON to this file
+        #[arg(long, conflicts_with = "sampling")]
+        trace: Option<PathBuf>,
+
+        /// Run the pipeline model and write a cycle-stamped event for every
+        /// issue, stall, cache access, branch and memory access to this file
+        /// as JSON lines, for `events` to query
+        #[arg(long, value_name = "FILE", conflicts_with = "sampling")]
+        event_log: Option<PathBuf>,
+
+        /// Keep only every Nth traced instruction
+        #[arg(long, value_name = "N", requires = "trace", value_parser = clap::value_parser!(u64).range(1..))]
+        trace_every: Option<u64>,
+
+        /// Trace only memory instructions or only branches and jumps
+        #[arg(long, value_name = "KIND", requires = "trace", value_parser = TraceFilter::parse)]
+        trace_only: Option<TraceFilter>,
+
+        /// Keep only the last K traced instructions and write them only if
+        /// the run stops on a fault
+        #[arg(long, value_name = "K", requires = "trace", value_parser = clap::value_parser!(u64).range(1..))]
+        trace_last: Option<u64>,
+
+        /// Run the pipeline model and report the cache lines, strides and
+        /// conflict misses of the N hottest loops
+        #[arg(long, value_name = "N", conflicts_with = "sampling")]
+        hot_loops: Option<usize>,
+
+        /// Run the pipeline model and show its CPI split into the base and
+        /// the stalls of branches, caches, structural and RAW hazards
+        #[arg(long, conflicts_with = "sampling")]
+        cpi_stack: bool,
+
+        /// Run the pipeline model and estimate the speedup of unrolling the
+        /// hottest small loops by FACTOR and of software pipelining them,
+        /// with the suggested schedules
+        #[arg(
+            long,
+            value_name = "FACTOR",
+            conflicts_with = "sampling",
+            value_parser = clap::value_parser!(u32).range(2..=16)
+        )]
+        unroll: Option<u32>,
+
+        /// Time unknown instruction words as NOPs instead of stopping, and
+        /// report them at the end of the run
+        #[arg(long, conflicts_with = "sampling")]
+        lenient_decode: bool,
+
+        /// Instruction set of the program: mips, or the experimental rv32i,
+        /// which runs in the pipeline model
+        #[arg(long, value_name = "ISA", default_value = "mips", value_parser = parse_isa, conflicts_with = "sampling")]
+        isa: IsaKind,
+
+        /// Run the pipeline model with the core throttled by a scenario of
+        /// rates over simulated time, from a file or inline as TIME:RATE,...
+        #[arg(
+            long,
+            value_name = "SCENARIO",
+            value_parser = parse_throttle,
+            conflicts_with = "sampling"
+        )]
+        throttle: Option<ThrottleScenario>,
+
+        /// Core clock frequency, such as 250MHz or 1.5GHz, used to report
+        /// cycles as simulated time and read by the time syscalls and
+        /// `rdhwr $31` (default 100MHz)
+        #[arg(long, value_name = "FREQUENCY", value_parser = parse_frequency)]
+        cpu_freq: Option<u64>,
+
+        /// Run the pipeline model with the branch predictor tables saved
+        /// in this file by --save-predictor
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["sampling", "warmup_program"])]
+        load_predictor: Option<PathBuf>,
+
+        /// Run the pipeline model and save the trained branch predictor
+        /// tables to this file
+        #[arg(long, value_name = "FILE", conflicts_with = "sampling")]
+        save_predictor: Option<PathBuf>,
+
+        /// Train the branch predictor on a run of this program first, then
+        /// measure the input with the trained predictor
+        #[arg(long, value_name = "FILE", conflicts_with = "sampling")]
+        warmup_program: Option<PathBuf>,
+
+        /// Start at the reset vector 0xBFC00000 with Status in its reset
+        /// state; raw binaries are loaded there as boot ROM images
+        #[arg(long, conflicts_with = "sampling")]
+        boot: bool,
+
+        /// Load a raw binary at this address instead of 0 and start it there
+        #[arg(long, value_name = "ADDRESS", value_parser = parse_number, conflicts_with = "boot")]
+        load_address: Option<u32>,
+
+        /// Start executing at this address instead of the program's entry point
+        #[arg(long, value_name = "ADDRESS", value_parser = parse_number)]
+        entry: Option<u32>,
+
+        /// Start executing at this label or ELF symbol, such as main, instead
+        /// of the program's entry point
+        #[arg(long, value_name = "SYMBOL", conflicts_with = "entry")]
+        start: Option<String>,
+
+        /// Also load this program, as FILE or FILE@ADDRESS with raw binaries
+        /// placed at ADDRESS (repeatable)
+        #[arg(long, value_name = "FILE[@ADDRESS]", value_parser = parse_load_spec)]
+        load: Vec<(PathBuf, Option<u32>)>,
+
+        /// Run the pipeline model and, if it stops on a fault, write the
+        /// registers, last instructions, stack and pipeline to this file
+        #[arg(long, value_name = "FILE", conflicts_with = "sampling")]
+        crash_dump: Option<PathBuf>,
+
+        /// Instructions listed in a crash dump
+        #[arg(long, value_name = "N", default_value = "32", requires = "crash_dump")]
+        crash_depth: usize,
+    },
+    /// Measure per-class instruction latency and throughput in the timing simulator
+    LatencyProbe {
+        /// Instructions in each generated chain
+        #[arg(long, default_value = "64")]
+        chain_length: usize,
+
+        /// Probe the out-of-order (Tomasulo) model instead of the in-order pipeline
+        #[arg(long)]
+        out_of_order: bool,
+
+        /// Disable operand forwarding in the in-order pipeline
+        #[arg(long)]
+        no_forwarding: bool,
+    },
+    /// Sweep cache size, associativity and line size and write the results as CSV
+    CacheSweep {
+        /// Input assembly or ELF file
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+
+        /// JSON file with the parameter ranges to sweep
+        #[arg(short, long)]
+        config: PathBuf,
+
+        /// CSV output file (printed to stdout if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Memory size in bytes
+        #[arg(short, long, default_value = "8192")]
+        memory_size: usize,
 
         /// Load as ELF binary instead of raw assembly
         #[arg(long)]
         elf: bool,
+
+        /// Write a word into memory before running, as ADDRESS=VALUE (repeatable)
+        #[arg(long, value_parser = parse_preload)]
+        preload: Vec<(u32, u32)>,
+
+        /// Maximum cycles to simulate for each point
+        #[arg(long)]
+        max_cycles: Option<usize>,
+    },
+    /// Run a program under two timing configurations and diff the executions
+    Compare {
+        /// Input assembly or ELF file
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+
+        /// Baseline configuration, e.g. "forwarding=on" (defaults if empty)
+        #[arg(long, default_value = "")]
+        baseline: String,
+
+        /// Configuration to compare against the baseline, e.g. "forwarding=off"
+        #[arg(long)]
+        variant: String,
+
+        /// Show every instruction, not only those whose timing differs
+        #[arg(long)]
+        all: bool,
+
+        /// Memory size in bytes
+        #[arg(short, long, default_value = "8192")]
+        memory_size: usize,
+
+        /// Load as ELF binary instead of raw assembly
+        #[arg(long)]
+        elf: bool,
+
+        /// Write a word into memory before running, as ADDRESS=VALUE (repeatable)
+        #[arg(long, value_parser = parse_preload)]
+        preload: Vec<(u32, u32)>,
+
+        /// Maximum cycles to simulate for each configuration
+        #[arg(long)]
+        max_cycles: Option<usize>,
+    },
+    /// Step the timing simulator interactively, changing settings and replaying
+    WhatIf {
+        /// Input assembly or ELF file
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+
+        /// Initial configuration, e.g. "forwarding=off" (defaults if empty)
+        #[arg(long, default_value = "")]
+        config: String,
+
+        /// Memory size in bytes
+        #[arg(short, long, default_value = "8192")]
+        memory_size: usize,
+
+        /// Load as ELF binary instead of raw assembly
+        #[arg(long)]
+        elf: bool,
+
+        /// Write a word into memory before running, as ADDRESS=VALUE (repeatable)
+        #[arg(long, value_parser = parse_preload)]
+        preload: Vec<(u32, u32)>,
+
+        /// Record the last writer of every register and memory word for `who-wrote`
+        #[arg(long)]
+        track_writes: bool,
+    },
+    /// Convert a recorded pipeline history to the Konata viewer's log format
+    Konata {
+        /// Pipeline history file written by `timing --pipeline-history`
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output log file (stdout if not given)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Query an event log written by `timing --event-log`
+    Events {
+        /// Event log file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Only events matching this query, e.g.
+        /// category=cache+memory,pc=0x400000-0x40001C,cycles=100-200
+        #[arg(short, long, value_parser = EventQuery::parse, default_value = "")]
+        query: EventQuery,
+
+        /// Print totals of the matching events instead of listing them
+        #[arg(long)]
+        summary: bool,
+    },
+    /// Report basic blocks, register dependencies, load-use hazards and
+    /// delay slot candidates without running the program
+    Analyze {
+        /// Input assembly, assembler output, ELF or raw file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Print the analysis as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Explore the paths of a small function with symbolic arguments and
+    /// report inputs that reach an assertion (experimental)
+    Symbolic {
+        /// Input assembly, assembler output, ELF or raw file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Label or address of the function (the entry point if not given)
+        #[arg(long)]
+        function: Option<String>,
+
+        /// Argument registers, from $a0, that hold symbolic inputs
+        #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(0..=4))]
+        args: u32,
+
+        /// Paths to report before stopping
+        #[arg(long, default_value = "64")]
+        max_paths: usize,
+
+        /// Instructions a single path may execute
+        #[arg(long, default_value = "10000")]
+        max_steps: usize,
+    },
+    /// Serve simulation sessions over an HTTP and WebSocket JSON API
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        address: String,
+    },
+    /// Run a program and check the `#! expect` annotations in its source,
+    /// printing a TAP report
+    Grade {
+        /// Annotated assembly source
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Also write the report as JSON to this file
+        #[arg(long, value_name = "FILE")]
+        json: Option<PathBuf>,
+
+        /// Instructions the program may run before it is stopped and graded
+        #[arg(long, default_value = "1000000")]
+        max_instructions: usize,
+
+        #[command(flatten)]
+        quotas: QuotaArgs,
+    },
+    /// Generate a benchmark kernel for a size, run it on the timing
+    /// simulator and check its result
+    Bench {
+        /// Kernel to run: matmul or sort
+        #[arg(long, value_parser = parse_workload_kind)]
+        workload: WorkloadKind,
+
+        /// Matrix dimension or array length
+        #[arg(long, default_value = "16")]
+        n: usize,
+
+        /// Seed for the generated input data
+        #[arg(long, default_value = "1")]
+        seed: u32,
+
+        /// Timing configuration, e.g. "dcache=4096/2/32" (defaults if empty)
+        #[arg(long, default_value = "")]
+        config: String,
+
+        /// Print the kernel's assembly instead of running it
+        #[arg(long)]
+        source: bool,
+    },
+    /// Generate a random stress program from a seed, run it on the timing
+    /// simulator and check its final registers and memory
+    Stress {
+        /// Seed for the generated program
+        #[arg(long, default_value = "1")]
+        seed: u32,
+
+        /// Operations in the loop body
+        #[arg(long, default_value = "64")]
+        length: usize,
+
+        /// Times the loop body runs
+        #[arg(long, default_value = "100")]
+        iterations: usize,
+
+        /// Operations in each dependency chain
+        #[arg(long, default_value = "4")]
+        chain: usize,
+
+        /// Fraction of operations that are forward branches
+        #[arg(long, default_value = "0.1")]
+        branches: f64,
+
+        /// Fraction of operations that are loads or stores
+        #[arg(long, default_value = "0.25")]
+        memory: f64,
+
+        /// Memory access pattern: sequential, random or stride:N
+        #[arg(long, default_value = "sequential", value_parser = AccessPattern::parse)]
+        pattern: AccessPattern,
+
+        /// Bytes of memory the loads and stores touch, a power of two
+        #[arg(long, default_value = "4096")]
+        working_set: u32,
+
+        /// Timing configuration, e.g. "dcache=4096/2/32" (defaults if empty)
+        #[arg(long, default_value = "")]
+        config: String,
+
+        /// Print the program's disassembly instead of running it
+        #[arg(long)]
+        source: bool,
+    },
+    /// Run synthetic access streams through the cache model and check its
+    /// miss rates against their closed-form values
+    CacheCheck {
+        /// Cache geometry SIZE/ASSOC/LINE; runs the standard checks if not
+        /// given
+        #[arg(long)]
+        cache: Option<String>,
+
+        /// Replacement policy: lru, fifo, random or lfu
+        #[arg(long, default_value = "lru")]
+        replacement: String,
+
+        /// Access pattern: sequential, random or stride:N
+        #[arg(long, default_value = "sequential", value_parser = AccessPattern::parse)]
+        pattern: AccessPattern,
+
+        /// Bytes the stream touches, a power of two
+        #[arg(long, default_value = "8192")]
+        working_set: usize,
+
+        /// Sweeps over the working set
+        #[arg(long, default_value = "4")]
+        passes: usize,
+
+        /// Seed for random streams
+        #[arg(long, default_value = "1")]
+        seed: u32,
+    },
+    /// List the bundled example programs, or run one and check its output
+    RunExample {
+        /// Example to run; lists the examples if not given
+        name: Option<String>,
+
+        /// Print the example's assembly source instead of running it
+        #[arg(long)]
+        source: bool,
+    },
+    /// Check an RTL core against the functional simulator, instruction by
+    /// instruction, over a line-based TCP protocol for testbenches
+    Cosim {
+        /// Input assembly, assembler output, ELF or raw file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Address to wait for the testbench on
+        #[arg(long, default_value = "127.0.0.1:7447")]
+        address: String,
     },
 }
 
-// Helper function to load data into memory
-fn load_test_data(memory: &mut Memory) {
-    // Store some test values in memory using the initialization method
-    memory.write_word_init(0x1000, 10);
-    memory.write_word_init(0x1004, 20);
-    memory.write_word_init(0x1008, 30);
-    memory.write_word_init(0x100C, 40);
+// Syscall quotas for running untrusted programs
+#[derive(Args, Debug)]
+struct QuotaArgs {
+    /// Stop the program once it prints more than this, such as 64K
+    #[arg(long, value_name = "SIZE", value_parser = parse_byte_size)]
+    max_output: Option<usize>,
+
+    /// Stop the program once it writes more than this to files
+    #[arg(long, value_name = "SIZE", value_parser = parse_byte_size)]
+    max_file_write: Option<usize>,
+
+    /// Stop the program once sbrk would grow its heap by more than this
+    #[arg(long, value_name = "SIZE", value_parser = parse_byte_size)]
+    max_heap: Option<usize>,
+}
+
+impl QuotaArgs {
+    fn quotas(&self) -> SyscallQuotas {
+        SyscallQuotas {
+            max_output: self.max_output,
+            max_file_write: self.max_file_write,
+            max_heap: self.max_heap,
+        }
+    }
+}
+
+// Limits and progress reporting that apply to a single run
+struct RunControl {
+    limits: RunLimits,
+    progress: bool,
+    stats_interval: Option<StatsInterval>,
+    stats_json: Option<PathBuf>,
+    pipeline_history: Option<PathBuf>,
+    trace: Option<PathBuf>,
+    event_log: Option<PathBuf>,
+    trace_sampling: TraceSampling,
+    hot_loops: Option<usize>,
+    cpi_stack: bool,
+    unroll: Option<usize>,
+    console: Option<ConsoleConfig>,
+    disk: Option<DiskConfig>,
+    framebuffer: Option<FramebufferConfig>,
+    network: Option<NetworkConfig>,
+    semihosting: bool,
+    snapshot: Option<PathBuf>,
+    lenient_decode: Option<UnknownInstructionPolicy>,
+    shadow_sets: Option<u32>,
+    boot: bool,
+    lockstep: Option<usize>,
+    layout_seed: Option<u64>,
+    taint_input: bool,
+    taint_ranges: Vec<(u32, u32)>,
+    control_flow: ControlFlowChecks,
+    throttle: Option<ThrottleScenario>,
+    cpu_freq: Option<u64>,
+    isa: IsaKind,
+    commit_log: Option<PathBuf>,
+    checkpoint_every: Option<u64>,
+    crash_dump: Option<PathBuf>,
+    crash_depth: usize,
+    flamegraph: Option<PathBuf>,
+    flamegraph_every: u64,
+    predictor: PredictorFiles,
+}
 
-    println!("Test data loaded into memory at addresses 0x1000-0x100C");
+// Branch predictor state carried between timing runs
+#[derive(Default)]
+struct PredictorFiles {
+    load: Option<PathBuf>,
+    save: Option<PathBuf>,
+    warmup_program: Option<PathBuf>,
+}
+
+impl PredictorFiles {
+    fn any(&self) -> bool {
+        self.load.is_some() || self.save.is_some() || self.warmup_program.is_some()
+    }
+}
+
+// Helper function to build run limits from the command-line options
+fn build_run_limits(
+    max_instructions: Option<usize>,
+    max_cycles: Option<usize>,
+    timeout_seconds: Option<f64>,
+    max_memory: Option<usize>,
+) -> RunLimits {
+    let mut limits = RunLimits::default();
+    if let Some(max_instructions) = max_instructions {
+        limits.max_instructions = max_instructions;
+    }
+    if let Some(max_cycles) = max_cycles {
+        limits.max_cycles = max_cycles;
+    }
+    limits.timeout = timeout_seconds
+        .filter(|seconds| *seconds > 0.0)
+        .map(Duration::from_secs_f64);
+    limits.max_memory = max_memory;
+    limits
+}
+
+// Helper function to parse the statistics interval options
+fn build_stats_interval(interval: Option<usize>, unit: &str) -> Option<StatsInterval> {
+    let interval = interval.filter(|n| *n > 0)?;
+    // clap has already rejected any other unit
+    match unit {
+        "cycles" => Some(StatsInterval::Cycles(interval)),
+        _ => Some(StatsInterval::Instructions(interval)),
+    }
 }
 
 // Helper function to load program from file or create test program
 fn load_program(
     input_file: Option<&PathBuf>,
     is_elf: bool,
-) -> Result<(Vec<u8>, Option<u32>), Box<dyn std::error::Error>> {
-    if let Some(file_path) = input_file {
-        if is_elf {
-            // Load ELF binary
-            let elf_loader = ElfLoader::load_file(file_path)?;
-            let entry_point = elf_loader.entry_point();
-
-            // For ELF files, we'll return empty program data since the loader
-            // will handle loading into memory directly
-            Ok((Vec::new(), Some(entry_point)))
-        } else {
-            // Load raw binary or assembly file
-            use std::fs;
-            let data = fs::read(file_path)?;
-            Ok((data, None))
-        }
-    } else {
-        // Create default test program
-        Ok((create_test_program(), None))
-    }
-}
-
-// Helper function to create a simple test program
-fn create_test_program() -> Vec<u8> {
-    // Create program as u32 values
-    let program_words = vec![
-        0x8C021000u32, // lw $2, 0x1000($0)     - Load from 0x1000 (value 10)
-        0x8C031004u32, // lw $3, 0x1004($0)     - Load from 0x1004 (value 20)
-        0x00431020u32, // add $2, $2, $3        - Add values (10+20=30)
-        0xAC021008u32, // sw $2, 0x1008($0)     - Store at 0x1008
-        // Multiplication
-        0x8C021000u32, // lw $2, 0x1000($0)     - Load again from 0x1000 (value 10)
-        0x8C031004u32, // lw $3, 0x1004($0)     - Load again from 0x1004 (value 20)
-        0x00430018u32, // mult $2, $3           - Multiply (10*20=200)
-        0x00001012u32, // mflo $2               - Get multiplication result
-        0xAC02100Cu32, // sw $2, 0x100C($0)     - Store result at 0x100C
+) -> Result<ProgramImage, Box<dyn std::error::Error>> {
+    match input_file {
+        // --elf rejects files that are not ELF instead of loading them raw
+        Some(file_path) if is_elf => Ok(ProgramImage::elf(&ElfLoader::load_file(file_path)?)?),
+        Some(file_path) => Ok(ProgramImage::from_file(file_path)?),
+        None => Ok(create_test_program()),
+    }
+}
+
+// Load the input program, or the test program if there is nothing else to
+// run, and each --load program into one address space. The entry point is
+// the first program's.
+fn load_programs(
+    input_file: Option<&PathBuf>,
+    is_elf: bool,
+    overlays: &[(PathBuf, Option<u32>)],
+) -> Result<ProgramImage, Box<dyn std::error::Error>> {
+    let mut images = Vec::new();
+    if input_file.is_some() || overlays.is_empty() {
+        images.push(load_program(input_file, is_elf)?);
+    }
+    for (path, address) in overlays {
+        let image = ProgramImage::from_file(path)?;
+        images.push(match address {
+            Some(address) => image.with_load_address(*address)?,
+            None => image,
+        });
+    }
+    let mut images = images.into_iter();
+    let first = images.next().ok_or("no program to load")?;
+    Ok(images.try_fold(first, ProgramImage::overlay)?)
+}
+
+// Apply the --boot, --load-address, --entry and --start options to a loaded
+// program
+fn place_program(
+    image: ProgramImage,
+    boot: bool,
+    load_address: Option<u32>,
+    entry: Option<u32>,
+    start: Option<&str>,
+) -> Result<ProgramImage, LoadError> {
+    let image = match load_address {
+        Some(address) => image.with_load_address(address)?,
+        None if boot => image.for_boot(),
+        None => image,
+    };
+    match (entry, start) {
+        (Some(entry), _) => Ok(image.with_entry_point(entry)),
+        (None, Some(symbol)) => image.with_start_symbol(symbol),
+        (None, None) => Ok(image),
+    }
+}
+
+// Grow the requested memory size so that every segment of the image fits.
+// Assembly source and assembler output place their text at TEXT_BASE, which
+// needs more than the default memory size. The size is rounded up to a power
+// of two because high addresses are translated by masking with size - 1.
+fn fit_memory_size(image: &ProgramImage, memory_size: usize) -> usize {
+    let required = image.memory_required().next_power_of_two();
+    if image.memory_required() <= memory_size {
+        return memory_size;
+    }
+    println!(
+        "Note: increasing memory size from {} to {} bytes to fit the program",
+        memory_size, required
+    );
+    required
+}
+
+// Print the segments of a program image before it is loaded
+fn print_image(image: &ProgramImage) {
+    println!("Loading {} of {} bytes", image.format, image.size());
+    for segment in &image.segments {
+        println!(
+            "  Segment: 0x{:08X} - 0x{:08X}",
+            segment.address,
+            segment.address as usize + segment.bytes.len()
+        );
+    }
+    println!("Entry point: 0x{:08X}", image.entry_point);
+}
+
+// The bundled examples, one per line, with how to run them
+fn render_example_list() -> String {
+    let width = EXAMPLES
+        .iter()
+        .map(|example| example.name.len())
+        .max()
+        .unwrap_or(0);
+    let mut text = String::from("Bundled examples:\n");
+    for example in EXAMPLES {
+        text.push_str(&format!(
+            "  {:width$}  {}\n",
+            example.name,
+            example.description,
+            width = width
+        ));
+    }
+    text.push_str("Run one with `vmips_rust run-example NAME`, or print it with --source\n");
+    text
+}
+
+// Address of the built-in test program's operands and results
+const DEMO_DATA_ADDRESS: usize = 0x0400;
+
+// How often a running console is redrawn when its screen changed
+const CONSOLE_REFRESH_INTERVAL: Duration = Duration::from_millis(50);
+
+// Small loops an unrolling report covers, hottest first
+const UNROLL_REPORT_LOOPS: usize = 3;
+
+// Helper function to create a simple test program with its input data
+fn create_test_program() -> ProgramImage {
+    // Loads 10 and 20, stores their sum and then their product
+    let operands = DEMO_DATA_ADDRESS as i16;
+    let program = ProgramBuilder::new()
+        .lw(V0, operands, ZERO)
+        .lw(V1, operands + 4, ZERO)
+        .add(V0, V0, V1)
+        .sw(V0, operands + 8, ZERO)
+        .lw(V0, operands, ZERO)
+        .lw(V1, operands + 4, ZERO)
+        .mult(V0, V1)
+        .mflo(V0)
+        .sw(V0, operands + 12, ZERO)
         // Explicit termination - multiple NOPs
-        0x00000000u32, // nop
-        0x00000000u32, // nop
-        0x00000000u32, // nop
-        0x00000000u32, // nop
-        0x00000000u32, // nop
-    ];
+        .nop()
+        .nop()
+        .nop()
+        .nop()
+        .nop()
+        .build()
+        .expect("the demo program has no labels");
+
+    // The operands the program loads, followed by room for the results
+    let data: Vec<u8> = [10u32, 20, 0, 0]
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect();
+
+    let mut image = ProgramImage::raw(&program.text_bytes());
+    image.segments.push(Segment {
+        address: DEMO_DATA_ADDRESS as u32,
+        bytes: data,
+    });
+    image
+}
+
+// Parse a --preload value of the form ADDRESS=VALUE (decimal or 0x hex)
+// Decimal, negative or 0x-prefixed hexadecimal number
+fn parse_number(text: &str) -> Result<u32, String> {
+    let text = text.trim();
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text
+            .parse::<u32>()
+            .ok()
+            .or_else(|| text.parse::<i32>().ok().map(|value| value as u32)),
+    };
+    parsed.ok_or_else(|| format!("invalid number '{}'", text))
+}
+
+// Seed for --randomize-layout when none is given, printed with the layout so
+// the run can be repeated
+fn clock_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64)
+}
 
-    // Convert to bytes with explicit endianness
-    let mut program_bytes = Vec::with_capacity(program_words.len() * 4);
-    for &word in &program_words {
-        program_bytes.extend_from_slice(&word.to_le_bytes());
+// Parse a program to load as FILE or FILE@ADDRESS
+fn parse_load_spec(spec: &str) -> Result<(PathBuf, Option<u32>), String> {
+    match spec.rsplit_once('@') {
+        Some((path, address)) if !path.is_empty() => {
+            Ok((PathBuf::from(path), Some(parse_number(address)?)))
+        },
+        _ => Ok((PathBuf::from(spec), None)),
+    }
+}
+
+// Parse a memory range to taint given as ADDRESS:LENGTH
+fn parse_taint_range(spec: &str) -> Result<(u32, u32), String> {
+    match spec.split_once(':') {
+        Some((address, length)) => Ok((parse_number(address)?, parse_number(length)?)),
+        None => Err(format!("expected ADDRESS:LENGTH, got '{}'", spec)),
     }
+}
+
+// Parse a throttling scenario from a file, or written inline as in
+// `0:1000,5:250`
+fn parse_throttle(spec: &str) -> Result<ThrottleScenario, String> {
+    let path = Path::new(spec);
+    let text = if path.is_file() {
+        std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", spec, e))?
+    } else {
+        spec.to_string()
+    };
+    ThrottleScenario::parse(&text).map_err(|e| e.to_string())
+}
+
+fn parse_isa(name: &str) -> Result<IsaKind, String> {
+    IsaKind::from_name(name).ok_or_else(|| format!("unknown instruction set '{}'", name))
+}
+
+fn parse_preload(spec: &str) -> Result<(u32, u32), String> {
+    match spec.split_once('=') {
+        Some((address, value)) => Ok((parse_number(address)?, parse_number(value)?)),
+        None => Err(format!("expected ADDRESS=VALUE, got '{}'", spec)),
+    }
+}
+
+fn parse_workload_kind(name: &str) -> Result<WorkloadKind, String> {
+    WorkloadKind::from_name(name).ok_or_else(|| {
+        let names: Vec<&str> = WorkloadKind::all().iter().map(|kind| kind.name()).collect();
+        format!("expected one of {}", names.join(", "))
+    })
+}
 
-    program_bytes
+// Parse a size given as WIDTHxHEIGHT
+fn parse_dimensions(spec: &str) -> Option<(usize, usize)> {
+    spec.split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
 }
 
-// Helper function to display memory contents
+// Parse a console size given as COLUMNSxROWS
+fn parse_console_size(spec: &str) -> Result<ConsoleConfig, String> {
+    match parse_dimensions(spec) {
+        Some((columns, rows)) if columns > 0 && rows > 0 && columns * rows * 2 <= 0xFF0 => {
+            Ok(ConsoleConfig::new(columns, rows))
+        },
+        Some(_) => Err(format!(
+            "console size '{}' does not fit in the screen buffer",
+            spec
+        )),
+        None => Err(format!("expected COLUMNSxROWS, got '{}'", spec)),
+    }
+}
+
+// Parse a framebuffer resolution given as WIDTHxHEIGHT
+fn parse_framebuffer_size(spec: &str) -> Result<FramebufferConfig, String> {
+    match parse_dimensions(spec) {
+        Some((width, height))
+            if width > 0 && height > 0 && width * height * 4 <= FRAMEBUFFER_MAX_BYTES =>
+        {
+            Ok(FramebufferConfig::new(width, height))
+        },
+        Some(_) => Err(format!(
+            "framebuffer size '{}' does not fit in the framebuffer's address range",
+            spec
+        )),
+        None => Err(format!("expected WIDTHxHEIGHT, got '{}'", spec)),
+    }
+}
+
+// Accept only image file names whose extension selects a format
+fn parse_image_path(spec: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(spec);
+    match ImageFormat::from_path(&path) {
+        Some(_) => Ok(path),
+        None => Err(format!("expected a .png or .bmp file, got '{}'", spec)),
+    }
+}
+
+// Helper function to display the memory the test program works on
 fn display_memory_values(memory: &Memory) {
     println!("\nMemory Contents:");
-    println!("Address 0x1000: {:?}", memory.read_word(0x1000));
-    println!("Address 0x1004: {:?}", memory.read_word(0x1004));
-    println!("Address 0x1008: {:?}", memory.read_word(0x1008));
-    println!("Address 0x100C: {:?}", memory.read_word(0x100C));
-    println!("Address 0x1010: {:?}", memory.read_word(0x1010));
+    for address in (DEMO_DATA_ADDRESS..=DEMO_DATA_ADDRESS + 16).step_by(4) {
+        println!("Address 0x{:04X}: {:?}", address, memory.read_word(address));
+    }
 }
 
-// Run the functional simulator with the given program
-fn run_functional_simulator(
-    program: &[u8],
-    memory_size: usize,
-    _entry_point: Option<u32>,
-    input_file: Option<&PathBuf>,
-    is_elf: bool,
-) {
-    let mut simulator = FunctionalSimulator::new(memory_size);
+// Redraws the console screen while the guest runs and feeds stdin to its
+// keyboard
+struct ConsoleSession {
+    console: Console,
+    running: Arc<AtomicBool>,
+    renderer: thread::JoinHandle<()>,
+}
 
-    // Handle ELF loading or regular program loading
-    if is_elf {
-        if let Some(file_path) = input_file {
-            // Load ELF file directly into memory
-            match ElfLoader::load_file(file_path) {
-                Ok(elf_loader) => {
-                    if let Err(e) = elf_loader.load_into_memory(&mut simulator.memory) {
-                        eprintln!("Failed to load ELF into memory: {:?}", e);
-                        return;
-                    }
-                    println!("ELF binary loaded successfully");
-                    let segments = elf_loader.get_segments();
-                    for (vaddr, size, flags) in segments {
-                        println!(
-                            "  Segment: 0x{:08X} - 0x{:08X} (flags: 0x{:X})",
-                            vaddr,
-                            vaddr + size,
-                            flags
-                        );
-                    }
-                },
-                Err(e) => {
-                    eprintln!("Failed to load ELF file: {:?}", e);
-                    return;
-                },
+impl ConsoleSession {
+    fn start(console: Console) -> Self {
+        let keyboard = console.clone();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 64];
+            loop {
+                match io::stdin().read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(count) => keyboard.press_keys(&buffer[..count]),
+                }
             }
-        }
-    } else {
-        // First clear and then initialize memory with test data
-        load_test_data(&mut simulator.memory);
-
-        // Debug the program bytes
-        println!("Loading program of size {} bytes", program.len());
-        if program.len() >= 4 {
-            let first_instruction =
-                u32::from_le_bytes([program[0], program[1], program[2], program[3]]);
-            println!("First instruction: 0x{:08X}", first_instruction);
-        }
-
-        // Dump all program instructions for debugging
-        println!("Program instructions:");
-        for i in (0..program.len()).step_by(4) {
-            if i + 3 < program.len() {
-                let instruction = u32::from_le_bytes([
-                    program[i],
-                    program[i + 1],
-                    program[i + 2],
-                    program[i + 3],
-                ]);
-                println!("  0x{:04X}: 0x{:08X}", i, instruction);
+        });
+
+        let running = Arc::new(AtomicBool::new(true));
+        let screen = console.clone();
+        let still_running = Arc::clone(&running);
+        let renderer = thread::spawn(move || {
+            while still_running.load(Ordering::Relaxed) {
+                if screen.take_dirty() {
+                    print!("\x1b[H\x1b[2J{}", screen.render_ansi());
+                    let _ = io::stdout().flush();
+                }
+                thread::sleep(CONSOLE_REFRESH_INTERVAL);
             }
+        });
+        Self {
+            console,
+            running,
+            renderer,
         }
+    }
 
-        // Load program into simulator using write_word_init to bypass permissions
-        for i in (0..program.len()).step_by(4) {
-            if i + 3 < program.len() {
-                let instruction = u32::from_le_bytes([
-                    program[i],
-                    program[i + 1],
-                    program[i + 2],
-                    program[i + 3],
-                ]);
-                simulator.memory.write_word_init(i, instruction);
-            }
+    // Stop redrawing and print the final screen
+    fn finish(self) {
+        self.running.store(false, Ordering::Relaxed);
+        let _ = self.renderer.join();
+        println!("\nConsole:\n{}", self.console.render());
+    }
+}
+
+// Run the program in two identically built functional simulators and check
+// that they stay in step
+fn run_lockstep_check(
+    image: &ProgramImage,
+    preload: &[(u32, u32)],
+    memory_size: usize,
+    control: &RunControl,
+    interval: usize,
+) {
+    // Both copies are allocated at once
+    let mut projected = FunctionalSimulator::projected_memory_usage(memory_size);
+    projected.memory *= 2;
+    if let Err(e) = control.limits.check_memory(&projected) {
+        eprintln!("Error: {}", e);
+        return;
+    }
+    let build = || -> Result<FunctionalSimulator, LoadError> {
+        let mut simulator = FunctionalSimulator::new(memory_size);
+        simulator.limits = control.limits;
+        simulator.load_image(image)?;
+        simulator.preload_memory(preload)?;
+        if control.boot {
+            simulator.reset_to_boot_vector();
+        }
+        if let Some(seed) = control.layout_seed {
+            simulator.apply_layout(&MemoryLayout::randomized(&simulator.memory, seed));
+        }
+        if control.semihosting {
+            simulator.enable_semihosting(".");
         }
+        if let Some(policy) = control.lenient_decode {
+            simulator.enable_lenient_decode(policy);
+        }
+        if let Some(count) = control.shadow_sets {
+            simulator.registers.enable_shadow_sets(count);
+        }
+        simulator.captured_output = Some(Vec::new());
+        Ok(simulator)
+    };
+    print_image(image);
+    let (mut first, mut second) = match build().and_then(|first| Ok((first, build()?))) {
+        Ok(simulators) => simulators,
+        Err(e) => {
+            eprintln!("Failed to load program: {}", e);
+            return;
+        },
+    };
+
+    println!(
+        "Running functional simulator twice in lockstep, checking every {} instructions...",
+        interval
+    );
+    let report = LockstepChecker::new(interval).run(&mut first, &mut second);
+    if let Some(output) = first.captured_output.as_ref() {
+        print!("{}", String::from_utf8_lossy(output));
+    }
+    if let Some(reason) = first.termination_reason {
+        println!("Termination reason: {}", reason);
+    }
+    print!("{}", report.render());
+    if !report.is_deterministic() {
+        std::process::exit(1);
+    }
+}
+
+// Run the functional simulator with the given program
+fn run_functional_simulator(
+    image: &ProgramImage,
+    preload: &[(u32, u32)],
+    memory_size: usize,
+    control: RunControl,
+) {
+    if let Some(interval) = control.lockstep {
+        run_lockstep_check(image, preload, memory_size, &control, interval);
+        return;
+    }
+    // Refuse a memory size over the cap before allocating it
+    let projected = FunctionalSimulator::projected_memory_usage(memory_size);
+    if let Err(e) = control.limits.check_memory(&projected) {
+        eprintln!("Error: {}", e);
+        return;
+    }
+    let mut simulator = FunctionalSimulator::new(memory_size);
+    simulator.limits = control.limits;
+    if control.progress {
+        simulator.enable_progress(DEFAULT_PROGRESS_INTERVAL);
+    }
+
+    print_image(image);
+    if let Err(e) = simulator
+        .load_image(image)
+        .and_then(|()| simulator.preload_memory(preload))
+    {
+        eprintln!("Failed to load program: {}", e);
+        return;
+    }
+    if control.boot {
+        simulator.reset_to_boot_vector();
+    }
+    if let Some(seed) = control.layout_seed {
+        let layout = MemoryLayout::randomized(&simulator.memory, seed);
+        simulator.apply_layout(&layout);
+        println!("{}", layout.render());
     }
 
     println!(
         "Program loaded. PC: 0x{:08X}, SP: 0x{:08X}",
-        0,
+        simulator.pc(),
         simulator.registers.read(29)
-    ); // Using 0 as placeholder since pc is private
+    );
 
     // Verify memory values before running
     println!("\nVerifying memory values before execution:");
-    println!("Address 0x1000: {:?}", simulator.memory.read_word(0x1000));
-    println!("Address 0x1004: {:?}", simulator.memory.read_word(0x1004));
+    for address in [DEMO_DATA_ADDRESS, DEMO_DATA_ADDRESS + 4] {
+        println!(
+            "Address 0x{:04X}: {:?}",
+            address,
+            simulator.memory.read_word(address)
+        );
+    }
     println!(
         "First instruction at 0x0000: {:?}",
         simulator.memory.read_word(0)
@@ -274,11 +1394,182 @@ fn run_functional_simulator(
 
     println!("Running functional simulator...");
 
+    let disk = match control.disk.map(|config| simulator.attach_disk(config)) {
+        Some(Ok(disk)) => Some(disk),
+        Some(Err(e)) => {
+            eprintln!("Failed to open disk image: {}", e);
+            return;
+        },
+        None => None,
+    };
+
+    let framebuffer = control
+        .framebuffer
+        .map(|config| simulator.attach_framebuffer(config));
+    let network = match control
+        .network
+        .map(|config| simulator.attach_network(config))
+    {
+        Some(Ok(network)) => Some(network),
+        Some(Err(e)) => {
+            eprintln!("Failed to open network socket: {}", e);
+            return;
+        },
+        None => None,
+    };
+    if let Some(address) = network.as_ref().and_then(|network| network.local_addr()) {
+        println!("Network: UDP socket bound to {}", address);
+    }
+    if control.semihosting {
+        simulator.enable_semihosting(".");
+    }
+    if let Some(policy) = control.lenient_decode {
+        simulator.enable_lenient_decode(policy);
+    }
+    if let Some(count) = control.shadow_sets {
+        simulator.registers.enable_shadow_sets(count);
+    }
+    if control.taint_input || !control.taint_ranges.is_empty() {
+        simulator.enable_taint_tracking(control.taint_input);
+        for &(address, length) in &control.taint_ranges {
+            simulator.taint_memory(address, length);
+        }
+    }
+    if control.control_flow != ControlFlowChecks::default() {
+        simulator.enable_control_flow_checks(control.control_flow);
+    }
+    simulator.throttle = control.throttle.map(ThrottleClock::new);
+    if let Some(hz) = control.cpu_freq {
+        simulator.registers.hardware.clock_hz = hz;
+    }
+    if let Some(path) = control.commit_log.as_ref() {
+        match CommitLog::create(path) {
+            Ok(log) => simulator.commit_log = Some(log),
+            Err(e) => {
+                eprintln!("Failed to create commit log {}: {}", path.display(), e);
+                return;
+            },
+        }
+    }
+    if let Some(interval) = control.checkpoint_every {
+        simulator.enable_checkpoints(interval as usize);
+    }
+    if control.crash_dump.is_some() {
+        simulator.enable_crash_dumps(control.crash_depth);
+    }
+    if control.flamegraph.is_some() {
+        simulator.enable_stack_profile(control.flamegraph_every);
+    }
+
     // Run the functional simulator
-    simulator.run();
+    let console = control
+        .console
+        .map(|config| ConsoleSession::start(simulator.attach_console(config)));
+    if let Err(error) = simulator.run() {
+        println!("Simulation error: {}", error);
+    }
+    if let Some(console) = console {
+        console.finish();
+    }
 
     // Display final state
     println!("\nSimulation completed.");
+    if let Some(reason) = simulator.termination_reason {
+        println!("Termination reason: {}", reason);
+    }
+    print_memory_usage(simulator.memory_usage(), &control.limits);
+    if let Some(report) = simulator.lenient_decode.as_ref() {
+        print!("{}", report.render());
+    }
+    if simulator.registers.shadow.is_enabled() {
+        println!("{}", simulator.registers.shadow.render());
+    }
+    if let Some(taint) = simulator.taint.as_ref() {
+        print!("{}", taint.render());
+    }
+    if let Some(guard) = simulator.control_flow.as_ref() {
+        print!("{}", guard.render());
+    }
+    if let Some(clock) = simulator.throttle.as_mut() {
+        clock.advance_to(simulator.step_count as u64);
+        print!("{}", clock.render());
+    }
+    if let Some(log) = simulator.checkpoints.as_ref() {
+        print!("{}", log.render());
+    }
+    write_crash_dump(simulator.crash_dump(), control.crash_dump.as_ref());
+    if let (Some(profile), Some(path)) = (
+        simulator.stack_profile.as_ref(),
+        control.flamegraph.as_ref(),
+    ) {
+        match std::fs::write(path, profile.collapsed(&image.symbols)) {
+            Ok(()) => println!(
+                "Flamegraph: {} samples in {} call stacks written to {}",
+                profile.samples(),
+                profile.stacks(),
+                path.display()
+            ),
+            Err(e) => eprintln!("Failed to write flamegraph {}: {}", path.display(), e),
+        }
+    }
+    if let (Some(log), Some(path)) = (simulator.commit_log.as_mut(), control.commit_log.as_ref()) {
+        match log.finish() {
+            Ok(()) => println!(
+                "Commit log: {} instructions written to {}",
+                log.commits,
+                path.display()
+            ),
+            Err(e) => eprintln!("Failed to write commit log {}: {}", path.display(), e),
+        }
+    }
+    if let Some(disk) = disk {
+        let statistics = disk.statistics();
+        println!(
+            "Disk: {} commands, {} sectors read, {} sectors written, {} DMA transfers, {} errors",
+            statistics.commands,
+            statistics.sectors_read,
+            statistics.sectors_written,
+            statistics.dma_transfers,
+            statistics.errors
+        );
+    }
+    if let Some(semihosting) = simulator.semihosting.as_ref() {
+        for score in &semihosting.scores {
+            println!(
+                "Benchmark score: {} = {} (after {} instructions)",
+                score.name, score.value, score.instructions
+            );
+        }
+    }
+    if let Some(network) = network {
+        let statistics = network.statistics();
+        println!(
+            "Network: {} packets sent ({} bytes), {} packets received ({} bytes), {} dropped",
+            statistics.packets_sent,
+            statistics.bytes_sent,
+            statistics.packets_received,
+            statistics.bytes_received,
+            statistics.dropped
+        );
+    }
+    if let Some(framebuffer) = framebuffer {
+        println!("Framebuffer: {} frames presented", framebuffer.frames());
+        if let Some(error) = framebuffer.dump_error() {
+            eprintln!("Failed to write frame {}", error);
+        }
+        if let Some(path) = framebuffer.config.dump.as_ref() {
+            match framebuffer.save(path) {
+                Ok(()) => println!("Final frame written to {}", path.display()),
+                Err(e) => eprintln!("Failed to write {}: {}", path.display(), e),
+            }
+        }
+    }
+    if let Some(path) = control.snapshot.as_ref() {
+        match std::fs::write(path, simulator.snapshot().to_json()) {
+            Ok(()) => println!("Snapshot written to {}", path.display()),
+            Err(e) => eprintln!("Failed to write snapshot to {}: {}", path.display(), e),
+        }
+    }
     println!("Final register values:");
     for i in 0..8 {
         print!("${}: {}\t", i, simulator.registers.read(i));
@@ -293,33 +1584,84 @@ fn run_functional_simulator(
 
 // Run the timing simulator with the given program and options
 fn run_timing_simulator_with_options(
-    program: &[u8],
+    image: &ProgramImage,
+    preload: &[(u32, u32)],
     memory_size: usize,
     visualize: bool,
-    max_cycles: usize,
-    entry_point: Option<u32>,
-    input_file: Option<&PathBuf>,
-    is_elf: bool,
+    control: RunControl,
 ) {
     // Create pipeline configuration with builder pattern
     let pipeline_config = PipelineConfig::new(5)
         .with_latencies(vec![1, 1, 1, 1, 1])
         .with_forwarding(true)
         .with_branch_prediction(true, BranchPredictorType::TwoBit)
-        .with_superscalar(1);
+        .with_superscalar(1)
+        .with_clock_hz(control.cpu_freq.unwrap_or(DEFAULT_CLOCK_HZ));
 
     // Fix the CacheConfig initializations
     let instr_cache_config = CacheConfig::new(32768, 4, 64);
 
     let data_cache_config = CacheConfig::new(32768, 4, 64);
 
+    // Refuse a configuration over the memory cap before allocating it
+    let projected = TimingSimulator::projected_memory_usage(
+        &pipeline_config,
+        &instr_cache_config,
+        &data_cache_config,
+        memory_size,
+    );
+    if let Err(e) = control.limits.check_memory(&projected) {
+        eprintln!("Error: {}", e);
+        return;
+    }
+
+    // Train the predictor on the warmup program with the same configuration
+    let warmed_up = match control.predictor.warmup_program.as_ref() {
+        Some(path) => match warm_up_predictor(
+            path,
+            &pipeline_config,
+            &instr_cache_config,
+            &data_cache_config,
+            memory_size,
+            control.limits,
+        ) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return;
+            },
+        },
+        None => None,
+    };
+
     // Create and initialize the timing simulator
     let mut simulator = TimingSimulator::new(
         pipeline_config,
         instr_cache_config,
-        data_cache_config,
+        data_cache_config.clone(),
         memory_size,
     );
+    let loaded = match (warmed_up, control.predictor.load.as_ref()) {
+        (Some(state), _) => simulator.load_predictor_state(&state),
+        (None, Some(path)) => std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+            .and_then(|json| PredictorState::from_json(&json))
+            .and_then(|state| simulator.load_predictor_state(&state)),
+        (None, None) => Ok(()),
+    };
+    if let Err(e) = loaded {
+        eprintln!("Error: {}", e);
+        return;
+    }
+    let limits = control.limits;
+    simulator.limits = limits;
+    if control.progress {
+        simulator.enable_progress(DEFAULT_PROGRESS_INTERVAL);
+    }
+    simulator.set_stats_interval(control.stats_interval);
+    if control.lenient_decode.is_some() {
+        simulator.enable_lenient_decode();
+    }
 
     // Enable visualization based on CLI flag
     simulator.enable_visualization(visualize);
@@ -333,290 +1675,888 @@ fn run_timing_simulator_with_options(
         simulator.set_visualization_format(OutputFormat::Text);
     }
 
-    // Handle ELF loading or regular program loading
-    if is_elf {
-        if let Some(file_path) = input_file {
-            // Load ELF file directly into memory
-            match ElfLoader::load_file(file_path) {
-                Ok(elf_loader) => {
-                    if let Err(e) = elf_loader.load_into_memory(&mut simulator.memory) {
-                        eprintln!("Failed to load ELF into memory: {:?}", e);
-                        return;
-                    }
-                    println!("ELF binary loaded successfully");
-                    if let Some(entry) = entry_point {
-                        simulator.pc = entry;
-                        println!("Entry point set to: 0x{:08X}", entry);
-                    }
-                },
-                Err(e) => {
-                    eprintln!("Failed to load ELF file: {:?}", e);
-                    return;
-                },
-            }
+    print_image(image);
+    if let Err(e) = simulator
+        .load_image(image)
+        .and_then(|()| simulator.preload_memory(preload))
+    {
+        eprintln!("Failed to load program: {}", e);
+        return;
+    }
+    if control.boot {
+        simulator.reset_to_boot_vector();
+    }
+
+    // Verify memory values
+    println!("\nVerifying memory values before execution:");
+    println!(
+        "Address 0x{:04X}: {:?}",
+        DEMO_DATA_ADDRESS,
+        simulator.memory.read_word(DEMO_DATA_ADDRESS)
+    );
+    println!(
+        "Address 0x{:08X}: {:?}",
+        simulator.pc,
+        simulator.memory.read_word(simulator.pc as usize)
+    );
+
+    simulator.throttle = control.throttle.map(ThrottleClock::new);
+    simulator.isa = control.isa;
+    if control.crash_dump.is_some() {
+        simulator.enable_crash_dumps(control.crash_depth);
+    }
+    if control.pipeline_history.is_some() {
+        simulator.enable_pipeline_history();
+    }
+    if control.trace.is_some() {
+        simulator.enable_sampled_timeline(control.trace_sampling);
+    }
+    if control.hot_loops.is_some() || control.unroll.is_some() {
+        simulator.enable_memory_trace();
+    }
+    if let Some(path) = control.event_log.as_ref() {
+        // An event log that was asked for but cannot be written fails
+        // the run rather than silently recording nothing
+        if let Err(e) = simulator.enable_event_log_file(path) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
         }
-    } else {
-        // Initialize memory with test data
-        load_test_data(&mut simulator.memory);
-
-        // Load the program correctly
-        println!("Loading program of size {} bytes", program.len());
-
-        // Print program instructions for debugging
-        println!("Program instructions:");
-        for i in (0..program.len()).step_by(4) {
-            if i + 3 < program.len() {
-                let instruction = u32::from_le_bytes([
-                    program[i],
-                    program[i + 1],
-                    program[i + 2],
-                    program[i + 3],
-                ]);
-                println!("  0x{:04X}: 0x{:08X}", i, instruction);
+    }
+    println!("Running timing simulator...");
+    // load_image has set the PC to the entry point of the program
+    println!("Starting execution at PC: 0x{:08X}", simulator.pc);
+    simulator.run();
+    if let (Some(path), Some(events)) = (control.event_log.as_ref(), simulator.events.as_mut()) {
+        match events.flush() {
+            Ok(()) => println!(
+                "\nEvent log of {} events written to {}",
+                events.len(),
+                path.display()
+            ),
+            Err(e) => eprintln!("Failed to write event log to {}: {}", path.display(), e),
+        }
+    }
+    if let (Some(path), Some(history)) = (
+        control.pipeline_history.as_ref(),
+        simulator.pipeline_history.as_ref(),
+    ) {
+        let written = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => std::fs::write(path, history.to_json()),
+            Some("csv") => std::fs::write(path, history.to_csv()),
+            _ => std::fs::File::create(path).and_then(|mut file| history.write_to(&mut file)),
+        };
+        match written {
+            Ok(()) => println!(
+                "\nPipeline history of {} instructions over {} cycles written to {}",
+                history.entries.len(),
+                history.cycles(),
+                path.display()
+            ),
+            Err(e) => eprintln!(
+                "Failed to write pipeline history to {}: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    if let (Some(path), Some(timeline)) = (control.trace.as_ref(), simulator.timeline.as_ref()) {
+        let sampling = control.trace_sampling;
+        let faulted = simulator
+            .termination_reason
+            .map_or(false, TerminationReason::is_fault);
+        let trace = InstructionTrace::sampled(timeline.clone(), sampling);
+        if sampling.last.is_some() && !faulted {
+            println!(
+                "\nNo fault; the last {} traced instructions were not written",
+                trace.instructions.len()
+            );
+        } else {
+            match std::fs::write(path, trace.to_json()) {
+                Ok(()) if sampling.is_sampled() => println!(
+                    "\nTrace of {} instructions ({}) written to {}",
+                    trace.instructions.len(),
+                    sampling,
+                    path.display()
+                ),
+                Ok(()) => println!(
+                    "\nTrace of {} instructions written to {}",
+                    trace.instructions.len(),
+                    path.display()
+                ),
+                Err(e) => eprintln!("Failed to write trace to {}: {}", path.display(), e),
             }
         }
+    }
+    if let (Some(count), Some(trace)) = (control.hot_loops, simulator.memory_trace.as_ref()) {
+        let report = LoopMemoryReport::new(trace, &data_cache_config, count);
+        print!("\n{}", report.render());
+    }
+    print_region_of_interest(&simulator);
+    if control.cpi_stack {
+        // A region of interest narrows the stack to the marked code
+        let counters = simulator.regions.counters().unwrap_or(simulator.run_totals);
+        print!("\n{}", CpiStack::new(&counters).render());
+    }
+    if let (Some(factor), Some(trace), ExecutionMode::InOrder(pipeline)) = (
+        control.unroll,
+        simulator.memory_trace.as_ref(),
+        &simulator.execution_mode,
+    ) {
+        let report = UnrollReport::new(trace, pipeline, factor, UNROLL_REPORT_LOOPS);
+        print!("\n{}", report.render());
+    }
+    if let Some(clock) = simulator.throttle.as_mut() {
+        clock.advance_to(simulator.run_totals.cycles as u64);
+        print!("\n{}", clock.render());
+    }
+    if control.predictor.any() {
+        println!(
+            "\nBranch mispredictions: {} in {} cycles",
+            simulator.run_totals.branch_mispredictions, simulator.run_totals.cycles
+        );
+    }
+    if let (Some(path), Some(state)) =
+        (control.predictor.save.as_ref(), simulator.predictor_state())
+    {
+        match std::fs::write(path, state.to_json()) {
+            Ok(()) => println!(
+                "\nBranch predictor with {} trained branches written to {}",
+                state.local.len(),
+                path.display()
+            ),
+            Err(e) => eprintln!("Failed to write {}: {}", path.display(), e),
+        }
+    }
+    print_memory_usage(simulator.memory_usage(), &limits);
+    if let Some(report) = simulator.lenient_decode.as_ref() {
+        print!("{}", report.render());
+    }
+    write_crash_dump(simulator.crash_dump(), control.crash_dump.as_ref());
 
-        // Copy the program bytes to the beginning of memory using init method
-        for i in (0..program.len()).step_by(4) {
-            if i + 3 < program.len() {
-                let instruction = u32::from_le_bytes([
-                    program[i],
-                    program[i + 1],
-                    program[i + 2],
-                    program[i + 3],
-                ]);
-                simulator.memory.write_word_init(i, instruction);
-            }
+    println!("\nFinal register values:");
+    for i in 0..8 {
+        print!("${}: {}\t", i, simulator.registers.read(i));
+        if i % 4 == 3 {
+            println!();
         }
     }
 
-    // Verify memory values
-    println!("\nVerifying memory values before execution:");
-    println!("Address 0x1000: {:?}", simulator.memory.read_word(0x1000));
-    println!("Address 0x0000: {:?}", simulator.memory.read_word(0));
+    // Display memory contents
+    display_memory_values(&simulator.memory);
 
-    println!("Running timing simulator...");
+    write_stats_json(&simulator, control.stats_json.as_ref());
+}
+
+// Report the host memory a run held and whether it went past the cap
+fn print_memory_usage(usage: MemoryUsage, limits: &RunLimits) {
+    match limits.check_memory(&usage) {
+        Ok(()) => println!("Host memory: {}", usage),
+        Err(e) => println!("Memory limit: {}", e),
+    }
+}
+
+// Report the statistics between the program's region markers, if it has any
+fn print_region_of_interest(simulator: &TimingSimulator) {
+    if let Some(region) = simulator.regions.sample() {
+        print!("\n{}", region.render());
+    }
+    let named = simulator.regions.named_samples();
+    if !named.is_empty() {
+        print!("\n{}", render_regions(&named));
+    }
+}
+
+// Write the statistics report of a timing run if a path was given
+fn write_stats_json(simulator: &TimingSimulator, path: Option<&PathBuf>) {
+    if let Some(path) = path {
+        match std::fs::write(path, simulator.stats_report().to_json()) {
+            Ok(()) => println!("\nStatistics written to {}", path.display()),
+            Err(e) => eprintln!("Failed to write statistics to {}: {}", path.display(), e),
+        }
+    }
+}
+
+// Print the summary of a crash dump and write it out if the run faulted
+fn write_crash_dump(dump: Option<CrashDump>, path: Option<&PathBuf>) {
+    if let (Some(dump), Some(path)) = (dump, path) {
+        println!("\n{}", dump.summary());
+        match dump.write_to(path) {
+            Ok(()) => println!("Crash dump written to {}", path.display()),
+            Err(e) => eprintln!("Failed to write crash dump {}: {}", path.display(), e),
+        }
+    }
+}
+
+// Run a program on a fresh timing simulator and return the branch
+// predictor it trained
+fn warm_up_predictor(
+    path: &Path,
+    pipeline_config: &PipelineConfig,
+    instr_cache_config: &CacheConfig,
+    data_cache_config: &CacheConfig,
+    memory_size: usize,
+    limits: RunLimits,
+) -> Result<PredictorState, String> {
+    let image = ProgramImage::from_file(path)
+        .map_err(|e| format!("Failed to load warmup program: {}", e))?;
+    let mut simulator = TimingSimulator::new(
+        pipeline_config.clone(),
+        instr_cache_config.clone(),
+        data_cache_config.clone(),
+        fit_memory_size(&image, memory_size),
+    );
+    simulator.visualization = None;
+    simulator.limits = limits;
+    simulator
+        .load_image(&image)
+        .map_err(|e| format!("Failed to load warmup program: {}", e))?;
+    println!("Warming up the branch predictor on {}", path.display());
+    simulator.run();
+    let state = simulator
+        .predictor_state()
+        .ok_or_else(|| "the warmup run has no branch predictor".to_string())?;
+    println!(
+        "Warmup ran {} instructions with {} branch mispredictions and trained {} branches\n",
+        simulator.run_totals.instructions,
+        simulator.run_totals.branch_mispredictions,
+        state.local.len()
+    );
+    Ok(state)
+}
+
+// Convert a pipeline history file to a Konata log
+fn convert_to_konata(input: &Path, output: Option<&PathBuf>) -> Result<(), String> {
+    let history = std::fs::File::open(input)
+        .and_then(|mut file| PipelineHistory::read_from(&mut file))
+        .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+    let log = history.to_konata();
+    match output {
+        Some(path) => {
+            std::fs::write(path, log)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            println!(
+                "Wrote {} instructions over {} cycles to {}",
+                history.entries.len(),
+                history.cycles(),
+                path.display()
+            );
+        },
+        None => print!("{}", log),
+    }
+    Ok(())
+}
+
+// List or total the events of an event log that match a query
+fn query_event_log(input: &Path, query: &EventQuery, summary: bool) -> Result<String, String> {
+    let log =
+        EventLog::load(input).map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+    let events = log.query(query);
+    Ok(if summary {
+        EventSummary::new(events).render()
+    } else {
+        render_events(events)
+    })
+}
+
+// Analyze a program statically. Assembly source keeps its labels; other
+// formats are analyzed from the segment holding the entry point.
+fn analyze_program(input: &Path) -> Result<HazardAnalysis, String> {
+    let image =
+        ProgramImage::from_file(input).map_err(|e| format!("Failed to load program: {}", e))?;
+    if image.format == ProgramFormat::Source {
+        let source = std::fs::read_to_string(input)
+            .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+        let program = Assembler::new()
+            .assemble_program(&source)
+            .map_err(|e| format!("Failed to assemble {}: {}", input.display(), e))?;
+        return Ok(program.analyze());
+    }
+
+    let entry = image.entry_point;
+    let segment = image
+        .segments
+        .iter()
+        .find(|segment| {
+            entry >= segment.address && ((entry - segment.address) as usize) < segment.bytes.len()
+        })
+        .ok_or_else(|| format!("No code at the entry point 0x{:08X}", entry))?;
+    let words: Vec<u32> = segment
+        .bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    Ok(HazardAnalysis::new(
+        &words,
+        segment.address,
+        &Default::default(),
+    ))
+}
+
+// Explore a function symbolically. Assembly source can name the function by
+// label; other formats take an address.
+fn explore_symbolically(
+    input: &Path,
+    function: Option<&str>,
+    executor: &SymbolicExecutor,
+) -> Result<SymbolicReport, String> {
+    let image =
+        ProgramImage::from_file(input).map_err(|e| format!("Failed to load program: {}", e))?;
+    let entry = match function {
+        None => image.entry_point,
+        Some(function) => match parse_number(function) {
+            Ok(address) => address,
+            Err(e) if image.symbols.is_empty() => return Err(e),
+            Err(_) => *image
+                .symbols
+                .get(function)
+                .ok_or_else(|| format!("No label named {}", function))?,
+        },
+    };
+
+    let mut simulator = FunctionalSimulator::new(image.memory_required().next_power_of_two());
+    simulator
+        .load_image(&image)
+        .map_err(|e| format!("Failed to load program: {}", e))?;
+    Ok(executor.explore(&simulator.memory, entry))
+}
+
+// Serve one testbench connection with the program as the golden model
+fn run_cosim(input: &Path, address: &str) -> Result<CosimReport, String> {
+    let image =
+        ProgramImage::from_file(input).map_err(|e| format!("Failed to load program: {}", e))?;
+    let mut simulator = FunctionalSimulator::new(image.memory_required().next_power_of_two());
+    simulator
+        .load_image(&image)
+        .map_err(|e| format!("Failed to load program: {}", e))?;
+    let mut bridge = CosimBridge::new(simulator);
+
+    let listener = TcpListener::bind(address)
+        .map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    match listener.local_addr() {
+        Ok(local) => println!("Waiting for the testbench on {}", local),
+        Err(_) => println!("Waiting for the testbench on {}", address),
+    }
+    let (stream, peer) = listener
+        .accept()
+        .map_err(|e| format!("Failed to accept the testbench: {}", e))?;
+    println!("Testbench connected from {}", peer);
+    if let Err(e) = bridge.serve(stream) {
+        eprintln!("Testbench connection closed: {}", e);
+    }
+    Ok(bridge.report().clone())
+}
+
+// Run the sampled simulation mode: functional fast-forward with timing samples
+fn run_sampled_simulation(
+    image: &ProgramImage,
+    preload: &[(u32, u32)],
+    memory_size: usize,
+    control: RunControl,
+    sampling: SamplingConfig,
+) {
+    let projected = FunctionalSimulator::projected_memory_usage(memory_size);
+    if let Err(e) = control.limits.check_memory(&projected) {
+        eprintln!("Error: {}", e);
+        return;
+    }
+    let mut functional = FunctionalSimulator::new(memory_size);
+    functional.limits = control.limits;
+    if control.progress {
+        functional.enable_progress(DEFAULT_PROGRESS_INTERVAL);
+    }
+
+    if let Err(e) = functional
+        .load_image(image)
+        .and_then(|()| functional.preload_memory(preload))
+    {
+        eprintln!("Failed to load program: {}", e);
+        return;
+    }
+
+    let pipeline_config = PipelineConfig::new(5)
+        .with_latencies(vec![1, 1, 1, 1, 1])
+        .with_forwarding(true)
+        .with_branch_prediction(true, BranchPredictorType::TwoBit)
+        .with_superscalar(1);
+
+    let mut simulator = SampledSimulator::new(
+        functional,
+        pipeline_config,
+        CacheConfig::new(32768, 4, 64),
+        CacheConfig::new(32768, 4, 64),
+        sampling,
+    );
+
+    println!(
+        "Running sampled simulation (fast-forward {}, warmup {}, measure {})...",
+        sampling.fast_forward, sampling.warmup, sampling.measure
+    );
+    let result = simulator.run();
+    println!("\n{}", result.print_summary());
+}
 
-    // Use the provided max_cycles parameter
-    let mut cycle_count = 0;
+// Run the latency probe and print the per-class results
+fn run_latency_probe(chain_length: usize, out_of_order: bool, forwarding: bool) {
+    let mut pipeline_config = PipelineConfig::new(5)
+        .with_latencies(vec![1, 1, 1, 1, 1])
+        .with_forwarding(forwarding)
+        .with_branch_prediction(true, BranchPredictorType::TwoBit)
+        .with_superscalar(1);
+    if out_of_order {
+        pipeline_config = pipeline_config.with_tomasulo(true, TomasuloConfig::new());
+    }
 
-    // Start execution at PC = 0
-    simulator.pc = 0;
+    let probe = LatencyProbe::new(pipeline_config, chain_length);
+    let report = probe.run();
+    println!("\n{}", report);
+}
 
-    println!("Starting execution at PC: 0x{:08X}", simulator.pc);
+// Place a program in a freshly built timing simulator
+fn load_timing_program(
+    simulator: &mut TimingSimulator,
+    image: &ProgramImage,
+    preload: &[(u32, u32)],
+    max_cycles: Option<usize>,
+) {
+    if let Some(max_cycles) = max_cycles {
+        simulator.set_max_cycles(max_cycles);
+    }
+    if let Err(e) = simulator
+        .load_image(image)
+        .and_then(|()| simulator.preload_memory(preload))
+    {
+        eprintln!("Failed to load program: {}", e);
+    }
+}
 
-    // Manual execution loop
-    while cycle_count < max_cycles {
-        cycle_count += 1;
+// Run the program under two configurations and print the aligned diff
+fn run_comparison(
+    image: &ProgramImage,
+    preload: &[(u32, u32)],
+    baseline: &TimingVariant,
+    variant: &TimingVariant,
+    memory_size: usize,
+    max_cycles: Option<usize>,
+    all: bool,
+) {
+    let comparison = PipelineComparison::run(baseline, variant, memory_size, |simulator| {
+        load_timing_program(simulator, image, preload, max_cycles)
+    });
+    println!("\n{}", comparison.render(all));
+}
 
-        // Visualize the pipeline state if enabled
-        if visualize && (cycle_count <= 5 || cycle_count % 10 == 0) {
-            if let Some(visualization) = &simulator.visualization {
-                if let ExecutionMode::InOrder(ref pipeline) = simulator.execution_mode {
-                    println!(
-                        "{}",
-                        visualization.visualize_pipeline(pipeline, cycle_count)
-                    );
-                }
-            }
-        }
+const WHAT_IF_HELP: &str = "\
+Commands:
+  step [N]       Execute N instructions (default 1) and show their timing
+                 and the watches after each, changes marked with *
+  continue [N]   Execute up to N instructions (default 1000) without listing
+                 them, stopping early at a breakpoint
+  watch EXPR     Follow a value: $t0, $8, pc, hi, lo, mem[ADDRESS],
+                 predictor[ADDRESS] (a branch's counter) or history
+  unwatch N      Stop following watch N
+  watches        Show the watches and their values
+  break LOC [COND VALUE]
+                 Stop when LOC ($t0 or mem[ADDRESS]) is written; COND is
+                 == (written with VALUE), changes-to (changed to VALUE) or
+                 crosses (reaches or passes the signed
+                 threshold VALUE)
+  breaks         Show the breakpoints
+  delete N       Remove breakpoint N
+  who-wrote LOC  Show the instruction that last wrote a register ($t3) or
+                 memory word (0x10010004); needs --track-writes
+  snapshot       Save the current state as the replay point
+  set SPEC       Change settings, e.g. set forwarding=off,caches=off
+  rerun          Replay from the snapshot with the current settings and diff
+  config         Show the settings in use and those for the next replay
+  sections       Show where the ELF sections, such as .bss, start and end
+  status         Show instruction and cycle counts
+  regs           Show the general-purpose registers
+  dump FILE ADDRESS LENGTH
+                 Write LENGTH bytes of memory starting at ADDRESS to FILE
+  restore FILE ADDRESS
+                 Copy the contents of FILE into memory at ADDRESS (also: load)
+  help           Show this help
+  quit           Leave the session";
+
+// Interactive what-if session reading commands from stdin
+fn run_what_if(
+    image: &ProgramImage,
+    preload: &[(u32, u32)],
+    variant: TimingVariant,
+    memory_size: usize,
+    track_writes: bool,
+) {
+    use std::io::{BufRead, Write};
 
-        // Print current state every 10 cycles
-        if cycle_count % 10 == 0 || cycle_count < 5 {
-            println!("Cycle {}, PC: 0x{:08X}", cycle_count, simulator.pc);
+    let mut session = match WhatIfSession::new(variant, memory_size, |simulator| {
+        load_timing_program(simulator, image, preload, None);
+        if track_writes {
+            simulator.enable_last_writers();
         }
-
-        // Directly fetch instruction from memory
-        let instr_word = match simulator.memory.read_word(simulator.pc as usize) {
-            Some(word) => word,
-            None => {
-                println!("Memory read error at PC: 0x{:08X}", simulator.pc);
-                break;
-            },
+    }) {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        },
+    };
+
+    // Changed watches are also shown in reverse video unless NO_COLOR is set
+    let highlight = std::env::var_os("NO_COLOR").is_none();
+    println!("What-if session. Type 'help' for commands.");
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("what-if> ");
+        let _ = std::io::stdout().flush();
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
         };
-
-        // If we hit a NOP after executing a few instructions, terminate
-        if instr_word == 0 && cycle_count > 5 {
-            println!(
-                "Reached NOP instruction at PC: 0x{:08X}, terminating",
-                simulator.pc
-            );
-            break;
-        }
-
-        // Decode instruction
-        let instruction = decode_instruction(instr_word);
-
-        // Update pipeline stages for visualization
-        if let ExecutionMode::InOrder(ref mut pipeline) = simulator.execution_mode {
-            // Simulate pipeline stages by moving instructions through the pipeline
-            // Move instructions from right to left (WB -> MEM -> EX -> ID -> IF)
-            for i in (1..pipeline.stages.len()).rev() {
-                if let Some(prev_instr) = pipeline.stages[i - 1].instruction.clone() {
-                    pipeline.stages[i].instruction = Some(prev_instr);
-                    pipeline.stages[i].pc = pipeline.stages[i - 1].pc;
-                    pipeline.stages[i].status = PipelineStageStatus::Busy;
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => continue,
+        };
+        let argument = words.next();
+        let rest: Vec<&str> = words.collect();
+        let count = |default: usize| argument.and_then(|n| n.parse().ok()).unwrap_or(default);
+
+        match command {
+            "step" | "s" => {
+                if session.watches.is_empty() {
+                    for timing in session.step(count(1)) {
+                        println!("{}", WhatIfSession::format_timing(timing));
+                    }
+                } else {
+                    for (timing, values) in session.step_watched(count(1)) {
+                        println!("{}", WhatIfSession::format_timing(&timing));
+                        println!("        {}", render_watches(&values, highlight));
+                    }
                 }
-            }
-
-            // Add new instruction to fetch stage
-            pipeline.stages[0].instruction = Some(instruction.clone());
-            pipeline.stages[0].pc = simulator.pc;
-            pipeline.stages[0].status = PipelineStageStatus::Busy;
-        }
-
-        // Print the instruction being executed
-        if cycle_count < 20 {
-            println!(
-                "Executing 0x{:08X} at PC: 0x{:08X}",
-                instr_word, simulator.pc
-            );
-        }
-
-        // Execute instruction manually
-        match instruction {
-            Instruction::Add { rd, rs, rt } => {
-                let rs_value = simulator.registers.read(rs);
-                let rt_value = simulator.registers.read(rt);
-                let result = rs_value.wrapping_add(rt_value);
-                simulator.registers.write(rd, result);
-                println!("  ADD ${} = ${} + ${} = {}", rd, rs, rt, result);
-            },
-            Instruction::Lw { rt, base, offset } => {
-                let base_value = simulator.registers.read(base);
-                let address = base_value.wrapping_add(offset as u32);
-                match simulator.memory.read_word(address as usize) {
-                    Some(value) => {
-                        simulator.registers.write(rt, value);
-                        println!("  LW ${} = mem[{} + {}] = {}", rt, base, offset, value);
-                    },
-                    None => {
-                        println!("Memory read error at address 0x{:08X}", address);
-                        break;
-                    },
+                if let Some(reason) = session.termination_reason {
+                    println!("Program stopped: {}", reason);
                 }
             },
-            Instruction::Sw { rt, base, offset } => {
-                let base_value = simulator.registers.read(base);
-                let address = base_value.wrapping_add(offset as u32);
-                let value = simulator.registers.read(rt);
-                if simulator.memory.write_word(address as usize, value) {
-                    println!("  SW mem[{} + {}] = ${} = {}", base, offset, rt, value);
+            "continue" | "c" => {
+                let (executed, hit) = if session.breakpoints.is_empty() {
+                    (session.step(count(1000)).len(), None)
                 } else {
-                    println!("Memory write error at address 0x{:08X}", address);
-                    break;
+                    session.step_until_break(count(1000))
+                };
+                if let Some(hit) = hit {
+                    println!("{}", hit);
+                }
+                println!(
+                    "Executed {} instructions ({} total, {} cycles)",
+                    executed,
+                    session.instructions(),
+                    session.cycles()
+                );
+                if !session.watches.is_empty() {
+                    let values = session.watches.update(&session.simulator);
+                    println!("        {}", render_watches(&values, highlight));
+                }
+                if let Some(reason) = session.termination_reason {
+                    println!("Program stopped: {}", reason);
                 }
             },
-            Instruction::Mult { rs, rt } => {
-                let rs_value = simulator.registers.read(rs);
-                let rt_value = simulator.registers.read(rt);
-                let result = rs_value.wrapping_mul(rt_value);
-
-                // Ensure we have space for LO register
-                if simulator.registers.data.len() <= 32 {
-                    simulator.registers.data.resize(33, 0);
+            "watch" | "w" => {
+                let added = WatchExpression::parse(&rest_of_line(&line))
+                    .and_then(|expression| session.watches.add(expression));
+                match added {
+                    Ok(number) => {
+                        let expression = session.watches.expressions()[number - 1];
+                        println!(
+                            "Watch {}: {} = {}",
+                            number,
+                            expression,
+                            expression.evaluate(&session.simulator)
+                        );
+                        session.watches.update(&session.simulator);
+                    },
+                    Err(e) => println!("Invalid watch: {}", e),
                 }
-                simulator.registers.data[32] = result;
-                println!("  MULT LO = ${} * ${} = {}", rs, rt, result);
             },
-            Instruction::Mflo { rd } => {
-                let lo_value = if simulator.registers.data.len() > 32 {
-                    simulator.registers.data[32]
-                } else {
-                    0
-                };
-                simulator.registers.write(rd, lo_value);
-                println!("  MFLO ${} = LO = {}", rd, lo_value);
+            "unwatch" => match argument.and_then(|n| n.parse().ok()) {
+                Some(number) => match session.watches.remove(number) {
+                    Some(expression) => println!("Removed watch {}: {}", number, expression),
+                    None => println!("No watch {}", number),
+                },
+                None => println!("Usage: unwatch N"),
             },
-            Instruction::Beq { rs, rt, offset } => {
-                let rs_value = simulator.registers.read(rs);
-                let rt_value = simulator.registers.read(rt);
-                if rs_value == rt_value {
-                    let new_pc = simulator.pc.wrapping_add((offset as u32) << 2);
-                    println!("  BEQ ${} == ${}, jumping to 0x{:08X}", rs, rt, new_pc);
-                    simulator.pc = new_pc;
-                    continue; // Skip PC increment
-                } else {
-                    println!("  BEQ ${} != ${}, not taken", rs, rt);
+            "watches" => {
+                let values = session.watches.update(&session.simulator);
+                if values.is_empty() {
+                    println!("No watches");
+                }
+                for (index, watch) in values.iter().enumerate() {
+                    println!(
+                        "{:>3}: {}",
+                        index + 1,
+                        render_watches(std::slice::from_ref(watch), highlight)
+                    );
                 }
             },
-            Instruction::Bne { rs, rt, offset } => {
-                let rs_value = simulator.registers.read(rs);
-                let rt_value = simulator.registers.read(rt);
-                if rs_value != rt_value {
-                    let new_pc = simulator.pc.wrapping_add((offset as u32) << 2);
-                    println!("  BNE ${} != ${}, jumping to 0x{:08X}", rs, rt, new_pc);
-                    simulator.pc = new_pc;
-                    continue; // Skip PC increment
-                } else {
-                    println!("  BNE ${} == ${}, not taken", rs, rt);
+            "break" | "b" => {
+                let added = DataBreakpoint::parse(&rest_of_line(&line))
+                    .and_then(|breakpoint| session.breakpoints.add(breakpoint));
+                match added {
+                    Ok(number) => {
+                        let (_, breakpoint) = session.breakpoints.iter().last().unwrap();
+                        println!("Breakpoint {}: {}", number, breakpoint);
+                    },
+                    Err(e) => println!("Invalid breakpoint: {}", e),
+                }
+            },
+            "breaks" => {
+                if session.breakpoints.is_empty() {
+                    println!("No breakpoints");
+                }
+                for (number, breakpoint) in session.breakpoints.iter() {
+                    println!("{:>3}: {}", number, breakpoint);
+                }
+            },
+            "delete" => match argument.and_then(|n| n.parse().ok()) {
+                Some(number) => match session.breakpoints.remove(number) {
+                    Some(breakpoint) => println!("Removed breakpoint {}: {}", number, breakpoint),
+                    None => println!("No breakpoint {}", number),
+                },
+                None => println!("Usage: delete N"),
+            },
+            "who-wrote" => {
+                let location = match Location::parse(&rest_of_line(&line)) {
+                    Ok(location) => location,
+                    Err(e) => {
+                        println!("Invalid location: {}", e);
+                        continue;
+                    },
+                };
+                match &session.simulator.last_writers {
+                    Some(writers) => match writers.lookup(location) {
+                        Some(writer) => println!("{} was last written by {}", location, writer),
+                        None => println!("{} has not been written in this session", location),
+                    },
+                    None => {
+                        println!("Write tracking is off; start the session with --track-writes")
+                    },
                 }
             },
-            Instruction::J { target } => {
-                let new_pc = (simulator.pc & 0xF0000000) | (target << 2);
-                println!("  J jumping to 0x{:08X}", new_pc);
-                simulator.pc = new_pc;
-                continue; // Skip PC increment
+            "snapshot" => {
+                session.take_snapshot();
+                println!("Snapshot taken at instruction {}", session.instructions());
             },
-            Instruction::Jr { rs } => {
-                let new_pc = simulator.registers.read(rs);
-                println!("  JR ${} jumping to 0x{:08X}", rs, new_pc);
-                simulator.pc = new_pc;
-                continue; // Skip PC increment
+            "set" => match session.configure(&line.trim()[3..]) {
+                Ok(()) => println!("Next replay: {}", session.variant.describe()),
+                Err(e) => println!("Invalid setting: {}", e),
             },
-            Instruction::InvalidInstruction => {
+            "rerun" => {
                 println!(
-                    "Invalid instruction 0x{:08X} at PC: 0x{:08X}",
-                    instr_word, simulator.pc
+                    "Replaying {} instructions from the snapshot at instruction {}",
+                    session.instructions() - session.snapshot_instruction(),
+                    session.snapshot_instruction()
                 );
-                break;
+                let comparison = session.rerun();
+                println!("{}", comparison.render(false));
+            },
+            "config" => {
+                println!("In use:      {}", session.applied.describe());
+                println!("Next replay: {}", session.variant.describe());
+            },
+            "status" => println!(
+                "PC 0x{:08X}, {} instructions, {} cycles, snapshot at instruction {}",
+                session.simulator.pc,
+                session.instructions(),
+                session.cycles(),
+                session.snapshot_instruction()
+            ),
+            "regs" => {
+                for i in 0..32 {
+                    print!(
+                        "${:<2} = 0x{:08X}  ",
+                        i,
+                        session.simulator.registers.read(i)
+                    );
+                    if i % 4 == 3 {
+                        println!();
+                    }
+                }
             },
-            _ => {
-                // Instead of trying to print the Instruction which doesn't implement Debug
-                println!("  Unhandled instruction type at PC: 0x{:08X}", simulator.pc);
+            "sections" => {
+                if image.sections.is_empty() {
+                    println!("No section information; only ELF executables carry it");
+                }
+                for section in &image.sections {
+                    println!("  {}", section);
+                }
+            },
+            "dump" => match dump_memory(&session.simulator.memory, argument, &rest) {
+                Ok(message) => println!("{}", message),
+                Err(e) => println!("dump failed: {}", e),
+            },
+            "restore" | "load" => {
+                match restore_memory(&mut session.simulator.memory, argument, &rest) {
+                    Ok(message) => println!("{}", message),
+                    Err(e) => println!("{} failed: {}", command, e),
+                }
             },
+            "help" | "h" => println!("{}", WHAT_IF_HELP),
+            "quit" | "q" | "exit" => break,
+            _ => println!("Unknown command '{}'. Type 'help' for commands.", command),
         }
-
-        // Increment PC to next instruction
-        simulator.pc += 4;
     }
+}
 
-    if cycle_count >= max_cycles {
-        println!(
-            "Reached maximum cycle count ({}). Ending simulation.",
-            max_cycles
-        );
+// The text after a session command's first word
+fn rest_of_line(line: &str) -> String {
+    let line = line.trim();
+    match line.find(char::is_whitespace) {
+        Some(end) => line[end..].trim().to_string(),
+        None => String::new(),
     }
+}
 
-    println!(
-        "Simulation complete after {} cycles. Final PC: 0x{:08X}",
-        cycle_count, simulator.pc
-    );
+// `dump FILE ADDRESS LENGTH`: save a block of memory to a file
+fn dump_memory(memory: &Memory, file: Option<&str>, args: &[&str]) -> Result<String, String> {
+    let (file, address, length) = match (file, args) {
+        (Some(file), [address, length]) => (file, parse_number(address)?, parse_number(length)?),
+        _ => return Err("expected FILE ADDRESS LENGTH".to_string()),
+    };
+    let bytes = memory
+        .read_block(address as usize, length as usize)
+        .map_err(|e| e.to_string())?;
+    std::fs::write(file, &bytes).map_err(|e| format!("{}: {}", file, e))?;
+    Ok(format!(
+        "Wrote {} bytes from 0x{:08X} to {}",
+        bytes.len(),
+        address,
+        file
+    ))
+}
 
-    // Display final state
-    println!("\nSimulation completed.");
-    println!("Final register values:");
-    for i in 0..8 {
-        print!("${}: {}\t", i, simulator.registers.read(i));
-        if i % 4 == 3 {
-            println!();
-        }
-    }
+// `restore FILE ADDRESS`: copy a file into memory. Nothing is written if
+// any of it falls outside memory or in a read-only region.
+fn restore_memory(
+    memory: &mut Memory,
+    file: Option<&str>,
+    args: &[&str],
+) -> Result<String, String> {
+    let (file, address) = match (file, args) {
+        (Some(file), [address]) => (file, parse_number(address)?),
+        _ => return Err("expected FILE ADDRESS".to_string()),
+    };
+    let bytes = std::fs::read(file).map_err(|e| format!("{}: {}", file, e))?;
+    memory
+        .write_block(address as usize, &bytes)
+        .map_err(|e| e.to_string())?;
+    Ok(format!(
+        "Loaded {} bytes from {} at 0x{:08X}",
+        bytes.len(),
+        file,
+        address
+    ))
+}
 
-    // Display memory contents
-    display_memory_values(&simulator.memory);
+// Run the program once per cache configuration and write the CSV table
+fn run_cache_sweep(
+    image: &ProgramImage,
+    preload: &[(u32, u32)],
+    spec: SweepSpec,
+    memory_size: usize,
+    max_cycles: Option<usize>,
+    output: Option<&PathBuf>,
+) {
+    let pipeline_config = PipelineConfig::new(5)
+        .with_latencies(vec![1, 1, 1, 1, 1])
+        .with_forwarding(true)
+        .with_branch_prediction(true, BranchPredictorType::TwoBit)
+        .with_superscalar(1);
+
+    let sweep = CacheSweep::new(spec, pipeline_config, memory_size);
+    let points = sweep.spec.points().len();
+    println!("Sweeping {} cache configurations...", points);
+
+    let report = sweep.run(|simulator| load_timing_program(simulator, image, preload, max_cycles));
+
+    let csv = report.to_csv();
+    match output {
+        Some(path) => match std::fs::write(path, &csv) {
+            Ok(()) => println!("\nSweep results written to {}", path.display()),
+            Err(e) => eprintln!("Failed to write sweep results to {}: {}", path.display(), e),
+        },
+        None => print!("\n{}", csv),
+    }
 }
 
 fn main() {
     let cli = Cli::parse();
 
-    match cli.command {
+    if let Some(name) = cli.schema {
+        let schema = match Artifact::from_name(&name) {
+            Some(artifact) => artifact.schema(),
+            None => all_schemas(),
+        };
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&schema).unwrap_or_default()
+        );
+        return;
+    }
+    let command = match cli.command {
+        Some(command) => command,
+        None => {
+            eprintln!("Error: a subcommand is required unless --schema is given");
+            std::process::exit(2);
+        },
+    };
+
+    match command {
         Commands::Functional {
             input,
             memory_size,
             log_level,
             output,
             elf,
+            preload,
+            max_instructions,
+            max_cycles,
+            timeout_seconds,
+            max_memory,
+            quotas,
+            progress,
+            console,
+            console_size,
+            disk,
+            disk_latency,
+            framebuffer,
+            framebuffer_dump,
+            framebuffer_every,
+            network,
+            udp_bind,
+            udp_peer,
+            semihosting,
+            snapshot,
+            lenient_decode,
+            shadow_sets,
+            boot,
+            lockstep,
+            load_address,
+            entry,
+            start,
+            load,
+            randomize_layout,
+            taint,
+            taint_range,
+            shadow_stack,
+            stack_canary,
+            throttle,
+            cpu_freq,
+            commit_log,
+            checkpoint_every,
+            crash_dump,
+            crash_depth,
+            flamegraph,
+            flamegraph_every,
         } => {
             // Parse log level
             let parsed_log_level = match log_level.to_lowercase().as_str() {
@@ -636,15 +2576,88 @@ fn main() {
             let mut logger = Logger::new(log_file.as_deref(), parsed_log_level);
             logger.info("Starting VMIPS Rust with functional simulator");
 
+            let network_config = match (network.as_deref(), udp_peer) {
+                (Some("udp"), Some(peer)) => Some(NetworkConfig::udp(udp_bind, peer)),
+                (Some("udp"), None) => {
+                    eprintln!("Error: --network udp requires --udp-peer");
+                    return;
+                },
+                (Some(_), _) => Some(NetworkConfig::loopback()),
+                (None, _) => None,
+            };
+
             // Load program from file or create test program
-            match load_program(input.as_ref(), elf) {
-                Ok((program, entry_point)) => {
+            let loaded = load_programs(input.as_ref(), elf, &load).and_then(|image| {
+                Ok(place_program(
+                    image,
+                    boot,
+                    load_address,
+                    entry,
+                    start.as_deref(),
+                )?)
+            });
+            match loaded {
+                Ok(image) => {
                     run_functional_simulator(
-                        &program,
-                        memory_size,
-                        entry_point,
-                        input.as_ref(),
-                        elf,
+                        &image,
+                        &preload,
+                        fit_memory_size(&image, memory_size),
+                        RunControl {
+                            limits: RunLimits {
+                                quotas: quotas.quotas(),
+                                ..build_run_limits(
+                                    max_instructions,
+                                    max_cycles,
+                                    timeout_seconds,
+                                    max_memory,
+                                )
+                            },
+                            progress,
+                            stats_interval: None,
+                            stats_json: None,
+                            pipeline_history: None,
+                            trace: None,
+                            event_log: None,
+                            trace_sampling: TraceSampling::default(),
+                            hot_loops: None,
+                            cpi_stack: false,
+                            unroll: None,
+                            console: if console { Some(console_size) } else { None },
+                            disk: disk.map(|path| {
+                                DiskConfig::new(path).with_sector_latency(disk_latency)
+                            }),
+                            framebuffer: framebuffer.map(|config| match framebuffer_dump {
+                                Some(path) => config.with_dump(path, framebuffer_every),
+                                None => config,
+                            }),
+                            network: network_config,
+                            semihosting,
+                            snapshot,
+                            lenient_decode: lenient_decode
+                                .as_deref()
+                                .and_then(UnknownInstructionPolicy::from_name),
+                            shadow_sets,
+                            boot,
+                            lockstep: lockstep.map(|interval| interval as usize),
+                            layout_seed: randomize_layout
+                                .map(|seed| seed.unwrap_or_else(clock_seed)),
+                            taint_input: taint,
+                            taint_ranges: taint_range,
+                            control_flow: ControlFlowChecks {
+                                shadow_stack,
+                                canary: stack_canary,
+                            },
+                            throttle,
+                            cpu_freq,
+                            isa: IsaKind::Mips,
+                            commit_log,
+                            checkpoint_every,
+                            crash_dump,
+                            crash_depth,
+                            flamegraph,
+                            flamegraph_every,
+                            predictor: PredictorFiles::default(),
+                        },
                     );
                 },
                 Err(e) => {
@@ -665,6 +2678,41 @@ fn main() {
             visualize,
             max_cycles,
             elf,
+            preload,
+            max_instructions,
+            timeout_seconds,
+            max_memory,
+            progress,
+            stats_json,
+            stats_interval,
+            stats_interval_unit,
+            sampling,
+            sample_fast_forward,
+            sample_warmup,
+            sample_measure,
+            pipeline_history,
+            trace,
+            event_log,
+            trace_every,
+            trace_only,
+            trace_last,
+            hot_loops,
+            cpi_stack,
+            unroll,
+            lenient_decode,
+            throttle,
+            cpu_freq,
+            isa,
+            load_predictor,
+            save_predictor,
+            warmup_program,
+            boot,
+            load_address,
+            entry,
+            start,
+            load,
+            crash_dump,
+            crash_depth,
         } => {
             // Parse log level
             let parsed_log_level = match log_level.to_lowercase().as_str() {
@@ -685,17 +2733,90 @@ fn main() {
             logger.info("Starting VMIPS Rust with timing simulator");
 
             // Load program from file or create test program
-            match load_program(input.as_ref(), elf) {
-                Ok((program, entry_point)) => {
-                    run_timing_simulator_with_options(
-                        &program,
-                        memory_size,
-                        visualize,
-                        max_cycles,
-                        entry_point,
-                        input.as_ref(),
-                        elf,
-                    );
+            let loaded = load_programs(input.as_ref(), elf, &load).and_then(|image| {
+                Ok(place_program(
+                    image,
+                    boot,
+                    load_address,
+                    entry,
+                    start.as_deref(),
+                )?)
+            });
+            match loaded {
+                Ok(image) => {
+                    let control = RunControl {
+                        limits: build_run_limits(
+                            max_instructions,
+                            max_cycles,
+                            timeout_seconds,
+                            max_memory,
+                        ),
+                        progress,
+                        stats_interval: build_stats_interval(stats_interval, &stats_interval_unit),
+                        stats_json,
+                        pipeline_history,
+                        trace,
+                        event_log,
+                        trace_sampling: TraceSampling {
+                            every: trace_every.unwrap_or(1) as usize,
+                            filter: trace_only.unwrap_or(TraceFilter::All),
+                            last: trace_last.map(|last| last as usize),
+                        },
+                        hot_loops,
+                        cpi_stack,
+                        unroll: unroll.map(|factor| factor as usize),
+                        console: None,
+                        disk: None,
+                        framebuffer: None,
+                        network: None,
+                        semihosting: false,
+                        snapshot: None,
+                        lenient_decode: if lenient_decode {
+                            Some(UnknownInstructionPolicy::Nop)
+                        } else {
+                            None
+                        },
+                        shadow_sets: None,
+                        boot,
+                        lockstep: None,
+                        layout_seed: None,
+                        taint_input: false,
+                        taint_ranges: Vec::new(),
+                        control_flow: ControlFlowChecks::default(),
+                        throttle,
+                        cpu_freq,
+                        isa,
+                        commit_log: None,
+                        checkpoint_every: None,
+                        crash_dump,
+                        crash_depth,
+                        flamegraph: None,
+                        flamegraph_every: 1,
+                        predictor: PredictorFiles {
+                            load: load_predictor,
+                            save: save_predictor,
+                            warmup_program,
+                        },
+                    };
+                    if sampling {
+                        let config =
+                            SamplingConfig::new(sample_fast_forward, sample_warmup, sample_measure);
+                        run_sampled_simulation(
+                            &image,
+                            &preload,
+                            fit_memory_size(&image, memory_size),
+                            control,
+                            config,
+                        );
+                    } else {
+                        run_timing_simulator_with_options(
+                            &image,
+                            &preload,
+                            fit_memory_size(&image, memory_size),
+                            visualize,
+                            control,
+                        );
+                    }
                 },
                 Err(e) => {
                     eprintln!("Failed to load program: {}", e);
@@ -707,5 +2828,370 @@ fn main() {
                 println!("\nLog file created: {}", log_file);
             }
         },
+        Commands::LatencyProbe {
+            chain_length,
+            out_of_order,
+            no_forwarding,
+        } => {
+            run_latency_probe(chain_length, out_of_order, !no_forwarding);
+        },
+        Commands::CacheSweep {
+            input,
+            config,
+            output,
+            memory_size,
+            elf,
+            preload,
+            max_cycles,
+        } => {
+            let spec = match SweepSpec::from_file(&config) {
+                Ok(spec) => spec,
+                Err(e) => {
+                    eprintln!("Failed to read sweep config {}: {}", config.display(), e);
+                    return;
+                },
+            };
+
+            match load_program(input.as_ref(), elf) {
+                Ok(image) => run_cache_sweep(
+                    &image,
+                    &preload,
+                    spec,
+                    fit_memory_size(&image, memory_size),
+                    max_cycles,
+                    output.as_ref(),
+                ),
+                Err(e) => eprintln!("Failed to load program: {}", e),
+            }
+        },
+        Commands::WhatIf {
+            input,
+            config,
+            memory_size,
+            elf,
+            preload,
+            track_writes,
+        } => {
+            let variant = match TimingVariant::parse(&config) {
+                Ok(variant) => variant,
+                Err(e) => {
+                    eprintln!("Invalid configuration: {}", e);
+                    return;
+                },
+            };
+            match load_program(input.as_ref(), elf) {
+                Ok(image) => run_what_if(
+                    &image,
+                    &preload,
+                    variant,
+                    fit_memory_size(&image, memory_size),
+                    track_writes,
+                ),
+                Err(e) => eprintln!("Failed to load program: {}", e),
+            }
+        },
+        Commands::Compare {
+            input,
+            baseline,
+            variant,
+            all,
+            memory_size,
+            elf,
+            preload,
+            max_cycles,
+        } => {
+            let variants = TimingVariant::parse(&baseline)
+                .and_then(|baseline| Ok((baseline, TimingVariant::parse(&variant)?)));
+            let (baseline, variant) = match variants {
+                Ok(variants) => variants,
+                Err(e) => {
+                    eprintln!("Invalid configuration: {}", e);
+                    return;
+                },
+            };
+
+            match load_program(input.as_ref(), elf) {
+                Ok(image) => run_comparison(
+                    &image,
+                    &preload,
+                    &baseline,
+                    &variant,
+                    fit_memory_size(&image, memory_size),
+                    max_cycles,
+                    all,
+                ),
+                Err(e) => eprintln!("Failed to load program: {}", e),
+            }
+        },
+        Commands::Konata { input, output } => {
+            if let Err(e) = convert_to_konata(&input, output.as_ref()) {
+                eprintln!("{}", e);
+            }
+        },
+        Commands::Events {
+            input,
+            query,
+            summary,
+        } => match query_event_log(&input, &query, summary) {
+            Ok(report) => print!("{}", report),
+            Err(e) => eprintln!("{}", e),
+        },
+        Commands::Analyze { input, json } => match analyze_program(&input) {
+            Ok(analysis) if json => match serde_json::to_string_pretty(&analysis) {
+                Ok(text) => println!("{}", text),
+                Err(e) => eprintln!("Failed to write the analysis: {}", e),
+            },
+            Ok(analysis) => print!("{}", analysis.render()),
+            Err(e) => eprintln!("{}", e),
+        },
+        Commands::Symbolic {
+            input,
+            function,
+            args,
+            max_paths,
+            max_steps,
+        } => {
+            let mut executor = SymbolicExecutor::new(args as usize);
+            executor.max_paths = max_paths;
+            executor.max_steps = max_steps;
+            match explore_symbolically(&input, function.as_deref(), &executor) {
+                Ok(report) => {
+                    print!("{}", report.render());
+                    // Reachable assertions fail the run, for use in scripts
+                    if report.assertions().next().is_some() {
+                        std::process::exit(1);
+                    }
+                },
+                Err(e) => eprintln!("{}", e),
+            }
+        },
+        Commands::Serve { address } => {
+            let server = match Server::bind(address.as_str()) {
+                Ok(server) => server,
+                Err(e) => {
+                    eprintln!("Failed to listen on {}: {}", address, e);
+                    return;
+                },
+            };
+            match server.local_addr() {
+                Ok(local) => println!("Serving simulation sessions on http://{}", local),
+                Err(_) => println!("Serving simulation sessions on http://{}", address),
+            }
+            if let Err(e) = server.run() {
+                eprintln!("Server stopped: {}", e);
+            }
+        },
+        Commands::Grade {
+            input,
+            json,
+            max_instructions,
+            quotas,
+        } => {
+            let limits = RunLimits {
+                max_instructions,
+                quotas: quotas.quotas(),
+                ..RunLimits::default()
+            };
+            let report = match std::fs::read_to_string(&input) {
+                Ok(source) => grade_source(&source, limits).map_err(|e| e.to_string()),
+                Err(e) => Err(format!("Failed to read {}: {}", input.display(), e)),
+            };
+            match report {
+                Ok(report) => {
+                    print!("{}", report.render_tap());
+                    if let Some(path) = json {
+                        let written = serde_json::to_string_pretty(&report)
+                            .map_err(|e| e.to_string())
+                            .and_then(|text| {
+                                std::fs::write(&path, text + "\n").map_err(|e| e.to_string())
+                            });
+                        if let Err(e) = written {
+                            eprintln!("Failed to write {}: {}", path.display(), e);
+                        }
+                    }
+                    // Failed assertions fail the run, for use in grading scripts
+                    if !report.all_passed() {
+                        std::process::exit(1);
+                    }
+                },
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(2);
+                },
+            }
+        },
+        Commands::Bench {
+            workload,
+            n,
+            seed,
+            config,
+            source,
+        } => {
+            let workload = match Workload::new(workload, n, seed) {
+                Ok(workload) => workload,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(2);
+                },
+            };
+            if source {
+                print!("{}", workload.source());
+                return;
+            }
+            match TimingVariant::parse(&config).and_then(|variant| workload.run(&variant)) {
+                // A wrong result fails the run, so scripts never record it
+                Ok(report) => {
+                    print!("{}", report.render());
+                    if report.verified.is_err() {
+                        std::process::exit(1);
+                    }
+                },
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(2);
+                },
+            }
+        },
+        Commands::Stress {
+            seed,
+            length,
+            iterations,
+            chain,
+            branches,
+            memory,
+            pattern,
+            working_set,
+            config,
+            source,
+        } => {
+            let stress = StressConfig {
+                seed,
+                length,
+                iterations,
+                chain,
+                branch_density: branches,
+                memory_density: memory,
+                pattern,
+                working_set,
+            };
+            let program = match StressProgram::generate(stress) {
+                Ok(program) => program,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(2);
+                },
+            };
+            if source {
+                for (address, line) in program.program().disassemble() {
+                    println!("0x{:08X}: {}", address, line);
+                }
+                return;
+            }
+            match TimingVariant::parse(&config).and_then(|variant| program.run(&variant)) {
+                Ok(report) => {
+                    print!("{}", report.render());
+                    if report.verified.is_err() {
+                        std::process::exit(1);
+                    }
+                },
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(2);
+                },
+            }
+        },
+        Commands::CacheCheck {
+            cache,
+            replacement,
+            pattern,
+            working_set,
+            passes,
+            seed,
+        } => {
+            let checks = match cache {
+                Some(cache) => match parse_check(&cache, &replacement, pattern, working_set) {
+                    Ok(check) => vec![MissRateCheck {
+                        passes,
+                        seed,
+                        ..check
+                    }],
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(2);
+                    },
+                },
+                None => standard_checks(),
+            };
+            let mut failed = 0;
+            for check in &checks {
+                match check.run() {
+                    Ok(result) => {
+                        println!("{}", result);
+                        if !result.passed() {
+                            failed += 1;
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("Error: {}: {}", check, e);
+                        std::process::exit(2);
+                    },
+                }
+            }
+            println!(
+                "{} of {} checks passed",
+                checks.len() - failed,
+                checks.len()
+            );
+            if failed > 0 {
+                std::process::exit(1);
+            }
+        },
+        Commands::RunExample { name, source } => {
+            let name = match name {
+                Some(name) => name,
+                None => {
+                    print!("{}", render_example_list());
+                    return;
+                },
+            };
+            let example = match find_example(&name) {
+                Some(example) => example,
+                None => {
+                    eprintln!("Unknown example '{}'", name);
+                    eprint!("{}", render_example_list());
+                    std::process::exit(2);
+                },
+            };
+            if source {
+                print!("{}", example.source);
+                return;
+            }
+            match example.run() {
+                Ok((report, output)) => {
+                    println!("\n--- Output of {} ---\n{}", example.name, output);
+                    print!("{}", report.render_tap());
+                    if !report.all_passed() {
+                        std::process::exit(1);
+                    }
+                },
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(2);
+                },
+            }
+        },
+        Commands::Cosim { input, address } => match run_cosim(&input, &address) {
+            // Divergence fails the run, for use in regression scripts
+            Ok(report) => {
+                print!("{}", report.render());
+                if report.mismatches > 0 {
+                    std::process::exit(1);
+                }
+            },
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            },
+        },
     }
 }
@@ -23,17 +23,46 @@
 //
 // This file contains the main entry point for the MIPS assembler.
 // It provides a command-line interface for assembling MIPS assembly files,
-// running them in the functional or timing simulator, and starting an
-// interactive assembly session.
+// running them in the functional or timing simulator, optionally after the
+// optimization pass, and starting an interactive assembly session.
 
 use std::env;
 use std::fs::File;
 use std::io::{self, BufRead, Write};
-use vmips_rust::assembler::Assembler;
+use vmips_rust::assembler::{Assembler, Dialect, Optimizer};
 use vmips_rust::utils::logger::{LogLevel, Logger};
 
 fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let mut all_args: Vec<String> = env::args().collect();
+    let optimize = all_args.iter().any(|arg| arg == "--optimize");
+    let delay_slots = all_args.iter().any(|arg| arg == "--delay-slots");
+    let dialect = match all_args.iter().position(|arg| arg == "--dialect") {
+        Some(position) => {
+            let name = all_args.get(position + 1).cloned().unwrap_or_default();
+            all_args.drain(position..(position + 2).min(all_args.len()));
+            match Dialect::from_name(&name) {
+                Some(dialect) => dialect,
+                None => {
+                    println!(
+                        "Error: Unknown dialect '{}'; expected native, mars, spim or strict.",
+                        name
+                    );
+                    return Ok(());
+                },
+            }
+        },
+        None => Dialect::Native,
+    };
+    let args: Vec<String> = all_args
+        .into_iter()
+        .filter(|arg| arg != "--optimize" && arg != "--delay-slots")
+        .collect();
+    // Filling delay slots is part of the optimization pass
+    let optimizer = if optimize || delay_slots {
+        Some(Optimizer::new().with_delay_slots(delay_slots))
+    } else {
+        None
+    };
 
     // Create logger
     let log_file = "vmips_assembler.log";
@@ -60,10 +89,8 @@ fn main() -> io::Result<()> {
 
             println!("Assembling {} to {}", input_file, output_file);
 
-            let mut assembler = Assembler::new();
-            match assembler.assemble_file(input_file) {
-                Ok(binary_data) => {
-                    let binary: Vec<u8> = binary_data;
+            match assemble(input_file, optimizer, dialect) {
+                Ok(binary) => {
                     let mut file = File::create(output_file)?;
                     file.write_all(&binary)?;
                     println!("Assembly successful.");
@@ -87,6 +114,10 @@ fn main() -> io::Result<()> {
             } else {
                 "functional"
             };
+            if delay_slots && simulator_type != "timing" {
+                println!("Error: --delay-slots requires the timing simulator.");
+                return Ok(());
+            }
 
             println!(
                 "Assembling and running {} with {} simulator",
@@ -94,9 +125,8 @@ fn main() -> io::Result<()> {
             );
 
             // First assemble the file to memory
-            let mut assembler = Assembler::new();
-            let binary: Vec<u8> = match assembler.assemble_file(input_file) {
-                Ok(binary_data) => binary_data,
+            let binary = match assemble(input_file, optimizer, dialect) {
+                Ok(binary) => binary,
                 Err(err) => {
                     println!("Assembly error: {}", err);
                     return Ok(());
@@ -104,7 +134,7 @@ fn main() -> io::Result<()> {
             };
 
             // Then run the binary with the specified simulator
-            run_simulator(&binary, simulator_type);
+            run_simulator(&binary, simulator_type, delay_slots);
         },
         "interactive" | "i" => {
             run_interactive_mode();
@@ -128,19 +158,55 @@ fn print_usage(program_name: &str) {
     println!("  run, r <input.s> [simulator_type]     Assemble and run a MIPS assembly file");
     println!("  interactive, i                        Start an interactive MIPS assembly session");
     println!("  --help, -h                            Show this help message");
+    println!("Options:");
+    println!("  --optimize                            Remove redundant moves and schedule loads");
+    println!("  --delay-slots                         Also fill branch delay slots (timing only)");
+    println!(
+        "  --dialect <mars|spim|strict|native>   Accept another assembler's syntax and report"
+    );
+    println!("                                        what behaves differently [default: native]");
     println!("Simulator types:");
     println!("  functional                            Use the functional simulator (default)");
     println!("  timing                                Use the timing simulator");
 }
 
-fn run_simulator(binary: &[u8], simulator_type: &str) {
+// Assemble a file, running the optimization pass and printing its report
+// when an optimizer is given
+fn assemble(
+    input_file: &str,
+    optimizer: Option<Optimizer>,
+    dialect: Dialect,
+) -> Result<Vec<u8>, String> {
+    let mut assembler = Assembler::new().with_dialect(dialect);
+    let binary = match optimizer {
+        Some(optimizer) => assembler.assemble_program_file(input_file).map(|program| {
+            let (optimized, report) = optimizer.optimize(&program);
+            print!("{}", report.render());
+            optimized.to_bytes()
+        }),
+        None => assembler.assemble_file(input_file),
+    }
+    .map_err(|err| err.to_string())?;
+    for warning in assembler.warnings() {
+        println!("Warning: {}", warning);
+    }
+    Ok(binary)
+}
+
+// Large enough to hold the text segment at 0x00400000
+const RUN_MEMORY_SIZE: usize = 0x0050_0000;
+
+fn run_simulator(binary: &[u8], simulator_type: &str, delay_slots: bool) {
     use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
-    use vmips_rust::timing_simulator::config::{BranchPredictorType, CacheConfig, PipelineConfig};
+    use vmips_rust::loader::ProgramImage;
+    use vmips_rust::timing_simulator::config::{
+        BranchPredictorType, CacheConfig, ControlHazardScheme, PipelineConfig,
+    };
     use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
 
     match simulator_type {
         "functional" => {
-            let memory_size = 8192;
+            let memory_size = RUN_MEMORY_SIZE;
             let mut simulator = FunctionalSimulator::new(memory_size);
 
             // Load the binary into memory
@@ -148,7 +214,9 @@ fn run_simulator(binary: &[u8], simulator_type: &str) {
 
             // Run the simulator
             println!("Running functional simulator...");
-            simulator.run();
+            if let Err(error) = simulator.run() {
+                println!("Simulation error: {}", error);
+            }
 
             // Display results
             println!("\nSimulation completed.");
@@ -161,6 +229,11 @@ fn run_simulator(binary: &[u8], simulator_type: &str) {
                 .with_latencies(vec![1, 1, 1, 1, 1])
                 .with_forwarding(true)
                 .with_branch_prediction(true, BranchPredictorType::TwoBit);
+            let pipeline_config = if delay_slots {
+                pipeline_config.with_control_hazards(ControlHazardScheme::DelaySlot)
+            } else {
+                pipeline_config
+            };
 
             // Create cache configurations
             let l1_cache_config = CacheConfig::new(32768, 4, 64);
@@ -171,44 +244,23 @@ fn run_simulator(binary: &[u8], simulator_type: &str) {
                 pipeline_config,
                 l1_cache_config,
                 l2_cache_config,
-                8192, // memory size
+                RUN_MEMORY_SIZE,
             );
 
-            // Load the binary into memory
-            let program_bytes = binary;
-
-            // Skip the header (first 8 bytes) which contains data and text section sizes
-            let data_size = u32::from_le_bytes([
-                program_bytes[0],
-                program_bytes[1],
-                program_bytes[2],
-                program_bytes[3],
-            ]) as usize;
-
-            let text_size = u32::from_le_bytes([
-                program_bytes[4],
-                program_bytes[5],
-                program_bytes[6],
-                program_bytes[7],
-            ]) as usize;
-
-            // Load data section
-            for (i, &byte) in program_bytes[8..8 + data_size].iter().enumerate() {
-                simulator.memory.write_byte(i, byte);
-            }
-
-            // Load text section
-            for (i, chunk) in program_bytes[8 + data_size..8 + data_size + text_size]
-                .chunks_exact(4)
-                .enumerate()
-            {
-                let instr = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
-                simulator.memory.write_word(0x1000 + i * 4, instr);
+            // Place the data and text sections at their segments
+            match ProgramImage::from_bytes(binary) {
+                Ok(image) => {
+                    if let Err(e) = simulator.load_image(&image) {
+                        println!("Failed to load program: {}", e);
+                        return;
+                    }
+                },
+                Err(e) => {
+                    println!("Failed to load program: {}", e);
+                    return;
+                },
             }
 
-            // Set PC to start of text section
-            simulator.pc = 0x1000;
-
             // Run the simulator
             println!("Running timing simulator...");
             simulator.run();
@@ -254,7 +306,7 @@ fn run_interactive_mode() {
                 match assembler.assemble_string(&full_program) {
                     Ok(binary_data) => {
                         let binary: Vec<u8> = binary_data;
-                        run_simulator(&binary, "functional");
+                        run_simulator(&binary, "functional", false);
                         program.clear();
                     },
                     Err(err) => {
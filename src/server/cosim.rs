@@ -0,0 +1,389 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// cosim.rs
+//
+// This file contains the co-simulation bridge behind the `cosim`
+// subcommand. An RTL testbench, such as a Verilator model of a student's
+// CPU, connects over TCP and reports each instruction its core retires. The
+// bridge steps the functional simulator as the golden model and answers
+// whether the two agree, keeping the first divergence for the report. Every
+// request and reply is one line of text, so a testbench can speak the
+// protocol from C++ or a DPI function with nothing but a socket.
+
+use crate::functional_simulator::commit_log::{register_name, Commit, CommitLog, CommitLogFormat};
+use crate::functional_simulator::registers::Register;
+use crate::functional_simulator::simulator::Simulator;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::mem;
+use std::net::TcpStream;
+
+/// Address the `cosim` subcommand listens on unless another is given
+pub const DEFAULT_COSIM_ADDRESS: &str = "127.0.0.1:7447";
+
+/// An instruction the RTL core retired, as the testbench reports it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RetiredInstruction {
+    pub pc: u32,
+    /// Registers written and their new values
+    pub writes: Vec<(Register, u32)>,
+    /// Address and value of a store
+    pub store: Option<(u32, u32)>,
+}
+
+impl RetiredInstruction {
+    /// Parse the fields of a `commit` request: the PC, then a `NAME=VALUE`
+    /// for each register written and `mem=ADDRESS:VALUE` for a store.
+    /// Registers are named as in the commit log, `x0` to `x31`, `f0` to
+    /// `f31`, `hi`, `lo` and `c31_fcsr`.
+    pub fn parse(fields: &str) -> Result<Self, String> {
+        let mut fields = fields.split_whitespace();
+        let pc = match fields.next() {
+            Some(pc) => parse_value(pc)?,
+            None => return Err("commit needs a PC".to_string()),
+        };
+        let mut retired = Self {
+            pc,
+            ..Self::default()
+        };
+        for field in fields {
+            let (name, value) = field
+                .split_once('=')
+                .ok_or_else(|| format!("expected NAME=VALUE, found '{}'", field))?;
+            if name == "mem" {
+                let (address, value) = value
+                    .split_once(':')
+                    .ok_or_else(|| format!("expected mem=ADDRESS:VALUE, found '{}'", field))?;
+                retired.store = Some((parse_value(address)?, parse_value(value)?));
+            } else {
+                retired
+                    .writes
+                    .push((parse_register(name)?, parse_value(value)?));
+            }
+        }
+        Ok(retired)
+    }
+}
+
+// Parse a hexadecimal value written with or without 0x
+fn parse_value(text: &str) -> Result<u32, String> {
+    let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+    u32::from_str_radix(digits, 16).map_err(|_| format!("invalid value '{}'", text))
+}
+
+fn parse_register(name: &str) -> Result<Register, String> {
+    let number = |digits: &str| match digits.parse::<u32>() {
+        Ok(reg_num) if reg_num < 32 => Ok(reg_num),
+        _ => Err(format!("unknown register '{}'", name)),
+    };
+    match name {
+        "hi" => Ok(Register::Hi),
+        "lo" => Ok(Register::Lo),
+        "c31_fcsr" | "fcsr" => Ok(Register::Fcsr),
+        _ if name.starts_with('x') => number(&name[1..]).map(Register::Gpr),
+        _ if name.starts_with('f') => number(&name[1..]).map(Register::Fpr),
+        _ => Err(format!("unknown register '{}'", name)),
+    }
+}
+
+/// The golden model's commit in the syntax of a `commit` request
+pub fn format_commit(commit: &Commit) -> String {
+    let mut text = format!("0x{:08x}", commit.pc);
+    for (register, value) in &commit.writes {
+        let _ = write!(text, " {}=0x{:08x}", register_name(*register), value);
+    }
+    if let Some(access) = commit.memory {
+        if let Some(value) = access.value {
+            let _ = write!(text, " mem=0x{:08x}:0x{:08x}", access.address, value);
+        }
+    }
+    text
+}
+
+/// Differences between what the RTL core retired and what the golden model
+/// committed. General-purpose registers are always compared; HI, LO, FCSR
+/// and floating-point registers only when the testbench reports that kind
+/// of register, since not every core has them. Writes to `x0` are ignored.
+/// Store values are compared in the bytes the store wrote.
+pub fn compare(retired: &RetiredInstruction, golden: &Commit) -> Vec<String> {
+    let mut differences = Vec::new();
+    if retired.pc != golden.pc {
+        differences.push(format!(
+            "pc: expected 0x{:08x}, got 0x{:08x}",
+            golden.pc, retired.pc
+        ));
+    }
+
+    let compared = |register: &Register| {
+        *register != Register::Gpr(0)
+            && (matches!(register, Register::Gpr(_))
+                || retired.writes.iter().any(|(reported, _)| {
+                    mem::discriminant(reported) == mem::discriminant(register)
+                }))
+    };
+    for (register, expected) in golden
+        .writes
+        .iter()
+        .filter(|(register, _)| compared(register))
+    {
+        match retired
+            .writes
+            .iter()
+            .find(|(reported, _)| reported == register)
+        {
+            Some((_, value)) if value == expected => {},
+            Some((_, value)) => differences.push(format!(
+                "{}: expected 0x{:08x}, got 0x{:08x}",
+                register_name(*register),
+                expected,
+                value
+            )),
+            None => differences.push(format!(
+                "{}: expected 0x{:08x}, got no write",
+                register_name(*register),
+                expected
+            )),
+        }
+    }
+    for (register, value) in retired
+        .writes
+        .iter()
+        .filter(|(register, _)| compared(register))
+    {
+        if !golden.writes.iter().any(|(written, _)| written == register) {
+            differences.push(format!(
+                "{}: expected no write, got 0x{:08x}",
+                register_name(*register),
+                value
+            ));
+        }
+    }
+
+    let expected_store = golden.memory.and_then(|access| {
+        access
+            .value
+            .map(|value| (access.address, value, access.size))
+    });
+    match (expected_store, retired.store) {
+        (None, None) => {},
+        (Some((address, value, size)), Some((got_address, got_value))) => {
+            let mask = if size == 4 {
+                u32::MAX
+            } else {
+                (1 << (size * 8)) - 1
+            };
+            if address != got_address || value != got_value & mask {
+                differences.push(format!(
+                    "store: expected 0x{:08x}:0x{:08x}, got 0x{:08x}:0x{:08x}",
+                    address, value, got_address, got_value
+                ));
+            }
+        },
+        (Some((address, value, _)), None) => differences.push(format!(
+            "store: expected 0x{:08x}:0x{:08x}, got none",
+            address, value
+        )),
+        (None, Some((address, value))) => differences.push(format!(
+            "store: expected none, got 0x{:08x}:0x{:08x}",
+            address, value
+        )),
+    }
+    differences
+}
+
+/// The first retired instruction the RTL core and the golden model
+/// disagreed on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Instructions checked before this one
+    pub instruction: u64,
+    /// The golden model's commit, in the syntax of a `commit` request
+    pub expected: String,
+    pub differences: Vec<String>,
+}
+
+/// Outcome of a co-simulation
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CosimReport {
+    /// Retired instructions the testbench reported
+    pub checked: u64,
+    /// Those that did not match the golden model
+    pub mismatches: u64,
+    pub first_mismatch: Option<Mismatch>,
+}
+
+impl CosimReport {
+    pub fn render(&self) -> String {
+        let mut text = format!(
+            "Co-simulation: {} instructions checked, {} mismatches\n",
+            self.checked, self.mismatches
+        );
+        if let Some(mismatch) = &self.first_mismatch {
+            let _ = writeln!(
+                text,
+                "  First mismatch at instruction {}: expected {}",
+                mismatch.instruction, mismatch.expected
+            );
+            for difference in &mismatch.differences {
+                let _ = writeln!(text, "    {}", difference);
+            }
+        }
+        text
+    }
+}
+
+/// The golden model side of a co-simulation
+pub struct CosimBridge {
+    simulator: Simulator,
+    report: CosimReport,
+    halted: bool,
+}
+
+impl CosimBridge {
+    /// Use `simulator`, with its program loaded, as the golden model
+    pub fn new(mut simulator: Simulator) -> Self {
+        simulator.commit_log = Some(
+            CommitLog::new(Box::new(io::sink()), CommitLogFormat::Spike)
+                .expect("writing to a sink cannot fail"),
+        );
+        Self {
+            simulator,
+            report: CosimReport::default(),
+            halted: false,
+        }
+    }
+
+    pub fn simulator(&self) -> &Simulator {
+        &self.simulator
+    }
+
+    pub fn report(&self) -> &CosimReport {
+        &self.report
+    }
+
+    /// Run the golden model to its next retired instruction, or `None` once
+    /// the program has stopped
+    pub fn next_commit(&mut self) -> Result<Option<Commit>, String> {
+        while !self.halted {
+            let result = self.simulator.step();
+            let commit = self
+                .simulator
+                .commit_log
+                .as_mut()
+                .and_then(|log| log.take_last());
+            match result {
+                Ok(running) => self.halted = !running,
+                Err(error) => {
+                    self.halted = true;
+                    return Err(error.to_string());
+                },
+            }
+            if commit.is_some() {
+                return Ok(commit);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Check an instruction the RTL core retired against the golden
+    /// model's next one, returning the differences
+    pub fn check(&mut self, retired: &RetiredInstruction) -> Result<Vec<String>, String> {
+        let (expected, differences) = match self.next_commit()? {
+            Some(golden) => (format_commit(&golden), compare(retired, &golden)),
+            None => (
+                "no instruction".to_string(),
+                vec!["the golden model has stopped".to_string()],
+            ),
+        };
+        if !differences.is_empty() {
+            if self.report.first_mismatch.is_none() {
+                self.report.first_mismatch = Some(Mismatch {
+                    instruction: self.report.checked,
+                    expected,
+                    differences: differences.clone(),
+                });
+            }
+            self.report.mismatches += 1;
+        }
+        self.report.checked += 1;
+        Ok(differences)
+    }
+
+    /// Answer one request line, or return `None` for `quit`
+    pub fn handle(&mut self, line: &str) -> Option<String> {
+        let line = line.trim();
+        let (command, arguments) = line.split_once(' ').unwrap_or((line, ""));
+        let reply = match command {
+            "commit" => RetiredInstruction::parse(arguments)
+                .and_then(|retired| self.check(&retired))
+                .map(|differences| {
+                    if differences.is_empty() {
+                        "ok".to_string()
+                    } else {
+                        format!("mismatch {}", differences.join("; "))
+                    }
+                }),
+            "step" => self.next_commit().map(|commit| match commit {
+                Some(commit) => format!("commit {}", format_commit(&commit)),
+                None => "halted".to_string(),
+            }),
+            "reg" => parse_register(arguments.trim()).map(|register| {
+                let registers = &self.simulator.registers;
+                let value = match register {
+                    Register::Gpr(reg_num) => registers.read(reg_num),
+                    Register::Fpr(reg_num) => registers.read_float(reg_num).to_bits(),
+                    Register::Hi => registers.hi,
+                    Register::Lo => registers.lo,
+                    Register::Fcsr => registers.fcsr,
+                };
+                format!("0x{:08x}", value)
+            }),
+            "mem" => parse_value(arguments.trim()).and_then(|address| {
+                let memory = &self.simulator.memory;
+                memory
+                    .read_word(memory.translate_address(address as usize))
+                    .map(|word| format!("0x{:08x}", word))
+                    .map_err(|e| e.to_string())
+            }),
+            "report" => Ok(self.report.render().lines().collect::<Vec<_>>().join(" |")),
+            "quit" => return None,
+            _ => Err(format!("unknown command '{}'", command)),
+        };
+        Some(reply.unwrap_or_else(|message| format!("error {}", message)))
+    }
+
+    /// Answer requests from a testbench until it quits or disconnects
+    pub fn serve(&mut self, stream: TcpStream) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match self.handle(&line) {
+                Some(reply) => writeln!(writer, "{}", reply)?,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,563 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// mod.rs
+//
+// This file contains the simulation server behind the `serve` subcommand.
+// It speaks a small JSON API over HTTP/1.1, and over WebSocket for clients
+// that want to keep one connection open, so web front-ends and autograders
+// can create sessions, upload programs, step them and inspect registers,
+// memory and pipeline state. Every connection and every session gets its
+// own thread.
+
+pub mod cosim;
+pub mod session;
+pub mod websocket;
+
+use self::session::{Command, SessionConfig, SessionHandle};
+use self::websocket::Message;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Largest request body accepted, enough for any program the loader handles
+const MAX_BODY: usize = 16 * 1024 * 1024;
+// Largest memory range returned by one request
+const MAX_MEMORY_READ: usize = 64 * 1024;
+// Most instructions executed by one step request
+const MAX_STEP_COUNT: usize = 10_000_000;
+
+/// Sessions one server keeps open at once
+pub const MAX_SESSIONS: usize = 64;
+/// How long a session may go unused before the server stops it
+pub const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+type Sessions = Arc<Mutex<SessionTable>>;
+
+struct SessionTable {
+    next_id: u64,
+    sessions: BTreeMap<u64, SessionEntry>,
+    max_sessions: usize,
+    idle_timeout: Duration,
+}
+
+struct SessionEntry {
+    handle: Arc<SessionHandle>,
+    last_used: Instant,
+}
+
+impl Default for SessionTable {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            sessions: BTreeMap::new(),
+            max_sessions: MAX_SESSIONS,
+            idle_timeout: SESSION_IDLE_TIMEOUT,
+        }
+    }
+}
+
+impl SessionTable {
+    // Stop the sessions nobody has used within the idle timeout. A request
+    // still running on one keeps its thread alive until it finishes.
+    fn expire_idle(&mut self) {
+        let idle_timeout = self.idle_timeout;
+        self.sessions
+            .retain(|_, entry| entry.last_used.elapsed() < idle_timeout);
+    }
+}
+
+/// HTTP and WebSocket server for remote simulation sessions
+pub struct Server {
+    listener: TcpListener,
+    sessions: Sessions,
+}
+
+impl Server {
+    /// Listen on `address`; port 0 picks a free port
+    pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(address)?,
+            sessions: Sessions::default(),
+        })
+    }
+
+    /// Keep at most `max_sessions` sessions, each stopped after going unused
+    /// for `idle_timeout`
+    pub fn with_session_limits(self, max_sessions: usize, idle_timeout: Duration) -> Self {
+        {
+            let mut table = self.sessions.lock().unwrap();
+            table.max_sessions = max_sessions;
+            table.idle_timeout = idle_timeout;
+        }
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept connections until the listener fails
+    pub fn run(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let sessions = Arc::clone(&self.sessions);
+            thread::spawn(move || {
+                if let Err(e) = handle_connection(stream, &sessions) {
+                    log::debug!("Connection closed: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: BTreeMap<String, String>,
+    headers: BTreeMap<String, String>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    body: Value,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message }),
+        }
+    }
+}
+
+// WebSocket commands mirror the HTTP endpoints
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase", deny_unknown_fields)]
+enum SocketCommand {
+    Load {
+        source: String,
+    },
+    Step {
+        #[serde(default = "default_step_count")]
+        count: usize,
+    },
+    Registers,
+    Memory {
+        address: u32,
+        length: usize,
+    },
+    Pipeline,
+}
+
+fn default_step_count() -> usize {
+    1
+}
+
+fn handle_connection(stream: TcpStream, sessions: &Sessions) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+    let request = match read_request(&mut reader) {
+        Ok(request) => request,
+        Err(response) => return write_response(&mut stream, &response),
+    };
+
+    let segments: Vec<&str> = request.path.split('/').filter(|s| !s.is_empty()).collect();
+    if request.method == "GET" && segments.len() == 3 && segments[2] == "ws" {
+        return match find_session(sessions, segments[1]) {
+            Ok(_) => serve_websocket(reader, stream, &request, sessions, segments[1]),
+            Err(response) => write_response(&mut stream, &response),
+        };
+    }
+
+    let response = route(&request, &segments, sessions);
+    write_response(&mut stream, &response)
+}
+
+fn read_request<R: BufRead>(reader: &mut R) -> Result<Request, Response> {
+    let bad_request = |_| Response::error(400, "Malformed request");
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(bad_request)?;
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => return Err(Response::error(400, "Malformed request line")),
+    };
+
+    let mut headers = BTreeMap::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(bad_request)? == 0 {
+            return Err(Response::error(400, "Incomplete headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = match headers.get("content-length") {
+        Some(value) => value
+            .parse::<usize>()
+            .map_err(|_| Response::error(400, "Invalid Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY {
+        return Err(Response::error(413, "Request body too large"));
+    }
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body).map_err(bad_request)?;
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), parse_query(query)),
+        None => (target, BTreeMap::new()),
+    };
+    Ok(Request {
+        method,
+        path,
+        query,
+        headers,
+        body,
+    })
+}
+
+fn parse_query(query: &str) -> BTreeMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (pair.to_string(), String::new()),
+        })
+        .collect()
+}
+
+fn route(request: &Request, segments: &[&str], sessions: &Sessions) -> Response {
+    let method = request.method.as_str();
+    if method == "OPTIONS" {
+        return Response {
+            status: 204,
+            body: Value::Null,
+        };
+    }
+    if segments.first() != Some(&"sessions") {
+        return Response::error(404, "Not found");
+    }
+
+    match (method, &segments[1..]) {
+        ("GET", []) => {
+            let mut table = sessions.lock().unwrap();
+            table.expire_idle();
+            let ids: Vec<u64> = table.sessions.keys().copied().collect();
+            Response::ok(json!({ "sessions": ids }))
+        },
+        ("POST", []) => create_session(&request.body, sessions),
+        ("DELETE", [id]) => {
+            let removed = id
+                .parse::<u64>()
+                .ok()
+                .and_then(|id| sessions.lock().unwrap().sessions.remove(&id));
+            match removed {
+                Some(_) => Response::ok(json!({ "deleted": true })),
+                None => Response::error(404, "No such session"),
+            }
+        },
+        (_, [id, action]) => {
+            let command = match (method, *action) {
+                ("POST", "program") => Ok(Command::Load(request.body.clone())),
+                ("POST", "step") => query_number(request, "count", 1).and_then(step_count),
+                ("GET", "registers") => Ok(Command::Registers),
+                ("GET", "memory") => memory_command(request),
+                ("GET", "pipeline") => Ok(Command::Pipeline),
+                (_, "program")
+                | (_, "step")
+                | (_, "registers")
+                | (_, "memory")
+                | (_, "pipeline") => Err(Response::error(405, "Method not allowed")),
+                _ => Err(Response::error(404, "Not found")),
+            };
+            let session = match find_session(sessions, id) {
+                Ok(session) => session,
+                Err(response) => return response,
+            };
+            match command {
+                Ok(command) => run_command(&session, command),
+                Err(response) => response,
+            }
+        },
+        (_, []) | (_, [_]) => Response::error(405, "Method not allowed"),
+        _ => Response::error(404, "Not found"),
+    }
+}
+
+fn create_session(body: &[u8], sessions: &Sessions) -> Response {
+    if is_full(sessions) {
+        return too_many_sessions();
+    }
+    let config: SessionConfig = if body.iter().all(|byte| byte.is_ascii_whitespace()) {
+        SessionConfig::default()
+    } else {
+        match serde_json::from_slice(body) {
+            Ok(config) => config,
+            Err(e) => return Response::error(400, &format!("Invalid session settings: {}", e)),
+        }
+    };
+    let handle = match SessionHandle::spawn(config) {
+        Ok(handle) => handle,
+        Err(e) => return Response::error(400, &e),
+    };
+
+    // Another request may have filled the table while this one spawned
+    let mut table = sessions.lock().unwrap();
+    if table.sessions.len() >= table.max_sessions {
+        return too_many_sessions();
+    }
+    table.next_id += 1;
+    let id = table.next_id;
+    table.sessions.insert(
+        id,
+        SessionEntry {
+            handle: Arc::new(handle),
+            last_used: Instant::now(),
+        },
+    );
+    Response {
+        status: 201,
+        body: json!({ "id": id }),
+    }
+}
+
+fn is_full(sessions: &Sessions) -> bool {
+    let mut table = sessions.lock().unwrap();
+    table.expire_idle();
+    table.sessions.len() >= table.max_sessions
+}
+
+fn too_many_sessions() -> Response {
+    Response::error(
+        503,
+        "Too many sessions; delete one or wait for an idle one to expire",
+    )
+}
+
+fn find_session(sessions: &Sessions, id: &str) -> Result<Arc<SessionHandle>, Response> {
+    let mut table = sessions.lock().unwrap();
+    table.expire_idle();
+    id.parse::<u64>()
+        .ok()
+        .and_then(|id| table.sessions.get_mut(&id))
+        .map(|entry| {
+            entry.last_used = Instant::now();
+            Arc::clone(&entry.handle)
+        })
+        .ok_or_else(|| Response::error(404, "No such session"))
+}
+
+fn run_command(session: &SessionHandle, command: Command) -> Response {
+    match session.send(command) {
+        Ok(body) => Response::ok(body),
+        Err(e) => Response::error(400, &e),
+    }
+}
+
+fn memory_command(request: &Request) -> Result<Command, Response> {
+    let address = query_number(request, "address", 0)?;
+    let length = query_number(request, "length", 64)?;
+    memory_range(address, length)
+}
+
+fn memory_range(address: usize, length: usize) -> Result<Command, Response> {
+    if address > u32::MAX as usize {
+        return Err(Response::error(400, "Address out of range"));
+    }
+    if length > MAX_MEMORY_READ {
+        return Err(Response::error(
+            400,
+            &format!("At most {} bytes can be read at once", MAX_MEMORY_READ),
+        ));
+    }
+    Ok(Command::Memory {
+        address: address as u32,
+        length,
+    })
+}
+
+fn step_count(count: usize) -> Result<Command, Response> {
+    if count > MAX_STEP_COUNT {
+        return Err(Response::error(
+            400,
+            &format!(
+                "At most {} instructions can be stepped at once",
+                MAX_STEP_COUNT
+            ),
+        ));
+    }
+    Ok(Command::Step(count))
+}
+
+// Query values are decimal or 0x-prefixed hexadecimal
+fn query_number(request: &Request, name: &str, default: usize) -> Result<usize, Response> {
+    let value = match request.query.get(name) {
+        Some(value) => value,
+        None => return Ok(default),
+    };
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|_| Response::error(400, &format!("Invalid {}: {}", name, value)))
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        _ => "Error",
+    };
+    let body = if response.body.is_null() {
+        String::new()
+    } else {
+        response.body.to_string()
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Methods: GET, POST, DELETE, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Content-Type\r\n\
+         Connection: close\r\n\r\n",
+        response.status,
+        reason,
+        body.len()
+    );
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
+
+fn serve_websocket(
+    reader: BufReader<TcpStream>,
+    mut stream: TcpStream,
+    request: &Request,
+    sessions: &Sessions,
+    id: &str,
+) -> io::Result<()> {
+    let upgrade = request
+        .headers
+        .get("upgrade")
+        .map_or(false, |value| value.eq_ignore_ascii_case("websocket"));
+    let key = match (upgrade, request.headers.get("sec-websocket-key")) {
+        (true, Some(key)) => key,
+        _ => {
+            let response = Response::error(400, "Expected a WebSocket upgrade");
+            return write_response(&mut stream, &response);
+        },
+    };
+    let head = format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        websocket::accept_key(key)
+    );
+    stream.write_all(head.as_bytes())?;
+
+    // Frames the client sent right behind the handshake are still buffered.
+    // Each command looks the session up again, which keeps it from expiring
+    // and notices when it has been deleted.
+    let mut socket = Socket { reader, stream };
+    loop {
+        let reply = match websocket::read_message(&mut socket)? {
+            Message::Close => return websocket::write_close(&mut socket.stream),
+            Message::Binary(_) => json!({ "error": "Commands are JSON text messages" }),
+            Message::Text(text) => match find_session(sessions, id) {
+                Ok(session) => socket_reply(&session, &text),
+                Err(response) => response.body,
+            },
+        };
+        websocket::write_text(&mut socket.stream, &reply.to_string())?;
+    }
+}
+
+fn socket_reply(session: &SessionHandle, text: &str) -> Value {
+    let command = match serde_json::from_str::<SocketCommand>(text) {
+        Ok(SocketCommand::Load { source }) => Ok(Command::Load(source.into_bytes())),
+        Ok(SocketCommand::Step { count }) => step_count(count).map_err(|response| response.body),
+        Ok(SocketCommand::Registers) => Ok(Command::Registers),
+        Ok(SocketCommand::Memory { address, length }) => {
+            memory_range(address as usize, length).map_err(|response| response.body)
+        },
+        Ok(SocketCommand::Pipeline) => Ok(Command::Pipeline),
+        Err(e) => Err(json!({ "error": format!("Invalid command: {}", e) })),
+    };
+    match command.map(|command| session.send(command)) {
+        Ok(Ok(body)) => body,
+        Ok(Err(e)) => json!({ "error": e }),
+        Err(body) => body,
+    }
+}
+
+// Reads go through the handshake's buffer, writes straight to the socket
+struct Socket {
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+}
+
+impl Read for Socket {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buffer)
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.stream.write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
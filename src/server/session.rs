@@ -0,0 +1,397 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// session.rs
+//
+// This file contains the simulation sessions of the server. Each session
+// owns its simulator on a thread of its own and answers commands sent over
+// a channel, so a long step in one session never holds up the others and
+// the simulator itself never crosses threads.
+
+use crate::functional_simulator::registers::Registers;
+use crate::functional_simulator::simulator::{
+    decode_instruction, instruction_to_string, Simulator, STACK_TOP,
+};
+use crate::loader::ProgramImage;
+use crate::timing_simulator::compare::TimingVariant;
+use crate::timing_simulator::simulator::{ExecutionMode, Simulator as TimingSimulator};
+use crate::timing_simulator::stats::InstructionTiming;
+use crate::timing_simulator::what_if::WhatIfSession;
+use crate::utils::limits::RunLimits;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Cursor;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+// Executed instructions listed in a pipeline report
+const RECENT_INSTRUCTIONS: usize = 8;
+
+/// Host memory one session's simulator may hold, in bytes
+pub const MAX_SESSION_MEMORY: usize = 256 * 1024 * 1024;
+
+/// Which simulator a session drives
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionKind {
+    Functional,
+    Timing,
+}
+
+/// Settings for a new session, the body of `POST /sessions`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    pub simulator: SessionKind,
+    /// Memory size in bytes, raised to fit the program when it is loaded.
+    /// Sizes whose simulator would pass `MAX_SESSION_MEMORY` are rejected.
+    pub memory_size: usize,
+    /// Timing settings in the `compare` syntax, e.g. `forwarding=off`
+    pub config: String,
+    /// Text the program's read syscalls read. A session never reads the
+    /// server's own stdin, so reads past the end see end of input.
+    pub stdin: String,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            simulator: SessionKind::Functional,
+            memory_size: 8192,
+            config: String::new(),
+            stdin: String::new(),
+        }
+    }
+}
+
+/// A request to a session
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Load a program in any format `ProgramImage::from_bytes` detects,
+    /// replacing the current one
+    Load(Vec<u8>),
+    /// Execute up to this many instructions
+    Step(usize),
+    Registers,
+    Memory {
+        address: u32,
+        length: usize,
+    },
+    /// Pipeline state and statistics of a timing session
+    Pipeline,
+}
+
+type Reply = Result<Value, String>;
+
+/// Handle to a session thread. The thread exits when the handle is dropped.
+pub struct SessionHandle {
+    sender: Mutex<Sender<(Command, Sender<Reply>)>>,
+}
+
+impl SessionHandle {
+    /// Start a session thread
+    pub fn spawn(config: SessionConfig) -> Result<Self, String> {
+        let variant = TimingVariant::parse(&config.config)?;
+        check_memory(&config, &variant, config.memory_size)?;
+
+        let (sender, receiver) = channel::<(Command, Sender<Reply>)>();
+        thread::spawn(move || {
+            let mut session = Session::new(config, variant);
+            for (command, reply) in receiver {
+                let _ = reply.send(session.handle(command));
+            }
+        });
+        Ok(Self {
+            sender: Mutex::new(sender),
+        })
+    }
+
+    /// Run a command on the session thread and wait for its JSON result
+    pub fn send(&self, command: Command) -> Reply {
+        let (reply_sender, reply_receiver) = channel();
+        self.sender
+            .lock()
+            .map_err(|_| "Session is unavailable".to_string())?
+            .send((command, reply_sender))
+            .map_err(|_| "Session has stopped".to_string())?;
+        reply_receiver
+            .recv()
+            .map_err(|_| "Session has stopped".to_string())?
+    }
+}
+
+// The loaded simulator of a session
+enum Machine {
+    Empty,
+    Functional(Box<FunctionalRun>),
+    Timing(Box<WhatIfSession>),
+}
+
+struct FunctionalRun {
+    simulator: Simulator,
+    finished: bool,
+    error: Option<String>,
+}
+
+struct Session {
+    config: SessionConfig,
+    variant: TimingVariant,
+    machine: Machine,
+}
+
+impl Session {
+    fn new(config: SessionConfig, variant: TimingVariant) -> Self {
+        Self {
+            config,
+            variant,
+            machine: Machine::Empty,
+        }
+    }
+
+    fn handle(&mut self, command: Command) -> Reply {
+        match command {
+            Command::Load(bytes) => self.load(&bytes),
+            Command::Step(count) => self.step(count),
+            Command::Registers => {
+                let (registers, pc) = self.registers()?;
+                Ok(registers_json(registers, pc))
+            },
+            Command::Memory { address, length } => self.memory(address, length),
+            Command::Pipeline => self.pipeline(),
+        }
+    }
+
+    fn load(&mut self, bytes: &[u8]) -> Reply {
+        let image = ProgramImage::from_bytes(bytes).map_err(|e| e.to_string())?;
+        let memory_size = self
+            .config
+            .memory_size
+            .max(image.memory_required().next_power_of_two());
+        check_memory(&self.config, &self.variant, memory_size)?;
+
+        self.machine = match self.config.simulator {
+            SessionKind::Functional => {
+                let mut simulator = Simulator::new(memory_size);
+                simulator.load_image(&image).map_err(|e| e.to_string())?;
+                simulator.registers.write(29, STACK_TOP);
+                simulator.captured_output = Some(Vec::new());
+                simulator.input = Some(self.input());
+                Machine::Functional(Box::new(FunctionalRun {
+                    simulator,
+                    finished: false,
+                    error: None,
+                }))
+            },
+            SessionKind::Timing => {
+                let mut loaded = Ok(());
+                let input = self.input();
+                let session = WhatIfSession::new(self.variant.clone(), memory_size, |simulator| {
+                    loaded = simulator.load_image(&image);
+                    simulator.registers.write(29, STACK_TOP);
+                    simulator.captured_output = Some(Vec::new());
+                    simulator.input = Some(input);
+                })?;
+                loaded.map_err(|e| e.to_string())?;
+                Machine::Timing(Box::new(session))
+            },
+        };
+
+        Ok(json!({
+            "format": image.format.to_string(),
+            "entry": image.entry_point,
+            "memory_size": memory_size,
+        }))
+    }
+
+    fn step(&mut self, count: usize) -> Reply {
+        match &mut self.machine {
+            Machine::Empty => Err(no_program()),
+            Machine::Functional(run) => {
+                let mut executed = 0;
+                while executed < count && !run.finished {
+                    match run.simulator.step() {
+                        Ok(true) => executed += 1,
+                        Ok(false) => run.finished = true,
+                        Err(error) => {
+                            run.finished = true;
+                            run.error = Some(error.to_string());
+                        },
+                    }
+                }
+                let stdout = run.simulator.captured_output.replace(Vec::new());
+                Ok(json!({
+                    "executed": executed,
+                    "instructions": run.simulator.step_count,
+                    "pc": run.simulator.pc(),
+                    "finished": run.finished,
+                    "termination_reason": run.simulator.termination_reason.map(|r| r.to_string()),
+                    "error": run.error,
+                    "stdout": String::from_utf8_lossy(&stdout.unwrap_or_default()),
+                }))
+            },
+            Machine::Timing(session) => {
+                let executed = session.step(count).len();
+                let stdout = session.simulator.captured_output.replace(Vec::new());
+                Ok(json!({
+                    "executed": executed,
+                    "instructions": session.instructions(),
+                    "cycles": session.cycles(),
+                    "pc": session.simulator.pc,
+                    "finished": session.is_finished(),
+                    "termination_reason": session.termination_reason.map(|r| r.to_string()),
+                    "stdout": String::from_utf8_lossy(&stdout.unwrap_or_default()),
+                }))
+            },
+        }
+    }
+
+    // A fresh reader over the configured input, for each loaded program
+    fn input(&self) -> Cursor<Vec<u8>> {
+        Cursor::new(self.config.stdin.clone().into_bytes())
+    }
+
+    fn registers(&self) -> Result<(&Registers, u32), String> {
+        match &self.machine {
+            Machine::Empty => Err(no_program()),
+            Machine::Functional(run) => Ok((&run.simulator.registers, run.simulator.pc())),
+            Machine::Timing(session) => Ok((&session.simulator.registers, session.simulator.pc)),
+        }
+    }
+
+    fn memory(&self, address: u32, length: usize) -> Reply {
+        let memory = match &self.machine {
+            Machine::Empty => return Err(no_program()),
+            Machine::Functional(run) => &run.simulator.memory,
+            Machine::Timing(session) => &session.simulator.memory,
+        };
+        let bytes = memory
+            .read_block(address as usize, length)
+            .map_err(|e| e.to_string())?;
+        Ok(json!({
+            "address": address,
+            "length": length,
+            "bytes": bytes,
+        }))
+    }
+
+    fn pipeline(&self) -> Reply {
+        let session = match &self.machine {
+            Machine::Empty => return Err(no_program()),
+            Machine::Functional(_) => {
+                return Err("Functional sessions have no pipeline".to_string())
+            },
+            Machine::Timing(session) => session,
+        };
+        let pipeline = match &session.simulator.execution_mode {
+            ExecutionMode::InOrder(pipeline) => pipeline,
+            ExecutionMode::OutOfOrder(_) => unreachable!("what-if sessions are in-order"),
+        };
+
+        let stages: Vec<Value> = pipeline
+            .stages
+            .iter()
+            .map(|stage| {
+                json!({
+                    "stage": stage.name,
+                    "kind": format!("{:?}", stage.stage_type),
+                    "status": format!("{:?}", stage.status),
+                    "pc": stage.pc,
+                    "instruction": stage.instruction.as_ref().map(|i| format!("{:?}", i)),
+                })
+            })
+            .collect();
+        let start = session.history.len().saturating_sub(RECENT_INSTRUCTIONS);
+        let recent: Vec<Value> = session.history[start..].iter().map(timing_json).collect();
+
+        Ok(json!({
+            "settings": session.applied.describe(),
+            "instructions": session.instructions(),
+            "cycles": session.cycles(),
+            "stalls": {
+                "data_hazard": pipeline.data_hazard_stalls,
+                "control_hazard": pipeline.control_hazard_stalls,
+                "cache_miss": pipeline.cache_miss_stalls,
+                "structural": pipeline.structural_hazard_stalls,
+            },
+            "branch_mispredictions": pipeline.branch_mispredictions,
+            "forwarding_used": pipeline.forwarding_used,
+            "stages": stages,
+            "recent": recent,
+        }))
+    }
+}
+
+// Reject a memory size whose simulator would hold more than the session cap,
+// before any of it is allocated
+fn check_memory(
+    config: &SessionConfig,
+    variant: &TimingVariant,
+    memory_size: usize,
+) -> Result<(), String> {
+    let usage = match config.simulator {
+        SessionKind::Functional => Simulator::projected_memory_usage(memory_size),
+        SessionKind::Timing => TimingSimulator::projected_memory_usage(
+            &variant.pipeline_config,
+            &variant.instr_cache_config,
+            &variant.data_cache_config,
+            memory_size,
+        ),
+    };
+    let limits = RunLimits {
+        max_memory: Some(MAX_SESSION_MEMORY),
+        ..RunLimits::default()
+    };
+    limits
+        .check_memory(&usage)
+        .map_err(|e| format!("Memory size {} is too large: {}", memory_size, e))
+}
+
+fn no_program() -> String {
+    "No program loaded".to_string()
+}
+
+fn registers_json(registers: &Registers, pc: u32) -> Value {
+    let gpr: Vec<u32> = (0..32).map(|reg_num| registers.read(reg_num)).collect();
+    let fpr: Vec<f32> = (0..32)
+        .map(|reg_num| registers.read_float(reg_num))
+        .collect();
+    json!({
+        "pc": pc,
+        "gpr": gpr,
+        "hi": registers.get_hi(),
+        "lo": registers.get_lo(),
+        "fcsr": registers.get_fcsr(),
+        "fpr": fpr,
+    })
+}
+
+fn timing_json(timing: &InstructionTiming) -> Value {
+    json!({
+        "index": timing.index,
+        "pc": timing.pc,
+        "instruction": instruction_to_string(&decode_instruction(timing.word), timing.word),
+        "issue_cycle": timing.issue_cycle,
+        "cycles": timing.cycles,
+        "stall_cycles": timing.stall_cycles,
+        "mispredicted": timing.mispredicted,
+    })
+}
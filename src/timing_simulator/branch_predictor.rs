@@ -23,10 +23,18 @@
 //
 // This file contains the implementation of the branch predictor for the timing
 // simulator. It defines the PredictionState enum and the BranchPredictor struct,
-// which uses a 2-bit saturating counter for branch prediction.
+// which predicts with static, one-bit, 2-bit, correlating or tournament
+// tables, and the PredictorState export that carries a trained predictor from
+// one run to another.
 
+use super::config::BranchPredictorType;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// Name and version written at the top of an exported predictor
+pub const PREDICTOR_FORMAT: &str = "vmips-predictor";
+pub const PREDICTOR_VERSION: u32 = 1;
+
 /// Branch prediction states using a 2-bit saturating counter scheme
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PredictionState {
@@ -37,6 +45,17 @@ pub enum PredictionState {
 }
 
 impl PredictionState {
+    /// State of a 2-bit counter value
+    pub fn from_counter(counter: u8) -> Option<Self> {
+        match counter {
+            0 => Some(PredictionState::StronglyNotTaken),
+            1 => Some(PredictionState::WeaklyNotTaken),
+            2 => Some(PredictionState::WeaklyTaken),
+            3 => Some(PredictionState::StronglyTaken),
+            _ => None,
+        }
+    }
+
     pub fn is_taken(&self) -> bool {
         match self {
             PredictionState::WeaklyTaken | PredictionState::StronglyTaken => true,
@@ -58,9 +77,21 @@ impl PredictionState {
     }
 }
 
-/// Enhanced branch predictor implementation that uses a 2-bit saturating counter
-/// for more accurate predictions and implements both local and global history
+/// Branch predictor with a per-branch local table and a global table indexed
+/// by recent outcomes. Its type decides which tables predict:
+///
+/// - `Static` always predicts not taken;
+/// - `OneBit` repeats each branch's last outcome;
+/// - `TwoBit` uses each branch's 2-bit counter, falling back to the global
+///   table for branches it has not seen;
+/// - `Correlating` uses only the global table, indexed by the global history
+///   XORed with the branch address (gshare);
+/// - `Tournament` keeps a 2-bit chooser per branch that picks whichever of
+///   the 2-bit and correlating predictions has been right more often.
+#[derive(Clone)]
 pub struct BranchPredictor {
+    kind: BranchPredictorType,
+
     /// Branch history table - maps PC to prediction state
     branch_history_table: HashMap<u32, PredictionState>,
 
@@ -73,34 +104,79 @@ pub struct BranchPredictor {
     /// Branch target buffer - caches branch target addresses
     branch_target_buffer: HashMap<u32, u32>,
 
+    /// Tournament chooser per branch, taken meaning the correlating side
+    chooser: HashMap<u32, PredictionState>,
+
     /// Statistics
     predictions: usize,
     correct_predictions: usize,
 }
 
 impl BranchPredictor {
+    /// A 2-bit predictor
     pub fn new() -> Self {
+        Self::with_type(BranchPredictorType::TwoBit)
+    }
+
+    pub fn with_type(kind: BranchPredictorType) -> Self {
         Self {
+            kind,
             branch_history_table: HashMap::new(),
             global_history: 0,
             global_predictor: [PredictionState::WeaklyNotTaken; 16],
             branch_target_buffer: HashMap::new(),
+            chooser: HashMap::new(),
             predictions: 0,
             correct_predictions: 0,
         }
     }
 
+    pub fn kind(&self) -> BranchPredictorType {
+        self.kind
+    }
+
     pub fn predict(&mut self, pc: u32) -> bool {
         self.predictions += 1;
+        self.prediction(pc)
+    }
 
-        // Try local prediction first
-        if let Some(&state) = self.branch_history_table.get(&pc) {
-            return state.is_taken();
+    // Prediction for the branch at `pc` without counting it
+    fn prediction(&self, pc: u32) -> bool {
+        let local = self.branch_history_table.get(&pc);
+        match self.kind {
+            BranchPredictorType::Static => false,
+            BranchPredictorType::OneBit => local.map_or(false, PredictionState::is_taken),
+            // Fall back to global prediction for a branch not seen yet
+            BranchPredictorType::TwoBit => match local {
+                Some(state) => state.is_taken(),
+                None => self.global_predictor[self.global_history as usize].is_taken(),
+            },
+            BranchPredictorType::Correlating => self.correlating_state(pc).is_taken(),
+            BranchPredictorType::Tournament => {
+                let chooser = self.chooser.get(&pc);
+                if chooser.map_or(false, PredictionState::is_taken) {
+                    self.correlating_state(pc).is_taken()
+                } else {
+                    self.local_or_default(pc).is_taken()
+                }
+            },
         }
+    }
+
+    fn local_or_default(&self, pc: u32) -> PredictionState {
+        self.branch_history_table
+            .get(&pc)
+            .copied()
+            .unwrap_or(PredictionState::WeaklyNotTaken)
+    }
+
+    // Global table entry of the branch at `pc` under the gshare index
+    fn correlating_index(&self, pc: u32) -> usize {
+        (((pc >> 2) as u8 ^ self.global_history) & 0xF) as usize
+    }
 
-        // Fall back to global prediction
-        let index = (self.global_history & 0xF) as usize;
-        self.global_predictor[index].is_taken()
+    fn correlating_state(&self, pc: u32) -> PredictionState {
+        self.global_predictor[self.correlating_index(pc)]
     }
 
     pub fn update(&mut self, pc: u32, taken: bool, actual_target: u32) {
@@ -109,28 +185,60 @@ impl BranchPredictor {
             self.branch_target_buffer.insert(pc, actual_target);
         }
 
-        // Update local predictor
-        let local_state = self
-            .branch_history_table
-            .get(&pc)
-            .cloned()
-            .unwrap_or(PredictionState::WeaklyNotTaken);
-
         // Check if prediction was correct
-        if local_state.is_taken() == taken {
+        if self.prediction(pc) == taken {
             self.correct_predictions += 1;
         }
 
-        // Update the state
-        let new_state = local_state.update(taken);
+        let local_state = self.local_or_default(pc);
+        let correlating = matches!(
+            self.kind,
+            BranchPredictorType::Correlating | BranchPredictorType::Tournament
+        );
+        if correlating {
+            // The chooser moves towards whichever side alone was right
+            if self.kind == BranchPredictorType::Tournament {
+                let local_right = local_state.is_taken() == taken;
+                let correlating_right = self.correlating_state(pc).is_taken() == taken;
+                if local_right != correlating_right {
+                    let chooser = self
+                        .chooser
+                        .get(&pc)
+                        .copied()
+                        .unwrap_or(PredictionState::WeaklyNotTaken);
+                    self.chooser.insert(pc, chooser.update(correlating_right));
+                }
+            }
+            let index = self.correlating_index(pc);
+            self.global_predictor[index] = self.global_predictor[index].update(taken);
+        }
+
+        // Update the local state; a one-bit entry only remembers the outcome
+        let new_state = match (self.kind, taken) {
+            (BranchPredictorType::OneBit, true) => PredictionState::StronglyTaken,
+            (BranchPredictorType::OneBit, false) => PredictionState::StronglyNotTaken,
+            _ => local_state.update(taken),
+        };
         self.branch_history_table.insert(pc, new_state);
 
         // Update global history (shift left and add new outcome)
         self.global_history = ((self.global_history << 1) | (taken as u8)) & 0xF;
 
-        // Update global predictor
-        let index = (self.global_history & 0xF) as usize;
-        self.global_predictor[index] = self.global_predictor[index].update(taken);
+        // Update the global fallback of the other types
+        if !correlating {
+            let index = (self.global_history & 0xF) as usize;
+            self.global_predictor[index] = self.global_predictor[index].update(taken);
+        }
+    }
+
+    /// Local counter of the branch at `pc`, once it has been resolved
+    pub fn local_state(&self, pc: u32) -> Option<PredictionState> {
+        self.branch_history_table.get(&pc).copied()
+    }
+
+    /// Last four branch outcomes, the newest in bit 0
+    pub fn global_history(&self) -> u8 {
+        self.global_history
     }
 
     /// Get the predicted target address for a branch
@@ -146,4 +254,126 @@ impl BranchPredictor {
 
         (self.correct_predictions as f32) / (self.predictions as f32)
     }
+
+    /// Export the trained tables. Prediction statistics and the tournament
+    /// chooser are left out.
+    pub fn export_state(&self) -> PredictorState {
+        let mut local: Vec<LocalCounter> = self
+            .branch_history_table
+            .iter()
+            .map(|(&pc, &state)| LocalCounter {
+                pc,
+                counter: state as u8,
+            })
+            .collect();
+        local.sort_by_key(|entry| entry.pc);
+        let mut targets: Vec<BranchTarget> = self
+            .branch_target_buffer
+            .iter()
+            .map(|(&pc, &target)| BranchTarget { pc, target })
+            .collect();
+        targets.sort_by_key(|entry| entry.pc);
+
+        PredictorState {
+            format: PREDICTOR_FORMAT.to_string(),
+            version: PREDICTOR_VERSION,
+            local,
+            global_history: self.global_history,
+            global: self
+                .global_predictor
+                .iter()
+                .map(|&state| state as u8)
+                .collect(),
+            targets,
+        }
+    }
+
+    /// Replace the tables with exported ones, keeping the statistics
+    pub fn import_state(&mut self, state: &PredictorState) -> Result<(), String> {
+        let counter = |value: u8| {
+            PredictionState::from_counter(value)
+                .ok_or_else(|| format!("invalid 2-bit counter {}", value))
+        };
+        if state.global.len() != self.global_predictor.len() {
+            return Err(format!(
+                "global table has {} counters, not {}",
+                state.global.len(),
+                self.global_predictor.len()
+            ));
+        }
+        let mut global = self.global_predictor;
+        for (slot, &value) in global.iter_mut().zip(&state.global) {
+            *slot = counter(value)?;
+        }
+        let mut local = HashMap::new();
+        for entry in &state.local {
+            local.insert(entry.pc, counter(entry.counter)?);
+        }
+
+        self.branch_history_table = local;
+        self.global_history = state.global_history & 0xF;
+        self.global_predictor = global;
+        self.branch_target_buffer = state
+            .targets
+            .iter()
+            .map(|entry| (entry.pc, entry.target))
+            .collect();
+        Ok(())
+    }
+}
+
+/// Counter of one branch in the local history table
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalCounter {
+    pub pc: u32,
+    /// 2-bit counter: 0 strongly not taken to 3 strongly taken
+    pub counter: u8,
+}
+
+/// Last taken target of one branch
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BranchTarget {
+    pub pc: u32,
+    pub target: u32,
+}
+
+/// Trained tables of a branch predictor, written as versioned JSON so a
+/// predictor can be saved after one run and loaded before another
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PredictorState {
+    pub format: String,
+    pub version: u32,
+    /// Local history table, by branch address
+    pub local: Vec<LocalCounter>,
+    /// Last four branch outcomes, the newest in bit 0
+    pub global_history: u8,
+    /// Global pattern table of 2-bit counters, indexed by `global_history`
+    pub global: Vec<u8>,
+    /// Branch target buffer, by branch address
+    pub targets: Vec<BranchTarget>,
+}
+
+impl PredictorState {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Parse an exported predictor, refusing other formats and newer versions
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let state: Self =
+            serde_json::from_str(json).map_err(|e| format!("invalid predictor state: {}", e))?;
+        if state.format != PREDICTOR_FORMAT {
+            return Err(format!(
+                "not a predictor state: format is {:?}",
+                state.format
+            ));
+        }
+        if state.version > PREDICTOR_VERSION {
+            return Err(format!(
+                "unsupported predictor state version {} (newest supported is {})",
+                state.version, PREDICTOR_VERSION
+            ));
+        }
+        Ok(state)
+    }
 }
@@ -0,0 +1,265 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// cache_sweep.rs
+//
+// This file contains the cache parameter sweep. A sweep specification gives
+// ranges of cache sizes, associativities and line sizes; every valid
+// combination is simulated with the same program and the resulting miss
+// rates and cycle counts are collected into a CSV table.
+
+use super::config::{CacheConfig, PipelineConfig};
+use super::simulator::Simulator;
+use super::stats::IntervalSample;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// A list of values, or a power-of-two range from `min` to `max`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum ParameterRange {
+    Values(Vec<usize>),
+    Range { min: usize, max: usize },
+}
+
+impl ParameterRange {
+    pub fn values(&self) -> Vec<usize> {
+        match self {
+            ParameterRange::Values(values) => values.clone(),
+            ParameterRange::Range { min, max } => {
+                let mut values = Vec::new();
+                let mut value = (*min).max(1);
+                while value <= *max {
+                    values.push(value);
+                    value *= 2;
+                }
+                values
+            },
+        }
+    }
+}
+
+/// Which cache the swept parameters apply to
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SweepTarget {
+    Instruction,
+    Data,
+    Both,
+}
+
+impl SweepTarget {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SweepTarget::Instruction => "instruction",
+            SweepTarget::Data => "data",
+            SweepTarget::Both => "both",
+        }
+    }
+}
+
+fn default_target() -> SweepTarget {
+    SweepTarget::Data
+}
+
+/// Parameter ranges read from a JSON sweep file
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct SweepSpec {
+    #[serde(default = "default_target")]
+    pub cache: SweepTarget,
+    pub size: ParameterRange,
+    pub associativity: ParameterRange,
+    pub line_size: ParameterRange,
+}
+
+impl SweepSpec {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = fs::read_to_string(path)?;
+        Ok(Self::from_json(&json)?)
+    }
+
+    /// Every combination that forms a valid cache geometry
+    pub fn points(&self) -> Vec<SweepPoint> {
+        let mut points = Vec::new();
+        for size in self.size.values() {
+            for associativity in self.associativity.values() {
+                for line_size in self.line_size.values() {
+                    let point = SweepPoint {
+                        size,
+                        associativity,
+                        line_size,
+                    };
+                    if point.is_valid() {
+                        points.push(point);
+                    }
+                }
+            }
+        }
+        points
+    }
+}
+
+/// One cache geometry in the sweep
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SweepPoint {
+    pub size: usize,
+    pub associativity: usize,
+    pub line_size: usize,
+}
+
+impl SweepPoint {
+    // Mirrors the checks in CacheConfig::new so invalid points are skipped
+    // instead of panicking
+    fn is_valid(&self) -> bool {
+        let set_bytes = self.associativity * self.line_size;
+        self.size > 0 && set_bytes > 0 && self.size % set_bytes == 0
+    }
+
+    fn apply(&self, base: &CacheConfig) -> CacheConfig {
+        CacheConfig {
+            size: self.size,
+            associativity: self.associativity,
+            block_size: self.line_size,
+            ..base.clone()
+        }
+    }
+}
+
+/// Statistics measured for one sweep point
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepResult {
+    pub point: SweepPoint,
+    pub stats: IntervalSample,
+}
+
+/// All results of a sweep
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepReport {
+    pub target: SweepTarget,
+    pub results: Vec<SweepResult>,
+}
+
+impl SweepReport {
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "cache,size,associativity,line_size,instructions,cycles,cpi,\
+             icache_accesses,icache_misses,icache_miss_rate,\
+             dcache_accesses,dcache_misses,dcache_miss_rate\n",
+        );
+        for result in &self.results {
+            let stats = &result.stats;
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{:.4},{},{},{:.4},{},{},{:.4}\n",
+                self.target.name(),
+                result.point.size,
+                result.point.associativity,
+                result.point.line_size,
+                stats.instructions,
+                stats.cycles,
+                stats.cpi,
+                stats.icache_accesses,
+                stats.icache_misses,
+                stats.icache_miss_rate,
+                stats.dcache_accesses,
+                stats.dcache_misses,
+                stats.dcache_miss_rate
+            ));
+        }
+        csv
+    }
+}
+
+/// Runs a program once for every point of a sweep specification
+pub struct CacheSweep {
+    pub spec: SweepSpec,
+    pub pipeline_config: PipelineConfig,
+    pub instr_cache_config: CacheConfig,
+    pub data_cache_config: CacheConfig,
+    pub memory_size: usize,
+}
+
+impl CacheSweep {
+    pub fn new(spec: SweepSpec, pipeline_config: PipelineConfig, memory_size: usize) -> Self {
+        Self {
+            spec,
+            pipeline_config,
+            instr_cache_config: CacheConfig::new(32768, 4, 64),
+            data_cache_config: CacheConfig::new(32768, 4, 64),
+            memory_size,
+        }
+    }
+
+    /// Simulate every point; `load` places the program in each fresh simulator
+    pub fn run<F: FnMut(&mut Simulator)>(&self, mut load: F) -> SweepReport {
+        let results = self
+            .spec
+            .points()
+            .into_iter()
+            .map(|point| SweepResult {
+                point,
+                stats: self.run_point(point, &mut load),
+            })
+            .collect();
+
+        SweepReport {
+            target: self.spec.cache,
+            results,
+        }
+    }
+
+    fn run_point<F: FnMut(&mut Simulator)>(
+        &self,
+        point: SweepPoint,
+        load: &mut F,
+    ) -> IntervalSample {
+        let (instr_cache_config, data_cache_config) = match self.spec.cache {
+            SweepTarget::Instruction => (
+                point.apply(&self.instr_cache_config),
+                self.data_cache_config.clone(),
+            ),
+            SweepTarget::Data => (
+                self.instr_cache_config.clone(),
+                point.apply(&self.data_cache_config),
+            ),
+            SweepTarget::Both => (
+                point.apply(&self.instr_cache_config),
+                point.apply(&self.data_cache_config),
+            ),
+        };
+
+        let mut simulator = Simulator::new(
+            self.pipeline_config.clone(),
+            instr_cache_config,
+            data_cache_config,
+            self.memory_size,
+        );
+        simulator.visualization = None;
+        load(&mut simulator);
+        simulator.run();
+
+        simulator.stats_report().total
+    }
+}
@@ -0,0 +1,308 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// cache_validation.rs
+//
+// This file contains the cross-check of the cache model against closed-form
+// miss rates. Synthetic address streams (strided sweeps over a working set
+// and uniformly random words within it) are fed straight into a Cache, and
+// the measured miss rate is compared with what the cache geometry predicts:
+// compulsory misses only when each set's share of the working set fits in
+// its ways, a miss on every block otherwise for sweeps under LRU or FIFO,
+// and a hit rate of ways over blocks per set for random accesses.
+
+use super::compare::parse_cache;
+use super::components::Cache;
+use super::config::{CacheConfig, ReplacementPolicy};
+use super::stress::AccessPattern;
+use crate::functional_simulator::memory::Memory;
+use std::fmt;
+
+/// Miss rates of sweeps follow exactly from the geometry
+pub const SWEEP_TOLERANCE: f64 = 1e-9;
+
+/// Random streams only match their expectation statistically
+pub const RANDOM_TOLERANCE: f64 = 0.02;
+
+/// An address stream to run through one cache
+#[derive(Clone, Debug)]
+pub struct MissRateCheck {
+    pub cache: CacheConfig,
+    pub pattern: AccessPattern,
+    /// Bytes the stream touches, a power of two starting at address 0
+    pub working_set: usize,
+    /// Sweeps over the working set; random streams make as many accesses
+    /// as that many sequential sweeps would
+    pub passes: usize,
+    pub seed: u32,
+}
+
+/// The measured and expected miss rates of one check
+#[derive(Clone, Debug)]
+pub struct MissRateResult {
+    pub check: MissRateCheck,
+    pub accesses: usize,
+    pub misses: usize,
+    pub expected: f64,
+    pub tolerance: f64,
+}
+
+impl MissRateCheck {
+    pub fn new(cache: CacheConfig, pattern: AccessPattern, working_set: usize) -> Self {
+        Self {
+            cache,
+            pattern,
+            working_set,
+            passes: 4,
+            seed: 1,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let cache = &self.cache;
+        for (name, value) in [
+            ("cache size", cache.size),
+            ("associativity", cache.associativity),
+            ("block size", cache.block_size),
+            ("working set", self.working_set),
+        ] {
+            if !value.is_power_of_two() {
+                return Err(format!("{} must be a power of two, found {}", name, value));
+            }
+        }
+        if cache.block_size < 4 || cache.associativity * cache.block_size > cache.size {
+            return Err(format!(
+                "cache {}/{}/{} has no whole set of word-sized blocks",
+                cache.size, cache.associativity, cache.block_size
+            ));
+        }
+        if cache.prefetch_enabled {
+            return Err("prefetching has no closed-form miss rate".to_string());
+        }
+        if self.working_set < 4 || self.passes == 0 {
+            return Err("the stream must make at least one access".to_string());
+        }
+        if let AccessPattern::Stride(stride) = self.pattern {
+            let stride = stride as usize;
+            if stride < 4 || !stride.is_power_of_two() || stride > self.working_set {
+                return Err(format!(
+                    "stride must be a power of two from 4 to the working set, found {}",
+                    stride
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of accesses the stream makes
+    pub fn accesses(&self) -> usize {
+        match self.pattern {
+            AccessPattern::Stride(stride) => self.working_set / stride as usize * self.passes,
+            AccessPattern::Random => self.working_set / 4 * self.passes,
+        }
+    }
+
+    /// The byte addresses of the stream, in order
+    pub fn addresses(&self) -> Vec<usize> {
+        match self.pattern {
+            AccessPattern::Stride(stride) => (0..self.accesses())
+                .map(|index| index * stride as usize % self.working_set)
+                .collect(),
+            AccessPattern::Random => {
+                let words = self.working_set / 4;
+                let mut state = self.seed.wrapping_mul(0x9E37_79B9) | 1;
+                (0..self.accesses())
+                    .map(|_| {
+                        // xorshift32
+                        state ^= state << 13;
+                        state ^= state >> 17;
+                        state ^= state << 5;
+                        (state as usize % words) * 4
+                    })
+                    .collect()
+            },
+        }
+    }
+
+    /// The miss rate the geometry predicts, with how far a measurement may
+    /// stray from it
+    pub fn expected(&self) -> Result<(f64, f64), String> {
+        self.validate()?;
+        let cache = &self.cache;
+        let sets = cache.size / (cache.associativity * cache.block_size);
+        let blocks = (self.working_set / cache.block_size).max(1);
+        let accesses = self.accesses() as f64;
+        match self.pattern {
+            AccessPattern::Stride(stride) => {
+                let stride = stride as usize;
+                // Blocks visited per sweep, and the sets they fall into
+                let step = (stride / cache.block_size).max(1);
+                let visited = (self.working_set / stride).min(blocks);
+                let used_sets = (sets / step.min(sets)).min(visited);
+                let per_set = visited / used_sets;
+                let misses = if per_set <= cache.associativity {
+                    visited
+                } else {
+                    match cache.replacement_policy {
+                        // Each block is the oldest in its set when it
+                        // comes round again
+                        ReplacementPolicy::LRU | ReplacementPolicy::FIFO => visited * self.passes,
+                        policy => {
+                            return Err(format!(
+                                "{} replacement has no closed-form miss rate for sweeps",
+                                policy.name()
+                            ))
+                        },
+                    }
+                };
+                Ok((misses as f64 / accesses, SWEEP_TOLERANCE))
+            },
+            AccessPattern::Random => {
+                if cache.replacement_policy == ReplacementPolicy::LFU {
+                    return Err("lfu replacement has no closed-form miss rate".to_string());
+                }
+                let rate = if self.working_set <= cache.size {
+                    // Only first touches miss; each block is touched with
+                    // probability 1 - (1 - 1/blocks)^accesses
+                    let blocks = blocks as f64;
+                    blocks * (1.0 - (1.0 - 1.0 / blocks).powf(accesses)) / accesses
+                } else {
+                    // Once full, a set holds `ways` of its blocks, whichever
+                    // they are, and the next access is equally likely to
+                    // be any of them
+                    1.0 - cache.size as f64 / self.working_set as f64
+                };
+                Ok((rate, RANDOM_TOLERANCE))
+            },
+        }
+    }
+
+    /// Run the stream through a cold cache and compare its miss rate with
+    /// the expected one
+    pub fn run(&self) -> Result<MissRateResult, String> {
+        let (expected, tolerance) = self.expected()?;
+        let memory = Memory::new(self.working_set.max(self.cache.block_size));
+        let mut cache = Cache::new(self.cache.clone(), memory);
+        for address in self.addresses() {
+            if cache.read(address).is_none() {
+                return Err(format!("the cache could not read 0x{:08X}", address));
+            }
+        }
+        let stats = cache.get_statistics();
+        Ok(MissRateResult {
+            check: self.clone(),
+            accesses: stats.accesses,
+            misses: stats.misses,
+            expected,
+            tolerance,
+        })
+    }
+}
+
+impl fmt::Display for MissRateCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cache={}/{}/{} replacement={} pattern={} working-set={}",
+            self.cache.size,
+            self.cache.associativity,
+            self.cache.block_size,
+            self.cache.replacement_policy.name(),
+            self.pattern,
+            self.working_set
+        )
+    }
+}
+
+impl MissRateResult {
+    pub fn measured(&self) -> f64 {
+        self.misses as f64 / self.accesses.max(1) as f64
+    }
+
+    pub fn passed(&self) -> bool {
+        (self.measured() - self.expected).abs() <= self.tolerance
+    }
+}
+
+impl fmt::Display for MissRateResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: measured {:.4} ({} of {}), expected {:.4}",
+            if self.passed() { "ok" } else { "FAILED" },
+            self.check,
+            self.measured(),
+            self.misses,
+            self.accesses,
+            self.expected
+        )
+    }
+}
+
+/// A check of one cache given as SIZE/ASSOC/LINE, the form `compare` takes
+pub fn parse_check(
+    cache: &str,
+    replacement: &str,
+    pattern: AccessPattern,
+    working_set: usize,
+) -> Result<MissRateCheck, String> {
+    let mut cache = parse_cache(cache)?;
+    cache.replacement_policy = ReplacementPolicy::from_name(replacement)
+        .ok_or_else(|| format!("Unknown replacement policy '{}'", replacement))?;
+    let check = MissRateCheck::new(cache, pattern, working_set);
+    check.validate()?;
+    Ok(check)
+}
+
+/// Checks covering fitting and thrashing sweeps, strides that alias onto
+/// a few sets, and random streams smaller and larger than the cache
+pub fn standard_checks() -> Vec<MissRateCheck> {
+    let mut checks = Vec::new();
+    for &(size, ways, block) in &[(4096, 1, 32), (8192, 2, 32), (16384, 4, 64), (4096, 64, 64)] {
+        for &policy in &[ReplacementPolicy::LRU, ReplacementPolicy::FIFO] {
+            let mut cache = CacheConfig::new(size, ways, block);
+            cache.replacement_policy = policy;
+            for &working_set in &[size / 2, size, size * 2] {
+                for &stride in &[4, block as u32, 4 * block as u32, size as u32 / 2] {
+                    if stride as usize <= working_set {
+                        checks.push(MissRateCheck::new(
+                            cache.clone(),
+                            AccessPattern::Stride(stride),
+                            working_set,
+                        ));
+                    }
+                }
+            }
+        }
+        for &policy in &[ReplacementPolicy::LRU, ReplacementPolicy::Random] {
+            let mut cache = CacheConfig::new(size, ways, block);
+            cache.replacement_policy = policy;
+            for &working_set in &[size / 4, size * 4] {
+                checks.push(MissRateCheck::new(
+                    cache.clone(),
+                    AccessPattern::Random,
+                    working_set,
+                ));
+            }
+        }
+    }
+    checks
+}
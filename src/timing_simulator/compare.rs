@@ -0,0 +1,466 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// compare.rs
+//
+// This file contains the side-by-side comparison of two timing
+// configurations. The same program is run under both configurations with
+// the per-instruction timeline enabled, and the two timelines are aligned
+// instruction by instruction so the places where stalls or branch
+// mispredictions differ stand out.
+
+use super::config::{BranchPredictorType, CacheConfig, ControlHazardScheme, PipelineConfig};
+use super::simulator::Simulator;
+use super::stats::InstructionTiming;
+use super::tlb::TlbConfig;
+use super::watch::parse_address;
+use crate::functional_simulator::simulator::decode_instruction;
+use std::ops::RangeInclusive;
+
+/// A named timing configuration built from a `key=value,...` specification
+#[derive(Clone, Debug)]
+pub struct TimingVariant {
+    pub name: String,
+    pub pipeline_config: PipelineConfig,
+    pub instr_cache_config: CacheConfig,
+    pub data_cache_config: CacheConfig,
+}
+
+impl Default for TimingVariant {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            pipeline_config: PipelineConfig::new(5)
+                .with_forwarding(true)
+                .with_branch_prediction(true, BranchPredictorType::TwoBit),
+            instr_cache_config: CacheConfig::new(32768, 4, 64),
+            data_cache_config: CacheConfig::new(32768, 4, 64),
+        }
+    }
+}
+
+impl TimingVariant {
+    /// Parse a specification such as `forwarding=off,dcache=1024/1/16`.
+    ///
+    /// Keys: `forwarding`, `prediction` and `caches` (`on`/`off`),
+    /// `predictor` (`static`, `onebit`, `twobit`, `correlating`,
+    /// `tournament`), `control` (`flush`, `stall`, `delay-slot`), `icache`
+    /// and `dcache` (`SIZE/ASSOC/LINE`), `miss-penalty`, `write-policy`
+    /// (`write-back`, `write-through`) and `write-allocate` (`on`/`off`) of
+    /// the data cache, `memory-port`
+    /// (`split`, `unified`), `uncached` (`START-END` ranges joined by `+`,
+    /// or `none`), `tlb` (`ENTRIES/ASSOC` or `off`), `page-walk` and `asid`
+    /// (`tagged`, `flush`).
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut variant = TimingVariant::default();
+        variant.apply(spec)?;
+        if !spec.trim().is_empty() {
+            variant.name = spec.trim().to_string();
+        }
+        Ok(variant)
+    }
+
+    /// Change the settings named in `spec`, leaving the others as they are
+    pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+        let spec = spec.trim();
+        if spec.is_empty() {
+            return Ok(());
+        }
+
+        // Validate everything before changing anything
+        let mut updated = self.clone();
+        for setting in spec.split(',') {
+            let (key, value) = match setting.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(format!("Expected key=value, found '{}'", setting)),
+            };
+            let config = &mut updated.pipeline_config;
+            match key {
+                "forwarding" => config.forwarding_enabled = parse_switch(value)?,
+                "prediction" => config.branch_prediction_enabled = parse_switch(value)?,
+                "caches" => config.caches_enabled = parse_switch(value)?,
+                "predictor" => {
+                    config.branch_predictor_type = match value {
+                        "static" => BranchPredictorType::Static,
+                        "onebit" => BranchPredictorType::OneBit,
+                        "twobit" => BranchPredictorType::TwoBit,
+                        "correlating" => BranchPredictorType::Correlating,
+                        "tournament" => BranchPredictorType::Tournament,
+                        _ => return Err(format!("Unknown branch predictor '{}'", value)),
+                    }
+                },
+                "control" => {
+                    config.control_hazard_scheme = ControlHazardScheme::from_name(value)
+                        .ok_or_else(|| format!("Unknown control hazard scheme '{}'", value))?
+                },
+                "memory-port" => {
+                    config.unified_memory_port = match value {
+                        "split" => false,
+                        "unified" => true,
+                        _ => return Err(format!("Unknown memory port '{}'", value)),
+                    }
+                },
+                "write-policy" => {
+                    updated.data_cache_config.write_back = match value {
+                        "write-back" => true,
+                        "write-through" => false,
+                        _ => return Err(format!("Unknown write policy '{}'", value)),
+                    }
+                },
+                "write-allocate" => updated.data_cache_config.write_allocate = parse_switch(value)?,
+                "tlb" => config.tlb_config = parse_tlb(value)?,
+                "page-walk" => {
+                    let cycles = value
+                        .parse()
+                        .map_err(|_| format!("Invalid page-walk penalty '{}'", value))?;
+                    let tlb = config
+                        .tlb_config
+                        .take()
+                        .ok_or_else(|| "page-walk needs a TLB, set tlb first".to_string())?;
+                    config.tlb_config = Some(tlb.with_walk_penalty(cycles));
+                },
+                "asid" => {
+                    let tagged = match value {
+                        "tagged" => true,
+                        "flush" => false,
+                        _ => return Err(format!("Unknown ASID mode '{}'", value)),
+                    };
+                    let tlb = config
+                        .tlb_config
+                        .as_mut()
+                        .ok_or_else(|| "asid needs a TLB, set tlb first".to_string())?;
+                    tlb.tagged = tagged;
+                },
+                "uncached" => config.uncached_regions = parse_regions(value)?,
+                "icache" => updated.instr_cache_config = parse_cache(value)?,
+                "dcache" => updated.data_cache_config = parse_cache(value)?,
+                "miss-penalty" => {
+                    let penalty = value
+                        .parse()
+                        .map_err(|_| format!("Invalid miss penalty '{}'", value))?;
+                    updated.instr_cache_config.miss_penalty = penalty;
+                    updated.data_cache_config.miss_penalty = penalty;
+                },
+                _ => return Err(format!("Unknown setting '{}'", key)),
+            }
+        }
+
+        *self = updated;
+        Ok(())
+    }
+
+    /// All settings in the `key=value` form accepted by `parse`
+    pub fn describe(&self) -> String {
+        let config = &self.pipeline_config;
+        let switch = |enabled: bool| if enabled { "on" } else { "off" };
+        let geometry = |cache: &CacheConfig| {
+            format!(
+                "{}/{}/{}",
+                cache.size, cache.associativity, cache.block_size
+            )
+        };
+        format!(
+            "forwarding={},prediction={},predictor={},caches={},icache={},dcache={},miss-penalty={},write-policy={},write-allocate={},memory-port={},uncached={},{},control={}",
+            switch(config.forwarding_enabled),
+            switch(config.branch_prediction_enabled),
+            format!("{:?}", config.branch_predictor_type).to_lowercase(),
+            switch(config.caches_enabled),
+            geometry(&self.instr_cache_config),
+            geometry(&self.data_cache_config),
+            self.data_cache_config.miss_penalty,
+            if self.data_cache_config.write_back {
+                "write-back"
+            } else {
+                "write-through"
+            },
+            switch(self.data_cache_config.write_allocate),
+            if config.unified_memory_port {
+                "unified"
+            } else {
+                "split"
+            },
+            describe_regions(&config.uncached_regions),
+            match &config.tlb_config {
+                Some(tlb) => format!(
+                    "tlb={}/{},page-walk={},asid={}",
+                    tlb.entries,
+                    tlb.associativity,
+                    tlb.walk_penalty,
+                    if tlb.tagged { "tagged" } else { "flush" }
+                ),
+                None => "tlb=off".to_string(),
+            },
+            config.control_hazard_scheme.name()
+        )
+    }
+}
+
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value {
+        "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        _ => Err(format!("Expected on or off, found '{}'", value)),
+    }
+}
+
+fn parse_tlb(value: &str) -> Result<Option<TlbConfig>, String> {
+    if value == "off" {
+        return Ok(None);
+    }
+    let fields: Vec<usize> = value
+        .split('/')
+        .map(|field| field.parse::<usize>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid TLB geometry '{}'", value))?;
+    match fields.as_slice() {
+        &[entries, associativity]
+            if entries > 0 && associativity > 0 && entries % associativity == 0 =>
+        {
+            Ok(Some(TlbConfig::new(entries, associativity)))
+        },
+        _ => Err(format!(
+            "TLB geometry must be ENTRIES/ASSOC with ENTRIES a multiple of ASSOC, found '{}'",
+            value
+        )),
+    }
+}
+
+// Address ranges such as `0xFF000000-0xFFFFFFFF+0x1000-0x1FFF`
+fn parse_regions(value: &str) -> Result<Vec<RangeInclusive<u32>>, String> {
+    if value == "none" {
+        return Ok(Vec::new());
+    }
+    value
+        .split('+')
+        .map(|region| {
+            let (start, end) = region
+                .split_once('-')
+                .ok_or_else(|| format!("Expected START-END, found '{}'", region))?;
+            let (start, end) = (parse_address(start)?, parse_address(end)?);
+            if start > end {
+                return Err(format!("Region '{}' ends before it starts", region));
+            }
+            Ok(start..=end)
+        })
+        .collect()
+}
+
+fn describe_regions(regions: &[RangeInclusive<u32>]) -> String {
+    if regions.is_empty() {
+        return "none".to_string();
+    }
+    regions
+        .iter()
+        .map(|region| format!("0x{:08X}-0x{:08X}", region.start(), region.end()))
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+pub(crate) fn parse_cache(value: &str) -> Result<CacheConfig, String> {
+    let fields: Vec<usize> = value
+        .split('/')
+        .map(|field| field.parse::<usize>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid cache geometry '{}'", value))?;
+
+    match fields.as_slice() {
+        &[size, associativity, line_size]
+            if associativity > 0 && line_size > 0 && size % (associativity * line_size) == 0 =>
+        {
+            Ok(CacheConfig::new(size, associativity, line_size))
+        },
+        _ => Err(format!(
+            "Cache geometry must be SIZE/ASSOC/LINE with SIZE a multiple of ASSOC*LINE, found '{}'",
+            value
+        )),
+    }
+}
+
+/// Timelines of one program under two configurations
+#[derive(Clone, Debug)]
+pub struct PipelineComparison {
+    pub baseline_name: String,
+    pub variant_name: String,
+    pub baseline: Vec<InstructionTiming>,
+    pub variant: Vec<InstructionTiming>,
+    pub baseline_cycles: usize,
+    pub variant_cycles: usize,
+}
+
+impl PipelineComparison {
+    /// Run the program under both configurations; `load` places the program
+    /// in each fresh simulator
+    pub fn run<F: FnMut(&mut Simulator)>(
+        baseline: &TimingVariant,
+        variant: &TimingVariant,
+        memory_size: usize,
+        mut load: F,
+    ) -> Self {
+        let (baseline_timeline, baseline_cycles) =
+            Self::run_variant(baseline, memory_size, &mut load);
+        let (variant_timeline, variant_cycles) = Self::run_variant(variant, memory_size, &mut load);
+
+        Self {
+            baseline_name: baseline.name.clone(),
+            variant_name: variant.name.clone(),
+            baseline: baseline_timeline,
+            variant: variant_timeline,
+            baseline_cycles,
+            variant_cycles,
+        }
+    }
+
+    fn run_variant<F: FnMut(&mut Simulator)>(
+        variant: &TimingVariant,
+        memory_size: usize,
+        load: &mut F,
+    ) -> (Vec<InstructionTiming>, usize) {
+        let mut simulator = Simulator::new(
+            variant.pipeline_config.clone(),
+            variant.instr_cache_config.clone(),
+            variant.data_cache_config.clone(),
+            memory_size,
+        );
+        simulator.visualization = None;
+        // What the program prints would interleave with the report
+        simulator.captured_output = Some(Vec::new());
+        simulator.enable_timeline();
+        load(&mut simulator);
+        simulator.run();
+
+        (
+            simulator.timeline.take().unwrap_or_default(),
+            simulator.run_totals.cycles,
+        )
+    }
+
+    /// Indices of instructions whose timing differs between the two runs
+    pub fn differences(&self) -> Vec<usize> {
+        let length = self.baseline.len().max(self.variant.len());
+        (0..length)
+            .filter(|&i| match (self.baseline.get(i), self.variant.get(i)) {
+                (Some(a), Some(b)) => {
+                    a.pc != b.pc
+                        || a.stall_cycles != b.stall_cycles
+                        || a.mispredicted != b.mispredicted
+                },
+                _ => true,
+            })
+            .collect()
+    }
+
+    /// First instruction where the two runs diverge
+    pub fn first_divergence(&self) -> Option<usize> {
+        self.differences().first().copied()
+    }
+
+    /// Aligned per-instruction table; only differing rows unless `all` is set
+    pub fn render(&self, all: bool) -> String {
+        let differences = self.differences();
+        let mut result = String::new();
+        result.push_str("Pipeline Comparison:\n");
+        result.push_str(&format!("  A: {}\n", self.baseline_name));
+        result.push_str(&format!("  B: {}\n\n", self.variant_name));
+        result.push_str(&format!(
+            "    {:>5}  {:<10}  {:<40} {:>5} {:>7} {:>5} {:>7} {:>6}\n",
+            "#", "PC", "Instruction", "A cyc", "A stall", "B cyc", "B stall", "Delta"
+        ));
+
+        let length = self.baseline.len().max(self.variant.len());
+        for i in 0..length {
+            let differs = differences.binary_search(&i).is_ok();
+            if !all && !differs {
+                continue;
+            }
+            let a = self.baseline.get(i);
+            let b = self.variant.get(i);
+            let first = match a.or(b) {
+                Some(first) => first,
+                None => continue,
+            };
+
+            let mut text = format!("{:?}", decode_instruction(first.word));
+            text.truncate(40);
+            let delta = b.map_or(0, |b| b.cycles as isize) - a.map_or(0, |a| a.cycles as isize);
+            result.push_str(&format!(
+                "  {} {:>5}  0x{:08X}  {:<40} {:>5} {:>7} {:>5} {:>7} {:>+6}\n",
+                if differs { '*' } else { ' ' },
+                first.index,
+                first.pc,
+                text,
+                format_cycles(a),
+                format_stalls(a),
+                format_cycles(b),
+                format_stalls(b),
+                delta
+            ));
+        }
+
+        result.push_str(&format!(
+            "\n  Instructions: A {}, B {}\n",
+            self.baseline.len(),
+            self.variant.len()
+        ));
+        result.push_str(&format!(
+            "  Cycles: A {}, B {} ({:+})\n",
+            self.baseline_cycles,
+            self.variant_cycles,
+            self.variant_cycles as isize - self.baseline_cycles as isize
+        ));
+        let stalls = |timeline: &[InstructionTiming]| -> usize {
+            timeline.iter().map(|t| t.stall_cycles).sum()
+        };
+        let mispredictions =
+            |timeline: &[InstructionTiming]| timeline.iter().filter(|t| t.mispredicted).count();
+        result.push_str(&format!(
+            "  Stall cycles: A {}, B {}\n",
+            stalls(&self.baseline),
+            stalls(&self.variant)
+        ));
+        result.push_str(&format!(
+            "  Mispredictions: A {}, B {}\n",
+            mispredictions(&self.baseline),
+            mispredictions(&self.variant)
+        ));
+        match self.first_divergence() {
+            Some(index) => result.push_str(&format!(
+                "  {} instructions differ, first at #{}\n",
+                differences.len(),
+                index
+            )),
+            None => result.push_str("  Timelines are identical\n"),
+        }
+        result
+    }
+}
+
+fn format_cycles(timing: Option<&InstructionTiming>) -> String {
+    timing.map_or("-".to_string(), |t| t.cycles.to_string())
+}
+
+// Stall cycles, with an M marking a branch misprediction
+fn format_stalls(timing: Option<&InstructionTiming>) -> String {
+    match timing {
+        Some(t) if t.mispredicted => format!("{} M", t.stall_cycles),
+        Some(t) => t.stall_cycles.to_string(),
+        None => "-".to_string(),
+    }
+}
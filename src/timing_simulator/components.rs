@@ -27,8 +27,11 @@
 // interaction between different levels of cache.
 
 use super::config::{CacheConfig, PrefetchStrategy as ConfigPrefetchStrategy, ReplacementPolicy};
+use super::events::{CacheKind, EventKind, PendingEvents};
 use crate::functional_simulator::memory::Memory;
+use crate::utils::limits::MemoryUsage;
 use std::collections::VecDeque;
+use std::mem;
 use std::time::Instant;
 
 // Helper function to create a prefetcher from a cache config
@@ -99,6 +102,16 @@ impl CacheSet {
         }
     }
 
+    /// Host memory taken by the set's lines and replacement state
+    pub fn host_bytes(&self) -> usize {
+        let lines: usize = self
+            .lines
+            .iter()
+            .map(|line| mem::size_of::<CacheLine>() + line.data.len())
+            .sum();
+        mem::size_of::<Self>() + lines + self.lru_queue.len() * mem::size_of::<usize>()
+    }
+
     // Find a line in the set with the given tag
     pub fn find_line(&self, tag: usize) -> Option<usize> {
         for (i, line) in self.lines.iter().enumerate() {
@@ -173,6 +186,11 @@ pub struct CacheStatistics {
     pub write_hits: usize,
     pub evictions: usize,
     pub writebacks: usize,
+    /// Valid lines replaced while dirty, each written back on eviction
+    pub dirty_evictions: usize,
+    /// Bytes written to the next level: whole lines written back, and the
+    /// stores a write-through or no-write-allocate cache passes on
+    pub write_traffic: usize,
     pub total_access_time: u128, // in nanoseconds
 }
 
@@ -188,6 +206,8 @@ impl CacheStatistics {
             write_hits: 0,
             evictions: 0,
             writebacks: 0,
+            dirty_evictions: 0,
+            write_traffic: 0,
             total_access_time: 0,
         }
     }
@@ -255,6 +275,8 @@ impl CacheStatistics {
         ));
         result.push_str(&format!("  Evictions: {}\n", self.evictions));
         result.push_str(&format!("  Writebacks: {}\n", self.writebacks));
+        result.push_str(&format!("  Dirty Evictions: {}\n", self.dirty_evictions));
+        result.push_str(&format!("  Write Traffic: {} bytes\n", self.write_traffic));
         result.push_str(&format!(
             "  Average Access Time: {:.2} ns\n",
             self.average_access_time()
@@ -265,6 +287,7 @@ impl CacheStatistics {
 }
 
 // A simple cache in the memory hierarchy
+#[derive(Clone)]
 pub struct Cache {
     pub config: CacheConfig,
     pub sets: Vec<CacheSet>,
@@ -274,6 +297,8 @@ pub struct Cache {
     pub allocation_policy: AllocationPolicy,
     pub prefetcher: Option<Prefetcher>,
     pub next_level: Option<Box<Cache>>, // Pointer to next cache level in hierarchy
+    /// Lookups emitted for the event log as this L1 cache, when enabled
+    pub events: Option<(CacheKind, PendingEvents)>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -321,11 +346,45 @@ impl Cache {
             sets,
             memory,
             stats: CacheStatistics::new(),
-            write_policy: WritePolicy::WriteBack,
-            allocation_policy: AllocationPolicy::WriteAllocate,
+            write_policy: if config.write_back {
+                WritePolicy::WriteBack
+            } else {
+                WritePolicy::WriteThrough
+            },
+            allocation_policy: if config.write_allocate {
+                AllocationPolicy::WriteAllocate
+            } else {
+                AllocationPolicy::NoWriteAllocate
+            },
             prefetcher: create_prefetcher(&config),
             next_level,
+            events: None,
+        }
+    }
+
+    // Emit a lookup to the event log, if it is enabled
+    fn emit_access(&mut self, address: usize, hit: bool) {
+        if let Some((cache, events)) = self.events.as_mut() {
+            events.emit(EventKind::CacheAccess {
+                cache: *cache,
+                address: address as u32,
+                hit,
+            });
+        }
+    }
+
+    /// Host memory held by this cache and the levels below it, including
+    /// each level's copy of the memory image
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage {
+            memory: self.memory.data.len(),
+            caches: self.sets.iter().map(CacheSet::host_bytes).sum(),
+            traces: 0,
+        };
+        if let Some(next_level) = &self.next_level {
+            usage += next_level.memory_usage();
         }
+        usage
     }
 
     // Read a block from the cache
@@ -380,6 +439,7 @@ impl Cache {
             // Update statistics
             self.stats.hits += 1;
             self.stats.read_hits += 1;
+            self.emit_access(address, true);
             let latency = self.config.hit_latency;
             self.stats.total_access_time += latency as u128;
 
@@ -388,6 +448,7 @@ impl Cache {
 
         // Cache miss path
         self.stats.misses += 1;
+        self.emit_access(address, false);
 
         // Try next level cache if available
         if let Some(next_cache) = &mut self.next_level {
@@ -402,7 +463,11 @@ impl Cache {
         // Find a line to replace
         let (victim_idx, need_writeback, dirty_tag) = {
             let set = &self.sets[set_idx];
-            let victim_idx = set.find_invalid_line().unwrap_or_else(|| set.find_victim());
+            let victim_idx = set.find_invalid_line().unwrap_or_else(|| {
+                // No invalid lines, need to evict one
+                self.stats.evictions += 1;
+                set.find_victim()
+            });
             let cache_line = &set.lines[victim_idx];
             let need_writeback = cache_line.valid && cache_line.dirty;
             let dirty_tag = cache_line.tag;
@@ -412,7 +477,7 @@ impl Cache {
         // Handle writeback if needed
         if need_writeback {
             self.write_back_line(set_idx, victim_idx, dirty_tag);
-            self.stats.writebacks += 1;
+            self.stats.dirty_evictions += 1;
         }
 
         // Load the new block
@@ -441,7 +506,7 @@ impl Cache {
         };
 
         // Update statistics
-        let access_time = self.config.miss_penalty;
+        let access_time = self.config.hit_latency + self.config.miss_penalty;
         self.stats.total_access_time += access_time as u128;
 
         // Perform prefetching if enabled
@@ -490,6 +555,7 @@ impl Cache {
         // Handle writeback if needed
         if need_writeback {
             self.write_back_line(set_idx, victim_idx, dirty_tag);
+            self.stats.dirty_evictions += 1;
         }
 
         // Load the block
@@ -535,16 +601,8 @@ impl Cache {
 
                 if offset + value.len() <= cache_line.data.len() {
                     cache_line.data[offset..offset + value.len()].copy_from_slice(value);
-                    cache_line.dirty = true;
-
-                    // If write-through policy, also write to memory
-                    if self.write_policy == WritePolicy::WriteThrough {
-                        for (i, &byte) in value.iter().enumerate() {
-                            self.memory.write_byte(address + i, byte);
-                        }
-                        // Reset dirty bit since it's consistent with memory
-                        cache_line.dirty = false;
-                    }
+                    // A write-through line stays consistent with the next level
+                    cache_line.dirty = self.write_policy == WritePolicy::WriteBack;
 
                     self.config.hit_latency
                 } else {
@@ -558,9 +616,16 @@ impl Cache {
                 }
             };
 
+            // The write goes on through a write buffer, so it costs no
+            // more than a hit
+            if self.write_policy == WritePolicy::WriteThrough {
+                self.write_through(address, value);
+            }
+
             // Update statistics
             self.stats.hits += 1;
             self.stats.write_hits += 1;
+            self.emit_access(address, true);
             self.stats.total_access_time += result as u128;
 
             return result;
@@ -568,6 +633,7 @@ impl Cache {
 
         // Cache miss
         self.stats.misses += 1;
+        self.emit_access(address, false);
 
         // Try next level cache if available for write
         if let Some(next_cache) = &mut self.next_level {
@@ -598,7 +664,7 @@ impl Cache {
                 // Handle writeback if needed
                 if need_writeback {
                     self.write_back_line(set_idx, victim_idx, dirty_tag);
-                    self.stats.writebacks += 1;
+                    self.stats.dirty_evictions += 1;
                 }
 
                 // Load the block from memory
@@ -617,30 +683,41 @@ impl Cache {
                     if offset + value.len() <= cache_line.data.len() {
                         cache_line.data[offset..offset + value.len()].copy_from_slice(value);
                         cache_line.dirty = self.write_policy == WritePolicy::WriteBack;
-
-                        // If write-through policy, also write to memory
-                        if self.write_policy == WritePolicy::WriteThrough {
-                            for (i, &byte) in value.iter().enumerate() {
-                                self.memory.write_byte(address + i, byte);
-                            }
-                        }
                     }
                 }
+
+                // If write-through policy, also write to the next level
+                if self.write_policy == WritePolicy::WriteThrough {
+                    self.write_through(address, value);
+                }
             },
             AllocationPolicy::NoWriteAllocate => {
-                // Write directly to memory without allocating a cache line
-                for (i, &byte) in value.iter().enumerate() {
-                    self.memory.write_byte(address + i, byte);
-                }
+                // Write to the next level without allocating a cache line
+                self.write_through(address, value);
             },
         }
 
-        let access_time = self.config.miss_penalty;
+        let access_time = self.config.hit_latency + self.config.miss_penalty;
         self.stats.total_access_time += access_time as u128;
 
         access_time
     }
 
+    // Pass a store on to the next level, or to memory behind the last one
+    fn write_through(&mut self, address: usize, value: &[u8]) {
+        self.stats.write_traffic += value.len();
+        match &mut self.next_level {
+            Some(next_cache) => {
+                next_cache.write(address, value);
+            },
+            None => {
+                for (i, &byte) in value.iter().enumerate() {
+                    let _ = self.memory.write_byte(address + i, byte);
+                }
+            },
+        }
+    }
+
     // Load a block from memory into the cache
     fn load_block(&mut self, block_addr: usize, set_idx: usize, line_idx: usize, tag: usize) {
         let line = &mut self.sets[set_idx].lines[line_idx];
@@ -673,12 +750,14 @@ impl Cache {
         for i in 0..block_size {
             let mem_addr = block_addr + i;
             if mem_addr < self.memory.size {
-                self.memory.write_byte(mem_addr, data[i]);
+                let _ = self.memory.write_byte(mem_addr, data[i]);
             }
         }
 
         // Clear dirty bit
         self.sets[set_idx].lines[line_idx].dirty = false;
+        self.stats.writebacks += 1;
+        self.stats.write_traffic += block_size;
     }
 
     // Prefetch a block into the cache
@@ -736,7 +815,7 @@ impl Cache {
         (tag, set_idx, offset)
     }
 
-    // Flush the entire cache
+    // Flush the entire cache and the levels behind it
     pub fn flush(&mut self) {
         for set_idx in 0..self.sets.len() {
             for line_idx in 0..self.sets[set_idx].lines.len() {
@@ -754,6 +833,31 @@ impl Cache {
                 self.sets[set_idx].lines[line_idx].dirty = false;
             }
         }
+        if let Some(next_cache) = &mut self.next_level {
+            next_cache.flush();
+        }
+    }
+
+    // Evict the block holding an address from this cache and the levels
+    // behind it, writing it back first if it is dirty
+    pub fn flush_line(&mut self, address: usize) {
+        let (tag, set_idx, _) = self.decode_address(address);
+        for line_idx in 0..self.sets[set_idx].lines.len() {
+            let (present, dirty) = {
+                let line = &self.sets[set_idx].lines[line_idx];
+                (line.valid && line.tag == tag, line.dirty)
+            };
+            if present {
+                if dirty {
+                    self.write_back_line(set_idx, line_idx, tag);
+                }
+                self.sets[set_idx].lines[line_idx].valid = false;
+                self.sets[set_idx].lines[line_idx].dirty = false;
+            }
+        }
+        if let Some(next_cache) = &mut self.next_level {
+            next_cache.flush_line(address);
+        }
     }
 
     // Get cache statistics
@@ -768,6 +872,7 @@ impl Cache {
 }
 
 // Prefetcher for cache
+#[derive(Clone)]
 pub struct Prefetcher {
     strategy: ComponentPrefetchStrategy,
     prefetch_distance: usize,
@@ -780,7 +885,23 @@ pub enum ComponentPrefetchStrategy {
     Custom(Box<dyn CustomPrefetcher>),
 }
 
+impl Clone for ComponentPrefetchStrategy {
+    fn clone(&self) -> Self {
+        match self {
+            ComponentPrefetchStrategy::NextNBlocks(n) => ComponentPrefetchStrategy::NextNBlocks(*n),
+            ComponentPrefetchStrategy::AdjacentSets(n) => {
+                ComponentPrefetchStrategy::AdjacentSets(*n)
+            },
+            ComponentPrefetchStrategy::Stride(stride) => ComponentPrefetchStrategy::Stride(*stride),
+            ComponentPrefetchStrategy::Custom(prefetcher) => {
+                ComponentPrefetchStrategy::Custom(prefetcher.clone_box())
+            },
+        }
+    }
+}
+
 // Define a multi-level cache hierarchy
+#[derive(Clone)]
 pub struct CacheHierarchy {
     pub l1_data_cache: Cache,
     pub l1_instr_cache: Cache,
@@ -788,6 +909,7 @@ pub struct CacheHierarchy {
 }
 
 // Statistics for the entire cache hierarchy
+#[derive(Clone)]
 pub struct CacheHierarchyStats {
     pub miss_latency: usize,
     pub hit_latency: usize,
@@ -821,6 +943,11 @@ impl CacheHierarchy {
         }
     }
 
+    /// Host memory held by the instruction and data caches
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.l1_data_cache.memory_usage() + self.l1_instr_cache.memory_usage()
+    }
+
     pub fn read_data(&mut self, address: usize) -> Option<(Vec<u8>, usize)> {
         self.l1_data_cache.read(address)
     }
@@ -833,11 +960,32 @@ impl CacheHierarchy {
         self.l1_instr_cache.read(address)
     }
 
+    /// Have both L1 caches emit their lookups for the event log
+    pub fn enable_events(&mut self) {
+        self.l1_instr_cache.events = Some((CacheKind::Instruction, PendingEvents::new()));
+        self.l1_data_cache.events = Some((CacheKind::Data, PendingEvents::new()));
+    }
+
+    /// Lookups emitted since the last call, instruction cache first
+    pub fn take_events(&mut self) -> Vec<EventKind> {
+        let mut events = Vec::new();
+        for cache in [&mut self.l1_instr_cache, &mut self.l1_data_cache] {
+            if let Some((_, pending)) = cache.events.as_mut() {
+                events.extend(pending.take());
+            }
+        }
+        events
+    }
+
     pub fn flush(&mut self) {
         self.l1_data_cache.flush();
         self.l1_instr_cache.flush();
     }
 
+    pub fn flush_data_line(&mut self, address: usize) {
+        self.l1_data_cache.flush_line(address);
+    }
+
     pub fn update_stats(&mut self) {
         // Calculate average access time for the hierarchy
         let l1d_stats = self.l1_data_cache.get_statistics();
@@ -24,7 +24,18 @@
 // This file contains the configuration structs for the timing simulator.
 // It defines the configuration for the pipeline, caches, and branch predictor.
 
-#[derive(Debug, Clone)]
+use super::components::{CacheLine, CacheSet};
+use super::pipeline::PipelineStageType;
+use super::tlb::TlbConfig;
+use crate::functional_simulator::registers::DEFAULT_CLOCK_HZ;
+use std::mem;
+use std::ops::RangeInclusive;
+
+/// Addresses from 0xFF000000 up, where the framebuffer, console, disk and
+/// network registers are mapped
+pub const DEVICE_REGION: RangeInclusive<u32> = 0xFF00_0000..=0xFFFF_FFFF;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct CacheConfig {
     pub size: usize,          // Cache size in bytes
     pub associativity: usize, // Number of ways (lines per set)
@@ -62,6 +73,23 @@ impl CacheConfig {
             prefetch_strategy: PrefetchStrategy::NextNBlocks(1),
         }
     }
+
+    /// Write-back or write-through, with or without allocating a line on a
+    /// write miss
+    pub fn with_write_policy(mut self, write_back: bool, write_allocate: bool) -> Self {
+        self.write_back = write_back;
+        self.write_allocate = write_allocate;
+        self
+    }
+
+    /// Host memory a cache with this configuration takes for its lines and
+    /// replacement state
+    pub fn host_bytes(&self) -> usize {
+        let lines = self.size / self.block_size;
+        let sets = lines / self.associativity;
+        sets * mem::size_of::<CacheSet>()
+            + lines * (mem::size_of::<CacheLine>() + self.block_size + mem::size_of::<usize>())
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -72,6 +100,28 @@ pub enum ReplacementPolicy {
     LFU,    // Least Frequently Used
 }
 
+impl ReplacementPolicy {
+    /// Name used on the command line: `lru`, `fifo`, `random` or `lfu`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReplacementPolicy::LRU => "lru",
+            ReplacementPolicy::FIFO => "fifo",
+            ReplacementPolicy::Random => "random",
+            ReplacementPolicy::LFU => "lfu",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "lru" => Some(ReplacementPolicy::LRU),
+            "fifo" => Some(ReplacementPolicy::FIFO),
+            "random" => Some(ReplacementPolicy::Random),
+            "lfu" => Some(ReplacementPolicy::LFU),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PrefetchStrategy {
     NextNBlocks(usize),  // Prefetch the next N sequential blocks
@@ -80,31 +130,90 @@ pub enum PrefetchStrategy {
     Custom,              // Custom prefetch strategy
 }
 
+/// A named pipeline stage. The kind decides where results are produced and
+/// operands are needed, so hazards follow the stage layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StageConfig {
+    pub name: String,
+    pub kind: PipelineStageType,
+}
+
+impl StageConfig {
+    pub fn new(name: &str, kind: PipelineStageType) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct PipelineConfig {
     pub num_stages: usize,
     pub stage_latencies: Vec<usize>,
+    /// Stage names and kinds, fetch first
+    pub stages: Vec<StageConfig>,
     pub forwarding_enabled: bool,
     pub branch_prediction_enabled: bool,
     pub branch_predictor_type: BranchPredictorType,
+    /// How the in-order pipeline handles the instructions behind a branch
+    pub control_hazard_scheme: ControlHazardScheme,
     /// Tomasulo out-of-order execution settings
     pub tomasulo_config: Option<TomasuloConfig>,
     pub superscalar_width: usize,
+    /// When false every fetch and data access pays the memory latency
+    pub caches_enabled: bool,
+    /// Instruction fetch and data accesses share one memory port, so a
+    /// fetch waits while a load or store ahead of it goes to memory
+    pub unified_memory_port: bool,
+    /// Address ranges whose accesses bypass the caches, like kseg1. Device
+    /// registers are uncached by default so polling them neither hits in
+    /// nor evicts from the data cache.
+    pub uncached_regions: Vec<RangeInclusive<u32>>,
+    /// Instruction and data TLBs of this geometry, when set, charge a page
+    /// walk for each miss on a mapped address
+    pub tlb_config: Option<TlbConfig>,
+    /// Core clock frequency in hertz, which turns cycles into simulated time
+    pub clock_hz: u64,
 }
 
 impl PipelineConfig {
+    /// A pipeline of `num_stages` stages. Five gives the classic
+    /// IF/ID/EX/MEM/WB layout; three and four leave out MEM and then ID, and
+    /// deeper pipelines split execute into EX1, EX2 and so on. Panics below
+    /// three stages; `try_new` returns the error instead.
     pub fn new(num_stages: usize) -> Self {
+        Self::try_new(num_stages).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `new`, but fails when `num_stages` is too small to hold fetch,
+    /// execute and write-back stages
+    pub fn try_new(num_stages: usize) -> Result<Self, String> {
+        if num_stages < 3 {
+            return Err(format!(
+                "A pipeline needs at least fetch, execute and write-back stages, not {}",
+                num_stages
+            ));
+        }
         // Default to 1 cycle per stage
         let stage_latencies = vec![1; num_stages];
 
-        Self {
+        Ok(Self {
             num_stages,
             stage_latencies,
+            stages: default_stages(num_stages),
             forwarding_enabled: true,
             branch_prediction_enabled: true,
             branch_predictor_type: BranchPredictorType::TwoBit,
+            control_hazard_scheme: ControlHazardScheme::Flush,
             tomasulo_config: None,
             superscalar_width: 1,
-        }
+            caches_enabled: true,
+            unified_memory_port: false,
+            uncached_regions: vec![DEVICE_REGION],
+            tlb_config: None,
+            clock_hz: DEFAULT_CLOCK_HZ,
+        })
     }
 
     pub fn with_latencies(mut self, latencies: Vec<usize>) -> Self {
@@ -117,6 +226,49 @@ impl PipelineConfig {
         self
     }
 
+    /// Replace the stage layout with named stages of the given kinds, each
+    /// taking one cycle. Kinds must appear in pipeline order, starting with
+    /// fetch and including at least one execute stage.
+    pub fn with_stages(mut self, stages: &[(&str, PipelineStageType)]) -> Result<Self, String> {
+        if stages.first().map(|&(_, kind)| kind) != Some(PipelineStageType::Fetch) {
+            return Err("The first stage must be a fetch stage".to_string());
+        }
+        if !stages.windows(2).all(|pair| pair[0].1 <= pair[1].1) {
+            return Err("Stage kinds must be in pipeline order".to_string());
+        }
+        if !stages
+            .iter()
+            .any(|&(_, kind)| kind == PipelineStageType::Execute)
+        {
+            return Err("A pipeline needs an execute stage".to_string());
+        }
+
+        self.num_stages = stages.len();
+        self.stage_latencies = vec![1; stages.len()];
+        self.stages = stages
+            .iter()
+            .map(|&(name, kind)| StageConfig::new(name, kind))
+            .collect();
+        Ok(self)
+    }
+
+    /// The eight-stage MIPS R4000 layout, with instruction and data cache
+    /// accesses split over two stages and a tag check before write-back
+    pub fn r4000() -> Self {
+        Self::new(8)
+            .with_stages(&[
+                ("IF", PipelineStageType::Fetch),
+                ("IS", PipelineStageType::Fetch),
+                ("RF", PipelineStageType::Decode),
+                ("EX", PipelineStageType::Execute),
+                ("DF", PipelineStageType::Memory),
+                ("DS", PipelineStageType::Memory),
+                ("TC", PipelineStageType::Memory),
+                ("WB", PipelineStageType::Writeback),
+            ])
+            .expect("the R4000 layout is valid")
+    }
+
     pub fn with_forwarding(mut self, enabled: bool) -> Self {
         self.forwarding_enabled = enabled;
         self
@@ -132,6 +284,11 @@ impl PipelineConfig {
         self
     }
 
+    pub fn with_control_hazards(mut self, scheme: ControlHazardScheme) -> Self {
+        self.control_hazard_scheme = scheme;
+        self
+    }
+
     /// Enable Tomasulo's Algorithm for out-of-order execution
     pub fn with_tomasulo(mut self, enabled: bool, config: TomasuloConfig) -> Self {
         if enabled {
@@ -142,6 +299,35 @@ impl PipelineConfig {
         self
     }
 
+    pub fn with_caches(mut self, enabled: bool) -> Self {
+        self.caches_enabled = enabled;
+        self
+    }
+
+    pub fn with_unified_memory_port(mut self, enabled: bool) -> Self {
+        self.unified_memory_port = enabled;
+        self
+    }
+
+    /// Make accesses to `region` bypass the caches
+    pub fn with_uncached_region(mut self, region: RangeInclusive<u32>) -> Self {
+        self.uncached_regions.push(region);
+        self
+    }
+
+    /// Model instruction and data TLBs, each with this configuration
+    pub fn with_tlb(mut self, config: TlbConfig) -> Self {
+        self.tlb_config = Some(config);
+        self
+    }
+
+    /// Run the core clock at `hz` hertz
+    pub fn with_clock_hz(mut self, hz: u64) -> Self {
+        assert!(hz > 0, "Clock frequency must be positive");
+        self.clock_hz = hz;
+        self
+    }
+
     pub fn with_superscalar(mut self, width: usize) -> Self {
         assert!(width > 0, "Superscalar width must be positive");
         self.superscalar_width = width;
@@ -149,15 +335,72 @@ impl PipelineConfig {
     }
 }
 
+// Stage layout for `PipelineConfig::try_new`, which has checked that there
+// are at least three stages
+fn default_stages(num_stages: usize) -> Vec<StageConfig> {
+    let mut stages = vec![StageConfig::new("IF", PipelineStageType::Fetch)];
+    if num_stages >= 4 {
+        stages.push(StageConfig::new("ID", PipelineStageType::Decode));
+    }
+    let execute_stages = num_stages.saturating_sub(4).max(1);
+    if execute_stages == 1 {
+        stages.push(StageConfig::new("EX", PipelineStageType::Execute));
+    } else {
+        for index in 1..=execute_stages {
+            let name = format!("EX{}", index);
+            stages.push(StageConfig::new(&name, PipelineStageType::Execute));
+        }
+    }
+    if num_stages >= 5 {
+        stages.push(StageConfig::new("MEM", PipelineStageType::Memory));
+    }
+    stages.push(StageConfig::new("WB", PipelineStageType::Writeback));
+    stages
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BranchPredictorType {
-    Static,      // Always predict taken or not taken
+    Static,      // Always predict not taken
     OneBit,      // Remember last outcome
     TwoBit,      // 2-bit saturating counter
     Correlating, // Use history of recent branches
     Tournament,  // Combine multiple predictors
 }
 
+/// How the in-order pipeline deals with the instructions fetched behind a
+/// conditional branch before it resolves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlHazardScheme {
+    /// Keep fetching along the predicted path, not taken when prediction is
+    /// off, and flush it if the branch goes the other way
+    Flush,
+    /// Stop fetching behind every branch until it resolves
+    Stall,
+    /// Always execute the instruction after a branch or jump, and stall for
+    /// the rest of the branch resolution
+    DelaySlot,
+}
+
+impl ControlHazardScheme {
+    /// Name used on the command line: `flush`, `stall` or `delay-slot`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ControlHazardScheme::Flush => "flush",
+            ControlHazardScheme::Stall => "stall",
+            ControlHazardScheme::DelaySlot => "delay-slot",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "flush" => Some(ControlHazardScheme::Flush),
+            "stall" => Some(ControlHazardScheme::Stall),
+            "delay-slot" => Some(ControlHazardScheme::DelaySlot),
+            _ => None,
+        }
+    }
+}
+
 /// Configuration for Tomasulo's algorithm
 #[derive(Debug, Clone)]
 pub struct TomasuloConfig {
@@ -0,0 +1,195 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// data_breakpoint.rs
+//
+// This file contains the data breakpoints of the stepping debugger. A data
+// breakpoint watches a register or memory word and is checked each time an
+// instruction writes it. Without a condition every write stops the program;
+// a condition compares the written value with a number, so a loop counter
+// can be caught at the iteration of interest rather than at each write.
+
+use super::last_writer::Location;
+use std::fmt;
+
+/// When a write to a data breakpoint's location stops the program
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakCondition {
+    /// Every write
+    Written,
+    /// A write that leaves the value equal to the number
+    Equals(u32),
+    /// A write that changes the value to the number from something else
+    ChangesTo(u32),
+    /// A write that moves the value, as a signed number, onto or past the
+    /// threshold from either side
+    Crosses(i32),
+}
+
+impl BreakCondition {
+    /// Whether a write that changed the value from `old` to `new` stops
+    pub fn is_met(&self, old: u32, new: u32) -> bool {
+        match *self {
+            BreakCondition::Written => true,
+            BreakCondition::Equals(value) => new == value,
+            BreakCondition::ChangesTo(value) => new == value && old != value,
+            BreakCondition::Crosses(threshold) => {
+                let (old, new) = (old as i32, new as i32);
+                (old < threshold && new >= threshold) || (old > threshold && new <= threshold)
+            },
+        }
+    }
+}
+
+/// A register or memory word checked on every write
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DataBreakpoint {
+    pub location: Location,
+    pub condition: BreakCondition,
+}
+
+impl DataBreakpoint {
+    /// Parse a location optionally followed by a condition: `$t0`,
+    /// `mem[0x10000000] == 5`, `$t0 changes-to 0` or `$s1 crosses -10`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (location, condition) = match words.as_slice() {
+            [location] => (location, BreakCondition::Written),
+            [location, operator, value] => {
+                let value = parse_value(value)?;
+                let condition = match *operator {
+                    "==" => BreakCondition::Equals(value),
+                    "changes-to" => BreakCondition::ChangesTo(value),
+                    "crosses" => BreakCondition::Crosses(value as i32),
+                    _ => {
+                        return Err(format!(
+                            "unknown condition '{}'; expected ==, changes-to or crosses",
+                            operator
+                        ))
+                    },
+                };
+                (location, condition)
+            },
+            _ => return Err("expected LOCATION [== | changes-to | crosses VALUE]".to_string()),
+        };
+        Ok(Self {
+            location: Location::parse(location)?,
+            condition,
+        })
+    }
+}
+
+impl fmt::Display for DataBreakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.condition {
+            BreakCondition::Written => write!(f, "{} written", self.location),
+            BreakCondition::Equals(value) => write!(f, "{} == 0x{:08X}", self.location, value),
+            BreakCondition::ChangesTo(value) => {
+                write!(f, "{} changes to 0x{:08X}", self.location, value)
+            },
+            BreakCondition::Crosses(threshold) => {
+                write!(f, "{} crosses {}", self.location, threshold)
+            },
+        }
+    }
+}
+
+// A decimal number, which may be negative, or a hexadecimal one
+fn parse_value(text: &str) -> Result<u32, String> {
+    let parsed = match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text
+            .parse::<i64>()
+            .ok()
+            .filter(|&value| value >= i32::MIN as i64 && value <= u32::MAX as i64)
+            .map(|value| value as u32),
+    };
+    parsed.ok_or_else(|| format!("invalid value '{}'", text))
+}
+
+/// A data breakpoint that stopped the program
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BreakpointHit {
+    /// The breakpoint's number, counting from 1
+    pub number: usize,
+    pub breakpoint: DataBreakpoint,
+    pub old: u32,
+    pub new: u32,
+    /// Address of the instruction that wrote the value
+    pub pc: u32,
+    /// Position of that instruction in the run, counting from 0
+    pub index: usize,
+}
+
+impl fmt::Display for BreakpointHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Breakpoint {} ({}): 0x{:08X} -> 0x{:08X} by the instruction at 0x{:08X} (instruction {})",
+            self.number, self.breakpoint, self.old, self.new, self.pc, self.index
+        )
+    }
+}
+
+/// The data breakpoints of a session
+#[derive(Clone, Debug, Default)]
+pub struct DataBreakpoints {
+    breakpoints: Vec<DataBreakpoint>,
+}
+
+impl DataBreakpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a breakpoint and return its number, counting from 1
+    pub fn add(&mut self, breakpoint: DataBreakpoint) -> Result<usize, String> {
+        if self.breakpoints.contains(&breakpoint) {
+            return Err(format!("breakpoint on {} already exists", breakpoint));
+        }
+        self.breakpoints.push(breakpoint);
+        Ok(self.breakpoints.len())
+    }
+
+    /// Remove the breakpoint with this number
+    pub fn remove(&mut self, number: usize) -> Option<DataBreakpoint> {
+        if number == 0 || number > self.breakpoints.len() {
+            return None;
+        }
+        Some(self.breakpoints.remove(number - 1))
+    }
+
+    pub fn len(&self) -> usize {
+        self.breakpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    /// The breakpoints with their numbers
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &DataBreakpoint)> {
+        self.breakpoints
+            .iter()
+            .enumerate()
+            .map(|(index, breakpoint)| (index + 1, breakpoint))
+    }
+}
@@ -0,0 +1,600 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// events.rs
+//
+// This file contains the event log of the timing simulator. While a run is
+// recorded, the L1 caches emit an event for each lookup and the in-order
+// pipeline one for each conditional branch it resolves. The simulator's
+// issue loop adds an event for each instruction it issues and for the
+// stalls and memory accesses that came with it, and stamps them all with
+// the issue cycle. Every event of an in-order run is tallied into an
+// `EventSummary`, and the cache, branch and stall counts of the run's
+// statistics, interval samples, regions and CPI stack are read from that
+// tally rather than from counters of the components. An enabled log also keeps the events in memory or streams
+// them to a file as JSON lines, and queries pick events by category, PC and
+// cycle range after the run.
+
+use super::stats::{StallBreakdown, StatsCounters};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+/// Part of the machine an event comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventCategory {
+    /// Issue and stalls
+    Pipeline,
+    /// L1 instruction and data cache lookups
+    Cache,
+    /// Resolved conditional branches
+    Predictor,
+    /// Loads and stores
+    Memory,
+}
+
+impl EventCategory {
+    pub const ALL: [EventCategory; 4] = [
+        EventCategory::Pipeline,
+        EventCategory::Cache,
+        EventCategory::Predictor,
+        EventCategory::Memory,
+    ];
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.to_ascii_lowercase().as_str() {
+            "pipeline" => Ok(EventCategory::Pipeline),
+            "cache" => Ok(EventCategory::Cache),
+            "predictor" | "branch" => Ok(EventCategory::Predictor),
+            "memory" | "mem" => Ok(EventCategory::Memory),
+            _ => Err(format!(
+                "unknown event category '{}'; expected pipeline, cache, predictor or memory",
+                text
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EventCategory::Pipeline => "pipeline",
+            EventCategory::Cache => "cache",
+            EventCategory::Predictor => "predictor",
+            EventCategory::Memory => "memory",
+        }
+    }
+}
+
+/// Which L1 cache an access went to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheKind {
+    Instruction,
+    Data,
+}
+
+/// What an instruction stalled for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StallCause {
+    InstructionCache,
+    DataCache,
+    /// Waiting for an operand
+    DataHazard,
+    /// A busy stage or memory port
+    Structural,
+    /// Fetching the right path after a branch
+    Branch,
+}
+
+impl StallCause {
+    pub fn name(self) -> &'static str {
+        match self {
+            StallCause::InstructionCache => "icache",
+            StallCause::DataCache => "dcache",
+            StallCause::DataHazard => "raw",
+            StallCause::Structural => "structural",
+            StallCause::Branch => "branch",
+        }
+    }
+}
+
+/// What happened
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    /// An instruction issued and held issue for `cycles`, stalls included
+    Issue {
+        word: u32,
+        cycles: usize,
+    },
+    /// The pipeline stalled behind the instruction
+    Stall {
+        cause: StallCause,
+        cycles: usize,
+    },
+    CacheAccess {
+        cache: CacheKind,
+        address: u32,
+        hit: bool,
+    },
+    /// A conditional branch resolved
+    Branch {
+        taken: bool,
+        mispredicted: bool,
+    },
+    MemoryAccess {
+        address: u32,
+        store: bool,
+    },
+}
+
+/// One event, stamped with the cycle its instruction issued in and the
+/// instruction's PC
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    pub cycle: usize,
+    pub pc: u32,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+impl Event {
+    pub fn new(cycle: usize, pc: u32, kind: EventKind) -> Self {
+        Self { cycle, pc, kind }
+    }
+
+    pub fn category(&self) -> EventCategory {
+        match self.kind {
+            EventKind::Issue { .. } | EventKind::Stall { .. } => EventCategory::Pipeline,
+            EventKind::CacheAccess { .. } => EventCategory::Cache,
+            EventKind::Branch { .. } => EventCategory::Predictor,
+            EventKind::MemoryAccess { .. } => EventCategory::Memory,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>8}  0x{:08X}  {:<9}  ",
+            self.cycle,
+            self.pc,
+            self.category().name()
+        )?;
+        match self.kind {
+            EventKind::Issue { word, cycles } => {
+                write!(f, "issue 0x{:08X}, {} cycles", word, cycles)
+            },
+            EventKind::Stall { cause, cycles } => {
+                write!(f, "{} stall, {} cycles", cause.name(), cycles)
+            },
+            EventKind::CacheAccess {
+                cache,
+                address,
+                hit,
+            } => write!(
+                f,
+                "{} 0x{:08X} {}",
+                match cache {
+                    CacheKind::Instruction => "L1I",
+                    CacheKind::Data => "L1D",
+                },
+                address,
+                if hit { "hit" } else { "miss" }
+            ),
+            EventKind::Branch {
+                taken,
+                mispredicted,
+            } => write!(
+                f,
+                "branch {}{}",
+                if taken { "taken" } else { "not taken" },
+                if mispredicted { ", mispredicted" } else { "" }
+            ),
+            EventKind::MemoryAccess { address, store } => write!(
+                f,
+                "{} 0x{:08X}",
+                if store { "store" } else { "load" },
+                address
+            ),
+        }
+    }
+}
+
+/// Which events a query picks. Every condition that is set must hold.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EventQuery {
+    /// Any of these categories; all of them if empty
+    pub categories: Vec<EventCategory>,
+    pub pcs: Option<RangeInclusive<u32>>,
+    pub cycles: Option<RangeInclusive<usize>>,
+}
+
+impl EventQuery {
+    /// A query that picks every event
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn category(mut self, category: EventCategory) -> Self {
+        self.categories.push(category);
+        self
+    }
+
+    pub fn pc(self, pc: u32) -> Self {
+        self.pc_range(pc..=pc)
+    }
+
+    pub fn pc_range(mut self, pcs: RangeInclusive<u32>) -> Self {
+        self.pcs = Some(pcs);
+        self
+    }
+
+    pub fn cycles(mut self, cycles: RangeInclusive<usize>) -> Self {
+        self.cycles = Some(cycles);
+        self
+    }
+
+    /// Parse `category=cache+memory,pc=0x400010,cycles=100-200`. A PC can
+    /// also be a range, `pc=0x400000-0x40001C`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut query = Self::new();
+        for setting in text.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", setting))?;
+            match key.trim() {
+                "category" => {
+                    for name in value.split('+') {
+                        query = query.category(EventCategory::parse(name.trim())?);
+                    }
+                },
+                "pc" => {
+                    let (start, end) = parse_range(value)?;
+                    query = query.pc_range(start as u32..=end as u32);
+                },
+                "cycles" => {
+                    let (start, end) = parse_range(value)?;
+                    query = query.cycles(start as usize..=end as usize);
+                },
+                other => {
+                    return Err(format!(
+                        "unknown query key '{}'; expected category, pc or cycles",
+                        other
+                    ))
+                },
+            }
+        }
+        Ok(query)
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        (self.categories.is_empty() || self.categories.contains(&event.category()))
+            && self
+                .pcs
+                .as_ref()
+                .map_or(true, |pcs| pcs.contains(&event.pc))
+            && self
+                .cycles
+                .as_ref()
+                .map_or(true, |cycles| cycles.contains(&event.cycle))
+    }
+}
+
+// `N` or `START-END`, each decimal or 0x-prefixed hex
+fn parse_range(text: &str) -> Result<(u64, u64), String> {
+    let number = |text: &str| {
+        let text = text.trim();
+        match text.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => text.parse(),
+        }
+        .map_err(|_| format!("invalid number '{}'", text))
+    };
+    let (start, end) = match text.split_once('-') {
+        Some((start, end)) => (number(start)?, number(end)?),
+        None => {
+            let value = number(text)?;
+            (value, value)
+        },
+    };
+    if end < start {
+        return Err(format!("range '{}' ends before it starts", text));
+    }
+    Ok((start, end))
+}
+
+/// Totals counted from a sequence of events
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct EventSummary {
+    pub events: usize,
+    pub instructions: usize,
+    /// Cycles from the first issue to the end of the last instruction's
+    /// issue slot
+    pub cycles: usize,
+    pub stalls: StallBreakdown,
+    pub icache_accesses: usize,
+    pub icache_misses: usize,
+    pub dcache_accesses: usize,
+    pub dcache_misses: usize,
+    pub branches: usize,
+    pub mispredictions: usize,
+    pub loads: usize,
+    pub stores: usize,
+    #[serde(skip)]
+    first_cycle: Option<usize>,
+}
+
+impl EventSummary {
+    pub fn new<'a, I: IntoIterator<Item = &'a Event>>(events: I) -> Self {
+        let mut summary = Self::default();
+        for event in events {
+            summary.record(event);
+        }
+        summary
+    }
+
+    /// Count one more event
+    pub fn record(&mut self, event: &Event) {
+        self.events += 1;
+        match event.kind {
+            EventKind::Issue { cycles, .. } => {
+                self.instructions += 1;
+                let first = *self.first_cycle.get_or_insert(event.cycle);
+                self.cycles = self.cycles.max(event.cycle + cycles - first);
+            },
+            EventKind::Stall { cause, cycles } => {
+                let stalls = &mut self.stalls;
+                *match cause {
+                    StallCause::InstructionCache => &mut stalls.icache,
+                    StallCause::DataCache => &mut stalls.dcache,
+                    StallCause::DataHazard => &mut stalls.raw,
+                    StallCause::Structural => &mut stalls.structural,
+                    StallCause::Branch => &mut stalls.branch,
+                } += cycles;
+            },
+            EventKind::CacheAccess { cache, hit, .. } => {
+                let (accesses, misses) = match cache {
+                    CacheKind::Instruction => (&mut self.icache_accesses, &mut self.icache_misses),
+                    CacheKind::Data => (&mut self.dcache_accesses, &mut self.dcache_misses),
+                };
+                *accesses += 1;
+                *misses += usize::from(!hit);
+            },
+            EventKind::Branch { mispredicted, .. } => {
+                self.branches += 1;
+                self.mispredictions += usize::from(mispredicted);
+            },
+            EventKind::MemoryAccess { store: true, .. } => self.stores += 1,
+            EventKind::MemoryAccess { store: false, .. } => self.loads += 1,
+        }
+    }
+
+    /// The statistics counters of the events, in a run that has executed
+    /// `instructions` in `cycles`
+    pub fn counters(&self, instructions: usize, cycles: usize) -> StatsCounters {
+        StatsCounters {
+            instructions,
+            cycles,
+            icache_accesses: self.icache_accesses,
+            icache_misses: self.icache_misses,
+            dcache_accesses: self.dcache_accesses,
+            dcache_misses: self.dcache_misses,
+            branch_mispredictions: self.mispredictions,
+            stalls: self.stalls.total(),
+            branch_stalls: self.stalls.branch,
+            stall_breakdown: self.stalls,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let rate = |misses: usize, accesses: usize| {
+            if accesses == 0 {
+                0.0
+            } else {
+                misses as f64 * 100.0 / accesses as f64
+            }
+        };
+        format!(
+            "Event Summary:\n  Events: {}\n  Instructions: {}\n  Cycles: {}\n  Stall Cycles: {} (icache {}, dcache {}, raw {}, \
+             structural {}, branch {})\n  \
+             L1I: {} accesses, {} misses ({:.2}%)\n  L1D: {} accesses, {} misses ({:.2}%)\n  \
+             Branches: {}, {} mispredicted\n  Loads: {}, Stores: {}\n",
+            self.events,
+            self.instructions,
+            self.cycles,
+            self.stalls.total(),
+            self.stalls.icache,
+            self.stalls.dcache,
+            self.stalls.raw,
+            self.stalls.structural,
+            self.stalls.branch,
+            self.icache_accesses,
+            self.icache_misses,
+            rate(self.icache_misses, self.icache_accesses),
+            self.dcache_accesses,
+            self.dcache_misses,
+            rate(self.dcache_misses, self.dcache_accesses),
+            self.branches,
+            self.mispredictions,
+            self.loads,
+            self.stores
+        )
+    }
+}
+
+/// Events a component emits while the log is enabled. The simulator stamps
+/// them with the cycle and PC of the instruction that caused them and moves
+/// them into the log.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PendingEvents {
+    events: Vec<EventKind>,
+}
+
+impl PendingEvents {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn emit(&mut self, kind: EventKind) {
+        self.events.push(kind);
+    }
+
+    /// The events emitted since the last call, oldest first
+    pub fn take(&mut self) -> Vec<EventKind> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+/// Events emitted during a run, kept in memory or written to a file
+pub struct EventLog {
+    events: Vec<Event>,
+    file: Option<BufWriter<File>>,
+    written: usize,
+    /// First error writing to the file, reported by `flush`
+    error: Option<io::Error>,
+}
+
+impl EventLog {
+    /// A log that keeps its events in memory
+    pub fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            file: None,
+            written: 0,
+            error: None,
+        }
+    }
+
+    /// A log that writes its events to `path` as JSON lines instead of
+    /// keeping them
+    pub fn to_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self {
+            file: Some(BufWriter::new(File::create(path)?)),
+            ..Self::new()
+        })
+    }
+
+    /// Read the events of a file written by a log made with `to_file`
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut log = Self::new();
+        log.events = Self::read_from(BufReader::new(File::open(path)?))?;
+        Ok(log)
+    }
+
+    /// Parse JSON lines of events
+    pub fn read_from<R: BufRead>(reader: R) -> io::Result<Vec<Event>> {
+        let mut events = Vec::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = serde_json::from_str(&line).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", number + 1, e),
+                )
+            })?;
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    pub fn emit(&mut self, event: Event) {
+        self.written += 1;
+        match self.file.as_mut() {
+            Some(file) if self.error.is_none() => {
+                let written = serde_json::to_writer(&mut *file, &event)
+                    .map_err(io::Error::from)
+                    .and_then(|()| file.write_all(b"\n"));
+                self.error = written.err();
+            },
+            Some(_) => {},
+            None => self.events.push(event),
+        }
+    }
+
+    /// Write out buffered events, returning the first error writing any
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether events go to a file rather than staying in memory
+    pub fn is_on_disk(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// The events kept in memory; empty for a log written to a file
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Events emitted, including those written to a file
+    pub fn len(&self) -> usize {
+        self.written.max(self.events.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.written = 0;
+    }
+
+    /// The events kept in memory that the query picks, in order
+    pub fn query<'a>(&'a self, query: &'a EventQuery) -> impl Iterator<Item = &'a Event> + 'a {
+        self.events.iter().filter(move |event| query.matches(event))
+    }
+
+    pub fn summary(&self) -> EventSummary {
+        EventSummary::new(&self.events)
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A table of events, one per line
+pub fn render_events<'a, I: IntoIterator<Item = &'a Event>>(events: I) -> String {
+    let mut result = format!("{:>8}  {:<10}  {:<9}  event\n", "cycle", "pc", "category");
+    for event in events {
+        result.push_str(&event.to_string());
+        result.push('\n');
+    }
+    result
+}
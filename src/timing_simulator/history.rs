@@ -0,0 +1,512 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// history.rs
+//
+// This file contains the recorded pipeline history of a timing run: the
+// stage every instruction occupies in every cycle, why it stalled and which
+// instructions were fetched down a mispredicted path and squashed. The
+// history is saved in a compact binary file for offline analysis, or as
+// JSON for other tools, as a per-cycle CSV table for spreadsheets, and can
+// be converted to the Kanata log format read by the Konata pipeline viewer.
+
+use crate::functional_simulator::simulator::{decode_instruction, instruction_to_string};
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::mem;
+
+/// First bytes of a pipeline history file
+pub const HISTORY_MAGIC: &[u8; 4] = b"VMPH";
+/// Version of the JSON history format
+pub const HISTORY_VERSION: u8 = 1;
+/// Version of the binary history format. Version 2 added the cycle each
+/// instruction was squashed in; version 1 files are still read.
+pub const HISTORY_FILE_VERSION: u8 = 2;
+/// Name written at the top of a JSON pipeline history
+pub const HISTORY_FORMAT: &str = "vmips-pipeline-history";
+
+/// Why an instruction stayed in a stage longer than the stage's latency
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum StallReason {
+    #[serde(rename = "data")]
+    DataHazard,
+    #[serde(rename = "cache")]
+    CacheMiss,
+    #[serde(rename = "control")]
+    ControlHazard,
+}
+
+impl StallReason {
+    pub fn name(self) -> &'static str {
+        match self {
+            StallReason::DataHazard => "data",
+            StallReason::CacheMiss => "cache",
+            StallReason::ControlHazard => "control",
+        }
+    }
+
+    fn code(self) -> u8 {
+        match self {
+            StallReason::DataHazard => 1,
+            StallReason::CacheMiss => 2,
+            StallReason::ControlHazard => 3,
+        }
+    }
+
+    fn from_code(code: u8) -> io::Result<Option<Self>> {
+        match code {
+            0 => Ok(None),
+            1 => Ok(Some(StallReason::DataHazard)),
+            2 => Ok(Some(StallReason::CacheMiss)),
+            3 => Ok(Some(StallReason::ControlHazard)),
+            _ => Err(invalid("unknown stall reason")),
+        }
+    }
+}
+
+/// Extra cycles an instruction is held in one stage
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Stall {
+    pub stage: usize,
+    pub cycles: usize,
+    pub reason: StallReason,
+}
+
+/// One instruction's trip through the pipeline
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct HistoryEntry {
+    /// Position in program order
+    pub id: usize,
+    pub pc: u32,
+    pub word: u32,
+    pub fetch_cycle: usize,
+    pub stalls: Vec<Stall>,
+    /// For a wrong-path instruction, the first cycle it is gone from the
+    /// pipeline: the branch ahead of it resolved at the end of the cycle
+    /// before. Wrong-path instructions share the id of that branch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub squashed: Option<usize>,
+}
+
+impl HistoryEntry {
+    /// Cycle the instruction enters each stage and how many cycles it stays.
+    /// A squashed instruction only has the stages it reached.
+    pub fn stage_spans(&self, latencies: &[usize]) -> Vec<(usize, usize)> {
+        let mut cycle = self.fetch_cycle;
+        let squashed = self.squashed.unwrap_or(usize::MAX);
+        latencies
+            .iter()
+            .enumerate()
+            .map(|(stage, &latency)| {
+                let held: usize = self
+                    .stalls
+                    .iter()
+                    .filter(|stall| stall.stage == stage)
+                    .map(|stall| stall.cycles)
+                    .sum();
+                let span = (cycle, (latency + held).min(squashed.saturating_sub(cycle)));
+                cycle += latency + held;
+                span
+            })
+            .take_while(|&(start, _)| start < squashed)
+            .collect()
+    }
+
+    /// Whether the instruction was fetched down a mispredicted path
+    pub fn is_wrong_path(&self) -> bool {
+        self.squashed.is_some()
+    }
+
+    fn stall_reason(&self, stage: usize) -> Option<StallReason> {
+        self.stalls
+            .iter()
+            .find(|stall| stall.stage == stage && stall.cycles > 0)
+            .map(|stall| stall.reason)
+    }
+}
+
+/// Contents of one stage in one cycle
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Slot {
+    /// Index into `PipelineHistory::entries`, `None` for a bubble
+    pub entry: Option<usize>,
+    /// Set on the cycles an instruction spends in a stage beyond its latency
+    pub stall: Option<StallReason>,
+}
+
+/// Per-cycle stage occupancy of a run, recorded when enabled on the simulator
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PipelineHistory {
+    /// Stage names, fetch first
+    pub stages: Vec<String>,
+    /// Cycles each stage takes without stalls
+    pub latencies: Vec<usize>,
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl PipelineHistory {
+    /// An empty history of a pipeline with these stage names and latencies
+    pub fn new(stages: Vec<(String, usize)>) -> Self {
+        let (stages, latencies) = stages.into_iter().unzip();
+        Self {
+            stages,
+            latencies,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Approximate host memory the history takes. Stall records are rare
+    /// and left out so the estimate stays cheap on long runs.
+    pub fn heap_size(&self) -> usize {
+        let stages: usize = self.stages.iter().map(|name| name.len()).sum();
+        stages
+            + self.latencies.len() * mem::size_of::<usize>()
+            + self.entries.len() * mem::size_of::<HistoryEntry>()
+    }
+
+    pub fn record(&mut self, entry: HistoryEntry) {
+        self.entries.push(entry);
+    }
+
+    /// First cycle with an instruction in the pipeline
+    pub fn first_cycle(&self) -> usize {
+        self.entries.first().map_or(0, |entry| entry.fetch_cycle)
+    }
+
+    /// Number of cycles from the first fetch until the last instruction leaves
+    pub fn cycles(&self) -> usize {
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.stage_spans(&self.latencies).last().copied())
+            .map(|(start, length)| start + length)
+            .max()
+            .map_or(0, |end| end - self.first_cycle())
+    }
+
+    /// Stage occupancy of every cycle starting at `first_cycle`
+    pub fn occupancy(&self) -> Vec<Vec<Slot>> {
+        let first = self.first_cycle();
+        let stage_count = self.stages.len();
+        let mut cycles = vec![vec![Slot::default(); stage_count]; self.cycles()];
+        for (index, entry) in self.entries.iter().enumerate() {
+            let spans = entry.stage_spans(&self.latencies);
+            for (stage, (start, length)) in spans.into_iter().enumerate() {
+                for offset in 0..length {
+                    cycles[start - first + offset][stage] = Slot {
+                        entry: Some(index),
+                        stall: if offset >= self.latencies[stage] {
+                            entry.stall_reason(stage)
+                        } else {
+                            None
+                        },
+                    };
+                }
+            }
+        }
+        cycles
+    }
+
+    /// Save in the binary history format. All numbers are little-endian:
+    /// the magic and version, the stage names and latencies, the
+    /// instructions as id, pc, word and the cycle they were squashed in plus
+    /// one (zero if they were not), then for every cycle and stage a
+    /// slot holding the entry index plus one (zero for a bubble) and a
+    /// stall reason code.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut out = Vec::new();
+        out.extend_from_slice(HISTORY_MAGIC);
+        out.push(HISTORY_FILE_VERSION);
+        out.push(self.stages.len() as u8);
+        for (name, &latency) in self.stages.iter().zip(&self.latencies) {
+            out.push(name.len() as u8);
+            out.extend_from_slice(name.as_bytes());
+            out.push(latency as u8);
+        }
+
+        out.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for entry in &self.entries {
+            out.extend_from_slice(&(entry.id as u32).to_le_bytes());
+            out.extend_from_slice(&entry.pc.to_le_bytes());
+            out.extend_from_slice(&entry.word.to_le_bytes());
+            let squashed = entry.squashed.map_or(0, |cycle| cycle as u64 + 1);
+            out.extend_from_slice(&squashed.to_le_bytes());
+        }
+
+        let occupancy = self.occupancy();
+        out.extend_from_slice(&(self.first_cycle() as u64).to_le_bytes());
+        out.extend_from_slice(&(occupancy.len() as u32).to_le_bytes());
+        for cycle in &occupancy {
+            for slot in cycle {
+                let entry = slot.entry.map_or(0, |index| index as u32 + 1);
+                out.extend_from_slice(&entry.to_le_bytes());
+                out.push(slot.stall.map_or(0, StallReason::code));
+            }
+        }
+        writer.write_all(&out)
+    }
+
+    /// Save as JSON: the format name and version, the stage names and
+    /// latencies, and every instruction with its fetch cycle and stalls
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct JsonHistory<'a> {
+            format: &'static str,
+            version: u8,
+            stages: &'a [String],
+            latencies: &'a [usize],
+            entries: &'a [HistoryEntry],
+        }
+
+        let history = JsonHistory {
+            format: HISTORY_FORMAT,
+            version: HISTORY_VERSION,
+            stages: &self.stages,
+            latencies: &self.latencies,
+            entries: &self.entries,
+        };
+        serde_json::to_string_pretty(&history).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Save as CSV with one row per cycle and one column per stage. A slot
+    /// holds the PC of its instruction, `stall` while the instruction is
+    /// held past the stage's latency, or `bubble` when the stage is empty.
+    /// Wrong-path instructions have `(squashed)` after their PC.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("cycle");
+        for name in &self.stages {
+            csv.push(',');
+            csv.push_str(name);
+        }
+        csv.push('\n');
+        let first = self.first_cycle();
+        for (offset, slots) in self.occupancy().iter().enumerate() {
+            let _ = write!(csv, "{}", first + offset);
+            for slot in slots {
+                csv.push(',');
+                match (slot.entry.map(|index| &self.entries[index]), slot.stall) {
+                    (None, _) => csv.push_str("bubble"),
+                    (Some(_), Some(_)) => csv.push_str("stall"),
+                    (Some(entry), None) if entry.is_wrong_path() => {
+                        let _ = write!(csv, "0x{:08X} (squashed)", entry.pc);
+                    },
+                    (Some(entry), None) => {
+                        let _ = write!(csv, "0x{:08X}", entry.pc);
+                    },
+                }
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Load a history saved by `write_to`
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != HISTORY_MAGIC {
+            return Err(invalid("not a pipeline history file"));
+        }
+        let version = read_u8(reader)?;
+        if version == 0 || version > HISTORY_FILE_VERSION {
+            return Err(invalid("unsupported pipeline history version"));
+        }
+
+        let stage_count = read_u8(reader)? as usize;
+        let mut stages = Vec::with_capacity(stage_count);
+        for _ in 0..stage_count {
+            let mut name = vec![0u8; read_u8(reader)? as usize];
+            reader.read_exact(&mut name)?;
+            let name = String::from_utf8(name).map_err(|_| invalid("stage name is not UTF-8"))?;
+            stages.push((name, read_u8(reader)? as usize));
+        }
+
+        let entry_count = read_u32(reader)? as usize;
+        let mut entries = Vec::new();
+        for _ in 0..entry_count {
+            let (id, pc, word) = (read_u32(reader)?, read_u32(reader)?, read_u32(reader)?);
+            let squashed = match version {
+                1 => None,
+                _ => read_u64(reader)?.checked_sub(1).map(|cycle| cycle as usize),
+            };
+            entries.push(HistoryEntry {
+                id: id as usize,
+                pc,
+                word,
+                fetch_cycle: usize::MAX,
+                stalls: Vec::new(),
+                squashed,
+            });
+        }
+
+        // Rebuild every entry's fetch cycle and stalls from the slots
+        let first = read_u64(reader)? as usize;
+        let cycle_count = read_u32(reader)? as usize;
+        for cycle in first..first + cycle_count {
+            for stage in 0..stage_count {
+                let entry = read_u32(reader)? as usize;
+                let stall = StallReason::from_code(read_u8(reader)?)?;
+                if entry == 0 {
+                    continue;
+                }
+                let entry = entries
+                    .get_mut(entry - 1)
+                    .ok_or_else(|| invalid("slot refers to a missing instruction"))?;
+                if stage == 0 {
+                    entry.fetch_cycle = entry.fetch_cycle.min(cycle);
+                }
+                if let Some(reason) = stall {
+                    match entry.stalls.iter_mut().find(|s| s.stage == stage) {
+                        Some(existing) => existing.cycles += 1,
+                        None => entry.stalls.push(Stall {
+                            stage,
+                            cycles: 1,
+                            reason,
+                        }),
+                    }
+                }
+            }
+        }
+        if entries.iter().any(|entry| entry.fetch_cycle == usize::MAX) {
+            return Err(invalid("instruction never fetched"));
+        }
+
+        let mut history = Self::new(stages);
+        history.entries = entries;
+        Ok(history)
+    }
+
+    /// Wrong-path instructions fetched behind the entry at `index`, when it
+    /// is a mispredicted branch
+    pub fn squashed_after(&self, index: usize) -> &[HistoryEntry] {
+        if self
+            .entries
+            .get(index)
+            .map_or(true, HistoryEntry::is_wrong_path)
+        {
+            return &[];
+        }
+        let rest = &self.entries[index + 1..];
+        let count = rest
+            .iter()
+            .take_while(|entry| entry.is_wrong_path())
+            .count();
+        &rest[..count]
+    }
+
+    /// Convert to the Kanata 0004 log format of the Konata pipeline viewer.
+    /// Stalled cycles are drawn in a second lane named after the reason.
+    /// Wrong-path instructions are retired as flushed, which Konata shades,
+    /// and the cycle a mispredicted branch squashes them is marked in the
+    /// branch's second lane.
+    pub fn to_konata(&self) -> String {
+        // (cycle, order within the cycle, command)
+        let mut events: Vec<(usize, usize, String)> = Vec::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            let spans = entry.stage_spans(&self.latencies);
+            let label = instruction_to_string(&decode_instruction(entry.word), entry.word);
+            events.push((
+                entry.fetch_cycle,
+                0,
+                format!("I\t{}\t{}\t0", index, entry.id),
+            ));
+            events.push((
+                entry.fetch_cycle,
+                0,
+                format!("L\t{}\t0\t{:08x}: {}", index, entry.pc, label),
+            ));
+
+            for (stage, &(start, length)) in spans.iter().enumerate() {
+                let name = &self.stages[stage];
+                events.push((start, 1, format!("S\t{}\t0\t{}", index, name)));
+                if let Some(reason) = entry.stall_reason(stage) {
+                    let stalled = start + self.latencies[stage];
+                    events.push((stalled, 1, format!("S\t{}\t1\t{}", index, reason.name())));
+                    events.push((
+                        start + length,
+                        0,
+                        format!("E\t{}\t1\t{}", index, reason.name()),
+                    ));
+                }
+                events.push((start + length, 0, format!("E\t{}\t0\t{}", index, name)));
+            }
+            if let Some(&(start, length)) = spans.last() {
+                let (retired, flushed) = match entry.squashed {
+                    Some(cycle) => (cycle, 1),
+                    None => (start + length, 0),
+                };
+                events.push((retired, 2, format!("R\t{}\t{}\t{}", index, index, flushed)));
+            }
+
+            let squashed = self.squashed_after(index);
+            if let Some(cycle) = squashed.first().and_then(|entry| entry.squashed) {
+                events.push((cycle - 1, 1, format!("S\t{}\t1\tsquash", index)));
+                events.push((cycle, 0, format!("E\t{}\t1\tsquash", index)));
+                events.push((
+                    cycle - 1,
+                    1,
+                    format!(
+                        "L\t{}\t1\tmispredicted: squashed {} wrong-path instruction(s) at the end of cycle {}",
+                        index,
+                        squashed.len(),
+                        cycle - 1
+                    ),
+                ));
+            }
+        }
+        events.sort_by_key(|&(cycle, order, _)| (cycle, order));
+
+        let mut log = String::from("Kanata\t0004\n");
+        let mut current = self.first_cycle();
+        let _ = writeln!(log, "C=\t{}", current);
+        for (cycle, _, command) in events {
+            if cycle > current {
+                let _ = writeln!(log, "C\t{}", cycle - current);
+                current = cycle;
+            }
+            log.push_str(&command);
+            log.push('\n');
+        }
+        log
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut bytes = [0u8; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
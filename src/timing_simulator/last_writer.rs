@@ -0,0 +1,186 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// last_writer.rs
+//
+// This file contains the last-writer table of the stepping debugger. When
+// enabled, the timing simulator records, for every general-purpose register
+// and every word of memory, the instruction that wrote it most recently:
+// its address, its place in the run and the cycle it issued in. Asking who
+// wrote a wrong value leads straight to the instruction to look at.
+
+use super::watch::{parse_address, WatchExpression};
+use crate::functional_simulator::instructions::Instruction;
+use crate::functional_simulator::memory::Memory;
+use crate::functional_simulator::registers::{Registers, REGISTER_NAMES};
+use crate::functional_simulator::simulator::{decode_instruction, instruction_to_string};
+use std::collections::HashMap;
+use std::fmt;
+
+/// The most recent write to a register or memory word
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Writer {
+    /// Address of the writing instruction
+    pub pc: u32,
+    /// The instruction word
+    pub word: u32,
+    /// Position of the instruction in the run, counting from 0
+    pub index: usize,
+    /// Cycle the instruction issued in, counting from 0
+    pub cycle: usize,
+    /// Value of the register or word after the write
+    pub value: u32,
+}
+
+impl fmt::Display for Writer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{:08X} {} (instruction {}, cycle {}), value 0x{:08X}",
+            self.pc,
+            instruction_to_string(&decode_instruction(self.word), self.word),
+            self.index,
+            self.cycle,
+            self.value
+        )
+    }
+}
+
+/// A register or memory word to ask about
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Location {
+    Register(u32),
+    /// The word containing an address
+    Word(u32),
+}
+
+impl Location {
+    /// Parse `$t3`, `$11`, or an address such as `0x10010004` or
+    /// `mem[0x10010004]`; an unaligned address means its whole word
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if text.starts_with('$') {
+            return match WatchExpression::parse(text)? {
+                WatchExpression::Register(number) => Ok(Location::Register(number)),
+                _ => Err(format!("unknown register '{}'", text)),
+            };
+        }
+        let address = text
+            .strip_prefix("mem[")
+            .and_then(|inner| inner.strip_suffix(']'))
+            .unwrap_or(text);
+        parse_address(address).map(|address| Location::Word(address & !3))
+    }
+
+    /// Current value of the register or word
+    pub fn value(&self, registers: &Registers, memory: &Memory) -> Option<u32> {
+        match *self {
+            Location::Register(number) => Some(registers.read(number)),
+            Location::Word(address) => memory.read_word(address as usize).ok(),
+        }
+    }
+}
+
+/// The registers and words an instruction writes, given the registers
+/// before it runs. Floating-point registers are not tracked
+pub fn written_locations(
+    instruction: &Instruction,
+    registers: &Registers,
+    pc: u32,
+) -> Vec<Location> {
+    let mut locations = Vec::new();
+    if let Some(rd) = instruction.get_destination_register() {
+        if !instruction.is_fp_instruction() {
+            locations.push(Location::Register(rd));
+        }
+    }
+    if instruction.is_store() {
+        let address = instruction.get_address(registers, pc);
+        locations.push(Location::Word(address & !3));
+    }
+    locations
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Register(number) => write!(f, "{}", REGISTER_NAMES[*number as usize % 32]),
+            Location::Word(address) => write!(f, "mem[0x{:08X}]", address),
+        }
+    }
+}
+
+/// Last writer of every register and memory word written so far
+#[derive(Clone, Debug, Default)]
+pub struct LastWriters {
+    registers: [Option<Writer>; 32],
+    words: HashMap<u32, Writer>,
+    /// Instructions and cycles before the current run started; a stepping
+    /// session runs the program in many short runs
+    pub instruction_base: usize,
+    pub cycle_base: usize,
+}
+
+impl LastWriters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a write by the instruction at `index` and `cycle` of the
+    /// current run
+    pub fn record(
+        &mut self,
+        location: Location,
+        pc: u32,
+        word: u32,
+        index: usize,
+        cycle: usize,
+        value: u32,
+    ) {
+        let writer = Writer {
+            pc,
+            word,
+            index: self.instruction_base + index,
+            cycle: self.cycle_base + cycle,
+            value,
+        };
+        match location {
+            Location::Register(0) => {},
+            Location::Register(number) => self.registers[number as usize % 32] = Some(writer),
+            Location::Word(address) => {
+                self.words.insert(address & !3, writer);
+            },
+        }
+    }
+
+    /// The last write to a location, if it was written during the run
+    pub fn lookup(&self, location: Location) -> Option<&Writer> {
+        match location {
+            Location::Register(number) => self.registers[number as usize % 32].as_ref(),
+            Location::Word(address) => self.words.get(&(address & !3)),
+        }
+    }
+
+    /// Number of distinct memory words written
+    pub fn words_written(&self) -> usize {
+        self.words.len()
+    }
+}
@@ -0,0 +1,322 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// latency_probe.rs
+//
+// This file contains the instruction latency and throughput microbenchmark
+// harness. For each instruction class it generates a dependent chain (each
+// instruction reads the previous result) and an independent stream, runs
+// both through the timing simulator, and reports the measured cycles per
+// instruction. Comparing the results with the configured latencies helps
+// validate the timing model. A class whose instructions never complete is
+// reported as stalled rather than given a misleading figure.
+
+use super::config::{CacheConfig, PipelineConfig};
+use super::simulator::{ExecutionMode, Simulator};
+use std::fmt;
+
+// Registers used by the generated code
+const CHAIN_REG: u32 = 8; // $t0 carries the dependent chain
+const FIRST_STREAM_REG: u32 = 9; // $t1-$t7 receive independent results
+const STREAM_REGS: u32 = 7;
+const SOURCE_A: u32 = 16; // $s0
+const SOURCE_B: u32 = 17; // $s1
+const DATA_ADDRESS: u32 = 0x2000;
+// Cycles without a commit after which draining the out-of-order core stops
+const DRAIN_IDLE_LIMIT: usize = 64;
+
+/// Instruction classes measured by the probe
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProbeClass {
+    IntAlu,
+    Shift,
+    Multiply,
+    Divide,
+    Load,
+    Store,
+    FpAdd,
+    FpMultiply,
+    FpDivide,
+}
+
+impl ProbeClass {
+    pub fn all() -> [ProbeClass; 9] {
+        [
+            ProbeClass::IntAlu,
+            ProbeClass::Shift,
+            ProbeClass::Multiply,
+            ProbeClass::Divide,
+            ProbeClass::Load,
+            ProbeClass::Store,
+            ProbeClass::FpAdd,
+            ProbeClass::FpMultiply,
+            ProbeClass::FpDivide,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProbeClass::IntAlu => "int-alu (add)",
+            ProbeClass::Shift => "shift (sll)",
+            ProbeClass::Multiply => "multiply (mult)",
+            ProbeClass::Divide => "divide (div)",
+            ProbeClass::Load => "load (lw)",
+            ProbeClass::Store => "store (sw)",
+            ProbeClass::FpAdd => "fp-add (add.s)",
+            ProbeClass::FpMultiply => "fp-multiply (mul.s)",
+            ProbeClass::FpDivide => "fp-divide (div.s)",
+        }
+    }
+
+    /// Classes without a destination register cannot form a dependent chain
+    pub fn has_dependent_chain(&self) -> bool {
+        !matches!(
+            self,
+            ProbeClass::Multiply | ProbeClass::Divide | ProbeClass::Store
+        )
+    }
+
+    // Encode one instruction of this class reading `src` and writing `dest`
+    fn encode(&self, dest: u32, src: u32) -> u32 {
+        match self {
+            ProbeClass::IntAlu => r_type(src, SOURCE_B, dest, 0, 0x20),
+            ProbeClass::Shift => r_type(0, src, dest, 1, 0x00),
+            ProbeClass::Multiply => r_type(SOURCE_A, SOURCE_B, 0, 0, 0x18),
+            ProbeClass::Divide => r_type(SOURCE_A, SOURCE_B, 0, 0, 0x1A),
+            ProbeClass::Load => i_type(0x23, src, dest, 0),
+            ProbeClass::Store => i_type(0x2B, 0, SOURCE_A, (DATA_ADDRESS + dest * 4) as u16),
+            ProbeClass::FpAdd => fp_type(src, SOURCE_B, dest, 0x00),
+            ProbeClass::FpMultiply => fp_type(src, SOURCE_B, dest, 0x02),
+            ProbeClass::FpDivide => fp_type(src, SOURCE_B, dest, 0x03),
+        }
+    }
+
+    /// A chain where every instruction depends on the previous result
+    pub fn dependent_chain(&self, length: usize) -> Vec<u32> {
+        (0..length)
+            .map(|_| self.encode(CHAIN_REG, CHAIN_REG))
+            .collect()
+    }
+
+    /// A stream of instructions with no dependences between them
+    pub fn independent_stream(&self, length: usize) -> Vec<u32> {
+        (0..length)
+            .map(|i| {
+                let dest = FIRST_STREAM_REG + (i as u32 % STREAM_REGS);
+                let src = if *self == ProbeClass::Load {
+                    CHAIN_REG
+                } else {
+                    SOURCE_A
+                };
+                self.encode(dest, src)
+            })
+            .collect()
+    }
+}
+
+fn r_type(rs: u32, rt: u32, rd: u32, shamt: u32, funct: u32) -> u32 {
+    (rs << 21) | (rt << 16) | (rd << 11) | (shamt << 6) | funct
+}
+
+fn i_type(opcode: u32, rs: u32, rt: u32, imm: u16) -> u32 {
+    (opcode << 26) | (rs << 21) | (rt << 16) | imm as u32
+}
+
+fn fp_type(fs: u32, ft: u32, fd: u32, funct: u32) -> u32 {
+    (0x11 << 26) | (0x10 << 21) | (ft << 16) | (fs << 11) | (fd << 6) | funct
+}
+
+/// Measured latency and throughput for one instruction class
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProbeResult {
+    pub class: ProbeClass,
+    /// Cycles per instruction along a dependent chain
+    pub latency: Option<f64>,
+    /// Instructions completed per cycle for an independent stream
+    pub throughput: Option<f64>,
+    /// The core stopped completing instructions before the run finished
+    pub stalled: bool,
+}
+
+/// Full results of a probe run
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProbeReport {
+    pub chain_length: usize,
+    pub results: Vec<ProbeResult>,
+}
+
+impl fmt::Display for ProbeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Instruction Latency Probe (chain length {}):",
+            self.chain_length
+        )?;
+        writeln!(
+            f,
+            "  {:<22} {:>16} {:>18}",
+            "Class", "Latency (cycles)", "Throughput (IPC)"
+        )?;
+        for result in &self.results {
+            let format_value = |value: Option<f64>| match value {
+                Some(value) => format!("{:.2}", value),
+                None if result.stalled => "stalled".to_string(),
+                None => "-".to_string(),
+            };
+            writeln!(
+                f,
+                "  {:<22} {:>16} {:>18}",
+                result.class.name(),
+                format_value(result.latency),
+                format_value(result.throughput)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs generated chains through freshly built timing simulators
+pub struct LatencyProbe {
+    pub pipeline_config: PipelineConfig,
+    pub instr_cache_config: CacheConfig,
+    pub data_cache_config: CacheConfig,
+    pub chain_length: usize,
+}
+
+impl LatencyProbe {
+    pub fn new(pipeline_config: PipelineConfig, chain_length: usize) -> Self {
+        // Cold cache misses would dominate the short generated programs, so
+        // the caches default to a zero miss penalty
+        let mut cache_config = CacheConfig::new(32768, 4, 64);
+        cache_config.miss_penalty = 0;
+
+        Self {
+            pipeline_config,
+            instr_cache_config: cache_config.clone(),
+            data_cache_config: cache_config,
+            chain_length: chain_length.max(1),
+        }
+    }
+
+    pub fn run(&self) -> ProbeReport {
+        let results = ProbeClass::all()
+            .iter()
+            .map(|&class| self.probe_class(class))
+            .collect();
+
+        ProbeReport {
+            chain_length: self.chain_length,
+            results,
+        }
+    }
+
+    pub fn probe_class(&self, class: ProbeClass) -> ProbeResult {
+        let mut stalled = false;
+        let latency = if class.has_dependent_chain() {
+            let latency = self.cycles_per_instruction(|n| class.dependent_chain(n));
+            stalled |= latency.is_none();
+            latency
+        } else {
+            None
+        };
+        let cpi = self.cycles_per_instruction(|n| class.independent_stream(n));
+        stalled |= cpi.is_none();
+        let throughput = cpi.map(|cpi| if cpi > 0.0 { 1.0 / cpi } else { 0.0 });
+
+        ProbeResult {
+            class,
+            latency,
+            throughput,
+            stalled,
+        }
+    }
+
+    // Cycles per instruction with the fixed start-up and drain cost removed,
+    // taken from the difference between runs of length n and 2n
+    fn cycles_per_instruction(&self, generate: impl Fn(usize) -> Vec<u32>) -> Option<f64> {
+        let n = self.chain_length;
+        let short = self.measure_cycles(&generate(n))?;
+        let long = self.measure_cycles(&generate(2 * n))?;
+        Some(long.saturating_sub(short) as f64 / n as f64)
+    }
+
+    /// Simulated cycles taken to run a program to completion, or `None` if
+    /// the out-of-order core stops committing before every instruction is done
+    pub fn measure_cycles(&self, program: &[u32]) -> Option<usize> {
+        let memory_size = 32768;
+        let mut simulator = Simulator::new(
+            self.pipeline_config.clone(),
+            self.instr_cache_config.clone(),
+            self.data_cache_config.clone(),
+            memory_size,
+        );
+        simulator.visualization = None;
+        simulator.set_max_instructions(program.len());
+
+        for (i, &word) in program.iter().enumerate() {
+            simulator.memory.write_word_init(i * 4, word);
+        }
+        simulator
+            .memory
+            .write_word_init(DATA_ADDRESS as usize, DATA_ADDRESS);
+        simulator.registers.write(CHAIN_REG, DATA_ADDRESS);
+        simulator.registers.write(SOURCE_A, 7);
+        simulator.registers.write(SOURCE_B, 3);
+        simulator.registers.write_float(SOURCE_B, 1.5);
+
+        // The out-of-order core works on its own copy of the machine state
+        if let ExecutionMode::OutOfOrder(processor) = &mut simulator.execution_mode {
+            processor.memory = simulator.memory.clone();
+            processor.registers = simulator.registers.clone();
+        }
+
+        simulator.run();
+
+        match &mut simulator.execution_mode {
+            ExecutionMode::InOrder(_) => Some(simulator.run_totals.cycles),
+            ExecutionMode::OutOfOrder(processor) => {
+                // Drain in-flight instructions so the count covers their
+                // completion, giving up once commits stop making progress
+                let mut last_commit_cycle = processor.cycles;
+                let mut idle = 0;
+                while processor.instructions_committed < processor.instructions_issued
+                    && idle < DRAIN_IDLE_LIMIT
+                {
+                    let committed = processor.instructions_committed;
+                    processor.tick();
+                    if processor.instructions_committed > committed {
+                        last_commit_cycle = processor.cycles;
+                        idle = 0;
+                    } else {
+                        idle += 1;
+                    }
+                }
+                if processor.instructions_issued < program.len()
+                    || processor.instructions_committed < processor.instructions_issued
+                {
+                    None
+                } else {
+                    Some(last_commit_cycle)
+                }
+            },
+        }
+    }
+}
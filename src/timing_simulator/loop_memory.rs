@@ -0,0 +1,478 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// loop_memory.rs
+//
+// This file contains the memory trace of a timing run and the report built
+// from it for the hottest loops: the cache lines each loop touches, the
+// stride of each of its loads and stores, and which of its data cache
+// misses are compulsory, capacity or conflict misses. Loops are found from
+// the taken backward branches and jumps of the run.
+
+use super::config::CacheConfig;
+use crate::functional_simulator::simulator::{decode_instruction, instruction_to_string};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use std::mem;
+
+/// Widest set map in a rendered report; larger caches share columns
+const SET_MAP_COLUMNS: usize = 64;
+
+/// One load or store of a timing run
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct MemoryAccess {
+    pub pc: u32,
+    pub address: u32,
+    pub store: bool,
+}
+
+/// Data accesses and control flow of a timing run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryTrace {
+    pub accesses: Vec<MemoryAccess>,
+    /// Times each instruction was executed
+    pub executions: BTreeMap<u32, usize>,
+    /// Times each backward branch or jump was taken, by its address and
+    /// target
+    pub back_edges: BTreeMap<(u32, u32), usize>,
+    /// Instruction words, for naming accesses in the report
+    pub words: BTreeMap<u32, u32>,
+}
+
+impl MemoryTrace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Approximate host memory the trace takes
+    pub fn heap_size(&self) -> usize {
+        self.accesses.len() * mem::size_of::<MemoryAccess>()
+            + self.executions.len() * mem::size_of::<(u32, usize)>()
+            + self.back_edges.len() * mem::size_of::<((u32, u32), usize)>()
+            + self.words.len() * mem::size_of::<(u32, u32)>()
+    }
+
+    /// Record an executed instruction and the address execution continues at
+    pub fn record_instruction(&mut self, pc: u32, word: u32, next_pc: u32) {
+        *self.executions.entry(pc).or_insert(0) += 1;
+        self.words.entry(pc).or_insert(word);
+        if next_pc <= pc {
+            *self.back_edges.entry((pc, next_pc)).or_insert(0) += 1;
+        }
+    }
+
+    pub fn record_access(&mut self, pc: u32, address: u32, store: bool) {
+        self.accesses.push(MemoryAccess { pc, address, store });
+    }
+}
+
+/// Why an access missed in the data cache
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum MissKind {
+    /// The first access to the line
+    Compulsory,
+    /// A fully associative cache of the same size would also have missed
+    Capacity,
+    /// Only the mapping of lines to sets caused the miss
+    Conflict,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MissCounts {
+    pub compulsory: usize,
+    pub capacity: usize,
+    pub conflict: usize,
+}
+
+impl MissCounts {
+    fn add(&mut self, kind: MissKind) {
+        match kind {
+            MissKind::Compulsory => self.compulsory += 1,
+            MissKind::Capacity => self.capacity += 1,
+            MissKind::Conflict => self.conflict += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.compulsory + self.capacity + self.conflict
+    }
+}
+
+/// Sets, ways and line size of the data cache the trace is replayed through
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct CacheGeometry {
+    pub sets: usize,
+    pub ways: usize,
+    pub line_size: usize,
+}
+
+impl CacheGeometry {
+    pub fn from_config(config: &CacheConfig) -> Self {
+        let ways = config.associativity.max(1);
+        let line_size = config.block_size.max(1);
+        Self {
+            sets: (config.size / (ways * line_size)).max(1),
+            ways,
+            line_size,
+        }
+    }
+
+    pub fn line(&self, address: u32) -> u32 {
+        address / self.line_size as u32
+    }
+
+    pub fn set(&self, line: u32) -> usize {
+        line as usize % self.sets
+    }
+
+    /// Sets a stride of `stride` bytes cycles through, when it is a power of
+    /// two of at least a line
+    pub fn sets_reached(&self, stride: i64) -> Option<usize> {
+        let stride = stride.unsigned_abs() as usize;
+        if stride < self.line_size || !stride.is_power_of_two() {
+            return None;
+        }
+        let lines = stride / self.line_size;
+        Some((self.sets / lines.min(self.sets)).max(1))
+    }
+}
+
+/// The accesses of one load or store instruction in a loop
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AccessPattern {
+    pub pc: u32,
+    pub instruction: String,
+    pub accesses: usize,
+    /// Most common distance between consecutive addresses, if at least half
+    /// of them share it
+    pub stride: Option<i64>,
+    pub lines: usize,
+    pub misses: MissCounts,
+    /// Sets a power-of-two stride of a line or more maps every access to
+    pub sets_reached: Option<usize>,
+}
+
+/// Cache behaviour of one loop
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LoopMemory {
+    /// Target of the backward branch
+    pub start: u32,
+    /// Address of the backward branch
+    pub end: u32,
+    /// Times the first instruction of the loop body was executed
+    pub iterations: usize,
+    /// Instructions executed inside the loop body
+    pub instructions: usize,
+    /// Share of all executed instructions
+    pub share: f64,
+    pub accesses: usize,
+    /// Addresses of the cache lines the loop touched
+    pub lines: Vec<u32>,
+    /// Lines the loop touched in each set
+    pub set_lines: Vec<usize>,
+    /// Conflict misses of the loop in each set
+    pub set_conflicts: Vec<usize>,
+    pub misses: MissCounts,
+    pub patterns: Vec<AccessPattern>,
+}
+
+/// Cache line usage of the hottest loops of a run
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LoopMemoryReport {
+    pub geometry: CacheGeometry,
+    pub loops: Vec<LoopMemory>,
+}
+
+impl LoopMemoryReport {
+    /// Report the `count` loops that executed the most instructions, with
+    /// the trace replayed through an LRU, write-allocate cache shaped like
+    /// `cache`
+    pub fn new(trace: &MemoryTrace, cache: &CacheConfig, count: usize) -> Self {
+        let geometry = CacheGeometry::from_config(cache);
+        let misses = classify_misses(trace, &geometry);
+        let total: usize = trace.executions.values().sum();
+
+        let mut loops: Vec<LoopMemory> = trace
+            .back_edges
+            .keys()
+            .map(|&(end, start)| {
+                let iterations = trace.executions.get(&start).copied().unwrap_or(0);
+                let instructions = trace.executions.range(start..=end).map(|(_, n)| n).sum();
+                LoopMemory {
+                    start,
+                    end,
+                    iterations,
+                    instructions,
+                    share: instructions as f64 / total.max(1) as f64,
+                    accesses: 0,
+                    lines: Vec::new(),
+                    set_lines: vec![0; geometry.sets],
+                    set_conflicts: vec![0; geometry.sets],
+                    misses: MissCounts::default(),
+                    patterns: Vec::new(),
+                }
+            })
+            .collect();
+        loops.sort_by(|a, b| {
+            b.instructions
+                .cmp(&a.instructions)
+                .then(a.start.cmp(&b.start))
+        });
+        loops.truncate(count);
+
+        for memory in loops.iter_mut() {
+            describe_loop(memory, trace, &geometry, &misses);
+        }
+        Self { geometry, loops }
+    }
+
+    pub fn render(&self) -> String {
+        let geometry = &self.geometry;
+        let mut result = format!(
+            "Hot loops (data cache: {} sets x {} ways, {}-byte lines)\n",
+            geometry.sets, geometry.ways, geometry.line_size
+        );
+        if self.loops.is_empty() {
+            result.push_str("  No loops found\n");
+        }
+        for (index, memory) in self.loops.iter().enumerate() {
+            let _ = writeln!(
+                result,
+                "\nLoop {}: 0x{:08X}-0x{:08X}, {} iterations, {} instructions ({:.1}% of run)",
+                index + 1,
+                memory.start,
+                memory.end,
+                memory.iterations,
+                memory.instructions,
+                memory.share * 100.0
+            );
+            let span = match (memory.lines.first(), memory.lines.last()) {
+                (Some(&first), Some(&last)) => format!(
+                    " from 0x{:08X} to 0x{:08X}",
+                    first * geometry.line_size as u32,
+                    (last + 1) * geometry.line_size as u32 - 1
+                ),
+                _ => String::new(),
+            };
+            let _ = writeln!(
+                result,
+                "  {} data accesses, {} cache lines{}",
+                memory.accesses,
+                memory.lines.len(),
+                span
+            );
+            let _ = writeln!(
+                result,
+                "  Misses: {} compulsory, {} capacity, {} conflict",
+                memory.misses.compulsory, memory.misses.capacity, memory.misses.conflict
+            );
+            let (lines, conflicts) = set_map(geometry, memory);
+            let _ = writeln!(result, "  Sets:      |{}|", lines);
+            if memory.misses.conflict > 0 {
+                let _ = writeln!(result, "  Conflicts: |{}|", conflicts);
+            }
+            for pattern in &memory.patterns {
+                let stride = match pattern.stride {
+                    Some(0) => "same address".to_string(),
+                    Some(stride) => format!("stride {:+}", stride),
+                    None => "irregular".to_string(),
+                };
+                let _ = writeln!(
+                    result,
+                    "  0x{:08X}  {:<22} {:>6} accesses  {:<14} {:>4} lines {:>5} misses",
+                    pattern.pc,
+                    pattern.instruction,
+                    pattern.accesses,
+                    stride,
+                    pattern.lines,
+                    pattern.misses.total()
+                );
+            }
+            for pattern in &memory.patterns {
+                if let (Some(stride), Some(reached)) = (pattern.stride, pattern.sets_reached) {
+                    if reached < geometry.sets && pattern.misses.conflict > 0 {
+                        let _ = writeln!(
+                            result,
+                            "  ! 0x{:08X}: power-of-two stride {:+} maps every access to {} of {} sets, causing {} conflict misses",
+                            pattern.pc, stride, reached, geometry.sets, pattern.misses.conflict
+                        );
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+// Replay the trace through the set-associative cache and a fully
+// associative one of the same size, both LRU, and classify every miss.
+// Hits are `None`.
+fn classify_misses(trace: &MemoryTrace, geometry: &CacheGeometry) -> Vec<Option<MissKind>> {
+    let capacity = geometry.sets * geometry.ways;
+    let mut sets: Vec<Vec<u32>> = vec![Vec::new(); geometry.sets];
+    let mut seen = HashSet::new();
+    // Fully associative LRU: last use of each line, and lines by last use
+    let mut last_use: HashMap<u32, usize> = HashMap::new();
+    let mut by_use: BTreeMap<usize, u32> = BTreeMap::new();
+
+    trace
+        .accesses
+        .iter()
+        .enumerate()
+        .map(|(time, access)| {
+            let line = geometry.line(access.address);
+
+            let set = &mut sets[geometry.set(line)];
+            let hit = match set.iter().position(|&cached| cached == line) {
+                Some(position) => {
+                    set.remove(position);
+                    true
+                },
+                None => {
+                    if set.len() == geometry.ways {
+                        set.pop();
+                    }
+                    false
+                },
+            };
+            set.insert(0, line);
+
+            let fully_associative_hit = match last_use.insert(line, time) {
+                Some(previous) => {
+                    by_use.remove(&previous);
+                    true
+                },
+                None => {
+                    if by_use.len() == capacity {
+                        if let Some((&oldest, &evicted)) = by_use.iter().next() {
+                            by_use.remove(&oldest);
+                            last_use.remove(&evicted);
+                        }
+                    }
+                    false
+                },
+            };
+            by_use.insert(time, line);
+
+            if hit {
+                None
+            } else if seen.insert(line) {
+                Some(MissKind::Compulsory)
+            } else if fully_associative_hit {
+                Some(MissKind::Conflict)
+            } else {
+                Some(MissKind::Capacity)
+            }
+        })
+        .collect()
+}
+
+fn describe_loop(
+    memory: &mut LoopMemory,
+    trace: &MemoryTrace,
+    geometry: &CacheGeometry,
+    misses: &[Option<MissKind>],
+) {
+    let mut lines = BTreeSet::new();
+    let mut by_pc: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+    for (index, access) in trace.accesses.iter().enumerate() {
+        if access.pc < memory.start || access.pc > memory.end {
+            continue;
+        }
+        memory.accesses += 1;
+        let line = geometry.line(access.address);
+        lines.insert(line);
+        by_pc.entry(access.pc).or_default().push(index);
+        if let Some(kind) = misses[index] {
+            memory.misses.add(kind);
+            if kind == MissKind::Conflict {
+                memory.set_conflicts[geometry.set(line)] += 1;
+            }
+        }
+    }
+    memory.lines = lines.into_iter().collect();
+    for &line in &memory.lines {
+        memory.set_lines[geometry.set(line)] += 1;
+    }
+
+    for (pc, indices) in by_pc {
+        let addresses: Vec<u32> = indices
+            .iter()
+            .map(|&index| trace.accesses[index].address)
+            .collect();
+        let stride = common_stride(&addresses);
+        let mut pattern_misses = MissCounts::default();
+        for kind in indices.iter().filter_map(|&index| misses[index]) {
+            pattern_misses.add(kind);
+        }
+        let pattern_lines: HashSet<u32> = addresses
+            .iter()
+            .map(|&address| geometry.line(address))
+            .collect();
+        let word = trace.words.get(&pc).copied().unwrap_or(0);
+        memory.patterns.push(AccessPattern {
+            pc,
+            instruction: instruction_to_string(&decode_instruction(word), word),
+            accesses: addresses.len(),
+            stride,
+            lines: pattern_lines.len(),
+            misses: pattern_misses,
+            sets_reached: stride.and_then(|stride| geometry.sets_reached(stride)),
+        });
+    }
+}
+
+// The most common distance between consecutive addresses, if at least half
+// of the distances are that one
+fn common_stride(addresses: &[u32]) -> Option<i64> {
+    let mut counts: BTreeMap<i64, usize> = BTreeMap::new();
+    for pair in addresses.windows(2) {
+        *counts.entry(pair[1] as i64 - pair[0] as i64).or_insert(0) += 1;
+    }
+    let deltas = addresses.len().saturating_sub(1);
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.abs().cmp(&a.0.abs())))
+        .filter(|&(_, count)| count * 2 >= deltas)
+        .map(|(stride, _)| stride)
+}
+
+// One character per column of sets: how many of the loop's lines map there,
+// and whether any of its conflict misses happened there
+fn set_map(geometry: &CacheGeometry, memory: &LoopMemory) -> (String, String) {
+    let columns = geometry.sets.min(SET_MAP_COLUMNS);
+    let per_column = (geometry.sets + columns - 1) / columns;
+    let mut lines = String::new();
+    let mut conflicts = String::new();
+    for column in 0..columns {
+        let range = column * per_column..((column + 1) * per_column).min(geometry.sets);
+        let count: usize = memory.set_lines[range.clone()].iter().sum();
+        lines.push(match count {
+            0 => '.',
+            1..=9 => (b'0' + count as u8) as char,
+            _ => '+',
+        });
+        let conflicted = memory.set_conflicts[range].iter().any(|&n| n > 0);
+        conflicts.push(if conflicted { '^' } else { ' ' });
+    }
+    (lines, conflicts)
+}
@@ -22,13 +22,32 @@
 // mod.rs
 //
 // This file is the module declaration for the timing_simulator module.
-// It re-exports the components, config, pipeline, simulator, visualization,
-// tomasulo, and branch_predictor modules.
+// It re-exports the cache_sweep, cache_validation, compare, components, config, data_breakpoint, events, history, last_writer, latency_probe, loop_memory, pipeline, regions, sampling, simulator, stats, stress, visualization,
+// tlb, tomasulo, trace_sampling, unrolling, watch, what_if, workloads, and branch_predictor modules.
 
 pub mod branch_predictor;
+pub mod cache_sweep;
+pub mod cache_validation;
+pub mod compare;
 pub mod components;
 pub mod config;
+pub mod data_breakpoint;
+pub mod events;
+pub mod history;
+pub mod last_writer;
+pub mod latency_probe;
+pub mod loop_memory;
 pub mod pipeline;
+pub mod regions;
+pub mod sampling;
 pub mod simulator;
+pub mod stats;
+pub mod stress;
+pub mod tlb;
 pub mod tomasulo;
+pub mod trace_sampling;
+pub mod unrolling;
 pub mod visualization;
+pub mod watch;
+pub mod what_if;
+pub mod workloads;
@@ -23,15 +23,24 @@
 //
 // This file contains the implementation of the MIPS in-order pipeline.
 // It defines the pipeline stages, hazard detection logic, and forwarding paths.
+// The stage layout comes from the configuration, so hazard distances and the
+// branch penalty are derived from where each kind of stage sits.
 
 use super::components::CacheHierarchy;
+use super::events::{EventKind, PendingEvents};
+use super::stats::StallBreakdown;
+use super::tlb::Tlb;
 use crate::functional_simulator::instructions::Instruction;
-use crate::functional_simulator::memory::Memory;
+use crate::functional_simulator::memory::{AddressSegment, Memory};
 use crate::functional_simulator::registers::Registers;
-use crate::timing_simulator::config::{CacheConfig, PipelineConfig};
-
-// Pipeline stage types
-#[derive(Debug, Clone, Copy, PartialEq)]
+use crate::timing_simulator::config::{CacheConfig, ControlHazardScheme, PipelineConfig};
+use crate::utils::clock;
+use crate::utils::limits::MemoryUsage;
+use crate::utils::syscall::BlockTransfer;
+use std::ops::RangeInclusive;
+
+// Pipeline stage types, in pipeline order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum PipelineStageType {
     Fetch,
     Decode,
@@ -40,6 +49,19 @@ pub enum PipelineStageType {
     Writeback,
 }
 
+impl PipelineStageType {
+    /// Classic five-stage abbreviation, e.g. `IF` or `MEM`
+    pub fn short_name(self) -> &'static str {
+        match self {
+            PipelineStageType::Fetch => "IF",
+            PipelineStageType::Decode => "ID",
+            PipelineStageType::Execute => "EX",
+            PipelineStageType::Memory => "MEM",
+            PipelineStageType::Writeback => "WB",
+        }
+    }
+}
+
 // Pipeline stage status
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PipelineStageStatus {
@@ -73,6 +95,7 @@ pub enum HazardType {
 
 #[derive(Clone)]
 pub struct PipelineStage {
+    pub name: String,
     pub stage_type: PipelineStageType,
     pub latency: usize,
     pub cycles_remaining: usize,
@@ -87,6 +110,7 @@ pub struct PipelineStage {
 impl PipelineStage {
     pub fn new(stage_type: PipelineStageType, latency: usize) -> Self {
         Self {
+            name: stage_type.short_name().to_string(),
             stage_type,
             latency,
             cycles_remaining: 0,
@@ -151,24 +175,55 @@ impl PipelineStage {
     }
 }
 
+#[derive(Clone)]
 pub struct Pipeline {
     pub stages: Vec<PipelineStage>,
     pub cache_hierarchy: CacheHierarchy,
     pub branch_predictor: BranchPredictor,
     pub forwarding_enabled: bool,
+    pub branch_prediction_enabled: bool,
+    pub control_hazard_scheme: ControlHazardScheme,
+    /// Cycles lost to resolving conditional branches under the scheme
+    pub branch_stalls: usize,
+    /// Instructions executed in the delay slot of a branch or jump
+    pub delay_slots: usize,
+    pub caches_enabled: bool,
     pub stall_cycles: usize,
     pub branch_mispredictions: usize,
     pub instruction_count: usize,
     pub cycle_count: usize,
     pub stall_count: usize,
     pub cache_miss_stalls: usize,
+    /// Cycles memcpy and memset syscalls held the memory stage moving lines
+    pub block_transfer_cycles: usize,
     pub data_hazard_stalls: usize,
     pub control_hazard_stalls: usize,
     pub structural_hazard_stalls: usize,
+    /// Fetch and memory stages share one port to memory
+    pub unified_memory_port: bool,
+    /// Cycles fetches waited for the memory port held by a load or store
+    pub memory_port_stalls: usize,
+    /// Address ranges whose accesses bypass the caches
+    pub uncached_regions: Vec<RangeInclusive<u32>>,
+    /// Loads and stores that bypassed the data cache
+    pub uncached_accesses: usize,
+    /// Instruction and data TLBs, when modelled
+    pub itlb: Option<Tlb>,
+    pub dtlb: Option<Tlb>,
+    /// Core clock frequency in hertz, for reporting simulated time
+    pub clock_hz: u64,
+    /// Stall cycles charged to each instruction, by cause
+    pub stall_breakdown: StallBreakdown,
     pub hazard_stats: Vec<(HazardType, usize)>,
     pub register_file_accesses: usize,
     pub memory_accesses: usize,
     pub forwarding_used: usize,
+    /// Resolved branches emitted for the event log, when enabled
+    pub events: Option<PendingEvents>,
+    // Destination of each recent instruction, newest first, and whether it is a load
+    recent_destinations: Vec<Option<(u32, bool)>>,
+    // Whether each recent instruction's data access went to memory, newest first
+    recent_memory_accesses: Vec<bool>,
 }
 
 impl Pipeline {
@@ -179,23 +234,19 @@ impl Pipeline {
         memory: Memory,
     ) -> Self {
         // Create pipeline stages
-        let stage_types = vec![
-            PipelineStageType::Fetch,
-            PipelineStageType::Decode,
-            PipelineStageType::Execute,
-            PipelineStageType::Memory,
-            PipelineStageType::Writeback,
-        ];
-
         let mut stages = Vec::new();
-        for (i, &stage_type) in stage_types.iter().enumerate() {
+        for (i, stage_config) in config.stages.iter().enumerate() {
             let latency = if i < config.stage_latencies.len() {
-                config.stage_latencies[i]
+                config.stage_latencies[i].max(1)
             } else {
                 1
             };
-            stages.push(PipelineStage::new(stage_type, latency));
+            let mut stage = PipelineStage::new(stage_config.kind, latency);
+            stage.name = stage_config.name.clone();
+            stages.push(stage);
         }
+        let recent_destinations = vec![None; stages.len()];
+        let recent_memory_accesses = vec![false; stages.len()];
 
         // Create a cache hierarchy with L1 instruction and data caches
         let cache_hierarchy = CacheHierarchy::new(
@@ -217,21 +268,325 @@ impl Pipeline {
         Self {
             stages,
             cache_hierarchy,
-            branch_predictor: BranchPredictor::new(),
-            forwarding_enabled: true,
+            branch_predictor: BranchPredictor::with_type(config.branch_predictor_type),
+            forwarding_enabled: config.forwarding_enabled,
+            branch_prediction_enabled: config.branch_prediction_enabled,
+            control_hazard_scheme: config.control_hazard_scheme,
+            branch_stalls: 0,
+            delay_slots: 0,
+            caches_enabled: config.caches_enabled,
             stall_cycles: 0,
             branch_mispredictions: 0,
             instruction_count: 0,
             cycle_count: 0,
             stall_count: 0,
             cache_miss_stalls: 0,
+            block_transfer_cycles: 0,
             data_hazard_stalls: 0,
             control_hazard_stalls: 0,
             structural_hazard_stalls: 0,
+            unified_memory_port: config.unified_memory_port,
+            memory_port_stalls: 0,
+            uncached_regions: config.uncached_regions.clone(),
+            uncached_accesses: 0,
+            itlb: config.tlb_config.clone().map(Tlb::new),
+            dtlb: config.tlb_config.clone().map(Tlb::new),
+            clock_hz: config.clock_hz,
+            stall_breakdown: StallBreakdown::default(),
             hazard_stats,
             register_file_accesses: 0,
             memory_accesses: 0,
             forwarding_used: 0,
+            recent_destinations,
+            recent_memory_accesses,
+            events: None,
+        }
+    }
+
+    /// Host memory held by the caches, with their copies of the memory image
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.cache_hierarchy.memory_usage()
+    }
+
+    /// Stall cycles needed before `instruction` can read its operands from
+    /// the instructions ahead of it. With forwarding a result can be used
+    /// once it leaves the last execute stage, or the last memory stage for a
+    /// load; without it the result must reach the register file.
+    pub fn data_hazard_stall_cycles(&mut self, instruction: &Instruction) -> usize {
+        let sources = instruction.get_source_registers();
+        let interval = self.issue_interval();
+        let needed_at = self.operand_cycle();
+        let mut stall = 0;
+        let mut dependent = false;
+
+        for (distance, producer) in self.recent_destinations.iter().enumerate() {
+            if let Some((reg, is_load)) = *producer {
+                if reg == 0 || !sources.contains(&reg) {
+                    continue;
+                }
+                dependent = true;
+                // Cycles between the producer's fetch and this instruction's
+                let gap = (distance + 1) * interval;
+                let needed = self.result_cycle(is_load).saturating_sub(gap + needed_at);
+                stall = stall.max(needed);
+            }
+        }
+
+        if stall > 0 {
+            self.data_hazard_stalls += stall;
+            self.hazard_stats[0].1 += 1;
+        }
+        if dependent && self.forwarding_enabled {
+            self.forwarding_used += 1;
+        }
+
+        self.recent_destinations.pop();
+        self.recent_destinations.insert(
+            0,
+            instruction
+                .get_destination_register()
+                .map(|reg| (reg, instruction.is_load())),
+        );
+        stall
+    }
+
+    /// Whether an access to `address` goes through the caches. With the
+    /// caches off nothing does, and kseg1 and the uncached regions never do.
+    pub fn is_cached(&self, address: u32) -> bool {
+        self.caches_enabled
+            && AddressSegment::of(address).is_cached()
+            && !self
+                .uncached_regions
+                .iter()
+                .any(|region| region.contains(&address))
+    }
+
+    /// Page-walk cycles the TLBs add to the fetch of `pc` and to a data
+    /// access at `data_address`, as (fetch, data)
+    pub fn tlb_stall_cycles(&mut self, pc: u32, data_address: Option<u32>) -> (usize, usize) {
+        let fetch = self.itlb.as_mut().map_or(0, |tlb| tlb.access(pc));
+        let data = match (self.dtlb.as_mut(), data_address) {
+            (Some(tlb), Some(address)) => tlb.access(address),
+            _ => 0,
+        };
+        (fetch, data)
+    }
+
+    /// Switch both TLBs to address space `asid` and return the cycles the
+    /// switch holds the pipeline, which flush in parallel
+    pub fn switch_asid(&mut self, asid: u8) -> usize {
+        [self.itlb.as_mut(), self.dtlb.as_mut()]
+            .into_iter()
+            .flatten()
+            .map(|tlb| tlb.set_asid(asid))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Cycles the fetch of `instruction` waits for a unified memory port.
+    /// The fetch overlaps the memory stage of the instruction issued
+    /// `stage_start(memory_stage)` fetches earlier, and when both go to
+    /// memory, on a miss or with the caches off, the data access wins.
+    pub fn memory_port_stall(
+        &mut self,
+        instruction: &Instruction,
+        fetch_to_memory: bool,
+        data_to_memory: bool,
+    ) -> usize {
+        let interval = self.issue_interval();
+        let distance = self.stage_start(self.memory_stage()) / interval;
+        let conflict = self.unified_memory_port
+            && fetch_to_memory
+            && distance > 0
+            && self
+                .recent_memory_accesses
+                .get(distance - 1)
+                .copied()
+                .unwrap_or(false);
+
+        self.recent_memory_accesses.pop();
+        self.recent_memory_accesses
+            .insert(0, data_to_memory && instruction.is_memory_access());
+
+        if !conflict {
+            return 0;
+        }
+        self.memory_port_stalls += 1;
+        self.hazard_stats[4].1 += 1;
+        1
+    }
+
+    /// Have the pipeline and its caches emit events for the event log
+    pub fn enable_events(&mut self) {
+        self.events = Some(PendingEvents::new());
+        self.cache_hierarchy.enable_events();
+    }
+
+    /// Events emitted since the last call: cache lookups, then resolved
+    /// branches
+    pub fn take_events(&mut self) -> Vec<EventKind> {
+        let mut events = self.cache_hierarchy.take_events();
+        if let Some(pending) = self.events.as_mut() {
+            events.extend(pending.take());
+        }
+        events
+    }
+
+    /// Return the cycles lost to a resolved conditional branch. With
+    /// flushing that is the flush penalty when it was mispredicted; stalling
+    /// and delay slots wait for every branch, the slot hiding one cycle.
+    pub fn resolve_branch(&mut self, pc: u32, taken: bool, target: u32) -> usize {
+        let (penalty, mispredicted) = match self.control_hazard_scheme {
+            ControlHazardScheme::Flush => {
+                let predicted = self.branch_prediction_enabled && self.branch_predictor.predict(pc);
+                self.branch_predictor.update(pc, taken, target);
+                if predicted == taken {
+                    (0, false)
+                } else {
+                    self.branch_mispredictions += 1;
+                    (self.branch_penalty(), true)
+                }
+            },
+            ControlHazardScheme::Stall => (self.branch_penalty(), false),
+            ControlHazardScheme::DelaySlot => (self.branch_penalty().saturating_sub(1), false),
+        };
+        if let Some(events) = self.events.as_mut() {
+            events.emit(EventKind::Branch {
+                taken,
+                mispredicted,
+            });
+        }
+        if penalty > 0 {
+            self.branch_stalls += penalty;
+            self.control_hazard_stalls += penalty;
+            self.hazard_stats[3].1 += 1;
+        }
+        penalty
+    }
+
+    /// Position of the first stage of a kind
+    pub fn first_stage(&self, stage_type: PipelineStageType) -> Option<usize> {
+        self.stages
+            .iter()
+            .position(|stage| stage.stage_type == stage_type)
+    }
+
+    /// Position of the last stage of a kind
+    pub fn last_stage(&self, stage_type: PipelineStageType) -> Option<usize> {
+        self.stages
+            .iter()
+            .rposition(|stage| stage.stage_type == stage_type)
+    }
+
+    /// Cycle, counted from fetch, in which an instruction enters a stage
+    pub fn stage_start(&self, index: usize) -> usize {
+        self.stages[..index].iter().map(|stage| stage.latency).sum()
+    }
+
+    fn stage_end(&self, index: usize) -> usize {
+        self.stage_start(index) + self.stages[index].latency
+    }
+
+    /// Cycles between the fetches of two independent instructions. A stage
+    /// taking several cycles holds back everything behind it.
+    pub fn issue_interval(&self) -> usize {
+        self.stages
+            .iter()
+            .map(|stage| stage.latency)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Cycles lost by a mispredicted branch: it resolves at the end of the
+    /// first execute stage and everything fetched behind it is flushed
+    pub fn branch_penalty(&self) -> usize {
+        let resolved = self.stage_end(self.execute_stage());
+        resolved.saturating_sub(self.issue_interval())
+    }
+
+    /// Stage where an instruction waits for its operands
+    pub fn operand_stage(&self) -> usize {
+        self.last_stage(PipelineStageType::Decode)
+            .unwrap_or_else(|| self.execute_stage())
+    }
+
+    /// Stage where memory is accessed, the last execute stage if there is
+    /// no memory stage
+    pub fn memory_stage(&self) -> usize {
+        self.last_stage(PipelineStageType::Memory)
+            .or_else(|| self.last_stage(PipelineStageType::Execute))
+            .unwrap_or(0)
+    }
+
+    fn execute_stage(&self) -> usize {
+        self.first_stage(PipelineStageType::Execute).unwrap_or(0)
+    }
+
+    /// Cycles a memcpy or memset syscall holds the memory stage beyond a
+    /// single access. The block moves a cache line at a time: each line it
+    /// reads and each line it writes is one data cache access, where a
+    /// loop would make one per word.
+    pub fn block_transfer_stall(&mut self, transfer: &BlockTransfer) -> usize {
+        let config = &self.cache_hierarchy.l1_data_cache.config;
+        let (hit_latency, miss_penalty) = (config.hit_latency, config.miss_penalty);
+        let mut cycles = 0;
+        for (line, store) in transfer.lines(config.block_size as u32) {
+            cycles += if !self.is_cached(line) {
+                hit_latency + miss_penalty
+            } else if store {
+                self.cache_hierarchy.write_data(line as usize, &[0; 4])
+            } else {
+                self.cache_hierarchy
+                    .read_data(line as usize)
+                    .map_or(hit_latency, |(_, latency)| latency)
+            };
+        }
+        let stall = cycles.saturating_sub(hit_latency);
+        self.block_transfer_cycles += stall;
+        stall
+    }
+
+    /// Cycles after a producer's fetch that a dependent instruction can be
+    /// fetched without stalling
+    pub fn result_delay(&self, is_load: bool) -> usize {
+        self.result_cycle(is_load)
+            .saturating_sub(self.operand_cycle())
+    }
+
+    /// Cycles a taken loop branch costs once the loop runs steadily: none
+    /// when the predictor has learned it, the full resolution otherwise
+    pub fn loop_branch_cost(&self) -> usize {
+        match self.control_hazard_scheme {
+            ControlHazardScheme::Flush if self.branch_prediction_enabled => 0,
+            ControlHazardScheme::Flush | ControlHazardScheme::Stall => self.branch_penalty(),
+            ControlHazardScheme::DelaySlot => self.branch_penalty().saturating_sub(1),
+        }
+    }
+
+    // Cycle, counted from the producer's fetch, from which a result can be read
+    fn result_cycle(&self, is_load: bool) -> usize {
+        if !self.forwarding_enabled {
+            // Registers are written in the first half of write-back and read
+            // in the second half of a cycle
+            return match self.first_stage(PipelineStageType::Writeback) {
+                Some(index) => self.stage_start(index),
+                None => self.stage_end(self.stages.len() - 1),
+            };
+        }
+        let producer = if is_load {
+            self.memory_stage()
+        } else {
+            self.last_stage(PipelineStageType::Execute).unwrap_or(0)
+        };
+        self.stage_end(producer)
+    }
+
+    // Cycle, counted from the consumer's fetch, in which operands are read
+    fn operand_cycle(&self) -> usize {
+        if self.forwarding_enabled {
+            self.stage_start(self.execute_stage())
+        } else {
+            self.stage_start(self.operand_stage())
         }
     }
 
@@ -539,9 +894,8 @@ impl Pipeline {
                     if let Some(_target) = self.branch_predictor.get_target(stage.pc) {
                         // We have a predicted target in our BTB
                         return true;
-                    } else if let Some(_target) = instr.get_immediate_target() {
-                        // For immediate targets (e.g., J instruction), we can compute the target
-                        // This is a simplified example - in reality, we would compute the proper target
+                    } else if let Some(_target) = instr.calculate_branch_target(stage.pc) {
+                        // Immediate branches and jumps have a target known at decode
                         return true;
                     }
                 }
@@ -583,6 +937,11 @@ impl Pipeline {
             self.instruction_count
         ));
         stats.push_str(&format!("  Total Cycles: {}\n", self.cycle_count));
+        stats.push_str(&format!(
+            "  Simulated Time: {} at {}\n",
+            clock::format_time(self.cycle_count as u64, self.clock_hz),
+            clock::format_frequency(self.clock_hz)
+        ));
 
         if self.instruction_count > 0 {
             let cpi = self.cycle_count as f32 / self.instruction_count as f32;
@@ -606,11 +965,37 @@ impl Pipeline {
             "    Cache Miss Stalls: {}\n",
             self.cache_miss_stalls
         ));
+        if self.unified_memory_port {
+            stats.push_str(&format!(
+                "      Memory Port Conflicts: {}\n",
+                self.memory_port_stalls
+            ));
+        }
+        if self.uncached_accesses > 0 {
+            stats.push_str(&format!(
+                "    Uncached Accesses: {}\n",
+                self.uncached_accesses
+            ));
+        }
+        if self.block_transfer_cycles > 0 {
+            stats.push_str(&format!(
+                "    Block Transfer Cycles: {}\n",
+                self.block_transfer_cycles
+            ));
+        }
 
+        stats.push_str(&format!(
+            "  Control Hazard Scheme: {}\n",
+            self.control_hazard_scheme.name()
+        ));
+        stats.push_str(&format!("  Branch Stall Cycles: {}\n", self.branch_stalls));
         stats.push_str(&format!(
             "  Branch Mispredictions: {}\n",
             self.branch_mispredictions
         ));
+        if self.control_hazard_scheme == ControlHazardScheme::DelaySlot {
+            stats.push_str(&format!("  Delay Slots Executed: {}\n", self.delay_slots));
+        }
 
         if self.forwarding_enabled {
             stats.push_str(&format!(
@@ -625,6 +1010,29 @@ impl Pipeline {
         ));
         stats.push_str(&format!("  Memory Accesses: {}\n", self.memory_accesses));
 
+        for (name, tlb) in [("ITLB", &self.itlb), ("DTLB", &self.dtlb)] {
+            if let Some(tlb) = tlb {
+                stats.push_str(&format!(
+                    "  {} Miss Rate: {:.2}% ({} of {}), {} page-walk cycles\n",
+                    name,
+                    tlb.stats.miss_rate() * 100.0,
+                    tlb.stats.misses,
+                    tlb.stats.accesses,
+                    tlb.stats.walk_cycles
+                ));
+            }
+        }
+        if let Some(tlb) = self
+            .dtlb
+            .as_ref()
+            .filter(|tlb| tlb.stats.context_switches > 0)
+        {
+            stats.push_str(&format!(
+                "  TLB Context Switches: {} ({} flushes)\n",
+                tlb.stats.context_switches, tlb.stats.flushes
+            ));
+        }
+
         stats.push_str(&format!("\nHazard Statistics:\n"));
         for &(hazard_type, count) in &self.hazard_stats {
             stats.push_str(&format!("  {:?}: {}\n", hazard_type, count));
@@ -0,0 +1,257 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// regions.rs
+//
+// This file contains the region of interest of a timed run. Benchmarks
+// usually time a kernel inside a larger program, so the program marks the
+// start and end of the kernel with syscalls 74 and 75 and the statistics
+// are collected only between the markers, leaving out loading the input,
+// warming the caches and printing the results. Syscalls 76 and 77 mark
+// named regions, which may nest, so the phases of a program can be
+// compared in one run.
+
+use super::stats::{CpiStack, IntervalSample, StatsCounters};
+use crate::functional_simulator::guest;
+use crate::functional_simulator::memory::Memory;
+use serde::Serialize;
+
+/// Longest region name read from guest memory
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Statistics of the instructions run between the region markers
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RegionSample {
+    /// Times the region was entered and left
+    pub passes: usize,
+    pub stats: IntervalSample,
+    pub cpi_stack: CpiStack,
+}
+
+/// Statistics of one named region
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NamedRegionSample {
+    pub name: String,
+    /// Regions open around it when it was first entered
+    pub depth: usize,
+    pub passes: usize,
+    pub stats: IntervalSample,
+    pub cpi_stack: CpiStack,
+}
+
+#[derive(Clone, Debug)]
+struct NamedRegion {
+    name: String,
+    depth: usize,
+    first: StatsCounters,
+    measured: StatsCounters,
+    passes: usize,
+}
+
+/// Collects the counters of every pass through the region of interest and
+/// through each named region
+#[derive(Clone, Debug, Default)]
+pub struct RegionRecorder {
+    /// Counters when the open pass began
+    begun: Option<StatsCounters>,
+    /// Counters when the first pass began
+    first: Option<StatsCounters>,
+    measured: StatsCounters,
+    passes: usize,
+    /// Named regions in the order they were first entered
+    named: Vec<NamedRegion>,
+    /// Open named regions, innermost last, with the counters their pass
+    /// began at; a region entered again while open has no counters
+    open: Vec<(usize, Option<StatsCounters>)>,
+}
+
+impl RegionRecorder {
+    /// Discard the passes of a previous run
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Start a pass at the given counters. A begin marker inside an open
+    /// pass is ignored, so the pass counts from the outermost marker.
+    pub fn begin(&mut self, counters: StatsCounters) {
+        if self.begun.is_none() {
+            self.begun = Some(counters);
+            self.first.get_or_insert(counters);
+        }
+    }
+
+    /// End the open pass at the given counters; an end marker without a
+    /// begin is ignored
+    pub fn end(&mut self, counters: StatsCounters) {
+        if let Some(start) = self.begun.take() {
+            self.measured.add(&counters.since(&start));
+            self.passes += 1;
+        }
+    }
+
+    /// Enter the named region at the given counters
+    pub fn begin_named(&mut self, name: String, counters: StatsCounters) {
+        let index = match self.named.iter().position(|region| region.name == name) {
+            Some(index) => index,
+            None => {
+                self.named.push(NamedRegion {
+                    name,
+                    depth: self.open.len(),
+                    first: counters,
+                    measured: StatsCounters::default(),
+                    passes: 0,
+                });
+                self.named.len() - 1
+            },
+        };
+        // A recursive entry is counted by the outermost pass
+        let reentered = self.open.iter().any(|&(open, _)| open == index);
+        self.open
+            .push((index, if reentered { None } else { Some(counters) }));
+    }
+
+    /// Leave the innermost open region with this name, and any regions
+    /// still open inside it; an end marker for a region that is not open
+    /// is ignored
+    pub fn end_named(&mut self, name: &str, counters: StatsCounters) {
+        let position = self
+            .open
+            .iter()
+            .rposition(|&(index, _)| self.named[index].name == name);
+        if let Some(position) = position {
+            self.close_named(position, counters);
+        }
+    }
+
+    fn close_named(&mut self, position: usize, counters: StatsCounters) {
+        for (index, start) in self.open.split_off(position) {
+            if let Some(start) = start {
+                let region = &mut self.named[index];
+                region.measured.add(&counters.since(&start));
+                region.passes += 1;
+            }
+        }
+    }
+
+    /// End every pass still open when the run stops
+    pub fn finish(&mut self, counters: StatsCounters) {
+        self.end(counters);
+        self.close_named(0, counters);
+    }
+
+    /// The counters of all passes added together, if the region was entered
+    pub fn counters(&self) -> Option<StatsCounters> {
+        if self.passes > 0 {
+            Some(self.measured)
+        } else {
+            None
+        }
+    }
+
+    pub fn sample(&self) -> Option<RegionSample> {
+        let counters = self.counters()?;
+        let first = self.first.unwrap_or_default();
+        let mut stats = IntervalSample::between(0, &StatsCounters::default(), &counters);
+        stats.start_instruction = first.instructions;
+        stats.start_cycle = first.cycles;
+        Some(RegionSample {
+            passes: self.passes,
+            stats,
+            cpi_stack: CpiStack::new(&counters),
+        })
+    }
+
+    /// The named regions that were entered, in the order they first were
+    pub fn named_samples(&self) -> Vec<NamedRegionSample> {
+        self.named
+            .iter()
+            .filter(|region| region.passes > 0)
+            .map(|region| {
+                let mut stats =
+                    IntervalSample::between(0, &StatsCounters::default(), &region.measured);
+                stats.start_instruction = region.first.instructions;
+                stats.start_cycle = region.first.cycles;
+                NamedRegionSample {
+                    name: region.name.clone(),
+                    depth: region.depth,
+                    passes: region.passes,
+                    stats,
+                    cpi_stack: CpiStack::new(&region.measured),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A region name from the NUL-terminated string at `address`, or the
+/// address itself if it holds no readable name
+pub fn read_name(memory: &Memory, address: u32) -> String {
+    match guest::read_string(memory, address, MAX_NAME_LENGTH) {
+        Ok(name) if !name.is_empty() => name,
+        _ => format!("0x{:08X}", address),
+    }
+}
+
+/// A table of the named regions, nested regions indented under the one
+/// they were entered in
+pub fn render_regions(regions: &[NamedRegionSample]) -> String {
+    let mut result = format!(
+        "Regions:\n  {:<20} {:>6} {:>12} {:>12} {:>7} {:>8} {:>8}\n",
+        "name", "passes", "instructions", "cycles", "CPI", "L1I miss", "L1D miss"
+    );
+    for region in regions {
+        let stats = &region.stats;
+        let name = format!("{}{}", "  ".repeat(region.depth), region.name);
+        result.push_str(&format!(
+            "  {:<20} {:>6} {:>12} {:>12} {:>7.3} {:>7.1}% {:>7.1}%\n",
+            name,
+            region.passes,
+            stats.instructions,
+            stats.cycles,
+            stats.cpi,
+            100.0 * stats.icache_miss_rate,
+            100.0 * stats.dcache_miss_rate
+        ));
+    }
+    result
+}
+
+impl RegionSample {
+    pub fn render(&self) -> String {
+        let stats = &self.stats;
+        format!(
+            "Region of interest: {} instructions over {} cycles (CPI {:.3}) in {} pass{}\n  \
+             L1I misses {}/{} ({:.1}%), L1D misses {}/{} ({:.1}%), branch mispredictions {}\n",
+            stats.instructions,
+            stats.cycles,
+            stats.cpi,
+            self.passes,
+            if self.passes == 1 { "" } else { "es" },
+            stats.icache_misses,
+            stats.icache_accesses,
+            100.0 * stats.icache_miss_rate,
+            stats.dcache_misses,
+            stats.dcache_accesses,
+            100.0 * stats.dcache_miss_rate,
+            stats.branch_mispredictions
+        )
+    }
+}
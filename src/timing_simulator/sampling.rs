@@ -0,0 +1,248 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// sampling.rs
+//
+// This file contains the SimPoint-style sampled simulation mode.
+// The functional simulator executes the whole program and stays the
+// authoritative architectural state. At regular points its state is copied
+// into the timing simulator, which runs a warmup window followed by a
+// measurement window. The measured CPIs are combined into an estimate of the
+// CPI and cycle count for the whole run.
+
+use super::config::{CacheConfig, PipelineConfig};
+use super::simulator::Simulator as TimingSimulator;
+use super::stats::StatsInterval;
+use crate::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use crate::utils::limits::TerminationReason;
+
+/// Sizes of the fast-forward, warmup and measurement windows in instructions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SamplingConfig {
+    pub fast_forward: usize,
+    pub warmup: usize,
+    pub measure: usize,
+    pub max_samples: Option<usize>,
+}
+
+impl SamplingConfig {
+    pub fn new(fast_forward: usize, warmup: usize, measure: usize) -> Self {
+        Self {
+            fast_forward,
+            warmup,
+            measure,
+            max_samples: None,
+        }
+    }
+
+    pub fn with_max_samples(mut self, max_samples: usize) -> Self {
+        self.max_samples = Some(max_samples);
+        self
+    }
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self::new(100_000, 1_000, 10_000)
+    }
+}
+
+/// Timing results for one measurement window
+#[derive(Clone, Debug, PartialEq)]
+pub struct SampleResult {
+    pub index: usize,
+    pub start_instruction: usize,
+    pub start_pc: u32,
+    pub instructions: usize,
+    pub cycles: usize,
+    pub cpi: f64,
+}
+
+/// Combined results of a sampled run
+#[derive(Clone, Debug, PartialEq)]
+pub struct SampledRun {
+    pub samples: Vec<SampleResult>,
+    pub total_instructions: usize,
+    pub estimated_cpi: f64,
+    pub estimated_cycles: f64,
+    pub termination_reason: Option<TerminationReason>,
+}
+
+impl SampledRun {
+    pub fn print_summary(&self) -> String {
+        let mut result = String::new();
+        result.push_str("Sampled Simulation Statistics:\n");
+        result.push_str(&format!(
+            "  Total Instructions: {}\n",
+            self.total_instructions
+        ));
+        result.push_str(&format!("  Samples: {}\n", self.samples.len()));
+        for sample in &self.samples {
+            result.push_str(&format!(
+                "    #{} at instruction {} (PC 0x{:08X}): {} instructions, {} cycles, CPI {:.2}\n",
+                sample.index,
+                sample.start_instruction,
+                sample.start_pc,
+                sample.instructions,
+                sample.cycles,
+                sample.cpi
+            ));
+        }
+        result.push_str(&format!("  Estimated CPI: {:.2}\n", self.estimated_cpi));
+        result.push_str(&format!(
+            "  Estimated Cycles: {:.0}\n",
+            self.estimated_cycles
+        ));
+        if let Some(reason) = self.termination_reason {
+            result.push_str(&format!("  Termination reason: {}\n", reason));
+        }
+        result
+    }
+}
+
+/// Fast-forwards functionally and simulates timing only for sampled windows
+pub struct SampledSimulator {
+    pub functional: FunctionalSimulator,
+    pub timing: TimingSimulator,
+    pub config: SamplingConfig,
+}
+
+impl SampledSimulator {
+    pub fn new(
+        functional: FunctionalSimulator,
+        pipeline_config: PipelineConfig,
+        instr_cache_config: CacheConfig,
+        data_cache_config: CacheConfig,
+        config: SamplingConfig,
+    ) -> Self {
+        let mut timing = TimingSimulator::new(
+            pipeline_config,
+            instr_cache_config,
+            data_cache_config,
+            functional.memory.size,
+        );
+        timing.visualization = None;
+
+        Self {
+            functional,
+            timing,
+            config,
+        }
+    }
+
+    /// Run the program to completion, sampling timing along the way
+    pub fn run(&mut self) -> SampledRun {
+        let mut samples = Vec::new();
+        let window = self.config.warmup + self.config.measure;
+
+        loop {
+            if !self.fast_forward(self.config.fast_forward) {
+                break;
+            }
+
+            let sampling_done =
+                matches!(self.config.max_samples, Some(max) if samples.len() >= max);
+            if sampling_done || self.config.measure == 0 {
+                // No more samples to take: finish the run functionally
+                while let Ok(true) = self.functional.step() {}
+                break;
+            }
+
+            if let Some(sample) = self.simulate_window(samples.len()) {
+                samples.push(sample);
+            }
+
+            // The functional simulator executes the sampled window itself
+            if !self.fast_forward(window) {
+                break;
+            }
+        }
+
+        let measured_instructions: usize = samples.iter().map(|s| s.instructions).sum();
+        let measured_cycles: usize = samples.iter().map(|s| s.cycles).sum();
+        let estimated_cpi = if measured_instructions > 0 {
+            measured_cycles as f64 / measured_instructions as f64
+        } else {
+            0.0
+        };
+        let total_instructions = self.functional.step_count;
+
+        SampledRun {
+            samples,
+            total_instructions,
+            estimated_cpi,
+            estimated_cycles: estimated_cpi * total_instructions as f64,
+            termination_reason: self.functional.termination_reason,
+        }
+    }
+
+    // Execute instructions functionally; false once the program has stopped
+    fn fast_forward(&mut self, instructions: usize) -> bool {
+        for _ in 0..instructions {
+            if !matches!(self.functional.step(), Ok(true)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Run the timing model over a warmup and measurement window starting
+    // from a copy of the functional state
+    fn simulate_window(&mut self, index: usize) -> Option<SampleResult> {
+        let start_instruction = self.functional.step_count;
+        let start_pc = self.functional.pc();
+
+        self.timing.registers = self.functional.registers.clone();
+        self.timing.memory = self.functional.memory.clone();
+        self.timing.pc = start_pc;
+        self.timing
+            .set_max_instructions(self.config.warmup + self.config.measure);
+        self.timing.set_stats_interval(if self.config.warmup > 0 {
+            Some(StatsInterval::Instructions(self.config.warmup))
+        } else {
+            None
+        });
+        self.timing.run();
+
+        // Everything after the warmup interval is measurement
+        let report = self.timing.stats_report();
+        let (warmup_instructions, warmup_cycles) = if self.config.warmup > 0 {
+            let warmup = report.intervals.first()?;
+            (warmup.instructions, warmup.cycles)
+        } else {
+            (0, 0)
+        };
+        let instructions = report.total.instructions - warmup_instructions;
+        let cycles = report.total.cycles - warmup_cycles;
+        if instructions == 0 {
+            return None;
+        }
+
+        Some(SampleResult {
+            index,
+            start_instruction,
+            start_pc,
+            instructions,
+            cycles,
+            cpi: cycles as f64 / instructions as f64,
+        })
+    }
+}
@@ -25,14 +25,44 @@
 // in-order or out-of-order execution mode. It also manages the simulation
 // loop, visualization, and performance statistics.
 
-use super::config::{CacheConfig, PipelineConfig};
-use super::pipeline::{Pipeline, PipelineStageStatus};
+use super::branch_predictor::PredictorState;
+use super::config::{CacheConfig, ControlHazardScheme, PipelineConfig};
+use super::events::{Event, EventKind, EventLog, EventSummary, StallCause};
+use super::history::{HistoryEntry, PipelineHistory, Stall, StallReason};
+use super::last_writer::{written_locations, LastWriters};
+use super::loop_memory::MemoryTrace;
+use super::pipeline::{Pipeline, PipelineStageStatus, PipelineStageType};
+use super::regions::{read_name, RegionRecorder};
+use super::stats::{
+    CpiStack, InstructionTiming, IntervalRecorder, IntervalSample, StatsCounters, StatsInterval,
+    StatsReport, STATS_FORMAT, STATS_VERSION,
+};
 use super::tomasulo::TomasuloProcessor;
+use super::trace_sampling::{TraceSampler, TraceSampling};
 use super::visualization::{OutputFormat, PipelineVisualization};
-use crate::functional_simulator::instructions::Instruction;
+use crate::functional_simulator::cp0::{CP0_ENTRYHI, CP0_STATUS, RESET_STATUS, RESET_VECTOR};
+use crate::functional_simulator::decode_report::{DecodeReport, UnknownInstructionPolicy};
+use crate::functional_simulator::instructions::{
+    branch_target, effective_address, jump_target, Instruction, NextPc,
+};
 use crate::functional_simulator::memory::Memory;
 use crate::functional_simulator::registers::Registers;
-use crate::functional_simulator::simulator::decode_instruction;
+use crate::functional_simulator::simulator::{decode_instruction, Exception};
+use crate::isa::IsaKind;
+use crate::loader::{preload_words, LoadError, ProgramImage};
+use crate::utils::clock;
+use crate::utils::crash_dump::{CrashDump, RecentInstructions, StageState};
+use crate::utils::limits::{MemoryUsage, RunLimits, TerminationReason, MEMORY_CHECK_INTERVAL};
+use crate::utils::progress::{ProgressCallback, ProgressReporter};
+use crate::utils::syscall::{
+    handle_syscall, handle_syscall_with_io, is_time_syscall, BlockTransfer, SYSCALL_CACHE_FLUSH,
+    SYSCALL_CACHE_FLUSH_LINE, SYSCALL_NAMED_REGION_BEGIN, SYSCALL_NAMED_REGION_END,
+    SYSCALL_REGION_BEGIN, SYSCALL_REGION_END, SYSCALL_SET_ASID,
+};
+use crate::utils::throttle::{cycle_counter, ThrottleClock};
+use std::io::{self, BufRead, Cursor, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 pub enum ExecutionMode {
     InOrder(Pipeline),             // Traditional in-order pipeline
@@ -45,7 +75,34 @@ pub struct Simulator {
     pub memory: Memory,
     pub pc: u32,
     pub visualization: Option<PipelineVisualization>,
-    pub max_steps: usize, // Maximum number of steps to execute
+    pub limits: RunLimits, // Instruction, cycle and time budgets for a run
+    pub termination_reason: Option<TerminationReason>,
+    pub progress: Option<ProgressReporter>,
+    pub interval_stats: IntervalRecorder,
+    pub run_totals: StatsCounters,
+    pub regions: RegionRecorder,
+    pub timeline: Option<Vec<InstructionTiming>>, // Per-instruction timing, when enabled
+    pub pipeline_history: Option<PipelineHistory>, // Per-cycle stage occupancy, when enabled
+    pub memory_trace: Option<MemoryTrace>,        // Data accesses and loops, when enabled
+    pub lenient_decode: Option<DecodeReport>,     // Unknown instructions run as NOPs, when enabled
+    pub throttle: Option<ThrottleClock>,          // Scenario the cycle counter is throttled by
+    pub last_writers: Option<LastWriters>, // Last write to each register and word, when enabled
+    pub trace_sampler: Option<TraceSampler>, // Thins out the timeline, when set
+    pub recent_instructions: Option<RecentInstructions>, // Last fetches, for crash dumps
+    pub events: Option<EventLog>,          // Cycle-stamped event log, when enabled
+    pub isa: IsaKind,                      // Instruction set the in-order pipeline runs
+    pub captured_output: Option<Vec<u8>>,  // Syscall output, when kept instead of printed
+    pub input: Option<Cursor<Vec<u8>>>,    // What read syscalls read instead of stdin, when set
+    pub event_totals: EventSummary,        // Tally of the run's events the statistics read
+}
+
+// Cycles as simulated time, with the clock frequency
+fn simulated_time(cycles: usize, clock_hz: u64) -> String {
+    format!(
+        "{} at {}",
+        clock::format_time(cycles as u64, clock_hz),
+        clock::format_frequency(clock_hz)
+    )
 }
 
 impl Simulator {
@@ -56,7 +113,9 @@ impl Simulator {
         memory_size: usize,
     ) -> Self {
         // Create the memory and registers that will be shared
-        let registers = Registers::new();
+        let mut registers = Registers::new();
+        registers.hardware.synci_step = data_cache_config.block_size as u32;
+        registers.hardware.clock_hz = pipeline_config.clock_hz;
         let memory = Memory::new(memory_size);
 
         // Make clones for the execution mode to use
@@ -88,7 +147,25 @@ impl Simulator {
             memory,
             pc: 0,
             visualization: Some(PipelineVisualization::new()), // Enable visualization by default
-            max_steps: 1000,                                   // Default to 1000 steps
+            limits: RunLimits::default(),
+            termination_reason: None,
+            progress: None,
+            interval_stats: IntervalRecorder::default(),
+            run_totals: StatsCounters::default(),
+            regions: RegionRecorder::default(),
+            timeline: None,
+            pipeline_history: None,
+            memory_trace: None,
+            lenient_decode: None,
+            throttle: None,
+            last_writers: None,
+            trace_sampler: None,
+            recent_instructions: None,
+            events: None,
+            isa: IsaKind::Mips,
+            captured_output: None,
+            input: None,
+            event_totals: EventSummary::default(),
         }
     }
 
@@ -124,7 +201,7 @@ impl Simulator {
         println!("Starting simulation at PC: 0x{:08X}", self.pc);
 
         // Debug output of initial instructions for tests
-        if let Some(first_instr) = self.memory.read_word(self.pc as usize) {
+        if let Ok(first_instr) = self.memory.read_word(self.pc as usize) {
             if first_instr == 0 {
                 println!("Program starts with NOP, may be empty. Run with caution.");
             } else {
@@ -133,7 +210,7 @@ impl Simulator {
                 println!("Decoded as: {:?}", decoded);
 
                 // Initialize first stage with the fetched instruction to make visualization work
-                if let Some(instr_word) = self.memory.read_word(self.pc as usize) {
+                if let Ok(instr_word) = self.memory.read_word(self.pc as usize) {
                     let instruction = decode_instruction(instr_word);
                     pipeline.stages[0].instruction = Some(instruction);
                     pipeline.stages[0].status = PipelineStageStatus::Busy;
@@ -145,7 +222,7 @@ impl Simulator {
         // For tests, preload some critical values - this helps ensure register values are correct
         for i in 0..3 {
             let addr = self.pc as usize + i * 4;
-            if let Some(instr_word) = self.memory.read_word(addr) {
+            if let Ok(instr_word) = self.memory.read_word(addr) {
                 if instr_word != 0 {
                     // Skip NOPs
                     let instruction = decode_instruction(instr_word);
@@ -161,7 +238,7 @@ impl Simulator {
             }
         }
 
-        while cycles < self.max_steps {
+        while cycles < self.limits.max_cycles {
             cycles += 1;
 
             // Visualize pipeline state if enabled
@@ -181,7 +258,7 @@ impl Simulator {
             let instruction = self.fetch_instruction();
 
             // Ensure the instruction is set in the pipeline stage for visualization
-            if let Some(instr_word) = self.memory.read_word(self.pc as usize) {
+            if let Ok(instr_word) = self.memory.read_word(self.pc as usize) {
                 let fetched_instr = decode_instruction(instr_word);
                 pipeline.stages[0].instruction = Some(fetched_instr.clone());
                 pipeline.stages[0].status = PipelineStageStatus::Busy;
@@ -204,7 +281,7 @@ impl Simulator {
             if let Instruction::Nop = instruction {
                 if cycles > 5 {
                     // Don't quit immediately if NOPs are at the beginning
-                    if let Some(instr_word) = self.memory.read_word(self.pc as usize) {
+                    if let Ok(instr_word) = self.memory.read_word(self.pc as usize) {
                         if instr_word == 0 {
                             println!(
                                 "Reached NOP instruction at PC: 0x{:08X}, terminating",
@@ -249,7 +326,7 @@ impl Simulator {
 
             // Execute the instruction and check for errors
             if cycles % 10 == 0 || cycles < 10 {
-                if let Some(instr_word) = self.memory.read_word(self.pc as usize) {
+                if let Ok(instr_word) = self.memory.read_word(self.pc as usize) {
                     println!(
                         "Cycle {}: Executing at PC 0x{:08X}: 0x{:08X} {:?}",
                         cycles, self.pc, instr_word, instruction
@@ -320,7 +397,7 @@ impl Simulator {
                     // Normal branch handling
                     if rs_val == rt_val {
                         // Calculate the branch target: PC+4+(offset<<2)
-                        self.pc = self.pc.wrapping_add(4).wrapping_add((offset as u32) << 2);
+                        self.pc = branch_target(self.pc, offset);
                         println!(
                             "  BEQ taken: New PC = 0x{:08X}, register values: ${}={}, ${}={}",
                             self.pc, rs, rs_val, rt, rt_val
@@ -343,7 +420,7 @@ impl Simulator {
                     let rs_val = self.registers.read(rs);
                     let rt_val = self.registers.read(rt);
                     if rs_val != rt_val {
-                        self.pc = self.pc.wrapping_add(4).wrapping_add((offset as u32) << 2);
+                        self.pc = branch_target(self.pc, offset);
                         if cycles % 10 == 0 || cycles < 10 {
                             println!("  BNE taken: New PC = 0x{:08X}", self.pc);
                         }
@@ -389,12 +466,12 @@ impl Simulator {
 
                     // Normal J instruction handling
                     // Calculate the jump target: combine upper 4 bits of PC with target<<2
-                    self.pc = (self.pc & 0xF0000000) | (target << 2);
+                    self.pc = jump_target(self.pc, target);
                     println!("  J: New PC = 0x{:08X}", self.pc);
                 },
                 Instruction::Jal { target } => {
                     self.registers.write(31, self.pc + 4);
-                    self.pc = (self.pc & 0xF0000000) | (target << 2);
+                    self.pc = jump_target(self.pc, target);
                     if cycles % 10 == 0 || cycles < 10 {
                         println!(
                             "  JAL: RA = 0x{:08X}, New PC = 0x{:08X}",
@@ -415,15 +492,15 @@ impl Simulator {
             }
 
             // Prevent runaway execution - if PC gets too large, exit
-            if self.pc >= self.memory.size as u32 {
+            if self.memory.translate_address(self.pc as usize) >= self.memory.size {
                 println!("PC exceeded memory size bounds. Ending simulation.");
                 break;
             }
 
-            if cycles >= self.max_steps {
+            if cycles >= self.limits.max_cycles {
                 println!(
                     "Reached maximum cycle count ({}) - stopping simulation",
-                    self.max_steps
+                    self.limits.max_cycles
                 );
                 break;
             }
@@ -450,7 +527,7 @@ impl Simulator {
         println!("Starting out-of-order simulation at PC: 0x{:08X}", self.pc);
 
         // Debug output of initial instructions
-        if let Some(first_instr) = self.memory.read_word(self.pc as usize) {
+        if let Ok(first_instr) = self.memory.read_word(self.pc as usize) {
             if first_instr == 0 {
                 println!("Program starts with NOP, may be empty. Run with caution.");
             } else {
@@ -460,7 +537,7 @@ impl Simulator {
             }
         }
 
-        while cycles < self.max_steps {
+        while cycles < self.limits.max_cycles {
             cycles += 1;
 
             // Fetch and issue next instruction
@@ -470,7 +547,7 @@ impl Simulator {
             if let Instruction::Nop = instruction {
                 if cycles > 5 {
                     // Don't quit immediately if NOPs are at the beginning
-                    if let Some(instr_word) = self.memory.read_word(self.pc as usize) {
+                    if let Ok(instr_word) = self.memory.read_word(self.pc as usize) {
                         if instr_word == 0 {
                             println!(
                                 "Reached NOP instruction at PC: 0x{:08X}, terminating",
@@ -489,7 +566,7 @@ impl Simulator {
 
             // Output debug information
             if cycles % 10 == 0 || cycles < 10 {
-                if let Some(instr_word) = self.memory.read_word(self.pc as usize) {
+                if let Ok(instr_word) = self.memory.read_word(self.pc as usize) {
                     println!(
                         "Cycle {}: Issuing at PC 0x{:08X}: 0x{:08X} {:?}",
                         cycles, self.pc, instr_word, instruction
@@ -513,7 +590,7 @@ impl Simulator {
                     let rs_val = self.registers.read(rs);
                     let rt_val = self.registers.read(rt);
                     if rs_val == rt_val {
-                        self.pc = self.pc.wrapping_add(4).wrapping_add((offset as u32) << 2);
+                        self.pc = branch_target(self.pc, offset);
                     } else {
                         self.pc += 4;
                     }
@@ -522,16 +599,16 @@ impl Simulator {
                     let rs_val = self.registers.read(rs);
                     let rt_val = self.registers.read(rt);
                     if rs_val != rt_val {
-                        self.pc = self.pc.wrapping_add(4).wrapping_add((offset as u32) << 2);
+                        self.pc = branch_target(self.pc, offset);
                     } else {
                         self.pc += 4;
                     }
                 },
                 Instruction::J { target } => {
-                    self.pc = (self.pc & 0xF0000000) | (target << 2);
+                    self.pc = jump_target(self.pc, target);
                 },
                 Instruction::Jal { target } => {
-                    self.pc = (self.pc & 0xF0000000) | (target << 2);
+                    self.pc = jump_target(self.pc, target);
                 },
                 Instruction::Jr { rs } => {
                     self.pc = self.registers.read(rs);
@@ -549,7 +626,7 @@ impl Simulator {
             self.memory = processor.memory.clone();
 
             // Prevent runaway execution
-            if self.pc >= self.memory.size as u32 {
+            if self.memory.translate_address(self.pc as usize) >= self.memory.size {
                 println!("PC exceeded memory size bounds. Ending simulation.");
                 break;
             }
@@ -559,10 +636,10 @@ impl Simulator {
                 println!("\n{}", processor.dump_state());
             }
 
-            if cycles >= self.max_steps {
+            if cycles >= self.limits.max_cycles {
                 println!(
                     "Reached maximum cycle count ({}) - stopping simulation",
-                    self.max_steps
+                    self.limits.max_cycles
                 );
                 break;
             }
@@ -582,6 +659,25 @@ impl Simulator {
         println!("\n{}", stats);
     }
 
+    /// Place a program image in memory and start at its entry point
+    pub fn load_image(&mut self, image: &ProgramImage) -> Result<(), LoadError> {
+        image.load_into(&mut self.memory)?;
+        self.pc = image.entry_point;
+        Ok(())
+    }
+
+    /// Start at the reset vector with Status in its reset state
+    pub fn reset_to_boot_vector(&mut self) {
+        self.registers.cp0[CP0_STATUS as usize] = RESET_STATUS;
+        self.pc = RESET_VECTOR;
+    }
+
+    /// Write initial word values into memory, for programs that expect data
+    /// the loader does not place
+    pub fn preload_memory(&mut self, values: &[(u32, u32)]) -> Result<(), LoadError> {
+        preload_words(&mut self.memory, values)
+    }
+
     /// Main run method that dispatches to the appropriate execution mode
     pub fn run(&mut self) {
         // Use a match to determine which execution mode we're in, but don't borrow yet
@@ -601,13 +697,41 @@ impl Simulator {
     fn run_in_order_simulation(&mut self) {
         // Extract the information we need first
         let pc_initial = self.pc;
-        let max_steps = self.max_steps;
+        let isa = self.isa;
+        let frontend = isa.frontend();
+        let limits = self.limits;
+        let started = Instant::now();
+        self.termination_reason = None;
+        if let Some(progress) = self.progress.as_mut() {
+            progress.start();
+        }
+        self.interval_stats.reset();
+        self.regions.reset();
+        if let Some(timeline) = self.timeline.as_mut() {
+            timeline.clear();
+        }
+        if let Some(history) = self.pipeline_history.as_mut() {
+            history.entries.clear();
+        }
+        if let Some(trace) = self.memory_trace.as_mut() {
+            *trace = MemoryTrace::new();
+        }
+        if let Some(events) = self.events.as_mut() {
+            events.clear();
+        }
+        // The components always emit events in a run, since the statistics
+        // are counted from them. Lookups made outside a run, such as while
+        // loading, are dropped.
+        self.event_totals = EventSummary::default();
+        if let ExecutionMode::InOrder(pipeline) = &mut self.execution_mode {
+            pipeline.enable_events();
+        }
         let _visualization_enabled = self.visualization.is_some();
 
         println!("Starting simulation at PC: 0x{:08X}", pc_initial);
 
         // Debug output of initial instructions for tests
-        if let Some(first_instr) = self.memory.read_word(pc_initial as usize) {
+        if let Ok(first_instr) = self.memory.read_word(pc_initial as usize) {
             println!("First instruction: 0x{:08X}", first_instr);
             let decoded = decode_instruction(first_instr);
             println!("Decoded as: {:?}", decoded);
@@ -616,7 +740,7 @@ impl Simulator {
         // Check which mode we're in
         if let ExecutionMode::InOrder(ref mut pipeline) = &mut self.execution_mode {
             // Initialize first stage with the fetched instruction if possible
-            if let Some(instr_word) = self.memory.read_word(self.pc as usize) {
+            if let Ok(instr_word) = self.memory.read_word(self.pc as usize) {
                 let fetched_instr = decode_instruction(instr_word);
                 pipeline.stages[0].instruction = Some(fetched_instr);
                 pipeline.stages[0].status = PipelineStageStatus::Busy;
@@ -625,10 +749,42 @@ impl Simulator {
 
             // Main simulation loop
             let mut cycles = 0;
+            let mut instructions = 0;
             let mut stall_cycles = 0;
-
-            while cycles < max_steps {
+            let delay_slots = pipeline.control_hazard_scheme == ControlHazardScheme::DelaySlot
+                && isa == IsaKind::Mips;
+            // Where to continue after the instruction in a delay slot
+            let mut delay_target: Option<u32> = None;
+
+            loop {
+                // Stop once the instruction, cycle or time budget is used up
+                if let Some(reason) = limits.exceeded(instructions, cycles, started) {
+                    println!("Stopping simulation: {}", reason);
+                    self.termination_reason = Some(reason);
+                    break;
+                }
+                if limits.max_memory.is_some() && cycles % MEMORY_CHECK_INTERVAL == 0 {
+                    let usage = pipeline.memory_usage()
+                        + Self::own_memory_usage(
+                            &self.memory,
+                            &self.timeline,
+                            &self.pipeline_history,
+                            &self.memory_trace,
+                            &self.interval_stats,
+                        );
+                    if limits.check_memory(&usage).is_err() {
+                        self.termination_reason = Some(TerminationReason::MemoryLimit);
+                        break;
+                    }
+                }
+                if self.interval_stats.is_due(instructions, cycles) {
+                    let counters = self.event_totals.counters(instructions, cycles);
+                    self.interval_stats.record(counters);
+                }
                 cycles += 1;
+                if let Some(progress) = self.progress.as_mut() {
+                    progress.update(instructions, cycles);
+                }
 
                 // Visualization will be called after pipeline update
 
@@ -639,13 +795,20 @@ impl Simulator {
 
                 // Fetch instruction directly rather than using self.fetch_instruction()
                 let instr_word = self.memory.read_word(self.pc as usize);
-                if instr_word.is_none() {
-                    println!("Memory access error during fetch at PC: 0x{:08X}", self.pc);
+                if let Err(error) = instr_word {
+                    println!(
+                        "Memory access error during fetch at PC: 0x{:08X}: {}",
+                        self.pc, error
+                    );
+                    self.termination_reason = Some(TerminationReason::MemoryFault);
                     break;
                 }
 
                 let instr_word = instr_word.unwrap();
-                let instruction = decode_instruction(instr_word);
+                let mut instruction = frontend.timing_instruction(instr_word);
+                if let Some(recent) = self.recent_instructions.as_mut() {
+                    recent.record(self.pc, instr_word);
+                }
 
                 // Diagnostic output for testing
                 if self.pc == 0 || self.pc == 4 || self.pc == 8 || self.pc == 12 {
@@ -663,7 +826,7 @@ impl Simulator {
                 }
 
                 // Update pipeline visualization
-                let fetched_instr = decode_instruction(instr_word);
+                let fetched_instr = frontend.timing_instruction(instr_word);
                 pipeline.stages[0].instruction = Some(fetched_instr.clone());
                 pipeline.stages[0].status = PipelineStageStatus::Busy;
                 pipeline.stages[0].pc = self.pc;
@@ -696,87 +859,353 @@ impl Simulator {
 
                 // Check for terminal conditions
                 if let Instruction::Nop = instruction {
-                    if cycles > 5 && instr_word == 0 {
+                    if cycles > 5 && instr_word == 0 && delay_target.is_none() {
                         println!(
                             "Reached NOP instruction at PC: 0x{:08X}, terminating",
                             self.pc
                         );
+                        self.termination_reason = Some(TerminationReason::Halted);
                         break;
                     }
                 }
 
                 if let Instruction::InvalidInstruction = instruction {
-                    println!("Invalid instruction at PC: 0x{:08X}", self.pc);
-                    break;
+                    match self.lenient_decode.as_mut() {
+                        Some(report) => {
+                            report.record(self.pc, instr_word);
+                            instruction = Instruction::Nop;
+                        },
+                        None => {
+                            println!("Invalid instruction at PC: 0x{:08X}", self.pc);
+                            self.termination_reason = Some(TerminationReason::InvalidInstruction);
+                            break;
+                        },
+                    }
                 }
 
-                // Execute instruction
-                let _result = pipeline.execute(&instruction, &self.registers, self.pc);
-
-                // CRITICAL: Always update state for ALL instructions to ensure tests pass
-                // This ensures registers are properly updated even if pipeline has issues
-                Self::update_state_helper(
-                    &mut self.registers,
-                    &mut self.memory,
-                    &self.visualization,
-                    &instruction,
-                    self.pc,
-                );
+                // Cache misses and data hazards stall the pipeline, and a
+                // multi-cycle stage holds back the next fetch
+                let (fetch_stall, mut data_stall) =
+                    Self::cache_stall_cycles(pipeline, &instruction, &self.registers, self.pc);
+                let hazard_stall = pipeline.data_hazard_stall_cycles(&instruction);
+                let structural_stall = pipeline.issue_interval() - 1
+                    + pipeline.memory_port_stall(&instruction, fetch_stall > 0, data_stall > 0);
+                pipeline.cache_miss_stalls += fetch_stall + data_stall;
+                pipeline.structural_hazard_stalls += structural_stall;
+                if let Instruction::Syscall = instruction {
+                    // memcpy and memset move whole lines in the memory stage
+                    if let Some(transfer) = BlockTransfer::of(&self.registers) {
+                        data_stall += pipeline.block_transfer_stall(&transfer);
+                        if let Some(trace) = self.memory_trace.as_mut() {
+                            let line_size =
+                                pipeline.cache_hierarchy.l1_data_cache.config.block_size;
+                            for (line, store) in transfer.lines(line_size as u32) {
+                                trace.record_access(self.pc, line, store);
+                            }
+                        }
+                    }
+                    match self.registers.read(2) {
+                        SYSCALL_CACHE_FLUSH => pipeline.cache_hierarchy.flush(),
+                        SYSCALL_CACHE_FLUSH_LINE => pipeline
+                            .cache_hierarchy
+                            .flush_data_line(self.registers.read(4) as usize),
+                        SYSCALL_REGION_BEGIN => {
+                            let counters = self.event_totals.counters(instructions, cycles);
+                            self.regions.begin(counters);
+                        },
+                        SYSCALL_REGION_END => {
+                            let counters = self.event_totals.counters(instructions, cycles);
+                            self.regions.end(counters);
+                        },
+                        SYSCALL_NAMED_REGION_BEGIN => {
+                            let counters = self.event_totals.counters(instructions, cycles);
+                            let name = read_name(&self.memory, self.registers.read(4));
+                            self.regions.begin_named(name, counters);
+                        },
+                        SYSCALL_NAMED_REGION_END => {
+                            let counters = self.event_totals.counters(instructions, cycles);
+                            let name = read_name(&self.memory, self.registers.read(4));
+                            self.regions.end_named(&name, counters);
+                        },
+                        SYSCALL_SET_ASID => {
+                            data_stall += pipeline.switch_asid(self.registers.read(4) as u8)
+                        },
+                        _ => {},
+                    }
+                }
+                // Writing EntryHi changes the address space like syscall 78
+                if let Instruction::Mtc0 {
+                    rt,
+                    rd: CP0_ENTRYHI,
+                } = instruction
+                {
+                    data_stall += pipeline.switch_asid(self.registers.read(rt) as u8);
+                }
+                stall_cycles = fetch_stall + data_stall + hazard_stall + structural_stall;
+                if let Some(trace) = self.memory_trace.as_mut() {
+                    if instruction.is_memory_access() {
+                        let address = instruction.get_address(&self.registers, self.pc);
+                        trace.record_access(self.pc, address, !instruction.is_load());
+                    }
+                }
 
-                // Update PC based on instruction
-                match instruction {
-                    Instruction::Beq { rs, rt, offset } => {
-                        let rs_val = self.registers.read(rs);
-                        let rt_val = self.registers.read(rt);
-                        println!(
-                            "Debug BEQ (simulation): rs({})={}, rt({})={}, offset={}, PC=0x{:08X}",
-                            rs, rs_val, rt, rt_val, offset, self.pc
-                        );
+                let data_address = instruction
+                    .is_memory_access()
+                    .then(|| instruction.get_address(&self.registers, self.pc));
 
-                        // Normal branch handling
-                        if rs_val == rt_val {
-                            self.pc = self.pc.wrapping_add(4).wrapping_add((offset as u32) << 2);
-                        } else {
-                            self.pc += 4;
+                let writes = if self.last_writers.is_some() {
+                    written_locations(&instruction, &self.registers, self.pc)
+                } else {
+                    Vec::new()
+                };
+
+                // Execute instruction; rdhwr reads the counters once it has
+                // been fetched and its operands are ready, so the difference of
+                // two reads covers the cache stalls of the code between them
+                self.registers.hardware.cycles = cycle_counter(
+                    &mut self.throttle,
+                    (cycles + fetch_stall + hazard_stall) as u64,
+                );
+                self.registers.hardware.instructions = instructions as u64;
+                let _result = pipeline.execute(&instruction, &self.registers, self.pc);
+                instructions += 1;
+                let issue_pc = self.pc;
+                let mispredictions = pipeline.branch_mispredictions;
+
+                let next_pc = if isa != IsaKind::Mips {
+                    // Other instruction sets execute their own instruction;
+                    // the pipeline has timed the MIPS one standing in for it
+                    frontend.execute(instr_word, &mut self.registers, &mut self.memory, self.pc)
+                } else if let Instruction::Syscall = instruction {
+                    let mut stdout = io::stdout();
+                    let output: &mut dyn Write = match self.captured_output.as_mut() {
+                        Some(buffer) => buffer,
+                        None => &mut stdout,
+                    };
+                    let input = self.input.as_mut().map(|input| input as &mut dyn BufRead);
+                    handle_syscall_with_io(&mut self.registers, &mut self.memory, input, output)
+                } else {
+                    self.registers.pc = self.pc;
+                    instruction.execute(&mut self.registers, &mut self.memory)
+                };
+                let Some(target) = next_pc.target(self.pc) else {
+                    self.termination_reason = Some(match next_pc {
+                        NextPc::Trap(Exception::InvalidInstruction) => {
+                            TerminationReason::InvalidInstruction
+                        },
+                        NextPc::Trap(Exception::BreakPoint) => TerminationReason::Breakpoint,
+                        NextPc::Trap(_) => TerminationReason::MemoryFault,
+                        _ => TerminationReason::Exited,
+                    });
+                    break;
+                };
+                // Returns skip the delay slot
+                if delay_slots && isa == IsaKind::Mips {
+                    match instruction {
+                        Instruction::Jal { .. } => self.registers.write(31, self.pc + 8),
+                        Instruction::Jalr { rd, .. } => self.registers.write(rd, self.pc + 8),
+                        _ => {},
+                    }
+                }
+                if !matches!(instruction, Instruction::J { .. } | Instruction::Jal { .. }) {
+                    if let Some(branch) = instruction.calculate_branch_target(self.pc) {
+                        let taken = target != self.pc.wrapping_add(4);
+                        stall_cycles += pipeline.resolve_branch(self.pc, taken, branch);
+                    }
+                }
+                self.pc = target;
+                // With delay slots the next instruction runs before a branch
+                // or jump takes effect
+                if let Some(target) = delay_target.take() {
+                    self.pc = target;
+                } else if delay_slots && instruction.is_branch_or_jump() {
+                    pipeline.delay_slots += 1;
+                    // A branch that is not taken continues after its slot
+                    let target = if self.pc == issue_pc + 4 {
+                        issue_pc + 8
+                    } else {
+                        self.pc
+                    };
+                    delay_target = Some(target);
+                    self.pc = issue_pc + 4;
+                }
+                pipeline.stall_count += stall_cycles;
+                let breakdown = &mut pipeline.stall_breakdown;
+                breakdown.icache += fetch_stall;
+                breakdown.dcache += data_stall;
+                breakdown.raw += hazard_stall;
+                breakdown.structural += structural_stall;
+                breakdown.branch +=
+                    stall_cycles - fetch_stall - data_stall - hazard_stall - structural_stall;
+                if let Some(trace) = self.memory_trace.as_mut() {
+                    trace.record_instruction(issue_pc, instr_word, self.pc);
+                }
+                if let Some(writers) = self.last_writers.as_mut() {
+                    for location in writes {
+                        if let Some(value) = location.value(&self.registers, &self.memory) {
+                            let index = instructions - 1;
+                            writers.record(
+                                location,
+                                issue_pc,
+                                instr_word,
+                                index,
+                                cycles - 1,
+                                value,
+                            );
                         }
-                    },
-                    Instruction::Bne { rs, rt, offset } => {
-                        let rs_val = self.registers.read(rs);
-                        let rt_val = self.registers.read(rt);
-                        if rs_val != rt_val {
-                            self.pc = self.pc.wrapping_add(4).wrapping_add((offset as u32) << 2);
-                        } else {
-                            self.pc += 4;
+                    }
+                }
+                // Every event is counted for the statistics, and kept when
+                // the log is enabled
+                let branch_stall =
+                    stall_cycles - fetch_stall - data_stall - hazard_stall - structural_stall;
+                let mut kinds = vec![EventKind::Issue {
+                    word: instr_word,
+                    cycles: 1 + stall_cycles,
+                }];
+                for (cause, cycles) in [
+                    (StallCause::InstructionCache, fetch_stall),
+                    (StallCause::DataCache, data_stall),
+                    (StallCause::DataHazard, hazard_stall),
+                    (StallCause::Structural, structural_stall),
+                    (StallCause::Branch, branch_stall),
+                ] {
+                    if cycles > 0 {
+                        kinds.push(EventKind::Stall { cause, cycles });
+                    }
+                }
+                // The caches and the pipeline emitted their own lookups and
+                // resolved branches while the instruction issued
+                kinds.extend(pipeline.take_events());
+                if let Some(address) = data_address {
+                    kinds.push(EventKind::MemoryAccess {
+                        address,
+                        store: !instruction.is_load(),
+                    });
+                }
+                for kind in kinds {
+                    let event = Event::new(cycles, issue_pc, kind);
+                    self.event_totals.record(&event);
+                    if let Some(events) = self.events.as_mut() {
+                        events.emit(event);
+                    }
+                }
+                if let Some(timeline) = self.timeline.as_mut() {
+                    let timing = InstructionTiming {
+                        index: instructions - 1,
+                        pc: issue_pc,
+                        word: instr_word,
+                        issue_cycle: cycles,
+                        cycles: 1 + stall_cycles,
+                        stall_cycles,
+                        mispredicted: pipeline.branch_mispredictions > mispredictions,
+                    };
+                    match self.trace_sampler.as_mut() {
+                        Some(sampler) => sampler.push(timeline, timing),
+                        None => timeline.push(timing),
+                    }
+                }
+                if let Some(history) = self.pipeline_history.as_mut() {
+                    // Other branches wait in execute while the right path is fetched
+                    let mut branch_stall =
+                        stall_cycles - fetch_stall - data_stall - hazard_stall - structural_stall;
+                    let execute = pipeline
+                        .first_stage(PipelineStageType::Execute)
+                        .unwrap_or(0);
+                    let mut wrong_path = Vec::new();
+                    if pipeline.branch_mispredictions > mispredictions {
+                        // A mispredicted branch goes through unhindered while the
+                        // predicted path is fetched behind it, until it resolves
+                        // at the end of execute and squashes that path
+                        let squashed = cycles
+                            + fetch_stall
+                            + hazard_stall
+                            + pipeline.stage_start(execute)
+                            + pipeline.stages[execute].latency;
+                        let interval = pipeline.issue_interval();
+                        let mut pc = match instruction {
+                            Instruction::Beq { offset, .. } | Instruction::Bne { offset, .. }
+                                if self.pc == issue_pc + 4 =>
+                            {
+                                branch_target(issue_pc, offset)
+                            },
+                            _ => issue_pc + 4,
+                        };
+                        let mut fetch_cycle = squashed - branch_stall;
+                        while fetch_cycle < squashed {
+                            wrong_path.push(HistoryEntry {
+                                id: instructions - 1,
+                                pc,
+                                word: self.memory.read_word(pc as usize).unwrap_or(0),
+                                fetch_cycle,
+                                stalls: Vec::new(),
+                                squashed: Some(squashed),
+                            });
+                            pc = pc.wrapping_add(4);
+                            fetch_cycle += interval;
                         }
-                    },
-                    Instruction::J { target } => {
-                        println!(
-                            "Debug J (simulation): PC=0x{:08X}, target=0x{:X}",
-                            self.pc, target
-                        );
-                        // Normal jump handling
-                        self.pc = (self.pc & 0xF0000000) | (target << 2);
-                    },
-                    Instruction::Jal { target } => {
-                        self.registers.write(31, self.pc + 4);
-                        self.pc = (self.pc & 0xF0000000) | (target << 2);
-                    },
-                    Instruction::Jr { rs } => {
-                        self.pc = self.registers.read(rs);
-                    },
-                    _ => {
-                        self.pc += 4;
-                    },
+                        branch_stall = 0;
+                    }
+                    let held = [
+                        (0, fetch_stall, StallReason::CacheMiss),
+                        (
+                            pipeline.operand_stage(),
+                            hazard_stall,
+                            StallReason::DataHazard,
+                        ),
+                        (execute, branch_stall, StallReason::ControlHazard),
+                        (pipeline.memory_stage(), data_stall, StallReason::CacheMiss),
+                    ];
+                    let stalls = held
+                        .iter()
+                        .filter(|&&(_, cycles, _)| cycles > 0)
+                        .map(|&(stage, cycles, reason)| Stall {
+                            stage,
+                            cycles,
+                            reason,
+                        })
+                        .collect();
+                    history.record(HistoryEntry {
+                        id: instructions - 1,
+                        pc: issue_pc,
+                        word: instr_word,
+                        fetch_cycle: cycles,
+                        stalls,
+                        squashed: None,
+                    });
+                    for entry in wrong_path {
+                        history.record(entry);
+                    }
                 }
 
                 // Safety check for PC
-                if self.pc >= self.memory.size as u32 {
+                if self.memory.translate_address(self.pc as usize) >= self.memory.size {
                     println!("PC exceeded memory bounds. Ending simulation.");
+                    self.termination_reason = Some(TerminationReason::Halted);
                     break;
                 }
             }
 
-            println!("\nSimulation completed after {} cycles", cycles);
+            if let Some(progress) = self.progress.as_mut() {
+                progress.finish(instructions, cycles);
+            }
+            self.run_totals = self.event_totals.counters(instructions, cycles);
+            self.interval_stats.finish(self.run_totals);
+            self.regions.finish(self.run_totals);
+            if let (Some(sampler), Some(timeline)) =
+                (self.trace_sampler.as_mut(), self.timeline.as_mut())
+            {
+                sampler.finish(timeline);
+            }
+            println!(
+                "\nSimulation completed after {} cycles ({})",
+                cycles,
+                simulated_time(cycles, self.registers.hardware.clock_hz)
+            );
+            println!("Instructions executed: {}", instructions);
+            if let Some(reason) = self.termination_reason {
+                println!("Termination reason: {}", reason);
+            }
             println!("Final PC: 0x{:08X}", self.pc);
             println!(
                 "Final register values: $2={}, $3={}",
@@ -786,11 +1215,30 @@ impl Simulator {
         }
     }
 
+    /// Seconds the last run would take on a core at the configured clock
+    /// frequency
+    pub fn simulated_seconds(&self) -> f64 {
+        clock::seconds(
+            self.run_totals.cycles as u64,
+            self.registers.hardware.clock_hz,
+        )
+    }
+
     // Implementation that handles out-of-order execution while avoiding borrow issues
     fn run_out_of_order_simulation(&mut self) {
         // Extract the information we need first
         let pc_initial = self.pc;
-        let max_steps = self.max_steps;
+        let limits = self.limits;
+        let started = Instant::now();
+        self.termination_reason = None;
+        if let Some(progress) = self.progress.as_mut() {
+            progress.start();
+        }
+        self.interval_stats.reset();
+        self.regions.reset();
+        if let Some(timeline) = self.timeline.as_mut() {
+            timeline.clear();
+        }
         let _visualization_enabled = self.visualization.is_some();
 
         println!(
@@ -799,7 +1247,7 @@ impl Simulator {
         );
 
         // Debug output of initial instructions
-        if let Some(first_instr) = self.memory.read_word(pc_initial as usize) {
+        if let Ok(first_instr) = self.memory.read_word(pc_initial as usize) {
             println!("First instruction: 0x{:08X}", first_instr);
             let decoded = decode_instruction(first_instr);
             println!("Decoded as: {:?}", decoded);
@@ -807,20 +1255,55 @@ impl Simulator {
 
         if let ExecutionMode::OutOfOrder(ref mut processor) = &mut self.execution_mode {
             let mut cycles = 0;
+            let mut instructions = 0;
 
             // Main simulation loop
-            while cycles < max_steps {
+            loop {
+                // Stop once the instruction, cycle or time budget is used up
+                if let Some(reason) = limits.exceeded(instructions, cycles, started) {
+                    println!("Stopping simulation: {}", reason);
+                    self.termination_reason = Some(reason);
+                    break;
+                }
+                if limits.max_memory.is_some() && cycles % MEMORY_CHECK_INTERVAL == 0 {
+                    let usage = processor.memory_usage()
+                        + Self::own_memory_usage(
+                            &self.memory,
+                            &self.timeline,
+                            &self.pipeline_history,
+                            &self.memory_trace,
+                            &self.interval_stats,
+                        );
+                    if limits.check_memory(&usage).is_err() {
+                        self.termination_reason = Some(TerminationReason::MemoryLimit);
+                        break;
+                    }
+                }
+                if self.interval_stats.is_due(instructions, cycles) {
+                    let counters = Self::tomasulo_counters(processor, instructions, cycles);
+                    self.interval_stats.record(counters);
+                }
                 cycles += 1;
+                if let Some(progress) = self.progress.as_mut() {
+                    progress.update(instructions, cycles);
+                }
 
                 // Fetch instruction directly rather than using self.fetch_instruction()
                 let instr_word = self.memory.read_word(self.pc as usize);
-                if instr_word.is_none() {
-                    println!("Memory access error during fetch at PC: 0x{:08X}", self.pc);
+                if let Err(error) = instr_word {
+                    println!(
+                        "Memory access error during fetch at PC: 0x{:08X}: {}",
+                        self.pc, error
+                    );
+                    self.termination_reason = Some(TerminationReason::MemoryFault);
                     break;
                 }
 
                 let instr_word = instr_word.unwrap();
-                let instruction = decode_instruction(instr_word);
+                let mut instruction = decode_instruction(instr_word);
+                if let Some(recent) = self.recent_instructions.as_mut() {
+                    recent.record(self.pc, instr_word);
+                }
 
                 // Diagnostic output for testing
                 if self.pc == 0 || self.pc == 4 || self.pc == 8 || self.pc == 12 {
@@ -844,13 +1327,23 @@ impl Simulator {
                             "Reached NOP instruction at PC: 0x{:08X}, terminating",
                             self.pc
                         );
+                        self.termination_reason = Some(TerminationReason::Halted);
                         break;
                     }
                 }
 
                 if let Instruction::InvalidInstruction = instruction {
-                    println!("Invalid instruction at PC: 0x{:08X}", self.pc);
-                    break;
+                    match self.lenient_decode.as_mut() {
+                        Some(report) => {
+                            report.record(self.pc, instr_word);
+                            instruction = Instruction::Nop;
+                        },
+                        None => {
+                            println!("Invalid instruction at PC: 0x{:08X}", self.pc);
+                            self.termination_reason = Some(TerminationReason::InvalidInstruction);
+                            break;
+                        },
+                    }
                 }
 
                 // Debug output
@@ -869,6 +1362,7 @@ impl Simulator {
                     }
                     continue;
                 }
+                instructions += 1;
 
                 // Update PC based on instruction type
                 match instruction {
@@ -876,7 +1370,7 @@ impl Simulator {
                         let rs_val = self.registers.read(rs);
                         let rt_val = self.registers.read(rt);
                         if rs_val == rt_val {
-                            self.pc = self.pc.wrapping_add(4).wrapping_add((offset as u32) << 2);
+                            self.pc = branch_target(self.pc, offset);
                         } else {
                             self.pc += 4;
                         }
@@ -885,16 +1379,16 @@ impl Simulator {
                         let rs_val = self.registers.read(rs);
                         let rt_val = self.registers.read(rt);
                         if rs_val != rt_val {
-                            self.pc = self.pc.wrapping_add(4).wrapping_add((offset as u32) << 2);
+                            self.pc = branch_target(self.pc, offset);
                         } else {
                             self.pc += 4;
                         }
                     },
                     Instruction::J { target } => {
-                        self.pc = (self.pc & 0xF0000000) | (target << 2);
+                        self.pc = jump_target(self.pc, target);
                     },
                     Instruction::Jal { target } => {
-                        self.pc = (self.pc & 0xF0000000) | (target << 2);
+                        self.pc = jump_target(self.pc, target);
                     },
                     Instruction::Jr { rs } => {
                         self.pc = self.registers.read(rs);
@@ -912,8 +1406,9 @@ impl Simulator {
                 self.memory = processor.memory.clone();
 
                 // Safety check for PC
-                if self.pc >= self.memory.size as u32 {
+                if self.memory.translate_address(self.pc as usize) >= self.memory.size {
                     println!("PC exceeded memory bounds. Ending simulation.");
+                    self.termination_reason = Some(TerminationReason::Halted);
                     break;
                 }
 
@@ -923,7 +1418,20 @@ impl Simulator {
                 }
             }
 
-            println!("\nSimulation completed after {} cycles", cycles);
+            if let Some(progress) = self.progress.as_mut() {
+                progress.finish(instructions, cycles);
+            }
+            self.run_totals = Self::tomasulo_counters(processor, instructions, cycles);
+            self.interval_stats.finish(self.run_totals);
+            println!(
+                "\nSimulation completed after {} cycles ({})",
+                cycles,
+                simulated_time(cycles, self.registers.hardware.clock_hz)
+            );
+            println!("Instructions executed: {}", instructions);
+            if let Some(reason) = self.termination_reason {
+                println!("Termination reason: {}", reason);
+            }
             println!("Final PC: 0x{:08X}", self.pc);
             println!(
                 "Final register values: $2={}, $3={}",
@@ -961,7 +1469,7 @@ impl Simulator {
         }
 
         // FIRST try direct memory access for most reliable testing
-        if let Some(instruction_word) = self.memory.read_word(self.pc as usize) {
+        if let Ok(instruction_word) = self.memory.read_word(self.pc as usize) {
             let instruction = decode_instruction(instruction_word);
             // If this is a real instruction (not a NOP or invalid), return it directly
             if !matches!(
@@ -982,7 +1490,7 @@ impl Simulator {
 
         // Fallback using direct memory access with warnings
         match self.memory.read_word(self.pc as usize) {
-            Some(instruction_word) => {
+            Ok(instruction_word) => {
                 let instruction = decode_instruction(instruction_word);
                 if self.pc == 0 || self.pc == 4 || self.pc == 8 || self.pc == 12 {
                     println!(
@@ -992,8 +1500,11 @@ impl Simulator {
                 }
                 instruction
             },
-            None => {
-                println!("Memory access error during fetch at PC: 0x{:08X}", self.pc);
+            Err(error) => {
+                println!(
+                    "Memory access error during fetch at PC: 0x{:08X}: {}",
+                    self.pc, error
+                );
                 Instruction::InvalidInstruction
             },
         }
@@ -1003,10 +1514,12 @@ impl Simulator {
         match &self.execution_mode {
             ExecutionMode::InOrder(pipeline) => match instruction {
                 Instruction::Add { rs, rt, .. }
+                | Instruction::Addu { rs, rt, .. }
                 | Instruction::Sub { rs, rt, .. }
                 | Instruction::And { rs, rt, .. }
                 | Instruction::Or { rs, rt, .. }
-                | Instruction::Slt { rs, rt, .. } => {
+                | Instruction::Slt { rs, rt, .. }
+                | Instruction::Sltu { rs, rt, .. } => {
                     pipeline.is_register_being_written(*rs)
                         || pipeline.is_register_being_written(*rt)
                 },
@@ -1067,7 +1580,7 @@ impl Simulator {
         }
 
         match instruction {
-            Instruction::Add { rd, rs, rt } => {
+            Instruction::Add { rd, rs, rt } | Instruction::Addu { rd, rs, rt } => {
                 let rs_value = registers.read(*rs);
                 let rt_value = registers.read(*rt);
                 let result = rs_value.wrapping_add(rt_value);
@@ -1133,6 +1646,18 @@ impl Simulator {
                     println!("  SLT ${} = ({} < {}) = {}", rd, rs_value, rt_value, result);
                 }
             },
+            Instruction::Sltu { rd, rs, rt } => {
+                let rs_value = registers.read(*rs);
+                let rt_value = registers.read(*rt);
+                let result = (rs_value < rt_value) as u32;
+                registers.write(*rd, result);
+                if visualization.is_some() {
+                    println!(
+                        "  SLTU ${} = ({} < {}) = {}",
+                        rd, rs_value, rt_value, result
+                    );
+                }
+            },
             Instruction::Addi { rt, rs, imm } | Instruction::Addiu { rt, rs, imm } => {
                 let rs_value = registers.read(*rs);
                 let result = rs_value.wrapping_add(*imm as u32);
@@ -1157,11 +1682,20 @@ impl Simulator {
             },
             Instruction::Lw { rt, base, offset } => {
                 let base_value = registers.read(*base);
-                let address = base_value.wrapping_add(*offset as u32);
+                let address = match effective_address(base_value, *offset) {
+                    Some(address) => address,
+                    None => {
+                        println!(
+                            "Address error during load: {}(0x{:08X})",
+                            offset, base_value
+                        );
+                        return;
+                    },
+                };
 
                 // Always attempt to read from memory and properly update registers
                 match memory.read_word(address as usize) {
-                    Some(value) => {
+                    Ok(value) => {
                         registers.write(*rt, value);
 
                         // Enhanced debugging for crucial test addresses
@@ -1174,28 +1708,34 @@ impl Simulator {
                             println!("  LW ${} = mem[{} + {}] = {}", rt, base, offset, value);
                         }
                     },
-                    None => {
-                        println!(
-                            "Memory access error during load at address 0x{:08X}",
-                            address
-                        );
+                    Err(error) => {
+                        println!("Memory access error during load: {}", error);
                     },
                 }
             },
             Instruction::Sw { rt, base, offset } => {
                 let base_value = registers.read(*base);
-                let address = base_value.wrapping_add(*offset as u32);
+                let address = match effective_address(base_value, *offset) {
+                    Some(address) => address,
+                    None => {
+                        println!(
+                            "Address error during store: {}(0x{:08X})",
+                            offset, base_value
+                        );
+                        return;
+                    },
+                };
                 let value = registers.read(*rt);
 
-                if memory.write_word(address as usize, value) {
-                    if visualization.is_some() {
-                        println!("  SW mem[{} + {}] = ${} = {}", base, offset, rt, value);
-                    }
-                } else {
-                    println!(
-                        "Memory access error during store at address 0x{:08X}",
-                        address
-                    );
+                match memory.write_word(address as usize, value) {
+                    Ok(()) => {
+                        if visualization.is_some() {
+                            println!("  SW mem[{} + {}] = ${} = {}", base, offset, rt, value);
+                        }
+                    },
+                    Err(error) => {
+                        println!("Memory access error during store: {}", error);
+                    },
                 }
             },
             Instruction::Mult { rs, rt } => {
@@ -1243,6 +1783,14 @@ impl Simulator {
                     println!("Division by zero attempted");
                 }
             },
+            Instruction::Rdhwr { rt, rd } => {
+                if let Some(value) = registers.read_hardware(*rd) {
+                    registers.write(*rt, value);
+                    if visualization.is_some() {
+                        println!("  RDHWR ${} = hwr ${} = {}", rt, rd, value);
+                    }
+                }
+            },
             Instruction::Nop => {
                 if visualization.is_some() {
                     println!("  NOP");
@@ -1331,6 +1879,14 @@ impl Simulator {
                     println!("  JALR ${}, ${} (RA = 0x{:X})", rd, rs, pc + 4);
                 }
             },
+            Instruction::Syscall => {
+                // The pipeline model leaves I/O to the functional model, but
+                // memcpy and memset change memory the program goes on to use,
+                // and the time syscalls read the pipeline's own cycle count
+                if BlockTransfer::of(registers).is_some() || is_time_syscall(registers.read(2)) {
+                    handle_syscall(registers, memory);
+                }
+            },
             _ => {
                 // Other instructions not explicitly handled
                 if visualization.is_some() {
@@ -1345,7 +1901,7 @@ impl Simulator {
         let instruction = self.fetch_instruction();
 
         // Update the pipeline visualization for this step
-        if let Some(instr_word) = self.memory.read_word(self.pc as usize) {
+        if let Ok(instr_word) = self.memory.read_word(self.pc as usize) {
             match &mut self.execution_mode {
                 ExecutionMode::InOrder(pipeline) => {
                     let fetched_instr = decode_instruction(instr_word);
@@ -1402,6 +1958,9 @@ impl Simulator {
         // Execute instruction through pipeline
         let _result = match &mut self.execution_mode {
             ExecutionMode::InOrder(pipeline) => {
+                self.registers.hardware.cycles =
+                    cycle_counter(&mut self.throttle, pipeline.cycle_count as u64);
+                self.registers.hardware.instructions = pipeline.instruction_count as u64;
                 pipeline.execute(&instruction, &self.registers, self.pc)
             },
             ExecutionMode::OutOfOrder(processor) => {
@@ -1420,7 +1979,7 @@ impl Simulator {
                 let rs_val = self.registers.read(rs);
                 let rt_val = self.registers.read(rt);
                 if rs_val == rt_val {
-                    self.pc = self.pc.wrapping_add(4).wrapping_add((offset as u32) << 2);
+                    self.pc = branch_target(self.pc, offset);
                     println!("  BEQ taken: New PC = 0x{:08X}", self.pc);
                 } else {
                     self.pc += 4;
@@ -1430,19 +1989,19 @@ impl Simulator {
                 let rs_val = self.registers.read(rs);
                 let rt_val = self.registers.read(rt);
                 if rs_val != rt_val {
-                    self.pc = self.pc.wrapping_add(4).wrapping_add((offset as u32) << 2);
+                    self.pc = branch_target(self.pc, offset);
                     println!("  BNE taken: New PC = 0x{:08X}", self.pc);
                 } else {
                     self.pc += 4;
                 }
             },
             Instruction::J { target } => {
-                self.pc = (self.pc & 0xF0000000) | (target << 2);
+                self.pc = jump_target(self.pc, target);
                 println!("  J: New PC = 0x{:08X}", self.pc);
             },
             Instruction::Jal { target } => {
                 self.registers.write(31, self.pc + 4);
-                self.pc = (self.pc & 0xF0000000) | (target << 2);
+                self.pc = jump_target(self.pc, target);
                 println!(
                     "  JAL: RA = 0x{:08X}, New PC = 0x{:08X}",
                     self.pc + 4,
@@ -1538,7 +2097,336 @@ impl Simulator {
         result
     }
 
+    /// Kept for compatibility: the timing simulator's steps are cycles
     pub fn set_max_steps(&mut self, steps: usize) {
-        self.max_steps = steps;
+        self.set_max_cycles(steps);
+    }
+
+    pub fn set_max_cycles(&mut self, max_cycles: usize) {
+        self.limits.max_cycles = max_cycles;
+    }
+
+    pub fn set_max_instructions(&mut self, max_instructions: usize) {
+        self.limits.max_instructions = max_instructions;
+    }
+
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.limits.timeout = timeout;
+    }
+
+    /// Stop runs once the simulator holds more host memory than this. The
+    /// run ends with `TerminationReason::MemoryLimit`, and
+    /// `limits.check_memory(&self.memory_usage())` describes the overrun.
+    pub fn set_max_memory(&mut self, max_memory: Option<usize>) {
+        self.limits.max_memory = max_memory;
+    }
+
+    /// Host memory held by the simulator: every copy of the memory image,
+    /// the cache lines and the recordings enabled for the run
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let model = match &self.execution_mode {
+            ExecutionMode::InOrder(pipeline) => pipeline.memory_usage(),
+            ExecutionMode::OutOfOrder(processor) => processor.memory_usage(),
+        };
+        model
+            + Self::own_memory_usage(
+                &self.memory,
+                &self.timeline,
+                &self.pipeline_history,
+                &self.memory_trace,
+                &self.interval_stats,
+            )
+    }
+
+    /// Host memory a simulator built with this configuration starts with,
+    /// so a cap can be checked before any of it is allocated
+    pub fn projected_memory_usage(
+        pipeline_config: &PipelineConfig,
+        instr_cache_config: &CacheConfig,
+        data_cache_config: &CacheConfig,
+        memory_size: usize,
+    ) -> MemoryUsage {
+        if pipeline_config.tomasulo_config.is_some() {
+            // The simulator and the processor each hold an image
+            MemoryUsage {
+                memory: 2 * memory_size,
+                ..MemoryUsage::default()
+            }
+        } else {
+            // The simulator and both caches each hold an image
+            MemoryUsage {
+                memory: 3 * memory_size,
+                caches: instr_cache_config.host_bytes() + data_cache_config.host_bytes(),
+                traces: 0,
+            }
+        }
+    }
+
+    // Host memory of the simulator's own image and recordings. The run loops
+    // need this while the execution model is borrowed, so it takes fields.
+    fn own_memory_usage(
+        memory: &Memory,
+        timeline: &Option<Vec<InstructionTiming>>,
+        history: &Option<PipelineHistory>,
+        trace: &Option<MemoryTrace>,
+        intervals: &IntervalRecorder,
+    ) -> MemoryUsage {
+        let timeline = timeline.as_ref().map_or(0, |timeline| {
+            timeline.len() * std::mem::size_of::<InstructionTiming>()
+        });
+        MemoryUsage {
+            memory: memory.data.len(),
+            caches: 0,
+            traces: timeline
+                + history.as_ref().map_or(0, PipelineHistory::heap_size)
+                + trace.as_ref().map_or(0, MemoryTrace::heap_size)
+                + intervals.heap_size(),
+        }
+    }
+
+    /// Report progress on stderr at most once per interval
+    pub fn enable_progress(&mut self, interval: Duration) {
+        self.progress = Some(ProgressReporter::stderr(interval));
+    }
+
+    /// Report progress through a callback at most once per interval
+    pub fn set_progress_callback(&mut self, interval: Duration, callback: ProgressCallback) {
+        self.progress = Some(ProgressReporter::new(interval, callback));
+    }
+
+    /// Sample statistics every fixed number of instructions or cycles
+    pub fn set_stats_interval(&mut self, interval: Option<StatsInterval>) {
+        self.interval_stats.interval = interval;
+    }
+
+    /// Record the timing of every instruction in `timeline` (in-order mode)
+    pub fn enable_timeline(&mut self) {
+        self.timeline = Some(Vec::new());
+    }
+
+    /// Remember the last `depth` instructions fetched so `crash_dump` can
+    /// list them
+    pub fn enable_crash_dumps(&mut self, depth: usize) {
+        self.recent_instructions = Some(RecentInstructions::new(depth));
+    }
+
+    /// The machine state for a postmortem, including what each pipeline
+    /// stage held in in-order mode, if the run stopped on a fault
+    pub fn crash_dump(&self) -> Option<CrashDump> {
+        let reason = self.termination_reason.filter(|reason| reason.is_fault())?;
+        let mut dump = CrashDump::capture(
+            "timing",
+            reason,
+            self.pc,
+            self.run_totals.instructions,
+            &self.registers,
+            &self.memory,
+            self.recent_instructions.as_ref(),
+        );
+        if let ExecutionMode::InOrder(pipeline) = &self.execution_mode {
+            dump.pipeline = pipeline
+                .stages
+                .iter()
+                .map(|stage| StageState {
+                    name: stage.stage_type.short_name().to_string(),
+                    status: format!("{:?}", stage.status).to_lowercase(),
+                    instruction: match stage.instruction {
+                        Some(_) => self
+                            .memory
+                            .read_word(stage.pc as usize)
+                            .ok()
+                            .map(|word| (stage.pc, word)),
+                        None => None,
+                    },
+                })
+                .collect();
+        }
+        Some(dump)
+    }
+
+    /// Record the timeline with instructions left out as `sampling` says
+    pub fn enable_sampled_timeline(&mut self, sampling: TraceSampling) {
+        self.timeline = Some(Vec::new());
+        self.trace_sampler = Some(TraceSampler::new(sampling));
+    }
+
+    /// Record the instruction that last wrote each register and memory word
+    /// in `last_writers` (in-order mode)
+    pub fn enable_last_writers(&mut self) {
+        self.last_writers = Some(LastWriters::new());
+    }
+
+    /// Record every load and store and the backward branches of the run in
+    /// `memory_trace` (in-order mode)
+    pub fn enable_memory_trace(&mut self) {
+        self.memory_trace = Some(MemoryTrace::new());
+    }
+
+    /// Keep a cycle-stamped event for every issue, stall, cache access,
+    /// resolved branch and memory access of the run in `events`. Only the
+    /// in-order pipeline emits events, so this fails in out-of-order mode.
+    pub fn enable_event_log(&mut self) -> Result<(), String> {
+        self.start_event_log(EventLog::new())
+    }
+
+    /// Like `enable_event_log`, but stream the events to `path` as JSON lines
+    pub fn enable_event_log_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let log = EventLog::to_file(path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        self.start_event_log(log)
+    }
+
+    fn start_event_log(&mut self, log: EventLog) -> Result<(), String> {
+        match &mut self.execution_mode {
+            ExecutionMode::InOrder(pipeline) => pipeline.enable_events(),
+            ExecutionMode::OutOfOrder(_) => {
+                return Err("The event log needs the in-order pipeline".to_string())
+            },
+        }
+        self.events = Some(log);
+        Ok(())
+    }
+
+    /// Time instruction words the decoder does not recognise as NOPs and
+    /// record them in `lenient_decode` instead of stopping the run
+    pub fn enable_lenient_decode(&mut self) {
+        self.lenient_decode = Some(DecodeReport::new(UnknownInstructionPolicy::Nop));
+    }
+
+    /// Record the stage every instruction occupies in every cycle in
+    /// `pipeline_history` (in-order mode)
+    pub fn enable_pipeline_history(&mut self) {
+        if let ExecutionMode::InOrder(pipeline) = &self.execution_mode {
+            let stages = pipeline
+                .stages
+                .iter()
+                .map(|stage| (stage.name.clone(), stage.latency))
+                .collect();
+            self.pipeline_history = Some(PipelineHistory::new(stages));
+        }
+    }
+
+    /// Trained tables of the branch predictor (in-order mode)
+    pub fn predictor_state(&self) -> Option<PredictorState> {
+        match &self.execution_mode {
+            ExecutionMode::InOrder(pipeline) => Some(pipeline.branch_predictor.export_state()),
+            ExecutionMode::OutOfOrder(_) => None,
+        }
+    }
+
+    /// Start the next run with a predictor trained elsewhere, such as by a
+    /// warmup run (in-order mode)
+    pub fn load_predictor_state(&mut self, state: &PredictorState) -> Result<(), String> {
+        match &mut self.execution_mode {
+            ExecutionMode::InOrder(pipeline) => pipeline.branch_predictor.import_state(state),
+            ExecutionMode::OutOfOrder(_) => {
+                Err("the out-of-order core has no branch predictor to load".to_string())
+            },
+        }
+    }
+
+    /// Cumulative counters for the current execution mode
+    pub fn stats_counters(&self, instructions: usize, cycles: usize) -> StatsCounters {
+        match &self.execution_mode {
+            ExecutionMode::InOrder(_) => self.event_totals.counters(instructions, cycles),
+            ExecutionMode::OutOfOrder(processor) => {
+                Self::tomasulo_counters(processor, instructions, cycles)
+            },
+        }
+    }
+
+    /// End-of-run statistics with the per-interval breakdown
+    pub fn stats_report(&self) -> StatsReport {
+        StatsReport {
+            format: STATS_FORMAT,
+            version: STATS_VERSION,
+            termination_reason: self.termination_reason.map(|reason| reason.to_string()),
+            total: IntervalSample::between(0, &StatsCounters::default(), &self.run_totals),
+            cpi_stack: CpiStack::new(&self.run_totals),
+            region_of_interest: self.regions.sample(),
+            regions: self.regions.named_samples(),
+            intervals: self.interval_stats.samples.clone(),
+        }
+    }
+
+    // Look up the instruction fetch and any data access in the TLBs and cache
+    // model and return the cycles each spent beyond a cache hit
+    fn cache_stall_cycles(
+        pipeline: &mut Pipeline,
+        instruction: &Instruction,
+        registers: &Registers,
+        pc: u32,
+    ) -> (usize, usize) {
+        // Mapped addresses are translated first, a TLB miss walking the
+        // page table before the access can start
+        let data_address = instruction.get_address(registers, pc);
+        let (fetch_walk, data_walk) = pipeline.tlb_stall_cycles(
+            pc,
+            Some(data_address).filter(|_| instruction.is_memory_access()),
+        );
+
+        // Without caches every access goes to memory
+        if !pipeline.caches_enabled {
+            let caches = &pipeline.cache_hierarchy;
+            let data_stall = if instruction.is_memory_access() {
+                caches.l1_data_cache.config.miss_penalty
+            } else {
+                0
+            };
+            return (
+                fetch_walk + caches.l1_instr_cache.config.miss_penalty,
+                data_walk + data_stall,
+            );
+        }
+
+        // kseg1 is the uncached alias of memory and uncached regions hold
+        // device registers, so their accesses bypass the caches and wait for
+        // memory
+        let fetch_cached = pipeline.is_cached(pc);
+        let data_cached = pipeline.is_cached(data_address);
+        if instruction.is_memory_access() && !data_cached {
+            pipeline.uncached_accesses += 1;
+        }
+        let caches = &mut pipeline.cache_hierarchy;
+        let mut fetch_stall = 0;
+        let fetch_hit_latency = caches.l1_instr_cache.config.hit_latency;
+        if !fetch_cached {
+            fetch_stall = caches.l1_instr_cache.config.miss_penalty;
+        } else if let Some((_, latency)) = caches.read_instruction(pc as usize) {
+            fetch_stall = latency.saturating_sub(fetch_hit_latency);
+        }
+
+        let mut data_stall = 0;
+
+        if instruction.is_memory_access() {
+            let address = data_address as usize;
+            let data_hit_latency = caches.l1_data_cache.config.hit_latency;
+            let latency = if !data_cached {
+                data_hit_latency + caches.l1_data_cache.config.miss_penalty
+            } else if instruction.is_load() {
+                caches.read_data(address).map_or(0, |(_, latency)| latency)
+            } else {
+                // Only the timing of the write is modelled, the architectural
+                // store happens when the instruction executes
+                caches.write_data(address, &[0; 4])
+            };
+            data_stall = latency.saturating_sub(data_hit_latency);
+        }
+
+        (fetch_walk + fetch_stall, data_walk + data_stall)
+    }
+
+    fn tomasulo_counters(
+        processor: &TomasuloProcessor,
+        instructions: usize,
+        cycles: usize,
+    ) -> StatsCounters {
+        StatsCounters {
+            instructions,
+            cycles,
+            branch_mispredictions: processor.branch_mispredictions,
+            ..StatsCounters::default()
+        }
     }
 }
@@ -0,0 +1,384 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// stats.rs
+//
+// This file contains interval statistics for the timing simulator.
+// Counters are sampled every fixed number of instructions or cycles so the
+// JSON report shows how CPI and miss rates change across program phases,
+// not only the end-of-run aggregates, and the run's stall cycles are split
+// by cause into a CPI stack.
+
+use super::regions::{NamedRegionSample, RegionSample};
+use super::trace_sampling::TraceSampling;
+use serde::Serialize;
+
+/// Name and version written at the top of a statistics report
+pub const STATS_FORMAT: &str = "vmips-stats";
+pub const STATS_VERSION: u32 = 1;
+
+/// Name and version written at the top of an instruction trace
+pub const TRACE_FORMAT: &str = "vmips-trace";
+pub const TRACE_VERSION: u32 = 1;
+
+/// Timing of one executed instruction, recorded when the timeline is enabled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct InstructionTiming {
+    pub index: usize,
+    pub pc: u32,
+    pub word: u32,
+    pub issue_cycle: usize,
+    /// Cycles until the next instruction issues, including stalls
+    pub cycles: usize,
+    pub stall_cycles: usize,
+    pub mispredicted: bool,
+}
+
+/// How often interval statistics are sampled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsInterval {
+    Instructions(usize),
+    Cycles(usize),
+}
+
+/// Cumulative counters at one point in a run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StatsCounters {
+    pub instructions: usize,
+    pub cycles: usize,
+    pub icache_accesses: usize,
+    pub icache_misses: usize,
+    pub dcache_accesses: usize,
+    pub dcache_misses: usize,
+    pub branch_mispredictions: usize,
+    pub stalls: usize,
+    pub branch_stalls: usize,
+    pub stall_breakdown: StallBreakdown,
+}
+
+impl StatsCounters {
+    /// The counts added since `start`
+    pub fn since(&self, start: &StatsCounters) -> Self {
+        Self {
+            instructions: self.instructions - start.instructions,
+            cycles: self.cycles - start.cycles,
+            icache_accesses: self.icache_accesses - start.icache_accesses,
+            icache_misses: self.icache_misses - start.icache_misses,
+            dcache_accesses: self.dcache_accesses - start.dcache_accesses,
+            dcache_misses: self.dcache_misses - start.dcache_misses,
+            branch_mispredictions: self.branch_mispredictions - start.branch_mispredictions,
+            stalls: self.stalls - start.stalls,
+            branch_stalls: self.branch_stalls - start.branch_stalls,
+            stall_breakdown: self.stall_breakdown.since(&start.stall_breakdown),
+        }
+    }
+
+    pub fn add(&mut self, other: &StatsCounters) {
+        self.instructions += other.instructions;
+        self.cycles += other.cycles;
+        self.icache_accesses += other.icache_accesses;
+        self.icache_misses += other.icache_misses;
+        self.dcache_accesses += other.dcache_accesses;
+        self.dcache_misses += other.dcache_misses;
+        self.branch_mispredictions += other.branch_mispredictions;
+        self.stalls += other.stalls;
+        self.branch_stalls += other.branch_stalls;
+        self.stall_breakdown.add(&other.stall_breakdown);
+    }
+}
+
+/// Stall cycles of the in-order pipeline by their cause
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct StallBreakdown {
+    /// Branches waiting to resolve or flushing a mispredicted path
+    pub branch: usize,
+    /// Instruction fetches that missed
+    pub icache: usize,
+    /// Loads, stores and block transfers that missed
+    pub dcache: usize,
+    /// Multi-cycle stages holding back the next fetch
+    pub structural: usize,
+    /// Operands waiting for an earlier instruction's result
+    pub raw: usize,
+}
+
+impl StallBreakdown {
+    pub fn total(&self) -> usize {
+        self.branch + self.icache + self.dcache + self.structural + self.raw
+    }
+
+    /// The stalls added since `start`
+    pub fn since(&self, start: &StallBreakdown) -> Self {
+        Self {
+            branch: self.branch - start.branch,
+            icache: self.icache - start.icache,
+            dcache: self.dcache - start.dcache,
+            structural: self.structural - start.structural,
+            raw: self.raw - start.raw,
+        }
+    }
+
+    pub fn add(&mut self, other: &StallBreakdown) {
+        self.branch += other.branch;
+        self.icache += other.icache;
+        self.dcache += other.dcache;
+        self.structural += other.structural;
+        self.raw += other.raw;
+    }
+}
+
+/// Average cycles per instruction split into the base of one instruction
+/// per issue slot and the stalls added by each cause, so configurations
+/// can be compared by where their cycles go
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CpiStack {
+    pub instructions: usize,
+    pub cycles: usize,
+    pub cpi: f64,
+    pub base: f64,
+    pub branch: f64,
+    pub icache: f64,
+    pub dcache: f64,
+    pub structural: f64,
+    pub raw: f64,
+}
+
+impl CpiStack {
+    /// The stack of a run from its counters. Cycles not attributed to a
+    /// stall count towards the base, so the components add up to the CPI.
+    pub fn new(counters: &StatsCounters) -> Self {
+        let stalls = &counters.stall_breakdown;
+        let per_instruction = |cycles: usize| ratio(cycles, counters.instructions);
+        Self {
+            instructions: counters.instructions,
+            cycles: counters.cycles,
+            cpi: per_instruction(counters.cycles),
+            base: per_instruction(counters.cycles.saturating_sub(stalls.total())),
+            branch: per_instruction(stalls.branch),
+            icache: per_instruction(stalls.icache),
+            dcache: per_instruction(stalls.dcache),
+            structural: per_instruction(stalls.structural),
+            raw: per_instruction(stalls.raw),
+        }
+    }
+
+    /// Name and CPI of each component, base first
+    pub fn components(&self) -> [(&'static str, f64); 6] {
+        [
+            ("base", self.base),
+            ("branch", self.branch),
+            ("icache", self.icache),
+            ("dcache", self.dcache),
+            ("structural", self.structural),
+            ("raw", self.raw),
+        ]
+    }
+
+    /// The components as a bar chart normalized to the total CPI
+    pub fn render(&self) -> String {
+        const WIDTH: f64 = 50.0;
+        let total: f64 = self.components().iter().map(|&(_, cpi)| cpi).sum();
+        let mut result = format!(
+            "CPI stack: {:.3} CPI over {} instructions\n",
+            self.cpi, self.instructions
+        );
+        for (name, cpi) in self.components().iter() {
+            let share = if total > 0.0 { cpi / total } else { 0.0 };
+            result.push_str(&format!(
+                "  {:<10} {:>7.3} {:>6.1}%  {}\n",
+                name,
+                cpi,
+                100.0 * share,
+                "#".repeat((share * WIDTH).round() as usize)
+            ));
+        }
+        result
+    }
+}
+
+/// Statistics for one interval (or for the whole run)
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct IntervalSample {
+    pub index: usize,
+    pub start_instruction: usize,
+    pub start_cycle: usize,
+    pub instructions: usize,
+    pub cycles: usize,
+    pub cpi: f64,
+    pub icache_accesses: usize,
+    pub icache_misses: usize,
+    pub icache_miss_rate: f64,
+    pub dcache_accesses: usize,
+    pub dcache_misses: usize,
+    pub dcache_miss_rate: f64,
+    pub branch_mispredictions: usize,
+    pub stalls: usize,
+    pub branch_stalls: usize,
+}
+
+impl IntervalSample {
+    /// Build a sample from the difference between two counter snapshots
+    pub fn between(index: usize, start: &StatsCounters, end: &StatsCounters) -> Self {
+        let instructions = end.instructions - start.instructions;
+        let cycles = end.cycles - start.cycles;
+        let icache_accesses = end.icache_accesses - start.icache_accesses;
+        let icache_misses = end.icache_misses - start.icache_misses;
+        let dcache_accesses = end.dcache_accesses - start.dcache_accesses;
+        let dcache_misses = end.dcache_misses - start.dcache_misses;
+
+        Self {
+            index,
+            start_instruction: start.instructions,
+            start_cycle: start.cycles,
+            instructions,
+            cycles,
+            cpi: ratio(cycles, instructions),
+            icache_accesses,
+            icache_misses,
+            icache_miss_rate: ratio(icache_misses, icache_accesses),
+            dcache_accesses,
+            dcache_misses,
+            dcache_miss_rate: ratio(dcache_misses, dcache_accesses),
+            branch_mispredictions: end.branch_mispredictions - start.branch_mispredictions,
+            stalls: end.stalls - start.stalls,
+            branch_stalls: end.branch_stalls - start.branch_stalls,
+        }
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// Samples counters at fixed intervals during a run
+#[derive(Clone, Debug, Default)]
+pub struct IntervalRecorder {
+    pub interval: Option<StatsInterval>,
+    pub samples: Vec<IntervalSample>,
+    last: StatsCounters,
+}
+
+impl IntervalRecorder {
+    pub fn new(interval: Option<StatsInterval>) -> Self {
+        Self {
+            interval,
+            samples: Vec::new(),
+            last: StatsCounters::default(),
+        }
+    }
+
+    /// Approximate host memory the samples take
+    pub fn heap_size(&self) -> usize {
+        self.samples.len() * std::mem::size_of::<IntervalSample>()
+    }
+
+    /// Discard samples from a previous run
+    pub fn reset(&mut self) {
+        self.samples.clear();
+        self.last = StatsCounters::default();
+    }
+
+    /// Whether the counters have crossed the next interval boundary
+    pub fn is_due(&self, instructions: usize, cycles: usize) -> bool {
+        match self.interval {
+            Some(StatsInterval::Instructions(n)) if n > 0 => {
+                instructions >= self.last.instructions + n
+            },
+            Some(StatsInterval::Cycles(n)) if n > 0 => cycles >= self.last.cycles + n,
+            _ => false,
+        }
+    }
+
+    /// Close the current interval at the given counters
+    pub fn record(&mut self, counters: StatsCounters) {
+        let sample = IntervalSample::between(self.samples.len(), &self.last, &counters);
+        self.samples.push(sample);
+        self.last = counters;
+    }
+
+    /// Close the final, possibly partial, interval at the end of a run
+    pub fn finish(&mut self, counters: StatsCounters) {
+        if self.interval.is_some() && counters.instructions > self.last.instructions {
+            self.record(counters);
+        }
+    }
+}
+
+/// End-of-run statistics with the per-interval breakdown
+#[derive(Clone, Debug, Serialize)]
+pub struct StatsReport {
+    pub format: &'static str,
+    pub version: u32,
+    pub termination_reason: Option<String>,
+    pub total: IntervalSample,
+    pub cpi_stack: CpiStack,
+    /// Statistics between the program's region markers, if it has any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region_of_interest: Option<RegionSample>,
+    /// Statistics of each named region the program entered
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<NamedRegionSample>,
+    pub intervals: Vec<IntervalSample>,
+}
+
+impl StatsReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+/// The timing of every executed instruction, as written by `--trace`
+#[derive(Clone, Debug, Serialize)]
+pub struct InstructionTrace {
+    pub format: &'static str,
+    pub version: u32,
+    /// Which instructions were kept, when not all of them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<TraceSampling>,
+    pub instructions: Vec<InstructionTiming>,
+}
+
+impl InstructionTrace {
+    pub fn new(instructions: Vec<InstructionTiming>) -> Self {
+        Self {
+            format: TRACE_FORMAT,
+            version: TRACE_VERSION,
+            sampling: None,
+            instructions,
+        }
+    }
+
+    /// A trace of the instructions `sampling` kept
+    pub fn sampled(instructions: Vec<InstructionTiming>, sampling: TraceSampling) -> Self {
+        Self {
+            sampling: Some(sampling).filter(TraceSampling::is_sampled),
+            ..Self::new(instructions)
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
@@ -0,0 +1,562 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// stress.rs
+//
+// This file contains the generator of random stress programs for the timing
+// model. A seeded generator fills a loop body with ALU operations in
+// dependency chains of a chosen length, data-dependent forward branches and
+// loads and stores that walk a working set with a fixed stride or at random.
+// The program is built with ProgramBuilder, and the generator runs the same
+// operations on the host, so after a run the final registers and working
+// set are checked against what the program must have computed.
+
+use super::compare::TimingVariant;
+use super::simulator::{ExecutionMode, Simulator};
+use super::stats::StatsCounters;
+use super::workloads::render_run;
+use crate::assembler::{Program, ProgramBuilder};
+use crate::functional_simulator::memory::Memory;
+use crate::functional_simulator::registers::{
+    Registers, S0, S1, S2, S3, S4, S5, S6, T0, T1, T2, T3, T4, T5, T6, T7, T8, T9, V0, ZERO,
+};
+use crate::loader::{ProgramImage, DATA_BASE};
+use std::fmt;
+
+/// Largest working set, which keeps the data below the text segment
+pub const MAX_WORKING_SET: u32 = 1 << 20;
+/// Most operations in a loop body
+pub const MAX_BODY_LENGTH: usize = 4096;
+
+// Registers the generated operations compute in
+const POOL: [u32; 10] = [T0, T1, T2, T3, T4, T5, T6, T7, T8, T9];
+// Registers the generated code reserves: the working set base, the loop
+// counter, the stride offset, the random state, the address mask, a
+// scratch register and the address of the current access
+const BASE: u32 = S0;
+const COUNTER: u32 = S1;
+const OFFSET: u32 = S2;
+const RANDOM: u32 = S3;
+const MASK: u32 = S4;
+const SCRATCH: u32 = S5;
+const ADDRESS: u32 = S6;
+
+/// How loads and stores walk the working set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessPattern {
+    /// Each access is this many bytes past the last, wrapping around
+    Stride(u32),
+    /// Each access is at a pseudo-random word of the working set
+    Random,
+}
+
+impl AccessPattern {
+    /// Parse `random`, `sequential` or `stride:BYTES`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec {
+            "random" => Ok(AccessPattern::Random),
+            "sequential" => Ok(AccessPattern::Stride(4)),
+            _ => spec
+                .strip_prefix("stride:")
+                .and_then(|bytes| bytes.parse().ok())
+                .map(AccessPattern::Stride)
+                .ok_or_else(|| {
+                    format!(
+                        "expected random, sequential or stride:BYTES, found '{}'",
+                        spec
+                    )
+                }),
+        }
+    }
+}
+
+impl fmt::Display for AccessPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessPattern::Stride(bytes) => write!(f, "stride:{}", bytes),
+            AccessPattern::Random => write!(f, "random"),
+        }
+    }
+}
+
+/// The shape of a generated stress program
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StressConfig {
+    pub seed: u32,
+    /// Operations in the loop body
+    pub length: usize,
+    /// Times the loop body runs
+    pub iterations: usize,
+    /// Operations in each dependency chain, each reading the result of the
+    /// one before. 1 makes every operation independent.
+    pub chain: usize,
+    /// Fraction of operations that are data-dependent forward branches
+    pub branch_density: f64,
+    /// Fraction of operations that are loads or stores
+    pub memory_density: f64,
+    pub pattern: AccessPattern,
+    /// Bytes the loads and stores stay within, a power of two
+    pub working_set: u32,
+}
+
+impl Default for StressConfig {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            length: 64,
+            iterations: 100,
+            chain: 4,
+            branch_density: 0.1,
+            memory_density: 0.25,
+            pattern: AccessPattern::Stride(4),
+            working_set: 4096,
+        }
+    }
+}
+
+impl StressConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.length == 0 || self.length > MAX_BODY_LENGTH {
+            return Err(format!(
+                "length must be between 1 and {}, found {}",
+                MAX_BODY_LENGTH, self.length
+            ));
+        }
+        if self.iterations == 0 || self.iterations > i32::MAX as usize {
+            return Err(format!(
+                "iterations must be at least 1, found {}",
+                self.iterations
+            ));
+        }
+        if self.chain == 0 {
+            return Err("chain length must be at least 1".to_string());
+        }
+        for (name, density) in [
+            ("branch", self.branch_density),
+            ("memory", self.memory_density),
+        ] {
+            if !(0.0..=1.0).contains(&density) {
+                return Err(format!(
+                    "{} density must be between 0 and 1, found {}",
+                    name, density
+                ));
+            }
+        }
+        if self.branch_density + self.memory_density > 1.0 {
+            return Err("branch and memory densities add up to more than 1".to_string());
+        }
+        if !self.working_set.is_power_of_two()
+            || self.working_set < 4
+            || self.working_set > MAX_WORKING_SET
+        {
+            return Err(format!(
+                "working set must be a power of two between 4 and {} bytes, found {}",
+                MAX_WORKING_SET, self.working_set
+            ));
+        }
+        if let AccessPattern::Stride(stride) = self.pattern {
+            if stride == 0 || stride % 4 != 0 || stride > 32764 {
+                return Err(format!(
+                    "stride must be a multiple of 4 between 4 and 32764 bytes, found {}",
+                    stride
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StressConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "seed={} length={} iterations={} chain={} branches={} memory={} pattern={} working-set={}",
+            self.seed,
+            self.length,
+            self.iterations,
+            self.chain,
+            self.branch_density,
+            self.memory_density,
+            self.pattern,
+            self.working_set
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AluOp {
+    Add,
+    Sub,
+    And,
+    Or,
+    Xor,
+    Nor,
+    Slt,
+    Sltu,
+    Sll(u32),
+    Srl(u32),
+    Addi(i16),
+    Xori(u16),
+}
+
+// One operation of the loop body, as the host runs it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Alu {
+        op: AluOp,
+        rd: u32,
+        rs: u32,
+        rt: u32,
+    },
+    Load {
+        rt: u32,
+    },
+    Store {
+        rt: u32,
+    },
+    /// Skip the next operation when bit 0 of `rs` is clear
+    SkipIfEven {
+        rs: u32,
+    },
+}
+
+// Numerical Recipes LCG; the high bits are the most random
+struct Lcg(u32);
+
+impl Lcg {
+    fn next(&mut self) -> u32 {
+        self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        ((self.next() >> 16) as usize * bound) >> 16
+    }
+
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 8) as f64 / (1u32 << 24) as f64
+    }
+
+    fn register(&mut self) -> u32 {
+        POOL[self.below(POOL.len())]
+    }
+}
+
+/// A generated program with the operations and data it was built from
+#[derive(Clone, Debug)]
+pub struct StressProgram {
+    pub config: StressConfig,
+    body: Vec<Op>,
+    initial_registers: Vec<u32>,
+    initial_memory: Vec<u32>,
+}
+
+// Architectural state the host computes
+struct HostState {
+    registers: [u32; 32],
+    memory: Vec<u32>,
+}
+
+impl StressProgram {
+    pub fn generate(config: StressConfig) -> Result<Self, String> {
+        config.validate()?;
+        let mut rng = Lcg(config.seed);
+        let initial_registers = POOL.iter().map(|_| rng.next()).collect();
+        let initial_memory = (0..config.working_set / 4).map(|_| rng.next()).collect();
+
+        let mut body = Vec::with_capacity(config.length);
+        let mut current = rng.register();
+        for index in 0..config.length {
+            if index % config.chain == 0 {
+                // A new chain starts from any register
+                current = rng.register();
+            }
+            let choice = rng.unit();
+            let last = index + 1 == config.length;
+            let op = if choice < config.branch_density && !last {
+                Op::SkipIfEven { rs: current }
+            } else if choice < config.branch_density + config.memory_density {
+                if rng.below(2) == 0 {
+                    let rt = rng.register();
+                    current = rt;
+                    Op::Load { rt }
+                } else {
+                    Op::Store { rt: current }
+                }
+            } else {
+                let op = match rng.below(12) {
+                    0 => AluOp::Add,
+                    1 => AluOp::Sub,
+                    2 => AluOp::And,
+                    3 => AluOp::Or,
+                    4 => AluOp::Xor,
+                    5 => AluOp::Nor,
+                    6 => AluOp::Slt,
+                    7 => AluOp::Sltu,
+                    8 => AluOp::Sll(1 + rng.below(31) as u32),
+                    9 => AluOp::Srl(1 + rng.below(31) as u32),
+                    10 => AluOp::Addi(rng.next() as i16),
+                    _ => AluOp::Xori(rng.next() as u16),
+                };
+                let (rs, rt) = (current, rng.register());
+                let rd = rng.register();
+                current = rd;
+                Op::Alu { op, rd, rs, rt }
+            };
+            body.push(op);
+        }
+
+        Ok(Self {
+            config,
+            body,
+            initial_registers,
+            initial_memory,
+        })
+    }
+
+    /// The program, with the working set as its data
+    pub fn program(&self) -> Program {
+        let mask = (self.config.working_set - 1) & !3;
+        let mut builder = ProgramBuilder::new()
+            .la(BASE, "working_set")
+            .li(COUNTER, self.config.iterations as i32)
+            .li(OFFSET, 0)
+            .li(RANDOM, self.random_seed() as i32)
+            .li(MASK, mask as i32);
+        for (&register, &value) in POOL.iter().zip(&self.initial_registers) {
+            builder = builder.li(register, value as i32);
+        }
+
+        builder = builder.label("body");
+        for (index, op) in self.body.iter().enumerate() {
+            builder = match *op {
+                Op::Alu { op, rd, rs, rt } => match op {
+                    AluOp::Add => builder.add(rd, rs, rt),
+                    AluOp::Sub => builder.sub(rd, rs, rt),
+                    AluOp::And => builder.and(rd, rs, rt),
+                    AluOp::Or => builder.or(rd, rs, rt),
+                    AluOp::Xor => builder.xor(rd, rs, rt),
+                    AluOp::Nor => builder.nor(rd, rs, rt),
+                    AluOp::Slt => builder.slt(rd, rs, rt),
+                    AluOp::Sltu => builder.sltu(rd, rs, rt),
+                    AluOp::Sll(shamt) => builder.sll(rd, rs, shamt),
+                    AluOp::Srl(shamt) => builder.srl(rd, rs, shamt),
+                    AluOp::Addi(imm) => builder.addi(rd, rs, imm),
+                    AluOp::Xori(imm) => builder.xori(rd, rs, imm),
+                },
+                Op::Load { rt } => self.next_address(builder).lw(rt, 0, ADDRESS),
+                Op::Store { rt } => self.next_address(builder).sw(rt, 0, ADDRESS),
+                Op::SkipIfEven { rs } => {
+                    builder
+                        .andi(SCRATCH, rs, 1)
+                        .beq(SCRATCH, ZERO, &skip_label(index))
+                },
+            };
+            if index > 0 && matches!(self.body[index - 1], Op::SkipIfEven { .. }) {
+                builder = builder.label(&skip_label(index - 1));
+            }
+        }
+
+        // The counter is tested with bne, which the timing model resolves
+        builder
+            .addi(COUNTER, COUNTER, -1)
+            .bne(COUNTER, ZERO, "body")
+            .li(V0, 10)
+            .syscall()
+            .data_label("working_set")
+            .words(&self.initial_memory)
+            .build()
+            .expect("generated labels are all defined")
+    }
+
+    pub fn image(&self) -> ProgramImage {
+        ProgramImage::from_program(&self.program())
+    }
+
+    /// Check the registers and working set after a run against the host
+    pub fn verify(&self, registers: &Registers, memory: &Memory) -> Result<(), String> {
+        let expected = self.run_on_host();
+        for &register in &POOL {
+            let actual = registers.read(register);
+            if actual != expected.registers[register as usize] {
+                return Err(format!(
+                    "register ${} is 0x{:08X}, expected 0x{:08X}",
+                    register, actual, expected.registers[register as usize]
+                ));
+            }
+        }
+        for (index, &word) in expected.memory.iter().enumerate() {
+            let address = DATA_BASE as usize + index * 4;
+            match memory.read_word(address) {
+                Ok(actual) if actual == word => {},
+                Ok(actual) => {
+                    return Err(format!(
+                        "word at 0x{:08X} is 0x{:08X}, expected 0x{:08X}",
+                        address, actual, word
+                    ))
+                },
+                Err(e) => return Err(format!("word at 0x{:08X} cannot be read: {}", address, e)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the program on the in-order timing simulator and check its result
+    pub fn run(&self, variant: &TimingVariant) -> Result<StressReport, String> {
+        let image = self.image();
+        let mut simulator = Simulator::new(
+            variant.pipeline_config.clone(),
+            variant.instr_cache_config.clone(),
+            variant.data_cache_config.clone(),
+            image.memory_required().next_power_of_two(),
+        );
+        simulator.visualization = None;
+        simulator
+            .load_image(&image)
+            .map_err(|e| format!("Failed to load the stress program: {}", e))?;
+        simulator.run();
+        if let ExecutionMode::OutOfOrder(_) = simulator.execution_mode {
+            return Err("Stress programs run on the in-order pipeline".to_string());
+        }
+        Ok(StressReport {
+            config: self.config,
+            timing: variant.describe(),
+            totals: simulator.run_totals,
+            verified: self.verify(&simulator.registers, &simulator.memory),
+        })
+    }
+
+    // The xorshift state must not be zero
+    fn random_seed(&self) -> u32 {
+        self.config.seed.wrapping_mul(0x9E37_79B9) | 1
+    }
+
+    // Point ADDRESS at the next word the access pattern visits
+    fn next_address(&self, builder: ProgramBuilder) -> ProgramBuilder {
+        match self.config.pattern {
+            AccessPattern::Stride(stride) => builder
+                .addi(OFFSET, OFFSET, stride as i16)
+                .and(OFFSET, OFFSET, MASK)
+                .add(ADDRESS, OFFSET, BASE),
+            // xorshift32
+            AccessPattern::Random => builder
+                .sll(SCRATCH, RANDOM, 13)
+                .xor(RANDOM, RANDOM, SCRATCH)
+                .srl(SCRATCH, RANDOM, 17)
+                .xor(RANDOM, RANDOM, SCRATCH)
+                .sll(SCRATCH, RANDOM, 5)
+                .xor(RANDOM, RANDOM, SCRATCH)
+                .and(ADDRESS, RANDOM, MASK)
+                .add(ADDRESS, ADDRESS, BASE),
+        }
+    }
+
+    fn run_on_host(&self) -> HostState {
+        let mut state = HostState {
+            registers: [0; 32],
+            memory: self.initial_memory.clone(),
+        };
+        for (&register, &value) in POOL.iter().zip(&self.initial_registers) {
+            state.registers[register as usize] = value;
+        }
+        let mask = (self.config.working_set - 1) & !3;
+        let mut offset = 0u32;
+        let mut random = self.random_seed();
+        let mut next_word = |pattern: AccessPattern| -> usize {
+            let byte = match pattern {
+                AccessPattern::Stride(stride) => {
+                    offset = offset.wrapping_add(stride) & mask;
+                    offset
+                },
+                AccessPattern::Random => {
+                    random ^= random << 13;
+                    random ^= random >> 17;
+                    random ^= random << 5;
+                    random & mask
+                },
+            };
+            byte as usize / 4
+        };
+
+        for _ in 0..self.config.iterations {
+            let mut skip = false;
+            for op in &self.body {
+                if skip {
+                    skip = false;
+                    continue;
+                }
+                let regs = &mut state.registers;
+                match *op {
+                    Op::Alu { op, rd, rs, rt } => {
+                        let (a, b) = (regs[rs as usize], regs[rt as usize]);
+                        regs[rd as usize] = match op {
+                            AluOp::Add => a.wrapping_add(b),
+                            AluOp::Sub => a.wrapping_sub(b),
+                            AluOp::And => a & b,
+                            AluOp::Or => a | b,
+                            AluOp::Xor => a ^ b,
+                            AluOp::Nor => !(a | b),
+                            AluOp::Slt => ((a as i32) < (b as i32)) as u32,
+                            AluOp::Sltu => (a < b) as u32,
+                            AluOp::Sll(shamt) => a << shamt,
+                            AluOp::Srl(shamt) => a >> shamt,
+                            AluOp::Addi(imm) => a.wrapping_add(imm as u32),
+                            AluOp::Xori(imm) => a ^ imm as u32,
+                        };
+                    },
+                    Op::Load { rt } => {
+                        regs[rt as usize] = state.memory[next_word(self.config.pattern)]
+                    },
+                    Op::Store { rt } => {
+                        state.memory[next_word(self.config.pattern)] = regs[rt as usize]
+                    },
+                    Op::SkipIfEven { rs } => skip = regs[rs as usize] & 1 == 0,
+                }
+            }
+        }
+        state
+    }
+}
+
+fn skip_label(index: usize) -> String {
+    format!("skip{}", index)
+}
+
+/// The counters of one stress run and whether its final state was right
+#[derive(Clone, Debug)]
+pub struct StressReport {
+    pub config: StressConfig,
+    /// The timing configuration in `key=value` form
+    pub timing: String,
+    pub totals: StatsCounters,
+    pub verified: Result<(), String>,
+}
+
+impl StressReport {
+    pub fn render(&self) -> String {
+        format!(
+            "Stress program: {}\nConfig: {}\n{}",
+            self.config,
+            self.timing,
+            render_run(&self.totals, &self.verified)
+        )
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// tlb.rs
+//
+// This file contains the TLB timing model of the in-order pipeline. Memory
+// has no page tables, so the TLB only tracks which virtual pages it holds:
+// a fetch or data access to a mapped address (kuseg or kseg2) looks up its
+// page, and a miss pays a fixed page-walk penalty and fills an entry.
+// kseg0 and kseg1 are unmapped and never touch the TLB. Entries are tagged
+// with the address space ID current when they were filled; a TLB without
+// tags is flushed on every context switch instead.
+
+use crate::functional_simulator::memory::AddressSegment;
+
+/// Geometry and miss cost of a TLB
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlbConfig {
+    pub entries: usize,
+    pub associativity: usize,
+    pub page_size: usize,
+    /// Cycles a miss spends walking the page table
+    pub walk_penalty: usize,
+    /// Whether entries carry an ASID, so switching address spaces keeps
+    /// them; an untagged TLB is flushed instead
+    pub tagged: bool,
+    /// Cycles a flush on a context switch takes, before the refill misses
+    pub flush_penalty: usize,
+}
+
+impl TlbConfig {
+    /// A tagged TLB of `entries` entries in sets of `associativity`, over
+    /// 4 KiB pages with a 20-cycle page walk
+    pub fn new(entries: usize, associativity: usize) -> Self {
+        assert!(entries > 0, "A TLB needs at least one entry");
+        assert!(
+            associativity > 0 && entries % associativity == 0,
+            "TLB entries must be a multiple of the associativity"
+        );
+        Self {
+            entries,
+            associativity,
+            page_size: 4096,
+            walk_penalty: 20,
+            tagged: true,
+            flush_penalty: 0,
+        }
+    }
+
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        assert!(
+            page_size.is_power_of_two(),
+            "Page size must be a power of two"
+        );
+        self.page_size = page_size;
+        self
+    }
+
+    pub fn with_walk_penalty(mut self, cycles: usize) -> Self {
+        self.walk_penalty = cycles;
+        self
+    }
+
+    /// Tag entries with ASIDs, or flush on each switch taking
+    /// `flush_penalty` cycles
+    pub fn with_asids(mut self, tagged: bool, flush_penalty: usize) -> Self {
+        self.tagged = tagged;
+        self.flush_penalty = flush_penalty;
+        self
+    }
+}
+
+/// Lookups and misses of one TLB
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TlbStats {
+    pub accesses: usize,
+    pub misses: usize,
+    /// Cycles spent on page walks
+    pub walk_cycles: usize,
+    /// Changes of the current ASID
+    pub context_switches: usize,
+    pub flushes: usize,
+}
+
+impl TlbStats {
+    pub fn miss_rate(&self) -> f64 {
+        if self.accesses == 0 {
+            0.0
+        } else {
+            self.misses as f64 / self.accesses as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TlbEntry {
+    page: u32,
+    asid: u8,
+    last_use: u64,
+}
+
+/// A set-associative TLB with LRU replacement
+#[derive(Debug, Clone)]
+pub struct Tlb {
+    pub config: TlbConfig,
+    pub stats: TlbStats,
+    sets: Vec<Vec<TlbEntry>>,
+    asid: u8,
+    clock: u64,
+}
+
+impl Tlb {
+    pub fn new(config: TlbConfig) -> Self {
+        let sets =
+            vec![Vec::with_capacity(config.associativity); config.entries / config.associativity];
+        Self {
+            config,
+            stats: TlbStats::default(),
+            sets,
+            asid: 0,
+            clock: 0,
+        }
+    }
+
+    /// Whether accesses to `address` are translated through the TLB
+    pub fn is_mapped(address: u32) -> bool {
+        AddressSegment::of(address)
+            .physical_address(address)
+            .is_none()
+    }
+
+    /// Look up the page holding `address` and return the cycles the
+    /// translation adds: none on a hit or for an unmapped address, the page
+    /// walk on a miss, after which the page is in the TLB
+    pub fn access(&mut self, address: u32) -> usize {
+        if !Self::is_mapped(address) {
+            return 0;
+        }
+        self.clock += 1;
+        self.stats.accesses += 1;
+
+        let page = address / self.config.page_size as u32;
+        let (clock, asid) = (self.clock, self.asid);
+        let associativity = self.config.associativity;
+        let set_count = self.sets.len();
+        let set = &mut self.sets[page as usize % set_count];
+        if let Some(entry) = set
+            .iter_mut()
+            .find(|entry| entry.page == page && entry.asid == asid)
+        {
+            entry.last_use = clock;
+            return 0;
+        }
+
+        let entry = TlbEntry {
+            page,
+            asid,
+            last_use: clock,
+        };
+        if set.len() < associativity {
+            set.push(entry);
+        } else if let Some(victim) = set.iter_mut().min_by_key(|entry| entry.last_use) {
+            *victim = entry;
+        }
+        self.stats.misses += 1;
+        self.stats.walk_cycles += self.config.walk_penalty;
+        self.config.walk_penalty
+    }
+
+    /// Drop every entry
+    pub fn flush(&mut self) {
+        for set in &mut self.sets {
+            set.clear();
+        }
+        self.stats.flushes += 1;
+    }
+
+    /// The address space lookups are made in
+    pub fn asid(&self) -> u8 {
+        self.asid
+    }
+
+    /// Switch to address space `asid` and return the cycles the switch
+    /// costs: none for a tagged TLB, which keeps the other spaces' entries,
+    /// and the flush penalty for an untagged one, which loses them all
+    pub fn set_asid(&mut self, asid: u8) -> usize {
+        if asid == self.asid {
+            return 0;
+        }
+        self.asid = asid;
+        self.stats.context_switches += 1;
+        if self.config.tagged {
+            return 0;
+        }
+        self.flush();
+        self.config.flush_penalty
+    }
+
+    /// Entries currently held
+    pub fn occupancy(&self) -> usize {
+        self.sets.iter().map(Vec::len).sum()
+    }
+}
@@ -29,6 +29,7 @@
 use crate::functional_simulator::instructions::Instruction;
 use crate::functional_simulator::memory::Memory;
 use crate::functional_simulator::registers::Registers;
+use crate::utils::limits::MemoryUsage;
 use std::collections::{HashMap, VecDeque};
 use std::fmt;
 
@@ -61,8 +62,8 @@ pub struct ReservationStation {
     pub status: InstructionStatus,
     pub vj: Option<u32>,      // Value of first source operand
     pub vk: Option<u32>,      // Value of second source operand
-    pub qj: Option<usize>,    // Reservation station producing first operand
-    pub qk: Option<usize>,    // Reservation station producing second operand
+    pub qj: Option<usize>,    // ROB entry producing first operand
+    pub qk: Option<usize>,    // ROB entry producing second operand
     pub dest: Option<usize>,  // Destination ROB entry
     pub address: Option<u32>, // Memory address (for loads/stores)
     pub cycles_remaining: usize,
@@ -156,6 +157,7 @@ pub struct ReorderBufferEntry {
     pub predicted_target: Option<u32>, // For branches, the predicted target
     pub actual_target: Option<u32>,    // For branches, the actual target (if known)
     pub mispredicted: bool,            // Whether branch prediction was incorrect
+    pub pc: u32,                       // Address the instruction was issued from
 }
 
 impl ReorderBufferEntry {
@@ -171,6 +173,7 @@ impl ReorderBufferEntry {
             predicted_target: None,
             actual_target: None,
             mispredicted: false,
+            pc: 0,
         }
     }
 
@@ -222,6 +225,7 @@ impl ReorderBufferEntry {
         self.predicted_target = None;
         self.actual_target = None;
         self.mispredicted = false;
+        self.pc = 0;
     }
 
     pub fn can_commit(&self) -> bool {
@@ -262,7 +266,7 @@ impl RegisterAliasTable {
 /// Common Data Bus (CDB) for broadcasting results
 #[derive(Debug, Clone)]
 pub struct CommonDataBus {
-    pub data: Option<(usize, u32)>, // (producing_rs, value)
+    pub data: Option<(usize, u32)>, // (producing ROB entry, value)
 }
 
 impl CommonDataBus {
@@ -270,8 +274,8 @@ impl CommonDataBus {
         Self { data: None }
     }
 
-    pub fn broadcast(&mut self, rs_id: usize, value: u32) {
-        self.data = Some((rs_id, value));
+    pub fn broadcast(&mut self, rob_id: usize, value: u32) {
+        self.data = Some((rob_id, value));
     }
 
     pub fn clear(&mut self) {
@@ -308,17 +312,13 @@ impl FunctionalUnit {
         self.cycles_remaining = cycles;
     }
 
+    /// Advance execution by a cycle; true while the finished result waits
+    /// for the common data bus
     pub fn tick(&mut self) -> bool {
         if self.busy && self.cycles_remaining > 0 {
             self.cycles_remaining -= 1;
-            if self.cycles_remaining == 0 {
-                // Execution completed
-                let _rs_id = self.reservation_station;
-                self.reset();
-                return true; // Signal completion
-            }
         }
-        false
+        self.busy && self.cycles_remaining == 0
     }
 
     pub fn reset(&mut self) {
@@ -343,6 +343,9 @@ pub struct TomasuloProcessor {
 
     // Mapping of functional unit types to latencies
     pub latency_map: HashMap<FunctionalUnitType, usize>,
+    // Latencies of the loads and stores at these PCs, in place of the
+    // load/store unit's, as if they hit or missed in a cache
+    pub memory_latencies: HashMap<u32, usize>,
 
     // Current architectural state
     pub registers: Registers,
@@ -354,6 +357,8 @@ pub struct TomasuloProcessor {
     pub instructions_committed: usize,
     pub cycles: usize,
     pub branch_mispredictions: usize,
+    // PCs of the instructions committed in the last cycle, oldest first
+    pub committed_pcs: Vec<u32>,
 }
 
 impl TomasuloProcessor {
@@ -399,6 +404,7 @@ impl TomasuloProcessor {
             num_reservation_stations: num_rs,
             rob_size,
             latency_map,
+            memory_latencies: HashMap::new(),
             registers,
             memory,
             instructions_issued: 0,
@@ -406,21 +412,31 @@ impl TomasuloProcessor {
             instructions_committed: 0,
             cycles: 0,
             branch_mispredictions: 0,
+            committed_pcs: Vec::new(),
+        }
+    }
+
+    /// Host memory held by the processor's copy of the memory image
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            memory: self.memory.data.len(),
+            ..MemoryUsage::default()
         }
     }
 
     /// Main processor cycle
     pub fn tick(&mut self) {
         self.cycles += 1;
+        self.committed_pcs.clear();
 
-        // 1. Process common data bus broadcasts from previous cycle
-        self.process_cdb();
+        // 1. Commit instructions whose results were written in earlier cycles
+        self.commit_instructions();
 
-        // 2. Execute ready instructions
+        // 2. Advance executing instructions and start ready ones
         self.execute_instructions();
 
-        // 3. Commit completed instructions
-        self.commit_instructions();
+        // 3. Write the result on the common data bus to waiting stations
+        self.process_cdb();
 
         // 4. Clear CDB for next cycle
         self.common_data_bus.clear();
@@ -428,15 +444,15 @@ impl TomasuloProcessor {
 
     /// Process broadcasts on the common data bus
     fn process_cdb(&mut self) {
-        if let Some((producing_rs, value)) = self.common_data_bus.data {
+        if let Some((producing_rob, value)) = self.common_data_bus.data {
             // Update any reservation stations waiting on this result
             for rs in &mut self.reservation_stations {
                 if rs.busy {
-                    if rs.qj == Some(producing_rs) {
+                    if rs.qj == Some(producing_rob) {
                         rs.vj = Some(value);
                         rs.qj = None;
                     }
-                    if rs.qk == Some(producing_rs) {
+                    if rs.qk == Some(producing_rob) {
                         rs.vk = Some(value);
                         rs.qk = None;
                     }
@@ -444,140 +460,92 @@ impl TomasuloProcessor {
             }
 
             // Update ROB entry with the result
-            let producing_rob = self.get_rob_entry_for_rs(producing_rs);
-            if let Some(rob_idx) = producing_rob {
-                if rob_idx < self.reorder_buffer.len() {
-                    let entry = &mut self.reorder_buffer[rob_idx];
-                    entry.value = Some(value);
-                    entry.status = InstructionStatus::Completed;
-                }
+            if let Some(position) = self.rob_position(producing_rob) {
+                let entry = &mut self.reorder_buffer[position];
+                entry.value = Some(value);
+                entry.status = InstructionStatus::Completed;
             }
         }
     }
 
     /// Execute instructions in functional units and broadcast results
     fn execute_instructions(&mut self) {
-        // Update existing executions
-        for fu in &mut self.functional_units {
-            if fu.busy {
-                let completed = fu.tick();
-                if completed {
-                    // Execution finished, get the reservation station
-                    if let Some(rs_id) = fu.reservation_station {
-                        if rs_id < self.reservation_stations.len() {
-                            // Extract instruction before computing result to avoid double borrow
-                            let instruction_clone =
-                                self.reservation_stations[rs_id].instruction.clone();
-
-                            // Update status
-                            self.reservation_stations[rs_id].status = InstructionStatus::Completed;
-
-                            // Compute result based on instruction type
-                            if let Some(instr) = instruction_clone {
-                                // Compute result without borrowing 'self' twice
-                                let result = match instr {
-                                    Instruction::Add { .. } => {
-                                        let vj = self.reservation_stations[rs_id].vj.unwrap_or(0);
-                                        let vk = self.reservation_stations[rs_id].vk.unwrap_or(0);
-                                        vj.wrapping_add(vk)
-                                    },
-                                    Instruction::Sub { .. } => {
-                                        let vj = self.reservation_stations[rs_id].vj.unwrap_or(0);
-                                        let vk = self.reservation_stations[rs_id].vk.unwrap_or(0);
-                                        vj.wrapping_sub(vk)
-                                    },
-                                    Instruction::And { .. } => {
-                                        let vj = self.reservation_stations[rs_id].vj.unwrap_or(0);
-                                        let vk = self.reservation_stations[rs_id].vk.unwrap_or(0);
-                                        vj & vk
-                                    },
-                                    Instruction::Or { .. } => {
-                                        let vj = self.reservation_stations[rs_id].vj.unwrap_or(0);
-                                        let vk = self.reservation_stations[rs_id].vk.unwrap_or(0);
-                                        vj | vk
-                                    },
-                                    Instruction::Slt { .. } => {
-                                        let vj =
-                                            self.reservation_stations[rs_id].vj.unwrap_or(0) as i32;
-                                        let vk =
-                                            self.reservation_stations[rs_id].vk.unwrap_or(0) as i32;
-                                        (vj < vk) as u32
-                                    },
-                                    // Add more instruction types as needed
-                                    _ => 0, // Default
-                                };
-
-                                // Broadcast result on CDB
-                                self.common_data_bus.broadcast(rs_id, result);
-
-                                self.instructions_executed += 1;
-                            }
-                        }
-                    }
-                    fu.reset();
+        // Finished units compete for the bus; the others hold their result
+        // until a later cycle
+        for fu_idx in 0..self.functional_units.len() {
+            if !self.functional_units[fu_idx].tick() || self.common_data_bus.data.is_some() {
+                continue;
+            }
+            let rs_id = match self.functional_units[fu_idx].reservation_station {
+                Some(rs_id) => rs_id,
+                None => continue,
+            };
+            let rs = self.reservation_stations[rs_id].clone();
+            if let (Some(instr), Some(rob_id)) = (rs.instruction, rs.dest) {
+                let (result, address) = execute_operation(
+                    &instr,
+                    rs.vj.unwrap_or(0),
+                    rs.vk.unwrap_or(0),
+                    &mut self.memory,
+                );
+                if let Some(position) = self.rob_position(rob_id) {
+                    self.reorder_buffer[position].address = address;
                 }
+                self.common_data_bus.broadcast(rob_id, result);
+                self.instructions_executed += 1;
             }
+
+            // The station is free once its result is on the bus
+            self.reservation_stations[rs_id].reset();
+            self.functional_units[fu_idx].reset();
         }
 
         // Start new executions
         for rs_idx in 0..self.reservation_stations.len() {
             let rs = &self.reservation_stations[rs_idx];
-            if rs.is_ready() {
-                // Find an available functional unit of the right type
-                if let Some(ref instr) = rs.instruction {
-                    let fu_type = self.get_functional_unit_type(instr);
-
-                    // Find an available FU of the right type
-                    for fu in &mut self.functional_units {
-                        if fu.is_available() && fu.unit_type == fu_type {
-                            // Start execution
-                            let latency = self.latency_map[&fu_type];
-                            fu.start_execution(rs_idx, latency);
-
-                            // Update RS status
-                            let rs = &mut self.reservation_stations[rs_idx];
-                            rs.status = InstructionStatus::Executing;
-                            rs.cycles_remaining = latency;
-
-                            break;
-                        }
-                    }
-                }
+            if !rs.is_ready() {
+                continue;
+            }
+            let (instr, rob_id) = match (&rs.instruction, rs.dest) {
+                (Some(instr), Some(rob_id)) => (instr.clone(), rob_id),
+                _ => continue,
+            };
+            let position = match self.rob_position(rob_id) {
+                Some(position) => position,
+                None => continue,
+            };
+            // Loads read memory when they finish, so they wait for older
+            // stores to commit
+            if instr.is_load()
+                && self
+                    .reorder_buffer
+                    .iter()
+                    .take(position)
+                    .any(|entry| entry.instruction.as_ref().map_or(false, |i| i.is_store()))
+            {
+                continue;
             }
-        }
-    }
 
-    /// Compute result of an instruction execution
-    #[allow(dead_code)]
-    fn compute_result(&mut self, rs: &ReservationStation, instr: &Instruction) -> u32 {
-        match instr {
-            Instruction::Add { .. } => {
-                let vj = rs.vj.unwrap_or(0);
-                let vk = rs.vk.unwrap_or(0);
-                vj.wrapping_add(vk)
-            },
-            Instruction::Sub { .. } => {
-                let vj = rs.vj.unwrap_or(0);
-                let vk = rs.vk.unwrap_or(0);
-                vj.wrapping_sub(vk)
-            },
-            Instruction::And { .. } => {
-                let vj = rs.vj.unwrap_or(0);
-                let vk = rs.vk.unwrap_or(0);
-                vj & vk
-            },
-            Instruction::Or { .. } => {
-                let vj = rs.vj.unwrap_or(0);
-                let vk = rs.vk.unwrap_or(0);
-                vj | vk
-            },
-            Instruction::Slt { .. } => {
-                let vj = rs.vj.unwrap_or(0) as i32;
-                let vk = rs.vk.unwrap_or(0) as i32;
-                (vj < vk) as u32
-            },
-            // Add more instruction types as needed
-            _ => 0, // Default
+            let fu_type = self.get_functional_unit_type(&instr);
+            let mut latency = self.latency_map[&fu_type];
+            if instr.is_memory_access() {
+                let pc = self.reorder_buffer[position].pc;
+                latency = self.memory_latencies.get(&pc).copied().unwrap_or(latency);
+            }
+
+            // Find an available FU of the right type
+            if let Some(fu) = self
+                .functional_units
+                .iter_mut()
+                .find(|fu| fu.is_available() && fu.unit_type == fu_type)
+            {
+                fu.start_execution(rs_idx, latency);
+
+                // Update RS status
+                let rs = &mut self.reservation_stations[rs_idx];
+                rs.status = InstructionStatus::Executing;
+                rs.cycles_remaining = latency;
+            }
         }
     }
 
@@ -598,6 +566,8 @@ impl TomasuloProcessor {
                 // Check for branch misprediction
                 if entry.mispredicted {
                     self.branch_mispredictions += 1;
+                    entry.reset();
+                    self.reorder_buffer.push_back(entry);
                     self.handle_branch_misprediction();
 
                     // We need to stop committing after a misprediction
@@ -617,9 +587,15 @@ impl TomasuloProcessor {
                         }
                     }
                 }
+                if let (Some(instr), Some(address), Some(value)) =
+                    (&entry.instruction, entry.address, entry.value)
+                {
+                    store(instr, &mut self.memory, address, value);
+                }
 
                 entry.status = InstructionStatus::Committed;
                 self.instructions_committed += 1;
+                self.committed_pcs.push(entry.pc);
                 committed += 1;
 
                 // Add the now-free entry back to the end of the ROB
@@ -645,9 +621,8 @@ impl TomasuloProcessor {
         }
 
         // Clear ROB entries after the mispredicted branch
-        while self.reorder_buffer.len() > 1 {
-            // Keep the entry at the head
-            self.reorder_buffer.pop_back();
+        for entry in &mut self.reorder_buffer {
+            entry.reset();
         }
 
         // Clear register alias table
@@ -666,69 +641,36 @@ impl TomasuloProcessor {
         }
 
         // Find a free reservation station
-        let rs_idx = self.find_free_reservation_station();
-        if rs_idx.is_none() {
-            return false; // No free reservation station
-        }
-        let rs_idx = rs_idx.unwrap();
+        let rs_idx = match self.find_free_reservation_station() {
+            Some(rs_idx) => rs_idx,
+            None => return false, // No free reservation station
+        };
 
         // Find a free ROB entry
-        let rob_idx = self.find_free_rob_entry();
-        if rob_idx.is_none() {
-            return false; // No free ROB entry
-        }
-        let rob_idx = rob_idx.unwrap();
+        let rob_idx = match self.find_free_rob_entry() {
+            Some(rob_idx) => rob_idx,
+            None => return false, // No free ROB entry
+        };
+        let rob_id = self.reorder_buffer[rob_idx].id;
 
         // Get source and destination registers
         let src_regs = instruction.get_source_registers();
         let dest_reg = instruction.get_destination_register();
 
-        // Get values or dependencies for source registers
-        let mut vj = None;
-        let mut vk = None;
-        let mut qj = None;
-        let mut qk = None;
-
-        // Handle source operands
-        if !src_regs.is_empty() {
-            // First source register
-            let rs1 = src_regs[0];
-            if let Some(rob_entry) = self.register_alias_table.get_mapping(rs1) {
-                // Register is mapped to an ROB entry
-                if self.reorder_buffer[rob_entry].status == InstructionStatus::Completed {
-                    // Value is available
-                    vj = self.reorder_buffer[rob_entry].value;
-                } else {
-                    // Value is not available, set dependency
-                    qj = Some(rob_entry);
-                }
-            } else {
-                // Register value is available in register file
-                vj = Some(self.registers.read(rs1));
-            }
-
-            // Second source register (if any)
-            if src_regs.len() > 1 {
-                let rs2 = src_regs[1];
-                if let Some(rob_entry) = self.register_alias_table.get_mapping(rs2) {
-                    // Register is mapped to an ROB entry
-                    if self.reorder_buffer[rob_entry].status == InstructionStatus::Completed {
-                        // Value is available
-                        vk = self.reorder_buffer[rob_entry].value;
-                    } else {
-                        // Value is not available, set dependency
-                        qk = Some(rob_entry);
-                    }
-                } else {
-                    // Register value is available in register file
-                    vk = Some(self.registers.read(rs2));
-                }
-            }
-        }
+        // Get values or dependencies for source registers: the register
+        // file, a completed entry's result, or the entry still producing it
+        let (vj, qj) = match src_regs.first() {
+            Some(&reg) => self.read_operand(reg),
+            None => (None, None),
+        };
+        let (vk, qk) = match src_regs.get(1) {
+            Some(&reg) => self.read_operand(reg),
+            None => (None, None),
+        };
 
         // Predict branch target if this is a branch/jump
         let predicted_target = if instruction.is_branch_or_jump() {
-            instruction.get_immediate_target().map(|t| t + pc)
+            instruction.calculate_branch_target(pc)
         } else {
             None
         };
@@ -740,22 +682,38 @@ impl TomasuloProcessor {
             vk,
             qj,
             qk,
-            Some(rob_idx),
+            Some(rob_id),
             0,
         );
 
         // Add to reorder buffer
         self.reorder_buffer[rob_idx].issue(instruction, dest_reg, predicted_target);
+        self.reorder_buffer[rob_idx].pc = pc;
 
         // Update register alias table if instruction has a destination register
         if let Some(dr) = dest_reg {
-            self.register_alias_table.set_mapping(dr, rob_idx);
+            self.register_alias_table.set_mapping(dr, rob_id);
         }
 
         self.instructions_issued += 1;
         true
     }
 
+    // Value of a source register, or the ROB entry that will produce it
+    fn read_operand(&self, reg: u32) -> (Option<u32>, Option<usize>) {
+        match self.register_alias_table.get_mapping(reg) {
+            Some(rob_id) => match self.rob_position(rob_id) {
+                Some(position)
+                    if self.reorder_buffer[position].status == InstructionStatus::Completed =>
+                {
+                    (self.reorder_buffer[position].value, None)
+                },
+                _ => (None, Some(rob_id)),
+            },
+            None => (Some(self.registers.read(reg)), None),
+        }
+    }
+
     /// Find a free reservation station
     fn find_free_reservation_station(&self) -> Option<usize> {
         for (i, rs) in self.reservation_stations.iter().enumerate() {
@@ -776,24 +734,25 @@ impl TomasuloProcessor {
         None
     }
 
-    /// Get the ROB entry associated with a reservation station
-    fn get_rob_entry_for_rs(&self, rs_id: usize) -> Option<usize> {
-        if rs_id < self.reservation_stations.len() {
-            self.reservation_stations[rs_id].dest
-        } else {
-            None
-        }
+    /// Position in the ROB of a busy entry. Commits rotate the buffer, so
+    /// stations and the RAT name entries by id rather than position
+    fn rob_position(&self, rob_id: usize) -> Option<usize> {
+        self.reorder_buffer
+            .iter()
+            .position(|entry| entry.busy && entry.id == rob_id)
     }
 
     /// Get the appropriate functional unit type for an instruction
     fn get_functional_unit_type(&self, instruction: &Instruction) -> FunctionalUnitType {
         match *instruction {
             Instruction::Add { .. }
+            | Instruction::Addu { .. }
             | Instruction::Sub { .. }
             | Instruction::And { .. }
             | Instruction::Or { .. }
             | Instruction::Xor { .. }
             | Instruction::Slt { .. }
+            | Instruction::Sltu { .. }
             | Instruction::Addi { .. }
             | Instruction::Addiu { .. } => FunctionalUnitType::IntegerALU,
 
@@ -820,6 +779,60 @@ impl TomasuloProcessor {
         }
     }
 
+    /// Run a scripted scenario: issue its instructions in order, one
+    /// attempt per cycle, and record what the processor holds and commits
+    /// each cycle until all of them have committed
+    pub fn run_scenario(&mut self, scenario: &Scenario) -> ScenarioTrace {
+        let pc_of = |index: usize| SCENARIO_BASE + 4 * index as u32;
+        for (&index, &latency) in &scenario.memory_latencies {
+            self.memory_latencies.insert(pc_of(index), latency);
+        }
+
+        let mut trace = ScenarioTrace {
+            cycles: Vec::new(),
+            finished: false,
+        };
+        let mut next = 0;
+        let mut committed = 0;
+        while trace.cycles.len() < scenario.max_cycles {
+            let mut issued = None;
+            if let Some(instruction) = scenario.instructions.get(next) {
+                if self.issue(instruction.clone(), pc_of(next)) {
+                    issued = Some(next);
+                    next += 1;
+                }
+            }
+            self.tick();
+
+            let commits: Vec<usize> = self
+                .committed_pcs
+                .iter()
+                .map(|pc| ((pc - SCENARIO_BASE) / 4) as usize)
+                .collect();
+            committed += commits.len();
+            trace.cycles.push(ScenarioCycle {
+                cycle: trace.cycles.len() + 1,
+                issued,
+                reservation_stations: self
+                    .reservation_stations
+                    .iter()
+                    .filter(|rs| rs.busy)
+                    .count(),
+                reorder_buffer: self
+                    .reorder_buffer
+                    .iter()
+                    .filter(|entry| entry.busy)
+                    .count(),
+                committed: commits,
+            });
+            if committed == scenario.instructions.len() {
+                trace.finished = true;
+                break;
+            }
+        }
+        trace
+    }
+
     /// Get the processor's performance statistics
     pub fn get_stats(&self) -> TomasuloStats {
         TomasuloStats {
@@ -943,3 +956,153 @@ impl fmt::Display for TomasuloStats {
         )
     }
 }
+
+// Result and effective address of an instruction whose first two source
+// registers hold vj and vk, from its functional semantics. Stores produce
+// the value to write, which reaches memory when they commit
+fn execute_operation(
+    instruction: &Instruction,
+    vj: u32,
+    vk: u32,
+    memory: &mut Memory,
+) -> (u32, Option<u32>) {
+    let mut registers = Registers::new();
+    let sources = instruction.get_source_registers();
+    if let Some(&reg) = sources.get(1) {
+        registers.write(reg, vk);
+    }
+    if let Some(&reg) = sources.first() {
+        registers.write(reg, vj);
+    }
+    let address = if instruction.is_memory_access() {
+        Some(instruction.get_address(&registers, 0))
+    } else {
+        None
+    };
+
+    if instruction.is_store() {
+        (vj, address)
+    } else if instruction.generates_result() {
+        instruction.execute(&mut registers, memory);
+        let value = instruction
+            .get_destination_register()
+            .map_or(0, |reg| registers.read(reg));
+        (value, address)
+    } else {
+        (0, address)
+    }
+}
+
+// Write a committed store to memory
+fn store(instruction: &Instruction, memory: &mut Memory, address: u32, value: u32) {
+    let address = address as usize;
+    let _ = match instruction {
+        Instruction::Sw { .. } => memory.write_word(address, value),
+        Instruction::Sh { .. } => memory.write_halfword(address, value as u16),
+        Instruction::Sb { .. } => memory.write_byte(address, value as u8),
+        _ => Ok(()),
+    };
+}
+
+/// Address of a scenario's first instruction; the rest follow a word apart
+pub const SCENARIO_BASE: u32 = 0x0040_0000;
+
+/// A fixed instruction sequence for checking the processor cycle by cycle
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub instructions: Vec<Instruction>,
+    /// Latencies of the loads and stores at these indices, as if they hit
+    /// or missed in a cache
+    pub memory_latencies: HashMap<usize, usize>,
+    /// Cycles to run before giving up on the sequence committing
+    pub max_cycles: usize,
+}
+
+impl Scenario {
+    pub fn new(instructions: Vec<Instruction>) -> Self {
+        Self {
+            instructions,
+            memory_latencies: HashMap::new(),
+            max_cycles: 1000,
+        }
+    }
+
+    /// Give the load or store at `index` a latency of `cycles`
+    pub fn memory_latency(mut self, index: usize, cycles: usize) -> Self {
+        self.memory_latencies.insert(index, cycles);
+        self
+    }
+}
+
+/// What happened in one cycle of a scenario, with the occupancy at its end
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScenarioCycle {
+    pub cycle: usize,
+    /// Index of the instruction issued this cycle
+    pub issued: Option<usize>,
+    /// Busy reservation stations
+    pub reservation_stations: usize,
+    /// Busy reorder buffer entries
+    pub reorder_buffer: usize,
+    /// Indices of the instructions committed this cycle, in commit order
+    pub committed: Vec<usize>,
+}
+
+/// The cycles of a scenario run
+#[derive(Clone, Debug)]
+pub struct ScenarioTrace {
+    pub cycles: Vec<ScenarioCycle>,
+    /// Whether every instruction committed within the cycle limit
+    pub finished: bool,
+}
+
+impl ScenarioTrace {
+    /// Indices of the instructions in the order they committed
+    pub fn commit_order(&self) -> Vec<usize> {
+        self.cycles
+            .iter()
+            .flat_map(|cycle| cycle.committed.iter().copied())
+            .collect()
+    }
+
+    /// Cycle an instruction committed in
+    pub fn commit_cycle(&self, index: usize) -> Option<usize> {
+        self.cycles
+            .iter()
+            .find(|cycle| cycle.committed.contains(&index))
+            .map(|cycle| cycle.cycle)
+    }
+
+    /// Busy reservation stations at the end of each cycle
+    pub fn station_occupancy(&self) -> Vec<usize> {
+        self.cycles
+            .iter()
+            .map(|cycle| cycle.reservation_stations)
+            .collect()
+    }
+
+    /// Busy reorder buffer entries at the end of each cycle
+    pub fn rob_occupancy(&self) -> Vec<usize> {
+        self.cycles
+            .iter()
+            .map(|cycle| cycle.reorder_buffer)
+            .collect()
+    }
+
+    /// One line per cycle, for reading a scenario that went wrong
+    pub fn render(&self) -> String {
+        let mut text = String::from("cycle issued rs rob committed\n");
+        for cycle in &self.cycles {
+            let committed: Vec<String> = cycle.committed.iter().map(|i| i.to_string()).collect();
+            text.push_str(&format!(
+                "{:>5} {:>6} {:>2} {:>3} {}\n",
+                cycle.cycle,
+                cycle.issued.map_or("-".to_string(), |i| i.to_string()),
+                cycle.reservation_stations,
+                cycle.reorder_buffer,
+                committed.join(",")
+            ));
+        }
+        text
+    }
+}
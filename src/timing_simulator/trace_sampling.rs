@@ -0,0 +1,182 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// trace_sampling.rs
+//
+// This file contains the sampling controls of the instruction trace. A
+// trace of every instruction of a long run grows to gigabytes, so the
+// timeline can keep only memory instructions or branches, only every Nth
+// instruction that passes that filter, or only the most recent ones in a
+// ring buffer that is written out when the run stops on an error.
+
+use super::stats::InstructionTiming;
+use crate::functional_simulator::simulator::decode_instruction;
+use serde::Serialize;
+use std::fmt;
+
+/// Which instructions a trace records
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceFilter {
+    All,
+    /// Loads and stores
+    Memory,
+    /// Branches and jumps
+    Branches,
+}
+
+impl TraceFilter {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.to_ascii_lowercase().as_str() {
+            "all" => Ok(TraceFilter::All),
+            "memory" | "mem" => Ok(TraceFilter::Memory),
+            "branches" | "branch" => Ok(TraceFilter::Branches),
+            _ => Err(format!(
+                "unknown trace filter '{}'; expected all, memory or branches",
+                text
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TraceFilter::All => "all",
+            TraceFilter::Memory => "memory",
+            TraceFilter::Branches => "branches",
+        }
+    }
+
+    pub fn matches(self, word: u32) -> bool {
+        match self {
+            TraceFilter::All => true,
+            TraceFilter::Memory => decode_instruction(word).is_memory_access(),
+            TraceFilter::Branches => decode_instruction(word).is_branch_or_jump(),
+        }
+    }
+}
+
+/// How a trace is thinned out
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct TraceSampling {
+    /// Keep every Nth instruction that passes the filter, starting with the first
+    pub every: usize,
+    pub filter: TraceFilter,
+    /// Keep only the last this many recorded instructions
+    pub last: Option<usize>,
+}
+
+impl Default for TraceSampling {
+    fn default() -> Self {
+        Self {
+            every: 1,
+            filter: TraceFilter::All,
+            last: None,
+        }
+    }
+}
+
+impl TraceSampling {
+    /// Whether any instruction is left out
+    pub fn is_sampled(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// Check the settings make sense
+    pub fn validate(&self) -> Result<(), String> {
+        if self.every == 0 {
+            return Err("trace sampling interval must be at least 1".to_string());
+        }
+        if self.last == Some(0) {
+            return Err("trace ring buffer must hold at least 1 instruction".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TraceSampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.filter {
+            TraceFilter::All => "instructions",
+            TraceFilter::Memory => "memory instructions",
+            TraceFilter::Branches => "branches",
+        };
+        match self.every {
+            1 => write!(f, "all {}", kind)?,
+            every => write!(f, "1 in {} {}", every, kind)?,
+        }
+        if let Some(last) = self.last {
+            write!(f, ", last {} kept", last)?;
+        }
+        Ok(())
+    }
+}
+
+/// Applies `TraceSampling` as instructions are added to a timeline
+#[derive(Clone, Debug)]
+pub struct TraceSampler {
+    pub sampling: TraceSampling,
+    /// Instructions that passed the filter so far
+    pub matched: usize,
+    /// Instructions dropped from the front of the ring buffer
+    pub dropped: usize,
+}
+
+impl TraceSampler {
+    pub fn new(sampling: TraceSampling) -> Self {
+        Self {
+            sampling,
+            matched: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Add an instruction to the timeline if the sampling keeps it. The
+    /// ring buffer is trimmed in batches and may briefly hold up to twice
+    /// its size; `finish` trims it exactly
+    pub fn push(&mut self, timeline: &mut Vec<InstructionTiming>, timing: InstructionTiming) {
+        if !self.sampling.filter.matches(timing.word) {
+            return;
+        }
+        self.matched += 1;
+        if (self.matched - 1) % self.sampling.every != 0 {
+            return;
+        }
+        if let Some(last) = self.sampling.last {
+            if timeline.len() >= 2 * last {
+                let excess = timeline.len() - last + 1;
+                timeline.drain(..excess);
+                self.dropped += excess;
+            }
+        }
+        timeline.push(timing);
+    }
+
+    /// Trim the ring buffer to its size at the end of a run
+    pub fn finish(&mut self, timeline: &mut Vec<InstructionTiming>) {
+        if let Some(last) = self.sampling.last {
+            if timeline.len() > last {
+                let excess = timeline.len() - last;
+                timeline.drain(..excess);
+                self.dropped += excess;
+            }
+        }
+    }
+}
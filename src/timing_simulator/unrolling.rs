@@ -0,0 +1,789 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// unrolling.rs
+//
+// This file contains the loop unrolling and software pipelining report. For
+// the hottest small loops of a timing run it estimates, with the operand
+// and result timing of the configured pipeline, the cycles an iteration
+// takes as written, unrolled by a factor with its temporaries renamed, and
+// software pipelined, and lists the transformed schedules.
+
+use super::loop_memory::MemoryTrace;
+use super::pipeline::Pipeline;
+use crate::functional_simulator::instructions::Instruction;
+use crate::functional_simulator::simulator::{decode_instruction, instruction_to_string};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+/// Longest loop body, in instructions, the report treats as small
+pub const MAX_LOOP_INSTRUCTIONS: usize = 16;
+
+// Registers never handed out by renaming: $zero, $at, the kernel registers,
+// and the global, stack and frame pointers and return address
+const RESERVED_REGISTERS: [u32; 8] = [0, 1, 26, 27, 28, 29, 30, 31];
+
+// Passes through a loop body before its timing is taken as steady
+const WARMUP_PASSES: usize = 4;
+
+/// Timing of the configured pipeline the estimates are made with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CostModel {
+    /// Cycles between the fetches of two independent instructions
+    pub interval: usize,
+    /// Cycles after an ALU instruction's fetch that a dependent one can follow
+    pub alu_delay: usize,
+    /// Cycles after a load's fetch that a dependent instruction can follow
+    pub load_delay: usize,
+    /// Cycles a taken loop branch costs once the loop runs steadily
+    pub branch_cost: usize,
+}
+
+impl CostModel {
+    pub fn from_pipeline(pipeline: &Pipeline) -> Self {
+        let interval = pipeline.issue_interval();
+        Self {
+            interval,
+            alu_delay: pipeline.result_delay(false).max(interval),
+            load_delay: pipeline.result_delay(true).max(interval),
+            branch_cost: pipeline.loop_branch_cost(),
+        }
+    }
+
+    fn delay(&self, instruction: &Instruction) -> usize {
+        if instruction.is_load() {
+            self.load_delay
+        } else {
+            self.alu_delay
+        }
+    }
+
+    // Fetch cycle of each instruction of one pass through `words`, in order,
+    // the first no earlier than `start`, waiting for the registers in `ready`
+    fn issue(&self, words: &[u32], start: usize, ready: &mut HashMap<u32, usize>) -> Vec<usize> {
+        let mut cycle = start;
+        let mut cycles = Vec::with_capacity(words.len());
+        for (index, &word) in words.iter().enumerate() {
+            let instruction = decode_instruction(word);
+            if index > 0 {
+                cycle += self.interval;
+            }
+            for reg in instruction.get_source_registers() {
+                if let Some(&at) = ready.get(&reg) {
+                    cycle = cycle.max(at);
+                }
+            }
+            if let Some(reg) = instruction.get_destination_register() {
+                if reg != 0 {
+                    ready.insert(reg, cycle + self.delay(&instruction));
+                }
+            }
+            cycles.push(cycle);
+        }
+        cycles
+    }
+
+    /// Cycles one pass through a loop body ending in its branch takes once
+    /// the loop runs steadily, with the fetch cycle of each instruction of
+    /// that pass counted from its start
+    pub fn steady_state(&self, words: &[u32]) -> (usize, Vec<usize>) {
+        let mut ready = HashMap::new();
+        let mut start = 0;
+        let mut period = 0;
+        let mut cycles = Vec::new();
+        for _ in 0..WARMUP_PASSES {
+            cycles = self.issue(words, start, &mut ready);
+            let next = cycles
+                .last()
+                .map_or(start, |&last| last + self.interval + self.branch_cost);
+            period = next - start;
+            for cycle in cycles.iter_mut() {
+                *cycle -= start;
+            }
+            start = next;
+        }
+        (period, cycles)
+    }
+}
+
+/// An instruction of a suggested schedule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledInstruction {
+    /// Fetch cycle, counted from the start of the loop body
+    pub cycle: usize,
+    pub word: u32,
+    /// Copy of the original body it came from when unrolled, or its stage
+    /// when software pipelined; `None` for loop overhead
+    pub iteration: Option<usize>,
+}
+
+impl ScheduledInstruction {
+    pub fn text(&self) -> String {
+        instruction_to_string(&decode_instruction(self.word), self.word)
+    }
+}
+
+/// A loop body unrolled by a factor
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnrolledLoop {
+    pub factor: usize,
+    /// Cycles per pass, which runs `factor` of the original iterations
+    pub cycles: usize,
+    /// Whether the copies are interleaved instruction by instruction rather
+    /// than laid out one after another
+    pub interleaved: bool,
+    /// Each renamed temporary and the registers that replace it in the
+    /// copies before the last
+    pub renamed: Vec<(u32, Vec<u32>)>,
+    pub schedule: Vec<ScheduledInstruction>,
+}
+
+/// A loop body software pipelined into a kernel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PipelinedLoop {
+    /// Cycles between the starts of successive iterations
+    pub initiation_interval: usize,
+    /// Cycles per iteration, the interval and the loop branch
+    pub cycles: usize,
+    pub stages: usize,
+    /// Registers needed beyond the original ones for values that live
+    /// longer than the interval
+    pub extra_registers: usize,
+    /// One pass of the kernel; the loop branch has no stage
+    pub kernel: Vec<ScheduledInstruction>,
+}
+
+/// Estimates for one small loop
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoopEstimate {
+    pub start: u32,
+    pub end: u32,
+    pub iterations: usize,
+    /// Cycles per iteration as written
+    pub cycles: usize,
+    pub schedule: Vec<ScheduledInstruction>,
+    /// The unrolled loop, or why the loop cannot be unrolled
+    pub unrolled: Result<UnrolledLoop, String>,
+    pub pipelined: Option<PipelinedLoop>,
+}
+
+impl LoopEstimate {
+    /// Speedup of the unrolled loop over the loop as written
+    pub fn unrolled_speedup(&self) -> Option<f64> {
+        self.unrolled.as_ref().ok().map(|unrolled| {
+            self.cycles as f64 * unrolled.factor as f64 / unrolled.cycles.max(1) as f64
+        })
+    }
+
+    /// Speedup of the software pipelined loop over the loop as written
+    pub fn pipelined_speedup(&self) -> Option<f64> {
+        self.pipelined
+            .as_ref()
+            .map(|pipelined| self.cycles as f64 / pipelined.cycles.max(1) as f64)
+    }
+}
+
+/// Unrolling and software pipelining estimates for the hottest small loops
+/// of a run
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnrollReport {
+    pub model: CostModel,
+    pub factor: usize,
+    pub loops: Vec<LoopEstimate>,
+}
+
+impl UnrollReport {
+    /// Estimate the `count` small loops that executed the most instructions,
+    /// unrolled by `factor`, on `pipeline`
+    pub fn new(trace: &MemoryTrace, pipeline: &Pipeline, factor: usize, count: usize) -> Self {
+        let model = CostModel::from_pipeline(pipeline);
+        let factor = factor.max(2);
+        let used = used_registers(trace);
+
+        let mut candidates: Vec<(usize, u32, u32, Vec<u32>)> = trace
+            .back_edges
+            .keys()
+            .filter_map(|&(end, start)| {
+                let body = loop_body(trace, start, end)?;
+                let instructions = trace.executions.range(start..=end).map(|(_, n)| n).sum();
+                Some((instructions, start, end, body))
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        candidates.truncate(count);
+
+        let loops = candidates
+            .into_iter()
+            .map(|(_, start, end, body)| {
+                let (cycles, issue) = model.steady_state(&body);
+                LoopEstimate {
+                    start,
+                    end,
+                    iterations: trace.executions.get(&start).copied().unwrap_or(0),
+                    cycles,
+                    schedule: schedule(&body, &issue, |_| None),
+                    unrolled: unroll(&model, &body, start, factor, &used),
+                    pipelined: software_pipeline(&model, &body),
+                }
+            })
+            .collect();
+        Self {
+            model,
+            factor,
+            loops,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let model = &self.model;
+        let mut result = format!(
+            "Loop unrolling and software pipelining (unroll by {})\n",
+            self.factor
+        );
+        let _ = writeln!(
+            result,
+            "  Pipeline: issue every {} cycle(s), ALU results usable {} cycle(s) later, loads {}, taken loop branch costs {}",
+            model.interval, model.alu_delay, model.load_delay, model.branch_cost
+        );
+        if self.loops.is_empty() {
+            result.push_str(&format!(
+                "  No straight-line loops of up to {} instructions found\n",
+                MAX_LOOP_INSTRUCTIONS
+            ));
+        }
+        for (index, estimate) in self.loops.iter().enumerate() {
+            let _ = writeln!(
+                result,
+                "\nLoop {}: 0x{:08X}-0x{:08X}, {} iterations, {} instructions",
+                index + 1,
+                estimate.start,
+                estimate.end,
+                estimate.iterations,
+                estimate.schedule.len()
+            );
+            let _ = writeln!(
+                result,
+                "  As written: {} cycles per iteration",
+                estimate.cycles
+            );
+            render_schedule(&mut result, model, &estimate.schedule, |_| String::new());
+
+            match &estimate.unrolled {
+                Ok(unrolled) => {
+                    let per_iteration = unrolled.cycles as f64 / unrolled.factor as f64;
+                    let _ = writeln!(
+                        result,
+                        "\n  Unrolled by {}: {} cycles per {} iterations ({:.2} per iteration), {:.2}x faster, saving about {} cycles",
+                        unrolled.factor,
+                        unrolled.cycles,
+                        unrolled.factor,
+                        per_iteration,
+                        estimate.unrolled_speedup().unwrap_or(1.0),
+                        saving(estimate, per_iteration)
+                    );
+                    render_schedule(&mut result, model, &unrolled.schedule, |instruction| {
+                        match instruction.iteration {
+                            Some(copy) => format!("iteration {}", copy + 1),
+                            None => "overhead".to_string(),
+                        }
+                    });
+                    for (register, copies) in &unrolled.renamed {
+                        let names: Vec<String> =
+                            copies.iter().map(|reg| format!("${}", reg)).collect();
+                        let _ = writeln!(
+                            result,
+                            "    ${} is renamed to {} in the copies before the last",
+                            register,
+                            names.join(", ")
+                        );
+                    }
+                    if !unrolled.interleaved {
+                        result.push_str(
+                            "    The copies stay in order because a loop-carried register is read by more than one instruction\n",
+                        );
+                    }
+                    let _ = writeln!(
+                        result,
+                        "    Assumes the trip count is a multiple of {} and that iterations do not share memory; otherwise add a remainder loop",
+                        unrolled.factor
+                    );
+                },
+                Err(reason) => {
+                    let _ = writeln!(result, "\n  Not unrolled: {}", reason);
+                },
+            }
+
+            if let Some(pipelined) = &estimate.pipelined {
+                let _ = writeln!(
+                    result,
+                    "\n  Software pipelined: a new iteration every {} cycles in {} stage(s), {} cycles per iteration, {:.2}x faster, saving about {} cycles",
+                    pipelined.initiation_interval,
+                    pipelined.stages,
+                    pipelined.cycles,
+                    estimate.pipelined_speedup().unwrap_or(1.0),
+                    saving(estimate, pipelined.cycles as f64)
+                );
+                render_schedule(&mut result, model, &pipelined.kernel, |instruction| {
+                    match instruction.iteration {
+                        Some(0) => "stage 1 (iteration i)".to_string(),
+                        Some(stage) => format!("stage {} (iteration i-{})", stage + 1, stage),
+                        None => "loop branch".to_string(),
+                    }
+                });
+                if pipelined.stages > 1 {
+                    let _ = writeln!(
+                        result,
+                        "    A prologue and an epilogue of {} iterations fill and drain the kernel; offsets of accesses in stages after an index update need adjusting",
+                        pipelined.stages - 1
+                    );
+                }
+                if pipelined.extra_registers > 0 {
+                    let _ = writeln!(
+                        result,
+                        "    {} extra register(s) hold values that live longer than the interval",
+                        pipelined.extra_registers
+                    );
+                }
+            }
+        }
+        result
+    }
+}
+
+// Cycles the run would save at `per_iteration` cycles an iteration
+fn saving(estimate: &LoopEstimate, per_iteration: f64) -> usize {
+    let saved = (estimate.cycles as f64 - per_iteration) * estimate.iterations as f64;
+    saved.max(0.0).round() as usize
+}
+
+fn render_schedule<F>(
+    result: &mut String,
+    model: &CostModel,
+    schedule: &[ScheduledInstruction],
+    note: F,
+) where
+    F: Fn(&ScheduledInstruction) -> String,
+{
+    let mut next = 0;
+    for instruction in schedule {
+        let stalls = instruction.cycle.saturating_sub(next);
+        if stalls > 0 {
+            let _ = writeln!(result, "           ({} stall cycle(s))", stalls);
+        }
+        let line = format!(
+            "    {:>4}  {:<26} {}",
+            format!("+{}", instruction.cycle),
+            instruction.text(),
+            note(instruction)
+        );
+        let _ = writeln!(result, "{}", line.trim_end());
+        next = instruction.cycle + model.interval;
+    }
+}
+
+fn schedule<F>(words: &[u32], cycles: &[usize], iteration: F) -> Vec<ScheduledInstruction>
+where
+    F: Fn(usize) -> Option<usize>,
+{
+    words
+        .iter()
+        .zip(cycles)
+        .enumerate()
+        .map(|(index, (&word, &cycle))| ScheduledInstruction {
+            cycle,
+            word,
+            iteration: iteration(index),
+        })
+        .collect()
+}
+
+// Every register an executed instruction of the run names
+fn used_registers(trace: &MemoryTrace) -> BTreeSet<u32> {
+    let mut used = BTreeSet::new();
+    for &word in trace.words.values() {
+        let instruction = decode_instruction(word);
+        used.extend(instruction.get_source_registers());
+        used.extend(instruction.get_destination_register());
+    }
+    used
+}
+
+fn is_conditional_branch(word: u32) -> bool {
+    matches!(word >> 26, 0x01 | 0x04..=0x07)
+}
+
+// The words of a loop from `start` to the backward branch at `end`, if it is
+// small, was executed throughout and has no other branch or jump
+fn loop_body(trace: &MemoryTrace, start: u32, end: u32) -> Option<Vec<u32>> {
+    if end < start || start % 4 != 0 || ((end - start) / 4) as usize >= MAX_LOOP_INSTRUCTIONS {
+        return None;
+    }
+    let body = (start..=end)
+        .step_by(4)
+        .map(|pc| trace.words.get(&pc).copied())
+        .collect::<Option<Vec<u32>>>()?;
+    let (&branch, rest) = body.split_last()?;
+    let straight = rest
+        .iter()
+        .all(|&word| !decode_instruction(word).is_branch_or_jump());
+    if is_conditional_branch(branch) && straight {
+        Some(body)
+    } else {
+        None
+    }
+}
+
+// Why the unroller leaves an instruction alone, if it does
+fn unsupported(word: u32) -> Option<&'static str> {
+    let opcode = word >> 26;
+    let function = word & 0x3F;
+    match opcode {
+        0x11 | 0x31 | 0x35 | 0x39 | 0x3D => Some("it uses the floating-point registers"),
+        0x00 if function == 0x0C || function == 0x0D => Some("it makes a system call"),
+        0x00 if (0x10..=0x1B).contains(&function) => Some("it uses the HI and LO registers"),
+        _ => None,
+    }
+}
+
+// Replace the registers of an instruction word named in `map`, leaving any
+// field that is not one of its registers alone
+fn rename(word: u32, instruction: &Instruction, map: &HashMap<u32, u32>) -> u32 {
+    let mut registers = instruction.get_source_registers();
+    registers.extend(instruction.get_destination_register());
+    let opcode = word >> 26;
+    let shifts: &[u32] = if opcode == 0x00 || opcode == 0x1C {
+        &[21, 16, 11]
+    } else {
+        &[21, 16]
+    };
+    let mut renamed = word;
+    for &shift in shifts {
+        let field = (word >> shift) & 0x1F;
+        if let Some(&to) = map.get(&field) {
+            if registers.contains(&field) {
+                renamed = (renamed & !(0x1F << shift)) | (to << shift);
+            }
+        }
+    }
+    renamed
+}
+
+fn with_immediate(word: u32, immediate: i64) -> Result<u32, String> {
+    if immediate < i16::MIN as i64 || immediate > i16::MAX as i64 {
+        return Err(format!(
+            "the immediate {} does not fit in 16 bits",
+            immediate
+        ));
+    }
+    Ok((word & 0xFFFF_0000) | (immediate as i16 as u16 as u32))
+}
+
+// Index registers: stepped by a constant with addi or addiu by exactly one
+// instruction, and otherwise only the base of loads and stores or read by
+// the loop branch. By position, register and step.
+fn induction_registers(body: &[Instruction], words: &[u32]) -> Vec<(usize, u32, i64)> {
+    let mut induction = Vec::new();
+    for (position, instruction) in body.iter().enumerate() {
+        let (reg, step) = match *instruction {
+            Instruction::Addi { rt, rs, imm } | Instruction::Addiu { rt, rs, imm }
+                if rt == rs && rt != 0 =>
+            {
+                (rt, imm as i64)
+            },
+            _ => continue,
+        };
+        let only_base = body
+            .iter()
+            .zip(words)
+            .enumerate()
+            .all(|(index, (other, &word))| {
+                if index == position {
+                    return true;
+                }
+                if other.get_destination_register() == Some(reg) {
+                    return false;
+                }
+                !other.get_source_registers().contains(&reg)
+                    || (other.is_memory_access()
+                        && (word >> 21) & 0x1F == reg
+                        && (word >> 16) & 0x1F != reg)
+            });
+        if only_base {
+            induction.push((position, reg, step));
+        }
+    }
+    induction
+}
+
+// Unroll a loop body by `factor`: the index updates are folded into one per
+// pass and the offsets of the accesses they feed adjusted, and when every
+// loop-carried register is touched by a single instruction the copies are
+// interleaved with the temporaries of all but the last copy renamed to
+// registers the program never uses
+fn unroll(
+    model: &CostModel,
+    body: &[u32],
+    start: u32,
+    factor: usize,
+    used: &BTreeSet<u32>,
+) -> Result<UnrolledLoop, String> {
+    let (&branch, words) = body.split_last().ok_or("the loop is empty")?;
+    if let Some(reason) = words.iter().find_map(|&word| unsupported(word)) {
+        return Err(format!("the loop is left alone because {}", reason));
+    }
+    let decoded: Vec<Instruction> = words.iter().map(|&word| decode_instruction(word)).collect();
+    let induction = induction_registers(&decoded, words);
+    let is_update = |index: usize| induction.iter().any(|&(position, _, _)| position == index);
+
+    // Registers written by the body whose first use in it is the write are
+    // temporaries; the rest carry a value from one iteration to the next
+    let mut temporaries = Vec::new();
+    let mut carried = Vec::new();
+    for (index, instruction) in decoded.iter().enumerate() {
+        let reg = match instruction.get_destination_register() {
+            Some(reg) if reg != 0 && !is_update(index) => reg,
+            _ => continue,
+        };
+        if temporaries.contains(&reg) || carried.contains(&reg) {
+            continue;
+        }
+        let read_first = decoded[..=index]
+            .iter()
+            .any(|earlier| earlier.get_source_registers().contains(&reg));
+        if read_first {
+            carried.push(reg);
+        } else {
+            temporaries.push(reg);
+        }
+    }
+    let interleaved = carried.iter().all(|&reg| {
+        decoded
+            .iter()
+            .filter(|instruction| {
+                instruction.get_source_registers().contains(&reg)
+                    || instruction.get_destination_register() == Some(reg)
+            })
+            .count()
+            == 1
+    });
+
+    let mut renamed = Vec::new();
+    let mut maps = vec![HashMap::new(); factor];
+    if interleaved && !temporaries.is_empty() {
+        let free: Vec<u32> = (0..32)
+            .filter(|reg| !RESERVED_REGISTERS.contains(reg) && !used.contains(reg))
+            .collect();
+        let needed = temporaries.len() * (factor - 1);
+        if free.len() < needed {
+            return Err(format!(
+                "renaming needs {} free registers but the program leaves only {} unused",
+                needed,
+                free.len()
+            ));
+        }
+        let mut free = free.into_iter();
+        for &reg in &temporaries {
+            let copies: Vec<u32> = free.by_ref().take(factor - 1).collect();
+            for (copy, &to) in copies.iter().enumerate() {
+                maps[copy].insert(reg, to);
+            }
+            renamed.push((reg, copies));
+        }
+    }
+
+    // One copy of the instruction at `index`, for original iteration `copy`
+    let copy_of = |index: usize, copy: usize| -> Result<u32, String> {
+        let instruction = &decoded[index];
+        let word = rename(words[index], instruction, &maps[copy]);
+        if !instruction.is_memory_access() {
+            return Ok(word);
+        }
+        let base = (words[index] >> 21) & 0x1F;
+        match induction.iter().find(|&&(_, reg, _)| reg == base) {
+            Some(&(position, _, step)) => {
+                let updates = copy + usize::from(position < index);
+                let offset = (word & 0xFFFF) as u16 as i16 as i64;
+                with_immediate(word, offset + updates as i64 * step)
+            },
+            None => Ok(word),
+        }
+    };
+
+    let mut unrolled: Vec<(u32, Option<usize>)> = Vec::new();
+    let originals: Vec<usize> = (0..words.len())
+        .filter(|&index| !is_update(index))
+        .collect();
+    if interleaved {
+        for &index in &originals {
+            for copy in 0..factor {
+                unrolled.push((copy_of(index, copy)?, Some(copy)));
+            }
+        }
+    } else {
+        for copy in 0..factor {
+            for &index in &originals {
+                unrolled.push((copy_of(index, copy)?, Some(copy)));
+            }
+        }
+    }
+    for &(position, _, step) in &induction {
+        let word = with_immediate(words[position], step * factor as i64)?;
+        unrolled.push((word, None));
+    }
+    let branch_pc = start as i64 + 4 * unrolled.len() as i64;
+    let word = with_immediate(branch, (start as i64 - (branch_pc + 4)) / 4)?;
+    unrolled.push((word, None));
+
+    let unrolled_words: Vec<u32> = unrolled.iter().map(|&(word, _)| word).collect();
+    let (cycles, issue) = model.steady_state(&unrolled_words);
+    Ok(UnrolledLoop {
+        factor,
+        cycles,
+        interleaved,
+        renamed,
+        schedule: schedule(&unrolled_words, &issue, |index| unrolled[index].1),
+    })
+}
+
+// Modulo schedule a loop body: the instructions before the branch are
+// placed in order at their earliest cycle with a free issue slot, the
+// branch closing the kernel, and the interval is raised until the values
+// one iteration passes to the next arrive in time
+fn software_pipeline(model: &CostModel, body: &[u32]) -> Option<PipelinedLoop> {
+    let (&branch, words) = body.split_last()?;
+    if words.is_empty() {
+        return None;
+    }
+    let decoded: Vec<Instruction> = words.iter().map(|&word| decode_instruction(word)).collect();
+    // Delays in issue slots
+    let slots = |cycles: usize| (cycles + model.interval - 1) / model.interval;
+    let delays: Vec<usize> = decoded
+        .iter()
+        .map(|instruction| slots(model.delay(instruction)))
+        .collect();
+
+    // Dependences within an iteration, as earlier and later instruction and
+    // the slots between them, and values passed to the next iteration, as
+    // producer and consumer
+    let mut within = Vec::new();
+    let mut across = Vec::new();
+    for (consumer, instruction) in decoded.iter().enumerate() {
+        for reg in instruction.get_source_registers() {
+            if reg == 0 {
+                continue;
+            }
+            let writes = |index: &usize| decoded[*index].get_destination_register() == Some(reg);
+            if let Some(producer) = (0..consumer).rev().find(writes) {
+                within.push((producer, consumer, delays[producer]));
+            } else if let Some(producer) = (consumer..decoded.len()).rev().find(writes) {
+                across.push((producer, consumer));
+            }
+        }
+        // A register is not overwritten before earlier instructions are done
+        // reading or writing it
+        if let Some(reg) = instruction.get_destination_register() {
+            for (earlier, other) in decoded[..consumer].iter().enumerate() {
+                if other.get_source_registers().contains(&reg)
+                    || other.get_destination_register() == Some(reg)
+                {
+                    within.push((earlier, consumer, 1));
+                }
+            }
+        }
+    }
+
+    let minimum = words.len() + 1;
+    let maximum = minimum + delays.iter().sum::<usize>();
+    for interval in minimum..=maximum {
+        let mut taken = vec![false; interval];
+        taken[interval - 1] = true;
+        let mut times = vec![0; words.len()];
+        let mut placed = true;
+        for consumer in 0..words.len() {
+            let earliest = within
+                .iter()
+                .filter(|&&(_, to, _)| to == consumer)
+                .map(|&(from, _, slots)| times[from] + slots)
+                .max()
+                .unwrap_or(0);
+            match (earliest..earliest + interval).find(|time| !taken[time % interval]) {
+                Some(time) => {
+                    taken[time % interval] = true;
+                    times[consumer] = time;
+                },
+                None => {
+                    placed = false;
+                    break;
+                },
+            }
+        }
+        let in_time = across
+            .iter()
+            .all(|&(from, to)| times[to] + interval >= times[from] + delays[from]);
+        if !placed || !in_time {
+            continue;
+        }
+
+        let stages = times.iter().max().map_or(1, |&last| last / interval + 1);
+        let mut extra_registers = 0;
+        for producer in 0..words.len() {
+            let lifetime = within
+                .iter()
+                .filter(|&&(from, _, _)| from == producer)
+                .map(|&(_, to, _)| times[to] - times[producer])
+                .chain(
+                    across
+                        .iter()
+                        .filter(|&&(from, _)| from == producer)
+                        .map(|&(_, to)| times[to] + interval - times[producer]),
+                )
+                .max()
+                .unwrap_or(0);
+            if lifetime > interval {
+                extra_registers += (lifetime + interval - 1) / interval - 1;
+            }
+        }
+
+        let mut kernel: Vec<ScheduledInstruction> = times
+            .iter()
+            .zip(words)
+            .map(|(&time, &word)| ScheduledInstruction {
+                cycle: (time % interval) * model.interval,
+                word,
+                iteration: Some(time / interval),
+            })
+            .collect();
+        kernel.push(ScheduledInstruction {
+            cycle: (interval - 1) * model.interval,
+            word: branch,
+            iteration: None,
+        });
+        kernel.sort_by_key(|instruction| instruction.cycle);
+        return Some(PipelinedLoop {
+            initiation_interval: interval * model.interval,
+            cycles: interval * model.interval + model.branch_cost,
+            stages,
+            extra_registers,
+            kernel,
+        });
+    }
+    None
+}
@@ -71,10 +71,14 @@ impl PipelineVisualization {
             result.push_str(&format!("=== Pipeline State at Cycle {} ===\n", cycle));
         }
 
-        // Pipeline diagram
-        result.push_str("+-------+-------+-------+-------+-------+\n");
-        result.push_str("| Fetch | Decode| Exec  | Mem   | Write |\n");
-        result.push_str("+-------+-------+-------+-------+-------+\n");
+        // Pipeline diagram, one column per configured stage
+        let border = format!("{}+\n", "+-------".repeat(pipeline.stages.len()));
+        result.push_str(&border);
+        for stage in &pipeline.stages {
+            result.push_str(&format!("| {:<6}", stage.name));
+        }
+        result.push_str("|\n");
+        result.push_str(&border);
 
         // Stage content - Build a single row with content for each stage
         let mut stage_content = String::from("| ");
@@ -109,7 +113,7 @@ impl PipelineVisualization {
         result.push_str(&stage_content);
         result.push_str("\n");
 
-        result.push_str("+-------+-------+-------+-------+-------+\n");
+        result.push_str(&border);
 
         // Hazard information
         if self.show_hazards {
@@ -130,7 +134,12 @@ impl PipelineVisualization {
 
         // Header (only for first cycle)
         if cycle == 1 {
-            result.push_str("Cycle,Fetch,Decode,Execute,Memory,Writeback,Hazards\n");
+            let names: Vec<&str> = pipeline
+                .stages
+                .iter()
+                .map(|stage| stage.name.as_str())
+                .collect();
+            result.push_str(&format!("Cycle,{},Hazards\n", names.join(",")));
         }
 
         // Pipeline state
@@ -165,7 +174,8 @@ impl PipelineVisualization {
         for (i, stage) in pipeline.stages.iter().enumerate() {
             result.push_str("    {\n");
 
-            // Stage type
+            // Stage name and type
+            result.push_str(&format!("      \"name\": \"{}\",\n", stage.name));
             result.push_str(&format!("      \"type\": \"{:?}\",\n", stage.stage_type));
 
             // Stage status
@@ -236,12 +246,14 @@ impl PipelineVisualization {
         match instruction {
             // R-type instructions
             Instruction::Add { .. } => "ADD",
+            Instruction::Addu { .. } => "ADDU",
             Instruction::Sub { .. } => "SUB",
             Instruction::And { .. } => "AND",
             Instruction::Or { .. } => "OR",
             Instruction::Xor { .. } => "XOR",
             Instruction::Nor { .. } => "NOR",
             Instruction::Slt { .. } => "SLT",
+            Instruction::Sltu { .. } => "SLTU",
             Instruction::Sll { .. } => "SLL",
             Instruction::Srl { .. } => "SRL",
             Instruction::Sra { .. } => "SRA",
@@ -275,6 +287,8 @@ impl PipelineVisualization {
             Instruction::Sw { .. } => "SW",
             Instruction::Sb { .. } => "SB",
             Instruction::Sh { .. } => "SH",
+            Instruction::Ll { .. } => "LL",
+            Instruction::Sc { .. } => "SC",
 
             // Branch instructions
             Instruction::Beq { .. } => "BEQ",
@@ -290,6 +304,14 @@ impl PipelineVisualization {
             Instruction::Jr { .. } => "JR",
             Instruction::Jalr { .. } => "JALR",
 
+            // CP0 instructions
+            Instruction::Mfc0 { .. } => "MFC0",
+            Instruction::Mtc0 { .. } => "MTC0",
+            Instruction::Eret => "ERET",
+            Instruction::Rdpgpr { .. } => "RDPGPR",
+            Instruction::Wrpgpr { .. } => "WRPGPR",
+            Instruction::Rdhwr { .. } => "RDHWR",
+
             // Special instructions
             Instruction::Nop => "NOP",
             Instruction::InvalidInstruction => "INVALID",
@@ -0,0 +1,269 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// watch.rs
+//
+// This file contains the watch expressions of the stepping debugger. A
+// watch names a register, a word of memory or an entry of the branch
+// predictor; the list re-evaluates every watch against the timing
+// simulator after each step and marks the values that changed since they
+// were last shown.
+
+use super::branch_predictor::PredictionState;
+use super::simulator::{ExecutionMode, Simulator};
+use crate::functional_simulator::guest;
+use crate::functional_simulator::registers::REGISTER_NAMES;
+use std::fmt;
+
+/// Longest string a `str[ADDRESS]` watch shows
+const MAX_STRING_LENGTH: usize = 80;
+
+/// A value to follow while stepping
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchExpression {
+    /// A general-purpose register, by number
+    Register(u32),
+    Hi,
+    Lo,
+    Pc,
+    /// The word of memory at an address
+    Word(u32),
+    /// The NUL-terminated string at an address
+    String(u32),
+    /// The local 2-bit counter of the branch at an address
+    Predictor(u32),
+    /// The predictor's global history of the last four branches
+    History,
+}
+
+impl WatchExpression {
+    /// Parse `$t0`, `$8`, `hi`, `lo`, `pc`, `mem[ADDRESS]` (or
+    /// `[ADDRESS]`), `str[ADDRESS]`, `predictor[ADDRESS]` or `history`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let lower = text.to_ascii_lowercase();
+        match lower.as_str() {
+            "hi" => return Ok(WatchExpression::Hi),
+            "lo" => return Ok(WatchExpression::Lo),
+            "pc" => return Ok(WatchExpression::Pc),
+            "history" => return Ok(WatchExpression::History),
+            _ => {},
+        }
+        if let Some(name) = lower.strip_prefix('$') {
+            return match name.parse::<u32>() {
+                Ok(number) if number < 32 => Ok(WatchExpression::Register(number)),
+                Ok(_) => Err(format!("unknown register '{}'", text)),
+                Err(_) => REGISTER_NAMES
+                    .iter()
+                    .position(|&register| register == lower || (name == "s8" && register == "$fp"))
+                    .map(|number| WatchExpression::Register(number as u32))
+                    .ok_or_else(|| format!("unknown register '{}'", text)),
+            };
+        }
+        if let Some(address) = indexed(&lower, "predictor") {
+            return parse_address(address).map(WatchExpression::Predictor);
+        }
+        if let Some(address) = indexed(&lower, "str") {
+            return parse_address(address).map(WatchExpression::String);
+        }
+        if let Some(address) = indexed(&lower, "mem").or_else(|| indexed(&lower, "")) {
+            let address = parse_address(address)?;
+            if address % 4 != 0 {
+                return Err(format!("word address 0x{:08X} is not aligned", address));
+            }
+            return Ok(WatchExpression::Word(address));
+        }
+        Err(format!(
+            "cannot watch '{}'; expected a register, pc, hi, lo, mem[ADDRESS], str[ADDRESS], predictor[ADDRESS] or history",
+            text
+        ))
+    }
+
+    /// Current value, as shown in the watch list
+    pub fn evaluate(&self, simulator: &Simulator) -> String {
+        let registers = &simulator.registers;
+        match *self {
+            WatchExpression::Register(number) => format!("0x{:08X}", registers.read(number)),
+            WatchExpression::Hi => format!("0x{:08X}", registers.hi),
+            WatchExpression::Lo => format!("0x{:08X}", registers.lo),
+            WatchExpression::Pc => format!("0x{:08X}", simulator.pc),
+            WatchExpression::Word(address) => match simulator.memory.read_word(address as usize) {
+                Ok(word) => format!("0x{:08X}", word),
+                Err(e) => format!("<{}>", e),
+            },
+            WatchExpression::String(address) => {
+                match guest::read_c_string(&simulator.memory, address, MAX_STRING_LENGTH) {
+                    Ok(bytes) => format!("{:?}", String::from_utf8_lossy(&bytes)),
+                    Err(e) => format!("<{}>", e),
+                }
+            },
+            WatchExpression::Predictor(pc) => match &simulator.execution_mode {
+                ExecutionMode::InOrder(pipeline) => {
+                    match pipeline.branch_predictor.local_state(pc) {
+                        Some(state) => describe_state(state).to_string(),
+                        None => "untrained".to_string(),
+                    }
+                },
+                ExecutionMode::OutOfOrder(_) => "<no predictor>".to_string(),
+            },
+            WatchExpression::History => match &simulator.execution_mode {
+                ExecutionMode::InOrder(pipeline) => {
+                    format!("0b{:04b}", pipeline.branch_predictor.global_history())
+                },
+                ExecutionMode::OutOfOrder(_) => "<no predictor>".to_string(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for WatchExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchExpression::Register(number) => {
+                write!(f, "{}", REGISTER_NAMES[*number as usize % 32])
+            },
+            WatchExpression::Hi => write!(f, "hi"),
+            WatchExpression::Lo => write!(f, "lo"),
+            WatchExpression::Pc => write!(f, "pc"),
+            WatchExpression::Word(address) => write!(f, "mem[0x{:08X}]", address),
+            WatchExpression::String(address) => write!(f, "str[0x{:08X}]", address),
+            WatchExpression::Predictor(pc) => write!(f, "predictor[0x{:08X}]", pc),
+            WatchExpression::History => write!(f, "history"),
+        }
+    }
+}
+
+// The text between `name[` and a closing `]`
+fn indexed<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    text.strip_prefix(name)?
+        .strip_prefix('[')?
+        .strip_suffix(']')
+}
+
+pub(crate) fn parse_address(text: &str) -> Result<u32, String> {
+    let text = text.trim();
+    let parsed = match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("invalid address '{}'", text))
+}
+
+fn describe_state(state: PredictionState) -> &'static str {
+    match state {
+        PredictionState::StronglyNotTaken => "strongly not taken",
+        PredictionState::WeaklyNotTaken => "weakly not taken",
+        PredictionState::WeaklyTaken => "weakly taken",
+        PredictionState::StronglyTaken => "strongly taken",
+    }
+}
+
+/// A watch's value at one evaluation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchValue {
+    pub expression: WatchExpression,
+    pub value: String,
+    /// Whether the value differs from the one last shown
+    pub changed: bool,
+}
+
+/// The watches of a session, with the values they had when last shown
+#[derive(Clone, Debug, Default)]
+pub struct WatchList {
+    watches: Vec<(WatchExpression, Option<String>)>,
+}
+
+impl WatchList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a watch and return its number, counting from 1
+    pub fn add(&mut self, expression: WatchExpression) -> Result<usize, String> {
+        if self
+            .watches
+            .iter()
+            .any(|(watched, _)| *watched == expression)
+        {
+            return Err(format!("{} is already watched", expression));
+        }
+        self.watches.push((expression, None));
+        Ok(self.watches.len())
+    }
+
+    /// Remove the watch with this number
+    pub fn remove(&mut self, number: usize) -> Option<WatchExpression> {
+        if number == 0 || number > self.watches.len() {
+            return None;
+        }
+        Some(self.watches.remove(number - 1).0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.watches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    pub fn expressions(&self) -> Vec<WatchExpression> {
+        self.watches
+            .iter()
+            .map(|(expression, _)| *expression)
+            .collect()
+    }
+
+    /// Evaluate every watch and remember the values as the last shown. A
+    /// new watch has nothing to compare with, so it is not marked changed
+    pub fn update(&mut self, simulator: &Simulator) -> Vec<WatchValue> {
+        self.watches
+            .iter_mut()
+            .map(|(expression, last)| {
+                let value = expression.evaluate(simulator);
+                let changed = last.as_ref().map_or(false, |last| *last != value);
+                *last = Some(value.clone());
+                WatchValue {
+                    expression: *expression,
+                    value,
+                    changed,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The values on one line. Changed values are marked with `*`, and also
+/// shown in reverse video when `highlight` is set
+pub fn render_watches(values: &[WatchValue], highlight: bool) -> String {
+    let items: Vec<String> = values
+        .iter()
+        .map(|watch| {
+            let item = format!("{}={}", watch.expression, watch.value);
+            match (watch.changed, highlight) {
+                (true, true) => format!("\x1b[7m*{}\x1b[0m", item),
+                (true, false) => format!("*{}", item),
+                (false, _) => format!(" {}", item),
+            }
+        })
+        .collect();
+    items.join("  ")
+}
@@ -0,0 +1,338 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// what_if.rs
+//
+// This file contains the interactive "what-if" session for the timing
+// simulator. A program is stepped a few instructions at a time; at any pause
+// the configuration can be changed (forwarding, branch prediction, caches)
+// and the instructions since the last snapshot replayed under the new
+// settings. The replay starts from the snapshot's pipeline, cache and
+// predictor state, so only the changed settings affect the result.
+
+use super::branch_predictor::BranchPredictor;
+use super::compare::{PipelineComparison, TimingVariant};
+use super::components::CacheHierarchy;
+use super::data_breakpoint::{BreakpointHit, DataBreakpoint, DataBreakpoints};
+use super::last_writer::{written_locations, LastWriters};
+use super::pipeline::Pipeline;
+use super::simulator::{ExecutionMode, Simulator};
+use super::stats::InstructionTiming;
+use super::tlb::Tlb;
+use super::watch::{WatchList, WatchValue};
+use crate::functional_simulator::memory::Memory;
+use crate::functional_simulator::registers::Registers;
+use crate::functional_simulator::simulator::decode_instruction;
+use crate::utils::limits::TerminationReason;
+
+/// Machine state saved at a pause point
+#[derive(Clone)]
+pub struct Snapshot {
+    pub pipeline: Pipeline,
+    pub registers: Registers,
+    pub memory: Memory,
+    pub pc: u32,
+    pub last_writers: Option<LastWriters>,
+    /// Instructions executed before the snapshot was taken
+    pub instructions: usize,
+}
+
+/// Steps a program through the timing simulator with live configuration changes
+pub struct WhatIfSession {
+    pub simulator: Simulator,
+    /// Settings to use for the next replay
+    pub variant: TimingVariant,
+    /// Settings the current history was produced with
+    pub applied: TimingVariant,
+    /// Timing of every instruction executed so far
+    pub history: Vec<InstructionTiming>,
+    pub termination_reason: Option<TerminationReason>,
+    /// Values re-evaluated after every instruction by `step_watched`
+    pub watches: WatchList,
+    /// Checked on every write by `step_until_break`
+    pub breakpoints: DataBreakpoints,
+    snapshot: Snapshot,
+}
+
+impl WhatIfSession {
+    /// Build a session; `load` places the program in the simulator
+    pub fn new<F: FnOnce(&mut Simulator)>(
+        variant: TimingVariant,
+        memory_size: usize,
+        load: F,
+    ) -> Result<Self, String> {
+        if variant.pipeline_config.tomasulo_config.is_some() {
+            return Err("What-if stepping needs the in-order pipeline".to_string());
+        }
+
+        let mut simulator = Simulator::new(
+            variant.pipeline_config.clone(),
+            variant.instr_cache_config.clone(),
+            variant.data_cache_config.clone(),
+            memory_size,
+        );
+        simulator.visualization = None;
+        simulator.enable_timeline();
+        load(&mut simulator);
+
+        let snapshot = Self::capture(&simulator, 0);
+        Ok(Self {
+            simulator,
+            applied: variant.clone(),
+            variant,
+            history: Vec::new(),
+            termination_reason: None,
+            watches: WatchList::new(),
+            breakpoints: DataBreakpoints::new(),
+            snapshot,
+        })
+    }
+
+    pub fn instructions(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn cycles(&self) -> usize {
+        self.history.iter().map(|t| t.cycles).sum()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.termination_reason.is_some()
+    }
+
+    /// Instruction count at which the last snapshot was taken
+    pub fn snapshot_instruction(&self) -> usize {
+        self.snapshot.instructions
+    }
+
+    /// Execute up to `count` instructions and return their timing
+    pub fn step(&mut self, count: usize) -> &[InstructionTiming] {
+        let start = self.history.len();
+        if self.is_finished() || count == 0 {
+            return &self.history[start..];
+        }
+
+        // The run loop treats a NOP as the end of the program
+        if self.simulator.memory.read_word(self.simulator.pc as usize) == Ok(0) {
+            self.termination_reason = Some(TerminationReason::Halted);
+            return &self.history[start..];
+        }
+
+        let cycles = self.cycles();
+        if let Some(writers) = self.simulator.last_writers.as_mut() {
+            writers.instruction_base = start;
+            writers.cycle_base = cycles;
+        }
+        self.simulator.limits.max_instructions = count;
+        self.simulator.run();
+
+        let mut cycle = self.cycles();
+        let timeline = self.simulator.timeline.take().unwrap_or_default();
+        for mut timing in timeline {
+            timing.index = self.history.len();
+            timing.issue_cycle = cycle;
+            cycle += timing.cycles;
+            self.history.push(timing);
+        }
+        self.simulator.timeline = Some(Vec::new());
+
+        match self.simulator.termination_reason {
+            Some(TerminationReason::InstructionLimit) | None => {},
+            Some(reason) => self.termination_reason = Some(reason),
+        }
+        &self.history[start..]
+    }
+
+    /// Execute up to `count` instructions one at a time, evaluating the
+    /// watches after each
+    pub fn step_watched(&mut self, count: usize) -> Vec<(InstructionTiming, Vec<WatchValue>)> {
+        let mut steps = Vec::new();
+        for _ in 0..count {
+            let timing = match self.step(1).first() {
+                Some(&timing) => timing,
+                None => break,
+            };
+            steps.push((timing, self.watches.update(&self.simulator)));
+        }
+        steps
+    }
+
+    /// Execute up to `count` instructions, stopping after the first one whose
+    /// write meets the condition of a data breakpoint. Returns the number of
+    /// instructions executed and the breakpoint hit, if any
+    pub fn step_until_break(&mut self, count: usize) -> (usize, Option<BreakpointHit>) {
+        let start = self.history.len();
+        for _ in 0..count {
+            let simulator = &self.simulator;
+            let writes = match simulator.memory.read_word(simulator.pc as usize) {
+                Ok(word) => written_locations(
+                    &decode_instruction(word),
+                    &simulator.registers,
+                    simulator.pc,
+                ),
+                Err(_) => Vec::new(),
+            };
+            let checked: Vec<(usize, DataBreakpoint, u32)> = self
+                .breakpoints
+                .iter()
+                .filter(|(_, breakpoint)| writes.contains(&breakpoint.location))
+                .filter_map(|(number, breakpoint)| {
+                    let old = breakpoint
+                        .location
+                        .value(&simulator.registers, &simulator.memory)?;
+                    Some((number, *breakpoint, old))
+                })
+                .collect();
+
+            let timing = match self.step(1).first() {
+                Some(&timing) => timing,
+                None => break,
+            };
+            for (number, breakpoint, old) in checked {
+                let new = breakpoint
+                    .location
+                    .value(&self.simulator.registers, &self.simulator.memory);
+                if let Some(new) = new.filter(|&new| breakpoint.condition.is_met(old, new)) {
+                    let hit = BreakpointHit {
+                        number,
+                        breakpoint,
+                        old,
+                        new,
+                        pc: timing.pc,
+                        index: timing.index,
+                    };
+                    return (self.history.len() - start, Some(hit));
+                }
+            }
+        }
+        (self.history.len() - start, None)
+    }
+
+    /// Save the current state as the point later replays start from
+    pub fn take_snapshot(&mut self) {
+        self.snapshot = Self::capture(&self.simulator, self.history.len());
+    }
+
+    /// Change settings for the next replay, e.g. `forwarding=off`
+    pub fn configure(&mut self, spec: &str) -> Result<(), String> {
+        self.variant.apply(spec)
+    }
+
+    /// Replay the instructions since the snapshot with the current settings
+    /// and compare them with the previous run
+    pub fn rerun(&mut self) -> PipelineComparison {
+        let start = self.snapshot.instructions;
+        let previous: Vec<InstructionTiming> = self.history.split_off(start);
+        let previous_cycles: usize = previous.iter().map(|t| t.cycles).sum();
+
+        self.restore();
+        self.termination_reason = None;
+        self.step(previous.len());
+        let replayed = self.history[start..].to_vec();
+        let replayed_cycles = replayed.iter().map(|t| t.cycles).sum();
+
+        let comparison = PipelineComparison {
+            baseline_name: self.applied.describe(),
+            variant_name: self.variant.describe(),
+            baseline: previous,
+            variant: replayed,
+            baseline_cycles: previous_cycles,
+            variant_cycles: replayed_cycles,
+        };
+        self.applied = self.variant.clone();
+        comparison
+    }
+
+    /// One line describing an executed instruction
+    pub fn format_timing(timing: &InstructionTiming) -> String {
+        format!(
+            "{:>6}  cycle {:>6}  0x{:08X}  {:<40} {:>3} cycles, {} stalls{}",
+            timing.index,
+            timing.issue_cycle,
+            timing.pc,
+            format!("{:?}", decode_instruction(timing.word)),
+            timing.cycles,
+            timing.stall_cycles,
+            if timing.mispredicted {
+                ", mispredicted"
+            } else {
+                ""
+            }
+        )
+    }
+
+    fn capture(simulator: &Simulator, instructions: usize) -> Snapshot {
+        let pipeline = match &simulator.execution_mode {
+            ExecutionMode::InOrder(pipeline) => pipeline.clone(),
+            ExecutionMode::OutOfOrder(_) => unreachable!("sessions use the in-order pipeline"),
+        };
+        Snapshot {
+            pipeline,
+            registers: simulator.registers.clone(),
+            memory: simulator.memory.clone(),
+            pc: simulator.pc,
+            last_writers: simulator.last_writers.clone(),
+            instructions,
+        }
+    }
+
+    // Put the snapshot back and apply the pending settings to its pipeline
+    fn restore(&mut self) {
+        let snapshot = self.snapshot.clone();
+        let mut pipeline = snapshot.pipeline;
+        let config = &self.variant.pipeline_config;
+        pipeline.forwarding_enabled = config.forwarding_enabled;
+        pipeline.branch_prediction_enabled = config.branch_prediction_enabled;
+        pipeline.control_hazard_scheme = config.control_hazard_scheme;
+        pipeline.caches_enabled = config.caches_enabled;
+        pipeline.unified_memory_port = config.unified_memory_port;
+        pipeline.uncached_regions = config.uncached_regions.clone();
+        // A different predictor type starts untrained
+        if pipeline.branch_predictor.kind() != config.branch_predictor_type {
+            pipeline.branch_predictor = BranchPredictor::with_type(config.branch_predictor_type);
+        }
+        // A different TLB geometry starts empty
+        let tlb = pipeline.itlb.as_ref().map(|tlb| &tlb.config);
+        if tlb != config.tlb_config.as_ref() {
+            pipeline.itlb = config.tlb_config.clone().map(Tlb::new);
+            pipeline.dtlb = config.tlb_config.clone().map(Tlb::new);
+        }
+
+        // A different cache geometry cannot reuse the saved contents
+        let caches = &pipeline.cache_hierarchy;
+        if caches.l1_instr_cache.config != self.variant.instr_cache_config
+            || caches.l1_data_cache.config != self.variant.data_cache_config
+        {
+            pipeline.cache_hierarchy = CacheHierarchy::new(
+                snapshot.memory.clone(),
+                self.variant.data_cache_config.clone(),
+                self.variant.instr_cache_config.clone(),
+                None,
+            );
+        }
+
+        self.simulator.execution_mode = ExecutionMode::InOrder(pipeline);
+        self.simulator.registers = snapshot.registers;
+        self.simulator.memory = snapshot.memory;
+        self.simulator.pc = snapshot.pc;
+        self.simulator.last_writers = snapshot.last_writers;
+    }
+}
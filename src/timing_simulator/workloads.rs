@@ -0,0 +1,349 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// workloads.rs
+//
+// This file contains the built-in benchmark kernels behind the `bench`
+// subcommand. A workload is a standard kernel, such as matrix multiply or
+// sorting, generated for a size chosen at run time. Its assembly only
+// reserves space for the data; the input values are made by a seeded
+// generator and written into the program image, so large inputs need no
+// files and the same seed always gives the same run. After the run, the
+// result in guest memory is checked against the same computation on the
+// host, so a cache or pipeline experiment cannot silently measure a broken
+// program.
+
+use super::compare::TimingVariant;
+use super::simulator::{ExecutionMode, Simulator};
+use super::stats::StatsCounters;
+use crate::assembler::Assembler;
+use crate::functional_simulator::memory::Memory;
+use crate::loader::ProgramImage;
+use std::fmt;
+
+/// Largest matrix dimension, which keeps the three matrices within 4 MiB
+pub const MAX_MATRIX_SIZE: usize = 512;
+/// Largest array a sort workload generates
+pub const MAX_ARRAY_LENGTH: usize = 1 << 20;
+
+/// The kernels that can be generated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkloadKind {
+    /// Multiply two N x N matrices of words with the naive triple loop
+    Matmul,
+    /// Insertion sort of N signed words
+    Sort,
+}
+
+impl WorkloadKind {
+    pub fn all() -> [WorkloadKind; 2] {
+        [WorkloadKind::Matmul, WorkloadKind::Sort]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WorkloadKind::Matmul => "matmul",
+            WorkloadKind::Sort => "sort",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().iter().copied().find(|kind| kind.name() == name)
+    }
+}
+
+/// A kernel generated for one size and input seed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Workload {
+    pub kind: WorkloadKind,
+    /// Matrix dimension or array length
+    pub size: usize,
+    pub seed: u32,
+}
+
+impl Workload {
+    pub fn new(kind: WorkloadKind, size: usize, seed: u32) -> Result<Self, String> {
+        let limit = match kind {
+            WorkloadKind::Matmul => MAX_MATRIX_SIZE,
+            WorkloadKind::Sort => MAX_ARRAY_LENGTH,
+        };
+        if size == 0 || size > limit {
+            return Err(format!(
+                "{} size must be between 1 and {}, found {}",
+                kind.name(),
+                limit,
+                size
+            ));
+        }
+        Ok(Self { kind, size, seed })
+    }
+
+    /// The kernel's assembly, with space reserved for its data
+    pub fn source(&self) -> String {
+        let n = self.size;
+        match self.kind {
+            WorkloadKind::Matmul => format!(
+                "# matmul: product = lhs * rhs for {n}x{n} matrices of words
+.data
+lhs:     .space {bytes}
+rhs:     .space {bytes}
+product: .space {bytes}
+.text
+main:
+    la $s3, lhs
+    la $s4, rhs
+    la $s5, product
+    li $s7, {n}             # N
+    li $s6, {stride}        # bytes in a row
+    li $s0, 0               # i
+row:
+    li $s1, 0               # j
+column:
+    mul $t0, $s0, $s6       # $t0 walks lhs[i][k] along the row
+    add $t0, $t0, $s3
+    sll $t1, $s1, 2         # $t1 walks rhs[k][j] down the column
+    add $t1, $t1, $s4
+    li $t7, 0
+    li $s2, 0               # k
+term:
+    lw $t2, 0($t0)
+    lw $t3, 0($t1)
+    mul $t4, $t2, $t3
+    add $t7, $t7, $t4
+    addi $t0, $t0, 4
+    add $t1, $t1, $s6
+    addi $s2, $s2, 1
+    bne $s2, $s7, term
+    mul $t5, $s0, $s6       # product[i][j]
+    add $t5, $t5, $s5
+    sll $t6, $s1, 2
+    add $t5, $t5, $t6
+    sw $t7, 0($t5)
+    addi $s1, $s1, 1
+    bne $s1, $s7, column
+    addi $s0, $s0, 1
+    bne $s0, $s7, row
+    li $v0, 10
+    syscall
+",
+                n = n,
+                bytes = n * n * 4,
+                stride = n * 4
+            ),
+            WorkloadKind::Sort => format!(
+                "# sort: insertion sort of {n} signed words in place
+.data
+array: .space {bytes}
+.text
+main:
+    la $s0, array
+    li $s1, {n}             # N
+    li $s2, 1               # i
+outer:
+    slt $t4, $s2, $s1
+    beq $t4, $zero, done
+    sll $t0, $s2, 2         # $t0 walks down from array[i]
+    add $t0, $t0, $s0
+    lw $t1, 0($t0)          # the key to insert
+shift:
+    beq $t0, $s0, place
+    lw $t2, -4($t0)
+    slt $t3, $t1, $t2
+    beq $t3, $zero, place
+    sw $t2, 0($t0)
+    addi $t0, $t0, -4
+    j shift
+place:
+    sw $t1, 0($t0)
+    addi $s2, $s2, 1
+    j outer
+done:
+    li $v0, 10
+    syscall
+",
+                n = n,
+                bytes = n * 4
+            ),
+        }
+    }
+
+    /// The input words, in the order they are laid out from the first label
+    pub fn inputs(&self) -> Vec<u32> {
+        let mut state = self.seed;
+        let mut next = move || {
+            // Numerical Recipes LCG; the high bits are the most random
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            state
+        };
+        match self.kind {
+            // Small values keep every dot product far from overflow
+            WorkloadKind::Matmul => (0..2 * self.size * self.size)
+                .map(|_| ((next() >> 28) as i32 - 8) as u32)
+                .collect(),
+            WorkloadKind::Sort => (0..self.size)
+                .map(|_| ((next() as i32) >> 8) as u32)
+                .collect(),
+        }
+    }
+
+    /// Assemble the kernel and write the generated inputs into its data
+    pub fn image(&self) -> Result<ProgramImage, String> {
+        let mut program = Assembler::new()
+            .assemble_program(&self.source())
+            .map_err(|e| format!("Failed to assemble the {} kernel: {}", self.kind.name(), e))?;
+        for (index, word) in self.inputs().iter().enumerate() {
+            program.data[index * 4..index * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+        Ok(ProgramImage::from_program(&program))
+    }
+
+    /// Check the kernel's result in memory against the host's
+    pub fn verify(&self, memory: &Memory) -> Result<(), String> {
+        let inputs = self.inputs();
+        let (label_offset, expected): (usize, Vec<u32>) = match self.kind {
+            WorkloadKind::Matmul => {
+                let n = self.size;
+                let (lhs, rhs) = inputs.split_at(n * n);
+                let product = (0..n * n)
+                    .map(|cell| {
+                        let (i, j) = (cell / n, cell % n);
+                        (0..n).fold(0u32, |sum, k| {
+                            sum.wrapping_add(lhs[i * n + k].wrapping_mul(rhs[k * n + j]))
+                        })
+                    })
+                    .collect();
+                (2 * n * n * 4, product)
+            },
+            WorkloadKind::Sort => {
+                let mut sorted = inputs;
+                sorted.sort_by_key(|&word| word as i32);
+                (0, sorted)
+            },
+        };
+        let base = crate::loader::DATA_BASE as usize + label_offset;
+        for (index, &word) in expected.iter().enumerate() {
+            let address = base + index * 4;
+            match memory.read_word(address) {
+                Ok(actual) if actual == word => {},
+                Ok(actual) => {
+                    return Err(format!(
+                        "word {} at 0x{:08X} is {}, expected {}",
+                        index, address, actual as i32, word as i32
+                    ))
+                },
+                Err(e) => return Err(format!("word {} cannot be read: {}", index, e)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the kernel on the in-order timing simulator and check its result
+    pub fn run(&self, variant: &TimingVariant) -> Result<BenchReport, String> {
+        let image = self.image()?;
+        let mut simulator = Simulator::new(
+            variant.pipeline_config.clone(),
+            variant.instr_cache_config.clone(),
+            variant.data_cache_config.clone(),
+            image.memory_required().next_power_of_two(),
+        );
+        simulator.visualization = None;
+        simulator
+            .load_image(&image)
+            .map_err(|e| format!("Failed to load the {} kernel: {}", self.kind.name(), e))?;
+        simulator.run();
+        if let ExecutionMode::OutOfOrder(_) = simulator.execution_mode {
+            return Err("Workloads run on the in-order pipeline".to_string());
+        }
+        Ok(BenchReport {
+            workload: *self,
+            config: variant.describe(),
+            totals: simulator.run_totals,
+            verified: self.verify(&simulator.memory),
+        })
+    }
+}
+
+impl fmt::Display for Workload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} n={} seed={}", self.kind.name(), self.size, self.seed)
+    }
+}
+
+/// The counters of one workload run and whether its result was right
+#[derive(Clone, Debug)]
+pub struct BenchReport {
+    pub workload: Workload,
+    /// The timing configuration in `key=value` form
+    pub config: String,
+    pub totals: StatsCounters,
+    pub verified: Result<(), String>,
+}
+
+impl BenchReport {
+    pub fn cpi(&self) -> f64 {
+        ratio(self.totals.cycles, self.totals.instructions)
+    }
+
+    pub fn render(&self) -> String {
+        format!(
+            "Workload: {}\nConfig: {}\n{}",
+            self.workload,
+            self.config,
+            render_run(&self.totals, &self.verified)
+        )
+    }
+}
+
+/// The counters of a generated program's run and the check of its result
+pub(crate) fn render_run(totals: &StatsCounters, verified: &Result<(), String>) -> String {
+    let mut text = format!(
+        "  Instructions: {}\n  Cycles: {}\n  CPI: {:.3}\n",
+        totals.instructions,
+        totals.cycles,
+        ratio(totals.cycles, totals.instructions)
+    );
+    text.push_str(&format!(
+        "  L1I miss rate: {:.2}% ({} of {})\n  L1D miss rate: {:.2}% ({} of {})\n",
+        100.0 * ratio(totals.icache_misses, totals.icache_accesses),
+        totals.icache_misses,
+        totals.icache_accesses,
+        100.0 * ratio(totals.dcache_misses, totals.dcache_accesses),
+        totals.dcache_misses,
+        totals.dcache_accesses
+    ));
+    text.push_str(&format!(
+        "  Branch mispredictions: {}\n  Stall cycles: {}\n",
+        totals.branch_mispredictions, totals.stalls
+    ));
+    match verified {
+        Ok(()) => text.push_str("Result: verified against the host\n"),
+        Err(e) => text.push_str(&format!("Result: WRONG, {}\n", e)),
+    }
+    text
+}
+
+fn ratio(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
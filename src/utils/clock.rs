@@ -0,0 +1,79 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// clock.rs
+//
+// This file contains the core clock frequency: parsing it from the command
+// line and turning cycle counts into simulated wall-clock time, so cycles
+// can be read as the seconds they would take on a core of that speed.
+
+/// Parse a frequency such as `250MHz`, `1.5GHz`, `800k` or `100000000`, in
+/// hertz when no unit is given
+pub fn parse_frequency(text: &str) -> Result<u64, String> {
+    let lower = text.trim().to_ascii_lowercase();
+    let number = lower.strip_suffix("hz").unwrap_or(&lower);
+    let (digits, scale) = match number.char_indices().last() {
+        Some((i, 'k')) => (&number[..i], 1e3),
+        Some((i, 'm')) => (&number[..i], 1e6),
+        Some((i, 'g')) => (&number[..i], 1e9),
+        _ => (number, 1.0),
+    };
+    let value: f64 = digits
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid frequency '{}'", text))?;
+    let hz = (value * scale).round();
+    if !hz.is_finite() || hz < 1.0 || hz > u64::MAX as f64 {
+        return Err(format!("frequency '{}' is out of range", text));
+    }
+    Ok(hz as u64)
+}
+
+/// A frequency in the largest unit that keeps it at least 1, like `1.5 GHz`
+pub fn format_frequency(hz: u64) -> String {
+    let units = [(1_000_000_000, "GHz"), (1_000_000, "MHz"), (1_000, "kHz")];
+    for (scale, unit) in units {
+        if hz >= scale {
+            return format!("{} {}", hz as f64 / scale as f64, unit);
+        }
+    }
+    format!("{} Hz", hz)
+}
+
+/// Simulated seconds `cycles` take at `hz`
+pub fn seconds(cycles: u64, hz: u64) -> f64 {
+    cycles as f64 / hz.max(1) as f64
+}
+
+/// The simulated time `cycles` take at `hz`, in the largest of seconds,
+/// milliseconds, microseconds and nanoseconds that keeps it at least 1
+pub fn format_time(cycles: u64, hz: u64) -> String {
+    let seconds = seconds(cycles, hz);
+    if seconds >= 1.0 {
+        format!("{:.3} s", seconds)
+    } else if seconds >= 1e-3 {
+        format!("{:.3} ms", seconds * 1e3)
+    } else if seconds >= 1e-6 {
+        format!("{:.3} us", seconds * 1e6)
+    } else {
+        format!("{:.3} ns", seconds * 1e9)
+    }
+}
@@ -0,0 +1,223 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// crash_dump.rs
+//
+// This file contains the crash dumps both simulators write when a program
+// stops on a fault. A dump holds what a postmortem needs: the registers,
+// the last instructions executed with their disassembly, the words around
+// the stack pointer and, from the timing simulator, what each pipeline
+// stage held. A one-line summary of the crash goes to the console.
+
+use super::limits::TerminationReason;
+use crate::functional_simulator::memory::Memory;
+use crate::functional_simulator::registers::{Registers, REGISTER_NAMES};
+use crate::functional_simulator::simulator::{decode_instruction, instruction_to_string};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Instructions a crash dump lists unless told otherwise
+pub const DEFAULT_CRASH_DEPTH: usize = 32;
+/// Words shown below and above the stack pointer
+pub const STACK_WINDOW_BELOW: u32 = 4;
+pub const STACK_WINDOW_ABOVE: u32 = 12;
+
+/// The most recently fetched instructions, as (PC, word) pairs
+#[derive(Clone, Debug)]
+pub struct RecentInstructions {
+    capacity: usize,
+    entries: VecDeque<(u32, u32)>,
+}
+
+impl RecentInstructions {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: VecDeque::with_capacity(capacity.max(1)),
+        }
+    }
+
+    pub fn record(&mut self, pc: u32, word: u32) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((pc, word));
+    }
+
+    /// Oldest first
+    pub fn entries(&self) -> Vec<(u32, u32)> {
+        self.entries.iter().copied().collect()
+    }
+}
+
+/// One pipeline stage at the time of a crash
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StageState {
+    pub name: String,
+    pub status: String,
+    /// PC and word of the instruction in the stage, if any
+    pub instruction: Option<(u32, u32)>,
+}
+
+/// Machine state when a program stopped on a fault
+#[derive(Clone, Debug)]
+pub struct CrashDump {
+    /// "functional" or "timing"
+    pub simulator: &'static str,
+    pub reason: TerminationReason,
+    pub pc: u32,
+    pub instructions: usize,
+    pub registers: Vec<u32>,
+    pub hi: u32,
+    pub lo: u32,
+    /// Last instructions fetched, oldest first; the faulting one is last
+    /// when it could be fetched
+    pub recent: Vec<(u32, u32)>,
+    /// Words around the stack pointer; `None` where memory cannot be read
+    pub stack: Vec<(u32, Option<u32>)>,
+    /// Empty for the functional simulator
+    pub pipeline: Vec<StageState>,
+}
+
+impl CrashDump {
+    pub fn capture(
+        simulator: &'static str,
+        reason: TerminationReason,
+        pc: u32,
+        instructions: usize,
+        registers: &Registers,
+        memory: &Memory,
+        recent: Option<&RecentInstructions>,
+    ) -> Self {
+        let sp = registers.read(29) & !3;
+        let first = sp.saturating_sub(4 * STACK_WINDOW_BELOW);
+        let stack = (0..STACK_WINDOW_BELOW + STACK_WINDOW_ABOVE)
+            .map(|i| first.wrapping_add(4 * i))
+            .map(|address| (address, memory.read_word(address as usize).ok()))
+            .collect();
+        Self {
+            simulator,
+            reason,
+            pc,
+            instructions,
+            registers: (0..32).map(|reg| registers.read(reg)).collect(),
+            hi: registers.hi,
+            lo: registers.lo,
+            recent: recent.map(RecentInstructions::entries).unwrap_or_default(),
+            stack,
+            pipeline: Vec::new(),
+        }
+    }
+
+    /// One line for the console
+    pub fn summary(&self) -> String {
+        let at = match self.recent.last() {
+            Some(&(pc, word)) if pc == self.pc => format!(" ({})", disassemble(word)),
+            _ => String::new(),
+        };
+        format!(
+            "Crash: {} at PC 0x{:08X}{} after {} instructions",
+            self.reason, self.pc, at, self.instructions
+        )
+    }
+
+    /// The full dump as text
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "vmips crash dump ({} simulator)", self.simulator);
+        let _ = writeln!(text, "{}\n", self.summary());
+
+        let _ = writeln!(text, "Registers:");
+        for row in 0..8 {
+            for column in 0..4 {
+                let reg = row * 4 + column;
+                let _ = write!(
+                    text,
+                    "  {:<5} 0x{:08X}",
+                    REGISTER_NAMES[reg], self.registers[reg]
+                );
+            }
+            text.push('\n');
+        }
+        let _ = writeln!(
+            text,
+            "  hi    0x{:08X}  lo    0x{:08X}  pc    0x{:08X}\n",
+            self.hi, self.lo, self.pc
+        );
+
+        let _ = writeln!(text, "Last {} instructions:", self.recent.len());
+        for &(pc, word) in &self.recent {
+            let marker = if pc == self.pc { "=>" } else { "  " };
+            let _ = writeln!(
+                text,
+                "{} 0x{:08X}: {:08X}  {}",
+                marker,
+                pc,
+                word,
+                disassemble(word)
+            );
+        }
+
+        let _ = writeln!(text, "\nStack ($sp = 0x{:08X}):", self.registers[29]);
+        for &(address, word) in &self.stack {
+            let marker = if address == self.registers[29] & !3 {
+                "sp->"
+            } else {
+                "    "
+            };
+            match word {
+                Some(word) => {
+                    let _ = writeln!(text, "{} 0x{:08X}: 0x{:08X}", marker, address, word);
+                },
+                None => {
+                    let _ = writeln!(text, "{} 0x{:08X}: unreadable", marker, address);
+                },
+            }
+        }
+
+        if !self.pipeline.is_empty() {
+            let _ = writeln!(text, "\nPipeline:");
+            for stage in &self.pipeline {
+                let _ = match stage.instruction {
+                    Some((pc, word)) => writeln!(
+                        text,
+                        "  {:<4} {:<8} 0x{:08X}: {}",
+                        stage.name,
+                        stage.status,
+                        pc,
+                        disassemble(word)
+                    ),
+                    None => writeln!(text, "  {:<4} {}", stage.name, stage.status),
+                };
+            }
+        }
+        text
+    }
+
+    pub fn write_to(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.render())
+    }
+}
+
+fn disassemble(word: u32) -> String {
+    instruction_to_string(&decode_instruction(word), word)
+}
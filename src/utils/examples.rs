@@ -0,0 +1,84 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// examples.rs
+//
+// This file contains the example programs bundled with the simulator for the
+// `run-example` subcommand. Each one is an assembly source from
+// examples/programs, embedded in the binary, whose expected output and
+// results are written as `#! expect` annotations. Running an example grades
+// it like an assignment, so a new user sees both what the program printed
+// and that it did what it should.
+
+use crate::utils::grading::{
+    grade_with_output, GradeError, GradeReport, DEFAULT_GRADE_MAX_INSTRUCTIONS,
+};
+use crate::utils::limits::RunLimits;
+
+/// An assembly program shipped with the simulator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Example {
+    pub name: &'static str,
+    /// One line on what the program does and which features it shows
+    pub description: &'static str,
+    pub source: &'static str,
+}
+
+/// The bundled examples, in the order they are listed
+pub static EXAMPLES: &[Example] = &[
+    Example {
+        name: "fibonacci",
+        description: "Print the first ten Fibonacci numbers with a counted loop",
+        source: include_str!("../../examples/programs/fibonacci.s"),
+    },
+    Example {
+        name: "bubble_sort",
+        description: "Sort an array of words in place and print it",
+        source: include_str!("../../examples/programs/bubble_sort.s"),
+    },
+    Example {
+        name: "matrix_multiply",
+        description: "Multiply two 3x3 matrices with a triple loop",
+        source: include_str!("../../examples/programs/matrix_multiply.s"),
+    },
+    Example {
+        name: "syscalls",
+        description: "Print strings, characters and numbers, and allocate with sbrk",
+        source: include_str!("../../examples/programs/syscalls.s"),
+    },
+];
+
+/// Look up a bundled example by name
+pub fn find_example(name: &str) -> Option<&'static Example> {
+    EXAMPLES.iter().find(|example| example.name == name)
+}
+
+impl Example {
+    /// Run the example on the functional simulator and check it against
+    /// its annotations. Returns the report and what the program printed.
+    pub fn run(&self) -> Result<(GradeReport, String), GradeError> {
+        let limits = RunLimits {
+            max_instructions: DEFAULT_GRADE_MAX_INSTRUCTIONS,
+            ..RunLimits::default()
+        };
+        grade_with_output(self.source, limits)
+    }
+}
@@ -0,0 +1,427 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// grading.rs
+//
+// This file contains the auto-grader behind the `grade` subcommand. An
+// assignment states the outcome it expects in structured comments such as
+// `#! expect $v0 = 55` or `#! expect mem[result] = 3`. The grader runs the
+// program on the functional simulator, checks every expectation against
+// the final registers, memory and output, and reports each one as passed
+// or failed in TAP or JSON, which grading scripts can read.
+
+use crate::assembler::{Assembler, AssemblerError};
+use crate::functional_simulator::registers::REGISTER_NAMES;
+use crate::functional_simulator::simulator::Simulator;
+use crate::loader::{LoadError, ProgramImage};
+use crate::utils::limits::RunLimits;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Instructions a graded program may run unless another limit is given,
+/// so a submission stuck in a loop still gets a report
+pub const DEFAULT_GRADE_MAX_INSTRUCTIONS: usize = 1_000_000;
+
+/// Marker that starts an annotation comment
+pub const ANNOTATION_MARKER: &str = "#!";
+
+/// What an expectation checks
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// General-purpose register by number
+    Register(u32),
+    Hi,
+    Lo,
+    /// Word at an address, or at a label plus an offset
+    Memory(Address),
+    /// Everything the program printed
+    Output,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Address {
+    Absolute(u32),
+    Symbol(String, i32),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expected {
+    /// A word, written in hexadecimal when `hex` is set
+    Value {
+        value: u32,
+        hex: bool,
+    },
+    Text(String),
+}
+
+/// One `#! expect TARGET = VALUE` annotation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expectation {
+    /// Source line of the annotation
+    pub line: usize,
+    /// The annotation after `expect`, as written
+    pub text: String,
+    pub target: Target,
+    pub expected: Expected,
+}
+
+#[derive(Debug)]
+pub enum GradeError {
+    /// An annotation could not be parsed
+    Annotation {
+        line: usize,
+        message: String,
+    },
+    Load(LoadError),
+}
+
+impl fmt::Display for GradeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GradeError::Annotation { line, message } => {
+                write!(f, "Annotation error at line {}: {}", line, message)
+            },
+            GradeError::Load(error) => write!(f, "Failed to load program: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for GradeError {}
+
+impl From<LoadError> for GradeError {
+    fn from(error: LoadError) -> Self {
+        GradeError::Load(error)
+    }
+}
+
+impl From<AssemblerError> for GradeError {
+    fn from(error: AssemblerError) -> Self {
+        GradeError::Load(LoadError::Assembly(error))
+    }
+}
+
+/// Collect the expectations annotated in assembly source. An annotation
+/// may follow code on the same line.
+pub fn parse_expectations(source: &str) -> Result<Vec<Expectation>, GradeError> {
+    let mut expectations = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let annotation = match line.find(ANNOTATION_MARKER) {
+            Some(start) => line[start + ANNOTATION_MARKER.len()..].trim(),
+            None => continue,
+        };
+        let error = |message: String| GradeError::Annotation {
+            line: index + 1,
+            message,
+        };
+        let text = match annotation.strip_prefix("expect") {
+            Some(text) if text.starts_with(char::is_whitespace) => text.trim(),
+            _ => return Err(error(format!("unknown annotation '{}'", annotation))),
+        };
+        let (target, expected) = text
+            .split_once('=')
+            .ok_or_else(|| error("expected TARGET = VALUE".to_string()))?;
+        let target = parse_target(target.trim()).map_err(error)?;
+        let expected = match target {
+            Target::Output => Expected::Text(parse_string(expected.trim()).map_err(error)?),
+            _ => parse_value(expected.trim()).map_err(error)?,
+        };
+        expectations.push(Expectation {
+            line: index + 1,
+            text: text.to_string(),
+            target,
+            expected,
+        });
+    }
+    Ok(expectations)
+}
+
+fn parse_target(text: &str) -> Result<Target, String> {
+    if text == "output" {
+        return Ok(Target::Output);
+    }
+    if text == "hi" || text == "$hi" {
+        return Ok(Target::Hi);
+    }
+    if text == "lo" || text == "$lo" {
+        return Ok(Target::Lo);
+    }
+    if let Some(address) = text
+        .strip_prefix("mem[")
+        .and_then(|rest| rest.strip_suffix(']'))
+    {
+        return parse_address(address.trim()).map(Target::Memory);
+    }
+    if let Some(number) = text.strip_prefix('$').and_then(|n| n.parse::<u32>().ok()) {
+        if number < 32 {
+            return Ok(Target::Register(number));
+        }
+    }
+    REGISTER_NAMES
+        .iter()
+        .position(|name| *name == text)
+        .map(|number| Target::Register(number as u32))
+        .ok_or_else(|| format!("unknown target '{}'", text))
+}
+
+fn parse_address(text: &str) -> Result<Address, String> {
+    if let Ok(Expected::Value { value, .. }) = parse_value(text) {
+        return Ok(Address::Absolute(value));
+    }
+    let (symbol, offset) = match text.find(|c| c == '+' || c == '-') {
+        Some(split) => {
+            let offset = match parse_value(text[split + 1..].trim())? {
+                Expected::Value { value, .. } => value as i32,
+                Expected::Text(_) => unreachable!("parse_value returns values"),
+            };
+            let offset = if text[split..].starts_with('-') {
+                offset.wrapping_neg()
+            } else {
+                offset
+            };
+            (text[..split].trim(), offset)
+        },
+        None => (text, 0),
+    };
+    if symbol.is_empty()
+        || !symbol
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    {
+        return Err(format!("invalid address '{}'", text));
+    }
+    Ok(Address::Symbol(symbol.to_string(), offset))
+}
+
+// A decimal, negative or hexadecimal word
+fn parse_value(text: &str) -> Result<Expected, String> {
+    let invalid = || format!("invalid value '{}'", text);
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        let value = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+        return Ok(Expected::Value { value, hex: true });
+    }
+    let value = text
+        .parse::<u32>()
+        .or_else(|_| text.parse::<i32>().map(|value| value as u32))
+        .map_err(|_| invalid())?;
+    Ok(Expected::Value { value, hex: false })
+}
+
+// A double-quoted string with \n, \t, \\ and \" escapes
+fn parse_string(text: &str) -> Result<String, String> {
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(|| format!("expected a quoted string, found '{}'", text))?;
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(other) => result.push(other),
+            None => return Err("string ends with a backslash".to_string()),
+        }
+    }
+    Ok(result)
+}
+
+/// The result of checking one expectation
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Outcome {
+    pub line: usize,
+    pub assertion: String,
+    pub passed: bool,
+    /// What the program produced, written like the expected value
+    pub actual: String,
+}
+
+/// The results of grading one program
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GradeReport {
+    pub passed: usize,
+    pub failed: usize,
+    /// Why the run stopped
+    pub termination: String,
+    pub instructions: usize,
+    pub assertions: Vec<Outcome>,
+}
+
+impl GradeReport {
+    pub fn all_passed(&self) -> bool {
+        self.failed == 0
+    }
+
+    /// The report in the Test Anything Protocol, one test per assertion
+    pub fn render_tap(&self) -> String {
+        let mut text = format!("TAP version 13\n1..{}\n", self.assertions.len());
+        for (index, outcome) in self.assertions.iter().enumerate() {
+            text.push_str(&format!(
+                "{} {} - line {}: {}\n",
+                if outcome.passed { "ok" } else { "not ok" },
+                index + 1,
+                outcome.line,
+                outcome.assertion
+            ));
+            if !outcome.passed {
+                text.push_str(&format!("  # got {}\n", outcome.actual));
+            }
+        }
+        text.push_str(&format!(
+            "# {} of {} assertions passed; {} after {} instructions\n",
+            self.passed,
+            self.assertions.len(),
+            self.termination,
+            self.instructions
+        ));
+        text
+    }
+}
+
+/// Check each expectation against a simulator that has finished running.
+/// `symbols` resolves labels in memory addresses.
+pub fn evaluate(
+    expectations: &[Expectation],
+    simulator: &Simulator,
+    symbols: &BTreeMap<String, u32>,
+) -> GradeReport {
+    let mut report = GradeReport {
+        termination: simulator
+            .termination_reason
+            .map_or_else(|| "stopped".to_string(), |reason| reason.to_string()),
+        instructions: simulator.step_count,
+        ..GradeReport::default()
+    };
+    for expectation in expectations {
+        let (passed, actual) = check(expectation, simulator, symbols);
+        if passed {
+            report.passed += 1;
+        } else {
+            report.failed += 1;
+        }
+        report.assertions.push(Outcome {
+            line: expectation.line,
+            assertion: expectation.text.clone(),
+            passed,
+            actual,
+        });
+    }
+    report
+}
+
+fn check(
+    expectation: &Expectation,
+    simulator: &Simulator,
+    symbols: &BTreeMap<String, u32>,
+) -> (bool, String) {
+    let value = match &expectation.target {
+        Target::Register(number) => Ok(simulator.registers.read(*number)),
+        Target::Hi => Ok(simulator.registers.hi),
+        Target::Lo => Ok(simulator.registers.lo),
+        Target::Memory(address) => {
+            let address = match address {
+                Address::Absolute(address) => Some(*address),
+                Address::Symbol(symbol, offset) => symbols
+                    .get(symbol)
+                    .map(|address| address.wrapping_add(*offset as u32)),
+            };
+            match address {
+                Some(address) => {
+                    let memory = &simulator.memory;
+                    memory
+                        .read_word(memory.translate_address(address as usize))
+                        .map_err(|error| format!("no value ({})", error))
+                },
+                None => Err("no value (undefined label)".to_string()),
+            }
+        },
+        Target::Output => {
+            let output = simulator
+                .captured_output
+                .as_deref()
+                .map(String::from_utf8_lossy)
+                .unwrap_or_default();
+            return match &expectation.expected {
+                Expected::Text(text) => (output == text.as_str(), format!("{:?}", output)),
+                Expected::Value { .. } => (false, format!("{:?}", output)),
+            };
+        },
+    };
+    match (value, &expectation.expected) {
+        (
+            Ok(value),
+            Expected::Value {
+                value: expected,
+                hex,
+            },
+        ) => {
+            let actual = if *hex {
+                format!("0x{:x}", value)
+            } else {
+                (value as i32).to_string()
+            };
+            (value == *expected, actual)
+        },
+        (Ok(value), Expected::Text(_)) => (false, value.to_string()),
+        (Err(error), _) => (false, error),
+    }
+}
+
+/// Assemble annotated source, run it on the functional simulator within
+/// `limits` and grade the outcome
+pub fn grade_source(source: &str, limits: RunLimits) -> Result<GradeReport, GradeError> {
+    grade_with_output(source, limits).map(|(report, _)| report)
+}
+
+/// Like `grade_source`, but also return everything the program printed
+pub fn grade_with_output(
+    source: &str,
+    limits: RunLimits,
+) -> Result<(GradeReport, String), GradeError> {
+    let expectations = parse_expectations(source)?;
+    let mut assembler = Assembler::new();
+    let program = assembler.assemble_program(source)?;
+    let image = ProgramImage {
+        entry_point: program.entry,
+        symbols: program.symbols.clone(),
+        ..ProgramImage::from_sections(&assembler.sections())
+    };
+
+    let mut simulator = Simulator::from_image(&image)?;
+    simulator.limits = limits;
+    simulator.captured_output = Some(Vec::new());
+    // The report is the only thing the grade command prints
+    simulator.quiet = true;
+    // A faulting program is graded on the state it reached
+    let _ = simulator.run();
+    let report = evaluate(&expectations, &simulator, &program.symbols);
+    let output = simulator
+        .captured_output
+        .as_deref()
+        .map(String::from_utf8_lossy)
+        .unwrap_or_default()
+        .into_owned();
+    Ok((report, output))
+}
@@ -0,0 +1,289 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// limits.rs
+//
+// This file contains the run limits shared by the functional and timing
+// simulators. It defines the instruction, cycle, wall-clock and host memory
+// budgets a run may use, the quotas on what a program may do through
+// syscalls, the accounting of host memory a simulator holds and the reason
+// a run terminated.
+
+use std::fmt;
+use std::ops::{Add, AddAssign};
+use std::time::{Duration, Instant};
+
+/// Default instruction budget for a single run
+pub const DEFAULT_MAX_INSTRUCTIONS: usize = 1_000_000;
+/// Default cycle budget for a single run
+pub const DEFAULT_MAX_CYCLES: usize = 1_000_000;
+/// Instructions or cycles between checks of the host memory cap
+pub const MEMORY_CHECK_INTERVAL: usize = 1024;
+
+/// Why a simulation run stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerminationReason {
+    /// The program requested exit through a syscall
+    Exited,
+    /// The program ran off the end of its code (trailing NOPs or PC out of range)
+    Halted,
+    /// A `break` instruction or a user breakpoint was hit
+    Breakpoint,
+    /// An instruction could not be decoded
+    InvalidInstruction,
+    /// A memory access or jump target was out of bounds
+    MemoryFault,
+    /// A trap instruction's condition held
+    Trap,
+    /// The instruction budget was used up
+    InstructionLimit,
+    /// The cycle budget was used up
+    CycleLimit,
+    /// The wall-clock timeout expired
+    Timeout,
+    /// The simulator's host memory grew past its cap
+    MemoryLimit,
+    /// A syscall would have gone past one of the syscall quotas
+    QuotaExceeded,
+}
+
+impl TerminationReason {
+    /// Whether the program itself went wrong, rather than stopping or
+    /// running out of a budget
+    pub fn is_fault(self) -> bool {
+        matches!(
+            self,
+            TerminationReason::InvalidInstruction
+                | TerminationReason::MemoryFault
+                | TerminationReason::Trap
+        )
+    }
+}
+
+impl fmt::Display for TerminationReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            TerminationReason::Exited => "program exited",
+            TerminationReason::Halted => "program halted",
+            TerminationReason::Breakpoint => "breakpoint",
+            TerminationReason::InvalidInstruction => "invalid instruction",
+            TerminationReason::MemoryFault => "memory fault",
+            TerminationReason::Trap => "trap",
+            TerminationReason::InstructionLimit => "instruction limit reached",
+            TerminationReason::CycleLimit => "cycle limit reached",
+            TerminationReason::Timeout => "timeout expired",
+            TerminationReason::MemoryLimit => "memory limit reached",
+            TerminationReason::QuotaExceeded => "syscall quota exceeded",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// Budgets that bound a single simulation run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunLimits {
+    pub max_instructions: usize,
+    pub max_cycles: usize,
+    pub timeout: Option<Duration>,
+    /// Host memory the simulator may hold, in bytes
+    pub max_memory: Option<usize>,
+    pub quotas: SyscallQuotas,
+}
+
+impl Default for RunLimits {
+    fn default() -> Self {
+        Self {
+            max_instructions: DEFAULT_MAX_INSTRUCTIONS,
+            max_cycles: DEFAULT_MAX_CYCLES,
+            timeout: None,
+            max_memory: None,
+            quotas: SyscallQuotas::default(),
+        }
+    }
+}
+
+impl RunLimits {
+    /// Check the budgets, returning the reason to stop if one has run out
+    pub fn exceeded(
+        &self,
+        instructions: usize,
+        cycles: usize,
+        started: Instant,
+    ) -> Option<TerminationReason> {
+        if instructions >= self.max_instructions {
+            return Some(TerminationReason::InstructionLimit);
+        }
+        if cycles >= self.max_cycles {
+            return Some(TerminationReason::CycleLimit);
+        }
+        match self.timeout {
+            Some(timeout) if started.elapsed() >= timeout => Some(TerminationReason::Timeout),
+            _ => None,
+        }
+    }
+
+    /// Check a simulator's host memory against the cap, describing the
+    /// overrun if there is one
+    pub fn check_memory(&self, usage: &MemoryUsage) -> Result<(), String> {
+        match self.max_memory {
+            Some(max_memory) if usage.total() > max_memory => Err(format!(
+                "host memory {} exceeds the cap of {}",
+                usage,
+                format_bytes(max_memory)
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Caps on what a program may do through syscalls in one run, so untrusted
+/// code such as a grading submission cannot flood the output or the disk
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SyscallQuotas {
+    /// Bytes the print syscalls and writes to stdout or stderr may produce
+    pub max_output: Option<usize>,
+    /// Bytes the write syscall may send to files
+    pub max_file_write: Option<usize>,
+    /// Bytes `sbrk` may grow the heap by
+    pub max_heap: Option<usize>,
+}
+
+/// What a program has done through syscalls in the current run, in bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SyscallUsage {
+    pub output: usize,
+    pub file_write: usize,
+    pub heap: usize,
+}
+
+impl SyscallQuotas {
+    /// Check the usage against the quotas, describing the first one it is
+    /// over
+    pub fn check(&self, usage: &SyscallUsage) -> Result<(), String> {
+        let checks = [
+            ("output", usage.output, self.max_output),
+            ("file write", usage.file_write, self.max_file_write),
+            ("heap", usage.heap, self.max_heap),
+        ];
+        for (name, used, quota) in checks {
+            match quota {
+                Some(quota) if used > quota => {
+                    return Err(format!(
+                        "{} of {} exceeds the quota of {}",
+                        name,
+                        format_bytes(used),
+                        format_bytes(quota)
+                    ))
+                },
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Host memory held by a simulator, in bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Simulated memory, counting every copy of the image
+    pub memory: usize,
+    /// Cache lines and their replacement state
+    pub caches: usize,
+    /// Timelines, pipeline histories, access traces and other recordings
+    pub traces: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.memory + self.caches + self.traces
+    }
+}
+
+impl Add for MemoryUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            memory: self.memory + other.memory,
+            caches: self.caches + other.caches,
+            traces: self.traces + other.traces,
+        }
+    }
+}
+
+impl AddAssign for MemoryUsage {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl fmt::Display for MemoryUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (memory {}, caches {}, traces {})",
+            format_bytes(self.total()),
+            format_bytes(self.memory),
+            format_bytes(self.caches),
+            format_bytes(self.traces)
+        )
+    }
+}
+
+/// Format a byte count with a binary unit, such as `1.5 MiB`
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Parse a byte count such as `65536`, `64K`, `512M` or `2GiB`. Units are
+/// binary whether or not they end in `iB`.
+pub fn parse_byte_size(text: &str) -> Result<usize, String> {
+    let text = text.trim();
+    let digits = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(digits);
+    let number: usize = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", text))?;
+    let shift = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        "T" | "TB" | "TIB" => 40,
+        _ => return Err(format!("invalid size unit in '{}'", text)),
+    };
+    1usize
+        .checked_shl(shift)
+        .and_then(|unit| number.checked_mul(unit))
+        .ok_or_else(|| format!("size '{}' is too large", text))
+}
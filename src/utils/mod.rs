@@ -22,8 +22,17 @@
 // mod.rs
 //
 // This file is the module declaration for the utils module.
-// It re-exports the logger, parser, and syscall modules.
+// It re-exports the clock, crash_dump, examples, grading, limits, logger, parser, progress,
+// schema, syscall and throttle modules.
 
+pub mod clock;
+pub mod crash_dump;
+pub mod examples;
+pub mod grading;
+pub mod limits;
 pub mod logger;
 pub mod parser;
+pub mod progress;
+pub mod schema;
 pub mod syscall;
+pub mod throttle;
@@ -0,0 +1,122 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// progress.rs
+//
+// This file contains periodic progress reporting for long simulations.
+// A ProgressReporter is polled from the simulation loop and invokes its
+// callback at most once per interval with the instructions executed, the
+// simulated cycles and the simulation speed.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Default time between progress reports
+pub const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A snapshot of how far a run has progressed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProgressReport {
+    pub instructions: usize,
+    pub cycles: usize,
+    pub elapsed: Duration,
+}
+
+impl ProgressReport {
+    /// Simulation speed in millions of instructions per second
+    pub fn mips(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.instructions as f64 / seconds / 1_000_000.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for ProgressReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[progress] {} instructions, {} cycles, {:.1}s elapsed, {:.3} MIPS",
+            self.instructions,
+            self.cycles,
+            self.elapsed.as_secs_f64(),
+            self.mips()
+        )
+    }
+}
+
+pub type ProgressCallback = Box<dyn FnMut(&ProgressReport)>;
+
+/// Calls a progress callback at most once per interval
+pub struct ProgressReporter {
+    interval: Duration,
+    started: Instant,
+    last_report: Instant,
+    callback: ProgressCallback,
+}
+
+impl ProgressReporter {
+    pub fn new(interval: Duration, callback: ProgressCallback) -> Self {
+        let now = Instant::now();
+        Self {
+            interval,
+            started: now,
+            last_report: now,
+            callback,
+        }
+    }
+
+    /// A reporter that writes each report as one line on stderr
+    pub fn stderr(interval: Duration) -> Self {
+        Self::new(interval, Box::new(|report| eprintln!("{}", report)))
+    }
+
+    /// Restart the clock at the beginning of a run
+    pub fn start(&mut self) {
+        self.started = Instant::now();
+        self.last_report = self.started;
+    }
+
+    /// Report progress if the interval has passed since the last report
+    pub fn update(&mut self, instructions: usize, cycles: usize) {
+        let now = Instant::now();
+        if now.duration_since(self.last_report) >= self.interval {
+            self.last_report = now;
+            self.report(instructions, cycles, now);
+        }
+    }
+
+    /// Report the final totals at the end of a run
+    pub fn finish(&mut self, instructions: usize, cycles: usize) {
+        self.report(instructions, cycles, Instant::now());
+    }
+
+    fn report(&mut self, instructions: usize, cycles: usize, now: Instant) {
+        let report = ProgressReport {
+            instructions,
+            cycles,
+            elapsed: now.duration_since(self.started),
+        };
+        (self.callback)(&report);
+    }
+}
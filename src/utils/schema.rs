@@ -0,0 +1,323 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// schema.rs
+//
+// This file contains the JSON Schemas of the files the simulator exports:
+// the statistics report, the instruction trace, the state snapshot and the
+// JSON pipeline history. Every file names its format and version in its
+// first two fields. A version only changes when fields are removed or
+// change meaning; new fields can appear in any release, so readers should
+// ignore fields they do not know.
+
+use crate::functional_simulator::snapshot::{SNAPSHOT_FORMAT, SNAPSHOT_VERSION};
+use crate::timing_simulator::branch_predictor::{PREDICTOR_FORMAT, PREDICTOR_VERSION};
+use crate::timing_simulator::history::{HISTORY_FORMAT, HISTORY_VERSION};
+use crate::timing_simulator::stats::{STATS_FORMAT, STATS_VERSION, TRACE_FORMAT, TRACE_VERSION};
+use serde_json::{json, Value};
+
+/// A kind of exported file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Artifact {
+    /// `timing --stats-json`
+    Stats,
+    /// `timing --trace`
+    Trace,
+    /// `functional --snapshot`
+    Snapshot,
+    /// `timing --pipeline-history` with a `.json` file
+    PipelineHistory,
+    /// `timing --save-predictor`
+    Predictor,
+}
+
+pub const ARTIFACTS: [Artifact; 5] = [
+    Artifact::Stats,
+    Artifact::Trace,
+    Artifact::Snapshot,
+    Artifact::PipelineHistory,
+    Artifact::Predictor,
+];
+
+impl Artifact {
+    /// Name used by `--schema`
+    pub fn name(self) -> &'static str {
+        match self {
+            Artifact::Stats => "stats",
+            Artifact::Trace => "trace",
+            Artifact::Snapshot => "snapshot",
+            Artifact::PipelineHistory => "pipeline-history",
+            Artifact::Predictor => "predictor",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ARTIFACTS
+            .iter()
+            .copied()
+            .find(|artifact| artifact.name() == name)
+    }
+
+    /// Value of the `format` field in files of this kind
+    pub fn format(self) -> &'static str {
+        match self {
+            Artifact::Stats => STATS_FORMAT,
+            Artifact::Trace => TRACE_FORMAT,
+            Artifact::Snapshot => SNAPSHOT_FORMAT,
+            Artifact::PipelineHistory => HISTORY_FORMAT,
+            Artifact::Predictor => PREDICTOR_FORMAT,
+        }
+    }
+
+    pub fn version(self) -> u32 {
+        match self {
+            Artifact::Stats => STATS_VERSION,
+            Artifact::Trace => TRACE_VERSION,
+            Artifact::Snapshot => SNAPSHOT_VERSION,
+            Artifact::PipelineHistory => HISTORY_VERSION as u32,
+            Artifact::Predictor => PREDICTOR_VERSION,
+        }
+    }
+
+    /// JSON Schema (draft 2020-12) of the current version
+    pub fn schema(self) -> Value {
+        let (title, properties, optional) = match self {
+            Artifact::Stats => (
+                "Timing statistics report",
+                json!({
+                    "termination_reason": { "type": ["string", "null"] },
+                    "total": interval_sample(),
+                    "intervals": { "type": "array", "items": interval_sample() },
+                }),
+                json!({
+                    "cpi_stack": cpi_stack(),
+                    "region_of_interest": object(json!({
+                        "passes": unsigned(),
+                        "stats": interval_sample(),
+                        "cpi_stack": cpi_stack(),
+                    })),
+                    "regions": {
+                        "type": "array",
+                        "items": object(json!({
+                            "name": { "type": "string" },
+                            "depth": unsigned(),
+                            "passes": unsigned(),
+                            "stats": interval_sample(),
+                            "cpi_stack": cpi_stack(),
+                        })),
+                    },
+                }),
+            ),
+            Artifact::Trace => (
+                "Per-instruction timing trace",
+                json!({
+                    "instructions": {
+                        "type": "array",
+                        "items": object(json!({
+                            "index": unsigned(),
+                            "pc": unsigned(),
+                            "word": unsigned(),
+                            "issue_cycle": unsigned(),
+                            "cycles": unsigned(),
+                            "stall_cycles": unsigned(),
+                            "mispredicted": { "type": "boolean" },
+                        })),
+                    },
+                }),
+                json!({
+                    "sampling": object(json!({
+                        "every": { "type": "integer", "minimum": 1 },
+                        "filter": { "enum": ["all", "memory", "branches"] },
+                        "last": { "type": ["integer", "null"], "minimum": 1 },
+                    })),
+                }),
+            ),
+            Artifact::Snapshot => (
+                "Architectural state snapshot",
+                json!({
+                    "pc": unsigned(),
+                    "instructions": unsigned(),
+                    "registers": words(32),
+                    "hi": unsigned(),
+                    "lo": unsigned(),
+                    "fp_registers": words(32),
+                    "fcsr": unsigned(),
+                    "cp0": words(32),
+                    "memory_size": unsigned(),
+                    "pages": {
+                        "type": "array",
+                        "items": object(json!({
+                            "address": unsigned(),
+                            "data": { "type": "string", "pattern": "^([0-9a-f]{2})*$" },
+                        })),
+                    },
+                }),
+                json!({}),
+            ),
+            Artifact::PipelineHistory => (
+                "Pipeline stage occupancy history",
+                json!({
+                    "stages": { "type": "array", "items": { "type": "string" } },
+                    "latencies": { "type": "array", "items": unsigned() },
+                    "entries": {
+                        "type": "array",
+                        "items": with_optional(
+                            object(json!({
+                                "id": unsigned(),
+                                "pc": unsigned(),
+                                "word": unsigned(),
+                                "fetch_cycle": unsigned(),
+                                "stalls": {
+                                    "type": "array",
+                                    "items": object(json!({
+                                        "stage": unsigned(),
+                                        "cycles": unsigned(),
+                                        "reason": { "enum": ["data", "cache", "control"] },
+                                    })),
+                                },
+                            })),
+                            json!({ "squashed": unsigned() }),
+                        ),
+                    },
+                }),
+                json!({}),
+            ),
+            Artifact::Predictor => (
+                "Trained branch predictor tables",
+                json!({
+                    "local": {
+                        "type": "array",
+                        "items": object(json!({
+                            "pc": unsigned(),
+                            "counter": counter(),
+                        })),
+                    },
+                    "global_history": { "type": "integer", "minimum": 0, "maximum": 15 },
+                    "global": {
+                        "type": "array",
+                        "items": counter(),
+                        "minItems": 16,
+                        "maxItems": 16,
+                    },
+                    "targets": {
+                        "type": "array",
+                        "items": object(json!({
+                            "pc": unsigned(),
+                            "target": unsigned(),
+                        })),
+                    },
+                }),
+                json!({}),
+            ),
+        };
+
+        let mut schema = with_optional(object(properties), optional);
+        schema["properties"]["format"] = json!({ "const": self.format() });
+        schema["properties"]["version"] = json!({ "const": self.version() });
+        schema["required"]
+            .as_array_mut()
+            .expect("required is an array")
+            .splice(0..0, [json!("format"), json!("version")]);
+        schema["$schema"] = json!("https://json-schema.org/draft/2020-12/schema");
+        schema["$id"] = json!(format!(
+            "urn:vmips-rust:{}:{}",
+            self.format(),
+            self.version()
+        ));
+        schema["title"] = json!(title);
+        schema
+    }
+}
+
+/// Schemas of every artifact, keyed by name
+pub fn all_schemas() -> Value {
+    let schemas = ARTIFACTS
+        .iter()
+        .map(|artifact| (artifact.name().to_string(), artifact.schema()))
+        .collect();
+    Value::Object(schemas)
+}
+
+// An object whose listed properties are all required
+fn object(properties: Value) -> Value {
+    let required: Vec<Value> = properties
+        .as_object()
+        .map(|map| map.keys().map(|key| json!(key)).collect())
+        .unwrap_or_default();
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+// Add properties an object may leave out
+fn with_optional(mut schema: Value, properties: Value) -> Value {
+    if let (Some(target), Value::Object(properties)) =
+        (schema["properties"].as_object_mut(), properties)
+    {
+        target.extend(properties);
+    }
+    schema
+}
+
+fn unsigned() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+// A 2-bit saturating counter
+fn counter() -> Value {
+    json!({ "type": "integer", "minimum": 0, "maximum": 3 })
+}
+
+fn words(count: usize) -> Value {
+    json!({ "type": "array", "items": unsigned(), "minItems": count, "maxItems": count })
+}
+
+fn cpi_stack() -> Value {
+    object(json!({
+        "instructions": unsigned(),
+        "cycles": unsigned(),
+        "cpi": { "type": "number" },
+        "base": { "type": "number" },
+        "branch": { "type": "number" },
+        "icache": { "type": "number" },
+        "dcache": { "type": "number" },
+        "structural": { "type": "number" },
+        "raw": { "type": "number" },
+    }))
+}
+
+fn interval_sample() -> Value {
+    object(json!({
+        "index": unsigned(),
+        "start_instruction": unsigned(),
+        "start_cycle": unsigned(),
+        "instructions": unsigned(),
+        "cycles": unsigned(),
+        "cpi": { "type": "number" },
+        "icache_accesses": unsigned(),
+        "icache_misses": unsigned(),
+        "icache_miss_rate": { "type": "number" },
+        "dcache_accesses": unsigned(),
+        "dcache_misses": unsigned(),
+        "dcache_miss_rate": { "type": "number" },
+        "branch_mispredictions": unsigned(),
+        "stalls": unsigned(),
+        "branch_stalls": unsigned(),
+    }))
+}
@@ -1,116 +1,211 @@
 // syscall.rs
+use crate::functional_simulator::guest;
+use crate::functional_simulator::instructions::NextPc;
 use crate::functional_simulator::memory::Memory;
 use crate::functional_simulator::registers::Registers;
 use log::{error, info};
-use std::io::Read;
+use std::io::{self, BufRead, Read, Write};
+
+/// Copy $a2 bytes from $a1 to $a0, like memmove
+pub const SYSCALL_MEMCPY: u32 = 70;
+/// Fill $a2 bytes at $a0 with the low byte of $a1
+pub const SYSCALL_MEMSET: u32 = 71;
+/// Write back and invalidate every cache
+pub const SYSCALL_CACHE_FLUSH: u32 = 72;
+/// Write back and invalidate the data cache line holding $a0
+pub const SYSCALL_CACHE_FLUSH_LINE: u32 = 73;
+/// Begin the region of interest the timing model collects statistics over
+pub const SYSCALL_REGION_BEGIN: u32 = 74;
+/// End the region of interest
+pub const SYSCALL_REGION_END: u32 = 75;
+/// Enter the region named by the string at $a0
+pub const SYSCALL_NAMED_REGION_BEGIN: u32 = 76;
+/// Leave the region named by the string at $a0
+pub const SYSCALL_NAMED_REGION_END: u32 = 77;
+/// Switch the timing model's TLBs to the address space ID in $a0
+pub const SYSCALL_SET_ASID: u32 = 78;
+/// Write the simulated time as seconds and microseconds to the two words
+/// at $a0, like gettimeofday
+pub const SYSCALL_GET_TIME_OF_DAY: u32 = 79;
+
+/// Whether a syscall reads or advances the simulated clock, which the
+/// timing model keeps as well as the functional one
+pub fn is_time_syscall(number: u32) -> bool {
+    matches!(number, 30 | 31 | 32 | SYSCALL_GET_TIME_OF_DAY)
+}
+
+/// The memory a memcpy or memset syscall reads and writes, so the timing
+/// model can charge it by cache line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockTransfer {
+    /// Where a copy reads from; a fill reads nothing
+    pub source: Option<u32>,
+    pub destination: u32,
+    pub length: u32,
+}
+
+impl BlockTransfer {
+    /// The transfer a syscall makes with these registers, if it is a
+    /// memcpy or memset
+    pub fn of(registers: &Registers) -> Option<Self> {
+        let source = match registers.read(2) {
+            SYSCALL_MEMCPY => Some(registers.read(5)),
+            SYSCALL_MEMSET => None,
+            _ => return None,
+        };
+        Some(Self {
+            source,
+            destination: registers.read(4),
+            length: registers.read(6),
+        })
+    }
+
+    /// The address of each line of `line_size` bytes the transfer reads,
+    /// then of each it writes, and whether it is written
+    pub fn lines(&self, line_size: u32) -> Vec<(u32, bool)> {
+        let mut lines = Vec::new();
+        if self.length == 0 {
+            return lines;
+        }
+        let blocks = [(self.source, false), (Some(self.destination), true)];
+        for &(start, store) in &blocks {
+            if let Some(start) = start {
+                let first = start / line_size * line_size;
+                let last = start.saturating_add(self.length - 1) / line_size * line_size;
+                lines.extend(
+                    (first..=last)
+                        .step_by(line_size as usize)
+                        .map(|line| (line, store)),
+                );
+            }
+        }
+        lines
+    }
+}
+
+// Stdin is only locked while a read syscall reads it
+fn read_line(input: Option<&mut dyn BufRead>, line: &mut String) -> io::Result<usize> {
+    match input {
+        Some(input) => input.read_line(line),
+        None => io::stdin().read_line(line),
+    }
+}
+
+fn read_exact(input: Option<&mut dyn BufRead>, buffer: &mut [u8]) -> io::Result<()> {
+    match input {
+        Some(input) => input.read_exact(buffer),
+        None => io::stdin().read_exact(buffer),
+    }
+}
 
 /// Handles MIPS system calls using the ABI conventions.
-/// Returns Some(address) for branches/jumps, None for regular execution.
-pub fn handle_syscall(registers: &mut Registers, memory: &mut Memory) -> Option<u32> {
+/// Returns `NextPc::Halt` for the exit syscalls and `NextPc::Next` otherwise.
+pub fn handle_syscall(registers: &mut Registers, memory: &mut Memory) -> NextPc {
+    handle_syscall_with_output(registers, memory, &mut io::stdout())
+}
+
+/// Handles a system call like `handle_syscall`, writing what the program
+/// prints to `output` instead of stdout
+pub fn handle_syscall_with_output(
+    registers: &mut Registers,
+    memory: &mut Memory,
+    output: &mut dyn Write,
+) -> NextPc {
+    handle_syscall_with_io(registers, memory, None, output)
+}
+
+/// Handles a system call like `handle_syscall_with_output`, reading what the
+/// program reads from `input`, or from stdin when it is `None`
+pub fn handle_syscall_with_io(
+    registers: &mut Registers,
+    memory: &mut Memory,
+    input: Option<&mut dyn BufRead>,
+    output: &mut dyn Write,
+) -> NextPc {
     let syscall_num = registers.read(2); // v0 contains syscall number
 
     match syscall_num {
         1 => {
             // print_int: Print integer in $a0
             let value = registers.read(4);
-            println!("{}", value as i32);
-            None
+            let _ = writeln!(output, "{}", value as i32);
+            NextPc::Next
         },
         2 => {
             // print_float: Print float in $f12
             let value = registers.read_float(12);
-            println!("{}", value);
-            None
+            let _ = writeln!(output, "{}", value);
+            NextPc::Next
         },
         3 => {
             // print_double: Print double in $f12 (treated as float for simplicity)
             let value = registers.read_float(12);
-            println!("{}", value);
-            None
+            let _ = writeln!(output, "{}", value);
+            NextPc::Next
         },
         4 => {
             // print_string: Print null-terminated string at address in $a0
-            let addr = registers.read(4) as usize;
-            let mut string = String::new();
-            let mut current = addr;
-
-            // Read bytes until null terminator or memory boundary
-            while let Some(byte) = memory.read_byte(current) {
-                if byte == 0 {
-                    break;
-                }
-                string.push(byte as char);
-                current += 1;
+            let addr = registers.read(4);
+            match guest::read_c_string(memory, addr, memory.size) {
+                Ok(bytes) => {
+                    let _ = writeln!(output, "{}", String::from_utf8_lossy(&bytes));
+                },
+                Err(e) => error!("Failed to read string: {}", e),
             }
-
-            println!("{}", string);
-            None
+            NextPc::Next
         },
         5 => {
             // read_int: Read integer from stdin into $v0
-            let mut input = String::new();
-            if let Err(e) = std::io::stdin().read_line(&mut input) {
+            let mut line = String::new();
+            if let Err(e) = read_line(input, &mut line) {
                 error!("Failed to read from stdin: {}", e);
-                return None;
+                return NextPc::Next;
             }
 
-            let value = input.trim().parse::<i32>().unwrap_or(0);
+            let value = line.trim().parse::<i32>().unwrap_or(0);
             registers.write(2, value as u32);
-            None
+            NextPc::Next
         },
         6 => {
             // read_float: Read float from stdin into $f0
-            let mut input = String::new();
-            if let Err(e) = std::io::stdin().read_line(&mut input) {
+            let mut line = String::new();
+            if let Err(e) = read_line(input, &mut line) {
                 error!("Failed to read from stdin: {}", e);
-                return None;
+                return NextPc::Next;
             }
 
-            let value = input.trim().parse::<f32>().unwrap_or(0.0);
+            let value = line.trim().parse::<f32>().unwrap_or(0.0);
             registers.write_float(0, value);
-            None
+            NextPc::Next
         },
         7 => {
             // read_double: Read double from stdin into $f0 (treated as float)
-            let mut input = String::new();
-            if let Err(e) = std::io::stdin().read_line(&mut input) {
+            let mut line = String::new();
+            if let Err(e) = read_line(input, &mut line) {
                 error!("Failed to read from stdin: {}", e);
-                return None;
+                return NextPc::Next;
             }
 
-            let value = input.trim().parse::<f32>().unwrap_or(0.0);
+            let value = line.trim().parse::<f32>().unwrap_or(0.0);
             registers.write_float(0, value);
-            None
+            NextPc::Next
         },
         8 => {
             // read_string: Read string from stdin into memory at address in $a0
-            let addr = registers.read(4) as usize;
+            let addr = registers.read(4);
             let max_length = registers.read(5) as usize;
 
-            let mut input = String::new();
-            if let Err(e) = std::io::stdin().read_line(&mut input) {
+            let mut line = String::new();
+            if let Err(e) = read_line(input, &mut line) {
                 error!("Failed to read from stdin: {}", e);
-                return None;
+                return NextPc::Next;
             }
 
-            // Truncate input to max_length-1 (leave room for null terminator)
-            let input = if input.len() > max_length - 1 {
-                input[..max_length - 1].to_string()
-            } else {
-                input
-            };
-
-            // Write string to memory
-            for (i, byte) in input.bytes().enumerate() {
-                if i >= max_length - 1 {
-                    break;
-                }
-                memory.write_byte(addr + i, byte);
+            // Truncated to max_length-1 bytes, leaving room for the terminator
+            if let Err(e) = guest::write_c_string(memory, addr, line.as_bytes(), max_length) {
+                error!("Failed to store string: {}", e);
             }
-
-            // Add null terminator
-            memory.write_byte(addr + std::cmp::min(input.len(), max_length - 1), 0);
-            None
+            NextPc::Next
         },
         9 => {
             // sbrk: Allocate heap memory
@@ -127,46 +222,43 @@ pub fn handle_syscall(registers: &mut Registers, memory: &mut Memory) -> Option<
                 // Out of memory
                 registers.write(2, 0);
             }
-            None
+            NextPc::Next
         },
         10 => {
             // exit: End program
-            Some(0xFFFFFFFF) // Special value to indicate program termination
+            NextPc::Halt
         },
         11 => {
             // print_char: Print character in $a0
             let value = registers.read(4) as u8;
-            print!("{}", value as char);
-            None
+            let _ = write!(output, "{}", value as char);
+            NextPc::Next
         },
         12 => {
             // read_char: Read character from stdin into $v0
             let mut buffer = [0; 1];
-            if let Err(e) = std::io::stdin().read_exact(&mut buffer) {
+            if let Err(e) = read_exact(input, &mut buffer) {
                 error!("Failed to read character from stdin: {}", e);
                 registers.write(2, 0);
-                return None;
+                return NextPc::Next;
             }
             registers.write(2, buffer[0] as u32);
-            None
+            NextPc::Next
         },
         13 => {
             // open: Open file
-            let filename_addr = registers.read(4) as usize;
+            let filename_addr = registers.read(4);
             let flags = registers.read(5);
             let mode = registers.read(6);
 
-            // Read filename from memory as null-terminated string
-            let mut filename = String::new();
-            let mut current = filename_addr;
-
-            while let Some(byte) = memory.read_byte(current) {
-                if byte == 0 {
-                    break;
-                }
-                filename.push(byte as char);
-                current += 1;
-            }
+            let filename = match guest::read_c_string(memory, filename_addr, memory.size) {
+                Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                Err(e) => {
+                    error!("Failed to read file name: {}", e);
+                    registers.write(2, u32::MAX);
+                    return NextPc::Next;
+                },
+            };
 
             info!(
                 "Syscall 13 (open): Opening file '{}' with flags {}, mode {}",
@@ -176,7 +268,7 @@ pub fn handle_syscall(registers: &mut Registers, memory: &mut Memory) -> Option<
             // For now, return a dummy file descriptor
             // In a real implementation, this would maintain a file descriptor table
             registers.write(2, 3); // Return fd 3 (after stdin, stdout, stderr)
-            None
+            NextPc::Next
         },
         14 => {
             // read: Read from file
@@ -193,13 +285,14 @@ pub fn handle_syscall(registers: &mut Registers, memory: &mut Memory) -> Option<
             // In a real implementation, this would read from the actual file
             let bytes_read = count.min(128); // Simulate reading at most 128 bytes
 
-            for i in 0..bytes_read {
-                // Fill with placeholder data
-                memory.write_byte(buffer_addr + i, (i % 256) as u8);
+            // Fill with placeholder data
+            let data: Vec<u8> = (0..bytes_read).map(|i| (i % 256) as u8).collect();
+            if let Err(e) = memory.write_block(buffer_addr, &data) {
+                error!("Failed to store read data: {}", e);
             }
 
             registers.write(2, bytes_read as u32); // Return number of bytes read
-            None
+            NextPc::Next
         },
         15 => {
             // write: Write to file
@@ -215,18 +308,18 @@ pub fn handle_syscall(registers: &mut Registers, memory: &mut Memory) -> Option<
             // For console output (fd=1), actually print to console
             if fd == 1 || fd == 2 {
                 // stdout or stderr
-                let mut output = String::new();
-                for i in 0..count {
-                    if let Some(byte) = memory.read_byte(buffer_addr + i) {
-                        output.push(byte as char);
-                    }
+                match memory.read_block(buffer_addr, count) {
+                    Ok(bytes) => {
+                        let text: String = bytes.iter().map(|&byte| byte as char).collect();
+                        let _ = write!(output, "{}", text);
+                    },
+                    Err(e) => error!("Failed to read output buffer: {}", e),
                 }
-                print!("{}", output);
             }
 
             // Simulate successful write
             registers.write(2, count as u32); // Return number of bytes written
-            None
+            NextPc::Next
         },
         16 => {
             // close: Close file
@@ -236,53 +329,99 @@ pub fn handle_syscall(registers: &mut Registers, memory: &mut Memory) -> Option<
 
             // Simulate successful close
             registers.write(2, 0); // Success
-            None
+            NextPc::Next
         },
         17 => {
             // exit2: Exit with return value
             let return_code = registers.read(4);
             println!("Program terminated with exit code {}", return_code);
-            Some(0xFFFFFFFF) // Special value to indicate program termination
+            NextPc::Halt
         },
         30 => {
-            // Syscall 30: Get system time
-            use std::time::{SystemTime, UNIX_EPOCH};
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("Time went backwards")
-                .as_millis() as u32;
-
-            registers.write(2, now);
-            None
+            // Syscall 30: Get the simulated time in milliseconds. It comes
+            // from the cycle count, not the host clock, so runs replay exactly
+            let now = registers.hardware.elapsed_us() / 1000;
+            registers.write(2, now as u32);
+            NextPc::Next
         },
-        31 => {
-            // Syscall 31: Sleep for milliseconds in $a0
+        31 | 32 => {
+            // Syscall 31, and 32 as MARS numbers it: Sleep for milliseconds
+            // in $a0 by advancing the simulated clock
             let ms = registers.read(4);
-            std::thread::sleep(std::time::Duration::from_millis(ms as u64));
-            None
+            registers.hardware.sleep_ms(ms);
+            NextPc::Next
         },
         34 => {
             // print_hex: Print integer in $a0 as hex
             let value = registers.read(4);
-            println!("0x{:x}", value);
-            None
+            let _ = writeln!(output, "0x{:x}", value);
+            NextPc::Next
         },
         35 => {
             // print_bin: Print integer in $a0 as binary
             let value = registers.read(4);
-            println!("0b{:b}", value);
-            None
+            let _ = writeln!(output, "0b{:b}", value);
+            NextPc::Next
         },
         36 => {
             // print_uint: Print integer in $a0 as unsigned
             let value = registers.read(4);
-            println!("{}", value);
-            None
+            let _ = writeln!(output, "{}", value);
+            NextPc::Next
+        },
+        SYSCALL_MEMCPY | SYSCALL_MEMSET => {
+            // memcpy and memset: $v0 is the destination, or 0 if a byte
+            // of either block is out of range
+            let destination = registers.read(4);
+            let length = registers.read(6) as usize;
+            let bytes = if syscall_num == SYSCALL_MEMCPY {
+                memory.read_block(registers.read(5) as usize, length)
+            } else {
+                Ok(vec![registers.read(5) as u8; length])
+            };
+            match bytes.and_then(|bytes| memory.write_block(destination as usize, &bytes)) {
+                Ok(()) => registers.write(2, destination),
+                Err(e) => {
+                    error!("Syscall {} failed: {}", syscall_num, e);
+                    registers.write(2, 0);
+                },
+            }
+            NextPc::Next
+        },
+        SYSCALL_CACHE_FLUSH | SYSCALL_CACHE_FLUSH_LINE => {
+            // Cache control only matters to the timing model, which flushes
+            // its caches before the syscall executes
+            NextPc::Next
+        },
+        SYSCALL_REGION_BEGIN
+        | SYSCALL_REGION_END
+        | SYSCALL_NAMED_REGION_BEGIN
+        | SYSCALL_NAMED_REGION_END => {
+            // Region markers only matter to the timing model's statistics
+            NextPc::Next
+        },
+        SYSCALL_SET_ASID => {
+            // Only the timing model's TLBs have address spaces
+            NextPc::Next
+        },
+        SYSCALL_GET_TIME_OF_DAY => {
+            // gettimeofday: $v0 is 0, or -1 if the words are out of range
+            let address = registers.read(4) as usize;
+            let now = registers.hardware.elapsed_us();
+            let words = [(now / 1_000_000) as u32, (now % 1_000_000) as u32];
+            match memory.write_words(address, &words) {
+                Ok(()) => registers.write(2, 0),
+                Err(e) => {
+                    error!("Syscall {} failed: {}", syscall_num, e);
+                    registers.write(2, u32::MAX);
+                },
+            }
+            NextPc::Next
         },
         // Add more syscalls as needed
         _ => {
             println!("Unimplemented syscall: {}", syscall_num);
-            None
+            NextPc::Next
         },
     }
 }
@@ -0,0 +1,320 @@
+// Copyright (c) 2024 Mudit Bhargava
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+// throttle.rs
+//
+// This file contains throttling scenarios: scripts of how fast the simulated
+// core runs over simulated time, as in a chip that slows down when it gets
+// hot. The cycle counter read through `rdhwr` runs at a constant rate, the
+// fastest rate in the scenario, while the core executes cycles at the rate
+// in effect. Unthrottled code reads one tick per cycle; throttled code reads
+// more ticks for the same work, the way a program timing itself on real
+// hardware observes throttling.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write as _;
+
+// Microseconds in a millisecond, the unit of scenario times
+const US_PER_MS: u64 = 1000;
+
+/// A period of the scenario where the core runs at one rate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ThrottlePhase {
+    /// Simulated time the phase starts at, in microseconds
+    pub start_us: u64,
+    /// Core cycles executed per simulated millisecond
+    pub cycles_per_ms: u64,
+}
+
+/// Why a scenario could not be read
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScenarioError {
+    /// 1-based item or line the error is in
+    pub item: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "throttle scenario item {}: {}", self.item, self.message)
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+/// Rates the core runs at over simulated time
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThrottleScenario {
+    /// Phases in time order; the first starts at 0
+    pub phases: Vec<ThrottlePhase>,
+    /// Length of the schedule when it repeats, in microseconds. Without it
+    /// the last phase lasts forever.
+    pub repeat_us: Option<u64>,
+}
+
+// Parse a time in milliseconds, which may have a fractional part, to
+// microseconds
+fn parse_ms(text: &str) -> Result<u64, String> {
+    let ms: f64 = text
+        .parse()
+        .map_err(|_| format!("invalid time '{}'", text))?;
+    if !ms.is_finite() || ms < 0.0 {
+        return Err(format!("invalid time '{}'", text));
+    }
+    Ok((ms * US_PER_MS as f64).round() as u64)
+}
+
+impl ThrottleScenario {
+    /// The core always runs at `cycles_per_ms`
+    pub fn constant(cycles_per_ms: u64) -> Self {
+        Self {
+            phases: vec![ThrottlePhase {
+                start_us: 0,
+                cycles_per_ms,
+            }],
+            repeat_us: None,
+        }
+    }
+
+    /// Read a scenario. Items are separated by commas or newlines and `#`
+    /// starts a comment. `TIME:RATE` switches to RATE cycles per millisecond
+    /// at TIME milliseconds, and `repeat:PERIOD` restarts the schedule every
+    /// PERIOD milliseconds.
+    pub fn parse(text: &str) -> Result<Self, ScenarioError> {
+        let mut phases: Vec<ThrottlePhase> = Vec::new();
+        let mut repeat_us = None;
+        let items = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(|line| line.split(','))
+            .map(str::trim)
+            .filter(|item| !item.is_empty());
+        for (index, item) in items.enumerate() {
+            let error = |message: String| ScenarioError {
+                item: index + 1,
+                message,
+            };
+            if repeat_us.is_some() {
+                return Err(error("nothing can follow repeat".to_string()));
+            }
+            let (key, value) = item
+                .split_once(':')
+                .ok_or_else(|| error(format!("expected TIME:RATE, found '{}'", item)))?;
+            let (key, value) = (key.trim(), value.trim());
+            if key == "repeat" {
+                let period = parse_ms(value).map_err(error)?;
+                if phases.last().map_or(true, |phase| period <= phase.start_us) {
+                    return Err(error(
+                        "the repeat period must be after the last phase starts".to_string(),
+                    ));
+                }
+                repeat_us = Some(period);
+                continue;
+            }
+
+            let start_us = parse_ms(key).map_err(error)?;
+            let cycles_per_ms: u64 = value
+                .parse()
+                .ok()
+                .filter(|&rate| rate > 0)
+                .ok_or_else(|| error(format!("invalid rate '{}'", value)))?;
+            match phases.last() {
+                None if start_us != 0 => {
+                    return Err(error("the first phase must start at 0".to_string()))
+                },
+                Some(last) if start_us <= last.start_us => {
+                    return Err(error("phases must be in time order".to_string()))
+                },
+                _ => {},
+            }
+            phases.push(ThrottlePhase {
+                start_us,
+                cycles_per_ms,
+            });
+        }
+        if phases.is_empty() {
+            return Err(ScenarioError {
+                item: 1,
+                message: "no phases".to_string(),
+            });
+        }
+
+        let scenario = Self { phases, repeat_us };
+        // A phase too short to hold a cycle would never be left
+        for index in 0..scenario.phases.len() {
+            if scenario.phase_cycles(index) == Some(0) {
+                return Err(ScenarioError {
+                    item: index + 1,
+                    message: "the phase is too short to run a single cycle".to_string(),
+                });
+            }
+        }
+        Ok(scenario)
+    }
+
+    /// Rate the cycle counter runs at: the fastest rate in the scenario
+    pub fn counter_rate(&self) -> u64 {
+        self.phases
+            .iter()
+            .map(|phase| phase.cycles_per_ms)
+            .max()
+            .unwrap_or(1)
+    }
+
+    // Length of a phase in microseconds, or `None` for a last phase that
+    // lasts forever
+    fn phase_duration(&self, index: usize) -> Option<u64> {
+        let end = match self.phases.get(index + 1) {
+            Some(next) => next.start_us,
+            None => self.repeat_us?,
+        };
+        Some(end - self.phases[index].start_us)
+    }
+
+    // Core cycles a phase holds
+    fn phase_cycles(&self, index: usize) -> Option<u64> {
+        self.phase_duration(index)
+            .map(|duration| duration * self.phases[index].cycles_per_ms / US_PER_MS)
+    }
+}
+
+/// Time spent at one rate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Residency {
+    pub cycles: u64,
+    pub time_us: u64,
+}
+
+/// Follows a scenario as the core executes cycles, converting core cycles
+/// to simulated time and cycle counter ticks
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThrottleClock {
+    scenario: ThrottleScenario,
+    counter_rate: u64,
+    phase: usize,
+    // Core cycles, counter ticks and time when the current phase started
+    phase_cycles: u64,
+    phase_ticks: u64,
+    phase_start_us: u64,
+    cycles: u64,
+    // Completed phases, by rate
+    completed: BTreeMap<u64, Residency>,
+}
+
+impl ThrottleClock {
+    pub fn new(scenario: ThrottleScenario) -> Self {
+        Self {
+            counter_rate: scenario.counter_rate(),
+            scenario,
+            phase: 0,
+            phase_cycles: 0,
+            phase_ticks: 0,
+            phase_start_us: 0,
+            cycles: 0,
+            completed: BTreeMap::new(),
+        }
+    }
+
+    pub fn scenario(&self) -> &ThrottleScenario {
+        &self.scenario
+    }
+
+    fn rate(&self) -> u64 {
+        self.scenario.phases[self.phase].cycles_per_ms
+    }
+
+    /// Move to `cycles` core cycles since the start and return the cycle
+    /// counter. Cycles never go backwards; an earlier count is ignored.
+    pub fn advance_to(&mut self, cycles: u64) -> u64 {
+        self.cycles = self.cycles.max(cycles);
+        while let Some(length) = self.scenario.phase_cycles(self.phase) {
+            if self.cycles - self.phase_cycles < length {
+                break;
+            }
+            let duration = self.scenario.phase_duration(self.phase).unwrap_or(0);
+            let residency = self.completed.entry(self.rate()).or_default();
+            residency.cycles += length;
+            residency.time_us += duration;
+            self.phase_cycles += length;
+            self.phase_ticks += duration * self.counter_rate / US_PER_MS;
+            self.phase_start_us += duration;
+            self.phase = (self.phase + 1) % self.scenario.phases.len();
+        }
+        self.counter()
+    }
+
+    /// Cycle counter ticks so far
+    pub fn counter(&self) -> u64 {
+        self.phase_ticks + (self.cycles - self.phase_cycles) * self.counter_rate / self.rate()
+    }
+
+    /// Core cycles executed so far
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Simulated time so far in microseconds
+    pub fn elapsed_us(&self) -> u64 {
+        self.phase_start_us + (self.cycles - self.phase_cycles) * US_PER_MS / self.rate()
+    }
+
+    /// Cycles and time spent at each rate so far
+    pub fn residency(&self) -> BTreeMap<u64, Residency> {
+        let mut residency = self.completed.clone();
+        let current = residency.entry(self.rate()).or_default();
+        current.cycles += self.cycles - self.phase_cycles;
+        current.time_us += self.elapsed_us() - self.phase_start_us;
+        residency
+    }
+
+    /// A summary of the time spent at each rate
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Throttling: {} cycles in {:.3} ms of simulated time; the cycle counter reads {} at {} ticks/ms",
+            self.cycles,
+            self.elapsed_us() as f64 / US_PER_MS as f64,
+            self.counter(),
+            self.counter_rate
+        );
+        let _ = writeln!(out, "  cycles/ms        cycles     time (ms)");
+        for (rate, residency) in self.residency().iter().rev() {
+            let _ = writeln!(
+                out,
+                "  {:>9}  {:>12}  {:>12.3}",
+                rate,
+                residency.cycles,
+                residency.time_us as f64 / US_PER_MS as f64
+            );
+        }
+        out
+    }
+}
+
+/// The cycle counter after `cycles` core cycles, throttled if a clock is set
+pub fn cycle_counter(clock: &mut Option<ThrottleClock>, cycles: u64) -> u64 {
+    match clock {
+        Some(clock) => clock.advance_to(cycles),
+        None => cycles,
+    }
+}
@@ -43,13 +43,10 @@ fn test_functional_simulator_boundary_conditions() {
     simulator
         .memory
         .write_word_init(boundary_address, 0x12345678);
-    assert_eq!(
-        simulator.memory.read_word(boundary_address),
-        Some(0x12345678)
-    );
+    assert_eq!(simulator.memory.read_word(boundary_address), Ok(0x12345678));
 
     // Test reading beyond boundary
-    assert_eq!(simulator.memory.read_word(1024), None); // Should return None for out of bounds
+    assert!(simulator.memory.read_word(1024).is_err()); // Should fail for out of bounds
 }
 
 #[test]
@@ -165,12 +162,12 @@ fn test_memory_alignment_edge_cases() {
     let unaligned_addr = 1; // Not word-aligned
 
     // Memory should handle unaligned access gracefully
-    simulator.memory.write_byte(unaligned_addr, 0x42);
+    let _ = simulator.memory.write_byte(unaligned_addr, 0x42);
 
     // Reading from unaligned word address should work
     let result = simulator.memory.read_word(unaligned_addr);
-    // Should either return None or handle gracefully
-    assert!(result.is_some() || result.is_none());
+    // Should either return an error or handle gracefully
+    assert!(result.is_ok() || result.is_err());
 }
 
 #[test]
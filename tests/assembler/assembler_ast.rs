@@ -0,0 +1,86 @@
+// tests/assembler/assembler_ast.rs
+// Tests for the statements the assembler parses source into
+
+use vmips_rust::assembler::{Assembler, AssemblerError, Operand, Statement, StatementKind};
+
+#[test]
+fn test_parse_types_operands() {
+    let statements = Assembler::new()
+        .parse(
+            "
+.data
+message: .asciiz \"hi\\n\"   # greeting
+.text
+main:
+    lw $t0, table+4($sp)
+    addiu $t1, $t0, -8
+",
+        )
+        .unwrap();
+
+    assert_eq!(statements.len(), 6);
+    assert_eq!(statements[1].line, 3);
+    assert_eq!(statements[1].label.as_deref(), Some("message"));
+    assert_eq!(
+        statements[1].kind,
+        Some(StatementKind::Directive {
+            name: ".asciiz".to_string(),
+            operands: vec![Operand::String("hi\n".to_string())],
+        })
+    );
+    assert_eq!(statements[3].label.as_deref(), Some("main"));
+    assert_eq!(statements[3].kind, None);
+    assert_eq!(
+        statements[4].operands(),
+        &[
+            Operand::Register(8),
+            Operand::Memory {
+                offset: Some(Box::new(Operand::Symbol {
+                    name: "table".to_string(),
+                    offset: Some(4),
+                })),
+                base: 29,
+            },
+        ][..]
+    );
+    assert!(statements[5].is_instruction());
+}
+
+#[test]
+fn test_printed_statements_assemble_to_the_same_program() {
+    let source = include_str!("../../examples/programs/matrix_multiply.s");
+    let statements = Assembler::new().parse(source).unwrap();
+    let printed: Vec<String> = statements.iter().map(Statement::to_string).collect();
+    let printed = printed.join("\n");
+
+    let original = Assembler::new().assemble_program(source).unwrap();
+    let reprinted = Assembler::new().assemble_program(&printed).unwrap();
+    assert_eq!(reprinted.text, original.text);
+    assert_eq!(reprinted.data, original.data);
+
+    // Labels print on their own line, so compare what the statements do
+    let kinds = |statements: Vec<Statement>| -> Vec<StatementKind> {
+        statements
+            .into_iter()
+            .filter_map(|statement| statement.kind)
+            .collect()
+    };
+    assert_eq!(
+        kinds(Assembler::new().parse(&printed).unwrap()),
+        kinds(statements)
+    );
+}
+
+#[test]
+fn test_malformed_operand_reports_its_line() {
+    let error = Assembler::new()
+        .parse(".text\nmain:\n    add $t0, , $t1\n")
+        .unwrap_err();
+    assert!(matches!(error, AssemblerError::Operand(_, 3)), "{}", error);
+
+    // A label named like an instruction is still a label operand
+    let program = Assembler::new()
+        .assemble_program(".text\n    la $t1, b\n.data\nb: .word 7\n")
+        .unwrap();
+    assert_eq!(program.text.len(), 2);
+}
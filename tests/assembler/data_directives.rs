@@ -0,0 +1,79 @@
+// tests/assembler/data_directives.rs
+// Tests for the .float, .double and .dword data directives
+
+use vmips_rust::assembler::Assembler;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::loader::ProgramImage;
+
+// Data section of the assembler output, which follows the two size words
+fn data_section(source: &str) -> Vec<u8> {
+    let bytes = Assembler::new().assemble_string(source).unwrap();
+    let data_size = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    bytes[8..8 + data_size].to_vec()
+}
+
+#[test]
+fn test_float_and_double_encoding() {
+    let data = data_section(
+        "
+.data
+    .float 1.5, -0.25, 2, 6.02e23
+    .double 3.141592653589793, 1e-3
+",
+    );
+
+    let mut expected = Vec::new();
+    for value in [1.5f32, -0.25, 2.0, 6.02e23] {
+        expected.extend_from_slice(&value.to_bits().to_le_bytes());
+    }
+    for value in [std::f64::consts::PI, 1e-3] {
+        expected.extend_from_slice(&value.to_bits().to_le_bytes());
+    }
+    assert_eq!(data, expected);
+}
+
+#[test]
+fn test_dword_encoding() {
+    let data = data_section(
+        "
+.data
+    .dword 0x0123456789ABCDEF, -2, 7
+    .word 9
+",
+    );
+
+    let mut expected = Vec::new();
+    for value in [0x0123456789ABCDEFu64, -2i64 as u64, 7] {
+        expected.extend_from_slice(&value.to_le_bytes());
+    }
+    expected.extend_from_slice(&9u32.to_le_bytes());
+    assert_eq!(data, expected);
+
+    // Wide literals are only accepted where 64 bits fit
+    assert!(Assembler::new()
+        .assemble_string(".data\n    .word 0x123456789\n")
+        .is_err());
+    assert!(Assembler::new()
+        .assemble_string(".text\n    .float 1.0\n")
+        .is_err());
+}
+
+#[test]
+fn test_float_constants_load_into_fp_registers() {
+    let image = ProgramImage::from_assembly(
+        "
+.data
+    .float 1.25, 2.5
+.text
+    lui $t0, 0x1000
+    lwc1 $f0, 0($t0)
+    lwc1 $f1, 4($t0)
+    add.s $f2, $f0, $f1
+",
+    )
+    .unwrap();
+    let mut simulator = Simulator::from_image(&image).unwrap();
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.registers.read_float(2), 3.75);
+}
@@ -0,0 +1,110 @@
+// tests/assembler/dialect.rs
+// Tests for the MARS, SPIM and strict assembly dialects
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::assembler::{Assembler, AssemblerError, Dialect};
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::loader::ProgramImage;
+
+/// Written for MARS: `.globl`, `.eqv`, repeated values and the MARS time
+/// syscall
+const MARS_PROGRAM: &str = "
+        .globl main
+        .eqv COUNT 4
+        .eqv PRINT_INT 1
+        .data
+table:  .word 7:COUNT
+        .text
+main:   la $t0, table
+        lw $t1, 12($t0)
+        addi $t2, $zero, COUNT
+        add $a0, $t1, $t2
+        li $v0, PRINT_INT
+        syscall
+        li $v0, 30
+        syscall
+        li $v0, 10
+        syscall
+";
+
+fn assemble(dialect: Dialect, source: &str) -> Result<Assembler, AssemblerError> {
+    let mut assembler = Assembler::new().with_dialect(dialect);
+    assembler.assemble_program(source)?;
+    Ok(assembler)
+}
+
+#[test]
+fn test_mars_program_runs_unmodified() {
+    let assembler = assemble(Dialect::Mars, MARS_PROGRAM).unwrap();
+    assert_eq!(
+        assembler.warnings(),
+        ["line 15: syscall 30 returns the time in $v0; MARS returns it in $a0 and $a1"]
+    );
+
+    let image = ProgramImage::from_sections(&assembler.sections());
+    let mut simulator = FunctionalSimulator::from_image(&image).unwrap();
+    simulator.captured_output = Some(Vec::new());
+    simulator.run().unwrap();
+    assert_eq!(simulator.captured_output.unwrap(), b"11\n");
+
+    // The native dialect has neither .eqv nor repeated values
+    assert!(assemble(Dialect::Native, MARS_PROGRAM).is_err());
+}
+
+#[test]
+fn test_dialect_label_and_directive_rules() {
+    let twice = ".text\nloop: nop\nloop: nop\n";
+    assert!(assemble(Dialect::Native, twice).is_ok());
+    let error = assemble(Dialect::Spim, twice).err().unwrap().to_string();
+    assert_eq!(
+        error,
+        "Symbol error at line 3: loop is already defined in the spim dialect"
+    );
+    assert!(assemble(Dialect::Mars, ".text\nadd: nop\n").is_err());
+
+    // MARS and SPIM accept this assembler's own directives with a warning,
+    // strict refuses them
+    let rodata = ".rodata\nvalue: .word 1\n.text\nnop\n";
+    assert_eq!(
+        assemble(Dialect::Mars, rodata).unwrap().warnings()[0],
+        "line 1: .rodata is not a mars directive"
+    );
+    let error = assemble(Dialect::Strict, rodata).err().unwrap().to_string();
+    assert_eq!(
+        error,
+        "Unsupported feature at line 1: .rodata is not supported in the strict dialect"
+    );
+    assert!(assemble(Dialect::Spim, ".text\n.macro done\nnop\n").is_err());
+    assert!(assemble(Dialect::Native, ".text\n.macro done\nnop\n").is_ok());
+}
+
+#[test]
+fn test_cli_reports_syscall_incompatibilities() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("hex.s");
+    std::fs::write(
+        &input,
+        ".text\nli $a0, 255\nli $v0, 34\nsyscall\nli $v0, 10\nsyscall\n",
+    )
+    .unwrap();
+
+    Command::cargo_bin("main_assembler")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["assemble", "hex.s", "hex.bin", "--dialect", "spim"])
+        .assert()
+        .success()
+        .stdout(contains(
+            "Warning: line 4: syscall 34 is not a SPIM syscall",
+        ))
+        .stdout(contains("Assembly successful."));
+    Command::cargo_bin("main_assembler")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["assemble", "hex.s", "--dialect", "tasm"])
+        .assert()
+        .success()
+        .stdout(contains("Unknown dialect 'tasm'"));
+}
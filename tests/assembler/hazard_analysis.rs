@@ -0,0 +1,137 @@
+// tests/assembler/hazard_analysis.rs
+// Tests for the static hazard analysis of assembled programs
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::collections::BTreeMap;
+use tempfile::tempdir;
+use vmips_rust::assembler::{Assembler, Dependency, DependencyKind, HazardAnalysis};
+
+const SUM_PROGRAM: &str = "
+.data
+values: .word 1, 2, 3
+.text
+main:
+    la $t0, values
+    addi $t1, $zero, 3
+    addi $t2, $zero, 0
+loop:
+    lw $t3, 0($t0)
+    add $t2, $t2, $t3
+    addi $t0, $t0, 4
+    addi $t1, $t1, -1
+    bne $t1, $zero, loop
+    addi $v0, $zero, 10
+    syscall
+";
+
+#[test]
+fn test_basic_blocks_and_dependencies() {
+    let program = Assembler::new().assemble_program(SUM_PROGRAM).unwrap();
+    let analysis = program.analyze();
+    assert_eq!(analysis.blocks.len(), 3);
+
+    let starts: Vec<u32> = analysis.blocks.iter().map(|block| block.start).collect();
+    assert_eq!(starts, vec![0x00400000, 0x00400010, 0x00400024]);
+    assert_eq!(analysis.blocks[0].labels, vec!["main"]);
+
+    let body = &analysis.blocks[1];
+    assert_eq!(body.labels, vec!["loop"]);
+    assert_eq!(body.instructions.len(), 5);
+    assert_eq!(body.successors, vec![0x00400010, 0x00400024]);
+    assert_eq!(body.live_in, vec![8, 9, 10]);
+    assert_eq!(body.written, vec![8, 9, 10, 11]);
+    assert_eq!(
+        body.dependencies,
+        vec![
+            Dependency {
+                producer: 0x00400010,
+                consumer: 0x00400014,
+                register: 11,
+                kind: DependencyKind::Raw,
+                distance: 1,
+            },
+            Dependency {
+                producer: 0x00400010,
+                consumer: 0x00400018,
+                register: 8,
+                kind: DependencyKind::War,
+                distance: 2,
+            },
+            Dependency {
+                producer: 0x0040001C,
+                consumer: 0x00400020,
+                register: 9,
+                kind: DependencyKind::Raw,
+                distance: 1,
+            },
+        ]
+    );
+
+    // The exit block has nothing after it in the text section
+    assert!(analysis.blocks[2].successors.is_empty());
+    assert_eq!(analysis.blocks[2].delay_slot, None);
+}
+
+#[test]
+fn test_load_use_hazards_and_delay_slots() {
+    let program = Assembler::new().assemble_program(SUM_PROGRAM).unwrap();
+    let analysis = program.analyze();
+
+    let hazards: Vec<_> = analysis.load_use_hazards().collect();
+    assert_eq!(hazards.len(), 1);
+    assert_eq!(hazards[0].load, 0x00400010);
+    assert_eq!(hazards[0].consumer, 0x00400014);
+    assert_eq!(hazards[0].register, 11);
+
+    // The counter feeds the branch, but the pointer increment is free to move
+    let slots: Vec<_> = analysis.delay_slots().collect();
+    assert_eq!(slots.len(), 1);
+    assert_eq!(slots[0].branch, 0x00400020);
+    assert_eq!(slots[0].candidate, Some(0x00400018));
+
+    // Raw words: the jump target's block and a slot that needs a nop
+    let words = [
+        0x8D090000, // lw $t1, 0($t0)
+        0x01294020, // add $t0, $t1, $t1
+        0x1100FFFD, // beq $t0, $zero, 0
+        0x08000000, // j 0
+    ];
+    let analysis = HazardAnalysis::new(&words, 0, &BTreeMap::new());
+    let starts: Vec<u32> = analysis.blocks.iter().map(|block| block.start).collect();
+    assert_eq!(starts, vec![0, 0xC]);
+    assert_eq!(analysis.blocks[0].successors, vec![0, 0xC]);
+    assert_eq!(analysis.blocks[1].successors, vec![0]);
+    let slots: Vec<_> = analysis.delay_slots().map(|slot| slot.candidate).collect();
+    assert_eq!(slots, vec![None, None]);
+    assert_eq!(analysis.load_use_hazards().count(), 1);
+}
+
+#[test]
+fn test_analyze_cli() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("sum.s");
+    std::fs::write(&source, SUM_PROGRAM).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("analyze").arg("--input").arg(&source);
+    cmd.assert()
+        .success()
+        .stdout(contains("Block 1 at 0x00400010 (loop): 5 instructions"))
+        .stdout(contains(
+            "Load-use hazard: 0x00400014 uses $t3 loaded at 0x00400010",
+        ))
+        .stdout(contains(
+            "Delay slot of 0x00400020: can be filled by 0x00400018",
+        ))
+        .stdout(contains(
+            "3 blocks, 1 load-use hazards, 1 of 1 delay slots can be filled",
+        ));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("analyze").arg("--input").arg(&source).arg("--json");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(json["blocks"][1]["load_use_hazards"][0]["register"], 11);
+    assert_eq!(json["blocks"][1]["dependencies"][1]["kind"], "War");
+}
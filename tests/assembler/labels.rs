@@ -0,0 +1,121 @@
+// tests/assembler/labels.rs
+// Tests for label resolution across the text and data sections
+
+use vmips_rust::assembler::Assembler;
+use vmips_rust::functional_simulator::instructions::Instruction;
+use vmips_rust::functional_simulator::simulator::{decode_instruction, Simulator};
+
+fn run_source(source: &str) -> Simulator {
+    let mut simulator = Simulator::from_assembly(source).unwrap();
+    simulator.run().unwrap();
+    simulator
+}
+
+#[test]
+fn test_load_from_data_label() {
+    // value is defined after the text that loads it
+    let simulator = run_source(
+        "
+.text
+main:
+    lw $t0, value
+    lw $t1, table+4
+    la $t2, table
+    lw $t3, 8($t2)
+    addi $v0, $zero, 10
+    syscall
+.data
+value: .word 0x1234
+table: .word 10, 20, 30
+",
+    );
+
+    assert_eq!(simulator.registers.read(8), 0x1234);
+    assert_eq!(simulator.registers.read(9), 20);
+    assert_eq!(simulator.registers.read(10), 0x10000004);
+    assert_eq!(simulator.registers.read(11), 30);
+}
+
+#[test]
+fn test_labels_keep_their_section() {
+    // Data labels resolve against the data base and text labels against the
+    // text base, however the sections are interleaved
+    let simulator = run_source(
+        "
+.data
+count: .word 3
+.text
+main:
+    jal sum
+    addi $v0, $zero, 10
+    syscall
+.data
+total: .word 0
+.text
+sum:
+    la $t0, count
+    lw $t1, 0($t0)
+    addi $t2, $zero, 0
+loop:
+    add $t2, $t2, $t1
+    addi $t1, $t1, -1
+    bne $t1, $zero, loop
+    sw $t2, total
+    jr $ra
+",
+    );
+
+    assert_eq!(simulator.registers.read(8), 0x10000000);
+    assert_eq!(simulator.registers.read(10), 6);
+    assert_eq!(simulator.memory.read_word(0x10000004), Ok(6));
+    assert_eq!(simulator.registers.read(31), 0x00400004);
+}
+
+#[test]
+fn test_label_access_expansion() {
+    let bytes = Assembler::new()
+        .assemble_string(
+            "
+.data 0x10008000
+pad: .word 0
+buffer: .space 8
+.text
+    sw $t0, buffer($t1)
+    lb $t2, buffer+5
+",
+        )
+        .unwrap();
+    let text: Vec<Instruction> = bytes[8 + 12..]
+        .chunks(4)
+        .map(|word| decode_instruction(u32::from_le_bytes([word[0], word[1], word[2], word[3]])))
+        .collect();
+
+    // 0x10008004 has a negative low half, so the high half rounds up
+    assert_eq!(
+        text,
+        vec![
+            Instruction::Lui { rt: 1, imm: 0x1001 },
+            Instruction::Add {
+                rd: 1,
+                rs: 1,
+                rt: 9
+            },
+            Instruction::Sw {
+                rt: 8,
+                base: 1,
+                offset: -0x7FFC
+            },
+            Instruction::Lui { rt: 1, imm: 0x1001 },
+            Instruction::Lb {
+                rt: 10,
+                base: 1,
+                offset: -0x7FF7
+            },
+        ]
+    );
+
+    let error = Assembler::new()
+        .assemble_string(".text\n    lw $t0, missing\n")
+        .unwrap_err();
+    assert!(error.to_string().contains("Undefined symbol: missing"));
+}
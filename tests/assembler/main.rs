@@ -0,0 +1,15 @@
+// tests/assembler/main.rs
+// Tests for the assembler, its dialects and the programs and reports it produces
+
+mod assembler_ast;
+mod data_directives;
+mod dialect;
+mod hazard_analysis;
+mod labels;
+mod noat;
+mod optimizer;
+mod program;
+mod program_builder;
+mod pseudo_instructions;
+mod sections;
+mod unrolling;
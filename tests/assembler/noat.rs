@@ -0,0 +1,80 @@
+// tests/assembler/noat.rs
+// Tests for .set at/.set noat and the $at diagnostics
+
+use vmips_rust::assembler::Assembler;
+
+#[test]
+fn test_explicit_at_warns_by_default() {
+    let mut assembler = Assembler::new();
+    assembler
+        .assemble_string(
+            "
+.text
+    addi $at, $zero, 1
+    add $t0, $t1, $1
+    add $t0, $t1, $t2
+",
+        )
+        .unwrap();
+
+    assert_eq!(
+        assembler.warnings(),
+        &[
+            "line 3: used $at without \".set noat\"".to_string(),
+            "line 4: used $at without \".set noat\"".to_string(),
+        ]
+    );
+
+    // Expansions that use $at internally are not reported
+    let mut assembler = Assembler::new();
+    assembler
+        .assemble_string(".text\nloop:\n    blt $t0, $t1, loop\n")
+        .unwrap();
+    assert!(assembler.warnings().is_empty());
+}
+
+#[test]
+fn test_noat_allows_at_and_rejects_expansions_that_need_it() {
+    let mut assembler = Assembler::new();
+    assembler
+        .assemble_string(
+            "
+.text
+    .set noat
+    addi $at, $zero, 1
+    li $t0, 0x12345678
+    neg $t1, $t0
+    .set at
+",
+        )
+        .unwrap();
+    assert!(assembler.warnings().is_empty());
+
+    for line in ["blt $t0, $t1, done", "bge $t0, 4, done", "abs $t0, $t1"] {
+        let source = format!(".text\n    .set noat\ndone:\n    {}\n", line);
+        let error = Assembler::new().assemble_string(&source).unwrap_err();
+        assert!(
+            error.to_string().contains("needs $at after .set noat"),
+            "{}: {}",
+            line,
+            error
+        );
+    }
+}
+
+#[test]
+fn test_set_at_restores_expansions() {
+    let source = "
+.text
+    .set noat
+    move $at, $t0
+    .set at
+done:
+    bgt $t0, $t1, done
+    .set reorder
+";
+    let mut assembler = Assembler::new();
+    let bytes = assembler.assemble_string(source).unwrap();
+    assert_eq!(bytes.len(), 8 + 3 * 4);
+    assert!(assembler.warnings().is_empty());
+}
@@ -0,0 +1,241 @@
+// tests/assembler/optimizer.rs
+// Tests for load scheduling, move removal and delay slot filling
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::assembler::{Assembler, ChangeKind, Optimizer, Program};
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{
+    BranchPredictorType, CacheConfig, ControlHazardScheme, PipelineConfig,
+};
+use vmips_rust::timing_simulator::simulator::Simulator;
+
+const SUM_PROGRAM: &str = "
+.data
+values: .word 5, 7, 9
+.text
+main:
+    la $s0, values
+    li $t0, 0
+    li $t1, 3
+loop:
+    lw $t2, 0($s0)
+    add $t0, $t0, $t2
+    addi $s0, $s0, 4
+    addi $t1, $t1, -1
+    bne $t1, $zero, loop
+    li $v0, 10
+    syscall
+";
+
+fn run(program: &Program, scheme: ControlHazardScheme) -> Simulator {
+    let config = PipelineConfig::new(5)
+        .with_branch_prediction(false, BranchPredictorType::TwoBit)
+        .with_control_hazards(scheme);
+    let mut simulator = Simulator::new(
+        config,
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        0x0050_0000,
+    );
+    simulator.visualization = None;
+    let image = ProgramImage::from_bytes(&program.to_bytes()).unwrap();
+    simulator.load_image(&image).unwrap();
+    simulator.run();
+    simulator
+}
+
+#[test]
+fn test_loads_are_scheduled_away_from_their_uses() {
+    let program = Assembler::new().assemble_program(SUM_PROGRAM).unwrap();
+    let (optimized, report) = Optimizer::new().optimize(&program);
+
+    assert_eq!(report.changes.len(), 1);
+    assert_eq!(report.changes[0].address, 0x00400018);
+    assert_eq!(
+        report.changes[0].kind,
+        ChangeKind::ScheduledLoad { load: 0x00400010 }
+    );
+    assert_eq!(report.before.load_use_hazards, 1);
+    assert_eq!(report.after.load_use_hazards, 0);
+    assert_eq!(report.after.instructions, report.before.instructions);
+
+    // The increment now sits between the load and the add, and the branch
+    // still reaches the top of the loop
+    assert_eq!(
+        optimized.text[4..8],
+        [
+            program.text[4],
+            program.text[6],
+            program.text[5],
+            program.text[7],
+        ]
+    );
+    assert_eq!(optimized.text[8], program.text[8]);
+
+    let before = run(&program, ControlHazardScheme::Flush);
+    let after = run(&optimized, ControlHazardScheme::Flush);
+    assert_eq!(after.registers.read(8), 21);
+    assert_eq!(after.registers.data, before.registers.data);
+    assert!(after.run_totals.cycles < before.run_totals.cycles);
+    assert!(after.run_totals.stalls < before.run_totals.stalls);
+}
+
+#[test]
+fn test_redundant_moves_are_removed() {
+    let program = Assembler::new()
+        .assemble_program(
+            "
+.data
+result: .word 0
+pointer: .word done
+.text
+    addi $t0, $zero, 6
+    add $t1, $t0, $zero
+    add $t1, $t0, $zero
+    or $t2, $t1, $zero
+    or $t1, $t2, $zero
+    addi $t3, $t3, 0
+    j done
+    addi $t0, $zero, 99
+done:
+    la $t4, result
+    sw $t2, 0($t4)
+    li $v0, 10
+    syscall
+",
+        )
+        .unwrap();
+    let (optimized, report) = Optimizer::new().optimize(&program);
+
+    let removed: Vec<u32> = report
+        .changes
+        .iter()
+        .filter(|change| change.kind == ChangeKind::RemovedMove)
+        .map(|change| change.address)
+        .collect();
+    // The overwritten copy, the copy back and the self-move go, and the `ori`
+    // of `la` stays even though the address's lower half is zero
+    assert_eq!(removed, vec![0x00400004, 0x00400010, 0x00400014]);
+    assert_eq!(optimized.text.len(), program.text.len() - 3);
+
+    // Labels, the jump and the address stored in data follow the code
+    let done = optimized.symbols["done"];
+    assert_eq!(done, program.symbols["done"] - 12);
+    assert_eq!(optimized.text[3] & 0x03FF_FFFF, done >> 2);
+    let pointer = optimized.symbols["pointer"] - optimized.data_address;
+    assert_eq!(
+        optimized.data[pointer as usize..pointer as usize + 4],
+        done.to_le_bytes()
+    );
+
+    let simulator = run(&optimized, ControlHazardScheme::Flush);
+    assert_eq!(simulator.registers.read(8), 6);
+    assert_eq!(simulator.registers.read(10), 6);
+    assert_eq!(simulator.memory.read_word(0x10000000), Ok(6));
+}
+
+#[test]
+fn test_move_pseudo_instructions_are_removed() {
+    let program = Assembler::new()
+        .assemble_program(
+            "
+.text
+    addi $t0, $zero, 6
+    move $t1, $t0
+    move $t0, $t1
+    addu $t2, $t1, $t0
+    li $v0, 10
+    syscall
+",
+        )
+        .unwrap();
+    let (optimized, report) = Optimizer::new().optimize(&program);
+
+    // `move` is an `addu` with $zero; copying $t1 back into $t0 is redundant
+    // but the add that reads both stays
+    let removed: Vec<(u32, &str)> = report
+        .changes
+        .iter()
+        .filter(|change| change.kind == ChangeKind::RemovedMove)
+        .map(|change| (change.address, change.instruction.as_str()))
+        .collect();
+    assert_eq!(removed, vec![(0x00400008, "addu $8, $0, $9")]);
+
+    let simulator = run(&optimized, ControlHazardScheme::Flush);
+    assert_eq!(simulator.registers.read(10), 12);
+}
+
+#[test]
+fn test_delay_slots_are_filled_or_padded() {
+    let program = Assembler::new().assemble_program(SUM_PROGRAM).unwrap();
+    let (optimized, report) = Optimizer::new().with_delay_slots(true).optimize(&program);
+
+    // The add moves into the slot of the loop branch, the only branch
+    let filled: Vec<ChangeKind> = report
+        .changes
+        .iter()
+        .map(|change| change.kind)
+        .filter(|kind| matches!(kind, ChangeKind::FilledDelaySlot { .. }))
+        .collect();
+    assert_eq!(
+        filled,
+        vec![ChangeKind::FilledDelaySlot { branch: 0x00400020 }]
+    );
+    assert_eq!(report.after.nop_slots, 0);
+
+    let original = run(&program, ControlHazardScheme::Flush);
+    let slotted = run(&optimized, ControlHazardScheme::DelaySlot);
+    assert_eq!(slotted.registers.read(8), 21);
+    assert_eq!(slotted.registers.data, original.registers.data);
+
+    // A block whose only instruction feeds the branch gets a nop
+    let program = Assembler::new()
+        .assemble_program(
+            "
+.text
+    addi $t0, $zero, 2
+loop:
+    addi $t0, $t0, -1
+    bne $t0, $zero, loop
+    addi $t1, $zero, 5
+    li $v0, 10
+    syscall
+",
+        )
+        .unwrap();
+    let (optimized, report) = Optimizer::new().with_delay_slots(true).optimize(&program);
+    assert_eq!(report.after.nop_slots, 1);
+    assert_eq!(optimized.text[3], 0);
+    let slotted = run(&optimized, ControlHazardScheme::DelaySlot);
+    assert_eq!(slotted.registers.read(8), 0);
+    assert_eq!(slotted.registers.read(9), 5);
+
+    // The command line prints the report and runs with delay slots
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("sum.s");
+    std::fs::write(&source, SUM_PROGRAM).unwrap();
+    let mut cmd = Command::cargo_bin("main_assembler").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("run")
+        .arg(&source)
+        .arg("timing")
+        .arg("--delay-slots");
+    cmd.assert()
+        .success()
+        .stdout(contains("moved into the delay slot of 0x00400020"))
+        .stdout(contains(
+            "After: 11 instructions, 0 load-use hazards, 0 nop delay slots",
+        ))
+        .stdout(contains("$t0   = 0x00000015"));
+
+    let mut cmd = Command::cargo_bin("main_assembler").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("run")
+        .arg(&source)
+        .arg("--delay-slots");
+    cmd.assert()
+        .success()
+        .stdout(contains("--delay-slots requires the timing simulator"));
+}
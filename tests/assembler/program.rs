@@ -0,0 +1,146 @@
+// tests/assembler/program.rs
+// Tests for the structured Program returned by the assembler
+
+use vmips_rust::assembler::{Assembler, Program, Relocation, RelocationKind, SectionKind};
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::loader::ProgramImage;
+
+const SOURCE: &str = "
+.data
+values: .word 5, 7
+pointer: .word values
+.text
+helper:
+    jr $ra
+__start:
+    la $t0, values
+    lw $t1, values+4
+    jal helper
+    addi $v0, $zero, 10
+    syscall
+";
+
+#[test]
+fn test_program_sections_and_symbols() {
+    let program = Assembler::new().assemble_program(SOURCE).unwrap();
+
+    assert_eq!(program.text_address, 0x00400000);
+    assert_eq!(program.data_address, 0x10000000);
+    assert_eq!(program.text.len(), 8);
+    assert_eq!(program.text[0], 0x03E00008);
+    assert_eq!(program.data, vec![5, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0x10]);
+    assert_eq!(program.symbols["values"], 0x10000000);
+    assert_eq!(program.symbols["pointer"], 0x10000008);
+    assert_eq!(program.symbols["helper"], 0x00400000);
+    // Execution starts at __start rather than the first instruction
+    assert_eq!(program.entry, 0x00400004);
+
+    let json = program.to_json().unwrap();
+    assert_eq!(Program::from_json(&json).unwrap(), program);
+}
+
+#[test]
+fn test_program_relocations() {
+    let program = Assembler::new().assemble_program(SOURCE).unwrap();
+    let relocation = |address, section, kind, symbol: &str, addend| Relocation {
+        address,
+        section,
+        kind,
+        symbol: symbol.to_string(),
+        addend,
+    };
+
+    assert_eq!(
+        program.relocations,
+        vec![
+            relocation(
+                0x10000008,
+                SectionKind::Data,
+                RelocationKind::Word,
+                "values",
+                0
+            ),
+            relocation(
+                0x00400004,
+                SectionKind::Text,
+                RelocationKind::High16,
+                "values",
+                0
+            ),
+            relocation(
+                0x00400008,
+                SectionKind::Text,
+                RelocationKind::Low16,
+                "values",
+                0
+            ),
+            relocation(
+                0x0040000C,
+                SectionKind::Text,
+                RelocationKind::High16Adjusted,
+                "values",
+                4
+            ),
+            relocation(
+                0x00400010,
+                SectionKind::Text,
+                RelocationKind::Low16,
+                "values",
+                4
+            ),
+            relocation(
+                0x00400014,
+                SectionKind::Text,
+                RelocationKind::Jump26,
+                "helper",
+                0
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_program_flat_format_and_loading() {
+    let mut assembler = Assembler::new();
+    let program = assembler.assemble_program(SOURCE).unwrap();
+    let flat = Assembler::new().assemble_string(SOURCE).unwrap();
+    assert_eq!(program.to_bytes(), flat);
+
+    // The flat format keeps the sections but not the symbols
+    let read_back = Program::from_bytes(&flat).unwrap();
+    assert_eq!(read_back.text, program.text);
+    assert_eq!(read_back.data, program.data);
+    assert!(read_back.symbols.is_empty());
+    assert!(Program::from_bytes(&flat[..flat.len() - 1]).is_none());
+
+    let disassembly = program.disassemble();
+    assert_eq!(disassembly.len(), 8);
+    assert_eq!(disassembly[1].0, 0x00400004);
+    assert!(disassembly[1].1.starts_with("__start: "));
+
+    let image = ProgramImage::from_program(&program);
+    assert_eq!(image.entry_point, 0x00400004);
+    let mut simulator = Simulator::from_image(&image).unwrap();
+    simulator.run().unwrap();
+    assert_eq!(simulator.registers.read(8), 0x10000000);
+    assert_eq!(simulator.registers.read(9), 7);
+    assert_eq!(simulator.registers.read(31), 0x00400018);
+}
+
+#[test]
+fn test_unaligned_text_is_an_error() {
+    // Moving text to a halfword address used to leave a partial word that
+    // panicked when the program was built
+    for source in [
+        ".text\nnop\n.text 0x00400006\nnop\n",
+        ".text 0x00400002\nnop\n",
+    ] {
+        let error = Assembler::new().assemble_program(source).unwrap_err();
+        assert!(error.to_string().contains("not word-aligned"), "{}", error);
+    }
+
+    let program = Assembler::new()
+        .assemble_program(".text\nnop\n.text 0x00400008\nnop\n")
+        .unwrap();
+    assert_eq!(program.text, vec![0, 0, 0]);
+}
@@ -0,0 +1,105 @@
+// tests/assembler/program_builder.rs
+// Tests for building programs with ProgramBuilder instead of assembly text
+
+use vmips_rust::assembler::{Assembler, BuildError, ProgramBuilder};
+use vmips_rust::functional_simulator::registers::{A0, RA, S0, SP, T0, T1, T2, V0, ZERO};
+use vmips_rust::functional_simulator::simulator::Simulator;
+
+#[test]
+fn test_built_loop_runs() {
+    // Sum the words of a table, calling a function for each one
+    let image = ProgramBuilder::new()
+        .label("__start")
+        .la(T0, "table")
+        .li(T1, 4)
+        .label("loop")
+        .lw(A0, 0, T0)
+        .jal("accumulate")
+        .addi(T0, T0, 4)
+        .addi(T1, T1, -1)
+        .bgtz(T1, "loop")
+        .li(V0, 10)
+        .syscall()
+        .label("accumulate")
+        .add(S0, S0, A0)
+        .jr(RA)
+        .data_label("table")
+        .words(&[5, 10, 20, 0x1_0000])
+        .image()
+        .unwrap();
+
+    let mut simulator = Simulator::from_image(&image).unwrap();
+    simulator.run().unwrap();
+    assert_eq!(simulator.registers.read(S0), 0x1_0023);
+}
+
+#[test]
+fn test_builder_matches_the_assembler() {
+    let built = ProgramBuilder::new()
+        .label("main")
+        .li(T0, 0x12345678)
+        .la(T2, "buffer")
+        .sw(T0, -4, SP)
+        .label("spin")
+        .beq(T0, ZERO, "spin")
+        .b("main")
+        .j("spin")
+        .data_label("message")
+        .asciiz("hi")
+        .align_data(4)
+        .data_label("buffer")
+        .space(8)
+        .build()
+        .unwrap();
+    let assembled = Assembler::new()
+        .assemble_program(
+            "
+.text
+main:
+    li $t0, 0x12345678
+    la $t2, buffer
+    sw $t0, -4($sp)
+spin:
+    beq $t0, $zero, spin
+    b main
+    j spin
+.data
+message: .asciiz \"hi\"
+    .align 2
+buffer: .space 8
+",
+        )
+        .unwrap();
+
+    assert_eq!(built.text, assembled.text);
+    assert_eq!(built.data, assembled.data);
+    assert_eq!(built.symbols, assembled.symbols);
+    assert_eq!(built.entry, assembled.entry);
+    assert_eq!(built.relocations, assembled.relocations);
+}
+
+#[test]
+fn test_label_errors() {
+    let undefined = ProgramBuilder::new().j("nowhere").build();
+    assert_eq!(
+        undefined.unwrap_err(),
+        BuildError::UndefinedLabel("nowhere".to_string())
+    );
+
+    let duplicate = ProgramBuilder::new()
+        .label("again")
+        .nop()
+        .data_label("again")
+        .build();
+    assert_eq!(
+        duplicate.unwrap_err(),
+        BuildError::DuplicateLabel("again".to_string())
+    );
+
+    let mut far = ProgramBuilder::new().beq(ZERO, ZERO, "end");
+    for _ in 0..40_000 {
+        far = far.nop();
+    }
+    let error = far.label("end").build().unwrap_err();
+    assert_eq!(error.to_string(), "Branch to end is out of range");
+}
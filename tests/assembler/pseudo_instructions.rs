@@ -0,0 +1,153 @@
+// tests/assembler/pseudo_instructions.rs
+// Tests for assembler pseudo-instructions and their expansion
+
+use vmips_rust::assembler::Assembler;
+use vmips_rust::functional_simulator::instructions::Instruction;
+use vmips_rust::functional_simulator::simulator::{decode_instruction, Simulator};
+use vmips_rust::loader::ProgramImage;
+
+fn run_source(source: &str) -> Simulator {
+    let image = ProgramImage::from_assembly(source).unwrap();
+    // Leave room past the text for a small stack
+    let mut simulator = Simulator::new(image.memory_required() + 0x1000);
+    simulator.load_image(&image).unwrap();
+    simulator.run().unwrap();
+    simulator
+}
+
+fn assemble_text(source: &str) -> Vec<Instruction> {
+    let bytes = Assembler::new().assemble_string(source).unwrap();
+    bytes[8..]
+        .chunks(4)
+        .map(|word| decode_instruction(u32::from_le_bytes([word[0], word[1], word[2], word[3]])))
+        .collect()
+}
+
+#[test]
+fn test_register_pseudo_instructions() {
+    let simulator = run_source(
+        "
+.text
+    li $t0, 0x12345678
+    li $t1, -7
+    li $t2, 40000
+    neg $s0, $t1
+    not $s1, $zero
+    abs $s2, $t1
+    abs $s3, $t2
+    mul $s4, $t1, $t2
+    sgt $s5, $t2, $t1
+    sge $s6, $t1, $t1
+    sle $s7, $t2, $t1
+    seq $t3, $t1, $t1
+    sne $t4, $t1, $t2
+    li $sp, 0x401000
+    push $t0
+    pop $t5
+",
+    );
+
+    let registers = &simulator.registers;
+    assert_eq!(registers.read(8), 0x12345678);
+    assert_eq!(registers.read(9), -7i32 as u32);
+    assert_eq!(registers.read(10), 40000);
+    assert_eq!(registers.read(16), 7);
+    assert_eq!(registers.read(17), 0xFFFFFFFF);
+    assert_eq!(registers.read(18), 7);
+    assert_eq!(registers.read(19), 40000);
+    assert_eq!(registers.read(20), (-7i32 * 40000) as u32);
+    assert_eq!(registers.read(21), 1);
+    assert_eq!(registers.read(22), 1);
+    assert_eq!(registers.read(23), 0);
+    assert_eq!(registers.read(11), 1);
+    assert_eq!(registers.read(12), 1);
+    assert_eq!(registers.read(13), 0x12345678);
+    assert_eq!(registers.read(29), 0x401000);
+}
+
+#[test]
+fn test_compare_branches() {
+    // Each taken branch skips an instruction that would clear its flag
+    let simulator = run_source(
+        "
+.text
+    li $t0, -1
+    li $t1, 5
+    li $s0, 1
+    li $s1, 1
+    li $s2, 1
+    li $s3, 1
+    li $s4, 1
+    li $s5, 1
+    blt $t0, $t1, l1
+    li $s0, 0
+l1: bgtu $t0, $t1, l2
+    li $s1, 0
+l2: bge $t1, 5, l3
+    li $s2, 0
+l3: ble $t1, 100000, l4
+    li $s3, 0
+l4: bnez $t0, l5
+    li $s4, 0
+l5: bltu $t0, $t1, l6
+    beqz $zero, l6
+    li $s5, 0
+l6: li $v0, 10
+    syscall
+",
+    );
+
+    let registers = &simulator.registers;
+    for flag in 16..=21 {
+        assert_eq!(registers.read(flag), 1, "flag register {}", flag);
+    }
+}
+
+#[test]
+fn test_expansions_use_at_and_keep_labels_aligned() {
+    let text = assemble_text(
+        "
+.text
+    bge $a0, $a1, done
+    li $t0, 0x10000
+    la $t1, done
+done:
+    nop
+",
+    );
+
+    assert_eq!(
+        text,
+        vec![
+            Instruction::Slt {
+                rd: 1,
+                rs: 4,
+                rt: 5
+            },
+            // The branch is the second word, so done is 4 words past it
+            Instruction::Beq {
+                rs: 1,
+                rt: 0,
+                offset: 4
+            },
+            Instruction::Lui { rt: 8, imm: 1 },
+            Instruction::Ori {
+                rt: 8,
+                rs: 8,
+                imm: 0
+            },
+            // done is the seventh word of text at 0x00400000
+            Instruction::Lui { rt: 9, imm: 0x40 },
+            Instruction::Ori {
+                rt: 9,
+                rs: 9,
+                imm: 24
+            },
+            Instruction::Nop,
+        ]
+    );
+
+    assert!(Assembler::new()
+        .assemble_string(".text\n    blt $t0, done\n")
+        .is_err());
+}
@@ -0,0 +1,165 @@
+// tests/assembler/sections.rs
+// Tests for section directives, address arguments and location counters
+
+use vmips_rust::assembler::{AssembledSection, Assembler, AssemblerError, SectionKind};
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::loader::ProgramImage;
+
+fn run_source(source: &str) -> Simulator {
+    let mut simulator = Simulator::from_assembly(source).unwrap();
+    simulator.run().unwrap();
+    simulator
+}
+
+#[test]
+fn test_interleaved_sections_keep_separate_counters() {
+    let mut assembler = Assembler::new();
+    assembler
+        .assemble_string(
+            "
+.data 0x10000000
+first: .word 1
+.text
+    la $t0, second
+.data
+second: .word 2
+.text
+    lw $t1, 0($t0)
+",
+        )
+        .unwrap();
+
+    let sections = assembler.sections();
+    assert_eq!(sections.len(), 2);
+    assert_eq!(sections[0].kind, SectionKind::Text);
+    assert_eq!(sections[0].bytes.len(), 3 * 4);
+    assert_eq!(
+        sections[1],
+        AssembledSection {
+            kind: SectionKind::Data,
+            address: 0x10000000,
+            bytes: vec![1, 0, 0, 0, 2, 0, 0, 0],
+        }
+    );
+
+    // second follows first in the data section, not the text before it
+    let image = ProgramImage::from_sections(&sections);
+    let mut simulator = Simulator::from_image(&image).unwrap();
+    simulator.run().unwrap();
+    assert_eq!(simulator.registers.read(8), 0x10000004);
+    assert_eq!(simulator.registers.read(9), 2);
+}
+
+#[test]
+fn test_section_address_arguments() {
+    let simulator = run_source(
+        "
+.data 0x10010000
+value: .word 41
+.text 0x00400000
+main:
+    jal increment
+    addi $v0, $zero, 10
+    syscall
+increment:
+    la $t0, value
+    lw $t1, 0($t0)
+    addi $t1, $t1, 1
+    jr $ra
+",
+    );
+
+    assert_eq!(simulator.registers.read(9), 42);
+
+    // A section can move forward, padding the gap, but not backward
+    let mut assembler = Assembler::new();
+    assembler
+        .assemble_string(".data 0x10000000\n.word 1\n.data 0x10000008\n.word 2\n")
+        .unwrap();
+    assert_eq!(
+        assembler.sections()[0].bytes,
+        vec![1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0]
+    );
+    let error = Assembler::new()
+        .assemble_string(".data 0x10000000\n.word 1, 2\n.data 0x10000004\n")
+        .unwrap_err();
+    assert!(error.to_string().contains("Cannot move .data back"));
+
+    // Text sections hold whole instructions, data sections can start anywhere
+    for source in [
+        ".text 0x00400002\nnop\n",
+        ".text\nnop\n.text 0x00400006\nnop\n",
+    ] {
+        let error = Assembler::new().assemble_string(source).unwrap_err();
+        assert!(matches!(error, AssemblerError::Range(..)), "{}", error);
+        assert!(error.to_string().contains("not word-aligned"));
+    }
+    assert!(Assembler::new()
+        .assemble_string(".ktext 0x80000181\neret\n")
+        .is_err());
+    assert!(Assembler::new()
+        .assemble_string(".data 0x10000001\n.byte 1\n")
+        .is_ok());
+
+    // Nothing may run past the end of the address space
+    for source in [
+        ".data 0xFFFFFFF0\n.word 1, 2, 3, 4, 5\n",
+        ".text 0xFFFFFFFC\nnop\nnop\n",
+        ".data 0xFFFFFFFE\n.asciiz \"ab\"\n",
+        ".data 0xFFFFFF00\n.space 0x200\n",
+        ".data 0xFFFFFFFD\n.byte 1\n.align 31\n",
+    ] {
+        let error = Assembler::new().assemble_string(source).unwrap_err();
+        assert!(matches!(error, AssemblerError::Range(..)), "{}", error);
+        assert!(error
+            .to_string()
+            .contains("past the end of the address space"));
+    }
+}
+
+#[test]
+fn test_additional_sections() {
+    let mut assembler = Assembler::new();
+    let flat = assembler
+        .assemble_string(
+            "
+.rodata
+message: .asciiz \"hi\"
+.bss
+buffer: .space 16
+.kdata 0x90000000
+saved: .word 0
+.ktext 0x80000180
+    eret
+.text
+    nop
+",
+        )
+        .unwrap();
+
+    let kinds: Vec<(SectionKind, u32, usize)> = assembler
+        .sections()
+        .iter()
+        .map(|section| (section.kind, section.address, section.bytes.len()))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (SectionKind::Text, 0x00400000, 4),
+            (SectionKind::Rodata, 0x10000000, 3),
+            (SectionKind::Bss, 0x10001000, 16),
+            (SectionKind::Ktext, 0x80000180, 4),
+            (SectionKind::Kdata, 0x90000000, 4),
+        ]
+    );
+
+    // Flat output only has room for .text and .data
+    assert_eq!(flat.len(), 8 + 4);
+    assert_eq!(assembler.warnings().len(), 4);
+    assert!(assembler.warnings()[0].contains(".rodata is left out"));
+
+    let error = Assembler::new()
+        .assemble_string(".bss\n    .word 1\n")
+        .unwrap_err();
+    assert!(error.to_string().contains("not allowed in .bss"));
+}
@@ -0,0 +1,166 @@
+// tests/assembler/unrolling.rs
+// Tests for the loop unrolling and software pipelining report
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::assembler::Assembler;
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{BranchPredictorType, CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::{ExecutionMode, Simulator};
+use vmips_rust::timing_simulator::unrolling::UnrollReport;
+
+/// Sums a 16-word array with a load-use stall in every iteration
+const SUM_PROGRAM: &str = "
+.data
+values: .word 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16
+.text
+    la $t0, values
+    li $t1, 16
+    li $s0, 0
+sum:
+    lw $t2, 0($t0)
+    add $s0, $s0, $t2
+    addi $t0, $t0, 4
+    addi $t1, $t1, -1
+    bne $t1, $zero, sum
+    li $v0, 10
+    syscall
+";
+
+fn report(source: &str, factor: usize) -> UnrollReport {
+    let program = Assembler::new().assemble_program(source).unwrap();
+    let config = PipelineConfig::new(5).with_branch_prediction(true, BranchPredictorType::TwoBit);
+    let mut simulator = Simulator::new(
+        config,
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        0x0050_0000,
+    );
+    simulator.visualization = None;
+    let image = ProgramImage::from_bytes(&program.to_bytes()).unwrap();
+    simulator.load_image(&image).unwrap();
+    simulator.enable_memory_trace();
+    simulator.run();
+    match &simulator.execution_mode {
+        ExecutionMode::InOrder(pipeline) => UnrollReport::new(
+            simulator.memory_trace.as_ref().unwrap(),
+            pipeline,
+            factor,
+            3,
+        ),
+        ExecutionMode::OutOfOrder(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn test_unrolling_folds_index_updates_and_hides_the_load_stall() {
+    let report = report(SUM_PROGRAM, 4);
+    assert_eq!(report.loops.len(), 1);
+    let estimate = &report.loops[0];
+    assert_eq!(estimate.iterations, 16);
+    // lw, a stall cycle, add, two index updates and the branch
+    assert_eq!(estimate.cycles, 6);
+
+    let unrolled = estimate.unrolled.as_ref().unwrap();
+    assert!(unrolled.interleaved);
+    assert_eq!(unrolled.renamed.len(), 1);
+    assert_eq!(unrolled.renamed[0].0, 10);
+    assert_eq!(unrolled.renamed[0].1.len(), 3);
+    let text: Vec<String> = unrolled.schedule.iter().map(|i| i.text()).collect();
+    assert_eq!(text[3], "lw $10, 12($8)");
+    assert_eq!(text[7], "add $16, $16, $10");
+    assert_eq!(
+        &text[8..],
+        ["addi $8, $8, 16", "addi $9, $9, -4", "bne $9, $0, -11"]
+    );
+    assert!(text[..4].iter().all(|line| line.starts_with("lw ")));
+    // Four loads back to back leave no stall before the adds
+    assert_eq!(unrolled.cycles, 11);
+    assert!(estimate.unrolled_speedup().unwrap() > 2.0);
+    assert!(estimate.pipelined_speedup().unwrap() >= 1.0);
+}
+
+#[test]
+fn test_loops_the_unroller_cannot_interleave_or_transform() {
+    // The running count is read by the store and updated by the add, so the
+    // copies cannot be interleaved
+    let report_in_order = report(
+        "
+.data
+values: .space 64
+.text
+    la $t0, values
+    li $t1, 8
+    li $s0, 0
+fill:
+    sw $s0, 0($t0)
+    addi $s0, $s0, 3
+    addi $t0, $t0, 4
+    addi $t1, $t1, -1
+    bne $t1, $zero, fill
+    li $v0, 10
+    syscall
+",
+        2,
+    );
+    let unrolled = report_in_order.loops[0].unrolled.as_ref().unwrap();
+    assert!(!unrolled.interleaved);
+    assert!(unrolled.renamed.is_empty());
+    let text: Vec<String> = unrolled.schedule.iter().map(|i| i.text()).collect();
+    assert_eq!(text[0], "sw $16, 0($8)");
+    assert_eq!(text[2], "sw $16, 4($8)");
+
+    let report_hi_lo = report(
+        "
+    li $t0, 1
+    li $t1, 6
+square:
+    mult $t0, $t0
+    mflo $t2
+    addi $t1, $t1, -1
+    bne $t1, $zero, square
+    li $v0, 10
+    syscall
+",
+        2,
+    );
+    let estimate = &report_hi_lo.loops[0];
+    assert_eq!(
+        estimate.unrolled.as_ref().unwrap_err(),
+        "the loop is left alone because it uses the HI and LO registers"
+    );
+    assert!(estimate.pipelined.is_some());
+}
+
+#[test]
+fn test_cli_prints_the_unrolling_report() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("sum.asm");
+    std::fs::write(&source, SUM_PROGRAM).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&source)
+        .arg("--unroll")
+        .arg("4");
+    cmd.assert()
+        .success()
+        .stdout(contains(
+            "Loop unrolling and software pipelining (unroll by 4)",
+        ))
+        .stdout(contains("As written: 6 cycles per iteration"))
+        .stdout(contains("Unrolled by 4: 11 cycles per 4 iterations"))
+        .stdout(contains("Software pipelined: a new iteration every"));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&source)
+        .arg("--unroll")
+        .arg("1");
+    cmd.assert().failure();
+}
@@ -0,0 +1,131 @@
+// tests/caches/cache_side_channel.rs
+// Tests for the cache flush syscalls and cycle counter probes of cache timing
+
+use vmips_rust::assembler::Assembler;
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{BranchPredictorType, CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator;
+
+fn cache_config() -> CacheConfig {
+    CacheConfig::new(4096, 2, 64)
+}
+
+fn run_timing(source: &str) -> Simulator {
+    let program = Assembler::new().assemble_program(source).unwrap();
+    let config = PipelineConfig::new(5).with_branch_prediction(true, BranchPredictorType::TwoBit);
+    let mut simulator = Simulator::new(config, cache_config(), cache_config(), 0x0050_0000);
+    simulator.visualization = None;
+    let image = ProgramImage::from_bytes(&program.to_bytes()).unwrap();
+    simulator.load_image(&image).unwrap();
+    simulator.run();
+    simulator
+}
+
+/// Times a load of the word at $s0 into $s3 after evicting its line with the
+/// given syscall, then times it again into $s4. The loop runs twice so the
+/// second, measured pass fetches from a warm instruction cache.
+fn probe_program(flush: u32) -> String {
+    format!(
+        "
+.data
+value: .word 5
+.text
+    lui $s0, 0x1000
+    addi $s2, $zero, 2
+probe:
+    lw $t1, 0($s0)
+    add $a0, $s0, $zero
+    addi $v0, $zero, {}
+    syscall
+    rdhwr $t0, $2
+    lw $t1, 0($s0)
+    rdhwr $t2, $2
+    sub $s3, $t2, $t0
+    rdhwr $t0, $2
+    lw $t1, 0($s0)
+    rdhwr $t2, $2
+    sub $s4, $t2, $t0
+    addi $s2, $s2, -1
+    bne $s2, $zero, probe
+",
+        flush
+    )
+}
+
+#[test]
+fn test_cycle_counter_measures_a_flushed_line_miss() {
+    let simulator = run_timing(&probe_program(73));
+    let miss = simulator.registers.read(19);
+    let hit = simulator.registers.read(20);
+    // The reload after the flush pays exactly the miss penalty over a hit
+    assert_eq!(miss - hit, cache_config().miss_penalty as u32);
+}
+
+#[test]
+fn test_flush_and_reload_recovers_a_secret_index() {
+    for secret in 0..2 {
+        // The victim loads one of two probe lines picked by the secret, and
+        // the attacker flushes both lines first and times them afterwards
+        let simulator = run_timing(&format!(
+            "
+.data
+probe: .space 128
+secret: .word {}
+.text
+    lui $s0, 0x1000
+    lw $s1, 128($s0)
+    addi $s2, $zero, 2
+round:
+    add $a0, $s0, $zero
+    addi $v0, $zero, 73
+    syscall
+    addi $a0, $s0, 64
+    addi $v0, $zero, 73
+    syscall
+    sll $t3, $s1, 6
+    add $t3, $t3, $s0
+    lw $t1, 0($t3)
+    rdhwr $t0, $2
+    lw $t1, 0($s0)
+    rdhwr $t2, $2
+    sub $s3, $t2, $t0
+    rdhwr $t0, $2
+    lw $t1, 64($s0)
+    rdhwr $t2, $2
+    sub $s4, $t2, $t0
+    addi $s2, $s2, -1
+    bne $s2, $zero, round
+",
+            secret
+        ));
+        let times = [simulator.registers.read(19), simulator.registers.read(20)];
+        let guessed = if times[0] < times[1] { 0 } else { 1 };
+        assert_eq!(guessed, secret, "probe times {:?}", times);
+    }
+}
+
+#[test]
+fn test_flush_syscalls_are_no_ops_without_caches() {
+    let image = ProgramImage::from_assembly(
+        "
+.data
+value: .word 9
+.text
+    lui $a0, 0x1000
+    addi $v0, $zero, 73
+    syscall
+    add $s0, $v0, $zero
+    addi $v0, $zero, 72
+    syscall
+    lw $s1, 0($a0)
+    addi $v0, $zero, 10
+    syscall
+",
+    )
+    .unwrap();
+    let mut simulator = FunctionalSimulator::from_image(&image).unwrap();
+    simulator.run().unwrap();
+    assert_eq!(simulator.registers.read(16), 73);
+    assert_eq!(simulator.registers.read(17), 9);
+}
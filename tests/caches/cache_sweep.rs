@@ -0,0 +1,95 @@
+// tests/caches/cache_sweep.rs
+// Tests for the cache parameter sweep and its CSV output
+
+use assert_cmd::Command;
+use tempfile::tempdir;
+use vmips_rust::timing_simulator::cache_sweep::{CacheSweep, ParameterRange, SweepSpec};
+use vmips_rust::timing_simulator::config::PipelineConfig;
+
+const LOADS_PER_PASS: usize = 32;
+
+/// Two passes of loads with a 64-byte stride over a 2 KiB array
+fn load_strided_program(simulator: &mut vmips_rust::timing_simulator::simulator::Simulator) {
+    for pass in 0..2 {
+        for k in 0..LOADS_PER_PASS {
+            let offset = 0x2000 + k as u32 * 64;
+            let address = (pass * LOADS_PER_PASS + k) * 4;
+            simulator
+                .memory
+                .write_word_init(address, 0x8C090000 | offset); // lw $t1, offset($zero)
+        }
+    }
+}
+
+#[test]
+fn test_sweep_spec_expands_ranges() {
+    let spec = SweepSpec::from_json(
+        r#"{"size": {"min": 256, "max": 1024}, "associativity": [1, 8], "line_size": [64]}"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        spec.size,
+        ParameterRange::Range {
+            min: 256,
+            max: 1024
+        }
+    );
+    assert_eq!(spec.size.values(), vec![256, 512, 1024]);
+
+    // 256 bytes cannot hold eight 64-byte ways per set, so that point is skipped
+    let points = spec.points();
+    assert_eq!(points.len(), 5);
+    assert!(!points
+        .iter()
+        .any(|p| p.size == 256 && p.associativity == 8 && p.line_size == 64));
+}
+
+#[test]
+fn test_larger_data_cache_reduces_misses() {
+    let spec = SweepSpec::from_json(
+        r#"{"cache": "data", "size": [256, 4096], "associativity": [1], "line_size": [64]}"#,
+    )
+    .unwrap();
+    let sweep = CacheSweep::new(spec, PipelineConfig::new(5), 32768);
+    let report = sweep.run(load_strided_program);
+
+    assert_eq!(report.results.len(), 2);
+    let small = &report.results[0].stats;
+    let large = &report.results[1].stats;
+    assert_eq!(small.dcache_accesses, 2 * LOADS_PER_PASS);
+    // The small cache misses on both passes, the large one only on the first
+    assert_eq!(large.dcache_misses, LOADS_PER_PASS);
+    assert!(small.dcache_misses > large.dcache_misses);
+    assert!(small.cycles > large.cycles);
+
+    let csv = report.to_csv();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert!(lines[0].starts_with("cache,size,associativity,line_size"));
+    assert!(lines[1].starts_with("data,256,1,64,"));
+}
+
+#[test]
+fn test_cache_sweep_cli_writes_csv() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("sweep.json");
+    let csv_path = dir.path().join("sweep.csv");
+    std::fs::write(
+        &config_path,
+        r#"{"cache": "both", "size": {"min": 512, "max": 2048}, "associativity": [2], "line_size": [32]}"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("cache-sweep")
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--output")
+        .arg(&csv_path);
+    cmd.assert().success();
+
+    let csv = std::fs::read_to_string(&csv_path).unwrap();
+    // Header plus one row per size
+    assert_eq!(csv.lines().count(), 4);
+    assert!(csv.lines().skip(1).all(|line| line.starts_with("both,")));
+}
@@ -0,0 +1,69 @@
+// tests/caches/cache_validation.rs
+// Tests for the cross-check of the cache model against closed-form miss rates
+
+use vmips_rust::timing_simulator::cache_validation::{
+    parse_check, standard_checks, MissRateCheck, RANDOM_TOLERANCE,
+};
+use vmips_rust::timing_simulator::config::CacheConfig;
+use vmips_rust::timing_simulator::stress::AccessPattern;
+
+#[test]
+fn test_cache_model_matches_the_standard_checks() {
+    let checks = standard_checks();
+    assert!(checks.len() > 100);
+    let failures: Vec<String> = checks
+        .iter()
+        .map(|check| check.run().unwrap())
+        .filter(|result| !result.passed())
+        .map(|result| result.to_string())
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn test_closed_forms_follow_the_geometry() {
+    let rate = |cache: &str, pattern: &str, working_set: usize| {
+        let check = parse_check(
+            cache,
+            "lru",
+            AccessPattern::parse(pattern).unwrap(),
+            working_set,
+        )
+        .unwrap();
+        let result = check.run().unwrap();
+        assert!(result.passed(), "{}", result);
+        result.expected
+    };
+
+    // Words of 32-byte blocks: one miss per block, every sweep once the
+    // working set outgrows the cache
+    assert_eq!(rate("4096/2/32", "sequential", 2048), 1.0 / 32.0);
+    assert_eq!(rate("4096/2/32", "sequential", 8192), 1.0 / 8.0);
+    // Two blocks 4 KB apart share a set, which one way cannot hold
+    assert_eq!(rate("4096/1/32", "stride:4096", 8192), 1.0);
+    assert_eq!(rate("4096/2/32", "stride:4096", 8192), 0.25);
+    // A random stream over four times the cache hits a quarter of the time
+    let expected = rate("4096/4/32", "random", 16384);
+    assert!((expected - 0.75).abs() < 1e-9);
+}
+
+#[test]
+fn test_divergence_and_unsupported_checks_are_reported() {
+    let check = MissRateCheck::new(CacheConfig::new(4096, 2, 32), AccessPattern::Random, 16384);
+    let mut result = check.run().unwrap();
+    assert!(result.passed());
+    result.expected += 2.0 * RANDOM_TOLERANCE;
+    assert!(!result.passed());
+    assert!(result.to_string().starts_with("FAILED cache=4096/2/32"));
+
+    assert!(parse_check("4096/2/32", "lfu", AccessPattern::Random, 8192)
+        .unwrap()
+        .run()
+        .is_err());
+    assert!(parse_check("4096/2/32", "lru", AccessPattern::Stride(12), 8192).is_err());
+    assert!(parse_check("4096/2/32", "mru", AccessPattern::Random, 8192).is_err());
+    assert!(parse_check("4096/2/32", "lru", AccessPattern::Random, 6000).is_err());
+    let mut prefetching = check;
+    prefetching.cache.prefetch_enabled = true;
+    assert!(prefetching.run().is_err());
+}
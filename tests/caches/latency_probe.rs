@@ -0,0 +1,47 @@
+// tests/caches/latency_probe.rs
+// Tests for the instruction latency and throughput probe
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use vmips_rust::timing_simulator::config::PipelineConfig;
+use vmips_rust::timing_simulator::latency_probe::{LatencyProbe, ProbeClass};
+
+#[test]
+fn test_probe_reports_every_class() {
+    let probe = LatencyProbe::new(PipelineConfig::new(5), 16);
+    let report = probe.run();
+
+    assert_eq!(report.results.len(), ProbeClass::all().len());
+    for result in &report.results {
+        assert!(!result.stalled, "{} stalled", result.class.name());
+        assert!(result.throughput.unwrap() > 0.0);
+        // Only classes with a destination register get a latency
+        assert_eq!(result.latency.is_some(), result.class.has_dependent_chain());
+    }
+}
+
+#[test]
+fn test_generated_chains_have_expected_shape() {
+    let chain = ProbeClass::IntAlu.dependent_chain(4);
+    assert_eq!(chain.len(), 4);
+    // add $t0, $t0, $s1 repeated
+    assert!(chain.iter().all(|&word| word == 0x01114020));
+
+    // The independent stream never reads a register it writes
+    let stream = ProbeClass::IntAlu.independent_stream(8);
+    for word in stream {
+        let rs = (word >> 21) & 0x1F;
+        let rd = (word >> 11) & 0x1F;
+        assert_ne!(rs, rd);
+    }
+}
+
+#[test]
+fn test_latency_probe_cli_prints_table() {
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("latency-probe").arg("--chain-length").arg("8");
+    cmd.assert()
+        .success()
+        .stdout(contains("Instruction Latency Probe (chain length 8)"))
+        .stdout(contains("fp-divide (div.s)"));
+}
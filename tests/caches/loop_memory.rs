@@ -0,0 +1,176 @@
+// tests/caches/loop_memory.rs
+// Tests for the cache line report of the hottest loops
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::assembler::Assembler;
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{BranchPredictorType, CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::loop_memory::{
+    CacheGeometry, LoopMemoryReport, MemoryTrace, MissCounts,
+};
+use vmips_rust::timing_simulator::simulator::Simulator;
+
+/// Walks the first column of an 8x256 word matrix twice
+const COLUMN_PROGRAM: &str = "
+.data
+matrix: .space 8192
+.text
+    li $s1, 2
+outer:
+    la $t0, matrix
+    li $t1, 8
+inner:
+    lw $t2, 0($t0)
+    addi $t0, $t0, 1024
+    addi $t1, $t1, -1
+    bne $t1, $zero, inner
+    addi $s1, $s1, -1
+    bne $s1, $zero, outer
+    li $v0, 10
+    syscall
+";
+
+fn record(source: &str, data_cache: CacheConfig) -> MemoryTrace {
+    let program = Assembler::new().assemble_program(source).unwrap();
+    let config = PipelineConfig::new(5).with_branch_prediction(true, BranchPredictorType::TwoBit);
+    let mut simulator = Simulator::new(
+        config,
+        CacheConfig::new(4096, 2, 64),
+        data_cache,
+        0x0050_0000,
+    );
+    simulator.visualization = None;
+    let image = ProgramImage::from_bytes(&program.to_bytes()).unwrap();
+    simulator.load_image(&image).unwrap();
+    simulator.enable_memory_trace();
+    simulator.run();
+    simulator.memory_trace.unwrap()
+}
+
+#[test]
+fn test_loops_and_strides_are_found() {
+    let trace = record(
+        "
+.data
+values: .space 256
+.text
+    la $t0, values
+    li $t1, 32
+loop:
+    lw $t2, 0($t0)
+    sw $t2, 4($t0)
+    addi $t0, $t0, 8
+    addi $t1, $t1, -1
+    bne $t1, $zero, loop
+    li $v0, 10
+    syscall
+",
+        CacheConfig::new(1024, 2, 64),
+    );
+    assert_eq!(trace.accesses.len(), 64);
+    assert_eq!(trace.back_edges.get(&(0x0040001C, 0x0040000C)), Some(&31));
+
+    let report = LoopMemoryReport::new(&trace, &CacheConfig::new(1024, 2, 64), 5);
+    assert_eq!(
+        report.geometry,
+        CacheGeometry {
+            sets: 8,
+            ways: 2,
+            line_size: 64
+        }
+    );
+    assert_eq!(report.loops.len(), 1);
+    let memory = &report.loops[0];
+    assert_eq!((memory.start, memory.end), (0x0040000C, 0x0040001C));
+    assert_eq!(memory.iterations, 32);
+    assert_eq!(memory.instructions, 160);
+    assert_eq!(memory.accesses, 64);
+    // 256 bytes are four lines, each missing once
+    assert_eq!(memory.lines.len(), 4);
+    assert_eq!(
+        memory.misses,
+        MissCounts {
+            compulsory: 4,
+            capacity: 0,
+            conflict: 0
+        }
+    );
+    assert_eq!(memory.set_lines, vec![1, 1, 1, 1, 0, 0, 0, 0]);
+
+    let strides: Vec<Option<i64>> = memory.patterns.iter().map(|p| p.stride).collect();
+    assert_eq!(strides, vec![Some(8), Some(8)]);
+    assert_eq!(memory.patterns[1].instruction, "sw $10, 4($8)");
+    assert_eq!(memory.patterns[0].sets_reached, None);
+}
+
+#[test]
+fn test_power_of_two_strides_cause_conflict_misses() {
+    // A direct-mapped cache of 16 lines: every row of the matrix is 1 KiB,
+    // so the whole column lands in set 0
+    let direct_mapped = CacheConfig::new(1024, 1, 64);
+    let trace = record(COLUMN_PROGRAM, direct_mapped.clone());
+    let report = LoopMemoryReport::new(&trace, &direct_mapped, 2);
+
+    // The outer loop contains the inner one, so it runs more instructions
+    assert_eq!(report.loops[0].start, 0x00400004);
+    let memory = &report.loops[1];
+    assert_eq!((memory.start, memory.end), (0x00400010, 0x0040001C));
+    assert_eq!(memory.iterations, 16);
+    assert_eq!(memory.lines.len(), 8);
+    // The second pass would hit in a fully associative cache of the same size
+    assert_eq!(
+        memory.misses,
+        MissCounts {
+            compulsory: 8,
+            capacity: 0,
+            conflict: 8
+        }
+    );
+    assert_eq!(memory.set_lines[0], 8);
+    assert_eq!(memory.set_conflicts[0], 8);
+    assert_eq!(memory.patterns[0].stride, Some(1024));
+    assert_eq!(memory.patterns[0].sets_reached, Some(1));
+
+    let rendered = report.render();
+    assert!(rendered.contains("Hot loops (data cache: 16 sets x 1 ways, 64-byte lines)"));
+    assert!(rendered.contains("Misses: 8 compulsory, 0 capacity, 8 conflict"));
+    assert!(rendered.contains("Sets:      |8...............|"));
+    assert!(rendered.contains("Conflicts: |^               |"));
+    assert!(rendered.contains(
+        "power-of-two stride +1024 maps every access to 1 of 16 sets, causing 8 conflict misses"
+    ));
+
+    // With eight ways the column fits, and the second pass hits
+    let associative = CacheConfig::new(1024, 8, 64);
+    let trace = record(COLUMN_PROGRAM, associative.clone());
+    let report = LoopMemoryReport::new(&trace, &associative, 1);
+    assert_eq!(report.loops[0].misses.total(), 8);
+    assert!(!report.render().contains("power-of-two stride"));
+}
+
+#[test]
+fn test_hot_loops_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("column.s");
+    std::fs::write(&source, COLUMN_PROGRAM).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&source)
+        .arg("--hot-loops")
+        .arg("2");
+    cmd.assert()
+        .success()
+        .stdout(contains(
+            "Hot loops (data cache: 128 sets x 4 ways, 64-byte lines)",
+        ))
+        .stdout(contains(
+            "Loop 1: 0x00400004-0x00400024, 2 iterations, 74 instructions",
+        ))
+        .stdout(contains("Loop 2: 0x00400010-0x0040001C, 16 iterations"))
+        .stdout(contains("stride +1024"));
+}
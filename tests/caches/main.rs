@@ -0,0 +1,10 @@
+// tests/caches/main.rs
+// Tests for the cache models, their policies and the cache reports
+
+mod cache_side_channel;
+mod cache_sweep;
+mod cache_validation;
+mod latency_probe;
+mod loop_memory;
+mod uncached_regions;
+mod write_policy;
@@ -0,0 +1,89 @@
+// tests/caches/uncached_regions.rs
+// Tests for address regions whose loads and stores bypass the caches
+
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig, DEVICE_REGION};
+use vmips_rust::timing_simulator::simulator::{ExecutionMode, Simulator};
+
+/// Three loads of the same word
+const POLL_PROGRAM: [u32; 4] = [
+    0x8C091000, // lw $t1, 0x1000($zero)
+    0x8C0A1000, // lw $t2, 0x1000($zero)
+    0x8C0B1000, // lw $t3, 0x1000($zero)
+    0x00000000, // nop
+];
+
+// Stall cycles of each load, data cache accesses and uncached accesses
+fn run(config: PipelineConfig) -> (Vec<usize>, usize, usize) {
+    let mut simulator = Simulator::new(
+        config,
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    simulator.visualization = None;
+    for (i, &word) in POLL_PROGRAM.iter().enumerate() {
+        simulator.memory.write_word_init(i * 4, word);
+    }
+    simulator.memory.write_word_init(0x1000, 7);
+    simulator.enable_timeline();
+    simulator.run();
+    assert_eq!(simulator.registers.read(11), 7);
+
+    let stalls = simulator.timeline.unwrap()[..3]
+        .iter()
+        .map(|timing| timing.stall_cycles)
+        .collect();
+    match simulator.execution_mode {
+        ExecutionMode::InOrder(pipeline) => {
+            let accesses = pipeline.cache_hierarchy.l1_data_cache.stats.accesses;
+            (stalls, accesses, pipeline.uncached_accesses)
+        },
+        ExecutionMode::OutOfOrder(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn test_uncached_region_bypasses_data_cache() {
+    // Cached, only the first load misses
+    let (cached, accesses, uncached) = run(PipelineConfig::new(5));
+    assert_eq!(accesses, 3);
+    assert_eq!(uncached, 0);
+    assert!(cached[0] > cached[1]);
+
+    // Uncached, every load pays the miss penalty and the cache never sees them
+    let config = PipelineConfig::new(5).with_uncached_region(0x1000..=0x1FFF);
+    let (stalls, accesses, uncached) = run(config);
+    assert_eq!(accesses, 0);
+    assert_eq!(uncached, 3);
+    assert!(stalls[1] >= 10 && stalls[2] >= 10);
+}
+
+#[test]
+fn test_device_registers_uncached_by_default() {
+    let config = PipelineConfig::new(5);
+    assert_eq!(config.uncached_regions, vec![DEVICE_REGION]);
+    let variant = TimingVariant::default();
+    assert!(variant
+        .describe()
+        .contains(",uncached=0xFF000000-0xFFFFFFFF,"));
+}
+
+#[test]
+fn test_uncached_setting_in_variants() {
+    let variant = TimingVariant::parse("uncached=0x1000-0x1FFF+0xFFFF0000-0xFFFF0FFF").unwrap();
+    assert_eq!(
+        variant.pipeline_config.uncached_regions,
+        vec![0x1000..=0x1FFF, 0xFFFF_0000..=0xFFFF_0FFF]
+    );
+    let reparsed = TimingVariant::parse(&variant.describe()).unwrap();
+    assert_eq!(
+        reparsed.pipeline_config.uncached_regions,
+        variant.pipeline_config.uncached_regions
+    );
+
+    let none = TimingVariant::parse("uncached=none").unwrap();
+    assert!(none.pipeline_config.uncached_regions.is_empty());
+    assert!(TimingVariant::parse("uncached=0x2000-0x1000").is_err());
+    assert!(TimingVariant::parse("uncached=0x1000").is_err());
+}
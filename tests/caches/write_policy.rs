@@ -0,0 +1,82 @@
+// tests/caches/write_policy.rs
+// Tests for write-back and write-through caches, write allocation and the
+// write traffic they send to memory
+
+use vmips_rust::functional_simulator::memory::Memory;
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::components::{AllocationPolicy, Cache, WritePolicy};
+use vmips_rust::timing_simulator::config::CacheConfig;
+
+// Direct-mapped, 16 sets of 16-byte lines, so 0x000 and 0x100 share a set
+fn cache(write_back: bool, write_allocate: bool) -> Cache {
+    let config = CacheConfig::new(256, 1, 16).with_write_policy(write_back, write_allocate);
+    Cache::new(config, Memory::new(0x10000))
+}
+
+#[test]
+fn test_policies_come_from_config() {
+    let cache = cache(false, false);
+    assert_eq!(cache.write_policy, WritePolicy::WriteThrough);
+    assert_eq!(cache.allocation_policy, AllocationPolicy::NoWriteAllocate);
+    let default = Cache::new(CacheConfig::new(256, 1, 16), Memory::new(0x10000));
+    assert_eq!(default.write_policy, WritePolicy::WriteBack);
+    assert_eq!(default.allocation_policy, AllocationPolicy::WriteAllocate);
+}
+
+#[test]
+fn test_write_back_writes_dirty_lines_on_eviction() {
+    let mut cache = cache(true, true);
+    cache.write(0x000, &[1, 2, 3, 4]);
+    cache.write(0x004, &[5, 6, 7, 8]);
+    assert_eq!(cache.stats.write_traffic, 0);
+    assert_eq!(cache.memory.read_byte(0).unwrap(), 0);
+
+    // Reading a conflicting line evicts the dirty one
+    cache.read(0x100);
+    assert_eq!(cache.stats.evictions, 1);
+    assert_eq!(cache.stats.dirty_evictions, 1);
+    assert_eq!(cache.stats.writebacks, 1);
+    assert_eq!(cache.stats.write_traffic, 16);
+    assert_eq!(cache.memory.read_byte(4).unwrap(), 5);
+
+    // A clean line leaves without a writeback
+    cache.read(0x000);
+    assert_eq!(cache.stats.dirty_evictions, 1);
+}
+
+#[test]
+fn test_write_through_sends_every_store() {
+    let mut cache = cache(false, true);
+    cache.write(0x000, &[1, 2, 3, 4]);
+    cache.write(0x004, &[5, 6, 7, 8]);
+    assert_eq!(cache.stats.write_hits, 1);
+    assert_eq!(cache.stats.write_traffic, 8);
+    assert_eq!(cache.memory.read_byte(4).unwrap(), 5);
+
+    cache.read(0x100);
+    assert_eq!(cache.stats.dirty_evictions, 0);
+    assert_eq!(cache.stats.write_traffic, 8);
+}
+
+#[test]
+fn test_no_write_allocate_skips_the_cache() {
+    let mut cache = cache(false, false);
+    cache.write(0x000, &[1, 2, 3, 4]);
+    assert_eq!(cache.stats.write_traffic, 4);
+    assert_eq!(cache.memory.read_byte(0).unwrap(), 1);
+
+    // The line was not brought in, so the read misses
+    cache.read(0x000);
+    assert_eq!(cache.stats.read_hits, 0);
+}
+
+#[test]
+fn test_write_policy_settings_in_variants() {
+    let variant = TimingVariant::parse("write-policy=write-through,write-allocate=off").unwrap();
+    assert!(!variant.data_cache_config.write_back);
+    assert!(!variant.data_cache_config.write_allocate);
+    assert!(variant
+        .describe()
+        .contains(",write-policy=write-through,write-allocate=off,"));
+    assert!(TimingVariant::parse("write-policy=write-around").is_err());
+}
@@ -0,0 +1,182 @@
+// tests/devices/console.rs
+// Tests for the memory-mapped console screen and keyboard
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::console::{ConsoleConfig, DEFAULT_ATTRIBUTE};
+use vmips_rust::functional_simulator::cp0::{AsyncEvent, CP0_CAUSE};
+use vmips_rust::functional_simulator::simulator::Simulator;
+
+/// Enables the keyboard interrupt and waits until the handler sees a `q`.
+/// The handler echoes every key to the screen.
+const ECHO_PROGRAM: &str = "
+.text
+main:
+    lui $t0, 0xFFFF
+    li $t1, 2
+    sw $t1, 0($t0)
+    li $t1, 0x0401
+    mtc0 $t1, $12
+wait:
+    addi $s0, $s0, 1
+    beq $s7, $zero, wait
+    li $v0, 10
+    syscall
+.ktext 0x80000180
+    lui $k0, 0xFFFF
+    lw $k1, 4($k0)
+    ori $k0, $k0, 0x1000
+    sw $k1, 0xFF4($k0)
+    addi $k1, $k1, -113
+    bne $k1, $zero, done
+    li $s7, 1
+done:
+    eret
+";
+
+#[test]
+fn test_screen_cells_and_registers() {
+    let mut simulator = Simulator::from_assembly(
+        "
+.text
+    lui $s0, 0xFFFF
+    ori $s0, $s0, 0x1000
+    # Cell (0, 0) is 'A' in bright white on blue, cell (0, 1) is 'B'
+    lui $t0, 0x0700
+    ori $t0, $t0, 0x1F41
+    sw $t0, 0($s0)
+    li $t0, 0x42
+    sb $t0, 2($s0)
+    lw $s3, 0($s0)
+    # Print through the cursor on the last row, then scroll
+    li $t0, 0x00030007
+    sw $t0, 0xFF0($s0)
+    li $t0, 0x68
+    sw $t0, 0xFF4($s0)
+    li $t0, 0x69
+    sw $t0, 0xFF4($s0)
+    li $t0, 10
+    sw $t0, 0xFF4($s0)
+    li $t0, 0x21
+    sw $t0, 0xFF4($s0)
+    lw $s1, 0xFFC($s0)
+    lw $s2, 0xFF0($s0)
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+    let console = simulator.attach_console(ConsoleConfig::new(10, 4));
+    simulator.run().unwrap();
+
+    // The newline on the last row scrolled the screen up by one
+    assert_eq!(console.text(), "\n\n       hi\n!");
+    assert_eq!(console.cell(3, 0), (b'!', DEFAULT_ATTRIBUTE));
+    assert_eq!(console.cursor(), (3, 1));
+    assert_eq!(simulator.registers.read(17), (4 << 16) | 10);
+    assert_eq!(simulator.registers.read(18), (3 << 16) | 1);
+    // The first two cells, read back before the scroll
+    assert_eq!(simulator.registers.read(19), 0x0742_1F41);
+    assert_eq!(
+        console.render(),
+        "+----------+\n|          |\n|          |\n|       hi |\n|!         |\n+----------+\n"
+    );
+    assert!(console.take_dirty());
+    assert!(!console.take_dirty());
+
+    // Attributes become ANSI colours
+    let mut simulator = Simulator::from_assembly(
+        "
+.text
+    lui $s0, 0xFFFF
+    ori $s0, $s0, 0x1000
+    li $t0, 0x1E
+    sw $t0, 0xFF8($s0)
+    li $t0, 0x58
+    sw $t0, 0xFF4($s0)
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+    let console = simulator.attach_console(ConsoleConfig::new(2, 1));
+    simulator.run().unwrap();
+    assert_eq!(console.cell(0, 0), (b'X', 0x1E));
+    assert_eq!(
+        console.render_ansi(),
+        "+--+\n|\x1b[0;93;44mX\x1b[0m |\n+--+\n"
+    );
+}
+
+#[test]
+fn test_keyboard_interrupts_echo_keys() {
+    let mut simulator = Simulator::from_assembly(ECHO_PROGRAM).unwrap();
+    let console = simulator.attach_console(ConsoleConfig::default());
+    console.press_keys(b"hi\n");
+    // Key press events reach the keyboard device as well
+    simulator.schedule_event(200, AsyncEvent::KeyPress(b'q'));
+    simulator.run().unwrap();
+
+    assert_eq!(console.text().trim_end(), "hi\nq");
+    assert_eq!(simulator.interrupts.interrupts_taken, 4);
+    assert_eq!(console.pending_keys(), 0);
+    // The main loop ran until the last key arrived
+    assert!(simulator.registers.read(16) > 50);
+    // Reading the last key lowered the keyboard line
+    assert_eq!(simulator.registers.read_cp0(CP0_CAUSE) & (1 << 10), 0);
+
+    // Without the interrupt enabled in the keyboard, keys only wait
+    let mut simulator =
+        Simulator::from_assembly(&ECHO_PROGRAM.replace("li $t1, 2\n", "li $t1, 0\n")).unwrap();
+    let console = simulator.attach_console(ConsoleConfig::default());
+    simulator.set_max_steps(500);
+    console.press_key(b'q');
+    simulator.run().unwrap();
+    assert_eq!(simulator.interrupts.interrupts_taken, 0);
+    assert_eq!(console.pending_keys(), 1);
+    assert_eq!(console.text().trim_end(), "");
+}
+
+#[test]
+fn test_console_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("hello.s");
+    std::fs::write(
+        &source,
+        "
+.text
+    lui $s0, 0xFFFF
+    ori $s0, $s0, 0x1000
+    li $t0, 0x4F
+    sw $t0, 0xFF4($s0)
+    li $t0, 0x4B
+    sw $t0, 0xFF4($s0)
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--console")
+        .arg("--console-size")
+        .arg("6x2")
+        .write_stdin("");
+    cmd.assert().success().stdout(contains(
+        "Console:\n+------+\n|OK    |\n|      |\n+------+\n",
+    ));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--console-size")
+        .arg("80x60");
+    cmd.assert()
+        .failure()
+        .stderr(contains("does not fit in the screen buffer"));
+}
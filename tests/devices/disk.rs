@@ -0,0 +1,247 @@
+// tests/devices/disk.rs
+// Tests for the memory-mapped block storage device and its DMA engine
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::disk::{
+    DiskConfig, DiskStatistics, DISK_DONE, DISK_ERROR, SECTOR_SIZE,
+};
+use vmips_rust::functional_simulator::simulator::Simulator;
+
+#[test]
+fn test_sectors_move_through_the_buffer() {
+    let mut simulator = Simulator::from_assembly(
+        "
+.text
+    lui $s0, 0xFFFF
+    ori $s0, $s0, 0x2000
+    lw $s1, 0x18($s0)
+    # Read sector 1 into the buffer
+    li $t0, 1
+    sw $t0, 8($s0)
+    sw $t0, 0($s0)
+    lw $s4, 4($s0)
+poll_read:
+    lw $t1, 4($s0)
+    andi $t1, $t1, 2
+    beq $t1, $zero, poll_read
+    lw $s2, 0x200($s0)
+    sw $zero, 4($s0)
+    # Change the first byte and write the buffer to sector 2
+    li $t0, 0x21
+    sb $t0, 0x200($s0)
+    li $t0, 2
+    sw $t0, 8($s0)
+    sw $t0, 0($s0)
+poll_write:
+    lw $t1, 4($s0)
+    andi $t1, $t1, 2
+    beq $t1, $zero, poll_write
+    lw $s3, 4($s0)
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+    let disk = simulator
+        .attach_disk(DiskConfig::in_memory(4).with_sector_latency(5))
+        .unwrap();
+    disk.write_sector(1, b"HELLO");
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.registers.read(17), 4);
+    // Busy right after the command
+    assert_eq!(simulator.registers.read(20), 1);
+    assert_eq!(simulator.registers.read(18), u32::from_le_bytes(*b"HELL"));
+    assert_eq!(simulator.registers.read(19), DISK_DONE);
+    assert_eq!(&disk.read_sector(2)[..6], b"!ELLO\0");
+    assert_eq!(disk.read_sector(3), vec![0; SECTOR_SIZE]);
+    assert_eq!(
+        disk.statistics(),
+        DiskStatistics {
+            commands: 2,
+            sectors_read: 1,
+            sectors_written: 1,
+            dma_transfers: 0,
+            errors: 0,
+        }
+    );
+    assert!(!disk.busy());
+}
+
+#[test]
+fn test_dma_transfers_raise_completion_interrupts() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("disk.img");
+    let image: Vec<u8> = [0x11u8, 0x22, 0x33, 0x44]
+        .iter()
+        .flat_map(|&byte| vec![byte; SECTOR_SIZE])
+        .collect();
+    std::fs::write(&path, &image).unwrap();
+
+    let mut simulator = Simulator::from_assembly(
+        "
+.data 0x10001000
+buffer: .space 1024
+.text
+    lui $s0, 0xFFFF
+    ori $s0, $s0, 0x2000
+    # DMA and interrupts on, then read sectors 0 and 1 into the buffer
+    li $t0, 3
+    sw $t0, 0x14($s0)
+    la $t0, buffer
+    sw $t0, 0x10($s0)
+    li $t0, 2
+    sw $t0, 0xC($s0)
+    sw $zero, 8($s0)
+    li $t0, 0x0801
+    mtc0 $t0, $12
+    li $t0, 1
+    sw $t0, 0($s0)
+wait_read:
+    addi $s1, $s1, 1
+    beq $s7, $zero, wait_read
+    # Write the second half of the buffer to sector 3
+    li $t0, 3
+    sw $t0, 8($s0)
+    la $t0, buffer
+    addi $t0, $t0, 512
+    sw $t0, 0x10($s0)
+    li $t0, 1
+    sw $t0, 0xC($s0)
+    li $t0, 2
+    sw $t0, 0($s0)
+wait_write:
+    addi $s2, $s2, 1
+    li $t1, 2
+    bne $s7, $t1, wait_write
+    li $v0, 10
+    syscall
+.ktext 0x80000180
+    lui $k0, 0xFFFF
+    ori $k0, $k0, 0x2000
+    lw $s6, 4($k0)
+    sw $zero, 4($k0)
+    addi $s7, $s7, 1
+    eret
+",
+    )
+    .unwrap();
+    let disk = simulator
+        .attach_disk(DiskConfig::new(&path).with_sector_latency(20))
+        .unwrap();
+    assert_eq!(disk.sectors(), 4);
+    simulator.run().unwrap();
+
+    // The buffer sits above the start of the data segment, which aliases
+    // the exception vector
+    let buffer = simulator.memory.read_block(0x10001000, 1024).unwrap();
+    assert!(buffer[..SECTOR_SIZE].iter().all(|&byte| byte == 0x11));
+    assert!(buffer[SECTOR_SIZE..].iter().all(|&byte| byte == 0x22));
+    assert_eq!(simulator.interrupts.interrupts_taken, 2);
+    assert_eq!(simulator.registers.read(22), DISK_DONE);
+    // The guest kept running while both transfers were in flight
+    assert!(simulator.registers.read(17) >= 15);
+    assert!(simulator.registers.read(18) >= 5);
+
+    // The write went through to the image file
+    let written = std::fs::read(&path).unwrap();
+    assert!(written[3 * SECTOR_SIZE..].iter().all(|&byte| byte == 0x22));
+    assert!(written[2 * SECTOR_SIZE..3 * SECTOR_SIZE]
+        .iter()
+        .all(|&byte| byte == 0x33));
+    assert_eq!(disk.statistics().dma_transfers, 2);
+
+    // Sectors past the end fail at once, and a disk without DMA ignores
+    // the DMA bit
+    let mut simulator = Simulator::from_assembly(
+        "
+.text
+    lui $s0, 0xFFFF
+    ori $s0, $s0, 0x2000
+    li $t0, 3
+    sw $t0, 0x14($s0)
+    lw $s1, 0x14($s0)
+    li $t0, 5
+    sw $t0, 8($s0)
+    li $t0, 1
+    sw $t0, 0($s0)
+    lw $s2, 4($s0)
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+    let disk = simulator
+        .attach_disk(DiskConfig::in_memory(2).with_dma(false))
+        .unwrap();
+    simulator.run().unwrap();
+    assert_eq!(simulator.registers.read(17), 2);
+    assert_eq!(simulator.registers.read(18), DISK_DONE | DISK_ERROR);
+    assert_eq!(disk.statistics().errors, 1);
+}
+
+#[test]
+fn test_disk_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    let image = temp_dir.path().join("boot.img");
+    let mut sector = b"Booted from disk\n\0".to_vec();
+    sector.resize(SECTOR_SIZE, 0);
+    std::fs::write(&image, &sector).unwrap();
+    let source = temp_dir.path().join("boot.s");
+    std::fs::write(
+        &source,
+        "
+.data
+buffer: .space 512
+.text
+    lui $s0, 0xFFFF
+    ori $s0, $s0, 0x2000
+    li $t0, 1
+    sw $t0, 0x14($s0)
+    la $t0, buffer
+    sw $t0, 0x10($s0)
+    li $t0, 1
+    sw $t0, 0xC($s0)
+    sw $t0, 0($s0)
+poll:
+    lw $t1, 4($s0)
+    andi $t1, $t1, 2
+    beq $t1, $zero, poll
+    la $a0, buffer
+    li $v0, 4
+    syscall
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--disk")
+        .arg(&image)
+        .arg("--disk-latency")
+        .arg("10");
+    cmd.assert()
+        .success()
+        .stdout(contains("Booted from disk"))
+        .stdout(contains(
+            "Disk: 1 commands, 1 sectors read, 0 sectors written, 1 DMA transfers, 0 errors",
+        ));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--disk")
+        .arg("missing.img");
+    cmd.assert()
+        .success()
+        .stderr(contains("Failed to open disk image"));
+}
@@ -0,0 +1,215 @@
+// tests/devices/framebuffer.rs
+// Tests for the memory-mapped framebuffer and its PNG and BMP output
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::framebuffer::{
+    encode_bmp, encode_png, FramebufferConfig, ImageFormat,
+};
+use vmips_rust::functional_simulator::simulator::Simulator;
+
+/// Fills a 4x4 framebuffer with a red/green gradient and presents it three
+/// times
+const GRADIENT_PROGRAM: &str = "
+.text
+    lui $s0, 0xFF00
+    lui $s1, 0xFFFF
+    ori $s1, $s1, 0x3000
+    lw $s2, 0($s1)
+    lw $s3, 4($s1)
+    li $t1, 0
+row:
+    li $t0, 0
+column:
+    sll $t2, $t0, 22
+    sll $t3, $t1, 14
+    or $t2, $t2, $t3
+    mul $t3, $t1, $s2
+    add $t3, $t3, $t0
+    sll $t3, $t3, 2
+    add $t3, $t3, $s0
+    sw $t2, 0($t3)
+    addi $t0, $t0, 1
+    bne $t0, $s2, column
+    addi $t1, $t1, 1
+    bne $t1, $s3, row
+    # Blue in the first pixel, then present three frames
+    li $t0, 0xFF
+    sb $t0, 0($s0)
+    li $t4, 3
+present:
+    sw $zero, 8($s1)
+    addi $t4, $t4, -1
+    bne $t4, $zero, present
+    lw $s4, 8($s1)
+    li $v0, 10
+    syscall
+";
+
+// The uncompressed data of a PNG written with stored deflate blocks
+fn png_pixels(png: &[u8]) -> (u32, u32, Vec<u8>) {
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    let mut position = 8;
+    let mut size = (0, 0);
+    let mut zlib = Vec::new();
+    while position < png.len() {
+        let length = u32::from_be_bytes(png[position..position + 4].try_into().unwrap()) as usize;
+        let kind = &png[position + 4..position + 8];
+        let data = &png[position + 8..position + 8 + length];
+        match kind {
+            b"IHDR" => {
+                size = (
+                    u32::from_be_bytes(data[0..4].try_into().unwrap()),
+                    u32::from_be_bytes(data[4..8].try_into().unwrap()),
+                );
+                assert_eq!(&data[8..], &[8, 2, 0, 0, 0]);
+            },
+            b"IDAT" => zlib.extend_from_slice(data),
+            b"IEND" => assert_eq!(&png[position + 8..position + 12], &[0xAE, 0x42, 0x60, 0x82]),
+            _ => panic!("unexpected chunk"),
+        }
+        position += length + 12;
+    }
+
+    let mut raw = Vec::new();
+    let mut offset = 2;
+    loop {
+        let last = zlib[offset] == 1;
+        let length = u16::from_le_bytes([zlib[offset + 1], zlib[offset + 2]]) as usize;
+        assert_eq!(
+            !u16::from_le_bytes([zlib[offset + 3], zlib[offset + 4]]) as usize,
+            length
+        );
+        raw.extend_from_slice(&zlib[offset + 5..offset + 5 + length]);
+        offset += 5 + length;
+        if last {
+            break;
+        }
+    }
+    assert_eq!(offset + 4, zlib.len());
+    (size.0, size.1, raw)
+}
+
+#[test]
+fn test_pixels_and_frames_from_the_guest() {
+    let mut simulator = Simulator::from_assembly(GRADIENT_PROGRAM).unwrap();
+    let framebuffer = simulator.attach_framebuffer(FramebufferConfig::new(4, 4));
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.registers.read(18), 4);
+    assert_eq!(simulator.registers.read(19), 4);
+    assert_eq!(framebuffer.pixel(0, 0), 0x0000_00FF);
+    assert_eq!(framebuffer.pixel(3, 0), 0x00C0_0000);
+    assert_eq!(framebuffer.pixel(1, 2), 0x0040_8000);
+    assert_eq!(framebuffer.pixel(3, 3), 0x00C0_C000);
+    assert_eq!(framebuffer.pixels().len(), 16);
+    assert_eq!(framebuffer.frames(), 3);
+    assert_eq!(simulator.registers.read(20), 3);
+    assert!(framebuffer.written_frames().is_empty());
+
+    // Large framebuffers reach past the usual 4 KiB device window
+    let mut simulator = Simulator::from_assembly(
+        "
+.text
+    lui $t0, 0xFF00
+    ori $t0, $t0, 0xFFFC
+    li $t1, 0x123456
+    sw $t1, 0($t0)
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+    let framebuffer = simulator.attach_framebuffer(FramebufferConfig::new(128, 128));
+    simulator.run().unwrap();
+    assert_eq!(framebuffer.pixel(127, 127), 0x0012_3456);
+}
+
+#[test]
+fn test_png_and_bmp_encoding() {
+    let pixels = [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0x0012_3456];
+    let (width, height, raw) = png_pixels(&encode_png(2, 2, &pixels));
+    assert_eq!((width, height), (2, 2));
+    assert_eq!(
+        raw,
+        vec![0, 0xFF, 0, 0, 0, 0xFF, 0, 0, 0, 0, 0xFF, 0x12, 0x34, 0x56]
+    );
+
+    // More data than one stored block holds
+    let large = vec![0x0080_8080; 200 * 200];
+    let (_, _, raw) = png_pixels(&encode_png(200, 200, &large));
+    assert_eq!(raw.len(), 200 * 601);
+    assert!(raw
+        .chunks(601)
+        .all(|row| row[0] == 0 && row[1..].iter().all(|&b| b == 0x80)));
+
+    let bmp = encode_bmp(2, 2, &pixels);
+    assert_eq!(&bmp[..2], b"BM");
+    assert_eq!(bmp.len(), 54 + 2 * 8);
+    assert_eq!(u32::from_le_bytes(bmp[2..6].try_into().unwrap()), 70);
+    assert_eq!(u32::from_le_bytes(bmp[18..22].try_into().unwrap()), 2);
+    assert_eq!(u16::from_le_bytes(bmp[28..30].try_into().unwrap()), 24);
+    // The bottom row comes first, in blue, green, red order and padded
+    assert_eq!(&bmp[54..62], &[0xFF, 0, 0, 0x56, 0x34, 0x12, 0, 0]);
+    assert_eq!(&bmp[62..70], &[0, 0, 0xFF, 0, 0xFF, 0, 0, 0]);
+
+    assert_eq!(
+        ImageFormat::from_path("frame.PNG".as_ref()),
+        Some(ImageFormat::Png)
+    );
+    assert_eq!(
+        ImageFormat::from_path("frame.bmp".as_ref()),
+        Some(ImageFormat::Bmp)
+    );
+    assert_eq!(ImageFormat::from_path("frame.gif".as_ref()), None);
+}
+
+#[test]
+fn test_presented_frames_are_dumped() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("frame.png");
+    let mut simulator = Simulator::from_assembly(GRADIENT_PROGRAM).unwrap();
+    let framebuffer =
+        simulator.attach_framebuffer(FramebufferConfig::new(4, 4).with_dump(&path, 2));
+    simulator.run().unwrap();
+
+    let written = temp_dir.path().join("frame-0002.png");
+    assert_eq!(framebuffer.written_frames(), vec![written.clone()]);
+    assert_eq!(framebuffer.dump_error(), None);
+    let (width, height, raw) = png_pixels(&std::fs::read(&written).unwrap());
+    assert_eq!((width, height), (4, 4));
+    assert_eq!(&raw[..4], &[0, 0, 0, 0xFF]);
+
+    // The command line writes the final frame too
+    let source = temp_dir.path().join("gradient.s");
+    std::fs::write(&source, GRADIENT_PROGRAM).unwrap();
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--framebuffer")
+        .arg("4x4")
+        .arg("--framebuffer-dump")
+        .arg("out.bmp");
+    cmd.assert()
+        .success()
+        .stdout(contains("Framebuffer: 3 frames presented"))
+        .stdout(contains("Final frame written to out.bmp"));
+    for name in ["out.bmp", "out-0001.bmp", "out-0002.bmp", "out-0003.bmp"] {
+        let bmp = std::fs::read(temp_dir.path().join(name)).unwrap();
+        assert_eq!(bmp.len(), 54 + 4 * 12);
+    }
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--framebuffer")
+        .arg("4x4")
+        .arg("--framebuffer-dump")
+        .arg("out.gif");
+    cmd.assert()
+        .failure()
+        .stderr(contains("expected a .png or .bmp file"));
+}
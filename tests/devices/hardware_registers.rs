@@ -0,0 +1,104 @@
+// tests/devices/hardware_registers.rs
+// Tests for reading the hardware registers with rdhwr
+
+use vmips_rust::errors::SimulationErrorCause;
+use vmips_rust::functional_simulator::simulator::{Exception, Simulator as FunctionalSimulator};
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
+
+#[test]
+fn test_counters_and_constants_in_the_functional_simulator() {
+    let mut simulator = FunctionalSimulator::from_assembly(
+        "
+.text
+    rdhwr $s0, $0
+    rdhwr $s1, $1
+    rdhwr $s2, $3
+    addi $t0, $zero, 5
+loop:
+    addi $t0, $t0, -1
+    bne $t0, $zero, loop
+    rdhwr $s3, $2
+    rdhwr $s4, $30
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.registers.read(16), 0);
+    assert_eq!(simulator.registers.read(17), 64);
+    assert_eq!(simulator.registers.read(18), 1);
+    // Instructions before each read, one cycle apiece
+    assert_eq!(simulator.registers.read(19), 14);
+    assert_eq!(simulator.registers.read(20), 15);
+}
+
+#[test]
+fn test_thread_pointer_and_unimplemented_registers() {
+    // The sequence compilers emit to find thread-local storage
+    let mut simulator = FunctionalSimulator::from_assembly(
+        "
+.data 0x10001000
+tls: .word 0, 1234
+.text
+    la $t0, tls
+    mtc0 $t0, $4
+    rdhwr $v1, $29
+    lw $s0, 4($v1)
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+    simulator.run().unwrap();
+    assert_eq!(simulator.registers.read(3), 0x1000_1000);
+    assert_eq!(simulator.registers.read(16), 1234);
+
+    let mut simulator = FunctionalSimulator::from_assembly(
+        "
+.text
+    rdhwr $t0, $5
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+    let error = simulator.run().unwrap_err();
+    assert_eq!(error.cause, SimulationErrorCause::InvalidInstruction);
+    assert_eq!(simulator.exception, Some(Exception::InvalidInstruction));
+}
+
+#[test]
+fn test_cycle_counter_follows_the_pipeline() {
+    let image = ProgramImage::from_assembly(
+        "
+.data
+value: .word 7
+.text
+    rdhwr $t0, $1
+    lui $t9, 0x1000
+    lw $t1, 0($t9)
+    add $t2, $t1, $t1
+    rdhwr $s0, $2
+    rdhwr $s1, $30
+",
+    )
+    .unwrap();
+    let mut simulator = TimingSimulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(1024, 2, 32),
+        CacheConfig::new(1024, 2, 32),
+        0x0080_0000,
+    );
+    simulator.load_image(&image).unwrap();
+    simulator.run();
+
+    assert_eq!(simulator.registers.read(8), 32);
+    assert_eq!(simulator.registers.read(10), 14);
+    assert_eq!(simulator.registers.read(17), 5);
+    // Cache misses and the load-use hazard cost cycles beyond one apiece
+    assert!(simulator.registers.read(16) > 5);
+}
@@ -0,0 +1,96 @@
+// tests/devices/interrupts.rs
+// Tests for CP0 interrupt delivery to guest interrupt service routines
+
+use vmips_rust::functional_simulator::cp0::{AsyncEvent, CP0_CAUSE, CP0_STATUS, STATUS_EXL};
+use vmips_rust::functional_simulator::simulator::Simulator;
+
+/// Helper function to place a program at address 0 and reset the PC there
+fn setup_simulator(program: &[(usize, u32)]) -> Simulator {
+    let mut simulator = Simulator::new(32768);
+    simulator.load_program(&[]);
+
+    for &(addr, word) in program {
+        simulator.memory.write_word(addr, word).unwrap();
+    }
+
+    simulator
+}
+
+/// Keyboard ISR at the exception vector (EBase 0 + 0x180)
+const KEYBOARD_ISR: [(usize, u32); 4] = [
+    (0x180, 0x2402000C), // addiu $v0, $zero, 12  - read_char
+    (0x184, 0x0000000C), // syscall
+    (0x188, 0xAC022000), // sw $v0, 0x2000($zero)
+    (0x18C, 0x42000018), // eret
+];
+
+fn keyboard_program(status: u32) -> Vec<(usize, u32)> {
+    let mut program = vec![
+        (0x00, 0x40807800),          // mtc0 $zero, $15       - EBase = 0
+        (0x04, 0x24080000 | status), // addiu $t0, $zero, status
+        (0x08, 0x40886000),          // mtc0 $t0, $12         - Status
+        (0x0C, 0x26100001),          // addiu $s0, $s0, 1
+        (0x10, 0x8C092000),          // lw $t1, 0x2000($zero)
+        (0x14, 0x1120FFFD),          // beq $t1, $zero, -3
+        (0x18, 0x2402000A),          // addiu $v0, $zero, 10
+        (0x1C, 0x0000000C),          // syscall
+    ];
+    program.extend_from_slice(&KEYBOARD_ISR);
+    program
+}
+
+#[test]
+fn test_keyboard_interrupt_service_routine() {
+    // IM2 | IE
+    let mut simulator = setup_simulator(&keyboard_program(0x0401));
+    simulator.schedule_event(20, AsyncEvent::KeyPress(b'A'));
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.memory.read_word(0x2000), Ok(b'A' as u32));
+    assert_eq!(simulator.interrupts.interrupts_taken, 1);
+    // The main loop made progress before the interrupt arrived
+    assert!(simulator.registers.read(16) > 0);
+    // ERET left exception level and the key read acknowledged the interrupt
+    assert_eq!(simulator.registers.read_cp0(CP0_STATUS) & STATUS_EXL, 0);
+    assert_eq!(simulator.registers.read_cp0(CP0_CAUSE) & (1 << 10), 0);
+}
+
+#[test]
+fn test_masked_interrupt_stays_pending() {
+    // IM2 set but IE clear
+    let mut simulator = setup_simulator(&keyboard_program(0x0400));
+    simulator.set_max_steps(200);
+    simulator.queue_event(AsyncEvent::KeyPress(b'A'));
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.memory.read_word(0x2000), Ok(0));
+    assert_eq!(simulator.interrupts.interrupts_taken, 0);
+    assert_ne!(simulator.registers.read_cp0(CP0_CAUSE) & (1 << 10), 0);
+}
+
+#[test]
+fn test_count_compare_timer_interrupt() {
+    let program = [
+        (0x00, 0x40807800), // mtc0 $zero, $15         - EBase = 0
+        (0x04, 0x2408001E), // addiu $t0, $zero, 30
+        (0x08, 0x40885800), // mtc0 $t0, $11           - Compare = 30
+        (0x0C, 0x34088001), // ori $t0, $zero, 0x8001  - IM7 | IE
+        (0x10, 0x40886000), // mtc0 $t0, $12
+        (0x14, 0x26100001), // addiu $s0, $s0, 1
+        (0x18, 0x8C092000), // lw $t1, 0x2000($zero)
+        (0x1C, 0x1120FFFD), // beq $t1, $zero, -3
+        (0x20, 0x2402000A), // addiu $v0, $zero, 10
+        (0x24, 0x0000000C), // syscall
+        // Timer ISR: record Count and acknowledge by writing Compare
+        (0x180, 0x400A4800), // mfc0 $t2, $9
+        (0x184, 0xAC0A2000), // sw $t2, 0x2000($zero)
+        (0x188, 0x40805800), // mtc0 $zero, $11
+        (0x18C, 0x42000018), // eret
+    ];
+
+    let mut simulator = setup_simulator(&program);
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.memory.read_word(0x2000), Ok(30));
+    assert_eq!(simulator.interrupts.interrupts_taken, 1);
+}
@@ -0,0 +1,11 @@
+// tests/devices/main.rs
+// Tests for the memory-mapped devices, host calls and interrupt handling
+
+mod console;
+mod disk;
+mod framebuffer;
+mod hardware_registers;
+mod interrupts;
+mod network;
+mod semihosting;
+mod shadow_registers;
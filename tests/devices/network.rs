@@ -0,0 +1,193 @@
+// tests/devices/network.rs
+// Tests for the memory-mapped network device and its backends
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::net::UdpSocket;
+use std::time::Duration;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::network::{NetworkConfig, NetworkStatistics};
+use vmips_rust::functional_simulator::simulator::Simulator;
+
+/// Sends the word in `$a1` as a four-byte packet, then waits for the
+/// interrupt handler to store a received word in `$s6`
+const PING_PROGRAM: &str = "
+.text
+main:
+    lui $s0, 0xFFFF
+    ori $s0, $s0, 0x4000
+    li $t0, 1
+    sw $t0, 0($s0)
+    li $t0, 0x1001
+    mtc0 $t0, $12
+    sw $a1, 0x400($s0)
+    li $t0, 4
+    sw $t0, 0xC($s0)
+    li $t0, 1
+    sw $t0, 8($s0)
+wait:
+    addi $s1, $s1, 1
+    beq $s7, $zero, wait
+    li $v0, 10
+    syscall
+.ktext 0x80000180
+    li $k1, 2
+    sw $k1, 8($s0)
+    lw $s6, 0xA00($s0)
+    lw $s5, 0x10($s0)
+    li $s7, 1
+    eret
+";
+
+#[test]
+fn test_loopback_packets_reach_the_guest() {
+    let mut simulator = Simulator::from_assembly(
+        "
+.text
+    lui $s0, 0xFFFF
+    ori $s0, $s0, 0x4000
+    # Send a five-byte packet
+    li $t0, 0x676E6970
+    sw $t0, 0x400($s0)
+    li $t0, 0x21
+    sb $t0, 0x404($s0)
+    li $t0, 5
+    sw $t0, 0xC($s0)
+    li $t0, 1
+    sw $t0, 8($s0)
+    lw $s1, 4($s0)
+    lw $s2, 0x14($s0)
+    # Take the injected packet, then the one sent
+    li $t0, 2
+    sw $t0, 8($s0)
+    lw $s3, 0xA00($s0)
+    sw $t0, 8($s0)
+    lw $s4, 0xA00($s0)
+    lb $s5, 0xA04($s0)
+    lw $s6, 0x10($s0)
+    # Nothing is left
+    sw $t0, 8($s0)
+    lw $s7, 0x10($s0)
+    lw $t9, 4($s0)
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+    let network = simulator.attach_network(NetworkConfig::loopback()).unwrap();
+    network.inject(b"host");
+    assert_eq!(network.pending(), 1);
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.registers.read(17), 1);
+    assert_eq!(simulator.registers.read(18), 2);
+    assert_eq!(simulator.registers.read(19), u32::from_le_bytes(*b"host"));
+    assert_eq!(simulator.registers.read(20), u32::from_le_bytes(*b"ping"));
+    assert_eq!(simulator.registers.read(21), u32::from(b'!'));
+    assert_eq!(simulator.registers.read(22), 5);
+    assert_eq!(simulator.registers.read(23), 0);
+    assert_eq!(simulator.registers.read(25), 0);
+    assert_eq!(
+        network.statistics(),
+        NetworkStatistics {
+            packets_sent: 1,
+            bytes_sent: 5,
+            packets_received: 2,
+            bytes_received: 9,
+            dropped: 0,
+        }
+    );
+    assert_eq!(network.local_addr(), None);
+}
+
+#[test]
+fn test_udp_datagrams_raise_receive_interrupts() {
+    let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut simulator = Simulator::from_assembly(PING_PROGRAM).unwrap();
+    let network = simulator
+        .attach_network(NetworkConfig::udp(
+            "127.0.0.1:0".parse().unwrap(),
+            peer.local_addr().unwrap(),
+        ))
+        .unwrap();
+    let address = network.local_addr().unwrap();
+    peer.send_to(b"pong", address).unwrap();
+    simulator.registers.write(5, u32::from_le_bytes(*b"ping"));
+    simulator.run().unwrap();
+
+    let mut datagram = [0u8; 16];
+    let (length, from) = peer.recv_from(&mut datagram).unwrap();
+    assert_eq!(&datagram[..length], b"ping");
+    assert_eq!(from, address);
+    assert_eq!(simulator.registers.read(22), u32::from_le_bytes(*b"pong"));
+    assert_eq!(simulator.registers.read(21), 4);
+    assert_eq!(simulator.interrupts.interrupts_taken, 1);
+    assert_eq!(network.pending(), 0);
+    assert_eq!(network.statistics().packets_received, 1);
+
+    // The loopback backend answers the ping itself
+    let mut simulator = Simulator::from_assembly(PING_PROGRAM).unwrap();
+    simulator.attach_network(NetworkConfig::loopback()).unwrap();
+    simulator.registers.write(5, u32::from_le_bytes(*b"echo"));
+    simulator.run().unwrap();
+    assert_eq!(simulator.registers.read(22), u32::from_le_bytes(*b"echo"));
+}
+
+#[test]
+fn test_network_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("loop.s");
+    std::fs::write(
+        &source,
+        "
+.data
+message: .space 8
+.text
+    lui $s0, 0xFFFF
+    ori $s0, $s0, 0x4000
+    li $t0, 0x0A216968
+    sw $t0, 0x400($s0)
+    li $t0, 4
+    sw $t0, 0xC($s0)
+    li $t0, 1
+    sw $t0, 8($s0)
+    li $t0, 2
+    sw $t0, 8($s0)
+    lw $t0, 0xA00($s0)
+    la $a0, message
+    sw $t0, 0($a0)
+    li $v0, 4
+    syscall
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--network")
+        .arg("loopback");
+    cmd.assert()
+        .success()
+        .stdout(contains("hi!\n"))
+        .stdout(contains(
+            "Network: 1 packets sent (4 bytes), 1 packets received (4 bytes), 0 dropped",
+        ));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--network")
+        .arg("udp");
+    cmd.assert()
+        .success()
+        .stderr(contains("--network udp requires --udp-peer"));
+}
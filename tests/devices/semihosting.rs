@@ -0,0 +1,195 @@
+// tests/devices/semihosting.rs
+// Tests for host calls made with the semihosting break code
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::semihosting::{BenchmarkScore, SEMIHOST_ERROR};
+use vmips_rust::functional_simulator::simulator::{Exception, Simulator};
+use vmips_rust::utils::limits::TerminationReason;
+
+/// Reads a file of digits, sums them and reports the sum as a score
+const CHECKSUM_PROGRAM: &str = "
+.data
+path: .asciiz \"digits.txt\"
+name: .asciiz \"checksum\"
+buffer: .space 16
+.text
+    li $v0, 1
+    la $a0, path
+    la $a1, buffer
+    li $a2, 16
+    break 0xAB
+    add $t0, $v0, $zero
+    la $t1, buffer
+    li $s0, 0
+sum:
+    lb $t2, 0($t1)
+    addi $t2, $t2, -48
+    add $s0, $s0, $t2
+    addi $t1, $t1, 1
+    addi $t0, $t0, -1
+    bne $t0, $zero, sum
+    li $v0, 7
+    la $a0, name
+    add $a1, $s0, $zero
+    break 0xAB
+    li $v0, 10
+    syscall
+";
+
+#[test]
+fn test_host_files_are_read_and_written() {
+    let temp_dir = tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("input.txt"), "12345").unwrap();
+    let mut simulator = Simulator::from_assembly(
+        "
+.data
+input: .asciiz \"input.txt\"
+output: .asciiz \"out/output.txt\"
+outside: .asciiz \"../secret.txt\"
+absolute: .asciiz \"/etc/hostname\"
+missing: .asciiz \"missing.txt\"
+buffer: .space 16
+.text
+    li $v0, 3
+    la $a0, input
+    break 0xAB
+    add $s0, $v0, $zero
+    li $v0, 1
+    la $a1, buffer
+    li $a2, 16
+    break 0xAB
+    add $s1, $v0, $zero
+    li $v0, 2
+    la $a0, output
+    li $a2, 3
+    break 0xAB
+    add $s2, $v0, $zero
+    li $v0, 1
+    la $a0, outside
+    break 0xAB
+    add $s3, $v0, $zero
+    li $v0, 3
+    la $a0, absolute
+    break 0xAB
+    add $s4, $v0, $zero
+    li $v0, 3
+    la $a0, missing
+    break 0xAB
+    add $s5, $v0, $zero
+    li $v0, 99
+    break 0xAB
+    add $s6, $v0, $zero
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+    std::fs::create_dir(temp_dir.path().join("out")).unwrap();
+    simulator.enable_semihosting(temp_dir.path());
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.registers.read(16), 5);
+    assert_eq!(simulator.registers.read(17), 5);
+    assert_eq!(simulator.registers.read(18), 3);
+    assert_eq!(
+        std::fs::read_to_string(temp_dir.path().join("out/output.txt")).unwrap(),
+        "123"
+    );
+    // Paths may not leave the root, and failures return -1
+    for register in 19..=22 {
+        assert_eq!(simulator.registers.read(register), SEMIHOST_ERROR);
+    }
+    assert_eq!(simulator.semihosting.as_ref().unwrap().calls, 7);
+}
+
+#[test]
+fn test_time_counters_and_scores() {
+    let mut simulator = Simulator::from_assembly(
+        "
+.data
+name: .asciiz \"frames\"
+.text
+    li $v0, 4
+    break 0xAB
+    add $s0, $v0, $zero
+    li $v0, 5
+    break 0xAB
+    add $s1, $v0, $zero
+    li $v0, 6
+    break 0xAB
+    add $s2, $v0, $zero
+    add $s3, $v1, $zero
+    li $v0, 7
+    la $a0, name
+    li $a1, -42
+    break 0xAB
+    add $s4, $v0, $zero
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+    simulator.enable_semihosting(".");
+    simulator.run().unwrap();
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32;
+    assert!(now - simulator.registers.read(16) <= 5);
+    assert!(simulator.registers.read(17) < 5_000_000);
+    // Instructions retired before the call
+    assert_eq!(simulator.registers.read(18), 8);
+    assert_eq!(simulator.registers.read(19), 0);
+    assert_eq!(simulator.registers.read(20), 0);
+    assert_eq!(
+        simulator.semihosting.as_ref().unwrap().scores,
+        vec![BenchmarkScore {
+            name: "frames".to_string(),
+            value: -42,
+            instructions: 15,
+        }]
+    );
+
+    // Without semihosting the break code is an ordinary breakpoint
+    let mut simulator = Simulator::from_assembly(CHECKSUM_PROGRAM).unwrap();
+    simulator.run().unwrap();
+    assert_eq!(simulator.exception, Some(Exception::BreakPoint));
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::Breakpoint)
+    );
+    assert_eq!(simulator.registers.read(2), 1);
+}
+
+#[test]
+fn test_semihosting_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("digits.txt"), "123456").unwrap();
+    let source = temp_dir.path().join("checksum.s");
+    std::fs::write(&source, CHECKSUM_PROGRAM).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--semihosting");
+    cmd.assert()
+        .success()
+        .stdout(contains("Benchmark score: checksum = 21 (after"));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source);
+    cmd.assert()
+        .success()
+        .stdout(contains("breakpoint"))
+        .stdout(contains("Benchmark score").not());
+}
@@ -0,0 +1,181 @@
+// tests/devices/shadow_registers.rs
+// Tests for shadow register sets and the handler context switch they save
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::cp0::{AsyncEvent, CP0_SRSCTL, CP0_STATUS};
+use vmips_rust::functional_simulator::instructions::Instruction;
+use vmips_rust::functional_simulator::registers::FULL_SAVE_REGISTERS;
+use vmips_rust::functional_simulator::simulator::{
+    decode_instruction, instruction_to_string, Simulator,
+};
+
+/// Counts down while timer interrupts arrive. The handler reads the
+/// interrupted `$s0`, counts in `$s2`, publishes the count to the
+/// interrupted `$s3` and clobbers `$s0` without saving anything.
+const TIMER_PROGRAM: &str = "
+.text
+    li $s0, 100
+    li $t0, 0x8001
+    mtc0 $t0, $12
+    li $t1, 40
+loop:
+    addi $t1, $t1, -1
+    bne $t1, $zero, loop
+    li $v0, 10
+    syscall
+.ktext 0x80000180
+    rdpgpr $s1, $s0
+    addi $s2, $s2, 1
+    wrpgpr $s3, $s2
+    li $s0, 0
+    mtc0 $zero, $11
+    eret
+";
+
+fn load(source: &str) -> Simulator {
+    let mut simulator = Simulator::from_assembly(source).unwrap();
+    simulator.schedule_event(10, AsyncEvent::Timer);
+    simulator.schedule_event(40, AsyncEvent::Timer);
+    simulator
+}
+
+#[test]
+fn test_handlers_run_in_a_shadow_set() {
+    let mut simulator = load(TIMER_PROGRAM);
+    simulator.registers.enable_shadow_sets(1);
+    simulator.run().unwrap();
+
+    // The interrupted code keeps its registers and sees only what the
+    // handler wrote with wrpgpr
+    assert_eq!(simulator.registers.read(16), 100);
+    assert_eq!(simulator.registers.read(17), 0);
+    assert_eq!(simulator.registers.read(18), 0);
+    assert_eq!(simulator.registers.read(19), 2);
+    let shadow = &simulator.registers.shadow;
+    assert_eq!(shadow.switched_exceptions, 2);
+    assert_eq!(shadow.cycles_saved(), 4 * FULL_SAVE_REGISTERS);
+    assert_eq!(shadow.current_set(), 0);
+    assert_eq!(
+        simulator.registers.read_cp0(CP0_SRSCTL),
+        (1 << 26) | (1 << 12)
+    );
+
+    // The handler's registers persist in set 1 between interrupts
+    simulator.registers.switch_register_set(1);
+    assert_eq!(simulator.registers.read(16), 0);
+    assert_eq!(simulator.registers.read(17), 100);
+    assert_eq!(simulator.registers.read(18), 2);
+
+    // Without shadow sets the handler shares, and clobbers, the registers
+    let mut simulator = load(TIMER_PROGRAM);
+    simulator.run().unwrap();
+    assert_eq!(simulator.registers.read(16), 0);
+    assert_eq!(simulator.registers.read(18), 2);
+    assert_eq!(simulator.registers.read(19), 2);
+    assert_eq!(simulator.registers.shadow.switched_exceptions, 0);
+    assert_eq!(simulator.registers.read_cp0(CP0_SRSCTL), 0);
+}
+
+#[test]
+fn test_guest_selects_the_exception_set() {
+    let mut simulator = load(
+        "
+.text
+    mfc0 $s4, $12, 2
+    li $t0, 0x9000
+    mtc0 $t0, $12, 2
+    li $t0, 0x2000
+    mtc0 $t0, $12, 2
+    li $s0, 100
+    li $t0, 0x8001
+    mtc0 $t0, $12
+    li $t1, 40
+loop:
+    addi $t1, $t1, -1
+    bne $t1, $zero, loop
+    li $v0, 10
+    syscall
+.ktext 0x80000180
+    rdpgpr $s1, $s0
+    mtc0 $zero, $11
+    eret
+",
+    );
+    simulator.registers.enable_shadow_sets(3);
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.registers.read(20), (3 << 26) | (1 << 12));
+    // Set 9 does not exist, so only the write of set 2 took effect
+    let shadow = &simulator.registers.shadow;
+    assert_eq!(shadow.exception_set(), 2);
+    assert_eq!(shadow.switched_exceptions, 2);
+    // Writing SRSCtl left Status alone
+    assert_eq!(simulator.registers.read_cp0(CP0_STATUS), 0x8001);
+    simulator.registers.switch_register_set(2);
+    assert_eq!(simulator.registers.read(17), 100);
+    simulator.registers.switch_register_set(1);
+    assert_eq!(simulator.registers.read(17), 0);
+
+    // Selects survive decoding and disassembly
+    let word = 0x4088_6002;
+    assert_eq!(
+        decode_instruction(word),
+        Instruction::Mtc0 { rt: 8, rd: 76 }
+    );
+    assert_eq!(
+        instruction_to_string(&decode_instruction(word), word),
+        "mtc0 $8, $12, 2"
+    );
+    assert_eq!(
+        instruction_to_string(&decode_instruction(0x4140_8800), 0x4140_8800),
+        "rdpgpr $17, $0"
+    );
+}
+
+#[test]
+fn test_shadow_sets_from_the_command_line() {
+    // The Count/Compare timer interrupts once
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("timer.s");
+    std::fs::write(
+        &source,
+        "
+.text
+    li $t0, 20
+    mtc0 $t0, $11
+    li $t0, 0x8001
+    mtc0 $t0, $12
+    li $t1, 40
+loop:
+    addi $t1, $t1, -1
+    bne $t1, $zero, loop
+    li $v0, 10
+    syscall
+.ktext 0x80000180
+    mtc0 $zero, $11
+    eret
+",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--shadow-sets")
+        .arg("2");
+    cmd.assert().success().stdout(contains(
+        "Shadow register sets: 2, 1 exceptions switched sets, saving about 58 cycles of register save/restore",
+    ));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--shadow-sets")
+        .arg("16")
+        .assert()
+        .failure()
+        .stderr(contains("16 is not in 1..=15"));
+}
@@ -1,4 +1,5 @@
-use vmips_rust::functional_simulator::memory::Memory;
+use vmips_rust::errors::SimulationErrorCause;
+use vmips_rust::functional_simulator::memory::{MemError, Memory};
 use vmips_rust::functional_simulator::registers::Registers;
 use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
 use vmips_rust::timing_simulator::config::{BranchPredictorType, CacheConfig, PipelineConfig};
@@ -9,20 +10,20 @@ fn test_memory_boundary_conditions() {
     let mut memory = Memory::new_simple(1024);
 
     // Test writing at memory boundaries
-    assert!(memory.write_word(0, 0x12345678));
-    assert!(memory.write_word(1020, 0x87654321));
+    assert!(memory.write_word(0, 0x12345678).is_ok());
+    assert!(memory.write_word(1020, 0x87654321).is_ok());
 
     // Test reading at boundaries
-    assert_eq!(memory.read_word(0), Some(0x12345678));
-    assert_eq!(memory.read_word(1020), Some(0x87654321));
+    assert_eq!(memory.read_word(0), Ok(0x12345678));
+    assert_eq!(memory.read_word(1020), Ok(0x87654321));
 
     // Test out-of-bounds access should fail
-    assert_eq!(memory.read_word(1024), None);
-    assert!(!memory.write_word(1024, 0x12345678));
+    assert_eq!(memory.read_word(1024), Err(MemError::OutOfBounds(1024)));
+    assert!(memory.write_word(1024, 0x12345678).is_err());
 
     // Test unaligned access should fail with simple memory
-    assert_eq!(memory.read_word(1), None);
-    assert!(!memory.write_word(1, 0x12345678));
+    assert_eq!(memory.read_word(1), Err(MemError::Misaligned(1)));
+    assert!(memory.write_word(1, 0x12345678).is_err());
 }
 
 #[test]
@@ -57,7 +58,7 @@ fn test_arithmetic_overflow() {
     simulator.memory.write_word_init(0, add_instruction);
 
     // Execute one step
-    simulator.step();
+    simulator.step().unwrap();
 
     // Check that overflow wraps around
     assert_eq!(simulator.registers.read(3), 0);
@@ -76,7 +77,7 @@ fn test_division_by_zero() {
     simulator.memory.write_word_init(0, div_instruction);
 
     // Execute one step - should not crash
-    simulator.step();
+    simulator.step().unwrap();
 
     // Result should be undefined but simulator should continue
     // (Implementation specific behavior)
@@ -94,10 +95,14 @@ fn test_branch_edge_cases() {
     let beq_instruction = 0x10220FFFu32; // BEQ $1, $2, 0xFFF
     simulator.memory.write_word_init(0, beq_instruction);
 
-    simulator.step();
+    let result = simulator.step();
 
-    // PC should have jumped (implementation specific)
-    // This tests that large offsets don't cause crashes
+    // The target lies beyond the end of memory, so the branch faults
+    // instead of crashing
+    assert!(matches!(
+        result.map_err(|error| error.cause),
+        Err(SimulationErrorCause::PcOutOfBounds(_))
+    ));
 }
 
 #[test]
@@ -108,18 +113,21 @@ fn test_load_store_edge_cases() {
     let lw_instruction = 0x8C010400u32; // LW $1, 0x400($0)
     simulator.memory.write_word_init(0, lw_instruction);
 
-    simulator.step();
+    let result = simulator.step();
 
-    // Load from uninitialized memory (behavior may vary)
+    // The load is past the end of memory and reports the faulting address
+    assert_eq!(
+        result.map_err(|error| error.cause),
+        Err(SimulationErrorCause::MemoryViolation(0x400))
+    );
     let _loaded_value = simulator.registers.read(1);
-    // Just verify the operation completed without crashing
 
     // Test store to boundary
     simulator.registers.write(2, 0x12345678);
     let sw_instruction = 0xAC0203FCu32; // SW $2, 0x3FC($0)
     simulator.memory.write_word_init(4, sw_instruction);
 
-    simulator.step();
+    let _ = simulator.step();
 
     // Verify store worked (if within bounds)
     let _stored_value = simulator.memory.read_word(0x3FC);
@@ -215,15 +223,18 @@ fn test_memory_alignment_errors() {
 
     // Test various misaligned accesses should fail
     for offset in 1..4 {
-        assert_eq!(memory.read_word(offset), None);
-        assert!(!memory.write_word(offset, 0x12345678));
+        assert_eq!(
+            memory.read_word(offset),
+            Err(MemError::Misaligned(offset as u32))
+        );
+        assert!(memory.write_word(offset, 0x12345678).is_err());
     }
 
     // Test aligned accesses work
     for i in 0..10 {
         let addr = i * 4;
-        assert!(memory.write_word(addr, i as u32));
-        assert_eq!(memory.read_word(addr), Some(i as u32));
+        assert!(memory.write_word(addr, i as u32).is_ok());
+        assert_eq!(memory.read_word(addr), Ok(i as u32));
     }
 }
 
@@ -262,7 +273,7 @@ fn test_simulator_state_consistency() {
 
     // Execute NOP
     simulator.memory.write_word_init(0, 0x00000000);
-    simulator.step();
+    simulator.step().unwrap();
 
     // State should be unchanged after NOP
     assert_eq!(simulator.registers.read(1), reg_val);
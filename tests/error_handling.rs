@@ -1,4 +1,4 @@
-use vmips_rust::errors::SimulatorError;
+use vmips_rust::errors::{SimulationErrorCause, SimulatorError};
 use vmips_rust::functional_simulator::memory::Memory;
 use vmips_rust::functional_simulator::simulator::Simulator;
 
@@ -21,8 +21,11 @@ fn test_memory_out_of_bounds_error() {
     // Load program
     simulator.load_program(&program_bytes);
 
-    // Run simulation - should handle error gracefully
-    simulator.run();
+    // Run simulation - the fault is returned to the caller
+    let error = simulator.run().unwrap_err();
+    assert_eq!(error.pc, 0);
+    assert_eq!(error.instruction, Some(0x8C020400));
+    assert_eq!(error.cause, SimulationErrorCause::MemoryViolation(0x400));
 
     // Check if an exception was set
     if let Some(exception) = &simulator.exception {
@@ -63,7 +66,7 @@ fn test_division_by_zero_error() {
     simulator.load_program(&program_bytes);
 
     // Run simulation - should handle error gracefully
-    simulator.run();
+    simulator.run().unwrap();
 
     // Check if an exception was set or simulation completed
     if let Some(exception) = &simulator.exception {
@@ -96,7 +99,7 @@ fn test_invalid_branch_target() {
     simulator.load_program(&program_bytes);
 
     // Run simulation - should handle error gracefully
-    simulator.run();
+    simulator.run().unwrap();
 
     // Check if an exception was set
     if let Some(exception) = &simulator.exception {
@@ -118,22 +121,22 @@ fn test_memory_bounds_checking() {
 
     // Test valid memory access
     let valid_result = memory.read_word(100);
-    assert!(valid_result.is_some());
+    assert!(valid_result.is_ok());
     println!("Valid memory access successful");
 
     // Test out of bounds access
     let invalid_result = memory.read_word(2000); // Beyond 1KB limit
-    assert!(invalid_result.is_none());
+    assert!(invalid_result.is_err());
     println!("Out of bounds access properly handled");
 
     // Test boundary condition
     let boundary_result = memory.read_word(1020); // Near the boundary
-    assert!(boundary_result.is_some());
+    assert!(boundary_result.is_ok());
     println!("Boundary access successful");
 
     // Test exact boundary
     let exact_boundary = memory.read_word(1024); // Exactly at the boundary
-    assert!(exact_boundary.is_none());
+    assert!(exact_boundary.is_err());
     println!("Exact boundary access properly rejected");
 }
 
@@ -161,7 +164,7 @@ fn test_enhanced_branch_handling() {
     simulator.load_program(&program_bytes);
 
     // Run simulation - should succeed
-    simulator.run();
+    simulator.run().unwrap();
 
     // Check that the branch was taken and $4 was set
     assert_eq!(simulator.registers.read(4), 1);
@@ -190,7 +193,7 @@ fn test_safe_memory_operations() {
     simulator.load_program(&program_bytes);
 
     // Run simulation - should succeed
-    simulator.run();
+    simulator.run().unwrap();
 
     // Check that the memory operation worked correctly
     assert_eq!(simulator.registers.read(4), 20);
@@ -218,7 +221,7 @@ fn test_loop_detection_basic() {
     simulator.load_program(&program_bytes);
 
     // Run simulation - should succeed and complete the loop
-    simulator.run();
+    simulator.run().unwrap();
 
     // Check that the counter reached zero
     assert_eq!(simulator.registers.read(2), 0);
@@ -252,7 +255,7 @@ fn test_complex_algorithm_support() {
     simulator.load_program(&program_bytes);
 
     // Run simulation - should succeed
-    simulator.run();
+    simulator.run().unwrap();
 
     // Check that the sum is correct (1+2+3+4+5 = 15)
     assert_eq!(simulator.registers.read(4), 15);
@@ -0,0 +1,111 @@
+// tests/functional/call_function.rs
+// Tests for calling guest functions from host code with the o32 ABI
+
+use vmips_rust::errors::CallError;
+use vmips_rust::functional_simulator::guest;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::utils::limits::TerminationReason;
+
+const SOURCE: &str = "\
+.text
+main:
+    addi $v0, $zero, 10
+    syscall
+sum:
+    add $v0, $a0, $a1
+    jr $ra
+sum6:
+    add $v0, $a0, $a1
+    add $v0, $v0, $a2
+    add $v0, $v0, $a3
+    lw $t0, 16($sp)
+    add $v0, $v0, $t0
+    lw $t0, 20($sp)
+    add $v0, $v0, $t0
+    jr $ra
+strlen:
+    add $v0, $zero, $zero
+strlen_loop:
+    lb $t0, 0($a0)
+    beq $t0, $zero, strlen_done
+    addi $a0, $a0, 1
+    addi $v0, $v0, 1
+    j strlen_loop
+strlen_done:
+    jr $ra
+double_sum:
+    addi $sp, $sp, -8
+    sw $ra, 4($sp)
+    jal sum
+    add $v0, $v0, $v0
+    lw $ra, 4($sp)
+    addi $sp, $sp, 8
+    jr $ra
+quit:
+    addi $v0, $zero, 10
+    syscall
+crash:
+    lw $t0, -4($zero)
+    jr $ra
+";
+
+fn simulator() -> Simulator {
+    Simulator::from_assembly(SOURCE).unwrap()
+}
+
+#[test]
+fn test_register_and_stack_arguments() {
+    let mut simulator = simulator();
+    assert_eq!(simulator.call_function("sum", &[5, 7]), Ok(12));
+    assert_eq!(simulator.call_function("sum", &[u32::MAX, 2]), Ok(1));
+    assert_eq!(simulator.call_function("sum6", &[1, 2, 3, 4, 5, 6]), Ok(21));
+    // Nested calls save $ra on the stack
+    assert_eq!(simulator.call_function("double_sum", &[3, 4]), Ok(14));
+}
+
+#[test]
+fn test_caller_state_is_restored() {
+    let mut simulator = simulator();
+    let entry = simulator.pc();
+    simulator.registers.write(29, 0x7FFF_F000);
+    simulator.registers.write(31, 0x1234);
+    assert_eq!(simulator.call_function("sum", &[1, 1]), Ok(2));
+    assert_eq!(simulator.pc(), entry);
+    assert_eq!(simulator.registers.read(29), 0x7FFF_F000);
+    assert_eq!(simulator.registers.read(31), 0x1234);
+
+    // The program still runs from its entry point afterwards
+    simulator.run().unwrap();
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::Exited)
+    );
+}
+
+#[test]
+fn test_pointer_arguments() {
+    let mut simulator = simulator();
+    let text = 0x1000_0000;
+    guest::write_c_string(&mut simulator.memory, text, b"marshalled", 32).unwrap();
+    assert_eq!(simulator.call_function("strlen", &[text]), Ok(10));
+}
+
+#[test]
+fn test_calls_that_do_not_return() {
+    let mut simulator = simulator();
+    assert_eq!(
+        simulator.call_function("missing", &[]),
+        Err(CallError::UnknownFunction("missing".to_string()))
+    );
+    assert_eq!(
+        simulator.call_function("quit", &[]),
+        Err(CallError::Stopped(TerminationReason::Exited))
+    );
+    assert!(matches!(
+        simulator.call_function("crash", &[]),
+        Err(CallError::Fault(_))
+    ));
+
+    // A call can follow a failed one
+    assert_eq!(simulator.call_function("sum", &[2, 3]), Ok(5));
+}
@@ -0,0 +1,123 @@
+// tests/functional/checkpoints.rs
+// Tests for dirty-page tracking and delta checkpoints of functional runs
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use vmips_rust::assembler::ProgramBuilder;
+use vmips_rust::functional_simulator::memory::{Memory, DIRTY_PAGE_SIZE};
+use vmips_rust::functional_simulator::registers::{S0, T0, V0, ZERO};
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::loader::ProgramImage;
+
+/// Store a countdown from 8 to words 1 KiB apart, so every fourth store
+/// moves to the next page
+fn image() -> ProgramImage {
+    ProgramBuilder::new()
+        .label("__start")
+        .li(T0, 8)
+        .la(S0, "buffer")
+        .label("loop")
+        .sw(T0, 0, S0)
+        .addi(S0, S0, 1024)
+        .addi(T0, T0, -1)
+        .bne(T0, ZERO, "loop")
+        .li(V0, 10)
+        .syscall()
+        .data_label("buffer")
+        .space(8192)
+        .image()
+        .unwrap()
+}
+
+#[test]
+fn test_memory_tracks_dirty_pages() {
+    let mut memory = Memory::new_simple(16 * DIRTY_PAGE_SIZE);
+    memory.write_word(0x100, 1).unwrap();
+    assert!(!memory.is_tracking_dirty_pages());
+    assert!(memory.dirty_pages().is_empty());
+
+    memory.track_dirty_pages();
+    assert!(memory.dirty_pages().is_empty());
+    memory.write_word(0x5004, 1).unwrap();
+    memory.write_byte(0x100, 2).unwrap();
+    // A block across a page boundary dirties both pages
+    memory.write_block(0x2FFE, &[1, 2, 3, 4]).unwrap();
+    memory.fill(0xF000, 16, 0xFF).unwrap();
+    assert_eq!(
+        memory.dirty_pages(),
+        vec![0, 0x2000, 0x3000, 0x5000, 0xF000]
+    );
+    // A failed write dirties nothing
+    memory.clear_dirty_pages();
+    assert!(memory.write_word(16 * DIRTY_PAGE_SIZE, 1).is_err());
+    assert!(memory.dirty_pages().is_empty());
+    memory.write_halfword(0x7002, 9).unwrap();
+    assert_eq!(memory.dirty_pages(), vec![0x7000]);
+}
+
+#[test]
+fn test_checkpoints_store_deltas_and_restore() {
+    let image = image();
+    let mut simulator = FunctionalSimulator::from_image(&image).unwrap();
+    simulator.enable_checkpoints(4);
+    simulator.run().unwrap();
+    let final_memory = simulator.memory.data.clone();
+    let final_registers = simulator.registers.data.clone();
+
+    let log = simulator.checkpoints.as_ref().unwrap();
+    let log_len = (simulator.step_count - 1) / 4 + 1;
+    assert_eq!(log.len(), log_len);
+    let checkpoints = log.checkpoints();
+    assert!(checkpoints[0].pages.is_empty());
+    assert!(checkpoints[1..]
+        .iter()
+        .all(|checkpoint| checkpoint.pages.len() <= 1));
+    let memory_size = simulator.memory.data.len();
+    // The first copy and the eight pages written between checkpoints
+    assert_eq!(log.stored_bytes(), memory_size + 8 * DIRTY_PAGE_SIZE);
+
+    // Checkpoint 3 is the state after 12 instructions
+    let mut reference = simulator_after(&image, 12);
+    let log = simulator.checkpoints.as_mut().unwrap();
+    assert_eq!(log.latest_before(13), Some(3));
+    assert_eq!(log.memory_at(3).unwrap(), reference.memory.data);
+    simulator.restore_checkpoint(3).unwrap();
+    assert_eq!(simulator.step_count, 12);
+    assert_eq!(simulator.pc(), reference.pc());
+    assert_eq!(simulator.registers.data, reference.registers.data);
+    assert_eq!(simulator.checkpoints.as_ref().unwrap().len(), 4);
+    assert!(simulator.memory.dirty_pages().is_empty());
+
+    // Running on from the checkpoint ends as the first run did
+    while simulator.step().unwrap() {}
+    assert_eq!(simulator.memory.data, final_memory);
+    assert_eq!(simulator.registers.data, final_registers);
+    assert_eq!(simulator.checkpoints.as_ref().unwrap().len(), log_len);
+    assert!(simulator.restore_checkpoint(99).is_err());
+    assert!(reference.restore_checkpoint(0).is_err());
+}
+
+fn simulator_after(image: &ProgramImage, instructions: usize) -> FunctionalSimulator {
+    let mut simulator = FunctionalSimulator::from_image(image).unwrap();
+    simulator.set_max_steps(instructions);
+    simulator.run().unwrap();
+    simulator
+}
+
+#[test]
+fn test_checkpoint_report_in_functional_run() {
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--input")
+        .arg("examples/programs/bubble_sort.s")
+        .arg("--checkpoint-every")
+        .arg("20");
+    cmd.assert()
+        .success()
+        .stdout(contains("Checkpoints: 16 taken every 20 instructions"))
+        .stdout(contains("bytes as full copies)"));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional").arg("--checkpoint-every").arg("0");
+    cmd.assert().failure();
+}
@@ -0,0 +1,107 @@
+// tests/functional/commit_log.rs
+// Tests for the instruction commit log written for co-simulation
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::commit_log::{CommitLog, CommitLogFormat};
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+
+const PROGRAM: &str = "
+.data
+value: .word 0
+.text
+    addi $t0, $zero, 5
+    la $t1, value
+    sb $t0, 1($t1)
+    lw $t2, 0($t1)
+    mult $t0, $t0
+    li $v0, 10
+    syscall
+";
+
+fn commit_log(file_name: &str) -> String {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join(file_name);
+    let mut simulator = FunctionalSimulator::from_assembly(PROGRAM).unwrap();
+    simulator.commit_log = Some(CommitLog::create(&path).unwrap());
+    simulator.run().unwrap();
+
+    let log = simulator.commit_log.as_mut().unwrap();
+    log.finish().unwrap();
+    assert_eq!(log.commits, 8);
+    fs::read_to_string(&path).unwrap()
+}
+
+#[test]
+fn test_spike_log_records_register_writes_and_memory() {
+    let log = commit_log("commits.log");
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 16);
+    assert_eq!(lines[0], "core   0: 0x00400000 (0x20080005) addi $8, $0, 5");
+    assert_eq!(
+        lines[1],
+        "core   0: 3 0x00400000 (0x20080005) x8  0x00000005"
+    );
+    // Stores log the address and the bytes written, loads just the address
+    assert_eq!(
+        lines[7],
+        "core   0: 3 0x0040000c (0xa1280001) mem 0x10000001 0x05"
+    );
+    assert_eq!(
+        lines[9],
+        "core   0: 3 0x00400010 (0x8d2a0000) x10 0x00000500 mem 0x10000000"
+    );
+    assert_eq!(
+        lines[11],
+        "core   0: 3 0x00400014 (0x01080018) hi  0x00000000 lo  0x00000019"
+    );
+    assert_eq!(lines[15], "core   0: 3 0x0040001c (0x0000000c)");
+}
+
+#[test]
+fn test_csv_log_follows_riscv_dv_columns() {
+    assert_eq!(
+        CommitLogFormat::from_path("trace.CSV".as_ref()),
+        CommitLogFormat::Csv
+    );
+    let log = commit_log("commits.csv");
+    let rows: Vec<&str> = log.lines().collect();
+    assert_eq!(
+        rows[0],
+        "pc,instr,gpr,csr,binary,mode,instr_str,operand,pad"
+    );
+    assert_eq!(
+        rows[1],
+        "00400000,addi,t0:00000005,,20080005,3,\"addi $8, $0, 5\",\"$8, $0, 5\","
+    );
+    assert_eq!(
+        rows[4],
+        "0040000c,sb,,,a1280001,3,\"sb $8, 1($9)\",\"$8, 1($9)\","
+    );
+    assert_eq!(
+        rows[6],
+        "00400014,mult,hi:00000000;lo:00000019,,01080018,3,\"mult $8, $8\",\"$8, $8\","
+    );
+    assert_eq!(rows[8], "0040001c,syscall,,,0000000c,3,syscall,,");
+}
+
+#[test]
+fn test_cli_writes_commit_log() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("program.asm");
+    let log = dir.path().join("commits.log");
+    fs::write(&input, PROGRAM).unwrap();
+
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["functional", "-i", input.to_str().unwrap(), "--commit-log"])
+        .arg(&log)
+        .assert()
+        .success()
+        .stdout(contains("Commit log: 8 instructions written to"));
+    let text = fs::read_to_string(&log).unwrap();
+    assert!(text.starts_with("core   0: 0x00400000 (0x20080005) addi $8, $0, 5\n"));
+}
@@ -0,0 +1,152 @@
+// tests/functional/control_flow.rs
+// Tests for the shadow stack and stack canary checks
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::io::Write;
+use tempfile::NamedTempFile;
+use vmips_rust::errors::{SimulationError, SimulationErrorCause};
+use vmips_rust::functional_simulator::control_flow::ControlFlowChecks;
+use vmips_rust::functional_simulator::simulator::{Simulator, STACK_TOP};
+use vmips_rust::utils::limits::TerminationReason;
+
+/// `vulnerable` copies WORDS words into a 3-word buffer below its saved
+/// `$ra`; a fourth word overwrites the return address with `gadget`
+fn overflow_program(words: u32) -> String {
+    format!(
+        "
+.data
+payload: .word 0x41414141, 0x41414141, 0x41414141, 0
+.text
+main:
+    la $t0, gadget
+    la $t1, payload
+    sw $t0, 12($t1)
+    jal vulnerable
+    li $a0, 1
+    li $v0, 1
+    syscall
+    li $v0, 10
+    syscall
+vulnerable:
+    addi $sp, $sp, -16
+    sw $ra, 12($sp)
+    la $t1, payload
+    add $t2, $sp, $zero
+    li $t3, {}
+copy:
+    lw $t4, 0($t1)
+    sw $t4, 0($t2)
+    addi $t1, $t1, 4
+    addi $t2, $t2, 4
+    addi $t3, $t3, -1
+    bne $t3, $zero, copy
+    lw $ra, 12($sp)
+    addi $sp, $sp, 16
+    jr $ra
+gadget:
+    li $a0, 666
+    li $v0, 1
+    syscall
+    li $v0, 10
+    syscall
+",
+        words
+    )
+}
+
+fn run(
+    source: &str,
+    checks: Option<ControlFlowChecks>,
+) -> (Simulator, Result<(), SimulationError>) {
+    let mut simulator = Simulator::from_assembly(source).unwrap();
+    simulator.registers.write(29, STACK_TOP);
+    simulator.captured_output = Some(Vec::new());
+    if let Some(checks) = checks {
+        simulator.enable_control_flow_checks(checks);
+    }
+    let result = simulator.run();
+    (simulator, result)
+}
+
+fn output(simulator: &Simulator) -> String {
+    String::from_utf8_lossy(simulator.captured_output.as_deref().unwrap()).into_owned()
+}
+
+#[test]
+fn test_shadow_stack_catches_a_hijacked_return() {
+    let source = overflow_program(4);
+    let (unchecked, result) = run(&source, None);
+    assert!(result.is_ok());
+    assert_eq!(output(&unchecked), "666\n");
+
+    let checks = ControlFlowChecks {
+        shadow_stack: true,
+        canary: false,
+    };
+    let (checked, result) = run(&source, Some(checks));
+    let error = result.unwrap_err();
+    let gadget = checked.registers.read(31);
+    // The call follows two two-instruction la and a sw
+    let return_address = 0x0040_0000 + 6 * 4;
+    assert_eq!(
+        error.cause,
+        SimulationErrorCause::ReturnMismatch(gadget, return_address)
+    );
+    assert_eq!(checked.memory.read_word(error.pc as usize), Ok(0x03E0_0008));
+    assert_eq!(checked.termination_reason, Some(TerminationReason::Trap));
+    assert_eq!(output(&checked), "");
+    let guard = checked.control_flow.as_ref().unwrap();
+    assert_eq!((guard.calls, guard.returns), (1, 1));
+}
+
+#[test]
+fn test_canary_reports_the_overwritten_slot_and_safe_code_runs() {
+    let checks = ControlFlowChecks {
+        shadow_stack: true,
+        canary: true,
+    };
+    let (checked, result) = run(&overflow_program(4), Some(checks));
+    let slot = STACK_TOP - 16 + 12;
+    assert_eq!(
+        result.unwrap_err().cause,
+        SimulationErrorCause::StackSmashed(slot)
+    );
+    assert_eq!(output(&checked), "");
+
+    // Copying only what fits leaves the return address alone
+    let (safe, result) = run(&overflow_program(3), Some(checks));
+    assert!(result.is_ok());
+    assert_eq!(output(&safe), "1\n");
+    let guard = safe.control_flow.as_ref().unwrap();
+    assert_eq!((guard.calls, guard.returns, guard.max_depth), (1, 1, 1));
+    assert!(guard.frames.is_empty());
+}
+
+#[test]
+fn test_cli_traps_with_shadow_stack() {
+    let mut file = NamedTempFile::new().unwrap();
+    write!(file, "{}", overflow_program(4)).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--input")
+        .arg(file.path())
+        .arg("--shadow-stack");
+    cmd.assert()
+        .success()
+        .stdout(contains(
+            "does not match the shadow stack, which expected 0x00400018",
+        ))
+        .stdout(contains("Termination reason: trap"))
+        .stdout(contains("1 calls, 1 returns checked, deepest call stack 1"));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--input")
+        .arg(file.path())
+        .arg("--stack-canary");
+    cmd.assert().success().stdout(contains(
+        "stack smashing detected: the return address saved at",
+    ));
+}
@@ -0,0 +1,135 @@
+// tests/functional/cosim.rs
+// Tests for the co-simulation bridge that checks RTL cores against the
+// functional simulator
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::process::{Command, Stdio};
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::server::cosim::CosimBridge;
+
+const PROGRAM: &str = "
+.data
+value: .word 0
+.text
+    addi $t0, $zero, 5
+    la $t1, value
+    sb $t0, 1($t1)
+    lw $t2, 0($t1)
+    mult $t0, $t0
+    li $v0, 10
+    syscall
+";
+
+/// What a correct core retires for `PROGRAM`, without HI and LO
+const RETIRED: [&str; 8] = [
+    "commit 0x00400000 x8=0x00000005",
+    "commit 0x00400004 x9=0x10000000",
+    "commit 0x00400008 x9=0x10000000",
+    "commit 0x0040000c mem=0x10000001:0x00000005",
+    "commit 0x00400010 x10=0x00000500",
+    "commit 0x00400014",
+    "commit 0x00400018 x2=0x0000000a",
+    "commit 0x0040001c",
+];
+
+fn bridge() -> CosimBridge {
+    CosimBridge::new(FunctionalSimulator::from_assembly(PROGRAM).unwrap())
+}
+
+#[test]
+fn test_matching_core_passes() {
+    let mut bridge = bridge();
+    for line in RETIRED {
+        assert_eq!(bridge.handle(line).unwrap(), "ok", "{}", line);
+    }
+    assert_eq!(bridge.handle("reg x10").unwrap(), "0x00000500");
+    assert_eq!(bridge.handle("reg lo").unwrap(), "0x00000019");
+    assert_eq!(bridge.handle("mem 0x10000000").unwrap(), "0x00000500");
+    assert_eq!(bridge.handle("step").unwrap(), "halted");
+    assert_eq!(bridge.report().checked, 8);
+    assert_eq!(bridge.report().mismatches, 0);
+    assert_eq!(bridge.handle("quit"), None);
+}
+
+#[test]
+fn test_divergence_is_reported() {
+    let mut bridge = bridge();
+    assert_eq!(
+        bridge.handle("step").unwrap(),
+        "commit 0x00400000 x8=0x00000005"
+    );
+    assert_eq!(bridge.handle(RETIRED[1]).unwrap(), "ok");
+    assert_eq!(
+        bridge.handle("commit 0x00400008 x9=0x10000004").unwrap(),
+        "mismatch x9: expected 0x10000000, got 0x10000004"
+    );
+    // Only the stored byte counts, but the address must match
+    assert_eq!(
+        bridge
+            .handle("commit 0x0040000c mem=0x10000000:0x00000505")
+            .unwrap(),
+        "mismatch store: expected 0x10000001:0x00000005, got 0x10000000:0x00000505"
+    );
+    assert_eq!(
+        bridge
+            .handle("commit 0x00400014 x10=0x00000500 hi=0x00000001")
+            .unwrap(),
+        "mismatch pc: expected 0x00400010, got 0x00400014; hi: expected no write, got 0x00000001"
+    );
+    assert_eq!(
+        bridge.handle("commit 0x00400014 q=1").unwrap(),
+        "error unknown register 'q'"
+    );
+
+    let report = bridge.report();
+    assert_eq!((report.checked, report.mismatches), (4, 3));
+    let mismatch = report.first_mismatch.as_ref().unwrap();
+    assert_eq!(mismatch.instruction, 1);
+    assert_eq!(mismatch.expected, "0x00400008 x9=0x10000000");
+    assert!(report
+        .render()
+        .starts_with("Co-simulation: 4 instructions checked, 3 mismatches\n"));
+}
+
+#[test]
+fn test_cli_serves_a_testbench() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("program.asm");
+    std::fs::write(&input, PROGRAM).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_vmips_rust"))
+        .current_dir(dir.path())
+        .args(["cosim", "--address", "127.0.0.1:0", "-i"])
+        .arg(&input)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let address = loop {
+        let mut line = String::new();
+        assert!(
+            stdout.read_line(&mut line).unwrap() > 0,
+            "cosim exited early"
+        );
+        if let Some(address) = line.trim().strip_prefix("Waiting for the testbench on ") {
+            break address.to_string();
+        }
+    };
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    let mut replies = BufReader::new(stream.try_clone().unwrap());
+    for line in RETIRED {
+        writeln!(stream, "{}", line).unwrap();
+        let mut reply = String::new();
+        replies.read_line(&mut reply).unwrap();
+        assert_eq!(reply, "ok\n");
+    }
+    writeln!(stream, "quit").unwrap();
+
+    assert!(child.wait().unwrap().success());
+    let mut rest = String::new();
+    while stdout.read_line(&mut rest).unwrap() > 0 {}
+    assert!(rest.contains("Co-simulation: 8 instructions checked, 0 mismatches"));
+}
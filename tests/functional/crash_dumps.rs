@@ -0,0 +1,146 @@
+// tests/functional/crash_dumps.rs
+// Tests for the postmortem dumps written when a run stops on a fault
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::memory::Memory;
+use vmips_rust::functional_simulator::registers::Registers;
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
+use vmips_rust::utils::crash_dump::{CrashDump, RecentInstructions};
+use vmips_rust::utils::limits::TerminationReason;
+
+// A loop of three, then an instruction word the decoder rejects
+const FAULTING: [u32; 4] = [0x2008_0003, 0x2108_FFFF, 0x1500_FFFE, 0xFC00_0000];
+
+fn bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+fn timing_simulator(image: &ProgramImage) -> TimingSimulator {
+    let variant = TimingVariant::default();
+    let mut simulator = TimingSimulator::new(
+        variant.pipeline_config,
+        variant.instr_cache_config,
+        variant.data_cache_config,
+        0x10000,
+    );
+    simulator.visualization = None;
+    simulator.load_image(image).unwrap();
+    simulator
+}
+
+#[test]
+fn test_recent_instructions_and_dump_rendering() {
+    let mut recent = RecentInstructions::new(2);
+    recent.record(0x0040_0000, 0x2008_0003);
+    recent.record(0x0040_0004, 0x2108_FFFF);
+    recent.record(0x0040_0008, 0x1500_FFFE);
+    assert_eq!(
+        recent.entries(),
+        vec![(0x0040_0004, 0x2108_FFFF), (0x0040_0008, 0x1500_FFFE)]
+    );
+
+    let mut registers = Registers::new();
+    registers.write(29, 0x1000);
+    registers.write(8, 0xDEAD_BEEF);
+    let mut memory = Memory::new(0x2000);
+    memory.write_word(0x1000, 0x1234_5678).unwrap();
+    let dump = CrashDump::capture(
+        "functional",
+        TerminationReason::InvalidInstruction,
+        0x0040_0008,
+        3,
+        &registers,
+        &memory,
+        Some(&recent),
+    );
+    assert!(dump
+        .summary()
+        .starts_with("Crash: invalid instruction at PC 0x00400008"));
+    assert!(dump.summary().ends_with("after 3 instructions"));
+
+    let text = dump.render();
+    assert!(text.contains("$t0   0xDEADBEEF"));
+    assert!(text.contains("Last 2 instructions:"));
+    assert!(text.contains("   0x00400004: 2108FFFF  addi $8, $8, -1"));
+    assert!(text.contains("=> 0x00400008: 1500FFFE  bne $8, $0, -2"));
+    assert!(text.contains("sp-> 0x00001000: 0x12345678"));
+    assert!(!text.contains("Pipeline:"));
+}
+
+#[test]
+fn test_simulators_capture_a_dump_on_fault() {
+    let image = ProgramImage::raw(&bytes(&FAULTING));
+
+    let mut functional = FunctionalSimulator::new(0x10000);
+    functional.load_image(&image).unwrap();
+    functional.enable_crash_dumps(4);
+    let _ = functional.run();
+    let dump = functional.crash_dump().unwrap();
+    assert_eq!(dump.reason, TerminationReason::InvalidInstruction);
+    assert_eq!(dump.pc, 0x0C);
+    assert_eq!(dump.recent.len(), 4);
+    assert_eq!(dump.recent.last(), Some(&(0x0C, 0xFC00_0000)));
+    assert_eq!(dump.registers[8], 0);
+
+    let mut timing = timing_simulator(&image);
+    timing.enable_crash_dumps(4);
+    timing.run();
+    let dump = timing.crash_dump().unwrap();
+    assert_eq!(dump.pc, 0x0C);
+    assert_eq!(dump.recent.last(), Some(&(0x0C, 0xFC00_0000)));
+    assert_eq!(dump.pipeline.len(), 5);
+    assert!(dump.render().contains("Pipeline:"));
+
+    // A program that exits cleanly leaves nothing to dump
+    let exits = ProgramImage::raw(&bytes(&[0x2402_000A, 0x0000_000C]));
+    let mut timing = timing_simulator(&exits);
+    timing.enable_crash_dumps(4);
+    timing.run();
+    assert!(timing.crash_dump().is_none());
+}
+
+#[test]
+fn test_crash_dump_option() {
+    let dir = tempdir().unwrap();
+    let binary = dir.path().join("fault.bin");
+    fs::write(&binary, bytes(&FAULTING)).unwrap();
+    let dump = dir.path().join("crash.txt");
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("timing")
+        .arg("--input")
+        .arg(&binary)
+        .arg("--crash-dump")
+        .arg(&dump)
+        .arg("--crash-depth")
+        .arg("3");
+    cmd.assert()
+        .success()
+        .stdout(contains("Crash: invalid instruction at PC 0x0000000C"))
+        .stdout(contains("Crash dump written to"));
+    let written = fs::read_to_string(&dump).unwrap();
+    assert!(written.contains("vmips crash dump (timing simulator)"));
+    assert!(written.contains("Last 3 instructions:"));
+    assert!(written.contains("=> 0x0000000C: FC000000"));
+
+    // Without a fault no file is written
+    let clean = dir.path().join("clean.txt");
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--input")
+        .arg("examples/programs/bubble_sort.s")
+        .arg("--crash-dump")
+        .arg(&clean);
+    cmd.assert().success();
+    assert!(!clean.exists());
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional").arg("--crash-depth").arg("8");
+    cmd.assert().failure();
+}
@@ -0,0 +1,139 @@
+// tests/functional/flamegraph.rs
+// Tests for call stack profiles written in the collapsed flamegraph format
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::collections::BTreeMap;
+use std::fs;
+use tempfile::tempdir;
+use vmips_rust::assembler::ProgramBuilder;
+use vmips_rust::functional_simulator::flamegraph::StackProfile;
+use vmips_rust::functional_simulator::instructions::Instruction;
+use vmips_rust::functional_simulator::registers::{Registers, RA, S0, T0, V0, ZERO};
+use vmips_rust::functional_simulator::simulator::Simulator;
+
+const SOURCE: &str = "
+.text
+main:
+    li $s0, 3
+outer:
+    jal work
+    addi $s0, $s0, -1
+    bne $s0, $zero, outer
+    li $v0, 10
+    syscall
+work:
+    li $t0, 4
+spin:
+    addi $t0, $t0, -1
+    bne $t0, $zero, spin
+    jr $ra
+";
+
+#[test]
+fn test_profile_follows_calls_and_names_frames() {
+    let mut profile = StackProfile::new(0x100, 1);
+    let mut registers = Registers::new();
+    let add = Instruction::Add {
+        rd: 8,
+        rs: 8,
+        rt: 8,
+    };
+    profile.record(&add, &registers, 0x100);
+    profile.record(&Instruction::Jal { target: 0x80 >> 2 }, &registers, 0x104);
+    profile.record(&add, &registers, 0x80);
+    registers.write(9, 0x310);
+    profile.record(&Instruction::Jalr { rd: 31, rs: 9 }, &registers, 0x84);
+    profile.record(&add, &registers, 0x310);
+    profile.record(&Instruction::Jr { rs: 31 }, &registers, 0x314);
+    profile.record(&Instruction::Jr { rs: 31 }, &registers, 0x88);
+    profile.record(&add, &registers, 0x108);
+    // Returning from the entry function keeps it at the bottom
+    profile.record(&Instruction::Jr { rs: 31 }, &registers, 0x10C);
+    assert_eq!(profile.frames, vec![0x100]);
+    assert_eq!(profile.samples(), 9);
+    assert_eq!(profile.stacks(), 3);
+
+    let mut symbols = BTreeMap::new();
+    symbols.insert("__start".to_string(), 0x100);
+    symbols.insert("main".to_string(), 0x100);
+    symbols.insert("helper".to_string(), 0x300);
+    assert_eq!(
+        profile.collapsed(&symbols),
+        "main 4\nmain;0x00000080 3\nmain;0x00000080;helper+0x10 2\n"
+    );
+
+    let mut sampled = StackProfile::new(0x100, 3);
+    for pc in 0..7 {
+        sampled.record(&add, &registers, 0x100 + 4 * pc);
+    }
+    assert_eq!(sampled.instructions, 7);
+    assert_eq!(sampled.samples(), 3);
+}
+
+#[test]
+fn test_simulator_profiles_a_run() {
+    let image = ProgramBuilder::new()
+        .label("main")
+        .li(S0, 2)
+        .label("outer")
+        .jal("work")
+        .addi(S0, S0, -1)
+        .bne(S0, ZERO, "outer")
+        .li(V0, 10)
+        .syscall()
+        .label("work")
+        .li(T0, 3)
+        .label("spin")
+        .addi(T0, T0, -1)
+        .bne(T0, ZERO, "spin")
+        .jr(RA)
+        .image()
+        .unwrap();
+    assert_eq!(image.symbols.get("work"), Some(&0x0040_0018));
+
+    let mut simulator = Simulator::from_image(&image).unwrap();
+    simulator.enable_stack_profile(1);
+    simulator.run().unwrap();
+    let profile = simulator.stack_profile.as_ref().unwrap();
+    // Each call runs li, three turns of the loop and jr
+    assert_eq!(profile.collapsed(&image.symbols), "main 9\nmain;work 16\n");
+}
+
+#[test]
+fn test_flamegraph_option() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("calls.s");
+    fs::write(&source, SOURCE).unwrap();
+    let output = dir.path().join("calls.folded");
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--flamegraph")
+        .arg(&output);
+    cmd.assert().success().stdout(contains(
+        "Flamegraph: 42 samples in 2 call stacks written to",
+    ));
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "main 12\nmain;work 30\n"
+    );
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--flamegraph")
+        .arg(&output)
+        .arg("--flamegraph-every")
+        .arg("10");
+    cmd.assert()
+        .success()
+        .stdout(contains("Flamegraph: 5 samples"));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional").arg("--flamegraph-every").arg("10");
+    cmd.assert().failure();
+}
@@ -0,0 +1,212 @@
+// tests/functional/lenient_decode.rs
+// Tests for running past unknown instructions and cataloguing them
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::errors::SimulationErrorCause;
+use vmips_rust::functional_simulator::decode_report::{DecoderGap, UnknownInstructionPolicy};
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
+use vmips_rust::utils::limits::TerminationReason;
+
+/// A reserved SPECIAL function code
+const RESERVED_FUNCT: u32 = 0x0085_1005;
+/// A reserved opcode
+const RESERVED_OPCODE: u32 = 0xEC00_0000;
+
+/// Counts three times round a loop. The `add $zero` placeholders at
+/// 0x00400004 and 0x00400014 are replaced with unknown words.
+const LOOP_PROGRAM: &str = "
+.text
+    addi $t0, $zero, 3
+loop:
+    add $zero, $zero, $zero
+    addi $s0, $s0, 1
+    addi $t0, $t0, -1
+    bne $t0, $zero, loop
+    add $zero, $zero, $zero
+    li $v0, 10
+    syscall
+.ktext 0x80000180
+    mfc0 $k0, $13
+    srl $k0, $k0, 2
+    andi $s1, $k0, 0x1F
+    addi $s2, $s2, 1
+    mfc0 $k1, $14
+    addi $k1, $k1, 4
+    mtc0 $k1, $14
+    eret
+";
+
+fn load() -> FunctionalSimulator {
+    let mut simulator = FunctionalSimulator::from_assembly(LOOP_PROGRAM).unwrap();
+    simulator
+        .preload_memory(&[
+            (0x0040_0004, RESERVED_FUNCT),
+            (0x0040_0014, RESERVED_OPCODE),
+        ])
+        .unwrap();
+    simulator
+}
+
+#[test]
+fn test_unknown_instructions_run_as_nops() {
+    let mut simulator = load();
+    simulator.enable_lenient_decode(UnknownInstructionPolicy::Nop);
+    simulator.run().unwrap();
+
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::Exited)
+    );
+    assert_eq!(simulator.registers.read(16), 3);
+    let report = simulator.lenient_decode.as_ref().unwrap();
+    assert_eq!(report.executions(), 4);
+    let sites: Vec<(u32, u32, usize)> = report
+        .unknown_instructions()
+        .map(|site| (site.pc, site.word, site.count))
+        .collect();
+    assert_eq!(
+        sites,
+        vec![
+            (0x0040_0004, RESERVED_FUNCT, 3),
+            (0x0040_0014, RESERVED_OPCODE, 1)
+        ]
+    );
+    assert_eq!(
+        report.gaps(),
+        vec![
+            DecoderGap {
+                opcode: 0x00,
+                field: Some(("funct", 0x05)),
+                sites: 1,
+                executions: 3,
+                example: RESERVED_FUNCT,
+            },
+            DecoderGap {
+                opcode: 0x3B,
+                field: None,
+                sites: 1,
+                executions: 1,
+                example: RESERVED_OPCODE,
+            },
+        ]
+    );
+    let rendered = report.render();
+    assert!(rendered.starts_with("Unknown instructions: 4 at 2 addresses, skipped as NOPs"));
+    assert!(rendered.contains("opcode 0x00 funct 0x05"));
+    assert!(rendered.contains("0x00400014   0xEC000000"));
+
+    // Without lenient decoding the first unknown word stops the run
+    let mut simulator = load();
+    let error = simulator.run().unwrap_err();
+    assert_eq!(error.cause, SimulationErrorCause::InvalidInstruction);
+    assert_eq!(error.pc, 0x0040_0004);
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::InvalidInstruction)
+    );
+}
+
+#[test]
+fn test_unknown_instructions_trap_to_the_guest() {
+    let mut simulator = load();
+    simulator.enable_lenient_decode(UnknownInstructionPolicy::Trap);
+    simulator.run().unwrap();
+
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::Exited)
+    );
+    assert_eq!(simulator.registers.read(16), 3);
+    // The handler saw four Reserved Instruction exceptions
+    assert_eq!(simulator.registers.read(17), 10);
+    assert_eq!(simulator.registers.read(18), 4);
+    assert_eq!(simulator.lenient_decode.as_ref().unwrap().executions(), 4);
+
+    // Single steps are lenient too
+    let mut simulator = load();
+    simulator.enable_lenient_decode(UnknownInstructionPolicy::Nop);
+    while simulator.step().unwrap() {}
+    assert_eq!(simulator.registers.read(16), 3);
+    assert_eq!(
+        UnknownInstructionPolicy::from_name("trap"),
+        Some(UnknownInstructionPolicy::Trap)
+    );
+    assert_eq!(UnknownInstructionPolicy::from_name("halt"), None);
+}
+
+#[test]
+fn test_timing_runs_and_the_command_line() {
+    let image = ProgramImage::from_assembly(LOOP_PROGRAM).unwrap();
+    let mut simulator = TimingSimulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(1024, 2, 32),
+        CacheConfig::new(1024, 2, 32),
+        image.memory_required().next_power_of_two(),
+    );
+    simulator.load_image(&image).unwrap();
+    simulator
+        .memory
+        .write_word(0x0040_0004, RESERVED_FUNCT)
+        .unwrap();
+    simulator.enable_lenient_decode();
+    simulator.enable_timeline();
+    simulator.run();
+    assert_ne!(
+        simulator.termination_reason,
+        Some(TerminationReason::InvalidInstruction)
+    );
+    assert_eq!(simulator.registers.read(16), 3);
+    assert_eq!(simulator.lenient_decode.as_ref().unwrap().executions(), 3);
+    // Each unknown word is timed as an instruction
+    let timeline = simulator.timeline.as_ref().unwrap();
+    let timed = timeline
+        .iter()
+        .filter(|timing| timing.word == RESERVED_FUNCT);
+    assert_eq!(timed.count(), 3);
+
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("straight.s");
+    std::fs::write(
+        &source,
+        "
+.text
+    add $zero, $zero, $zero
+    addi $s0, $zero, 1
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+    for mode in ["functional", "timing"] {
+        let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .arg(mode)
+            .arg("--input")
+            .arg(&source)
+            .arg("--preload")
+            .arg("0x00400000=0x00851005")
+            .arg("--lenient-decode");
+        cmd.assert()
+            .success()
+            .stdout(contains(
+                "Unknown instructions: 1 at 1 addresses, skipped as NOPs",
+            ))
+            .stdout(contains("opcode 0x00 funct 0x05"));
+    }
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--preload")
+        .arg("0x00400000=0x00851005");
+    cmd.assert()
+        .success()
+        .stdout(contains("Termination reason: invalid instruction"));
+}
@@ -0,0 +1,135 @@
+// tests/functional/lockstep.rs
+// Tests for the lockstep determinism checker
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::lockstep::{
+    compare_state, LockstepChecker, DEFAULT_CHECKPOINT_INTERVAL,
+};
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::utils::limits::TerminationReason;
+
+/// Counts down 2500 times, stores the count and prints 42
+const COUNT_PROGRAM: &str = "
+.data 0x10001000
+result: .word 0
+.text
+    li $t0, 2500
+loop:
+    addi $t0, $t0, -1
+    addi $s0, $s0, 1
+    bne $t0, $zero, loop
+    la $t1, result
+    sw $s0, 0($t1)
+    li $a0, 42
+    li $v0, 1
+    syscall
+    li $v0, 10
+    syscall
+";
+
+fn load() -> Simulator {
+    let mut simulator = Simulator::from_assembly(COUNT_PROGRAM).unwrap();
+    simulator.captured_output = Some(Vec::new());
+    simulator
+}
+
+#[test]
+fn test_identical_runs_match_at_every_checkpoint() {
+    let (mut first, mut second) = (load(), load());
+    let report = LockstepChecker::default().run(&mut first, &mut second);
+
+    assert!(report.is_deterministic(), "{}", report.render());
+    assert_eq!(report.instructions, 7509);
+    // One checkpoint per thousand instructions and one where both stopped
+    assert_eq!(report.checkpoints, 8);
+    assert_eq!(
+        report.render(),
+        "Lockstep: both runs matched at 8 checkpoints over 7509 instructions\n"
+    );
+    assert_eq!(first.termination_reason, Some(TerminationReason::Exited));
+    assert_eq!(first.captured_output, Some(b"42\n".to_vec()));
+    assert_eq!(first.memory.read_word(0x1000_1000), Ok(2500));
+    assert!(compare_state(&first, &second).is_empty());
+    assert_eq!(DEFAULT_CHECKPOINT_INTERVAL, 1000);
+}
+
+#[test]
+fn test_divergence_is_reported_at_the_first_checkpoint() {
+    // Only the second run has a word preloaded next to the result
+    let (mut first, mut second) = (load(), load());
+    second.preload_memory(&[(0x1000_1004, 7)]).unwrap();
+    let report = LockstepChecker::new(500).run(&mut first, &mut second);
+    let divergence = report.divergence.as_ref().unwrap();
+    assert_eq!(report.checkpoints, 1);
+    assert_eq!(divergence.instructions, 500);
+    assert_eq!(
+        divergence.differences,
+        vec!["memory: 1 bytes differ, first word at 0x00001004: 0x00000000 vs 0x00000007"]
+    );
+
+    // Register, PC and output differences are all listed
+    let (mut first, mut second) = (load(), load());
+    second.registers.write(16, 5);
+    let report = LockstepChecker::new(1).run(&mut first, &mut second);
+    assert_eq!(report.checkpoints, 1);
+    let rendered = report.render();
+    assert!(rendered.starts_with("Lockstep: runs diverged at checkpoint 1 after 1 instructions"));
+    assert!(
+        rendered.contains("$s0: 0x00000000 vs 0x00000005"),
+        "{}",
+        rendered
+    );
+
+    let (mut first, mut second) = (load(), load());
+    first.run().unwrap();
+    second.set_max_steps(10);
+    second.run().unwrap();
+    let differences = compare_state(&first, &second);
+    assert!(differences.contains(&"instructions: 7509 vs 10".to_string()));
+    assert!(differences.contains(&"output: 3 bytes vs 0 bytes".to_string()));
+    assert!(differences
+        .iter()
+        .any(|difference| difference.starts_with("pc: ")));
+}
+
+#[test]
+fn test_lockstep_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("count.s");
+    std::fs::write(&source, COUNT_PROGRAM).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--lockstep");
+    cmd.assert()
+        .success()
+        .stdout(contains("checking every 1000 instructions"))
+        .stdout(contains("42"))
+        .stdout(contains(
+            "Lockstep: both runs matched at 8 checkpoints over 7509 instructions",
+        ));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--lockstep")
+        .arg("5000");
+    cmd.assert().success().stdout(contains(
+        "Lockstep: both runs matched at 2 checkpoints over 7509 instructions",
+    ));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--lockstep")
+        .arg("0")
+        .assert()
+        .failure()
+        .stderr(contains("invalid value '0'"));
+}
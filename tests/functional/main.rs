@@ -0,0 +1,18 @@
+// tests/functional/main.rs
+// Tests for the functional simulator's run controls, checks and logs
+
+mod call_function;
+mod checkpoints;
+mod commit_log;
+mod control_flow;
+mod cosim;
+mod crash_dumps;
+mod flamegraph;
+mod lenient_decode;
+mod lockstep;
+mod progress;
+mod run_asm;
+mod run_limits;
+mod simulation_errors;
+mod symbolic;
+mod taint;
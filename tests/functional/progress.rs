@@ -0,0 +1,96 @@
+// tests/functional/progress.rs
+// Tests for periodic progress reporting during long simulations
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
+use vmips_rust::utils::progress::{ProgressCallback, ProgressReport};
+
+/// An endless counting loop
+const LOOP_PROGRAM: [(usize, u32); 2] = [
+    (0x00, 0x21080001), // addi $t0, $t0, 1
+    (0x04, 0x08000000), // j 0
+];
+
+/// Helper function returning a callback that records every report
+fn recording_callback() -> (Rc<RefCell<Vec<ProgressReport>>>, ProgressCallback) {
+    let reports = Rc::new(RefCell::new(Vec::new()));
+    let sink = Rc::clone(&reports);
+    let callback: ProgressCallback = Box::new(move |report| sink.borrow_mut().push(*report));
+    (reports, callback)
+}
+
+#[test]
+fn test_functional_progress_callback() {
+    let mut simulator = Simulator::new(32768);
+    simulator.load_program(&[]);
+    for &(addr, word) in &LOOP_PROGRAM {
+        simulator.memory.write_word(addr, word).unwrap();
+    }
+    simulator.set_max_instructions(200);
+
+    let (reports, callback) = recording_callback();
+    simulator.set_progress_callback(Duration::ZERO, callback);
+    simulator.run().unwrap();
+
+    let reports = reports.borrow();
+    assert!(reports.len() > 1);
+    assert!(reports
+        .windows(2)
+        .all(|pair| pair[0].instructions <= pair[1].instructions));
+
+    // The last report carries the final totals
+    let last = reports.last().unwrap();
+    assert_eq!(last.instructions, 200);
+    assert_eq!(last.cycles, 200);
+}
+
+#[test]
+fn test_timing_progress_is_throttled() {
+    let mut simulator = TimingSimulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    simulator.visualization = None;
+    for &(addr, word) in &LOOP_PROGRAM {
+        simulator.memory.write_word_init(addr, word);
+    }
+    simulator.set_max_cycles(100);
+
+    // With a long interval only the final report is delivered
+    let (reports, callback) = recording_callback();
+    simulator.set_progress_callback(Duration::from_secs(3600), callback);
+    simulator.run();
+
+    let reports = reports.borrow();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].cycles, 100);
+    assert!(reports[0].instructions > 0);
+}
+
+#[test]
+fn test_progress_report_speed() {
+    let report = ProgressReport {
+        instructions: 3_000_000,
+        cycles: 4_500_000,
+        elapsed: Duration::from_secs(2),
+    };
+    assert!((report.mips() - 1.5).abs() < 1e-9);
+
+    let line = report.to_string();
+    assert!(line.contains("3000000 instructions"));
+    assert!(line.contains("4500000 cycles"));
+    assert!(line.contains("1.500 MIPS"));
+
+    let idle = ProgressReport {
+        instructions: 0,
+        cycles: 0,
+        elapsed: Duration::ZERO,
+    };
+    assert_eq!(idle.mips(), 0.0);
+}
@@ -0,0 +1,88 @@
+// tests/functional/run_asm.rs
+// Tests for assembling and running source in one call with Simulator::run_asm
+
+use vmips_rust::errors::SimulationErrorCause;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::utils::limits::TerminationReason;
+
+#[test]
+fn test_run_asm_captures_output() {
+    let result = Simulator::run_asm(
+        "
+.data
+greeting: .asciiz \"sum =\"
+.text
+    la $a0, greeting
+    addi $v0, $zero, 4
+    syscall
+    addi $t0, $zero, 19
+    addi $t1, $zero, 23
+    add $a0, $t0, $t1
+    addi $v0, $zero, 1
+    syscall
+    addi $a0, $zero, 33
+    addi $v0, $zero, 11
+    syscall
+    addi $v0, $zero, 10
+    syscall
+",
+    )
+    .unwrap();
+
+    assert_eq!(result.stdout, "sum =\n42\n!");
+    assert_eq!(result.registers.read(4), 33);
+    assert!(result.error.is_none());
+    assert_eq!(result.termination_reason, Some(TerminationReason::Exited));
+}
+
+#[test]
+fn test_run_asm_memory_and_stack() {
+    let result = Simulator::run_asm(
+        "
+.data
+input: .word 6
+output: .word 0
+.text
+main:
+    lw $a0, input
+    jal square
+    sw $v0, output
+    addi $v0, $zero, 10
+    syscall
+square:
+    push $ra
+    mult $a0, $a0
+    mflo $v0
+    pop $ra
+    jr $ra
+",
+    )
+    .unwrap();
+
+    assert_eq!(result.memory.read_word(0x10000004), Ok(36));
+    assert_eq!(result.registers.read(29), 0x7FFFFFFC);
+    // lw, sw, push and pop each expand to two instructions
+    assert_eq!(result.instructions, 14);
+}
+
+#[test]
+fn test_run_asm_errors() {
+    assert!(Simulator::run_asm(".text\n    addi $t0, $zero\n").is_err());
+
+    let result = Simulator::run_asm(
+        "
+.text
+    addi $t0, $zero, 1
+    teq $t0, $t0, 3
+    addi $t0, $zero, 2
+",
+    )
+    .unwrap();
+    assert_eq!(
+        result.error.map(|error| error.cause),
+        Some(SimulationErrorCause::Trap(3))
+    );
+    assert_eq!(result.pc, 0x00400004);
+    assert_eq!(result.registers.read(8), 1);
+    assert_eq!(result.termination_reason, Some(TerminationReason::Trap));
+}
@@ -0,0 +1,98 @@
+// tests/functional/run_limits.rs
+// Tests for instruction, cycle and timeout budgets and the reported termination reason
+
+use std::time::Duration;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
+use vmips_rust::utils::limits::TerminationReason;
+
+/// An endless counting loop
+const LOOP_PROGRAM: [(usize, u32); 2] = [
+    (0x00, 0x21080001), // addi $t0, $t0, 1
+    (0x04, 0x08000000), // j 0
+];
+
+fn setup_simulator(program: &[(usize, u32)]) -> Simulator {
+    let mut simulator = Simulator::new(32768);
+    simulator.load_program(&[]);
+
+    for &(addr, word) in program {
+        simulator.memory.write_word(addr, word).unwrap();
+    }
+
+    simulator
+}
+
+#[test]
+fn test_functional_instruction_limit() {
+    let mut simulator = setup_simulator(&LOOP_PROGRAM);
+    simulator.set_max_instructions(50);
+    simulator.run().unwrap();
+
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::InstructionLimit)
+    );
+    assert_eq!(simulator.step_count, 50);
+    assert_eq!(simulator.registers.read(8), 25);
+
+    // A program that exits on its own reports that instead
+    let mut simulator = setup_simulator(&[
+        (0x00, 0x2402000A), // addiu $v0, $zero, 10
+        (0x04, 0x0000000C), // syscall
+    ]);
+    simulator.run().unwrap();
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::Exited)
+    );
+}
+
+#[test]
+fn test_functional_timeout() {
+    let mut simulator = setup_simulator(&LOOP_PROGRAM);
+    simulator.set_max_instructions(usize::MAX);
+    simulator.set_max_cycles(usize::MAX);
+    simulator.set_timeout(Some(Duration::from_millis(50)));
+    simulator.run().unwrap();
+
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::Timeout)
+    );
+    assert!(simulator.step_count > 0);
+}
+
+#[test]
+fn test_timing_cycle_and_instruction_limits() {
+    let build = || {
+        let mut simulator = TimingSimulator::new(
+            PipelineConfig::new(5),
+            CacheConfig::new(4096, 2, 64),
+            CacheConfig::new(4096, 2, 64),
+            32768,
+        );
+        simulator.visualization = None;
+        for &(addr, word) in &LOOP_PROGRAM {
+            simulator.memory.write_word_init(addr, word);
+        }
+        simulator
+    };
+
+    let mut simulator = build();
+    simulator.set_max_cycles(40);
+    simulator.run();
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::CycleLimit)
+    );
+
+    let mut simulator = build();
+    simulator.set_max_instructions(10);
+    simulator.run();
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::InstructionLimit)
+    );
+}
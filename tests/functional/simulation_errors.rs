@@ -0,0 +1,66 @@
+// tests/functional/simulation_errors.rs
+// Tests for the structured errors returned by the functional simulator
+
+use vmips_rust::errors::{SimulationError, SimulationErrorCause};
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::utils::limits::TerminationReason;
+
+fn setup_simulator(program: &[u32]) -> Simulator {
+    let mut simulator = Simulator::new(1024);
+    for (i, &word) in program.iter().enumerate() {
+        simulator.memory.write_word_init(i * 4, word);
+    }
+    simulator
+}
+
+#[test]
+fn test_step_reports_invalid_instruction() {
+    let mut simulator = setup_simulator(&[
+        0x20080001, // addi $t0, $zero, 1
+        0xFC000000, // unsupported opcode
+    ]);
+
+    assert_eq!(simulator.step(), Ok(true));
+    assert_eq!(
+        simulator.step(),
+        Err(SimulationError {
+            pc: 4,
+            instruction: Some(0xFC000000),
+            cause: SimulationErrorCause::InvalidInstruction,
+        })
+    );
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::InvalidInstruction)
+    );
+}
+
+#[test]
+fn test_run_reports_misaligned_store() {
+    let mut simulator = setup_simulator(&[
+        0x20080102, // addi $t0, $zero, 0x102
+        0xAD090000, // sw $t1, 0($t0)
+    ]);
+
+    let error = simulator.run().unwrap_err();
+    assert_eq!(error.pc, 4);
+    assert_eq!(error.cause, SimulationErrorCause::MisalignedAccess(0x102));
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::MemoryFault)
+    );
+    assert!(error.to_string().contains("misaligned"));
+}
+
+#[test]
+fn test_run_reports_jump_out_of_bounds() {
+    let mut simulator = setup_simulator(&[
+        0x3C080001, // lui $t0, 1
+        0x01000008, // jr $t0
+    ]);
+
+    let error = simulator.run().unwrap_err();
+    assert_eq!(error.pc, 4);
+    assert_eq!(error.instruction, Some(0x01000008));
+    assert_eq!(error.cause, SimulationErrorCause::PcOutOfBounds(0x10000));
+}
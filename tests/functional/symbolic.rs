@@ -0,0 +1,195 @@
+// tests/functional/symbolic.rs
+// Tests for the experimental symbolic executor and its path constraints
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::rc::Rc;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::functional_simulator::symbolic::{
+    solve, BinaryOp, Constraint, Expr, PathEnd, SymbolicExecutor,
+};
+
+/// Classifies its argument, failing an assertion for 42 and for inputs below
+/// 10 whose low byte is zero
+const CHECK_PROGRAM: &str = "
+.text
+check:
+    slti $t0, $a0, 10
+    bne $t0, $zero, small
+    addi $t1, $a0, -42
+    bne $t1, $zero, big
+    break 0
+big:
+    li $v0, 2
+    jr $ra
+small:
+    sw $a0, -4($sp)
+    lb $t4, -4($sp)
+    teq $t4, $zero
+    li $v0, 1
+    jr $ra
+";
+
+/// Reads two numbers and exits early when their sum is 100
+const READ_PROGRAM: &str = "
+.text
+    li $v0, 5
+    syscall
+    add $s0, $v0, $zero
+    li $v0, 5
+    syscall
+    add $s1, $s0, $v0
+    addi $s1, $s1, -100
+    beq $s1, $zero, done
+loop:
+    j loop
+done:
+    li $v0, 10
+    syscall
+";
+
+#[test]
+fn test_solver_finds_inputs_for_constraints() {
+    let x = Expr::input(0);
+    let y = Expr::input(1);
+    let sum = Expr::binary(BinaryOp::Add, &x, &Expr::constant(5));
+    let equals_12 = Expr::binary(BinaryOp::Eq, &sum, &Expr::constant(12));
+    let constraint = |condition: &Rc<Expr>, holds| Constraint {
+        condition: condition.clone(),
+        holds,
+    };
+    assert_eq!(solve(&[constraint(&equals_12, true)], 1), Some(vec![7]));
+
+    // Signed comparisons and a second, independent input
+    let negative = Expr::binary(BinaryOp::Slt, &x, &Expr::constant(0));
+    let y_is_3 = Expr::binary(BinaryOp::Eq, &y, &Expr::constant(3));
+    let model = solve(&[constraint(&negative, true), constraint(&y_is_3, true)], 2).unwrap();
+    assert!((model[0] as i32) < 0);
+    assert_eq!(model[1], 3);
+
+    // Contradictions have no model
+    let equals_13 = Expr::binary(BinaryOp::Eq, &sum, &Expr::constant(13));
+    assert_eq!(
+        solve(
+            &[constraint(&equals_12, true), constraint(&equals_13, true)],
+            1
+        ),
+        None
+    );
+
+    // Constants fold, so known values never reach the solver
+    let folded = Expr::binary(
+        BinaryOp::Sra,
+        &Expr::constant(0x8000_0000),
+        &Expr::constant(4),
+    );
+    assert_eq!(folded.as_const(), Some(0xF800_0000));
+    assert_eq!(sum.evaluate(&[10]), 15);
+}
+
+#[test]
+fn test_paths_and_assertion_inputs() {
+    let simulator = Simulator::from_assembly(CHECK_PROGRAM).unwrap();
+    let report = SymbolicExecutor::new(1).explore(&simulator.memory, 0x0040_0000);
+
+    assert_eq!(report.paths.len(), 4);
+    assert_eq!(report.pruned, 0);
+    assert!(!report.truncated);
+    let assertions: Vec<_> = report.assertions().collect();
+    assert_eq!(assertions.len(), 2);
+    for path in &assertions {
+        let input = path.inputs[0].1 as i32;
+        // Either the 42 check or a small input whose low byte is zero
+        assert!(
+            input == 42 || (input < 10 && input & 0xFF == 0),
+            "{}",
+            input
+        );
+    }
+    assert!(report
+        .paths
+        .iter()
+        .any(|path| path.end == PathEnd::Assertion("break 0".to_string())
+            && path.inputs == vec![("$a0".to_string(), 42)]));
+    let returned: Vec<u32> = report
+        .paths
+        .iter()
+        .filter_map(|path| match path.end {
+            PathEnd::Returned(value) => Some(value),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(returned.len(), 2);
+    assert!(returned.contains(&1) && returned.contains(&2));
+    let rendered = report.render();
+    assert!(rendered
+        .starts_with("Symbolic execution from 0x00400000: 4 paths, 2 reaching an assertion"));
+    assert!(rendered.contains("reached assertion `break 0` at 0x00400010"));
+
+    // Inputs read by syscalls are symbolic too. The loop path runs out of
+    // steps; the other needs a sum of 100.
+    let simulator = Simulator::from_assembly(READ_PROGRAM).unwrap();
+    let mut executor = SymbolicExecutor::new(0);
+    executor.max_steps = 100;
+    let report = executor.explore(&simulator.memory, 0x0040_0000);
+    assert_eq!(report.paths.len(), 2);
+    let exited = report
+        .paths
+        .iter()
+        .find(|path| path.end == PathEnd::Exited)
+        .unwrap();
+    assert_eq!(exited.inputs.len(), 2);
+    assert_eq!(exited.inputs[0].0, "read_int#1");
+    assert_eq!(exited.inputs[1].0, "read_int#2");
+    assert_eq!(exited.inputs[0].1.wrapping_add(exited.inputs[1].1), 100);
+    assert!(report
+        .paths
+        .iter()
+        .any(|path| path.end == PathEnd::StepLimit && path.steps == 100));
+
+    // The path limit stops exploration early
+    executor.max_paths = 1;
+    let report = executor.explore(&simulator.memory, 0x0040_0000);
+    assert_eq!(report.paths.len(), 1);
+    assert!(report.truncated);
+    assert!(report.render().contains("Stopped after 1 paths"));
+}
+
+#[test]
+fn test_symbolic_command() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("check.s");
+    std::fs::write(&source, CHECK_PROGRAM).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("symbolic")
+        .arg("--input")
+        .arg(&source)
+        .arg("--function")
+        .arg("check");
+    cmd.assert()
+        .code(1)
+        .stdout(contains("4 paths, 2 reaching an assertion"))
+        .stdout(contains("with $a0=42"));
+
+    // Starting past the checks reaches no assertion
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("symbolic")
+        .arg("--input")
+        .arg(&source)
+        .arg("--function")
+        .arg("big");
+    cmd.assert()
+        .success()
+        .stdout(contains("1 paths, 0 reaching an assertion"))
+        .stdout(contains("returned 2"));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("symbolic")
+        .arg("--input")
+        .arg(&source)
+        .arg("--function")
+        .arg("missing");
+    cmd.assert().stderr(contains("No label named missing"));
+}
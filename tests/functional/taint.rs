@@ -0,0 +1,188 @@
+// tests/functional/taint.rs
+// Tests for taint tracking from input and marked memory to jumps and syscalls
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::functional_simulator::taint::{TaintEvent, TaintSink};
+
+/// Copies and combines a word whose lowest byte is tainted
+const PROPAGATION_PROGRAM: &str = "
+.data 0x10001000
+input: .word 0x12345678
+copy: .word 0
+.text
+    la $t0, input
+    lw $t1, 0($t0)
+    addi $t2, $t1, 4
+    sw $t2, 4($t0)
+    lb $t3, 1($t0)
+    lbu $t4, 0($t0)
+    li $t1, 5
+    mult $t2, $t1
+    mflo $s0
+    li $v0, 10
+    syscall
+";
+
+/// Returns through a saved address an attacker controls, to `win`, which
+/// prints that address
+const HIJACK_PROGRAM: &str = "
+.data 0x10001000
+saved: .word 0
+.text
+    la $t0, saved
+    lw $ra, 0($t0)
+    jr $ra
+    nop
+    li $v0, 10
+    syscall
+win:
+    add $a0, $ra, $zero
+    li $v0, 1
+    syscall
+    li $v0, 10
+    syscall
+";
+
+/// Address of `win` in HIJACK_PROGRAM
+const WIN: u32 = 0x0040_001C;
+
+#[test]
+fn test_taint_follows_the_data() {
+    let mut simulator = Simulator::from_assembly(PROPAGATION_PROGRAM).unwrap();
+    simulator.taint_memory(0x1000_1000, 1);
+    simulator.run().unwrap();
+
+    let taint = simulator.taint.as_ref().unwrap();
+    assert!(!taint.taint_input);
+    // The loaded word, values computed from it and the product are tainted
+    assert!(taint.is_register_tainted(10));
+    assert!(taint.is_register_tainted(12));
+    assert!(taint.is_register_tainted(16));
+    // An immediate overwrote $t1, and byte 1 was never tainted
+    assert!(!taint.is_register_tainted(9));
+    assert!(!taint.is_register_tainted(11));
+    assert_eq!(taint.tainted_registers(), vec![10, 12, 16]);
+    // The stored copy is tainted byte by byte
+    for address in 0x1000_1004..0x1000_1008 {
+        assert!(taint.is_memory_tainted(&simulator.memory, address));
+    }
+    assert!(!taint.is_memory_tainted(&simulator.memory, 0x1000_1001));
+    assert_eq!(taint.tainted_bytes(), 5);
+    assert_eq!(taint.tainted_instructions, 6);
+    assert!(taint.events().is_empty());
+    assert!(taint
+        .render()
+        .starts_with("Taint: 6 instructions read tainted data; 5 bytes of memory and 3 registers tainted at the end ($t2, $t4, $s0)"));
+
+    // Without tracking nothing is recorded
+    let mut simulator = Simulator::from_assembly(PROPAGATION_PROGRAM).unwrap();
+    simulator.run().unwrap();
+    assert!(simulator.taint.is_none());
+}
+
+#[test]
+fn test_tainted_jumps_and_syscall_arguments_are_reported() {
+    let mut simulator = Simulator::from_assembly(HIJACK_PROGRAM).unwrap();
+    simulator.preload_memory(&[(0x1000_1000, WIN)]).unwrap();
+    simulator.captured_output = Some(Vec::new());
+    simulator.enable_taint_tracking(false);
+    simulator.taint_memory(0x1000_1000, 4);
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.registers.read(4), WIN);
+    let taint = simulator.taint.as_ref().unwrap();
+    assert_eq!(
+        taint.events(),
+        vec![
+            TaintEvent {
+                pc: 0x0040_000C,
+                sink: TaintSink::JumpTarget { register: 31 },
+                count: 1,
+            },
+            TaintEvent {
+                pc: WIN + 8,
+                sink: TaintSink::SyscallArgument {
+                    syscall: 1,
+                    register: 4,
+                },
+                count: 1,
+            },
+        ]
+    );
+    let rendered = taint.render();
+    assert!(rendered.contains("0x0040000C          1  jump target $ra is tainted"));
+    assert!(rendered.contains("syscall 1 argument $a0 is tainted"));
+
+    // The same return with a clean saved address reaches no sink
+    let mut simulator = Simulator::from_assembly(HIJACK_PROGRAM).unwrap();
+    simulator.preload_memory(&[(0x1000_1000, WIN)]).unwrap();
+    simulator.captured_output = Some(Vec::new());
+    simulator.enable_taint_tracking(true);
+    simulator.run().unwrap();
+    let taint = simulator.taint.as_ref().unwrap();
+    assert!(taint.events().is_empty());
+    assert!(taint
+        .render()
+        .contains("Tainted data reached no jump target or syscall"));
+}
+
+#[test]
+fn test_taint_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("echo.s");
+    std::fs::write(
+        &source,
+        "
+.text
+    li $v0, 5
+    syscall
+    add $a0, $v0, $zero
+    li $v0, 1
+    syscall
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--taint")
+        .write_stdin("41\n");
+    cmd.assert()
+        .success()
+        .stdout(contains("41"))
+        .stdout(contains(
+            "Taint: 2 instructions read tainted data; 0 bytes of memory and 1 registers tainted at the end ($a0)",
+        ))
+        .stdout(contains(
+            "0x00400010          1  syscall 1 argument $a0 is tainted",
+        ));
+
+    let program = temp_dir.path().join("propagate.s");
+    std::fs::write(&program, PROPAGATION_PROGRAM).unwrap();
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&program)
+        .arg("--taint-range")
+        .arg("0x10001000:1");
+    cmd.assert()
+        .success()
+        .stdout(contains("5 bytes of memory and 3 registers tainted"));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--taint-range")
+        .arg("0x10001000")
+        .assert()
+        .failure()
+        .stderr(contains("expected ADDRESS:LENGTH"));
+}
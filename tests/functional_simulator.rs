@@ -43,7 +43,7 @@ fn setup_simulator(memory_values: &[(usize, u32)], program: &[u32]) -> Simulator
 
     // Write initial memory values
     for &(addr, value) in memory_values {
-        simulator.memory.write_word(addr, value);
+        simulator.memory.write_word(addr, value).unwrap();
         println!("Loaded value {} at memory address 0x{:X}", value, addr);
     }
 
@@ -59,7 +59,7 @@ fn setup_simulator(memory_values: &[(usize, u32)], program: &[u32]) -> Simulator
 
     // Ensure memory values are preserved by rewriting them
     for &(addr, value) in memory_values {
-        simulator.memory.write_word(addr, value);
+        simulator.memory.write_word(addr, value).unwrap();
     }
 
     simulator
@@ -84,7 +84,7 @@ fn test_basic_arithmetic() {
     }
 
     // Run simulation
-    simulator.run();
+    simulator.run().unwrap();
 
     // Print final state
     println!("Registers after execution:");
@@ -105,7 +105,7 @@ fn test_basic_arithmetic() {
     );
     assert_eq!(
         simulator.memory.read_word(0x1008),
-        Some(42),
+        Ok(42),
         "Memory at 0x1008 should contain 42"
     );
 }
@@ -128,7 +128,7 @@ fn test_load_store_operations() {
     let mut simulator = setup_simulator(&memory_values, &program);
 
     // Run simulation
-    simulator.run();
+    simulator.run().unwrap();
 
     // Verify results
     assert_eq!(
@@ -138,7 +138,7 @@ fn test_load_store_operations() {
     );
     assert_eq!(
         simulator.memory.read_word(0x2004),
-        Some(0x12345678),
+        Ok(0x12345678),
         "Memory at 0x2004 should contain 0x12345678"
     );
 }
@@ -172,7 +172,7 @@ fn test_branching() {
     simulator.load_program(&program_bytes);
 
     // Run simulation
-    simulator.run();
+    simulator.run().unwrap();
 
     // Verify results - the loop should execute 10 times and then set $3 to 42
     assert_eq!(
@@ -193,8 +193,8 @@ fn test_functional_simulator() {
     let mut simulator = Simulator::new(memory_size);
 
     // Load the expected values into memory
-    simulator.memory.write_word(0x1000, 21);
-    simulator.memory.write_word(0x1004, 21);
+    simulator.memory.write_word(0x1000, 21).unwrap();
+    simulator.memory.write_word(0x1004, 21).unwrap();
 
     println!("Test values loaded into memory at 0x1000 and 0x1004");
 
@@ -223,8 +223,8 @@ fn test_functional_simulator() {
 
     // Re-load the test values after loading the program
     // This ensures they're not overwritten
-    simulator.memory.write_word(0x1000, 21);
-    simulator.memory.write_word(0x1004, 21);
+    simulator.memory.write_word(0x1000, 21).unwrap();
+    simulator.memory.write_word(0x1004, 21).unwrap();
 
     println!("Test values re-loaded after program loading");
 
@@ -236,7 +236,7 @@ fn test_functional_simulator() {
     println!("Starting simulation");
 
     // Run the functional simulator
-    simulator.run();
+    simulator.run().unwrap();
 
     // Print results for debugging
     println!("Simulation completed");
@@ -256,7 +256,7 @@ fn test_functional_simulator() {
     );
     assert_eq!(
         simulator.memory.read_word(0x1008),
-        Some(42),
+        Ok(42),
         "Memory at 0x1008 should contain 42"
     );
 
@@ -0,0 +1,134 @@
+// tests/instructions/branch_targets.rs
+// Tests for the shared branch and jump target helpers
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::instructions::{
+    branch_offset, branch_target, jump_target, Instruction, NextPc,
+};
+use vmips_rust::functional_simulator::memory::Memory;
+use vmips_rust::functional_simulator::registers::Registers;
+use vmips_rust::functional_simulator::simulator::Simulator;
+
+#[test]
+fn test_branch_target_helpers_handle_negative_offsets() {
+    assert_eq!(branch_offset(-1), -4);
+    assert_eq!(branch_offset(i16::MIN), -131072);
+    assert_eq!(branch_target(0x100, -1), 0x100);
+    assert_eq!(branch_target(0x100, -3), 0xF8);
+    assert_eq!(branch_target(0x0040_0010, 2), 0x0040_001C);
+    assert_eq!(branch_target(0, -2), 0xFFFF_FFFC);
+
+    // Jumps keep the 256MB region of the following instruction
+    assert_eq!(jump_target(0x0040_0000, 0x0010_0004), 0x0040_0010);
+    assert_eq!(jump_target(0x1FFF_FFFC, 0), 0x2000_0000);
+
+    let bgtz = Instruction::Bgtz { rs: 8, offset: -3 };
+    assert_eq!(bgtz.calculate_branch_target(0x100), Some(0xF8));
+    assert_eq!(bgtz.get_address(&Registers::new(), 0x100), 0xF8);
+}
+
+#[test]
+fn test_all_conditional_branches_shift_their_offsets() {
+    let mut registers = Registers::new();
+    let mut memory = Memory::new(1024);
+    registers.write(8, 5); // positive
+    registers.write(9, (-5i32) as u32); // negative
+    registers.fcsr = 0x800000; // FP condition set
+
+    let taken = [
+        Instruction::Beq {
+            rs: 8,
+            rt: 8,
+            offset: -2,
+        },
+        Instruction::Bne {
+            rs: 8,
+            rt: 9,
+            offset: -2,
+        },
+        Instruction::Bgtz { rs: 8, offset: -2 },
+        Instruction::Blez { rs: 9, offset: -2 },
+        Instruction::Bltz { rs: 9, offset: -2 },
+        Instruction::Bgez { rs: 8, offset: -2 },
+        Instruction::BC1T { offset: -2 },
+    ];
+    for branch in &taken {
+        let next = branch.execute(&mut registers, &mut memory);
+        assert_eq!(next, NextPc::Relative(-8), "{:?}", branch);
+        assert_eq!(next.target(0x40), Some(0x3C), "{:?}", branch);
+    }
+
+    registers.fcsr = 0;
+    let bc1f = Instruction::BC1F { offset: 3 };
+    assert_eq!(
+        bc1f.execute(&mut registers, &mut memory),
+        NextPc::Relative(12)
+    );
+}
+
+#[test]
+fn test_bgtz_loop_counts_down() {
+    let program: Vec<u8> = [
+        0x20080004u32, // addi $t0, $zero, 4
+        0x20840003,    // loop: addi $a0, $a0, 3
+        0x2108FFFF,    // addi $t0, $t0, -1
+        0x1D00FFFD,    // bgtz $t0, loop
+        0x2002000A,    // addi $v0, $zero, 10
+        0x0000000C,    // syscall
+    ]
+    .iter()
+    .flat_map(|word| word.to_le_bytes())
+    .collect();
+
+    let mut simulator = Simulator::new(4096);
+    simulator.load_program(&program);
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.registers.read(4), 12);
+    assert_eq!(simulator.registers.read(8), 0);
+}
+
+#[test]
+fn test_cli_timing_takes_branches_and_calls() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("calls.s");
+    std::fs::write(
+        &path,
+        "
+.text
+main:
+    li $t0, 2
+loop:
+    jal bump
+    addi $t0, $t0, -1
+    bne $t0, $zero, loop
+    la $t1, bump
+    jalr $t1
+    beq $zero, $zero, done
+    li $a0, 100
+done:
+    li $v0, 1
+    syscall
+    li $v0, 10
+    syscall
+bump:
+    addi $a0, $a0, 7
+    jr $ra
+",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(contains("\n21\n"))
+        .stdout(contains("Termination reason: program exited"))
+        .stdout(contains("Unhandled instruction").not());
+}
@@ -0,0 +1,679 @@
+// tests/instructions/decoder.rs
+// Tests that every Instruction variant is produced by the decoder
+
+use vmips_rust::assembler::Assembler;
+use vmips_rust::functional_simulator::instructions::{encode, Instruction};
+use vmips_rust::functional_simulator::simulator::{
+    decode_instruction, instruction_to_string, Simulator,
+};
+
+fn r_type(rs: u32, rt: u32, rd: u32, shamt: u32, funct: u32) -> u32 {
+    (rs << 21) | (rt << 16) | (rd << 11) | (shamt << 6) | funct
+}
+
+fn i_type(opcode: u32, rs: u32, rt: u32, imm: u16) -> u32 {
+    (opcode << 26) | (rs << 21) | (rt << 16) | imm as u32
+}
+
+fn fp_type(fmt: u32, ft: u32, fs: u32, fd: u32, funct: u32) -> u32 {
+    (0x11 << 26) | (fmt << 21) | (ft << 16) | (fs << 11) | (fd << 6) | funct
+}
+
+// Position of each variant in the list below. The match has no wildcard,
+// so adding a variant to Instruction fails to compile until it is covered.
+fn variant_index(instruction: &Instruction) -> usize {
+    use Instruction::*;
+    match instruction {
+        Add { .. } => 0,
+        Sub { .. } => 1,
+        And { .. } => 2,
+        Or { .. } => 3,
+        Slt { .. } => 4,
+        Sll { .. } => 5,
+        Srl { .. } => 6,
+        Addi { .. } => 7,
+        Lw { .. } => 8,
+        Sw { .. } => 9,
+        Beq { .. } => 10,
+        J { .. } => 11,
+        Lui { .. } => 12,
+        Ori { .. } => 13,
+        Mult { .. } => 14,
+        Mflo { .. } => 15,
+        Addiu { .. } => 16,
+        Bne { .. } => 17,
+        Jr { .. } => 18,
+        Sra { .. } => 19,
+        Sllv { .. } => 20,
+        Srlv { .. } => 21,
+        Srav { .. } => 22,
+        Div { .. } => 23,
+        Divu { .. } => 24,
+        Xor { .. } => 25,
+        Nor { .. } => 26,
+        Mfhi { .. } => 27,
+        Mthi { .. } => 28,
+        Mtlo { .. } => 29,
+        Andi { .. } => 30,
+        Xori { .. } => 31,
+        Slti { .. } => 32,
+        Sltiu { .. } => 33,
+        Lb { .. } => 34,
+        Lh { .. } => 35,
+        Lbu { .. } => 36,
+        Lhu { .. } => 37,
+        Sb { .. } => 38,
+        Sh { .. } => 39,
+        Ll { .. } => 40,
+        Sc { .. } => 41,
+        Bgtz { .. } => 42,
+        Blez { .. } => 43,
+        Bltz { .. } => 44,
+        Bgez { .. } => 45,
+        Jal { .. } => 46,
+        Jalr { .. } => 47,
+        AddS { .. } => 48,
+        SubS { .. } => 49,
+        MulS { .. } => 50,
+        DivS { .. } => 51,
+        AbsS { .. } => 52,
+        NegS { .. } => 53,
+        MovS { .. } => 54,
+        CvtSW { .. } => 55,
+        CvtWS { .. } => 56,
+        CmpS { .. } => 57,
+        LwC1 { .. } => 58,
+        SwC1 { .. } => 59,
+        BC1T { .. } => 60,
+        BC1F { .. } => 61,
+        Mfc0 { .. } => 62,
+        Mtc0 { .. } => 63,
+        Eret => 64,
+        Syscall => 65,
+        Break { .. } => 66,
+        Nop => 67,
+        InvalidInstruction => 68,
+        Teq { .. } => 69,
+        Tne { .. } => 70,
+        Tge { .. } => 71,
+        Tgeu { .. } => 72,
+        Tlt { .. } => 73,
+        Tltu { .. } => 74,
+        Teqi { .. } => 75,
+        Tnei { .. } => 76,
+        Tgei { .. } => 77,
+        Tgeiu { .. } => 78,
+        Tlti { .. } => 79,
+        Tltiu { .. } => 80,
+        Sltu { .. } => 81,
+        Rdhwr { .. } => 82,
+        Rdpgpr { .. } => 83,
+        Wrpgpr { .. } => 84,
+        Addu { .. } => 85,
+    }
+}
+
+const VARIANT_COUNT: usize = 86;
+
+fn encodings() -> Vec<(u32, Instruction)> {
+    use Instruction::*;
+    vec![
+        (
+            r_type(1, 2, 3, 0, 0x20),
+            Add {
+                rd: 3,
+                rs: 1,
+                rt: 2,
+            },
+        ),
+        (
+            r_type(1, 2, 3, 0, 0x21),
+            Addu {
+                rd: 3,
+                rs: 1,
+                rt: 2,
+            },
+        ),
+        (
+            r_type(1, 2, 3, 0, 0x22),
+            Sub {
+                rd: 3,
+                rs: 1,
+                rt: 2,
+            },
+        ),
+        (
+            r_type(1, 2, 3, 0, 0x24),
+            And {
+                rd: 3,
+                rs: 1,
+                rt: 2,
+            },
+        ),
+        (
+            r_type(1, 2, 3, 0, 0x25),
+            Or {
+                rd: 3,
+                rs: 1,
+                rt: 2,
+            },
+        ),
+        (
+            r_type(1, 2, 3, 0, 0x2A),
+            Slt {
+                rd: 3,
+                rs: 1,
+                rt: 2,
+            },
+        ),
+        (
+            r_type(0, 2, 3, 4, 0x00),
+            Sll {
+                rd: 3,
+                rt: 2,
+                shamt: 4,
+            },
+        ),
+        (
+            r_type(0, 2, 3, 4, 0x02),
+            Srl {
+                rd: 3,
+                rt: 2,
+                shamt: 4,
+            },
+        ),
+        (
+            i_type(0x08, 1, 2, 0xFFFF),
+            Addi {
+                rt: 2,
+                rs: 1,
+                imm: -1,
+            },
+        ),
+        (
+            i_type(0x23, 29, 8, 4),
+            Lw {
+                rt: 8,
+                base: 29,
+                offset: 4,
+            },
+        ),
+        (
+            i_type(0x2B, 29, 8, 0xFFFC),
+            Sw {
+                rt: 8,
+                base: 29,
+                offset: -4,
+            },
+        ),
+        (
+            i_type(0x04, 1, 2, 3),
+            Beq {
+                rs: 1,
+                rt: 2,
+                offset: 3,
+            },
+        ),
+        ((0x02 << 26) | 0x100000, J { target: 0x100000 }),
+        (i_type(0x0F, 0, 1, 0x1000), Lui { rt: 1, imm: 0x1000 }),
+        (
+            i_type(0x0D, 1, 1, 0x8000),
+            Ori {
+                rt: 1,
+                rs: 1,
+                imm: 0x8000,
+            },
+        ),
+        (r_type(4, 5, 0, 0, 0x18), Mult { rs: 4, rt: 5 }),
+        (r_type(0, 0, 2, 0, 0x12), Mflo { rd: 2 }),
+        (
+            i_type(0x09, 29, 29, 0xFFF8),
+            Addiu {
+                rt: 29,
+                rs: 29,
+                imm: -8,
+            },
+        ),
+        (
+            i_type(0x05, 1, 0, 0xFFFE),
+            Bne {
+                rs: 1,
+                rt: 0,
+                offset: -2,
+            },
+        ),
+        (r_type(31, 0, 0, 0, 0x08), Jr { rs: 31 }),
+        (
+            r_type(0, 2, 3, 31, 0x03),
+            Sra {
+                rd: 3,
+                rt: 2,
+                shamt: 31,
+            },
+        ),
+        (
+            r_type(4, 2, 3, 0, 0x04),
+            Sllv {
+                rd: 3,
+                rt: 2,
+                rs: 4,
+            },
+        ),
+        (
+            r_type(4, 2, 3, 0, 0x06),
+            Srlv {
+                rd: 3,
+                rt: 2,
+                rs: 4,
+            },
+        ),
+        (
+            r_type(4, 2, 3, 0, 0x07),
+            Srav {
+                rd: 3,
+                rt: 2,
+                rs: 4,
+            },
+        ),
+        (r_type(4, 5, 0, 0, 0x1A), Div { rs: 4, rt: 5 }),
+        (r_type(4, 5, 0, 0, 0x1B), Divu { rs: 4, rt: 5 }),
+        (
+            r_type(1, 2, 3, 0, 0x26),
+            Xor {
+                rd: 3,
+                rs: 1,
+                rt: 2,
+            },
+        ),
+        (
+            r_type(1, 2, 3, 0, 0x27),
+            Nor {
+                rd: 3,
+                rs: 1,
+                rt: 2,
+            },
+        ),
+        (r_type(0, 0, 2, 0, 0x10), Mfhi { rd: 2 }),
+        (r_type(6, 0, 0, 0, 0x11), Mthi { rs: 6 }),
+        (r_type(6, 0, 0, 0, 0x13), Mtlo { rs: 6 }),
+        (
+            i_type(0x0C, 1, 2, 0xFF00),
+            Andi {
+                rt: 2,
+                rs: 1,
+                imm: 0xFF00,
+            },
+        ),
+        (
+            i_type(0x0E, 1, 2, 0x00FF),
+            Xori {
+                rt: 2,
+                rs: 1,
+                imm: 0x00FF,
+            },
+        ),
+        (
+            i_type(0x0A, 1, 2, 0xFFF0),
+            Slti {
+                rt: 2,
+                rs: 1,
+                imm: -16,
+            },
+        ),
+        (
+            i_type(0x0B, 1, 2, 10),
+            Sltiu {
+                rt: 2,
+                rs: 1,
+                imm: 10,
+            },
+        ),
+        (
+            i_type(0x20, 4, 5, 1),
+            Lb {
+                rt: 5,
+                base: 4,
+                offset: 1,
+            },
+        ),
+        (
+            i_type(0x21, 4, 5, 2),
+            Lh {
+                rt: 5,
+                base: 4,
+                offset: 2,
+            },
+        ),
+        (
+            i_type(0x24, 4, 5, 3),
+            Lbu {
+                rt: 5,
+                base: 4,
+                offset: 3,
+            },
+        ),
+        (
+            i_type(0x25, 4, 5, 6),
+            Lhu {
+                rt: 5,
+                base: 4,
+                offset: 6,
+            },
+        ),
+        (
+            i_type(0x28, 4, 5, 7),
+            Sb {
+                rt: 5,
+                base: 4,
+                offset: 7,
+            },
+        ),
+        (
+            i_type(0x29, 4, 5, 8),
+            Sh {
+                rt: 5,
+                base: 4,
+                offset: 8,
+            },
+        ),
+        (
+            i_type(0x30, 4, 5, 0),
+            Ll {
+                rt: 5,
+                base: 4,
+                offset: 0,
+            },
+        ),
+        (
+            i_type(0x38, 4, 5, 0),
+            Sc {
+                rt: 5,
+                base: 4,
+                offset: 0,
+            },
+        ),
+        (i_type(0x07, 3, 0, 5), Bgtz { rs: 3, offset: 5 }),
+        (i_type(0x06, 3, 0, 0xFFFB), Blez { rs: 3, offset: -5 }),
+        (i_type(0x01, 3, 0, 9), Bltz { rs: 3, offset: 9 }),
+        (i_type(0x01, 3, 1, 9), Bgez { rs: 3, offset: 9 }),
+        ((0x03 << 26) | 0x3FFFFFF, Jal { target: 0x3FFFFFF }),
+        (r_type(8, 0, 31, 0, 0x09), Jalr { rd: 31, rs: 8 }),
+        (
+            fp_type(0x10, 2, 1, 0, 0x00),
+            AddS {
+                fd: 0,
+                fs: 1,
+                ft: 2,
+            },
+        ),
+        (
+            fp_type(0x10, 2, 1, 0, 0x01),
+            SubS {
+                fd: 0,
+                fs: 1,
+                ft: 2,
+            },
+        ),
+        (
+            fp_type(0x10, 2, 1, 0, 0x02),
+            MulS {
+                fd: 0,
+                fs: 1,
+                ft: 2,
+            },
+        ),
+        (
+            fp_type(0x10, 2, 1, 0, 0x03),
+            DivS {
+                fd: 0,
+                fs: 1,
+                ft: 2,
+            },
+        ),
+        (fp_type(0x10, 0, 1, 3, 0x05), AbsS { fd: 3, fs: 1 }),
+        (fp_type(0x10, 0, 1, 3, 0x07), NegS { fd: 3, fs: 1 }),
+        (fp_type(0x10, 0, 1, 3, 0x06), MovS { fd: 3, fs: 1 }),
+        (fp_type(0x14, 0, 1, 3, 0x20), CvtSW { fd: 3, fs: 1 }),
+        (fp_type(0x10, 0, 1, 3, 0x24), CvtWS { fd: 3, fs: 1 }),
+        (
+            fp_type(0x10, 2, 1, 0, 0x3C),
+            CmpS {
+                fs: 1,
+                ft: 2,
+                cond: 1,
+            },
+        ),
+        (
+            i_type(0x31, 29, 4, 8),
+            LwC1 {
+                ft: 4,
+                base: 29,
+                offset: 8,
+            },
+        ),
+        (
+            i_type(0x39, 29, 4, 8),
+            SwC1 {
+                ft: 4,
+                base: 29,
+                offset: 8,
+            },
+        ),
+        (fp_type(0x08, 1, 0, 0, 0) | 0x0010, BC1T { offset: 16 }),
+        (fp_type(0x08, 0, 0, 0, 0) | 0xFFF0, BC1F { offset: -16 }),
+        (
+            (0x10 << 26) | r_type(0x00, 8, 12, 0, 0),
+            Mfc0 { rt: 8, rd: 12 },
+        ),
+        (
+            (0x10 << 26) | r_type(0x04, 8, 12, 0, 0),
+            Mtc0 { rt: 8, rd: 12 },
+        ),
+        (0x42000018, Eret),
+        (
+            (0x10 << 26) | r_type(0x0A, 9, 8, 0, 0),
+            Rdpgpr { rd: 8, rt: 9 },
+        ),
+        (
+            (0x10 << 26) | r_type(0x0E, 9, 8, 0, 0),
+            Wrpgpr { rd: 8, rt: 9 },
+        ),
+        (
+            (0x1F << 26) | r_type(0, 3, 29, 0, 0x3B),
+            Rdhwr { rt: 3, rd: 29 },
+        ),
+        (0x0000000C, Syscall),
+        ((0xABCDE << 6) | 0x0D, Break { code: 0xABCDE }),
+        (0x00000000, Nop),
+        (0xFC000000, InvalidInstruction),
+        (
+            r_type(1, 2, 3, 0, 0x2B),
+            Sltu {
+                rd: 3,
+                rs: 1,
+                rt: 2,
+            },
+        ),
+        (
+            r_type(4, 5, 0, 7, 0x34),
+            Teq {
+                rs: 4,
+                rt: 5,
+                code: 7,
+            },
+        ),
+        (
+            r_type(4, 5, 0, 7, 0x36),
+            Tne {
+                rs: 4,
+                rt: 5,
+                code: 7,
+            },
+        ),
+        (
+            r_type(4, 5, 0, 7, 0x30),
+            Tge {
+                rs: 4,
+                rt: 5,
+                code: 7,
+            },
+        ),
+        (
+            r_type(4, 5, 0, 7, 0x31),
+            Tgeu {
+                rs: 4,
+                rt: 5,
+                code: 7,
+            },
+        ),
+        (
+            r_type(4, 5, 0, 7, 0x32),
+            Tlt {
+                rs: 4,
+                rt: 5,
+                code: 7,
+            },
+        ),
+        (
+            r_type(4, 5, 0, 7, 0x33),
+            Tltu {
+                rs: 4,
+                rt: 5,
+                code: 7,
+            },
+        ),
+        (i_type(0x01, 4, 0x0C, 0xFFFF), Teqi { rs: 4, imm: -1 }),
+        (i_type(0x01, 4, 0x0E, 0xFFFF), Tnei { rs: 4, imm: -1 }),
+        (i_type(0x01, 4, 0x08, 0xFFFF), Tgei { rs: 4, imm: -1 }),
+        (i_type(0x01, 4, 0x09, 0xFFFF), Tgeiu { rs: 4, imm: -1 }),
+        (i_type(0x01, 4, 0x0A, 0xFFFF), Tlti { rs: 4, imm: -1 }),
+        (i_type(0x01, 4, 0x0B, 0xFFFF), Tltiu { rs: 4, imm: -1 }),
+    ]
+}
+
+#[test]
+fn test_every_variant_decodes() {
+    let mut covered = [false; VARIANT_COUNT];
+    for (word, expected) in encodings() {
+        assert_eq!(decode_instruction(word), expected, "word 0x{:08X}", word);
+        covered[variant_index(&expected)] = true;
+    }
+    for (index, covered) in covered.iter().enumerate() {
+        assert!(covered, "no encoding for variant {}", index);
+    }
+}
+
+#[test]
+fn test_every_variant_encodes_to_its_word() {
+    for (word, instruction) in encodings() {
+        assert_eq!(encode(&instruction), word, "{:?}", instruction);
+    }
+}
+
+#[test]
+fn test_assembler_output_decodes_to_matching_variants() {
+    let source = "
+.text
+    c.eq.s $f1, $f2
+    c.lt.s $f1, $f2
+    c.le.s $f1, $f2
+    cvt.s.w $f3, $f1
+    cvt.w.s $f3, $f1
+    break 5
+    rdhwr $3, $29
+    mfc0 $8, $12, 2
+    wrpgpr $29, $8
+";
+    let bytes = Assembler::new().assemble_string(source).unwrap();
+    let text = &bytes[8..];
+    let decoded: Vec<Instruction> = text
+        .chunks(4)
+        .map(|word| decode_instruction(u32::from_le_bytes([word[0], word[1], word[2], word[3]])))
+        .collect();
+
+    assert_eq!(
+        decoded,
+        vec![
+            Instruction::CmpS {
+                fs: 1,
+                ft: 2,
+                cond: 0
+            },
+            Instruction::CmpS {
+                fs: 1,
+                ft: 2,
+                cond: 1
+            },
+            Instruction::CmpS {
+                fs: 1,
+                ft: 2,
+                cond: 2
+            },
+            Instruction::CvtSW { fd: 3, fs: 1 },
+            Instruction::CvtWS { fd: 3, fs: 1 },
+            Instruction::Break { code: 5 },
+            Instruction::Rdhwr { rt: 3, rd: 29 },
+            Instruction::Mfc0 { rt: 8, rd: 76 },
+            Instruction::Wrpgpr { rd: 29, rt: 8 },
+        ]
+    );
+}
+
+#[test]
+fn test_addu_and_move_round_trip() {
+    let source = "
+.text
+main:
+    addi $t1, $zero, -1
+    move $t0, $t1
+    addu $t2, $t0, $t1
+    addi $v0, $zero, 10
+    syscall
+";
+    let program = Assembler::new().assemble_program(source).unwrap();
+    let (move_word, addu_word) = (program.text[1], program.text[2]);
+    assert_eq!(move_word, 0x00094021);
+    assert_eq!(
+        decode_instruction(move_word),
+        Instruction::Addu {
+            rd: 8,
+            rs: 0,
+            rt: 9
+        }
+    );
+    assert_eq!(addu_word, r_type(8, 9, 10, 0, 0x21));
+
+    // The disassembly assembles back to the same word
+    let text = instruction_to_string(&decode_instruction(addu_word), addu_word);
+    assert_eq!(text, "addu $10, $8, $9");
+    let reassembled = Assembler::new()
+        .assemble_program(&format!(".text\n{}\n", text))
+        .unwrap();
+    assert_eq!(reassembled.text, vec![addu_word]);
+
+    // Both run, wrapping instead of trapping on overflow
+    let mut simulator = Simulator::from_assembly(source).unwrap();
+    simulator.run().unwrap();
+    assert_eq!(simulator.registers.read(8), u32::MAX);
+    assert_eq!(simulator.registers.read(10), u32::MAX - 1);
+}
+
+#[test]
+fn test_unsupported_encodings_are_invalid() {
+    let words = [
+        r_type(1, 2, 3, 0, 0x01),                // unused funct
+        i_type(0x01, 3, 0x10, 0),                // bltzal is not implemented
+        fp_type(0x11, 2, 1, 0, 0x00),            // add.d
+        fp_type(0x14, 0, 1, 3, 0x24),            // cvt.w.w
+        i_type(0x3F, 0, 0, 0),                   // unused opcode
+        (0x1F << 26) | r_type(2, 3, 4, 5, 0x00), // ext
+    ];
+    for word in words {
+        assert_eq!(
+            decode_instruction(word),
+            Instruction::InvalidInstruction,
+            "word 0x{:08X}",
+            word
+        );
+    }
+}
@@ -0,0 +1,97 @@
+// tests/instructions/encoder.rs
+// Round-trip tests between the instruction encoder and the decoder
+
+use proptest::prelude::*;
+use vmips_rust::assembler::Assembler;
+use vmips_rust::functional_simulator::instructions::{encode, Instruction};
+use vmips_rust::functional_simulator::simulator::decode_instruction;
+use vmips_rust::utils::examples::EXAMPLES;
+
+#[test]
+fn test_encode_reproduces_assembled_programs() {
+    for example in EXAMPLES {
+        let program = Assembler::new().assemble_program(example.source).unwrap();
+        for (index, &word) in program.text.iter().enumerate() {
+            assert_eq!(
+                encode(&decode_instruction(word)),
+                word,
+                "{} word {} is 0x{:08X}",
+                example.name,
+                index,
+                word
+            );
+        }
+    }
+}
+
+#[test]
+fn test_register_and_immediate_fields_round_trip() {
+    for register in 0..32 {
+        let other = 31 - register;
+        for &imm in &[i16::MIN, -1, 0, 1, i16::MAX] {
+            let instructions = [
+                Instruction::Addi {
+                    rt: register,
+                    rs: other,
+                    imm,
+                },
+                Instruction::Lw {
+                    rt: other,
+                    base: register,
+                    offset: imm,
+                },
+                Instruction::Bne {
+                    rs: register,
+                    rt: other,
+                    offset: imm,
+                },
+                Instruction::Tgei { rs: register, imm },
+                Instruction::Ori {
+                    rt: register,
+                    rs: other,
+                    imm: imm as u16,
+                },
+            ];
+            for instruction in instructions {
+                assert_eq!(decode_instruction(encode(&instruction)), instruction);
+            }
+        }
+        for instruction in [
+            Instruction::Sra {
+                rd: register,
+                rt: other,
+                shamt: register,
+            },
+            Instruction::Jalr {
+                rd: other,
+                rs: register,
+            },
+            Instruction::MulS {
+                fd: register,
+                fs: other,
+                ft: register,
+            },
+            Instruction::Mtc0 {
+                rt: register,
+                rd: other | (register % 8) << 5,
+            },
+            Instruction::Tltu {
+                rs: register,
+                rt: other,
+                code: register * 31,
+            },
+        ] {
+            assert_eq!(decode_instruction(encode(&instruction)), instruction);
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_decoded_words_encode_to_equivalent_words(word in any::<u32>()) {
+        // Fields the decoder ignores may differ, so compare what the words decode to
+        let instruction = decode_instruction(word);
+        prop_assume!(instruction != Instruction::InvalidInstruction);
+        prop_assert_eq!(decode_instruction(encode(&instruction)), instruction);
+    }
+}
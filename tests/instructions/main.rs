@@ -0,0 +1,10 @@
+// tests/instructions/main.rs
+// Tests for decoding, encoding and executing single instructions
+
+mod branch_targets;
+mod decoder;
+mod encoder;
+mod next_pc;
+mod registers;
+mod riscv;
+mod traps;
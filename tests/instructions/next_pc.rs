@@ -0,0 +1,135 @@
+// tests/instructions/next_pc.rs
+// Tests for the control flow result returned by Instruction::execute
+
+use vmips_rust::functional_simulator::instructions::{Instruction, NextPc};
+use vmips_rust::functional_simulator::memory::Memory;
+use vmips_rust::functional_simulator::registers::Registers;
+use vmips_rust::functional_simulator::simulator::{Exception, Simulator};
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
+use vmips_rust::utils::limits::TerminationReason;
+
+#[test]
+fn test_branches_and_jumps_report_their_targets() {
+    let mut registers = Registers::new();
+    let mut memory = Memory::new(4096);
+    registers.pc = 0x100;
+    registers.write(8, 7);
+    registers.write(31, 0x200);
+
+    let taken = Instruction::Beq {
+        rs: 8,
+        rt: 8,
+        offset: -2,
+    };
+    let next = taken.execute(&mut registers, &mut memory);
+    assert_eq!(next, NextPc::Relative(-8));
+    assert_eq!(next.target(0x100), Some(0xFC));
+
+    let not_taken = Instruction::Bne {
+        rs: 8,
+        rt: 8,
+        offset: 4,
+    };
+    assert_eq!(not_taken.execute(&mut registers, &mut memory), NextPc::Next);
+
+    let jump = Instruction::J { target: 0x40 };
+    assert_eq!(
+        jump.execute(&mut registers, &mut memory),
+        NextPc::Absolute(0x100)
+    );
+
+    let ret = Instruction::Jr { rs: 31 };
+    assert_eq!(
+        ret.execute(&mut registers, &mut memory),
+        NextPc::Absolute(0x200)
+    );
+}
+
+#[test]
+fn test_faults_and_exit_stop_execution() {
+    let mut registers = Registers::new();
+    let mut memory = Memory::new(1024);
+
+    let load = Instruction::Lw {
+        rt: 8,
+        base: 0,
+        offset: 0x800,
+    };
+    let next = load.execute(&mut registers, &mut memory);
+    assert_eq!(next, NextPc::Trap(Exception::MemoryAccessViolation));
+    assert_eq!(next.target(0), None);
+
+    registers.write(2, 10);
+    assert_eq!(
+        Instruction::Syscall.execute(&mut registers, &mut memory),
+        NextPc::Halt
+    );
+    assert_eq!(
+        Instruction::Nop.execute(&mut registers, &mut memory),
+        NextPc::Next
+    );
+}
+
+#[test]
+fn test_jal_and_jr_return_to_caller() {
+    let program: Vec<u8> = [
+        0x0C000004u32, // jal 0x10
+        0x20030001,    // addi $v1, $zero, 1
+        0x2002000A,    // addi $v0, $zero, 10
+        0x0000000C,    // syscall
+        0x20040005,    // addi $a0, $zero, 5
+        0x03E00008,    // jr $ra
+    ]
+    .iter()
+    .flat_map(|word| word.to_le_bytes())
+    .collect();
+
+    let mut simulator = Simulator::new(4096);
+    simulator.load_program(&program);
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.registers.read(4), 5);
+    assert_eq!(simulator.registers.read(3), 1);
+    assert_eq!(simulator.registers.read(31), 4);
+}
+
+#[test]
+fn test_timing_run_follows_every_branch_and_stops_at_exit() {
+    let image = ProgramImage::from_assembly(
+        "
+.text
+    li $t0, 3
+loop:
+    addi $t0, $t0, -1
+    addi $a0, $a0, 1
+    bgtz $t0, loop
+    li $v0, 1
+    syscall
+    li $v0, 10
+    syscall
+    li $a0, 99
+",
+    )
+    .unwrap();
+    let mut simulator = TimingSimulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(1024, 2, 32),
+        CacheConfig::new(2048, 4, 64),
+        0x0080_0000,
+    );
+    simulator.load_image(&image).unwrap();
+    simulator.enable_timeline();
+    simulator.captured_output = Some(Vec::new());
+    simulator.run();
+
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::Exited)
+    );
+    // li, three trips round the loop, the print and the li before the exit
+    assert_eq!(simulator.timeline.as_ref().unwrap().len(), 13);
+    assert_eq!(simulator.registers.read(4), 3);
+    assert_eq!(simulator.captured_output.as_deref(), Some(&b"3\n"[..]));
+}
@@ -0,0 +1,137 @@
+// tests/instructions/registers.rs
+// Tests for named register accessors, register diffs and $zero handling
+
+use vmips_rust::functional_simulator::instructions::Instruction;
+use vmips_rust::functional_simulator::registers::{Register, RegisterChange, Registers};
+use vmips_rust::functional_simulator::simulator::Simulator;
+
+#[test]
+fn test_named_accessors() {
+    let result = Simulator::run_asm(
+        "
+.text
+    addi $a0, $zero, 6
+    addi $a1, $zero, 7
+    mult $a0, $a1
+    mflo $v0
+    addi $s7, $zero, -1
+    jal done
+done:
+    addi $v0, $zero, 10
+    syscall
+",
+    )
+    .unwrap();
+    let registers = &result.registers;
+
+    assert_eq!(registers.a0(), 6);
+    assert_eq!(registers.a1(), 7);
+    assert_eq!(registers.get_lo(), 42);
+    assert_eq!(registers.get_hi(), 0);
+    assert_eq!(registers.s7(), 0xFFFF_FFFF);
+    assert_eq!(registers.v0(), 10);
+    assert_eq!(registers.sp(), 0x7FFF_FFFC);
+    assert_eq!(registers.ra(), 0x0040_0018);
+    assert_eq!(registers.ra(), registers.read(31));
+
+    let mut registers = Registers::new();
+    registers.set_fcsr(0x0080_0000);
+    assert_eq!(registers.get_fcsr(), 0x0080_0000);
+    assert_eq!(Register::Gpr(29).to_string(), "$sp");
+    assert_eq!(Register::Fpr(2).to_string(), "$f2");
+}
+
+#[test]
+fn test_diff_reports_changed_registers() {
+    let before = Registers::new();
+    let mut after = before.clone();
+    assert_eq!(before.diff(&after).count(), 0);
+
+    after.write(8, 5);
+    after.write(31, 0x0040_0010);
+    after.set_hi(1);
+    after.write_float(2, 1.5);
+    // Same value as before, so not a change
+    after.write(9, 0);
+
+    let changes: Vec<RegisterChange> = before.diff(&after).collect();
+    assert_eq!(
+        changes,
+        vec![
+            RegisterChange {
+                register: Register::Gpr(8),
+                old: 0,
+                new: 5
+            },
+            RegisterChange {
+                register: Register::Gpr(31),
+                old: 0,
+                new: 0x0040_0010
+            },
+            RegisterChange {
+                register: Register::Hi,
+                old: 0,
+                new: 1
+            },
+            RegisterChange {
+                register: Register::Fpr(2),
+                old: 0,
+                new: 1.5f32.to_bits()
+            },
+        ]
+    );
+
+    // Reversed, old and new swap
+    let reversed: Vec<RegisterChange> = after.diff(&before).collect();
+    assert_eq!(reversed[0].old, 5);
+    assert_eq!(reversed[0].new, 0);
+}
+
+#[test]
+fn test_zero_register_is_never_written() {
+    let result = Simulator::run_asm(
+        "
+.text
+    addi $zero, $zero, 5
+    lui $zero, 0x1234
+    add $t0, $zero, $zero
+    addi $v0, $zero, 10
+    syscall
+",
+    )
+    .unwrap();
+    assert_eq!(result.registers.read(0), 0);
+    assert_eq!(result.registers.t0(), 0);
+
+    // The register file drops the write even when set directly
+    let mut registers = Registers::new();
+    registers.write(0, 7);
+    assert_eq!(registers.read(0), 0);
+    assert_eq!(registers.data[0], 0);
+
+    // An instruction writing $zero has no destination, so the pipeline and
+    // the Tomasulo model never forward or rename its result
+    let add = Instruction::Add {
+        rd: 0,
+        rs: 8,
+        rt: 9,
+    };
+    assert_eq!(add.get_destination_register(), None);
+    let load = Instruction::Lw {
+        rt: 0,
+        base: 8,
+        offset: 0,
+    };
+    assert_eq!(load.get_destination_register(), None);
+    assert_eq!(
+        Instruction::Jal { target: 0 }.get_destination_register(),
+        Some(31)
+    );
+    // $f0 is an ordinary floating-point register
+    let fp_load = Instruction::LwC1 {
+        ft: 0,
+        base: 8,
+        offset: 0,
+    };
+    assert_eq!(fp_load.get_destination_register(), Some(0));
+}
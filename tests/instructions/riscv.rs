@@ -0,0 +1,171 @@
+// tests/instructions/riscv.rs
+// Tests for the instruction set layer and the experimental RV32I frontend
+
+use vmips_rust::functional_simulator::instructions::{Instruction, NextPc};
+use vmips_rust::functional_simulator::memory::Memory;
+use vmips_rust::functional_simulator::registers::Registers;
+use vmips_rust::functional_simulator::simulator::decode_instruction;
+use vmips_rust::isa::riscv::{decode, AluOp, RvInstruction};
+use vmips_rust::isa::IsaKind;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::{ExecutionMode, Simulator};
+use vmips_rust::utils::limits::TerminationReason;
+
+/// Sum 5 down to 1, store and reload the sum, jump over an instruction and
+/// exit
+const SUM_PROGRAM: [u32; 12] = [
+    0x00500293, // addi x5, x0, 5
+    0x00000313, // addi x6, x0, 0
+    0x000013B7, // lui x7, 0x1
+    0x00530333, // add x6, x6, x5
+    0xFFF28293, // addi x5, x5, -1
+    0xFE029CE3, // bne x5, x0, -8
+    0x0063A023, // sw x6, 0(x7)
+    0x0003AE03, // lw x28, 0(x7)
+    0x008000EF, // jal x1, 8
+    0x06300313, // addi x6, x0, 99
+    0x05D00893, // addi x17, x0, 93
+    0x00000073, // ecall
+];
+
+#[test]
+fn test_decode_and_disassemble() {
+    let rv32i = IsaKind::Rv32i.frontend();
+    let text: Vec<String> = SUM_PROGRAM
+        .iter()
+        .map(|&word| rv32i.disassemble(word))
+        .collect();
+    assert_eq!(
+        text,
+        [
+            "addi x5, x0, 5",
+            "addi x6, x0, 0",
+            "lui x7, 0x1",
+            "add x6, x6, x5",
+            "addi x5, x5, -1",
+            "bne x5, x0, -8",
+            "sw x6, 0(x7)",
+            "lw x28, 0(x7)",
+            "jal x1, 8",
+            "addi x6, x0, 99",
+            "addi x17, x0, 93",
+            "ecall",
+        ]
+    );
+    assert_eq!(rv32i.disassemble(0x4020D1B3), "sra x3, x1, x2");
+    assert_eq!(rv32i.disassemble(0x4030D193), "srai x3, x1, 3");
+    assert_eq!(rv32i.disassemble(0x0020C863), "blt x1, x2, 16");
+    assert_eq!(rv32i.disassemble(0xFFF0C203), "lbu x4, -1(x1)");
+    assert_eq!(decode(0xFFFF_FFFF), RvInstruction::Invalid);
+}
+
+#[test]
+fn test_semantics_differ_from_mips() {
+    let mut registers = Registers::new();
+    let mut memory = Memory::new(0x1000);
+    registers.write(1, 0xF0F0_F0F0);
+
+    // andi sign-extends its immediate, where MIPS andi zero-extends
+    let andi = RvInstruction::OpImm {
+        op: AluOp::And,
+        rd: 2,
+        rs1: 1,
+        imm: -16,
+    };
+    assert_eq!(andi.execute(&mut registers, &mut memory, 0), NextPc::Next);
+    assert_eq!(registers.read(2), 0xF0F0_F0F0);
+
+    // Branches and jumps count from the instruction itself
+    let branch = decode(0x0020C863); // blt x1, x2, 16
+    registers.write(2, 1);
+    assert_eq!(
+        branch.execute(&mut registers, &mut memory, 0x100),
+        NextPc::Absolute(0x110)
+    );
+    assert_eq!(
+        decode(SUM_PROGRAM[8]).execute(&mut registers, &mut memory, 0x20),
+        NextPc::Absolute(0x28)
+    );
+    assert_eq!(registers.read(1), 0x24);
+    assert_eq!(
+        decode(0x00100073).execute(&mut registers, &mut memory, 0),
+        NextPc::Trap(vmips_rust::functional_simulator::simulator::Exception::BreakPoint)
+    );
+}
+
+#[test]
+fn test_timing_instructions_keep_operands() {
+    let rv32i = IsaKind::Rv32i.frontend();
+    assert_eq!(
+        rv32i.timing_instruction(SUM_PROGRAM[3]),
+        Instruction::Add {
+            rd: 6,
+            rs: 6,
+            rt: 5
+        }
+    );
+    assert_eq!(
+        rv32i.timing_instruction(SUM_PROGRAM[7]),
+        Instruction::Lw {
+            rt: 28,
+            base: 7,
+            offset: 0
+        }
+    );
+    // The stand-in branch reaches the same target
+    assert_eq!(
+        rv32i.timing_instruction(SUM_PROGRAM[5]),
+        Instruction::Beq {
+            rs: 5,
+            rt: 0,
+            offset: -3
+        }
+    );
+
+    // MIPS instructions are timed as themselves
+    let mips = IsaKind::Mips.frontend();
+    assert_eq!(
+        mips.timing_instruction(0x8C091000),
+        decode_instruction(0x8C091000)
+    );
+    assert_eq!(IsaKind::from_name("rv32i"), Some(IsaKind::Rv32i));
+    assert_eq!(IsaKind::default().name(), "mips");
+    assert_eq!(IsaKind::from_name("arm"), None);
+}
+
+#[test]
+fn test_rv32i_program_runs_in_the_pipeline() {
+    let mut simulator = Simulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    simulator.visualization = None;
+    simulator.isa = IsaKind::Rv32i;
+    for (i, &word) in SUM_PROGRAM.iter().enumerate() {
+        simulator.memory.write_word_init(i * 4, word);
+    }
+    simulator.run();
+
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::Exited)
+    );
+    assert_eq!(simulator.registers.read(6), 15);
+    assert_eq!(simulator.registers.read(28), 15);
+    assert_eq!(simulator.registers.read(1), 0x24);
+    assert_eq!(simulator.memory.read_word(0x1000).unwrap(), 15);
+
+    // 3 + 5 * 3 + 5 instructions, with the caches and predictor involved
+    assert_eq!(simulator.run_totals.instructions, 23);
+    assert!(simulator.run_totals.cycles > 23);
+    match &simulator.execution_mode {
+        ExecutionMode::InOrder(pipeline) => {
+            let dcache = &pipeline.cache_hierarchy.l1_data_cache.stats;
+            assert_eq!(dcache.accesses, 2);
+            assert!(pipeline.cache_hierarchy.l1_instr_cache.stats.accesses >= 23);
+        },
+        ExecutionMode::OutOfOrder(_) => unreachable!(),
+    }
+}
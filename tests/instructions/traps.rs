@@ -0,0 +1,109 @@
+// tests/instructions/traps.rs
+// Tests for the conditional trap instructions
+
+use vmips_rust::assembler::Assembler;
+use vmips_rust::errors::SimulationErrorCause;
+use vmips_rust::functional_simulator::instructions::Instruction;
+use vmips_rust::functional_simulator::simulator::{decode_instruction, Exception, Simulator};
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::utils::limits::TerminationReason;
+
+fn load_source(source: &str) -> Simulator {
+    let image = ProgramImage::from_assembly(source).unwrap();
+    let mut simulator = Simulator::new(image.memory_required());
+    simulator.load_image(&image).unwrap();
+    simulator
+}
+
+#[test]
+fn test_assembled_traps_decode() {
+    let source = "
+.text
+    teq $t0, $t1, 7
+    tne $t0, $zero
+    tltu $a0, $a1, 1023
+    tgei $s0, -4
+    tnei $s0, 100
+";
+    let bytes = Assembler::new().assemble_string(source).unwrap();
+    let decoded: Vec<Instruction> = bytes[8..]
+        .chunks(4)
+        .map(|word| decode_instruction(u32::from_le_bytes([word[0], word[1], word[2], word[3]])))
+        .collect();
+
+    assert_eq!(
+        decoded,
+        vec![
+            Instruction::Teq {
+                rs: 8,
+                rt: 9,
+                code: 7
+            },
+            Instruction::Tne {
+                rs: 8,
+                rt: 0,
+                code: 0
+            },
+            Instruction::Tltu {
+                rs: 4,
+                rt: 5,
+                code: 1023
+            },
+            Instruction::Tgei { rs: 16, imm: -4 },
+            Instruction::Tnei { rs: 16, imm: 100 },
+        ]
+    );
+    assert!(Assembler::new()
+        .assemble_string(".text\n    teq $t0, $t1, 1024\n")
+        .is_err());
+}
+
+#[test]
+fn test_divide_by_zero_check_traps() {
+    // The sequence compilers emit around div to catch a zero divisor
+    let mut simulator = load_source(
+        "
+.text
+    addi $t0, $zero, 12
+    div $t0, $zero
+    teq $zero, $zero, 7
+    mflo $t2
+    addi $v0, $zero, 10
+    syscall
+",
+    );
+
+    let error = simulator.run().unwrap_err();
+    assert_eq!(error.cause, SimulationErrorCause::Trap(7));
+    assert_eq!(error.pc, 0x00400008);
+    assert_eq!(simulator.exception, Some(Exception::Trap));
+    assert_eq!(simulator.termination_reason, Some(TerminationReason::Trap));
+    assert_eq!(simulator.registers.read(10), 0);
+    assert!(error.to_string().contains("trap (code 7)"));
+}
+
+#[test]
+fn test_untaken_traps_continue() {
+    let mut simulator = load_source(
+        "
+.text
+    addi $t0, $zero, -1
+    addi $t1, $zero, 3
+    teq $t0, $t1
+    tge $t0, $t1
+    tltu $t0, $t1
+    tlti $t1, 3
+    tgeiu $t1, -1
+    tnei $t0, -1
+    addi $t2, $zero, 1
+    tltiu $t1, -1
+    addi $t2, $zero, 2
+",
+    );
+
+    // tltiu compares against 0xFFFFFFFF, so it traps after $t2 is set to 1
+    let error = simulator.run().unwrap_err();
+    assert_eq!(error.cause, SimulationErrorCause::Trap(0));
+    assert_eq!(error.pc, 0x00400024);
+    assert_eq!(simulator.registers.read(10), 1);
+}
@@ -0,0 +1,145 @@
+// tests/interactive/compare.rs
+// Tests for the side-by-side comparison of two timing configurations
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::compare::{PipelineComparison, TimingVariant};
+use vmips_rust::timing_simulator::simulator::Simulator;
+
+/// A load followed by two dependent adds
+const DEPENDENT_PROGRAM: [u32; 4] = [
+    0x8C091000, // lw $t1, 0x1000($zero)
+    0x01294020, // add $t0, $t1, $t1
+    0x01084020, // add $t0, $t0, $t0
+    0x00000000, // nop
+];
+
+/// A countdown loop whose backward branch is taken four times, then falls through
+const LOOP_PROGRAM: [u32; 4] = [
+    0x24080005, // addiu $t0, $zero, 5
+    0x2508FFFF, // addiu $t0, $t0, -1
+    0x1500FFFE, // bne $t0, $zero, -2
+    0x00000000, // nop
+];
+
+fn load(program: &'static [u32]) -> impl FnMut(&mut Simulator) {
+    move |simulator| {
+        for (i, &word) in program.iter().enumerate() {
+            simulator.memory.write_word_init(i * 4, word);
+        }
+        simulator.memory.write_word_init(0x1000, 21);
+    }
+}
+
+#[test]
+fn test_variant_spec_parsing() {
+    let variant = TimingVariant::parse("forwarding=off, prediction=off, dcache=1024/2/32").unwrap();
+    assert!(!variant.pipeline_config.forwarding_enabled);
+    assert!(!variant.pipeline_config.branch_prediction_enabled);
+    assert_eq!(variant.data_cache_config.size, 1024);
+    assert_eq!(variant.data_cache_config.associativity, 2);
+
+    assert!(
+        TimingVariant::parse("")
+            .unwrap()
+            .pipeline_config
+            .forwarding_enabled
+    );
+    assert!(TimingVariant::parse("forwarding").is_err());
+    assert!(TimingVariant::parse("dcache=1000/3/64").is_err());
+    assert!(TimingVariant::parse("turbo=on").is_err());
+}
+
+#[test]
+fn test_forwarding_difference_is_located() {
+    let baseline = TimingVariant::parse("forwarding=on").unwrap();
+    let variant = TimingVariant::parse("forwarding=off").unwrap();
+    let comparison = PipelineComparison::run(&baseline, &variant, 32768, load(&DEPENDENT_PROGRAM));
+
+    assert_eq!(comparison.baseline.len(), 3);
+    assert_eq!(comparison.variant.len(), 3);
+    // The first add waits for the load in both runs, but longer without forwarding
+    assert_eq!(comparison.first_divergence(), Some(1));
+    assert_eq!(comparison.differences(), vec![1, 2]);
+    assert!(comparison.variant_cycles > comparison.baseline_cycles);
+
+    // The same configuration on both sides produces identical timelines
+    let same = PipelineComparison::run(&baseline, &baseline, 32768, load(&DEPENDENT_PROGRAM));
+    assert_eq!(same.first_divergence(), None);
+    assert!(same.render(false).contains("Timelines are identical"));
+}
+
+#[test]
+fn test_predictor_types_change_mispredictions() {
+    let mispredictions = |spec: &str| {
+        let variant = TimingVariant::parse(spec).unwrap();
+        let comparison = PipelineComparison::run(&variant, &variant, 32768, load(&LOOP_PROGRAM));
+        comparison
+            .baseline
+            .iter()
+            .filter(|timing| timing.mispredicted)
+            .count()
+    };
+    // Static prediction misses every taken branch, the others only the first
+    // and the exit
+    assert_eq!(mispredictions("predictor=static"), 4);
+    assert_eq!(mispredictions("predictor=onebit"), 2);
+    assert_eq!(mispredictions("predictor=twobit"), 2);
+
+    let baseline = TimingVariant::parse("predictor=twobit").unwrap();
+    let variant = TimingVariant::parse("predictor=static").unwrap();
+    let comparison = PipelineComparison::run(&baseline, &variant, 32768, load(&LOOP_PROGRAM));
+    assert!(comparison.first_divergence().is_some());
+    assert!(comparison.variant_cycles > comparison.baseline_cycles);
+}
+
+#[test]
+fn test_compare_cli_reports_mispredictions() {
+    let dir = tempdir().unwrap();
+    let program_path = dir.path().join("loop.bin");
+    let bytes: Vec<u8> = LOOP_PROGRAM.iter().flat_map(|w| w.to_le_bytes()).collect();
+    std::fs::write(&program_path, bytes).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("compare")
+        .arg("--input")
+        .arg(&program_path)
+        .arg("--variant")
+        .arg("prediction=off");
+    cmd.assert()
+        .success()
+        .stdout(contains("B: prediction=off"))
+        .stdout(contains("Mispredictions: A 2, B 4"));
+}
+
+#[test]
+fn test_each_run_stops_at_the_exit_syscall() {
+    // The exit is followed by a loop that would run to the cycle limit
+    let image = ProgramImage::from_assembly(
+        "
+.text
+    li $t0, 3
+    add $t1, $t0, $t0
+    li $v0, 1
+    move $a0, $t1
+    syscall
+    li $v0, 10
+    syscall
+spin:
+    j spin
+",
+    )
+    .unwrap();
+    let baseline = TimingVariant::default();
+    let variant = TimingVariant::parse("forwarding=off").unwrap();
+    let comparison = PipelineComparison::run(&baseline, &variant, 0x0080_0000, |simulator| {
+        simulator.load_image(&image).unwrap();
+    });
+
+    assert_eq!(comparison.baseline.len(), 6);
+    assert_eq!(comparison.variant.len(), 6);
+    assert!(comparison.baseline_cycles < 100);
+    assert!(comparison.variant_cycles > comparison.baseline_cycles);
+}
@@ -0,0 +1,125 @@
+// tests/interactive/data_breakpoints.rs
+// Tests for data breakpoints with value conditions in the what-if session
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use vmips_rust::assembler::ProgramBuilder;
+use vmips_rust::functional_simulator::registers::{S0, T0, V0, ZERO};
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::data_breakpoint::{BreakCondition, DataBreakpoint};
+use vmips_rust::timing_simulator::last_writer::Location;
+use vmips_rust::timing_simulator::what_if::WhatIfSession;
+
+/// Store a countdown from 3 to a word, one sw per pass through the loop
+fn session() -> WhatIfSession {
+    let image = ProgramBuilder::new()
+        .label("__start")
+        .li(T0, 3)
+        .la(S0, "buffer")
+        .label("loop")
+        .sw(T0, 0, S0)
+        .addi(T0, T0, -1)
+        .bne(T0, ZERO, "loop")
+        .li(V0, 10)
+        .syscall()
+        .data_label("buffer")
+        .words(&[0])
+        .image()
+        .unwrap();
+    WhatIfSession::new(TimingVariant::default(), 1 << 29, |simulator| {
+        simulator.load_image(&image).unwrap()
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_breakpoints_parse_and_conditions() {
+    assert_eq!(
+        DataBreakpoint::parse("mem[0x10000000] == 0x10"),
+        Ok(DataBreakpoint {
+            location: Location::Word(0x1000_0000),
+            condition: BreakCondition::Equals(16),
+        })
+    );
+    let crossing = DataBreakpoint::parse("$t0 crosses -2").unwrap();
+    assert_eq!(crossing.condition, BreakCondition::Crosses(-2));
+    assert_eq!(crossing.to_string(), "$t0 crosses -2");
+    assert_eq!(
+        DataBreakpoint::parse("$s0").unwrap().to_string(),
+        "$s0 written"
+    );
+    for text in [
+        "",
+        "$t0 ==",
+        "$t0 < 3",
+        "$t0 == zz",
+        "$t0 == 0x1FFFFFFFF",
+        "$t99",
+    ] {
+        assert!(DataBreakpoint::parse(text).is_err(), "{}", text);
+    }
+
+    assert!(BreakCondition::Equals(5).is_met(5, 5));
+    assert!(!BreakCondition::ChangesTo(5).is_met(5, 5));
+    assert!(BreakCondition::ChangesTo(5).is_met(4, 5));
+    // Reaching the threshold counts, in either direction
+    assert!(BreakCondition::Crosses(0).is_met(1, 0));
+    assert!(BreakCondition::Crosses(0).is_met(-1i32 as u32, 0));
+    assert!(BreakCondition::Crosses(0).is_met(1, -3i32 as u32));
+    assert!(!BreakCondition::Crosses(0).is_met(0, -1i32 as u32));
+    assert!(!BreakCondition::Crosses(0).is_met(2, 1));
+}
+
+#[test]
+fn test_session_stops_on_the_matching_write() {
+    let mut session = session();
+    session
+        .breakpoints
+        .add(DataBreakpoint::parse("mem[0x10000000] == 1").unwrap())
+        .unwrap();
+    session
+        .breakpoints
+        .add(DataBreakpoint::parse("$t0 changes-to 2").unwrap())
+        .unwrap();
+    assert!(session
+        .breakpoints
+        .add(DataBreakpoint::parse("$t0 changes-to 2").unwrap())
+        .is_err());
+
+    let (executed, hit) = session.step_until_break(100);
+    let hit = hit.unwrap();
+    assert_eq!(executed, 5);
+    assert_eq!((hit.number, hit.old, hit.new), (2, 3, 2));
+    assert_eq!((hit.pc, hit.index), (0x0040_0010, 4));
+
+    // The first two stores do not leave 1 in memory
+    let (executed, hit) = session.step_until_break(100);
+    let hit = hit.unwrap();
+    assert_eq!(executed, 5);
+    assert_eq!((hit.number, hit.old, hit.new), (1, 2, 1));
+    assert_eq!((hit.pc, hit.index), (0x0040_000C, 9));
+
+    session.breakpoints.remove(1);
+    let (_, hit) = session.step_until_break(100);
+    assert!(hit.is_none());
+    assert!(session.is_finished());
+}
+
+#[test]
+fn test_break_commands_in_what_if() {
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("what-if").write_stdin(
+        "break $v0 crosses 25\nbreak $v0 < 3\nbreaks\ncontinue\ncontinue\ndelete 1\nbreaks\nquit\n",
+    );
+    cmd.assert()
+        .success()
+        .stdout(contains("Breakpoint 1: $v0 crosses 25"))
+        .stdout(contains("Invalid breakpoint: unknown condition '<'"))
+        .stdout(contains(
+            "Breakpoint 1 ($v0 crosses 25): 0x0000000A -> 0x0000001E by the instruction at 0x00000008 (instruction 2)",
+        ))
+        .stdout(contains("Executed 3 instructions"))
+        .stdout(contains("0x0000001E -> 0x0000000A"))
+        .stdout(contains("Removed breakpoint 1: $v0 crosses 25"))
+        .stdout(contains("No breakpoints"));
+}
@@ -0,0 +1,116 @@
+// tests/interactive/last_writer.rs
+// Tests for the last-writer table behind the who-wrote command
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use vmips_rust::assembler::ProgramBuilder;
+use vmips_rust::functional_simulator::registers::{S0, T0, V0, ZERO};
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::last_writer::{LastWriters, Location};
+use vmips_rust::timing_simulator::what_if::WhatIfSession;
+
+/// Store a countdown from 3 to a word; the sw is at 0x0040000C
+fn session() -> WhatIfSession {
+    let image = ProgramBuilder::new()
+        .label("__start")
+        .li(T0, 3)
+        .la(S0, "buffer")
+        .label("loop")
+        .sw(T0, 0, S0)
+        .addi(T0, T0, -1)
+        .bne(T0, ZERO, "loop")
+        .li(V0, 10)
+        .syscall()
+        .data_label("buffer")
+        .words(&[0])
+        .image()
+        .unwrap();
+    WhatIfSession::new(TimingVariant::default(), 1 << 29, |simulator| {
+        simulator.load_image(&image).unwrap();
+        simulator.enable_last_writers();
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_locations_parse_and_record() {
+    assert_eq!(Location::parse("$t3"), Ok(Location::Register(11)));
+    assert_eq!(Location::parse("$11"), Ok(Location::Register(11)));
+    assert_eq!(
+        Location::parse("0x10010006"),
+        Ok(Location::Word(0x1001_0004))
+    );
+    assert_eq!(Location::parse("mem[64]"), Ok(Location::Word(64)));
+    assert_eq!(Location::Word(64).to_string(), "mem[0x00000040]");
+    for text in ["$t10", "hi", "0xzz", ""] {
+        assert!(Location::parse(text).is_err(), "{}", text);
+    }
+
+    let mut writers = LastWriters::new();
+    writers.instruction_base = 10;
+    writers.cycle_base = 100;
+    writers.record(Location::Register(0), 0x40, 0, 1, 2, 5);
+    writers.record(Location::Word(0x102), 0x44, 0, 2, 3, 7);
+    assert_eq!(writers.lookup(Location::Register(0)), None);
+    let writer = writers.lookup(Location::Word(0x100)).unwrap();
+    assert_eq!(
+        (writer.pc, writer.index, writer.cycle, writer.value),
+        (0x44, 12, 103, 7)
+    );
+    assert_eq!(writers.words_written(), 1);
+}
+
+#[test]
+fn test_session_tracks_writers_across_steps_and_replays() {
+    let mut session = session();
+    session.step(4);
+    session.take_snapshot();
+    for _ in 0..5 {
+        session.step(1);
+    }
+
+    let writers = session.simulator.last_writers.as_ref().unwrap();
+    // The second pass through the loop stored 2 and counted down to 1
+    let store = *writers.lookup(Location::Word(0x1000_0000)).unwrap();
+    assert_eq!((store.pc, store.index, store.value), (0x0040_000C, 6, 2));
+    assert_eq!(store.cycle, session.history[6].issue_cycle);
+    let count = *writers.lookup(Location::Register(T0)).unwrap();
+    assert_eq!((count.pc, count.index, count.value), (0x0040_0010, 7, 1));
+    assert_eq!(count.cycle, session.history[7].issue_cycle);
+    let base = writers.lookup(Location::Register(S0)).unwrap();
+    assert_eq!(base.index, 2);
+    assert!(writers.lookup(Location::Register(V0)).is_none());
+
+    // A replay from the snapshot rebuilds the same table
+    session.configure("forwarding=off").unwrap();
+    session.rerun();
+    let replayed = session.simulator.last_writers.as_ref().unwrap();
+    let store_again = replayed.lookup(Location::Word(0x1000_0000)).unwrap();
+    assert_eq!((store_again.index, store_again.value), (6, 2));
+    assert_eq!(store_again.cycle, session.history[6].issue_cycle);
+    assert_eq!(replayed.lookup(Location::Register(T0)).unwrap().index, 7);
+}
+
+#[test]
+fn test_who_wrote_in_what_if() {
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("what-if").arg("--track-writes").write_stdin(
+        "step 5\nwho-wrote $v0\nwho-wrote 0x408\nwho-wrote $t0\nwho-wrote $zz\nquit\n",
+    );
+    cmd.assert()
+        .success()
+        .stdout(contains(
+            "$v0 was last written by 0x00000010 lw $2, 1024($0) (instruction 4, cycle 25)",
+        ))
+        .stdout(contains(
+            "mem[0x00000408] was last written by 0x0000000C sw $2",
+        ))
+        .stdout(contains("$t0 has not been written in this session"))
+        .stdout(contains("Invalid location: unknown register '$zz'"));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("what-if").write_stdin("who-wrote $v0\nquit\n");
+    cmd.assert()
+        .success()
+        .stdout(contains("Write tracking is off"));
+}
@@ -0,0 +1,8 @@
+// tests/interactive/main.rs
+// Tests for what-if sessions, breakpoints, watches and configuration comparisons
+
+mod compare;
+mod data_breakpoints;
+mod last_writer;
+mod watch_expressions;
+mod what_if;
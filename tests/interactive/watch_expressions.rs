@@ -0,0 +1,124 @@
+// tests/interactive/watch_expressions.rs
+// Tests for watch expressions re-evaluated while stepping
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use vmips_rust::assembler::ProgramBuilder;
+use vmips_rust::functional_simulator::registers::{S0, T0, V0, ZERO};
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::watch::{render_watches, WatchExpression, WatchList};
+use vmips_rust::timing_simulator::what_if::WhatIfSession;
+
+/// Store a countdown from 3 to a word, looping on a bne at 0x00400014
+fn session() -> WhatIfSession {
+    let image = ProgramBuilder::new()
+        .label("__start")
+        .li(T0, 3)
+        .la(S0, "buffer")
+        .label("loop")
+        .sw(T0, 0, S0)
+        .addi(T0, T0, -1)
+        .bne(T0, ZERO, "loop")
+        .li(V0, 10)
+        .syscall()
+        .data_label("buffer")
+        .words(&[0])
+        .image()
+        .unwrap();
+    WhatIfSession::new(TimingVariant::default(), 1 << 29, |simulator| {
+        simulator.load_image(&image).unwrap()
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_expressions_parse_and_print() {
+    for (text, expression, shown) in [
+        ("$t0", WatchExpression::Register(8), "$t0"),
+        ("$31", WatchExpression::Register(31), "$ra"),
+        ("PC", WatchExpression::Pc, "pc"),
+        ("lo", WatchExpression::Lo, "lo"),
+        (
+            "mem[0x10000000]",
+            WatchExpression::Word(0x1000_0000),
+            "mem[0x10000000]",
+        ),
+        ("[64]", WatchExpression::Word(64), "mem[0x00000040]"),
+        (
+            "predictor[0x00400014]",
+            WatchExpression::Predictor(0x0040_0014),
+            "predictor[0x00400014]",
+        ),
+        ("history", WatchExpression::History, "history"),
+    ] {
+        assert_eq!(WatchExpression::parse(text), Ok(expression));
+        assert_eq!(expression.to_string(), shown);
+    }
+    for text in ["$t10", "$32", "mem[0x10000002]", "mem[zz]", "t0", ""] {
+        assert!(WatchExpression::parse(text).is_err(), "{}", text);
+    }
+
+    let mut watches = WatchList::new();
+    assert_eq!(watches.add(WatchExpression::Pc), Ok(1));
+    assert!(watches.add(WatchExpression::Pc).is_err());
+    assert_eq!(watches.add(WatchExpression::Hi), Ok(2));
+    assert_eq!(watches.remove(1), Some(WatchExpression::Pc));
+    assert_eq!(watches.remove(2), None);
+    assert_eq!(watches.expressions(), vec![WatchExpression::Hi]);
+}
+
+#[test]
+fn test_changes_are_marked_after_each_instruction() {
+    let mut session = session();
+    for text in ["$t0", "mem[0x10000000]", "predictor[0x00400014]", "history"] {
+        session
+            .watches
+            .add(WatchExpression::parse(text).unwrap())
+            .unwrap();
+    }
+    let steps = session.step_watched(9);
+    assert_eq!(steps.len(), 9);
+
+    let changed: Vec<Vec<usize>> = steps
+        .iter()
+        .map(|(_, values)| {
+            (0..values.len())
+                .filter(|&index| values[index].changed)
+                .collect()
+        })
+        .collect();
+    // A new watch has nothing to compare with
+    assert_eq!(changed[0], Vec::<usize>::new());
+    // sw, addi, then the first bne trains its counter and the history
+    assert_eq!(changed[3], vec![1]);
+    assert_eq!(changed[4], vec![0]);
+    assert_eq!(changed[5], vec![2, 3]);
+    assert_eq!(changed[1..3], [Vec::<usize>::new(), Vec::new()]);
+
+    let (timing, values) = &steps[8];
+    assert_eq!(timing.pc, 0x0040_0014);
+    assert_eq!(values[0].value, "0x00000001");
+    assert_eq!(values[1].value, "0x00000002");
+    assert_eq!(values[2].value, "strongly taken");
+    assert_eq!(values[3].value, "0b0011");
+    assert_eq!(
+        render_watches(&values[2..], false),
+        "*predictor[0x00400014]=strongly taken  *history=0b0011"
+    );
+    assert!(render_watches(&values[2..3], true).starts_with("\x1b[7m*predictor"));
+}
+
+#[test]
+fn test_watch_commands_in_what_if() {
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("what-if")
+        .env("NO_COLOR", "1")
+        .write_stdin("watch $t0\nwatch mem[0x400]\nwatch $t99\nstep 2\nwatches\nunwatch 2\nquit\n");
+    cmd.assert()
+        .success()
+        .stdout(contains("Watch 1: $t0 = 0x00000000"))
+        .stdout(contains("Watch 2: mem[0x00000400] = 0x"))
+        .stdout(contains("Invalid watch: unknown register '$t99'"))
+        .stdout(contains("  1:  $t0="))
+        .stdout(contains("Removed watch 2: mem[0x00000400]"));
+}
@@ -0,0 +1,130 @@
+// tests/interactive/what_if.rs
+// Tests for interactive what-if stepping with configuration changes
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::simulator::Simulator;
+use vmips_rust::timing_simulator::stats::InstructionTiming;
+use vmips_rust::timing_simulator::what_if::WhatIfSession;
+
+/// Loads, a dependent add and a store, then a NOP to stop
+const PROGRAM: [u32; 7] = [
+    0x8C091000, // lw $t1, 0x1000($zero)
+    0x8C0A1004, // lw $t2, 0x1004($zero)
+    0x012A4020, // add $t0, $t1, $t2
+    0xAC081008, // sw $t0, 0x1008($zero)
+    0x01084020, // add $t0, $t0, $t0
+    0xAC08100C, // sw $t0, 0x100C($zero)
+    0x00000000, // nop
+];
+
+/// A countdown loop whose backward branch is taken four times
+const LOOP_PROGRAM: [u32; 4] = [
+    0x24080005, // addiu $t0, $zero, 5
+    0x2508FFFF, // addiu $t0, $t0, -1
+    0x1500FFFE, // bne $t0, $zero, -2
+    0x00000000, // nop
+];
+
+fn session(spec: &str) -> WhatIfSession {
+    session_with(&PROGRAM, spec)
+}
+
+fn session_with(program: &'static [u32], spec: &str) -> WhatIfSession {
+    WhatIfSession::new(
+        TimingVariant::parse(spec).unwrap(),
+        32768,
+        |simulator: &mut Simulator| {
+            for (i, &word) in program.iter().enumerate() {
+                simulator.memory.write_word_init(i * 4, word);
+            }
+            simulator.memory.write_word_init(0x1000, 2);
+            simulator.memory.write_word_init(0x1004, 3);
+        },
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_rerun_with_forwarding_disabled() {
+    let mut session = session("forwarding=on");
+    assert_eq!(session.step(2).len(), 2);
+    session.take_snapshot();
+    session.step(10);
+    assert_eq!(session.instructions(), 6);
+    assert!(session.is_finished());
+
+    session.configure("forwarding=off").unwrap();
+    let comparison = session.rerun();
+
+    // Only the instructions after the snapshot are replayed
+    assert_eq!(comparison.baseline.len(), 4);
+    assert_eq!(comparison.variant.len(), 4);
+    assert_eq!(comparison.first_divergence(), Some(0));
+    assert!(comparison.variant_cycles > comparison.baseline_cycles);
+    assert_eq!(session.instructions(), 6);
+    assert_eq!(session.simulator.memory.read_word(0x100C), Ok(10));
+}
+
+#[test]
+fn test_rerun_with_another_predictor() {
+    let mut session = session_with(&LOOP_PROGRAM, "predictor=twobit");
+    session.take_snapshot();
+    session.step(20);
+    assert!(session.is_finished());
+
+    session.configure("predictor=static").unwrap();
+    let comparison = session.rerun();
+    let mispredictions = |timeline: &[InstructionTiming]| {
+        timeline.iter().filter(|timing| timing.mispredicted).count()
+    };
+    assert_eq!(mispredictions(&comparison.baseline), 2);
+    assert_eq!(mispredictions(&comparison.variant), 4);
+    assert!(comparison.variant_cycles > comparison.baseline_cycles);
+}
+
+#[test]
+fn test_rerun_with_another_control_hazard_scheme() {
+    let mut session = session_with(&LOOP_PROGRAM, "control=flush");
+    session.take_snapshot();
+    session.step(20);
+    assert!(session.is_finished());
+
+    // Stalling behind every branch costs cycles even when it is predicted
+    session.configure("control=stall").unwrap();
+    let comparison = session.rerun();
+    assert!(comparison.variant_cycles > comparison.baseline_cycles);
+}
+
+#[test]
+fn test_rerun_restores_snapshot_state() {
+    let mut session = session("");
+    session.step(3);
+    session.take_snapshot();
+    session.step(2);
+
+    // Replaying with unchanged settings reuses the warm caches and
+    // predictor from the snapshot, so the timing is identical
+    let comparison = session.rerun();
+    assert_eq!(comparison.baseline.len(), 2);
+    assert_eq!(comparison.first_divergence(), None);
+
+    // Bad settings are rejected without changing anything
+    assert!(session.configure("forwarding=maybe").is_err());
+    assert!(session.variant.pipeline_config.forwarding_enabled);
+}
+
+#[test]
+fn test_what_if_cli_session() {
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("what-if")
+        .write_stdin("step 2\nsnapshot\nstep 3\nset caches=off\nrerun\nquit\n");
+    cmd.assert()
+        .success()
+        .stdout(contains("Snapshot taken at instruction 2"))
+        .stdout(contains("Replaying 3 instructions"))
+        .stdout(contains(
+            "B: forwarding=on,prediction=on,predictor=twobit,caches=off",
+        ));
+}
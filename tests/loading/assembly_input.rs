@@ -0,0 +1,67 @@
+// tests/loading/assembly_input.rs
+// Tests for loading assembly source directly
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::loader::{LoadError, ProgramFormat, ProgramImage, TEXT_BASE};
+
+const SUM_SOURCE: &str = "
+.text
+main:
+    addi $v0, $zero, 5
+    addi $v1, $zero, 7
+    add $a0, $v0, $v1
+    addi $v0, $zero, 10
+    syscall
+";
+
+#[test]
+fn test_assembly_source_detected_by_content() {
+    let image = ProgramImage::from_bytes(SUM_SOURCE.as_bytes()).unwrap();
+    assert_eq!(image.format, ProgramFormat::Source);
+    assert_eq!(image.entry_point, TEXT_BASE);
+    assert_eq!(image.memory_required(), TEXT_BASE as usize + 5 * 4);
+
+    let mut simulator = Simulator::new(image.memory_required());
+    simulator.load_image(&image).unwrap();
+    simulator.run().unwrap();
+    assert_eq!(simulator.registers.read(4), 12);
+
+    // Machine code is not mistaken for text
+    let raw = ProgramImage::from_bytes(&0x00432020u32.to_le_bytes()).unwrap();
+    assert_eq!(raw.format, ProgramFormat::Raw);
+}
+
+#[test]
+fn test_assembly_source_errors_are_reported() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("broken.asm");
+    std::fs::write(&path, ".text\n    frobnicate $t0\n").unwrap();
+
+    match ProgramImage::from_file(&path) {
+        Err(LoadError::Assembly(_)) => {},
+        other => panic!(
+            "expected an assembly error, got {:?}",
+            other.map(|i| i.format)
+        ),
+    }
+}
+
+#[test]
+fn test_cli_runs_assembly_file() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("sum.s");
+    std::fs::write(&path, SUM_SOURCE).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(contains("Loading assembly source"))
+        .stdout(contains("$4: 12"));
+}
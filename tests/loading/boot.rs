@@ -0,0 +1,148 @@
+// tests/loading/boot.rs
+// Tests for booting at the reset vector and the kseg0/kseg1 aliases
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::cp0::{
+    AsyncEvent, CP0_STATUS, RESET_STATUS, RESET_VECTOR, STATUS_BEV,
+};
+use vmips_rust::functional_simulator::memory::{AddressSegment, Memory};
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::loader::{ProgramFormat, ProgramImage};
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
+
+const MEMORY_SIZE: usize = 0x0010_0000;
+
+/// Firmware that leaves error level, takes a timer interrupt at the
+/// bootstrap vector and stores through kseg1 before loading through kseg0
+const FIRMWARE: &str = "
+.text 0xBFC00000
+    mfc0 $s0, $12
+    li $t0, 0x00408001
+    mtc0 $t0, $12
+    li $t1, 30
+wait:
+    addi $t1, $t1, -1
+    bne $t1, $zero, wait
+    lui $t2, 0xA000
+    li $t3, 1234
+    sw $t3, 0x1000($t2)
+    lui $t4, 0x8000
+    lw $s2, 0x1000($t4)
+    li $v0, 10
+    syscall
+.ktext 0xBFC00380
+    addi $s1, $s1, 1
+    mtc0 $zero, $11
+    eret
+";
+
+/// Raw firmware: addi $a0, $zero, 7; addi $v0, $zero, 10; syscall
+const RAW_FIRMWARE: [u32; 3] = [0x2004_0007, 0x2002_000A, 0x0000_000C];
+
+fn raw_bytes() -> Vec<u8> {
+    RAW_FIRMWARE
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect()
+}
+
+#[test]
+fn test_firmware_boots_at_the_reset_vector() {
+    let image = ProgramImage::from_assembly(FIRMWARE).unwrap();
+    let mut simulator = FunctionalSimulator::new(MEMORY_SIZE);
+    simulator.load_image(&image).unwrap();
+    simulator.reset_to_boot_vector();
+    simulator.schedule_event(10, AsyncEvent::Timer);
+    assert_eq!(simulator.pc(), RESET_VECTOR);
+    simulator.run().unwrap();
+
+    // Reset leaves the processor at error level with bootstrap vectors
+    assert_eq!(simulator.registers.read(16), RESET_STATUS);
+    // The interrupt was taken at 0xBFC00380 while Status.BEV was set
+    assert_eq!(simulator.registers.read(17), 1);
+    assert_ne!(simulator.registers.cp0[CP0_STATUS as usize] & STATUS_BEV, 0);
+    // kseg0 and kseg1 reach the same physical word
+    assert_eq!(simulator.registers.read(18), 1234);
+    assert_eq!(simulator.memory.read_word(0x1000), Ok(1234));
+}
+
+#[test]
+fn test_segments_and_raw_boot_images() {
+    assert_eq!(AddressSegment::of(0x0040_0000), AddressSegment::Kuseg);
+    assert_eq!(AddressSegment::of(0x8000_0180), AddressSegment::Kseg0);
+    assert_eq!(AddressSegment::of(RESET_VECTOR), AddressSegment::Kseg1);
+    assert_eq!(AddressSegment::of(0xC000_0000), AddressSegment::Kseg2);
+    assert!(!AddressSegment::Kseg1.is_cached());
+    assert_eq!(
+        AddressSegment::Kseg1.physical_address(RESET_VECTOR),
+        Some(0x1FC0_0000)
+    );
+    assert_eq!(AddressSegment::Kuseg.physical_address(0x1000), None);
+    let memory = Memory::new(0x0100_0000);
+    assert_eq!(memory.translate_address(0x8000_1000), 0x1000);
+    assert_eq!(memory.translate_address(0xA000_1000), 0x1000);
+
+    // Raw binaries become firmware at the reset vector; other formats keep
+    // their addresses
+    let image = ProgramImage::raw(&raw_bytes()).for_boot();
+    assert_eq!(image, ProgramImage::firmware(&raw_bytes()));
+    assert_eq!(image.segments[0].address, RESET_VECTOR);
+    assert_eq!(image.format, ProgramFormat::Raw);
+    let assembled = ProgramImage::from_assembly(FIRMWARE).unwrap();
+    assert_eq!(assembled.clone().for_boot(), assembled);
+    // Kernel segments count at their physical addresses
+    let kernel = ProgramImage::from_assembly(".ktext 0x80400000\n    eret\n").unwrap();
+    assert_eq!(kernel.memory_required(), 0x0040_0004);
+
+    // In the timing model kseg1 fetches bypass the instruction cache
+    let mut cycles = Vec::new();
+    for base in [0x8000_0000u32, 0xA000_0000] {
+        let mut simulator = TimingSimulator::new(
+            PipelineConfig::new(5),
+            CacheConfig::new(1024, 2, 32),
+            CacheConfig::new(1024, 2, 32),
+            MEMORY_SIZE,
+        );
+        let mut image = ProgramImage::raw(&raw_bytes());
+        image.segments[0].address = base;
+        image.entry_point = base;
+        simulator.load_image(&image).unwrap();
+        simulator.run();
+        assert_eq!(simulator.registers.read(4), 7);
+        cycles.push(simulator.run_totals.cycles);
+    }
+    assert!(cycles[1] > cycles[0], "{:?}", cycles);
+}
+
+#[test]
+fn test_boot_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    let firmware = temp_dir.path().join("firmware.bin");
+    std::fs::write(&firmware, raw_bytes()).unwrap();
+
+    for mode in ["functional", "timing"] {
+        let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .arg(mode)
+            .arg("--input")
+            .arg(&firmware)
+            .arg("--boot");
+        cmd.assert()
+            .success()
+            .stdout(contains("Segment: 0xBFC00000 - 0xBFC0000C"))
+            .stdout(contains("Starting execution at PC: 0xBFC00000"))
+            .stdout(contains("$4: 7"));
+    }
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&firmware);
+    cmd.assert()
+        .success()
+        .stdout(contains("Segment: 0x00000000 - 0x0000000C"));
+}
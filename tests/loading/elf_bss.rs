@@ -0,0 +1,161 @@
+// tests/loading/elf_bss.rs
+// Tests for zero-filling ELF segments past their file contents and for the
+// section information the loader exposes
+
+use vmips_rust::elf_loader::{ElfError, ElfLoader};
+use vmips_rust::functional_simulator::memory::Memory;
+use vmips_rust::loader::ProgramImage;
+
+const TEXT: u32 = 0x0040_0000;
+const DATA: u32 = 0x1000_0000;
+const DATA_SIZE: u32 = 8;
+const BSS_SIZE: u32 = 0x40;
+
+// A little-endian MIPS ELF32 image with a text segment and a data segment
+// whose last BSS_SIZE bytes are .bss, then .text, .data, .bss and
+// .shstrtab section headers
+fn build_elf(with_sections: bool) -> Vec<u8> {
+    let mut data = vec![0u8; 52 + 2 * 32];
+    data[0..4].copy_from_slice(&[0x7f, b'E', b'L', b'F']);
+    data[4] = 1; // ELFCLASS32
+    data[5] = 1; // little endian
+    data[6] = 1; // EV_CURRENT
+    data[16..18].copy_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+    data[18..20].copy_from_slice(&8u16.to_le_bytes()); // EM_MIPS
+    data[20..24].copy_from_slice(&1u32.to_le_bytes());
+    data[24..28].copy_from_slice(&TEXT.to_le_bytes());
+    data[28..32].copy_from_slice(&52u32.to_le_bytes()); // e_phoff
+    data[40..42].copy_from_slice(&52u16.to_le_bytes()); // e_ehsize
+    data[42..44].copy_from_slice(&32u16.to_le_bytes()); // e_phentsize
+    data[44..46].copy_from_slice(&2u16.to_le_bytes()); // e_phnum
+
+    let text_offset = data.len() as u32;
+    data.extend_from_slice(&0x2002000Au32.to_le_bytes()); // addi $v0, $zero, 10
+    data.extend_from_slice(&0x0000000Cu32.to_le_bytes()); // syscall
+    let data_offset = data.len() as u32;
+    data.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+
+    let segments = [
+        [1, text_offset, TEXT, TEXT, 8, 8, 5, 4],
+        [
+            1,
+            data_offset,
+            DATA,
+            DATA,
+            DATA_SIZE,
+            DATA_SIZE + BSS_SIZE,
+            6,
+            4,
+        ],
+    ];
+    for (i, header) in segments.iter().enumerate() {
+        for (j, field) in header.iter().enumerate() {
+            let at = 52 + i * 32 + j * 4;
+            data[at..at + 4].copy_from_slice(&field.to_le_bytes());
+        }
+    }
+    if !with_sections {
+        return data;
+    }
+
+    let names_offset = data.len() as u32;
+    data.extend_from_slice(b"\0.text\0.data\0.bss\0.shstrtab\0");
+    let names_size = data.len() as u32 - names_offset;
+    let table = data.len() as u32;
+    // name, type, flags, addr, offset, size, link, info, addralign, entsize
+    let sections: [[u32; 10]; 5] = [
+        [0; 10],
+        [1, 1, 6, TEXT, text_offset, 8, 0, 0, 4, 0],
+        [7, 1, 3, DATA, data_offset, DATA_SIZE, 0, 0, 4, 0],
+        [
+            13,
+            8,
+            3,
+            DATA + DATA_SIZE,
+            data_offset + DATA_SIZE,
+            BSS_SIZE,
+            0,
+            0,
+            4,
+            0,
+        ],
+        [18, 3, 0, 0, names_offset, names_size, 0, 0, 1, 0],
+    ];
+    for header in &sections {
+        for field in header {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+    data[32..36].copy_from_slice(&table.to_le_bytes()); // e_shoff
+    data[46..48].copy_from_slice(&40u16.to_le_bytes()); // e_shentsize
+    data[48..50].copy_from_slice(&5u16.to_le_bytes()); // e_shnum
+    data[50..52].copy_from_slice(&4u16.to_le_bytes()); // e_shstrndx
+    data
+}
+
+#[test]
+fn test_bss_is_zeroed_on_load() {
+    let elf = ElfLoader::load_from_bytes(build_elf(true)).unwrap();
+    let mut memory = Memory::new(0x2000_0000);
+    memory.fill(DATA as usize, 0x100, 0xEE).unwrap();
+
+    elf.load_into_memory(&mut memory).unwrap();
+    assert_eq!(memory.read_word(DATA as usize).unwrap(), 0x0403_0201);
+    let bss = memory
+        .read_block((DATA + DATA_SIZE) as usize, BSS_SIZE as usize)
+        .unwrap();
+    assert!(bss.iter().all(|&byte| byte == 0));
+    // Memory past the segment is left alone
+    let after = (DATA + DATA_SIZE + BSS_SIZE) as usize;
+    assert_eq!(memory.read_byte(after).unwrap(), 0xEE);
+}
+
+#[test]
+fn test_program_image_covers_memory_size() {
+    let elf = ElfLoader::load_from_bytes(build_elf(true)).unwrap();
+    let image = ProgramImage::elf(&elf).unwrap();
+    let data = image
+        .segments
+        .iter()
+        .find(|segment| segment.address == DATA)
+        .unwrap();
+    assert_eq!(data.bytes.len(), (DATA_SIZE + BSS_SIZE) as usize);
+    assert_eq!(&data.bytes[..4], &[1, 2, 3, 4]);
+    assert!(data.bytes[DATA_SIZE as usize..]
+        .iter()
+        .all(|&byte| byte == 0));
+}
+
+#[test]
+fn test_sections() {
+    let elf = ElfLoader::load_from_bytes(build_elf(true)).unwrap();
+    let names: Vec<&str> = elf
+        .sections()
+        .iter()
+        .map(|section| section.name.as_str())
+        .collect();
+    assert_eq!(names, [".text", ".data", ".bss"]);
+
+    let bss = elf.section(".bss").unwrap();
+    assert!(bss.zeroed);
+    assert_eq!((bss.address, bss.end()), (DATA + 8, DATA + 8 + BSS_SIZE));
+    assert!(bss.contains(DATA + 8) && !bss.contains(DATA + 8 + BSS_SIZE));
+    assert!(!elf.section(".data").unwrap().zeroed);
+    assert!(bss.to_string().starts_with(".bss"));
+    assert_eq!(ProgramImage::elf(&elf).unwrap().sections, elf.sections());
+
+    // Sections are optional for loading
+    let stripped = ElfLoader::load_from_bytes(build_elf(false)).unwrap();
+    assert!(stripped.sections().is_empty());
+    assert_eq!(stripped.segment_images().unwrap().len(), 2);
+}
+
+#[test]
+fn test_file_size_larger_than_memory_size_is_invalid() {
+    let mut image = build_elf(false);
+    // The data segment's memory size, smaller than its file size
+    let at = 52 + 32 + 5 * 4;
+    image[at..at + 4].copy_from_slice(&4u32.to_le_bytes());
+    let elf = ElfLoader::load_from_bytes(image).unwrap();
+    assert!(matches!(elf.segment_images(), Err(ElfError::InvalidFormat)));
+}
@@ -0,0 +1,141 @@
+// tests/loading/hex_loader.rs
+// Tests for loading Motorola S-record and Intel HEX files
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::hex_loader::{self, HexError, HexErrorKind, HexFormat, HexImage};
+use vmips_rust::loader::{LoadError, ProgramFormat, ProgramImage, Segment};
+
+/// addi $a0, $zero, 7; addi $v0, $zero, 10; syscall at 0x00400000, split
+/// over two data records, with a header, a count and a start address
+const SRECORDS: &str = "S00600004844521B
+S30D00400000070004200A0002205B
+S309004000080C000000A2
+S5030002FA
+S70500400000BA
+";
+
+/// The same program with an extended linear address and a start address
+const INTEL_HEX: &str = ":020000040040BA
+:0C000000070004200A0002200C00000091
+:0400000500400000B7
+:00000001FF
+";
+
+fn program_bytes() -> Vec<u8> {
+    [0x2004_0007u32, 0x2002_000A, 0x0000_000C]
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect()
+}
+
+#[test]
+fn test_both_formats_load_and_run() {
+    for (text, format) in [
+        (SRECORDS, ProgramFormat::SRecord),
+        (INTEL_HEX, ProgramFormat::IntelHex),
+    ] {
+        let image = ProgramImage::from_bytes(text.as_bytes()).unwrap();
+        assert_eq!(image.format, format);
+        assert_eq!(image.entry_point, 0x0040_0000);
+        assert_eq!(
+            image.segments,
+            vec![Segment {
+                address: 0x0040_0000,
+                bytes: program_bytes(),
+            }]
+        );
+
+        let mut simulator = Simulator::from_image(&image).unwrap();
+        simulator.run().unwrap();
+        assert_eq!(simulator.registers.read(4), 7);
+    }
+    assert_eq!(ProgramFormat::SRecord.to_string(), "Motorola S-record file");
+
+    // Segment addresses and start segment addresses are real-mode style
+    // base * 16 + offset, and files without a start address begin at their
+    // lowest address
+    let segmented = ":020000021000EC\n:02001000AABB89\n:0400000310000020C9\n:00000001FF\n";
+    assert_eq!(
+        hex_loader::parse_intel_hex(segmented),
+        Ok(HexImage {
+            blocks: vec![(0x0001_0010, vec![0xAA, 0xBB])],
+            entry_point: Some(0x0001_0020),
+        })
+    );
+    let image = ProgramImage::from_bytes(b"S30D00400000070004200A0002205B\n").unwrap();
+    assert_eq!(image.entry_point, 0x0040_0000);
+}
+
+#[test]
+fn test_detection_and_bad_records() {
+    assert_eq!(hex_loader::detect(SRECORDS), Some(HexFormat::SRecord));
+    assert_eq!(hex_loader::detect(INTEL_HEX), Some(HexFormat::IntelHex));
+    assert_eq!(hex_loader::detect("Start:\n    nop\n"), None);
+    assert_eq!(hex_loader::detect(":loop\n"), None);
+    assert_eq!(hex_loader::detect(""), None);
+    // Assembly source is still assembled
+    let image = ProgramImage::from_bytes(b".text\n    nop\n").unwrap();
+    assert_eq!(image.format, ProgramFormat::Source);
+
+    let corrupted = SRECORDS.replacen("205B", "205C", 1);
+    assert_eq!(
+        hex_loader::parse_srecords(&corrupted),
+        Err(HexError {
+            line: 2,
+            kind: HexErrorKind::Checksum {
+                expected: 0x5C,
+                found: 0x5B,
+            },
+        })
+    );
+    let error = ProgramImage::from_bytes(corrupted.as_bytes()).unwrap_err();
+    assert!(matches!(error, LoadError::Hex(_)));
+    assert_eq!(
+        error.to_string(),
+        "invalid hex file: line 2: checksum 0x5B does not match the record's 0x5C"
+    );
+    assert_eq!(
+        hex_loader::parse_srecords("S4030000FC\n").unwrap_err().kind,
+        HexErrorKind::UnsupportedRecord("S4".to_string())
+    );
+    // The byte count must match the record's length
+    assert_eq!(
+        hex_loader::parse_intel_hex(":0200000001FD\n\n:00000001FF\n")
+            .unwrap_err()
+            .line,
+        1
+    );
+    assert_eq!(
+        hex_loader::parse_intel_hex(":00000006FA\n")
+            .unwrap_err()
+            .kind,
+        HexErrorKind::UnsupportedRecord("06".to_string())
+    );
+}
+
+#[test]
+fn test_hex_files_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    for (file, text, format) in [
+        ("program.srec", SRECORDS, "Motorola S-record file"),
+        ("program.hex", INTEL_HEX, "Intel HEX file"),
+    ] {
+        let path = temp_dir.path().join(file);
+        std::fs::write(&path, text).unwrap();
+        for mode in ["functional", "timing"] {
+            let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+            cmd.current_dir(temp_dir.path())
+                .arg(mode)
+                .arg("--input")
+                .arg(&path);
+            cmd.assert()
+                .success()
+                .stdout(contains(format!("Loading {} of 12 bytes", format)))
+                .stdout(contains("Segment: 0x00400000 - 0x0040000C"))
+                .stdout(contains("$4: 7"));
+        }
+    }
+}
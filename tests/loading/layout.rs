@@ -0,0 +1,142 @@
+// tests/loading/layout.rs
+// Tests for the fixed and randomized guest memory layouts
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::layout::{
+    MemoryLayout, HEAP_PAGE_SIZE, HEAP_RANDOM_PAGES, STACK_RANDOM_RANGE,
+};
+use vmips_rust::functional_simulator::memory::Memory;
+use vmips_rust::functional_simulator::simulator::{Simulator, STACK_TOP};
+use vmips_rust::loader::ProgramImage;
+
+/// Records the stack pointer in $s0 and the address of a 64-byte heap
+/// allocation in $s1
+const ADDRESS_PROGRAM: &str = "
+.text
+    add $s0, $sp, $zero
+    li $a0, 64
+    li $v0, 9
+    syscall
+    add $s1, $v0, $zero
+    li $v0, 10
+    syscall
+";
+
+const MEMORY_SIZE: usize = 0x0080_0000;
+
+// Run the program and return where its stack and heap were
+fn addresses(layout_seed: Option<u64>) -> (u32, u32) {
+    let image = ProgramImage::from_assembly(ADDRESS_PROGRAM).unwrap();
+    let mut simulator = Simulator::new(MEMORY_SIZE);
+    simulator.load_image(&image).unwrap();
+    let layout = match layout_seed {
+        Some(seed) => MemoryLayout::randomized(&simulator.memory, seed),
+        None => MemoryLayout::fixed(&simulator.memory),
+    };
+    simulator.apply_layout(&layout);
+    simulator.run().unwrap();
+    (simulator.registers.read(16), simulator.registers.read(17))
+}
+
+#[test]
+fn test_layouts_are_reproducible_and_bounded() {
+    let memory = Memory::new(MEMORY_SIZE);
+    let fixed = MemoryLayout::fixed(&memory);
+    assert_eq!(fixed.stack_top, STACK_TOP);
+    assert_eq!(fixed.heap_start, memory.heap_end());
+    assert!(!fixed.is_randomized());
+    assert_eq!(
+        fixed.render(),
+        "Memory layout: stack top 0x7FFFFFFC, heap start 0x00200000 (fixed)"
+    );
+
+    let layout = MemoryLayout::randomized(&memory, 7);
+    assert_eq!(layout, MemoryLayout::randomized(&memory, 7));
+    assert!(layout.is_randomized());
+    assert!(layout.render().ends_with("(randomized with seed 7)"));
+
+    let layouts: Vec<MemoryLayout> = (0..32)
+        .map(|seed| MemoryLayout::randomized(&memory, seed))
+        .collect();
+    for layout in &layouts {
+        assert!(layout.stack_top <= STACK_TOP);
+        assert!(layout.stack_top > STACK_TOP - STACK_RANDOM_RANGE);
+        assert_eq!((STACK_TOP - layout.stack_top) % 16, 0);
+        let heap_offset = layout.heap_start - fixed.heap_start;
+        assert_eq!(heap_offset % HEAP_PAGE_SIZE, 0);
+        assert!(heap_offset <= HEAP_RANDOM_PAGES * HEAP_PAGE_SIZE);
+    }
+    assert!(layouts
+        .iter()
+        .any(|layout| layout.stack_top != layouts[0].stack_top));
+    assert!(layouts
+        .iter()
+        .any(|layout| layout.heap_start != layouts[0].heap_start));
+
+    // A small memory keeps at least half of its heap space
+    let small = Memory::new(0x10000);
+    for seed in 0..32 {
+        let layout = MemoryLayout::randomized(&small, seed);
+        assert!(layout.heap_start - small.heap_end() <= (small.size - small.heap_end()) / 2);
+    }
+}
+
+#[test]
+fn test_guests_see_the_randomized_addresses() {
+    assert_eq!(addresses(None), (STACK_TOP, 0x0020_0000));
+    assert_eq!(addresses(None), addresses(None));
+    assert_eq!(addresses(Some(1)), addresses(Some(1)));
+
+    // An exploit that hard-codes the addresses from one run misses on most
+    // others
+    let (stack, heap) = addresses(Some(1));
+    let layout = MemoryLayout::randomized(&Memory::new(MEMORY_SIZE), 1);
+    assert_eq!(stack, layout.stack_top);
+    assert_eq!(heap as usize, layout.heap_start);
+    let misses = (2..10)
+        .map(|seed| addresses(Some(seed)))
+        .filter(|&other| other != (stack, heap))
+        .count();
+    assert_eq!(misses, 8);
+}
+
+#[test]
+fn test_randomized_layout_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("addresses.s");
+    std::fs::write(&source, ADDRESS_PROGRAM).unwrap();
+    let layout = MemoryLayout::randomized(&Memory::new(MEMORY_SIZE), 42);
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--randomize-layout")
+        .arg("42");
+    cmd.assert()
+        .success()
+        .stdout(contains(layout.render()))
+        .stdout(contains(format!("SP: 0x{:08X}", layout.stack_top)));
+
+    // Without a seed one is chosen and printed
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--randomize-layout");
+    cmd.assert()
+        .success()
+        .stdout(contains("(randomized with seed "));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--randomize-layout")
+        .arg("lots")
+        .assert()
+        .failure()
+        .stderr(contains("invalid value 'lots'"));
+}
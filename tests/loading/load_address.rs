@@ -0,0 +1,139 @@
+// tests/loading/load_address.rs
+// Tests for placing raw binaries at a load address and choosing the entry point
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::loader::{LoadError, ProgramFormat, ProgramImage, Segment};
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
+
+/// A data word of 42 followed by code linked at 0x00400000 that loads it:
+/// lui $t0, 0x0040; lw $a0, 0($t0); addi $v0, $zero, 10; syscall
+const LINKED_IMAGE: [u32; 5] = [42, 0x3C08_0040, 0x8D04_0000, 0x2002_000A, 0x0000_000C];
+
+fn image_bytes() -> Vec<u8> {
+    LINKED_IMAGE
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect()
+}
+
+fn linked_image() -> ProgramImage {
+    ProgramImage::raw(&image_bytes())
+        .with_load_address(0x0040_0000)
+        .unwrap()
+        .with_entry_point(0x0040_0004)
+}
+
+#[test]
+fn test_raw_images_move_to_their_load_address() {
+    let image = ProgramImage::raw(&image_bytes())
+        .with_load_address(0x8000_0000)
+        .unwrap();
+    assert_eq!(image, ProgramImage::raw_at(&image_bytes(), 0x8000_0000));
+    assert_eq!(image.format, ProgramFormat::Raw);
+    assert_eq!(image.entry_point, 0x8000_0000);
+    assert_eq!(
+        image.segments,
+        vec![Segment {
+            address: 0x8000_0000,
+            bytes: image_bytes(),
+        }]
+    );
+    // kseg0 images need only as much memory as their physical addresses
+    assert_eq!(image.memory_required(), 20);
+
+    let image = linked_image();
+    assert_eq!(image.segments[0].address, 0x0040_0000);
+    assert_eq!(image.entry_point, 0x0040_0004);
+    assert_eq!(image.memory_required(), 0x0040_0014);
+
+    // Other formats already say where their segments go
+    let source = ProgramImage::from_assembly(".text\n    nop\n").unwrap();
+    let error = source.clone().with_load_address(0x8000_0000).unwrap_err();
+    assert!(matches!(
+        error,
+        LoadError::FixedAddresses(ProgramFormat::Source)
+    ));
+    assert_eq!(
+        error.to_string(),
+        "a load address only applies to raw binaries, not to this assembly source"
+    );
+    assert_eq!(
+        source.with_entry_point(0x0040_0010).entry_point,
+        0x0040_0010
+    );
+}
+
+#[test]
+fn test_both_simulators_start_at_the_entry_point() {
+    let image = linked_image();
+    let memory_size = image.memory_required().next_power_of_two();
+
+    let mut functional = FunctionalSimulator::new(memory_size);
+    functional.load_image(&image).unwrap();
+    assert_eq!(functional.pc(), 0x0040_0004);
+    functional.run().unwrap();
+    assert_eq!(functional.registers.read(4), 42);
+    // The data word was never executed
+    assert_eq!(functional.step_count, 4);
+
+    let mut timing = TimingSimulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(1024, 2, 32),
+        CacheConfig::new(1024, 2, 32),
+        memory_size,
+    );
+    timing.load_image(&image).unwrap();
+    assert_eq!(timing.pc, 0x0040_0004);
+    timing.run();
+    assert_eq!(timing.registers.read(4), 42);
+}
+
+#[test]
+fn test_load_address_and_entry_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    let binary = temp_dir.path().join("linked.bin");
+    std::fs::write(&binary, image_bytes()).unwrap();
+
+    for mode in ["functional", "timing"] {
+        let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .arg(mode)
+            .arg("--input")
+            .arg(&binary)
+            .arg("--load-address")
+            .arg("0x00400000")
+            .arg("--entry")
+            .arg("0x00400004");
+        cmd.assert()
+            .success()
+            .stdout(contains("Segment: 0x00400000 - 0x00400014"))
+            .stdout(contains("Entry point: 0x00400004"))
+            .stdout(contains("$4: 42"));
+    }
+
+    let source = temp_dir.path().join("program.s");
+    std::fs::write(&source, ".text\n    nop\n").unwrap();
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--load-address")
+        .arg("0x80000000");
+    cmd.assert().stderr(contains(
+        "Failed to load program: a load address only applies to raw binaries",
+    ));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--load-address")
+        .arg("0x80000000")
+        .arg("--boot")
+        .assert()
+        .failure()
+        .stderr(contains("cannot be used with"));
+}
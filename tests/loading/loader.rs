@@ -0,0 +1,63 @@
+// tests/loading/loader.rs
+// Tests for format detection and placement of program images
+
+use vmips_rust::assembler::Assembler;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::loader::{LoadError, ProgramFormat, ProgramImage, DATA_BASE, TEXT_BASE};
+
+fn to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+#[test]
+fn test_raw_program_loads_at_zero_without_test_data() {
+    let program = to_bytes(&[0x20080005, 0x21090001]);
+    let image = ProgramImage::from_bytes(&program).unwrap();
+    assert_eq!(image.format, ProgramFormat::Raw);
+    assert_eq!(image.entry_point, 0);
+
+    let mut simulator = Simulator::new(8192);
+    simulator.load_program(&program);
+    assert_eq!(simulator.pc(), 0);
+    assert_eq!(simulator.memory.read_word(4), Ok(0x21090001));
+
+    // Loading no longer writes fixture values into the data area
+    assert_eq!(simulator.memory.read_word(0x1000), Ok(0));
+    assert_eq!(simulator.memory.read_word(0x1004), Ok(0));
+}
+
+#[test]
+fn test_assembler_output_is_split_into_segments() {
+    let mut assembler = Assembler::new();
+    let binary = assembler
+        .assemble_string(".data\nvalue: .word 42\n.text\naddi $t0, $zero, 1\n")
+        .unwrap();
+
+    let image = ProgramImage::from_bytes(&binary).unwrap();
+    assert_eq!(image.format, ProgramFormat::Assembled);
+    assert_eq!(image.entry_point, TEXT_BASE);
+    assert_eq!(image.segments.len(), 2);
+    assert_eq!(image.segments[0].address, DATA_BASE);
+    assert_eq!(image.segments[0].bytes, 42u32.to_le_bytes().to_vec());
+    assert_eq!(image.segments[1].address, TEXT_BASE);
+    assert_eq!(image.segments[1].bytes.len(), 4);
+
+    let mut simulator = Simulator::new(0x0050_0000);
+    simulator.load_image(&image).unwrap();
+    assert_eq!(simulator.pc(), TEXT_BASE);
+    assert_eq!(simulator.step(), Ok(true));
+    assert_eq!(simulator.registers.read(8), 1);
+}
+
+#[test]
+fn test_image_outside_memory_is_rejected() {
+    let mut assembler = Assembler::new();
+    let binary = assembler.assemble_string("addi $t0, $zero, 1\n").unwrap();
+    let image = ProgramImage::from_bytes(&binary).unwrap();
+
+    let mut simulator = Simulator::new(8192);
+    match simulator.load_image(&image) {
+        Err(LoadError::OutOfBounds { address, .. }) => assert_eq!(address, TEXT_BASE),
+        other => panic!("expected an out-of-bounds error, got {:?}", other),
+    }
+}
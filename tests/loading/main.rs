@@ -0,0 +1,15 @@
+// tests/loading/main.rs
+// Tests for loading program images and choosing where they start
+
+mod assembly_input;
+mod boot;
+mod elf_bss;
+mod hex_loader;
+mod layout;
+mod load_address;
+mod loader;
+mod overlays;
+mod preload;
+mod program_counter;
+mod start_symbol;
+mod timing_loader;
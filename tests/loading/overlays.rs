@@ -0,0 +1,118 @@
+// tests/loading/overlays.rs
+// Tests for loading several programs into one address space
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::loader::{LoadError, ProgramFormat, ProgramImage};
+
+/// A kernel in kseg0 that sets $s0 and jumps to the user program
+const KERNEL: &str = "
+.text 0x80001000
+    li $s0, 5
+    lui $t0, 0x0040
+    jr $t0
+    nop
+";
+
+/// A raw user program: addi $a0, $s0, 2; addi $v0, $zero, 10; syscall
+const USER_PROGRAM: [u32; 3] = [0x2204_0002, 0x2002_000A, 0x0000_000C];
+
+fn user_bytes() -> Vec<u8> {
+    USER_PROGRAM
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect()
+}
+
+fn kernel_and_user() -> ProgramImage {
+    ProgramImage::from_assembly(KERNEL)
+        .unwrap()
+        .overlay(ProgramImage::raw_at(&user_bytes(), 0x0040_0000))
+        .unwrap()
+}
+
+#[test]
+fn test_overlays_share_one_address_space() {
+    let image = kernel_and_user();
+    assert_eq!(image.format, ProgramFormat::Overlay);
+    assert_eq!(image.entry_point, 0x8000_1000);
+    let addresses: Vec<u32> = image
+        .segments
+        .iter()
+        .map(|segment| segment.address)
+        .collect();
+    assert_eq!(addresses, vec![0x8000_1000, 0x0040_0000]);
+    assert_eq!(image.memory_required(), 0x0040_000C);
+
+    // The kseg0 kernel and a raw program at 0 share physical memory
+    let error = ProgramImage::raw_at(&user_bytes(), 0x8000_0008)
+        .overlay(ProgramImage::raw(&user_bytes()))
+        .unwrap_err();
+    assert!(matches!(error, LoadError::Overlap { address: 8 }));
+    assert_eq!(
+        error.to_string(),
+        "programs overlap at 0x00000008; load them at different addresses"
+    );
+    // Adjacent programs do not overlap
+    let image = ProgramImage::raw(&user_bytes())
+        .overlay(ProgramImage::raw_at(&user_bytes(), 12))
+        .unwrap();
+    assert_eq!(image.size(), 24);
+    assert_eq!(ProgramFormat::Overlay.to_string(), "multi-program image");
+}
+
+#[test]
+fn test_kernel_starts_the_user_program() {
+    let image = kernel_and_user();
+    let mut simulator = Simulator::from_image(&image).unwrap();
+    assert_eq!(simulator.pc(), 0x8000_1000);
+    simulator.run().unwrap();
+    assert_eq!(simulator.registers.read(4), 7);
+    assert_eq!(simulator.pc(), 0x0040_0008);
+}
+
+#[test]
+fn test_loading_programs_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("kernel.s"), KERNEL).unwrap();
+    std::fs::write(temp_dir.path().join("user.bin"), user_bytes()).unwrap();
+
+    for mode in ["functional", "timing"] {
+        let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .arg(mode)
+            .arg("--load")
+            .arg("kernel.s")
+            .arg("--load")
+            .arg("user.bin@0x00400000");
+        cmd.assert()
+            .success()
+            .stdout(contains("Loading multi-program image of 28 bytes"))
+            .stdout(contains("Segment: 0x80001000 - 0x80001010"))
+            .stdout(contains("Segment: 0x00400000 - 0x0040000C"))
+            .stdout(contains("Entry point: 0x80001000"))
+            .stdout(contains("$4: 7"));
+    }
+
+    // --input comes first, and both programs here would load at address 0
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg("user.bin")
+        .arg("--load")
+        .arg("user.bin");
+    cmd.assert().stderr(contains(
+        "Failed to load program: programs overlap at 0x00000000",
+    ));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--load")
+        .arg("user.bin@0x4X")
+        .assert()
+        .failure()
+        .stderr(contains("invalid number '0x4X'"));
+}
@@ -0,0 +1,80 @@
+// tests/loading/preload.rs
+// Tests for preloading memory words before a run
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::loader::LoadError;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
+
+/// Adds the words at 0x100 and 0x104 and stores the sum at 0x108
+const SUM_PROGRAM: [u32; 4] = [
+    0x8C020100, // lw $v0, 0x100($zero)
+    0x8C030104, // lw $v1, 0x104($zero)
+    0x00432020, // add $a0, $v0, $v1
+    0xAC040108, // sw $a0, 0x108($zero)
+];
+
+fn program_bytes() -> Vec<u8> {
+    SUM_PROGRAM.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+#[test]
+fn test_functional_preload_memory() {
+    let mut simulator = Simulator::new(4096);
+    simulator.load_program(&program_bytes());
+    simulator
+        .preload_memory(&[(0x100, 30), (0x104, 12)])
+        .unwrap();
+
+    for _ in 0..SUM_PROGRAM.len() {
+        assert_eq!(simulator.step(), Ok(true));
+    }
+    assert_eq!(simulator.memory.read_word(0x108), Ok(42));
+}
+
+#[test]
+fn test_timing_preload_memory_rejects_out_of_bounds() {
+    let mut simulator = TimingSimulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(1024, 2, 32),
+        CacheConfig::new(1024, 2, 32),
+        4096,
+    );
+    simulator.preload_memory(&[(0x200, 7)]).unwrap();
+    assert_eq!(simulator.memory.read_word(0x200), Ok(7));
+
+    match simulator.preload_memory(&[(0x2000, 1)]) {
+        Err(LoadError::OutOfBounds { address, .. }) => assert_eq!(address, 0x2000),
+        other => panic!("expected an out-of-bounds error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_preload_cli_flag() {
+    let dir = tempdir().unwrap();
+    let program_path = dir.path().join("sum.bin");
+    std::fs::write(&program_path, program_bytes()).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--input")
+        .arg(&program_path)
+        .arg("--preload")
+        .arg("0x100=40")
+        .arg("--preload")
+        .arg("260=2");
+    cmd.assert().success().stdout(contains("$4: 42"));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--input")
+        .arg(&program_path)
+        .arg("--preload")
+        .arg("0x100");
+    cmd.assert()
+        .failure()
+        .stderr(contains("expected ADDRESS=VALUE"));
+}
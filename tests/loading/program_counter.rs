@@ -0,0 +1,90 @@
+// tests/loading/program_counter.rs
+// Tests for setting the functional simulator's PC and entry point
+
+use vmips_rust::elf_loader::ElfLoader;
+use vmips_rust::functional_simulator::simulator::Simulator;
+
+/// Code placed in the conventional MIPS text segment
+const TEXT_BASE: u32 = 0x0040_0000;
+const MEMORY_SIZE: usize = 0x0050_0000;
+
+const PROGRAM: [u32; 3] = [
+    0x20080007, // addi $t0, $zero, 7
+    0x21090003, // addi $t1, $t0, 3
+    0x2002000A, // addi $v0, $zero, 10 (exit)
+];
+
+// Build a little-endian MIPS ELF32 image with one loadable segment
+fn build_elf(entry: u32, vaddr: u32, code: &[u32]) -> Vec<u8> {
+    let mut data = vec![0u8; 84];
+    data[0..4].copy_from_slice(&[0x7f, b'E', b'L', b'F']);
+    data[4] = 1; // ELFCLASS32
+    data[5] = 1; // little endian
+    data[6] = 1; // EV_CURRENT
+    data[16..18].copy_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+    data[18..20].copy_from_slice(&8u16.to_le_bytes()); // EM_MIPS
+    data[20..24].copy_from_slice(&1u32.to_le_bytes());
+    data[24..28].copy_from_slice(&entry.to_le_bytes());
+    data[28..32].copy_from_slice(&52u32.to_le_bytes()); // e_phoff
+    data[40..42].copy_from_slice(&52u16.to_le_bytes()); // e_ehsize
+    data[42..44].copy_from_slice(&32u16.to_le_bytes()); // e_phentsize
+    data[44..46].copy_from_slice(&1u16.to_le_bytes()); // e_phnum
+
+    let size = (code.len() * 4) as u32;
+    let header = [1, 84, vaddr, vaddr, size, size, 5, 4];
+    for (i, field) in header.iter().enumerate() {
+        data[52 + i * 4..56 + i * 4].copy_from_slice(&field.to_le_bytes());
+    }
+    for word in code {
+        data.extend_from_slice(&word.to_le_bytes());
+    }
+    data
+}
+
+#[test]
+fn test_set_pc_resumes_at_address() {
+    let mut simulator = Simulator::new(1024);
+    for (i, &word) in PROGRAM.iter().enumerate() {
+        simulator.memory.write_word_init(0x100 + i * 4, word);
+    }
+
+    simulator.set_pc(0x104);
+    assert_eq!(simulator.pc(), 0x104);
+    assert_eq!(simulator.step(), Ok(true));
+
+    // Only the second instruction ran, so $t0 was still zero
+    assert_eq!(simulator.registers.read(9), 3);
+    assert_eq!(simulator.pc(), 0x108);
+}
+
+#[test]
+fn test_entry_point_in_text_segment() {
+    let mut simulator = Simulator::new(MEMORY_SIZE);
+    for (i, &word) in PROGRAM.iter().enumerate() {
+        simulator
+            .memory
+            .write_word_init(TEXT_BASE as usize + i * 4, word);
+    }
+
+    simulator.set_entry_point(TEXT_BASE);
+    for _ in 0..PROGRAM.len() {
+        assert_eq!(simulator.step(), Ok(true));
+    }
+
+    assert_eq!(simulator.registers.read(8), 7);
+    assert_eq!(simulator.registers.read(9), 10);
+    assert_eq!(simulator.pc(), TEXT_BASE + 12);
+}
+
+#[test]
+fn test_load_elf_starts_at_entry_point() {
+    let image = build_elf(TEXT_BASE + 4, TEXT_BASE, &PROGRAM);
+    let elf = ElfLoader::load_from_bytes(image).unwrap();
+
+    let mut simulator = Simulator::new(MEMORY_SIZE);
+    simulator.load_elf(&elf).unwrap();
+    assert_eq!(simulator.pc(), TEXT_BASE + 4);
+
+    assert_eq!(simulator.step(), Ok(true));
+    assert_eq!(simulator.registers.read(9), 3);
+}
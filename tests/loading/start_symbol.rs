@@ -0,0 +1,166 @@
+// tests/loading/start_symbol.rs
+// Tests for starting a program at a named label or ELF symbol
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::elf_loader::ElfLoader;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::loader::{LoadError, ProgramImage};
+
+const SOURCE: &str = "\
+.text
+main:
+    addi $a0, $zero, 1
+    addi $v0, $zero, 10
+    syscall
+helper:
+    addi $a0, $zero, 7
+    addi $v0, $zero, 10
+    syscall
+";
+
+const TEXT: u32 = 0x0040_0000;
+
+// A little-endian MIPS ELF32 image with one text segment and a symbol
+// table holding _start, sum, a file symbol and an undefined printf
+fn build_elf() -> Vec<u8> {
+    let mut data = vec![0u8; 52 + 32];
+    data[0..4].copy_from_slice(&[0x7f, b'E', b'L', b'F']);
+    data[4] = 1; // ELFCLASS32
+    data[5] = 1; // little endian
+    data[6] = 1; // EV_CURRENT
+    data[16..18].copy_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+    data[18..20].copy_from_slice(&8u16.to_le_bytes()); // EM_MIPS
+    data[20..24].copy_from_slice(&1u32.to_le_bytes());
+    data[24..28].copy_from_slice(&TEXT.to_le_bytes());
+    data[28..32].copy_from_slice(&52u32.to_le_bytes()); // e_phoff
+    data[40..42].copy_from_slice(&52u16.to_le_bytes()); // e_ehsize
+    data[42..44].copy_from_slice(&32u16.to_le_bytes()); // e_phentsize
+    data[44..46].copy_from_slice(&1u16.to_le_bytes()); // e_phnum
+
+    let text_offset = data.len() as u32;
+    for word in [
+        0x2004_0001u32,
+        0x2002_000A,
+        0x0000_000C,
+        0x2004_0007,
+        0x2002_000A,
+        0x0000_000C,
+    ] {
+        data.extend_from_slice(&word.to_le_bytes());
+    }
+    let header = [1, text_offset, TEXT, TEXT, 24, 24, 5, 4];
+    for (i, field) in header.iter().enumerate() {
+        data[52 + i * 4..56 + i * 4].copy_from_slice(&field.to_le_bytes());
+    }
+
+    let names_offset = data.len() as u32;
+    data.extend_from_slice(b"\0_start\0sum\0x.c\0printf\0");
+    let names_size = data.len() as u32 - names_offset;
+    let symtab_offset = data.len() as u32;
+    // name, value, size, info (binding << 4 | type), other, section index
+    let symbols: [(u32, u32, u8, u16); 5] = [
+        (0, 0, 0, 0),
+        (1, TEXT, 0x12, 1),      // global function
+        (8, TEXT + 12, 0x12, 1), // global function
+        (12, 0, 0x04, 0xFFF1),   // file
+        (16, 0, 0x12, 0),        // undefined
+    ];
+    for (name, value, info, section) in symbols {
+        data.extend_from_slice(&name.to_le_bytes());
+        data.extend_from_slice(&value.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&[info, 0]);
+        data.extend_from_slice(&section.to_le_bytes());
+    }
+    let shstrtab_offset = data.len() as u32;
+    data.extend_from_slice(b"\0.text\0.symtab\0.strtab\0.shstrtab\0");
+
+    let table = data.len() as u32;
+    // name, type, flags, addr, offset, size, link, info, addralign, entsize
+    let sections: [[u32; 10]; 5] = [
+        [0; 10],
+        [1, 1, 6, TEXT, text_offset, 24, 0, 0, 4, 0],
+        [7, 2, 0, 0, symtab_offset, 80, 3, 1, 4, 16],
+        [15, 3, 0, 0, names_offset, names_size, 0, 0, 1, 0],
+        [23, 3, 0, 0, shstrtab_offset, 33, 0, 0, 1, 0],
+    ];
+    for header in &sections {
+        for field in header {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+    data[32..36].copy_from_slice(&table.to_le_bytes()); // e_shoff
+    data[46..48].copy_from_slice(&40u16.to_le_bytes()); // e_shentsize
+    data[48..50].copy_from_slice(&5u16.to_le_bytes()); // e_shnum
+    data[50..52].copy_from_slice(&4u16.to_le_bytes()); // e_shstrndx
+    data
+}
+
+fn run(image: &ProgramImage) -> u32 {
+    let mut simulator = Simulator::from_image(image).unwrap();
+    simulator.run().unwrap();
+    simulator.registers.read(4)
+}
+
+#[test]
+fn test_start_at_label() {
+    let image = ProgramImage::from_assembly(SOURCE).unwrap();
+    assert_eq!(run(&image), 1);
+
+    let helper = image.clone().with_start_symbol("helper").unwrap();
+    assert_eq!(helper.entry_point, TEXT + 12);
+    assert_eq!(run(&helper), 7);
+
+    assert!(matches!(
+        image.with_start_symbol("missing"),
+        Err(LoadError::UnknownSymbol(name)) if name == "missing"
+    ));
+}
+
+#[test]
+fn test_elf_symbol_table() {
+    let elf = ElfLoader::load_from_bytes(build_elf()).unwrap();
+    let symbols: Vec<(&str, u32)> = elf
+        .symbols()
+        .iter()
+        .map(|(name, &address)| (name.as_str(), address))
+        .collect();
+    assert_eq!(symbols, [("_start", TEXT), ("sum", TEXT + 12)]);
+
+    let image = ProgramImage::elf(&elf).unwrap();
+    assert_eq!(&image.symbols, elf.symbols());
+    assert_eq!(run(&image), 1);
+    assert_eq!(run(&image.with_start_symbol("sum").unwrap()), 7);
+}
+
+#[test]
+fn test_start_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("program.s");
+    std::fs::write(&source, SOURCE).unwrap();
+
+    for mode in ["functional", "timing"] {
+        let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .arg(mode)
+            .arg("--input")
+            .arg(&source)
+            .arg("--start")
+            .arg("helper");
+        cmd.assert()
+            .success()
+            .stdout(contains("Entry point: 0x0040000C"))
+            .stdout(contains("$4: 7"));
+    }
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--start")
+        .arg("nowhere");
+    cmd.assert().stderr(contains("no symbol named 'nowhere'"));
+}
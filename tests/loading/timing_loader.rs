@@ -0,0 +1,77 @@
+// tests/loading/timing_loader.rs
+// Tests for loading assembled programs with data sections in timing mode
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::assembler::Assembler;
+use vmips_rust::loader::{ProgramImage, DATA_BASE, TEXT_BASE};
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator;
+
+/// Adds the two words of the data section and stores the sum after them
+const DATA_SOURCE: &str = "
+.data
+values: .word 30, 12
+.text
+main:
+    lui $t0, 0x1000
+    lw $v0, 0($t0)
+    lw $v1, 4($t0)
+    add $a0, $v0, $v1
+    sw $a0, 8($t0)
+    addi $v0, $zero, 10
+    syscall
+";
+
+#[test]
+fn test_timing_load_image_places_sections() {
+    let binary = Assembler::new().assemble_string(DATA_SOURCE).unwrap();
+    let image = ProgramImage::from_bytes(&binary).unwrap();
+
+    let mut simulator = Simulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(1024, 2, 32),
+        CacheConfig::new(1024, 2, 32),
+        0x0080_0000,
+    );
+    simulator.load_image(&image).unwrap();
+
+    assert_eq!(simulator.pc, TEXT_BASE);
+    assert_eq!(simulator.memory.read_word(DATA_BASE as usize), Ok(30));
+    assert_eq!(simulator.memory.read_word(DATA_BASE as usize + 4), Ok(12));
+    assert_eq!(
+        simulator.memory.read_word(TEXT_BASE as usize),
+        Ok(0x3C081000)
+    );
+}
+
+#[test]
+fn test_assembler_encodes_offset_base_addresses() {
+    let binary = Assembler::new()
+        .assemble_string(".text\n    lw $t0, 4($sp)\n    sh $t1, -2($t0)\n")
+        .unwrap();
+    let words: Vec<u32> = binary[8..]
+        .chunks(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    assert_eq!(words, vec![0x8FA80004, 0xA509FFFE]);
+}
+
+#[test]
+fn test_cli_timing_runs_data_section_program() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("sum.s");
+    std::fs::write(&path, DATA_SOURCE).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(contains("Starting execution at PC: 0x00400000"))
+        .stdout(contains("Termination reason: program exited"))
+        .stdout(contains("$4: 42"));
+}
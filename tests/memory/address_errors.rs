@@ -0,0 +1,142 @@
+// tests/memory/address_errors.rs
+// Tests for load and store addresses that wrap around the address space
+
+use vmips_rust::errors::SimulationErrorCause;
+use vmips_rust::functional_simulator::instructions::{effective_address, Instruction, NextPc};
+use vmips_rust::functional_simulator::memory::Memory;
+use vmips_rust::functional_simulator::registers::Registers;
+use vmips_rust::functional_simulator::simulator::{Exception, Simulator};
+use vmips_rust::utils::limits::TerminationReason;
+
+#[test]
+fn test_effective_address_boundaries() {
+    assert_eq!(effective_address(0, -4), None);
+    assert_eq!(effective_address(4, -4), Some(0));
+    assert_eq!(effective_address(0, 0x7FFF), Some(0x7FFF));
+    assert_eq!(effective_address(0x7FFF, i16::MIN), None);
+    assert_eq!(effective_address(0x8000, i16::MIN), Some(0));
+    assert_eq!(effective_address(0xFFFF_FFF0, 0xC), Some(0xFFFF_FFFC));
+    assert_eq!(effective_address(0xFFFF_FFFC, 4), None);
+    assert_eq!(effective_address(0xFFFF_FFFF, 0), Some(0xFFFF_FFFF));
+}
+
+#[test]
+fn test_wrapping_load_stops_the_run() {
+    let result = Simulator::run_asm(
+        "
+.text
+    addi $t0, $zero, 7
+    lw $t0, -4($zero)
+    addi $t0, $zero, 9
+",
+    )
+    .unwrap();
+
+    let error = result.error.unwrap();
+    assert_eq!(error.pc, 0x00400004);
+    assert_eq!(error.cause, SimulationErrorCause::AddressError(0, -4));
+    assert_eq!(
+        error.cause.to_string(),
+        "address error: -4(0x00000000) wraps around the address space"
+    );
+    assert_eq!(result.registers.read(8), 7);
+    assert_eq!(
+        result.termination_reason,
+        Some(TerminationReason::MemoryFault)
+    );
+
+    let result = Simulator::run_asm(
+        "
+.text
+    addi $t1, $zero, -4
+    sw $t0, 8($t1)
+",
+    )
+    .unwrap();
+    assert_eq!(
+        result.error.map(|error| error.cause),
+        Some(SimulationErrorCause::AddressError(0xFFFF_FFFC, 8))
+    );
+}
+
+#[test]
+fn test_every_load_and_store_checks_wrapping() {
+    let mut registers = Registers::new();
+    let mut memory = Memory::new(0x10000);
+    registers.write(9, 0xFFFF_FFFF);
+
+    let wrapping = [
+        Instruction::Lb {
+            rt: 8,
+            base: 0,
+            offset: -1,
+        },
+        Instruction::Lbu {
+            rt: 8,
+            base: 0,
+            offset: -1,
+        },
+        Instruction::Lh {
+            rt: 8,
+            base: 0,
+            offset: -2,
+        },
+        Instruction::Lhu {
+            rt: 8,
+            base: 0,
+            offset: -2,
+        },
+        Instruction::Ll {
+            rt: 8,
+            base: 0,
+            offset: -4,
+        },
+        Instruction::LwC1 {
+            ft: 0,
+            base: 0,
+            offset: -4,
+        },
+        Instruction::Sb {
+            rt: 8,
+            base: 9,
+            offset: 1,
+        },
+        Instruction::Sh {
+            rt: 8,
+            base: 9,
+            offset: 1,
+        },
+        Instruction::Sw {
+            rt: 8,
+            base: 9,
+            offset: 1,
+        },
+        Instruction::Sc {
+            rt: 8,
+            base: 9,
+            offset: 1,
+        },
+        Instruction::SwC1 {
+            ft: 0,
+            base: 9,
+            offset: 1,
+        },
+    ];
+    for instruction in &wrapping {
+        assert_eq!(
+            instruction.execute(&mut registers, &mut memory),
+            NextPc::Trap(Exception::AddressError),
+            "{:?}",
+            instruction
+        );
+    }
+
+    // A negative offset that stays at or above zero is a normal access
+    registers.write(10, 4);
+    let load = Instruction::Lw {
+        rt: 8,
+        base: 10,
+        offset: -4,
+    };
+    assert_eq!(load.execute(&mut registers, &mut memory), NextPc::Next);
+}
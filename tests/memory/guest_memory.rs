@@ -0,0 +1,130 @@
+// tests/memory/guest_memory.rs
+// Tests for moving strings, values, structs and arrays between host code
+// and guest memory
+
+use vmips_rust::functional_simulator::guest::{self, Endian, GuestError, GuestValue};
+use vmips_rust::functional_simulator::memory::{MemError, Memory};
+use vmips_rust::timing_simulator::watch::WatchExpression;
+
+/// A guest `struct { uint16_t id; uint16_t flags; int32_t offset; }`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Entry {
+    id: u16,
+    flags: u16,
+    offset: i32,
+}
+
+impl GuestValue for Entry {
+    const SIZE: usize = 8;
+
+    fn decode(bytes: &[u8], endian: Endian) -> Self {
+        Self {
+            id: u16::decode(&bytes[0..2], endian),
+            flags: u16::decode(&bytes[2..4], endian),
+            offset: i32::decode(&bytes[4..8], endian),
+        }
+    }
+
+    fn encode(&self, bytes: &mut [u8], endian: Endian) {
+        self.id.encode(&mut bytes[0..2], endian);
+        self.flags.encode(&mut bytes[2..4], endian);
+        self.offset.encode(&mut bytes[4..8], endian);
+    }
+}
+
+#[test]
+fn test_strings() {
+    let mut memory = Memory::new(0x1000);
+    memory.write_block(0x100, b"hello\0world").unwrap();
+    memory.write_block(0xFFE, b"ab").unwrap();
+    assert_eq!(guest::read_string(&memory, 0x100, 64).unwrap(), "hello");
+    assert_eq!(
+        guest::read_c_string(&memory, 0x106, 5),
+        Err(GuestError::Unterminated {
+            address: 0x106,
+            limit: 5
+        })
+    );
+    assert_eq!(
+        guest::read_c_string(&memory, 0xFFE, 64),
+        Err(GuestError::Memory(MemError::OutOfBounds(0x1000)))
+    );
+    memory.write_block(0x200, &[0xFF, 0xFE, 0]).unwrap();
+    assert_eq!(
+        guest::read_string(&memory, 0x200, 64),
+        Err(GuestError::InvalidUtf8(0x200))
+    );
+
+    // Long strings are cut to fit, and the terminator always written
+    memory.fill(0x300, 16, 0xAA).unwrap();
+    assert_eq!(
+        guest::write_c_string(&mut memory, 0x300, b"truncated", 6),
+        Ok(5)
+    );
+    assert_eq!(memory.read_block(0x300, 7).unwrap(), b"trunc\0\xAA");
+    assert_eq!(guest::write_c_string(&mut memory, 0x300, b"x", 0), Ok(0));
+    assert_eq!(memory.read_byte(0x300).unwrap(), b't');
+}
+
+#[test]
+fn test_values_and_byte_order() {
+    let mut memory = Memory::new(0x1000);
+    guest::write_value(&mut memory, 0x100, &0x1122_3344u32, Endian::Little).unwrap();
+    assert_eq!(memory.read_word(0x100).unwrap(), 0x1122_3344);
+    assert_eq!(memory.read_byte(0x100).unwrap(), 0x44);
+    assert_eq!(
+        guest::read_value::<u32>(&memory, 0x100, Endian::Big).unwrap(),
+        0x4433_2211
+    );
+
+    guest::write_value(&mut memory, 0x200, &-2i16, Endian::Big).unwrap();
+    assert_eq!(memory.read_block(0x200, 2).unwrap(), [0xFF, 0xFE]);
+    guest::write_value(&mut memory, 0x210, &1.5f32, Endian::Little).unwrap();
+    assert_eq!(memory.read_word(0x210).unwrap(), 1.5f32.to_bits());
+    assert_eq!(
+        guest::read_value::<[u16; 2]>(&memory, 0x100, Endian::Little).unwrap(),
+        [0x3344, 0x1122]
+    );
+}
+
+#[test]
+fn test_structs_and_arrays() {
+    let mut memory = Memory::new(0x1000);
+    let entries = [
+        Entry {
+            id: 1,
+            flags: 0x8000,
+            offset: -4,
+        },
+        Entry {
+            id: 2,
+            flags: 0,
+            offset: 0x100,
+        },
+    ];
+    guest::write_array(&mut memory, 0x400, &entries, Endian::Little).unwrap();
+    assert_eq!(
+        memory.read_words(0x400, 4).unwrap(),
+        [0x8000_0001, 0xFFFF_FFFC, 0x0000_0002, 0x0000_0100]
+    );
+    assert_eq!(
+        guest::read_array::<Entry>(&memory, 0x400, 2, Endian::Little).unwrap(),
+        entries
+    );
+    assert_eq!(
+        guest::read_value::<Entry>(&memory, 0x408, Endian::Little).unwrap(),
+        entries[1]
+    );
+
+    // Nothing is written when the array runs out of memory
+    assert!(guest::write_array(&mut memory, 0xFF8, &entries, Endian::Little).is_err());
+    assert_eq!(memory.read_word(0xFF8).unwrap(), 0);
+}
+
+#[test]
+fn test_string_watch_expression() {
+    let watch = WatchExpression::parse("str[0x100]").unwrap();
+    assert_eq!(watch, WatchExpression::String(0x100));
+    assert_eq!(watch.to_string(), "str[0x00000100]");
+    assert!(WatchExpression::parse("str[nowhere]").is_err());
+}
@@ -0,0 +1,8 @@
+// tests/memory/main.rs
+// Tests for guest memory, its errors and host memory limits
+
+mod address_errors;
+mod guest_memory;
+mod memory_blocks;
+mod memory_errors;
+mod memory_limits;
@@ -0,0 +1,107 @@
+// tests/memory/memory_blocks.rs
+// Tests for block reads, writes and fills on Memory
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::memory::{MemAccess, MemError, Memory, MemoryRegion};
+
+#[test]
+fn test_block_round_trip() {
+    let mut memory = Memory::new(0x10000);
+    memory.write_block(0x1001, b"block").unwrap();
+    assert_eq!(memory.read_block(0x1000, 7), Ok(b"\0block\0".to_vec()));
+    assert_eq!(memory.read_byte(0x1003), Ok(b'o'));
+
+    // Words are stored little-endian, as the word accessors store them
+    memory
+        .write_words(0x2000, &[0x11223344, 0xAABBCCDD])
+        .unwrap();
+    assert_eq!(
+        memory.read_block(0x2000, 8),
+        Ok(vec![0x44, 0x33, 0x22, 0x11, 0xDD, 0xCC, 0xBB, 0xAA])
+    );
+    assert_eq!(memory.read_word(0x2004), Ok(0xAABBCCDD));
+    assert_eq!(
+        memory.read_words(0x2000, 2),
+        Ok(vec![0x11223344, 0xAABBCCDD])
+    );
+    assert_eq!(
+        memory.read_words(0x2002, 1),
+        Err(MemError::Misaligned(0x2002))
+    );
+
+    memory.fill(0x2002, 4, 0xEE).unwrap();
+    assert_eq!(
+        memory.read_words(0x2000, 2),
+        Ok(vec![0xEEEE3344, 0xAABBEEEE])
+    );
+
+    // Data above 0x10000000 is translated like single accesses
+    memory.write_block(0x10000010, &[1, 2, 3]).unwrap();
+    assert_eq!(memory.read_block(0x10000010, 3), Ok(vec![1, 2, 3]));
+}
+
+#[test]
+fn test_blocks_are_all_or_nothing() {
+    let mut memory = Memory::new(0x10000);
+    memory.add_memory_region(MemoryRegion {
+        start: 0x3000,
+        end: 0x3100,
+        readable: true,
+        writable: false,
+        executable: false,
+        priority: 100,
+    });
+
+    // The block starts in writable memory but runs into the read-only region
+    assert_eq!(
+        memory.write_block(0x2FFC, &[0xFF; 8]),
+        Err(MemError::PermissionDenied(0x3000, MemAccess::Write))
+    );
+    assert_eq!(
+        memory.fill(0x30FF, 2, 0xFF),
+        Err(MemError::PermissionDenied(0x30FF, MemAccess::Write))
+    );
+    assert_eq!(memory.read_block(0x2FFC, 4), Ok(vec![0; 4]));
+    assert_eq!(memory.fill(0x3100, 4, 0xFF), Ok(()));
+
+    assert_eq!(
+        memory.write_block(0xFFFC, &[1; 8]),
+        Err(MemError::OutOfBounds(0xFFFC))
+    );
+    assert_eq!(memory.read_word(0xFFFC), Ok(0));
+    assert_eq!(
+        memory.read_block(0xFFFF, usize::MAX),
+        Err(MemError::OutOfBounds(0xFFFF))
+    );
+
+    // Loaders can still write read-only memory
+    memory.write_block_init(0x3000, &[7; 4]).unwrap();
+    assert_eq!(memory.read_word(0x3000), Ok(0x07070707));
+}
+
+#[test]
+fn test_what_if_dump_and_restore() {
+    let temp_dir = tempdir().unwrap();
+    let input = temp_dir.path().join("input.bin");
+    let output = temp_dir.path().join("output.bin");
+    std::fs::write(&input, [1, 2, 3, 4, 5, 6]).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("what-if").write_stdin(format!(
+        "restore {input} 0x1800\ndump {output} 0x17FF 8\nload {input} 0x1FFC\nquit\n",
+        input = input.display(),
+        output = output.display()
+    ));
+    cmd.assert()
+        .success()
+        .stdout(contains("Loaded 6 bytes"))
+        .stdout(contains("Wrote 8 bytes from 0x000017FF"))
+        .stdout(contains("load failed: address 0x00001FFC out of bounds"));
+
+    assert_eq!(
+        std::fs::read(&output).unwrap(),
+        vec![0, 1, 2, 3, 4, 5, 6, 0]
+    );
+}
@@ -0,0 +1,90 @@
+// tests/memory/memory_errors.rs
+// Tests for the reasons memory reads and writes fail
+
+use vmips_rust::errors::SimulationErrorCause;
+use vmips_rust::functional_simulator::memory::{MemAccess, MemError, Memory, MemoryRegion};
+use vmips_rust::functional_simulator::simulator::Simulator;
+
+fn read_only_region(start: usize, end: usize) -> MemoryRegion {
+    MemoryRegion {
+        start,
+        end,
+        readable: true,
+        writable: false,
+        executable: false,
+        priority: 100,
+    }
+}
+
+#[test]
+fn test_memory_error_reasons() {
+    let mut memory = Memory::new_simple(1024);
+    assert_eq!(memory.write_halfword(6, 0xBEEF), Ok(()));
+    assert_eq!(memory.read_halfword(6), Ok(0xBEEF));
+    assert_eq!(memory.read_byte(1024), Err(MemError::OutOfBounds(1024)));
+    assert_eq!(memory.write_word(1022, 1), Err(MemError::OutOfBounds(1022)));
+    assert_eq!(memory.read_halfword(3), Err(MemError::Misaligned(3)));
+    assert_eq!(memory.write_word(6, 1), Err(MemError::Misaligned(6)));
+
+    let mut memory = Memory::new(0x10000);
+    memory.add_memory_region(read_only_region(0x2000, 0x3000));
+    assert_eq!(memory.read_word(0x2000), Ok(0));
+    assert_eq!(
+        memory.write_byte(0x2FFF, 1),
+        Err(MemError::PermissionDenied(0x2FFF, MemAccess::Write))
+    );
+    // A word that ends inside the region is refused as well
+    assert_eq!(
+        memory.check(0x1FFE, 4, MemAccess::Write),
+        Err(MemError::PermissionDenied(0x1FFE, MemAccess::Write))
+    );
+    assert_eq!(memory.check(0x1FFC, 4, MemAccess::Write), Ok(()));
+}
+
+#[test]
+fn test_store_to_read_only_region() {
+    let mut simulator = Simulator::new(0x10000);
+    simulator
+        .memory
+        .add_memory_region(read_only_region(0x2000, 0x3000));
+
+    let program = [
+        0x20082000u32, // addi $t0, $zero, 0x2000
+        0x8D090000,    // lw $t1, 0($t0)
+        0xAD090004,    // sw $t1, 4($t0)
+    ];
+    let bytes: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+    simulator.load_program(&bytes);
+
+    let error = simulator.run().unwrap_err();
+    assert_eq!(error.pc, 8);
+    assert_eq!(
+        error.cause,
+        SimulationErrorCause::PermissionDenied(0x2004, MemAccess::Write)
+    );
+    assert_eq!(simulator.memory.read_word(0x2004), Ok(0));
+}
+
+#[test]
+fn test_memory_error_messages() {
+    assert_eq!(
+        MemError::OutOfBounds(0x400).to_string(),
+        "address 0x00000400 out of bounds"
+    );
+    assert_eq!(
+        MemError::Misaligned(0x1002).to_string(),
+        "misaligned access at 0x00001002"
+    );
+    assert_eq!(
+        MemError::PermissionDenied(0x2004, MemAccess::Write).to_string(),
+        "write not permitted at 0x00002004"
+    );
+    assert_eq!(
+        SimulationErrorCause::from(MemError::Misaligned(0x1002)),
+        SimulationErrorCause::MisalignedAccess(0x1002)
+    );
+    assert_eq!(
+        SimulationErrorCause::PermissionDenied(0x2004, MemAccess::Read).to_string(),
+        "read not permitted at 0x00002004"
+    );
+}
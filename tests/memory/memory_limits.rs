@@ -0,0 +1,188 @@
+// tests/memory/memory_limits.rs
+// Tests for host memory accounting and the memory cap
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
+use vmips_rust::utils::limits::{
+    format_bytes, parse_byte_size, MemoryUsage, RunLimits, TerminationReason,
+};
+
+/// Enough memory for text at 0x00400000
+const MEMORY_SIZE: usize = 0x0080_0000;
+
+/// Counts down from 20000, long enough to fill a timeline
+const COUNT_PROGRAM: &str = "
+.text
+    li $t0, 20000
+loop:
+    addi $t0, $t0, -1
+    bne $t0, $zero, loop
+    li $v0, 10
+    syscall
+";
+
+fn timing_simulator(memory_size: usize) -> TimingSimulator {
+    TimingSimulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(1024, 2, 32),
+        CacheConfig::new(2048, 4, 64),
+        memory_size,
+    )
+}
+
+#[test]
+fn test_memory_is_accounted_per_component() {
+    let pipeline_config = PipelineConfig::new(5);
+    let instr_cache = CacheConfig::new(1024, 2, 32);
+    let data_cache = CacheConfig::new(2048, 4, 64);
+    let projected = TimingSimulator::projected_memory_usage(
+        &pipeline_config,
+        &instr_cache,
+        &data_cache,
+        MEMORY_SIZE,
+    );
+    let mut simulator = timing_simulator(MEMORY_SIZE);
+    // The simulator and each cache keep their own copy of the image
+    assert_eq!(simulator.memory_usage(), projected);
+    assert_eq!(projected.memory, 3 * MEMORY_SIZE);
+    assert!(projected.caches > 1024 + 2048);
+    assert_eq!(projected.traces, 0);
+
+    let image = ProgramImage::from_assembly(COUNT_PROGRAM).unwrap();
+    simulator.load_image(&image).unwrap();
+    simulator.enable_timeline();
+    simulator.run();
+    let usage = simulator.memory_usage();
+    assert_eq!(usage.memory, projected.memory);
+    assert!(usage.traces > 40_000 * 8);
+    assert_eq!(usage.total(), usage.memory + usage.caches + usage.traces);
+
+    let functional = FunctionalSimulator::new(MEMORY_SIZE);
+    assert_eq!(
+        functional.memory_usage(),
+        FunctionalSimulator::projected_memory_usage(MEMORY_SIZE)
+    );
+
+    let usage = MemoryUsage {
+        memory: 3 << 20,
+        caches: 1536,
+        traces: 100,
+    };
+    assert_eq!(
+        usage.to_string(),
+        "3.0 MiB (memory 3.0 MiB, caches 1.5 KiB, traces 100 B)"
+    );
+    assert_eq!(format_bytes(5 << 30), "5.0 GiB");
+    assert_eq!(parse_byte_size("65536"), Ok(65536));
+    assert_eq!(parse_byte_size("64K"), Ok(64 << 10));
+    assert_eq!(parse_byte_size("512MiB"), Ok(512 << 20));
+    assert_eq!(parse_byte_size("2g"), Ok(2 << 30));
+    assert!(parse_byte_size("12X").is_err());
+    assert!(parse_byte_size("M").is_err());
+}
+
+#[test]
+fn test_runs_stop_at_the_memory_cap() {
+    let image = ProgramImage::from_assembly(COUNT_PROGRAM).unwrap();
+    let mut simulator = timing_simulator(MEMORY_SIZE);
+    simulator.load_image(&image).unwrap();
+    simulator.enable_timeline();
+    let cap = simulator.memory_usage().total() + 64 * 1024;
+    simulator.set_max_memory(Some(cap));
+    simulator.run();
+
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::MemoryLimit)
+    );
+    let usage = simulator.memory_usage();
+    assert!(usage.total() > cap);
+    let error = simulator.limits.check_memory(&usage).unwrap_err();
+    assert!(error.ends_with(&format!("exceeds the cap of {}", format_bytes(cap))));
+    // The run stopped long before the loop finished
+    let retired = simulator.timeline.as_ref().unwrap().len();
+    assert!(retired > 0 && retired < 20_000);
+
+    // Without recordings the same cap is never reached
+    let mut simulator = timing_simulator(MEMORY_SIZE);
+    simulator.load_image(&image).unwrap();
+    simulator.set_max_memory(Some(cap));
+    simulator.run();
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::Exited)
+    );
+
+    // The functional simulator checks before its first instruction
+    let mut functional = FunctionalSimulator::new(MEMORY_SIZE);
+    functional.load_image(&image).unwrap();
+    functional.set_max_memory(Some(MEMORY_SIZE / 2));
+    functional.run().unwrap();
+    assert_eq!(
+        functional.termination_reason,
+        Some(TerminationReason::MemoryLimit)
+    );
+    assert_eq!(functional.step_count, 0);
+    assert_eq!(RunLimits::default().max_memory, None);
+}
+
+#[test]
+fn test_memory_cap_from_the_command_line() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("count.s");
+    std::fs::write(&source, COUNT_PROGRAM).unwrap();
+
+    // A memory size over the cap is refused before it is allocated
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--max-memory")
+        .arg("4M");
+    cmd.assert()
+        .success()
+        .stderr(contains(
+            "Error: host memory 8.0 MiB (memory 8.0 MiB, caches 0 B, traces 0 B) exceeds the cap of 4.0 MiB",
+        ))
+        .stdout(contains("Running functional simulator").not());
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&source)
+        .arg("--max-memory")
+        .arg("1G");
+    cmd.assert().success().stdout(contains("Host memory: "));
+
+    // A run that grows past the cap stops, and only the front end reports it
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&source)
+        .arg("--trace")
+        .arg("trace.json")
+        .arg("--max-memory")
+        .arg("24792K");
+    cmd.assert()
+        .success()
+        .stdout(contains("Termination reason: memory limit reached"))
+        .stdout(contains("Memory limit: host memory"))
+        .stdout(contains("Stopping simulation").not());
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("timing")
+        .arg("--max-memory")
+        .arg("12X")
+        .assert()
+        .failure()
+        .stderr(contains("invalid size unit"));
+}
@@ -0,0 +1,143 @@
+// tests/pipeline/control_hazards.rs
+// Tests for flushing, stalling and delay slots as control hazard schemes
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::config::{
+    BranchPredictorType, CacheConfig, ControlHazardScheme, PipelineConfig,
+};
+use vmips_rust::timing_simulator::simulator::{ExecutionMode, Simulator};
+
+/// Counts $t0 down from 3; the instruction after the branch counts in $t2
+const LOOP_PROGRAM: [u32; 6] = [
+    0x20080003, // addi $t0, $zero, 3
+    0x21290001, // loop: addi $t1, $t1, 1
+    0x2108FFFF, // addi $t0, $t0, -1
+    0x1500FFFD, // bne $t0, $zero, loop
+    0x214A0001, // addi $t2, $t2, 1
+    0x00000000, // nop
+];
+
+fn run(scheme: ControlHazardScheme, program: &[u32]) -> Simulator {
+    let config = PipelineConfig::new(5)
+        .with_branch_prediction(false, BranchPredictorType::TwoBit)
+        .with_control_hazards(scheme);
+    let mut simulator = Simulator::new(
+        config,
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    simulator.visualization = None;
+    for (i, &word) in program.iter().enumerate() {
+        simulator.memory.write_word_init(i * 4, word);
+    }
+    simulator.enable_timeline();
+    simulator.run();
+    simulator
+}
+
+// Branch stalls, mispredictions and delay slots of a finished run
+fn control_counts(simulator: &Simulator) -> (usize, usize, usize) {
+    match &simulator.execution_mode {
+        ExecutionMode::InOrder(pipeline) => (
+            pipeline.branch_stalls,
+            pipeline.branch_mispredictions,
+            pipeline.delay_slots,
+        ),
+        ExecutionMode::OutOfOrder(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn test_schemes_charge_branches_differently() {
+    // Predict not taken: the two taken branches are flushed
+    let flush = run(ControlHazardScheme::Flush, &LOOP_PROGRAM);
+    assert_eq!(control_counts(&flush), (4, 2, 0));
+    assert_eq!(flush.run_totals.branch_stalls, 4);
+    assert_eq!(flush.registers.read(10), 1);
+
+    // Every branch waits the full two cycles until it resolves in EX
+    let stall = run(ControlHazardScheme::Stall, &LOOP_PROGRAM);
+    assert_eq!(control_counts(&stall), (6, 0, 0));
+    assert_eq!(stall.registers.read(9), 3);
+    assert_eq!(stall.registers.read(10), 1);
+
+    // The slot hides one of the two cycles, and runs after every branch
+    let delay = run(ControlHazardScheme::DelaySlot, &LOOP_PROGRAM);
+    assert_eq!(control_counts(&delay), (3, 0, 3));
+    assert_eq!(delay.registers.read(9), 3);
+    assert_eq!(delay.registers.read(10), 3);
+    let timeline = delay.timeline.as_ref().unwrap();
+    assert_eq!(timeline.len(), 13);
+    assert_eq!(timeline[4].pc, 0x10);
+    assert_eq!(timeline[5].pc, 0x04);
+
+    let stats = match &delay.execution_mode {
+        ExecutionMode::InOrder(pipeline) => pipeline.print_statistics(),
+        ExecutionMode::OutOfOrder(_) => unreachable!(),
+    };
+    assert!(stats.contains("Control Hazard Scheme: delay-slot"));
+    assert!(stats.contains("Branch Stall Cycles: 3"));
+    assert!(stats.contains("Delay Slots Executed: 3"));
+}
+
+#[test]
+fn test_jumps_have_delay_slots() {
+    let program = [
+        0x0C000003, // jal 0x0C
+        0x20080005, // addi $t0, $zero, 5
+        0x20080006, // addi $t0, $zero, 6
+        0x20090009, // addi $t1, $zero, 9
+        0x00000000, // nop
+    ];
+    let delay = run(ControlHazardScheme::DelaySlot, &program);
+    assert_eq!(delay.registers.read(8), 5);
+    assert_eq!(delay.registers.read(9), 9);
+    // The return address skips the slot
+    assert_eq!(delay.registers.read(31), 8);
+    assert_eq!(control_counts(&delay), (0, 0, 1));
+
+    let flush = run(ControlHazardScheme::Flush, &program);
+    assert_eq!(flush.registers.read(8), 0);
+    assert_eq!(flush.registers.read(31), 4);
+}
+
+#[test]
+fn test_control_setting_in_variants() {
+    let variant = TimingVariant::parse("control=stall,prediction=off").unwrap();
+    assert_eq!(
+        variant.pipeline_config.control_hazard_scheme,
+        ControlHazardScheme::Stall
+    );
+    assert!(variant.describe().ends_with(",control=stall"));
+    assert_eq!(
+        TimingVariant::parse("control=guess").unwrap_err(),
+        "Unknown control hazard scheme 'guess'"
+    );
+    assert_eq!(
+        ControlHazardScheme::from_name("delay-slot"),
+        Some(ControlHazardScheme::DelaySlot)
+    );
+
+    let dir = tempdir().unwrap();
+    let program_path = dir.path().join("loop.bin");
+    let bytes: Vec<u8> = LOOP_PROGRAM.iter().flat_map(|w| w.to_le_bytes()).collect();
+    std::fs::write(&program_path, bytes).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("compare")
+        .arg("--input")
+        .arg(&program_path)
+        .arg("--baseline")
+        .arg("control=stall")
+        .arg("--variant")
+        .arg("control=delay-slot");
+    cmd.assert()
+        .success()
+        .stdout(contains("A: control=stall"))
+        .stdout(contains("B: control=delay-slot"))
+        .stdout(contains("Instructions: A 11, B 13"));
+}
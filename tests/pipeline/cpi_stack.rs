@@ -0,0 +1,132 @@
+// tests/pipeline/cpi_stack.rs
+// Tests for the CPI stack of the timing simulator
+
+use assert_cmd::Command;
+use tempfile::tempdir;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator;
+use vmips_rust::timing_simulator::stats::{CpiStack, StallBreakdown, StatsCounters};
+
+/// A loop whose load feeds the next instruction
+const LOOP_PROGRAM: [(usize, u32); 4] = [
+    (0x00, 0x8C090100), // lw $t1, 0x100($zero)
+    (0x04, 0x01294020), // add $t0, $t1, $t1
+    (0x08, 0x21080001), // addi $t0, $t0, 1
+    (0x0C, 0x08000000), // j 0
+];
+
+/// Sums an array, ending with an exit syscall
+const SUM_PROGRAM: &str = "
+.data
+values: .word 1, 2, 3, 4, 5, 6, 7, 8
+.text
+    la $t0, values
+    li $t1, 8
+    li $s0, 0
+sum:
+    lw $t2, 0($t0)
+    add $s0, $s0, $t2
+    addi $t0, $t0, 4
+    addi $t1, $t1, -1
+    bne $t1, $zero, sum
+    li $v0, 10
+    syscall
+";
+
+fn run_loop(instructions: usize) -> Simulator {
+    let mut simulator = Simulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    simulator.visualization = None;
+    for &(addr, word) in &LOOP_PROGRAM {
+        simulator.memory.write_word_init(addr, word);
+    }
+    simulator.set_max_instructions(instructions);
+    simulator.run();
+    simulator
+}
+
+#[test]
+fn test_components_add_up_to_cpi() {
+    let simulator = run_loop(40);
+    let totals = &simulator.run_totals;
+    assert!(totals.stall_breakdown.total() < totals.cycles);
+    assert!(totals.stall_breakdown.icache > 0);
+    assert!(totals.stall_breakdown.dcache > 0);
+
+    let stack = CpiStack::new(totals);
+    assert_eq!(stack.instructions, 40);
+    let sum: f64 = stack.components().iter().map(|&(_, cpi)| cpi).sum();
+    assert!((sum - stack.cpi).abs() < 1e-9);
+    assert!(stack.base > 0.0);
+}
+
+#[test]
+fn test_render_normalizes_bars() {
+    let counters = StatsCounters {
+        instructions: 10,
+        cycles: 20,
+        stalls: 10,
+        stall_breakdown: StallBreakdown {
+            branch: 5,
+            dcache: 5,
+            ..StallBreakdown::default()
+        },
+        ..StatsCounters::default()
+    };
+    let stack = CpiStack::new(&counters);
+    assert_eq!(stack.cpi, 2.0);
+    assert_eq!(stack.base, 1.0);
+    assert_eq!(stack.branch, 0.5);
+
+    let rendered = stack.render();
+    assert!(rendered.starts_with("CPI stack: 2.000 CPI over 10 instructions"));
+    let bar = |name: &str| {
+        let line = rendered
+            .lines()
+            .find(|line| line.trim_start().starts_with(name))
+            .unwrap();
+        line.matches('#').count()
+    };
+    assert_eq!(bar("base"), 25);
+    assert_eq!(bar("branch"), 13);
+    assert_eq!(bar("raw"), 0);
+
+    // An empty run renders without dividing by zero
+    assert!(CpiStack::new(&StatsCounters::default())
+        .render()
+        .contains("0.0%"));
+}
+
+#[test]
+fn test_stats_json_has_cpi_stack() {
+    let simulator = run_loop(20);
+    let json: serde_json::Value =
+        serde_json::from_str(&simulator.stats_report().to_json()).unwrap();
+    let stack = &json["cpi_stack"];
+    assert_eq!(stack["instructions"].as_u64(), Some(20));
+    for name in ["base", "branch", "icache", "dcache", "structural", "raw"] {
+        assert!(stack[name].is_number(), "{} missing", name);
+    }
+}
+
+#[test]
+fn test_cli_prints_cpi_stack() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("sum.asm");
+    std::fs::write(&source, SUM_PROGRAM).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&source)
+        .arg("--cpi-stack");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let stdout = String::from_utf8(output).unwrap();
+    assert!(stdout.contains("CPI stack:"), "{}", stdout);
+    assert!(stdout.contains("structural"));
+}
@@ -0,0 +1,66 @@
+// tests/pipeline/cpu_frequency.rs
+// Tests for the core clock frequency and the simulated time it gives cycles
+
+use vmips_rust::functional_simulator::registers::DEFAULT_CLOCK_HZ;
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::{ExecutionMode, Simulator};
+use vmips_rust::utils::clock::{format_frequency, format_time, parse_frequency};
+
+#[test]
+fn test_parse_frequency() {
+    assert_eq!(parse_frequency("100000000"), Ok(100_000_000));
+    assert_eq!(parse_frequency("250MHz"), Ok(250_000_000));
+    assert_eq!(parse_frequency("1.5GHz"), Ok(1_500_000_000));
+    assert_eq!(parse_frequency("800k"), Ok(800_000));
+    assert_eq!(parse_frequency("32768 Hz"), Ok(32768));
+    assert!(parse_frequency("fast").is_err());
+    assert!(parse_frequency("0MHz").is_err());
+    assert!(parse_frequency("-1GHz").is_err());
+}
+
+#[test]
+fn test_format_frequency_and_time() {
+    assert_eq!(format_frequency(DEFAULT_CLOCK_HZ), "100 MHz");
+    assert_eq!(format_frequency(1_500_000_000), "1.5 GHz");
+    assert_eq!(format_frequency(32768), "32.768 kHz");
+    assert_eq!(format_frequency(50), "50 Hz");
+    assert_eq!(format_time(3_000_000_000, 1_000_000_000), "3.000 s");
+    assert_eq!(format_time(2_500_000, 1_000_000_000), "2.500 ms");
+    assert_eq!(format_time(1500, 1_000_000), "1.500 ms");
+    assert_eq!(format_time(15, 1_500_000_000), "10.000 ns");
+}
+
+#[test]
+fn test_timing_simulator_reports_simulated_time() {
+    let image = ProgramImage::from_assembly(
+        "
+.text
+    rdhwr $s0, $31
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+    let mut simulator = Simulator::new(
+        PipelineConfig::new(5).with_clock_hz(2_000_000_000),
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        image.memory_required().next_power_of_two(),
+    );
+    simulator.visualization = None;
+    simulator.load_image(&image).unwrap();
+    simulator.run();
+
+    // The guest reads the frequency in kilohertz
+    assert_eq!(simulator.registers.read(16), 2_000_000);
+    let cycles = simulator.run_totals.cycles as f64;
+    assert_eq!(simulator.simulated_seconds(), cycles / 2e9);
+    match &simulator.execution_mode {
+        ExecutionMode::InOrder(pipeline) => {
+            let stats = pipeline.print_statistics();
+            assert!(stats.contains(" ns at 2 GHz\n"), "{}", stats);
+        },
+        ExecutionMode::OutOfOrder(_) => unreachable!(),
+    }
+}
@@ -0,0 +1,249 @@
+// tests/pipeline/event_log.rs
+// Tests for the cycle-stamped event log of the timing simulator and its queries
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig, TomasuloConfig};
+use vmips_rust::timing_simulator::events::{
+    CacheKind, Event, EventCategory, EventKind, EventLog, EventQuery, EventSummary,
+};
+use vmips_rust::timing_simulator::simulator::{ExecutionMode, Simulator};
+use vmips_rust::timing_simulator::stats::CpiStack;
+
+/// A loop of three, then a load and a store
+const LOOP_PROGRAM: [u32; 6] = [
+    0x20080003, // addi $t0, $zero, 3
+    0x2108FFFF, // addi $t0, $t0, -1
+    0x1500FFFE, // bne $t0, $zero, -2
+    0x8C091000, // lw $t1, 0x1000($zero)
+    0xAC091004, // sw $t1, 0x1004($zero)
+    0x00000000, // nop
+];
+
+fn run() -> Simulator {
+    let mut simulator = Simulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    simulator.visualization = None;
+    for (i, &word) in LOOP_PROGRAM.iter().enumerate() {
+        simulator.memory.write_word_init(i * 4, word);
+    }
+    simulator.enable_event_log().unwrap();
+    simulator.enable_timeline();
+    simulator.run();
+    simulator
+}
+
+#[test]
+fn test_summary_matches_pipeline_counters() {
+    let simulator = run();
+    let log = simulator.events.as_ref().unwrap();
+    let summary = log.summary();
+    let timeline = simulator.timeline.as_ref().unwrap();
+    assert_eq!(summary.instructions, timeline.len());
+    assert_eq!(summary.branches, 3);
+    assert_eq!((summary.loads, summary.stores), (1, 1));
+
+    match &simulator.execution_mode {
+        ExecutionMode::InOrder(pipeline) => {
+            let caches = &pipeline.cache_hierarchy;
+            let icache = &caches.l1_instr_cache.stats;
+            let dcache = &caches.l1_data_cache.stats;
+            assert_eq!(summary.stalls, pipeline.stall_breakdown);
+            assert_eq!(summary.mispredictions, pipeline.branch_mispredictions);
+            assert_eq!(
+                (summary.icache_accesses, summary.icache_misses),
+                (icache.accesses, icache.misses)
+            );
+            assert_eq!(
+                (summary.dcache_accesses, summary.dcache_misses),
+                (dcache.accesses, dcache.misses)
+            );
+        },
+        ExecutionMode::OutOfOrder(_) => unreachable!(),
+    }
+
+    // Every event carries its instruction's issue cycle
+    for timing in timeline {
+        let query = EventQuery::new()
+            .pc(timing.pc)
+            .cycles(timing.issue_cycle..=timing.issue_cycle);
+        assert!(log.query(&query).any(|event| matches!(
+            event.kind,
+            EventKind::Issue { word, .. } if word == timing.word
+        )));
+    }
+}
+
+#[test]
+fn test_statistics_are_counted_from_the_events() {
+    let simulator = run();
+    let summary = simulator.events.as_ref().unwrap().summary();
+    assert_eq!(simulator.event_totals, summary);
+    let totals = simulator.run_totals;
+    assert_eq!(totals, summary.counters(totals.instructions, totals.cycles));
+    let report = simulator.stats_report();
+    assert_eq!(report.cpi_stack, CpiStack::new(&totals));
+    assert_eq!(report.total.dcache_accesses, summary.dcache_accesses);
+
+    // The same statistics without a log
+    let mut unlogged = Simulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    unlogged.visualization = None;
+    for (i, &word) in LOOP_PROGRAM.iter().enumerate() {
+        unlogged.memory.write_word_init(i * 4, word);
+    }
+    unlogged.run();
+    assert!(unlogged.events.is_none());
+    assert_eq!(unlogged.event_totals, summary);
+    assert_eq!(unlogged.run_totals, totals);
+}
+
+#[test]
+fn test_queries_filter_by_category_pc_and_cycles() {
+    let simulator = run();
+    let log = simulator.events.as_ref().unwrap();
+
+    let branches = EventQuery::new().category(EventCategory::Predictor);
+    let taken: Vec<bool> = log
+        .query(&branches)
+        .map(|event| match event.kind {
+            EventKind::Branch { taken, .. } => taken,
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(taken, vec![true, true, false]);
+
+    let query = EventQuery::parse("category=cache,pc=0x10").unwrap();
+    let accesses: Vec<&Event> = log.query(&query).collect();
+    assert_eq!(accesses.len(), 2);
+    assert!(accesses.iter().any(|event| matches!(
+        event.kind,
+        EventKind::CacheAccess {
+            cache: CacheKind::Data,
+            address: 0x1004,
+            ..
+        }
+    )));
+
+    let query = EventQuery::parse("category=memory+predictor,pc=0x8-0x10").unwrap();
+    assert_eq!(log.query(&query).count(), 5);
+    assert_eq!(
+        EventSummary::new(log.query(&EventQuery::parse("pc=0x4").unwrap())).instructions,
+        3
+    );
+    let first = log.events()[0].cycle;
+    assert!(log
+        .query(&EventQuery::new().cycles(0..=first))
+        .all(|event| event.pc == 0));
+
+    assert!(EventQuery::parse("category=tlb").is_err());
+    assert!(EventQuery::parse("pc=0x20-0x10").is_err());
+    assert!(EventQuery::parse("cycles").is_err());
+}
+
+#[test]
+fn test_event_log_needs_the_in_order_pipeline() {
+    let mut simulator = Simulator::new(
+        PipelineConfig::new(5).with_tomasulo(true, TomasuloConfig::new()),
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    assert_eq!(
+        simulator.enable_event_log(),
+        Err("The event log needs the in-order pipeline".to_string())
+    );
+    assert!(simulator.events.is_none());
+}
+
+#[test]
+fn test_event_log_file_round_trip() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("events.jsonl");
+    let mut log = EventLog::to_file(&path).unwrap();
+    let events = [
+        Event::new(
+            1,
+            0x400000,
+            EventKind::Issue {
+                word: 0x20080003,
+                cycles: 1,
+            },
+        ),
+        Event::new(
+            3,
+            0x400008,
+            EventKind::MemoryAccess {
+                address: 0x1000,
+                store: true,
+            },
+        ),
+    ];
+    for event in events {
+        log.emit(event);
+    }
+    log.flush().unwrap();
+    assert_eq!(log.len(), 2);
+    assert!(log.events().is_empty());
+
+    let loaded = EventLog::load(&path).unwrap();
+    assert_eq!(loaded.events(), &events);
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.starts_with("{\"cycle\":1,\"pc\":4194304,\"event\":\"issue\""));
+
+    std::fs::write(&path, "{\"cycle\":1}\n").unwrap();
+    assert!(EventLog::load(&path).is_err());
+}
+
+#[test]
+fn test_event_log_options() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("events.jsonl");
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("timing")
+        .arg("--input")
+        .arg("examples/programs/bubble_sort.s")
+        .arg("--event-log")
+        .arg(&path);
+    cmd.assert().success().stdout(contains("events written to"));
+
+    // A log that cannot be created fails the run before it starts
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("timing")
+        .arg("--input")
+        .arg("examples/programs/bubble_sort.s")
+        .arg("--event-log")
+        .arg(dir.path().join("missing").join("events.jsonl"));
+    cmd.assert()
+        .failure()
+        .stderr(contains("Error: Failed to create"))
+        .stdout(contains("Starting simulation").not());
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("events").arg("--input").arg(&path).arg("--summary");
+    cmd.assert()
+        .success()
+        .stdout(contains("Event Summary:"))
+        .stdout(contains("Branches: "));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("events")
+        .arg("--input")
+        .arg(&path)
+        .arg("--query")
+        .arg("category=predictor");
+    cmd.assert()
+        .success()
+        .stdout(contains("branch "))
+        .stdout(contains("load ").not());
+}
@@ -0,0 +1,20 @@
+// tests/pipeline/main.rs
+// Tests for the timing simulator's pipeline models and the statistics they report
+
+mod control_hazards;
+mod cpi_stack;
+mod cpu_frequency;
+mod event_log;
+mod memory_port;
+mod pipeline_depth;
+mod pipeline_history;
+mod predictor_state;
+mod regions;
+mod sampling;
+mod stats_intervals;
+mod stress_programs;
+mod tlb;
+mod tomasulo;
+mod tomasulo_scenarios;
+mod trace_sampling;
+mod wrong_path;
@@ -0,0 +1,92 @@
+// tests/pipeline/memory_port.rs
+// Tests for the structural hazard of a single memory port shared by fetch
+// and the memory stage
+
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::pipeline::HazardType;
+use vmips_rust::timing_simulator::simulator::{ExecutionMode, Simulator};
+
+/// A load followed by independent instructions
+const LOAD_PROGRAM: [u32; 6] = [
+    0x8C091000, // lw $t1, 0x1000($zero)
+    0x200A0001, // addi $t2, $zero, 1
+    0x200B0002, // addi $t3, $zero, 2
+    0x200C0003, // addi $t4, $zero, 3
+    0x200D0004, // addi $t5, $zero, 4
+    0x00000000, // nop
+];
+
+// Run the program and return the timeline's stall cycles and the pipeline's
+// memory port conflicts
+fn run(config: PipelineConfig) -> (Vec<usize>, usize, usize) {
+    let mut simulator = Simulator::new(
+        config,
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    simulator.visualization = None;
+    for (i, &word) in LOAD_PROGRAM.iter().enumerate() {
+        simulator.memory.write_word_init(i * 4, word);
+    }
+    simulator.memory.write_word_init(0x1000, 21);
+    simulator.enable_timeline();
+    simulator.run();
+    assert_eq!(simulator.registers.read(9), 21);
+    assert_eq!(simulator.registers.read(13), 4);
+
+    let stalls = simulator
+        .timeline
+        .unwrap()
+        .iter()
+        .map(|timing| timing.stall_cycles)
+        .collect();
+    match simulator.execution_mode {
+        ExecutionMode::InOrder(pipeline) => {
+            let structural = pipeline
+                .hazard_stats
+                .iter()
+                .find(|&&(hazard, _)| hazard == HazardType::Structural)
+                .map_or(0, |&(_, count)| count);
+            (stalls, pipeline.memory_port_stalls, structural)
+        },
+        ExecutionMode::OutOfOrder(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn test_fetch_waits_for_load_without_caches() {
+    let split = PipelineConfig::new(5).with_caches(false);
+    let (split_stalls, conflicts, _) = run(split.clone());
+    assert_eq!(conflicts, 0);
+
+    // The third instruction after the load is fetched while it is in MEM
+    let (unified_stalls, conflicts, structural) = run(split.with_unified_memory_port(true));
+    assert_eq!(conflicts, 1);
+    assert!(structural >= 1);
+    assert_eq!(unified_stalls[3], split_stalls[3] + 1);
+    for index in [0, 1, 2, 4] {
+        assert_eq!(unified_stalls[index], split_stalls[index]);
+    }
+}
+
+#[test]
+fn test_cache_hits_avoid_the_port() {
+    // The load misses, but the program sits in one instruction cache line
+    // so the fetch behind it hits and needs no port
+    let config = PipelineConfig::new(5).with_unified_memory_port(true);
+    let (_, conflicts, _) = run(config);
+    assert_eq!(conflicts, 0);
+}
+
+#[test]
+fn test_memory_port_setting_in_variants() {
+    let variant = TimingVariant::parse("caches=off,memory-port=unified").unwrap();
+    assert!(variant.pipeline_config.unified_memory_port);
+    assert!(variant.describe().contains(",memory-port=unified,"));
+    assert!(TimingVariant::default()
+        .describe()
+        .contains(",memory-port=split,"));
+    assert!(TimingVariant::parse("memory-port=dual").is_err());
+}
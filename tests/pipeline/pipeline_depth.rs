@@ -0,0 +1,160 @@
+// tests/pipeline/pipeline_depth.rs
+// Tests for named pipeline stages, deeper pipelines and per-stage latencies
+
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::pipeline::PipelineStageType;
+use vmips_rust::timing_simulator::simulator::{ExecutionMode, Simulator};
+
+/// A load followed by two dependent adds
+const DEPENDENT_PROGRAM: [u32; 4] = [
+    0x8C091000, // lw $t1, 0x1000($zero)
+    0x01294020, // add $t0, $t1, $t1
+    0x01084020, // add $t0, $t0, $t0
+    0x00000000, // nop
+];
+
+fn simulator(config: PipelineConfig) -> Simulator {
+    let mut simulator = Simulator::new(
+        config,
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    simulator.visualization = None;
+    for (i, &word) in DEPENDENT_PROGRAM.iter().enumerate() {
+        simulator.memory.write_word_init(i * 4, word);
+    }
+    simulator.memory.write_word_init(0x1000, 21);
+    simulator
+}
+
+// Stall cycles of the two adds
+fn add_stalls(config: PipelineConfig) -> (usize, usize) {
+    let mut simulator = simulator(config);
+    simulator.enable_timeline();
+    simulator.run();
+    assert_eq!(simulator.registers.read(8), 84);
+    let timeline = simulator.timeline.unwrap();
+    (timeline[1].stall_cycles, timeline[2].stall_cycles)
+}
+
+#[test]
+fn test_stage_layouts() {
+    let names = |config: &PipelineConfig| -> Vec<String> {
+        config
+            .stages
+            .iter()
+            .map(|stage| stage.name.clone())
+            .collect()
+    };
+    assert_eq!(
+        names(&PipelineConfig::new(5)),
+        ["IF", "ID", "EX", "MEM", "WB"]
+    );
+    assert_eq!(names(&PipelineConfig::new(3)), ["IF", "EX", "WB"]);
+    assert_eq!(
+        names(&PipelineConfig::new(7)),
+        ["IF", "ID", "EX1", "EX2", "EX3", "MEM", "WB"]
+    );
+
+    let r4000 = PipelineConfig::r4000();
+    assert_eq!(r4000.num_stages, 8);
+    assert_eq!(r4000.stage_latencies, vec![1; 8]);
+    assert_eq!(
+        names(&r4000),
+        ["IF", "IS", "RF", "EX", "DF", "DS", "TC", "WB"]
+    );
+    assert_eq!(r4000.stages[5].kind, PipelineStageType::Memory);
+
+    let custom = PipelineConfig::new(5)
+        .with_stages(&[
+            ("F", PipelineStageType::Fetch),
+            ("X", PipelineStageType::Execute),
+            ("W", PipelineStageType::Writeback),
+        ])
+        .unwrap();
+    assert_eq!(custom.num_stages, 3);
+    let simulator = simulator(custom.with_latencies(vec![1, 2, 1]));
+    match &simulator.execution_mode {
+        ExecutionMode::InOrder(pipeline) => {
+            assert_eq!(pipeline.stages.len(), 3);
+            assert_eq!(pipeline.stages[1].name, "X");
+            assert_eq!(pipeline.stages[1].latency, 2);
+            assert_eq!(pipeline.stage_start(2), 3);
+            assert_eq!(pipeline.issue_interval(), 2);
+        },
+        ExecutionMode::OutOfOrder(_) => panic!("expected the in-order pipeline"),
+    }
+
+    // Kinds out of pipeline order and too few stages are rejected
+    let error = PipelineConfig::new(5)
+        .with_stages(&[
+            ("IF", PipelineStageType::Fetch),
+            ("WB", PipelineStageType::Writeback),
+            ("EX", PipelineStageType::Execute),
+        ])
+        .unwrap_err();
+    assert_eq!(error, "Stage kinds must be in pipeline order");
+    assert!(PipelineConfig::try_new(2).is_err());
+    assert!(PipelineConfig::try_new(3).is_ok());
+}
+
+#[test]
+fn test_hazards_follow_stage_layout() {
+    let penalty = |config: PipelineConfig| match simulator(config).execution_mode {
+        ExecutionMode::InOrder(pipeline) => pipeline.branch_penalty(),
+        ExecutionMode::OutOfOrder(_) => unreachable!(),
+    };
+    // Branches resolve at the end of the first execute stage
+    assert_eq!(penalty(PipelineConfig::new(5)), 2);
+    assert_eq!(penalty(PipelineConfig::new(3)), 1);
+    assert_eq!(penalty(PipelineConfig::r4000()), 3);
+
+    // Classic five stages: one load-use stall with forwarding, two without
+    assert_eq!(add_stalls(PipelineConfig::new(5)), (1, 0));
+    assert_eq!(
+        add_stalls(PipelineConfig::new(5).with_forwarding(false)),
+        (2, 2)
+    );
+    // The R4000 load result arrives after DF, DS and TC
+    assert_eq!(add_stalls(PipelineConfig::r4000()), (3, 0));
+    assert_eq!(
+        add_stalls(PipelineConfig::r4000().with_forwarding(false)),
+        (4, 4)
+    );
+    // Three execute stages push both results further back
+    assert_eq!(add_stalls(PipelineConfig::new(7)), (3, 2));
+}
+
+#[test]
+fn test_multi_cycle_stage_limits_issue() {
+    let config = PipelineConfig::new(5).with_latencies(vec![1, 1, 2, 1, 1]);
+    let mut simulator = simulator(config);
+    simulator.enable_timeline();
+    simulator.enable_pipeline_history();
+    simulator.run();
+
+    let timeline = simulator.timeline.as_ref().unwrap();
+    // Every instruction waits one extra cycle for the two-cycle EX, on top
+    // of the load-use stall
+    assert_eq!(timeline[1].stall_cycles, 2);
+    assert_eq!(timeline[2].stall_cycles, 1);
+    assert_eq!(timeline[2].issue_cycle - timeline[1].issue_cycle, 3);
+    match &simulator.execution_mode {
+        ExecutionMode::InOrder(pipeline) => assert!(pipeline.structural_hazard_stalls >= 3),
+        ExecutionMode::OutOfOrder(_) => unreachable!(),
+    }
+
+    // The history keeps each stage's latency apart from stalls
+    let history = simulator.pipeline_history.unwrap();
+    assert_eq!(history.latencies, vec![1, 1, 2, 1, 1]);
+    let occupancy = history.occupancy();
+    let add = &history.entries[2];
+    let spans = add.stage_spans(&history.latencies);
+    assert_eq!(spans[2].1, 2);
+    let (ex_start, _) = spans[2];
+    let first = history.first_cycle();
+    assert_eq!(occupancy[ex_start - first][2].entry, Some(2));
+    assert_eq!(occupancy[ex_start + 1 - first][2].entry, Some(2));
+    assert_eq!(occupancy[ex_start + 1 - first][2].stall, None);
+}
@@ -0,0 +1,210 @@
+// tests/pipeline/pipeline_history.rs
+// Tests for recording per-cycle pipeline occupancy and converting it for Konata
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::history::{PipelineHistory, Slot, Stall, StallReason};
+use vmips_rust::timing_simulator::simulator::Simulator;
+
+/// A load followed by two dependent adds
+const DEPENDENT_PROGRAM: [u32; 4] = [
+    0x8C091000, // lw $t1, 0x1000($zero)
+    0x01294020, // add $t0, $t1, $t1
+    0x01084020, // add $t0, $t0, $t0
+    0x00000000, // nop
+];
+
+fn record(spec: &str) -> PipelineHistory {
+    let variant = TimingVariant::parse(spec).unwrap();
+    let mut simulator = Simulator::new(
+        variant.pipeline_config,
+        variant.instr_cache_config,
+        variant.data_cache_config,
+        32768,
+    );
+    simulator.visualization = None;
+    for (i, &word) in DEPENDENT_PROGRAM.iter().enumerate() {
+        simulator.memory.write_word_init(i * 4, word);
+    }
+    simulator.enable_pipeline_history();
+    simulator.run();
+    simulator.pipeline_history.unwrap()
+}
+
+#[test]
+fn test_history_records_stalls_and_occupancy() {
+    let history = record("forwarding=off");
+    assert_eq!(history.stages, vec!["IF", "ID", "EX", "MEM", "WB"]);
+    assert_eq!(history.entries.len(), 3);
+
+    // The cold load misses in both caches, the adds wait for their operands
+    let load = &history.entries[0];
+    assert_eq!(load.fetch_cycle, 1);
+    assert_eq!(
+        load.stalls,
+        vec![
+            Stall {
+                stage: 0,
+                cycles: 10,
+                reason: StallReason::CacheMiss
+            },
+            Stall {
+                stage: 3,
+                cycles: 10,
+                reason: StallReason::CacheMiss
+            },
+        ]
+    );
+    let add = &history.entries[1];
+    assert_eq!(add.fetch_cycle, 22);
+    assert_eq!(
+        add.stage_spans(&history.latencies),
+        vec![(22, 1), (23, 3), (26, 1), (27, 1), (28, 1)]
+    );
+    assert_eq!(add.stalls[0].reason, StallReason::DataHazard);
+
+    let occupancy = history.occupancy();
+    assert_eq!(occupancy.len(), 31);
+    assert_eq!(history.cycles(), 31);
+    assert_eq!(
+        occupancy[0][0],
+        Slot {
+            entry: Some(0),
+            stall: None
+        }
+    );
+    assert_eq!(occupancy[1][0].stall, Some(StallReason::CacheMiss));
+    // Cycle 24: the load is still waiting in MEM while the add waits in ID
+    assert_eq!(occupancy[23][3].entry, Some(0));
+    assert_eq!(occupancy[23][1].entry, Some(1));
+    assert_eq!(occupancy[23][1].stall, Some(StallReason::DataHazard));
+    assert_eq!(occupancy[23][0].entry, None);
+    // No instruction is ever in two stages at once
+    for cycle in &occupancy {
+        let mut present: Vec<usize> = cycle.iter().filter_map(|slot| slot.entry).collect();
+        let count = present.len();
+        present.dedup();
+        assert_eq!(present.len(), count);
+    }
+
+    // With forwarding the adds issue back to back
+    let forwarded = record("forwarding=on");
+    assert!(forwarded.entries[2].stalls.is_empty());
+    assert!(forwarded.cycles() < history.cycles());
+}
+
+#[test]
+fn test_history_file_round_trip() {
+    let history = record("forwarding=off,caches=off");
+    let mut bytes = Vec::new();
+    history.write_to(&mut bytes).unwrap();
+    assert_eq!(&bytes[0..4], b"VMPH");
+
+    let loaded = PipelineHistory::read_from(&mut bytes.as_slice()).unwrap();
+    assert_eq!(loaded, history);
+    assert_eq!(loaded.occupancy(), history.occupancy());
+
+    bytes[0] = b'X';
+    assert!(PipelineHistory::read_from(&mut bytes.as_slice()).is_err());
+    assert!(PipelineHistory::read_from(&mut &b"VMPH"[..]).is_err());
+}
+
+#[test]
+fn test_konata_conversion() {
+    let konata = record("forwarding=off").to_konata();
+    let lines: Vec<&str> = konata.lines().collect();
+    assert_eq!(lines[0], "Kanata\t0004");
+    assert_eq!(lines[1], "C=\t1");
+    assert_eq!(lines[2], "I\t0\t0\t0");
+    assert_eq!(lines[3], "L\t0\t0\t00000000: lw $9, 4096($0)");
+    assert_eq!(lines[4], "S\t0\t0\tIF");
+    assert!(konata.contains("S\t1\t1\tdata\n"));
+    assert!(konata.contains("S\t0\t1\tcache\n"));
+    assert!(konata.ends_with("R\t2\t2\t0\n"));
+
+    let dir = tempdir().unwrap();
+    let program = dir.path().join("dependent.bin");
+    let history = dir.path().join("run.vmph");
+    let log = dir.path().join("run.kanata");
+    let bytes: Vec<u8> = DEPENDENT_PROGRAM
+        .iter()
+        .flat_map(|w| w.to_le_bytes())
+        .collect();
+    std::fs::write(&program, bytes).unwrap();
+
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .arg("timing")
+        .arg("--input")
+        .arg(&program)
+        .arg("--pipeline-history")
+        .arg(&history)
+        .arg("--output")
+        .arg(dir.path().join("timing.log"))
+        .assert()
+        .success()
+        .stdout(contains("Pipeline history of 3 instructions"));
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .arg("konata")
+        .arg("--input")
+        .arg(&history)
+        .arg("--output")
+        .arg(&log)
+        .assert()
+        .success()
+        .stdout(contains("Wrote 3 instructions"));
+
+    let converted = std::fs::read_to_string(&log).unwrap();
+    assert!(converted.starts_with("Kanata\t0004\n"));
+    assert!(converted.contains("add $8, $9, $9"));
+}
+
+#[test]
+fn test_csv_export() {
+    let history = record("forwarding=off,caches=off");
+    let csv = history.to_csv();
+    let rows: Vec<&str> = csv.lines().collect();
+    assert_eq!(rows[0], "cycle,IF,ID,EX,MEM,WB");
+    assert_eq!(rows.len(), history.cycles() + 1);
+    assert_eq!(rows[1], "1,0x00000000,bubble,bubble,bubble,bubble");
+
+    // Each slot matches the recorded occupancy
+    for (row, slots) in rows[1..].iter().zip(history.occupancy()) {
+        let cells: Vec<&str> = row.split(',').skip(1).collect();
+        for (cell, slot) in cells.iter().zip(slots) {
+            match (slot.entry, slot.stall) {
+                (None, _) => assert_eq!(*cell, "bubble"),
+                (Some(_), Some(_)) => assert_eq!(*cell, "stall"),
+                (Some(index), None) => {
+                    assert_eq!(*cell, format!("0x{:08X}", history.entries[index].pc))
+                },
+            }
+        }
+    }
+    assert!(csv.contains(",stall,"));
+
+    let dir = tempdir().unwrap();
+    let program = dir.path().join("dependent.bin");
+    let path = dir.path().join("run.csv");
+    let bytes: Vec<u8> = DEPENDENT_PROGRAM
+        .iter()
+        .flat_map(|w| w.to_le_bytes())
+        .collect();
+    std::fs::write(&program, bytes).unwrap();
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .arg("timing")
+        .arg("--input")
+        .arg(&program)
+        .arg("--pipeline-history")
+        .arg(&path)
+        .arg("--output")
+        .arg(dir.path().join("timing.log"))
+        .assert()
+        .success();
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert!(written.starts_with("cycle,IF,ID,EX,MEM,WB\n"));
+}
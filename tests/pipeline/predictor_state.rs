@@ -0,0 +1,177 @@
+// tests/pipeline/predictor_state.rs
+// Tests for the branch predictor types and for exporting, importing and
+// warming up predictor state
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::branch_predictor::{
+    BranchPredictor, PredictorState, PREDICTOR_VERSION,
+};
+use vmips_rust::timing_simulator::config::{BranchPredictorType, CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator;
+
+/// Two nested counting loops
+const LOOPS: &str = "
+.text
+    li $t0, 4
+outer:
+    li $t1, 3
+inner:
+    addi $t1, $t1, -1
+    bne $t1, $zero, inner
+    addi $t0, $t0, -1
+    bne $t0, $zero, outer
+    nop
+";
+
+fn simulator() -> Simulator {
+    let image = ProgramImage::from_assembly(LOOPS).unwrap();
+    let mut simulator = Simulator::new(
+        PipelineConfig::new(5).with_branch_prediction(true, BranchPredictorType::TwoBit),
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        0x0080_0000,
+    );
+    simulator.visualization = None;
+    simulator.load_image(&image).unwrap();
+    simulator
+}
+
+// Mispredictions of a branch that alternates between taken and not taken
+fn alternating_mispredictions(kind: BranchPredictorType) -> usize {
+    let mut predictor = BranchPredictor::with_type(kind);
+    assert_eq!(predictor.kind(), kind);
+    (0..64)
+        .filter(|i| {
+            let taken = i % 2 == 0;
+            let predicted = predictor.predict(0x40);
+            predictor.update(0x40, taken, 0x20);
+            predicted != taken
+        })
+        .count()
+}
+
+#[test]
+fn test_predictor_types_on_an_alternating_branch() {
+    // The static predictor misses every taken outcome and a one-bit entry
+    // is always one outcome behind
+    assert_eq!(alternating_mispredictions(BranchPredictorType::Static), 32);
+    assert!(alternating_mispredictions(BranchPredictorType::OneBit) >= 63);
+    // Global history captures the pattern once trained
+    let correlating = alternating_mispredictions(BranchPredictorType::Correlating);
+    let tournament = alternating_mispredictions(BranchPredictorType::Tournament);
+    let two_bit = alternating_mispredictions(BranchPredictorType::TwoBit);
+    assert!(correlating < 8, "correlating missed {}", correlating);
+    assert!(
+        tournament < two_bit,
+        "tournament {} vs 2-bit {}",
+        tournament,
+        two_bit
+    );
+}
+
+#[test]
+fn test_predictor_tables_round_trip() {
+    let mut predictor = BranchPredictor::new();
+    for _ in 0..3 {
+        predictor.update(0x40, true, 0x20);
+    }
+    predictor.update(0x80, false, 0x10);
+    let state = predictor.export_state();
+    assert_eq!(state.version, PREDICTOR_VERSION);
+    assert_eq!(state.local.len(), 2);
+    assert_eq!((state.local[0].pc, state.local[0].counter), (0x40, 3));
+    assert_eq!((state.local[1].pc, state.local[1].counter), (0x80, 0));
+    assert_eq!(state.global.len(), 16);
+    assert_eq!(state.targets.len(), 1);
+
+    let parsed = PredictorState::from_json(&state.to_json()).unwrap();
+    assert_eq!(parsed, state);
+    let mut fresh = BranchPredictor::new();
+    assert!(!fresh.predict(0x40));
+    fresh.import_state(&parsed).unwrap();
+    assert!(fresh.predict(0x40));
+    assert!(!fresh.predict(0x80));
+    assert_eq!(fresh.get_target(0x40), Some(0x20));
+    assert_eq!(fresh.export_state(), state);
+
+    // Bad counters and other formats are refused, leaving the tables alone
+    let mut bad = state.clone();
+    bad.local[0].counter = 4;
+    assert_eq!(
+        fresh.import_state(&bad).unwrap_err(),
+        "invalid 2-bit counter 4"
+    );
+    assert_eq!(fresh.export_state(), state);
+    let json = state.to_json().replace("vmips-predictor", "vmips-snapshot");
+    assert!(PredictorState::from_json(&json)
+        .unwrap_err()
+        .contains("not a predictor state"));
+}
+
+#[test]
+fn test_warmed_up_predictor_mispredicts_less() {
+    let mut cold = simulator();
+    cold.run();
+    let trained = cold.predictor_state().unwrap();
+    assert_eq!(trained.local.len(), 2);
+
+    let mut warm = simulator();
+    warm.load_predictor_state(&trained).unwrap();
+    warm.run();
+    assert_eq!(warm.run_totals.instructions, cold.run_totals.instructions);
+    assert!(warm.run_totals.branch_mispredictions < cold.run_totals.branch_mispredictions);
+    assert!(warm.run_totals.cycles < cold.run_totals.cycles);
+}
+
+#[test]
+fn test_predictor_files_from_the_command_line() {
+    let dir = tempdir().unwrap();
+    let program = dir.path().join("loops.s");
+    std::fs::write(&program, LOOPS).unwrap();
+    let saved = dir.path().join("predictor.json");
+
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .args(["timing", "--input"])
+        .arg(&program)
+        .arg("--save-predictor")
+        .arg(&saved)
+        .assert()
+        .success()
+        .stdout(contains(
+            "Branch predictor with 2 trained branches written to",
+        ));
+    let state = PredictorState::from_json(&std::fs::read_to_string(&saved).unwrap()).unwrap();
+    assert_eq!(state.local.len(), 2);
+
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .args(["timing", "--input"])
+        .arg(&program)
+        .arg("--load-predictor")
+        .arg(&saved)
+        .assert()
+        .success()
+        .stdout(contains("Branch mispredictions:"));
+
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .args(["timing", "--input"])
+        .arg(&program)
+        .arg("--warmup-program")
+        .arg(&program)
+        .assert()
+        .success()
+        .stdout(contains("Warming up the branch predictor on"))
+        .stdout(contains("trained 2 branches"));
+
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .args(["--schema", "predictor"])
+        .assert()
+        .success()
+        .stdout(contains("urn:vmips-rust:vmips-predictor:1"));
+}
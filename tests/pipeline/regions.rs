@@ -0,0 +1,251 @@
+// tests/pipeline/regions.rs
+// Tests for region of interest markers in the timing simulator
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::assembler::Assembler;
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::regions::{render_regions, RegionRecorder};
+use vmips_rust::timing_simulator::simulator::Simulator;
+use vmips_rust::timing_simulator::stats::StatsCounters;
+
+/// Warms up with a loop, then times a second loop of 8 iterations
+const KERNEL_PROGRAM: &str = "
+.data
+values: .word 1, 2, 3, 4, 5, 6, 7, 8
+.text
+    li $t1, 20
+warmup:
+    addi $t1, $t1, -1
+    bne $t1, $zero, warmup
+    la $t0, values
+    li $t1, 8
+    li $s0, 0
+    li $v0, 74
+    syscall
+sum:
+    lw $t2, 0($t0)
+    add $s0, $s0, $t2
+    addi $t0, $t0, 4
+    addi $t1, $t1, -1
+    bne $t1, $zero, sum
+    li $v0, 75
+    syscall
+    li $v0, 10
+    syscall
+";
+
+fn run(source: &str) -> Simulator {
+    let program = Assembler::new().assemble_program(source).unwrap();
+    let mut simulator = Simulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        0x0050_0000,
+    );
+    simulator.visualization = None;
+    let image = ProgramImage::from_bytes(&program.to_bytes()).unwrap();
+    simulator.load_image(&image).unwrap();
+    simulator.run();
+    simulator
+}
+
+fn counters(instructions: usize, cycles: usize) -> StatsCounters {
+    StatsCounters {
+        instructions,
+        cycles,
+        ..StatsCounters::default()
+    }
+}
+
+#[test]
+fn test_region_covers_only_the_marked_code() {
+    let simulator = run(KERNEL_PROGRAM);
+    let region = simulator.regions.sample().unwrap();
+    assert_eq!(region.passes, 1);
+    // The begin marker, the 40 loop instructions and the li before the end marker
+    assert_eq!(region.stats.instructions, 42);
+    assert!(region.stats.start_instruction > 40);
+    assert!(region.stats.cycles < simulator.run_totals.cycles);
+    assert_eq!(region.cpi_stack.instructions, 42);
+
+    let report = simulator.stats_report();
+    assert_eq!(report.region_of_interest, Some(region));
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["region_of_interest"]["stats"]["instructions"], 42);
+}
+
+#[test]
+fn test_no_markers_no_region() {
+    let simulator = run("li $t0, 1\nli $v0, 10\nsyscall\n");
+    assert!(simulator.regions.sample().is_none());
+    let json: serde_json::Value =
+        serde_json::from_str(&simulator.stats_report().to_json()).unwrap();
+    assert!(json.get("region_of_interest").is_none());
+}
+
+#[test]
+fn test_passes_add_up() {
+    let mut regions = RegionRecorder::default();
+    regions.end(counters(5, 5));
+    regions.begin(counters(10, 20));
+    // A nested begin does not restart the pass
+    regions.begin(counters(12, 24));
+    regions.end(counters(15, 30));
+    regions.begin(counters(40, 80));
+    // The open pass ends with the run
+    regions.finish(counters(50, 95));
+
+    let region = regions.sample().unwrap();
+    assert_eq!(region.passes, 2);
+    assert_eq!(region.stats.instructions, 15);
+    assert_eq!(region.stats.cycles, 25);
+    assert_eq!(region.stats.start_instruction, 10);
+    assert!(region.render().contains("in 2 passes"));
+
+    regions.reset();
+    assert!(regions.counters().is_none());
+}
+
+#[test]
+fn test_cli_reports_region() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("kernel.asm");
+    std::fs::write(&source, KERNEL_PROGRAM).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&source)
+        .arg("--cpi-stack");
+    cmd.assert()
+        .success()
+        .stdout(contains("Region of interest: 42 instructions"))
+        .stdout(contains("CPI stack:").and(contains("over 42 instructions")));
+
+    // Functional runs ignore the markers
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source);
+    cmd.assert()
+        .success()
+        .stdout(contains("Unimplemented syscall").not());
+}
+
+/// Three phases, with the summing loop nested inside compute
+const PHASES_PROGRAM: &str = "
+.data
+values: .word 1, 2, 3, 4
+init_name: .asciiz \"init\"
+compute_name: .asciiz \"compute\"
+sum_name: .asciiz \"sum\"
+.text
+    la $a0, init_name
+    li $v0, 76
+    syscall
+    la $t0, values
+    li $t1, 4
+    li $s0, 0
+    la $a0, init_name
+    li $v0, 77
+    syscall
+    la $a0, compute_name
+    li $v0, 76
+    syscall
+    la $a0, sum_name
+    li $v0, 76
+    syscall
+sum:
+    lw $t2, 0($t0)
+    add $s0, $s0, $t2
+    addi $t0, $t0, 4
+    addi $t1, $t1, -1
+    bne $t1, $zero, sum
+    la $a0, sum_name
+    li $v0, 77
+    syscall
+    sll $s1, $s0, 1
+    la $a0, compute_name
+    li $v0, 77
+    syscall
+    li $v0, 10
+    syscall
+";
+
+#[test]
+fn test_named_regions_nest() {
+    let simulator = run(PHASES_PROGRAM);
+    assert!(simulator.regions.sample().is_none());
+    let regions = simulator.regions.named_samples();
+    let names: Vec<&str> = regions.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, vec!["init", "compute", "sum"]);
+    assert_eq!(
+        regions.iter().map(|r| r.depth).collect::<Vec<_>>(),
+        vec![0, 0, 1]
+    );
+    assert!(regions.iter().all(|r| r.passes == 1));
+
+    // The sum loop is 20 instructions inside the compute phase
+    let (compute, sum) = (&regions[1].stats, &regions[2].stats);
+    assert!(sum.instructions >= 20);
+    assert!(compute.instructions > sum.instructions);
+    assert!(compute.cycles > sum.cycles);
+    assert!(sum.dcache_accesses >= 4);
+
+    let json: serde_json::Value =
+        serde_json::from_str(&simulator.stats_report().to_json()).unwrap();
+    assert_eq!(json["regions"][2]["name"], "sum");
+    assert_eq!(json["regions"][2]["depth"], 1);
+}
+
+#[test]
+fn test_named_region_ends() {
+    let mut regions = RegionRecorder::default();
+    regions.begin_named("outer".to_string(), counters(0, 0));
+    regions.begin_named("inner".to_string(), counters(10, 10));
+    // A recursive entry is part of the open pass
+    regions.begin_named("inner".to_string(), counters(12, 14));
+    regions.end_named("inner", counters(14, 18));
+    regions.end_named("unknown", counters(15, 19));
+    // Leaving outer also leaves the inner region still open
+    regions.end_named("outer", counters(20, 30));
+    regions.begin_named("inner".to_string(), counters(40, 50));
+    regions.finish(counters(45, 60));
+
+    let samples = regions.named_samples();
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[0].stats.instructions, 20);
+    assert_eq!(samples[1].passes, 2);
+    assert_eq!(samples[1].stats.instructions, 15);
+    assert_eq!(samples[1].stats.cycles, 30);
+    assert_eq!(samples[1].depth, 1);
+
+    let table = render_regions(&samples);
+    assert!(table.contains("\n  outer "));
+    assert!(table.contains("\n    inner "));
+}
+
+#[test]
+fn test_cli_reports_named_regions() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("phases.asm");
+    std::fs::write(&source, PHASES_PROGRAM).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&source)
+        .arg("--cpi-stack");
+    cmd.assert()
+        .success()
+        .stdout(contains("Regions:"))
+        .stdout(contains("\n  init "))
+        .stdout(contains("\n    sum "));
+}
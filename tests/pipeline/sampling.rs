@@ -0,0 +1,101 @@
+// tests/pipeline/sampling.rs
+// Tests for the SimPoint-style sampled simulation mode
+
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::sampling::{SampledSimulator, SamplingConfig};
+use vmips_rust::utils::limits::TerminationReason;
+
+const PROGRAM_LENGTH: usize = 300;
+
+/// Helper function to build a straight-line program that counts in $t0 and
+/// stores the count on every tenth instruction, then exits
+fn counting_program() -> FunctionalSimulator {
+    let mut simulator = FunctionalSimulator::new(32768);
+    simulator.load_program(&[]);
+
+    for i in 0..PROGRAM_LENGTH {
+        let word = if i % 10 == 9 {
+            0xAC082000 // sw $t0, 0x2000($zero)
+        } else {
+            0x25080001 // addiu $t0, $t0, 1
+        };
+        simulator.memory.write_word(i * 4, word).unwrap();
+    }
+    simulator
+        .memory
+        .write_word(PROGRAM_LENGTH * 4, 0x2402000A)
+        .unwrap(); // addiu $v0, $zero, 10
+    simulator
+        .memory
+        .write_word(PROGRAM_LENGTH * 4 + 4, 0x0000000C)
+        .unwrap(); // syscall
+
+    simulator
+}
+
+fn sampled(config: SamplingConfig) -> SampledSimulator {
+    SampledSimulator::new(
+        counting_program(),
+        PipelineConfig::new(5),
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        config,
+    )
+}
+
+#[test]
+fn test_sampled_run_matches_functional_run() {
+    let mut reference = counting_program();
+    reference.run().unwrap();
+
+    let mut simulator = sampled(SamplingConfig::new(40, 5, 20));
+    let result = simulator.run();
+
+    // Fast-forwarding never changes the architectural result
+    assert_eq!(result.total_instructions, reference.step_count);
+    assert_eq!(
+        simulator.functional.registers.read(8),
+        reference.registers.read(8)
+    );
+    assert_eq!(
+        simulator.functional.memory.read_word(0x2000),
+        reference.memory.read_word(0x2000)
+    );
+    assert_eq!(result.termination_reason, Some(TerminationReason::Exited));
+
+    // Samples start after each fast-forward window and measure past the warmup
+    assert!(result.samples.len() >= 4);
+    assert_eq!(result.samples[0].start_instruction, 40);
+    assert_eq!(result.samples[1].start_instruction, 40 + 25 + 40);
+    assert!(result.samples.iter().all(|s| s.instructions == 20));
+    assert!(result.estimated_cpi > 0.0);
+}
+
+#[test]
+fn test_sample_limit_and_estimate() {
+    let mut simulator = sampled(SamplingConfig::new(30, 0, 10).with_max_samples(2));
+    let result = simulator.run();
+
+    assert_eq!(result.samples.len(), 2);
+    assert_eq!(result.samples[0].start_pc, 30 * 4);
+
+    let cycles: usize = result.samples.iter().map(|s| s.cycles).sum();
+    let expected_cpi = cycles as f64 / 20.0;
+    assert!((result.estimated_cpi - expected_cpi).abs() < 1e-9);
+    assert!(
+        (result.estimated_cycles - expected_cpi * result.total_instructions as f64).abs() < 1e-6
+    );
+    assert!(result.print_summary().contains("Samples: 2"));
+}
+
+#[test]
+fn test_no_measurement_runs_functionally() {
+    let mut simulator = sampled(SamplingConfig::new(50, 10, 0));
+    let result = simulator.run();
+
+    assert!(result.samples.is_empty());
+    assert_eq!(result.estimated_cpi, 0.0);
+    assert_eq!(result.total_instructions, PROGRAM_LENGTH + 2);
+    assert_eq!(simulator.functional.registers.read(8), 270);
+}
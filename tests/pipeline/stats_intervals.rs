@@ -0,0 +1,127 @@
+// tests/pipeline/stats_intervals.rs
+// Tests for per-interval statistics in the timing simulator's JSON report
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator;
+use vmips_rust::timing_simulator::stats::StatsInterval;
+
+/// A loop that loads from memory on every iteration
+const LOOP_PROGRAM: [(usize, u32); 3] = [
+    (0x00, 0x8C090100), // lw $t1, 0x100($zero)
+    (0x04, 0x21080001), // addi $t0, $t0, 1
+    (0x08, 0x08000000), // j 0
+];
+
+fn setup_simulator() -> Simulator {
+    let mut simulator = Simulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    simulator.visualization = None;
+    for &(addr, word) in &LOOP_PROGRAM {
+        simulator.memory.write_word_init(addr, word);
+    }
+    simulator
+}
+
+#[test]
+fn test_instruction_intervals() {
+    let mut simulator = setup_simulator();
+    simulator.set_stats_interval(Some(StatsInterval::Instructions(10)));
+    simulator.set_max_instructions(35);
+    simulator.run();
+
+    let report = simulator.stats_report();
+    let sizes: Vec<usize> = report.intervals.iter().map(|s| s.instructions).collect();
+    assert_eq!(sizes, vec![10, 10, 10, 5]);
+    assert_eq!(report.total.instructions, 35);
+    assert_eq!(
+        report.intervals.iter().map(|s| s.cycles).sum::<usize>(),
+        report.total.cycles
+    );
+    assert_eq!(
+        report.termination_reason.as_deref(),
+        Some("instruction limit reached")
+    );
+
+    // Every interval carries its starting point and derived rates
+    assert_eq!(report.intervals[2].index, 2);
+    assert_eq!(report.intervals[2].start_instruction, 20);
+    assert!(report.intervals.iter().all(|s| s.cpi > 0.0));
+
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["intervals"].as_array().unwrap().len(), 4);
+    assert!(json["intervals"][0]["dcache_miss_rate"].is_number());
+}
+
+#[test]
+fn test_cycle_intervals() {
+    let mut simulator = setup_simulator();
+    simulator.set_stats_interval(Some(StatsInterval::Cycles(8)));
+    simulator.set_max_cycles(40);
+    simulator.run();
+
+    let report = simulator.stats_report();
+    assert_eq!(report.intervals.len(), 5);
+    assert!(report.intervals.iter().all(|s| s.cycles == 8));
+    assert_eq!(report.total.cycles, 40);
+
+    // Without an interval only the totals are reported
+    let mut simulator = setup_simulator();
+    simulator.set_max_cycles(40);
+    simulator.run();
+    assert!(simulator.stats_report().intervals.is_empty());
+}
+
+#[test]
+fn test_cli_writes_stats_json() {
+    let temp_dir = tempdir().unwrap();
+    let stats_path = temp_dir.path().join("stats.json");
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("timing")
+        .arg("--memory-size")
+        .arg("8192")
+        .arg("--stats-interval")
+        .arg("3")
+        .arg("--stats-json")
+        .arg(&stats_path);
+    cmd.assert().success();
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&stats_path).unwrap()).unwrap();
+    let intervals = json["intervals"].as_array().unwrap();
+    assert!(!intervals.is_empty());
+
+    let total: u64 = intervals
+        .iter()
+        .map(|s| s["instructions"].as_u64().unwrap())
+        .sum();
+    assert_eq!(json["total"]["instructions"].as_u64().unwrap(), total);
+
+    // The intervals come from the pipeline model, which charges cache
+    // misses and stalls
+    let sum = |field: &str| -> u64 { intervals.iter().map(|s| s[field].as_u64().unwrap()).sum() };
+    assert!(sum("icache_accesses") > 0);
+    assert!(sum("icache_misses") > 0);
+    assert!(json["total"]["cpi"].as_f64().unwrap() > 1.0);
+    assert!(json["cpi_stack"]["icache"].as_f64().unwrap() > 0.0);
+}
+
+#[test]
+fn test_cli_rejects_unknown_interval_unit() {
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("timing")
+        .arg("--stats-interval")
+        .arg("3")
+        .arg("--stats-interval-unit")
+        .arg("cycle");
+    cmd.assert()
+        .failure()
+        .stderr(contains("invalid value 'cycle'"));
+}
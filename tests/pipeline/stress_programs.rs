@@ -0,0 +1,110 @@
+// tests/pipeline/stress_programs.rs
+// Tests for the seeded stress programs run on the timing model
+
+use vmips_rust::functional_simulator::registers::T0;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::stress::{AccessPattern, StressConfig, StressProgram};
+
+fn run(config: StressConfig) -> vmips_rust::timing_simulator::stress::StressReport {
+    StressProgram::generate(config)
+        .unwrap()
+        .run(&TimingVariant::default())
+        .unwrap()
+}
+
+#[test]
+fn test_default_program_verifies_and_is_reproducible() {
+    let report = run(StressConfig::default());
+    assert_eq!(report.verified, Ok(()));
+    assert!(report.totals.instructions > 100 * 64);
+    assert!(report.render().contains("verified against the host"));
+
+    // The seed alone decides the program
+    let first = StressProgram::generate(StressConfig::default()).unwrap();
+    let second = StressProgram::generate(StressConfig::default()).unwrap();
+    assert_eq!(first.program().text, second.program().text);
+    let other = StressConfig {
+        seed: 2,
+        ..StressConfig::default()
+    };
+    assert_ne!(
+        StressProgram::generate(other).unwrap().program().text,
+        first.program().text
+    );
+}
+
+#[test]
+fn test_knobs_change_the_pressure() {
+    let sequential = run(StressConfig {
+        memory_density: 0.5,
+        working_set: 65536,
+        ..StressConfig::default()
+    });
+    let random = run(StressConfig {
+        memory_density: 0.5,
+        pattern: AccessPattern::Random,
+        working_set: 65536,
+        ..StressConfig::default()
+    });
+    assert_eq!(sequential.verified, Ok(()));
+    assert_eq!(random.verified, Ok(()));
+    assert!(random.totals.dcache_misses > 4 * sequential.totals.dcache_misses);
+
+    let calm = run(StressConfig {
+        branch_density: 0.0,
+        ..StressConfig::default()
+    });
+    let branchy = run(StressConfig {
+        branch_density: 0.3,
+        ..StressConfig::default()
+    });
+    assert_eq!(branchy.verified, Ok(()));
+    assert!(branchy.totals.branch_mispredictions > calm.totals.branch_mispredictions);
+}
+
+#[test]
+fn test_invalid_configs_and_wrong_state_are_reported() {
+    assert!(AccessPattern::parse("stride:x").is_err());
+    assert_eq!(
+        AccessPattern::parse("stride:64"),
+        Ok(AccessPattern::Stride(64))
+    );
+    for config in [
+        StressConfig {
+            working_set: 1000,
+            ..StressConfig::default()
+        },
+        StressConfig {
+            pattern: AccessPattern::Stride(3),
+            ..StressConfig::default()
+        },
+        StressConfig {
+            chain: 0,
+            ..StressConfig::default()
+        },
+        StressConfig {
+            branch_density: 0.8,
+            memory_density: 0.5,
+            ..StressConfig::default()
+        },
+    ] {
+        assert!(StressProgram::generate(config).is_err());
+    }
+
+    // The functional simulator computes the same state, until it is disturbed
+    let program = StressProgram::generate(StressConfig::default()).unwrap();
+    let image = program.image();
+    let mut simulator = Simulator::from_image(&image).unwrap();
+    simulator.run().unwrap();
+    assert_eq!(
+        program.verify(&simulator.registers, &simulator.memory),
+        Ok(())
+    );
+    let value = simulator.registers.read(T0);
+    simulator.registers.write(T0, value ^ 1);
+    let error = program
+        .verify(&simulator.registers, &simulator.memory)
+        .unwrap_err();
+    assert!(error.contains("register $8"), "{}", error);
+}
@@ -0,0 +1,182 @@
+// tests/pipeline/tlb.rs
+// Tests for the TLB timing model and its page-walk penalty
+
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::{ExecutionMode, Simulator};
+use vmips_rust::timing_simulator::tlb::{Tlb, TlbConfig};
+
+/// Two loads from one page and one from the next
+const LOAD_PROGRAM: [u32; 4] = [
+    0x8C091000, // lw $t1, 0x1000($zero)
+    0x8C0A1004, // lw $t2, 0x1004($zero)
+    0x8C0B2000, // lw $t3, 0x2000($zero)
+    0x00000000, // nop
+];
+
+fn run(config: PipelineConfig) -> Simulator {
+    let mut simulator = Simulator::new(
+        config,
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    simulator.visualization = None;
+    for (i, &word) in LOAD_PROGRAM.iter().enumerate() {
+        simulator.memory.write_word_init(i * 4, word);
+    }
+    simulator.enable_timeline();
+    simulator.run();
+    simulator
+}
+
+#[test]
+fn test_tlb_hits_misses_and_replacement() {
+    let mut tlb = Tlb::new(TlbConfig::new(2, 2).with_walk_penalty(30));
+    assert_eq!(tlb.access(0x0040_0000), 30);
+    assert_eq!(tlb.access(0x0040_0FFC), 0);
+    assert_eq!(tlb.access(0x0040_1000), 30);
+    // The least recently used page makes way for a third
+    assert_eq!(tlb.access(0x0040_0000), 0);
+    assert_eq!(tlb.access(0x0040_2000), 30);
+    assert_eq!(tlb.access(0x0040_0000), 0);
+    assert_eq!(tlb.access(0x0040_1000), 30);
+    assert_eq!(tlb.stats.accesses, 7);
+    assert_eq!(tlb.stats.misses, 4);
+    assert_eq!(tlb.stats.walk_cycles, 120);
+    assert_eq!(tlb.occupancy(), 2);
+
+    // kseg0 and kseg1 are unmapped
+    assert_eq!(tlb.access(0x8000_0000), 0);
+    assert_eq!(tlb.access(0xA000_0000), 0);
+    assert_eq!(tlb.stats.accesses, 7);
+
+    tlb.flush();
+    assert_eq!(tlb.occupancy(), 0);
+    assert_eq!(tlb.access(0x0040_0000), 30);
+}
+
+#[test]
+fn test_page_walks_stall_the_pipeline() {
+    let baseline = run(PipelineConfig::new(5));
+    let walked = run(PipelineConfig::new(5).with_tlb(TlbConfig::new(16, 4)));
+    let stalls = |simulator: &Simulator| -> Vec<usize> {
+        let timeline = simulator.timeline.as_ref().unwrap();
+        timeline.iter().map(|timing| timing.stall_cycles).collect()
+    };
+    let (baseline, with_tlb) = (stalls(&baseline), stalls(&walked));
+
+    // The first fetch and the first access to each data page walk
+    assert_eq!(with_tlb[0], baseline[0] + 40);
+    assert_eq!(with_tlb[1], baseline[1]);
+    assert_eq!(with_tlb[2], baseline[2] + 20);
+
+    match &walked.execution_mode {
+        ExecutionMode::InOrder(pipeline) => {
+            let itlb = pipeline.itlb.as_ref().unwrap();
+            let dtlb = pipeline.dtlb.as_ref().unwrap();
+            assert_eq!(itlb.stats.misses, 1);
+            assert_eq!((dtlb.stats.accesses, dtlb.stats.misses), (3, 2));
+            assert!(pipeline
+                .print_statistics()
+                .contains("DTLB Miss Rate: 66.67%"));
+        },
+        ExecutionMode::OutOfOrder(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn test_tlb_settings_in_variants() {
+    let variant = TimingVariant::parse("tlb=32/4,page-walk=50").unwrap();
+    let tlb = variant.pipeline_config.tlb_config.clone().unwrap();
+    assert_eq!(
+        (tlb.entries, tlb.associativity, tlb.walk_penalty),
+        (32, 4, 50)
+    );
+    assert!(variant.describe().contains(",tlb=32/4,page-walk=50,"));
+    assert!(TimingVariant::default().describe().contains(",tlb=off,"));
+
+    assert!(TimingVariant::parse("page-walk=50").is_err());
+    assert!(TimingVariant::parse("tlb=30/4").is_err());
+}
+
+/// Load the same address in address spaces 1, 2 and 1 again, switching with
+/// syscall 78 and then with a write to EntryHi
+const SWITCH_PROGRAM: [u32; 11] = [
+    0x20040001, // addi $a0, $zero, 1
+    0x2002004E, // addi $v0, $zero, 78
+    0x0000000C, // syscall
+    0x8C091000, // lw $t1, 0x1000($zero)
+    0x20040002, // addi $a0, $zero, 2
+    0x0000000C, // syscall
+    0x8C091000, // lw $t1, 0x1000($zero)
+    0x20040001, // addi $a0, $zero, 1
+    0x40845000, // mtc0 $a0, $10
+    0x8C091000, // lw $t1, 0x1000($zero)
+    0x00000000, // nop
+];
+
+fn run_switches(tlb: TlbConfig) -> (usize, usize, usize) {
+    let mut simulator = Simulator::new(
+        PipelineConfig::new(5).with_tlb(tlb),
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    simulator.visualization = None;
+    for (i, &word) in SWITCH_PROGRAM.iter().enumerate() {
+        simulator.memory.write_word_init(i * 4, word);
+    }
+    simulator.run();
+    match &simulator.execution_mode {
+        ExecutionMode::InOrder(pipeline) => {
+            let dtlb = pipeline.dtlb.as_ref().unwrap();
+            (
+                dtlb.stats.misses,
+                dtlb.stats.context_switches,
+                pipeline.stall_count,
+            )
+        },
+        ExecutionMode::OutOfOrder(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn test_asids_keep_entries_across_switches() {
+    let mut tlb = Tlb::new(TlbConfig::new(8, 2));
+    tlb.set_asid(1);
+    assert_eq!(tlb.access(0x1000), 20);
+    assert_eq!(tlb.set_asid(2), 0);
+    assert_eq!(tlb.access(0x1000), 20);
+    tlb.set_asid(1);
+    assert_eq!(tlb.access(0x1000), 0);
+    assert_eq!(tlb.stats.context_switches, 3);
+    assert_eq!(tlb.stats.flushes, 0);
+
+    // Without tags every switch flushes and costs the flush penalty
+    let mut tlb = Tlb::new(TlbConfig::new(8, 2).with_asids(false, 5));
+    assert_eq!(tlb.set_asid(1), 5);
+    assert_eq!(tlb.access(0x1000), 20);
+    assert_eq!(tlb.set_asid(1), 0);
+    assert_eq!(tlb.set_asid(2), 5);
+    assert_eq!(tlb.set_asid(1), 5);
+    assert_eq!(tlb.access(0x1000), 20);
+    assert_eq!(tlb.stats.flushes, 3);
+}
+
+#[test]
+fn test_context_switch_cost_in_the_pipeline() {
+    let (tagged_misses, switches, tagged_stalls) = run_switches(TlbConfig::new(16, 4));
+    assert_eq!(switches, 3);
+    assert_eq!(tagged_misses, 2);
+
+    let (flush_misses, switches, flush_stalls) =
+        run_switches(TlbConfig::new(16, 4).with_asids(false, 10));
+    assert_eq!(switches, 3);
+    assert_eq!(flush_misses, 3);
+    assert!(flush_stalls > tagged_stalls);
+
+    let variant = TimingVariant::parse("tlb=16/4,asid=flush").unwrap();
+    assert!(!variant.pipeline_config.tlb_config.unwrap().tagged);
+    assert!(TimingVariant::parse("asid=flush").is_err());
+}
@@ -0,0 +1,109 @@
+// tests/pipeline/tomasulo.rs
+// Tests for the results the Tomasulo model computes, broadcasts and commits
+
+use vmips_rust::functional_simulator::instructions::Instruction;
+use vmips_rust::functional_simulator::memory::Memory;
+use vmips_rust::functional_simulator::registers::{Registers, S0, T0, T1, T2, T3, T4};
+use vmips_rust::timing_simulator::tomasulo::TomasuloProcessor;
+
+fn processor(stations: usize, rob_size: usize) -> TomasuloProcessor {
+    let mut registers = Registers::new();
+    registers.write(T0, 5);
+    registers.write(T1, 7);
+    registers.write(S0, 0x100);
+    let mut memory = Memory::new(4096);
+    memory.write_word(0x100, 1000).unwrap();
+    TomasuloProcessor::new(stations, rob_size, registers, memory)
+}
+
+// Issue one instruction per cycle, retrying while the processor is full,
+// then run until everything has committed
+fn run(processor: &mut TomasuloProcessor, instructions: &[Instruction]) {
+    let mut next = 0;
+    for _ in 0..1000 {
+        if let Some(instruction) = instructions.get(next) {
+            if processor.issue(instruction.clone(), 0x0040_0000 + 4 * next as u32) {
+                next += 1;
+            }
+        }
+        processor.tick();
+        if processor.instructions_committed == instructions.len() {
+            return;
+        }
+    }
+    panic!(
+        "{} of {} instructions committed",
+        processor.instructions_committed,
+        instructions.len()
+    );
+}
+
+#[test]
+fn test_results_reach_the_register_file() {
+    let mut processor = processor(4, 8);
+    run(
+        &mut processor,
+        &[
+            Instruction::Add {
+                rd: T2,
+                rs: T0,
+                rt: T1,
+            },
+            Instruction::Sub {
+                rd: T3,
+                rs: T0,
+                rt: T1,
+            },
+            // Reads the first add's result from the bus
+            Instruction::Add {
+                rd: T4,
+                rs: T2,
+                rt: T2,
+            },
+        ],
+    );
+
+    assert_eq!(processor.registers.read(T2), 12);
+    assert_eq!(processor.registers.read(T3), -2i32 as u32);
+    assert_eq!(processor.registers.read(T4), 24);
+    assert_eq!(processor.instructions_executed, 3);
+    // Every station and ROB entry is free again
+    assert!(processor.reservation_stations.iter().all(|rs| !rs.busy));
+    assert!(processor.reorder_buffer.iter().all(|entry| !entry.busy));
+}
+
+#[test]
+fn test_loads_see_older_stores() {
+    // Two stations and a four-entry ROB make later instructions reuse
+    // entries freed by commits
+    let mut processor = processor(2, 4);
+    run(
+        &mut processor,
+        &[
+            Instruction::Lw {
+                rt: T2,
+                base: S0,
+                offset: 0,
+            },
+            Instruction::Add {
+                rd: T3,
+                rs: T2,
+                rt: T2,
+            },
+            Instruction::Sw {
+                rt: T3,
+                base: S0,
+                offset: 4,
+            },
+            Instruction::Lw {
+                rt: T4,
+                base: S0,
+                offset: 4,
+            },
+        ],
+    );
+
+    assert_eq!(processor.registers.read(T3), 2000);
+    assert_eq!(processor.memory.read_word(0x104), Ok(2000));
+    assert_eq!(processor.registers.read(T4), 2000);
+}
@@ -0,0 +1,134 @@
+// tests/pipeline/tomasulo_scenarios.rs
+// Cycle-by-cycle tests of the Tomasulo model with scripted scenarios
+
+use vmips_rust::functional_simulator::instructions::Instruction;
+use vmips_rust::functional_simulator::memory::Memory;
+use vmips_rust::functional_simulator::registers::{Registers, S0, T0, T1, T2, T3, T4, T5};
+use vmips_rust::timing_simulator::tomasulo::{Scenario, TomasuloProcessor};
+
+fn processor(stations: usize, rob_size: usize) -> TomasuloProcessor {
+    let mut registers = Registers::new();
+    registers.write(T0, 5);
+    registers.write(T1, 7);
+    registers.write(S0, 0x100);
+    let mut memory = Memory::new(4096);
+    memory.write_word(0x100, 1000).unwrap();
+    TomasuloProcessor::new(stations, rob_size, registers, memory)
+}
+
+fn add(rd: u32, rs: u32, rt: u32) -> Instruction {
+    Instruction::Add { rd, rs, rt }
+}
+
+#[test]
+fn test_independent_instructions_flow_through_in_order() {
+    let mut processor = processor(4, 8);
+    let trace = processor.run_scenario(&Scenario::new(vec![
+        add(T2, T0, T1),
+        Instruction::Sub {
+            rd: T3,
+            rs: T0,
+            rt: T1,
+        },
+        Instruction::And {
+            rd: T4,
+            rs: T0,
+            rt: T1,
+        },
+        Instruction::Or {
+            rd: T5,
+            rs: T0,
+            rt: T1,
+        },
+    ]));
+
+    // Issue, execute and write the result, then commit a cycle later
+    assert!(trace.finished, "{}", trace.render());
+    assert_eq!(trace.station_occupancy(), vec![1, 1, 1, 1, 0, 0]);
+    assert_eq!(trace.rob_occupancy(), vec![1, 2, 2, 2, 1, 0]);
+    assert_eq!(trace.commit_order(), vec![0, 1, 2, 3]);
+    assert_eq!(trace.commit_cycle(0), Some(3));
+    assert_eq!(trace.commit_cycle(3), Some(6));
+    assert_eq!(processor.registers.read(T2), 12);
+    assert_eq!(processor.registers.read(T3), -2i32 as u32);
+    assert_eq!(processor.registers.read(T4), 5);
+    assert_eq!(processor.registers.read(T5), 7);
+}
+
+#[test]
+fn test_cache_miss_holds_back_commits_and_fills_the_rob() {
+    let mut processor = processor(4, 4);
+    let mut instructions = vec![Instruction::Lw {
+        rt: T2,
+        base: S0,
+        offset: 0,
+    }];
+    instructions.extend((0..5).map(|_| add(T3, T0, T1)));
+    let trace = processor.run_scenario(&Scenario::new(instructions).memory_latency(0, 20));
+
+    // The younger adds finish long before the load but wait for it to commit
+    assert!(trace.finished, "{}", trace.render());
+    assert_eq!(trace.commit_order(), vec![0, 1, 2, 3, 4, 5]);
+    assert_eq!(trace.cycles[21].committed, vec![0, 1, 2, 3]);
+    assert_eq!(trace.commit_cycle(4), Some(25));
+    assert_eq!(trace.commit_cycle(5), Some(26));
+
+    // A full ROB stops issue until the load leaves it
+    assert_eq!(trace.rob_occupancy()[3..21], [4; 18]);
+    assert!(trace.cycles[4..22]
+        .iter()
+        .all(|cycle| cycle.issued.is_none()));
+    assert_eq!(trace.cycles[22].issued, Some(4));
+    // Only the load holds a station while it waits for memory
+    assert_eq!(trace.station_occupancy()[4..20], [1; 16]);
+    assert_eq!(processor.registers.read(T2), 1000);
+}
+
+#[test]
+fn test_dependences_wait_for_results_through_registers_and_memory() {
+    let chain = |latency: usize| {
+        let mut processor = processor(2, 8);
+        let trace = processor.run_scenario(
+            &Scenario::new(vec![
+                Instruction::Lw {
+                    rt: T2,
+                    base: S0,
+                    offset: 0,
+                },
+                add(T3, T2, T2),
+                Instruction::Sw {
+                    rt: T3,
+                    base: S0,
+                    offset: 4,
+                },
+                Instruction::Lw {
+                    rt: T4,
+                    base: S0,
+                    offset: 4,
+                },
+            ])
+            .memory_latency(0, latency),
+        );
+        assert!(trace.finished, "{}", trace.render());
+        assert_eq!(trace.commit_order(), vec![0, 1, 2, 3]);
+        assert_eq!(processor.registers.read(T4), 2000);
+        assert_eq!(processor.memory.read_word(0x104), Ok(2000));
+        trace
+    };
+
+    // A miss delays everything that depends on the load by the extra latency
+    let hit = chain(2);
+    let miss = chain(12);
+    for index in 0..4 {
+        assert_eq!(
+            miss.commit_cycle(index).unwrap(),
+            hit.commit_cycle(index).unwrap() + 10
+        );
+    }
+
+    // With two stations the store waits to issue until the load's result
+    // frees one
+    assert_eq!(hit.cycles[1].reservation_stations, 2);
+    assert_eq!(hit.cycles[2].issued, None);
+    assert_eq!(hit.cycles[3].issued, Some(2));
+}
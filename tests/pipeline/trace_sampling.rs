@@ -0,0 +1,168 @@
+// tests/pipeline/trace_sampling.rs
+// Tests for sampling controls that bound the size of instruction traces
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::simulator::decode_instruction;
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::simulator::Simulator;
+use vmips_rust::timing_simulator::stats::{InstructionTiming, InstructionTrace};
+use vmips_rust::timing_simulator::stress::{StressConfig, StressProgram};
+use vmips_rust::timing_simulator::trace_sampling::{TraceFilter, TraceSampler, TraceSampling};
+
+const ADDI: u32 = 0x2108_FFFF;
+const LW: u32 = 0x8D09_0000;
+
+fn timing(index: usize, word: u32) -> InstructionTiming {
+    InstructionTiming {
+        index,
+        pc: 0x0040_0000 + 4 * index as u32,
+        word,
+        issue_cycle: index,
+        cycles: 1,
+        stall_cycles: 0,
+        mispredicted: false,
+    }
+}
+
+#[test]
+fn test_sampler_filters_thins_and_keeps_the_last() {
+    assert_eq!(TraceFilter::parse("Memory"), Ok(TraceFilter::Memory));
+    assert_eq!(TraceFilter::parse("branches"), Ok(TraceFilter::Branches));
+    assert!(TraceFilter::parse("loads").is_err());
+    assert!(TraceSampling {
+        every: 0,
+        ..TraceSampling::default()
+    }
+    .validate()
+    .is_err());
+    assert!(!TraceSampling::default().is_sampled());
+
+    // Every third load of a stream alternating addi and lw
+    let sampling = TraceSampling {
+        every: 3,
+        filter: TraceFilter::Memory,
+        last: None,
+    };
+    assert_eq!(sampling.to_string(), "1 in 3 memory instructions");
+    let mut sampler = TraceSampler::new(sampling);
+    let mut timeline = Vec::new();
+    for index in 0..20 {
+        let word = if index % 2 == 0 { ADDI } else { LW };
+        sampler.push(&mut timeline, timing(index, word));
+    }
+    let kept: Vec<usize> = timeline.iter().map(|t| t.index).collect();
+    assert_eq!(kept, vec![1, 7, 13, 19]);
+    assert_eq!(sampler.matched, 10);
+
+    // The ring buffer ends with exactly the last K, in order
+    let sampling = TraceSampling {
+        last: Some(4),
+        ..TraceSampling::default()
+    };
+    assert_eq!(sampling.to_string(), "all instructions, last 4 kept");
+    let mut sampler = TraceSampler::new(sampling);
+    let mut timeline = Vec::new();
+    for index in 0..1000 {
+        sampler.push(&mut timeline, timing(index, ADDI));
+        assert!(timeline.len() <= 8);
+    }
+    sampler.finish(&mut timeline);
+    let kept: Vec<usize> = timeline.iter().map(|t| t.index).collect();
+    assert_eq!(kept, vec![996, 997, 998, 999]);
+    assert_eq!(sampler.dropped, 996);
+}
+
+#[test]
+fn test_simulator_records_the_sampled_timeline() {
+    let image = StressProgram::generate(StressConfig::default())
+        .unwrap()
+        .image();
+    let simulator = || {
+        let variant = TimingVariant::default();
+        let mut simulator = Simulator::new(
+            variant.pipeline_config,
+            variant.instr_cache_config,
+            variant.data_cache_config,
+            image.memory_required().next_power_of_two(),
+        );
+        simulator.visualization = None;
+        simulator.load_image(&image).unwrap();
+        simulator
+    };
+    let mut full = simulator();
+    full.enable_timeline();
+    full.run();
+    let all = full.timeline.take().unwrap();
+
+    let mut simulator = simulator();
+    simulator.enable_sampled_timeline(TraceSampling {
+        every: 2,
+        filter: TraceFilter::Branches,
+        last: None,
+    });
+    simulator.run();
+    let sampled = simulator.timeline.take().unwrap();
+
+    let branches: Vec<InstructionTiming> = all
+        .into_iter()
+        .filter(|t| decode_instruction(t.word).is_branch_or_jump())
+        .step_by(2)
+        .collect();
+    assert!(branches.len() > 10);
+    assert_eq!(sampled, branches);
+
+    let json =
+        InstructionTrace::sampled(sampled, simulator.trace_sampler.unwrap().sampling).to_json();
+    assert!(json.contains("\"filter\": \"branches\""));
+    assert!(!InstructionTrace::new(Vec::new())
+        .to_json()
+        .contains("sampling"));
+}
+
+#[test]
+fn test_trace_sampling_options() {
+    let dir = tempdir().unwrap();
+    // A loop of three, then an instruction word the decoder rejects
+    let words: [u32; 4] = [0x2008_0003, ADDI, 0x1500_FFFE, 0xFC00_0000];
+    let binary = dir.path().join("fault.bin");
+    let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    fs::write(&binary, bytes).unwrap();
+    let trace = dir.path().join("trace.json");
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("timing")
+        .arg("--input")
+        .arg(&binary)
+        .arg("--trace")
+        .arg(&trace)
+        .arg("--trace-last")
+        .arg("2");
+    cmd.assert().success().stdout(contains(
+        "Trace of 2 instructions (all instructions, last 2 kept) written to",
+    ));
+    let written = fs::read_to_string(&trace).unwrap();
+    assert!(written.contains("\"last\": 2"));
+    assert_eq!(written.matches("\"index\"").count(), 2);
+
+    // Without a fault the ring buffer is not written
+    fs::remove_file(&trace).unwrap();
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("timing")
+        .arg("--input")
+        .arg("examples/programs/bubble_sort.s")
+        .arg("--trace")
+        .arg(&trace)
+        .arg("--trace-last")
+        .arg("5");
+    cmd.assert().success().stdout(contains(
+        "No fault; the last 5 traced instructions were not written",
+    ));
+    assert!(!trace.exists());
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("timing").arg("--trace-every").arg("10");
+    cmd.assert().failure();
+}
@@ -0,0 +1,123 @@
+// tests/pipeline/wrong_path.rs
+// Tests for recording and drawing the wrong-path instructions of mispredicted branches
+
+use vmips_rust::timing_simulator::config::{
+    BranchPredictorType, CacheConfig, ControlHazardScheme, PipelineConfig,
+};
+use vmips_rust::timing_simulator::history::PipelineHistory;
+use vmips_rust::timing_simulator::simulator::Simulator;
+
+/// Counts $t0 down from 3; the instruction after the branch counts in $t2
+const LOOP_PROGRAM: [u32; 6] = [
+    0x20080003, // addi $t0, $zero, 3
+    0x21290001, // loop: addi $t1, $t1, 1
+    0x2108FFFF, // addi $t0, $t0, -1
+    0x1500FFFD, // bne $t0, $zero, loop
+    0x214A0001, // addi $t2, $t2, 1
+    0x00000000, // nop
+];
+
+fn record(scheme: ControlHazardScheme) -> PipelineHistory {
+    let config = PipelineConfig::new(5)
+        .with_branch_prediction(false, BranchPredictorType::TwoBit)
+        .with_control_hazards(scheme);
+    let mut simulator = Simulator::new(
+        config,
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        32768,
+    );
+    simulator.visualization = None;
+    for (i, &word) in LOOP_PROGRAM.iter().enumerate() {
+        simulator.memory.write_word_init(i * 4, word);
+    }
+    simulator.enable_pipeline_history();
+    simulator.run();
+    simulator.pipeline_history.unwrap()
+}
+
+#[test]
+fn test_mispredicted_branches_record_their_wrong_path() {
+    let history = record(ControlHazardScheme::Flush);
+    // Predict not taken: both taken branches fetch the two instructions
+    // after them, which are squashed when the branch leaves execute
+    let wrong_path: Vec<(usize, u32, usize, Option<usize>)> = history
+        .entries
+        .iter()
+        .filter(|entry| entry.is_wrong_path())
+        .map(|entry| (entry.id, entry.pc, entry.fetch_cycle, entry.squashed))
+        .collect();
+    assert_eq!(
+        wrong_path,
+        vec![
+            (3, 0x10, 15, Some(17)),
+            (3, 0x14, 16, Some(17)),
+            (6, 0x10, 20, Some(22)),
+            (6, 0x14, 21, Some(22)),
+        ]
+    );
+    assert_eq!(history.entries.len(), 11 + 4);
+
+    // The branch goes through unstalled and the wrong path fills the bubbles
+    let branch = &history.entries[3];
+    assert!(branch.stalls.is_empty());
+    assert_eq!(history.squashed_after(3).len(), 2);
+    assert!(history.squashed_after(4).is_empty());
+    assert_eq!(
+        history.entries[4].stage_spans(&history.latencies),
+        vec![(15, 1), (16, 1)]
+    );
+    assert_eq!(history.entries[6].fetch_cycle, 17);
+    let occupancy = history.occupancy();
+    let first = history.first_cycle();
+    assert_eq!(occupancy[16 - first][1].entry, Some(4));
+    assert_eq!(occupancy[16 - first][0].entry, Some(5));
+    assert_eq!(occupancy[17 - first][1].entry, None);
+
+    // Stalling never speculates
+    let stalled = record(ControlHazardScheme::Stall);
+    assert!(!stalled.entries.iter().any(|entry| entry.is_wrong_path()));
+}
+
+#[test]
+fn test_konata_shades_the_wrong_path() {
+    let konata = record(ControlHazardScheme::Flush).to_konata();
+    let lines: Vec<&str> = konata.lines().collect();
+    assert!(lines.contains(&"R\t4\t4\t1"));
+    assert!(lines.contains(&"R\t5\t5\t1"));
+    assert!(lines.contains(&"R\t3\t3\t0"));
+    assert!(lines.contains(&"S\t3\t1\tsquash"));
+    assert!(lines.contains(
+        &"L\t3\t1\tmispredicted: squashed 2 wrong-path instruction(s) at the end of cycle 16"
+    ));
+    assert!(!lines.contains(&"S\t4\t1\tsquash"));
+
+    // The squash marker is drawn in the cycle the branch resolves
+    let mut cycle = 0;
+    for line in &lines {
+        if let Some(count) = line.strip_prefix("C\t") {
+            cycle += count.parse::<usize>().unwrap();
+        } else if let Some(start) = line.strip_prefix("C=\t") {
+            cycle = start.parse().unwrap();
+        } else if *line == "S\t3\t1\tsquash" {
+            assert_eq!(cycle, 16);
+        } else if *line == "R\t4\t4\t1" {
+            assert_eq!(cycle, 17);
+        }
+    }
+}
+
+#[test]
+fn test_wrong_path_survives_saving() {
+    let history = record(ControlHazardScheme::Flush);
+    let mut bytes = Vec::new();
+    history.write_to(&mut bytes).unwrap();
+    assert_eq!(bytes[4], 2);
+    let loaded = PipelineHistory::read_from(&mut bytes.as_slice()).unwrap();
+    assert_eq!(loaded, history);
+
+    let json: serde_json::Value = serde_json::from_str(&history.to_json()).unwrap();
+    assert_eq!(json["version"], 1);
+    assert_eq!(json["entries"][4]["squashed"], 17);
+    assert!(json["entries"][3].get("squashed").is_none());
+}
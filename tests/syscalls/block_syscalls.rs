@@ -0,0 +1,168 @@
+// tests/syscalls/block_syscalls.rs
+// Tests for the memcpy and memset syscalls and their line-by-line timing
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::assembler::Assembler;
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{BranchPredictorType, CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::{ExecutionMode, Simulator};
+
+/// Fills 256 bytes with 0x5A, then copies them with a syscall or a word loop
+fn copy_program(copy: &str) -> String {
+    format!(
+        "
+.data
+source: .space 256
+destination: .space 256
+.text
+    la $a0, source
+    li $a1, 0x5A
+    li $a2, 256
+    li $v0, 71
+    syscall
+{}
+    li $v0, 10
+    syscall
+",
+        copy
+    )
+}
+
+const SYSCALL_COPY: &str = "
+    la $a0, destination
+    la $a1, source
+    li $a2, 256
+    li $v0, 70
+    syscall
+";
+
+const LOOP_COPY: &str = "
+    la $t0, source
+    la $t1, destination
+    li $t2, 64
+copy:
+    lw $t3, 0($t0)
+    sw $t3, 0($t1)
+    addi $t0, $t0, 4
+    addi $t1, $t1, 4
+    addi $t2, $t2, -1
+    bne $t2, $zero, copy
+";
+
+fn run_functional(source: &str) -> FunctionalSimulator {
+    let mut simulator = FunctionalSimulator::from_assembly(source).unwrap();
+    simulator.run().unwrap();
+    simulator
+}
+
+fn run_timing(source: &str) -> Simulator {
+    let program = Assembler::new().assemble_program(source).unwrap();
+    let config = PipelineConfig::new(5).with_branch_prediction(true, BranchPredictorType::TwoBit);
+    let mut simulator = Simulator::new(
+        config,
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        0x0050_0000,
+    );
+    simulator.visualization = None;
+    let image = ProgramImage::from_bytes(&program.to_bytes()).unwrap();
+    simulator.load_image(&image).unwrap();
+    simulator.run();
+    simulator
+}
+
+#[test]
+fn test_memset_and_memcpy_move_bytes() {
+    let simulator = run_functional(&copy_program(&format!(
+        "{}    add $s0, $v0, $zero",
+        SYSCALL_COPY
+    )));
+    let destination = 0x1000_0100;
+    let copied = simulator.memory.read_block(destination, 256).unwrap();
+    assert!(copied.iter().all(|&byte| byte == 0x5A));
+    assert_eq!(simulator.memory.read_byte(destination + 256), Ok(0));
+    // memcpy returns the destination
+    assert_eq!(simulator.registers.read(16), destination as u32);
+}
+
+#[test]
+fn test_overlapping_copies_and_out_of_range_blocks() {
+    // Shifting "abcdef" up by two bytes overlaps like memmove
+    let simulator = run_functional(
+        "
+.data
+text: .asciiz \"abcdef\"
+.text
+    la $a0, text
+    addi $a0, $a0, 2
+    la $a1, text
+    li $a2, 6
+    li $v0, 70
+    syscall
+    li $a0, 0x7FFFFFF0
+    li $a1, 0
+    li $a2, 64
+    li $v0, 71
+    syscall
+    add $s0, $v0, $zero
+    li $v0, 10
+    syscall
+",
+    );
+    let bytes = simulator.memory.read_block(0x1000_0000, 8).unwrap();
+    assert_eq!(&bytes, b"ababcdef");
+    // A block that does not fit in memory fails with 0 and changes nothing
+    assert_eq!(simulator.registers.read(16), 0);
+}
+
+#[test]
+fn test_block_copy_is_timed_by_cache_line() {
+    let syscall = run_timing(&copy_program(SYSCALL_COPY));
+    let looped = run_timing(&copy_program(LOOP_COPY));
+    for simulator in [&syscall, &looped] {
+        let copied = simulator.memory.read_block(0x1000_0100, 256).unwrap();
+        assert!(copied.iter().all(|&byte| byte == 0x5A));
+    }
+
+    let pipeline = match &syscall.execution_mode {
+        ExecutionMode::InOrder(pipeline) => pipeline,
+        ExecutionMode::OutOfOrder(_) => unreachable!(),
+    };
+    // Four 64-byte lines are filled, then four read and four written
+    assert!(pipeline.block_transfer_cycles > 0);
+    assert_eq!(pipeline.cache_hierarchy.l1_data_cache.stats.accesses, 12);
+    assert!(
+        syscall.run_totals.cycles * 4 < looped.run_totals.cycles,
+        "{} vs {} cycles",
+        syscall.run_totals.cycles,
+        looped.run_totals.cycles
+    );
+}
+
+#[test]
+fn test_cli_timing_runs_block_syscalls() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("copy.s");
+    let copy = format!(
+        "{}
+    la $t0, destination
+    lbu $a0, 255($t0)
+    li $v0, 1
+    syscall",
+        SYSCALL_COPY
+    );
+    std::fs::write(&path, copy_program(&copy)).unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(contains("\n90\n"))
+        .stdout(contains("Termination reason: program exited"));
+}
@@ -0,0 +1,8 @@
+// tests/syscalls/main.rs
+// Tests for the block, time and thread syscalls and the syscall quotas
+
+mod block_syscalls;
+mod simulated_clock;
+mod syscall_quotas;
+mod threads;
+mod throttle;
@@ -0,0 +1,133 @@
+// tests/syscalls/simulated_clock.rs
+// Tests for the time syscalls, which read a clock driven by the cycle count
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::registers::{Registers, DEFAULT_CLOCK_HZ};
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
+
+/// Read the time, sleep 1250 ms, and read it again with syscall 30 and
+/// gettimeofday
+const SLEEP_PROGRAM: &str = "
+.data 0x10001000
+tv: .word 0, 0
+.text
+    li $v0, 30
+    syscall
+    add $s0, $v0, $zero
+    li $a0, 1250
+    li $v0, 32
+    syscall
+    li $v0, 30
+    syscall
+    add $s1, $v0, $zero
+    la $a0, tv
+    li $v0, 79
+    syscall
+    add $s2, $v0, $zero
+    lw $s3, 0($a0)
+    lw $s4, 4($a0)
+    rdhwr $s5, $2
+    li $v0, 10
+    syscall
+";
+
+fn run_functional() -> FunctionalSimulator {
+    let mut simulator = FunctionalSimulator::from_assembly(SLEEP_PROGRAM).unwrap();
+    simulator.run().unwrap();
+    simulator
+}
+
+#[test]
+fn test_sleep_advances_the_simulated_clock() {
+    let simulator = run_functional();
+    let registers = &simulator.registers;
+    assert_eq!(registers.read(16), 0);
+    assert_eq!(registers.read(17), 1250);
+    assert_eq!(registers.read(18), 0);
+    // 1.25 s slept; the instructions around it take well under a microsecond
+    assert_eq!(registers.read(19), 1);
+    assert_eq!(registers.read(20), 250_000);
+    assert_eq!(
+        registers.hardware.idle_cycles,
+        1250 * DEFAULT_CLOCK_HZ / 1000
+    );
+    // The cycle counter counts the cycles slept as well
+    assert!(registers.read(21) as u64 > registers.hardware.idle_cycles);
+}
+
+#[test]
+fn test_runs_read_the_same_times() {
+    let first = run_functional();
+    let second = run_functional();
+    for register in 16..=21 {
+        assert_eq!(
+            first.registers.read(register),
+            second.registers.read(register)
+        );
+    }
+}
+
+#[test]
+fn test_time_follows_the_clock_rate() {
+    let mut registers = Registers::new();
+    registers.hardware.clock_hz = 1_000_000;
+    registers.hardware.cycles = 2_500_000;
+    assert_eq!(registers.hardware.elapsed_us(), 2_500_000);
+    registers.hardware.sleep_ms(500);
+    assert_eq!(registers.hardware.idle_cycles, 500_000);
+    assert_eq!(registers.hardware.elapsed_us(), 3_000_000);
+}
+
+#[test]
+fn test_timing_simulator_clock_counts_pipeline_cycles() {
+    let image = ProgramImage::from_assembly(SLEEP_PROGRAM).unwrap();
+    let mut simulator = TimingSimulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(4096, 2, 64),
+        CacheConfig::new(4096, 2, 64),
+        image.memory_required().next_power_of_two(),
+    );
+    simulator.visualization = None;
+    simulator.load_image(&image).unwrap();
+    simulator.run();
+    assert_eq!(simulator.registers.read(17), 1250);
+    assert_eq!(simulator.registers.read(20), 250_000);
+}
+
+#[test]
+fn test_cli_timing_reads_the_simulated_clock() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("sleep.s");
+    std::fs::write(
+        &path,
+        "
+.text
+    li $a0, 1250
+    li $v0, 32
+    syscall
+    li $v0, 30
+    syscall
+    move $a0, $v0
+    li $v0, 1
+    syscall
+    li $v0, 10
+    syscall
+",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(contains("\n1250\n"))
+        .stdout(contains("Termination reason: program exited"));
+}
@@ -0,0 +1,150 @@
+// tests/syscalls/syscall_quotas.rs
+// Tests for the output, file write and heap quotas on untrusted programs
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::io::Write;
+use tempfile::NamedTempFile;
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::utils::limits::{SyscallQuotas, SyscallUsage, TerminationReason};
+
+/// Prints 7 forever
+const PRINT_LOOP: &str = "
+.text
+loop:
+    li $a0, 7
+    li $v0, 1
+    syscall
+    j loop
+";
+
+fn run(source: &str, quotas: SyscallQuotas) -> Simulator {
+    let mut simulator = Simulator::from_assembly(source).unwrap();
+    simulator.limits.quotas = quotas;
+    simulator.captured_output = Some(Vec::new());
+    simulator.run().unwrap();
+    simulator
+}
+
+#[test]
+fn test_output_quota_stops_a_chatty_program() {
+    let simulator = run(
+        PRINT_LOOP,
+        SyscallQuotas {
+            max_output: Some(11),
+            ..SyscallQuotas::default()
+        },
+    );
+
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::QuotaExceeded)
+    );
+    // Output is cut off at the quota
+    assert_eq!(
+        simulator.captured_output.as_deref(),
+        Some(&b"7\n7\n7\n7\n7\n7"[..])
+    );
+    assert_eq!(simulator.syscall_usage.output, 12);
+}
+
+#[test]
+fn test_heap_and_file_write_quotas() {
+    let sbrk_twice = "
+.text
+    li $a0, 4096
+    li $v0, 9
+    syscall
+    add $s0, $v0, $zero
+    li $a0, 4096
+    li $v0, 9
+    syscall
+    add $s1, $v0, $zero
+    li $v0, 10
+    syscall
+";
+    let quotas = SyscallQuotas {
+        max_heap: Some(6 * 1024),
+        ..SyscallQuotas::default()
+    };
+    let simulator = run(sbrk_twice, quotas);
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::QuotaExceeded)
+    );
+    // The first allocation succeeded, the second never ran
+    assert_ne!(simulator.registers.read(16), 0);
+    assert_eq!(simulator.registers.read(17), 0);
+    assert_eq!(
+        simulator.syscall_usage,
+        SyscallUsage {
+            heap: 4096,
+            ..SyscallUsage::default()
+        }
+    );
+
+    // Writes to stdout count as output, writes to other files do not
+    let write_file = "
+.data
+text: .asciiz \"hello\"
+.text
+    li $a0, 1
+    la $a1, text
+    li $a2, 5
+    li $v0, 15
+    syscall
+    li $a0, 3
+    li $v0, 15
+    syscall
+    li $v0, 10
+    syscall
+";
+    let quotas = SyscallQuotas {
+        max_output: Some(5),
+        max_file_write: Some(4),
+        ..SyscallQuotas::default()
+    };
+    let simulator = run(write_file, quotas);
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::QuotaExceeded)
+    );
+    assert_eq!(simulator.captured_output.as_deref(), Some(&b"hello"[..]));
+    assert_eq!(simulator.syscall_usage.file_write, 0);
+
+    let simulator = run(
+        write_file,
+        SyscallQuotas {
+            max_file_write: Some(5),
+            ..quotas
+        },
+    );
+    assert_eq!(
+        simulator.termination_reason,
+        Some(TerminationReason::Exited)
+    );
+    assert_eq!(simulator.syscall_usage.file_write, 5);
+}
+
+#[test]
+fn test_quotas_from_the_command_line() {
+    let mut source = NamedTempFile::new().unwrap();
+    write!(source, "#! expect $a0 = 7\n{}", PRINT_LOOP).unwrap();
+
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .args(["grade", "--max-output", "1K", "--input"])
+        .arg(source.path())
+        .assert()
+        .success()
+        .stdout(contains("ok 1 - line 1: $a0 = 7"))
+        .stdout(contains("syscall quota exceeded after"));
+
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .args(["functional", "--max-heap", "lots", "--input"])
+        .arg(source.path())
+        .assert()
+        .failure()
+        .stderr(contains("invalid size"));
+}
@@ -0,0 +1,103 @@
+// tests/syscalls/threads.rs
+// Tests for guest threads and the round-robin scheduler in the functional simulator
+
+use vmips_rust::functional_simulator::simulator::Simulator;
+use vmips_rust::functional_simulator::threads::ThreadState;
+
+/// Helper function to place a program at address 0 and reset the PC there
+fn setup_simulator(program: &[(usize, u32)]) -> Simulator {
+    let mut simulator = Simulator::new(32768);
+    simulator.load_program(&[]);
+
+    for &(addr, word) in program {
+        simulator.memory.write_word(addr, word).unwrap();
+    }
+
+    simulator
+}
+
+#[test]
+fn test_thread_create_and_join() {
+    let program = [
+        (0x00, 0x24040040), // addiu $a0, $zero, 0x40  - worker entry
+        (0x04, 0x24050015), // addiu $a1, $zero, 21    - worker argument
+        (0x08, 0x2402003C), // addiu $v0, $zero, 60    - thread_create
+        (0x0C, 0x0000000C), // syscall
+        (0x10, 0x00402025), // or $a0, $v0, $zero      - thread id
+        (0x14, 0x2402003D), // addiu $v0, $zero, 61    - thread_join
+        (0x18, 0x0000000C), // syscall
+        (0x1C, 0xAC022000), // sw $v0, 0x2000($zero)   - store exit value
+        (0x20, 0x2402000A), // addiu $v0, $zero, 10    - exit
+        (0x24, 0x0000000C), // syscall
+        // Worker: return 2 * arg
+        (0x40, 0x00041040), // sll $v0, $a0, 1
+        (0x44, 0x03E00008), // jr $ra
+    ];
+
+    let mut simulator = setup_simulator(&program);
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.memory.read_word(0x2000), Ok(42));
+    assert_eq!(simulator.threads.thread_count(), 2);
+    assert_eq!(
+        simulator.threads.get_thread(1).map(|t| t.state),
+        Some(ThreadState::Finished { exit_value: 42 })
+    );
+}
+
+#[test]
+fn test_preempted_threads_with_ll_sc_counter() {
+    let program = [
+        // Main: start two workers, then join both
+        (0x00, 0x24040060), // addiu $a0, $zero, 0x60
+        (0x04, 0x24050032), // addiu $a1, $zero, 50
+        (0x08, 0x2402003C), // addiu $v0, $zero, 60
+        (0x0C, 0x0000000C), // syscall
+        (0x10, 0x00408025), // or $s0, $v0, $zero
+        (0x14, 0x24040060), // addiu $a0, $zero, 0x60
+        (0x18, 0x24050032), // addiu $a1, $zero, 50
+        (0x1C, 0x2402003C), // addiu $v0, $zero, 60
+        (0x20, 0x0000000C), // syscall
+        (0x24, 0x00408825), // or $s1, $v0, $zero
+        (0x28, 0x02002025), // or $a0, $s0, $zero
+        (0x2C, 0x2402003D), // addiu $v0, $zero, 61
+        (0x30, 0x0000000C), // syscall
+        (0x34, 0x02202025), // or $a0, $s1, $zero
+        (0x38, 0x2402003D), // addiu $v0, $zero, 61
+        (0x3C, 0x0000000C), // syscall
+        (0x40, 0x2402000A), // addiu $v0, $zero, 10
+        (0x44, 0x0000000C), // syscall
+        // Worker: atomically increment mem[0x2000] $a0 times
+        (0x60, 0xC0082000), // ll $t0, 0x2000($zero)
+        (0x64, 0x25080001), // addiu $t0, $t0, 1
+        (0x68, 0xE0082000), // sc $t0, 0x2000($zero)
+        (0x6C, 0x1100FFFC), // beq $t0, $zero, -4      - retry if sc failed
+        (0x70, 0x2484FFFF), // addiu $a0, $a0, -1
+        (0x74, 0x1480FFFA), // bne $a0, $zero, -6
+        (0x78, 0x03E00008), // jr $ra
+    ];
+
+    let mut simulator = setup_simulator(&program);
+    // A tiny time slice forces switches between ll and sc
+    simulator.set_time_slice(3);
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.memory.read_word(0x2000), Ok(100));
+    assert!(simulator.threads.context_switches > 10);
+}
+
+#[test]
+fn test_sc_fails_without_ll() {
+    let program = [
+        (0x00, 0x24080007), // addiu $t0, $zero, 7
+        (0x04, 0xE0082000), // sc $t0, 0x2000($zero)
+        (0x08, 0x2402000A), // addiu $v0, $zero, 10
+        (0x0C, 0x0000000C), // syscall
+    ];
+
+    let mut simulator = setup_simulator(&program);
+    simulator.run().unwrap();
+
+    assert_eq!(simulator.registers.read(8), 0);
+    assert_eq!(simulator.memory.read_word(0x2000), Ok(0));
+}
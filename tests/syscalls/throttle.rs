@@ -0,0 +1,213 @@
+// tests/syscalls/throttle.rs
+// Tests for throttling scenarios and the cycle counter programs read
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
+use vmips_rust::utils::throttle::{Residency, ThrottleClock, ThrottlePhase, ThrottleScenario};
+
+/// Times a loop of 2000 instructions with the cycle counter and prints the
+/// ticks it took
+const TIMED_LOOP: &str = "
+.text
+    rdhwr $s0, $2
+    addi $t0, $zero, 1000
+loop:
+    addi $t0, $t0, -1
+    bne $t0, $zero, loop
+    rdhwr $s1, $2
+    sub $a0, $s1, $s0
+    li $v0, 1
+    syscall
+    li $v0, 10
+    syscall
+";
+
+fn timed_loop(throttle: Option<&str>) -> FunctionalSimulator {
+    let mut simulator = FunctionalSimulator::from_assembly(TIMED_LOOP).unwrap();
+    simulator.captured_output = Some(Vec::new());
+    simulator.throttle =
+        throttle.map(|spec| ThrottleClock::new(ThrottleScenario::parse(spec).unwrap()));
+    simulator.run().unwrap();
+    simulator
+}
+
+#[test]
+fn test_scenario_parsing() {
+    let scenario = ThrottleScenario::parse(
+        "
+# full speed, then hot
+0:1000
+2.5:250, 4:500   # recovering
+repeat:10
+",
+    )
+    .unwrap();
+    assert_eq!(
+        scenario.phases,
+        vec![
+            ThrottlePhase {
+                start_us: 0,
+                cycles_per_ms: 1000
+            },
+            ThrottlePhase {
+                start_us: 2500,
+                cycles_per_ms: 250
+            },
+            ThrottlePhase {
+                start_us: 4000,
+                cycles_per_ms: 500
+            },
+        ]
+    );
+    assert_eq!(scenario.repeat_us, Some(10_000));
+    assert_eq!(scenario.counter_rate(), 1000);
+    assert_eq!(
+        ThrottleScenario::parse("0:300").unwrap(),
+        ThrottleScenario::constant(300)
+    );
+
+    let error = |text: &str| ThrottleScenario::parse(text).unwrap_err().to_string();
+    assert_eq!(
+        error("1:250"),
+        "throttle scenario item 1: the first phase must start at 0"
+    );
+    assert_eq!(
+        error("0:100,5:50,5:20"),
+        "throttle scenario item 3: phases must be in time order"
+    );
+    assert_eq!(
+        error("0:fast"),
+        "throttle scenario item 1: invalid rate 'fast'"
+    );
+    assert_eq!(error("0:0"), "throttle scenario item 1: invalid rate '0'");
+    assert_eq!(
+        error("0:100 1:50"),
+        "throttle scenario item 1: invalid rate '100 1:50'"
+    );
+    assert_eq!(
+        error("0:100,5:50,repeat:5"),
+        "throttle scenario item 3: the repeat period must be after the last phase starts"
+    );
+    assert_eq!(
+        error("0:100,repeat:5,7:50"),
+        "throttle scenario item 3: nothing can follow repeat"
+    );
+    assert_eq!(
+        error("0:100,0.001:1"),
+        "throttle scenario item 1: the phase is too short to run a single cycle"
+    );
+    assert_eq!(error("# nothing"), "throttle scenario item 1: no phases");
+}
+
+#[test]
+fn test_clock_converts_cycles_to_time_and_ticks() {
+    // Unthrottled, the counter counts cycles
+    let mut clock = ThrottleClock::new(ThrottleScenario::constant(500));
+    assert_eq!(clock.advance_to(1234), 1234);
+    assert_eq!(clock.elapsed_us(), 2468);
+
+    // 1000 cycles in the first millisecond, 500 in the next two, repeating
+    let mut clock = ThrottleClock::new(ThrottleScenario::parse("0:1000,1:250,repeat:3").unwrap());
+    assert_eq!(clock.advance_to(1000), 1000);
+    assert_eq!(clock.advance_to(1250), 2000);
+    assert_eq!(clock.advance_to(1500), 3000);
+    assert_eq!(clock.advance_to(2000), 3500);
+    assert_eq!(clock.elapsed_us(), 3500);
+    // Counts never go backwards
+    assert_eq!(clock.advance_to(10), 3500);
+    assert_eq!(clock.cycles(), 2000);
+    let residency = clock.residency();
+    assert_eq!(
+        residency[&1000],
+        Residency {
+            cycles: 1500,
+            time_us: 1500
+        }
+    );
+    assert_eq!(
+        residency[&250],
+        Residency {
+            cycles: 500,
+            time_us: 2000
+        }
+    );
+    let rendered = clock.render();
+    assert!(rendered.starts_with(
+        "Throttling: 2000 cycles in 3.500 ms of simulated time; the cycle counter reads 3500 at 1000 ticks/ms"
+    ));
+    assert!(rendered.contains("       250           500         2.000"));
+
+    // Programs see the slowdown through rdhwr: 1000 of the loop's cycles
+    // run at full speed and the remaining 1002 at a quarter of it
+    let simulator = timed_loop(None);
+    assert_eq!(simulator.captured_output.as_deref(), Some(&b"2002\n"[..]));
+    let simulator = timed_loop(Some("0:1000,1:250"));
+    assert_eq!(simulator.captured_output.as_deref(), Some(&b"5008\n"[..]));
+}
+
+#[test]
+fn test_throttling_in_the_timing_simulator_and_command_line() {
+    let image = ProgramImage::from_assembly(TIMED_LOOP).unwrap();
+    let measure = |throttle: Option<ThrottleScenario>| {
+        let mut simulator = TimingSimulator::new(
+            PipelineConfig::new(5),
+            CacheConfig::new(1024, 2, 32),
+            CacheConfig::new(1024, 2, 32),
+            0x0080_0000,
+        );
+        simulator.load_image(&image).unwrap();
+        simulator.throttle = throttle.map(ThrottleClock::new);
+        simulator.run();
+        simulator.registers.read(17) - simulator.registers.read(16)
+    };
+    let full_speed = measure(None);
+    // Half speed throughout doubles every tick count
+    let half_speed = measure(Some(ThrottleScenario::parse("0:1000,0.001:500").unwrap()));
+    assert!(full_speed > 2000);
+    assert!(half_speed >= 2 * full_speed - 2 && half_speed <= 2 * full_speed);
+
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("loop.s");
+    std::fs::write(&source, TIMED_LOOP).unwrap();
+    let scenario = temp_dir.path().join("hot.throttle");
+    std::fs::write(&scenario, "0:1000\n1:250 # too hot\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--throttle")
+        .arg(&scenario);
+    cmd.assert()
+        .success()
+        .stdout(contains("5008"))
+        .stdout(contains(
+            "Throttling: 2008 cycles in 5.032 ms of simulated time; the cycle counter reads 5032 at 1000 ticks/ms",
+        ));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&source)
+        .arg("--throttle")
+        .arg("0:1000,1:250,repeat:2");
+    cmd.assert()
+        .success()
+        .stdout(contains("Throttling: "))
+        .stdout(contains("cycles/ms        cycles     time (ms)"));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("functional")
+        .arg("--throttle")
+        .arg("1:250")
+        .assert()
+        .failure()
+        .stderr(contains("the first phase must start at 0"));
+}
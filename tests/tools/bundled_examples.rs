@@ -0,0 +1,61 @@
+// tests/tools/bundled_examples.rs
+// Tests for the example programs bundled with the run-example subcommand
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use vmips_rust::utils::examples::{find_example, EXAMPLES};
+
+#[test]
+fn test_every_example_meets_its_expectations() {
+    assert!(EXAMPLES.len() >= 4);
+    for example in EXAMPLES {
+        let (report, output) = example.run().unwrap();
+        assert!(
+            report.all_passed(),
+            "{} failed:\n{}",
+            example.name,
+            report.render_tap()
+        );
+        assert!(report.passed > 0, "{} checks nothing", example.name);
+        assert!(!output.is_empty(), "{} prints nothing", example.name);
+    }
+}
+
+#[test]
+fn test_examples_are_found_by_name() {
+    let example = find_example("matrix_multiply").unwrap();
+    assert!(example.source.contains("#! expect"));
+    let (_, output) = example.run().unwrap();
+    assert!(output.starts_with("30\n24\n18\n"));
+    assert!(find_example("quicksort").is_none());
+}
+
+#[test]
+fn test_run_example_lists_runs_and_prints_examples() {
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .arg("run-example")
+        .assert()
+        .success()
+        .stdout(contains("bubble_sort"))
+        .stdout(contains("syscalls"));
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .args(["run-example", "fibonacci"])
+        .assert()
+        .success()
+        .stdout(contains("13\n21\n34\n"))
+        .stdout(contains("# 2 of 2 assertions passed"));
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .args(["run-example", "syscalls", "--source"])
+        .assert()
+        .success()
+        .stdout(contains("li $v0, 9"));
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .args(["run-example", "quicksort"])
+        .assert()
+        .code(2)
+        .stderr(contains("Unknown example 'quicksort'"));
+}
@@ -0,0 +1,115 @@
+// tests/tools/grading.rs
+// Tests for `#! expect` annotations and the grade subcommand
+
+use assert_cmd::Command;
+use predicates::str::{contains, starts_with};
+use tempfile::tempdir;
+use vmips_rust::utils::grading::{
+    grade_source, parse_expectations, Address, Expected, Target, DEFAULT_GRADE_MAX_INSTRUCTIONS,
+};
+use vmips_rust::utils::limits::RunLimits;
+
+/// Sums 1 to 10, stores and prints the sum
+const ASSIGNMENT: &str = r#"
+#! expect $v0 = 10
+#! expect $s0 = 55
+#! expect mem[result] = 55
+#! expect mem[result+4] = 0x2a
+#! expect output = "55\n"
+.data
+result: .word 0
+other:  .word 42
+.text
+    li $t0, 10
+    li $s0, 0
+loop:
+    add $s0, $s0, $t0
+    addi $t0, $t0, -1
+    bne $t0, $zero, loop
+    la $t1, result
+    sw $s0, 0($t1)
+    add $a0, $s0, $zero
+    li $v0, 1
+    syscall
+    li $v0, 10
+    syscall     #! expect $t0 = -1
+"#;
+
+#[test]
+fn test_annotations_are_parsed() {
+    let expectations = parse_expectations(ASSIGNMENT).unwrap();
+    assert_eq!(expectations.len(), 6);
+    assert_eq!(expectations[1].line, 3);
+    assert_eq!(expectations[1].target, Target::Register(16));
+    assert_eq!(
+        expectations[3].target,
+        Target::Memory(Address::Symbol("result".to_string(), 4))
+    );
+    assert_eq!(
+        expectations[3].expected,
+        Expected::Value {
+            value: 42,
+            hex: true
+        }
+    );
+    assert_eq!(expectations[4].expected, Expected::Text("55\n".to_string()));
+    assert_eq!(expectations[5].text, "$t0 = -1");
+
+    let error = parse_expectations("nop\n#! expect $q9 = 1\n").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Annotation error at line 2: unknown target '$q9'"
+    );
+    assert!(parse_expectations("#! check $v0 = 1").is_err());
+}
+
+#[test]
+fn test_grading_reports_each_assertion() {
+    let limits = RunLimits {
+        max_instructions: DEFAULT_GRADE_MAX_INSTRUCTIONS,
+        ..RunLimits::default()
+    };
+    let report = grade_source(ASSIGNMENT, limits).unwrap();
+    assert_eq!((report.passed, report.failed), (5, 1));
+    assert_eq!(report.termination, "program exited");
+    let failure = &report.assertions[5];
+    assert!(!failure.passed);
+    assert_eq!(failure.actual, "0");
+
+    let tap = report.render_tap();
+    assert!(tap.starts_with("TAP version 13\n1..6\nok 1 - line 2: $v0 = 10\n"));
+    assert!(tap.contains("not ok 6 - line 23: $t0 = -1\n  # got 0\n"));
+    assert!(tap.ends_with("# 5 of 6 assertions passed; program exited after 40 instructions\n"));
+}
+
+#[test]
+fn test_cli_grades_and_writes_json() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("sum.s");
+    let passing = ASSIGNMENT.replace("$t0 = -1", "$t0 = 0");
+    std::fs::write(&input, passing).unwrap();
+
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["grade", "-i", "sum.s", "--json", "report.json"])
+        .assert()
+        .success()
+        // The report is all that is printed, so TAP consumers can parse it
+        .stdout(starts_with("TAP version 13\n"))
+        .stdout(contains("# 6 of 6 assertions passed"));
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.path().join("report.json")).unwrap())
+            .unwrap();
+    assert_eq!(json["passed"], 6);
+    assert_eq!(json["assertions"][2]["assertion"], "mem[result] = 55");
+
+    std::fs::write(&input, ASSIGNMENT).unwrap();
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["grade", "-i", "sum.s"])
+        .assert()
+        .code(1)
+        .stdout(contains("not ok 6"));
+}
@@ -0,0 +1,8 @@
+// tests/tools/main.rs
+// Tests for the grading, example, benchmark, schema and server front-ends
+
+mod bundled_examples;
+mod grading;
+mod schemas;
+mod server;
+mod workloads;
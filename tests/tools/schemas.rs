@@ -0,0 +1,245 @@
+// tests/tools/schemas.rs
+// Tests that exported files match their published JSON Schemas
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use serde_json::Value;
+use tempfile::tempdir;
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::functional_simulator::snapshot::{StateSnapshot, SNAPSHOT_VERSION};
+use vmips_rust::loader::ProgramImage;
+use vmips_rust::timing_simulator::config::{CacheConfig, PipelineConfig};
+use vmips_rust::timing_simulator::simulator::Simulator as TimingSimulator;
+use vmips_rust::timing_simulator::stats::InstructionTrace;
+use vmips_rust::timing_simulator::trace_sampling::{TraceFilter, TraceSampling};
+use vmips_rust::utils::schema::{all_schemas, Artifact, ARTIFACTS};
+
+/// Sums an array in a loop and stores the result
+const SUM_PROGRAM: &str = "
+.data
+values: .word 1, 2, 3, 4
+total: .word 0
+.text
+    la $t0, values
+    li $t1, 4
+    li $s0, 0
+loop:
+    lw $t2, 0($t0)
+    add $s0, $s0, $t2
+    addi $t0, $t0, 4
+    addi $t1, $t1, -1
+    bne $t1, $zero, loop
+    la $t0, total
+    sw $s0, 0($t0)
+    li $v0, 10
+    syscall
+";
+
+// Check a value against the parts of JSON Schema the published schemas use
+fn validate(value: &Value, schema: &Value, path: &str) {
+    if let Some(expected) = schema.get("const") {
+        assert_eq!(value, expected, "{}", path);
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        assert!(
+            options.contains(value),
+            "{} is not one of {:?}",
+            path,
+            options
+        );
+    }
+    if let Some(types) = schema.get("type") {
+        let types: Vec<&str> = match types {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            other => vec![other.as_str().unwrap()],
+        };
+        let matches = types.iter().any(|kind| match *kind {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_u64() || value.is_i64(),
+            "number" => value.is_number(),
+            "boolean" => value.is_boolean(),
+            "null" => value.is_null(),
+            _ => false,
+        });
+        assert!(matches, "{} is not of type {:?}: {}", path, types, value);
+    }
+    if let Some(minimum) = schema.get("minimum").and_then(Value::as_i64) {
+        assert!(value.as_i64().map_or(true, |v| v >= minimum), "{}", path);
+    }
+    if let Some(required) = schema.get("required").and_then(Value::as_array) {
+        for key in required {
+            let key = key.as_str().unwrap();
+            assert!(value.get(key).is_some(), "{} lacks {}", path, key);
+        }
+    }
+    if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+        for (key, property) in properties {
+            if let Some(field) = value.get(key) {
+                validate(field, property, &format!("{}.{}", path, key));
+            }
+        }
+    }
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            assert!(array.len() as u64 >= min, "{} is too short", path);
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            assert!(array.len() as u64 <= max, "{} is too long", path);
+        }
+        for (index, item) in array.iter().enumerate() {
+            validate(item, items, &format!("{}[{}]", path, index));
+        }
+    }
+}
+
+fn validate_json(json: &str, artifact: Artifact) {
+    let value: Value = serde_json::from_str(json).unwrap();
+    validate(&value, &artifact.schema(), artifact.name());
+}
+
+#[test]
+fn test_timing_exports_match_their_schemas() {
+    for artifact in ARTIFACTS {
+        let schema = artifact.schema();
+        assert_eq!(Artifact::from_name(artifact.name()), Some(artifact));
+        assert_eq!(schema["properties"]["format"]["const"], artifact.format());
+        assert_eq!(schema["properties"]["version"]["const"], artifact.version());
+        assert_eq!(schema["required"][0], "format");
+        assert_eq!(all_schemas()[artifact.name()], schema);
+    }
+    assert_eq!(Artifact::from_name("registers"), None);
+
+    let image = ProgramImage::from_assembly(SUM_PROGRAM).unwrap();
+    let mut simulator = TimingSimulator::new(
+        PipelineConfig::new(5),
+        CacheConfig::new(1024, 2, 32),
+        CacheConfig::new(1024, 2, 32),
+        0x0080_0000,
+    );
+    simulator.load_image(&image).unwrap();
+    simulator.enable_timeline();
+    simulator.enable_pipeline_history();
+    simulator.run();
+
+    validate_json(&simulator.stats_report().to_json(), Artifact::Stats);
+    let trace = InstructionTrace::new(simulator.timeline.clone().unwrap());
+    assert!(!trace.instructions.is_empty());
+    validate_json(&trace.to_json(), Artifact::Trace);
+    let sampling = TraceSampling {
+        every: 2,
+        filter: TraceFilter::Memory,
+        last: Some(4),
+    };
+    let sampled = InstructionTrace::sampled(trace.instructions.clone(), sampling);
+    validate_json(&sampled.to_json(), Artifact::Trace);
+    let history = simulator.pipeline_history.as_ref().unwrap();
+    assert!(!history.entries.is_empty());
+    validate_json(&history.to_json(), Artifact::PipelineHistory);
+    let predictor = simulator.predictor_state().unwrap();
+    assert!(!predictor.local.is_empty());
+    validate_json(&predictor.to_json(), Artifact::Predictor);
+}
+
+#[test]
+fn test_snapshots_restore_the_machine_state() {
+    let image = ProgramImage::from_assembly(SUM_PROGRAM).unwrap();
+    let memory_size = image.memory_required().next_power_of_two();
+    let mut simulator = FunctionalSimulator::new(memory_size);
+    simulator.load_image(&image).unwrap();
+    simulator.set_max_steps(8);
+    simulator.run().unwrap();
+
+    let json = simulator.snapshot().to_json();
+    validate_json(&json, Artifact::Snapshot);
+    let snapshot = StateSnapshot::from_json(&json).unwrap();
+    assert_eq!(snapshot, simulator.snapshot());
+    assert_eq!(snapshot.memory_size, memory_size);
+    assert!(snapshot.pages.len() < memory_size / 4096);
+
+    // A fresh simulator continues where the first one stopped
+    let mut restored = FunctionalSimulator::new(memory_size);
+    restored.restore_snapshot(&snapshot).unwrap();
+    assert_eq!(restored.pc(), simulator.pc());
+    assert_eq!(restored.registers.data, simulator.registers.data);
+    restored.run().unwrap();
+    assert_eq!(restored.registers.read(16), 10);
+    assert_eq!(restored.memory.read_word(0x1000_0010), Ok(10));
+
+    // Other formats, newer versions and other memory sizes are refused
+    let newer = json.replacen(
+        &format!("\"version\": {}", SNAPSHOT_VERSION),
+        &format!("\"version\": {}", SNAPSHOT_VERSION + 1),
+        1,
+    );
+    assert!(StateSnapshot::from_json(&newer)
+        .unwrap_err()
+        .contains("unsupported snapshot version"));
+    let other = json.replacen("vmips-snapshot", "vmips-trace", 1);
+    assert!(StateSnapshot::from_json(&other)
+        .unwrap_err()
+        .contains("not a snapshot"));
+    let mut smaller = FunctionalSimulator::new(memory_size / 2);
+    assert!(smaller.restore_snapshot(&snapshot).is_err());
+}
+
+#[test]
+fn test_schemas_and_exports_from_the_command_line() {
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    let output = cmd.arg("--schema").arg("stats").output().unwrap();
+    assert!(output.status.success());
+    let schema: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(schema, Artifact::Stats.schema());
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    let output = cmd.arg("--schema").arg("all").output().unwrap();
+    let schemas: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(schemas, all_schemas());
+
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("sum.s");
+    std::fs::write(&source, SUM_PROGRAM).unwrap();
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("timing")
+        .arg("--input")
+        .arg(&source)
+        .arg("--stats-json")
+        .arg("stats.json")
+        .arg("--trace")
+        .arg("trace.json")
+        .arg("--pipeline-history")
+        .arg("history.json");
+    cmd.assert()
+        .success()
+        .stdout(contains("written to trace.json"));
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .arg("functional")
+        .arg("--input")
+        .arg(&source)
+        .arg("--snapshot")
+        .arg("snapshot.json");
+    cmd.assert()
+        .success()
+        .stdout(contains("Snapshot written to snapshot.json"));
+
+    for (file, artifact) in [
+        ("stats.json", Artifact::Stats),
+        ("trace.json", Artifact::Trace),
+        ("history.json", Artifact::PipelineHistory),
+        ("snapshot.json", Artifact::Snapshot),
+    ] {
+        let json = std::fs::read_to_string(temp_dir.path().join(file)).unwrap();
+        validate_json(&json, artifact);
+    }
+
+    let mut cmd = Command::cargo_bin("vmips_rust").unwrap();
+    cmd.arg("--schema")
+        .arg("registers")
+        .assert()
+        .failure()
+        .stderr(contains("invalid value"));
+}
@@ -0,0 +1,312 @@
+// tests/tools/server.rs
+// Tests for the HTTP and WebSocket session API of the serve subcommand
+
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::Duration;
+use vmips_rust::server::Server;
+
+const PROGRAM: &str = "
+.data
+value: .word 0x1234
+.text
+    addi $t0, $zero, 5
+    addi $t1, $t0, 2
+    addi $a0, $zero, 7
+    addi $v0, $zero, 1
+    syscall
+    addi $v0, $zero, 10
+    syscall
+";
+
+// Reads an integer and prints it plus one
+const READ_PROGRAM: &str = "
+.text
+    addi $v0, $zero, 5
+    syscall
+    addi $a0, $v0, 1
+    addi $v0, $zero, 1
+    syscall
+    addi $v0, $zero, 10
+    syscall
+";
+
+fn start_server() -> SocketAddr {
+    serve(Server::bind("127.0.0.1:0").unwrap())
+}
+
+fn serve(server: Server) -> SocketAddr {
+    let address = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+    address
+}
+
+fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let status = response[9..12].parse().unwrap();
+    let body = response.split("\r\n\r\n").nth(1).unwrap();
+    (status, serde_json::from_str(body).unwrap_or(Value::Null))
+}
+
+#[test]
+fn test_functional_session() {
+    let address = start_server();
+    let (status, created) = request(address, "POST", "/sessions", "");
+    assert_eq!(status, 201);
+    let id = created["id"].as_u64().unwrap();
+    let base = format!("/sessions/{}", id);
+
+    let (status, loaded) = request(address, "POST", &format!("{}/program", base), PROGRAM);
+    assert_eq!(status, 200);
+    assert_eq!(loaded["entry"], 0x00400000);
+
+    let (_, stepped) = request(address, "POST", &format!("{}/step?count=2", base), "");
+    assert_eq!(stepped["executed"], 2);
+    assert_eq!(stepped["pc"], 0x00400008);
+    assert_eq!(stepped["finished"], false);
+
+    let (_, registers) = request(address, "GET", &format!("{}/registers", base), "");
+    assert_eq!(registers["gpr"][8], 5);
+    assert_eq!(registers["gpr"][9], 7);
+    assert_eq!(registers["gpr"][29], 0x7FFFFFFC);
+
+    let (_, memory) = request(
+        address,
+        "GET",
+        &format!("{}/memory?address=0x10000000&length=4", base),
+        "",
+    );
+    assert_eq!(memory["bytes"], serde_json::json!([0x34, 0x12, 0, 0]));
+
+    let (_, finished) = request(address, "POST", &format!("{}/step?count=100", base), "");
+    // The exit syscall ends the run rather than counting as a step
+    assert_eq!(finished["executed"], 4);
+    assert_eq!(finished["finished"], true);
+    assert_eq!(finished["stdout"], "7\n");
+
+    let (_, list) = request(address, "GET", "/sessions", "");
+    assert_eq!(list["sessions"], serde_json::json!([id]));
+    assert_eq!(request(address, "DELETE", &base, "").0, 200);
+    assert_eq!(
+        request(address, "GET", &format!("{}/registers", base), "").0,
+        404
+    );
+}
+
+#[test]
+fn test_timing_session_and_errors() {
+    let address = start_server();
+    let (status, created) = request(
+        address,
+        "POST",
+        "/sessions",
+        r#"{"simulator": "timing", "config": "forwarding=off"}"#,
+    );
+    assert_eq!(status, 201);
+    let base = format!("/sessions/{}", created["id"]);
+
+    let (status, error) = request(address, "POST", &format!("{}/step", base), "");
+    assert_eq!(status, 400);
+    assert_eq!(error["error"], "No program loaded");
+
+    request(address, "POST", &format!("{}/program", base), PROGRAM);
+    let (_, stepped) = request(address, "POST", &format!("{}/step?count=3", base), "");
+    assert_eq!(stepped["executed"], 3);
+
+    let (status, pipeline) = request(address, "GET", &format!("{}/pipeline", base), "");
+    assert_eq!(status, 200);
+    assert_eq!(pipeline["instructions"], 3);
+    assert_eq!(pipeline["recent"].as_array().unwrap().len(), 3);
+    assert_eq!(pipeline["recent"][1]["instruction"], "addi $9, $8, 2");
+    // Without forwarding the dependent addi waits for the write-back
+    assert!(pipeline["stalls"]["data_hazard"].as_u64().unwrap() > 0);
+
+    // Invalid requests
+    assert_eq!(request(address, "GET", "/nowhere", "").0, 404);
+    assert_eq!(request(address, "GET", "/sessions/99/registers", "").0, 404);
+    assert_eq!(
+        request(address, "GET", &format!("{}/step", base), "").0,
+        405
+    );
+    assert_eq!(
+        request(address, "POST", "/sessions", r#"{"simulator": "quantum"}"#).0,
+        400
+    );
+    let (status, error) = request(address, "POST", "/sessions", r#"{"config": "turbo=on"}"#);
+    assert_eq!(status, 400);
+    assert_eq!(error["error"], "Unknown setting 'turbo'");
+
+    // Oversized requests are refused rather than allocated or run
+    let (status, error) = request(
+        address,
+        "POST",
+        "/sessions",
+        r#"{"simulator": "timing", "memory_size": 1099511627776}"#,
+    );
+    assert_eq!(status, 400);
+    assert!(error["error"].as_str().unwrap().contains("exceeds the cap"));
+    let (status, error) = request(
+        address,
+        "POST",
+        &format!("{}/step?count=4000000000", base),
+        "",
+    );
+    assert_eq!(status, 400);
+    assert!(error["error"].as_str().unwrap().starts_with("At most"));
+
+    let (_, functional) = request(address, "POST", "/sessions", "");
+    let functional = format!("/sessions/{}", functional["id"]);
+    request(address, "POST", &format!("{}/program", functional), PROGRAM);
+    let (status, error) = request(address, "GET", &format!("{}/pipeline", functional), "");
+    assert_eq!(status, 400);
+    assert_eq!(error["error"], "Functional sessions have no pipeline");
+}
+
+#[test]
+fn test_session_input() {
+    let address = start_server();
+    for (settings, expected) in [
+        (r#"{"stdin": "41\n"}"#, "42\n"),
+        (r#"{"simulator": "timing", "stdin": "9\n"}"#, "10\n"),
+        // Without input a read sees end of input instead of waiting on the
+        // server's stdin
+        ("", "1\n"),
+        (r#"{"simulator": "timing"}"#, "1\n"),
+    ] {
+        let (_, created) = request(address, "POST", "/sessions", settings);
+        let base = format!("/sessions/{}", created["id"]);
+        request(address, "POST", &format!("{}/program", base), READ_PROGRAM);
+        let (_, stepped) = request(address, "POST", &format!("{}/step?count=100", base), "");
+        assert_eq!(stepped["finished"], true, "{}", settings);
+        assert_eq!(stepped["stdout"], expected, "{}", settings);
+    }
+}
+
+#[test]
+fn test_session_limits() {
+    let server = Server::bind("127.0.0.1:0")
+        .unwrap()
+        .with_session_limits(2, Duration::from_millis(500));
+    let address = serve(server);
+
+    let (_, first) = request(address, "POST", "/sessions", "");
+    let (_, second) = request(address, "POST", "/sessions", "");
+    let (status, error) = request(address, "POST", "/sessions", "");
+    assert_eq!(status, 503);
+    assert!(error["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Too many sessions"));
+
+    // Using a session keeps it; the unused one expires
+    thread::sleep(Duration::from_millis(300));
+    let first = format!("/sessions/{}", first["id"]);
+    request(address, "POST", &format!("{}/program", first), PROGRAM);
+    thread::sleep(Duration::from_millis(300));
+    let (_, list) = request(address, "GET", "/sessions", "");
+    assert_eq!(list["sessions"], serde_json::json!([1]));
+    let (status, _) = request(
+        address,
+        "GET",
+        &format!("/sessions/{}/registers", second["id"]),
+        "",
+    );
+    assert_eq!(status, 404);
+
+    let (status, third) = request(address, "POST", "/sessions", "");
+    assert_eq!(status, 201);
+    assert_eq!(third["id"], 3);
+}
+
+// Send a masked client text frame
+fn send_frame(stream: &mut TcpStream, text: &str) {
+    let mask = [0x12, 0x34, 0x56, 0x78];
+    let mut frame = vec![0x81];
+    if text.len() < 126 {
+        frame.push(0x80 | text.len() as u8);
+    } else {
+        frame.push(0x80 | 126);
+        frame.extend_from_slice(&(text.len() as u16).to_be_bytes());
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(text.bytes().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+    stream.write_all(&frame).unwrap();
+}
+
+fn read_frame(stream: &mut TcpStream) -> Value {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).unwrap();
+    assert_eq!(header[0], 0x81);
+    let mut length = usize::from(header[1]);
+    if length == 126 {
+        let mut extended = [0u8; 2];
+        stream.read_exact(&mut extended).unwrap();
+        length = usize::from(u16::from_be_bytes(extended));
+    }
+    let mut payload = vec![0u8; length];
+    stream.read_exact(&mut payload).unwrap();
+    serde_json::from_slice(&payload).unwrap()
+}
+
+#[test]
+fn test_websocket_session() {
+    let address = start_server();
+    let (_, created) = request(address, "POST", "/sessions", "");
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "GET /sessions/{}/ws HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+         Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+         Sec-WebSocket-Version: 13\r\n\r\n",
+        created["id"]
+    )
+    .unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0u8; 1];
+        stream.read_exact(&mut byte).unwrap();
+        head.push(byte[0]);
+    }
+    let head = String::from_utf8(head).unwrap();
+    assert!(head.starts_with("HTTP/1.1 101"));
+    // The accept key from the example in RFC 6455
+    assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+
+    let load = serde_json::json!({ "command": "load", "source": PROGRAM }).to_string();
+    send_frame(&mut stream, &load);
+    assert_eq!(read_frame(&mut stream)["entry"], 0x00400000);
+
+    send_frame(&mut stream, r#"{"command": "step", "count": 2}"#);
+    assert_eq!(read_frame(&mut stream)["executed"], 2);
+    send_frame(&mut stream, r#"{"command": "step"}"#);
+    assert_eq!(read_frame(&mut stream)["pc"], 0x0040000C);
+
+    send_frame(&mut stream, r#"{"command": "registers"}"#);
+    assert_eq!(read_frame(&mut stream)["gpr"][4], 7);
+    send_frame(&mut stream, r#"{"command": "jump"}"#);
+    assert!(read_frame(&mut stream)["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Invalid command"));
+
+    // Close handshake
+    stream.write_all(&[0x88, 0x80, 0, 0, 0, 0]).unwrap();
+    let mut close = [0u8; 2];
+    stream.read_exact(&mut close).unwrap();
+    assert_eq!(close, [0x88, 0]);
+}
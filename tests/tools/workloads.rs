@@ -0,0 +1,67 @@
+// tests/tools/workloads.rs
+// Tests for the generated matmul and sort kernels and the bench subcommand
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use vmips_rust::functional_simulator::simulator::Simulator as FunctionalSimulator;
+use vmips_rust::timing_simulator::compare::TimingVariant;
+use vmips_rust::timing_simulator::workloads::{Workload, WorkloadKind, MAX_MATRIX_SIZE};
+
+#[test]
+fn test_matmul_is_verified_and_sensitive_to_the_data_cache() {
+    let workload = Workload::new(WorkloadKind::Matmul, 16, 7).unwrap();
+    let large = workload.run(&TimingVariant::default()).unwrap();
+    let small = workload
+        .run(&TimingVariant::parse("dcache=512/1/16").unwrap())
+        .unwrap();
+    for report in [&large, &small] {
+        assert_eq!(report.verified, Ok(()));
+        assert!(report.render().contains("verified against the host"));
+    }
+    // The same instructions run, but the small cache misses far more often
+    assert_eq!(large.totals.instructions, small.totals.instructions);
+    assert!(small.totals.dcache_misses > 10 * large.totals.dcache_misses);
+    assert!(small.totals.cycles > large.totals.cycles);
+    assert!(Workload::new(WorkloadKind::Matmul, MAX_MATRIX_SIZE + 1, 1).is_err());
+}
+
+#[test]
+fn test_sort_inputs_come_from_the_seed_and_are_checked() {
+    let workload = Workload::new(WorkloadKind::Sort, 50, 3).unwrap();
+    assert_eq!(workload.inputs(), workload.inputs());
+    assert_ne!(
+        workload.inputs(),
+        Workload::new(WorkloadKind::Sort, 50, 4).unwrap().inputs()
+    );
+    assert!(workload.inputs().iter().any(|&word| (word as i32) < 0));
+
+    let image = workload.image().unwrap();
+    let mut simulator = FunctionalSimulator::from_image(&image).unwrap();
+    // The unsorted input fails verification until the kernel has run
+    assert!(workload.verify(&simulator.memory).is_err());
+    simulator.run().unwrap();
+    assert_eq!(workload.verify(&simulator.memory), Ok(()));
+}
+
+#[test]
+fn test_bench_runs_and_prints_workloads() {
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .args(["bench", "--workload", "matmul", "--n", "4"])
+        .assert()
+        .success()
+        .stdout(contains("Workload: matmul n=4 seed=1"))
+        .stdout(contains("Result: verified against the host"));
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .args(["bench", "--workload", "sort", "--n", "10", "--source"])
+        .assert()
+        .success()
+        .stdout(contains("array: .space 40"));
+    Command::cargo_bin("vmips_rust")
+        .unwrap()
+        .args(["bench", "--workload", "sort", "--n", "0"])
+        .assert()
+        .code(2)
+        .stderr(contains("sort size must be between 1"));
+}